@@ -33,6 +33,7 @@ use crate::channel_with_priority::Priority;
 use crate::mailbox::{Command, CommandOrMessage};
 use crate::progress::{Progress, ProtectedZoneGuard};
 use crate::scheduler::{Callback, SchedulerMessage};
+use crate::simulation::InFlightCounter;
 use crate::spawn_builder::SpawnBuilder;
 use crate::{AsyncActor, KillSwitch, Mailbox, QueueCapacity, SendError, SyncActor};
 
@@ -185,6 +186,7 @@ pub struct ActorContextInner<Message> {
     kill_switch: KillSwitch,
     scheduler_mailbox: Mailbox<SchedulerMessage>,
     actor_state: AtomicState,
+    in_flight_counter: Option<InFlightCounter>,
 }
 
 impl<A: Actor> ActorContext<A> {
@@ -192,6 +194,7 @@ impl<A: Actor> ActorContext<A> {
         self_mailbox: Mailbox<A::Message>,
         kill_switch: KillSwitch,
         scheduler_mailbox: Mailbox<SchedulerMessage>,
+        in_flight_counter: Option<InFlightCounter>,
     ) -> Self {
         ActorContext {
             inner: ActorContextInner {
@@ -200,6 +203,7 @@ impl<A: Actor> ActorContext<A> {
                 kill_switch,
                 scheduler_mailbox,
                 actor_state: AtomicState::default(),
+                in_flight_counter,
             }
             .into(),
             phantom_data: PhantomData,
@@ -246,6 +250,7 @@ impl<A: Actor> ActorContext<A> {
             actor,
             self.scheduler_mailbox.clone(),
             self.kill_switch.clone(),
+            self.in_flight_counter.clone(),
         )
     }
 
@@ -262,6 +267,14 @@ impl<A: Actor> ActorContext<A> {
         self.actor_state.get_state()
     }
 
+    /// Records that the actor is done with its initialization or with a command or message,
+    /// when the time of the universe is simulated.
+    pub(crate) fn record_processed(&self, num_processed: usize) {
+        if let Some(in_flight_counter) = &self.in_flight_counter {
+            in_flight_counter.sub(num_processed);
+        }
+    }
+
     pub(crate) fn process(&mut self) {
         self.actor_state.process();
     }
@@ -400,6 +413,43 @@ impl<A: Actor + AsyncActor> ActorContext<A> {
         self.self_mailbox.send_message(msg).await
     }
 
+    /// Waits for `duration`.
+    ///
+    /// Actors should call this method rather than `tokio::time::sleep`: when the time of the
+    /// universe is simulated, the sleep ends when the simulated time reaches its deadline.
+    /// The actor is not identified as blocked while sleeping.
+    pub async fn sleep(&self, duration: Duration) {
+        let _guard = self.protect_zone();
+        let in_flight_counter = if let Some(in_flight_counter) = &self.in_flight_counter {
+            in_flight_counter
+        } else {
+            tokio::time::sleep(duration).await;
+            return;
+        };
+        let (wake_tx, wake_rx) = tokio::sync::oneshot::channel();
+        let (mut sleep_guard, sleep_waker) = in_flight_counter.sleep();
+        let callback = Callback(Box::pin(async move {
+            sleep_waker.wake();
+            let _ = wake_tx.send(());
+        }));
+        let scheduler_msg = SchedulerMessage::ScheduleEvent {
+            timeout: duration,
+            callback,
+        };
+        // The event is queued in the scheduler mailbox before the actor stops being counted as
+        // busy, so that the scheduler registers it before advancing the time.
+        if self
+            .inner
+            .scheduler_mailbox
+            .send_message(scheduler_msg)
+            .await
+            .is_ok()
+        {
+            sleep_guard.start();
+            let _ = wake_rx.await;
+        }
+    }
+
     pub async fn schedule_self_msg(&self, after_duration: Duration, msg: A::Message) {
         let self_mailbox = self.inner.self_mailbox.clone();
         let callback = Callback(Box::pin(async move {
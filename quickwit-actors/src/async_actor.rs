@@ -26,7 +26,7 @@ use tracing::{debug, error, info, Instrument};
 use crate::actor::{process_command, ActorExitStatus};
 use crate::actor_handle::ActorHandle;
 use crate::actor_with_state_tx::ActorWithStateTx;
-use crate::mailbox::{CommandOrMessage, Inbox};
+use crate::mailbox::{Command, CommandOrMessage, Inbox};
 use crate::{Actor, ActorContext, RecvError};
 
 /// An async actor is executed on a regular tokio task.
@@ -127,11 +127,15 @@ async fn process_msg<A: Actor + AsyncActor>(
     }
     ctx.progress().record_progress();
 
-    let command_or_msg_recv_res = if ctx.state().is_running() {
-        inbox.recv_timeout().await
-    } else {
-        // The actor is paused. We only process command and scheduled message.
-        inbox.recv_timeout_cmd_and_scheduled_msg_only().await
+    let command_or_msg_recv_res = {
+        // An actor waiting for a message is not blocked.
+        let _guard = ctx.protect_zone();
+        if ctx.state().is_running() {
+            inbox.recv_timeout().await
+        } else {
+            // The actor is paused. We only process command and scheduled message.
+            inbox.recv_timeout_cmd_and_scheduled_msg_only().await
+        }
     };
 
     ctx.progress().record_progress();
@@ -142,12 +146,19 @@ async fn process_msg<A: Actor + AsyncActor>(
     match command_or_msg_recv_res {
         Ok(CommandOrMessage::Command(cmd)) => {
             ctx.process();
-            process_command(actor, cmd, ctx, state_tx)
+            let is_counted = matches!(cmd, Command::ExitWithSuccess);
+            let exit_status_opt = process_command(actor, cmd, ctx, state_tx);
+            if is_counted {
+                ctx.record_processed(1);
+            }
+            exit_status_opt
         }
         Ok(CommandOrMessage::Message(msg)) => {
             ctx.process();
             let span = actor.message_span(msg_id, &msg);
-            actor.process_message(msg, ctx).instrument(span).await.err()
+            let exit_status_opt = actor.process_message(msg, ctx).instrument(span).await.err();
+            ctx.record_processed(1);
+            exit_status_opt
         }
         Err(RecvError::Disconnected) => Some(ActorExitStatus::Success),
         Err(RecvError::Timeout) => {
@@ -175,6 +186,7 @@ async fn async_actor_loop<A: AsyncActor>(
 
     let mut exit_status_opt: Option<ActorExitStatus> =
         actor_with_state_tx.actor.initialize(&ctx).await.err();
+    ctx.record_processed(1);
 
     let mut msg_id: u64 = 1;
     let mut exit_status: ActorExitStatus = loop {
@@ -193,6 +205,8 @@ async fn async_actor_loop<A: AsyncActor>(
         msg_id += 1;
     };
     ctx.record_progress();
+    // The commands and messages left in the inbox will never be processed.
+    ctx.record_processed(inbox.discard_pending());
     if let Err(finalize_error) = actor_with_state_tx
         .actor
         .finalize(&exit_status, &ctx)
@@ -125,6 +125,16 @@ impl<T> Sender<T> {
         self.channel(priority).try_send(msg)?;
         Ok(())
     }
+
+    /// Returns the number of messages waiting in the channel of the given priority.
+    pub fn len(&self, priority: Priority) -> usize {
+        self.channel(priority).len()
+    }
+
+    /// Returns the capacity of the channel of the given priority, or `None` if it is unbounded.
+    pub fn capacity(&self, priority: Priority) -> Option<usize> {
+        self.channel(priority).capacity()
+    }
 }
 
 pub struct Receiver<T> {
@@ -244,6 +254,19 @@ impl<T> Receiver<T> {
         }
     }
 
+    /// Returns the number of messages waiting in the channel.
+    pub fn len(&self) -> usize {
+        self.high_priority_rx.len() + self.low_priority_rx.len() + self.pending.iter().count()
+    }
+
+    /// Drains all of the pending messages without waiting and returns them.
+    pub fn try_drain_all(&mut self) -> Vec<T> {
+        let mut messages: Vec<T> = self.pending.take().into_iter().collect();
+        messages.extend(self.high_priority_rx.try_iter());
+        messages.extend(self.low_priority_rx.try_iter());
+        messages
+    }
+
     /// Drain all of the pending low priority messages and return them.
     pub fn drain_low_priority(&self) -> Vec<T> {
         let mut messages = Vec::new();
@@ -429,4 +452,20 @@ mod tests {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_sender_len_and_capacity() -> anyhow::Result<()> {
+        let (sender, mut receiver) = super::channel::<usize>(QueueCapacity::Bounded(3));
+        assert_eq!(sender.capacity(Priority::Low), Some(3));
+        assert_eq!(sender.capacity(Priority::High), None);
+        sender.send(1, Priority::Low).await?;
+        sender.send(2, Priority::Low).await?;
+        sender.send(3, Priority::High).await?;
+        assert_eq!(sender.len(Priority::Low), 2);
+        assert_eq!(sender.len(Priority::High), 1);
+        assert_eq!(receiver.recv_timeout(TEST_TIMEOUT).await, Ok(3));
+        assert_eq!(receiver.recv_timeout(TEST_TIMEOUT).await, Ok(1));
+        assert_eq!(sender.len(Priority::Low), 1);
+        Ok(())
+    }
 }
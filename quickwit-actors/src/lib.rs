@@ -37,6 +37,7 @@ mod mailbox;
 mod observation;
 mod progress;
 mod scheduler;
+mod simulation;
 mod spawn_builder;
 mod sync_actor;
 #[cfg(test)]
@@ -56,7 +57,9 @@ pub use universe::Universe;
 pub use self::actor::ActorContext;
 pub use self::actor_state::ActorState;
 pub use self::channel_with_priority::{QueueCapacity, RecvError, SendError};
-pub use self::mailbox::{create_mailbox, create_test_mailbox, Command, CommandOrMessage, Mailbox};
+pub use self::mailbox::{
+    create_mailbox, create_test_mailbox, Command, CommandOrMessage, Inbox, Mailbox,
+};
 
 /// Heartbeat used to verify that actors are progressing.
 ///
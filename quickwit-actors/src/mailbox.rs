@@ -20,11 +20,13 @@
 use std::any::Any;
 use std::fmt;
 use std::hash::Hash;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, Weak};
 
+use once_cell::sync::OnceCell;
 use tokio::sync::oneshot;
 
 use crate::channel_with_priority::{Priority, Receiver, Sender};
+use crate::simulation::InFlightCounter;
 use crate::{QueueCapacity, RecvError, SendError};
 
 /// A mailbox is the object that makes it possible to send a message
@@ -91,6 +93,23 @@ impl<Message> From<Command> for CommandOrMessage<Message> {
 pub(crate) struct Inner<Message> {
     pub(crate) tx: Sender<CommandOrMessage<Message>>,
     instance_id: String,
+    /// Set when the actor is spawned in a universe with simulated time.
+    in_flight_counter: OnceCell<InFlightCounter>,
+    /// Mailboxes the actor forwards its messages to, see [`Mailbox::forward_fill_ratio_of`].
+    downstream_mailboxes: Mutex<Vec<Box<dyn DownstreamMailbox>>>,
+}
+
+/// Weak reference to a mailbox of any message type, used to read its fill ratio without
+/// keeping its actor alive.
+trait DownstreamMailbox: Send + Sync {
+    /// Returns the fill ratio of the mailbox, or `None` once it is dropped.
+    fn fill_ratio(&self) -> Option<f64>;
+}
+
+impl<Message: Send + 'static> DownstreamMailbox for Weak<Inner<Message>> {
+    fn fill_ratio(&self) -> Option<f64> {
+        self.upgrade().map(|inner| Mailbox { inner }.fill_ratio())
+    }
 }
 
 /// Commands are messages that can be send to control the behavior of an actor.
@@ -197,12 +216,65 @@ impl<Message> Mailbox<Message> {
         &self.inner.instance_id
     }
 
+    /// Returns the number of messages waiting in the mailbox, commands excluded.
+    pub fn num_pending_messages(&self) -> usize {
+        self.inner.tx.len(Priority::Low)
+    }
+
+    /// Returns the number of messages the mailbox can hold, or `None` if it is unbounded.
+    pub fn capacity(&self) -> Option<usize> {
+        self.inner.tx.capacity(Priority::Low)
+    }
+
+    /// Returns the fill ratio of the mailbox, or 0 if it is unbounded. The mailbox of an actor
+    /// forwarding its messages reports instead the fill ratio of the fullest mailbox it forwards
+    /// to, so that the senders see the backlog of the actors doing the work.
+    pub fn fill_ratio(&self) -> f64 {
+        let downstream_mailboxes = self
+            .inner
+            .downstream_mailboxes
+            .lock()
+            .expect("Downstream mailboxes lock should not be poisoned.");
+        if !downstream_mailboxes.is_empty() {
+            return downstream_mailboxes
+                .iter()
+                .flat_map(|downstream_mailbox| downstream_mailbox.fill_ratio())
+                .fold(0.0, f64::max);
+        }
+        match self.capacity() {
+            Some(capacity) if capacity > 0 => self.num_pending_messages() as f64 / capacity as f64,
+            _ => 0.0,
+        }
+    }
+
+    /// Declares that the actor of this mailbox forwards its messages to the actor of
+    /// `downstream_mailbox`. The fill ratio of this mailbox then reflects the one of
+    /// `downstream_mailbox`, which is not kept alive by the link.
+    pub fn forward_fill_ratio_of<DownstreamMessage: Send + 'static>(
+        &self,
+        downstream_mailbox: &Mailbox<DownstreamMessage>,
+    ) {
+        self.inner
+            .downstream_mailboxes
+            .lock()
+            .expect("Downstream mailboxes lock should not be poisoned.")
+            .push(Box::new(Arc::downgrade(&downstream_mailbox.inner)));
+    }
+
     pub(crate) async fn send_with_priority(
         &self,
         cmd_or_msg: CommandOrMessage<Message>,
         priority: Priority,
     ) -> Result<(), SendError> {
-        self.inner.tx.send(cmd_or_msg, priority).await
+        let is_counted = is_counted_in_flight(&cmd_or_msg);
+        if is_counted {
+            self.increment_in_flight();
+        }
+        let send_res = self.inner.tx.send(cmd_or_msg, priority).await;
+        if is_counted {
+            self.decrement_in_flight_on_error(&send_res);
+        }
+        send_res
     }
 
     pub(crate) fn send_with_priority_blocking(
@@ -210,7 +282,33 @@ impl<Message> Mailbox<Message> {
         cmd_or_msg: CommandOrMessage<Message>,
         priority: Priority,
     ) -> Result<(), SendError> {
-        self.inner.tx.send_blocking(cmd_or_msg, priority)
+        let is_counted = is_counted_in_flight(&cmd_or_msg);
+        if is_counted {
+            self.increment_in_flight();
+        }
+        let send_res = self.inner.tx.send_blocking(cmd_or_msg, priority);
+        if is_counted {
+            self.decrement_in_flight_on_error(&send_res);
+        }
+        send_res
+    }
+
+    pub(crate) fn set_in_flight_counter(&self, in_flight_counter: InFlightCounter) {
+        let _ = self.inner.in_flight_counter.set(in_flight_counter);
+    }
+
+    // The count is incremented before sending, otherwise the actor could process the message and
+    // decrement the count first.
+    fn increment_in_flight(&self) {
+        if let Some(in_flight_counter) = self.inner.in_flight_counter.get() {
+            in_flight_counter.add(1);
+        }
+    }
+
+    fn decrement_in_flight_on_error(&self, send_res: &Result<(), SendError>) {
+        if let (Err(_), Some(in_flight_counter)) = (send_res, self.inner.in_flight_counter.get()) {
+            in_flight_counter.sub(1);
+        }
     }
 
     /// SendError is returned if the actor has already exited.
@@ -233,16 +331,50 @@ impl<Message> Mailbox<Message> {
     }
 
     pub fn try_send_message(&self, message: Message) -> Result<(), SendError> {
-        self.inner
+        self.increment_in_flight();
+        let send_res = self
+            .inner
             .tx
-            .try_send(CommandOrMessage::Message(message), Priority::Low)
+            .try_send(CommandOrMessage::Message(message), Priority::Low);
+        self.decrement_in_flight_on_error(&send_res);
+        send_res
     }
 }
 
+/// Returns whether a command or message holds the time of a universe with simulated time until
+/// it is processed.
+///
+/// Only messages and `ExitWithSuccess` commands, which flow from upstream to downstream actors,
+/// are counted. The other commands control actors from the outside: a sleeping actor only
+/// processes them once the time has advanced.
+pub(crate) fn is_counted_in_flight<Message>(cmd_or_msg: &CommandOrMessage<Message>) -> bool {
+    matches!(
+        cmd_or_msg,
+        CommandOrMessage::Message(_) | CommandOrMessage::Command(Command::ExitWithSuccess)
+    )
+}
+
 pub struct Inbox<Message> {
     rx: Receiver<CommandOrMessage<Message>>,
 }
 
+impl<Message> Inbox<Message> {
+    /// Returns the number of commands and messages waiting in the inbox.
+    pub(crate) fn len(&self) -> usize {
+        self.rx.len()
+    }
+
+    /// Drops the pending commands and messages, and returns the number of those counted in
+    /// flight.
+    pub(crate) fn discard_pending(&mut self) -> usize {
+        self.rx
+            .try_drain_all()
+            .iter()
+            .filter(|cmd_or_msg| is_counted_in_flight(cmd_or_msg))
+            .count()
+    }
+}
+
 impl<Message: fmt::Debug> Inbox<Message> {
     pub(crate) async fn recv_timeout(&mut self) -> Result<CommandOrMessage<Message>, RecvError> {
         self.rx.recv_timeout(crate::message_timeout()).await
@@ -301,6 +433,8 @@ pub fn create_mailbox<M>(
         inner: Arc::new(Inner {
             tx,
             instance_id: quickwit_common::new_coolid(&actor_name),
+            in_flight_counter: OnceCell::new(),
+            downstream_mailboxes: Mutex::new(Vec::new()),
         }),
     };
     let inbox = Inbox { rx };
@@ -29,6 +29,7 @@ use tokio::sync::oneshot::Sender;
 use tokio::task::JoinHandle;
 use tracing::info;
 
+use crate::simulation::InFlightCounter;
 use crate::{Actor, ActorContext, AsyncActor};
 
 pub(crate) struct Callback(pub Pin<Box<dyn Future<Output = ()> + Sync + Send + 'static>>);
@@ -80,6 +81,12 @@ pub(crate) enum SchedulerMessage {
         time_shift: TimeShift,
         tx: tokio::sync::oneshot::Sender<()>,
     },
+    /// Sent once the actors of a universe with simulated time are idle, to fire the next events
+    /// scheduled before the deadline.
+    AdvanceSimulatedTime {
+        deadline: Instant,
+        tx: tokio::sync::oneshot::Sender<()>,
+    },
 }
 
 impl fmt::Debug for SchedulerMessage {
@@ -96,6 +103,10 @@ impl fmt::Debug for SchedulerMessage {
             SchedulerMessage::SimulateAdvanceTime { .. } => {
                 f.debug_struct("SimulateAdvanceTime").finish()
             }
+            SchedulerMessage::AdvanceSimulatedTime { deadline, .. } => f
+                .debug_struct("AdvanceSimulatedTime")
+                .field("deadline", deadline)
+                .finish(),
         }
     }
 }
@@ -112,6 +123,30 @@ pub(crate) struct Scheduler {
     simulated_time_shift: Duration,
     future_events: BinaryHeap<Reverse<TimeoutEvent>>,
     next_timeout: Option<JoinHandle<()>>,
+    simulated_clock_opt: Option<SimulatedClock>,
+}
+
+/// Clock of a universe whose time is simulated: it only advances when the time is shifted, and
+/// only once the actors are idle.
+struct SimulatedClock {
+    start: Instant,
+    in_flight_counter: InFlightCounter,
+}
+
+impl Scheduler {
+    /// Creates a scheduler that never fires events on its own.
+    ///
+    /// Events are fired when the time is shifted, in the order of their deadlines, and each
+    /// time only once all the actors counted by `in_flight_counter` are idle.
+    pub(crate) fn with_simulated_time(in_flight_counter: InFlightCounter) -> Self {
+        Scheduler {
+            simulated_clock_opt: Some(SimulatedClock {
+                start: Instant::now(),
+                in_flight_counter,
+            }),
+            ..Default::default()
+        }
+    }
 }
 
 impl Actor for Scheduler {
@@ -143,10 +178,22 @@ impl AsyncActor for Scheduler {
                 self.process_schedule_event(timeout, callback, ctx).await;
             }
             SchedulerMessage::Timeout => self.process_timeout(ctx).await,
+            SchedulerMessage::SimulateAdvanceTime { time_shift, tx }
+                if self.simulated_clock_opt.is_some() =>
+            {
+                let deadline = match time_shift {
+                    TimeShift::ToInstant(instant) => instant,
+                    TimeShift::ByDuration(duration) => self.simulated_now() + duration,
+                };
+                self.advance_simulated_time_when_idle(deadline, tx, ctx);
+            }
             SchedulerMessage::SimulateAdvanceTime { time_shift, tx } => {
                 self.process_simulate_advance_time(time_shift, tx, ctx)
                     .await
             }
+            SchedulerMessage::AdvanceSimulatedTime { deadline, tx } => {
+                self.process_advance_simulated_time(deadline, tx, ctx).await
+            }
         }
         Ok(())
     }
@@ -214,6 +261,57 @@ impl Scheduler {
         }
     }
 
+    /// Fires the next events scheduled before `deadline` once the actors are idle.
+    ///
+    /// The `AdvanceSimulatedTime` message is queued after the events scheduled by the actors
+    /// until they became idle, so these events are registered before the time advances.
+    fn advance_simulated_time_when_idle(
+        &self,
+        deadline: Instant,
+        tx: Sender<()>,
+        ctx: &ActorContext<Self>,
+    ) {
+        let in_flight_counter = if let Some(simulated_clock) = &self.simulated_clock_opt {
+            simulated_clock.in_flight_counter.clone()
+        } else {
+            return;
+        };
+        let self_mailbox = ctx.mailbox().clone();
+        tokio::task::spawn(async move {
+            in_flight_counter.wait_until_idle(crate::HEARTBEAT).await;
+            let _ = self_mailbox
+                .send_message(SchedulerMessage::AdvanceSimulatedTime { deadline, tx })
+                .await;
+        });
+    }
+
+    async fn process_advance_simulated_time(
+        &mut self,
+        deadline: Instant,
+        tx: Sender<()>,
+        ctx: &ActorContext<Self>,
+    ) {
+        let now = self.simulated_now();
+        match self.next_event_deadline().filter(|t| t <= &deadline) {
+            Some(next_evt_deadline) => {
+                if next_evt_deadline > now {
+                    self.simulated_time_shift += next_evt_deadline - now;
+                }
+                info!(simulated_now=?self.simulated_now(), "advance-simulated-time");
+                while let Some(next_evt) = self.find_next_event_before(next_evt_deadline) {
+                    next_evt.0.await;
+                }
+                self.advance_simulated_time_when_idle(deadline, tx, ctx);
+            }
+            None => {
+                if deadline > now {
+                    self.simulated_time_shift += deadline - now;
+                }
+                let _ = tx.send(());
+            }
+        }
+    }
+
     async fn advance_by_duration(&mut self, time_shift: Duration, ctx: &ActorContext<Self>) {
         info!(time_shift=?time_shift, "advance-time");
         self.simulated_time_shift += time_shift;
@@ -223,6 +321,16 @@ impl Scheduler {
     fn next_event_deadline(&self) -> Option<Instant> {
         self.future_events.peek().map(|rev| rev.0.deadline)
     }
+    /// Pops the next event if its deadline is at or before `deadline`.
+    fn find_next_event_before(&mut self, deadline: Instant) -> Option<Callback> {
+        let next_event_deadline = self.next_event_deadline()?;
+        if next_event_deadline <= deadline {
+            self.future_events.pop().map(|rev| rev.0.callback)
+        } else {
+            None
+        }
+    }
+
     fn find_next_event_before_now(&mut self, simulated_now: Instant) -> Option<Callback> {
         let next_event_deadline = self.next_event_deadline()?;
         if next_event_deadline < simulated_now {
@@ -233,6 +341,9 @@ impl Scheduler {
     }
 
     fn simulated_now(&self) -> Instant {
+        if let Some(simulated_clock) = &self.simulated_clock_opt {
+            return simulated_clock.start + self.simulated_time_shift;
+        }
         Instant::now() + self.simulated_time_shift
     }
 
@@ -247,6 +358,10 @@ impl Scheduler {
     }
 
     fn schedule_next_timeout(&mut self, ctx: &ActorContext<Self>) {
+        if self.simulated_clock_opt.is_some() {
+            // With simulated time, events are only fired when the time is shifted.
+            return;
+        }
         let simulated_now = self.simulated_now();
         let next_deadline_opt = self.future_events.peek().map(|evt| evt.0.deadline);
         let timeout = match next_deadline_opt {
@@ -0,0 +1,173 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::Instant;
+use tracing::warn;
+
+/// Counts the commands and messages sent to the actors of a universe running with simulated
+/// time that are not processed yet, plus the initializations of the actors in progress.
+///
+/// The scheduler of such a universe only advances the time once the count drops to zero, that
+/// is once all the actors are waiting for a message or sleeping until a scheduled deadline.
+#[derive(Clone, Default)]
+pub(crate) struct InFlightCounter(Arc<AtomicUsize>);
+
+impl InFlightCounter {
+    pub(crate) fn add(&self, num_in_flight: usize) {
+        self.0.fetch_add(num_in_flight, Ordering::SeqCst);
+    }
+
+    pub(crate) fn sub(&self, num_processed: usize) {
+        self.0.fetch_sub(num_processed, Ordering::SeqCst);
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.load(Ordering::SeqCst) == 0
+    }
+
+    /// Returns the two halves of a sleep of the calling actor: the guard removes the work of the
+    /// actor from the count from the call to [`SleepGuard::start`] until the guard is dropped or
+    /// [`SleepWaker::wake`] is called, whichever comes first.
+    pub(crate) fn sleep(&self) -> (SleepGuard, SleepWaker) {
+        let sleep_state = Arc::new(AtomicU8::new(NOT_STARTED));
+        let sleep_guard = SleepGuard {
+            in_flight_counter: self.clone(),
+            sleep_state: sleep_state.clone(),
+        };
+        let sleep_waker = SleepWaker {
+            in_flight_counter: self.clone(),
+            sleep_state,
+        };
+        (sleep_guard, sleep_waker)
+    }
+
+    /// Waits until no work is in flight.
+    ///
+    /// Actors can be fed from outside of the universe, by a network listener for instance, in
+    /// which case the count might never drop to zero. The wait is therefore bounded: the time
+    /// advances anyway after `max_wait` of real time, and the simulation is no longer
+    /// deterministic.
+    pub(crate) async fn wait_until_idle(&self, max_wait: Duration) {
+        let start = Instant::now();
+        loop {
+            if self.is_zero() {
+                // Sync actors run on their own threads: we give them a chance to pick up a message
+                // sent in the meantime before declaring the universe idle.
+                tokio::task::yield_now().await;
+                if self.is_zero() {
+                    return;
+                }
+            }
+            if start.elapsed() > max_wait {
+                warn!(
+                    num_in_flight = self.0.load(Ordering::SeqCst),
+                    "Advancing the simulated time while actors are still busy."
+                );
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+}
+
+const NOT_STARTED: u8 = 0;
+const SLEEPING: u8 = 1;
+const AWAKE: u8 = 2;
+
+/// Puts the work of a sleeping actor back into the count when dropped, unless the actor was
+/// already woken up.
+pub(crate) struct SleepGuard {
+    in_flight_counter: InFlightCounter,
+    sleep_state: Arc<AtomicU8>,
+}
+
+impl SleepGuard {
+    pub(crate) fn start(&mut self) {
+        if self
+            .sleep_state
+            .compare_exchange(NOT_STARTED, SLEEPING, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            self.in_flight_counter.sub(1);
+        }
+    }
+}
+
+impl Drop for SleepGuard {
+    fn drop(&mut self) {
+        if self.sleep_state.swap(AWAKE, Ordering::SeqCst) == SLEEPING {
+            self.in_flight_counter.add(1);
+        }
+    }
+}
+
+/// Puts the work of a sleeping actor back into the count as soon as the scheduler wakes the
+/// actor up, so that the time does not advance again before the actor resumes.
+///
+/// Waking up an actor that has not started sleeping yet only prevents it from doing so.
+pub(crate) struct SleepWaker {
+    in_flight_counter: InFlightCounter,
+    sleep_state: Arc<AtomicU8>,
+}
+
+impl SleepWaker {
+    pub(crate) fn wake(self) {
+        if self.sleep_state.swap(AWAKE, Ordering::SeqCst) == SLEEPING {
+            self.in_flight_counter.add(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_flight_counter_sleep() {
+        let in_flight_counter = InFlightCounter::default();
+        in_flight_counter.add(1);
+        {
+            let (mut sleep_guard, sleep_waker) = in_flight_counter.sleep();
+            assert!(!in_flight_counter.is_zero());
+            sleep_guard.start();
+            assert!(in_flight_counter.is_zero());
+            sleep_waker.wake();
+            assert!(!in_flight_counter.is_zero());
+        }
+        assert!(!in_flight_counter.is_zero());
+        {
+            let (mut sleep_guard, _sleep_waker) = in_flight_counter.sleep();
+            sleep_guard.start();
+            assert!(in_flight_counter.is_zero());
+        }
+        assert!(!in_flight_counter.is_zero());
+        {
+            let (mut sleep_guard, sleep_waker) = in_flight_counter.sleep();
+            sleep_waker.wake();
+            sleep_guard.start();
+            assert!(!in_flight_counter.is_zero());
+        }
+        in_flight_counter.sub(1);
+        assert!(in_flight_counter.is_zero());
+    }
+}
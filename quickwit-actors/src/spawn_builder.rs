@@ -20,6 +20,7 @@
 use crate::async_actor::spawn_async_actor;
 use crate::mailbox::Inbox;
 use crate::scheduler::SchedulerMessage;
+use crate::simulation::InFlightCounter;
 use crate::sync_actor::spawn_sync_actor;
 use crate::{
     create_mailbox, Actor, ActorContext, ActorHandle, AsyncActor, KillSwitch, Mailbox, SyncActor,
@@ -30,6 +31,7 @@ pub struct SpawnBuilder<A: Actor> {
     actor: A,
     scheduler_mailbox: Mailbox<SchedulerMessage>,
     kill_switch: KillSwitch,
+    in_flight_counter: Option<InFlightCounter>,
     #[allow(clippy::type_complexity)]
     mailboxes: Option<(Mailbox<A::Message>, Inbox<A::Message>)>,
 }
@@ -39,11 +41,13 @@ impl<A: Actor> SpawnBuilder<A> {
         actor: A,
         scheduler_mailbox: Mailbox<SchedulerMessage>,
         kill_switch: KillSwitch,
+        in_flight_counter: Option<InFlightCounter>,
     ) -> Self {
         SpawnBuilder {
             actor,
             scheduler_mailbox,
             kill_switch,
+            in_flight_counter,
             mailboxes: None,
         }
     }
@@ -75,10 +79,17 @@ impl<A: Actor> SpawnBuilder<A> {
             let queue_capacity = self.actor.queue_capacity();
             create_mailbox(actor_name, queue_capacity)
         });
+        if let Some(in_flight_counter) = &self.in_flight_counter {
+            mailbox.set_in_flight_counter(in_flight_counter.clone());
+            // The initialization of the actor and the messages sent before the actor was spawned
+            // are in flight too.
+            in_flight_counter.add(1 + inbox.len());
+        }
         let ctx = ActorContext::new(
             mailbox,
             self.kill_switch.clone(),
             self.scheduler_mailbox.clone(),
+            self.in_flight_counter.clone(),
         );
         (self.actor, ctx, inbox)
     }
@@ -23,7 +23,7 @@ use tracing::{error, info, Span};
 
 use crate::actor::{process_command, ActorExitStatus};
 use crate::actor_with_state_tx::ActorWithStateTx;
-use crate::mailbox::{CommandOrMessage, Inbox};
+use crate::mailbox::{Command, CommandOrMessage, Inbox};
 use crate::{Actor, ActorContext, ActorHandle, RecvError};
 
 /// An sync actor is executed on a tokio blocking task.
@@ -122,11 +122,15 @@ fn process_msg<A: Actor + SyncActor>(
 
     ctx.progress().record_progress();
 
-    let command_or_msg_recv_res = if ctx.state().is_running() {
-        inbox.recv_timeout_blocking()
-    } else {
-        // The actor is paused. We only process command and scheduled message.
-        inbox.recv_timeout_cmd_and_scheduled_msg_only_blocking()
+    let command_or_msg_recv_res = {
+        // An actor waiting for a message is not blocked.
+        let _guard = ctx.protect_zone();
+        if ctx.state().is_running() {
+            inbox.recv_timeout_blocking()
+        } else {
+            // The actor is paused. We only process command and scheduled message.
+            inbox.recv_timeout_cmd_and_scheduled_msg_only_blocking()
+        }
     };
 
     ctx.progress().record_progress();
@@ -136,13 +140,20 @@ fn process_msg<A: Actor + SyncActor>(
     match command_or_msg_recv_res {
         Ok(CommandOrMessage::Command(cmd)) => {
             ctx.process();
-            process_command(actor, cmd, ctx, state_tx)
+            let is_counted = matches!(cmd, Command::ExitWithSuccess);
+            let exit_status_opt = process_command(actor, cmd, ctx, state_tx);
+            if is_counted {
+                ctx.record_processed(1);
+            }
+            exit_status_opt
         }
         Ok(CommandOrMessage::Message(msg)) => {
             ctx.process();
             let span = actor.message_span(msg_id, &msg);
             let _span_guard = span.enter();
-            actor.process_message(msg, ctx).err()
+            let exit_status_opt = actor.process_message(msg, ctx).err();
+            ctx.record_processed(1);
+            exit_status_opt
         }
         Err(RecvError::Timeout) => {
             ctx.idle();
@@ -170,6 +181,7 @@ fn sync_actor_loop<A: SyncActor>(
 
     let mut exit_status_opt: Option<ActorExitStatus> =
         actor_with_state_tx.actor.initialize(&ctx).err();
+    ctx.record_processed(1);
 
     let mut msg_id = 1;
 
@@ -187,6 +199,8 @@ fn sync_actor_loop<A: SyncActor>(
         msg_id += 1;
     };
 
+    // The commands and messages left in the inbox will never be processed.
+    ctx.record_processed(inbox.discard_pending());
     if let Err(finalize_error) = actor_with_state_tx.actor.finalize(&exit_status, &ctx) {
         error!(error=?finalize_error, "Finalizing failed, set exit status to panicked.");
         exit_status = ActorExitStatus::Panicked;
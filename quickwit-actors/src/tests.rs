@@ -649,3 +649,26 @@ async fn test_actor_finalize_error_set_exit_status_to_panicked() -> anyhow::Resu
 
     Ok(())
 }
+
+#[test]
+fn test_mailbox_fill_ratio() {
+    let (forwarder_mailbox, _forwarder_inbox) =
+        crate::create_mailbox::<Ping>("forwarder".to_string(), crate::QueueCapacity::Bounded(1));
+    forwarder_mailbox.try_send_message(Ping).unwrap();
+    assert_eq!(forwarder_mailbox.fill_ratio(), 1.0);
+
+    let (worker_mailbox, _worker_inbox) =
+        crate::create_mailbox::<Ping>("worker".to_string(), crate::QueueCapacity::Bounded(4));
+    forwarder_mailbox.forward_fill_ratio_of(&worker_mailbox);
+    assert_eq!(forwarder_mailbox.fill_ratio(), 0.0);
+    worker_mailbox.try_send_message(Ping).unwrap();
+    assert_eq!(forwarder_mailbox.fill_ratio(), 0.25);
+
+    let (unbounded_mailbox, _unbounded_inbox) = crate::create_test_mailbox::<Ping>();
+    unbounded_mailbox.try_send_message(Ping).unwrap();
+    assert_eq!(unbounded_mailbox.fill_ratio(), 0.0);
+
+    // The link does not keep the downstream actor alive.
+    drop(worker_mailbox);
+    assert_eq!(forwarder_mailbox.fill_ratio(), 0.0);
+}
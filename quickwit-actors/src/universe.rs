@@ -22,6 +22,7 @@ use std::time::Duration;
 use crate::channel_with_priority::Priority;
 use crate::mailbox::{Command, CommandOrMessage};
 use crate::scheduler::{SchedulerMessage, TimeShift};
+use crate::simulation::InFlightCounter;
 use crate::spawn_builder::SpawnBuilder;
 use crate::{Actor, KillSwitch, Mailbox, QueueCapacity, Scheduler};
 
@@ -35,21 +36,45 @@ pub struct Universe {
     // This killswitch is used for the scheduler, and will be used by default for all spawned
     // actors.
     kill_switch: KillSwitch,
+    in_flight_counter: Option<InFlightCounter>,
 }
 
 impl Universe {
     /// Creates a new universe.
     #[allow(clippy::new_without_default)]
     pub fn new() -> Universe {
-        let scheduler = Scheduler::default();
+        Self::with_scheduler(Scheduler::default(), None)
+    }
+
+    /// Creates a new universe whose time is simulated, for deterministic unit tests.
+    ///
+    /// The time of such a universe is frozen: scheduled messages and the sleeps of
+    /// `ActorContext::sleep` only end when the time is shifted with
+    /// [`Universe::simulate_time_shift`]. The time is then shifted from one deadline to the
+    /// next, each time once all the actors of the universe are idle, that is once they have
+    /// processed all their messages and are waiting for a new one or sleeping. Commit timeouts,
+    /// heartbeats and source loops can therefore be tested without any real sleep, and in the
+    /// same order at every run.
+    pub fn with_simulated_time() -> Universe {
+        let in_flight_counter = InFlightCounter::default();
+        let scheduler = Scheduler::with_simulated_time(in_flight_counter.clone());
+        Self::with_scheduler(scheduler, Some(in_flight_counter))
+    }
+
+    fn with_scheduler(
+        scheduler: Scheduler,
+        in_flight_counter: Option<InFlightCounter>,
+    ) -> Universe {
         let kill_switch = KillSwitch::default();
         let (mailbox, _inbox) =
             crate::create_mailbox("fake-mailbox".to_string(), QueueCapacity::Unbounded);
+        // The scheduler itself is never counted as busy.
         let (scheduler_mailbox, _scheduler_inbox) =
-            SpawnBuilder::new(scheduler, mailbox, kill_switch.clone()).spawn_async();
+            SpawnBuilder::new(scheduler, mailbox, kill_switch.clone(), None).spawn_async();
         Universe {
             scheduler_mailbox,
             kill_switch,
+            in_flight_counter,
         }
     }
 
@@ -63,7 +88,8 @@ impl Universe {
     /// These message might have generated more messages for instance.
     ///
     /// This simulation triggers progress step by step, and after each step, leaves 100ms for actors
-    /// to schedule extra messages.
+    /// to schedule extra messages. In a universe created with
+    /// [`Universe::with_simulated_time`], each step waits for the actors to be idle instead.
     pub async fn simulate_time_shift(&self, duration: Duration) {
         let (tx, rx) = tokio::sync::oneshot::channel::<()>();
         let _ = self
@@ -81,6 +107,7 @@ impl Universe {
             actor,
             self.scheduler_mailbox.clone(),
             self.kill_switch.clone(),
+            self.in_flight_counter.clone(),
         )
     }
 
@@ -116,6 +143,8 @@ impl Drop for Universe {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
     use std::time::Duration;
 
     use async_trait::async_trait;
@@ -167,4 +196,76 @@ mod tests {
         // See comment on `universe.simulate_advance_time`.
         assert_eq!(count_after_advance_time, 4);
     }
+
+    #[tokio::test]
+    async fn test_schedule_for_actor_with_simulated_time() {
+        let universe = Universe::with_simulated_time();
+        let (_mailbox, handle) = universe
+            .spawn_actor(ActorWithSchedule::default())
+            .spawn_async();
+        assert_eq!(handle.process_pending_and_observe().await.state, 1);
+        let start = std::time::Instant::now();
+        universe
+            .simulate_time_shift(Duration::from_secs(3_600))
+            .await;
+        assert_eq!(handle.process_pending_and_observe().await.state, 61);
+        universe.simulate_time_shift(Duration::from_secs(59)).await;
+        assert_eq!(handle.process_pending_and_observe().await.state, 61);
+        universe.simulate_time_shift(Duration::from_secs(1)).await;
+        assert_eq!(handle.process_pending_and_observe().await.state, 62);
+        assert!(start.elapsed() < Duration::from_secs(30));
+    }
+
+    struct SleepingLoopActor {
+        num_loops: Arc<AtomicUsize>,
+    }
+
+    #[derive(Debug)]
+    struct Loop;
+
+    impl Actor for SleepingLoopActor {
+        type Message = Loop;
+
+        type ObservableState = usize;
+
+        fn observable_state(&self) -> usize {
+            self.num_loops.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl AsyncActor for SleepingLoopActor {
+        async fn initialize(&mut self, ctx: &ActorContext<Self>) -> Result<(), ActorExitStatus> {
+            self.process_message(Loop, ctx).await
+        }
+
+        async fn process_message(
+            &mut self,
+            _: Loop,
+            ctx: &ActorContext<Self>,
+        ) -> Result<(), ActorExitStatus> {
+            ctx.sleep(Duration::from_secs(10)).await;
+            self.num_loops.fetch_add(1, Ordering::SeqCst);
+            ctx.send_self_message(Loop).await?;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sleep_with_simulated_time() {
+        let universe = Universe::with_simulated_time();
+        let num_loops = Arc::new(AtomicUsize::default());
+        let sleeping_actor = SleepingLoopActor {
+            num_loops: num_loops.clone(),
+        };
+        // The actor spends its time sleeping, so we read the counter directly rather than
+        // observing the actor.
+        let (_mailbox, _handle) = universe.spawn_actor(sleeping_actor).spawn_async();
+        universe.simulate_time_shift(Duration::from_secs(5)).await;
+        assert_eq!(num_loops.load(Ordering::SeqCst), 0);
+        universe.simulate_time_shift(Duration::from_secs(55)).await;
+        assert_eq!(num_loops.load(Ordering::SeqCst), 6);
+        universe.simulate_time_shift(Duration::from_secs(600)).await;
+        assert_eq!(num_loops.load(Ordering::SeqCst), 66);
+    }
 }
@@ -0,0 +1,442 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+
+use anyhow::{bail, Context};
+use clap::ArgMatches;
+use colored::Colorize;
+use quickwit_client::{QuickwitClient, SearchQuery, SearchResponse};
+use quickwit_common::uri::Uri;
+use quickwit_storage::load_file;
+use serde::Deserialize;
+use tracing::debug;
+
+#[derive(Debug, PartialEq)]
+pub struct CompareArgs {
+    pub queries_uri: Uri,
+    pub baseline_endpoint: String,
+    pub candidate_endpoint: String,
+    pub index_id: Option<String>,
+    pub tolerances: Tolerances,
+}
+
+/// Differences between the results of the two endpoints that are not reported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerances {
+    /// Maximum relative difference between the numbers of hits, e.g. `0.01` for 1%.
+    pub num_hits: f64,
+    /// Minimum fraction of the hits returned by both endpoints, e.g. `0.9` for 90%.
+    pub min_hit_overlap: f64,
+}
+
+impl Default for Tolerances {
+    fn default() -> Self {
+        Tolerances {
+            num_hits: 0.0,
+            min_hit_overlap: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BenchCliCommand {
+    Compare(CompareArgs),
+}
+
+impl BenchCliCommand {
+    pub async fn execute(self) -> anyhow::Result<()> {
+        match self {
+            Self::Compare(args) => compare_cli(args).await,
+        }
+    }
+
+    pub fn parse_cli_args(matches: &ArgMatches) -> anyhow::Result<Self> {
+        let (subcommand, submatches) = matches
+            .subcommand()
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse bench subcommand arguments."))?;
+        match subcommand {
+            "compare" => Self::parse_compare_args(submatches).map(Self::Compare),
+            _ => bail!("Bench subcommand `{}` is not implemented.", subcommand),
+        }
+    }
+
+    fn parse_compare_args(matches: &ArgMatches) -> anyhow::Result<CompareArgs> {
+        let queries_uri = matches
+            .value_of("queries")
+            .map(Uri::try_new)
+            .expect("`queries` is a required arg.")?;
+        let baseline_endpoint = matches
+            .value_of("baseline")
+            .map(String::from)
+            .expect("`baseline` is a required arg.");
+        let candidate_endpoint = matches
+            .value_of("candidate")
+            .map(String::from)
+            .expect("`candidate` is a required arg.");
+        let index_id = matches.value_of("index").map(String::from);
+        let mut tolerances = Tolerances::default();
+        if let Some(num_hits_tolerance) = matches.value_of("num-hits-tolerance") {
+            tolerances.num_hits = parse_fraction(num_hits_tolerance, "num-hits-tolerance")?;
+        }
+        if let Some(min_hit_overlap) = matches.value_of("min-hit-overlap") {
+            tolerances.min_hit_overlap = parse_fraction(min_hit_overlap, "min-hit-overlap")?;
+        }
+        Ok(CompareArgs {
+            queries_uri,
+            baseline_endpoint,
+            candidate_endpoint,
+            index_id,
+            tolerances,
+        })
+    }
+}
+
+fn parse_fraction(value: &str, arg_name: &str) -> anyhow::Result<f64> {
+    let fraction: f64 = value
+        .parse()
+        .with_context(|| format!("Failed to parse `{}` value `{}`.", arg_name, value))?;
+    if !(0.0..=1.0).contains(&fraction) {
+        bail!("`{}` must be between 0 and 1, got `{}`.", arg_name, value);
+    }
+    Ok(fraction)
+}
+
+/// Query of a query set file, which holds one JSON object per line.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+struct BenchQuery {
+    /// Index to search. Defaults to the index passed on the command line.
+    #[serde(default)]
+    index: Option<String>,
+    query: String,
+    #[serde(default)]
+    search_fields: Vec<String>,
+    #[serde(default)]
+    start_timestamp: Option<String>,
+    #[serde(default)]
+    end_timestamp: Option<String>,
+    #[serde(default = "default_max_hits")]
+    max_hits: u64,
+    #[serde(default)]
+    sort_by_field: Option<String>,
+}
+
+fn default_max_hits() -> u64 {
+    20
+}
+
+impl BenchQuery {
+    fn search_query(&self) -> SearchQuery {
+        SearchQuery {
+            search_fields: self.search_fields.clone(),
+            start_timestamp: self.start_timestamp.clone(),
+            end_timestamp: self.end_timestamp.clone(),
+            max_hits: self.max_hits,
+            sort_by_field: self.sort_by_field.clone(),
+            ..SearchQuery::new(self.query.clone())
+        }
+    }
+}
+
+fn parse_query_set(content: &[u8]) -> anyhow::Result<Vec<BenchQuery>> {
+    let content = std::str::from_utf8(content).context("Query set file is not valid UTF-8.")?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(line_idx, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Failed to parse query at line {}.", line_idx + 1))
+        })
+        .collect()
+}
+
+/// Returns the differences between the responses of the baseline and the candidate endpoints
+/// that exceed the tolerances. Hits are compared regardless of their order.
+fn compare_search_responses(
+    baseline: &SearchResponse,
+    candidate: &SearchResponse,
+    tolerances: &Tolerances,
+) -> Vec<String> {
+    let mut differences = Vec::new();
+    let num_hits_delta = (baseline.num_hits as f64 - candidate.num_hits as f64).abs();
+    if num_hits_delta > tolerances.num_hits * baseline.num_hits.max(1) as f64 {
+        differences.push(format!(
+            "Number of hits differs: {} (baseline) vs {} (candidate).",
+            baseline.num_hits, candidate.num_hits
+        ));
+    }
+    let mut baseline_hits: HashMap<String, usize> = HashMap::new();
+    for hit in &baseline.hits {
+        *baseline_hits.entry(hit.to_string()).or_default() += 1;
+    }
+    let mut num_common_hits = 0;
+    for hit in &candidate.hits {
+        if let Some(count) = baseline_hits.get_mut(&hit.to_string()) {
+            if *count > 0 {
+                *count -= 1;
+                num_common_hits += 1;
+            }
+        }
+    }
+    let num_hits_max = baseline.hits.len().max(candidate.hits.len());
+    let hit_overlap = if num_hits_max == 0 {
+        1.0
+    } else {
+        num_common_hits as f64 / num_hits_max as f64
+    };
+    if hit_overlap < tolerances.min_hit_overlap {
+        differences.push(format!(
+            "Hit overlap is {:.2}: {} hit(s) missing from the candidate, {} extra hit(s).",
+            hit_overlap,
+            baseline.hits.len() - num_common_hits,
+            candidate.hits.len() - num_common_hits
+        ));
+    }
+    for (endpoint, response) in [("baseline", baseline), ("candidate", candidate)] {
+        if !response.errors.is_empty() {
+            differences.push(format!(
+                "The {} failed to search some splits: {}.",
+                endpoint,
+                response.errors.join(", ")
+            ));
+        }
+    }
+    differences
+}
+
+async fn compare_cli(args: CompareArgs) -> anyhow::Result<()> {
+    debug!(args = ?args, "bench-compare");
+    let content = load_file(&args.queries_uri).await?;
+    let queries = parse_query_set(content.as_slice())?;
+    let baseline_client = QuickwitClient::builder(args.baseline_endpoint.clone()).build()?;
+    let candidate_client = QuickwitClient::builder(args.candidate_endpoint.clone()).build()?;
+
+    let mut num_queries_with_differences = 0;
+    for (query_idx, bench_query) in queries.iter().enumerate() {
+        let index_id = bench_query
+            .index
+            .as_ref()
+            .or_else(|| args.index_id.as_ref())
+            .with_context(|| {
+                format!(
+                    "Query #{} has no index: set `index` in the query or pass `--index`.",
+                    query_idx + 1
+                )
+            })?;
+        let search_query = bench_query.search_query();
+        let (baseline_res, candidate_res) = tokio::join!(
+            baseline_client.search(index_id, &search_query),
+            candidate_client.search(index_id, &search_query)
+        );
+        let differences = match (baseline_res, candidate_res) {
+            (Ok(baseline), Ok(candidate)) => {
+                compare_search_responses(&baseline, &candidate, &args.tolerances)
+            }
+            (baseline_res, candidate_res) => {
+                [("baseline", baseline_res), ("candidate", candidate_res)]
+                    .into_iter()
+                    .filter_map(|(endpoint, search_res)| {
+                        search_res
+                            .err()
+                            .map(|error| format!("The {} search failed: {}.", endpoint, error))
+                    })
+                    .collect()
+            }
+        };
+        let status = if differences.is_empty() {
+            "OK".green()
+        } else {
+            num_queries_with_differences += 1;
+            "DIFF".red()
+        };
+        println!(
+            "{} #{} `{}` on `{}`",
+            status,
+            query_idx + 1,
+            bench_query.query,
+            index_id
+        );
+        for difference in differences {
+            println!("    {}", difference);
+        }
+    }
+    println!(
+        "Compared {} queries: {} with differences.",
+        queries.len(),
+        num_queries_with_differences
+    );
+    if num_queries_with_differences > 0 {
+        bail!(
+            "{} queries returned different results.",
+            num_queries_with_differences
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{load_yaml, App, AppSettings};
+    use serde_json::json;
+
+    use super::*;
+    use crate::cli::CliCommand;
+
+    fn search_response(num_hits: u64, hits: Vec<serde_json::Value>) -> SearchResponse {
+        SearchResponse {
+            num_hits,
+            hits,
+            elapsed_time_micros: 0,
+            errors: Vec::new(),
+            snapshot: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_compare_args() -> anyhow::Result<()> {
+        let yaml = load_yaml!("cli.yaml");
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app.try_get_matches_from(vec![
+            "bench",
+            "compare",
+            "--queries",
+            "/queries.ndjson",
+            "--baseline",
+            "http://localhost:7280",
+            "--candidate",
+            "http://localhost:8280",
+            "--index",
+            "wikipedia",
+            "--num-hits-tolerance",
+            "0.01",
+        ])?;
+        let command = CliCommand::parse_cli_args(&matches)?;
+        assert_eq!(
+            command,
+            CliCommand::Bench(BenchCliCommand::Compare(CompareArgs {
+                queries_uri: Uri::try_new("file:///queries.ndjson")?,
+                baseline_endpoint: "http://localhost:7280".to_string(),
+                candidate_endpoint: "http://localhost:8280".to_string(),
+                index_id: Some("wikipedia".to_string()),
+                tolerances: Tolerances {
+                    num_hits: 0.01,
+                    min_hit_overlap: 1.0,
+                },
+            }))
+        );
+
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app.try_get_matches_from(vec![
+            "bench",
+            "compare",
+            "--queries",
+            "/queries.ndjson",
+            "--baseline",
+            "http://localhost:7280",
+            "--candidate",
+            "http://localhost:8280",
+            "--min-hit-overlap",
+            "1.5",
+        ])?;
+        assert!(CliCommand::parse_cli_args(&matches).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_query_set() {
+        let content = br#"{"query": "title:apollo", "index": "wikipedia"}
+
+{"query": "body:moon", "searchFields": ["body"], "startTimestamp": "now-1d", "maxHits": 100}
+"#;
+        let queries = parse_query_set(content).unwrap();
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[0].index.as_deref(), Some("wikipedia"));
+        assert_eq!(queries[0].max_hits, 20);
+        let search_query = queries[1].search_query();
+        assert_eq!(search_query.query, "body:moon");
+        assert_eq!(search_query.search_fields, vec!["body".to_string()]);
+        assert_eq!(search_query.start_timestamp.as_deref(), Some("now-1d"));
+        assert_eq!(search_query.max_hits, 100);
+
+        let error = parse_query_set(b"{\"query\": \"a\"}\n{\"qery\": \"b\"}").unwrap_err();
+        assert_eq!(error.to_string(), "Failed to parse query at line 2.");
+    }
+
+    #[test]
+    fn test_compare_search_responses() {
+        let baseline = search_response(
+            100,
+            vec![
+                json!({"id": 1}),
+                json!({"id": 2}),
+                json!({"id": 3}),
+                json!({"id": 4}),
+            ],
+        );
+        let same_hits_in_other_order = search_response(
+            100,
+            vec![
+                json!({"id": 4}),
+                json!({"id": 3}),
+                json!({"id": 2}),
+                json!({"id": 1}),
+            ],
+        );
+        assert!(compare_search_responses(
+            &baseline,
+            &same_hits_in_other_order,
+            &Tolerances::default()
+        )
+        .is_empty());
+
+        let candidate = search_response(
+            99,
+            vec![
+                json!({"id": 1}),
+                json!({"id": 2}),
+                json!({"id": 3}),
+                json!({"id": 5}),
+            ],
+        );
+        let differences = compare_search_responses(&baseline, &candidate, &Tolerances::default());
+        assert_eq!(
+            differences,
+            vec![
+                "Number of hits differs: 100 (baseline) vs 99 (candidate).".to_string(),
+                "Hit overlap is 0.75: 1 hit(s) missing from the candidate, 1 extra hit(s)."
+                    .to_string(),
+            ]
+        );
+        let tolerances = Tolerances {
+            num_hits: 0.01,
+            min_hit_overlap: 0.75,
+        };
+        assert!(compare_search_responses(&baseline, &candidate, &tolerances).is_empty());
+
+        let mut failed_candidate = search_response(100, baseline.hits.clone());
+        failed_candidate.errors = vec!["split-1".to_string()];
+        assert_eq!(
+            compare_search_responses(&baseline, &failed_candidate, &Tolerances::default()),
+            vec!["The candidate failed to search some splits: split-1.".to_string()]
+        );
+    }
+}
@@ -21,14 +21,20 @@ use anyhow::bail;
 use clap::ArgMatches;
 use tracing::Level;
 
+use crate::bench::BenchCliCommand;
+use crate::config::ConfigCliCommand;
 use crate::index::IndexCliCommand;
+use crate::run::RunCliCommand;
 use crate::service::ServiceCliCommand;
 use crate::source::SourceCliCommand;
 use crate::split::SplitCliCommand;
 
 #[derive(Debug, PartialEq)]
 pub enum CliCommand {
+    Bench(BenchCliCommand),
+    Config(ConfigCliCommand),
     Index(IndexCliCommand),
+    Run(RunCliCommand),
     Service(ServiceCliCommand),
     Source(SourceCliCommand),
     Split(SplitCliCommand),
@@ -37,7 +43,10 @@ pub enum CliCommand {
 impl CliCommand {
     pub fn default_log_level(&self) -> Level {
         match self {
+            CliCommand::Bench(_) => Level::ERROR,
+            CliCommand::Config(_) => Level::ERROR,
             CliCommand::Index(subcommand) => subcommand.default_log_level(),
+            CliCommand::Run(_) => Level::INFO,
             CliCommand::Service(_) => Level::INFO,
             CliCommand::Source(_) => Level::ERROR,
             CliCommand::Split(_) => Level::ERROR,
@@ -49,7 +58,10 @@ impl CliCommand {
             .subcommand()
             .ok_or_else(|| anyhow::anyhow!("Failed to parse command arguments."))?;
         match subcommand {
+            "bench" => BenchCliCommand::parse_cli_args(submatches).map(CliCommand::Bench),
+            "config" => ConfigCliCommand::parse_cli_args(submatches).map(CliCommand::Config),
             "index" => IndexCliCommand::parse_cli_args(submatches).map(CliCommand::Index),
+            "run" => RunCliCommand::parse_cli_args(submatches).map(CliCommand::Run),
             "service" => ServiceCliCommand::parse_cli_args(submatches).map(CliCommand::Service),
             "source" => SourceCliCommand::parse_cli_args(submatches).map(CliCommand::Source),
             "split" => SplitCliCommand::parse_cli_args(submatches).map(CliCommand::Split),
@@ -59,7 +71,10 @@ impl CliCommand {
 
     pub async fn execute(self) -> anyhow::Result<()> {
         match self {
+            CliCommand::Bench(subcommand) => subcommand.execute().await,
+            CliCommand::Config(subcommand) => subcommand.execute().await,
             CliCommand::Index(subcommand) => subcommand.execute().await,
+            CliCommand::Run(subcommand) => subcommand.execute().await,
             CliCommand::Service(subcommand) => subcommand.execute().await,
             CliCommand::Source(subcommand) => subcommand.execute().await,
             CliCommand::Split(subcommand) => subcommand.execute().await,
@@ -0,0 +1,117 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use anyhow::bail;
+use clap::ArgMatches;
+use colored::Colorize;
+use quickwit_common::uri::Uri;
+use quickwit_config::IndexConfig;
+use quickwit_storage::load_file;
+use tracing::debug;
+
+#[derive(Debug, PartialEq)]
+pub struct ValidateIndexConfigArgs {
+    pub index_config_uri: Uri,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigCliCommand {
+    ValidateIndexConfig(ValidateIndexConfigArgs),
+}
+
+impl ConfigCliCommand {
+    pub async fn execute(self) -> anyhow::Result<()> {
+        match self {
+            Self::ValidateIndexConfig(args) => validate_index_config_cli(args).await,
+        }
+    }
+
+    pub fn parse_cli_args(matches: &ArgMatches) -> anyhow::Result<Self> {
+        let (subcommand, submatches) = matches
+            .subcommand()
+            .ok_or_else(|| anyhow::anyhow!("Failed to parse config subcommand arguments."))?;
+        match subcommand {
+            "validate" => Self::parse_validate_args(submatches).map(Self::ValidateIndexConfig),
+            _ => bail!("Config subcommand `{}` is not implemented.", subcommand),
+        }
+    }
+
+    fn parse_validate_args(matches: &ArgMatches) -> anyhow::Result<ValidateIndexConfigArgs> {
+        let index_config_uri = matches
+            .value_of("index-config")
+            .map(Uri::try_new)
+            .expect("`index-config` is a required arg.")?;
+        Ok(ValidateIndexConfigArgs { index_config_uri })
+    }
+}
+
+/// Prints the warnings reported by the linting of an index config.
+pub fn print_index_config_warnings(warnings: &[String]) {
+    for warning in warnings {
+        println!("{} {}", "Warning:".yellow(), warning);
+    }
+}
+
+async fn validate_index_config_cli(args: ValidateIndexConfigArgs) -> anyhow::Result<()> {
+    debug!(args = ?args, "validate-index-config");
+    let file_content = load_file(&args.index_config_uri).await?;
+    let index_config = IndexConfig::load(&args.index_config_uri, file_content.as_slice()).await?;
+    let warnings = index_config.lint()?;
+    print_index_config_warnings(&warnings);
+    println!(
+        "Index config `{}` is valid{}.",
+        index_config.index_id,
+        if warnings.is_empty() {
+            ""
+        } else {
+            " but has warnings"
+        }
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{load_yaml, App, AppSettings};
+
+    use super::*;
+    use crate::cli::CliCommand;
+
+    #[test]
+    fn test_parse_validate_index_config_args() -> anyhow::Result<()> {
+        let yaml = load_yaml!("cli.yaml");
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app.try_get_matches_from(vec![
+            "config",
+            "validate",
+            "--index-config",
+            "/index-conf.yaml",
+        ])?;
+        let command = CliCommand::parse_cli_args(&matches)?;
+        assert_eq!(
+            command,
+            CliCommand::Config(ConfigCliCommand::ValidateIndexConfig(
+                ValidateIndexConfigArgs {
+                    index_config_uri: Uri::try_new("file:///index-conf.yaml")?,
+                }
+            ))
+        );
+        Ok(())
+    }
+}
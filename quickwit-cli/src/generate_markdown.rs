@@ -161,7 +161,6 @@ fn generate_markdown_from_clap(app: &App) {
 
         for subcommand in command.get_subcommands().filter(|subcommand| {
             subcommand.get_name() != "demux"
-                && subcommand.get_name() != "merge"
                 && subcommand.get_name() != "extract"
                 && !(subcommand.get_name() == "describe" && command_name == "split")
         }) {
@@ -19,6 +19,8 @@
 
 use std::collections::{HashSet, VecDeque};
 use std::io::{stdout, Stdout, Write};
+use std::net::Ipv4Addr;
+use std::ops::Range;
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
 use std::{env, fmt, io};
@@ -27,26 +29,42 @@ use anyhow::{bail, Context};
 use chrono::Utc;
 use clap::ArgMatches;
 use colored::Colorize;
+use futures::StreamExt;
 use itertools::Itertools;
 use quickwit_actors::{ActorHandle, ObservationType};
+use quickwit_client::{QuickwitClient, RetryPolicy};
 use quickwit_common::uri::Uri;
 use quickwit_common::{run_checklist, GREEN_COLOR};
-use quickwit_config::{IndexConfig, IndexerConfig, SourceConfig, SourceParams};
-use quickwit_core::{create_index, delete_index, garbage_collect_index, reset_index};
+use quickwit_config::{
+    timestamp_resolution, DatasetFormat, DatasetSourceParams, FileSourceParams, IndexConfig,
+    IndexerConfig, QuickwitConfig, SearchSettings, SourceConfig, SourceParams,
+};
+use quickwit_core::{
+    analyze_index_compaction, apply_compaction_recommendations, create_index, delete_index,
+    garbage_collect_index, reset_index, CompactionReport,
+};
 use quickwit_doc_mapper::tag_pruning::match_tag_field_name;
 use quickwit_indexing::actors::{IndexingPipeline, IndexingServer};
+use quickwit_indexing::merge_index;
 use quickwit_indexing::models::IndexingStatistics;
-use quickwit_indexing::source::INGEST_SOURCE_ID;
-use quickwit_metastore::{quickwit_metastore_uri_resolver, IndexMetadata, Split, SplitState};
+use quickwit_indexing::source::{doc_mapping_from_parquet_dataset, INGEST_SOURCE_ID};
+use quickwit_metastore::{
+    quickwit_metastore_uri_resolver, IndexMetadata, MetastoreError, Split, SplitState,
+};
 use quickwit_proto::{SearchRequest, SearchResponse};
-use quickwit_search::{single_node_search, SearchResponseRest};
+use quickwit_search::{
+    single_node_search, single_node_search_tail, SearchResponseRest, TAIL_POLL_INTERVAL,
+};
 use quickwit_storage::{load_file, quickwit_storage_uri_resolver};
 use quickwit_telemetry::payload::TelemetryEvent;
+use tabled::Tabled;
 use tracing::{debug, info, Level};
 
+use crate::config::print_index_config_warnings;
 use crate::stats::{mean, percentile, std_deviation};
 use crate::{
-    load_quickwit_config, parse_duration_with_unit, run_index_checklist, THROUGHPUT_WINDOW_SIZE,
+    load_quickwit_config, make_table, parse_duration_with_unit, run_index_checklist,
+    THROUGHPUT_WINDOW_SIZE,
 };
 
 #[derive(Debug, Eq, PartialEq)]
@@ -54,6 +72,17 @@ pub struct DescribeIndexArgs {
     pub config_uri: Uri,
     pub data_dir: Option<PathBuf>,
     pub index_id: String,
+    /// REST endpoint of the node whose indexing pipelines are reported. Defaults to the node
+    /// configured by the config file.
+    pub endpoint: Option<String>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct AnalyzeIndexArgs {
+    pub config_uri: Uri,
+    pub data_dir: Option<PathBuf>,
+    pub index_id: String,
+    pub apply: bool,
 }
 
 #[derive(Debug, PartialEq)]
@@ -73,6 +102,16 @@ pub struct IngestDocsArgs {
     pub overwrite: bool,
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub struct IngestDatasetArgs {
+    pub index_id: String,
+    pub input_uri: Uri,
+    pub format: DatasetFormat,
+    pub config_uri: Uri,
+    pub data_dir: Option<PathBuf>,
+    pub overwrite: bool,
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct SearchIndexArgs {
     pub index_id: String,
@@ -82,6 +121,7 @@ pub struct SearchIndexArgs {
     pub search_fields: Option<Vec<String>>,
     pub start_timestamp: Option<i64>,
     pub end_timestamp: Option<i64>,
+    pub tail: bool,
     pub config_uri: Uri,
     pub data_dir: Option<PathBuf>,
 }
@@ -104,7 +144,16 @@ pub struct GarbageCollectIndexArgs {
 }
 
 #[derive(Debug, PartialEq, Eq)]
-pub struct MergeOrDemuxArgs {
+pub struct MergeIndexArgs {
+    pub index_id: String,
+    pub start_timestamp: Option<i64>,
+    pub end_timestamp: Option<i64>,
+    pub config_uri: Uri,
+    pub data_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct DemuxIndexArgs {
     pub index_id: String,
     pub config_uri: Uri,
     pub data_dir: Option<PathBuf>,
@@ -112,13 +161,15 @@ pub struct MergeOrDemuxArgs {
 
 #[derive(Debug, PartialEq)]
 pub enum IndexCliCommand {
+    Analyze(AnalyzeIndexArgs),
     Create(CreateIndexArgs),
     Describe(DescribeIndexArgs),
     Delete(DeleteIndexArgs),
-    Demux(MergeOrDemuxArgs),
-    Merge(MergeOrDemuxArgs),
+    Demux(DemuxIndexArgs),
+    Merge(MergeIndexArgs),
     GarbageCollect(GarbageCollectIndexArgs),
     Ingest(IngestDocsArgs),
+    IngestDataset(IngestDatasetArgs),
     Search(SearchIndexArgs),
 }
 
@@ -141,8 +192,10 @@ impl IndexCliCommand {
             "merge" => Self::parse_merge_args(submatches),
             "demux" => Self::parse_demux_args(submatches),
             "describe" => Self::parse_describe_args(submatches),
+            "analyze" => Self::parse_analyze_args(submatches),
             "gc" => Self::parse_garbage_collect_args(submatches),
             "ingest" => Self::parse_ingest_args(submatches),
+            "ingest-dataset" => Self::parse_ingest_dataset_args(submatches),
             _ => bail!("Index subcommand `{}` is not implemented.", subcommand),
         }
     }
@@ -157,10 +210,31 @@ impl IndexCliCommand {
             .map(Uri::try_new)
             .expect("`config` is a required arg.")?;
         let data_dir = matches.value_of("data-dir").map(PathBuf::from);
+        let endpoint = matches.value_of("endpoint").map(str::to_string);
         Ok(Self::Describe(DescribeIndexArgs {
             config_uri,
             index_id,
             data_dir,
+            endpoint,
+        }))
+    }
+
+    fn parse_analyze_args(matches: &ArgMatches) -> anyhow::Result<Self> {
+        let index_id = matches
+            .value_of("index")
+            .expect("`index` is a required arg.")
+            .to_string();
+        let config_uri = matches
+            .value_of("config")
+            .map(Uri::try_new)
+            .expect("`config` is a required arg.")?;
+        let data_dir = matches.value_of("data-dir").map(PathBuf::from);
+        let apply = matches.is_present("apply");
+        Ok(Self::Analyze(AnalyzeIndexArgs {
+            config_uri,
+            data_dir,
+            index_id,
+            apply,
         }))
     }
 
@@ -190,9 +264,8 @@ impl IndexCliCommand {
             .expect("`index` is a required arg.")
             .to_string();
         let input_path_opt = if let Some(input_path) = matches.value_of("input-path") {
-            Uri::try_new(input_path)?
-                .filepath()
-                .map(|path| path.to_path_buf())
+            let input_uri = Uri::try_new(input_path)?;
+            Some(FileSourceParams::filepath_from_uri(&input_uri))
         } else {
             None
         };
@@ -212,6 +285,36 @@ impl IndexCliCommand {
         }))
     }
 
+    fn parse_ingest_dataset_args(matches: &ArgMatches) -> anyhow::Result<Self> {
+        let index_id = matches
+            .value_of("index")
+            .expect("`index` is a required arg.")
+            .to_string();
+        let input_uri = matches
+            .value_of("input-uri")
+            .map(Uri::try_new)
+            .expect("`input-uri` is a required arg.")?;
+        let format = matches
+            .value_of("format")
+            .expect("`format` is a required arg.")
+            .parse::<DatasetFormat>()?;
+        let config_uri = matches
+            .value_of("config")
+            .map(Uri::try_new)
+            .expect("`config` is a required arg.")?;
+        let data_dir = matches.value_of("data-dir").map(PathBuf::from);
+        let overwrite = matches.is_present("overwrite");
+
+        Ok(Self::IngestDataset(IngestDatasetArgs {
+            index_id,
+            input_uri,
+            format,
+            config_uri,
+            data_dir,
+            overwrite,
+        }))
+    }
+
     fn parse_search_args(matches: &ArgMatches) -> anyhow::Result<Self> {
         let index_id = matches
             .value_of("index")
@@ -236,6 +339,7 @@ impl IndexCliCommand {
         } else {
             None
         };
+        let tail = matches.is_present("tail");
         let config_uri = matches
             .value_of("config")
             .map(Uri::try_new)
@@ -249,6 +353,7 @@ impl IndexCliCommand {
             search_fields,
             start_timestamp,
             end_timestamp,
+            tail,
             config_uri,
             data_dir,
         }))
@@ -263,9 +368,21 @@ impl IndexCliCommand {
             .value_of("config")
             .map(Uri::try_new)
             .expect("`config` is a required arg.")?;
+        let start_timestamp = if matches.is_present("start-timestamp") {
+            Some(matches.value_of_t::<i64>("start-timestamp")?)
+        } else {
+            None
+        };
+        let end_timestamp = if matches.is_present("end-timestamp") {
+            Some(matches.value_of_t::<i64>("end-timestamp")?)
+        } else {
+            None
+        };
         let data_dir = matches.value_of("data-dir").map(PathBuf::from);
-        Ok(Self::Merge(MergeOrDemuxArgs {
+        Ok(Self::Merge(MergeIndexArgs {
             index_id,
+            start_timestamp,
+            end_timestamp,
             config_uri,
             data_dir,
         }))
@@ -281,7 +398,7 @@ impl IndexCliCommand {
             .map(Uri::try_new)
             .expect("`config` is a required arg.")?;
         let data_dir = matches.value_of("data-dir").map(PathBuf::from);
-        Ok(Self::Demux(MergeOrDemuxArgs {
+        Ok(Self::Demux(DemuxIndexArgs {
             index_id,
             config_uri,
             data_dir,
@@ -335,10 +452,12 @@ impl IndexCliCommand {
         match self {
             Self::Create(args) => create_index_cli(args).await,
             Self::Describe(args) => describe_index_cli(args).await,
+            Self::Analyze(args) => analyze_index_cli(args).await,
             Self::Ingest(args) => ingest_docs_cli(args).await,
+            Self::IngestDataset(args) => ingest_dataset_cli(args).await,
             Self::Search(args) => search_index_cli(args).await,
-            Self::Merge(args) => merge_or_demux_cli(args, true, false).await,
-            Self::Demux(args) => merge_or_demux_cli(args, false, true).await,
+            Self::Merge(args) => merge_index_cli(args).await,
+            Self::Demux(args) => demux_index_cli(args).await,
             Self::GarbageCollect(args) => garbage_collect_index_cli(args).await,
             Self::Delete(args) => delete_index_cli(args).await,
         }
@@ -349,6 +468,10 @@ pub async fn describe_index_cli(args: DescribeIndexArgs) -> anyhow::Result<()> {
     debug!(args = ?args, "describe");
     let metastore_uri_resolver = quickwit_metastore_uri_resolver();
     let quickwit_config = load_quickwit_config(args.config_uri, args.data_dir).await?;
+    let rest_endpoint = match args.endpoint {
+        Some(endpoint) => endpoint,
+        None => local_rest_endpoint(&quickwit_config)?,
+    };
     let metastore = metastore_uri_resolver
         .resolve(&quickwit_config.metastore_uri)
         .await?;
@@ -424,12 +547,14 @@ pub async fn describe_index_cli(args: DescribeIndexArgs) -> anyhow::Result<()> {
         );
     }
 
+    print_pipeline_throughputs(&index_metadata.index_id, &rest_endpoint).await;
+
     if splits.is_empty() {
         return Ok(());
     }
 
     println!();
-    println!("2. Statistics on splits");
+    println!("3. Statistics on splits");
     println!("===============================================================================");
     println!("Document count stats:");
     print_descriptive_stats(&splits_num_docs);
@@ -445,9 +570,197 @@ pub async fn describe_index_cli(args: DescribeIndexArgs) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Returns the REST endpoint of the node configured by `quickwit_config`, reached through the
+/// loopback interface when the node listens on all interfaces.
+pub(crate) fn local_rest_endpoint(quickwit_config: &QuickwitConfig) -> anyhow::Result<String> {
+    let mut rest_socket_addr = quickwit_config.rest_socket_addr()?;
+    if rest_socket_addr.ip().is_unspecified() {
+        rest_socket_addr.set_ip(Ipv4Addr::LOCALHOST.into());
+    }
+    Ok(format!("http://{}", rest_socket_addr))
+}
+
+#[derive(Tabled)]
+struct StageThroughputRow {
+    #[header("Source")]
+    source_id: String,
+    #[header("Stage")]
+    stage: String,
+    #[header("Docs/s")]
+    num_docs_per_sec: String,
+    #[header("MB/s")]
+    num_mb_per_sec: String,
+    #[header("Saturation")]
+    saturation: String,
+}
+
+/// Prints the live throughput of the indexing pipelines of the index running on the node
+/// serving `rest_endpoint`. The node may not run any pipeline of the index, or not run at all:
+/// the rest of the description is still relevant, so this is not an error.
+async fn print_pipeline_throughputs(index_id: &str, rest_endpoint: &str) {
+    println!();
+    println!("2. Indexing throughput");
+    println!("===============================================================================");
+    let pipeline_throughputs_res = match QuickwitClient::builder(rest_endpoint)
+        .timeout(Duration::from_secs(5))
+        .retry_policy(RetryPolicy::no_retry())
+        .build()
+    {
+        Ok(client) => client.pipeline_throughputs(index_id).await,
+        Err(error) => Err(error),
+    };
+    let pipelines = match pipeline_throughputs_res {
+        Ok(pipeline_throughputs) => pipeline_throughputs.pipelines,
+        Err(error) => {
+            println!(
+                "Failed to fetch the indexing throughput from `{}`: {}",
+                rest_endpoint, error
+            );
+            return;
+        }
+    };
+    if pipelines.is_empty() {
+        println!(
+            "No indexing pipeline of the index is running on `{}`.",
+            rest_endpoint
+        );
+        return;
+    }
+    for pipeline in &pipelines {
+        println!(
+            "{:<35} {}",
+            format!("Bottleneck of `{}`:", pipeline.source_id).color(GREEN_COLOR),
+            pipeline.bottleneck
+        );
+    }
+    let rows = pipelines.iter().flat_map(|pipeline| {
+        pipeline.stages.iter().map(|stage| StageThroughputRow {
+            source_id: pipeline.source_id.clone(),
+            stage: stage.stage.clone(),
+            num_docs_per_sec: format!("{:.1}", stage.num_docs_per_sec),
+            num_mb_per_sec: format!("{:.2}", stage.num_mb_per_sec),
+            saturation: format!("{:.0}%", stage.saturation * 100.0),
+        })
+    });
+    println!("{}", make_table("Stages (last 30 seconds)", rows));
+}
+
+pub async fn analyze_index_cli(args: AnalyzeIndexArgs) -> anyhow::Result<()> {
+    debug!(args = ?args, "analyze");
+    let quickwit_config = load_quickwit_config(args.config_uri, args.data_dir).await?;
+    let metastore = quickwit_metastore_uri_resolver()
+        .resolve(&quickwit_config.metastore_uri)
+        .await?;
+    let report = analyze_index_compaction(metastore.as_ref(), &args.index_id).await?;
+    print_compaction_report(&report);
+
+    if report.recommendations.is_empty() {
+        println!("The merge policy and demux settings of the index need no change.");
+        return Ok(());
+    }
+    if !args.apply {
+        println!("Run the command with `--apply` to apply the recommendations.");
+        return Ok(());
+    }
+    let num_applied_recommendations =
+        apply_compaction_recommendations(metastore.as_ref(), &report).await?;
+    println!(
+        "Applied {} recommendation(s) to index `{}`. Restart the indexers of the index for the \
+         new settings to take effect.",
+        num_applied_recommendations, report.index_id
+    );
+    Ok(())
+}
+
+#[derive(Tabled)]
+struct TagFieldRow {
+    #[header("Tag Field")]
+    field_name: String,
+    #[header("Values")]
+    num_values: String,
+    #[header("Values per Split")]
+    avg_values_per_split: String,
+}
+
+#[derive(Tabled)]
+struct RecommendationRow {
+    #[header("Setting")]
+    setting: String,
+    #[header("Current")]
+    current_value: String,
+    #[header("Recommended")]
+    recommended_value: String,
+    #[header("Reason")]
+    reason: String,
+}
+
+fn print_compaction_report(report: &CompactionReport) {
+    println!();
+    println!("1. Splits");
+    println!("===============================================================================");
+    println!(
+        "{:<35} {}",
+        "Number of published splits:".color(GREEN_COLOR),
+        report.num_splits
+    );
+    println!(
+        "{:<35} {}",
+        "Number of mature splits:".color(GREEN_COLOR),
+        report.num_mature_splits
+    );
+    println!(
+        "{:<35} {}",
+        "Number of published documents:".color(GREEN_COLOR),
+        report.num_docs
+    );
+    println!(
+        "{:<35} {}",
+        "Documents per split (min/q1/q2/q3/max):".color(GREEN_COLOR),
+        report
+            .split_num_docs_quartiles
+            .iter()
+            .map(|num_docs| num_docs.to_string())
+            .join(" / ")
+    );
+    if let Some(time_overlap) = report.time_overlap {
+        println!(
+            "{:<35} {:.2}",
+            "Mature splits per timestamp:".color(GREEN_COLOR),
+            time_overlap
+        );
+    }
+    if !report.tag_fields.is_empty() {
+        println!();
+        let rows = report.tag_fields.iter().map(|tag_field| TagFieldRow {
+            field_name: tag_field.field_name.clone(),
+            num_values: if tag_field.num_splits_without_values > 0 {
+                format!("{}+", tag_field.num_values)
+            } else {
+                tag_field.num_values.to_string()
+            },
+            avg_values_per_split: format!("{:.1}", tag_field.avg_values_per_split),
+        });
+        println!("{}", make_table("2. Tag Fields", rows));
+    }
+    if !report.recommendations.is_empty() {
+        println!();
+        let rows = report
+            .recommendations
+            .iter()
+            .map(|recommendation| RecommendationRow {
+                setting: recommendation.setting.to_string(),
+                current_value: recommendation.current_value.clone(),
+                recommended_value: recommendation.recommended_value.clone(),
+                reason: recommendation.reason.clone(),
+            });
+        println!("{}", make_table("3. Recommendations", rows));
+    }
+    println!();
+}
+
 pub async fn show_demux_stats(demux_field_name: &str, splits: &[Split]) {
     println!();
-    println!("3. Demux stats");
+    println!("4. Demux stats");
     println!("===============================================================================");
     let demux_uniq_values: HashSet<String> = splits
         .iter()
@@ -573,6 +886,7 @@ pub async fn create_index_cli(args: CreateIndexArgs) -> anyhow::Result<()> {
     let quickwit_config = load_quickwit_config(args.config_uri, args.data_dir).await?;
     let file_content = load_file(&args.index_config_uri).await?;
     let index_config = IndexConfig::load(&args.index_config_uri, file_content.as_slice()).await?;
+    print_index_config_warnings(&index_config.lint()?);
 
     let index_uri = if let Some(index_uri) = index_config.index_uri.as_ref() {
         index_uri.to_string()
@@ -612,6 +926,7 @@ pub async fn create_index_cli(args: CreateIndexArgs) -> anyhow::Result<()> {
         search_settings: index_config.search_settings,
         create_timestamp: Utc::now().timestamp(),
         update_timestamp: Utc::now().timestamp(),
+        lifecycle_state: Default::default(),
     };
     create_index(&quickwit_config.metastore_uri, index_metadata.clone()).await?;
     println!("Index `{}` successfully created.", index_config.index_id);
@@ -633,8 +948,11 @@ pub async fn ingest_docs_cli(args: IngestDocsArgs) -> anyhow::Result<()> {
     let source = SourceConfig {
         source_id: INGEST_SOURCE_ID.to_string(),
         source_params,
+        filter: None,
+        routing: None,
+        transform: None,
     };
-    run_index_checklist(&config.metastore_uri, &args.index_id, Some(&source)).await?;
+    run_index_checklist(&config.metastore_uri, &args.index_id, Some(&source), false).await?;
     let metastore_uri_resolver = quickwit_metastore_uri_resolver();
     let metastore = metastore_uri_resolver
         .resolve(&config.metastore_uri)
@@ -681,31 +999,160 @@ pub async fn ingest_docs_cli(args: IngestDocsArgs) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub async fn search_index(args: SearchIndexArgs) -> anyhow::Result<SearchResponse> {
-    debug!(args = ?args, "search-index");
-    let quickwit_config = load_quickwit_config(args.config_uri, args.data_dir).await?;
-    let storage_uri_resolver = quickwit_storage_uri_resolver();
+pub async fn ingest_dataset_cli(args: IngestDatasetArgs) -> anyhow::Result<()> {
+    debug!(args = ?args, "ingest-dataset");
+    quickwit_telemetry::send_telemetry_event(TelemetryEvent::Ingest).await;
+
+    let config = load_quickwit_config(args.config_uri, args.data_dir).await?;
     let metastore_uri_resolver = quickwit_metastore_uri_resolver();
     let metastore = metastore_uri_resolver
-        .resolve(&quickwit_config.metastore_uri)
+        .resolve(&config.metastore_uri)
         .await?;
-    let search_request = SearchRequest {
-        index_id: args.index_id,
+    let input_uri = args.input_uri.to_string();
+
+    // The index is created from the schema of the dataset if it does not exist yet.
+    match metastore.index_metadata(&args.index_id).await {
+        Ok(_) => {}
+        Err(MetastoreError::IndexDoesNotExist { .. }) => {
+            if args.format != DatasetFormat::Parquet {
+                bail!(
+                    "Index `{}` does not exist. Create it before ingesting a `{}` dataset.",
+                    args.index_id,
+                    args.format.extension()
+                );
+            }
+            let doc_mapping = doc_mapping_from_parquet_dataset(&input_uri).await?;
+            let default_search_fields = serde_json::to_value(&doc_mapping.field_mappings)?
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|field_mapping| field_mapping["type"] == "text")
+                .filter_map(|field_mapping| field_mapping["name"].as_str())
+                .map(|field_name| field_name.to_string())
+                .collect();
+            let index_uri = format!("{}/{}", config.default_index_root_uri, args.index_id);
+            let index_metadata = IndexMetadata {
+                index_id: args.index_id.clone(),
+                index_uri,
+                checkpoint: Default::default(),
+                sources: Default::default(),
+                doc_mapping,
+                indexing_settings: Default::default(),
+                search_settings: SearchSettings {
+                    default_search_fields,
+                    preferred_searcher_nodes: Vec::new(),
+                },
+                create_timestamp: Utc::now().timestamp(),
+                update_timestamp: Utc::now().timestamp(),
+                lifecycle_state: Default::default(),
+            };
+            metastore.create_index(index_metadata).await?;
+            println!(
+                "Index `{}` created from the schema of the dataset.",
+                args.index_id
+            );
+        }
+        Err(error) => return Err(error.into()),
+    }
+    let source = SourceConfig {
+        source_id: INGEST_SOURCE_ID.to_string(),
+        source_params: SourceParams::Dataset(DatasetSourceParams {
+            uri: input_uri,
+            format: args.format,
+        }),
+        filter: None,
+        routing: None,
+        transform: None,
+    };
+    run_index_checklist(&config.metastore_uri, &args.index_id, Some(&source), false).await?;
+    let index_metadata = metastore.index_metadata(&args.index_id).await?;
+    let storage_resolver = quickwit_storage_uri_resolver().clone();
+    let storage = storage_resolver.resolve(&index_metadata.index_uri)?;
+
+    if args.overwrite {
+        reset_index(&index_metadata, metastore.clone(), storage.clone()).await?;
+    }
+    let client = IndexingServer::spawn(
+        config.data_dir_path,
+        IndexerConfig::default(),
+        metastore,
+        storage_resolver,
+    );
+    let pipeline_id = client.spawn_pipeline(args.index_id.clone(), source).await?;
+    let pipeline_handle = client.detach_pipeline(&pipeline_id).await?;
+    let statistics = start_statistics_reporting_loop(pipeline_handle, false).await?;
+    if statistics.num_published_splits > 0 {
+        println!(
+            "Now, you can query the index with the following command:\nquickwit index search \
+             --index {} --config ./config/quickwit.yaml --query \"my query\"",
+            args.index_id
+        );
+    }
+    Ok(())
+}
+
+fn search_request_from_args(args: &SearchIndexArgs) -> SearchRequest {
+    SearchRequest {
+        index_id: args.index_id.clone(),
         query: args.query.clone(),
-        search_fields: args.search_fields.unwrap_or_default(),
+        search_fields: args.search_fields.clone().unwrap_or_default(),
         start_timestamp: args.start_timestamp,
         end_timestamp: args.end_timestamp,
         max_hits: args.max_hits as u64,
         start_offset: args.start_offset as u64,
         sort_order: None,
         sort_by_field: None,
-    };
+        terms_lookup: None,
+        preferred_searcher_nodes: Vec::new(),
+        profile: false,
+        pin_splits: false,
+        snapshot: None,
+        start_time_expression: None,
+        end_time_expression: None,
+    }
+}
+
+pub async fn search_index(args: SearchIndexArgs) -> anyhow::Result<SearchResponse> {
+    debug!(args = ?args, "search-index");
+    let search_request = search_request_from_args(&args);
+    let quickwit_config = load_quickwit_config(args.config_uri, args.data_dir).await?;
+    let storage_uri_resolver = quickwit_storage_uri_resolver();
+    let metastore_uri_resolver = quickwit_metastore_uri_resolver();
+    let metastore = metastore_uri_resolver
+        .resolve(&quickwit_config.metastore_uri)
+        .await?;
     let search_response: SearchResponse =
         single_node_search(&search_request, &*metastore, storage_uri_resolver.clone()).await?;
     Ok(search_response)
 }
 
+/// Prints the documents matching the query as new splits are published, one JSON
+/// document per line, until interrupted.
+pub async fn tail_index_cli(args: SearchIndexArgs) -> anyhow::Result<()> {
+    debug!(args = ?args, "tail-index");
+    let search_request = search_request_from_args(&args);
+    let quickwit_config = load_quickwit_config(args.config_uri, args.data_dir).await?;
+    let storage_uri_resolver = quickwit_storage_uri_resolver();
+    let metastore_uri_resolver = quickwit_metastore_uri_resolver();
+    let metastore = metastore_uri_resolver
+        .resolve(&quickwit_config.metastore_uri)
+        .await?;
+    let mut hit_stream = single_node_search_tail(
+        search_request,
+        metastore,
+        storage_uri_resolver.clone(),
+        TAIL_POLL_INTERVAL,
+    )?;
+    while let Some(hit_result) = hit_stream.next().await {
+        println!("{}", hit_result?.json);
+    }
+    Ok(())
+}
+
 pub async fn search_index_cli(args: SearchIndexArgs) -> anyhow::Result<()> {
+    if args.tail {
+        return tail_index_cli(args).await;
+    }
     let search_response: SearchResponse = search_index(args).await?;
     let search_response_rest = SearchResponseRest::try_from(search_response)?;
     let search_response_json = serde_json::to_string_pretty(&search_response_rest)?;
@@ -713,39 +1160,93 @@ pub async fn search_index_cli(args: SearchIndexArgs) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub async fn merge_or_demux_cli(
-    args: MergeOrDemuxArgs,
-    merge_enabled: bool,
-    demux_enabled: bool,
-) -> anyhow::Result<()> {
-    debug!(args = ?args, merge_enabled = merge_enabled, demux_enabled = demux_enabled, "run-merge-operations");
+pub async fn merge_index_cli(args: MergeIndexArgs) -> anyhow::Result<()> {
+    debug!(args = ?args, "merge-index");
     let config = load_quickwit_config(args.config_uri, args.data_dir).await?;
-    run_index_checklist(&config.metastore_uri, &args.index_id, None).await?;
-    let indexer_config = IndexerConfig {
-        ..Default::default()
-    };
+    run_index_checklist(&config.metastore_uri, &args.index_id, None, false).await?;
+    if let (Some(start_timestamp), Some(end_timestamp)) = (args.start_timestamp, args.end_timestamp)
+    {
+        if start_timestamp >= end_timestamp {
+            bail!(
+                "'start-timestamp' `{}` must be lower than 'end-timestamp' `{}`.",
+                start_timestamp,
+                end_timestamp
+            );
+        }
+    }
     let metastore_uri_resolver = quickwit_metastore_uri_resolver();
     let metastore = metastore_uri_resolver
         .resolve(&config.metastore_uri)
         .await?;
-    let storage_resolver = quickwit_storage_uri_resolver().clone();
-    let client = IndexingServer::spawn(
+    // Split time ranges are expressed in the resolution of the index timestamp field.
+    let index_metadata = metastore.index_metadata(&args.index_id).await?;
+    let resolution = timestamp_resolution(
+        &index_metadata.doc_mapping,
+        &index_metadata.indexing_settings,
+    );
+    let compaction_time_range = Range {
+        start: args.start_timestamp.map_or(i64::MIN, |start_timestamp| {
+            resolution.secs_to_units(start_timestamp)
+        }),
+        end: args.end_timestamp.map_or(i64::MAX, |end_timestamp| {
+            resolution.secs_to_units(end_timestamp)
+        }),
+    };
+    let num_splits_before = metastore
+        .list_splits(
+            &args.index_id,
+            SplitState::Published,
+            Some(compaction_time_range.clone()),
+            None,
+        )
+        .await?
+        .len();
+    merge_index(
+        args.index_id.clone(),
         config.data_dir_path,
-        indexer_config,
-        metastore,
-        storage_resolver,
+        metastore.clone(),
+        quickwit_storage_uri_resolver().clone(),
+        true,
+        false,
+        Some(compaction_time_range.clone()),
+    )
+    .await?;
+    let num_splits_after = metastore
+        .list_splits(
+            &args.index_id,
+            SplitState::Published,
+            Some(compaction_time_range),
+            None,
+        )
+        .await?
+        .len();
+    println!(
+        "Index `{}` successfully merged: {} split(s) before, {} split(s) after.",
+        args.index_id, num_splits_before, num_splits_after
     );
-    let pipeline_id = client
-        .spawn_merge_pipeline(args.index_id.clone(), merge_enabled, demux_enabled)
-        .await?;
-    let pipeline_handle = client.detach_pipeline(&pipeline_id).await?;
-    let (pipeline_exit_status, _pipeline_statistics) = pipeline_handle.join().await;
-    if !pipeline_exit_status.is_success() {
-        bail!(pipeline_exit_status);
-    }
     Ok(())
 }
 
+pub async fn demux_index_cli(args: DemuxIndexArgs) -> anyhow::Result<()> {
+    debug!(args = ?args, "demux-index");
+    let config = load_quickwit_config(args.config_uri, args.data_dir).await?;
+    run_index_checklist(&config.metastore_uri, &args.index_id, None, false).await?;
+    let metastore_uri_resolver = quickwit_metastore_uri_resolver();
+    let metastore = metastore_uri_resolver
+        .resolve(&config.metastore_uri)
+        .await?;
+    merge_index(
+        args.index_id.clone(),
+        config.data_dir_path,
+        metastore,
+        quickwit_storage_uri_resolver().clone(),
+        false,
+        true,
+        None,
+    )
+    .await
+}
+
 pub async fn delete_index_cli(args: DeleteIndexArgs) -> anyhow::Result<()> {
     debug!(args = ?args, "delete-index");
     quickwit_telemetry::send_telemetry_event(TelemetryEvent::Delete).await;
@@ -22,18 +22,27 @@ use std::time::Duration;
 
 use anyhow::bail;
 use once_cell::sync::Lazy;
+use quickwit_common::log_filter::{reset_log_filter_directives, set_log_filter_directives};
 use quickwit_common::run_checklist;
 use quickwit_common::uri::Uri;
-use quickwit_config::{QuickwitConfig, SourceConfig};
-use quickwit_indexing::check_source_connectivity;
+use quickwit_config::{
+    build_doc_mapper, set_storage_config_instance, QuickwitConfig, SourceConfig,
+};
+use quickwit_doc_mapper::DocMapper;
+use quickwit_indexing::{check_source_connectivity, check_source_decoding};
 use quickwit_metastore::quickwit_metastore_uri_resolver;
+use quickwit_serve::ConfigReloader;
 use quickwit_storage::{load_file, quickwit_storage_uri_resolver};
 use regex::Regex;
 use tabled::{Alignment, Header, Modify, Row, Style, Table, Tabled};
-use tracing::info;
+use tokio::sync::watch;
+use tracing::{error, info};
 
+pub mod bench;
 pub mod cli;
+pub mod config;
 pub mod index;
+pub mod run;
 pub mod service;
 pub mod source;
 pub mod split;
@@ -45,6 +54,9 @@ const THROUGHPUT_WINDOW_SIZE: usize = 5;
 /// This environment variable can be set to send telemetry events to a jaeger instance.
 pub const QW_JAEGER_ENABLED_ENV_KEY: &str = "QW_JAEGER_ENABLED";
 
+/// This environment variable sets the format of the logs: `text` (default) or `json`.
+pub const QW_LOG_FORMAT_ENV_KEY: &str = "QW_LOG_FORMAT";
+
 /// This environment variable can be set to send data to tokio console.
 pub const QW_TOKIO_CONSOLE_ENABLED_ENV_KEY: &str = "QW_TOKIO_CONSOLE_ENABLED";
 
@@ -69,7 +81,7 @@ pub fn parse_duration_with_unit(duration_with_unit_str: &str) -> anyhow::Result<
     };
 }
 
-async fn load_quickwit_config(
+async fn read_quickwit_config(
     uri: Uri,
     data_dir: Option<PathBuf>,
 ) -> anyhow::Result<QuickwitConfig> {
@@ -79,13 +91,65 @@ async fn load_quickwit_config(
     Ok(config)
 }
 
+/// Loads the config and sets the storage config used by the storages created afterwards. The
+/// storage config cannot be reloaded.
+async fn load_quickwit_config(
+    uri: Uri,
+    data_dir: Option<PathBuf>,
+) -> anyhow::Result<QuickwitConfig> {
+    let config = read_quickwit_config(uri, data_dir).await?;
+    set_storage_config_instance(config.storage_config.clone().unwrap_or_default());
+    Ok(config)
+}
+
+/// Creates a reloader loading the config again from `uri`.
+fn config_reloader_for_uri(
+    config: QuickwitConfig,
+    uri: Uri,
+    data_dir: Option<PathBuf>,
+) -> ConfigReloader {
+    ConfigReloader::with_loader(config, move || {
+        read_quickwit_config(uri.clone(), data_dir.clone())
+    })
+}
+
+/// Sets the log level of the Quickwit crates, or restores the log filter set at startup if
+/// no level is given.
+fn set_log_level(log_level_opt: Option<&str>) -> anyhow::Result<()> {
+    match log_level_opt {
+        Some(log_level) => set_log_filter_directives(&format!("quickwit={}", log_level))?,
+        None => reset_log_filter_directives()?,
+    };
+    Ok(())
+}
+
+/// Applies the log level of the config and follows its changes across config reloads.
+async fn follow_log_level(mut config_rx: watch::Receiver<QuickwitConfig>) {
+    let mut log_level_opt: Option<String> = None;
+    loop {
+        let new_log_level_opt = config_rx.borrow().log_level.clone();
+        if new_log_level_opt != log_level_opt {
+            info!(log_level = ?new_log_level_opt, "Setting log level.");
+            if let Err(error) = set_log_level(new_log_level_opt.as_deref()) {
+                error!(error = ?error, "Failed to set log level.");
+            }
+            log_level_opt = new_log_level_opt;
+        }
+        if config_rx.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
 /// Runs connectivity checks for a given `metastore_uri` and `index_id`.
 /// Optionaly, it takes a `SourceConfig` that will be checked instead
-/// of the index's sources.
+/// of the index's sources. Deep checks also read the first documents of
+/// the sources and parse them with the doc mapper of the index.
 pub async fn run_index_checklist(
     metastore_uri: &str,
     index_id: &str,
     source_to_check: Option<&SourceConfig>,
+    deep: bool,
 ) -> anyhow::Result<()> {
     let mut checks: Vec<(&str, anyhow::Result<()>)> = Vec::new();
     let metastore_uri_resolver = quickwit_metastore_uri_resolver();
@@ -97,16 +161,25 @@ pub async fn run_index_checklist(
     let storage = storage_uri_resolver.resolve(&index_metadata.index_uri)?;
     checks.push(("storage", storage.check().await));
 
+    let doc_mapper_opt = if deep {
+        Some(build_doc_mapper(
+            &index_metadata.doc_mapping,
+            &index_metadata.search_settings,
+            &index_metadata.indexing_settings,
+        )?)
+    } else {
+        None
+    };
     if let Some(source_config) = source_to_check {
         checks.push((
             source_config.source_id.as_str(),
-            check_source_connectivity(source_config).await,
+            check_source(source_config, doc_mapper_opt.as_deref()).await,
         ));
     } else {
         for source_config in index_metadata.sources.values() {
             checks.push((
                 source_config.source_id.as_str(),
-                check_source_connectivity(source_config).await,
+                check_source(source_config, doc_mapper_opt.as_deref()).await,
             ));
         }
     }
@@ -116,6 +189,18 @@ pub async fn run_index_checklist(
     Ok(())
 }
 
+/// Checks the connectivity of a source, then its decoding if a doc mapper is passed.
+async fn check_source(
+    source_config: &SourceConfig,
+    doc_mapper_opt: Option<&dyn DocMapper>,
+) -> anyhow::Result<()> {
+    check_source_connectivity(source_config).await?;
+    if let Some(doc_mapper) = doc_mapper_opt {
+        check_source_decoding(source_config, doc_mapper).await?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
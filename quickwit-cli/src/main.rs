@@ -19,17 +19,18 @@
 
 use std::env;
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use clap::{load_yaml, App, AppSettings};
 use opentelemetry::global;
 use opentelemetry::sdk::propagation::TraceContextPropagator;
 use quickwit_cli::cli::CliCommand;
-use quickwit_cli::QW_JAEGER_ENABLED_ENV_KEY;
+use quickwit_cli::{QW_JAEGER_ENABLED_ENV_KEY, QW_LOG_FORMAT_ENV_KEY};
+use quickwit_common::log_filter::set_log_filter_reloader;
 use quickwit_telemetry::payload::TelemetryEvent;
 use tracing::{info, Level};
 use tracing_subscriber::fmt::time::UtcTime;
 use tracing_subscriber::prelude::*;
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::{reload, EnvFilter};
 
 fn setup_logging_and_tracing(level: Level) -> anyhow::Result<()> {
     #[cfg(feature = "tokio-console")]
@@ -40,24 +41,52 @@ fn setup_logging_and_tracing(level: Level) -> anyhow::Result<()> {
             return Ok(());
         }
     }
-    let env_filter = env::var("RUST_LOG")
-        .map(|_| EnvFilter::from_default_env())
-        .or_else(|_| EnvFilter::try_new(format!("quickwit={}", level)))
-        .context("Failed to set up tracing env filter.")?;
+    let default_directives = env::var("RUST_LOG").unwrap_or_else(|_| format!("quickwit={}", level));
+    let env_filter =
+        EnvFilter::try_new(&default_directives).context("Failed to set up tracing env filter.")?;
+    // The filter is reloadable so that the log levels can change while the process is running.
+    let (env_filter, reload_handle) = reload::Layer::new(env_filter);
+    set_log_filter_reloader(
+        move |directives: &str| {
+            let env_filter = EnvFilter::try_new(directives)?;
+            reload_handle.reload(env_filter)?;
+            Ok(())
+        },
+        default_directives,
+    );
     global::set_text_map_propagator(TraceContextPropagator::new());
-    let registry = tracing_subscriber::registry().with(env_filter);
-    let event_format = tracing_subscriber::fmt::format()
-        .with_target(true)
-        .with_timer(
-            // We do not rely on the Rfc3339 implementation, because it has a nanosecond precision.
-            // See discussion here: https://github.com/time-rs/time/discussions/418
-            UtcTime::new(
-                time::format_description::parse(
-                    "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3]Z",
-                )
-                .expect("Time format invalid"),
-            ),
-        );
+    // We do not rely on the Rfc3339 implementation, because it has a nanosecond precision.
+    // See discussion here: https://github.com/time-rs/time/discussions/418
+    let timer = UtcTime::new(
+        time::format_description::parse(
+            "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3]Z",
+        )
+        .expect("Time format invalid"),
+    );
+    let (text_layer_opt, json_layer_opt) = match env::var(QW_LOG_FORMAT_ENV_KEY).as_deref() {
+        Err(_) | Ok("text") => {
+            let event_format = tracing_subscriber::fmt::format()
+                .with_target(true)
+                .with_timer(timer);
+            let text_layer = tracing_subscriber::fmt::layer().event_format(event_format);
+            (Some(text_layer), None)
+        }
+        Ok("json") => {
+            let json_layer = tracing_subscriber::fmt::layer()
+                .json()
+                .with_target(true)
+                .with_timer(timer);
+            (None, Some(json_layer))
+        }
+        Ok(log_format) => bail!(
+            "Log format `{}` is not supported. Available formats are: `text` and `json`.",
+            log_format
+        ),
+    };
+    let registry = tracing_subscriber::registry()
+        .with(env_filter)
+        .with(text_layer_opt)
+        .with(json_layer_opt);
     if std::env::var_os(QW_JAEGER_ENABLED_ENV_KEY).is_some() {
         // TODO: use install_batch once this issue is fixed: https://github.com/open-telemetry/opentelemetry-rust/issues/545
         let tracer = opentelemetry_jaeger::new_pipeline()
@@ -66,15 +95,11 @@ fn setup_logging_and_tracing(level: Level) -> anyhow::Result<()> {
             .install_simple()
             .context("Failed to initialize Jaeger exporter.")?;
         registry
-            .with(tracing_subscriber::fmt::layer().event_format(event_format))
             .with(tracing_opentelemetry::layer().with_tracer(tracer))
             .try_init()
             .context("Failed to set up tracing.")?
     } else {
-        registry
-            .with(tracing_subscriber::fmt::layer().event_format(event_format))
-            .try_init()
-            .context("Failed to set up tracing.")?
+        registry.try_init().context("Failed to set up tracing.")?
     }
     Ok(())
 }
@@ -149,11 +174,13 @@ mod tests {
     use clap::{load_yaml, App, AppSettings};
     use quickwit_cli::cli::CliCommand;
     use quickwit_cli::index::{
-        CreateIndexArgs, DeleteIndexArgs, DescribeIndexArgs, GarbageCollectIndexArgs,
-        IndexCliCommand, IngestDocsArgs, MergeOrDemuxArgs, SearchIndexArgs,
+        AnalyzeIndexArgs, CreateIndexArgs, DeleteIndexArgs, DemuxIndexArgs, DescribeIndexArgs,
+        GarbageCollectIndexArgs, IndexCliCommand, IngestDatasetArgs, IngestDocsArgs,
+        MergeIndexArgs, SearchIndexArgs,
     };
     use quickwit_cli::split::{DescribeSplitArgs, ExtractSplitArgs, SplitCliCommand};
     use quickwit_common::uri::Uri;
+    use quickwit_config::DatasetFormat;
 
     #[test]
     fn test_parse_create_args() -> anyhow::Result<()> {
@@ -262,6 +289,58 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_ingest_dataset_args() -> anyhow::Result<()> {
+        let yaml = load_yaml!("cli.yaml");
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app.try_get_matches_from(vec![
+            "index",
+            "ingest-dataset",
+            "--index",
+            "trips",
+            "--input-uri",
+            "s3://my-bucket/trips",
+            "--config",
+            "/config.yaml",
+        ])?;
+        let command = CliCommand::parse_cli_args(&matches)?;
+        let expected_cmd = CliCommand::Index(IndexCliCommand::IngestDataset(IngestDatasetArgs {
+            index_id: "trips".to_string(),
+            input_uri: Uri::try_new("s3://my-bucket/trips").unwrap(),
+            format: DatasetFormat::Parquet,
+            config_uri: Uri::try_new("file:///config.yaml").unwrap(),
+            data_dir: None,
+            overwrite: false,
+        }));
+        assert_eq!(command, expected_cmd);
+
+        let yaml = load_yaml!("cli.yaml");
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app.try_get_matches_from(vec![
+            "index",
+            "ingest-dataset",
+            "--index",
+            "trips",
+            "--input-uri",
+            "s3://my-bucket/trips",
+            "--format",
+            "csv",
+            "--config",
+            "/config.yaml",
+            "--overwrite",
+        ])?;
+        let command = CliCommand::parse_cli_args(&matches)?;
+        assert!(matches!(
+            command,
+            CliCommand::Index(IndexCliCommand::IngestDataset(IngestDatasetArgs {
+                format: DatasetFormat::Csv,
+                overwrite: true,
+                ..
+            }))
+        ));
+        Ok(())
+    }
+
     #[test]
     fn test_parse_search_args() -> anyhow::Result<()> {
         let yaml = load_yaml!("cli.yaml");
@@ -326,6 +405,7 @@ mod tests {
                 search_fields: Some(field_names),
                 start_timestamp: Some(0),
                 end_timestamp: Some(1),
+                tail: false,
                 config_uri: _config_uri,
                 data_dir: None,
             })) if &index_id == "wikipedia"
@@ -446,8 +526,34 @@ mod tests {
         let command = CliCommand::parse_cli_args(&matches)?;
         assert!(matches!(
             command,
-            CliCommand::Index(IndexCliCommand::Merge(MergeOrDemuxArgs {
+            CliCommand::Index(IndexCliCommand::Merge(MergeIndexArgs {
+                index_id,
+                start_timestamp: None,
+                end_timestamp: None,
+                ..
+            })) if &index_id == "wikipedia"
+        ));
+
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app.try_get_matches_from(vec![
+            "index",
+            "merge",
+            "--index",
+            "wikipedia",
+            "--start-timestamp",
+            "1641024000",
+            "--end-timestamp",
+            "1641110400",
+            "--config",
+            "/config.yaml",
+        ])?;
+        let command = CliCommand::parse_cli_args(&matches)?;
+        assert!(matches!(
+            command,
+            CliCommand::Index(IndexCliCommand::Merge(MergeIndexArgs {
                 index_id,
+                start_timestamp: Some(1641024000),
+                end_timestamp: Some(1641110400),
                 ..
             })) if &index_id == "wikipedia"
         ));
@@ -469,7 +575,7 @@ mod tests {
         let command = CliCommand::parse_cli_args(&matches)?;
         assert!(matches!(
             command,
-            CliCommand::Index(IndexCliCommand::Demux(MergeOrDemuxArgs {
+            CliCommand::Index(IndexCliCommand::Demux(DemuxIndexArgs {
                 index_id,
                 ..
             })) if &index_id == "wikipedia"
@@ -494,9 +600,57 @@ mod tests {
             command,
             CliCommand::Index(IndexCliCommand::Describe(DescribeIndexArgs {
                 index_id,
+                endpoint: None,
                 ..
             })) if &index_id == "wikipedia"
         ));
+
+        let yaml = load_yaml!("cli.yaml");
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app.try_get_matches_from(vec![
+            "index",
+            "describe",
+            "--index",
+            "wikipedia",
+            "--config",
+            "quickwit.yaml",
+            "--endpoint",
+            "http://indexer-1:7280",
+        ])?;
+        let command = CliCommand::parse_cli_args(&matches)?;
+        assert!(matches!(
+            command,
+            CliCommand::Index(IndexCliCommand::Describe(DescribeIndexArgs {
+                endpoint: Some(endpoint),
+                ..
+            })) if &endpoint == "http://indexer-1:7280"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_analyze_index_args() -> anyhow::Result<()> {
+        let yaml = load_yaml!("cli.yaml");
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app.try_get_matches_from(vec![
+            "index",
+            "analyze",
+            "--index",
+            "wikipedia",
+            "--config",
+            "/quickwit.yaml",
+            "--apply",
+        ])?;
+        let command = CliCommand::parse_cli_args(&matches)?;
+        assert_eq!(
+            command,
+            CliCommand::Index(IndexCliCommand::Analyze(AnalyzeIndexArgs {
+                config_uri: Uri::try_new("file:///quickwit.yaml")?,
+                data_dir: None,
+                index_id: "wikipedia".to_string(),
+                apply: true,
+            }))
+        );
         Ok(())
     }
 
@@ -0,0 +1,411 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::bail;
+use chrono::Utc;
+use clap::ArgMatches;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use quickwit_common::run_checklist;
+use quickwit_common::uri::Uri;
+use quickwit_config::{IndexConfig, QuickwitConfig, OTEL_TRACES_INDEX_ID};
+use quickwit_core::Janitor;
+use quickwit_indexing::actors::IndexingServer;
+use quickwit_indexing::ingest_api::init_ingest_api;
+use quickwit_metastore::{
+    quickwit_metastore_uri_resolver, IndexMetadata, Metastore, MetastoreError,
+};
+#[cfg(unix)]
+use quickwit_serve::reload_config_on_sighup;
+use quickwit_serve::{
+    join_cluster, save_searcher_caches, serve_quickwit, ConfigReloader, QuickwitService,
+};
+use quickwit_storage::quickwit_storage_uri_resolver;
+use quickwit_telemetry::payload::TelemetryEvent;
+use tracing::{debug, info};
+
+use crate::{config_reloader_for_uri, follow_log_level, load_quickwit_config};
+
+/// Set of services started by `quickwit run`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunMode {
+    /// Runs the indexer, the searcher, the REST API, and a local metastore in one process.
+    Standalone,
+}
+
+impl RunMode {
+    /// Returns the services run in this mode.
+    pub fn services(&self) -> HashSet<QuickwitService> {
+        match self {
+            RunMode::Standalone => QuickwitService::all(),
+        }
+    }
+}
+
+impl FromStr for RunMode {
+    type Err = anyhow::Error;
+
+    fn from_str(mode_str: &str) -> anyhow::Result<Self> {
+        match mode_str {
+            "standalone" => Ok(RunMode::Standalone),
+            _ => bail!(
+                "Run mode `{}` is not implemented. Available modes are: `standalone`.",
+                mode_str
+            ),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RunCliCommand {
+    pub services: HashSet<QuickwitService>,
+    pub config_uri: Option<Uri>,
+    pub data_dir_path: Option<PathBuf>,
+    pub index_ids: Vec<String>,
+}
+
+impl RunCliCommand {
+    pub fn parse_cli_args(matches: &ArgMatches) -> anyhow::Result<Self> {
+        // Explicitly listed services take precedence over the run mode.
+        let services = if let Some(service_strs) = matches.values_of("service") {
+            let mut services = HashSet::new();
+            for service_str in service_strs {
+                services.extend(QuickwitService::parse_services(service_str)?);
+            }
+            services
+        } else {
+            matches.value_of_t::<RunMode>("mode")?.services()
+        };
+        let config_uri = matches.value_of("config").map(Uri::try_new).transpose()?;
+        let data_dir_path = matches.value_of("data-dir").map(PathBuf::from);
+        let index_ids = matches
+            .values_of("indexes")
+            .map(|values| values.map(String::from).collect())
+            .unwrap_or_default();
+        Ok(RunCliCommand {
+            services,
+            config_uri,
+            data_dir_path,
+            index_ids,
+        })
+    }
+
+    pub async fn execute(self) -> anyhow::Result<()> {
+        run_cli(self).await
+    }
+}
+
+/// Loads the Quickwit config, or falls back to the default config when no config file is
+/// passed. In the latter case, the data dir is created if it does not exist and the config
+/// cannot be reloaded.
+async fn load_run_config(
+    config_uri_opt: Option<Uri>,
+    data_dir_path_opt: Option<PathBuf>,
+) -> anyhow::Result<ConfigReloader> {
+    if let Some(config_uri) = config_uri_opt {
+        let config = load_quickwit_config(config_uri.clone(), data_dir_path_opt.clone()).await?;
+        return Ok(config_reloader_for_uri(
+            config,
+            config_uri,
+            data_dir_path_opt,
+        ));
+    }
+    let mut config = QuickwitConfig::default();
+    if let Some(data_dir_path) = data_dir_path_opt {
+        config.data_dir_path = data_dir_path;
+    }
+    tokio::fs::create_dir_all(&config.data_dir_path).await?;
+    config.validate()?;
+    info!(config = ?config, "No config file passed, using the default Quickwit config.");
+    Ok(ConfigReloader::new(config))
+}
+
+/// Returns the name under which the node services are reported, `standalone` when the node
+/// runs all of them.
+fn services_name(services: &HashSet<QuickwitService>) -> String {
+    if *services == QuickwitService::all() {
+        return "standalone".to_string();
+    }
+    let mut services: Vec<QuickwitService> = services.iter().copied().collect();
+    services.sort();
+    services
+        .iter()
+        .map(QuickwitService::as_str)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+async fn run_cli(args: RunCliCommand) -> anyhow::Result<()> {
+    debug!(args = ?args, "run");
+    if args.services.is_empty() {
+        bail!("At least one service must be enabled.");
+    }
+    let telemetry_event = TelemetryEvent::RunService(services_name(&args.services));
+    quickwit_telemetry::send_telemetry_event(telemetry_event).await;
+
+    let config_reloader = Arc::new(load_run_config(args.config_uri, args.data_dir_path).await?);
+    tokio::spawn(follow_log_level(config_reloader.subscribe()));
+    #[cfg(unix)]
+    tokio::spawn(reload_config_on_sighup(config_reloader.clone()));
+    let config = config_reloader.config();
+    let metastore = quickwit_metastore_uri_resolver()
+        .resolve(&config.metastore_uri)
+        .await?;
+    run_checklist(vec![("metastore", metastore.check_connectivity().await)]);
+
+    if args
+        .index_ids
+        .iter()
+        .any(|index_id| index_id == OTEL_TRACES_INDEX_ID)
+    {
+        create_otel_traces_index_if_not_exists(&config, &*metastore).await?;
+    }
+
+    let mut service_futures: Vec<BoxFuture<anyhow::Result<()>>> = Vec::new();
+    if args.services.contains(&QuickwitService::Indexer) {
+        init_ingest_api(&config.data_dir_path)?;
+        IndexingServer::clean_up_scratch_directories(&config.data_dir_path, &args.index_ids).await;
+        let indexing_server_client = IndexingServer::spawn(
+            config.data_dir_path.clone(),
+            config.indexer_config.clone(),
+            metastore.clone(),
+            quickwit_storage_uri_resolver().clone(),
+        );
+        indexing_server_client
+            .follow_config(config_reloader.subscribe())
+            .await?;
+        // The merges are scheduled by the janitor of the cluster.
+        indexing_server_client.delegate_merges_to_janitor().await?;
+        for index_id in &args.index_ids {
+            indexing_server_client
+                .spawn_pipelines(index_id.clone())
+                .await?;
+        }
+        let indexer = async move {
+            let (exit_status, _) = indexing_server_client.join_server().await;
+            Err::<(), _>(anyhow::anyhow!("Indexing server exited: {}.", exit_status))
+        };
+        service_futures.push(indexer.boxed());
+    }
+    // The node joins the cluster whatever its services, advertising them to the other nodes.
+    let cluster = join_cluster(&config, args.services.clone()).await?;
+    if args.services.contains(&QuickwitService::Janitor) {
+        let merge_indexing_server_client = IndexingServer::spawn(
+            config.data_dir_path.clone(),
+            config.indexer_config.clone(),
+            metastore.clone(),
+            quickwit_storage_uri_resolver().clone(),
+        );
+        let janitor = Janitor::new(
+            metastore.clone(),
+            quickwit_storage_uri_resolver().clone(),
+            cluster.clone(),
+            merge_indexing_server_client,
+        );
+        service_futures.push(janitor.run().boxed());
+    }
+    service_futures.push(serve_quickwit(config_reloader.clone(), metastore, cluster).boxed());
+    let services_res = tokio::select! {
+        services_res = futures::future::try_join_all(service_futures) => services_res.map(|_| ()),
+        ctrl_c_res = tokio::signal::ctrl_c() => {
+            info!("Received shutdown signal.");
+            ctrl_c_res.map_err(anyhow::Error::from)
+        }
+    };
+    if args.services.contains(&QuickwitService::LeafSearcher) {
+        save_searcher_caches(&config_reloader.config()).await;
+    }
+    services_res
+}
+
+/// Creates the built-in `otel-traces` index under the default index root URI, unless an index
+/// with this ID already exists.
+async fn create_otel_traces_index_if_not_exists(
+    config: &QuickwitConfig,
+    metastore: &dyn Metastore,
+) -> anyhow::Result<()> {
+    match metastore.index_metadata(OTEL_TRACES_INDEX_ID).await {
+        Ok(_) => return Ok(()),
+        Err(MetastoreError::IndexDoesNotExist { .. }) => {}
+        Err(error) => return Err(error.into()),
+    }
+    let index_config = IndexConfig::otel_traces()?;
+    let index_uri = format!(
+        "{}/{}",
+        config.default_index_root_uri, index_config.index_id
+    );
+    let index_metadata = IndexMetadata {
+        index_id: index_config.index_id.clone(),
+        index_uri,
+        checkpoint: Default::default(),
+        sources: index_config.sources(),
+        doc_mapping: index_config.doc_mapping,
+        indexing_settings: index_config.indexing_settings,
+        search_settings: index_config.search_settings,
+        create_timestamp: Utc::now().timestamp(),
+        update_timestamp: Utc::now().timestamp(),
+        lifecycle_state: Default::default(),
+    };
+    metastore.create_index(index_metadata).await?;
+    info!(
+        index_id = OTEL_TRACES_INDEX_ID,
+        "Created the built-in index."
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{load_yaml, App, AppSettings};
+    use quickwit_metastore::FileBackedMetastore;
+
+    use super::*;
+    use crate::cli::CliCommand;
+
+    #[test]
+    fn test_parse_run_standalone_args() -> anyhow::Result<()> {
+        let yaml = load_yaml!("cli.yaml");
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app.try_get_matches_from(vec!["run"])?;
+        let command = CliCommand::parse_cli_args(&matches)?;
+        assert_eq!(
+            command,
+            CliCommand::Run(RunCliCommand {
+                services: QuickwitService::all(),
+                config_uri: None,
+                data_dir_path: None,
+                index_ids: Vec::new(),
+            })
+        );
+
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app.try_get_matches_from(vec![
+            "run",
+            "--mode",
+            "standalone",
+            "--config",
+            "/config.yaml",
+            "--indexes",
+            "foo",
+            "bar",
+        ])?;
+        let command = CliCommand::parse_cli_args(&matches)?;
+        assert_eq!(
+            command,
+            CliCommand::Run(RunCliCommand {
+                services: QuickwitService::all(),
+                config_uri: Some(Uri::try_new("file:///config.yaml")?),
+                data_dir_path: None,
+                index_ids: vec!["foo".to_string(), "bar".to_string()],
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_run_services_args() -> anyhow::Result<()> {
+        let yaml = load_yaml!("cli.yaml");
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app.try_get_matches_from(vec![
+            "run",
+            "--service",
+            "indexer",
+            "janitor",
+            "--indexes",
+            "foo",
+        ])?;
+        let command = CliCommand::parse_cli_args(&matches)?;
+        assert_eq!(
+            command,
+            CliCommand::Run(RunCliCommand {
+                services: [QuickwitService::Indexer, QuickwitService::Janitor]
+                    .into_iter()
+                    .collect(),
+                config_uri: None,
+                data_dir_path: None,
+                index_ids: vec!["foo".to_string()],
+            })
+        );
+
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches =
+            app.try_get_matches_from(vec!["run", "--service", "searcher", "control-plane"])?;
+        let command = CliCommand::parse_cli_args(&matches)?;
+        assert_eq!(
+            command,
+            CliCommand::Run(RunCliCommand {
+                services: [
+                    QuickwitService::RootSearcher,
+                    QuickwitService::LeafSearcher,
+                    QuickwitService::ControlPlane
+                ]
+                .into_iter()
+                .collect(),
+                config_uri: None,
+                data_dir_path: None,
+                index_ids: Vec::new(),
+            })
+        );
+
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        assert!(app
+            .try_get_matches_from(vec!["run", "--service", "metastore"])
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_run_mode() {
+        assert_eq!(
+            "standalone".parse::<RunMode>().unwrap(),
+            RunMode::Standalone
+        );
+        assert!("cluster".parse::<RunMode>().is_err());
+    }
+
+    #[test]
+    fn test_services_name() {
+        assert_eq!(services_name(&QuickwitService::all()), "standalone");
+        let services = [QuickwitService::LeafSearcher, QuickwitService::Indexer]
+            .into_iter()
+            .collect();
+        assert_eq!(services_name(&services), "indexer,leaf-searcher");
+    }
+
+    #[tokio::test]
+    async fn test_create_otel_traces_index_if_not_exists() -> anyhow::Result<()> {
+        let config = QuickwitConfig::for_test()?;
+        let metastore = FileBackedMetastore::for_test();
+        create_otel_traces_index_if_not_exists(&config, &metastore).await?;
+        let index_metadata = metastore.index_metadata(OTEL_TRACES_INDEX_ID).await?;
+        assert_eq!(
+            index_metadata.index_uri,
+            format!("{}/otel-traces", config.default_index_root_uri)
+        );
+        assert!(index_metadata.sources.contains_key("otlp-traces"));
+        // Creating the index again is a no-op.
+        create_otel_traces_index_if_not_exists(&config, &metastore).await?;
+        Ok(())
+    }
+}
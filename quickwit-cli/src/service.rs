@@ -18,6 +18,7 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use anyhow::bail;
 use clap::ArgMatches;
@@ -25,12 +26,14 @@ use quickwit_common::run_checklist;
 use quickwit_common::uri::Uri;
 use quickwit_indexing::actors::IndexingServer;
 use quickwit_metastore::quickwit_metastore_uri_resolver;
-use quickwit_serve::run_searcher;
+#[cfg(unix)]
+use quickwit_serve::reload_config_on_sighup;
+use quickwit_serve::{join_cluster, save_searcher_caches, serve_quickwit, QuickwitService};
 use quickwit_storage::quickwit_storage_uri_resolver;
 use quickwit_telemetry::payload::TelemetryEvent;
-use tracing::debug;
+use tracing::{debug, info};
 
-use crate::load_quickwit_config;
+use crate::{config_reloader_for_uri, follow_log_level, load_quickwit_config};
 
 #[derive(Debug, PartialEq)]
 pub struct RunIndexerArgs {
@@ -119,17 +122,27 @@ async fn run_indexer_cli(args: RunIndexerArgs) -> anyhow::Result<()> {
     let telemetry_event = TelemetryEvent::RunService("indexer".to_string());
     quickwit_telemetry::send_telemetry_event(telemetry_event).await;
 
-    let config = load_quickwit_config(args.config_uri, args.data_dir_path).await?;
+    let config = load_quickwit_config(args.config_uri.clone(), args.data_dir_path.clone()).await?;
+    let config_reloader = Arc::new(config_reloader_for_uri(
+        config.clone(),
+        args.config_uri,
+        args.data_dir_path,
+    ));
+    tokio::spawn(follow_log_level(config_reloader.subscribe()));
+    #[cfg(unix)]
+    tokio::spawn(reload_config_on_sighup(config_reloader.clone()));
     let metastore = quickwit_metastore_uri_resolver()
         .resolve(&config.metastore_uri)
         .await?;
     let storage_resolver = quickwit_storage_uri_resolver().clone();
+    IndexingServer::clean_up_scratch_directories(&config.data_dir_path, &args.index_ids).await;
     let client = IndexingServer::spawn(
         config.data_dir_path,
         config.indexer_config,
         metastore,
         storage_resolver,
     );
+    client.follow_config(config_reloader.subscribe()).await?;
     for index_id in args.index_ids {
         client.spawn_pipelines(index_id).await?;
     }
@@ -145,14 +158,30 @@ async fn run_searcher_cli(args: RunSearcherArgs) -> anyhow::Result<()> {
     let telemetry_event = TelemetryEvent::RunService("searcher".to_string());
     quickwit_telemetry::send_telemetry_event(telemetry_event).await;
 
-    let config = load_quickwit_config(args.config_uri, args.data_dir_path).await?;
+    let config = load_quickwit_config(args.config_uri.clone(), args.data_dir_path.clone()).await?;
     let metastore_uri_resolver = quickwit_metastore_uri_resolver();
     let metastore = metastore_uri_resolver
         .resolve(&config.metastore_uri)
         .await?;
     run_checklist(vec![("metastore", metastore.check_connectivity().await)]);
-    run_searcher(config, metastore).await?;
-    Ok(())
+    let config_reloader = Arc::new(config_reloader_for_uri(
+        config,
+        args.config_uri,
+        args.data_dir_path,
+    ));
+    tokio::spawn(follow_log_level(config_reloader.subscribe()));
+    #[cfg(unix)]
+    tokio::spawn(reload_config_on_sighup(config_reloader.clone()));
+    let cluster = join_cluster(&config_reloader.config(), QuickwitService::searcher()).await?;
+    let serve_res = tokio::select! {
+        serve_res = serve_quickwit(config_reloader.clone(), metastore, cluster) => serve_res,
+        ctrl_c_res = tokio::signal::ctrl_c() => {
+            info!("Received shutdown signal.");
+            ctrl_c_res.map_err(anyhow::Error::from)
+        }
+    };
+    save_searcher_caches(&config_reloader.config()).await;
+    serve_res
 }
 
 #[cfg(test)]
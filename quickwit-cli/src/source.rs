@@ -17,19 +17,23 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::BTreeMap;
+
 use anyhow::{bail, Context};
 use clap::ArgMatches;
 use itertools::Itertools;
+use quickwit_client::{QuickwitClient, ResetSourceCheckpointRequest};
 use quickwit_common::uri::Uri;
-use quickwit_config::{SourceConfig, SourceParams};
-use quickwit_indexing::check_source_connectivity;
-use quickwit_metastore::checkpoint::SourceCheckpoint;
+use quickwit_config::{build_doc_mapper, render_config_template, SourceConfig, SourceParams};
+use quickwit_indexing::{check_source_connectivity, check_source_decoding};
+use quickwit_metastore::checkpoint::{PartitionId, Position, SourceCheckpoint};
 use quickwit_metastore::{quickwit_metastore_uri_resolver, IndexMetadata};
 use quickwit_storage::load_file;
 use serde_json::{Map, Value};
 use tabled::{Table, Tabled};
 
-use crate::{load_quickwit_config, make_table};
+use crate::index::local_rest_endpoint;
+use crate::{load_quickwit_config, make_table, run_index_checklist};
 
 #[derive(Debug, PartialEq)]
 pub struct AddSourceArgs {
@@ -39,6 +43,17 @@ pub struct AddSourceArgs {
     pub source_type: String,
     /// Can be an inline JSON object or a path to a file holding a JSON object.
     pub params: String,
+    /// Reads the first documents of the source and parses them before adding it.
+    pub deep: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CheckSourceArgs {
+    pub config_uri: Uri,
+    pub index_id: String,
+    pub source_id: String,
+    /// Reads the first documents of the source and parses them with the doc mapper of the index.
+    pub deep: bool,
 }
 
 #[derive(Debug, PartialEq)]
@@ -55,27 +70,89 @@ pub struct DescribeSourceArgs {
     pub source_id: String,
 }
 
+#[derive(Debug, PartialEq)]
+pub struct DescribeSourceCheckpointArgs {
+    pub config_uri: Uri,
+    pub index_id: String,
+    pub source_id: String,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct ListSourcesArgs {
     pub config_uri: Uri,
     pub index_id: String,
 }
 
+#[derive(Debug, PartialEq)]
+pub struct PauseSourceArgs {
+    pub config_uri: Uri,
+    pub index_id: String,
+    pub source_id: String,
+    /// REST endpoint of the node running the indexing pipeline of the source. Defaults to the
+    /// node configured by the config file.
+    pub endpoint: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ResumeSourceArgs {
+    pub config_uri: Uri,
+    pub index_id: String,
+    pub source_id: String,
+    /// REST endpoint of the node running the indexing pipeline of the source. Defaults to the
+    /// node configured by the config file.
+    pub endpoint: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ResetSourceCheckpointArgs {
+    pub config_uri: Uri,
+    pub index_id: String,
+    pub source_id: String,
+    /// Last positions to consider indexed, per partition ID.
+    pub positions: Option<BTreeMap<String, String>>,
+    /// Resumes from the records published after this timestamp, in milliseconds.
+    pub timestamp: Option<i64>,
+    /// REST endpoint of the node running the indexing pipeline of the source. Defaults to the
+    /// node configured by the config file.
+    pub endpoint: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PruneSourceCheckpointArgs {
+    pub config_uri: Uri,
+    pub index_id: String,
+    pub source_id: String,
+    /// IDs of the retired partitions to remove from the checkpoint.
+    pub partition_ids: Vec<String>,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum SourceCliCommand {
     AddSource(AddSourceArgs),
+    CheckSource(CheckSourceArgs),
     DeleteSource(DeleteSourceArgs),
     DescribeSource(DescribeSourceArgs),
+    DescribeSourceCheckpoint(DescribeSourceCheckpointArgs),
     ListSources(ListSourcesArgs),
+    PauseSource(PauseSourceArgs),
+    ResumeSource(ResumeSourceArgs),
+    ResetSourceCheckpoint(ResetSourceCheckpointArgs),
+    PruneSourceCheckpoint(PruneSourceCheckpointArgs),
 }
 
 impl SourceCliCommand {
     pub async fn execute(self) -> anyhow::Result<()> {
         match self {
             Self::AddSource(args) => add_source_cli(args).await,
+            Self::CheckSource(args) => check_source_cli(args).await,
             Self::DeleteSource(args) => delete_source_cli(args).await,
             Self::DescribeSource(args) => describe_source_cli(args).await,
+            Self::DescribeSourceCheckpoint(args) => describe_source_checkpoint_cli(args).await,
             Self::ListSources(args) => list_sources_cli(args).await,
+            Self::PauseSource(args) => pause_source_cli(args).await,
+            Self::ResumeSource(args) => resume_source_cli(args).await,
+            Self::ResetSourceCheckpoint(args) => reset_source_checkpoint_cli(args).await,
+            Self::PruneSourceCheckpoint(args) => prune_source_checkpoint_cli(args).await,
         }
     }
 
@@ -85,9 +162,21 @@ impl SourceCliCommand {
             .ok_or_else(|| anyhow::anyhow!("Failed to parse source subcommand arguments."))?;
         match subcommand {
             "add" => Self::parse_add_args(submatches).map(Self::AddSource),
+            "check" => Self::parse_check_args(submatches).map(Self::CheckSource),
             "delete" => Self::parse_delete_args(submatches).map(Self::DeleteSource),
             "describe" => Self::parse_describe_args(submatches).map(Self::DescribeSource),
+            "describe-checkpoint" => {
+                Self::parse_describe_checkpoint_args(submatches).map(Self::DescribeSourceCheckpoint)
+            }
             "list" => Self::parse_list_args(submatches).map(Self::ListSources),
+            "pause" => Self::parse_pause_args(submatches).map(Self::PauseSource),
+            "resume" => Self::parse_resume_args(submatches).map(Self::ResumeSource),
+            "reset-checkpoint" => {
+                Self::parse_reset_checkpoint_args(submatches).map(Self::ResetSourceCheckpoint)
+            }
+            "prune-checkpoint" => {
+                Self::parse_prune_checkpoint_args(submatches).map(Self::PruneSourceCheckpoint)
+            }
             _ => bail!("Source subcommand `{}` is not implemented.", subcommand),
         }
     }
@@ -113,12 +202,36 @@ impl SourceCliCommand {
             .value_of("params")
             .map(String::from)
             .expect("`params` is a required arg.");
+        let deep = matches.is_present("deep");
         Ok(AddSourceArgs {
             config_uri,
             index_id,
             source_id,
             source_type,
             params,
+            deep,
+        })
+    }
+
+    fn parse_check_args(matches: &ArgMatches) -> anyhow::Result<CheckSourceArgs> {
+        let config_uri = matches
+            .value_of("config")
+            .map(Uri::try_new)
+            .expect("`config` is a required arg.")?;
+        let index_id = matches
+            .value_of("index")
+            .map(String::from)
+            .expect("`index` is a required arg.");
+        let source_id = matches
+            .value_of("source")
+            .map(String::from)
+            .expect("`source` is a required arg.");
+        let deep = matches.is_present("deep");
+        Ok(CheckSourceArgs {
+            config_uri,
+            index_id,
+            source_id,
+            deep,
         })
     }
 
@@ -162,6 +275,28 @@ impl SourceCliCommand {
         })
     }
 
+    fn parse_describe_checkpoint_args(
+        matches: &ArgMatches,
+    ) -> anyhow::Result<DescribeSourceCheckpointArgs> {
+        let config_uri = matches
+            .value_of("config")
+            .map(Uri::try_new)
+            .expect("`config` is a required arg.")?;
+        let index_id = matches
+            .value_of("index")
+            .map(String::from)
+            .expect("`index` is a required arg.");
+        let source_id = matches
+            .value_of("source")
+            .map(String::from)
+            .expect("`source` is a required arg.");
+        Ok(DescribeSourceCheckpointArgs {
+            config_uri,
+            index_id,
+            source_id,
+        })
+    }
+
     fn parse_list_args(matches: &ArgMatches) -> anyhow::Result<ListSourcesArgs> {
         let config_uri = matches
             .value_of("config")
@@ -176,6 +311,131 @@ impl SourceCliCommand {
             index_id,
         })
     }
+
+    fn parse_pause_args(matches: &ArgMatches) -> anyhow::Result<PauseSourceArgs> {
+        let config_uri = matches
+            .value_of("config")
+            .map(Uri::try_new)
+            .expect("`config` is a required arg.")?;
+        let index_id = matches
+            .value_of("index")
+            .map(String::from)
+            .expect("`index` is a required arg.");
+        let source_id = matches
+            .value_of("source")
+            .map(String::from)
+            .expect("`source` is a required arg.");
+        let endpoint = matches.value_of("endpoint").map(String::from);
+        Ok(PauseSourceArgs {
+            config_uri,
+            index_id,
+            source_id,
+            endpoint,
+        })
+    }
+
+    fn parse_resume_args(matches: &ArgMatches) -> anyhow::Result<ResumeSourceArgs> {
+        let config_uri = matches
+            .value_of("config")
+            .map(Uri::try_new)
+            .expect("`config` is a required arg.")?;
+        let index_id = matches
+            .value_of("index")
+            .map(String::from)
+            .expect("`index` is a required arg.");
+        let source_id = matches
+            .value_of("source")
+            .map(String::from)
+            .expect("`source` is a required arg.");
+        let endpoint = matches.value_of("endpoint").map(String::from);
+        Ok(ResumeSourceArgs {
+            config_uri,
+            index_id,
+            source_id,
+            endpoint,
+        })
+    }
+
+    fn parse_reset_checkpoint_args(
+        matches: &ArgMatches,
+    ) -> anyhow::Result<ResetSourceCheckpointArgs> {
+        let config_uri = matches
+            .value_of("config")
+            .map(Uri::try_new)
+            .expect("`config` is a required arg.")?;
+        let index_id = matches
+            .value_of("index")
+            .map(String::from)
+            .expect("`index` is a required arg.");
+        let source_id = matches
+            .value_of("source")
+            .map(String::from)
+            .expect("`source` is a required arg.");
+        let positions = matches
+            .values_of("position")
+            .map(|values| {
+                values
+                    .map(parse_partition_position)
+                    .collect::<anyhow::Result<_>>()
+            })
+            .transpose()?;
+        let timestamp = matches
+            .value_of("timestamp")
+            .map(|timestamp| {
+                timestamp
+                    .parse::<i64>()
+                    .with_context(|| format!("Failed to parse timestamp `{}`.", timestamp))
+            })
+            .transpose()?;
+        let endpoint = matches.value_of("endpoint").map(String::from);
+        Ok(ResetSourceCheckpointArgs {
+            config_uri,
+            index_id,
+            source_id,
+            positions,
+            timestamp,
+            endpoint,
+        })
+    }
+
+    fn parse_prune_checkpoint_args(
+        matches: &ArgMatches,
+    ) -> anyhow::Result<PruneSourceCheckpointArgs> {
+        let config_uri = matches
+            .value_of("config")
+            .map(Uri::try_new)
+            .expect("`config` is a required arg.")?;
+        let index_id = matches
+            .value_of("index")
+            .map(String::from)
+            .expect("`index` is a required arg.");
+        let source_id = matches
+            .value_of("source")
+            .map(String::from)
+            .expect("`source` is a required arg.");
+        let partition_ids = matches
+            .values_of("partition")
+            .expect("`partition` is a required arg.")
+            .map(String::from)
+            .collect();
+        Ok(PruneSourceCheckpointArgs {
+            config_uri,
+            index_id,
+            source_id,
+            partition_ids,
+        })
+    }
+}
+
+/// Parses a position formatted as `PARTITION_ID=POSITION`.
+fn parse_partition_position(partition_position: &str) -> anyhow::Result<(String, String)> {
+    let (partition_id, position) = partition_position.split_once('=').with_context(|| {
+        format!(
+            "Failed to parse position `{}`: expected `PARTITION_ID=POSITION`.",
+            partition_position
+        )
+    })?;
+    Ok((partition_id.to_string(), position.to_string()))
 }
 
 async fn add_source_cli(args: AddSourceArgs) -> anyhow::Result<()> {
@@ -191,9 +451,21 @@ async fn add_source_cli(args: AddSourceArgs) -> anyhow::Result<()> {
     let source = SourceConfig {
         source_id: args.source_id.clone(),
         source_params,
+        filter: None,
+        routing: None,
+        transform: None,
     };
     source.validate()?;
     check_source_connectivity(&source).await?;
+    if args.deep {
+        let index_metadata = metastore.index_metadata(&args.index_id).await?;
+        let doc_mapper = build_doc_mapper(
+            &index_metadata.doc_mapping,
+            &index_metadata.search_settings,
+            &index_metadata.indexing_settings,
+        )?;
+        check_source_decoding(&source, &*doc_mapper).await?;
+    }
 
     metastore.add_source(&args.index_id, source).await?;
     println!(
@@ -203,6 +475,22 @@ async fn add_source_cli(args: AddSourceArgs) -> anyhow::Result<()> {
     Ok(())
 }
 
+async fn check_source_cli(args: CheckSourceArgs) -> anyhow::Result<()> {
+    let quickwit_config = load_quickwit_config(args.config_uri, None).await?;
+    let index_metadata = resolve_index(&quickwit_config.metastore_uri, &args.index_id).await?;
+    let source = index_metadata
+        .sources
+        .get(&args.source_id)
+        .with_context(|| format!("Source `{}` does not exist.", args.source_id))?;
+    run_index_checklist(
+        &quickwit_config.metastore_uri,
+        &args.index_id,
+        Some(source),
+        args.deep,
+    )
+    .await
+}
+
 async fn delete_source_cli(args: DeleteSourceArgs) -> anyhow::Result<()> {
     let config = load_quickwit_config(args.config_uri, None).await?;
     let metastore = quickwit_metastore_uri_resolver()
@@ -260,6 +548,24 @@ where
         .sorted_by(|left, right| left.key.cmp(&right.key));
     let params_table = make_table("Parameters", params_rows);
 
+    let checkpoint_table = make_checkpoint_table(&checkpoint);
+    Ok((source_table, params_table, checkpoint_table))
+}
+
+async fn describe_source_checkpoint_cli(args: DescribeSourceCheckpointArgs) -> anyhow::Result<()> {
+    let config = load_quickwit_config(args.config_uri, None).await?;
+    let metastore = quickwit_metastore_uri_resolver()
+        .resolve(&config.metastore_uri)
+        .await?;
+    let source_checkpoint = metastore
+        .source_checkpoint(&args.index_id, &args.source_id)
+        .await?;
+    let checkpoint_table = make_checkpoint_table(&source_checkpoint);
+    display_tables(&[checkpoint_table]);
+    Ok(())
+}
+
+fn make_checkpoint_table(checkpoint: &SourceCheckpoint) -> Table {
     let checkpoint_rows = checkpoint
         .iter()
         .map(|(partition_id, position)| CheckpointRow {
@@ -267,8 +573,7 @@ where
             offset: position.as_str().to_string(),
         })
         .sorted_by(|left, right| left.partition_id.cmp(&right.partition_id));
-    let checkpoint_table = make_table("Checkpoint", checkpoint_rows);
-    Ok((source_table, params_table, checkpoint_table))
+    make_table("Checkpoint", checkpoint_rows)
 }
 
 async fn list_sources_cli(args: ListSourcesArgs) -> anyhow::Result<()> {
@@ -279,6 +584,93 @@ async fn list_sources_cli(args: ListSourcesArgs) -> anyhow::Result<()> {
     Ok(())
 }
 
+async fn pause_source_cli(args: PauseSourceArgs) -> anyhow::Result<()> {
+    let quickwit_config = load_quickwit_config(args.config_uri, None).await?;
+    let rest_endpoint = match args.endpoint {
+        Some(endpoint) => endpoint,
+        None => local_rest_endpoint(&quickwit_config)?,
+    };
+    QuickwitClient::builder(rest_endpoint.as_str())
+        .build()?
+        .pause_source(&args.index_id, &args.source_id)
+        .await?;
+    println!(
+        "Source `{}` of index `{}` paused on `{}`.",
+        args.source_id, args.index_id, rest_endpoint
+    );
+    Ok(())
+}
+
+async fn resume_source_cli(args: ResumeSourceArgs) -> anyhow::Result<()> {
+    let quickwit_config = load_quickwit_config(args.config_uri, None).await?;
+    let rest_endpoint = match args.endpoint {
+        Some(endpoint) => endpoint,
+        None => local_rest_endpoint(&quickwit_config)?,
+    };
+    QuickwitClient::builder(rest_endpoint.as_str())
+        .build()?
+        .resume_source(&args.index_id, &args.source_id)
+        .await?;
+    println!(
+        "Source `{}` of index `{}` resumed on `{}`.",
+        args.source_id, args.index_id, rest_endpoint
+    );
+    Ok(())
+}
+
+async fn reset_source_checkpoint_cli(args: ResetSourceCheckpointArgs) -> anyhow::Result<()> {
+    let quickwit_config = load_quickwit_config(args.config_uri, None).await?;
+    let rest_endpoint = match args.endpoint {
+        Some(endpoint) => endpoint,
+        None => local_rest_endpoint(&quickwit_config)?,
+    };
+    let reset_request = ResetSourceCheckpointRequest {
+        positions: args.positions,
+        timestamp: args.timestamp,
+    };
+    let source_checkpoint = QuickwitClient::builder(rest_endpoint.as_str())
+        .build()?
+        .reset_source_checkpoint(&args.index_id, &args.source_id, &reset_request)
+        .await?;
+    println!(
+        "Checkpoint of source `{}` of index `{}` reset on `{}`. Resume the source to reprocess it.",
+        args.source_id, args.index_id, rest_endpoint
+    );
+    let checkpoint: SourceCheckpoint = source_checkpoint
+        .checkpoint
+        .into_iter()
+        .map(|(partition_id, position)| (PartitionId::from(partition_id), Position::from(position)))
+        .collect();
+    display_tables(&[make_checkpoint_table(&checkpoint)]);
+    Ok(())
+}
+
+async fn prune_source_checkpoint_cli(args: PruneSourceCheckpointArgs) -> anyhow::Result<()> {
+    let config = load_quickwit_config(args.config_uri, None).await?;
+    let metastore = quickwit_metastore_uri_resolver()
+        .resolve(&config.metastore_uri)
+        .await?;
+    let partition_ids: Vec<PartitionId> = args
+        .partition_ids
+        .into_iter()
+        .map(PartitionId::from)
+        .collect();
+    metastore
+        .prune_source_checkpoint(&args.index_id, &args.source_id, &partition_ids)
+        .await?;
+    println!(
+        "{} partition(s) pruned from the checkpoint of source `{}` of index `{}`.",
+        partition_ids.len(),
+        args.source_id,
+        args.index_id
+    );
+    let source_checkpoint = metastore
+        .source_checkpoint(&args.index_id, &args.source_id)
+        .await?;
+    display_tables(&[make_checkpoint_table(&source_checkpoint)]);
+    Ok(())
+}
+
 fn make_list_sources_table<I>(sources: I) -> Table
 where I: IntoIterator<Item = SourceConfig> {
     let rows = sources
@@ -350,14 +742,16 @@ fn flatten_json(value: Value) -> Vec<(String, Value)> {
 }
 
 /// Tries to read a JSON object from a string, assuming the string is an inline JSON object or a
-/// path to a file holding a JSON object.
+/// path to a file holding a JSON object. The `${VAR}` expressions of the file are replaced by the
+/// values of the corresponding environment variables.
 async fn sniff_params(params: &str) -> anyhow::Result<Map<String, Value>> {
     if let Ok(Value::Object(values)) = serde_json::from_str(params) {
         return Ok(values);
     }
     let params_uri = Uri::try_new(params)?;
     let params_bytes = load_file(&params_uri).await?;
-    if let Ok(Value::Object(values)) = serde_json::from_slice(params_bytes.as_slice()) {
+    let rendered_params = render_config_template(params_bytes.as_slice())?;
+    if let Ok(Value::Object(values)) = serde_json::from_slice(&rendered_params) {
         return Ok(values);
     }
     bail!("Failed to parse JSON object from `{}`.", params)
@@ -375,7 +769,6 @@ mod tests {
     use std::path::Path;
 
     use clap::{load_yaml, App, AppSettings};
-    use quickwit_metastore::checkpoint::{PartitionId, Position};
     use quickwit_storage::{quickwit_storage_uri_resolver, PutPayload};
     use serde_json::json;
 
@@ -454,6 +847,34 @@ mod tests {
             source_id: "hdfs-logs-source".to_string(),
             source_type: "kafka".to_string(),
             params: "{}".to_string(),
+            deep: false,
+        }));
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn test_parse_check_source_args() {
+        let yaml = load_yaml!("cli.yaml");
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app
+            .try_get_matches_from(vec![
+                "source",
+                "check",
+                "--index",
+                "hdfs-logs",
+                "--source",
+                "hdfs-logs-source",
+                "--config",
+                "/conf.yaml",
+                "--deep",
+            ])
+            .unwrap();
+        let command = CliCommand::parse_cli_args(&matches).unwrap();
+        let expected_command = CliCommand::Source(SourceCliCommand::CheckSource(CheckSourceArgs {
+            config_uri: Uri::try_new("file:///conf.yaml").unwrap(),
+            index_id: "hdfs-logs".to_string(),
+            source_id: "hdfs-logs-source".to_string(),
+            deep: true,
         }));
         assert_eq!(command, expected_command);
     }
@@ -526,6 +947,9 @@ mod tests {
         let sources = vec![SourceConfig {
             source_id: "foo-source".to_string(),
             source_params: SourceParams::file("path/to/file"),
+            filter: None,
+            routing: None,
+            transform: None,
         }];
         let expected_source = vec![SourceRow {
             source_id: "foo-source".to_string(),
@@ -583,16 +1007,212 @@ mod tests {
         assert_eq!(command, expected_command);
     }
 
+    #[test]
+    fn test_parse_pause_and_resume_source_args() {
+        let yaml = load_yaml!("cli.yaml");
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app
+            .try_get_matches_from(vec![
+                "source",
+                "pause",
+                "--index",
+                "hdfs-logs",
+                "--source",
+                "hdfs-logs-source",
+                "--config",
+                "/conf.yaml",
+            ])
+            .unwrap();
+        let command = CliCommand::parse_cli_args(&matches).unwrap();
+        let expected_command = CliCommand::Source(SourceCliCommand::PauseSource(PauseSourceArgs {
+            config_uri: Uri::try_new("file:///conf.yaml").unwrap(),
+            index_id: "hdfs-logs".to_string(),
+            source_id: "hdfs-logs-source".to_string(),
+            endpoint: None,
+        }));
+        assert_eq!(command, expected_command);
+
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app
+            .try_get_matches_from(vec![
+                "source",
+                "resume",
+                "--index",
+                "hdfs-logs",
+                "--source",
+                "hdfs-logs-source",
+                "--config",
+                "/conf.yaml",
+                "--endpoint",
+                "http://indexer-1:7280",
+            ])
+            .unwrap();
+        let command = CliCommand::parse_cli_args(&matches).unwrap();
+        let expected_command =
+            CliCommand::Source(SourceCliCommand::ResumeSource(ResumeSourceArgs {
+                config_uri: Uri::try_new("file:///conf.yaml").unwrap(),
+                index_id: "hdfs-logs".to_string(),
+                source_id: "hdfs-logs-source".to_string(),
+                endpoint: Some("http://indexer-1:7280".to_string()),
+            }));
+        assert_eq!(command, expected_command);
+    }
+
+    #[test]
+    fn test_parse_checkpoint_source_args() {
+        let yaml = load_yaml!("cli.yaml");
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app
+            .try_get_matches_from(vec![
+                "source",
+                "describe-checkpoint",
+                "--index",
+                "hdfs-logs",
+                "--source",
+                "hdfs-logs-source",
+                "--config",
+                "/conf.yaml",
+            ])
+            .unwrap();
+        let command = CliCommand::parse_cli_args(&matches).unwrap();
+        let expected_command = CliCommand::Source(SourceCliCommand::DescribeSourceCheckpoint(
+            DescribeSourceCheckpointArgs {
+                config_uri: Uri::try_new("file:///conf.yaml").unwrap(),
+                index_id: "hdfs-logs".to_string(),
+                source_id: "hdfs-logs-source".to_string(),
+            },
+        ));
+        assert_eq!(command, expected_command);
+
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app
+            .try_get_matches_from(vec![
+                "source",
+                "reset-checkpoint",
+                "--index",
+                "hdfs-logs",
+                "--source",
+                "hdfs-logs-source",
+                "--config",
+                "/conf.yaml",
+                "--position",
+                "0=00000000000000000042",
+                "--position",
+                "1=",
+            ])
+            .unwrap();
+        let command = CliCommand::parse_cli_args(&matches).unwrap();
+        let expected_positions = BTreeMap::from([
+            ("0".to_string(), "00000000000000000042".to_string()),
+            ("1".to_string(), "".to_string()),
+        ]);
+        let expected_command = CliCommand::Source(SourceCliCommand::ResetSourceCheckpoint(
+            ResetSourceCheckpointArgs {
+                config_uri: Uri::try_new("file:///conf.yaml").unwrap(),
+                index_id: "hdfs-logs".to_string(),
+                source_id: "hdfs-logs-source".to_string(),
+                positions: Some(expected_positions),
+                timestamp: None,
+                endpoint: None,
+            },
+        ));
+        assert_eq!(command, expected_command);
+
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app
+            .try_get_matches_from(vec![
+                "source",
+                "reset-checkpoint",
+                "--index",
+                "hdfs-logs",
+                "--source",
+                "hdfs-logs-source",
+                "--config",
+                "/conf.yaml",
+                "--timestamp",
+                "1650000000000",
+                "--endpoint",
+                "http://indexer-1:7280",
+            ])
+            .unwrap();
+        let command = CliCommand::parse_cli_args(&matches).unwrap();
+        let expected_command = CliCommand::Source(SourceCliCommand::ResetSourceCheckpoint(
+            ResetSourceCheckpointArgs {
+                config_uri: Uri::try_new("file:///conf.yaml").unwrap(),
+                index_id: "hdfs-logs".to_string(),
+                source_id: "hdfs-logs-source".to_string(),
+                positions: None,
+                timestamp: Some(1_650_000_000_000),
+                endpoint: Some("http://indexer-1:7280".to_string()),
+            },
+        ));
+        assert_eq!(command, expected_command);
+
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app
+            .try_get_matches_from(vec![
+                "source",
+                "reset-checkpoint",
+                "--index",
+                "hdfs-logs",
+                "--source",
+                "hdfs-logs-source",
+                "--config",
+                "/conf.yaml",
+                "--position",
+                "00000000000000000042",
+            ])
+            .unwrap();
+        assert!(CliCommand::parse_cli_args(&matches).is_err());
+
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app
+            .try_get_matches_from(vec![
+                "source",
+                "prune-checkpoint",
+                "--index",
+                "hdfs-logs",
+                "--source",
+                "hdfs-logs-source",
+                "--config",
+                "/conf.yaml",
+                "--partition",
+                "shardId-000000000000",
+                "--partition",
+                "shardId-000000000001",
+            ])
+            .unwrap();
+        let command = CliCommand::parse_cli_args(&matches).unwrap();
+        let expected_command = CliCommand::Source(SourceCliCommand::PruneSourceCheckpoint(
+            PruneSourceCheckpointArgs {
+                config_uri: Uri::try_new("file:///conf.yaml").unwrap(),
+                index_id: "hdfs-logs".to_string(),
+                source_id: "hdfs-logs-source".to_string(),
+                partition_ids: vec![
+                    "shardId-000000000000".to_string(),
+                    "shardId-000000000001".to_string(),
+                ],
+            },
+        ));
+        assert_eq!(command, expected_command);
+    }
+
     #[test]
     fn test_make_list_sources_table() {
         let sources = [
             SourceConfig {
                 source_id: "foo-source".to_string(),
                 source_params: SourceParams::stdin(),
+                filter: None,
+                routing: None,
+                transform: None,
             },
             SourceConfig {
                 source_id: "bar-source".to_string(),
                 source_params: SourceParams::stdin(),
+                filter: None,
+                routing: None,
+                transform: None,
             },
         ];
         let expected_sources = [
@@ -17,22 +17,31 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::ops::{Range, RangeInclusive};
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use anyhow::{bail, Context};
 use chrono::{NaiveDate, NaiveDateTime};
 use clap::ArgMatches;
+use futures::{StreamExt, TryStreamExt};
 use humansize::{file_size_opts, FileSize};
 use itertools::Itertools;
 use quickwit_common::uri::Uri;
+use quickwit_config::timestamp_resolution;
+use quickwit_core::rebuild_hotcaches;
 use quickwit_directories::{
-    get_hotcache_from_split, read_split_footer, BundleDirectory, HotDirectory,
+    get_hotcache_from_split, read_split_footer, BundleDirectory, CachingDirectory, HotDirectory,
+    StorageDirectory, HOTCACHE_VERSION,
 };
 use quickwit_metastore::{quickwit_metastore_uri_resolver, Split, SplitState};
 use quickwit_storage::{quickwit_storage_uri_resolver, BundleStorage, Storage};
 use tabled::{Table, Tabled};
+use tantivy::directory::{FileSlice, OwnedBytes};
+use tantivy::schema::{Field, Value};
+use tantivy::space_usage::PerFieldSpaceUsage;
+use tantivy::{DocAddress, Index, ReloadPolicy, Searcher};
 use tracing::debug;
 
 use crate::{load_quickwit_config, make_table};
@@ -55,6 +64,7 @@ pub struct DescribeSplitArgs {
     pub index_id: String,
     pub split_id: String,
     pub verbose: bool,
+    pub field_sizes: bool,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -66,11 +76,21 @@ pub struct ExtractSplitArgs {
     pub target_dir: PathBuf,
 }
 
+#[derive(Debug, Eq, PartialEq)]
+pub struct RebuildHotcacheArgs {
+    pub config_uri: Uri,
+    pub data_dir: Option<PathBuf>,
+    pub index_id: String,
+    pub split_ids: Vec<String>,
+    pub dry_run: bool,
+}
+
 #[derive(Debug, PartialEq)]
 pub enum SplitCliCommand {
     List(ListSplitArgs),
     Describe(DescribeSplitArgs),
     Extract(ExtractSplitArgs),
+    RebuildHotcache(RebuildHotcacheArgs),
 }
 
 impl SplitCliCommand {
@@ -82,6 +102,7 @@ impl SplitCliCommand {
             "list" => Self::parse_list_args(submatches),
             "describe" => Self::parse_describe_args(submatches),
             "extract" => Self::parse_extract_split_args(submatches),
+            "rebuild-hotcache" => Self::parse_rebuild_hotcache_args(submatches),
             _ => bail!("Subcommand `{}` is not implemented.", subcommand),
         }
     }
@@ -163,12 +184,14 @@ impl SplitCliCommand {
             .expect("`config` is a required arg.")?;
         let data_dir = matches.value_of("data-dir").map(PathBuf::from);
         let verbose = matches.is_present("verbose");
+        let field_sizes = matches.is_present("field-sizes");
 
         Ok(Self::Describe(DescribeSplitArgs {
             config_uri,
             index_id,
             split_id,
             verbose,
+            field_sizes,
             data_dir,
         }))
     }
@@ -200,11 +223,35 @@ impl SplitCliCommand {
         }))
     }
 
+    fn parse_rebuild_hotcache_args(matches: &ArgMatches) -> anyhow::Result<Self> {
+        let index_id = matches
+            .value_of("index")
+            .map(String::from)
+            .expect("'index-id' is a required arg.");
+        let split_ids = matches
+            .values_of("splits")
+            .map_or(Vec::new(), |values| values.map(String::from).collect());
+        let config_uri = matches
+            .value_of("config")
+            .map(Uri::try_new)
+            .expect("`config` is a required arg.")?;
+        let data_dir = matches.value_of("data-dir").map(PathBuf::from);
+        let dry_run = matches.is_present("dry-run");
+        Ok(Self::RebuildHotcache(RebuildHotcacheArgs {
+            config_uri,
+            data_dir,
+            index_id,
+            split_ids,
+            dry_run,
+        }))
+    }
+
     pub async fn execute(self) -> anyhow::Result<()> {
         match self {
             Self::List(args) => list_split_cli(args).await,
             Self::Describe(args) => describe_split_cli(args).await,
             Self::Extract(args) => extract_split_cli(args).await,
+            Self::RebuildHotcache(args) => rebuild_hotcache_cli(args).await,
         }
     }
 }
@@ -217,15 +264,36 @@ async fn list_split_cli(args: ListSplitArgs) -> anyhow::Result<()> {
     let metastore = metastore_uri_resolver
         .resolve(&quickwit_config.metastore_uri)
         .await?;
-    let splits = metastore.list_all_splits(&args.index_id).await?;
-
-    let filtered_splits = filter_splits(
-        splits,
-        args.states,
-        args.start_date,
-        args.end_date,
-        args.tags,
-    )?;
+    // Split time ranges are expressed in the resolution of the index timestamp field.
+    let index_metadata = metastore.index_metadata(&args.index_id).await?;
+    let resolution = timestamp_resolution(
+        &index_metadata.doc_mapping,
+        &index_metadata.indexing_settings,
+    );
+    let start_date = args
+        .start_date
+        .map(|start_date| resolution.secs_to_units(start_date));
+    let end_date = args
+        .end_date
+        .map(|end_date| resolution.secs_to_units(end_date));
+    // The states and time range filters are evaluated by the metastore, so only the matching
+    // splits are loaded.
+    let splits = if args.states.is_empty() {
+        metastore.list_all_splits(&args.index_id).await?
+    } else {
+        let time_range_opt = time_range_from_dates(start_date, end_date);
+        let mut splits = Vec::new();
+        for split_state in &args.states {
+            splits.extend(
+                metastore
+                    .list_splits(&args.index_id, *split_state, time_range_opt.clone(), None)
+                    .await?,
+            );
+        }
+        splits
+    };
+
+    let filtered_splits = filter_splits(splits, args.states, start_date, end_date, args.tags)?;
     let filtered_splits_table = make_list_splits_table(filtered_splits);
 
     println!("{filtered_splits_table}");
@@ -246,9 +314,9 @@ async fn describe_split_cli(args: DescribeSplitArgs) -> anyhow::Result<()> {
     let index_storage = storage_uri_resolver.resolve(&index_metadata.index_uri)?;
 
     let split_file = PathBuf::from(format!("{}.split", args.split_id));
-    let (split_footer, _) = read_split_footer(index_storage, &split_file).await?;
+    let (split_footer, _) = read_split_footer(index_storage.clone(), &split_file).await?;
     let stats = BundleDirectory::get_stats_split(split_footer.clone())?;
-    let hotcache_bytes = get_hotcache_from_split(split_footer)?;
+    let hotcache_bytes = get_hotcache_from_split(split_footer.clone())?;
     for (path, size) in stats {
         let readable_size = size.file_size(file_size_opts::DECIMAL).unwrap();
         println!("{:?} {}", path, readable_size);
@@ -260,9 +328,200 @@ async fn describe_split_cli(args: DescribeSplitArgs) -> anyhow::Result<()> {
             println!("HotCache {:?} {}", path, readable_size);
         }
     }
+    if args.field_sizes {
+        let index = open_split_index(index_storage, split_file, split_footer)?;
+        let field_sizes = compute_field_sizes(&index).await?;
+        let field_size_rows = field_sizes
+            .into_iter()
+            .sorted_by_key(|(_, field_sizes)| std::cmp::Reverse(field_sizes.total()))
+            .map(|(field_name, field_sizes)| FieldSizeRow::new(field_name, &field_sizes));
+        println!("{}", make_table("Field Sizes", field_size_rows));
+    }
     Ok(())
 }
 
+/// Maximum number of documents read to estimate the share of each field in the doc store.
+const MAX_NUM_DOCS_SAMPLED: u32 = 10_000;
+
+/// Maximum number of sampled documents fetched concurrently.
+const NUM_CONCURRENT_DOC_FETCHES: usize = 100;
+
+/// Opens the index of a split the way the searchers do: the split footer and its hotcache are
+/// read upfront, and the rest of the split is fetched with ranged reads as it is accessed.
+fn open_split_index(
+    index_storage: Arc<dyn Storage>,
+    split_file: PathBuf,
+    split_footer: OwnedBytes,
+) -> anyhow::Result<Index> {
+    let (hotcache_bytes, bundle_storage) = BundleStorage::open_from_split_data(
+        index_storage,
+        split_file,
+        FileSlice::new(Box::new(split_footer)),
+    )?;
+    let directory = StorageDirectory::new(Arc::new(bundle_storage));
+    let caching_directory = CachingDirectory::new_with_unlimited_capacity(Arc::new(directory));
+    let hot_directory = HotDirectory::open(caching_directory, hotcache_bytes.read_bytes()?)?;
+    let index = Index::open(hot_directory)?;
+    Ok(index)
+}
+
+/// Number of bytes used by a field in each data structure of a split.
+#[derive(Debug, Default, Eq, PartialEq)]
+struct FieldSizes {
+    term_dict: u64,
+    postings: u64,
+    positions: u64,
+    fast_fields: u64,
+    fieldnorms: u64,
+    /// The doc store is compressed by blocks of documents, so the number of bytes used by a
+    /// field is estimated from its share of the uncompressed stored values of a sample of docs.
+    stored_estimate: u64,
+}
+
+impl FieldSizes {
+    fn total(&self) -> u64 {
+        self.term_dict
+            + self.postings
+            + self.positions
+            + self.fast_fields
+            + self.fieldnorms
+            + self.stored_estimate
+    }
+}
+
+/// Computes the number of bytes used by each field of a split, summed over its segments.
+async fn compute_field_sizes(index: &Index) -> anyhow::Result<BTreeMap<String, FieldSizes>> {
+    let schema = index.schema();
+    let reader = index
+        .reader_builder()
+        .num_searchers(1)
+        .reload_policy(ReloadPolicy::Manual)
+        .try_into()?;
+    let searcher = reader.searcher();
+    let space_usage = searcher.space_usage()?;
+
+    let mut field_sizes: BTreeMap<String, FieldSizes> = schema
+        .fields()
+        .map(|(_, field_entry)| (field_entry.name().to_string(), FieldSizes::default()))
+        .collect();
+    let mut store_num_bytes = 0;
+    for segment_space_usage in space_usage.segments() {
+        let mut add_usage =
+            |per_field_usage: &PerFieldSpaceUsage,
+             field_size_mut: fn(&mut FieldSizes) -> &mut u64| {
+                for (field, field_usage) in per_field_usage.fields() {
+                    let field_name = schema.get_field_name(*field).to_string();
+                    *field_size_mut(field_sizes.entry(field_name).or_default()) +=
+                        field_usage.total() as u64;
+                }
+            };
+        add_usage(segment_space_usage.termdict(), |sizes| &mut sizes.term_dict);
+        add_usage(segment_space_usage.postings(), |sizes| &mut sizes.postings);
+        add_usage(segment_space_usage.positions(), |sizes| {
+            &mut sizes.positions
+        });
+        add_usage(segment_space_usage.fast_fields(), |sizes| {
+            &mut sizes.fast_fields
+        });
+        add_usage(segment_space_usage.fieldnorms(), |sizes| {
+            &mut sizes.fieldnorms
+        });
+        store_num_bytes += segment_space_usage.store().total() as u64;
+    }
+    let stored_num_bytes_per_field = sample_stored_num_bytes_per_field(&searcher).await?;
+    let sampled_num_bytes: u64 = stored_num_bytes_per_field.values().sum();
+    if sampled_num_bytes > 0 {
+        for (field, num_bytes) in stored_num_bytes_per_field {
+            let field_name = schema.get_field_name(field).to_string();
+            field_sizes.entry(field_name).or_default().stored_estimate =
+                (store_num_bytes as u128 * num_bytes as u128 / sampled_num_bytes as u128) as u64;
+        }
+    }
+    Ok(field_sizes)
+}
+
+/// Sums the number of bytes of the values stored for each field over a sample of at most
+/// `MAX_NUM_DOCS_SAMPLED` docs evenly spread across the split.
+async fn sample_stored_num_bytes_per_field(
+    searcher: &Searcher,
+) -> anyhow::Result<HashMap<Field, u64>> {
+    let num_docs: u64 = searcher
+        .segment_readers()
+        .iter()
+        .map(|segment_reader| segment_reader.max_doc() as u64)
+        .sum();
+    let step = (num_docs / MAX_NUM_DOCS_SAMPLED as u64).max(1) as usize;
+    let doc_addresses: Vec<DocAddress> = searcher
+        .segment_readers()
+        .iter()
+        .enumerate()
+        .flat_map(|(segment_ord, segment_reader)| {
+            (0..segment_reader.max_doc())
+                .step_by(step)
+                .map(move |doc_id| DocAddress::new(segment_ord as u32, doc_id))
+        })
+        .collect();
+    let mut doc_stream = futures::stream::iter(doc_addresses)
+        .map(|doc_address| searcher.doc_async(doc_address))
+        .buffer_unordered(NUM_CONCURRENT_DOC_FETCHES);
+    let mut stored_num_bytes_per_field = HashMap::new();
+    while let Some(doc) = doc_stream.try_next().await? {
+        for field_value in doc.field_values() {
+            *stored_num_bytes_per_field
+                .entry(field_value.field())
+                .or_insert(0) += stored_value_num_bytes(field_value.value());
+        }
+    }
+    Ok(stored_num_bytes_per_field)
+}
+
+fn stored_value_num_bytes(value: &Value) -> u64 {
+    let num_bytes = match value {
+        Value::Str(text) => text.len(),
+        Value::PreTokStr(pre_tokenized_text) => pre_tokenized_text.text.len(),
+        Value::Facet(facet) => facet.encoded_str().len(),
+        Value::Bytes(bytes) => bytes.len(),
+        _ => 8,
+    };
+    num_bytes as u64
+}
+
+#[derive(Tabled)]
+struct FieldSizeRow {
+    #[header("Field")]
+    field_name: String,
+    #[header("Term Dict")]
+    term_dict: String,
+    #[header("Postings")]
+    postings: String,
+    #[header("Positions")]
+    positions: String,
+    #[header("Fast Fields")]
+    fast_fields: String,
+    #[header("Fieldnorms")]
+    fieldnorms: String,
+    #[header("Stored (est.)")]
+    stored_estimate: String,
+    #[header("Total")]
+    total: String,
+}
+
+impl FieldSizeRow {
+    fn new(field_name: String, field_sizes: &FieldSizes) -> Self {
+        let readable_size = |num_bytes: u64| num_bytes.file_size(file_size_opts::DECIMAL).unwrap();
+        Self {
+            field_name,
+            term_dict: readable_size(field_sizes.term_dict),
+            postings: readable_size(field_sizes.postings),
+            positions: readable_size(field_sizes.positions),
+            fast_fields: readable_size(field_sizes.fast_fields),
+            fieldnorms: readable_size(field_sizes.fieldnorms),
+            stored_estimate: readable_size(field_sizes.stored_estimate),
+            total: readable_size(field_sizes.total()),
+        }
+    }
+}
+
 async fn extract_split_cli(args: ExtractSplitArgs) -> anyhow::Result<()> {
     debug!(args = ?args, "extract-split");
 
@@ -292,14 +551,49 @@ async fn extract_split_cli(args: ExtractSplitArgs) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn filter_splits(
-    splits: Vec<Split>,
-    states: Vec<SplitState>,
-    start_date: Option<i64>,
-    end_date: Option<i64>,
-    tags: BTreeSet<String>,
-) -> anyhow::Result<Vec<Split>> {
-    let time_range_opt = match (start_date, end_date) {
+async fn rebuild_hotcache_cli(args: RebuildHotcacheArgs) -> anyhow::Result<()> {
+    debug!(args = ?args, "rebuild-hotcache");
+
+    let quickwit_config = load_quickwit_config(args.config_uri, args.data_dir).await?;
+    let storage_uri_resolver = quickwit_storage_uri_resolver();
+    let metastore_uri_resolver = quickwit_metastore_uri_resolver();
+    let metastore = metastore_uri_resolver
+        .resolve(&quickwit_config.metastore_uri)
+        .await?;
+    let index_metadata = metastore.index_metadata(&args.index_id).await?;
+    let index_storage = storage_uri_resolver.resolve(&index_metadata.index_uri)?;
+    let rebuilds = rebuild_hotcaches(
+        &*metastore,
+        index_storage,
+        &args.index_id,
+        &args.split_ids,
+        args.dry_run,
+    )
+    .await?;
+    if rebuilds.is_empty() {
+        println!(
+            "All the hotcaches are up to date (version {}).",
+            HOTCACHE_VERSION
+        );
+        return Ok(());
+    }
+    for rebuild in rebuilds {
+        match rebuild.new_split_id {
+            Some(new_split_id) => println!(
+                "Split `{}` (hotcache version {}) replaced by split `{}`.",
+                rebuild.split_id, rebuild.hotcache_version, new_split_id
+            ),
+            None => println!(
+                "Split `{}` has an outdated hotcache (version {}).",
+                rebuild.split_id, rebuild.hotcache_version
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn time_range_from_dates(start_date: Option<i64>, end_date: Option<i64>) -> Option<Range<i64>> {
+    match (start_date, end_date) {
         (None, None) => None,
         (None, Some(end_date)) => Some(Range {
             start: i64::MIN,
@@ -313,7 +607,17 @@ fn filter_splits(
             start: start_date,
             end: end_date,
         }),
-    };
+    }
+}
+
+fn filter_splits(
+    splits: Vec<Split>,
+    states: Vec<SplitState>,
+    start_date: Option<i64>,
+    end_date: Option<i64>,
+    tags: BTreeSet<String>,
+) -> anyhow::Result<Vec<Split>> {
+    let time_range_opt = time_range_from_dates(start_date, end_date);
     let is_disjoint_time_range = |left: &Range<i64>, right: &RangeInclusive<i64>| {
         left.end <= *right.start() || *right.end() < left.start
     };
@@ -492,12 +796,73 @@ mod tests {
                 index_id,
                 split_id,
                 verbose: false,
+                field_sizes: false,
                 ..
             })) if &index_id == "wikipedia" && &split_id == "ABC"
         ));
         Ok(())
     }
 
+    #[test]
+    fn test_parse_split_describe_field_sizes_args() -> anyhow::Result<()> {
+        let yaml = load_yaml!("cli.yaml");
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app.try_get_matches_from(vec![
+            "split",
+            "describe",
+            "--index",
+            "wikipedia",
+            "--split",
+            "ABC",
+            "--config",
+            "file:///config.yaml",
+            "--field-sizes",
+        ])?;
+        let command = CliCommand::parse_cli_args(&matches)?;
+        assert!(matches!(
+            command,
+            CliCommand::Split(SplitCliCommand::Describe(DescribeSplitArgs {
+                field_sizes: true,
+                ..
+            }))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compute_field_sizes() -> anyhow::Result<()> {
+        use tantivy::doc;
+        use tantivy::schema::{Schema, FAST, STORED, TEXT};
+
+        let mut schema_builder = Schema::builder();
+        let body_field = schema_builder.add_text_field("body", TEXT | STORED);
+        let timestamp_field = schema_builder.add_i64_field("timestamp", FAST | STORED);
+        let index = Index::create_in_ram(schema_builder.build());
+        let mut index_writer = index.writer_with_num_threads(1, 10_000_000)?;
+        for timestamp in 0..100 {
+            index_writer.add_document(doc!(
+                body_field => "the quick brown fox jumps over the lazy dog",
+                timestamp_field => timestamp,
+            ));
+        }
+        index_writer.commit()?;
+
+        let field_sizes = compute_field_sizes(&index).await?;
+        let body_sizes = &field_sizes["body"];
+        assert!(body_sizes.term_dict > 0);
+        assert!(body_sizes.postings > 0);
+        assert!(body_sizes.positions > 0);
+        assert!(body_sizes.fieldnorms > 0);
+        assert_eq!(body_sizes.fast_fields, 0);
+        let timestamp_sizes = &field_sizes["timestamp"];
+        assert!(timestamp_sizes.fast_fields > 0);
+        assert_eq!(timestamp_sizes.positions, 0);
+        // The body values are much larger than the 8-byte timestamps.
+        assert!(body_sizes.stored_estimate > 4 * timestamp_sizes.stored_estimate);
+        assert!(timestamp_sizes.stored_estimate > 0);
+        Ok(())
+    }
+
     #[test]
     fn test_parse_split_extract_args() -> anyhow::Result<()> {
         let yaml = load_yaml!("cli.yaml");
@@ -527,6 +892,34 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_split_rebuild_hotcache_args() -> anyhow::Result<()> {
+        let yaml = load_yaml!("cli.yaml");
+        let app = App::from(yaml).setting(AppSettings::NoBinaryName);
+        let matches = app.try_get_matches_from(vec![
+            "split",
+            "rebuild-hotcache",
+            "--index",
+            "wikipedia",
+            "--splits",
+            "ABC,DEF",
+            "--config",
+            "file:///config.yaml",
+            "--dry-run",
+        ])?;
+        let command = CliCommand::parse_cli_args(&matches)?;
+        let expected_command =
+            CliCommand::Split(SplitCliCommand::RebuildHotcache(RebuildHotcacheArgs {
+                config_uri: Uri::try_new("file:///config.yaml")?,
+                data_dir: None,
+                index_id: "wikipedia".to_string(),
+                split_ids: vec!["ABC".to_string(), "DEF".to_string()],
+                dry_run: true,
+            }));
+        assert_eq!(command, expected_command);
+        Ok(())
+    }
+
     fn make_split(
         split_id: &str,
         split_state: SplitState,
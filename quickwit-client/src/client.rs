@@ -0,0 +1,677 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::fmt;
+use std::time::Duration;
+
+use bytes::Bytes;
+use quickwit_proto::search_service_client::SearchServiceClient;
+use reqwest::{Method, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use tonic::transport::{Channel, Endpoint};
+
+use crate::models::{
+    ExportQuery, Job, PausedSources, PipelineThroughputs, ResetSourceCheckpointRequest,
+    SearchQuery, SearchResponse, SearchStreamQuery, SourceCheckpoint, WriteBlocks,
+};
+use crate::{ClientError, ClientResult, RetryPolicy};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_POOL_MAX_IDLE_PER_HOST: usize = 16;
+
+/// Builds a [`QuickwitClient`].
+#[derive(Clone, Debug)]
+pub struct QuickwitClientBuilder {
+    rest_endpoint: String,
+    grpc_endpoint_opt: Option<String>,
+    timeout: Duration,
+    connect_timeout: Duration,
+    pool_max_idle_per_host: usize,
+    retry_policy: RetryPolicy,
+}
+
+impl QuickwitClientBuilder {
+    /// Sets the gRPC endpoint of the node, e.g. `http://localhost:7281`, required by
+    /// [`QuickwitClient::root_search`].
+    pub fn grpc_endpoint(mut self, grpc_endpoint: impl Into<String>) -> Self {
+        self.grpc_endpoint_opt = Some(grpc_endpoint.into());
+        self
+    }
+
+    /// Sets the timeout of a single attempt of a request (30s by default).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the timeout for establishing a REST connection (5s by default).
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    /// Sets the maximum number of idle REST connections kept open (16 by default).
+    pub fn pool_max_idle_per_host(mut self, pool_max_idle_per_host: usize) -> Self {
+        self.pool_max_idle_per_host = pool_max_idle_per_host;
+        self
+    }
+
+    /// Sets the policy applied to the requests failing with a retryable error.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Builds the client.
+    ///
+    /// The connections are established lazily, on the first requests. With a gRPC endpoint, this
+    /// method must be called within a tokio runtime.
+    pub fn build(self) -> ClientResult<QuickwitClient> {
+        let rest_endpoint = parse_rest_endpoint(&self.rest_endpoint)?;
+        let http_client = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .connect_timeout(self.connect_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .build()
+            .map_err(|error| ClientError::InvalidEndpoint {
+                endpoint: self.rest_endpoint.clone(),
+                message: error.to_string(),
+            })?;
+        let grpc_client_opt = if let Some(grpc_endpoint) = &self.grpc_endpoint_opt {
+            let endpoint = Endpoint::from_shared(grpc_endpoint.clone())
+                .map_err(|error| ClientError::InvalidEndpoint {
+                    endpoint: grpc_endpoint.clone(),
+                    message: error.to_string(),
+                })?
+                .timeout(self.timeout);
+            // The channel multiplexes the requests over a single HTTP/2 connection, and
+            // reconnects automatically.
+            let channel = endpoint.connect_lazy();
+            Some(SearchServiceClient::new(channel).accept_gzip())
+        } else {
+            None
+        };
+        Ok(QuickwitClient {
+            rest_endpoint,
+            http_client,
+            grpc_client_opt,
+            retry_policy: self.retry_policy,
+        })
+    }
+}
+
+/// Appends a trailing slash to the endpoint so that the API paths are joined to its full path.
+fn parse_rest_endpoint(rest_endpoint: &str) -> ClientResult<Url> {
+    let mut rest_endpoint_with_slash = rest_endpoint.to_string();
+    if !rest_endpoint_with_slash.ends_with('/') {
+        rest_endpoint_with_slash.push('/');
+    }
+    let url =
+        Url::parse(&rest_endpoint_with_slash).map_err(|error| ClientError::InvalidEndpoint {
+            endpoint: rest_endpoint.to_string(),
+            message: error.to_string(),
+        })?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(ClientError::InvalidEndpoint {
+            endpoint: rest_endpoint.to_string(),
+            message: "The scheme must be `http` or `https`.".to_string(),
+        });
+    }
+    Ok(url)
+}
+
+/// Client of the REST and gRPC APIs of a Quickwit node.
+///
+/// The client is cheap to clone: the clones share the same connection pools.
+#[derive(Clone)]
+pub struct QuickwitClient {
+    rest_endpoint: Url,
+    http_client: reqwest::Client,
+    grpc_client_opt: Option<SearchServiceClient<Channel>>,
+    retry_policy: RetryPolicy,
+}
+
+impl fmt::Debug for QuickwitClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QuickwitClient")
+            .field("rest_endpoint", &self.rest_endpoint.as_str())
+            .field("has_grpc_endpoint", &self.grpc_client_opt.is_some())
+            .finish()
+    }
+}
+
+impl QuickwitClient {
+    /// Creates a builder for a client of the node serving the REST API at `rest_endpoint`, e.g.
+    /// `http://localhost:7280`.
+    pub fn builder(rest_endpoint: impl Into<String>) -> QuickwitClientBuilder {
+        QuickwitClientBuilder {
+            rest_endpoint: rest_endpoint.into(),
+            grpc_endpoint_opt: None,
+            timeout: DEFAULT_TIMEOUT,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            pool_max_idle_per_host: DEFAULT_POOL_MAX_IDLE_PER_HOST,
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    /// Searches an index through the REST API.
+    pub async fn search(
+        &self,
+        index_id: &str,
+        search_query: &SearchQuery,
+    ) -> ClientResult<SearchResponse> {
+        let path = format!("api/v1/{}/search", index_id);
+        self.request_json(Method::GET, &path, &search_query.query_params())
+            .await
+    }
+
+    /// Searches an index through the gRPC API. Requires a gRPC endpoint.
+    pub async fn root_search(
+        &self,
+        search_request: quickwit_proto::SearchRequest,
+    ) -> ClientResult<quickwit_proto::SearchResponse> {
+        let grpc_client = self
+            .grpc_client_opt
+            .as_ref()
+            .ok_or(ClientError::MissingGrpcEndpoint)?;
+        self.retry_policy
+            .retry(|| {
+                let mut grpc_client = grpc_client.clone();
+                let search_request = search_request.clone();
+                async move {
+                    let response = grpc_client.root_search(search_request).await?;
+                    Ok(response.into_inner())
+                }
+            })
+            .await
+    }
+
+    /// Streams the values of a fast field of the documents matching a query.
+    ///
+    /// Only the initial request is retried: the stream fails if the connection is lost
+    /// afterwards.
+    pub async fn search_stream(
+        &self,
+        index_id: &str,
+        search_stream_query: &SearchStreamQuery,
+    ) -> ClientResult<SearchStream> {
+        let path = format!("api/v1/{}/search/stream", index_id);
+        let query_params = search_stream_query.query_params();
+        let response = self
+            .retry_policy
+            .retry(|| self.request(Method::GET, &path, &query_params, None))
+            .await?;
+        Ok(SearchStream { response })
+    }
+
+    /// Starts a job exporting the documents matching a query to Parquet files.
+    ///
+    /// The request is not retried, as sending it again may start a second job.
+    pub async fn start_export(
+        &self,
+        index_id: &str,
+        export_query: &ExportQuery,
+    ) -> ClientResult<Job> {
+        let path = format!("api/v1/{}/export", index_id);
+        let body = serde_json::to_value(export_query)
+            .expect("Export queries should always be serializable.");
+        let response = self.request(Method::POST, &path, &[], Some(&body)).await?;
+        let job = response.json::<Job>().await?;
+        Ok(job)
+    }
+
+    /// Returns the record of a job.
+    pub async fn job(&self, job_id: &str) -> ClientResult<Job> {
+        let path = format!("api/v1/jobs/{}", job_id);
+        self.request_json(Method::GET, &path, &[]).await
+    }
+
+    /// Lists the jobs of the cluster, or only those of an index, most recent first.
+    pub async fn list_jobs(&self, index_id_opt: Option<&str>) -> ClientResult<Vec<Job>> {
+        let query_params: Vec<(&str, String)> = index_id_opt
+            .map(|index_id| ("indexId", index_id.to_string()))
+            .into_iter()
+            .collect();
+        self.request_json(Method::GET, "api/v1/jobs", &query_params)
+            .await
+    }
+
+    /// Cancels a running job.
+    ///
+    /// The request is not retried, as a retry would fail once the first attempt cancelled the job.
+    pub async fn cancel_job(&self, job_id: &str) -> ClientResult<Job> {
+        let path = format!("api/v1/jobs/{}/cancel", job_id);
+        let response = self.request(Method::POST, &path, &[], None).await?;
+        let job = response.json::<Job>().await?;
+        Ok(job)
+    }
+
+    /// Lists the members of the cluster the node belongs to.
+    pub async fn list_members(&self) -> ClientResult<quickwit_proto::ListMembersResponse> {
+        let query_params = [("format", "json".to_string())];
+        self.request_json(Method::GET, "cluster/members", &query_params)
+            .await
+    }
+
+    /// Lists the write-blocked indexes of the node.
+    pub async fn write_blocks(&self) -> ClientResult<WriteBlocks> {
+        self.request_json(Method::GET, "api/v1/write-blocks", &[])
+            .await
+    }
+
+    /// Blocks the indexing of an index on the node.
+    pub async fn apply_write_block(&self, index_id: &str) -> ClientResult<WriteBlocks> {
+        let path = format!("api/v1/indexes/{}/write-block", index_id);
+        self.request_json(Method::PUT, &path, &[]).await
+    }
+
+    /// Clears the write block of an index on the node.
+    pub async fn clear_write_block(&self, index_id: &str) -> ClientResult<WriteBlocks> {
+        let path = format!("api/v1/indexes/{}/write-block", index_id);
+        self.request_json(Method::DELETE, &path, &[]).await
+    }
+
+    /// Lists the paused sources of the node.
+    pub async fn paused_sources(&self) -> ClientResult<PausedSources> {
+        self.request_json(Method::GET, "api/v1/paused-sources", &[])
+            .await
+    }
+
+    /// Pauses the indexing of a source on the node.
+    pub async fn pause_source(
+        &self,
+        index_id: &str,
+        source_id: &str,
+    ) -> ClientResult<PausedSources> {
+        let path = format!("api/v1/indexes/{}/sources/{}/pause", index_id, source_id);
+        self.request_json(Method::PUT, &path, &[]).await
+    }
+
+    /// Resumes the indexing of a paused source on the node.
+    pub async fn resume_source(
+        &self,
+        index_id: &str,
+        source_id: &str,
+    ) -> ClientResult<PausedSources> {
+        let path = format!("api/v1/indexes/{}/sources/{}/pause", index_id, source_id);
+        self.request_json(Method::DELETE, &path, &[]).await
+    }
+
+    /// Returns the checkpoint of a source.
+    pub async fn source_checkpoint(
+        &self,
+        index_id: &str,
+        source_id: &str,
+    ) -> ClientResult<SourceCheckpoint> {
+        let path = format!(
+            "api/v1/indexes/{}/sources/{}/checkpoint",
+            index_id, source_id
+        );
+        self.request_json(Method::GET, &path, &[]).await
+    }
+
+    /// Resets the checkpoint of a source. The source must be paused on the node first.
+    pub async fn reset_source_checkpoint(
+        &self,
+        index_id: &str,
+        source_id: &str,
+        reset_request: &ResetSourceCheckpointRequest,
+    ) -> ClientResult<SourceCheckpoint> {
+        let path = format!(
+            "api/v1/indexes/{}/sources/{}/checkpoint",
+            index_id, source_id
+        );
+        let body = serde_json::to_value(reset_request)
+            .expect("Checkpoint reset requests should always be serializable.");
+        let response = self.request(Method::PUT, &path, &[], Some(&body)).await?;
+        let source_checkpoint = response.json::<SourceCheckpoint>().await?;
+        Ok(source_checkpoint)
+    }
+
+    /// Returns the throughput of the indexing pipelines of an index running on the node.
+    pub async fn pipeline_throughputs(&self, index_id: &str) -> ClientResult<PipelineThroughputs> {
+        let path = format!("api/v1/indexes/{}/pipelines", index_id);
+        self.request_json(Method::GET, &path, &[]).await
+    }
+
+    /// Returns whether the node is alive.
+    pub async fn is_alive(&self) -> ClientResult<bool> {
+        let result = self
+            .retry_policy
+            .retry(|| self.request(Method::GET, "health/livez", &[], None))
+            .await;
+        match result {
+            Ok(_) => Ok(true),
+            Err(ClientError::Api {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                ..
+            }) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    async fn request_json<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        query_params: &[(&str, String)],
+    ) -> ClientResult<T> {
+        self.retry_policy
+            .retry(|| async {
+                let response = self
+                    .request(method.clone(), path, query_params, None)
+                    .await?;
+                let body = response.json::<T>().await?;
+                Ok(body)
+            })
+            .await
+    }
+
+    /// Sends a single request, and turns the error status codes into errors.
+    async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        query_params: &[(&str, String)],
+        body_opt: Option<&serde_json::Value>,
+    ) -> ClientResult<Response> {
+        let url = self
+            .rest_endpoint
+            .join(path)
+            .map_err(|error| ClientError::InvalidEndpoint {
+                endpoint: self.rest_endpoint.to_string(),
+                message: error.to_string(),
+            })?;
+        let mut request_builder = self.http_client.request(method, url).query(query_params);
+        if let Some(body) = body_opt {
+            request_builder = request_builder.json(body);
+        }
+        let response = request_builder.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response.text().await.unwrap_or_default();
+        Err(ClientError::Api {
+            status,
+            message: parse_error_message(body),
+        })
+    }
+}
+
+/// Body of the error responses of the REST API.
+#[derive(Deserialize)]
+struct ApiErrorBody {
+    error: String,
+}
+
+fn parse_error_message(body: String) -> String {
+    serde_json::from_str::<ApiErrorBody>(&body)
+        .map(|error_body| error_body.error)
+        .unwrap_or(body)
+}
+
+/// Response of the search stream API, read chunk by chunk.
+pub struct SearchStream {
+    response: Response,
+}
+
+impl SearchStream {
+    /// Returns the next chunk of the stream, or `None` once the stream is complete.
+    pub async fn next_chunk(&mut self) -> ClientResult<Option<Bytes>> {
+        let chunk_opt = self.response.chunk().await?;
+        Ok(chunk_opt)
+    }
+
+    /// Reads the rest of the stream into memory.
+    pub async fn collect(mut self) -> ClientResult<Vec<u8>> {
+        let mut buffer = Vec::new();
+        while let Some(chunk) = self.next_chunk().await? {
+            buffer.extend_from_slice(&chunk);
+        }
+        Ok(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use serde_json::json;
+    use warp::http::StatusCode as WarpStatusCode;
+    use warp::Filter;
+
+    use super::*;
+    use crate::JobState;
+
+    fn test_client(addr: SocketAddr) -> QuickwitClient {
+        QuickwitClient::builder(format!("http://{}", addr))
+            .retry_policy(RetryPolicy {
+                max_num_retries: 2,
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(1),
+            })
+            .build()
+            .unwrap()
+    }
+
+    fn spawn_server(
+        routes: impl Filter<Extract = impl warp::Reply, Error = warp::Rejection>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    ) -> SocketAddr {
+        let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        addr
+    }
+
+    #[test]
+    fn test_parse_rest_endpoint() {
+        assert_eq!(
+            parse_rest_endpoint("http://localhost:7280")
+                .unwrap()
+                .join("api/v1/write-blocks")
+                .unwrap()
+                .as_str(),
+            "http://localhost:7280/api/v1/write-blocks"
+        );
+        assert_eq!(
+            parse_rest_endpoint("https://proxy/quickwit")
+                .unwrap()
+                .join("api/v1/write-blocks")
+                .unwrap()
+                .as_str(),
+            "https://proxy/quickwit/api/v1/write-blocks"
+        );
+        assert!(parse_rest_endpoint("localhost:7280").is_err());
+        assert!(parse_rest_endpoint("ftp://localhost").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_search() {
+        let routes = warp::path!("api" / "v1" / String / "search")
+            .and(warp::query::<HashMap<String, String>>())
+            .map(|index_id: String, params: HashMap<String, String>| {
+                assert_eq!(index_id, "my-index");
+                assert_eq!(params["query"], "title:snoopy");
+                assert_eq!(params["searchField"], "title,body");
+                warp::reply::json(&json!({
+                    "numHits": 1,
+                    "hits": [{"title": ["snoopy"]}],
+                    "elapsedTimeMicros": 10,
+                    "errors": [],
+                }))
+            });
+        let client = test_client(spawn_server(routes));
+        let mut search_query = SearchQuery::new("title:snoopy");
+        search_query.search_fields = vec!["title".to_string(), "body".to_string()];
+        let search_response = client.search("my-index", &search_query).await.unwrap();
+        assert_eq!(search_response.num_hits, 1);
+        assert_eq!(search_response.hits, vec![json!({"title": ["snoopy"]})]);
+    }
+
+    #[tokio::test]
+    async fn test_client_retries_unavailable_node() {
+        let num_requests = Arc::new(AtomicUsize::new(0));
+        let num_requests_clone = num_requests.clone();
+        let routes = warp::path!("api" / "v1" / "write-blocks").map(move || {
+            if num_requests_clone.fetch_add(1, Ordering::SeqCst) == 0 {
+                return warp::reply::with_status(
+                    warp::reply::json(&json!({"error": "unavailable"})),
+                    WarpStatusCode::SERVICE_UNAVAILABLE,
+                );
+            }
+            warp::reply::with_status(
+                warp::reply::json(&json!({"writeBlocks": {"my-index": "manual"}})),
+                WarpStatusCode::OK,
+            )
+        });
+        let client = test_client(spawn_server(routes));
+        let write_blocks = client.write_blocks().await.unwrap();
+        assert_eq!(write_blocks.write_blocks["my-index"], "manual");
+        assert_eq!(num_requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_client_api_error() {
+        let num_requests = Arc::new(AtomicUsize::new(0));
+        let num_requests_clone = num_requests.clone();
+        let routes = warp::path!("api" / "v1" / "indexes" / String / "write-block").map(
+            move |index_id: String| {
+                num_requests_clone.fetch_add(1, Ordering::SeqCst);
+                warp::reply::with_status(
+                    warp::reply::json(&json!({
+                        "error": format!("No write block found for index `{}`.", index_id)
+                    })),
+                    WarpStatusCode::NOT_FOUND,
+                )
+            },
+        );
+        let client = test_client(spawn_server(routes));
+        let error = client.clear_write_block("my-index").await.unwrap_err();
+        assert!(matches!(
+            error,
+            ClientError::Api { status: StatusCode::NOT_FOUND, ref message }
+                if message == "No write block found for index `my-index`."
+        ));
+        assert_eq!(num_requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_client_search_stream() {
+        let routes = warp::path!("api" / "v1" / String / "search" / "stream")
+            .map(|_index_id: String| "1\n2\n3\n");
+        let client = test_client(spawn_server(routes));
+        let search_stream = client
+            .search_stream("my-index", &SearchStreamQuery::new("*", "id"))
+            .await
+            .unwrap();
+        assert_eq!(search_stream.collect().await.unwrap(), b"1\n2\n3\n");
+    }
+
+    #[tokio::test]
+    async fn test_client_jobs() {
+        fn job_json(job_id: &str, state: &str) -> serde_json::Value {
+            json!({
+                "jobId": job_id,
+                "indexId": "my-index",
+                "kind": "export",
+                "state": state,
+                "progress": 0.0,
+                "nodeId": "node-1",
+                "createTimestamp": 1,
+                "updateTimestamp": 1,
+                "details": {"numDocsExported": 0, "files": []},
+            })
+        }
+        let start_export_route = warp::path!("api" / "v1" / String / "export")
+            .and(warp::post())
+            .and(warp::body::json())
+            .map(|index_id: String, body: serde_json::Value| {
+                assert_eq!(index_id, "my-index");
+                assert_eq!(body["outputUri"], "s3://my-bucket/exports");
+                warp::reply::json(&job_json("my-job", "running"))
+            });
+        let list_jobs_route = warp::path!("api" / "v1" / "jobs")
+            .and(warp::get())
+            .and(warp::query::<HashMap<String, String>>())
+            .map(|query_params: HashMap<String, String>| {
+                assert_eq!(query_params.get("indexId").unwrap(), "my-index");
+                warp::reply::json(&json!([job_json("my-job", "running")]))
+            });
+        let job_route = warp::path!("api" / "v1" / "jobs" / String)
+            .and(warp::get())
+            .map(|job_id: String| warp::reply::json(&job_json(&job_id, "succeeded")));
+        let cancel_job_route = warp::path!("api" / "v1" / "jobs" / String / "cancel")
+            .and(warp::post())
+            .map(|job_id: String| warp::reply::json(&job_json(&job_id, "cancelled")));
+        let client = test_client(spawn_server(
+            start_export_route
+                .or(list_jobs_route)
+                .or(job_route)
+                .or(cancel_job_route),
+        ));
+        let job = client
+            .start_export("my-index", &ExportQuery::new("*", "s3://my-bucket/exports"))
+            .await
+            .unwrap();
+        assert_eq!(job.job_id, "my-job");
+        assert_eq!(job.state, JobState::Running);
+        assert_eq!(job.details["numDocsExported"], 0);
+
+        let jobs = client.list_jobs(Some("my-index")).await.unwrap();
+        assert_eq!(jobs.len(), 1);
+
+        let job = client.job("my-job").await.unwrap();
+        assert_eq!(job.state, JobState::Succeeded);
+        assert!(job.error.is_none());
+
+        let job = client.cancel_job("my-job").await.unwrap();
+        assert_eq!(job.state, JobState::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_client_unreachable_node() {
+        let client = QuickwitClient::builder("http://127.0.0.1:1")
+            .retry_policy(RetryPolicy::no_retry())
+            .build()
+            .unwrap();
+        let error = client.is_alive().await.unwrap_err();
+        assert!(matches!(error, ClientError::Transport(_)));
+    }
+
+    #[tokio::test]
+    async fn test_client_root_search_requires_grpc_endpoint() {
+        let client = QuickwitClient::builder("http://127.0.0.1:7280")
+            .build()
+            .unwrap();
+        let error = client
+            .root_search(quickwit_proto::SearchRequest::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ClientError::MissingGrpcEndpoint));
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use reqwest::StatusCode;
+use thiserror::Error;
+
+/// Error returned by the [`QuickwitClient`](crate::QuickwitClient).
+#[allow(missing_docs)]
+#[derive(Error, Debug)]
+pub enum ClientError {
+    /// The endpoint passed to the client builder is not a valid URL.
+    #[error("Invalid endpoint `{endpoint}`: {message}")]
+    InvalidEndpoint { endpoint: String, message: String },
+    /// The request requires a gRPC endpoint, but none was configured.
+    #[error("No gRPC endpoint is configured.")]
+    MissingGrpcEndpoint,
+    /// The node could not be reached, or the request timed out.
+    #[error("Failed to reach the node: {0}")]
+    Transport(String),
+    /// The REST API answered with an error status code.
+    #[error("API error (status {status}): {message}")]
+    Api { status: StatusCode, message: String },
+    /// The gRPC API answered with an error status.
+    #[error("gRPC error (code {code:?}): {message}")]
+    Grpc { code: tonic::Code, message: String },
+    /// The response could not be deserialized.
+    #[error("Failed to deserialize the response: {0}")]
+    Deserialization(String),
+}
+
+impl ClientError {
+    /// Returns whether the request may succeed if it is sent again.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ClientError::Transport(_) => true,
+            ClientError::Api { status, .. } => matches!(
+                *status,
+                StatusCode::TOO_MANY_REQUESTS
+                    | StatusCode::BAD_GATEWAY
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT
+            ),
+            ClientError::Grpc { code, .. } => matches!(
+                code,
+                tonic::Code::Unavailable | tonic::Code::ResourceExhausted | tonic::Code::Unknown
+            ),
+            ClientError::InvalidEndpoint { .. }
+            | ClientError::MissingGrpcEndpoint
+            | ClientError::Deserialization(_) => false,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_decode() {
+            return ClientError::Deserialization(error.to_string());
+        }
+        ClientError::Transport(error.to_string())
+    }
+}
+
+impl From<tonic::Status> for ClientError {
+    fn from(status: tonic::Status) -> Self {
+        ClientError::Grpc {
+            code: status.code(),
+            message: status.message().to_string(),
+        }
+    }
+}
+
+/// Result type of the [`QuickwitClient`](crate::QuickwitClient) requests.
+pub type ClientResult<T> = Result<T, ClientError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_error_is_retryable() {
+        assert!(ClientError::Transport("connection refused".to_string()).is_retryable());
+        assert!(ClientError::Api {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            message: "unavailable".to_string()
+        }
+        .is_retryable());
+        assert!(!ClientError::Api {
+            status: StatusCode::NOT_FOUND,
+            message: "Index does not exist".to_string()
+        }
+        .is_retryable());
+        assert!(ClientError::from(tonic::Status::unavailable("unavailable")).is_retryable());
+        assert!(!ClientError::from(tonic::Status::invalid_argument("invalid")).is_retryable());
+        assert!(!ClientError::MissingGrpcEndpoint.is_retryable());
+    }
+}
@@ -0,0 +1,58 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+#![warn(missing_docs)]
+
+//! `quickwit-client` is a client of the APIs of a Quickwit node, with typed requests and
+//! responses:
+//! - search, through the REST API or the gRPC API.
+//! - search stream, through the REST API.
+//! - export to Parquet files, through the REST API.
+//! - cluster members, write blocks, paused sources, indexing throughput and liveness, through the
+//!   REST API.
+//!
+//! The client keeps a pool of connections to the node and retries the requests failing with a
+//! transient error, such as an unreachable or overloaded node.
+//!
+//! ```no_run
+//! # async fn example() -> quickwit_client::ClientResult<()> {
+//! use quickwit_client::{QuickwitClient, SearchQuery};
+//!
+//! let client = QuickwitClient::builder("http://localhost:7280").build()?;
+//! let search_response = client
+//!     .search("wikipedia", &SearchQuery::new("title:apollo"))
+//!     .await?;
+//! println!("{} hits", search_response.num_hits);
+//! # Ok(())
+//! # }
+//! ```
+
+mod client;
+mod error;
+mod models;
+mod retry;
+
+pub use client::{QuickwitClient, QuickwitClientBuilder, SearchStream};
+pub use error::{ClientError, ClientResult};
+pub use models::{
+    ExportQuery, Job, JobKind, JobState, PausedSources, PipelineThroughput, PipelineThroughputs,
+    ResetSourceCheckpointRequest, SearchQuery, SearchResponse, SearchStreamQuery, SourceCheckpoint,
+    StageThroughput, StreamOutputFormat, WriteBlocks,
+};
+pub use retry::RetryPolicy;
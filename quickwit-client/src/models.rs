@@ -0,0 +1,403 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Search request sent to the REST search API.
+///
+/// Timestamps are strings as the REST API also accepts relative time expressions such as
+/// `now-15m`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchQuery {
+    /// Query text, in the tantivy query language.
+    pub query: String,
+    /// Fields to search on. The default search fields of the index are used if empty.
+    pub search_fields: Vec<String>,
+    /// If set, restricts the search to documents with a `timestamp >= start_timestamp`.
+    pub start_timestamp: Option<String>,
+    /// If set, restricts the search to documents with a `timestamp < end_timestamp`.
+    pub end_timestamp: Option<String>,
+    /// Maximum number of hits to return.
+    pub max_hits: u64,
+    /// Rank of the first hit to return.
+    pub start_offset: u64,
+    /// Field to sort the hits by, prefixed with `-` for a descending order.
+    pub sort_by_field: Option<String>,
+    /// If set, the response holds a snapshot token pinning the searched splits.
+    pub pin_splits: bool,
+    /// Snapshot token returned by a previous search, to search the splits it pins, e.g. to fetch
+    /// the next page of hits consistently.
+    pub snapshot: Option<String>,
+}
+
+impl SearchQuery {
+    /// Creates a search query returning the first 20 hits matching `query`.
+    pub fn new(query: impl Into<String>) -> Self {
+        SearchQuery {
+            query: query.into(),
+            search_fields: Vec::new(),
+            start_timestamp: None,
+            end_timestamp: None,
+            max_hits: 20,
+            start_offset: 0,
+            sort_by_field: None,
+            pin_splits: false,
+            snapshot: None,
+        }
+    }
+
+    pub(crate) fn query_params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![
+            ("query", self.query.clone()),
+            ("maxHits", self.max_hits.to_string()),
+            ("startOffset", self.start_offset.to_string()),
+        ];
+        push_common_params(
+            &mut params,
+            &self.search_fields,
+            &self.start_timestamp,
+            &self.end_timestamp,
+        );
+        if let Some(sort_by_field) = &self.sort_by_field {
+            params.push(("sortByField", sort_by_field.clone()));
+        }
+        if self.pin_splits {
+            params.push(("pinSplits", "true".to_string()));
+        }
+        if let Some(snapshot) = &self.snapshot {
+            params.push(("snapshot", snapshot.clone()));
+        }
+        params
+    }
+}
+
+/// Output format of the search stream API.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamOutputFormat {
+    /// Comma-separated values, one value per line.
+    Csv,
+    /// ClickHouse RowBinary format.
+    ClickHouseRowBinary,
+}
+
+/// Search stream request sent to the REST search stream API.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchStreamQuery {
+    /// Query text, in the tantivy query language.
+    pub query: String,
+    /// Fast field whose values are streamed.
+    pub fast_field: String,
+    /// Fields to search on. The default search fields of the index are used if empty.
+    pub search_fields: Vec<String>,
+    /// If set, restricts the search to documents with a `timestamp >= start_timestamp`.
+    pub start_timestamp: Option<String>,
+    /// If set, restricts the search to documents with a `timestamp < end_timestamp`.
+    pub end_timestamp: Option<String>,
+    /// Output format of the stream.
+    pub output_format: StreamOutputFormat,
+}
+
+impl SearchStreamQuery {
+    /// Creates a search stream query streaming the values of `fast_field` as CSV.
+    pub fn new(query: impl Into<String>, fast_field: impl Into<String>) -> Self {
+        SearchStreamQuery {
+            query: query.into(),
+            fast_field: fast_field.into(),
+            search_fields: Vec::new(),
+            start_timestamp: None,
+            end_timestamp: None,
+            output_format: StreamOutputFormat::Csv,
+        }
+    }
+
+    pub(crate) fn query_params(&self) -> Vec<(&'static str, String)> {
+        let output_format = match self.output_format {
+            StreamOutputFormat::Csv => "csv",
+            StreamOutputFormat::ClickHouseRowBinary => "clickHouseRowBinary",
+        };
+        let mut params = vec![
+            ("query", self.query.clone()),
+            ("fastField", self.fast_field.clone()),
+            ("outputFormat", output_format.to_string()),
+        ];
+        push_common_params(
+            &mut params,
+            &self.search_fields,
+            &self.start_timestamp,
+            &self.end_timestamp,
+        );
+        params
+    }
+}
+
+fn push_common_params(
+    params: &mut Vec<(&'static str, String)>,
+    search_fields: &[String],
+    start_timestamp_opt: &Option<String>,
+    end_timestamp_opt: &Option<String>,
+) {
+    if !search_fields.is_empty() {
+        params.push(("searchField", search_fields.join(",")));
+    }
+    if let Some(start_timestamp) = start_timestamp_opt {
+        params.push(("startTimestamp", start_timestamp.clone()));
+    }
+    if let Some(end_timestamp) = end_timestamp_opt {
+        params.push(("endTimestamp", end_timestamp.clone()));
+    }
+}
+
+/// Response of the REST search API.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResponse {
+    /// Overall number of documents matching the query.
+    pub num_hits: u64,
+    /// Documents returned.
+    pub hits: Vec<serde_json::Value>,
+    /// Time spent by the node on the search.
+    pub elapsed_time_micros: u64,
+    /// Errors raised while searching some of the splits.
+    pub errors: Vec<String>,
+    /// Snapshot token pinning the searched splits, if requested.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
+}
+
+/// Write blocks of a node, per index ID. The value is the reason of the block, e.g. `manual`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteBlocks {
+    /// Reason of the write block, per index ID.
+    pub write_blocks: BTreeMap<String, String>,
+}
+
+/// Paused sources of a node, per index ID.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PausedSources {
+    /// IDs of the paused sources, per index ID.
+    pub paused_sources: BTreeMap<String, Vec<String>>,
+}
+
+/// Checkpoint of a source.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceCheckpoint {
+    /// Last position indexed, per partition ID. The empty position stands for the beginning of
+    /// the partition.
+    pub checkpoint: BTreeMap<String, String>,
+}
+
+/// Request resetting the checkpoint of a source. When neither the positions nor the timestamp are
+/// set, the checkpoint is reset to the beginning of every partition.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResetSourceCheckpointRequest {
+    /// Last positions to consider indexed, per partition ID.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub positions: Option<BTreeMap<String, String>>,
+    /// Resumes from the records published after this timestamp, in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+}
+
+/// Throughput of a stage of an indexing pipeline, averaged over the last 30 seconds.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StageThroughput {
+    /// Stage of the pipeline: `indexer`, `uploader` or `publisher`.
+    pub stage: String,
+    /// Number of documents processed per second.
+    pub num_docs_per_sec: f64,
+    /// Megabytes of documents for the indexer, megabytes of splits for the other stages.
+    pub num_mb_per_sec: f64,
+    /// Average fill ratio of the input of the stage, between 0 and 1.
+    pub saturation: f64,
+}
+
+/// Throughput of an indexing pipeline running on a node.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineThroughput {
+    /// ID of the index.
+    pub index_id: String,
+    /// ID of the source of the pipeline.
+    pub source_id: String,
+    /// Throughput of the stages, from upstream to downstream.
+    pub stages: Vec<StageThroughput>,
+    /// Stage slowing down the pipeline, `source` when the pipeline keeps up with its source.
+    pub bottleneck: String,
+}
+
+/// Indexing pipelines of an index running on a node.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineThroughputs {
+    /// Throughput of each pipeline.
+    pub pipelines: Vec<PipelineThroughput>,
+}
+
+/// Export request sent to the REST export API.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportQuery {
+    /// Query text, in the tantivy query language.
+    pub query: String,
+    /// Fields to search on. The default search fields of the index are used if empty.
+    pub search_fields: Vec<String>,
+    /// If set, restricts the export to documents with a `timestamp >= start_timestamp`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_timestamp: Option<i64>,
+    /// If set, restricts the export to documents with a `timestamp < end_timestamp`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_timestamp: Option<i64>,
+    /// Maximum number of documents to export.
+    pub max_hits: u64,
+    /// Columns to export. All the stored fields are exported if empty.
+    pub fields: Vec<String>,
+    /// Storage URI of the directory where the Parquet files are written.
+    pub output_uri: String,
+}
+
+impl ExportQuery {
+    /// Creates an export query writing up to 100,000 documents matching `query` to
+    /// `output_uri`.
+    pub fn new(query: impl Into<String>, output_uri: impl Into<String>) -> Self {
+        ExportQuery {
+            query: query.into(),
+            search_fields: Vec::new(),
+            start_timestamp: None,
+            end_timestamp: None,
+            max_hits: 100_000,
+            fields: Vec::new(),
+            output_uri: output_uri.into(),
+        }
+    }
+}
+
+/// Kind of long-running operation run by a job.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    /// Export of documents to Parquet files.
+    Export,
+    /// Deletion of the dangling splits of an index.
+    GarbageCollection,
+}
+
+/// State of a job.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    /// The job is still running.
+    Running,
+    /// The job completed.
+    Succeeded,
+    /// The job stopped on an error.
+    Failed,
+    /// The job was cancelled.
+    Cancelled,
+}
+
+/// Record of a job, returned by the REST jobs API.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    /// ID of the job.
+    pub job_id: String,
+    /// ID of the index the job operates on.
+    pub index_id: String,
+    /// Kind of the job.
+    pub kind: JobKind,
+    /// State of the job.
+    pub state: JobState,
+    /// Progress of the job, in percent.
+    pub progress: f64,
+    /// ID of the node running the job.
+    pub node_id: String,
+    /// Time at which the job was created, as a Unix timestamp in seconds.
+    pub create_timestamp: i64,
+    /// Time at which the job was last updated, as a Unix timestamp in seconds.
+    pub update_timestamp: i64,
+    /// Details specific to the kind of the job, such as the files written by an export.
+    #[serde(default)]
+    pub details: serde_json::Value,
+    /// Error that stopped the job, if it failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_query_params() {
+        let mut search_query = SearchQuery::new("title:snoopy");
+        assert_eq!(
+            search_query.query_params(),
+            vec![
+                ("query", "title:snoopy".to_string()),
+                ("maxHits", "20".to_string()),
+                ("startOffset", "0".to_string()),
+            ]
+        );
+        search_query.search_fields = vec!["title".to_string(), "body".to_string()];
+        search_query.start_timestamp = Some("now-1h".to_string());
+        search_query.sort_by_field = Some("-timestamp".to_string());
+        let query_params = search_query.query_params();
+        assert!(query_params.contains(&("searchField", "title,body".to_string())));
+        assert!(query_params.contains(&("startTimestamp", "now-1h".to_string())));
+        assert!(query_params.contains(&("sortByField", "-timestamp".to_string())));
+    }
+
+    #[test]
+    fn test_search_stream_query_params() {
+        let mut search_stream_query = SearchStreamQuery::new("*", "timestamp");
+        search_stream_query.output_format = StreamOutputFormat::ClickHouseRowBinary;
+        assert_eq!(
+            search_stream_query.query_params(),
+            vec![
+                ("query", "*".to_string()),
+                ("fastField", "timestamp".to_string()),
+                ("outputFormat", "clickHouseRowBinary".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_query_serialization() {
+        let mut export_query = ExportQuery::new("severity:ERROR", "s3://my-bucket/exports");
+        export_query.fields = vec!["timestamp".to_string()];
+        assert_eq!(
+            serde_json::to_value(&export_query).unwrap(),
+            serde_json::json!({
+                "query": "severity:ERROR",
+                "searchFields": [],
+                "maxHits": 100_000,
+                "fields": ["timestamp"],
+                "outputUri": "s3://my-bucket/exports",
+            })
+        );
+    }
+}
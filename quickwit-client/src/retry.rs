@@ -0,0 +1,166 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::future::Future;
+use std::time::Duration;
+
+use rand::Rng;
+use tracing::warn;
+
+use crate::ClientResult;
+
+/// Policy applied to the requests failing with a retryable error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of times a request is sent again after its first attempt.
+    pub max_num_retries: usize,
+    /// Delay before the first retry.
+    pub base_delay: Duration,
+    /// Upper bound of the delay between two attempts.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_num_retries: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub fn no_retry() -> Self {
+        RetryPolicy {
+            max_num_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the delay before the retry following the attempt `num_attempts`, using an
+    /// exponential backoff with full jitter.
+    fn delay(&self, num_attempts: usize) -> Duration {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(1u32 << num_attempts.min(16))
+            .min(self.max_delay);
+        if ceiling.is_zero() {
+            return ceiling;
+        }
+        rand::thread_rng().gen_range(Duration::ZERO..=ceiling)
+    }
+
+    /// Calls `f` until it succeeds, fails with an error that is not retryable, or the maximum
+    /// number of retries is reached.
+    pub(crate) async fn retry<T, F, Fut>(&self, f: F) -> ClientResult<T>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = ClientResult<T>>,
+    {
+        let mut num_attempts = 0;
+        loop {
+            match f().await {
+                Err(error) if error.is_retryable() && num_attempts < self.max_num_retries => {
+                    let delay = self.delay(num_attempts);
+                    num_attempts += 1;
+                    warn!(
+                        error = ?error,
+                        num_attempts = num_attempts,
+                        delay = ?delay,
+                        "Request failed, retrying."
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::ClientError;
+
+    fn test_policy(max_num_retries: usize) -> RetryPolicy {
+        RetryPolicy {
+            max_num_retries,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        let num_calls = AtomicUsize::new(0);
+        let result = test_policy(3)
+            .retry(|| async {
+                if num_calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                    return Err(ClientError::Transport("connection refused".to_string()));
+                }
+                Ok(42)
+            })
+            .await;
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(num_calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up() {
+        let num_calls = AtomicUsize::new(0);
+        let result: ClientResult<()> = test_policy(2)
+            .retry(|| async {
+                num_calls.fetch_add(1, Ordering::SeqCst);
+                Err(ClientError::Transport("connection refused".to_string()))
+            })
+            .await;
+        assert!(matches!(result, Err(ClientError::Transport(_))));
+        assert_eq!(num_calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_does_not_retry_non_retryable_errors() {
+        let num_calls = AtomicUsize::new(0);
+        let result: ClientResult<()> = test_policy(3)
+            .retry(|| async {
+                num_calls.fetch_add(1, Ordering::SeqCst);
+                Err(ClientError::MissingGrpcEndpoint)
+            })
+            .await;
+        assert!(matches!(result, Err(ClientError::MissingGrpcEndpoint)));
+        assert_eq!(num_calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let retry_policy = RetryPolicy {
+            max_num_retries: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+        };
+        for num_attempts in 0..10 {
+            assert!(retry_policy.delay(num_attempts) <= Duration::from_millis(500));
+        }
+        assert!(retry_policy.delay(0) <= Duration::from_millis(100));
+    }
+}
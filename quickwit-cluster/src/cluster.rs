@@ -17,15 +17,19 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, Instant};
 
 use quickwit_swim::prelude::{
     ArtilleryError, ArtilleryMember, ArtilleryMemberEvent, ArtilleryMemberState,
     Cluster as ArtilleryCluster, ClusterConfig as ArtilleryClusterConfig,
 };
+use serde::{Deserialize, Serialize};
 use tokio::sync::watch;
 use tokio::time::timeout;
 use tokio_stream::wrappers::WatchStream;
@@ -40,6 +44,108 @@ const CLUSTER_ID: &str = "quickwit-cluster";
 
 const CLUSTER_EVENT_TIMEOUT: Duration = Duration::from_millis(200);
 
+/// Prefix of the payload messages through which the nodes report their load.
+const NODE_LOAD_PAYLOAD_PREFIX: &str = "load:";
+
+/// Prefix of the payload messages through which the nodes publish their key-value state.
+const NODE_STATE_PAYLOAD_PREFIX: &str = "state:";
+
+/// Key of the node state holding the services run by the node, e.g. `indexer,leaf-searcher`.
+const AVAILABLE_SERVICES_KEY: &str = "available_services";
+
+/// Interval at which the nodes send their state to the other members again, so that the members
+/// that missed an update catch up: the payload messages are not acknowledged.
+const NODE_STATE_RESEND_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Time after which the load reported by a node is ignored, e.g. when the node stopped
+/// reporting it.
+const NODE_LOAD_TTL: Duration = Duration::from_secs(10);
+
+/// A service run by a Quickwit node.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum QuickwitService {
+    /// Runs the indexing pipelines of the node's indexes.
+    Indexer,
+    /// Serves the search API: dispatches the searches to the leaf searchers of the cluster and
+    /// merges their results.
+    RootSearcher,
+    /// Searches the splits assigned by the root searchers.
+    LeafSearcher,
+    /// Runs the periodic maintenance tasks of the node's indexes, such as garbage collection and
+    /// retention enforcement. Only the elected janitor of the cluster runs them.
+    Janitor,
+    /// Serves the API managing the indexes of the cluster: source checkpoints, garbage
+    /// collection, and jobs.
+    ControlPlane,
+}
+
+impl QuickwitService {
+    /// Returns the name of the service.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuickwitService::Indexer => "indexer",
+            QuickwitService::RootSearcher => "root-searcher",
+            QuickwitService::LeafSearcher => "leaf-searcher",
+            QuickwitService::Janitor => "janitor",
+            QuickwitService::ControlPlane => "control-plane",
+        }
+    }
+
+    /// Returns all the services.
+    pub fn all() -> HashSet<QuickwitService> {
+        [
+            QuickwitService::Indexer,
+            QuickwitService::RootSearcher,
+            QuickwitService::LeafSearcher,
+            QuickwitService::Janitor,
+            QuickwitService::ControlPlane,
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    /// Returns the services of a searcher, which serves the search API and searches splits.
+    pub fn searcher() -> HashSet<QuickwitService> {
+        [QuickwitService::RootSearcher, QuickwitService::LeafSearcher]
+            .into_iter()
+            .collect()
+    }
+
+    /// Parses a service name, or `searcher`, which stands for the root and leaf searchers.
+    pub fn parse_services(services_str: &str) -> anyhow::Result<HashSet<QuickwitService>> {
+        if services_str == "searcher" {
+            return Ok(QuickwitService::searcher());
+        }
+        let service = services_str.parse::<QuickwitService>()?;
+        Ok([service].into_iter().collect())
+    }
+}
+
+impl fmt::Display for QuickwitService {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for QuickwitService {
+    type Err = anyhow::Error;
+
+    fn from_str(service_str: &str) -> anyhow::Result<Self> {
+        match service_str {
+            "indexer" => Ok(QuickwitService::Indexer),
+            "root-searcher" => Ok(QuickwitService::RootSearcher),
+            "leaf-searcher" => Ok(QuickwitService::LeafSearcher),
+            "janitor" => Ok(QuickwitService::Janitor),
+            "control-plane" => Ok(QuickwitService::ControlPlane),
+            _ => anyhow::bail!(
+                "Service `{}` does not exist. Available services are `indexer`, `root-searcher`, \
+                 `leaf-searcher`, `janitor`, and `control-plane`.",
+                service_str
+            ),
+        }
+    }
+}
+
 /// A member information.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Member {
@@ -51,19 +157,176 @@ pub struct Member {
 
     /// If true, it means self.
     pub is_self: bool,
+
+    /// Services run by the member, empty until the member published its state.
+    pub available_services: HashSet<QuickwitService>,
+}
+
+impl Member {
+    /// Returns true if the member runs the given service.
+    pub fn has_service(&self, service: QuickwitService) -> bool {
+        self.available_services.contains(&service)
+    }
+}
+
+/// Split search load of a searcher, reported periodically to the other members of the cluster.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeLoad {
+    /// Number of split searches waiting to be admitted.
+    pub num_queued_split_searches: u64,
+    /// Number of split searches running.
+    pub num_ongoing_split_searches: u64,
+}
+
+impl NodeLoad {
+    /// Returns the number of split searches queued or running on the node.
+    pub fn num_split_searches(&self) -> u64 {
+        self.num_queued_split_searches + self.num_ongoing_split_searches
+    }
+}
+
+/// Last load reported by each node, keyed by node ID, along with the time it was received.
+type NodeLoads = Arc<RwLock<HashMap<String, (NodeLoad, Instant)>>>;
+
+/// Key-value state published by a node to the other members of the cluster.
+pub type NodeState = BTreeMap<String, String>;
+
+/// Last state published by each node, keyed by node ID.
+type NodeStates = Arc<RwLock<HashMap<String, NodeState>>>;
+
+/// Records the load reported by a member in a payload message.
+fn record_node_load(
+    node_loads: &NodeLoads,
+    artillery_member: &ArtilleryMember,
+    node_load_json: &str,
+) {
+    let node_id = artillery_member.node_id();
+    match serde_json::from_str::<NodeLoad>(node_load_json) {
+        Ok(node_load) => {
+            node_loads
+                .write()
+                .expect("Node loads lock should not be poisoned.")
+                .insert(node_id, (node_load, Instant::now()));
+        }
+        Err(error) => warn!(node_id = %node_id, error = ?error, "Failed to parse node load."),
+    }
+}
+
+/// Records the state published by a member in a payload message. Returns true if the state
+/// changed.
+fn record_node_state(
+    node_states: &NodeStates,
+    artillery_member: &ArtilleryMember,
+    node_state_json: &str,
+) -> bool {
+    let node_id = artillery_member.node_id();
+    let node_state = match serde_json::from_str::<NodeState>(node_state_json) {
+        Ok(node_state) => node_state,
+        Err(error) => {
+            warn!(node_id = %node_id, error = ?error, "Failed to parse node state.");
+            return false;
+        }
+    };
+    let mut node_states_guard = node_states
+        .write()
+        .expect("Node states lock should not be poisoned.");
+    if node_states_guard.get(&node_id) == Some(&node_state) {
+        return false;
+    }
+    node_states_guard.insert(node_id, node_state);
+    true
+}
+
+/// Sends the state of the node to the given members.
+fn send_node_state(
+    artillery_cluster: &ArtilleryCluster,
+    node_id: &str,
+    node_states: &NodeStates,
+    target_node_ids: impl IntoIterator<Item = String>,
+) {
+    let node_state_json = match node_states
+        .read()
+        .expect("Node states lock should not be poisoned.")
+        .get(node_id)
+    {
+        Some(node_state) => {
+            serde_json::to_string(node_state).expect("Node state should be serializable.")
+        }
+        None => return,
+    };
+    let payload = format!("{}{}", NODE_STATE_PAYLOAD_PREFIX, node_state_json);
+    for target_node_id in target_node_ids {
+        artillery_cluster.send_payload(target_node_id, &payload);
+    }
+}
+
+/// Encodes the services run by a node as the value of [`AVAILABLE_SERVICES_KEY`].
+fn encode_available_services(available_services: &HashSet<QuickwitService>) -> String {
+    let mut services: Vec<QuickwitService> = available_services.iter().copied().collect();
+    services.sort();
+    services
+        .iter()
+        .map(QuickwitService::as_str)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Extracts the services run by a node from its state. The services unknown to the node, e.g.
+/// advertised by a more recent version, are ignored.
+fn decode_available_services(node_state_opt: Option<&NodeState>) -> HashSet<QuickwitService> {
+    let services_str =
+        match node_state_opt.and_then(|node_state| node_state.get(AVAILABLE_SERVICES_KEY)) {
+            Some(services_str) => services_str,
+            None => return HashSet::new(),
+        };
+    services_str
+        .split(',')
+        .filter(|service_str| !service_str.is_empty())
+        .flat_map(|service_str| {
+            service_str
+                .parse::<QuickwitService>()
+                .map_err(|error| warn!(error = %error, "Unknown service advertised."))
+        })
+        .collect()
+}
+
+/// Elects the leader of a service: the member with the lowest node ID among the members running
+/// the service. Every member elects the same leader once the member lists have converged, but two
+/// members may briefly consider themselves leaders while a node joins or leaves the cluster, so
+/// the tasks run by the leader must be idempotent.
+pub fn elect_service_leader(members: &[Member], service: QuickwitService) -> Option<&Member> {
+    members
+        .iter()
+        .filter(|member| member.has_service(service))
+        .min_by(|left, right| {
+            (&left.node_id, left.listen_addr).cmp(&(&right.node_id, right.listen_addr))
+        })
 }
 
 /// This is an implementation of a cluster using the SWIM protocol.
 pub struct Cluster {
+    /// The ID of the node.
+    pub node_id: String,
+
     /// A socket address that represents itself.
     pub listen_addr: SocketAddr,
 
+    /// Services run by the node and advertised to the cluster.
+    pub available_services: HashSet<QuickwitService>,
+
     /// The actual cluster that implement the SWIM protocol.
-    artillery_cluster: ArtilleryCluster,
+    artillery_cluster: Arc<ArtilleryCluster>,
 
     /// A receiver(channel) for exchanging members in a cluster.
     members: watch::Receiver<Vec<Member>>,
 
+    /// Last load reported by each member, the node included.
+    node_loads: NodeLoads,
+
+    /// Last state published by each member, the node included. The services run by the members
+    /// are published in their state.
+    node_states: NodeStates,
+
     /// A stop flag of cluster monitoring task.
     /// Once the cluster is created, a task to monitor cluster events will be started.
     /// Nodes do not need to be monitored for events once they are detached from the cluster.
@@ -72,11 +335,15 @@ pub struct Cluster {
 }
 
 impl Cluster {
-    /// Create a cluster given a host key and a listen address.
+    /// Create a cluster given a host key, the services run by the node, and a listen address.
     /// When a cluster is created, the thread that monitors cluster events
     /// will be started at the same time.
-    pub fn new(node_id: String, listen_addr: SocketAddr) -> ClusterResult<Self> {
-        info!( node_id=?node_id, listen_addr=?listen_addr, "Create new cluster.");
+    pub fn new(
+        node_id: String,
+        available_services: HashSet<QuickwitService>,
+        listen_addr: SocketAddr,
+    ) -> ClusterResult<Self> {
+        info!( node_id=?node_id, available_services=?available_services, listen_addr=?listen_addr, "Create new cluster.");
         let config = ArtilleryClusterConfig {
             cluster_key: CLUSTER_ID.as_bytes().to_vec(),
             listen_addr,
@@ -97,11 +364,25 @@ impl Cluster {
 
         let (members_sender, members_receiver) = watch::channel(Vec::new());
 
+        let node_state: NodeState = [(
+            AVAILABLE_SERVICES_KEY.to_string(),
+            encode_available_services(&available_services),
+        )]
+        .into_iter()
+        .collect();
+        let node_states: NodeStates = Arc::new(RwLock::new(
+            [(node_id.clone(), node_state)].into_iter().collect(),
+        ));
+
         // Create cluster.
         let cluster = Cluster {
+            node_id: node_id.clone(),
             listen_addr,
-            artillery_cluster,
+            available_services: available_services.clone(),
+            artillery_cluster: Arc::new(artillery_cluster),
             members: members_receiver,
+            node_loads: Default::default(),
+            node_states,
             stop: Arc::new(AtomicBool::new(false)),
         };
 
@@ -110,6 +391,7 @@ impl Cluster {
             node_id,
             listen_addr,
             is_self: true,
+            available_services,
         };
         let initial_members: Vec<Member> = vec![member];
         if members_sender.send(initial_members).is_err() {
@@ -117,23 +399,94 @@ impl Cluster {
         }
 
         // Prepare to start a task that will monitor cluster events.
+        let task_node_id = cluster.node_id.clone();
         let task_listen_addr = cluster.listen_addr;
         let task_stop = cluster.stop.clone();
+        let task_node_loads = cluster.node_loads.clone();
+        let task_node_states = cluster.node_states.clone();
+        // The task must not keep the artillery cluster alive: dropping it makes the node leave
+        // the cluster.
+        let task_artillery_cluster: Weak<ArtilleryCluster> =
+            Arc::downgrade(&cluster.artillery_cluster);
 
         // Start to monitor the cluster events.
         tokio::task::spawn_blocking(move || {
+            let mut artillery_members: Vec<ArtilleryMember> = Vec::new();
+            let mut node_state_sent_at = Instant::now();
             loop {
+                if node_state_sent_at.elapsed() >= NODE_STATE_RESEND_INTERVAL {
+                    node_state_sent_at = Instant::now();
+                    if let Some(artillery_cluster) = task_artillery_cluster.upgrade() {
+                        let target_node_ids = artillery_members
+                            .iter()
+                            .filter(|member| member.is_remote() && is_alive(member))
+                            .map(ArtilleryMember::node_id);
+                        send_node_state(
+                            &artillery_cluster,
+                            &task_node_id,
+                            &task_node_states,
+                            target_node_ids,
+                        );
+                    }
+                }
                 match swim_event_rx.recv_timeout(CLUSTER_EVENT_TIMEOUT) {
-                    Ok((artillery_members, artillery_member_event)) => {
-                        log_artillery_event(artillery_member_event);
-                        let updated_memberlist: Vec<Member> = artillery_members
-                            .into_iter()
-                            .filter(|member| match member.state() {
-                                ArtilleryMemberState::Alive | ArtilleryMemberState::Suspect => true,
-                                ArtilleryMemberState::Down | ArtilleryMemberState::Left => false,
-                            })
-                            .map(|member| convert_member(member, task_listen_addr))
-                            .collect();
+                    Ok((updated_artillery_members, artillery_member_event)) => {
+                        artillery_members = updated_artillery_members;
+                        match &artillery_member_event {
+                            ArtilleryMemberEvent::Payload(artillery_member, message) => {
+                                // Load reports do not change the member list.
+                                if let Some(node_load_json) =
+                                    message.strip_prefix(NODE_LOAD_PAYLOAD_PREFIX)
+                                {
+                                    record_node_load(
+                                        &task_node_loads,
+                                        artillery_member,
+                                        node_load_json,
+                                    );
+                                    continue;
+                                }
+                                if let Some(node_state_json) =
+                                    message.strip_prefix(NODE_STATE_PAYLOAD_PREFIX)
+                                {
+                                    if !record_node_state(
+                                        &task_node_states,
+                                        artillery_member,
+                                        node_state_json,
+                                    ) {
+                                        continue;
+                                    }
+                                } else {
+                                    log_artillery_event(&artillery_member_event);
+                                }
+                            }
+                            ArtilleryMemberEvent::Joined(artillery_member)
+                            | ArtilleryMemberEvent::WentUp(artillery_member) => {
+                                log_artillery_event(&artillery_member_event);
+                                // The new member learns about the services of the node right
+                                // away rather than on the next resend.
+                                if let Some(artillery_cluster) = task_artillery_cluster.upgrade() {
+                                    send_node_state(
+                                        &artillery_cluster,
+                                        &task_node_id,
+                                        &task_node_states,
+                                        [artillery_member.node_id()],
+                                    );
+                                }
+                            }
+                            _ => log_artillery_event(&artillery_member_event),
+                        }
+                        let updated_memberlist: Vec<Member> = {
+                            let node_states_guard = task_node_states
+                                .read()
+                                .expect("Node states lock should not be poisoned.");
+                            artillery_members
+                                .iter()
+                                .filter(|member| is_alive(member))
+                                .map(|member| {
+                                    convert_member(member, task_listen_addr, &node_states_guard)
+                                })
+                                .collect()
+                        };
                         debug!(updated_memberlist=?updated_memberlist);
                         if members_sender.send(updated_memberlist).is_err() {
                             // Somehow the cluster has been dropped.
@@ -168,6 +521,79 @@ impl Cluster {
         self.members.borrow().clone()
     }
 
+    /// Returns true if the node is the current leader of the given service.
+    pub fn is_service_leader(&self, service: QuickwitService) -> bool {
+        elect_service_leader(&self.members.borrow(), service).map_or(false, |leader| leader.is_self)
+    }
+
+    /// Sets a key of the state of the node and publishes the state to the other members of the
+    /// cluster.
+    pub fn set_key_value(&self, key: &str, value: &str) {
+        self.node_states
+            .write()
+            .expect("Node states lock should not be poisoned.")
+            .entry(self.node_id.clone())
+            .or_default()
+            .insert(key.to_string(), value.to_string());
+        let target_node_ids: Vec<String> = self
+            .members
+            .borrow()
+            .iter()
+            .filter(|member| !member.is_self)
+            .map(|member| member.node_id.clone())
+            .collect();
+        send_node_state(
+            &self.artillery_cluster,
+            &self.node_id,
+            &self.node_states,
+            target_node_ids,
+        );
+    }
+
+    /// Returns the last state published by a member, the node included.
+    pub fn node_state(&self, node_id: &str) -> Option<NodeState> {
+        self.node_states
+            .read()
+            .expect("Node states lock should not be poisoned.")
+            .get(node_id)
+            .cloned()
+    }
+
+    /// Reports the load of the node to the other members of the cluster. The load is sent
+    /// directly to each member rather than gossiped, so it must be reported periodically.
+    pub fn broadcast_load(&self, node_load: NodeLoad) {
+        self.node_loads
+            .write()
+            .expect("Node loads lock should not be poisoned.")
+            .insert(self.node_id.clone(), (node_load, Instant::now()));
+        let payload = format!(
+            "{}{}",
+            NODE_LOAD_PAYLOAD_PREFIX,
+            serde_json::to_string(&node_load).expect("Node load should be serializable.")
+        );
+        for member in self
+            .members
+            .borrow()
+            .iter()
+            .filter(|member| !member.is_self)
+        {
+            self.artillery_cluster
+                .send_payload(member.node_id.clone(), &payload);
+        }
+    }
+
+    /// Returns the last load reported by each member, keyed by node ID. Members that have not
+    /// reported their load recently are left out.
+    pub fn node_loads(&self) -> HashMap<String, NodeLoad> {
+        self.node_loads
+            .read()
+            .expect("Node loads lock should not be poisoned.")
+            .iter()
+            .filter(|(_, (_, received_at))| received_at.elapsed() < NODE_LOAD_TTL)
+            .map(|(node_id, (node_load, _))| (node_id.clone(), *node_load))
+            .collect()
+    }
+
     /// Specify the address of a running node and join the cluster to which the node belongs.
     pub async fn add_peer_node(&self, peer_addr: SocketAddr) {
         if peer_addr != self.listen_addr {
@@ -204,23 +630,37 @@ impl Cluster {
     }
 }
 
-/// Convert the Artillery's member into Quickwit's one.
-fn convert_member(member: ArtilleryMember, self_listen_addr: SocketAddr) -> Member {
+fn is_alive(member: &ArtilleryMember) -> bool {
+    match member.state() {
+        ArtilleryMemberState::Alive | ArtilleryMemberState::Suspect => true,
+        ArtilleryMemberState::Down | ArtilleryMemberState::Left => false,
+    }
+}
+
+/// Convert the Artillery's member into Quickwit's one, with the services published in the state
+/// of the member.
+fn convert_member(
+    member: &ArtilleryMember,
+    self_listen_addr: SocketAddr,
+    node_states: &HashMap<String, NodeState>,
+) -> Member {
     let listen_addr = if let Some(addr) = member.remote_host() {
         addr
     } else {
         self_listen_addr
     };
-
+    let node_id = member.node_id();
+    let available_services = decode_available_services(node_states.get(&node_id));
     Member {
-        node_id: member.node_id(),
+        node_id,
         listen_addr,
         is_self: member.is_current(),
+        available_services,
     }
 }
 
 /// Output member event as log.
-fn log_artillery_event(artillery_member_event: ArtilleryMemberEvent) {
+fn log_artillery_event(artillery_member_event: &ArtilleryMemberEvent) {
     match artillery_member_event {
         ArtilleryMemberEvent::Joined(artillery_member) => {
             info!(node_id=?artillery_member.node_id(), remote_host=?artillery_member.remote_host(), "Joined.");
@@ -246,7 +686,7 @@ fn log_artillery_event(artillery_member_event: ArtilleryMemberEvent) {
 pub fn create_cluster_for_test_with_id(peer_uuid: String) -> anyhow::Result<Cluster> {
     let port = quickwit_common::net::find_available_port()?;
     let peer_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port);
-    let cluster = Cluster::new(peer_uuid, peer_addr)?;
+    let cluster = Cluster::new(peer_uuid, QuickwitService::searcher(), peer_addr)?;
     Ok(cluster)
 }
 
@@ -273,30 +713,120 @@ mod tests {
     async fn test_cluster_convert_member() {
         let node_id = Uuid::new_v4().to_string();
         let remote_host = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 0);
+        let node_states: HashMap<String, NodeState> = [(
+            node_id.clone(),
+            [(AVAILABLE_SERVICES_KEY.to_string(), "indexer".to_string())]
+                .into_iter()
+                .collect(),
+        )]
+        .into_iter()
+        .collect();
         {
-            let artillery_member =
-                ArtilleryMember::new(node_id.clone(), remote_host, 0, ArtilleryMemberState::Alive);
-
-            let member = convert_member(artillery_member, remote_host);
+            let artillery_member = ArtilleryMember::new(
+                "unknown-node".to_string(),
+                remote_host,
+                0,
+                ArtilleryMemberState::Alive,
+            );
+
+            let member = convert_member(&artillery_member, remote_host, &node_states);
             let expected_member = Member {
-                node_id: node_id.clone(),
+                node_id: "unknown-node".to_string(),
                 listen_addr: remote_host,
                 is_self: false,
+                available_services: HashSet::new(),
             };
             assert_eq!(member, expected_member);
         }
         {
             let artillery_member = ArtilleryMember::current(node_id.clone());
-            let member = convert_member(artillery_member, remote_host);
+            let member = convert_member(&artillery_member, remote_host, &node_states);
             let expected_member = Member {
                 node_id,
                 listen_addr: remote_host,
                 is_self: true,
+                available_services: [QuickwitService::Indexer].into_iter().collect(),
             };
             assert_eq!(member, expected_member);
         }
     }
 
+    #[test]
+    fn test_available_services_node_state() {
+        let available_services: HashSet<QuickwitService> =
+            [QuickwitService::LeafSearcher, QuickwitService::Indexer]
+                .into_iter()
+                .collect();
+        let services_str = encode_available_services(&available_services);
+        assert_eq!(services_str, "indexer,leaf-searcher");
+        let node_state = |services_str: &str| -> NodeState {
+            [(AVAILABLE_SERVICES_KEY.to_string(), services_str.to_string())]
+                .into_iter()
+                .collect()
+        };
+        assert_eq!(
+            decode_available_services(Some(&node_state(&services_str))),
+            available_services
+        );
+        assert_eq!(
+            decode_available_services(Some(&node_state("janitor,unknown"))),
+            [QuickwitService::Janitor].into_iter().collect()
+        );
+        assert!(decode_available_services(Some(&node_state(""))).is_empty());
+        assert!(decode_available_services(Some(&NodeState::new())).is_empty());
+        assert!(decode_available_services(None).is_empty());
+    }
+
+    #[test]
+    fn test_parse_quickwit_service() {
+        for service in QuickwitService::all() {
+            assert_eq!(
+                service.as_str().parse::<QuickwitService>().unwrap(),
+                service
+            );
+        }
+        assert!("searcher".parse::<QuickwitService>().is_err());
+        assert_eq!(
+            QuickwitService::parse_services("searcher").unwrap(),
+            QuickwitService::searcher()
+        );
+        assert_eq!(
+            QuickwitService::parse_services("control-plane").unwrap(),
+            [QuickwitService::ControlPlane].into_iter().collect()
+        );
+        assert!(QuickwitService::parse_services("unknown").is_err());
+    }
+
+    #[test]
+    fn test_elect_service_leader() {
+        let member = |node_id: &str, port: u16, services: &[QuickwitService]| Member {
+            node_id: node_id.to_string(),
+            listen_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port),
+            is_self: false,
+            available_services: services.iter().copied().collect(),
+        };
+        let members = vec![
+            member("node-3", 3, &[QuickwitService::Janitor]),
+            member("node-1", 1, &[QuickwitService::LeafSearcher]),
+            member("node-2", 22, &[QuickwitService::Janitor]),
+            member(
+                "node-2",
+                21,
+                &[QuickwitService::Indexer, QuickwitService::Janitor],
+            ),
+        ];
+        let leader = elect_service_leader(&members, QuickwitService::Janitor).unwrap();
+        assert_eq!(leader.node_id, "node-2");
+        assert_eq!(leader.listen_addr.port(), 21);
+        assert_eq!(
+            elect_service_leader(&members, QuickwitService::LeafSearcher)
+                .unwrap()
+                .node_id,
+            "node-1"
+        );
+        assert!(elect_service_leader(&members[..1], QuickwitService::Indexer).is_none());
+    }
+
     #[tokio::test]
     async fn test_cluster_single_node() -> anyhow::Result<()> {
         let cluster = create_cluster_for_test()?;
@@ -308,6 +838,8 @@ mod tests {
             .collect();
         let expected_members = vec![cluster.listen_addr];
         assert_eq!(members, expected_members);
+        assert!(cluster.is_service_leader(QuickwitService::LeafSearcher));
+        assert!(!cluster.is_service_leader(QuickwitService::Janitor));
 
         cluster.leave().await;
         Ok(())
@@ -359,6 +891,90 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_cluster_node_state() -> anyhow::Result<()> {
+        let cluster1 = create_cluster_for_test()?;
+        let port = quickwit_common::net::find_available_port()?;
+        let cluster2 = Cluster::new(
+            Uuid::new_v4().to_string(),
+            [QuickwitService::Indexer].into_iter().collect(),
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port),
+        )?;
+        cluster2.add_peer_node(cluster1.listen_addr).await;
+        let ten_secs = Duration::from_secs(10);
+        let has_services = |members: &Vec<Member>| {
+            members.len() == 2
+                && members
+                    .iter()
+                    .all(|member| !member.available_services.is_empty())
+        };
+        for cluster in [&cluster1, &cluster2] {
+            cluster.wait_for_members(has_services, ten_secs).await?;
+        }
+        let cluster1_members = cluster1.members();
+        let member2 = cluster1_members
+            .iter()
+            .find(|member| member.node_id == cluster2.node_id)
+            .unwrap();
+        assert_eq!(
+            member2.available_services,
+            [QuickwitService::Indexer].into_iter().collect()
+        );
+        let cluster2_members = cluster2.members();
+        let member1 = cluster2_members
+            .iter()
+            .find(|member| member.node_id == cluster1.node_id)
+            .unwrap();
+        assert_eq!(member1.available_services, QuickwitService::searcher());
+
+        cluster1.set_key_value("key", "value");
+        assert_eq!(
+            cluster1.node_state(&cluster1.node_id).unwrap().get("key"),
+            Some(&"value".to_string())
+        );
+        timeout(ten_secs, async {
+            while cluster2
+                .node_state(&cluster1.node_id)
+                .and_then(|node_state| node_state.get("key").cloned())
+                .is_none()
+            {
+                sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cluster_broadcast_load() -> anyhow::Result<()> {
+        let cluster1 = create_cluster_for_test()?;
+        let cluster2 = create_cluster_for_test()?;
+        cluster2.add_peer_node(cluster1.listen_addr).await;
+        let ten_secs = Duration::from_secs(10);
+        for cluster in [&cluster1, &cluster2] {
+            cluster
+                .wait_for_members(|members| members.len() == 2, ten_secs)
+                .await?;
+        }
+        let node_load = NodeLoad {
+            num_queued_split_searches: 3,
+            num_ongoing_split_searches: 8,
+        };
+        cluster1.broadcast_load(node_load);
+        assert_eq!(
+            cluster1.node_loads(),
+            HashMap::from([(cluster1.node_id.clone(), node_load)])
+        );
+        timeout(ten_secs, async {
+            while cluster2.node_loads().get(&cluster1.node_id) != Some(&node_load) {
+                sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await?;
+        assert_eq!(node_load.num_split_searches(), 11);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_cluster_rejoin_with_different_id_issue_1018() -> anyhow::Result<()> {
         quickwit_common::setup_logging_for_tests();
@@ -395,7 +1011,11 @@ mod tests {
 
         sleep(Duration::from_secs(3)).await;
 
-        let cluster2 = Cluster::new("newid".to_string(), cluster2_listen_addr)?;
+        let cluster2 = Cluster::new(
+            "newid".to_string(),
+            QuickwitService::all(),
+            cluster2_listen_addr,
+        )?;
         cluster2.add_peer_node(cluster1.listen_addr).await;
 
         for _ in 0..4_000 {
@@ -460,10 +1080,18 @@ mod tests {
 
         sleep(Duration::from_secs(3)).await;
 
-        let cluster2 = Cluster::new("newid".to_string(), cluster2_listen_addr)?;
+        let cluster2 = Cluster::new(
+            "newid".to_string(),
+            QuickwitService::all(),
+            cluster2_listen_addr,
+        )?;
         cluster2.add_peer_node(cluster1.listen_addr).await;
 
-        let cluster3 = Cluster::new("newid2".to_string(), cluster3_listen_addr)?;
+        let cluster3 = Cluster::new(
+            "newid2".to_string(),
+            QuickwitService::all(),
+            cluster3_listen_addr,
+        )?;
         cluster3.add_peer_node(cluster2.listen_addr).await;
 
         sleep(Duration::from_secs(10)).await;
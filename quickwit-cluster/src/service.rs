@@ -31,10 +31,19 @@ use crate::error::ClusterError;
 /// Convert the member state to the protobuf one.
 impl From<Member> for PMember {
     fn from(member: Member) -> Self {
+        let mut available_services: Vec<String> = member
+            .available_services
+            .iter()
+            .map(|service| service.to_string())
+            .collect();
+        available_services.sort();
         PMember {
             id: member.node_id.to_string(),
             listen_address: member.listen_addr.to_string(),
             is_self: member.is_self,
+            available_services,
+            num_queued_split_searches: 0,
+            num_ongoing_split_searches: 0,
         }
     }
 }
@@ -71,11 +80,19 @@ impl ClusterService for ClusterServiceImpl {
         &self,
         _request: ListMembersRequest,
     ) -> Result<ListMembersResponse, ClusterError> {
+        let node_loads = self.cluster.node_loads();
         let members = self
             .cluster
             .members()
             .into_iter()
-            .map(PMember::from)
+            .map(|member| {
+                let node_load = node_loads.get(&member.node_id).copied().unwrap_or_default();
+                PMember {
+                    num_queued_split_searches: node_load.num_queued_split_searches,
+                    num_ongoing_split_searches: node_load.num_ongoing_split_searches,
+                    ..PMember::from(member)
+                }
+            })
             .collect();
         Ok(ListMembersResponse { members })
     }
@@ -97,7 +114,7 @@ mod tests {
     use quickwit_proto::Member as PMember;
     use uuid::Uuid;
 
-    use crate::cluster::Member;
+    use crate::cluster::{Member, QuickwitService};
 
     #[tokio::test]
     async fn test_cluster_convert_proto_member() {
@@ -109,6 +126,9 @@ mod tests {
             node_id: host_id.clone(),
             listen_addr,
             is_self,
+            available_services: [QuickwitService::LeafSearcher, QuickwitService::Indexer]
+                .into_iter()
+                .collect(),
         };
         println!("member={:?}", member);
 
@@ -119,6 +139,9 @@ mod tests {
             id: host_id,
             listen_address: listen_addr.to_string(),
             is_self,
+            available_services: vec!["indexer".to_string(), "leaf-searcher".to_string()],
+            num_queued_split_searches: 0,
+            num_ongoing_split_searches: 0,
         };
         println!("expected={:?}", expected);
 
@@ -20,6 +20,8 @@
 mod checklist;
 mod coolid;
 pub mod fs;
+pub mod log_filter;
+pub mod memory_usage;
 pub mod metrics;
 pub mod net;
 pub mod rand;
@@ -0,0 +1,141 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Mutex;
+
+use anyhow::Context;
+use once_cell::sync::OnceCell;
+
+type SetDirectivesFn = Box<dyn Fn(&str) -> anyhow::Result<()> + Send + Sync>;
+
+/// The log filter of the process, expressed as `RUST_LOG` directives, e.g.
+/// `quickwit=info,quickwit_search=debug`.
+struct LogFilter {
+    set_directives_fn: SetDirectivesFn,
+    default_directives: String,
+    directives: Mutex<String>,
+}
+
+impl LogFilter {
+    fn new(set_directives_fn: SetDirectivesFn, default_directives: String) -> Self {
+        LogFilter {
+            set_directives_fn,
+            directives: Mutex::new(default_directives.clone()),
+            default_directives,
+        }
+    }
+
+    fn directives(&self) -> String {
+        self.directives
+            .lock()
+            .expect("Log filter lock should not be poisoned.")
+            .clone()
+    }
+
+    fn set_directives(&self, directives: &str) -> anyhow::Result<String> {
+        let mut current_directives = self
+            .directives
+            .lock()
+            .expect("Log filter lock should not be poisoned.");
+        (self.set_directives_fn)(directives)?;
+        Ok(std::mem::replace(
+            &mut *current_directives,
+            directives.to_string(),
+        ))
+    }
+}
+
+static LOG_FILTER: OnceCell<LogFilter> = OnceCell::new();
+
+/// Registers the function changing the log filter of the process, along with the directives
+/// set at startup.
+pub fn set_log_filter_reloader<F>(set_directives_fn: F, default_directives: String)
+where F: Fn(&str) -> anyhow::Result<()> + Send + Sync + 'static {
+    let log_filter = LogFilter::new(Box::new(set_directives_fn), default_directives);
+    if LOG_FILTER.set(log_filter).is_err() {
+        tracing::error!("Log filter reloader is already set.");
+    }
+}
+
+/// Returns the directives of the log filter, if it can change at runtime.
+pub fn log_filter_directives() -> Option<String> {
+    LOG_FILTER.get().map(LogFilter::directives)
+}
+
+/// Sets the directives of the log filter and returns the previous ones.
+pub fn set_log_filter_directives(directives: &str) -> anyhow::Result<String> {
+    LOG_FILTER
+        .get()
+        .context("The log filter of this process cannot change at runtime.")?
+        .set_directives(directives)
+}
+
+/// Restores the directives of the log filter set at startup.
+pub fn reset_log_filter_directives() -> anyhow::Result<String> {
+    let log_filter = LOG_FILTER
+        .get()
+        .context("The log filter of this process cannot change at runtime.")?;
+    log_filter.set_directives(&log_filter.default_directives)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn test_log_filter_set_directives() {
+        let applied_directives = Arc::new(Mutex::new(Vec::new()));
+        let applied_directives_clone = applied_directives.clone();
+        let log_filter = LogFilter::new(
+            Box::new(move |directives: &str| {
+                if directives.contains('!') {
+                    anyhow::bail!("Invalid directives.");
+                }
+                applied_directives_clone
+                    .lock()
+                    .unwrap()
+                    .push(directives.to_string());
+                Ok(())
+            }),
+            "quickwit=info".to_string(),
+        );
+        assert_eq!(log_filter.directives(), "quickwit=info");
+
+        let previous_directives = log_filter
+            .set_directives("quickwit=info,quickwit_search=debug")
+            .unwrap();
+        assert_eq!(previous_directives, "quickwit=info");
+        assert_eq!(
+            log_filter.directives(),
+            "quickwit=info,quickwit_search=debug"
+        );
+
+        assert!(log_filter.set_directives("quickwit=!").is_err());
+        assert_eq!(
+            log_filter.directives(),
+            "quickwit=info,quickwit_search=debug"
+        );
+        assert_eq!(
+            *applied_directives.lock().unwrap(),
+            vec!["quickwit=info,quickwit_search=debug".to_string()]
+        );
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::fmt;
+
+use once_cell::sync::Lazy;
+use prometheus::{IntGauge, IntGaugeVec};
+
+use crate::metrics::new_gauge_vec;
+
+/// Components of a Quickwit node whose memory usage is accounted for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MemoryComponent {
+    /// Split footers cached by the searcher.
+    SplitFooterCache,
+    /// Fast field slices cached by the searcher.
+    FastFieldCache,
+    /// Slices cached by the caching directories of the splits being searched.
+    CachingDirectories,
+    /// Split footers held by in-flight leaf searches.
+    LeafSearch,
+    /// Heaps of the index writers of the splits being indexed.
+    IndexerHeaps,
+    /// Documents buffered by in-flight fetch docs requests.
+    FetchDocs,
+}
+
+impl MemoryComponent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemoryComponent::SplitFooterCache => "split_footer_cache",
+            MemoryComponent::FastFieldCache => "fast_field_cache",
+            MemoryComponent::CachingDirectories => "caching_directories",
+            MemoryComponent::LeafSearch => "leaf_search",
+            MemoryComponent::IndexerHeaps => "indexer_heaps",
+            MemoryComponent::FetchDocs => "fetch_docs",
+        }
+    }
+
+    pub fn all() -> [MemoryComponent; 6] {
+        [
+            MemoryComponent::SplitFooterCache,
+            MemoryComponent::FastFieldCache,
+            MemoryComponent::CachingDirectories,
+            MemoryComponent::LeafSearch,
+            MemoryComponent::IndexerHeaps,
+            MemoryComponent::FetchDocs,
+        ]
+    }
+
+    fn gauge(&self) -> IntGauge {
+        MEMORY_USAGE.with_label_values(&[self.as_str()])
+    }
+}
+
+impl fmt::Display for MemoryComponent {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.as_str())
+    }
+}
+
+static MEMORY_USAGE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    new_gauge_vec(
+        "quickwit_memory_usage",
+        "Memory used by the node, in bytes, per component.",
+        &["component"],
+    )
+});
+
+/// Returns the number of bytes currently used by `component`.
+pub fn memory_usage(component: MemoryComponent) -> i64 {
+    component.gauge().get()
+}
+
+/// Accounts for the memory used by a component, until the guard is dropped.
+pub struct MemoryUsageGuard {
+    gauge: IntGauge,
+    num_bytes: usize,
+}
+
+impl MemoryUsageGuard {
+    pub fn new(component: MemoryComponent, num_bytes: usize) -> Self {
+        let gauge = component.gauge();
+        gauge.add(num_bytes as i64);
+        MemoryUsageGuard { gauge, num_bytes }
+    }
+
+    /// Returns the number of bytes accounted for by this guard.
+    pub fn num_bytes(&self) -> usize {
+        self.num_bytes
+    }
+
+    pub fn add(&mut self, num_bytes: usize) {
+        self.num_bytes += num_bytes;
+        self.gauge.add(num_bytes as i64);
+    }
+
+    pub fn sub(&mut self, num_bytes: usize) {
+        let num_bytes = num_bytes.min(self.num_bytes);
+        self.num_bytes -= num_bytes;
+        self.gauge.sub(num_bytes as i64);
+    }
+}
+
+impl Drop for MemoryUsageGuard {
+    fn drop(&mut self) {
+        self.gauge.sub(self.num_bytes as i64);
+    }
+}
+
+impl fmt::Debug for MemoryUsageGuard {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("MemoryUsageGuard")
+            .field("num_bytes", &self.num_bytes)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_usage_guard() {
+        let initial_memory_usage = memory_usage(MemoryComponent::FetchDocs);
+        let mut memory_usage_guard = MemoryUsageGuard::new(MemoryComponent::FetchDocs, 100);
+        assert_eq!(
+            memory_usage(MemoryComponent::FetchDocs),
+            initial_memory_usage + 100
+        );
+        memory_usage_guard.add(50);
+        memory_usage_guard.sub(20);
+        assert_eq!(memory_usage_guard.num_bytes(), 130);
+        assert_eq!(
+            memory_usage(MemoryComponent::FetchDocs),
+            initial_memory_usage + 130
+        );
+        memory_usage_guard.sub(1_000);
+        assert_eq!(memory_usage_guard.num_bytes(), 0);
+        memory_usage_guard.add(10);
+        drop(memory_usage_guard);
+        assert_eq!(
+            memory_usage(MemoryComponent::FetchDocs),
+            initial_memory_usage
+        );
+    }
+}
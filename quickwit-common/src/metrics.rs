@@ -17,7 +17,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use prometheus::{Encoder, IntCounter, IntGauge, Opts, TextEncoder};
+use prometheus::{Encoder, IntCounter, IntGauge, IntGaugeVec, Opts, TextEncoder};
 
 pub fn new_counter(name: &str, description: &str) -> IntCounter {
     let counter =
@@ -32,6 +32,13 @@ pub fn new_gauge(name: &str, description: &str) -> IntGauge {
     gauge
 }
 
+pub fn new_gauge_vec(name: &str, description: &str, label_names: &[&str]) -> IntGaugeVec {
+    let gauge_vec = IntGaugeVec::new(Opts::new(name, description), label_names)
+        .expect("Failed to create gauge vec");
+    prometheus::register(Box::new(gauge_vec.clone())).expect("Failed to register gauge vec");
+    gauge_vec
+}
+
 pub fn metrics_handler() -> impl warp::Reply {
     let metric_families = prometheus::gather();
     let mut buffer = Vec::new();
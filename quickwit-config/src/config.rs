@@ -17,19 +17,25 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::{BTreeMap, HashSet};
 use std::ffi::OsStr;
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
 
 use anyhow::{bail, Context};
 use byte_unit::Byte;
-use json_comments::StripComments;
 use once_cell::sync::OnceCell;
 use quickwit_common::net::{get_socket_addr, parse_socket_addr_with_default_port};
 use quickwit_common::new_coolid;
 use quickwit_common::uri::Uri;
 use serde::{Deserialize, Serialize};
-use tracing::{info, warn};
+use tracing::{info, warn, Level};
+
+use crate::memory_limit::memory_limit_ratio_or;
+use crate::parser::{parse_json, parse_toml, parse_yaml};
+use crate::templating::render_config_template;
 
 static DEFAULT_DATA_DIR_PATH: &str = "./qwdata";
 
@@ -68,9 +74,39 @@ pub struct IndexerConfig {
     pub split_store_max_num_bytes: Byte,
     #[serde(default = "IndexerConfig::default_split_store_max_num_splits")]
     pub split_store_max_num_splits: usize,
+    /// Maximum heap size of an indexing pipeline. The heap size of the indexes exceeding it
+    /// is lowered to this value.
+    #[serde(default = "IndexerConfig::default_max_pipeline_heap_size")]
+    pub max_pipeline_heap_size: Byte,
+    /// Fraction of the scratch disk space in use above which the indexes of the indexer are
+    /// write-blocked.
+    #[serde(default = "IndexerConfig::default_disk_high_watermark")]
+    pub disk_high_watermark: f64,
+    /// Maximum size of the scratch directory of an indexing pipeline. The merges of a pipeline
+    /// exceeding it are deferred until the pipeline frees some scratch space.
+    #[serde(default = "IndexerConfig::default_max_pipeline_scratch_num_bytes")]
+    pub max_pipeline_scratch_num_bytes: Byte,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub disabled_sources: Vec<DisabledSourceConfig>,
+}
+
+/// A source of an index whose indexing pipeline is not run by the indexer.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DisabledSourceConfig {
+    pub index_id: String,
+    pub source_id: String,
 }
 
 impl IndexerConfig {
+    /// Returns true if the source `source_id` of the index `index_id` is disabled.
+    pub fn is_source_disabled(&self, index_id: &str, source_id: &str) -> bool {
+        self.disabled_sources.iter().any(|disabled_source| {
+            disabled_source.index_id == index_id && disabled_source.source_id == source_id
+        })
+    }
+
     fn default_split_store_max_num_bytes() -> Byte {
         Byte::from_bytes(100_000_000_000) // 100G
     }
@@ -79,11 +115,28 @@ impl IndexerConfig {
         1_000
     }
 
+    /// A quarter of the memory limit, 2G if the memory limit cannot be detected.
+    fn default_max_pipeline_heap_size() -> Byte {
+        memory_limit_ratio_or(0.25, Byte::from_bytes(2_000_000_000))
+    }
+
+    fn default_disk_high_watermark() -> f64 {
+        0.95
+    }
+
+    fn default_max_pipeline_scratch_num_bytes() -> Byte {
+        Byte::from_bytes(50_000_000_000) // 50G
+    }
+
     #[doc(hidden)]
     pub fn for_test() -> anyhow::Result<Self> {
         let indexer_config = IndexerConfig {
             split_store_max_num_bytes: Byte::from_bytes(1_000_000),
             split_store_max_num_splits: 3,
+            max_pipeline_heap_size: Byte::from_bytes(2_000_000_000),
+            disk_high_watermark: 1.0,
+            max_pipeline_scratch_num_bytes: Byte::from_bytes(10_000_000_000),
+            disabled_sources: Vec::new(),
         };
         Ok(indexer_config)
     }
@@ -94,14 +147,34 @@ impl Default for IndexerConfig {
         Self {
             split_store_max_num_bytes: Self::default_split_store_max_num_bytes(),
             split_store_max_num_splits: Self::default_split_store_max_num_splits(),
+            max_pipeline_heap_size: Self::default_max_pipeline_heap_size(),
+            disk_high_watermark: Self::default_disk_high_watermark(),
+            max_pipeline_scratch_num_bytes: Self::default_max_pipeline_scratch_num_bytes(),
+            disabled_sources: Vec::new(),
         }
     }
 }
 
-pub static SEARCHER_CONFIG_INSTANCE: once_cell::sync::OnceCell<SearcherConfig> = OnceCell::new();
+static SEARCHER_CONFIG_INSTANCE: OnceCell<RwLock<Arc<SearcherConfig>>> = OnceCell::new();
 
-pub fn get_searcher_config_instance() -> &'static SearcherConfig {
-    SEARCHER_CONFIG_INSTANCE.get_or_init(SearcherConfig::default)
+fn searcher_config_instance() -> &'static RwLock<Arc<SearcherConfig>> {
+    SEARCHER_CONFIG_INSTANCE.get_or_init(|| RwLock::new(Arc::new(SearcherConfig::default())))
+}
+
+/// Returns the searcher config of the node. The config may change when the node config is
+/// reloaded, so callers should not hold on to it.
+pub fn get_searcher_config_instance() -> Arc<SearcherConfig> {
+    searcher_config_instance()
+        .read()
+        .expect("Searcher config lock should not be poisoned.")
+        .clone()
+}
+
+/// Sets the searcher config of the node.
+pub fn set_searcher_config_instance(searcher_config: SearcherConfig) {
+    *searcher_config_instance()
+        .write()
+        .expect("Searcher config lock should not be poisoned.") = Arc::new(searcher_config);
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -112,20 +185,125 @@ pub struct SearcherConfig {
     pub split_footer_cache_capacity: Byte,
     #[serde(default = "SearcherConfig::default_max_num_concurrent_split_streams")]
     pub max_num_concurrent_split_streams: usize,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub virtual_indexes: Vec<VirtualIndexConfig>,
+    #[serde(default)]
+    pub grpc_compression: GrpcCompression,
+    #[serde(default = "SearcherConfig::default_max_grpc_message_size")]
+    pub max_grpc_message_size: Byte,
+    /// Interval at which the searcher refreshes its in-memory catalog of the published splits
+    /// of the searched indexes. `0` disables the catalog, and the splits are listed from the
+    /// metastore on every search.
+    #[serde(default)]
+    pub split_catalog_refresh_interval_secs: u64,
+    /// Interval at which the searcher saves the manifest of its split footer cache, used to warm
+    /// up its caches when it restarts. `0` disables the manifest, and the saving of the caches on
+    /// shutdown.
+    #[serde(default = "SearcherConfig::default_cache_manifest_save_interval_secs")]
+    pub cache_manifest_save_interval_secs: u64,
+    /// Maximum number of opened splits kept by the searcher so that repeated searches on the
+    /// same splits skip opening them. `0`, the default, disables the pool.
+    #[serde(default)]
+    pub split_reader_pool_capacity: usize,
+    /// Maximum size of the data cached by the opened splits of the pool. The least recently
+    /// used splits are dropped from the pool to stay under this size.
+    #[serde(default = "SearcherConfig::default_split_reader_pool_max_size")]
+    pub split_reader_pool_max_size: Byte,
+    /// Time after which an opened split is dropped from the pool, releasing the data cached
+    /// while searching it.
+    #[serde(default = "SearcherConfig::default_split_reader_pool_ttl_secs")]
+    pub split_reader_pool_ttl_secs: u64,
+    /// Number of searches that can run concurrently on an opened split of the pool.
+    #[serde(default = "SearcherConfig::default_split_reader_pool_num_searchers")]
+    pub split_reader_pool_num_searchers: usize,
+    /// A split whose search takes longer than this factor times the median search duration of
+    /// the splits of the same request is searched again concurrently. `0` disables speculative
+    /// searches.
+    #[serde(default)]
+    pub speculative_split_search_factor: f64,
+    /// Maximum number of splits searched concurrently by the leaf searches of the node. Waiting
+    /// split searches are admitted in weighted fair order across indexes.
+    #[serde(default = "SearcherConfig::default_max_num_concurrent_split_searches")]
+    pub max_num_concurrent_split_searches: usize,
+    /// Share of the split search capacity granted to an index relative to the other indexes,
+    /// keyed by index ID. Indexes default to a weight of 1.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub index_search_weights: BTreeMap<String, u32>,
+}
+
+/// Compression of the gRPC responses sent by the searcher to the other nodes of the cluster.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GrpcCompression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Default for GrpcCompression {
+    fn default() -> Self {
+        GrpcCompression::None
+    }
+}
+
+/// A virtual index is searchable as one index and is defined as the union of several
+/// concrete indexes with compatible doc mappings.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct VirtualIndexConfig {
+    pub index_id: String,
+    pub indexes: Vec<String>,
 }
 
 impl SearcherConfig {
+    /// Returns the config of the virtual index `index_id`, if it exists.
+    pub fn virtual_index(&self, index_id: &str) -> Option<&VirtualIndexConfig> {
+        self.virtual_indexes
+            .iter()
+            .find(|virtual_index| virtual_index.index_id == index_id)
+    }
+
+    /// 10% of the memory limit, 1G if the memory limit cannot be detected.
     fn default_fast_field_cache_capacity() -> Byte {
-        Byte::from_bytes(1_000_000_000) // 1G
+        memory_limit_ratio_or(0.1, Byte::from_bytes(1_000_000_000))
     }
 
+    /// 5% of the memory limit, 500M if the memory limit cannot be detected.
     fn default_split_footer_cache_capacity() -> Byte {
-        Byte::from_bytes(500_000_000) // 500M
+        memory_limit_ratio_or(0.05, Byte::from_bytes(500_000_000))
     }
 
     fn default_max_num_concurrent_split_streams() -> usize {
         100
     }
+
+    /// 4MiB, the default maximum size of a gRPC message.
+    fn default_max_grpc_message_size() -> Byte {
+        Byte::from_bytes(4 * 1024 * 1024)
+    }
+
+    fn default_cache_manifest_save_interval_secs() -> u64 {
+        300
+    }
+
+    /// 5% of the memory limit, 500M if the memory limit cannot be detected.
+    fn default_split_reader_pool_max_size() -> Byte {
+        memory_limit_ratio_or(0.05, Byte::from_bytes(500_000_000))
+    }
+
+    fn default_split_reader_pool_ttl_secs() -> u64 {
+        60
+    }
+
+    fn default_split_reader_pool_num_searchers() -> usize {
+        4
+    }
+
+    fn default_max_num_concurrent_split_searches() -> usize {
+        100
+    }
 }
 
 impl Default for SearcherConfig {
@@ -134,22 +312,212 @@ impl Default for SearcherConfig {
             fast_field_cache_capacity: Self::default_fast_field_cache_capacity(),
             split_footer_cache_capacity: Self::default_split_footer_cache_capacity(),
             max_num_concurrent_split_streams: Self::default_max_num_concurrent_split_streams(),
+            virtual_indexes: Vec::new(),
+            grpc_compression: GrpcCompression::default(),
+            max_grpc_message_size: Self::default_max_grpc_message_size(),
+            split_catalog_refresh_interval_secs: 0,
+            cache_manifest_save_interval_secs: Self::default_cache_manifest_save_interval_secs(),
+            split_reader_pool_capacity: 0,
+            split_reader_pool_max_size: Self::default_split_reader_pool_max_size(),
+            split_reader_pool_ttl_secs: Self::default_split_reader_pool_ttl_secs(),
+            split_reader_pool_num_searchers: Self::default_split_reader_pool_num_searchers(),
+            speculative_split_search_factor: 0.0,
+            max_num_concurrent_split_searches: Self::default_max_num_concurrent_split_searches(),
+            index_search_weights: BTreeMap::new(),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct S3Config {
     pub region: Option<String>,
     pub endpoint: Option<String>,
+    #[serde(default)]
+    pub http_client: StorageHttpClientConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Settings of the HTTP client shared by the storages of a backend. The connections of the
+/// client are reused across storages and requests.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct StorageHttpClientConfig {
+    /// Maximum number of idle connections kept open per host.
+    #[serde(default = "StorageHttpClientConfig::default_pool_max_idle_per_host")]
+    pub pool_max_idle_per_host: usize,
+    /// Time after which an idle connection is closed.
+    #[serde(default = "StorageHttpClientConfig::default_pool_idle_timeout_secs")]
+    pub pool_idle_timeout_secs: u64,
+    /// Interval between the TCP keep-alive probes, and the HTTP/2 pings if HTTP/2 is enabled.
+    /// `0` disables keep-alive.
+    #[serde(default = "StorageHttpClientConfig::default_keep_alive_interval_secs")]
+    pub keep_alive_interval_secs: u64,
+    /// Negotiates HTTP/2 with the endpoints supporting it, multiplexing the requests over
+    /// fewer connections.
+    #[serde(default)]
+    pub http2: bool,
+}
+
+impl StorageHttpClientConfig {
+    fn default_pool_max_idle_per_host() -> usize {
+        128
+    }
+
+    fn default_pool_idle_timeout_secs() -> u64 {
+        10
+    }
+
+    fn default_keep_alive_interval_secs() -> u64 {
+        60
+    }
+}
+
+impl Default for StorageHttpClientConfig {
+    fn default() -> Self {
+        Self {
+            pool_max_idle_per_host: Self::default_pool_max_idle_per_host(),
+            pool_idle_timeout_secs: Self::default_pool_idle_timeout_secs(),
+            keep_alive_interval_secs: Self::default_keep_alive_interval_secs(),
+            http2: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct StorageConfig {
     #[serde(rename = "s3")]
     pub s3_config: S3Config,
 }
 
+static STORAGE_CONFIG_INSTANCE: OnceCell<RwLock<Arc<StorageConfig>>> = OnceCell::new();
+
+fn storage_config_instance() -> &'static RwLock<Arc<StorageConfig>> {
+    STORAGE_CONFIG_INSTANCE.get_or_init(|| RwLock::new(Arc::new(StorageConfig::default())))
+}
+
+/// Returns the storage config of the node. The storages already created keep the config they
+/// were created with.
+pub fn get_storage_config_instance() -> Arc<StorageConfig> {
+    storage_config_instance()
+        .read()
+        .expect("Storage config lock should not be poisoned.")
+        .clone()
+}
+
+/// Sets the storage config of the node.
+pub fn set_storage_config_instance(storage_config: StorageConfig) {
+    *storage_config_instance()
+        .write()
+        .expect("Storage config lock should not be poisoned.") = Arc::new(storage_config);
+}
+
+/// Authentication of the REST API requests and field masks applied to the documents they
+/// return.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AuthConfig {
+    /// API keys accepted by the REST API, each granting a role.
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+    /// Fields hidden from some roles in the documents returned by the searches.
+    #[serde(default)]
+    pub field_masks: Vec<FieldMaskConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKeyConfig {
+    pub key: String,
+    pub role: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FieldMaskConfig {
+    /// ID of the index the mask applies to. A trailing `*` matches the index IDs by prefix,
+    /// e.g. `logs*` covers the write alias `logs` and the indexes it rolls over to.
+    pub index_id: String,
+    /// Roles the fields are hidden from.
+    pub roles: Vec<String>,
+    /// Fields removed from the documents. Nested fields are designated by their dotted path,
+    /// e.g. `user.email`.
+    pub fields: Vec<String>,
+}
+
+impl FieldMaskConfig {
+    fn matches_index(&self, index_id: &str) -> bool {
+        match self.index_id.strip_suffix('*') {
+            Some(index_id_prefix) => index_id.starts_with(index_id_prefix),
+            None => self.index_id == index_id,
+        }
+    }
+}
+
+impl AuthConfig {
+    /// Returns the role granted by the API key, if the key is known.
+    pub fn role(&self, api_key: &str) -> Option<&str> {
+        self.api_keys
+            .iter()
+            .find(|api_key_config| api_key_config.key == api_key)
+            .map(|api_key_config| api_key_config.role.as_str())
+    }
+
+    /// Returns the fields hidden from `role` in the documents of the index `index_id`.
+    pub fn masked_fields(&self, index_id: &str, role: &str) -> Vec<String> {
+        let mut masked_fields: Vec<String> = self
+            .field_masks
+            .iter()
+            .filter(|field_mask| {
+                field_mask.matches_index(index_id)
+                    && field_mask
+                        .roles
+                        .iter()
+                        .any(|masked_role| masked_role == role)
+            })
+            .flat_map(|field_mask| field_mask.fields.iter().cloned())
+            .collect();
+        masked_fields.sort();
+        masked_fields.dedup();
+        masked_fields
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.api_keys.is_empty() {
+            bail!("Auth config must define at least one API key.");
+        }
+        let mut api_keys = HashSet::new();
+        for api_key_config in &self.api_keys {
+            if api_key_config.key.is_empty() || api_key_config.role.is_empty() {
+                bail!("API keys and their roles must not be empty.");
+            }
+            if !api_keys.insert(&api_key_config.key) {
+                bail!(
+                    "API key granting role `{}` is defined more than once.",
+                    api_key_config.role
+                );
+            }
+        }
+        for field_mask in &self.field_masks {
+            if field_mask.index_id.is_empty() || field_mask.fields.is_empty() {
+                bail!("Field masks must have an index ID and at least one field.");
+            }
+            for role in &field_mask.roles {
+                if !self
+                    .api_keys
+                    .iter()
+                    .any(|api_key_config| &api_key_config.role == role)
+                {
+                    bail!(
+                        "Field mask of index `{}` refers to role `{}`, which no API key grants.",
+                        field_mask.index_id,
+                        role
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct QuickwitConfig {
@@ -177,6 +545,11 @@ pub struct QuickwitConfig {
     pub searcher_config: SearcherConfig,
     #[serde(rename = "storage")]
     pub storage_config: Option<StorageConfig>,
+    #[serde(default)]
+    pub log_level: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth: Option<AuthConfig>,
 }
 
 impl QuickwitConfig {
@@ -216,20 +589,20 @@ impl QuickwitConfig {
                 uri
             ),
         };
-        parser_fn(config_content)
+        let rendered_content = render_config_template(config_content)?;
+        parser_fn(&rendered_content)
     }
 
     fn from_json(bytes: &[u8]) -> anyhow::Result<Self> {
-        serde_json::from_reader(StripComments::new(bytes))
-            .context("Failed to parse JSON server config file.")
+        parse_json(bytes).context("Failed to parse JSON server config file.")
     }
 
     fn from_toml(bytes: &[u8]) -> anyhow::Result<Self> {
-        toml::from_slice(bytes).context("Failed to parse TOML server config file.")
+        parse_toml(bytes).context("Failed to parse TOML server config file.")
     }
 
     fn from_yaml(bytes: &[u8]) -> anyhow::Result<Self> {
-        serde_yaml::from_slice(bytes).context("Failed to parse YAML server config file.")
+        parse_yaml(bytes).context("Failed to parse YAML server config file.")
     }
 
     pub fn validate(&self) -> anyhow::Result<()> {
@@ -243,6 +616,64 @@ impl QuickwitConfig {
                 self.data_dir_path.display()
             );
         }
+        if let Some(log_level) = &self.log_level {
+            if Level::from_str(log_level).is_err() {
+                bail!(
+                    "Log level `{}` is invalid. Available log levels are `trace`, `debug`, \
+                     `info`, `warn`, and `error`.",
+                    log_level
+                );
+            }
+        }
+        let disk_high_watermark = self.indexer_config.disk_high_watermark;
+        if !(disk_high_watermark > 0.0 && disk_high_watermark <= 1.0) {
+            bail!(
+                "Disk high watermark `{}` is invalid. It must be in the range ]0, 1].",
+                disk_high_watermark
+            );
+        }
+        if self.searcher_config.split_reader_pool_num_searchers == 0 {
+            bail!("Split reader pool number of searchers must be greater than 0.");
+        }
+        let speculative_split_search_factor = self.searcher_config.speculative_split_search_factor;
+        if speculative_split_search_factor.is_nan()
+            || (speculative_split_search_factor != 0.0 && speculative_split_search_factor < 1.0)
+        {
+            bail!(
+                "Speculative split search factor must be 0 or greater than or equal to 1, got \
+                 `{}`.",
+                speculative_split_search_factor
+            );
+        }
+        if self.searcher_config.max_num_concurrent_split_searches == 0 {
+            bail!("Maximum number of concurrent split searches must be greater than 0.");
+        }
+        for (index_id, weight) in &self.searcher_config.index_search_weights {
+            if *weight == 0 {
+                bail!(
+                    "Search weight of index `{}` must be greater than 0.",
+                    index_id
+                );
+            }
+        }
+        let mut virtual_index_ids = HashSet::new();
+        for virtual_index in &self.searcher_config.virtual_indexes {
+            if !virtual_index_ids.insert(&virtual_index.index_id) {
+                bail!(
+                    "Virtual index `{}` is defined more than once.",
+                    virtual_index.index_id
+                );
+            }
+            if virtual_index.indexes.is_empty() {
+                bail!(
+                    "Virtual index `{}` must contain at least one index.",
+                    virtual_index.index_id
+                );
+            }
+        }
+        if let Some(auth_config) = &self.auth {
+            auth_config.validate()?;
+        }
         Ok(())
     }
 
@@ -306,6 +737,8 @@ impl Default for QuickwitConfig {
             indexer_config: IndexerConfig::default(),
             searcher_config: SearcherConfig::default(),
             storage_config: None,
+            log_level: None,
+            auth: None,
         }
     }
 }
@@ -358,6 +791,13 @@ mod tests {
                     IndexerConfig {
                         split_store_max_num_bytes: Byte::from_str("1T").unwrap(),
                         split_store_max_num_splits: 10_000,
+                        max_pipeline_heap_size: Byte::from_str("4G").unwrap(),
+                        disk_high_watermark: 0.9,
+                        max_pipeline_scratch_num_bytes: Byte::from_str("20G").unwrap(),
+                        disabled_sources: vec![DisabledSourceConfig {
+                            index_id: "wikipedia".to_string(),
+                            source_id: "kafka-source".to_string(),
+                        }],
                     }
                 );
 
@@ -367,6 +807,21 @@ mod tests {
                         fast_field_cache_capacity: Byte::from_str("10G").unwrap(),
                         split_footer_cache_capacity: Byte::from_str("1G").unwrap(),
                         max_num_concurrent_split_streams: 120,
+                        virtual_indexes: Vec::new(),
+                        grpc_compression: GrpcCompression::Zstd,
+                        max_grpc_message_size: Byte::from_str("16MiB").unwrap(),
+                        split_catalog_refresh_interval_secs: 10,
+                        cache_manifest_save_interval_secs: 60,
+                        split_reader_pool_capacity: 500,
+                        split_reader_pool_max_size: Byte::from_str("2G").unwrap(),
+                        split_reader_pool_ttl_secs: 120,
+                        split_reader_pool_num_searchers: 8,
+                        speculative_split_search_factor: 4.0,
+                        max_num_concurrent_split_searches: 50,
+                        index_search_weights: BTreeMap::from([
+                            ("wikipedia".to_string(), 4),
+                            ("hdfs-logs".to_string(), 1),
+                        ]),
                     }
                 );
 
@@ -375,8 +830,15 @@ mod tests {
                     S3Config {
                         region: Some("us-east-1".to_string()),
                         endpoint: Some("https://s3.us-east-1.amazonaws.com".to_string()),
+                        http_client: StorageHttpClientConfig {
+                            pool_max_idle_per_host: 256,
+                            pool_idle_timeout_secs: 30,
+                            keep_alive_interval_secs: 15,
+                            http2: true,
+                        },
                     }
                 );
+                assert_eq!(config.log_level, Some("debug".to_string()));
                 Ok(())
             }
         };
@@ -398,6 +860,16 @@ mod tests {
         assert_eq!(searcher_config, SearcherConfig::default());
     }
 
+    #[test]
+    fn test_storage_config_default_values() {
+        let storage_config =
+            serde_yaml::from_str::<StorageConfig>("s3:\n  region: us-east-1").unwrap();
+        assert_eq!(
+            storage_config.s3_config.http_client,
+            StorageHttpClientConfig::default()
+        );
+    }
+
     #[test]
     fn test_config_default_values() {
         {
@@ -469,6 +941,219 @@ mod tests {
         assert!(quickwit_config.validate().is_ok());
     }
 
+    #[test]
+    fn test_quickwit_config_validate_virtual_indexes() {
+        let virtual_index = VirtualIndexConfig {
+            index_id: "logs".to_string(),
+            indexes: vec!["logs-team-a".to_string(), "logs-team-b".to_string()],
+        };
+        let mut quickwit_config = QuickwitConfig {
+            data_dir_path: env::current_dir().unwrap(),
+            ..Default::default()
+        };
+        quickwit_config.searcher_config.virtual_indexes = vec![virtual_index.clone()];
+        assert!(quickwit_config.validate().is_ok());
+        assert_eq!(
+            quickwit_config.searcher_config.virtual_index("logs"),
+            Some(&virtual_index)
+        );
+        assert!(quickwit_config
+            .searcher_config
+            .virtual_index("logs-team-a")
+            .is_none());
+
+        quickwit_config.searcher_config.virtual_indexes =
+            vec![virtual_index.clone(), virtual_index];
+        assert!(quickwit_config.validate().is_err());
+
+        quickwit_config.searcher_config.virtual_indexes = vec![VirtualIndexConfig {
+            index_id: "logs".to_string(),
+            indexes: Vec::new(),
+        }];
+        assert!(quickwit_config.validate().is_err());
+    }
+
+    #[test]
+    fn test_quickwit_config_validate_auth() {
+        let mut quickwit_config = QuickwitConfig {
+            data_dir_path: env::current_dir().unwrap(),
+            ..Default::default()
+        };
+        let api_key = |key: &str, role: &str| ApiKeyConfig {
+            key: key.to_string(),
+            role: role.to_string(),
+        };
+        let field_mask = FieldMaskConfig {
+            index_id: "logs".to_string(),
+            roles: vec!["analyst".to_string()],
+            fields: vec!["user.email".to_string()],
+        };
+        quickwit_config.auth = Some(AuthConfig {
+            api_keys: vec![api_key("key-a", "admin"), api_key("key-b", "analyst")],
+            field_masks: vec![field_mask.clone()],
+        });
+        assert!(quickwit_config.validate().is_ok());
+
+        quickwit_config.auth = Some(AuthConfig::default());
+        assert!(quickwit_config.validate().is_err());
+
+        quickwit_config.auth = Some(AuthConfig {
+            api_keys: vec![api_key("key-a", "admin"), api_key("key-a", "analyst")],
+            field_masks: Vec::new(),
+        });
+        assert!(quickwit_config.validate().is_err());
+
+        quickwit_config.auth = Some(AuthConfig {
+            api_keys: vec![api_key("key-a", "admin")],
+            field_masks: vec![field_mask],
+        });
+        assert!(quickwit_config.validate().is_err());
+    }
+
+    #[test]
+    fn test_auth_config_masked_fields() {
+        let auth_config = AuthConfig {
+            api_keys: vec![ApiKeyConfig {
+                key: "key-a".to_string(),
+                role: "analyst".to_string(),
+            }],
+            field_masks: vec![
+                FieldMaskConfig {
+                    index_id: "logs*".to_string(),
+                    roles: vec!["analyst".to_string()],
+                    fields: vec!["user.email".to_string()],
+                },
+                FieldMaskConfig {
+                    index_id: "logs-000002".to_string(),
+                    roles: vec!["analyst".to_string(), "auditor".to_string()],
+                    fields: vec!["user.email".to_string(), "user.ip".to_string()],
+                },
+            ],
+        };
+        assert_eq!(auth_config.role("key-a"), Some("analyst"));
+        assert_eq!(auth_config.role("key-b"), None);
+        assert_eq!(
+            auth_config.masked_fields("logs", "analyst"),
+            vec!["user.email".to_string()]
+        );
+        assert_eq!(
+            auth_config.masked_fields("logs-000002", "analyst"),
+            vec!["user.email".to_string(), "user.ip".to_string()]
+        );
+        assert_eq!(
+            auth_config.masked_fields("logs-000002", "auditor"),
+            vec!["user.ip".to_string()]
+        );
+        assert!(auth_config.masked_fields("traces", "analyst").is_empty());
+        assert!(auth_config.masked_fields("logs", "admin").is_empty());
+    }
+
+    #[test]
+    fn test_quickwit_config_validate_log_level() {
+        let mut quickwit_config = QuickwitConfig {
+            data_dir_path: env::current_dir().unwrap(),
+            log_level: Some("debug".to_string()),
+            ..Default::default()
+        };
+        assert!(quickwit_config.validate().is_ok());
+
+        quickwit_config.log_level = Some("verbose".to_string());
+        assert!(quickwit_config.validate().is_err());
+    }
+
+    #[test]
+    fn test_quickwit_config_validate_split_reader_pool_num_searchers() {
+        let mut quickwit_config = QuickwitConfig {
+            data_dir_path: env::current_dir().unwrap(),
+            ..Default::default()
+        };
+        quickwit_config
+            .searcher_config
+            .split_reader_pool_num_searchers = 1;
+        assert!(quickwit_config.validate().is_ok());
+
+        quickwit_config
+            .searcher_config
+            .split_reader_pool_num_searchers = 0;
+        assert!(quickwit_config.validate().is_err());
+    }
+
+    #[test]
+    fn test_quickwit_config_validate_speculative_split_search_factor() {
+        let mut quickwit_config = QuickwitConfig {
+            data_dir_path: env::current_dir().unwrap(),
+            ..Default::default()
+        };
+        for speculative_split_search_factor in [0.0, 1.0, 3.5] {
+            quickwit_config
+                .searcher_config
+                .speculative_split_search_factor = speculative_split_search_factor;
+            assert!(quickwit_config.validate().is_ok());
+        }
+        for speculative_split_search_factor in [-1.0, 0.5, f64::NAN] {
+            quickwit_config
+                .searcher_config
+                .speculative_split_search_factor = speculative_split_search_factor;
+            assert!(quickwit_config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_quickwit_config_validate_split_search_admission() {
+        let mut quickwit_config = QuickwitConfig {
+            data_dir_path: env::current_dir().unwrap(),
+            ..Default::default()
+        };
+        quickwit_config
+            .searcher_config
+            .index_search_weights
+            .insert("wikipedia".to_string(), 3);
+        assert!(quickwit_config.validate().is_ok());
+
+        quickwit_config
+            .searcher_config
+            .index_search_weights
+            .insert("hdfs-logs".to_string(), 0);
+        assert!(quickwit_config.validate().is_err());
+
+        quickwit_config.searcher_config.index_search_weights.clear();
+        quickwit_config
+            .searcher_config
+            .max_num_concurrent_split_searches = 0;
+        assert!(quickwit_config.validate().is_err());
+    }
+
+    #[test]
+    fn test_quickwit_config_validate_disk_high_watermark() {
+        let mut quickwit_config = QuickwitConfig {
+            data_dir_path: env::current_dir().unwrap(),
+            ..Default::default()
+        };
+        quickwit_config.indexer_config.disk_high_watermark = 1.0;
+        assert!(quickwit_config.validate().is_ok());
+
+        quickwit_config.indexer_config.disk_high_watermark = 0.0;
+        assert!(quickwit_config.validate().is_err());
+
+        quickwit_config.indexer_config.disk_high_watermark = 1.5;
+        assert!(quickwit_config.validate().is_err());
+    }
+
+    #[test]
+    fn test_indexer_config_disabled_sources() {
+        let indexer_config = serde_yaml::from_str::<IndexerConfig>(
+            r#"
+            disabled_sources:
+              - index_id: wikipedia
+                source_id: kafka-source
+        "#,
+        )
+        .unwrap();
+        assert!(indexer_config.is_source_disabled("wikipedia", "kafka-source"));
+        assert!(!indexer_config.is_source_disabled("wikipedia", "file-source"));
+        assert!(!indexer_config.is_source_disabled("hdfs-logs", "kafka-source"));
+    }
+
     #[test]
     fn test_peer_socket_addrs() {
         {
@@ -509,4 +1194,28 @@ mod tests {
             .unwrap_err();
         assert!(config.to_string().contains("Data dir"));
     }
+
+    #[tokio::test]
+    async fn test_quickwit_config_env_var_interpolation() {
+        let config_uri = Uri::try_new("file:///config/quickwit.yaml").unwrap();
+        let config_yaml = r#"
+            version: 0
+            rest_listen_port: ${QW_TEST_UNSET_REST_LISTEN_PORT:-7290}
+            metastore_uri: s3://${QW_TEST_UNSET_BUCKET:-quickwit-dev}/indexes
+        "#;
+        let config = QuickwitConfig::from_uri(&config_uri, config_yaml.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(config.rest_listen_port, 7290);
+        assert_eq!(config.metastore_uri, "s3://quickwit-dev/indexes");
+
+        let config_yaml = "version: 0\nmetastore_uri: ${QW_TEST_UNSET_METASTORE_URI}\n";
+        let error = QuickwitConfig::from_uri(&config_uri, config_yaml.as_bytes())
+            .await
+            .unwrap_err();
+        assert!(error
+            .root_cause()
+            .to_string()
+            .contains("QW_TEST_UNSET_METASTORE_URI"));
+    }
 }
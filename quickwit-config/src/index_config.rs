@@ -25,14 +25,21 @@ use std::time::Duration;
 
 use anyhow::{bail, Context};
 use byte_unit::Byte;
-use json_comments::StripComments;
 use quickwit_common::uri::Uri;
 use quickwit_doc_mapper::{
-    DefaultDocMapperBuilder, DocMapper, FieldMappingEntry, SortBy, SortByConfig, SortOrder,
+    default_search_field_warnings, resolve_versioning_fields, DefaultDocMapperBuilder, DocMapper,
+    FieldMappingEntry, SortBy, SortByConfig, SortOrder, TimestampResolution,
 };
 use serde::{Deserialize, Serialize};
 
-use crate::source_config::SourceConfig;
+use crate::parser::{parse_json, parse_toml, parse_yaml};
+use crate::source_config::{SourceConfig, SourceParams};
+use crate::templating::render_config_template;
+
+/// ID of the built-in index receiving the OpenTelemetry traces.
+pub const OTEL_TRACES_INDEX_ID: &str = "otel-traces";
+
+const OTEL_TRACES_INDEX_CONFIG: &str = include_str!("../resources/index_config/otel-traces.yaml");
 
 // Note(fmassot): `DocMapping` is a struct only used for
 // serialization/deserialization of `DocMapper` parameters.
@@ -119,7 +126,128 @@ impl Default for MergePolicy {
     }
 }
 
-fn is_false(val: &bool) -> bool {
+/// Drops the documents whose ID was already seen within a recent time window. Seen IDs are
+/// recorded in a rolling bloom filter, so a small fraction of unique documents, bounded by
+/// `false_positive_rate`, is dropped as well.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DeduplicationSettings {
+    /// Field holding the document ID. Documents without this field are never deduplicated.
+    pub doc_id_field: String,
+    #[serde(default = "DeduplicationSettings::default_window_secs")]
+    pub window_secs: u64,
+    /// Expected number of documents ingested within a window, used to size the bloom filter.
+    #[serde(default = "DeduplicationSettings::default_max_num_docs_per_window")]
+    pub max_num_docs_per_window: usize,
+    #[serde(default = "DeduplicationSettings::default_false_positive_rate")]
+    pub false_positive_rate: f64,
+}
+
+impl DeduplicationSettings {
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window_secs)
+    }
+
+    fn default_window_secs() -> u64 {
+        600
+    }
+
+    fn default_max_num_docs_per_window() -> usize {
+        1_000_000
+    }
+
+    fn default_false_positive_rate() -> f64 {
+        0.001
+    }
+}
+
+/// Keeps only the latest version of each document, for sources emitting successive versions of
+/// the same document. The document with the highest `version_field` value among the documents
+/// sharing the same `doc_id_field` value wins.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct VersioningSettings {
+    pub doc_id_field: String,
+    pub version_field: String,
+}
+
+/// Deletes the splits whose documents are all older than the retention period. Enforced
+/// periodically by the janitor.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RetentionSettings {
+    pub period_secs: u64,
+}
+
+impl RetentionSettings {
+    pub fn period(&self) -> Duration {
+        Duration::from_secs(self.period_secs)
+    }
+}
+
+/// Manages the index as one generation of the indexes written through `write_alias`. Enforced
+/// periodically by the janitor, which rolls the alias over to a new index, created from the
+/// config of the current one, once the current index crosses a rollover threshold, and moves the
+/// previous generations through the read-only, frozen, and deleted phases.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct LifecycleSettings {
+    /// Name of the alias that the indexers follow and the searches target, resolving to all the
+    /// generations of the index. New generations are named `<write_alias>-<generation>`.
+    pub write_alias: String,
+    /// Rolls the alias over once the index is older than this age.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollover_max_age_secs: Option<u64>,
+    /// Rolls the alias over once the published splits of the index hold more than this number of
+    /// bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollover_max_num_bytes: Option<u64>,
+    /// Freezes a read-only index this long after it was rolled over: its splits are not merged
+    /// anymore.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub freeze_after_secs: Option<u64>,
+    /// Deletes an index, along with its split files, this long after it was rolled over.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delete_after_secs: Option<u64>,
+}
+
+impl LifecycleSettings {
+    pub fn rollover_max_age(&self) -> Option<Duration> {
+        self.rollover_max_age_secs.map(Duration::from_secs)
+    }
+
+    pub fn freeze_after(&self) -> Option<Duration> {
+        self.freeze_after_secs.map(Duration::from_secs)
+    }
+
+    pub fn delete_after(&self) -> Option<Duration> {
+        self.delete_after_secs.map(Duration::from_secs)
+    }
+}
+
+/// Writes the documents rejected by the doc mapper, along with the reason of their rejection, to
+/// a dead-letter directory instead of only counting them.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct DeadLetterSettings {
+    /// URI of the dead-letter directory: a local directory path or a storage URI.
+    pub uri: String,
+    /// Maximum delay before the rejected documents are written.
+    #[serde(default = "DeadLetterSettings::default_flush_interval_secs")]
+    pub flush_interval_secs: u64,
+}
+
+impl DeadLetterSettings {
+    fn default_flush_interval_secs() -> u64 {
+        30
+    }
+
+    pub fn flush_interval(&self) -> Duration {
+        Duration::from_secs(self.flush_interval_secs)
+    }
+}
+
+pub(crate) fn is_false(val: &bool) -> bool {
     !*val
 }
 
@@ -131,10 +259,20 @@ pub struct IndexingSettings {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub demux_field: Option<String>,
     pub timestamp_field: Option<String>,
+    /// Name of the fast field stamped with the time at which each document is indexed, in the
+    /// resolution of the timestamp field, so that the indexing delay can be measured and
+    /// documents can be queried by arrival time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ingest_timestamp_field: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sort_field: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sort_order: Option<SortOrder>,
+    /// Sorts the documents of a split by the timestamp field when the split is created, so that
+    /// time range queries benefit from better locality. Mutually exclusive with `sort_field`: an
+    /// index config setting both is rejected.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub sort_by_timestamp: bool,
     #[serde(default = "IndexingSettings::default_commit_timeout_secs")]
     pub commit_timeout_secs: usize,
     /// A split containing a number of docs greather than or equal to this value is considered
@@ -147,6 +285,20 @@ pub struct IndexingSettings {
     pub merge_policy: MergePolicy,
     #[serde(default)]
     pub resources: IndexingResources,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deduplication: Option<DeduplicationSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub versioning: Option<VersioningSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<RetentionSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dead_letter: Option<DeadLetterSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifecycle: Option<LifecycleSettings>,
+    /// Runs all the sources of the index concurrently in a single indexing pipeline, feeding the
+    /// same indexer, instead of running one pipeline per source.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub shared_pipeline: bool,
 }
 
 impl IndexingSettings {
@@ -171,6 +323,12 @@ impl IndexingSettings {
             let order = self.sort_order.unwrap_or_default();
             return SortBy::FastField { field_name, order };
         }
+        if self.sort_by_timestamp {
+            if let Some(field_name) = self.timestamp_field.clone() {
+                let order = self.sort_order.unwrap_or_default();
+                return SortBy::FastField { field_name, order };
+            }
+        }
         SortBy::DocId
     }
 
@@ -189,13 +347,21 @@ impl Default for IndexingSettings {
             demux_enabled: false,
             demux_field: None,
             timestamp_field: None,
+            ingest_timestamp_field: None,
             sort_field: None,
             sort_order: None,
+            sort_by_timestamp: false,
             commit_timeout_secs: Self::default_commit_timeout_secs(),
             split_num_docs_target: Self::default_split_num_docs_target(),
             merge_enabled: Self::default_merge_enabled(),
             merge_policy: MergePolicy::default(),
             resources: IndexingResources::default(),
+            deduplication: None,
+            versioning: None,
+            retention: None,
+            dead_letter: None,
+            lifecycle: None,
+            shared_pipeline: false,
         }
     }
 }
@@ -205,6 +371,11 @@ impl Default for IndexingSettings {
 pub struct SearchSettings {
     #[serde(default)]
     pub default_search_fields: Vec<String>,
+    /// IDs of the searcher nodes that the root prefers to assign the leaf searches of the index
+    /// to, e.g. the nodes located in the region of the index storage. All the searchers are used
+    /// if empty or if none of them is available.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub preferred_searcher_nodes: Vec<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -248,20 +419,28 @@ impl IndexConfig {
                 uri
             ),
         };
-        parser_fn(file_content)
+        let rendered_content = render_config_template(file_content)?;
+        parser_fn(&rendered_content)
     }
 
     fn from_json(bytes: &[u8]) -> anyhow::Result<Self> {
-        serde_json::from_reader(StripComments::new(bytes))
-            .context("Failed to parse JSON index config file.")
+        parse_json(bytes).context("Failed to parse JSON index config file.")
     }
 
     fn from_toml(bytes: &[u8]) -> anyhow::Result<Self> {
-        toml::from_slice(bytes).context("Failed to parse TOML index config file.")
+        parse_toml(bytes).context("Failed to parse TOML index config file.")
     }
 
     fn from_yaml(bytes: &[u8]) -> anyhow::Result<Self> {
-        serde_yaml::from_slice(bytes).context("Failed to parse YAML index config file.")
+        parse_yaml(bytes).context("Failed to parse YAML index config file.")
+    }
+
+    /// Returns the config of the built-in `otel-traces` index, which maps the spans received
+    /// by its `otlp` source.
+    pub fn otel_traces() -> anyhow::Result<Self> {
+        let config = Self::from_yaml(OTEL_TRACES_INDEX_CONFIG.as_bytes())?;
+        config.validate()?;
+        Ok(config)
     }
 
     pub fn sources(&self) -> HashMap<String, SourceConfig> {
@@ -271,6 +450,31 @@ impl IndexConfig {
             .collect()
     }
 
+    /// Returns warnings about settings that are valid but make some searches fail or ignore
+    /// their time bounds at query time.
+    pub fn lint(&self) -> anyhow::Result<Vec<String>> {
+        let doc_mapper = build_doc_mapper(
+            &self.doc_mapping,
+            &self.search_settings,
+            &self.indexing_settings,
+        )?;
+        let mut warnings = Vec::new();
+
+        if self.indexing_settings.timestamp_field.is_none() {
+            warnings.push(
+                "No `timestamp_field` is set in the indexing settings: the timestamp bounds of \
+                 search requests are ignored and every split is searched. Set it to a fast `i64` \
+                 field holding the document timestamps."
+                    .to_string(),
+            );
+        }
+        warnings.extend(default_search_field_warnings(
+            &doc_mapper.schema(),
+            &self.search_settings.default_search_fields,
+        ));
+        Ok(warnings)
+    }
+
     fn validate(&self) -> anyhow::Result<()> {
         if self.sources.len() > self.sources().len() {
             bail!("Index config contains duplicate sources.")
@@ -278,16 +482,152 @@ impl IndexConfig {
 
         for source in self.sources.iter() {
             source.validate()?;
+            let routes_to_self = source.routing.iter().any(|routing_params| {
+                routing_params
+                    .routes
+                    .iter()
+                    .any(|route| route.index_id == self.index_id)
+            });
+            if routes_to_self {
+                bail!(
+                    "Source `{}` cannot route documents to its own index `{}`.",
+                    source.source_id,
+                    self.index_id
+                )
+            }
+            if self.indexing_settings.shared_pipeline && source.routing.is_some() {
+                bail!(
+                    "Source `{}` cannot route documents: index `{}` runs its sources in a shared \
+                     pipeline.",
+                    source.source_id,
+                    self.index_id
+                )
+            }
+        }
+
+        if self.indexing_settings.sort_by_timestamp {
+            if self.indexing_settings.timestamp_field.is_none() {
+                bail!("Index config `sort_by_timestamp` requires a `timestamp_field`.")
+            }
+            if self.indexing_settings.sort_field.is_some() {
+                bail!("Index config `sort_by_timestamp` and `sort_field` are mutually exclusive.")
+            }
+        }
+
+        if let Some(retention) = &self.indexing_settings.retention {
+            if self.indexing_settings.timestamp_field.is_none() {
+                bail!("Index config `retention` requires a `timestamp_field`.")
+            }
+            if retention.period_secs == 0 {
+                bail!("Index config retention `period_secs` must be strictly positive.")
+            }
+        }
+
+        if let Some(lifecycle) = &self.indexing_settings.lifecycle {
+            if lifecycle.write_alias.is_empty() || lifecycle.write_alias == self.index_id {
+                bail!(
+                    "Index config lifecycle `write_alias` must be non-empty and differ from the \
+                     index ID."
+                )
+            }
+            if lifecycle.rollover_max_age_secs.is_none()
+                && lifecycle.rollover_max_num_bytes.is_none()
+            {
+                bail!(
+                    "Index config lifecycle requires `rollover_max_age_secs` or \
+                     `rollover_max_num_bytes`."
+                )
+            }
+            let thresholds = [
+                lifecycle.rollover_max_age_secs,
+                lifecycle.rollover_max_num_bytes,
+                lifecycle.freeze_after_secs,
+                lifecycle.delete_after_secs,
+            ];
+            if thresholds.contains(&Some(0)) {
+                bail!("Index config lifecycle thresholds must be strictly positive.")
+            }
+            if let (Some(freeze_after_secs), Some(delete_after_secs)) =
+                (lifecycle.freeze_after_secs, lifecycle.delete_after_secs)
+            {
+                if delete_after_secs < freeze_after_secs {
+                    bail!(
+                        "Index config lifecycle `delete_after_secs` must be greater than or equal \
+                         to `freeze_after_secs`."
+                    )
+                }
+            }
+            for source in self.sources.iter() {
+                if let SourceParams::IngestApi(params) = &source.source_params {
+                    if params.index_id != lifecycle.write_alias {
+                        bail!(
+                            "Source `{}` must read the ingest queue of the write alias `{}`: the \
+                             queue outlives the generations of the index.",
+                            source.source_id,
+                            lifecycle.write_alias
+                        )
+                    }
+                }
+            }
+        }
+
+        if let Some(dead_letter) = &self.indexing_settings.dead_letter {
+            if let Err(error) = Uri::try_new(&dead_letter.uri) {
+                bail!(
+                    "Index config dead letter `uri` `{}` is invalid: {}",
+                    dead_letter.uri,
+                    error
+                )
+            }
+            if dead_letter.flush_interval_secs == 0 {
+                bail!("Index config dead letter `flush_interval_secs` must be strictly positive.")
+            }
+        }
+
+        if let Some(deduplication) = &self.indexing_settings.deduplication {
+            if deduplication.window_secs == 0 || deduplication.max_num_docs_per_window == 0 {
+                bail!(
+                    "Index config deduplication `window_secs` and `max_num_docs_per_window` must \
+                     be strictly positive."
+                )
+            }
+            if !(deduplication.false_positive_rate > 0.0 && deduplication.false_positive_rate < 1.0)
+            {
+                bail!(
+                    "Index config deduplication `false_positive_rate` must be in the range ]0, 1[."
+                )
+            }
         }
 
         // Validation is made by building the doc mapper.
         // Note: this needs a deep refactoring to separate the doc mapping configuration,
         // and doc mapper implementations.
-        let _ = build_doc_mapper(
+        let doc_mapper = build_doc_mapper(
             &self.doc_mapping,
             &self.search_settings,
             &self.indexing_settings,
         )?;
+        if let Some(deduplication) = &self.indexing_settings.deduplication {
+            if doc_mapper
+                .schema()
+                .get_field(&deduplication.doc_id_field)
+                .is_none()
+            {
+                bail!(
+                    "Index config deduplication `doc_id_field` `{}` must be declared in the doc \
+                     mapping.",
+                    deduplication.doc_id_field
+                )
+            }
+        }
+        if let Some(versioning) = &self.indexing_settings.versioning {
+            resolve_versioning_fields(
+                &versioning.doc_id_field,
+                &versioning.version_field,
+                &doc_mapper.schema(),
+            )
+            .context("Index config `versioning` is invalid.")?;
+        }
 
         if self.indexing_settings.merge_policy.max_merge_factor
             < self.indexing_settings.merge_policy.merge_factor
@@ -316,17 +656,37 @@ pub fn build_doc_mapper(
         SortBy::FastField { field_name, order } => Some(SortByConfig { field_name, order }),
     };
     builder.timestamp_field = indexing_settings.timestamp_field.clone();
+    builder.ingest_timestamp_field = indexing_settings.ingest_timestamp_field.clone();
     builder.field_mappings = doc_mapping.field_mappings.clone();
     builder.tag_fields = doc_mapping.tag_fields.iter().cloned().collect();
     builder.store_source = doc_mapping.store_source;
     Ok(Arc::new(builder.build()?))
 }
 
+/// Returns the unit of the timestamps held by the timestamp field of an index, in which its split
+/// time ranges are expressed.
+pub fn timestamp_resolution(
+    doc_mapping: &DocMapping,
+    indexing_settings: &IndexingSettings,
+) -> TimestampResolution {
+    indexing_settings
+        .timestamp_field
+        .as_ref()
+        .and_then(|timestamp_field_name| {
+            doc_mapping
+                .field_mappings
+                .iter()
+                .find_map(|field_mapping| field_mapping.find_field_mapping(timestamp_field_name))
+        })
+        .map(|timestamp_field_mapping| timestamp_field_mapping.resolution)
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
-    use crate::SourceParams;
+    use crate::{DocRoute, DocRoutingParams, IngestApiSourceParams, SourceParams};
 
     fn get_resource_path(resource_filename: &str) -> String {
         format!(
@@ -414,6 +774,7 @@ mod tests {
                             "severity_text".to_string(),
                             "body".to_string()
                         ],
+                        preferred_searcher_nodes: vec!["searcher-us-east-1".to_string()],
                     }
                 );
                 assert_eq!(index_config.sources.len(), 2);
@@ -461,6 +822,7 @@ mod tests {
                 index_config.search_settings,
                 SearchSettings {
                     default_search_fields: vec!["body".to_string()],
+                    preferred_searcher_nodes: Vec::new(),
                 }
             );
             assert!(index_config.sources.is_empty());
@@ -505,12 +867,92 @@ mod tests {
                 index_config.search_settings,
                 SearchSettings {
                     default_search_fields: vec!["body".to_string()],
+                    preferred_searcher_nodes: Vec::new(),
                 }
             );
             assert!(index_config.sources.is_empty());
         }
     }
 
+    #[test]
+    fn test_indexing_settings_sort_by_timestamp() {
+        let indexing_settings = IndexingSettings {
+            timestamp_field: Some("timestamp".to_string()),
+            sort_by_timestamp: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            indexing_settings.sort_by(),
+            SortBy::FastField {
+                field_name: "timestamp".to_string(),
+                order: SortOrder::Desc
+            }
+        );
+        let indexing_settings = IndexingSettings {
+            timestamp_field: Some("timestamp".to_string()),
+            sort_field: Some("severity".to_string()),
+            sort_order: Some(SortOrder::Asc),
+            sort_by_timestamp: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            indexing_settings.sort_by(),
+            SortBy::FastField {
+                field_name: "severity".to_string(),
+                order: SortOrder::Asc
+            }
+        );
+        let indexing_settings = IndexingSettings {
+            sort_by_timestamp: true,
+            ..Default::default()
+        };
+        assert_eq!(indexing_settings.sort_by(), SortBy::DocId);
+    }
+
+    #[test]
+    fn test_otel_traces_index_config() {
+        let index_config = IndexConfig::otel_traces().unwrap();
+        assert_eq!(index_config.index_id, OTEL_TRACES_INDEX_ID);
+        assert!(index_config.index_uri.is_none());
+        assert_eq!(
+            timestamp_resolution(&index_config.doc_mapping, &index_config.indexing_settings),
+            TimestampResolution::Nanoseconds
+        );
+        assert_eq!(index_config.sources.len(), 1);
+        assert_eq!(index_config.sources[0].source_type(), "otlp");
+        assert!(index_config.lint().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_index_config_lint() {
+        let index_config = IndexConfig::from_yaml(
+            br#"
+            version: 0
+            index_id: my-index
+            doc_mapping:
+              field_mappings:
+                - name: severity
+                  type: text
+                  tokenizer: raw
+                - name: body
+                  type: text
+                - name: title
+                  type: text
+                  record: position
+                - name: status_code
+                  type: u64
+            search_settings:
+              default_search_fields: [severity, body, title, status_code]
+            "#,
+        )
+        .unwrap();
+        let warnings = index_config.lint().unwrap();
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].contains("`timestamp_field`"));
+        assert!(warnings[1].contains("`body` does not record positions"));
+        assert!(warnings[2].contains("`status_code` is of type `u64`"));
+    }
+
     #[tokio::test]
     async fn test_validate() {
         let index_config_filepath = get_resource_path("minimal-hdfs-logs.yaml");
@@ -543,10 +985,16 @@ mod tests {
                 SourceConfig {
                     source_id: "void_1".to_string(),
                     source_params: SourceParams::void(),
+                    filter: None,
+                    routing: None,
+                    transform: None,
                 },
                 SourceConfig {
                     source_id: "void_1".to_string(),
                     source_params: SourceParams::void(),
+                    filter: None,
+                    routing: None,
+                    transform: None,
                 },
             ];
             assert!(invalid_index_config.validate().is_err());
@@ -556,12 +1004,38 @@ mod tests {
                 .to_string()
                 .contains("Index config contains duplicate sources."));
         }
+        {
+            // Route documents from a source of an index with a shared pipeline.
+            let mut invalid_index_config = index_config.clone();
+            invalid_index_config.indexing_settings.shared_pipeline = true;
+            invalid_index_config.sources = vec![SourceConfig {
+                source_id: "void_1".to_string(),
+                source_params: SourceParams::void(),
+                filter: None,
+                routing: Some(DocRoutingParams {
+                    field: "service".to_string(),
+                    routes: vec![DocRoute {
+                        values: vec![serde_json::json!("checkout")],
+                        index_id: "checkout-logs".to_string(),
+                    }],
+                }),
+                transform: None,
+            }];
+            assert!(invalid_index_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("runs its sources in a shared pipeline"));
+        }
         {
             // Add source file params with no filepath.
             let mut invalid_index_config = index_config.clone();
             invalid_index_config.sources = vec![SourceConfig {
                 source_id: "file_params_1".to_string(),
                 source_params: SourceParams::stdin(),
+                filter: None,
+                routing: None,
+                transform: None,
             }];
             assert!(invalid_index_config.validate().is_err());
             assert!(invalid_index_config
@@ -570,6 +1044,176 @@ mod tests {
                 .to_string()
                 .contains("must contain a `filepath`"));
         }
+        {
+            // Sort by timestamp without a timestamp field.
+            let mut invalid_index_config = index_config.clone();
+            invalid_index_config.indexing_settings.sort_by_timestamp = true;
+            assert!(invalid_index_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("requires a `timestamp_field`"));
+        }
+        {
+            // Enforce a retention period without a timestamp field.
+            let mut invalid_index_config = index_config.clone();
+            invalid_index_config.indexing_settings.retention =
+                Some(RetentionSettings { period_secs: 3600 });
+            assert!(invalid_index_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("`retention` requires a `timestamp_field`"));
+        }
+        {
+            // Write rejected documents to an invalid dead-letter URI.
+            let mut invalid_index_config = index_config.clone();
+            invalid_index_config.indexing_settings.dead_letter = Some(DeadLetterSettings {
+                uri: "~dead-letter".to_string(),
+                flush_interval_secs: 30,
+            });
+            assert!(invalid_index_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("dead letter `uri` `~dead-letter` is invalid"));
+            invalid_index_config.indexing_settings.dead_letter = Some(DeadLetterSettings {
+                uri: "s3://quickwit-dead-letter/hdfs-logs".to_string(),
+                flush_interval_secs: 0,
+            });
+            assert!(invalid_index_config.validate().is_err());
+        }
+        {
+            // Manage the lifecycle of the index without a rollover threshold.
+            let mut invalid_index_config = index_config.clone();
+            let lifecycle = LifecycleSettings {
+                write_alias: "hdfs".to_string(),
+                rollover_max_age_secs: None,
+                rollover_max_num_bytes: None,
+                freeze_after_secs: Some(3_600),
+                delete_after_secs: Some(86_400),
+            };
+            invalid_index_config.indexing_settings.lifecycle = Some(lifecycle.clone());
+            assert!(invalid_index_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("requires `rollover_max_age_secs` or `rollover_max_num_bytes`"));
+
+            let mut valid_index_config = index_config.clone();
+            valid_index_config.indexing_settings.lifecycle = Some(LifecycleSettings {
+                rollover_max_num_bytes: Some(1_000_000_000),
+                ..lifecycle.clone()
+            });
+            valid_index_config.validate().unwrap();
+
+            // Delete the index before freezing it.
+            let mut invalid_index_config = valid_index_config.clone();
+            invalid_index_config.indexing_settings.lifecycle = Some(LifecycleSettings {
+                rollover_max_num_bytes: Some(1_000_000_000),
+                delete_after_secs: Some(60),
+                ..lifecycle.clone()
+            });
+            assert!(invalid_index_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("`delete_after_secs` must be greater than or equal"));
+
+            // Read the ingest queue of a single generation of the index.
+            let mut invalid_index_config = valid_index_config.clone();
+            invalid_index_config.sources = vec![SourceConfig {
+                source_id: "ingest-api".to_string(),
+                source_params: SourceParams::IngestApi(IngestApiSourceParams {
+                    index_id: invalid_index_config.index_id.clone(),
+                }),
+                filter: None,
+                routing: None,
+                transform: None,
+            }];
+            assert!(invalid_index_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("must read the ingest queue of the write alias `hdfs`"));
+        }
+        {
+            // Deduplicate on a field not declared in the mapping.
+            let mut invalid_index_config = index_config.clone();
+            invalid_index_config.indexing_settings.deduplication = Some(DeduplicationSettings {
+                doc_id_field: "invalid-field".to_string(),
+                window_secs: 600,
+                max_num_docs_per_window: 1_000_000,
+                false_positive_rate: 0.001,
+            });
+            assert!(invalid_index_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("must be declared in the doc mapping"));
+        }
+        {
+            // Deduplicate with an invalid false positive rate.
+            let mut invalid_index_config = index_config.clone();
+            invalid_index_config.indexing_settings.deduplication = Some(DeduplicationSettings {
+                doc_id_field: "body".to_string(),
+                window_secs: 600,
+                max_num_docs_per_window: 1_000_000,
+                false_positive_rate: 1.0,
+            });
+            assert!(invalid_index_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("`false_positive_rate` must be in the range"));
+        }
+        {
+            // Keep the latest version of documents according to a non fast field.
+            let mut invalid_index_config = index_config.clone();
+            invalid_index_config.indexing_settings.versioning = Some(VersioningSettings {
+                doc_id_field: "timestamp".to_string(),
+                version_field: "body".to_string(),
+            });
+            assert!(invalid_index_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("Index config `versioning` is invalid."));
+        }
+        {
+            // Keep the latest version of documents identified by a non-integer field.
+            let mut invalid_index_config = index_config.clone();
+            for (field_name, field_type) in [("id", "f64"), ("version", "i64")] {
+                let field_mapping = serde_json::from_value(serde_json::json!({
+                    "name": field_name,
+                    "type": field_type,
+                    "fast": true,
+                }))
+                .unwrap();
+                invalid_index_config
+                    .doc_mapping
+                    .field_mappings
+                    .push(field_mapping);
+            }
+            invalid_index_config.indexing_settings.versioning = Some(VersioningSettings {
+                doc_id_field: "id".to_string(),
+                version_field: "version".to_string(),
+            });
+            let error = invalid_index_config.validate().unwrap_err();
+            assert!(format!("{:#}", error).contains("Doc ID field must be of type u64 or i64"));
+        }
+        {
+            // Stamp the ingest timestamp into a field declared in the mapping.
+            let mut invalid_index_config = index_config.clone();
+            invalid_index_config
+                .indexing_settings
+                .ingest_timestamp_field = Some("timestamp".to_string());
+            assert!(invalid_index_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("is added by the indexer"));
+        }
         {
             // Add a demux field not declared in the mapping.
             let mut invalid_index_config = index_config;
@@ -19,17 +19,35 @@
 
 mod config;
 mod index_config;
+mod memory_limit;
+mod parser;
+mod reload;
+mod schema;
 mod source_config;
+mod templating;
 
 pub use config::{
-    get_searcher_config_instance, IndexerConfig, QuickwitConfig, SearcherConfig,
-    SEARCHER_CONFIG_INSTANCE,
+    get_searcher_config_instance, get_storage_config_instance, set_searcher_config_instance,
+    set_storage_config_instance, ApiKeyConfig, AuthConfig, DisabledSourceConfig, FieldMaskConfig,
+    GrpcCompression, IndexerConfig, QuickwitConfig, S3Config, SearcherConfig, StorageConfig,
+    StorageHttpClientConfig, VirtualIndexConfig,
 };
 pub use index_config::{
-    build_doc_mapper, DocMapping, IndexConfig, IndexingResources, IndexingSettings, MergePolicy,
-    SearchSettings,
+    build_doc_mapper, timestamp_resolution, DeadLetterSettings, DeduplicationSettings, DocMapping,
+    IndexConfig, IndexingResources, IndexingSettings, LifecycleSettings, MergePolicy,
+    RetentionSettings, SearchSettings, VersioningSettings, OTEL_TRACES_INDEX_ID,
 };
+pub use memory_limit::{memory_limit, QW_MEMORY_LIMIT_ENV_KEY};
+pub use reload::ConfigReloadReport;
+pub use schema::{INDEX_CONFIG_JSON_SCHEMA, QUICKWIT_CONFIG_JSON_SCHEMA};
 pub use source_config::{
-    FileSourceParams, KafkaSourceParams, SourceConfig, SourceParams, VecSourceParams,
-    VoidSourceParams,
+    log_level_rank, DatasetFormat, DatasetSourceParams, DebeziumParams, DirPublishedAction,
+    DirSourceParams, DocFilterCondition, DocFilterOperator, DocFilterParams, DocFormat, DocFraming,
+    DocRoute, DocRoutingParams, DocTransformParams, DocTransformStep, FileSourceParams,
+    GrpcSourceParams, IngestApiSourceParams, KafkaDecodingParams, KafkaOAuthBearerParams,
+    KafkaSourceParams, KinesisSourceParams, MqttSourceParams, MultilineParams, NatsSourceParams,
+    OtlpSourceParams, PayloadDecodingParams, ProtobufDecodingParams, PubSubSourceParams,
+    PulsarSourceParams, SourceConfig, SourceParams, SqsSourceParams, SyslogProtocol,
+    SyslogSourceParams, VecSourceParams, VoidSourceParams,
 };
+pub use templating::render_config_template;
@@ -0,0 +1,134 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::Path;
+use std::str::FromStr;
+
+use byte_unit::Byte;
+use once_cell::sync::OnceCell;
+use tracing::{info, warn};
+
+/// This environment variable overrides the memory limit detected at startup.
+pub const QW_MEMORY_LIMIT_ENV_KEY: &str = "QW_MEMORY_LIMIT";
+
+const CGROUP_V2_MEMORY_MAX_PATH: &str = "/sys/fs/cgroup/memory.max";
+const CGROUP_V1_MEMORY_LIMIT_PATH: &str = "/sys/fs/cgroup/memory/memory.limit_in_bytes";
+const MEMINFO_PATH: &str = "/proc/meminfo";
+
+/// cgroup v1 reports the absence of limit as a very large number, rounded down to the page
+/// size. Any limit above this threshold is considered unlimited.
+const CGROUP_UNLIMITED_THRESHOLD: u64 = 1 << 62;
+
+/// Returns the memory available to the process: the value of `QW_MEMORY_LIMIT` if set,
+/// otherwise the memory limit of the container, or the total memory of the host if the
+/// process does not run in a container with a memory limit. The limit is computed once and
+/// cached.
+pub fn memory_limit() -> Option<Byte> {
+    static MEMORY_LIMIT: OnceCell<Option<Byte>> = OnceCell::new();
+    *MEMORY_LIMIT.get_or_init(|| {
+        if let Ok(memory_limit_str) = std::env::var(QW_MEMORY_LIMIT_ENV_KEY) {
+            match Byte::from_str(&memory_limit_str) {
+                Ok(memory_limit) => {
+                    info!(memory_limit = %memory_limit, "Memory limit set by environment variable.");
+                    return Some(memory_limit);
+                }
+                Err(error) => {
+                    warn!(error = ?error, "Failed to parse memory limit environment variable.")
+                }
+            }
+        }
+        let memory_limit_opt =
+            detect_memory_limit().map(|num_bytes| Byte::from_bytes(num_bytes.into()));
+        match memory_limit_opt {
+            Some(memory_limit) => info!(memory_limit = %memory_limit, "Detected memory limit."),
+            None => warn!("Failed to detect memory limit, using default memory settings."),
+        }
+        memory_limit_opt
+    })
+}
+
+/// Returns `ratio` of the memory limit, or `default_value` if the memory limit is unknown.
+pub(crate) fn memory_limit_ratio_or(ratio: f64, default_value: Byte) -> Byte {
+    match memory_limit() {
+        Some(memory_limit) => Byte::from_bytes((memory_limit.get_bytes() as f64 * ratio) as _),
+        None => default_value,
+    }
+}
+
+/// Detects the memory limit of the cgroup of the process, with cgroup v2 and v1 hierarchies,
+/// and caps it to the total memory of the host.
+fn detect_memory_limit() -> Option<u64> {
+    let cgroup_memory_limit_opt = read_to_string_opt(CGROUP_V2_MEMORY_MAX_PATH)
+        .or_else(|| read_to_string_opt(CGROUP_V1_MEMORY_LIMIT_PATH))
+        .and_then(|content| parse_cgroup_memory_limit(&content));
+    let host_memory_opt =
+        read_to_string_opt(MEMINFO_PATH).and_then(|content| parse_meminfo_total_memory(&content));
+    match (cgroup_memory_limit_opt, host_memory_opt) {
+        (Some(cgroup_memory_limit), Some(host_memory)) => {
+            Some(cgroup_memory_limit.min(host_memory))
+        }
+        (cgroup_memory_limit_opt, host_memory_opt) => cgroup_memory_limit_opt.or(host_memory_opt),
+    }
+}
+
+fn read_to_string_opt(path: impl AsRef<Path>) -> Option<String> {
+    std::fs::read_to_string(path).ok()
+}
+
+/// Parses the content of `memory.max` (cgroup v2) or `memory.limit_in_bytes` (cgroup v1).
+fn parse_cgroup_memory_limit(content: &str) -> Option<u64> {
+    let memory_limit = content.trim().parse::<u64>().ok()?;
+    if memory_limit >= CGROUP_UNLIMITED_THRESHOLD {
+        return None;
+    }
+    Some(memory_limit)
+}
+
+/// Parses the total memory of the host from the content of `/proc/meminfo`.
+fn parse_meminfo_total_memory(content: &str) -> Option<u64> {
+    let line = content.lines().find(|line| line.starts_with("MemTotal:"))?;
+    let mut tokens = line["MemTotal:".len()..].split_whitespace();
+    let value = tokens.next()?.parse::<u64>().ok()?;
+    match tokens.next() {
+        Some("kB") => Some(value * 1024),
+        None => Some(value),
+        Some(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cgroup_memory_limit() {
+        assert_eq!(parse_cgroup_memory_limit("536870912\n"), Some(536_870_912));
+        assert_eq!(parse_cgroup_memory_limit("max\n"), None);
+        assert_eq!(parse_cgroup_memory_limit("9223372036854771712\n"), None);
+        assert_eq!(parse_cgroup_memory_limit(""), None);
+    }
+
+    #[test]
+    fn test_parse_meminfo_total_memory() {
+        let meminfo = "MemTotal:       16318028 kB\nMemFree:         1062696 kB\n";
+        assert_eq!(parse_meminfo_total_memory(meminfo), Some(16_318_028 * 1024));
+        assert_eq!(parse_meminfo_total_memory("MemFree: 1062696 kB\n"), None);
+        assert_eq!(parse_meminfo_total_memory("MemTotal: 12 MB\n"), None);
+    }
+}
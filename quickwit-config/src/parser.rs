@@ -0,0 +1,128 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::fmt::Display;
+
+use anyhow::Context;
+use json_comments::StripComments;
+use serde::de::DeserializeOwned;
+
+/// Builds the error of a config file that failed to deserialize. The message of the underlying
+/// error carries the line and column of the faulty value, and is prefixed with the path of the
+/// field, e.g. `doc_mapping.field_mappings[2].type`, like the errors of `serde_yaml`.
+fn path_to_error<E: Display>(error: serde_path_to_error::Error<E>) -> anyhow::Error {
+    let path = error.path().to_string();
+    if path == "." {
+        return anyhow::anyhow!("{}", error.inner());
+    }
+    anyhow::anyhow!("{}: {}", path, error.inner())
+}
+
+pub(crate) fn parse_json<T: DeserializeOwned>(bytes: &[u8]) -> anyhow::Result<T> {
+    let mut deserializer = serde_json::Deserializer::from_reader(StripComments::new(bytes));
+    serde_path_to_error::deserialize(&mut deserializer).map_err(path_to_error)
+}
+
+pub(crate) fn parse_toml<T: DeserializeOwned>(bytes: &[u8]) -> anyhow::Result<T> {
+    let toml_str = std::str::from_utf8(bytes).context("TOML config file is not valid UTF-8.")?;
+    let mut deserializer = toml::Deserializer::new(toml_str);
+    serde_path_to_error::deserialize(&mut deserializer).map_err(path_to_error)
+}
+
+/// The errors of `serde_yaml` already carry the path of the field and the position of the value.
+pub(crate) fn parse_yaml<T: DeserializeOwned>(bytes: &[u8]) -> anyhow::Result<T> {
+    let config = serde_yaml::from_slice(bytes)?;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct TestConfig {
+        version: usize,
+        #[serde(default)]
+        sources: Vec<TestSource>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct TestSource {
+        source_id: String,
+        num_partitions: usize,
+    }
+
+    #[test]
+    fn test_parse_yaml_error_position() {
+        let yaml = "version: 0\nsources:\n  - source_id: my-source\n    num_partitions: many\n";
+        let error = parse_yaml::<TestConfig>(yaml.as_bytes()).unwrap_err();
+        let error_message = error.to_string();
+        assert!(
+            error_message.contains("sources[0].num_partitions: invalid type"),
+            "{}",
+            error_message
+        );
+        assert!(
+            error_message.contains("line 4 column 21"),
+            "{}",
+            error_message
+        );
+    }
+
+    #[test]
+    fn test_parse_json_error_position() {
+        let json = r#"{
+            // Comments are allowed.
+            "version": 0,
+            "sources": [{"source_id": "my-source", "num_partition": 1}]
+        }"#;
+        let error = parse_json::<TestConfig>(json.as_bytes()).unwrap_err();
+        let error_message = error.to_string();
+        assert!(
+            error_message.starts_with("sources[0].num_partition: unknown field"),
+            "{}",
+            error_message
+        );
+        assert!(error_message.contains("at line 4"), "{}", error_message);
+    }
+
+    #[test]
+    fn test_parse_toml_error_position() {
+        let toml = "version = 0\n\n[[sources]]\nsource_id = \"my-source\"\nnum_partitions = -1\n";
+        let error = parse_toml::<TestConfig>(toml.as_bytes()).unwrap_err();
+        let error_message = error.to_string();
+        assert!(
+            error_message.starts_with("sources[0].num_partitions: invalid value"),
+            "{}",
+            error_message
+        );
+    }
+
+    #[test]
+    fn test_parse_root_error() {
+        let error = parse_yaml::<TestConfig>(b"sources: []").unwrap_err();
+        assert!(error.to_string().starts_with("missing field `version`"));
+    }
+}
@@ -0,0 +1,360 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use serde::Serialize;
+
+use crate::QuickwitConfig;
+
+/// Lists the settings changed by a config reload.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigReloadReport {
+    /// Settings whose new value was applied to the running node.
+    pub applied_settings: Vec<String>,
+    /// Settings whose new value is only taken into account after a restart.
+    pub restart_required_settings: Vec<String>,
+}
+
+impl ConfigReloadReport {
+    /// Records the setting if its value changed. Returns true if the new value must be
+    /// applied.
+    fn record<T: PartialEq>(
+        &mut self,
+        setting: &str,
+        current_value: &T,
+        new_value: &T,
+        is_reloadable: bool,
+    ) -> bool {
+        if current_value == new_value {
+            return false;
+        }
+        if is_reloadable {
+            self.applied_settings.push(setting.to_string());
+        } else {
+            self.restart_required_settings.push(setting.to_string());
+        }
+        is_reloadable
+    }
+}
+
+impl QuickwitConfig {
+    /// Returns the config resulting from reloading `new_config` into the running config, along
+    /// with the report of the changed settings. Only the settings that can safely change
+    /// while the node is running take their new value: the log level, the auth config, the disk
+    /// high watermark, the disabled sources, the split streams concurrency limit, the maximum gRPC
+    /// message size, and the virtual indexes. The other settings keep their current value until
+    /// the node restarts.
+    pub fn reload(&self, new_config: &QuickwitConfig) -> (QuickwitConfig, ConfigReloadReport) {
+        let mut report = ConfigReloadReport::default();
+        let mut reloaded_config = self.clone();
+
+        report.record("node_id", &self.node_id, &new_config.node_id, false);
+        report.record(
+            "listen_address",
+            &self.listen_address,
+            &new_config.listen_address,
+            false,
+        );
+        report.record(
+            "rest_listen_port",
+            &self.rest_listen_port,
+            &new_config.rest_listen_port,
+            false,
+        );
+        report.record(
+            "peer_seeds",
+            &self.peer_seeds,
+            &new_config.peer_seeds,
+            false,
+        );
+        report.record(
+            "metastore_uri",
+            &self.metastore_uri,
+            &new_config.metastore_uri,
+            false,
+        );
+        report.record(
+            "default_index_root_uri",
+            &self.default_index_root_uri,
+            &new_config.default_index_root_uri,
+            false,
+        );
+        report.record(
+            "data_dir",
+            &self.data_dir_path,
+            &new_config.data_dir_path,
+            false,
+        );
+        report.record(
+            "storage",
+            &self.storage_config,
+            &new_config.storage_config,
+            false,
+        );
+        if report.record("log_level", &self.log_level, &new_config.log_level, true) {
+            reloaded_config.log_level = new_config.log_level.clone();
+        }
+        // The REST API reads the auth config on each request, so that keys can be rotated.
+        if report.record("auth", &self.auth, &new_config.auth, true) {
+            reloaded_config.auth = new_config.auth.clone();
+        }
+
+        let indexer_config = &self.indexer_config;
+        let new_indexer_config = &new_config.indexer_config;
+        report.record(
+            "indexer.split_store_max_num_bytes",
+            &indexer_config.split_store_max_num_bytes,
+            &new_indexer_config.split_store_max_num_bytes,
+            false,
+        );
+        report.record(
+            "indexer.split_store_max_num_splits",
+            &indexer_config.split_store_max_num_splits,
+            &new_indexer_config.split_store_max_num_splits,
+            false,
+        );
+        report.record(
+            "indexer.max_pipeline_heap_size",
+            &indexer_config.max_pipeline_heap_size,
+            &new_indexer_config.max_pipeline_heap_size,
+            false,
+        );
+        if report.record(
+            "indexer.disk_high_watermark",
+            &indexer_config.disk_high_watermark,
+            &new_indexer_config.disk_high_watermark,
+            true,
+        ) {
+            reloaded_config.indexer_config.disk_high_watermark =
+                new_indexer_config.disk_high_watermark;
+        }
+        report.record(
+            "indexer.max_pipeline_scratch_num_bytes",
+            &indexer_config.max_pipeline_scratch_num_bytes,
+            &new_indexer_config.max_pipeline_scratch_num_bytes,
+            false,
+        );
+        if report.record(
+            "indexer.disabled_sources",
+            &indexer_config.disabled_sources,
+            &new_indexer_config.disabled_sources,
+            true,
+        ) {
+            reloaded_config.indexer_config.disabled_sources =
+                new_indexer_config.disabled_sources.clone();
+        }
+
+        // The caches are allocated when the searcher starts.
+        let searcher_config = &self.searcher_config;
+        let new_searcher_config = &new_config.searcher_config;
+        report.record(
+            "searcher.fast_field_cache_capacity",
+            &searcher_config.fast_field_cache_capacity,
+            &new_searcher_config.fast_field_cache_capacity,
+            false,
+        );
+        report.record(
+            "searcher.split_footer_cache_capacity",
+            &searcher_config.split_footer_cache_capacity,
+            &new_searcher_config.split_footer_cache_capacity,
+            false,
+        );
+        if report.record(
+            "searcher.max_num_concurrent_split_streams",
+            &searcher_config.max_num_concurrent_split_streams,
+            &new_searcher_config.max_num_concurrent_split_streams,
+            true,
+        ) {
+            reloaded_config
+                .searcher_config
+                .max_num_concurrent_split_streams =
+                new_searcher_config.max_num_concurrent_split_streams;
+        }
+        report.record(
+            "searcher.split_catalog_refresh_interval_secs",
+            &searcher_config.split_catalog_refresh_interval_secs,
+            &new_searcher_config.split_catalog_refresh_interval_secs,
+            false,
+        );
+        report.record(
+            "searcher.cache_manifest_save_interval_secs",
+            &searcher_config.cache_manifest_save_interval_secs,
+            &new_searcher_config.cache_manifest_save_interval_secs,
+            false,
+        );
+        report.record(
+            "searcher.split_reader_pool_capacity",
+            &searcher_config.split_reader_pool_capacity,
+            &new_searcher_config.split_reader_pool_capacity,
+            false,
+        );
+        report.record(
+            "searcher.split_reader_pool_max_size",
+            &searcher_config.split_reader_pool_max_size,
+            &new_searcher_config.split_reader_pool_max_size,
+            false,
+        );
+        report.record(
+            "searcher.split_reader_pool_ttl_secs",
+            &searcher_config.split_reader_pool_ttl_secs,
+            &new_searcher_config.split_reader_pool_ttl_secs,
+            false,
+        );
+        report.record(
+            "searcher.split_reader_pool_num_searchers",
+            &searcher_config.split_reader_pool_num_searchers,
+            &new_searcher_config.split_reader_pool_num_searchers,
+            false,
+        );
+        if report.record(
+            "searcher.speculative_split_search_factor",
+            &searcher_config.speculative_split_search_factor,
+            &new_searcher_config.speculative_split_search_factor,
+            true,
+        ) {
+            reloaded_config
+                .searcher_config
+                .speculative_split_search_factor =
+                new_searcher_config.speculative_split_search_factor;
+        }
+        if report.record(
+            "searcher.max_num_concurrent_split_searches",
+            &searcher_config.max_num_concurrent_split_searches,
+            &new_searcher_config.max_num_concurrent_split_searches,
+            true,
+        ) {
+            reloaded_config
+                .searcher_config
+                .max_num_concurrent_split_searches =
+                new_searcher_config.max_num_concurrent_split_searches;
+        }
+        if report.record(
+            "searcher.index_search_weights",
+            &searcher_config.index_search_weights,
+            &new_searcher_config.index_search_weights,
+            true,
+        ) {
+            reloaded_config.searcher_config.index_search_weights =
+                new_searcher_config.index_search_weights.clone();
+        }
+        // The gRPC server is configured when the searcher starts.
+        report.record(
+            "searcher.grpc_compression",
+            &searcher_config.grpc_compression,
+            &new_searcher_config.grpc_compression,
+            false,
+        );
+        if report.record(
+            "searcher.max_grpc_message_size",
+            &searcher_config.max_grpc_message_size,
+            &new_searcher_config.max_grpc_message_size,
+            true,
+        ) {
+            reloaded_config.searcher_config.max_grpc_message_size =
+                new_searcher_config.max_grpc_message_size;
+        }
+        if report.record(
+            "searcher.virtual_indexes",
+            &searcher_config.virtual_indexes,
+            &new_searcher_config.virtual_indexes,
+            true,
+        ) {
+            reloaded_config.searcher_config.virtual_indexes =
+                new_searcher_config.virtual_indexes.clone();
+        }
+        (reloaded_config, report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byte_unit::Byte;
+
+    use super::*;
+    use crate::{ApiKeyConfig, AuthConfig, DisabledSourceConfig, VirtualIndexConfig};
+
+    #[test]
+    fn test_reload_unchanged_config() {
+        let config = QuickwitConfig::default();
+        let (reloaded_config, report) = config.reload(&config);
+        assert_eq!(reloaded_config, config);
+        assert_eq!(report, ConfigReloadReport::default());
+    }
+
+    #[test]
+    fn test_reload_config() {
+        let config = QuickwitConfig::default();
+        let mut new_config = config.clone();
+        new_config.rest_listen_port = 1111;
+        new_config.log_level = Some("debug".to_string());
+        new_config.auth = Some(AuthConfig {
+            api_keys: vec![ApiKeyConfig {
+                key: "secret".to_string(),
+                role: "analyst".to_string(),
+            }],
+            field_masks: Vec::new(),
+        });
+        new_config.indexer_config.disk_high_watermark = 0.8;
+        new_config.indexer_config.disabled_sources = vec![DisabledSourceConfig {
+            index_id: "wikipedia".to_string(),
+            source_id: "kafka-source".to_string(),
+        }];
+        new_config.searcher_config.fast_field_cache_capacity = Byte::from_bytes(1_000);
+        new_config.searcher_config.max_num_concurrent_split_streams = 10;
+        new_config.searcher_config.virtual_indexes = vec![VirtualIndexConfig {
+            index_id: "logs".to_string(),
+            indexes: vec!["logs-team-a".to_string()],
+        }];
+
+        let (reloaded_config, report) = config.reload(&new_config);
+        assert_eq!(
+            report.applied_settings,
+            vec![
+                "log_level",
+                "auth",
+                "indexer.disk_high_watermark",
+                "indexer.disabled_sources",
+                "searcher.max_num_concurrent_split_streams",
+                "searcher.virtual_indexes"
+            ]
+        );
+        assert_eq!(
+            report.restart_required_settings,
+            vec!["rest_listen_port", "searcher.fast_field_cache_capacity"]
+        );
+        assert_eq!(reloaded_config.rest_listen_port, config.rest_listen_port);
+        assert_eq!(
+            reloaded_config.searcher_config.fast_field_cache_capacity,
+            config.searcher_config.fast_field_cache_capacity
+        );
+        assert_eq!(reloaded_config.log_level, new_config.log_level);
+        assert_eq!(reloaded_config.auth, new_config.auth);
+        assert_eq!(reloaded_config.indexer_config, new_config.indexer_config);
+        assert_eq!(
+            reloaded_config
+                .searcher_config
+                .max_num_concurrent_split_streams,
+            10
+        );
+        assert_eq!(
+            reloaded_config.searcher_config.virtual_indexes,
+            new_config.searcher_config.virtual_indexes
+        );
+    }
+}
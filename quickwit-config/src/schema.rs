@@ -0,0 +1,175 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+/// JSON schema of the node config file, e.g. for editor completion and validation.
+pub const QUICKWIT_CONFIG_JSON_SCHEMA: &str =
+    include_str!("../resources/schemas/quickwit-config.schema.json");
+
+/// JSON schema of the index config file, e.g. for editor completion and validation.
+pub const INDEX_CONFIG_JSON_SCHEMA: &str =
+    include_str!("../resources/schemas/index-config.schema.json");
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        ApiKeyConfig, AuthConfig, DeadLetterSettings, DeduplicationSettings, FieldMaskConfig,
+        IndexConfig, IndexingSettings, LifecycleSettings, QuickwitConfig, RetentionSettings,
+        VersioningSettings, VirtualIndexConfig,
+    };
+
+    fn get_resource_path(resource_filename: &str) -> String {
+        format!(
+            "{}/resources/tests/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            resource_filename
+        )
+    }
+
+    fn resolve_ref<'a>(sub_schema: &'a Value, schema: &'a Value) -> &'a Value {
+        match sub_schema["$ref"].as_str() {
+            Some(reference) => {
+                let definition_name = reference
+                    .strip_prefix("#/definitions/")
+                    .expect("Schema references should point to a definition.");
+                &schema["definitions"][definition_name]
+            }
+            None => sub_schema,
+        }
+    }
+
+    /// Checks that every key of `value` is a property of the schema, so that the schema does not
+    /// fall behind the config structs.
+    fn assert_described_by_schema(value: &Value, sub_schema: &Value, schema: &Value, path: &str) {
+        let sub_schema = resolve_ref(sub_schema, schema);
+        match value {
+            Value::Object(object) => {
+                let properties = match sub_schema["properties"].as_object() {
+                    Some(properties) => properties,
+                    // Free-form objects, such as the source params.
+                    None => return,
+                };
+                for (key, value) in object {
+                    let field_path = format!("{}.{}", path, key);
+                    let property = properties.get(key).unwrap_or_else(|| {
+                        panic!("Field `{}` is missing from the schema.", field_path)
+                    });
+                    assert_described_by_schema(value, property, schema, &field_path);
+                }
+            }
+            Value::Array(items) => {
+                for (idx, item) in items.iter().enumerate() {
+                    let item_path = format!("{}[{}]", path, idx);
+                    assert_described_by_schema(item, &sub_schema["items"], schema, &item_path);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_quickwit_config_json_schema() {
+        let schema: Value = serde_json::from_str(QUICKWIT_CONFIG_JSON_SCHEMA).unwrap();
+        let config_yaml =
+            std::fs::read_to_string(get_resource_path("config/quickwit.yaml")).unwrap();
+        let mut config = serde_yaml::from_str::<QuickwitConfig>(&config_yaml).unwrap();
+        config.searcher_config.virtual_indexes = vec![VirtualIndexConfig {
+            index_id: "logs".to_string(),
+            indexes: vec!["logs-2021".to_string(), "logs-2022".to_string()],
+        }];
+        config.auth = Some(AuthConfig {
+            api_keys: vec![ApiKeyConfig {
+                key: "secret".to_string(),
+                role: "analyst".to_string(),
+            }],
+            field_masks: vec![FieldMaskConfig {
+                index_id: "logs*".to_string(),
+                roles: vec!["analyst".to_string()],
+                fields: vec!["user.email".to_string()],
+            }],
+        });
+        let config_json = serde_json::to_value(&config).unwrap();
+        assert_described_by_schema(&config_json, &schema, &schema, "");
+        let default_config_json = serde_json::to_value(&QuickwitConfig::default()).unwrap();
+        assert_described_by_schema(&default_config_json, &schema, &schema, "");
+    }
+
+    #[test]
+    fn test_index_config_json_schema() {
+        let schema: Value = serde_json::from_str(INDEX_CONFIG_JSON_SCHEMA).unwrap();
+        for resource_filename in [
+            "index_config/hdfs-logs.yaml",
+            "index_config/partial-hdfs-logs.yaml",
+            "index_config/minimal-hdfs-logs.yaml",
+        ] {
+            let config_yaml =
+                std::fs::read_to_string(get_resource_path(resource_filename)).unwrap();
+            let config_json =
+                serde_json::to_value(&serde_yaml::from_str::<IndexConfig>(&config_yaml).unwrap())
+                    .unwrap();
+            assert_described_by_schema(&config_json, &schema, &schema, "");
+        }
+        let indexing_settings = IndexingSettings {
+            demux_enabled: true,
+            ingest_timestamp_field: Some("ingest_timestamp".to_string()),
+            sort_by_timestamp: true,
+            deduplication: Some(DeduplicationSettings {
+                doc_id_field: "id".to_string(),
+                window_secs: 60,
+                max_num_docs_per_window: 1_000,
+                false_positive_rate: 0.01,
+            }),
+            versioning: Some(VersioningSettings {
+                doc_id_field: "id".to_string(),
+                version_field: "version".to_string(),
+            }),
+            retention: Some(RetentionSettings {
+                period_secs: 86_400,
+            }),
+            lifecycle: Some(LifecycleSettings {
+                write_alias: "hdfs-logs".to_string(),
+                rollover_max_age_secs: Some(86_400),
+                rollover_max_num_bytes: Some(10_000_000_000),
+                freeze_after_secs: Some(604_800),
+                delete_after_secs: Some(2_592_000),
+            }),
+            dead_letter: Some(DeadLetterSettings {
+                uri: "s3://quickwit-dead-letter/hdfs-logs".to_string(),
+                flush_interval_secs: 30,
+            }),
+            ..Default::default()
+        };
+        assert_described_by_schema(
+            &serde_json::to_value(&indexing_settings).unwrap(),
+            &schema["properties"]["indexing_settings"],
+            &schema,
+            ".indexing_settings",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Field `.indexer.max_heap_size` is missing from the schema.")]
+    fn test_assert_described_by_schema_detects_missing_fields() {
+        let schema: Value = serde_json::from_str(QUICKWIT_CONFIG_JSON_SCHEMA).unwrap();
+        let config_json = serde_json::json!({"version": 0, "indexer": {"max_heap_size": "1G"}});
+        assert_described_by_schema(&config_json, &schema, &schema, "");
+    }
+}
@@ -17,18 +17,36 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::{BTreeMap, HashSet};
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
 use anyhow::bail;
 use quickwit_common::uri::Uri;
+use quickwit_doc_mapper::DateFormat;
+use regex::Regex;
 use serde::de::Error;
 use serde::{Deserialize, Deserializer, Serialize};
 
+use crate::index_config::is_false;
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct SourceConfig {
     pub source_id: String,
     #[serde(flatten)]
     pub source_params: SourceParams,
+    /// Drops the unwanted documents emitted by the source before they are indexed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<DocFilterParams>,
+    /// Routes the documents emitted by the source to other indexes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub routing: Option<DocRoutingParams>,
+    /// Renames, removes, or parses the fields of the documents emitted by the source before
+    /// they are filtered and indexed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<DocTransformParams>,
 }
 
 impl SourceConfig {
@@ -42,6 +60,25 @@ impl SourceConfig {
     ///
     /// TODO refactor #1065
     pub fn validate(&self) -> anyhow::Result<()> {
+        if let Some(filter_params) = &self.filter {
+            filter_params.validate(&self.source_id)?;
+        }
+        if let Some(transform_params) = &self.transform {
+            transform_params.validate(&self.source_id)?;
+        }
+        if let Some(routing_params) = &self.routing {
+            routing_params.validate(&self.source_id)?;
+            match &self.source_params {
+                SourceParams::File(_) | SourceParams::Kinesis(_) | SourceParams::Pulsar(_) => {}
+                SourceParams::Kafka(kafka_params) if !kafka_params.enable_rebalancing => {}
+                _ => bail!(
+                    "Source `{}` cannot route documents: routing requires a `file`, `kafka` \
+                     (without rebalancing), `kinesis`, or `pulsar` source that can replay its \
+                     documents from a checkpoint.",
+                    self.source_id
+                ),
+            }
+        }
         match &self.source_params {
             // We want to forbid source_config with no filepath
             SourceParams::File(file_params) => {
@@ -51,9 +88,350 @@ impl SourceConfig {
                         self.source_id
                     )
                 }
+                let is_remote_file =
+                    file_params.storage_uri().is_some() || file_params.http_url().is_some();
+                if file_params.follow && is_remote_file {
+                    bail!(
+                        "Source `{}` of type `file` can only follow a local file.",
+                        self.source_id
+                    )
+                }
+                if !file_params.http_headers.is_empty() && file_params.http_url().is_none() {
+                    bail!(
+                        "Source `{}` of type `file` can only send `http_headers` when downloading \
+                         a file over HTTP(S).",
+                        self.source_id
+                    )
+                }
+                if let Some(multiline_params) = &file_params.multiline {
+                    multiline_params.validate(&self.source_id, "file")?;
+                }
+                file_params.format.validate(
+                    file_params.columns.as_deref(),
+                    file_params.multiline.is_some(),
+                    &self.source_id,
+                    "file",
+                )?;
+                file_params.framing.validate(
+                    file_params.format,
+                    file_params.multiline.is_some(),
+                    file_params.follow,
+                    &self.source_id,
+                    "file",
+                )
+            }
+            SourceParams::Dir(dir_params) => {
+                if let Err(error) = glob::Pattern::new(&dir_params.pattern) {
+                    bail!(
+                        "Source `{}` of type `dir` contains an invalid `pattern` `{}`: {}.",
+                        self.source_id,
+                        dir_params.pattern,
+                        error
+                    )
+                }
+                if dir_params.poll_interval_secs == 0 {
+                    bail!(
+                        "Source `{}` of type `dir` must have a strictly positive                          `poll_interval_secs`.",
+                        self.source_id
+                    )
+                }
+                dir_params.format.validate(
+                    dir_params.columns.as_deref(),
+                    false,
+                    &self.source_id,
+                    "dir",
+                )?;
+                dir_params.framing.validate(
+                    dir_params.format,
+                    false,
+                    false,
+                    &self.source_id,
+                    "dir",
+                )?;
+                match (dir_params.on_published, &dir_params.published_dir_path) {
+                    (DirPublishedAction::Move, None) => bail!(
+                        "Source `{}` of type `dir` must contain a `published_dir_path` to move                          the published files to.",
+                        self.source_id
+                    ),
+                    (DirPublishedAction::Move, Some(published_dir_path))
+                        if *published_dir_path == dir_params.dir_path =>
+                    {
+                        bail!(
+                            "Source `{}` of type `dir` must move the published files out of                              `dir_path`.",
+                            self.source_id
+                        )
+                    }
+                    (DirPublishedAction::Keep | DirPublishedAction::Delete, Some(_)) => bail!(
+                        "Source `{}` of type `dir` only accepts a `published_dir_path` when                          `on_published` is `move`.",
+                        self.source_id
+                    ),
+                    _ => Ok(()),
+                }
+            }
+            SourceParams::Dataset(dataset_params) => {
+                if dataset_params.uri.trim().is_empty() {
+                    bail!(
+                        "Source `{}` of type `dataset` must contain a non-empty `uri`",
+                        self.source_id
+                    )
+                }
+                Ok(())
+            }
+            SourceParams::Nats(nats_params) => {
+                if !nats_params.address.starts_with("nats://")
+                    && !nats_params.address.starts_with("tls://")
+                {
+                    bail!(
+                        "Source `{}` of type `nats` must contain an `address` starting with \
+                         `nats://` or `tls://`, got `{}`.",
+                        self.source_id,
+                        nats_params.address
+                    )
+                }
+                for (property, name) in [
+                    ("stream", &nats_params.stream),
+                    ("consumer", &nats_params.consumer),
+                ] {
+                    if !is_valid_nats_name(name) {
+                        bail!(
+                            "Source `{}` of type `nats` must contain a non-empty `{}` without \
+                             whitespaces, `.`, `*` or `>`, got `{}`.",
+                            self.source_id,
+                            property,
+                            name
+                        )
+                    }
+                }
+                if let Some(decoding_params) = &nats_params.decoding {
+                    decoding_params.validate(&self.source_id, "nats")?;
+                }
+                Ok(())
+            }
+            SourceParams::Grpc(grpc_params) => {
+                if !grpc_params.endpoint.starts_with("http://")
+                    && !grpc_params.endpoint.starts_with("https://")
+                {
+                    bail!(
+                        "Source `{}` of type `grpc` must contain an `endpoint` starting with \
+                         `http://` or `https://`, got `{}`.",
+                        self.source_id,
+                        grpc_params.endpoint
+                    )
+                }
+                if grpc_params.batch_num_docs == 0 {
+                    bail!(
+                        "Source `{}` of type `grpc` must have a strictly positive \
+                         `batch_num_docs`.",
+                        self.source_id
+                    )
+                }
+                Ok(())
+            }
+            SourceParams::IngestApi(ingest_api_params) => {
+                if ingest_api_params.index_id.trim().is_empty() {
+                    bail!(
+                        "Source `{}` of type `ingest_api` must contain a non-empty `index_id`.",
+                        self.source_id
+                    )
+                }
+                Ok(())
+            }
+            SourceParams::Mqtt(mqtt_params) => {
+                if !mqtt_params.broker_url.starts_with("mqtt://")
+                    && !mqtt_params.broker_url.starts_with("mqtts://")
+                {
+                    bail!(
+                        "Source `{}` of type `mqtt` must contain a `broker_url` starting with \
+                         `mqtt://` or `mqtts://`, got `{}`.",
+                        self.source_id,
+                        mqtt_params.broker_url
+                    )
+                }
+                if mqtt_params.topics.is_empty() {
+                    bail!(
+                        "Source `{}` of type `mqtt` must contain at least one topic.",
+                        self.source_id
+                    )
+                }
+                for topic in &mqtt_params.topics {
+                    if !is_valid_mqtt_topic_filter(topic) {
+                        bail!(
+                            "Source `{}` of type `mqtt` contains an invalid topic filter `{}`: \
+                             wildcards `+` and `#` must occupy an entire level and `#` must be \
+                             the last level.",
+                            self.source_id,
+                            topic
+                        )
+                    }
+                }
+                if mqtt_params.client_id.is_empty() {
+                    bail!(
+                        "Source `{}` of type `mqtt` must contain a non-empty `client_id`.",
+                        self.source_id
+                    )
+                }
+                if mqtt_params.password.is_some() && mqtt_params.username.is_none() {
+                    bail!(
+                        "Source `{}` of type `mqtt` must contain a `username` when a `password` \
+                         is set.",
+                        self.source_id
+                    )
+                }
+                if let Some(decoding_params) = &mqtt_params.decoding {
+                    decoding_params.validate(&self.source_id, "mqtt")?;
+                }
+                Ok(())
+            }
+            SourceParams::Otlp(otlp_params) => {
+                if otlp_params.listen_address.parse::<SocketAddr>().is_err() {
+                    bail!(
+                        "Source `{}` of type `otlp` must contain a valid `listen_address`, got \
+                         `{}`.",
+                        self.source_id,
+                        otlp_params.listen_address
+                    )
+                }
+                if otlp_params
+                    .grpc_listen_address
+                    .parse::<SocketAddr>()
+                    .is_err()
+                {
+                    bail!(
+                        "Source `{}` of type `otlp` must contain a valid `grpc_listen_address`, \
+                         got `{}`.",
+                        self.source_id,
+                        otlp_params.grpc_listen_address
+                    )
+                }
+                Ok(())
+            }
+            SourceParams::Pulsar(pulsar_params) => {
+                if !pulsar_params.address.starts_with("pulsar://")
+                    && !pulsar_params.address.starts_with("pulsar+ssl://")
+                {
+                    bail!(
+                        "Source `{}` of type `pulsar` must contain an `address` starting with \
+                         `pulsar://` or `pulsar+ssl://`, got `{}`.",
+                        self.source_id,
+                        pulsar_params.address
+                    )
+                }
+                if let Some(decoding_params) = &pulsar_params.decoding {
+                    decoding_params.validate(&self.source_id, "pulsar")?;
+                }
+                Ok(())
+            }
+            SourceParams::PubSub(pubsub_params) => {
+                if !pubsub_params.has_valid_subscription_name() {
+                    bail!(
+                        "Source `{}` of type `pubsub` must contain a `subscription` of the form \
+                         `projects/<project>/subscriptions/<subscription>`, got `{}`.",
+                        self.source_id,
+                        pubsub_params.subscription
+                    )
+                }
+                if !(1..=1_000).contains(&pubsub_params.max_messages_per_pull) {
+                    bail!(
+                        "Source `{}` of type `pubsub` must have a `max_messages_per_pull` between \
+                         1 and 1000, got `{}`.",
+                        self.source_id,
+                        pubsub_params.max_messages_per_pull
+                    )
+                }
+                if let Some(decoding_params) = &pubsub_params.decoding {
+                    decoding_params.validate(&self.source_id, "pubsub")?;
+                }
+                Ok(())
+            }
+            SourceParams::Sqs(sqs_params) => {
+                if !sqs_params.queue_url.starts_with("https://")
+                    && !sqs_params.queue_url.starts_with("http://")
+                {
+                    bail!(
+                        "Source `{}` of type `sqs` must contain a `queue_url` starting with \
+                         `https://` or `http://`, got `{}`.",
+                        self.source_id,
+                        sqs_params.queue_url
+                    )
+                }
+                Ok(())
+            }
+            SourceParams::Syslog(syslog_params) => {
+                if syslog_params.listen_address.parse::<SocketAddr>().is_err() {
+                    bail!(
+                        "Source `{}` of type `syslog` must contain a valid `listen_address`, got \
+                         `{}`.",
+                        self.source_id,
+                        syslog_params.listen_address
+                    )
+                }
+                if let Some(multiline_params) = &syslog_params.multiline {
+                    multiline_params.validate(&self.source_id, "syslog")?;
+                }
+                Ok(())
+            }
+            SourceParams::Kafka(kafka_params) => {
+                match (&kafka_params.topic, &kafka_params.topic_pattern) {
+                    (topic, None) if topic.is_empty() => bail!(
+                        "Source `{}` of type `kafka` must contain a `topic` or a `topic_pattern`.",
+                        self.source_id
+                    ),
+                    (topic, Some(_)) if !topic.is_empty() => bail!(
+                        "Source `{}` of type `kafka` cannot contain both a `topic` and a \
+                         `topic_pattern`.",
+                        self.source_id
+                    ),
+                    (_, Some(topic_pattern)) => {
+                        if let Err(error) = kafka_params.topic_regex() {
+                            bail!(
+                                "Source `{}` of type `kafka` must contain a valid \
+                                 `topic_pattern`, got `{}`: {}",
+                                self.source_id,
+                                topic_pattern,
+                                error
+                            )
+                        }
+                    }
+                    _ => {}
+                }
+                if kafka_params.enable_rebalancing
+                    && kafka_params.client_params.get("group.id").is_none()
+                {
+                    bail!(
+                        "Source `{}` of type `kafka` must contain a `client_params.group.id` to \
+                         enable rebalancing.",
+                        self.source_id
+                    )
+                }
+                if kafka_params.topic_discovery_interval_secs == Some(0) {
+                    bail!(
+                        "Source `{}` of type `kafka` must have a `topic_discovery_interval_secs` \
+                         greater than 0.",
+                        self.source_id
+                    )
+                }
+                if let Some(decoding_params) = &kafka_params.decoding {
+                    decoding_params.validate(&self.source_id)?;
+                }
+                if let Some(oauthbearer_params) = &kafka_params.oauthbearer {
+                    oauthbearer_params.validate(&self.source_id)?;
+                    for key in [
+                        "enable.sasl.oauthbearer.unsecure.jwt",
+                        "sasl.oauthbearer.method",
+                    ] {
+                        if kafka_params.client_params.get(key).is_some() {
+                            bail!(
+                                "Source `{}` of type `kafka` cannot contain both an `oauthbearer` \
+                                 provider and `client_params.{}`.",
+                                self.source_id,
+                                key
+                            )
+                        }
+                    }
+                }
                 Ok(())
             }
-            SourceParams::Kafka(_) | SourceParams::Kinesis(_) => {
+            SourceParams::Kinesis(_) => {
                 // TODO consider any validation opportunity
                 Ok(())
             }
@@ -63,20 +441,61 @@ impl SourceConfig {
 
     pub fn source_type(&self) -> &str {
         match self.source_params {
+            SourceParams::Dataset(_) => "dataset",
+            SourceParams::Dir(_) => "dir",
             SourceParams::File(_) => "file",
+            SourceParams::Grpc(_) => "grpc",
+            SourceParams::IngestApi(_) => "ingest_api",
             SourceParams::Kafka(_) => "kafka",
             SourceParams::Kinesis(_) => "kinesis",
+            SourceParams::Mqtt(_) => "mqtt",
+            SourceParams::Nats(_) => "nats",
+            SourceParams::Otlp(_) => "otlp",
+            SourceParams::PubSub(_) => "pubsub",
+            SourceParams::Pulsar(_) => "pulsar",
+            SourceParams::Sqs(_) => "sqs",
+            SourceParams::Syslog(_) => "syslog",
             SourceParams::Vec(_) => "vec",
             SourceParams::Void(_) => "void",
         }
     }
 
+    /// Returns the format of the lines read by the source. Only the file and directory sources
+    /// read CSV or TSV rows.
+    pub fn doc_format(&self) -> DocFormat {
+        match &self.source_params {
+            SourceParams::Dir(params) => params.format,
+            SourceParams::File(params) => params.format,
+            _ => DocFormat::Json,
+        }
+    }
+
+    /// Returns whether the documents emitted by the source are decoded rows whose values are
+    /// all strings and must be coerced to the types of the doc mapping before indexing.
+    pub fn decodes_rows(&self) -> bool {
+        match &self.source_params {
+            SourceParams::Dataset(params) => params.format == DatasetFormat::Csv,
+            _ => !self.doc_format().is_json(),
+        }
+    }
+
     // TODO: Remove after source factory refactor.
     pub fn params(&self) -> serde_json::Value {
         match &self.source_params {
+            SourceParams::Dataset(params) => serde_json::to_value(params),
+            SourceParams::Dir(params) => serde_json::to_value(params),
             SourceParams::File(params) => serde_json::to_value(params),
+            SourceParams::Grpc(params) => serde_json::to_value(params),
+            SourceParams::IngestApi(params) => serde_json::to_value(params),
             SourceParams::Kafka(params) => serde_json::to_value(params),
             SourceParams::Kinesis(params) => serde_json::to_value(params),
+            SourceParams::Mqtt(params) => serde_json::to_value(params),
+            SourceParams::Nats(params) => serde_json::to_value(params),
+            SourceParams::Otlp(params) => serde_json::to_value(params),
+            SourceParams::PubSub(params) => serde_json::to_value(params),
+            SourceParams::Pulsar(params) => serde_json::to_value(params),
+            SourceParams::Sqs(params) => serde_json::to_value(params),
+            SourceParams::Syslog(params) => serde_json::to_value(params),
             SourceParams::Vec(params) => serde_json::to_value(params),
             SourceParams::Void(params) => serde_json::to_value(params),
         }
@@ -87,13 +506,35 @@ impl SourceConfig {
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "source_type", content = "params")]
 pub enum SourceParams {
+    #[serde(rename = "dataset")]
+    Dataset(DatasetSourceParams),
+    #[serde(rename = "dir")]
+    Dir(DirSourceParams),
     #[serde(rename = "file")]
     File(FileSourceParams),
+    #[serde(rename = "grpc")]
+    Grpc(GrpcSourceParams),
+    #[serde(rename = "ingest_api")]
+    IngestApi(IngestApiSourceParams),
     #[serde(rename = "kafka")]
     Kafka(KafkaSourceParams),
     #[doc(hidden)]
     #[serde(rename = "kinesis")]
     Kinesis(KinesisSourceParams),
+    #[serde(rename = "mqtt")]
+    Mqtt(MqttSourceParams),
+    #[serde(rename = "nats")]
+    Nats(NatsSourceParams),
+    #[serde(rename = "otlp")]
+    Otlp(OtlpSourceParams),
+    #[serde(rename = "pubsub")]
+    PubSub(PubSubSourceParams),
+    #[serde(rename = "pulsar")]
+    Pulsar(PulsarSourceParams),
+    #[serde(rename = "sqs")]
+    Sqs(SqsSourceParams),
+    #[serde(rename = "syslog")]
+    Syslog(SyslogSourceParams),
     #[serde(rename = "vec")]
     Vec(VecSourceParams),
     #[serde(rename = "void")]
@@ -117,20 +558,250 @@ impl SourceParams {
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct FileSourceParams {
-    /// Path of the file to read. Assume stdin if None.
+    /// Path of the file to read, URI of an object of a storage such as `s3://bucket/key`, or
+    /// `http://` or `https://` URL of a file to download.
+    /// Assume stdin if None.
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(default)]
     #[serde(deserialize_with = "absolute_filepath_from_str")]
     pub filepath: Option<PathBuf>, //< If None read from stdin.
+    /// Assembles the lines of multiline records, such as stack traces, into single documents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multiline: Option<MultilineParams>,
+    /// Whether the source keeps reading the lines appended to the file after reaching its end,
+    /// following the file across rotations, instead of exiting.
+    #[serde(default)]
+    pub follow: bool,
+    /// Headers sent with the requests downloading a file over HTTP(S), such as an
+    /// `Authorization` header.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub http_headers: BTreeMap<String, String>,
+    /// Format of the lines of the file.
+    #[serde(default, skip_serializing_if = "DocFormat::is_json")]
+    pub format: DocFormat,
+    /// Names of the columns of CSV or TSV rows. The first line of the file holds them if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Vec<String>>,
+    /// How the documents of the file are delimited.
+    #[serde(default, skip_serializing_if = "DocFraming::is_ndjson")]
+    pub framing: DocFraming,
+}
+
+/// Parameters of the directory source.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DirSourceParams {
+    /// Directory watched for new files.
+    #[serde(deserialize_with = "absolute_dirpath_from_str")]
+    pub dir_path: PathBuf,
+    /// Glob pattern matched against the names of the files of the directory, e.g. `*.json`.
+    #[serde(default = "DirSourceParams::default_pattern")]
+    pub pattern: String,
+    /// Interval between two scans of the directory once all the files are read.
+    #[serde(default = "DirSourceParams::default_poll_interval_secs")]
+    pub poll_interval_secs: u64,
+    /// What happens to a file once all its documents are published.
+    #[serde(default)]
+    pub on_published: DirPublishedAction,
+    /// Directory where the published files are moved to when `on_published` is `move`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(deserialize_with = "absolute_filepath_from_str")]
+    pub published_dir_path: Option<PathBuf>,
+    /// Format of the lines of the files.
+    #[serde(default, skip_serializing_if = "DocFormat::is_json")]
+    pub format: DocFormat,
+    /// Names of the columns of CSV or TSV rows. The first line of each file holds them if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Vec<String>>,
+    /// How the documents of the files are delimited.
+    #[serde(default, skip_serializing_if = "DocFraming::is_ndjson")]
+    pub framing: DocFraming,
+}
+
+impl DirSourceParams {
+    pub fn new<P: AsRef<Path>>(dir_path: P) -> Self {
+        Self {
+            dir_path: dir_path.as_ref().to_path_buf(),
+            pattern: Self::default_pattern(),
+            poll_interval_secs: Self::default_poll_interval_secs(),
+            on_published: DirPublishedAction::default(),
+            published_dir_path: None,
+            format: DocFormat::default(),
+            columns: None,
+            framing: DocFraming::default(),
+        }
+    }
+
+    fn default_pattern() -> String {
+        "*".to_string()
+    }
+
+    fn default_poll_interval_secs() -> u64 {
+        10
+    }
+}
+
+/// Action applied by a directory source to the files whose documents are all published.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DirPublishedAction {
+    /// Leaves the file in the directory.
+    Keep,
+    /// Deletes the file.
+    Delete,
+    /// Moves the file to the `published_dir_path` directory.
+    Move,
+}
+
+impl Default for DirPublishedAction {
+    fn default() -> Self {
+        DirPublishedAction::Keep
+    }
+}
+
+/// Format of the lines read by the file and directory sources.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocFormat {
+    /// JSON objects.
+    Json,
+    /// Comma-separated values, converted into JSON objects keyed by column name.
+    Csv,
+    /// Tab-separated values, converted into JSON objects keyed by column name.
+    Tsv,
+}
+
+impl DocFormat {
+    pub fn is_json(&self) -> bool {
+        *self == DocFormat::Json
+    }
+
+    /// Returns the name of the format, as written in a source config.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DocFormat::Json => "json",
+            DocFormat::Csv => "csv",
+            DocFormat::Tsv => "tsv",
+        }
+    }
+
+    fn validate(
+        &self,
+        columns_opt: Option<&[String]>,
+        has_multiline: bool,
+        source_id: &str,
+        source_type: &str,
+    ) -> anyhow::Result<()> {
+        if self.is_json() {
+            if columns_opt.is_some() {
+                bail!(
+                    "Source `{}` of type `{}` only accepts `columns` with the `csv` and `tsv` \
+                     formats.",
+                    source_id,
+                    source_type
+                )
+            }
+            return Ok(());
+        }
+        if has_multiline {
+            bail!(
+                "Source `{}` of type `{}` cannot assemble multiline records with the `{}` format.",
+                source_id,
+                source_type,
+                self.as_str()
+            )
+        }
+        if let Some(columns) = columns_opt {
+            if columns.is_empty() || columns.iter().any(String::is_empty) {
+                bail!(
+                    "Source `{}` of type `{}` must contain non-empty `columns`.",
+                    source_id,
+                    source_type
+                )
+            }
+            let unique_columns: HashSet<&String> = columns.iter().collect();
+            if unique_columns.len() != columns.len() {
+                bail!(
+                    "Source `{}` of type `{}` must not contain duplicate `columns`.",
+                    source_id,
+                    source_type
+                )
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for DocFormat {
+    fn default() -> Self {
+        DocFormat::Json
+    }
+}
+
+/// Delimitation of the documents read by the file and directory sources.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocFraming {
+    /// One document per line.
+    Ndjson,
+    /// A single JSON array of documents, possibly spanning the whole file, read element by
+    /// element.
+    JsonArray,
+    /// Documents prefixed with their number of bytes, as a 32-bit big-endian integer.
+    LengthPrefixed,
+}
+
+impl DocFraming {
+    pub fn is_ndjson(&self) -> bool {
+        *self == DocFraming::Ndjson
+    }
+
+    fn validate(
+        &self,
+        format: DocFormat,
+        has_multiline: bool,
+        follow: bool,
+        source_id: &str,
+        source_type: &str,
+    ) -> anyhow::Result<()> {
+        if !self.is_ndjson() && (!format.is_json() || has_multiline || follow) {
+            bail!(
+                "Source `{}` of type `{}` only supports the `json_array` and `length_prefixed` \
+                 framings with the `json` format, without `multiline` or `follow`.",
+                source_id,
+                source_type
+            )
+        }
+        Ok(())
+    }
+}
+
+impl Default for DocFraming {
+    fn default() -> Self {
+        DocFraming::Ndjson
+    }
+}
+
+fn absolute_dirpath_from_str<'de, D>(deserializer: D) -> Result<PathBuf, D::Error>
+where D: Deserializer<'de> {
+    let dirpath: String = Deserialize::deserialize(deserializer)?;
+    let uri = Uri::try_new(&dirpath).map_err(D::Error::custom)?;
+    uri.filepath()
+        .map(|path| path.to_path_buf())
+        .ok_or_else(|| D::Error::custom(format!("`{}` is not a local directory path.", dirpath)))
+}
+
+// Deserializing a filepath string into an absolute filepath, or a storage URI.
+fn is_http_url(filepath: &str) -> bool {
+    filepath.starts_with("http://") || filepath.starts_with("https://")
 }
 
-// Deserializing a filepath string into an absolute filepath.
 fn absolute_filepath_from_str<'de, D>(deserializer: D) -> Result<Option<PathBuf>, D::Error>
 where D: Deserializer<'de> {
     let filepath_opt: Option<String> = Deserialize::deserialize(deserializer)?;
     if let Some(filepath) = filepath_opt {
         let uri = Uri::try_new(&filepath).map_err(D::Error::custom)?;
-        Ok(uri.filepath().map(|path| path.to_path_buf()))
+        Ok(Some(FileSourceParams::filepath_from_uri(&uri)))
     } else {
         Ok(None)
     }
@@ -140,19 +811,115 @@ impl FileSourceParams {
     pub fn file<P: AsRef<Path>>(filepath: P) -> Self {
         FileSourceParams {
             filepath: Some(filepath.as_ref().to_path_buf()),
+            multiline: None,
+            follow: false,
+            http_headers: BTreeMap::new(),
+            format: DocFormat::default(),
+            columns: None,
+            framing: DocFraming::default(),
         }
     }
 
     pub fn stdin() -> Self {
-        FileSourceParams { filepath: None }
+        FileSourceParams {
+            filepath: None,
+            multiline: None,
+            follow: false,
+            http_headers: BTreeMap::new(),
+            format: DocFormat::default(),
+            columns: None,
+            framing: DocFraming::default(),
+        }
+    }
+
+    /// Returns the path of the file for a `file://` URI, and the URI itself otherwise.
+    pub fn filepath_from_uri(uri: &Uri) -> PathBuf {
+        uri.filepath()
+            .map(|path| path.to_path_buf())
+            .unwrap_or_else(|| PathBuf::from(uri.as_ref()))
+    }
+
+    /// Returns the URI of the file if it is an object of a storage rather than a local file.
+    pub fn storage_uri(&self) -> Option<&str> {
+        self.filepath
+            .as_ref()
+            .and_then(|filepath| filepath.to_str())
+            .filter(|filepath| filepath.contains("://") && !is_http_url(filepath))
+    }
+
+    /// Returns the URL of the file if it is downloaded over HTTP(S).
+    pub fn http_url(&self) -> Option<&str> {
+        self.filepath
+            .as_ref()
+            .and_then(|filepath| filepath.to_str())
+            .filter(|filepath| is_http_url(filepath))
+    }
+}
+
+/// Format of the files of a dataset source.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DatasetFormat {
+    Csv,
+    Parquet,
+}
+
+impl DatasetFormat {
+    /// Returns the extension of the files of this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DatasetFormat::Csv => "csv",
+            DatasetFormat::Parquet => "parquet",
+        }
+    }
+}
+
+impl FromStr for DatasetFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format_str: &str) -> anyhow::Result<Self> {
+        match format_str.to_lowercase().as_str() {
+            "csv" => Ok(DatasetFormat::Csv),
+            "parquet" => Ok(DatasetFormat::Parquet),
+            _ => bail!(
+                "Unknown dataset format `{}`. Supported formats are `csv` and `parquet`.",
+                format_str
+            ),
+        }
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DatasetSourceParams {
+    /// Storage URI of the directory holding the dataset files.
+    pub uri: String,
+    /// Format of the dataset files.
+    pub format: DatasetFormat,
+}
+
+/// Parameters of the source draining the ingest API queue of an index.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IngestApiSourceParams {
+    /// ID of the index whose documents are pushed to `POST /api/v1/<index_id>/ingest`.
+    pub index_id: String,
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct KafkaSourceParams {
-    /// Name of the topic that the source consumes.
+    /// Name of the topic that the source consumes. Exclusive with `topic_pattern`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub topic: String,
+    /// Regular expression matching the entire names of the topics that the source consumes.
+    /// The topics created after the source started are discovered periodically.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic_pattern: Option<String>,
+    /// Interval in seconds between two discoveries of the topics matching `topic_pattern`.
+    /// Defaults to 30 seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic_discovery_interval_secs: Option<u64>,
     /// Kafka client log level. Possible values are `debug`, `info`, `warn`, and `error`.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub client_log_level: Option<String>,
@@ -160,45 +927,2352 @@ pub struct KafkaSourceParams {
     #[serde(default = "serde_json::Value::default")]
     #[serde(skip_serializing_if = "serde_json::Value::is_null")]
     pub client_params: serde_json::Value,
+    /// Unwraps the Debezium change event envelopes of the messages when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debezium: Option<DebeziumParams>,
+    /// Shares the partitions among the indexing pipelines of the source, as members of the
+    /// consumer group `client_params.group.id` using cooperative rebalancing, instead of
+    /// consuming all the partitions in every pipeline.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub enable_rebalancing: bool,
+    /// Authenticates with SASL/OAUTHBEARER, using the tokens of the provider. The source renews
+    /// the tokens before they expire.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oauthbearer: Option<KafkaOAuthBearerParams>,
+    /// Decodes the messages into JSON documents. By default, messages must hold JSON documents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decoding: Option<KafkaDecodingParams>,
 }
 
-#[doc(hidden)]
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct KinesisSourceParams {
-    stream_name: String,
-}
+impl KafkaSourceParams {
+    /// Compiles `topic_pattern`, anchored so that it matches entire topic names.
+    pub fn topic_regex(&self) -> anyhow::Result<Option<Regex>> {
+        match &self.topic_pattern {
+            Some(topic_pattern) => Ok(Some(Regex::new(&format!("^(?:{})$", topic_pattern))?)),
+            None => Ok(None),
+        }
+    }
 
-#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
-#[serde(deny_unknown_fields)]
-pub struct VecSourceParams {
-    pub items: Vec<String>,
-    pub batch_num_docs: usize,
-    #[serde(default)]
-    pub partition: String,
+    /// Returns the interval between two discoveries of the topics matching `topic_pattern`.
+    pub fn topic_discovery_interval(&self) -> Duration {
+        Duration::from_secs(self.topic_discovery_interval_secs.unwrap_or(30))
+    }
 }
 
+/// Provider of the SASL/OAUTHBEARER tokens of a Kafka source.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-#[serde(deny_unknown_fields)]
-pub struct VoidSourceParams;
+#[serde(tag = "provider", rename_all = "snake_case", deny_unknown_fields)]
+pub enum KafkaOAuthBearerParams {
+    /// Requests tokens from an OAuth 2.0 token endpoint with the client credentials grant, e.g.
+    /// for Confluent Cloud.
+    ClientCredentials {
+        token_endpoint_url: String,
+        client_id: String,
+        client_secret: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        scope: Option<String>,
+        /// SASL extensions sent to the brokers along with the tokens, e.g. `logicalCluster` and
+        /// `identityPoolId` for Confluent Cloud.
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        extensions: BTreeMap<String, String>,
+    },
+    /// Signs tokens for the IAM access control of Amazon MSK with the AWS credentials of the
+    /// default provider chain.
+    AwsMskIam { region: String },
+}
 
-#[cfg(test)]
-mod tests {
-    use quickwit_common::uri::Uri;
+impl KafkaOAuthBearerParams {
+    fn validate(&self, source_id: &str) -> anyhow::Result<()> {
+        match self {
+            KafkaOAuthBearerParams::ClientCredentials {
+                token_endpoint_url,
+                client_id,
+                ..
+            } => {
+                if !token_endpoint_url.starts_with("https://")
+                    && !token_endpoint_url.starts_with("http://")
+                {
+                    bail!(
+                        "Source `{}` of type `kafka` must contain an `oauthbearer.token_endpoint_url` \
+                         starting with `https://` or `http://`, got `{}`.",
+                        source_id,
+                        token_endpoint_url
+                    )
+                }
+                if client_id.is_empty() {
+                    bail!(
+                        "Source `{}` of type `kafka` must contain a non-empty \
+                         `oauthbearer.client_id`.",
+                        source_id
+                    )
+                }
+            }
+            KafkaOAuthBearerParams::AwsMskIam { region } => {
+                if region.is_empty() {
+                    bail!(
+                        "Source `{}` of type `kafka` must contain a non-empty \
+                         `oauthbearer.region`.",
+                        source_id
+                    )
+                }
+            }
+        }
+        Ok(())
+    }
+}
 
-    use crate::FileSourceParams;
+/// Encoding of the messages of a Kafka source that do not hold JSON documents.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "format", rename_all = "snake_case", deny_unknown_fields)]
+pub enum KafkaDecodingParams {
+    /// Avro records in the Confluent wire format, whose schemas are fetched from a Confluent
+    /// Schema Registry by ID.
+    Avro {
+        schema_registry_url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        schema_registry_username: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        schema_registry_password: Option<String>,
+    },
+    /// Protobuf messages, decoded with a descriptor set.
+    Protobuf(ProtobufDecodingParams),
+}
 
-    #[test]
-    fn test_file_source_params_serialization() {
-        {
-            let json = r#"{
-                "filepath": "source-path.json"
-            }"#;
-            let file_params = serde_yaml::from_str::<FileSourceParams>(json).unwrap();
-            let uri = Uri::try_new("source-path.json").unwrap();
+impl KafkaDecodingParams {
+    fn validate(&self, source_id: &str) -> anyhow::Result<()> {
+        match self {
+            KafkaDecodingParams::Avro {
+                schema_registry_url,
+                schema_registry_username,
+                schema_registry_password,
+            } => {
+                if !schema_registry_url.starts_with("https://")
+                    && !schema_registry_url.starts_with("http://")
+                {
+                    bail!(
+                        "Source `{}` of type `kafka` must contain a \
+                         `decoding.schema_registry_url` starting with `https://` or `http://`, \
+                         got `{}`.",
+                        source_id,
+                        schema_registry_url
+                    )
+                }
+                if schema_registry_username.is_some() != schema_registry_password.is_some() {
+                    bail!(
+                        "Source `{}` of type `kafka` must contain both \
+                         `decoding.schema_registry_username` and \
+                         `decoding.schema_registry_password`, or neither.",
+                        source_id
+                    )
+                }
+            }
+            KafkaDecodingParams::Protobuf(protobuf_params) => {
+                protobuf_params.validate(source_id, "kafka")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Encoding of the message payloads of a source that do not hold JSON documents.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "format", rename_all = "snake_case")]
+pub enum PayloadDecodingParams {
+    /// Protobuf messages, decoded with a descriptor set.
+    Protobuf(ProtobufDecodingParams),
+}
+
+impl PayloadDecodingParams {
+    fn validate(&self, source_id: &str, source_type: &str) -> anyhow::Result<()> {
+        match self {
+            PayloadDecodingParams::Protobuf(protobuf_params) => {
+                protobuf_params.validate(source_id, source_type)
+            }
+        }
+    }
+}
+
+/// Parameters for decoding protobuf messages into JSON documents.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProtobufDecodingParams {
+    /// URI of the file descriptor set describing the messages, as written by
+    /// `protoc --include_imports --descriptor_set_out`.
+    pub descriptor_set_uri: String,
+    /// Fully qualified name of the message type, e.g. `acme.logs.v1.LogRecord`.
+    pub message_name: String,
+}
+
+impl ProtobufDecodingParams {
+    fn validate(&self, source_id: &str, source_type: &str) -> anyhow::Result<()> {
+        if let Err(error) = Uri::try_new(&self.descriptor_set_uri) {
+            bail!(
+                "Source `{}` of type `{}` must contain a valid `decoding.descriptor_set_uri`: {}",
+                source_id,
+                source_type,
+                error
+            )
+        }
+        let is_valid_message_name = !self.message_name.is_empty()
+            && self.message_name.split('.').all(|name| {
+                !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+            });
+        if !is_valid_message_name {
+            bail!(
+                "Source `{}` of type `{}` must contain a fully qualified `decoding.message_name`, \
+                 e.g. `acme.logs.v1.LogRecord`, got `{}`.",
+                source_id,
+                source_type,
+                self.message_name
+            )
+        }
+        Ok(())
+    }
+}
+
+/// Parameters for indexing the Debezium change events of a database table.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DebeziumParams {
+    /// Field set to `"true"` in the documents of deleted rows and to `"false"` otherwise.
+    #[serde(default = "DebeziumParams::default_delete_marker_field")]
+    pub delete_marker_field: String,
+    /// Field receiving the `ts_ms` timestamp of the change event, converted to seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp_field: Option<String>,
+}
+
+impl DebeziumParams {
+    fn default_delete_marker_field() -> String {
+        "__deleted".to_string()
+    }
+}
+
+impl Default for DebeziumParams {
+    fn default() -> Self {
+        Self {
+            delete_marker_field: Self::default_delete_marker_field(),
+            timestamp_field: None,
+        }
+    }
+}
+
+/// Parameters for assembling the lines of multiline records, such as Java stack traces, into
+/// single documents. A line starts a new record if it matches `start_pattern`, or if it does not
+/// match `continuation_pattern`, and is appended to the pending record otherwise.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MultilineParams {
+    /// Regular expression matching the first line of a record. Exclusive with
+    /// `continuation_pattern`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_pattern: Option<String>,
+    /// Regular expression matching the lines continuing a record. Exclusive with
+    /// `start_pattern`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub continuation_pattern: Option<String>,
+    /// Maximum number of lines of a record. Longer records are split.
+    #[serde(default = "MultilineParams::default_max_lines")]
+    pub max_lines: usize,
+    /// Maximum time in milliseconds a pending record waits for its next line before it is
+    /// emitted, for the sources that do not know when a record ends.
+    #[serde(default = "MultilineParams::default_max_wait_millis")]
+    pub max_wait_millis: u64,
+}
+
+impl MultilineParams {
+    fn default_max_lines() -> usize {
+        500
+    }
+
+    fn default_max_wait_millis() -> u64 {
+        1_000
+    }
+
+    /// Compiles the start and continuation patterns, anchored at the beginning of the lines.
+    pub fn regexes(&self) -> anyhow::Result<(Option<Regex>, Option<Regex>)> {
+        let compile = |pattern: &Option<String>| -> anyhow::Result<Option<Regex>> {
+            pattern
+                .as_ref()
+                .map(|pattern| Regex::new(&format!("^(?:{})", pattern)))
+                .transpose()
+                .map_err(anyhow::Error::from)
+        };
+        Ok((
+            compile(&self.start_pattern)?,
+            compile(&self.continuation_pattern)?,
+        ))
+    }
+
+    fn validate(&self, source_id: &str, source_type: &str) -> anyhow::Result<()> {
+        if self.start_pattern.is_some() == self.continuation_pattern.is_some() {
+            bail!(
+                "Source `{}` of type `{}` must contain exactly one of `multiline.start_pattern` \
+                 and `multiline.continuation_pattern`.",
+                source_id,
+                source_type
+            )
+        }
+        if let Err(error) = self.regexes() {
+            bail!(
+                "Source `{}` of type `{}` must contain a valid multiline pattern: {}",
+                source_id,
+                source_type,
+                error
+            )
+        }
+        if self.max_lines == 0 {
+            bail!(
+                "Source `{}` of type `{}` must have a `multiline.max_lines` greater than 0.",
+                source_id,
+                source_type
+            )
+        }
+        Ok(())
+    }
+}
+
+/// Filter applied to the documents of a source before they are indexed. A document is kept if it
+/// matches all the `include` conditions and none of the `exclude` conditions.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DocFilterParams {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<DocFilterCondition>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<DocFilterCondition>,
+}
+
+impl DocFilterParams {
+    fn validate(&self, source_id: &str) -> anyhow::Result<()> {
+        if self.include.is_empty() && self.exclude.is_empty() {
+            bail!(
+                "Source `{}` must contain at least one `filter.include` or `filter.exclude` \
+                 condition.",
+                source_id
+            )
+        }
+        for condition in self.include.iter().chain(&self.exclude) {
+            condition.validate(source_id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Condition on the value of a field of a document.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DocFilterCondition {
+    /// Path of the field, with the names of nested fields separated by dots, e.g. `http.status`.
+    pub field: String,
+    #[serde(flatten)]
+    pub operator: DocFilterOperator,
+}
+
+impl DocFilterCondition {
+    fn validate(&self, source_id: &str) -> anyhow::Result<()> {
+        match &self.operator {
+            DocFilterOperator::Matches(pattern) => {
+                if let Err(error) = Regex::new(pattern) {
+                    bail!(
+                        "Source `{}` must contain a valid filter pattern for field `{}`: {}",
+                        source_id,
+                        self.field,
+                        error
+                    )
+                }
+            }
+            DocFilterOperator::MinLevel(level) => {
+                if log_level_rank(level).is_none() {
+                    bail!(
+                        "Source `{}` must contain a valid filter level for field `{}`. Value `{}` \
+                         is not supported.",
+                        source_id,
+                        self.field,
+                        level
+                    )
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocFilterOperator {
+    /// The field is equal to the value.
+    Equals(serde_json::Value),
+    /// The field is equal to one of the values.
+    In(Vec<serde_json::Value>),
+    /// The field is a string matching the regular expression.
+    Matches(String),
+    /// The field is present, or absent if `false`.
+    Exists(bool),
+    /// The field is a number greater than or equal to the value.
+    Gte(f64),
+    /// The field is a number less than or equal to the value.
+    Lte(f64),
+    /// The field is a log level at least as severe as the level, e.g. `WARN` keeps the
+    /// warnings, errors, and more severe levels.
+    MinLevel(String),
+}
+
+/// Returns the rank of a log level by increasing severity, following the syslog severities and
+/// the usual names of the logging libraries. Levels are case insensitive.
+pub fn log_level_rank(level: &str) -> Option<u8> {
+    let rank = match level.to_lowercase().as_str() {
+        "trace" => 0,
+        "debug" => 1,
+        "info" | "informational" => 2,
+        "notice" => 3,
+        "warn" | "warning" => 4,
+        "error" | "err" => 5,
+        "critical" | "crit" | "fatal" => 6,
+        "alert" => 7,
+        "emergency" | "emerg" => 8,
+        _ => return None,
+    };
+    Some(rank)
+}
+
+/// Transformation of the documents of a source before they are filtered and parsed by the doc
+/// mapper. The steps are applied in order.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DocTransformParams {
+    pub steps: Vec<DocTransformStep>,
+}
+
+impl DocTransformParams {
+    fn validate(&self, source_id: &str) -> anyhow::Result<()> {
+        if self.steps.is_empty() {
+            bail!(
+                "Source `{}` must contain at least one `transform.steps` step.",
+                source_id
+            )
+        }
+        for step in &self.steps {
+            let field_paths: Vec<&str> = match step {
+                DocTransformStep::Rename { from, to } => {
+                    if from == to {
+                        bail!(
+                            "Source `{}` cannot rename field `{}` to itself.",
+                            source_id,
+                            from
+                        )
+                    }
+                    vec![from.as_str(), to.as_str()]
+                }
+                DocTransformStep::Remove { fields } => {
+                    if fields.is_empty() {
+                        bail!(
+                            "Source `{}` must contain at least one field to remove.",
+                            source_id
+                        )
+                    }
+                    fields.iter().map(String::as_str).collect()
+                }
+                DocTransformStep::Set { field, .. } => vec![field.as_str()],
+                DocTransformStep::ParseTimestamp {
+                    field,
+                    input_formats,
+                    ..
+                } => {
+                    if input_formats.is_empty() {
+                        bail!(
+                            "Source `{}` must contain at least one input format to parse field \
+                             `{}`.",
+                            source_id,
+                            field
+                        )
+                    }
+                    vec![field.as_str()]
+                }
+                DocTransformStep::DropIf(condition) => {
+                    condition.validate(source_id)?;
+                    vec![condition.field.as_str()]
+                }
+            };
+            if let Some(field_path) = field_paths
+                .into_iter()
+                .find(|field_path| field_path.split('.').any(str::is_empty))
+            {
+                bail!(
+                    "Source `{}` contains an invalid transform field path `{}`.",
+                    source_id,
+                    field_path
+                )
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Step of a [`DocTransformParams`]. Fields are designated by their path, with the names of
+/// nested fields separated by dots, e.g. `http.status`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum DocTransformStep {
+    /// Moves the value of a field to another field, replacing its value if any.
+    Rename { from: String, to: String },
+    /// Removes fields.
+    Remove { fields: Vec<String> },
+    /// Sets a field to a constant value.
+    Set {
+        field: String,
+        value: serde_json::Value,
+    },
+    /// Parses the value of a field with the first of the input formats that accepts it, and
+    /// replaces it with the date in the output format, a number of seconds since the Unix epoch
+    /// by default.
+    ParseTimestamp {
+        field: String,
+        input_formats: Vec<DateFormat>,
+        #[serde(default = "DocTransformStep::default_output_format")]
+        output_format: DateFormat,
+    },
+    /// Drops the documents matching the condition.
+    DropIf(DocFilterCondition),
+}
+
+impl DocTransformStep {
+    fn default_output_format() -> DateFormat {
+        DateFormat::EpochSecs
+    }
+}
+
+/// Routing of the documents of a source to several indexes following the value of a field. The
+/// documents that do not match any route are indexed into the index declaring the source.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DocRoutingParams {
+    /// Path of the routing field, with the names of nested fields separated by dots, e.g.
+    /// `service.name`.
+    pub field: String,
+    pub routes: Vec<DocRoute>,
+}
+
+impl DocRoutingParams {
+    fn validate(&self, source_id: &str) -> anyhow::Result<()> {
+        if self.field.is_empty() {
+            bail!(
+                "Source `{}` must contain a non-empty `routing.field`.",
+                source_id
+            )
+        }
+        if self.routes.is_empty() {
+            bail!("Source `{}` must contain at least one route.", source_id)
+        }
+        let mut index_ids = HashSet::new();
+        let mut values = HashSet::new();
+        for route in &self.routes {
+            if !index_ids.insert(route.index_id.as_str()) {
+                bail!(
+                    "Source `{}` routes documents to index `{}` more than once.",
+                    source_id,
+                    route.index_id
+                )
+            }
+            if route.values.is_empty() {
+                bail!(
+                    "Source `{}` must contain at least one value for the route to index `{}`.",
+                    source_id,
+                    route.index_id
+                )
+            }
+            for value in &route.values {
+                if !values.insert(value.to_string()) {
+                    bail!(
+                        "Source `{}` routes the value `{}` to several indexes.",
+                        source_id,
+                        value
+                    )
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Route sending the documents whose routing field is equal to one of the values to an index.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DocRoute {
+    pub index_id: String,
+    pub values: Vec<serde_json::Value>,
+}
+
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KinesisSourceParams {
+    pub stream_name: String,
+    /// Region of the stream. Defaults to the region of the environment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Custom endpoint of the Kinesis API, for instance LocalStack's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MqttSourceParams {
+    /// URL of the MQTT broker, e.g. `mqtt://localhost:1883` or `mqtts://broker.example.com:8883`.
+    pub broker_url: String,
+    /// Topic filters that the source subscribes to with QoS 1. Filters may contain the `+`
+    /// (single level) and `#` (multi level) wildcards, e.g. `devices/+/telemetry`.
+    pub topics: Vec<String>,
+    /// Client identifier of the persistent session opened on the broker.
+    #[serde(default = "MqttSourceParams::default_client_id")]
+    pub client_id: String,
+    /// Username used to authenticate against the broker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// Password used to authenticate against the broker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Decodes the message payloads into JSON documents. By default, payloads must hold JSON
+    /// documents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decoding: Option<PayloadDecodingParams>,
+}
+
+impl MqttSourceParams {
+    fn default_client_id() -> String {
+        "quickwit".to_string()
+    }
+}
+
+/// Wildcards must occupy an entire topic level and the multi-level wildcard `#` can only be the
+/// last level of a topic filter.
+fn is_valid_mqtt_topic_filter(topic: &str) -> bool {
+    if topic.is_empty() {
+        return false;
+    }
+    let levels: Vec<&str> = topic.split('/').collect();
+    let num_levels = levels.len();
+    levels.iter().enumerate().all(|(idx, level)| {
+        if level.contains('#') {
+            *level == "#" && idx == num_levels - 1
+        } else {
+            !level.contains('+') || *level == "+"
+        }
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NatsSourceParams {
+    /// Address of the NATS server, e.g. `nats://localhost:4222`.
+    pub address: String,
+    /// Name of the JetStream stream that the source consumes.
+    pub stream: String,
+    /// Name of the durable consumer created on the stream for the source.
+    #[serde(default = "NatsSourceParams::default_consumer")]
+    pub consumer: String,
+    /// Only consumes the messages whose subject matches this filter, e.g. `logs.>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter_subject: Option<String>,
+    /// Token used to authenticate against the server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_token: Option<String>,
+    /// Decodes the message payloads into JSON documents. By default, payloads must hold JSON
+    /// documents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decoding: Option<PayloadDecodingParams>,
+}
+
+impl NatsSourceParams {
+    fn default_consumer() -> String {
+        "quickwit".to_string()
+    }
+}
+
+/// Stream and consumer names cannot contain whitespaces, nor the `.`, `*` and `>` characters
+/// reserved by subjects.
+fn is_valid_nats_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '.' | '*' | '>'))
+}
+
+/// Parameters of a source proxying to a source plugin, i.e. a sidecar process implementing the
+/// `SourcePluginService` gRPC service.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GrpcSourceParams {
+    /// Endpoint of the plugin, e.g. `http://localhost:50051`.
+    pub endpoint: String,
+    /// Parameters forwarded as is to the plugin.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<serde_json::Value>,
+    /// Maximum number of documents that the plugin returns per batch.
+    #[serde(default = "GrpcSourceParams::default_batch_num_docs")]
+    pub batch_num_docs: u64,
+}
+
+impl GrpcSourceParams {
+    fn default_batch_num_docs() -> u64 {
+        1_000
+    }
+}
+
+/// Parameters of the OpenTelemetry Protocol (OTLP) trace receiver.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OtlpSourceParams {
+    /// Socket address on which the receiver accepts OTLP/HTTP export requests.
+    #[serde(default = "OtlpSourceParams::default_listen_address")]
+    pub listen_address: String,
+    /// Socket address on which the receiver accepts OTLP/gRPC export requests.
+    #[serde(default = "OtlpSourceParams::default_grpc_listen_address")]
+    pub grpc_listen_address: String,
+}
+
+impl OtlpSourceParams {
+    fn default_listen_address() -> String {
+        "0.0.0.0:4318".to_string()
+    }
+
+    fn default_grpc_listen_address() -> String {
+        "0.0.0.0:4317".to_string()
+    }
+}
+
+impl Default for OtlpSourceParams {
+    fn default() -> Self {
+        Self {
+            listen_address: Self::default_listen_address(),
+            grpc_listen_address: Self::default_grpc_listen_address(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PubSubSourceParams {
+    /// Fully qualified name of the Google Pub/Sub subscription that the source pulls from, e.g.
+    /// `projects/my-project/subscriptions/logs`.
+    pub subscription: String,
+    /// Maximum number of messages returned by a single pull request.
+    #[serde(default = "PubSubSourceParams::default_max_messages_per_pull")]
+    pub max_messages_per_pull: i32,
+    /// Decodes the message payloads into JSON documents. By default, payloads must hold JSON
+    /// documents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decoding: Option<PayloadDecodingParams>,
+}
+
+impl PubSubSourceParams {
+    fn default_max_messages_per_pull() -> i32 {
+        1_000
+    }
+
+    fn has_valid_subscription_name(&self) -> bool {
+        let segments: Vec<&str> = self.subscription.split('/').collect();
+        match segments[..] {
+            ["projects", project, "subscriptions", subscription] => {
+                !project.is_empty() && !subscription.is_empty()
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PulsarSourceParams {
+    /// Name of the topic that the source consumes, e.g. `persistent://public/default/logs`.
+    /// All the partitions of a partitioned topic are consumed.
+    pub topic: String,
+    /// Address of the Pulsar broker or proxy, e.g. `pulsar://localhost:6650`.
+    pub address: String,
+    /// Token used to authenticate against the cluster.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_token: Option<String>,
+    /// Decodes the message payloads into JSON documents. By default, payloads must hold JSON
+    /// documents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decoding: Option<PayloadDecodingParams>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SqsSourceParams {
+    /// URL of the SQS queue receiving the S3 event notifications, e.g.
+    /// `https://sqs.us-east-1.amazonaws.com/123456789012/my-queue`.
+    pub queue_url: String,
+    /// Region of the queue. Only required when the queue URL does not contain the region, for
+    /// instance with a custom endpoint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Whether the S3 objects are deleted once their documents are published.
+    #[serde(default)]
+    pub delete_objects: bool,
+}
+
+/// Parameters of the syslog listener.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyslogSourceParams {
+    /// Socket address on which the listener receives syslog messages.
+    #[serde(default = "SyslogSourceParams::default_listen_address")]
+    pub listen_address: String,
+    /// Transport protocol of the listener.
+    #[serde(default)]
+    pub protocol: SyslogProtocol,
+    /// Assembles the consecutive messages of a sender forming multiline records, such as stack
+    /// traces, into single documents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multiline: Option<MultilineParams>,
+}
+
+impl SyslogSourceParams {
+    fn default_listen_address() -> String {
+        "0.0.0.0:1514".to_string()
+    }
+}
+
+impl Default for SyslogSourceParams {
+    fn default() -> Self {
+        Self {
+            listen_address: Self::default_listen_address(),
+            protocol: SyslogProtocol::default(),
+            multiline: None,
+        }
+    }
+}
+
+/// Transport protocol of a syslog source.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyslogProtocol {
+    /// One message per datagram.
+    Udp,
+    /// Messages framed with octet counting or delimited by newlines (RFC 6587).
+    Tcp,
+}
+
+impl Default for SyslogProtocol {
+    fn default() -> Self {
+        SyslogProtocol::Udp
+    }
+}
+
+impl SyslogProtocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SyslogProtocol::Udp => "udp",
+            SyslogProtocol::Tcp => "tcp",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VecSourceParams {
+    pub items: Vec<String>,
+    pub batch_num_docs: usize,
+    #[serde(default)]
+    pub partition: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VoidSourceParams;
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use quickwit_common::uri::Uri;
+
+    use crate::{
+        DatasetFormat, DatasetSourceParams, DebeziumParams, DirPublishedAction, DirSourceParams,
+        FileSourceParams, GrpcSourceParams, IngestApiSourceParams, KafkaSourceParams,
+        MqttSourceParams, MultilineParams, NatsSourceParams, OtlpSourceParams, PubSubSourceParams,
+        PulsarSourceParams, SourceConfig, SourceParams, SqsSourceParams, SyslogProtocol,
+        SyslogSourceParams,
+    };
+
+    #[test]
+    fn test_file_source_params_serialization() {
+        {
+            let json = r#"{
+                "filepath": "source-path.json"
+            }"#;
+            let file_params = serde_yaml::from_str::<FileSourceParams>(json).unwrap();
+            let uri = Uri::try_new("source-path.json").unwrap();
+            assert!(file_params.storage_uri().is_none());
             assert_eq!(
                 file_params.filepath.unwrap().as_path(),
                 uri.filepath().unwrap()
             )
         }
+        {
+            let yaml = r#"
+                filepath: s3://my-bucket/logs/2022-02-22.json
+            "#;
+            let file_params = serde_yaml::from_str::<FileSourceParams>(yaml).unwrap();
+            assert_eq!(
+                file_params.storage_uri(),
+                Some("s3://my-bucket/logs/2022-02-22.json")
+            );
+            assert!(!file_params.follow);
+        }
+        {
+            let yaml = r#"
+                filepath: s3://my-bucket/logs/2022-02-22.json
+                follow: true
+            "#;
+            let source_config = SourceConfig {
+                source_id: "file-source".to_string(),
+                source_params: SourceParams::File(serde_yaml::from_str(yaml).unwrap()),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("can only follow a local file"));
+        }
+        {
+            let yaml = r#"
+                filepath: https://example.com/dumps/2022-02-22.json
+                http_headers:
+                    Authorization: Bearer my-token
+            "#;
+            let file_params = serde_yaml::from_str::<FileSourceParams>(yaml).unwrap();
+            assert!(file_params.storage_uri().is_none());
+            assert_eq!(
+                file_params.http_url(),
+                Some("https://example.com/dumps/2022-02-22.json")
+            );
+            assert_eq!(
+                file_params.http_headers.get("Authorization").unwrap(),
+                "Bearer my-token"
+            );
+        }
+        {
+            let yaml = r#"
+                filepath: s3://my-bucket/logs/2022-02-22.json
+                http_headers:
+                    Authorization: Bearer my-token
+            "#;
+            let source_config = SourceConfig {
+                source_id: "file-source".to_string(),
+                source_params: SourceParams::File(serde_yaml::from_str(yaml).unwrap()),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("can only send `http_headers`"));
+        }
+    }
+
+    #[test]
+    fn test_file_source_params_multiline() {
+        let file_source_config = |yaml: &str| SourceConfig {
+            source_id: "file-source".to_string(),
+            source_params: SourceParams::File(serde_yaml::from_str(yaml).unwrap()),
+            filter: None,
+            routing: None,
+            transform: None,
+        };
+        {
+            let source_config = file_source_config(
+                r#"
+                filepath: app.log
+                multiline:
+                  start_pattern: '\d{4}-\d{2}-\d{2}'
+            "#,
+            );
+            source_config.validate().unwrap();
+            let multiline_params = match source_config.source_params {
+                SourceParams::File(file_params) => file_params.multiline.unwrap(),
+                _ => unreachable!(),
+            };
+            assert_eq!(multiline_params.max_lines, 500);
+            assert_eq!(multiline_params.max_wait_millis, 1_000);
+            let (start_regex_opt, continuation_regex_opt) = multiline_params.regexes().unwrap();
+            assert!(start_regex_opt.unwrap().is_match("2022-02-22 ERROR"));
+            assert!(continuation_regex_opt.is_none());
+        }
+        {
+            let source_config = file_source_config(
+                r#"
+                filepath: app.log
+                multiline:
+                  start_pattern: '\d+'
+                  continuation_pattern: '\s'
+            "#,
+            );
+            assert!(source_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("exactly one of"));
+        }
+        {
+            let source_config = file_source_config(
+                r#"
+                filepath: app.log
+                multiline:
+                  continuation_pattern: '(\s'
+            "#,
+            );
+            assert!(source_config.validate().is_err());
+        }
+        {
+            let multiline_params = MultilineParams {
+                start_pattern: None,
+                continuation_pattern: Some(r"\s+at ".to_string()),
+                max_lines: 0,
+                max_wait_millis: 1_000,
+            };
+            let source_config = SourceConfig {
+                source_id: "syslog-source".to_string(),
+                source_params: SourceParams::Syslog(SyslogSourceParams {
+                    multiline: Some(multiline_params),
+                    ..Default::default()
+                }),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_dataset_source_params_serialization() {
+        let yaml = r#"
+            uri: s3://my-bucket/datasets/trips
+            format: parquet
+        "#;
+        let dataset_params = serde_yaml::from_str::<DatasetSourceParams>(yaml).unwrap();
+        assert_eq!(
+            dataset_params,
+            DatasetSourceParams {
+                uri: "s3://my-bucket/datasets/trips".to_string(),
+                format: DatasetFormat::Parquet,
+            }
+        );
+        assert_eq!("CSV".parse::<DatasetFormat>().unwrap(), DatasetFormat::Csv);
+        assert!("avro".parse::<DatasetFormat>().is_err());
+    }
+
+    #[test]
+    fn test_kafka_source_params_debezium_serialization() {
+        {
+            let yaml = r#"
+                topic: dbserver.inventory.customers
+            "#;
+            let kafka_params = serde_yaml::from_str::<KafkaSourceParams>(yaml).unwrap();
+            assert!(kafka_params.debezium.is_none());
+        }
+        {
+            let yaml = r#"
+                topic: dbserver.inventory.customers
+                debezium: {}
+            "#;
+            let kafka_params = serde_yaml::from_str::<KafkaSourceParams>(yaml).unwrap();
+            assert_eq!(kafka_params.debezium, Some(DebeziumParams::default()));
+            assert_eq!(
+                kafka_params.debezium.unwrap().delete_marker_field,
+                "__deleted"
+            );
+        }
+        {
+            let yaml = r#"
+                topic: dbserver.inventory.customers
+                debezium:
+                  delete_marker_field: is_deleted
+                  timestamp_field: updated_at
+            "#;
+            let kafka_params = serde_yaml::from_str::<KafkaSourceParams>(yaml).unwrap();
+            assert_eq!(
+                kafka_params.debezium,
+                Some(DebeziumParams {
+                    delete_marker_field: "is_deleted".to_string(),
+                    timestamp_field: Some("updated_at".to_string()),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_kafka_source_params_topic_pattern() {
+        let kafka_source_config = |yaml: &str| SourceConfig {
+            source_id: "kafka-source".to_string(),
+            source_params: SourceParams::Kafka(serde_yaml::from_str(yaml).unwrap()),
+            filter: None,
+            routing: None,
+            transform: None,
+        };
+        {
+            let source_config = kafka_source_config(
+                r#"
+                topic_pattern: "logs-.*"
+                topic_discovery_interval_secs: 10
+            "#,
+            );
+            source_config.validate().unwrap();
+            let kafka_params = match source_config.source_params {
+                SourceParams::Kafka(kafka_params) => kafka_params,
+                _ => unreachable!(),
+            };
+            assert!(kafka_params.topic.is_empty());
+            assert_eq!(
+                kafka_params.topic_discovery_interval(),
+                Duration::from_secs(10)
+            );
+            let topic_regex = kafka_params.topic_regex().unwrap().unwrap();
+            assert!(topic_regex.is_match("logs-checkout"));
+            assert!(!topic_regex.is_match("app-logs-checkout"));
+        }
+        {
+            let source_config = kafka_source_config("client_log_level: info");
+            assert!(source_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("must contain a `topic` or a `topic_pattern`"));
+        }
+        {
+            let source_config = kafka_source_config(
+                r#"
+                topic: logs
+                topic_pattern: "logs-.*"
+            "#,
+            );
+            assert!(source_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("cannot contain both"));
+        }
+        {
+            let source_config = kafka_source_config(r#"topic_pattern: "logs-(""#);
+            assert!(source_config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_source_config_filter() {
+        let yaml = r#"
+            source_id: kafka-source
+            source_type: kafka
+            params:
+              topic: logs
+            filter:
+              include:
+                - field: level
+                  min_level: WARN
+              exclude:
+                - field: http.path
+                  matches: ^/health
+                - field: service
+                  in: [canary, smoke-test]
+        "#;
+        let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+        source_config.validate().unwrap();
+        let filter_params = source_config.filter.unwrap();
+        assert_eq!(
+            filter_params.include,
+            vec![DocFilterCondition {
+                field: "level".to_string(),
+                operator: DocFilterOperator::MinLevel("WARN".to_string()),
+            }]
+        );
+        assert_eq!(filter_params.exclude.len(), 2);
+        assert_eq!(
+            filter_params.exclude[1].operator,
+            DocFilterOperator::In(vec![
+                serde_json::json!("canary"),
+                serde_json::json!("smoke-test")
+            ])
+        );
+
+        let filter_config = |filter_params: DocFilterParams| SourceConfig {
+            source_id: "void-source".to_string(),
+            source_params: SourceParams::void(),
+            filter: Some(filter_params),
+            routing: None,
+            transform: None,
+        };
+        assert!(filter_config(DocFilterParams::default())
+            .validate()
+            .is_err());
+        let invalid_level = DocFilterParams {
+            include: vec![DocFilterCondition {
+                field: "level".to_string(),
+                operator: DocFilterOperator::MinLevel("LOUD".to_string()),
+            }],
+            exclude: Vec::new(),
+        };
+        assert!(filter_config(invalid_level)
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .contains("Value `LOUD` is not supported"));
+        let invalid_pattern = DocFilterParams {
+            include: Vec::new(),
+            exclude: vec![DocFilterCondition {
+                field: "message".to_string(),
+                operator: DocFilterOperator::Matches("(".to_string()),
+            }],
+        };
+        assert!(filter_config(invalid_pattern).validate().is_err());
+    }
+
+    #[test]
+    fn test_source_config_transform() {
+        let yaml = r#"
+            source_id: hdfs-logs-kafka-source
+            source_type: kafka
+            params:
+              topic: logs
+            transform:
+              steps:
+                - rename:
+                    from: ts
+                    to: timestamp
+                - remove:
+                    fields: [debug, http.headers]
+                - set:
+                    field: env
+                    value: prod
+                - parse_timestamp:
+                    field: timestamp
+                    input_formats: [rfc3339, "%Y-%m-%d %H:%M:%S"]
+                - drop_if:
+                    field: level
+                    in: [DEBUG, TRACE]
+        "#;
+        let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+        source_config.validate().unwrap();
+        let transform_params = source_config.transform.unwrap();
+        assert_eq!(transform_params.steps.len(), 5);
+        assert_eq!(
+            transform_params.steps[0],
+            DocTransformStep::Rename {
+                from: "ts".to_string(),
+                to: "timestamp".to_string(),
+            }
+        );
+        assert_eq!(
+            transform_params.steps[3],
+            DocTransformStep::ParseTimestamp {
+                field: "timestamp".to_string(),
+                input_formats: vec![
+                    DateFormat::Rfc3339,
+                    DateFormat::Strftime("%Y-%m-%d %H:%M:%S".to_string())
+                ],
+                output_format: DateFormat::EpochSecs,
+            }
+        );
+        assert_eq!(
+            transform_params.steps[4],
+            DocTransformStep::DropIf(DocFilterCondition {
+                field: "level".to_string(),
+                operator: DocFilterOperator::In(vec![
+                    serde_json::json!("DEBUG"),
+                    serde_json::json!("TRACE")
+                ]),
+            })
+        );
+
+        let transform_config = |steps: Vec<DocTransformStep>| SourceConfig {
+            source_id: "void-source".to_string(),
+            source_params: SourceParams::void(),
+            filter: None,
+            routing: None,
+            transform: Some(DocTransformParams { steps }),
+        };
+        assert!(transform_config(Vec::new()).validate().is_err());
+        assert!(transform_config(vec![DocTransformStep::Rename {
+            from: "ts".to_string(),
+            to: "ts".to_string(),
+        }])
+        .validate()
+        .unwrap_err()
+        .to_string()
+        .contains("cannot rename field `ts` to itself"));
+        assert!(transform_config(vec![DocTransformStep::Remove {
+            fields: vec!["http..headers".to_string()],
+        }])
+        .validate()
+        .unwrap_err()
+        .to_string()
+        .contains("invalid transform field path `http..headers`"));
+        assert!(transform_config(vec![DocTransformStep::ParseTimestamp {
+            field: "timestamp".to_string(),
+            input_formats: Vec::new(),
+            output_format: DateFormat::EpochSecs,
+        }])
+        .validate()
+        .is_err());
+        assert!(
+            transform_config(vec![DocTransformStep::DropIf(DocFilterCondition {
+                field: "level".to_string(),
+                operator: DocFilterOperator::MinLevel("LOUD".to_string()),
+            })])
+            .validate()
+            .is_err()
+        );
+        {
+            let yaml = r#"
+                steps:
+                  - parse_timestamp:
+                      field: timestamp
+                      input_formats: [yesterday]
+            "#;
+            assert!(serde_yaml::from_str::<DocTransformParams>(yaml).is_err());
+        }
+    }
+
+    #[test]
+    fn test_source_config_routing() {
+        let yaml = r#"
+            source_id: firehose
+            source_type: kafka
+            params:
+              topic: logs
+            routing:
+              field: service.name
+              routes:
+                - index_id: checkout-logs
+                  values: [checkout, payment]
+                - index_id: search-logs
+                  values: [search]
+        "#;
+        let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+        source_config.validate().unwrap();
+        let routing_params = source_config.routing.as_ref().unwrap();
+        assert_eq!(routing_params.field, "service.name");
+        assert_eq!(
+            routing_params.routes[0],
+            DocRoute {
+                index_id: "checkout-logs".to_string(),
+                values: vec![serde_json::json!("checkout"), serde_json::json!("payment")],
+            }
+        );
+
+        let routing_config = |source_params: SourceParams, routes: Vec<DocRoute>| SourceConfig {
+            source_id: "firehose".to_string(),
+            source_params,
+            filter: None,
+            routing: Some(DocRoutingParams {
+                field: "service.name".to_string(),
+                routes,
+            }),
+            transform: None,
+        };
+        let route = |index_id: &str, value: &str| DocRoute {
+            index_id: index_id.to_string(),
+            values: vec![serde_json::json!(value)],
+        };
+        assert!(routing_config(SourceParams::file("logs.json"), Vec::new())
+            .validate()
+            .is_err());
+        assert!(routing_config(
+            SourceParams::file("logs.json"),
+            vec![
+                route("checkout-logs", "checkout"),
+                route("search-logs", "checkout")
+            ]
+        )
+        .validate()
+        .unwrap_err()
+        .to_string()
+        .contains("routes the value `\"checkout\"` to several indexes"));
+        assert!(routing_config(
+            SourceParams::void(),
+            vec![route("checkout-logs", "checkout")]
+        )
+        .validate()
+        .unwrap_err()
+        .to_string()
+        .contains("cannot route documents"));
+    }
+
+    #[test]
+    fn test_kafka_source_params_enable_rebalancing() {
+        let kafka_source_config = |yaml: &str| SourceConfig {
+            source_id: "kafka-source".to_string(),
+            source_params: SourceParams::Kafka(serde_yaml::from_str(yaml).unwrap()),
+            filter: None,
+            routing: None,
+            transform: None,
+        };
+        {
+            let source_config = kafka_source_config(
+                r#"
+                topic: logs
+                client_params:
+                  group.id: quickwit
+                enable_rebalancing: true
+            "#,
+            );
+            source_config.validate().unwrap();
+        }
+        {
+            let source_config = kafka_source_config(
+                r#"
+                topic: logs
+                enable_rebalancing: true
+            "#,
+            );
+            assert!(source_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("must contain a `client_params.group.id`"));
+        }
+    }
+
+    #[test]
+    fn test_kafka_source_params_oauthbearer() {
+        let kafka_source_config = |yaml: &str| SourceConfig {
+            source_id: "kafka-source".to_string(),
+            source_params: SourceParams::Kafka(serde_yaml::from_str(yaml).unwrap()),
+            filter: None,
+            routing: None,
+            transform: None,
+        };
+        {
+            let source_config = kafka_source_config(
+                r#"
+                topic: logs
+                oauthbearer:
+                  provider: client_credentials
+                  token_endpoint_url: https://auth.example.com/oauth2/token
+                  client_id: quickwit
+                  client_secret: secret
+                  extensions:
+                    logicalCluster: lkc-123
+            "#,
+            );
+            source_config.validate().unwrap();
+            let kafka_params = match source_config.source_params {
+                SourceParams::Kafka(kafka_params) => kafka_params,
+                _ => panic!("Expected Kafka source params."),
+            };
+            assert_eq!(
+                kafka_params.oauthbearer,
+                Some(KafkaOAuthBearerParams::ClientCredentials {
+                    token_endpoint_url: "https://auth.example.com/oauth2/token".to_string(),
+                    client_id: "quickwit".to_string(),
+                    client_secret: "secret".to_string(),
+                    scope: None,
+                    extensions: [("logicalCluster".to_string(), "lkc-123".to_string())]
+                        .into_iter()
+                        .collect(),
+                })
+            );
+        }
+        {
+            let source_config = kafka_source_config(
+                r#"
+                topic: logs
+                oauthbearer:
+                  provider: aws_msk_iam
+                  region: us-east-1
+            "#,
+            );
+            source_config.validate().unwrap();
+        }
+        {
+            let source_config = kafka_source_config(
+                r#"
+                topic: logs
+                oauthbearer:
+                  provider: aws_msk_iam
+                  region: ""
+            "#,
+            );
+            assert!(source_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("non-empty `oauthbearer.region`"));
+        }
+        {
+            let source_config = kafka_source_config(
+                r#"
+                topic: logs
+                client_params:
+                  sasl.oauthbearer.method: oidc
+                oauthbearer:
+                  provider: aws_msk_iam
+                  region: us-east-1
+            "#,
+            );
+            assert!(source_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("`client_params.sasl.oauthbearer.method`"));
+        }
+        {
+            let kafka_params = serde_yaml::from_str::<KafkaSourceParams>(
+                r#"
+                topic: logs
+                oauthbearer:
+                  provider: kerberos
+            "#,
+            );
+            assert!(kafka_params.is_err());
+        }
+    }
+
+    #[test]
+    fn test_kafka_source_params_decoding() {
+        let kafka_source_config = |yaml: &str| SourceConfig {
+            source_id: "kafka-source".to_string(),
+            source_params: SourceParams::Kafka(serde_yaml::from_str(yaml).unwrap()),
+            filter: None,
+            routing: None,
+            transform: None,
+        };
+        {
+            let source_config = kafka_source_config(
+                r#"
+                topic: logs
+                decoding:
+                  format: avro
+                  schema_registry_url: http://localhost:8081
+            "#,
+            );
+            source_config.validate().unwrap();
+            let kafka_params = match source_config.source_params {
+                SourceParams::Kafka(kafka_params) => kafka_params,
+                _ => panic!("Expected Kafka source params."),
+            };
+            assert_eq!(
+                kafka_params.decoding,
+                Some(KafkaDecodingParams::Avro {
+                    schema_registry_url: "http://localhost:8081".to_string(),
+                    schema_registry_username: None,
+                    schema_registry_password: None,
+                })
+            );
+        }
+        {
+            let source_config = kafka_source_config(
+                r#"
+                topic: logs
+                decoding:
+                  format: avro
+                  schema_registry_url: https://psrc-123.confluent.cloud
+                  schema_registry_username: key
+            "#,
+            );
+            assert!(source_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("or neither"));
+        }
+        {
+            let source_config = kafka_source_config(
+                r#"
+                topic: logs
+                decoding:
+                  format: avro
+                  schema_registry_url: localhost:8081
+            "#,
+            );
+            assert!(source_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("`decoding.schema_registry_url`"));
+        }
+        {
+            let source_config = kafka_source_config(
+                r#"
+                topic: logs
+                decoding:
+                  format: protobuf
+                  descriptor_set_uri: s3://my-bucket/logs.desc
+                  message_name: acme.logs.v1.LogRecord
+            "#,
+            );
+            source_config.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_payload_decoding_params() {
+        {
+            let yaml = r#"
+                source_id: pulsar-logs
+                source_type: pulsar
+                params:
+                  topic: logs
+                  address: pulsar://localhost:6650
+                  decoding:
+                    format: protobuf
+                    descriptor_set_uri: ./logs.desc
+                    message_name: acme.logs.v1.LogRecord
+            "#;
+            let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+            source_config.validate().unwrap();
+            let pulsar_params = match source_config.source_params {
+                SourceParams::Pulsar(pulsar_params) => pulsar_params,
+                _ => panic!("Expected Pulsar source params."),
+            };
+            assert_eq!(
+                pulsar_params.decoding,
+                Some(PayloadDecodingParams::Protobuf(ProtobufDecodingParams {
+                    descriptor_set_uri: "./logs.desc".to_string(),
+                    message_name: "acme.logs.v1.LogRecord".to_string(),
+                }))
+            );
+        }
+        {
+            let yaml = r#"
+                format: protobuf
+                descriptor_set_uri: ./logs.desc
+                message_name: acme.logs.v1.LogRecord
+                message_names: [acme.logs.v1.LogRecord]
+            "#;
+            assert!(serde_yaml::from_str::<PayloadDecodingParams>(yaml).is_err());
+        }
+        for message_name in [
+            "",
+            "acme.logs.",
+            "acme..LogRecord",
+            "acme.logs.v1.Log-Record",
+        ] {
+            let decoding_params = PayloadDecodingParams::Protobuf(ProtobufDecodingParams {
+                descriptor_set_uri: "./logs.desc".to_string(),
+                message_name: message_name.to_string(),
+            });
+            assert!(decoding_params
+                .validate("nats-logs", "nats")
+                .unwrap_err()
+                .to_string()
+                .contains("`decoding.message_name`"));
+        }
+    }
+
+    #[test]
+    fn test_otlp_source_params_serialization() {
+        {
+            let otlp_params = serde_yaml::from_str::<OtlpSourceParams>("{}").unwrap();
+            assert_eq!(otlp_params.listen_address, "0.0.0.0:4318");
+            assert_eq!(otlp_params.grpc_listen_address, "0.0.0.0:4317");
+        }
+        {
+            let yaml = r#"
+                source_id: otlp-traces
+                source_type: otlp
+                params:
+                  listen_address: 127.0.0.1:4319
+                  grpc_listen_address: 127.0.0.1:4320
+            "#;
+            let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+            assert_eq!(source_config.source_type(), "otlp");
+            assert_eq!(
+                source_config.source_params,
+                SourceParams::Otlp(OtlpSourceParams {
+                    listen_address: "127.0.0.1:4319".to_string(),
+                    grpc_listen_address: "127.0.0.1:4320".to_string(),
+                })
+            );
+            source_config.validate().unwrap();
+        }
+        {
+            let source_config = SourceConfig {
+                source_id: "otlp-traces".to_string(),
+                source_params: SourceParams::Otlp(OtlpSourceParams {
+                    listen_address: "localhost".to_string(),
+                    grpc_listen_address: "0.0.0.0:4317".to_string(),
+                }),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config.validate().is_err());
+        }
+        {
+            let source_config = SourceConfig {
+                source_id: "otlp-traces".to_string(),
+                source_params: SourceParams::Otlp(OtlpSourceParams {
+                    listen_address: "0.0.0.0:4318".to_string(),
+                    grpc_listen_address: "localhost".to_string(),
+                }),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_ingest_api_source_params_serialization() {
+        {
+            let yaml = r#"
+                source_id: ingest-api
+                source_type: ingest_api
+                params:
+                  index_id: my-index
+            "#;
+            let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+            assert_eq!(source_config.source_type(), "ingest_api");
+            assert_eq!(
+                source_config.source_params,
+                SourceParams::IngestApi(IngestApiSourceParams {
+                    index_id: "my-index".to_string(),
+                })
+            );
+            source_config.validate().unwrap();
+        }
+        {
+            let source_config = SourceConfig {
+                source_id: "ingest-api".to_string(),
+                source_params: SourceParams::IngestApi(IngestApiSourceParams {
+                    index_id: "".to_string(),
+                }),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_syslog_source_params_serialization() {
+        {
+            let syslog_params = serde_yaml::from_str::<SyslogSourceParams>("{}").unwrap();
+            assert_eq!(syslog_params, SyslogSourceParams::default());
+            assert_eq!(syslog_params.listen_address, "0.0.0.0:1514");
+            assert_eq!(syslog_params.protocol, SyslogProtocol::Udp);
+        }
+        {
+            let yaml = r#"
+                source_id: syslog
+                source_type: syslog
+                params:
+                  listen_address: 127.0.0.1:6514
+                  protocol: tcp
+            "#;
+            let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+            assert_eq!(source_config.source_type(), "syslog");
+            assert_eq!(
+                source_config.source_params,
+                SourceParams::Syslog(SyslogSourceParams {
+                    listen_address: "127.0.0.1:6514".to_string(),
+                    protocol: SyslogProtocol::Tcp,
+                    multiline: None,
+                })
+            );
+            source_config.validate().unwrap();
+        }
+        {
+            let yaml = r#"
+                protocol: sctp
+            "#;
+            assert!(serde_yaml::from_str::<SyslogSourceParams>(yaml).is_err());
+        }
+        {
+            let source_config = SourceConfig {
+                source_id: "syslog".to_string(),
+                source_params: SourceParams::Syslog(SyslogSourceParams {
+                    listen_address: "localhost".to_string(),
+                    protocol: SyslogProtocol::Udp,
+                    multiline: None,
+                }),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_dir_source_params_serialization() {
+        {
+            let yaml = r#"
+                source_id: dir
+                source_type: dir
+                params:
+                  dir_path: /var/log/app
+            "#;
+            let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+            assert_eq!(source_config.source_type(), "dir");
+            assert_eq!(
+                source_config.source_params,
+                SourceParams::Dir(DirSourceParams::new("/var/log/app"))
+            );
+            source_config.validate().unwrap();
+        }
+        {
+            let yaml = r#"
+                source_id: dir
+                source_type: dir
+                params:
+                  dir_path: /var/log/app
+                  pattern: "*.json"
+                  poll_interval_secs: 1
+                  on_published: move
+                  published_dir_path: /var/log/app-published
+            "#;
+            let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+            assert_eq!(
+                source_config.source_params,
+                SourceParams::Dir(DirSourceParams {
+                    dir_path: PathBuf::from("/var/log/app"),
+                    pattern: "*.json".to_string(),
+                    poll_interval_secs: 1,
+                    on_published: DirPublishedAction::Move,
+                    published_dir_path: Some(PathBuf::from("/var/log/app-published")),
+                    format: DocFormat::Json,
+                    columns: None,
+                    framing: DocFraming::Ndjson,
+                })
+            );
+            source_config.validate().unwrap();
+        }
+        {
+            let mut dir_params = DirSourceParams::new("/var/log/app");
+            dir_params.on_published = DirPublishedAction::Move;
+            let source_config = SourceConfig {
+                source_id: "dir".to_string(),
+                source_params: SourceParams::Dir(dir_params),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("must contain a `published_dir_path`"));
+        }
+        {
+            let mut dir_params = DirSourceParams::new("/var/log/app");
+            dir_params.pattern = "[*.json".to_string();
+            let source_config = SourceConfig {
+                source_id: "dir".to_string(),
+                source_params: SourceParams::Dir(dir_params),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("invalid `pattern`"));
+        }
+    }
+
+    #[test]
+    fn test_source_params_doc_format() {
+        {
+            let yaml = r#"
+                source_id: trips
+                source_type: dir
+                params:
+                  dir_path: /data/trips
+                  pattern: "*.csv"
+                  format: csv
+            "#;
+            let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+            assert_eq!(source_config.doc_format(), DocFormat::Csv);
+            assert!(source_config.decodes_rows());
+            source_config.validate().unwrap();
+        }
+        {
+            let source_config = SourceConfig {
+                source_id: "trips".to_string(),
+                source_params: SourceParams::Dataset(DatasetSourceParams {
+                    uri: "s3://datasets/trips".to_string(),
+                    format: DatasetFormat::Csv,
+                }),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert_eq!(source_config.doc_format(), DocFormat::Json);
+            assert!(source_config.decodes_rows());
+        }
+        {
+            let source_config = SourceConfig {
+                source_id: "trips".to_string(),
+                source_params: SourceParams::Dataset(DatasetSourceParams {
+                    uri: "s3://datasets/trips".to_string(),
+                    format: DatasetFormat::Parquet,
+                }),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(!source_config.decodes_rows());
+        }
+        {
+            let yaml = r#"
+                source_id: trips
+                source_type: file
+                params:
+                  filepath: /data/trips.tsv
+                  format: tsv
+                  columns: [vendor, city, distance]
+            "#;
+            let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+            assert_eq!(source_config.doc_format(), DocFormat::Tsv);
+            let file_params = match &source_config.source_params {
+                SourceParams::File(file_params) => file_params,
+                _ => panic!("Expected a file source."),
+            };
+            assert_eq!(
+                file_params.columns.as_deref().unwrap(),
+                ["vendor", "city", "distance"]
+            );
+            source_config.validate().unwrap();
+        }
+        {
+            let mut file_params = FileSourceParams::file("/data/trips.json");
+            file_params.columns = Some(vec!["vendor".to_string()]);
+            let source_config = SourceConfig {
+                source_id: "trips".to_string(),
+                source_params: SourceParams::File(file_params),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert_eq!(source_config.doc_format(), DocFormat::Json);
+            assert!(source_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("only accepts `columns` with the `csv` and `tsv` formats"));
+        }
+        {
+            let mut dir_params = DirSourceParams::new("/data/trips");
+            dir_params.format = DocFormat::Csv;
+            dir_params.columns = Some(vec!["vendor".to_string(), "vendor".to_string()]);
+            let source_config = SourceConfig {
+                source_id: "trips".to_string(),
+                source_params: SourceParams::Dir(dir_params),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("duplicate `columns`"));
+        }
+        {
+            let mut file_params = FileSourceParams::file("/data/app.csv");
+            file_params.format = DocFormat::Csv;
+            file_params.multiline = Some(MultilineParams {
+                start_pattern: Some(r"\d+".to_string()),
+                continuation_pattern: None,
+                max_lines: 500,
+                max_wait_millis: 1_000,
+            });
+            let source_config = SourceConfig {
+                source_id: "app".to_string(),
+                source_params: SourceParams::File(file_params),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("cannot assemble multiline records with the `csv` format"));
+        }
+    }
+
+    #[test]
+    fn test_source_params_doc_framing() {
+        {
+            let yaml = r#"
+                source_id: events
+                source_type: file
+                params:
+                  filepath: s3://bucket/events.json
+                  framing: json_array
+            "#;
+            let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+            let file_params = match &source_config.source_params {
+                SourceParams::File(file_params) => file_params,
+                _ => panic!("Expected a file source."),
+            };
+            assert_eq!(file_params.framing, DocFraming::JsonArray);
+            source_config.validate().unwrap();
+        }
+        {
+            let mut dir_params = DirSourceParams::new("/data/events");
+            dir_params.framing = DocFraming::LengthPrefixed;
+            let source_config = SourceConfig {
+                source_id: "events".to_string(),
+                source_params: SourceParams::Dir(dir_params.clone()),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            source_config.validate().unwrap();
+            assert_eq!(
+                source_config.params()["framing"],
+                serde_json::json!("length_prefixed")
+            );
+            dir_params.format = DocFormat::Csv;
+            let source_config = SourceConfig {
+                source_id: "events".to_string(),
+                source_params: SourceParams::Dir(dir_params),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config
+                .validate()
+                .unwrap_err()
+                .to_string()
+                .contains("only supports the `json_array` and `length_prefixed` framings"));
+        }
+        {
+            let mut file_params = FileSourceParams::file("/var/log/events.json");
+            file_params.framing = DocFraming::JsonArray;
+            file_params.follow = true;
+            let source_config = SourceConfig {
+                source_id: "events".to_string(),
+                source_params: SourceParams::File(file_params),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_pulsar_source_params_serialization() {
+        {
+            let yaml = r#"
+                source_id: pulsar-logs
+                source_type: pulsar
+                params:
+                  topic: persistent://public/default/logs
+                  address: pulsar://localhost:6650
+            "#;
+            let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+            assert_eq!(source_config.source_type(), "pulsar");
+            assert_eq!(
+                source_config.source_params,
+                SourceParams::Pulsar(PulsarSourceParams {
+                    topic: "persistent://public/default/logs".to_string(),
+                    address: "pulsar://localhost:6650".to_string(),
+                    auth_token: None,
+                    decoding: None,
+                })
+            );
+            source_config.validate().unwrap();
+        }
+        {
+            let yaml = r#"
+                topic: logs
+                address: pulsar+ssl://pulsar.example.com:6651
+                auth_token: my-token
+            "#;
+            let pulsar_params = serde_yaml::from_str::<PulsarSourceParams>(yaml).unwrap();
+            assert_eq!(pulsar_params.auth_token.as_deref(), Some("my-token"));
+        }
+        {
+            let source_config = SourceConfig {
+                source_id: "pulsar-logs".to_string(),
+                source_params: SourceParams::Pulsar(PulsarSourceParams {
+                    topic: "logs".to_string(),
+                    address: "localhost:6650".to_string(),
+                    auth_token: None,
+                    decoding: None,
+                }),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_pubsub_source_params_serialization() {
+        {
+            let yaml = r#"
+                source_id: pubsub-logs
+                source_type: pubsub
+                params:
+                  subscription: projects/my-project/subscriptions/logs
+            "#;
+            let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+            assert_eq!(source_config.source_type(), "pubsub");
+            assert_eq!(
+                source_config.source_params,
+                SourceParams::PubSub(PubSubSourceParams {
+                    subscription: "projects/my-project/subscriptions/logs".to_string(),
+                    max_messages_per_pull: 1_000,
+                    decoding: None,
+                })
+            );
+            source_config.validate().unwrap();
+        }
+        for (subscription, max_messages_per_pull) in [
+            ("logs", 1_000),
+            ("projects/my-project/topics/logs", 1_000),
+            ("projects//subscriptions/logs", 1_000),
+            ("projects/my-project/subscriptions/logs", 0),
+            ("projects/my-project/subscriptions/logs", 1_001),
+        ] {
+            let source_config = SourceConfig {
+                source_id: "pubsub-logs".to_string(),
+                source_params: SourceParams::PubSub(PubSubSourceParams {
+                    subscription: subscription.to_string(),
+                    max_messages_per_pull,
+                    decoding: None,
+                }),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_sqs_source_params_serialization() {
+        {
+            let yaml = r#"
+                source_id: sqs-logs
+                source_type: sqs
+                params:
+                  queue_url: https://sqs.us-east-1.amazonaws.com/123456789012/logs
+            "#;
+            let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+            assert_eq!(source_config.source_type(), "sqs");
+            assert_eq!(
+                source_config.source_params,
+                SourceParams::Sqs(SqsSourceParams {
+                    queue_url: "https://sqs.us-east-1.amazonaws.com/123456789012/logs".to_string(),
+                    region: None,
+                    delete_objects: false,
+                })
+            );
+            source_config.validate().unwrap();
+        }
+        {
+            let yaml = r#"
+                queue_url: http://localhost:4566/000000000000/logs
+                region: eu-west-1
+                delete_objects: true
+            "#;
+            let sqs_params = serde_yaml::from_str::<SqsSourceParams>(yaml).unwrap();
+            assert_eq!(sqs_params.region.as_deref(), Some("eu-west-1"));
+            assert!(sqs_params.delete_objects);
+        }
+        {
+            let source_config = SourceConfig {
+                source_id: "sqs-logs".to_string(),
+                source_params: SourceParams::Sqs(SqsSourceParams {
+                    queue_url: "sqs.us-east-1.amazonaws.com/123456789012/logs".to_string(),
+                    region: None,
+                    delete_objects: false,
+                }),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_nats_source_params_serialization() {
+        {
+            let yaml = r#"
+                source_id: nats-logs
+                source_type: nats
+                params:
+                  address: nats://localhost:4222
+                  stream: LOGS
+            "#;
+            let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+            assert_eq!(source_config.source_type(), "nats");
+            assert_eq!(
+                source_config.source_params,
+                SourceParams::Nats(NatsSourceParams {
+                    address: "nats://localhost:4222".to_string(),
+                    stream: "LOGS".to_string(),
+                    consumer: "quickwit".to_string(),
+                    filter_subject: None,
+                    auth_token: None,
+                    decoding: None,
+                })
+            );
+            source_config.validate().unwrap();
+        }
+        {
+            let yaml = r#"
+                address: tls://nats.example.com:4222
+                stream: LOGS
+                consumer: quickwit-logs
+                filter_subject: logs.>
+                auth_token: my-token
+            "#;
+            let nats_params = serde_yaml::from_str::<NatsSourceParams>(yaml).unwrap();
+            assert_eq!(nats_params.consumer, "quickwit-logs");
+            assert_eq!(nats_params.filter_subject.as_deref(), Some("logs.>"));
+            assert_eq!(nats_params.auth_token.as_deref(), Some("my-token"));
+        }
+        for (address, stream, consumer) in [
+            ("localhost:4222", "LOGS", "quickwit"),
+            ("nats://localhost:4222", "", "quickwit"),
+            ("nats://localhost:4222", "LOGS.>", "quickwit"),
+            ("nats://localhost:4222", "LOGS", "quickwit logs"),
+        ] {
+            let source_config = SourceConfig {
+                source_id: "nats-logs".to_string(),
+                source_params: SourceParams::Nats(NatsSourceParams {
+                    address: address.to_string(),
+                    stream: stream.to_string(),
+                    consumer: consumer.to_string(),
+                    filter_subject: None,
+                    auth_token: None,
+                    decoding: None,
+                }),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_grpc_source_params_serialization() {
+        {
+            let yaml = r#"
+                source_id: my-plugin
+                source_type: grpc
+                params:
+                  endpoint: http://localhost:50051
+                  params:
+                    table: logs
+            "#;
+            let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+            assert_eq!(source_config.source_type(), "grpc");
+            assert_eq!(
+                source_config.source_params,
+                SourceParams::Grpc(GrpcSourceParams {
+                    endpoint: "http://localhost:50051".to_string(),
+                    params: Some(serde_json::json!({"table": "logs"})),
+                    batch_num_docs: 1_000,
+                })
+            );
+            source_config.validate().unwrap();
+        }
+        for (endpoint, batch_num_docs) in
+            [("localhost:50051", 1_000), ("http://localhost:50051", 0)]
+        {
+            let source_config = SourceConfig {
+                source_id: "my-plugin".to_string(),
+                source_params: SourceParams::Grpc(GrpcSourceParams {
+                    endpoint: endpoint.to_string(),
+                    params: None,
+                    batch_num_docs,
+                }),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_mqtt_source_params_serialization() {
+        {
+            let yaml = r#"
+                source_id: mqtt-telemetry
+                source_type: mqtt
+                params:
+                  broker_url: mqtt://localhost:1883
+                  topics:
+                    - devices/+/telemetry
+                    - gateways/#
+            "#;
+            let source_config = serde_yaml::from_str::<SourceConfig>(yaml).unwrap();
+            assert_eq!(source_config.source_type(), "mqtt");
+            assert_eq!(
+                source_config.source_params,
+                SourceParams::Mqtt(MqttSourceParams {
+                    broker_url: "mqtt://localhost:1883".to_string(),
+                    topics: vec!["devices/+/telemetry".to_string(), "gateways/#".to_string()],
+                    client_id: "quickwit".to_string(),
+                    username: None,
+                    password: None,
+                    decoding: None,
+                })
+            );
+            source_config.validate().unwrap();
+        }
+        {
+            let yaml = r#"
+                broker_url: mqtts://broker.example.com:8883
+                topics: [telemetry]
+                client_id: quickwit-telemetry
+                username: quickwit
+                password: my-password
+            "#;
+            let mqtt_params = serde_yaml::from_str::<MqttSourceParams>(yaml).unwrap();
+            assert_eq!(mqtt_params.client_id, "quickwit-telemetry");
+            assert_eq!(mqtt_params.username.as_deref(), Some("quickwit"));
+            assert_eq!(mqtt_params.password.as_deref(), Some("my-password"));
+        }
+        for (broker_url, topics) in [
+            ("localhost:1883", vec!["telemetry"]),
+            ("mqtt://localhost:1883", Vec::new()),
+            ("mqtt://localhost:1883", vec![""]),
+            ("mqtt://localhost:1883", vec!["devices/#/telemetry"]),
+            ("mqtt://localhost:1883", vec!["devices/dev+/telemetry"]),
+            ("mqtt://localhost:1883", vec!["devices/#+"]),
+        ] {
+            let source_config = SourceConfig {
+                source_id: "mqtt-telemetry".to_string(),
+                source_params: SourceParams::Mqtt(MqttSourceParams {
+                    broker_url: broker_url.to_string(),
+                    topics: topics.into_iter().map(str::to_string).collect(),
+                    client_id: "quickwit".to_string(),
+                    username: None,
+                    password: None,
+                    decoding: None,
+                }),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            assert!(source_config.validate().is_err());
+        }
     }
 }
@@ -0,0 +1,166 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use anyhow::{bail, Context};
+
+/// Replaces the `${VAR}` and `${VAR:-default}` expressions of a config file by the values of
+/// the corresponding environment variables, so that the same file can be deployed in several
+/// environments. The default value is used when the variable is unset or empty. `$${` is
+/// rendered as a literal `${`. Comment lines are left untouched.
+pub fn render_config_template(config_content: &[u8]) -> anyhow::Result<Vec<u8>> {
+    render_config_template_with(config_content, |key| std::env::var(key).ok())
+}
+
+fn render_config_template_with<F>(config_content: &[u8], lookup_env: F) -> anyhow::Result<Vec<u8>>
+where F: Fn(&str) -> Option<String> {
+    let config_str =
+        std::str::from_utf8(config_content).context("Config file is not valid UTF-8.")?;
+    let mut rendered = String::with_capacity(config_str.len());
+    for (line_idx, line) in config_str.split_inclusive('\n').enumerate() {
+        let trimmed_line = line.trim_start();
+        if trimmed_line.starts_with('#') || trimmed_line.starts_with("//") {
+            rendered.push_str(line);
+            continue;
+        }
+        render_line(line, &lookup_env, &mut rendered)
+            .with_context(|| format!("Failed to render line {} of config file.", line_idx + 1))?;
+    }
+    Ok(rendered.into_bytes())
+}
+
+fn render_line<F>(line: &str, lookup_env: &F, rendered: &mut String) -> anyhow::Result<()>
+where F: Fn(&str) -> Option<String> {
+    let mut remaining = line;
+    while let Some(dollar_pos) = remaining.find('$') {
+        rendered.push_str(&remaining[..dollar_pos]);
+        remaining = &remaining[dollar_pos..];
+        if let Some(escaped) = remaining.strip_prefix("$${") {
+            rendered.push_str("${");
+            remaining = escaped;
+            continue;
+        }
+        let expression_start = match remaining.strip_prefix("${") {
+            Some(expression_start) => expression_start,
+            None => {
+                rendered.push('$');
+                remaining = &remaining[1..];
+                continue;
+            }
+        };
+        let expression_len = match expression_start.find('}') {
+            Some(expression_len) => expression_len,
+            None => bail!("Unterminated `${{` expression."),
+        };
+        let expression = &expression_start[..expression_len];
+        let (key, default_opt) = match expression.split_once(":-") {
+            Some((key, default)) => (key, Some(default)),
+            None => (expression, None),
+        };
+        if !is_valid_env_key(key) {
+            bail!("Invalid environment variable name `{}`.", key);
+        }
+        let value = match (
+            lookup_env(key).filter(|value| !value.is_empty()),
+            default_opt,
+        ) {
+            (Some(value), _) => value,
+            (None, Some(default)) => default.to_string(),
+            (None, None) => bail!(
+                "Environment variable `{}` is not set and has no default value.",
+                key
+            ),
+        };
+        rendered.push_str(&value);
+        remaining = &expression_start[expression_len + 1..];
+    }
+    rendered.push_str(remaining);
+    Ok(())
+}
+
+fn is_valid_env_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    matches!(chars.next(), Some(first_char) if first_char.is_ascii_alphabetic() || first_char == '_')
+        && chars.all(|char| char.is_ascii_alphanumeric() || char == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn render(config_content: &str) -> anyhow::Result<String> {
+        let env: HashMap<&str, &str> = [
+            ("QW_BUCKET", "my-bucket"),
+            ("KAFKA_BROKERS", "kafka-0:9092,kafka-1:9092"),
+            ("QW_EMPTY", ""),
+        ]
+        .into_iter()
+        .collect();
+        let rendered = render_config_template_with(config_content.as_bytes(), |key| {
+            env.get(key).map(|value| value.to_string())
+        })?;
+        Ok(String::from_utf8(rendered).unwrap())
+    }
+
+    #[test]
+    fn test_render_config_template() {
+        assert_eq!(
+            render("metastore_uri: s3://${QW_BUCKET}/indexes\n").unwrap(),
+            "metastore_uri: s3://my-bucket/indexes\n"
+        );
+        assert_eq!(
+            render("bootstrap.servers: ${KAFKA_BROKERS}").unwrap(),
+            "bootstrap.servers: kafka-0:9092,kafka-1:9092"
+        );
+        assert_eq!(
+            render("rest_listen_port: ${QW_PORT:-7280}").unwrap(),
+            "rest_listen_port: 7280"
+        );
+        assert_eq!(
+            render("data_dir: ${QW_EMPTY:-/qwdata}").unwrap(),
+            "data_dir: /qwdata"
+        );
+        assert_eq!(
+            render("index_uri: s3://${QW_BUCKET:-default}/${QW_BUCKET}").unwrap(),
+            "index_uri: s3://my-bucket/my-bucket"
+        );
+        assert_eq!(
+            render("price: $5 and $${NOT_RENDERED}").unwrap(),
+            "price: $5 and ${NOT_RENDERED}"
+        );
+        assert_eq!(
+            render("# index_uri: ${QW_UNSET}\nversion: 0\n").unwrap(),
+            "# index_uri: ${QW_UNSET}\nversion: 0\n"
+        );
+    }
+
+    #[test]
+    fn test_render_config_template_errors() {
+        let error = render("version: 0\nindex_uri: ${QW_UNSET}\n").unwrap_err();
+        assert_eq!(error.to_string(), "Failed to render line 2 of config file.");
+        assert_eq!(
+            error.root_cause().to_string(),
+            "Environment variable `QW_UNSET` is not set and has no default value."
+        );
+        assert!(render("index_uri: ${QW_BUCKET").is_err());
+        assert!(render("index_uri: ${QW-BUCKET}").is_err());
+        assert!(render_config_template_with(&[0xff, 0xfe], |_| None).is_err());
+    }
+}
@@ -0,0 +1,572 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::RangeInclusive;
+
+use quickwit_config::IndexingSettings;
+use quickwit_doc_mapper::tag_pruning::match_tag_field_name;
+use quickwit_metastore::{IndexMetadata, Metastore, SplitMetadata, SplitState};
+use serde::Serialize;
+
+/// Above this average number of mature splits overlapping each timestamp, time range queries
+/// open most of the splits.
+const MAX_TIME_OVERLAP: f64 = 4.0;
+
+/// Above this number of immature splits per `max_merge_factor`, merges lag behind indexing.
+const MAX_IMMATURE_SPLITS_PER_MERGE: usize = 4;
+
+/// Highest merge factor recommended.
+const MAX_RECOMMENDED_MERGE_FACTOR: usize = 32;
+
+/// Lowest split target recommended.
+const MIN_RECOMMENDED_SPLIT_NUM_DOCS_TARGET: usize = 1_000_000;
+
+/// Distribution of the values of a tag field over the splits of an index.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TagFieldStats {
+    /// Name of the tag field.
+    pub field_name: String,
+    /// Number of distinct values of the field, over the splits which record their values.
+    pub num_values: usize,
+    /// Number of splits holding too many distinct values for them to be recorded as tags.
+    pub num_splits_without_values: usize,
+    /// Average number of distinct values per split recording its values.
+    pub avg_values_per_split: f64,
+}
+
+/// Change of an indexing setting recommended by a [`CompactionReport`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Recommendation {
+    /// Path of the setting in the `indexing_settings` section of the index config.
+    pub setting: &'static str,
+    /// Current value of the setting.
+    pub current_value: String,
+    /// Recommended value of the setting.
+    pub recommended_value: String,
+    /// Why the change is recommended.
+    pub reason: String,
+}
+
+/// Split size distribution, time overlap and tag cardinality of the published splits of an
+/// index, along with the merge policy and demux settings recommended for the index.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CompactionReport {
+    /// ID of the index.
+    pub index_id: String,
+    /// Number of published splits.
+    pub num_splits: usize,
+    /// Number of published documents.
+    pub num_docs: usize,
+    /// Number of splits holding at least `split_num_docs_target` documents.
+    pub num_mature_splits: usize,
+    /// Minimum, first quartile, median, third quartile and maximum of the number of documents
+    /// per split.
+    pub split_num_docs_quartiles: [usize; 5],
+    /// Average number of mature splits overlapping each timestamp, if the splits have a time
+    /// range.
+    pub time_overlap: Option<f64>,
+    /// Distribution of the values of the tag fields.
+    pub tag_fields: Vec<TagFieldStats>,
+    /// Recommended changes of the indexing settings.
+    pub recommendations: Vec<Recommendation>,
+    /// Indexing settings with the recommendations applied.
+    #[serde(skip)]
+    pub recommended_indexing_settings: IndexingSettings,
+}
+
+/// Analyzes the published splits of the index `index_id` and recommends merge policy and demux
+/// settings.
+pub async fn analyze_index_compaction(
+    metastore: &dyn Metastore,
+    index_id: &str,
+) -> anyhow::Result<CompactionReport> {
+    let index_metadata = metastore.index_metadata(index_id).await?;
+    let splits: Vec<SplitMetadata> = metastore
+        .list_splits(index_id, SplitState::Published, None, None)
+        .await?
+        .into_iter()
+        .map(|split| split.split_metadata)
+        .collect();
+    build_compaction_report(&index_metadata, &splits)
+}
+
+/// Replaces the indexing settings of the index with the settings recommended by `report`.
+/// Returns the number of recommendations applied.
+pub async fn apply_compaction_recommendations(
+    metastore: &dyn Metastore,
+    report: &CompactionReport,
+) -> anyhow::Result<usize> {
+    if report.recommendations.is_empty() {
+        return Ok(0);
+    }
+    metastore
+        .update_indexing_settings(
+            &report.index_id,
+            report.recommended_indexing_settings.clone(),
+        )
+        .await?;
+    Ok(report.recommendations.len())
+}
+
+fn build_compaction_report(
+    index_metadata: &IndexMetadata,
+    splits: &[SplitMetadata],
+) -> anyhow::Result<CompactionReport> {
+    let indexing_settings = &index_metadata.indexing_settings;
+    let split_num_docs_target = indexing_settings.split_num_docs_target;
+    let mut splits_num_docs: Vec<usize> = splits.iter().map(|split| split.num_docs).collect();
+    splits_num_docs.sort_unstable();
+    let num_mature_splits = splits_num_docs
+        .iter()
+        .filter(|num_docs| **num_docs >= split_num_docs_target)
+        .count();
+    let mature_time_ranges: Vec<RangeInclusive<i64>> = splits
+        .iter()
+        .filter(|split| split.num_docs >= split_num_docs_target)
+        .flat_map(|split| split.time_range.clone())
+        .collect();
+    let tag_fields: Vec<TagFieldStats> = index_metadata
+        .doc_mapping
+        .tag_fields
+        .iter()
+        .map(|field_name| tag_field_stats(field_name, splits))
+        .collect();
+    let mut report = CompactionReport {
+        index_id: index_metadata.index_id.clone(),
+        num_splits: splits.len(),
+        num_docs: splits_num_docs.iter().sum(),
+        num_mature_splits,
+        split_num_docs_quartiles: quartiles(&splits_num_docs),
+        time_overlap: time_overlap(&mature_time_ranges),
+        tag_fields,
+        recommendations: Vec::new(),
+        recommended_indexing_settings: indexing_settings.clone(),
+    };
+    recommend_merge_settings(&mut report);
+    recommend_demux_settings(&mut report, index_metadata)?;
+    Ok(report)
+}
+
+fn recommend_merge_settings(report: &mut CompactionReport) {
+    let settings = &mut report.recommended_indexing_settings;
+    let num_immature_splits = report.num_splits - report.num_mature_splits;
+    let merge_factor = settings.merge_policy.merge_factor;
+    let max_merge_factor = settings.merge_policy.max_merge_factor;
+
+    if !settings.merge_enabled {
+        if num_immature_splits > merge_factor {
+            settings.merge_enabled = true;
+            report.recommendations.push(Recommendation {
+                setting: "merge_enabled",
+                current_value: "false".to_string(),
+                recommended_value: "true".to_string(),
+                reason: format!(
+                    "{} splits hold less than `split_num_docs_target` documents and are never \
+                     merged.",
+                    num_immature_splits
+                ),
+            });
+        }
+    } else if num_immature_splits > MAX_IMMATURE_SPLITS_PER_MERGE * max_merge_factor
+        && merge_factor < MAX_RECOMMENDED_MERGE_FACTOR
+    {
+        let recommended_merge_factor = (merge_factor * 2).min(MAX_RECOMMENDED_MERGE_FACTOR);
+        let recommended_max_merge_factor =
+            recommended_merge_factor + max_merge_factor.saturating_sub(merge_factor);
+        settings.merge_policy.merge_factor = recommended_merge_factor;
+        settings.merge_policy.max_merge_factor = recommended_max_merge_factor;
+        let reason = format!(
+            "{} splits hold less than `split_num_docs_target` documents: merging more splits at \
+             once catches up with the merge backlog.",
+            num_immature_splits
+        );
+        report.recommendations.push(Recommendation {
+            setting: "merge_policy.merge_factor",
+            current_value: merge_factor.to_string(),
+            recommended_value: recommended_merge_factor.to_string(),
+            reason: reason.clone(),
+        });
+        report.recommendations.push(Recommendation {
+            setting: "merge_policy.max_merge_factor",
+            current_value: max_merge_factor.to_string(),
+            recommended_value: recommended_max_merge_factor.to_string(),
+            reason,
+        });
+    }
+
+    let split_num_docs_target = settings.split_num_docs_target;
+    if let Some(time_overlap) = report.time_overlap {
+        if time_overlap > MAX_TIME_OVERLAP
+            && split_num_docs_target / 2 >= MIN_RECOMMENDED_SPLIT_NUM_DOCS_TARGET
+        {
+            settings.split_num_docs_target = split_num_docs_target / 2;
+            report.recommendations.push(Recommendation {
+                setting: "split_num_docs_target",
+                current_value: split_num_docs_target.to_string(),
+                recommended_value: settings.split_num_docs_target.to_string(),
+                reason: format!(
+                    "{:.1} mature splits overlap each timestamp on average, so time range queries \
+                     open most of them: smaller splits span narrower time ranges.",
+                    time_overlap
+                ),
+            });
+        }
+    }
+}
+
+fn recommend_demux_settings(
+    report: &mut CompactionReport,
+    index_metadata: &IndexMetadata,
+) -> anyhow::Result<()> {
+    let settings = &mut report.recommended_indexing_settings;
+    if settings.demux_enabled {
+        let demux_field_stats_opt = settings.demux_field.as_ref().and_then(|demux_field| {
+            report
+                .tag_fields
+                .iter()
+                .find(|tag_field| &tag_field.field_name == demux_field)
+        });
+        let demux_field_stats = match demux_field_stats_opt {
+            Some(demux_field_stats) if demux_field_stats.num_splits_without_values == 0 => {
+                demux_field_stats
+            }
+            _ => return Ok(()),
+        };
+        let demux_factor = settings.merge_policy.demux_factor;
+        if demux_field_stats.num_values == 1 {
+            settings.demux_enabled = false;
+            report.recommendations.push(Recommendation {
+                setting: "demux_enabled",
+                current_value: "true".to_string(),
+                recommended_value: "false".to_string(),
+                reason: format!(
+                    "Demux field `{}` has a single value: demuxing does not group documents.",
+                    demux_field_stats.field_name
+                ),
+            });
+        } else if demux_field_stats.num_values > 1 && demux_field_stats.num_values < demux_factor {
+            settings.merge_policy.demux_factor = demux_field_stats.num_values;
+            report.recommendations.push(Recommendation {
+                setting: "merge_policy.demux_factor",
+                current_value: demux_factor.to_string(),
+                recommended_value: demux_field_stats.num_values.to_string(),
+                reason: format!(
+                    "Demux field `{}` has {} values: demuxing into more splits than values \
+                     produces small splits.",
+                    demux_field_stats.field_name, demux_field_stats.num_values
+                ),
+            });
+        }
+        return Ok(());
+    }
+    if report.num_splits < settings.merge_policy.merge_factor {
+        return Ok(());
+    }
+    // Demuxing requires a fast and indexed field. The best candidate is the tag field whose
+    // values are the most mixed across splits, since tag pruning then skips few splits.
+    let schema = index_metadata.build_doc_mapper()?.schema();
+    let demux_candidate_opt = report
+        .tag_fields
+        .iter()
+        .filter(|tag_field| {
+            tag_field.num_values > 1
+                && tag_field.num_splits_without_values == 0
+                && tag_field.avg_values_per_split * 2.0 > tag_field.num_values as f64
+        })
+        .filter(|tag_field| {
+            schema
+                .get_field(&tag_field.field_name)
+                .map(|field| {
+                    let field_entry = schema.get_field_entry(field);
+                    field_entry.is_fast() && field_entry.is_indexed()
+                })
+                .unwrap_or(false)
+        })
+        .max_by_key(|tag_field| tag_field.num_values);
+    if let Some(demux_candidate) = demux_candidate_opt {
+        let reason = format!(
+            "Splits hold {:.1} of the {} values of tag field `{}` on average, so tag pruning \
+             skips few splits: demuxing groups the documents by value.",
+            demux_candidate.avg_values_per_split,
+            demux_candidate.num_values,
+            demux_candidate.field_name
+        );
+        report.recommendations.push(Recommendation {
+            setting: "demux_field",
+            current_value: settings.demux_field.clone().unwrap_or_default(),
+            recommended_value: demux_candidate.field_name.clone(),
+            reason: reason.clone(),
+        });
+        report.recommendations.push(Recommendation {
+            setting: "demux_enabled",
+            current_value: "false".to_string(),
+            recommended_value: "true".to_string(),
+            reason,
+        });
+        settings.demux_field = Some(demux_candidate.field_name.clone());
+        settings.demux_enabled = true;
+    }
+    Ok(())
+}
+
+fn tag_field_stats(field_name: &str, splits: &[SplitMetadata]) -> TagFieldStats {
+    let mut values: BTreeSet<&str> = BTreeSet::new();
+    let mut num_splits_with_values = 0;
+    let mut num_splits_without_values = 0;
+    let mut num_split_values = 0;
+    for split in splits {
+        let split_values: Vec<&str> = split
+            .tags
+            .iter()
+            .filter(|tag| match_tag_field_name(field_name, tag))
+            .map(String::as_str)
+            .collect();
+        if split_values.is_empty() {
+            num_splits_without_values += 1;
+            continue;
+        }
+        num_splits_with_values += 1;
+        num_split_values += split_values.len();
+        values.extend(split_values);
+    }
+    TagFieldStats {
+        field_name: field_name.to_string(),
+        num_values: values.len(),
+        num_splits_without_values,
+        avg_values_per_split: if num_splits_with_values == 0 {
+            0.0
+        } else {
+            num_split_values as f64 / num_splits_with_values as f64
+        },
+    }
+}
+
+fn quartiles(sorted_values: &[usize]) -> [usize; 5] {
+    if sorted_values.is_empty() {
+        return [0; 5];
+    }
+    let last_idx = sorted_values.len() - 1;
+    [0, 1, 2, 3, 4].map(|quarter| sorted_values[last_idx * quarter / 4])
+}
+
+/// Returns the sum of the lengths of the time ranges divided by the length of their union.
+fn time_overlap(time_ranges: &[RangeInclusive<i64>]) -> Option<f64> {
+    if time_ranges.is_empty() {
+        return None;
+    }
+    let mut sorted_time_ranges: BTreeMap<i64, i64> = BTreeMap::new();
+    let mut total_length = 0;
+    for time_range in time_ranges {
+        total_length += time_range.end() - time_range.start() + 1;
+        let end = sorted_time_ranges
+            .entry(*time_range.start())
+            .or_insert(i64::MIN);
+        *end = (*end).max(*time_range.end());
+    }
+    let mut union_length = 0;
+    let mut current_range_opt: Option<(i64, i64)> = None;
+    for (start, end) in sorted_time_ranges {
+        current_range_opt = match current_range_opt {
+            Some((current_start, current_end)) if start <= current_end + 1 => {
+                Some((current_start, current_end.max(end)))
+            }
+            Some((current_start, current_end)) => {
+                union_length += current_end - current_start + 1;
+                Some((start, end))
+            }
+            None => Some((start, end)),
+        };
+    }
+    if let Some((current_start, current_end)) = current_range_opt {
+        union_length += current_end - current_start + 1;
+    }
+    Some(total_length as f64 / union_length as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_config::MergePolicy;
+
+    use super::*;
+
+    fn split(
+        num_docs: usize,
+        time_range: Option<RangeInclusive<i64>>,
+        tags: &[&str],
+    ) -> SplitMetadata {
+        SplitMetadata {
+            num_docs,
+            time_range,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn test_index_metadata(indexing_settings: IndexingSettings) -> IndexMetadata {
+        let mut index_metadata = IndexMetadata::for_test("test-index", "ram:///indexes/test-index");
+        index_metadata.indexing_settings = IndexingSettings {
+            timestamp_field: Some("timestamp".to_string()),
+            ..indexing_settings
+        };
+        index_metadata
+    }
+
+    #[test]
+    fn test_time_overlap() {
+        assert_eq!(time_overlap(&[]), None);
+        assert_eq!(time_overlap(&[0..=9, 10..=19]), Some(1.0));
+        assert_eq!(time_overlap(&[0..=9, 0..=9, 0..=9]), Some(3.0));
+        assert_eq!(time_overlap(&[0..=9, 5..=14, 100..=109]), Some(30.0 / 25.0));
+    }
+
+    #[test]
+    fn test_quartiles() {
+        assert_eq!(quartiles(&[]), [0; 5]);
+        assert_eq!(quartiles(&[7]), [7; 5]);
+        assert_eq!(quartiles(&[1, 2, 3, 4, 5]), [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_tag_field_stats() {
+        let splits = vec![
+            split(10, None, &["owner!", "owner:alice", "owner:bob"]),
+            split(10, None, &["owner!", "owner:bob", "owner:carol"]),
+            split(10, None, &["owner!"]),
+        ];
+        assert_eq!(
+            tag_field_stats("owner", &splits),
+            TagFieldStats {
+                field_name: "owner".to_string(),
+                num_values: 3,
+                num_splits_without_values: 1,
+                avg_values_per_split: 2.0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_compaction_report_without_recommendation() -> anyhow::Result<()> {
+        let index_metadata = test_index_metadata(IndexingSettings {
+            split_num_docs_target: 100,
+            ..Default::default()
+        });
+        let splits = vec![
+            split(100, Some(0..=99), &[]),
+            split(150, Some(100..=199), &[]),
+            split(10, Some(200..=209), &[]),
+        ];
+        let report = build_compaction_report(&index_metadata, &splits)?;
+        assert_eq!(report.num_splits, 3);
+        assert_eq!(report.num_docs, 260);
+        assert_eq!(report.num_mature_splits, 2);
+        assert_eq!(report.split_num_docs_quartiles, [10, 10, 100, 100, 150]);
+        assert_eq!(report.time_overlap, Some(1.0));
+        assert!(report.recommendations.is_empty());
+        assert_eq!(
+            report.recommended_indexing_settings,
+            index_metadata.indexing_settings
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_compaction_report_recommends_merge_settings() -> anyhow::Result<()> {
+        let index_metadata = test_index_metadata(IndexingSettings {
+            split_num_docs_target: 4_000_000,
+            merge_enabled: false,
+            ..Default::default()
+        });
+        let mut splits: Vec<SplitMetadata> = (0..5)
+            .map(|_| split(4_000_000, Some(0..=99), &[]))
+            .collect();
+        splits.extend((0..20).map(|_| split(1_000, Some(100..=109), &[])));
+        let report = build_compaction_report(&index_metadata, &splits)?;
+        assert_eq!(report.time_overlap, Some(5.0));
+        let settings: Vec<&str> = report
+            .recommendations
+            .iter()
+            .map(|recommendation| recommendation.setting)
+            .collect();
+        assert_eq!(settings, ["merge_enabled", "split_num_docs_target"]);
+        assert!(report.recommended_indexing_settings.merge_enabled);
+        assert_eq!(
+            report.recommended_indexing_settings.split_num_docs_target,
+            2_000_000
+        );
+
+        let index_metadata = test_index_metadata(IndexingSettings {
+            split_num_docs_target: 1_000_000,
+            merge_policy: MergePolicy {
+                merge_factor: 10,
+                max_merge_factor: 12,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        let splits: Vec<SplitMetadata> = (0..50).map(|_| split(1_000, None, &[])).collect();
+        let report = build_compaction_report(&index_metadata, &splits)?;
+        assert_eq!(report.time_overlap, None);
+        let merge_policy = &report.recommended_indexing_settings.merge_policy;
+        assert_eq!(merge_policy.merge_factor, 20);
+        assert_eq!(merge_policy.max_merge_factor, 22);
+        assert_eq!(report.recommendations.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compaction_report_recommends_demux_settings() -> anyhow::Result<()> {
+        // `owner` is a tag field of the test index, but it is not a fast field: it cannot be
+        // demuxed on.
+        let index_metadata = test_index_metadata(IndexingSettings::default());
+        let splits: Vec<SplitMetadata> = (0..10)
+            .map(|_| split(1_000, None, &["owner!", "owner:alice", "owner:bob"]))
+            .collect();
+        let report = build_compaction_report(&index_metadata, &splits)?;
+        assert_eq!(report.tag_fields[0].num_values, 2);
+        assert!(report.recommendations.is_empty());
+
+        let index_metadata = test_index_metadata(IndexingSettings {
+            demux_enabled: true,
+            demux_field: Some("owner".to_string()),
+            ..Default::default()
+        });
+        let report = build_compaction_report(&index_metadata, &splits)?;
+        assert_eq!(report.recommendations.len(), 1);
+        assert_eq!(
+            report.recommendations[0].setting,
+            "merge_policy.demux_factor"
+        );
+        assert_eq!(
+            report
+                .recommended_indexing_settings
+                .merge_policy
+                .demux_factor,
+            2
+        );
+
+        let splits: Vec<SplitMetadata> = (0..10)
+            .map(|_| split(1_000, None, &["owner!", "owner:alice"]))
+            .collect();
+        let report = build_compaction_report(&index_metadata, &splits)?;
+        assert_eq!(report.recommendations[0].setting, "demux_enabled");
+        assert!(!report.recommended_indexing_settings.demux_enabled);
+        Ok(())
+    }
+}
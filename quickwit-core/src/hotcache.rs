@@ -0,0 +1,245 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use quickwit_directories::{
+    get_hotcache_from_split, read_split_footer, write_hotcache, BundleDirectory, HotDirectory,
+    HOTCACHE_VERSION,
+};
+use quickwit_indexing::new_split_id;
+use quickwit_metastore::{Metastore, SplitMetadata, SplitState};
+use quickwit_storage::Storage;
+use tantivy::directory::FileSlice;
+use tracing::info;
+
+/// Split whose hotcache is outdated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotcacheRebuild {
+    /// ID of the split.
+    pub split_id: String,
+    /// Version of the hotcache of the split.
+    pub hotcache_version: u32,
+    /// ID of the split with a rebuilt hotcache replacing it, `None` for dry runs.
+    pub new_split_id: Option<String>,
+}
+
+/// Rebuilds the hotcache of the published splits of the index `index_id` written with a version
+/// older than [`HOTCACHE_VERSION`], without re-indexing their documents.
+///
+/// The body of a split is copied as is into a new split with the rebuilt hotcache, which then
+/// replaces the split in the metastore. Searches keep on reading either split in the meantime,
+/// and the replaced split files are deleted by the garbage collection.
+///
+/// * `split_ids` - The splits to rebuild. All the published splits if empty.
+/// * `dry_run` - Should this only return the splits with an outdated hotcache.
+pub async fn rebuild_hotcaches(
+    metastore: &dyn Metastore,
+    storage: Arc<dyn Storage>,
+    index_id: &str,
+    split_ids: &[String],
+    dry_run: bool,
+) -> anyhow::Result<Vec<HotcacheRebuild>> {
+    let mut splits: Vec<SplitMetadata> = metastore
+        .list_splits(index_id, SplitState::Published, None, None)
+        .await?
+        .into_iter()
+        .map(|split| split.split_metadata)
+        .collect();
+    if !split_ids.is_empty() {
+        splits.retain(|split| split_ids.contains(&split.split_id));
+        for split_id in split_ids {
+            if !splits.iter().any(|split| &split.split_id == split_id) {
+                bail!(
+                    "Split `{}` is not a published split of index `{}`.",
+                    split_id,
+                    index_id
+                );
+            }
+        }
+    }
+    let mut rebuilds = Vec::new();
+    for split in splits {
+        let split_file = split_file_path(&split.split_id);
+        let (split_footer, _) = read_split_footer(storage.clone(), &split_file).await?;
+        let hotcache_bytes = get_hotcache_from_split(split_footer)?;
+        let hotcache_version = HotDirectory::get_hotcache_version(hotcache_bytes)?;
+        if hotcache_version >= HOTCACHE_VERSION {
+            continue;
+        }
+        let new_split_id_opt = if dry_run {
+            None
+        } else {
+            Some(rebuild_split_hotcache(metastore, storage.clone(), index_id, &split).await?)
+        };
+        rebuilds.push(HotcacheRebuild {
+            split_id: split.split_id,
+            hotcache_version,
+            new_split_id: new_split_id_opt,
+        });
+    }
+    Ok(rebuilds)
+}
+
+/// Writes a copy of the split with a rebuilt hotcache, which replaces the split. Returns the ID
+/// of the new split.
+async fn rebuild_split_hotcache(
+    metastore: &dyn Metastore,
+    storage: Arc<dyn Storage>,
+    index_id: &str,
+    split: &SplitMetadata,
+) -> anyhow::Result<String> {
+    let split_file = split_file_path(&split.split_id);
+    let split_data = storage.get_all(&split_file).await?;
+    let hotcache_len = get_hotcache_from_split(split_data.clone())?.len();
+    let split_directory =
+        BundleDirectory::open_split(FileSlice::new(Box::new(split_data.clone())))?;
+    let mut hotcache = Vec::new();
+    write_hotcache(split_directory, &mut hotcache).with_context(|| {
+        format!(
+            "Failed to rebuild the hotcache of split `{}`.",
+            split.split_id
+        )
+    })?;
+
+    // The body and the bundle metadata are kept as is: the offsets of the files of the split do
+    // not change.
+    let body_len = split_data.len() - hotcache_len - 8;
+    let mut new_split_data = Vec::with_capacity(body_len + hotcache.len() + 8);
+    new_split_data.extend_from_slice(&split_data.as_slice()[..body_len]);
+    new_split_data.extend_from_slice(&hotcache);
+    new_split_data.extend_from_slice(&(hotcache.len() as u64).to_le_bytes());
+
+    let new_split_id = new_split_id();
+    let new_split = SplitMetadata {
+        split_id: new_split_id.clone(),
+        footer_offsets: split.footer_offsets.start..new_split_data.len() as u64,
+        ..split.clone()
+    };
+    metastore.stage_split(index_id, new_split).await?;
+    storage
+        .put(&split_file_path(&new_split_id), Box::new(new_split_data))
+        .await?;
+    metastore
+        .replace_splits(index_id, &[&new_split_id], &[&split.split_id])
+        .await?;
+    info!(
+        index_id = index_id,
+        split_id = %split.split_id,
+        new_split_id = %new_split_id,
+        "rebuilt-hotcache"
+    );
+    Ok(new_split_id)
+}
+
+fn split_file_path(split_id: &str) -> PathBuf {
+    Path::new(split_id).with_extension("split")
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_indexing::TestSandbox;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rebuild_hotcaches() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let index_id = "test-rebuild-hotcaches";
+        let doc_mapping_yaml = r#"
+            field_mappings:
+              - name: body
+                type: text
+        "#;
+        let test_sandbox = TestSandbox::create(index_id, doc_mapping_yaml, "{}", &["body"]).await?;
+        test_sandbox
+            .add_documents(vec![serde_json::json!({"body": "hello happy tax payer"})])
+            .await?;
+        let metastore = test_sandbox.metastore();
+        let storage = test_sandbox.storage();
+        let split = metastore.list_all_splits(index_id).await?[0]
+            .split_metadata
+            .clone();
+
+        // Splits with an up to date hotcache are left untouched.
+        let rebuilds =
+            rebuild_hotcaches(&*metastore, storage.clone(), index_id, &[], false).await?;
+        assert!(rebuilds.is_empty());
+
+        // Downgrades the version recorded in the header of the hotcache of the split.
+        let split_file = split_file_path(&split.split_id);
+        let split_data = storage.get_all(&split_file).await?;
+        let hotcache_len = get_hotcache_from_split(split_data.clone())?.len();
+        let mut outdated_split_data = split_data.as_slice().to_vec();
+        let hotcache_start = outdated_split_data.len() - 8 - hotcache_len;
+        outdated_split_data[hotcache_start + 1..hotcache_start + 5]
+            .copy_from_slice(&0u32.to_le_bytes());
+        storage
+            .put(&split_file, Box::new(outdated_split_data))
+            .await?;
+
+        let split_ids = vec![split.split_id.clone()];
+        let rebuilds =
+            rebuild_hotcaches(&*metastore, storage.clone(), index_id, &split_ids, true).await?;
+        assert_eq!(
+            rebuilds,
+            vec![HotcacheRebuild {
+                split_id: split.split_id.clone(),
+                hotcache_version: 0,
+                new_split_id: None,
+            }]
+        );
+
+        let rebuilds =
+            rebuild_hotcaches(&*metastore, storage.clone(), index_id, &split_ids, false).await?;
+        let new_split_id = rebuilds[0].new_split_id.clone().unwrap();
+        let published_splits = metastore
+            .list_splits(index_id, SplitState::Published, None, None)
+            .await?;
+        assert_eq!(published_splits.len(), 1);
+        let new_split = &published_splits[0].split_metadata;
+        assert_eq!(new_split.split_id, new_split_id);
+        assert_eq!(new_split.num_docs, split.num_docs);
+        let new_split_file = split_file_path(&new_split_id);
+        assert_eq!(
+            storage.file_num_bytes(&new_split_file).await?,
+            new_split.footer_offsets.end
+        );
+        let (split_footer, _) = read_split_footer(storage.clone(), &new_split_file).await?;
+        let hotcache_bytes = get_hotcache_from_split(split_footer)?;
+        assert_eq!(
+            HotDirectory::get_hotcache_version(hotcache_bytes)?,
+            HOTCACHE_VERSION
+        );
+        let new_split_data = storage.get_all(&new_split_file).await?;
+        let split_directory =
+            BundleDirectory::open_split(FileSlice::new(Box::new(new_split_data)))?;
+        let index = tantivy::Index::open(split_directory)?;
+        assert_eq!(index.reader()?.searcher().num_docs(), 1);
+
+        assert!(
+            rebuild_hotcaches(&*metastore, storage, index_id, &split_ids, true)
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,511 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use anyhow::bail;
+use quickwit_config::LifecycleSettings;
+use quickwit_metastore::{
+    alias_index_metadatas, IndexLifecycleState, IndexMetadata, LeaseFence, LifecyclePhase,
+    Metastore, MetastoreError, SplitState,
+};
+use tracing::info;
+
+/// Action taken on an index by [`enforce_lifecycle_policy`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LifecycleAction {
+    /// The write alias was rolled over from the index to the new index `new_index_id`.
+    RolledOver {
+        /// ID of the index created for the next generation.
+        new_index_id: String,
+    },
+    /// The index was marked read-only because a later generation of its write alias exists,
+    /// e.g. after a pass interrupted during a rollover.
+    MarkedReadOnly,
+    /// The index was frozen: its splits are not merged anymore.
+    Frozen,
+    /// The index is being deleted: its splits were marked for deletion, and their files are
+    /// deleted by garbage collection.
+    Deleting {
+        /// Number of splits marked for deletion by this pass.
+        num_marked_splits: usize,
+    },
+    /// The splits of the index were all deleted, and so was the index.
+    Deleted,
+}
+
+/// Moves an index whose lifecycle is managed to its next phase, if it is due, and returns the
+/// action taken.
+///
+/// - The hot index of the write alias is rolled over once it crosses a rollover threshold: the
+///   index of the next generation is created from its config, sources, and checkpoint, then the
+///   index is marked read-only. The indexers following the alias switch to the new index, which
+///   resumes the sources from the copied checkpoint: the documents indexed by the previous index
+///   after the rollover are indexed again, so the rollover is at-least-once.
+/// - A read-only index is frozen `freeze_after_secs` after its rollover.
+/// - A read-only or frozen index is deleted `delete_after_secs` after its rollover: its splits are
+///   marked for deletion, provided that `fence` still holds, and the index is deleted from the
+///   metastore once garbage collection deleted them all.
+///
+/// `index_metadatas` are the indexes of the metastore, among which the generations of the write
+/// alias of the index are looked up.
+pub async fn enforce_lifecycle_policy(
+    index_metadata: &IndexMetadata,
+    index_metadatas: &[IndexMetadata],
+    metastore: &dyn Metastore,
+    fence: &LeaseFence,
+    now_timestamp_secs: i64,
+) -> anyhow::Result<Option<LifecycleAction>> {
+    let lifecycle = match &index_metadata.indexing_settings.lifecycle {
+        Some(lifecycle) => lifecycle,
+        None => return Ok(None),
+    };
+    let index_id = &index_metadata.index_id;
+    let lifecycle_state = &index_metadata.lifecycle_state;
+    if lifecycle_state.phase == LifecyclePhase::Hot {
+        let is_latest_generation = alias_index_metadatas(index_metadatas, &lifecycle.write_alias)
+            .iter()
+            .all(|alias_index_metadata| {
+                alias_index_metadata.lifecycle_state.generation <= lifecycle_state.generation
+            });
+        if !is_latest_generation {
+            mark_read_only(index_metadata, metastore, now_timestamp_secs).await?;
+            return Ok(Some(LifecycleAction::MarkedReadOnly));
+        }
+        if !is_rollover_due(index_metadata, lifecycle, metastore, now_timestamp_secs).await? {
+            return Ok(None);
+        }
+        let next_index_metadata =
+            next_generation_index_metadata(index_metadata, lifecycle, now_timestamp_secs)?;
+        let new_index_id = next_index_metadata.index_id.clone();
+        match metastore.create_index(next_index_metadata).await {
+            // The index was created by a previous pass, interrupted before the rollover completed.
+            Ok(()) | Err(MetastoreError::IndexAlreadyExists { .. }) => {}
+            Err(error) => return Err(error.into()),
+        }
+        mark_read_only(index_metadata, metastore, now_timestamp_secs).await?;
+        info!(index_id = %index_id, new_index_id = %new_index_id, write_alias = %lifecycle.write_alias, "Rolled write alias over.");
+        return Ok(Some(LifecycleAction::RolledOver { new_index_id }));
+    }
+    let rollover_timestamp = lifecycle_state
+        .rollover_timestamp
+        .unwrap_or(index_metadata.update_timestamp);
+    let elapsed_secs = now_timestamp_secs.saturating_sub(rollover_timestamp).max(0) as u64;
+    if lifecycle
+        .delete_after_secs
+        .map_or(false, |delete_after_secs| elapsed_secs >= delete_after_secs)
+    {
+        return delete_index_splits(index_id, metastore, fence)
+            .await
+            .map(Some);
+    }
+    if lifecycle_state.phase == LifecyclePhase::ReadOnly
+        && lifecycle
+            .freeze_after_secs
+            .map_or(false, |freeze_after_secs| elapsed_secs >= freeze_after_secs)
+    {
+        let frozen_state = IndexLifecycleState {
+            phase: LifecyclePhase::Frozen,
+            ..lifecycle_state.clone()
+        };
+        metastore
+            .update_lifecycle_state(index_id, frozen_state)
+            .await?;
+        info!(index_id = %index_id, "Froze index.");
+        return Ok(Some(LifecycleAction::Frozen));
+    }
+    Ok(None)
+}
+
+/// Returns whether the index is older than the maximum age of the write index or its published
+/// splits hold more than its maximum number of bytes.
+async fn is_rollover_due(
+    index_metadata: &IndexMetadata,
+    lifecycle: &LifecycleSettings,
+    metastore: &dyn Metastore,
+    now_timestamp_secs: i64,
+) -> anyhow::Result<bool> {
+    if let Some(rollover_max_age_secs) = lifecycle.rollover_max_age_secs {
+        let age_secs = now_timestamp_secs.saturating_sub(index_metadata.create_timestamp);
+        if age_secs >= rollover_max_age_secs as i64 {
+            return Ok(true);
+        }
+    }
+    if let Some(rollover_max_num_bytes) = lifecycle.rollover_max_num_bytes {
+        let num_bytes: u64 = metastore
+            .list_splits(&index_metadata.index_id, SplitState::Published, None, None)
+            .await?
+            .iter()
+            .map(|split| split.split_metadata.footer_offsets.end)
+            .sum();
+        if num_bytes >= rollover_max_num_bytes {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Builds the metadata of the index of the next generation of the write alias, named
+/// `<write_alias>-<generation>` and stored next to the index.
+fn next_generation_index_metadata(
+    index_metadata: &IndexMetadata,
+    lifecycle: &LifecycleSettings,
+    now_timestamp_secs: i64,
+) -> anyhow::Result<IndexMetadata> {
+    let generation = index_metadata.lifecycle_state.generation + 1;
+    let new_index_id = format!("{}-{:06}", lifecycle.write_alias, generation);
+    let index_root_uri = match index_metadata
+        .index_uri
+        .strip_suffix(&index_metadata.index_id)
+    {
+        Some(index_root_uri) => index_root_uri,
+        None => bail!(
+            "Failed to roll index `{}` over: its URI `{}` does not end with the index ID, so the \
+             URI of the next generation cannot be derived from it.",
+            index_metadata.index_id,
+            index_metadata.index_uri
+        ),
+    };
+    Ok(IndexMetadata {
+        index_id: new_index_id.clone(),
+        index_uri: format!("{}{}", index_root_uri, new_index_id),
+        create_timestamp: now_timestamp_secs,
+        update_timestamp: now_timestamp_secs,
+        lifecycle_state: IndexLifecycleState {
+            phase: LifecyclePhase::Hot,
+            generation,
+            rollover_timestamp: None,
+        },
+        ..index_metadata.clone()
+    })
+}
+
+async fn mark_read_only(
+    index_metadata: &IndexMetadata,
+    metastore: &dyn Metastore,
+    now_timestamp_secs: i64,
+) -> anyhow::Result<()> {
+    let read_only_state = IndexLifecycleState {
+        phase: LifecyclePhase::ReadOnly,
+        rollover_timestamp: Some(now_timestamp_secs),
+        ..index_metadata.lifecycle_state.clone()
+    };
+    metastore
+        .update_lifecycle_state(&index_metadata.index_id, read_only_state)
+        .await?;
+    Ok(())
+}
+
+/// Marks the splits of the index for deletion, or deletes the index if it has no splits left.
+async fn delete_index_splits(
+    index_id: &str,
+    metastore: &dyn Metastore,
+    fence: &LeaseFence,
+) -> anyhow::Result<LifecycleAction> {
+    let splits = metastore.list_all_splits(index_id).await?;
+    if splits.is_empty() {
+        metastore.delete_index(index_id).await?;
+        info!(index_id = %index_id, "Deleted index.");
+        return Ok(LifecycleAction::Deleted);
+    }
+    let split_ids: Vec<&str> = splits
+        .iter()
+        .filter(|split| split.split_state != SplitState::MarkedForDeletion)
+        .map(|split| split.split_id())
+        .collect();
+    if !split_ids.is_empty() {
+        metastore
+            .mark_splits_for_deletion_fenced(index_id, &split_ids, fence)
+            .await?;
+        info!(index_id = %index_id, num_marked_splits = split_ids.len(), "Marked splits of index for deletion.");
+    }
+    Ok(LifecycleAction::Deleting {
+        num_marked_splits: split_ids.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_metastore::{write_index_metadata, MockMetastore, Split, SplitMetadata};
+
+    use super::*;
+
+    const NOW_TIMESTAMP: i64 = 1_000_000;
+
+    fn lifecycle_settings() -> LifecycleSettings {
+        LifecycleSettings {
+            write_alias: "logs".to_string(),
+            rollover_max_age_secs: Some(3_600),
+            rollover_max_num_bytes: Some(1_000),
+            freeze_after_secs: Some(600),
+            delete_after_secs: Some(1_200),
+        }
+    }
+
+    fn alias_index_metadata(
+        generation: u64,
+        phase: LifecyclePhase,
+        rollover_timestamp: Option<i64>,
+    ) -> IndexMetadata {
+        let index_id = format!("logs-{:06}", generation);
+        let index_uri = format!("ram:///indexes/{}", index_id);
+        let mut index_metadata = IndexMetadata::for_test(&index_id, &index_uri);
+        index_metadata.indexing_settings.lifecycle = Some(lifecycle_settings());
+        index_metadata.create_timestamp = NOW_TIMESTAMP - 60;
+        index_metadata.lifecycle_state = IndexLifecycleState {
+            phase,
+            generation,
+            rollover_timestamp,
+        };
+        index_metadata
+    }
+
+    fn make_split(split_id: &str, split_state: SplitState, num_bytes: u64) -> Split {
+        Split {
+            split_metadata: SplitMetadata {
+                split_id: split_id.to_string(),
+                footer_offsets: num_bytes - 10..num_bytes,
+                ..Default::default()
+            },
+            split_state,
+            update_timestamp: 0,
+        }
+    }
+
+    fn fence() -> LeaseFence {
+        LeaseFence {
+            lease_id: "janitor".to_string(),
+            fencing_token: 3,
+        }
+    }
+
+    #[test]
+    fn test_write_index_metadata() {
+        let mut unrelated_index_metadata =
+            IndexMetadata::for_test("unrelated", "ram:///indexes/unrelated");
+        unrelated_index_metadata.lifecycle_state.generation = 9;
+        let index_metadatas = vec![
+            alias_index_metadata(2, LifecyclePhase::Hot, None),
+            alias_index_metadata(1, LifecyclePhase::ReadOnly, Some(0)),
+            alias_index_metadata(3, LifecyclePhase::Frozen, Some(0)),
+            unrelated_index_metadata,
+        ];
+        let write_index_id = &write_index_metadata(&index_metadatas, "logs")
+            .unwrap()
+            .index_id;
+        assert_eq!(write_index_id, "logs-000002");
+        assert_eq!(alias_index_metadatas(&index_metadatas, "logs").len(), 3);
+        assert!(write_index_metadata(&index_metadatas, "unrelated").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_enforce_lifecycle_policy_rolls_over_write_index() -> anyhow::Result<()> {
+        let index_metadata = alias_index_metadata(1, LifecyclePhase::Hot, None);
+        let index_metadatas = vec![index_metadata.clone()];
+        let mut mock_metastore = MockMetastore::default();
+        let mut published_splits = vec![
+            vec![make_split("split-1", SplitState::Published, 600)],
+            vec![
+                make_split("split-1", SplitState::Published, 600),
+                make_split("split-2", SplitState::Published, 400),
+            ],
+        ];
+        mock_metastore.expect_list_splits().times(2).returning(
+            move |index_id, split_state, _, _| {
+                assert_eq!(index_id, "logs-000001");
+                assert_eq!(split_state, SplitState::Published);
+                Ok(published_splits.remove(0))
+            },
+        );
+        mock_metastore
+            .expect_create_index()
+            .times(1)
+            .returning(|new_index_metadata| {
+                assert_eq!(new_index_metadata.index_id, "logs-000002");
+                assert_eq!(new_index_metadata.index_uri, "ram:///indexes/logs-000002");
+                assert_eq!(new_index_metadata.lifecycle_state.generation, 2);
+                assert_eq!(
+                    new_index_metadata.lifecycle_state.phase,
+                    LifecyclePhase::Hot
+                );
+                assert_eq!(new_index_metadata.create_timestamp, NOW_TIMESTAMP);
+                Ok(())
+            });
+        mock_metastore
+            .expect_update_lifecycle_state()
+            .times(1)
+            .returning(|index_id, lifecycle_state| {
+                assert_eq!(index_id, "logs-000001");
+                assert_eq!(
+                    lifecycle_state,
+                    IndexLifecycleState {
+                        phase: LifecyclePhase::ReadOnly,
+                        generation: 1,
+                        rollover_timestamp: Some(NOW_TIMESTAMP),
+                    }
+                );
+                Ok(())
+            });
+        // The index is younger than the maximum age and holds fewer bytes than the maximum.
+        let action = enforce_lifecycle_policy(
+            &index_metadata,
+            &index_metadatas,
+            &mock_metastore,
+            &fence(),
+            NOW_TIMESTAMP,
+        )
+        .await?;
+        assert_eq!(action, None);
+
+        let action = enforce_lifecycle_policy(
+            &index_metadata,
+            &index_metadatas,
+            &mock_metastore,
+            &fence(),
+            NOW_TIMESTAMP,
+        )
+        .await?;
+        assert_eq!(
+            action,
+            Some(LifecycleAction::RolledOver {
+                new_index_id: "logs-000002".to_string()
+            })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_enforce_lifecycle_policy_completes_interrupted_rollover() -> anyhow::Result<()> {
+        let index_metadata = alias_index_metadata(1, LifecyclePhase::Hot, None);
+        let index_metadatas = vec![
+            index_metadata.clone(),
+            alias_index_metadata(2, LifecyclePhase::Hot, None),
+        ];
+        let mut mock_metastore = MockMetastore::default();
+        mock_metastore
+            .expect_update_lifecycle_state()
+            .times(1)
+            .returning(|index_id, lifecycle_state| {
+                assert_eq!(index_id, "logs-000001");
+                assert_eq!(lifecycle_state.phase, LifecyclePhase::ReadOnly);
+                Ok(())
+            });
+        let action = enforce_lifecycle_policy(
+            &index_metadata,
+            &index_metadatas,
+            &mock_metastore,
+            &fence(),
+            NOW_TIMESTAMP,
+        )
+        .await?;
+        assert_eq!(action, Some(LifecycleAction::MarkedReadOnly));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_enforce_lifecycle_policy_freezes_and_deletes() -> anyhow::Result<()> {
+        let mut mock_metastore = MockMetastore::default();
+        mock_metastore
+            .expect_update_lifecycle_state()
+            .times(1)
+            .returning(|index_id, lifecycle_state| {
+                assert_eq!(index_id, "logs-000001");
+                assert_eq!(lifecycle_state.phase, LifecyclePhase::Frozen);
+                assert_eq!(
+                    lifecycle_state.rollover_timestamp,
+                    Some(NOW_TIMESTAMP - 600)
+                );
+                Ok(())
+            });
+        let mut all_splits = vec![
+            vec![
+                make_split("split-1", SplitState::Published, 100),
+                make_split("split-2", SplitState::MarkedForDeletion, 100),
+            ],
+            vec![make_split("split-1", SplitState::MarkedForDeletion, 100)],
+            Vec::new(),
+        ];
+        mock_metastore
+            .expect_list_all_splits()
+            .times(3)
+            .returning(move |_| Ok(all_splits.remove(0)));
+        mock_metastore
+            .expect_mark_splits_for_deletion_fenced()
+            .times(1)
+            .returning(|index_id, split_ids, fence| {
+                assert_eq!(index_id, "logs-000001");
+                assert_eq!(split_ids, vec!["split-1"]);
+                assert_eq!(fence.fencing_token, 3);
+                Ok(())
+            });
+        mock_metastore
+            .expect_delete_index()
+            .times(1)
+            .returning(|index_id| {
+                assert_eq!(index_id, "logs-000001");
+                Ok(())
+            });
+
+        // A read-only index rolled over recently is left as is.
+        let index_metadata =
+            alias_index_metadata(1, LifecyclePhase::ReadOnly, Some(NOW_TIMESTAMP - 60));
+        let action = enforce_lifecycle_policy(
+            &index_metadata,
+            &[],
+            &mock_metastore,
+            &fence(),
+            NOW_TIMESTAMP,
+        )
+        .await?;
+        assert_eq!(action, None);
+
+        let index_metadata =
+            alias_index_metadata(1, LifecyclePhase::ReadOnly, Some(NOW_TIMESTAMP - 600));
+        let action = enforce_lifecycle_policy(
+            &index_metadata,
+            &[],
+            &mock_metastore,
+            &fence(),
+            NOW_TIMESTAMP,
+        )
+        .await?;
+        assert_eq!(action, Some(LifecycleAction::Frozen));
+
+        let index_metadata =
+            alias_index_metadata(1, LifecyclePhase::Frozen, Some(NOW_TIMESTAMP - 1_200));
+        for expected_action in [
+            LifecycleAction::Deleting {
+                num_marked_splits: 1,
+            },
+            LifecycleAction::Deleting {
+                num_marked_splits: 0,
+            },
+            LifecycleAction::Deleted,
+        ] {
+            let action = enforce_lifecycle_policy(
+                &index_metadata,
+                &[],
+                &mock_metastore,
+                &fence(),
+                NOW_TIMESTAMP,
+            )
+            .await?;
+            assert_eq!(action, Some(expected_action));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,494 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::bail;
+use chrono::Utc;
+use quickwit_cluster::cluster::{Cluster, QuickwitService};
+use quickwit_config::timestamp_resolution;
+use quickwit_indexing::actors::IndexingServerClient;
+use quickwit_indexing::{run_garbage_collect, IndexingSplitStore, SplitDeletionError};
+use quickwit_metastore::{
+    IndexMetadata, LeaseFence, LifecyclePhase, Metastore, MetastoreError, SplitState,
+};
+use quickwit_storage::StorageUriResolver;
+use tracing::{debug, error, info, warn};
+
+use crate::index_lifecycle::{enforce_lifecycle_policy, LifecycleAction};
+
+/// Interval between two passes of the janitor.
+const JANITOR_RUN_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// ID of the lease held by the janitor running the tasks.
+const JANITOR_LEASE_ID: &str = "janitor";
+
+/// The lease outlives the interval between two passes, so that the leader keeps it from one pass
+/// to the next.
+const JANITOR_LEASE_TTL: Duration = Duration::from_secs(2 * 15 * 60);
+
+/// Threshold period after which the janitor garbage collects stale staged splits.
+const STAGED_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// Period during which the splits marked for deletion are kept, so that the in-flight queries
+/// that selected them can complete.
+const DELETION_GRACE_PERIOD: Duration = Duration::from_secs(120);
+
+/// Counters of the tasks run by the janitor.
+#[derive(Debug, Clone, Default)]
+pub struct JanitorCounters {
+    /// The number of passes run while the node was the leader of the janitors.
+    pub num_passes: usize,
+    /// The number of passes skipped because another node was the leader of the janitors or held
+    /// their lease.
+    pub num_skipped_passes: usize,
+    /// The number of splits marked for deletion because they exceeded the retention period.
+    pub num_expired_splits: usize,
+    /// The number of files deleted by garbage collection.
+    pub num_deleted_files: usize,
+    /// The number of bytes deleted by garbage collection.
+    pub num_deleted_bytes: usize,
+    /// The number of merge pipelines run to completion.
+    pub num_merge_runs: usize,
+    /// The number of write aliases rolled over to a new index.
+    pub num_rollovers: usize,
+    /// The number of indexes deleted at the end of their lifecycle.
+    pub num_deleted_indexes: usize,
+}
+
+/// Runs the periodic maintenance tasks of the indexes of the metastore: lifecycle enforcement,
+/// retention enforcement, garbage collection of the dangling splits, then merges. Several nodes can
+/// run the janitor service for availability, but only the leader elected among them runs the tasks.
+///
+/// The cluster members may disagree on the leader while they converge, so the leader also
+/// acquires the janitor lease from the metastore, and fences the mutations of the splits with
+/// it: the metastore checks the fencing token atomically with each mutation, so a node that lost
+/// the lease cannot change the splits anymore. Merges are not fenced: a merge publishes its
+/// split only if the splits it replaces are still published.
+pub struct Janitor {
+    metastore: Arc<dyn Metastore>,
+    storage_resolver: StorageUriResolver,
+    cluster: Arc<Cluster>,
+    /// Indexing server running the merge pipelines of the janitor.
+    indexing_server_client: IndexingServerClient,
+    counters: JanitorCounters,
+}
+
+impl Janitor {
+    /// Creates a janitor maintaining the indexes of the metastore. The indexes are listed on
+    /// each pass, so that the indexes created since the janitor started are maintained as well.
+    pub fn new(
+        metastore: Arc<dyn Metastore>,
+        storage_resolver: StorageUriResolver,
+        cluster: Arc<Cluster>,
+        indexing_server_client: IndexingServerClient,
+    ) -> Self {
+        Self {
+            metastore,
+            storage_resolver,
+            cluster,
+            indexing_server_client,
+            counters: JanitorCounters::default(),
+        }
+    }
+
+    /// Returns the counters of the tasks run so far.
+    pub fn counters(&self) -> &JanitorCounters {
+        &self.counters
+    }
+
+    /// Runs a pass every [`JANITOR_RUN_INTERVAL`]. Failures are logged and retried on the next
+    /// pass.
+    pub async fn run(mut self) -> anyhow::Result<()> {
+        info!("Janitor started.");
+        let mut interval = tokio::time::interval(JANITOR_RUN_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.run_pass().await;
+        }
+    }
+
+    /// Runs the tasks of every index if the node is the leader of the janitors and holds their
+    /// lease.
+    pub async fn run_pass(&mut self) {
+        let node_id = &self.cluster.node_id;
+        if !self.cluster.is_service_leader(QuickwitService::Janitor) {
+            debug!("Janitor is not the leader, skipping pass.");
+            // Hands the lease over to the new leader right away if the node held it.
+            if let Err(error) = self
+                .metastore
+                .release_lease(JANITOR_LEASE_ID, node_id)
+                .await
+            {
+                warn!(error = ?error, "Janitor failed to release lease.");
+            }
+            self.counters.num_skipped_passes += 1;
+            return;
+        }
+        let fence = match self
+            .metastore
+            .acquire_lease(JANITOR_LEASE_ID, node_id, JANITOR_LEASE_TTL)
+            .await
+        {
+            Ok(lease) => lease.fence(),
+            Err(error) => {
+                warn!(error = ?error, "Janitor failed to acquire lease, skipping pass.");
+                self.counters.num_skipped_passes += 1;
+                return;
+            }
+        };
+        self.counters.num_passes += 1;
+        let index_metadatas = match self.metastore.list_indexes_metadatas().await {
+            Ok(index_metadatas) => index_metadatas,
+            Err(error) => {
+                error!(error = ?error, "Janitor failed to list indexes.");
+                return;
+            }
+        };
+        for index_metadata in &index_metadatas {
+            if let Err(error) = self
+                .run_index_tasks(index_metadata, &index_metadatas, &fence)
+                .await
+            {
+                error!(index_id = %index_metadata.index_id, error = ?error, "Janitor failed to maintain index.");
+                if is_lease_lost(&error) {
+                    break;
+                }
+            }
+        }
+    }
+
+    async fn run_index_tasks(
+        &mut self,
+        index_metadata: &IndexMetadata,
+        index_metadatas: &[IndexMetadata],
+        fence: &LeaseFence,
+    ) -> anyhow::Result<()> {
+        let index_id = &index_metadata.index_id;
+        // Skips the index early if the lease was lost, the mutations being fenced anyway.
+        self.metastore
+            .check_lease(&fence.lease_id, fence.fencing_token)
+            .await?;
+        let lifecycle_action_opt = enforce_lifecycle_policy(
+            index_metadata,
+            index_metadatas,
+            &*self.metastore,
+            fence,
+            Utc::now().timestamp(),
+        )
+        .await?;
+        match lifecycle_action_opt {
+            Some(LifecycleAction::RolledOver { .. }) => self.counters.num_rollovers += 1,
+            Some(LifecycleAction::Deleted) => {
+                self.counters.num_deleted_indexes += 1;
+                return Ok(());
+            }
+            _ => {}
+        }
+        let expired_split_ids = enforce_retention_policy(
+            index_metadata,
+            &*self.metastore,
+            fence,
+            Utc::now().timestamp(),
+        )
+        .await?;
+        if !expired_split_ids.is_empty() {
+            info!(index_id = %index_id, num_expired_splits = expired_split_ids.len(), "Janitor marked expired splits for deletion.");
+            self.counters.num_expired_splits += expired_split_ids.len();
+        }
+        let storage = self.storage_resolver.resolve(&index_metadata.index_uri)?;
+        let deleted_file_entries = run_garbage_collect(
+            index_id,
+            IndexingSplitStore::create_with_no_local_store(storage),
+            self.metastore.clone(),
+            STAGED_GRACE_PERIOD,
+            DELETION_GRACE_PERIOD,
+            false,
+            Some(fence),
+        )
+        .await?;
+        if !deleted_file_entries.is_empty() {
+            info!(index_id = %index_id, num_deleted_files = deleted_file_entries.len(), "Janitor garbage collected index.");
+            self.counters.num_deleted_files += deleted_file_entries.len();
+            self.counters.num_deleted_bytes += deleted_file_entries
+                .iter()
+                .map(|entry| entry.file_size_in_bytes as usize)
+                .sum::<usize>();
+        }
+        // The splits of the frozen indexes and of the indexes being deleted are not merged.
+        let is_frozen = index_metadata.lifecycle_state.phase == LifecyclePhase::Frozen
+            || matches!(
+                lifecycle_action_opt,
+                Some(LifecycleAction::Frozen | LifecycleAction::Deleting { .. })
+            );
+        let indexing_settings = &index_metadata.indexing_settings;
+        if !is_frozen && (indexing_settings.merge_enabled || indexing_settings.demux_enabled) {
+            self.run_merges(index_metadata).await?;
+            self.counters.num_merge_runs += 1;
+        }
+        Ok(())
+    }
+
+    /// Runs a merge pipeline on the index, which merges its published splits according to the
+    /// merge policy of the index, and waits for it to complete.
+    async fn run_merges(&self, index_metadata: &IndexMetadata) -> anyhow::Result<()> {
+        let pipeline_id = self
+            .indexing_server_client
+            .spawn_merge_pipeline(
+                index_metadata.index_id.clone(),
+                index_metadata.indexing_settings.merge_enabled,
+                index_metadata.indexing_settings.demux_enabled,
+                None,
+            )
+            .await?;
+        let pipeline_handle = self
+            .indexing_server_client
+            .detach_pipeline(&pipeline_id)
+            .await?;
+        let (exit_status, _statistics) = pipeline_handle.join().await;
+        if !exit_status.is_success() {
+            bail!(exit_status);
+        }
+        Ok(())
+    }
+}
+
+/// Marks for deletion the published splits of an index whose documents are all older than the
+/// retention period of the index, and returns their IDs. Splits without a time range are kept.
+/// The files of the splits are deleted later on by garbage collection.
+///
+/// The splits are marked for deletion only if `fence` still holds.
+pub async fn enforce_retention_policy(
+    index_metadata: &IndexMetadata,
+    metastore: &dyn Metastore,
+    fence: &LeaseFence,
+    now_timestamp_secs: i64,
+) -> anyhow::Result<Vec<String>> {
+    let retention = match &index_metadata.indexing_settings.retention {
+        Some(retention) => retention,
+        None => return Ok(Vec::new()),
+    };
+    let resolution = timestamp_resolution(
+        &index_metadata.doc_mapping,
+        &index_metadata.indexing_settings,
+    );
+    let cutoff_timestamp = resolution
+        .secs_to_units(now_timestamp_secs.saturating_sub(retention.period().as_secs() as i64));
+    let expired_split_ids: Vec<String> = metastore
+        .list_splits(
+            &index_metadata.index_id,
+            SplitState::Published,
+            Some(i64::MIN..cutoff_timestamp),
+            None,
+        )
+        .await?
+        .into_iter()
+        .filter(|split| {
+            split
+                .split_metadata
+                .time_range
+                .as_ref()
+                .map_or(false, |time_range| *time_range.end() < cutoff_timestamp)
+        })
+        .map(|split| split.split_metadata.split_id)
+        .collect();
+    if expired_split_ids.is_empty() {
+        return Ok(expired_split_ids);
+    }
+    let split_ids: Vec<&str> = expired_split_ids.iter().map(String::as_str).collect();
+    metastore
+        .mark_splits_for_deletion_fenced(&index_metadata.index_id, &split_ids, fence)
+        .await?;
+    Ok(expired_split_ids)
+}
+
+/// Returns whether a task failed because the janitor lost its lease.
+fn is_lease_lost(error: &anyhow::Error) -> bool {
+    let metastore_error_opt = error.downcast_ref::<MetastoreError>().or_else(|| {
+        match error.downcast_ref::<SplitDeletionError>() {
+            Some(SplitDeletionError::MetastoreFailure(metastore_error)) => Some(metastore_error),
+            _ => None,
+        }
+    });
+    matches!(metastore_error_opt, Some(MetastoreError::LeaseLost { .. }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    use quickwit_common::net::find_available_port;
+    use quickwit_config::{IndexerConfig, RetentionSettings};
+    use quickwit_indexing::actors::IndexingServer;
+    use quickwit_metastore::{Lease, MockMetastore, Split, SplitMetadata};
+
+    use super::*;
+
+    fn create_janitor_cluster_for_test(node_id: &str) -> anyhow::Result<Arc<Cluster>> {
+        let listen_addr = SocketAddr::new(
+            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            find_available_port()?,
+        );
+        let available_services = [QuickwitService::Janitor].into_iter().collect();
+        let cluster = Cluster::new(node_id.to_string(), available_services, listen_addr)?;
+        Ok(Arc::new(cluster))
+    }
+
+    #[tokio::test]
+    async fn test_janitor_pass_requires_lease() -> anyhow::Result<()> {
+        let cluster = create_janitor_cluster_for_test("node-1")?;
+        let mut mock_metastore = MockMetastore::default();
+        let mut acquire_results = vec![
+            Ok(Lease {
+                lease_id: JANITOR_LEASE_ID.to_string(),
+                holder_id: "node-1".to_string(),
+                fencing_token: 3,
+                expiration_timestamp: i64::MAX,
+            }),
+            Err(MetastoreError::LeaseAlreadyHeld {
+                lease_id: JANITOR_LEASE_ID.to_string(),
+                holder_id: "node-2".to_string(),
+            }),
+        ];
+        mock_metastore.expect_acquire_lease().times(2).returning(
+            move |lease_id, holder_id, ttl| {
+                assert_eq!(lease_id, JANITOR_LEASE_ID);
+                assert_eq!(holder_id, "node-1");
+                assert_eq!(ttl, JANITOR_LEASE_TTL);
+                acquire_results.pop().unwrap()
+            },
+        );
+        mock_metastore
+            .expect_list_indexes_metadatas()
+            .times(1)
+            .returning(|| {
+                Ok(vec![
+                    IndexMetadata::for_test("test-index-1", "ram:///indexes/test-index-1"),
+                    IndexMetadata::for_test("test-index-2", "ram:///indexes/test-index-2"),
+                ])
+            });
+        // The lease is lost before the first task: the remaining indexes are not maintained.
+        mock_metastore
+            .expect_check_lease()
+            .times(1)
+            .returning(|lease_id, fencing_token| {
+                assert_eq!(fencing_token, 3);
+                Err(MetastoreError::LeaseLost {
+                    lease_id: lease_id.to_string(),
+                    fencing_token,
+                })
+            });
+        let metastore: Arc<dyn Metastore> = Arc::new(mock_metastore);
+        let data_dir = tempfile::tempdir()?;
+        let indexing_server_client = IndexingServer::spawn(
+            data_dir.path().to_path_buf(),
+            IndexerConfig::default(),
+            metastore.clone(),
+            StorageUriResolver::for_test(),
+        );
+        let mut janitor = Janitor::new(
+            metastore,
+            StorageUriResolver::for_test(),
+            cluster.clone(),
+            indexing_server_client,
+        );
+        janitor.run_pass().await;
+        assert_eq!(janitor.counters().num_skipped_passes, 1);
+        assert_eq!(janitor.counters().num_passes, 0);
+
+        janitor.run_pass().await;
+        assert_eq!(janitor.counters().num_skipped_passes, 1);
+        assert_eq!(janitor.counters().num_passes, 1);
+        assert_eq!(janitor.counters().num_expired_splits, 0);
+        assert_eq!(janitor.counters().num_merge_runs, 0);
+        cluster.leave().await;
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_lease_lost() {
+        let lease_lost = || MetastoreError::LeaseLost {
+            lease_id: JANITOR_LEASE_ID.to_string(),
+            fencing_token: 3,
+        };
+        assert!(is_lease_lost(&anyhow::Error::new(lease_lost())));
+        assert!(is_lease_lost(&anyhow::Error::new(
+            SplitDeletionError::MetastoreFailure(lease_lost())
+        )));
+        assert!(!is_lease_lost(&anyhow::Error::new(
+            MetastoreError::IndexDoesNotExist {
+                index_id: "test-index".to_string()
+            }
+        )));
+    }
+
+    fn make_split(split_id: &str, time_range_opt: Option<(i64, i64)>) -> Split {
+        Split {
+            split_metadata: SplitMetadata {
+                split_id: split_id.to_string(),
+                time_range: time_range_opt.map(|(start, end)| start..=end),
+                ..Default::default()
+            },
+            split_state: SplitState::Published,
+            update_timestamp: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_enforce_retention_policy() -> anyhow::Result<()> {
+        let mut index_metadata = IndexMetadata::for_test("test-index", "ram:///indexes/test-index");
+        let mut mock_metastore = MockMetastore::default();
+        mock_metastore.expect_list_splits().times(1).returning(
+            |index_id, split_state, time_range_opt, _tags| {
+                assert_eq!(index_id, "test-index");
+                assert_eq!(split_state, SplitState::Published);
+                assert_eq!(time_range_opt, Some(i64::MIN..1_000));
+                Ok(vec![
+                    make_split("expired", Some((0, 999))),
+                    make_split("overlapping", Some((500, 1_500))),
+                    make_split("no-time-range", None),
+                ])
+            },
+        );
+        mock_metastore
+            .expect_mark_splits_for_deletion_fenced()
+            .times(1)
+            .returning(|index_id, split_ids, fence| {
+                assert_eq!(index_id, "test-index");
+                assert_eq!(split_ids, vec!["expired"]);
+                assert_eq!(fence.fencing_token, 3);
+                Ok(())
+            });
+        let fence = LeaseFence {
+            lease_id: JANITOR_LEASE_ID.to_string(),
+            fencing_token: 3,
+        };
+        // Without a retention policy, no split expires.
+        let expired_split_ids =
+            enforce_retention_policy(&index_metadata, &mock_metastore, &fence, 4_600).await?;
+        assert!(expired_split_ids.is_empty());
+
+        index_metadata.indexing_settings.retention = Some(RetentionSettings { period_secs: 3_600 });
+        let expired_split_ids =
+            enforce_retention_policy(&index_metadata, &mock_metastore, &fence, 4_600).await?;
+        assert_eq!(expired_split_ids, vec!["expired".to_string()]);
+        Ok(())
+    }
+}
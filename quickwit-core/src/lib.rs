@@ -25,10 +25,26 @@
 //! - `index_data` for indexing new-line delimited json documents
 //! - `search_index` for searching an index
 //! - `delete_index` for deleting an index
+//! - `analyze_index_compaction` for recommending merge policy and demux settings
+//! - `rebuild_hotcaches` for rebuilding the outdated hotcaches of the splits of an index
+//! - `Janitor` for running the periodic maintenance tasks of indexes
+//! - `enforce_lifecycle_policy` for rolling write aliases over and moving indexes through their
+//!   lifecycle phases
 
+mod compaction_report;
+mod hotcache;
 mod index;
+mod index_lifecycle;
+mod janitor;
 
+pub use compaction_report::{
+    analyze_index_compaction, apply_compaction_recommendations, CompactionReport, Recommendation,
+    TagFieldStats,
+};
+pub use hotcache::{rebuild_hotcaches, HotcacheRebuild};
 pub use index::{create_index, delete_index, garbage_collect_index, reset_index};
+pub use index_lifecycle::{enforce_lifecycle_policy, LifecycleAction};
+pub use janitor::{enforce_retention_policy, Janitor, JanitorCounters};
 
 #[cfg(test)]
 mod tests {
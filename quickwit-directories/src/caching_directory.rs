@@ -23,6 +23,7 @@ use std::sync::Arc;
 use std::{fmt, io};
 
 use async_trait::async_trait;
+use quickwit_common::memory_usage::MemoryComponent;
 use quickwit_storage::SliceCache;
 use tantivy::directory::error::OpenReadError;
 use tantivy::directory::{FileHandle, OwnedBytes};
@@ -48,13 +49,18 @@ impl CachingDirectory {
     /// The overall number of bytes held in memory may exceed the capacity at one point
     /// if a read request is large than `capacity_in_bytes`.
     /// In that case, the read payload will not be saved in the cache.
+    ///
+    /// The cached bytes are accounted for in the `caching_directories` memory usage.
     pub fn new_with_capacity_in_bytes(
         underlying: Arc<dyn Directory>,
         capacity_in_bytes: usize,
     ) -> CachingDirectory {
         CachingDirectory {
             underlying,
-            cache: Arc::new(SliceCache::with_capacity_in_bytes(capacity_in_bytes)),
+            cache: Arc::new(SliceCache::with_capacity_in_bytes(
+                capacity_in_bytes,
+                MemoryComponent::CachingDirectories,
+            )),
         }
     }
 
@@ -66,9 +72,16 @@ impl CachingDirectory {
     pub fn new_with_unlimited_capacity(underlying: Arc<dyn Directory>) -> CachingDirectory {
         CachingDirectory {
             underlying,
-            cache: Arc::new(SliceCache::with_infinite_capacity()),
+            cache: Arc::new(SliceCache::with_infinite_capacity(
+                MemoryComponent::CachingDirectories,
+            )),
         }
     }
+
+    /// Returns the number of bytes held by the cache of the directory.
+    pub fn num_bytes(&self) -> usize {
+        self.cache.num_bytes()
+    }
 }
 
 impl fmt::Debug for CachingDirectory {
@@ -32,6 +32,20 @@ use tantivy::{AsyncIoResult, Directory, HasLen, Index, IndexReader, ReloadPolicy
 
 use crate::{CachingDirectory, DebugProxyDirectory};
 
+/// Version of the content of the hotcache, recorded in its header.
+///
+/// It must be bumped whenever [`write_hotcache`] changes what it caches, so that the hotcache of
+/// existing splits can be detected as outdated and rebuilt. Hotcaches written before the version
+/// was recorded hold version 1.
+pub const HOTCACHE_VERSION: u32 = 1;
+
+/// Format of the hotcache without a recorded version.
+const LEGACY_FORMAT: u8 = 0;
+
+/// Format of the hotcache in which the format byte is followed by the version of the content of
+/// the hotcache, as a 4 bytes little endian integer.
+const VERSIONED_FORMAT: u8 = 1;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct SliceCacheIndexEntry {
     start: usize, //< legacy. We keep this instead of range due to existing indices.
@@ -97,8 +111,8 @@ impl StaticDirectoryCacheBuilder {
 
     /// Flush needs to be called afterwards.
     pub fn write(self, wrt: &mut dyn io::Write) -> tantivy::Result<()> {
-        // Write format version
-        wrt.write_all(b"\x00")?;
+        wrt.write_all(&[VERSIONED_FORMAT])?;
+        wrt.write_all(&HOTCACHE_VERSION.to_le_bytes())?;
 
         let file_lengths_bytes = serde_cbor::to_vec(&self.file_lengths).unwrap();
         wrt.write_all(&(file_lengths_bytes.len() as u64).to_le_bytes())?;
@@ -130,6 +144,29 @@ where T: serde::de::DeserializeOwned {
     value
 }
 
+/// Reads the header of the hotcache and returns the version of its content.
+fn read_hotcache_header(bytes: &mut OwnedBytes) -> tantivy::Result<u32> {
+    if bytes.is_empty() {
+        return Err(tantivy::TantivyError::DataCorruption(
+            DataCorruption::comment_only("Hotcache is empty."),
+        ));
+    }
+    match bytes.read_u8() {
+        LEGACY_FORMAT => Ok(1),
+        VERSIONED_FORMAT if bytes.len() >= 4 => {
+            let hotcache_version = u32::from_le_bytes(bytes.as_slice()[..4].try_into().unwrap());
+            bytes.advance(4);
+            Ok(hotcache_version)
+        }
+        format_version => Err(tantivy::TantivyError::DataCorruption(
+            DataCorruption::comment_only(format!(
+                "Format version not supported: `{}`",
+                format_version
+            )),
+        )),
+    }
+}
+
 #[derive(Debug)]
 struct StaticDirectoryCache {
     file_lengths: HashMap<PathBuf, u64>,
@@ -138,16 +175,7 @@ struct StaticDirectoryCache {
 
 impl StaticDirectoryCache {
     pub fn open(mut bytes: OwnedBytes) -> tantivy::Result<StaticDirectoryCache> {
-        let format_version = bytes.read_u8();
-
-        if format_version != 0 {
-            return Err(tantivy::TantivyError::DataCorruption(
-                DataCorruption::comment_only(format!(
-                    "Format version not supported: `{}`",
-                    format_version
-                )),
-            ));
-        }
+        read_hotcache_header(&mut bytes)?;
 
         let file_lengths: HashMap<PathBuf, u64> = deserialize_cbor(&mut bytes).unwrap();
 
@@ -360,6 +388,12 @@ impl HotDirectory {
         let static_cache = StaticDirectoryCache::open(hot_cache_bytes)?;
         Ok(static_cache.get_stats())
     }
+
+    /// Returns the version of the content of the hotcache. Hotcaches with a version older than
+    /// [`HOTCACHE_VERSION`] can be rebuilt with [`write_hotcache`].
+    pub fn get_hotcache_version(mut hot_cache_bytes: OwnedBytes) -> tantivy::Result<u32> {
+        read_hotcache_header(&mut hot_cache_bytes)
+    }
 }
 
 struct FileSliceWithCache {
@@ -703,4 +737,36 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_hotcache_version() -> tantivy::Result<()> {
+        let mut directory_cache_builder = StaticDirectoryCacheBuilder::default();
+        directory_cache_builder
+            .add_file(Path::new("one.txt"), 100)
+            .add_bytes(b"hello", 0);
+        let mut buffer = Vec::new();
+        directory_cache_builder.write(&mut buffer)?;
+        assert_eq!(buffer[0], VERSIONED_FORMAT);
+        assert_eq!(
+            HotDirectory::get_hotcache_version(OwnedBytes::new(buffer.clone()))?,
+            HOTCACHE_VERSION
+        );
+        // Hotcaches written before the version was recorded.
+        let mut legacy_buffer = vec![LEGACY_FORMAT];
+        legacy_buffer.extend_from_slice(&buffer[5..]);
+        assert_eq!(
+            HotDirectory::get_hotcache_version(OwnedBytes::new(legacy_buffer.clone()))?,
+            1
+        );
+        let directory_cache = StaticDirectoryCache::open(OwnedBytes::new(legacy_buffer))?;
+        assert_eq!(
+            directory_cache.get_file_length(Path::new("one.txt")),
+            Some(100)
+        );
+
+        buffer[0] = 2;
+        assert!(HotDirectory::get_hotcache_version(OwnedBytes::new(buffer)).is_err());
+        assert!(HotDirectory::get_hotcache_version(OwnedBytes::empty()).is_err());
+        Ok(())
+    }
 }
@@ -38,7 +38,7 @@ mod union_directory;
 pub use self::bundle_directory::{get_hotcache_from_split, read_split_footer, BundleDirectory};
 pub use self::caching_directory::CachingDirectory;
 pub use self::debug_proxy_directory::{DebugProxyDirectory, ReadOperation};
-pub use self::hot_directory::{write_hotcache, HotDirectory};
+pub use self::hot_directory::{write_hotcache, HotDirectory, HOTCACHE_VERSION};
 pub use self::storage_directory::StorageDirectory;
 pub use self::union_directory::UnionDirectory;
 
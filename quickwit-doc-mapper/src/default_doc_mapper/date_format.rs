@@ -0,0 +1,316 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::bail;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+/// An input format accepted when parsing the values of a `date` or `i64` field.
+///
+/// Parsed dates are always normalized to UTC: dates carrying an offset are converted to UTC,
+/// and dates without any offset are assumed to be expressed in UTC.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum DateFormat {
+    /// RFC 3339 date, e.g. `2021-12-16T10:05:30+01:00`.
+    Rfc3339,
+    /// Number of seconds since the Unix epoch.
+    EpochSecs,
+    /// Number of milliseconds since the Unix epoch.
+    EpochMillis,
+    /// Number of microseconds since the Unix epoch.
+    EpochMicros,
+    /// Number of nanoseconds since the Unix epoch.
+    EpochNanos,
+    /// `strftime`-like pattern, e.g. `%Y-%m-%d %H:%M:%S %z`.
+    Strftime(String),
+}
+
+impl DateFormat {
+    /// Parses a JSON value with this format. Epoch formats accept JSON numbers and numeric
+    /// strings, the other formats accept JSON strings only.
+    pub fn parse_json(&self, json_value: &JsonValue) -> Option<DateTime<Utc>> {
+        match json_value {
+            JsonValue::Number(value_as_number) if self.is_epoch() => {
+                self.date_time_from_epoch(value_as_number.as_i64()?)
+            }
+            JsonValue::String(value_as_str) => self.parse_str(value_as_str),
+            _ => None,
+        }
+    }
+
+    /// Parses a string with this format.
+    pub fn parse_str(&self, value_as_str: &str) -> Option<DateTime<Utc>> {
+        match self {
+            DateFormat::Rfc3339 => DateTime::parse_from_rfc3339(value_as_str)
+                .ok()
+                .map(|date_time| date_time.with_timezone(&Utc)),
+            DateFormat::Strftime(pattern) => {
+                if let Ok(date_time) = DateTime::parse_from_str(value_as_str, pattern) {
+                    return Some(date_time.with_timezone(&Utc));
+                }
+                if let Ok(naive_date_time) = NaiveDateTime::parse_from_str(value_as_str, pattern) {
+                    return Some(Utc.from_utc_datetime(&naive_date_time));
+                }
+                let naive_date = NaiveDate::parse_from_str(value_as_str, pattern).ok()?;
+                Some(Utc.from_utc_datetime(&naive_date.and_hms(0, 0, 0)))
+            }
+            _ => self.date_time_from_epoch(value_as_str.trim().parse().ok()?),
+        }
+    }
+
+    /// Formats a date with this format: epoch formats produce JSON numbers, the other formats
+    /// produce JSON strings. Returns `None` if the number of units elapsed since the Unix epoch
+    /// does not fit in an `i64`.
+    pub fn format_json(&self, date_time: &DateTime<Utc>) -> Option<JsonValue> {
+        match self {
+            DateFormat::Rfc3339 => Some(JsonValue::String(date_time.to_rfc3339())),
+            DateFormat::Strftime(pattern) => {
+                Some(JsonValue::String(date_time.format(pattern).to_string()))
+            }
+            _ => {
+                let num_units_per_sec = self.num_units_per_sec()?;
+                let subsec_units =
+                    date_time.timestamp_subsec_nanos() as i64 / (1_000_000_000 / num_units_per_sec);
+                let timestamp = date_time
+                    .timestamp()
+                    .checked_mul(num_units_per_sec)?
+                    .checked_add(subsec_units)?;
+                Some(JsonValue::from(timestamp))
+            }
+        }
+    }
+
+    /// Returns whether the format is a number of units elapsed since the Unix epoch.
+    pub fn is_epoch(&self) -> bool {
+        matches!(
+            self,
+            DateFormat::EpochSecs
+                | DateFormat::EpochMillis
+                | DateFormat::EpochMicros
+                | DateFormat::EpochNanos
+        )
+    }
+
+    fn num_units_per_sec(&self) -> Option<i64> {
+        match self {
+            DateFormat::EpochSecs => Some(1),
+            DateFormat::EpochMillis => Some(1_000),
+            DateFormat::EpochMicros => Some(1_000_000),
+            DateFormat::EpochNanos => Some(1_000_000_000),
+            _ => None,
+        }
+    }
+
+    fn date_time_from_epoch(&self, value: i64) -> Option<DateTime<Utc>> {
+        let num_units_per_sec = self.num_units_per_sec()?;
+        let secs = value.div_euclid(num_units_per_sec);
+        let nanos = value.rem_euclid(num_units_per_sec) * (1_000_000_000 / num_units_per_sec);
+        Utc.timestamp_opt(secs, nanos as u32).single()
+    }
+}
+
+impl FromStr for DateFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format_str: &str) -> anyhow::Result<Self> {
+        let date_format = match format_str {
+            "rfc3339" => DateFormat::Rfc3339,
+            "epoch_secs" => DateFormat::EpochSecs,
+            "epoch_millis" => DateFormat::EpochMillis,
+            "epoch_micros" => DateFormat::EpochMicros,
+            "epoch_nanos" => DateFormat::EpochNanos,
+            pattern => {
+                if !pattern.contains('%')
+                    || StrftimeItems::new(pattern).any(|item| item == Item::Error)
+                {
+                    bail!(
+                        "Unknown date format `{}`. Expected `rfc3339`, `epoch_secs`, \
+                         `epoch_millis`, `epoch_micros`, `epoch_nanos`, or a strftime pattern.",
+                        pattern
+                    );
+                }
+                DateFormat::Strftime(pattern.to_string())
+            }
+        };
+        Ok(date_format)
+    }
+}
+
+impl fmt::Display for DateFormat {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DateFormat::Rfc3339 => write!(formatter, "rfc3339"),
+            DateFormat::EpochSecs => write!(formatter, "epoch_secs"),
+            DateFormat::EpochMillis => write!(formatter, "epoch_millis"),
+            DateFormat::EpochMicros => write!(formatter, "epoch_micros"),
+            DateFormat::EpochNanos => write!(formatter, "epoch_nanos"),
+            DateFormat::Strftime(pattern) => write!(formatter, "{}", pattern),
+        }
+    }
+}
+
+impl TryFrom<String> for DateFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(format_str: String) -> anyhow::Result<Self> {
+        format_str.parse()
+    }
+}
+
+impl From<DateFormat> for String {
+    fn from(date_format: DateFormat) -> String {
+        date_format.to_string()
+    }
+}
+
+/// Parses a JSON value with the first of `date_formats` that accepts it.
+pub(crate) fn parse_date_with_formats(
+    json_value: &JsonValue,
+    date_formats: &[DateFormat],
+) -> Option<DateTime<Utc>> {
+    date_formats
+        .iter()
+        .find_map(|date_format| date_format.parse_json(json_value))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_date_format_from_str() {
+        assert_eq!(
+            "rfc3339".parse::<DateFormat>().unwrap(),
+            DateFormat::Rfc3339
+        );
+        assert_eq!(
+            "epoch_millis".parse::<DateFormat>().unwrap(),
+            DateFormat::EpochMillis
+        );
+        assert_eq!(
+            "%Y-%m-%d %H:%M:%S".parse::<DateFormat>().unwrap(),
+            DateFormat::Strftime("%Y-%m-%d %H:%M:%S".to_string())
+        );
+        assert!("epoch_days".parse::<DateFormat>().is_err());
+        assert!("%Y-%m-%d %Q".parse::<DateFormat>().is_err());
+    }
+
+    #[test]
+    fn test_date_format_parse() {
+        let expected = Utc.ymd(2021, 12, 16).and_hms(9, 5, 30);
+        assert_eq!(
+            DateFormat::Rfc3339.parse_json(&json!("2021-12-16T10:05:30+01:00")),
+            Some(expected)
+        );
+        assert_eq!(
+            DateFormat::EpochSecs.parse_json(&json!(1639645530)),
+            Some(expected)
+        );
+        assert_eq!(
+            DateFormat::EpochMillis.parse_json(&json!("1639645530000")),
+            Some(expected)
+        );
+        assert_eq!(
+            DateFormat::EpochNanos.parse_json(&json!(1639645530000000000i64)),
+            Some(expected)
+        );
+        assert_eq!(
+            DateFormat::Strftime("%d/%m/%Y %H:%M:%S %z".to_string())
+                .parse_json(&json!("16/12/2021 04:05:30 -0500")),
+            Some(expected)
+        );
+        assert_eq!(
+            DateFormat::Strftime("%Y-%m-%d %H:%M:%S".to_string())
+                .parse_json(&json!("2021-12-16 09:05:30")),
+            Some(expected)
+        );
+        assert_eq!(
+            DateFormat::Strftime("%Y-%m-%d".to_string()).parse_json(&json!("2021-12-16")),
+            Some(Utc.ymd(2021, 12, 16).and_hms(0, 0, 0))
+        );
+        assert_eq!(DateFormat::Rfc3339.parse_json(&json!(1639645530)), None);
+        assert_eq!(DateFormat::EpochSecs.parse_json(&json!("2021-12-16")), None);
+    }
+
+    #[test]
+    fn test_date_format_format_json() {
+        let date_time = Utc.ymd(2021, 12, 16).and_hms_milli(9, 5, 30, 250);
+        assert_eq!(
+            DateFormat::Rfc3339.format_json(&date_time),
+            Some(json!("2021-12-16T09:05:30.250+00:00"))
+        );
+        assert_eq!(
+            DateFormat::EpochSecs.format_json(&date_time),
+            Some(json!(1639645530))
+        );
+        assert_eq!(
+            DateFormat::EpochMicros.format_json(&date_time),
+            Some(json!(1639645530250000i64))
+        );
+        assert_eq!(
+            DateFormat::EpochNanos.format_json(&date_time),
+            Some(json!(1639645530250000000i64))
+        );
+        assert_eq!(
+            DateFormat::Strftime("%Y-%m-%d %H:%M".to_string()).format_json(&date_time),
+            Some(json!("2021-12-16 09:05"))
+        );
+        // Nanoseconds since the Unix epoch overflow an `i64` after 2262.
+        let date_time = Utc.ymd(2300, 1, 1).and_hms(0, 0, 0);
+        assert_eq!(DateFormat::EpochNanos.format_json(&date_time), None);
+        assert_eq!(
+            DateFormat::EpochSecs.format_json(&date_time),
+            Some(json!(10_413_792_000i64))
+        );
+    }
+
+    #[test]
+    fn test_parse_date_with_formats() {
+        let date_formats = vec![
+            DateFormat::Rfc3339,
+            DateFormat::EpochMillis,
+            DateFormat::Strftime("%Y-%m-%d %H:%M:%S".to_string()),
+        ];
+        let expected = Utc.ymd(2021, 12, 16).and_hms(9, 5, 30);
+        for json_value in [
+            json!("2021-12-16T09:05:30Z"),
+            json!(1639645530000i64),
+            json!("2021-12-16 09:05:30"),
+        ] {
+            assert_eq!(
+                parse_date_with_formats(&json_value, &date_formats),
+                Some(expected)
+            );
+        }
+        assert_eq!(
+            parse_date_with_formats(&json!("yesterday"), &date_formats),
+            None
+        );
+    }
+}
@@ -21,21 +21,23 @@ use std::collections::{BTreeSet, HashSet};
 use std::convert::TryFrom;
 
 use anyhow::{bail, Context};
+use chrono::Utc;
 use quickwit_proto::SearchRequest;
 use serde::{Deserialize, Serialize};
 use serde_json::{self, Value as JsonValue};
 use tantivy::query::Query;
 use tantivy::schema::{
-    Cardinality, FieldEntry, FieldType, FieldValue, Schema, SchemaBuilder, Value, STORED,
+    Cardinality, FieldEntry, FieldType, FieldValue, IntOptions, Schema, SchemaBuilder, Value,
+    STORED,
 };
 use tantivy::Document;
 use tracing::info;
 
 use super::field_mapping_entry::{DocParsingError, FieldPath};
 use super::{default_as_true, FieldMappingEntry, FieldMappingType};
-use crate::query_builder::build_query;
+use crate::query_builder::{build_query, build_split_query};
 use crate::sort_by::{SortBy, SortOrder};
-use crate::{DocMapper, QueryParserError, SOURCE_FIELD_NAME};
+use crate::{DocMapper, QueryParserError, TimestampResolution, SOURCE_FIELD_NAME};
 
 /// Name of the raw tokenizer.
 const RAW_TOKENIZER_NAME: &str = "raw";
@@ -63,6 +65,9 @@ pub struct DefaultDocMapperBuilder {
     #[serde(skip_serializing_if = "Option::is_none")]
     /// Name of the field to demux by.
     pub demux_field: Option<String>,
+    /// Name of the field stamped with the time at which each document is indexed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ingest_timestamp_field: Option<String>,
 }
 
 /// Specifies the name of the sort field and the sort order for an index.
@@ -94,6 +99,7 @@ impl DefaultDocMapperBuilder {
             field_mappings: vec![],
             tag_fields: Default::default(),
             demux_field: None,
+            ingest_timestamp_field: None,
         }
     }
 
@@ -141,15 +147,25 @@ impl DefaultDocMapperBuilder {
         // Build the root mapping entry, it has an empty name so that we don't prefix all
         // field name with it.
         let field_mappings = FieldMappingEntry::root(FieldMappingType::Object(self.field_mappings));
+        let timestamp_resolution = self
+            .timestamp_field
+            .as_ref()
+            .and_then(|timestamp_field_name| {
+                field_mappings.find_field_mapping(timestamp_field_name)
+            })
+            .map(|timestamp_field_mapping| timestamp_field_mapping.resolution)
+            .unwrap_or_default();
         Ok(DefaultDocMapper {
             schema,
             store_source: self.store_source,
             default_search_field_names,
             timestamp_field_name: self.timestamp_field,
+            timestamp_resolution,
             sort_by,
             field_mappings,
             tag_field_names,
             demux_field_name: self.demux_field,
+            ingest_timestamp_field_name: self.ingest_timestamp_field,
         })
     }
 
@@ -200,6 +216,22 @@ impl DefaultDocMapperBuilder {
         if self.store_source {
             builder.add_text_field(SOURCE_FIELD_NAME, STORED);
         }
+        if let Some(ingest_timestamp_field_name) = &self.ingest_timestamp_field {
+            if ingest_timestamp_field_name == SOURCE_FIELD_NAME
+                || unique_field_names.contains(ingest_timestamp_field_name)
+            {
+                bail!(
+                    "Ingest timestamp field `{}` is added by the indexer, please, remove it from \
+                     the field mappings.",
+                    ingest_timestamp_field_name
+                );
+            }
+            let ingest_timestamp_options = IntOptions::default()
+                .set_indexed()
+                .set_stored()
+                .set_fast(Cardinality::SingleValue);
+            builder.add_i64_field(ingest_timestamp_field_name, ingest_timestamp_options);
+        }
 
         Ok(builder.build())
     }
@@ -311,6 +343,66 @@ fn resolve_demux_field(
     Ok(())
 }
 
+/// Checks that the fields used to keep only the latest version of each document are single-valued
+/// u64 or i64 fast fields. The doc ID field must also be indexed, as the search looks up the
+/// versions of a document by its ID.
+pub fn resolve_versioning_fields(
+    doc_id_field_name: &str,
+    version_field_name: &str,
+    schema: &Schema,
+) -> anyhow::Result<()> {
+    for (field_kind, field_name) in [
+        ("Doc ID", doc_id_field_name),
+        ("Version", version_field_name),
+    ] {
+        let field = schema.get_field(field_name).with_context(|| {
+            format!(
+                "Unknown {} field: `{}`",
+                field_kind.to_lowercase(),
+                field_name
+            )
+        })?;
+        let field_entry = schema.get_field_entry(field);
+        if !field_entry.is_fast() {
+            bail!(
+                "{} field must be a fast field, please add the fast property to your field `{}`.",
+                field_kind,
+                field_name
+            )
+        }
+        match field_entry.field_type() {
+            FieldType::U64(options) | FieldType::I64(options) => {
+                if options.get_fastfield_cardinality() == Some(Cardinality::MultiValues) {
+                    bail!(
+                        "{} field cannot be an array, please change your field `{}` from an array \
+                         to a single value.",
+                        field_kind,
+                        field_name
+                    )
+                }
+            }
+            _ => {
+                bail!(
+                    "{} field must be of type u64 or i64, please change your field type `{}` to \
+                     u64 or i64.",
+                    field_kind,
+                    field_name
+                )
+            }
+        }
+    }
+    let doc_id_field = schema
+        .get_field(doc_id_field_name)
+        .expect("The doc ID field should exist.");
+    if !schema.get_field_entry(doc_id_field).is_indexed() {
+        bail!(
+            "Doc ID field must be indexed, please add the indexed property to your field `{}`.",
+            doc_id_field_name
+        )
+    }
+    Ok(())
+}
+
 impl TryFrom<DefaultDocMapperBuilder> for DefaultDocMapper {
     type Error = anyhow::Error;
 
@@ -336,6 +428,7 @@ impl From<DefaultDocMapper> for DefaultDocMapperBuilder {
                 .field_mappings()
                 .unwrap_or_else(Vec::new),
             demux_field: value.demux_field_name(),
+            ingest_timestamp_field: value.ingest_timestamp_field_name(),
             sort_by: sort_by_config,
             tag_fields: value.tag_field_names.into_iter().collect(),
             default_search_fields: value.default_search_field_names,
@@ -357,6 +450,8 @@ pub struct DefaultDocMapper {
     pub default_search_field_names: Vec<String>,
     /// Timestamp field name.
     pub timestamp_field_name: Option<String>,
+    /// Unit of the timestamps held by the timestamp field.
+    pub timestamp_resolution: TimestampResolution,
     /// Sort field name and order.
     pub sort_by: SortBy,
     /// List of field mappings which defines how a json field is mapped to index fields.
@@ -368,6 +463,8 @@ pub struct DefaultDocMapper {
     pub tag_field_names: BTreeSet<String>,
     /// Demux field name.
     pub demux_field_name: Option<String>,
+    /// Ingest timestamp field name.
+    pub ingest_timestamp_field_name: Option<String>,
 }
 
 impl DefaultDocMapper {
@@ -400,6 +497,10 @@ impl std::fmt::Debug for DefaultDocMapper {
             )
             .field("timestamp_field_name", &self.timestamp_field_name())
             .field("demux_field_name", &self.demux_field_name())
+            .field(
+                "ingest_timestamp_field_name",
+                &self.ingest_timestamp_field_name(),
+            )
             // TODO: complete it.
             .finish()
     }
@@ -433,6 +534,16 @@ impl DocMapper for DefaultDocMapper {
             // value.
             document.add(FieldValue::new(source, Value::Str(doc_json)));
         }
+        if let Some(ingest_timestamp_field_name) = &self.ingest_timestamp_field_name {
+            let ingest_timestamp_field = self
+                .schema
+                .get_field(ingest_timestamp_field_name)
+                .ok_or_else(|| {
+                    DocParsingError::NoSuchFieldInSchema(ingest_timestamp_field_name.clone())
+                })?;
+            let ingest_timestamp = self.timestamp_resolution.date_time_to_units(&Utc::now());
+            document.add_i64(ingest_timestamp_field, ingest_timestamp);
+        }
         Ok(document)
     }
 
@@ -444,6 +555,19 @@ impl DocMapper for DefaultDocMapper {
         build_query(split_schema, request, &self.default_search_field_names)
     }
 
+    fn split_query(
+        &self,
+        split_schema: Schema,
+        request: &SearchRequest,
+    ) -> Result<(Box<dyn Query>, Vec<String>), QueryParserError> {
+        build_split_query(
+            &self.schema,
+            split_schema,
+            request,
+            &self.default_search_field_names,
+        )
+    }
+
     fn schema(&self) -> Schema {
         self.schema.clone()
     }
@@ -452,10 +576,18 @@ impl DocMapper for DefaultDocMapper {
         self.timestamp_field_name.clone()
     }
 
+    fn timestamp_resolution(&self) -> TimestampResolution {
+        self.timestamp_resolution
+    }
+
     fn demux_field_name(&self) -> Option<String> {
         self.demux_field_name.clone()
     }
 
+    fn ingest_timestamp_field_name(&self) -> Option<String> {
+        self.ingest_timestamp_field_name.clone()
+    }
+
     fn sort_by(&self) -> SortBy {
         self.sort_by.clone()
     }
@@ -469,11 +601,13 @@ impl DocMapper for DefaultDocMapper {
 mod tests {
     use std::collections::HashMap;
 
+    use chrono::Utc;
     use serde_json::{self, Value as JsonValue};
 
     use super::DefaultDocMapper;
     use crate::{
-        DefaultDocMapperBuilder, DocMapper, DocParsingError, SortBy, SortOrder, SOURCE_FIELD_NAME,
+        resolve_versioning_fields, DefaultDocMapperBuilder, DocMapper, DocParsingError, SortBy,
+        SortOrder, TimestampResolution, SOURCE_FIELD_NAME,
     };
 
     const JSON_DOC_VALUE: &str = r#"
@@ -677,6 +811,111 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_doc_mapper_with_timestamp_resolution() -> anyhow::Result<()> {
+        let doc_mapper = r#"{
+            "type": "default",
+            "default_search_fields": [],
+            "timestamp_field": "span.start_time",
+            "tag_fields": [],
+            "field_mappings": [
+                {
+                    "name": "span",
+                    "type": "object",
+                    "field_mappings": [
+                        {
+                            "name": "start_time",
+                            "type": "i64",
+                            "fast": true,
+                            "resolution": "microseconds",
+                            "input_formats": ["rfc3339", "epoch_nanos"]
+                        }
+                    ]
+                }
+            ]
+        }"#;
+        let doc_mapper = serde_json::from_str::<DefaultDocMapperBuilder>(doc_mapper)?.build()?;
+        assert_eq!(
+            doc_mapper.timestamp_resolution(),
+            TimestampResolution::Microseconds
+        );
+        let schema = doc_mapper.schema();
+        let timestamp_field = schema.get_field("span.start_time").unwrap();
+        for doc_json in [
+            r#"{"span": {"start_time": "2021-12-19T17:39:57.123456Z"}}"#,
+            r#"{"span": {"start_time": 1639935597123456789}}"#,
+        ] {
+            let document = doc_mapper.doc_from_json(doc_json.to_string())?;
+            assert_eq!(
+                document.get_first(timestamp_field).unwrap().i64_value(),
+                Some(1_639_935_597_123_456)
+            );
+        }
+        let doc_mapper_json = serde_json::to_string(&doc_mapper)?;
+        assert!(doc_mapper_json.contains(r#""resolution":"microseconds""#));
+        Ok(())
+    }
+
+    #[test]
+    fn test_doc_mapper_with_ingest_timestamp_field() -> anyhow::Result<()> {
+        let doc_mapper = r#"{
+            "type": "default",
+            "default_search_fields": [],
+            "timestamp_field": "timestamp",
+            "ingest_timestamp_field": "ingest_timestamp",
+            "tag_fields": [],
+            "field_mappings": [
+                {
+                    "name": "timestamp",
+                    "type": "i64",
+                    "fast": true,
+                    "resolution": "milliseconds"
+                }
+            ]
+        }"#;
+        let doc_mapper = serde_json::from_str::<DefaultDocMapperBuilder>(doc_mapper)?.build()?;
+        let schema = doc_mapper.schema();
+        let ingest_timestamp_field = schema.get_field("ingest_timestamp").unwrap();
+        assert!(schema.get_field_entry(ingest_timestamp_field).is_fast());
+
+        let before_millis = Utc::now().timestamp_millis();
+        let document = doc_mapper
+            .doc_from_json(r#"{"timestamp": 1639935597123, "ingest_timestamp": 42}"#.to_string())?;
+        let after_millis = Utc::now().timestamp_millis();
+        let ingest_timestamp = document
+            .get_first(ingest_timestamp_field)
+            .and_then(|value| value.i64_value())
+            .unwrap();
+        assert!((before_millis..=after_millis).contains(&ingest_timestamp));
+
+        let doc_mapper_json = serde_json::to_string(&doc_mapper)?;
+        assert!(doc_mapper_json.contains(r#""ingest_timestamp_field":"ingest_timestamp""#));
+        Ok(())
+    }
+
+    #[test]
+    fn test_fail_to_build_doc_mapper_with_mapped_ingest_timestamp_field() -> anyhow::Result<()> {
+        let doc_mapper = r#"{
+            "type": "default",
+            "default_search_fields": [],
+            "ingest_timestamp_field": "ingest_timestamp",
+            "tag_fields": [],
+            "field_mappings": [
+                {
+                    "name": "ingest_timestamp",
+                    "type": "i64"
+                }
+            ]
+        }"#;
+        let builder = serde_json::from_str::<DefaultDocMapperBuilder>(doc_mapper)?;
+        assert_eq!(
+            builder.build().unwrap_err().to_string(),
+            "Ingest timestamp field `ingest_timestamp` is added by the indexer, please, remove it \
+             from the field mappings."
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_fail_to_build_doc_mapper_with_multivalued_timestamp_field() -> anyhow::Result<()> {
         let doc_mapper = r#"{
@@ -1036,4 +1275,59 @@ mod tests {
         assert_eq!(builder.build().unwrap_err().to_string(), expected_msg);
         Ok(())
     }
+
+    #[test]
+    fn test_resolve_versioning_fields() -> anyhow::Result<()> {
+        let doc_mapper = r#"{
+            "type": "default",
+            "default_search_fields": [],
+            "tag_fields": [],
+            "field_mappings": [
+                {
+                    "name": "id",
+                    "type": "u64",
+                    "fast": true
+                },
+                {
+                    "name": "version",
+                    "type": "i64",
+                    "fast": true
+                },
+                {
+                    "name": "title",
+                    "type": "text"
+                },
+                {
+                    "name": "not_indexed_id",
+                    "type": "u64",
+                    "indexed": false,
+                    "fast": true
+                }
+            ]
+        }"#;
+        let builder = serde_json::from_str::<DefaultDocMapperBuilder>(doc_mapper)?;
+        let schema = builder.build()?.schema();
+        resolve_versioning_fields("id", "version", &schema)?;
+        assert_eq!(
+            resolve_versioning_fields("id", "title", &schema)
+                .unwrap_err()
+                .to_string(),
+            "Version field must be a fast field, please add the fast property to your field \
+             `title`."
+        );
+        assert_eq!(
+            resolve_versioning_fields("not_indexed_id", "version", &schema)
+                .unwrap_err()
+                .to_string(),
+            "Doc ID field must be indexed, please add the indexed property to your field \
+             `not_indexed_id`."
+        );
+        assert_eq!(
+            resolve_versioning_fields("unknown", "version", &schema)
+                .unwrap_err()
+                .to_string(),
+            "Unknown doc id field: `unknown`"
+        );
+        Ok(())
+    }
 }
@@ -20,7 +20,7 @@
 use std::convert::TryFrom;
 
 use anyhow::bail;
-use chrono::{FixedOffset, Utc};
+use chrono::{DateTime, Utc};
 use itertools::{process_results, Itertools};
 use serde::{Deserialize, Serialize};
 use serde_json::{self, Value as JsonValue};
@@ -30,8 +30,10 @@ use tantivy::schema::{
 };
 use thiserror::Error;
 
-use super::{default_as_true, FieldMappingType};
+use super::date_format::parse_date_with_formats;
+use super::{default_as_true, DateFormat, FieldMappingType};
 use crate::default_doc_mapper::validate_field_mapping_name;
+use crate::TimestampResolution;
 
 /// A `FieldMappingEntry` defines how a field is indexed, stored,
 /// and mapped from a JSON document to the related index fields.
@@ -45,13 +47,86 @@ pub struct FieldMappingEntry {
     pub name: String,
     /// Property parameters which defines the type and the way the value must be indexed.
     pub mapping_type: FieldMappingType,
+    /// Formats accepted when parsing the values of a `date` or `i64` field, tried in order.
+    /// Date fields fall back to RFC 3339 and `i64` fields to plain integers when empty.
+    pub input_formats: Vec<DateFormat>,
+    /// Unit of the timestamps held by an `i64` field, in which the dates parsed with the input
+    /// formats are converted.
+    pub resolution: TimestampResolution,
 }
 
 impl FieldMappingEntry {
     /// Creates a new [`FieldMappingEntry`].
     pub fn new(name: String, mapping_type: FieldMappingType) -> Self {
         assert!(validate_field_mapping_name(&name).is_ok());
-        FieldMappingEntry { name, mapping_type }
+        FieldMappingEntry {
+            name,
+            mapping_type,
+            input_formats: Vec::new(),
+            resolution: TimestampResolution::default(),
+        }
+    }
+
+    /// Sets the formats accepted when parsing the values of a `date` or `i64` field.
+    pub fn with_input_formats(mut self, input_formats: Vec<DateFormat>) -> Self {
+        self.input_formats = input_formats;
+        self
+    }
+
+    /// Sets the unit of the timestamps held by an `i64` field.
+    pub fn with_resolution(mut self, resolution: TimestampResolution) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    /// Returns the leaf field mapping whose field path is `field_name`.
+    pub fn find_field_mapping(&self, field_name: &str) -> Option<&FieldMappingEntry> {
+        match &self.mapping_type {
+            FieldMappingType::Object(entries) => {
+                let sub_field_name = if self.name.is_empty() {
+                    field_name
+                } else {
+                    field_name
+                        .strip_prefix(self.name.as_str())?
+                        .strip_prefix('.')?
+                };
+                entries
+                    .iter()
+                    .find_map(|entry| entry.find_field_mapping(sub_field_name))
+            }
+            _ => (self.name == field_name).then(|| self),
+        }
+    }
+
+    /// Parses a bound of a range query on the field the way the values of the field are parsed,
+    /// and returns it in the representation the field stores: a timestamp in the field resolution
+    /// for `i64` fields, and an RFC 3339 date in UTC for `date` fields. Besides the input formats,
+    /// dates are always accepted in RFC 3339. The bounds of the other fields are returned as is.
+    /// Returns `None` if the bound cannot be parsed.
+    pub fn parse_range_bound(&self, bound: &str) -> Option<String> {
+        match &self.mapping_type {
+            FieldMappingType::I64(..) => {
+                // Integers are plain integers unless an epoch input format is configured.
+                if !self.input_formats.iter().any(DateFormat::is_epoch)
+                    && bound.parse::<i64>().is_ok()
+                {
+                    return Some(bound.to_string());
+                }
+                let date_time = self.parse_range_bound_date_time(bound)?;
+                Some(self.resolution.date_time_to_units(&date_time).to_string())
+            }
+            FieldMappingType::Date(..) => {
+                let date_time = self.parse_range_bound_date_time(bound)?;
+                Some(date_time.to_rfc3339())
+            }
+            _ => Some(bound.to_string()),
+        }
+    }
+
+    fn parse_range_bound_date_time(&self, bound: &str) -> Option<DateTime<Utc>> {
+        let bound_json = JsonValue::String(bound.to_string());
+        parse_date_with_formats(&bound_json, &self.input_formats)
+            .or_else(|| DateFormat::Rfc3339.parse_json(&bound_json))
     }
 
     /// Creates a new root [`FieldMappingEntry`].
@@ -59,6 +134,8 @@ impl FieldMappingEntry {
         FieldMappingEntry {
             name: "".to_string(),
             mapping_type,
+            input_formats: Vec::new(),
+            resolution: TimestampResolution::default(),
         }
     }
 
@@ -200,6 +277,17 @@ impl FieldMappingEntry {
                     |iter| iter.flatten().collect(),
                 )?
             }
+            // Numbers are plain integers unless an epoch input format is configured.
+            JsonValue::Number(_) if self.input_formats.iter().any(DateFormat::is_epoch) => {
+                let date_time = self.parse_date_time(&json_value)?;
+                let timestamp = self.resolution.date_time_to_units(&date_time);
+                vec![(FieldPath::new(&self.name), Value::I64(timestamp))]
+            }
+            JsonValue::String(_) if !self.input_formats.is_empty() => {
+                let date_time = self.parse_date_time(&json_value)?;
+                let timestamp = self.resolution.date_time_to_units(&date_time);
+                vec![(FieldPath::new(&self.name), Value::I64(timestamp))]
+            }
             JsonValue::Number(value_as_number) => {
                 if let Some(value_as_i64) = value_as_number.as_i64() {
                     vec![(FieldPath::new(&self.name), Value::I64(value_as_i64))]
@@ -335,32 +423,41 @@ impl FieldMappingEntry {
                     |iter| iter.flatten().collect(),
                 )?
             }
-            JsonValue::String(value_as_str) => {
-                let dt_with_fixed_tz: chrono::DateTime<FixedOffset> =
-                    chrono::DateTime::parse_from_rfc3339(&value_as_str).map_err(|err| {
-                        DocParsingError::ValueError(
-                            self.name.clone(),
-                            format!("Expected RFC 3339 date, got '{}'. {:?}", value_as_str, err),
-                        )
-                    })?;
-                vec![(
-                    FieldPath::new(&self.name),
-                    Value::Date(dt_with_fixed_tz.with_timezone(&Utc)),
-                )]
-            }
             JsonValue::Null => {
                 vec![]
             }
             _ => {
-                return Err(DocParsingError::ValueError(
-                    self.name.clone(),
-                    format!("Expected RFC 3339 date, got '{}'.", json_value),
-                ))
+                let date_time = self.parse_date_time(&json_value)?;
+                vec![(FieldPath::new(&self.name), Value::Date(date_time))]
             }
         };
         Ok(parsed_values)
     }
 
+    /// Parses a date with the first of the entry input formats that accepts it, RFC 3339 being
+    /// the only format accepted when none is configured.
+    fn parse_date_time(&self, json_value: &JsonValue) -> Result<DateTime<Utc>, DocParsingError> {
+        let default_input_formats = [DateFormat::Rfc3339];
+        let input_formats = if self.input_formats.is_empty() {
+            &default_input_formats[..]
+        } else {
+            &self.input_formats[..]
+        };
+        parse_date_with_formats(json_value, input_formats).ok_or_else(|| {
+            DocParsingError::ValueError(
+                self.name.clone(),
+                format!(
+                    "Expected date in format {}, got '{}'.",
+                    input_formats
+                        .iter()
+                        .map(|input_format| format!("`{}`", input_format))
+                        .join(" or "),
+                    json_value
+                ),
+            )
+        })
+    }
+
     fn parse_bytes(
         &self,
         json_value: JsonValue,
@@ -491,6 +588,10 @@ struct FieldMappingEntryForSerialization {
     #[serde(skip_serializing_if = "Option::is_none")]
     record: Option<IndexRecordOption>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    input_formats: Vec<DateFormat>,
+    #[serde(default, skip_serializing_if = "TimestampResolution::is_seconds")]
+    resolution: TimestampResolution,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     field_mappings: Vec<FieldMappingEntryForSerialization>,
 }
 
@@ -512,8 +613,23 @@ impl TryFrom<FieldMappingEntryForSerialization> for FieldMappingEntry {
                 type_str
             ),
         };
+        if !value.input_formats.is_empty() && !matches!(value.field_type_str(), "i64" | "date") {
+            bail!(
+                "Error when parsing field `{}`: `input_formats` is allowed only for `date` and \
+                 `i64` fields.",
+                value.name
+            )
+        }
+        if !value.resolution.is_seconds() && value.field_type_str() != "i64" {
+            bail!(
+                "Error when parsing field `{}`: `resolution` is allowed only for `i64` fields.",
+                value.name
+            )
+        }
         validate_field_mapping_name(&value.name)?;
-        Ok(FieldMappingEntry::new(value.name, field_type))
+        Ok(FieldMappingEntry::new(value.name, field_type)
+            .with_input_formats(value.input_formats)
+            .with_resolution(value.resolution))
     }
 }
 
@@ -526,6 +642,8 @@ impl From<FieldMappingEntry> for FieldMappingEntryForSerialization {
             .map(FieldMappingEntryForSerialization::from)
             .collect();
         let type_with_cardinality = value.mapping_type.type_with_cardinality();
+        let input_formats = value.input_formats;
+        let resolution = value.resolution;
         let mut fast = false;
         let mut indexed = None;
         let mut record = None;
@@ -565,6 +683,8 @@ impl From<FieldMappingEntry> for FieldMappingEntryForSerialization {
             record,
             stored,
             tokenizer,
+            input_formats,
+            resolution,
             field_mappings,
         }
     }
@@ -1378,6 +1498,178 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_date_with_input_formats() -> anyhow::Result<()> {
+        let entry = serde_json::from_str::<FieldMappingEntry>(
+            r#"
+            {
+                "name": "my_field_name",
+                "type": "date",
+                "input_formats": ["rfc3339", "epoch_millis", "%Y-%m-%d %H:%M:%S %z"]
+            }
+            "#,
+        )?;
+        let datetime_utc = Utc.ymd(2021, 12, 19).and_hms(17, 39, 57);
+        for json_value in [
+            json!("2021-12-19T16:39:57-01:00"),
+            json!(1639935597000i64),
+            json!("2021-12-19 18:39:57 +0100"),
+        ] {
+            let parsed_value = entry.parse(json_value)?;
+            assert_eq!(parsed_value.len(), 1);
+            assert_eq!(parsed_value[0].1, Value::Date(datetime_utc));
+        }
+        let parsed_error = entry.parse(json!("19/12/2021"));
+        assert!(matches!(
+            parsed_error,
+            Err(DocParsingError::ValueError(_, _))
+        ));
+        let entry_json = serde_json::to_value(&entry)?;
+        assert_eq!(
+            entry_json["input_formats"],
+            json!(["rfc3339", "epoch_millis", "%Y-%m-%d %H:%M:%S %z"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_i64_with_input_formats() -> anyhow::Result<()> {
+        let entry = serde_json::from_str::<FieldMappingEntry>(
+            r#"
+            {
+                "name": "timestamp",
+                "type": "i64",
+                "fast": true,
+                "input_formats": ["epoch_nanos", "%Y-%m-%dT%H:%M:%S%.f%z"]
+            }
+            "#,
+        )?;
+        let parsed_value = entry.parse(json!(1639935597123456789i64))?;
+        assert_eq!(parsed_value[0].1, Value::I64(1639935597));
+        let parsed_value = entry.parse(json!("2021-12-19T16:39:57.123-0100"))?;
+        assert_eq!(parsed_value[0].1, Value::I64(1639935597));
+
+        let entry = serde_json::from_str::<FieldMappingEntry>(
+            r#"
+            {
+                "name": "timestamp",
+                "type": "i64",
+                "input_formats": ["rfc3339"]
+            }
+            "#,
+        )?;
+        let parsed_value = entry.parse(json!(1639935597))?;
+        assert_eq!(parsed_value[0].1, Value::I64(1639935597));
+        let parsed_value = entry.parse(json!("2021-12-19T17:39:57Z"))?;
+        assert_eq!(parsed_value[0].1, Value::I64(1639935597));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_range_bound() -> anyhow::Result<()> {
+        let entry = serde_json::from_str::<FieldMappingEntry>(
+            r#"
+            {
+                "name": "timestamp",
+                "type": "i64",
+                "input_formats": ["epoch_secs", "%Y-%m-%d %H:%M:%S"],
+                "resolution": "milliseconds"
+            }
+            "#,
+        )?;
+        assert_eq!(
+            entry.parse_range_bound("1639935597").as_deref(),
+            Some("1639935597000")
+        );
+        assert_eq!(
+            entry.parse_range_bound("2021-12-19 17:39:57").as_deref(),
+            Some("1639935597000")
+        );
+        assert_eq!(
+            entry.parse_range_bound("2021-12-19T17:39:57.5Z").as_deref(),
+            Some("1639935597500")
+        );
+        assert_eq!(entry.parse_range_bound("19/12/2021"), None);
+
+        let entry = serde_json::from_str::<FieldMappingEntry>(
+            r#"
+            {
+                "name": "timestamp",
+                "type": "i64"
+            }
+            "#,
+        )?;
+        assert_eq!(entry.parse_range_bound("42").as_deref(), Some("42"));
+        assert_eq!(
+            entry.parse_range_bound("2021-12-19T17:39:57Z").as_deref(),
+            Some("1639935597")
+        );
+        assert_eq!(entry.parse_range_bound("forty-two"), None);
+
+        let entry = serde_json::from_str::<FieldMappingEntry>(
+            r#"
+            {
+                "name": "my_field_name",
+                "type": "date",
+                "input_formats": ["%Y-%m-%d %H:%M:%S %z"]
+            }
+            "#,
+        )?;
+        assert_eq!(
+            entry
+                .parse_range_bound("2021-12-19 18:39:57 +0100")
+                .as_deref(),
+            Some("2021-12-19T17:39:57+00:00")
+        );
+        assert_eq!(
+            entry
+                .parse_range_bound("2021-12-19T16:39:57-01:00")
+                .as_deref(),
+            Some("2021-12-19T17:39:57+00:00")
+        );
+
+        let entry = serde_json::from_str::<FieldMappingEntry>(
+            r#"
+            {
+                "name": "my_field_name",
+                "type": "text"
+            }
+            "#,
+        )?;
+        assert_eq!(entry.parse_range_bound("abc").as_deref(), Some("abc"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_invalid_input_formats() {
+        let error = serde_json::from_str::<FieldMappingEntry>(
+            r#"
+            {
+                "name": "my_field_name",
+                "type": "text",
+                "input_formats": ["rfc3339"]
+            }
+            "#,
+        )
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("`input_formats` is allowed only for `date` and `i64` fields"));
+        let error = serde_json::from_str::<FieldMappingEntry>(
+            r#"
+            {
+                "name": "my_field_name",
+                "type": "date",
+                "input_formats": ["yyyy-MM-dd"]
+            }
+            "#,
+        )
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Unknown date format `yyyy-MM-dd`"));
+    }
+
     #[test]
     fn test_parse_bytes() -> anyhow::Result<()> {
         let entry = serde_json::from_str::<FieldMappingEntry>(
@@ -33,7 +33,7 @@ pub enum FieldMappingType {
     U64(IntOptions, Cardinality),
     /// 64-bit float mapping type configuration.
     F64(IntOptions, Cardinality),
-    /// RFC 3339 date mapping type configuration.
+    /// Date mapping type configuration.
     Date(IntOptions, Cardinality),
     /// Bytes mapping type configuration.
     Bytes(BytesOptions, Cardinality),
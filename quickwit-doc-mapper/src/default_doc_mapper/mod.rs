@@ -17,6 +17,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+mod date_format;
 mod default_mapper;
 mod field_mapping_entry;
 mod field_mapping_type;
@@ -25,7 +26,10 @@ use anyhow::bail;
 use once_cell::sync::Lazy;
 use regex::Regex;
 
-pub use self::default_mapper::{DefaultDocMapper, DefaultDocMapperBuilder, SortByConfig};
+pub use self::date_format::DateFormat;
+pub use self::default_mapper::{
+    resolve_versioning_fields, DefaultDocMapper, DefaultDocMapperBuilder, SortByConfig,
+};
 pub use self::field_mapping_entry::{DocParsingError, FieldMappingEntry};
 pub use self::field_mapping_type::FieldMappingType;
 
@@ -26,7 +26,7 @@ use tantivy::query::Query;
 use tantivy::schema::{Field, Schema};
 use tantivy::Document;
 
-use crate::{DocParsingError, QueryParserError, SortBy};
+use crate::{DocParsingError, QueryParserError, SortBy, TimestampResolution};
 
 /// The `DocMapper` trait defines the way of defining how a (json) document,
 /// and the fields it contains, are stored and indexed.
@@ -57,6 +57,20 @@ pub trait DocMapper: Send + Sync + Debug + DynClone + 'static {
         request: &SearchRequest,
     ) -> Result<Box<dyn Query>, QueryParserError>;
 
+    /// Returns the query of a split, along with the fields of the query missing from the split.
+    ///
+    /// A split built before a field was added to the doc mapper does not have this field. Instead
+    /// of failing, the clauses on the fields missing from `split_schema` are rewritten to match
+    /// no document of the split.
+    fn split_query(
+        &self,
+        split_schema: Schema,
+        request: &SearchRequest,
+    ) -> Result<(Box<dyn Query>, Vec<String>), QueryParserError> {
+        let query = self.query(split_schema, request)?;
+        Ok((query, Vec::new()))
+    }
+
     /// Returns the default sort
     fn sort_by(&self) -> SortBy {
         SortBy::DocId
@@ -75,6 +89,11 @@ pub trait DocMapper: Send + Sync + Debug + DynClone + 'static {
         None
     }
 
+    /// Returns the unit of the timestamps held by the timestamp field.
+    fn timestamp_resolution(&self) -> TimestampResolution {
+        TimestampResolution::Seconds
+    }
+
     /// Returns the tag field names
     fn tag_field_names(&self) -> BTreeSet<String> {
         Default::default()
@@ -84,6 +103,12 @@ pub trait DocMapper: Send + Sync + Debug + DynClone + 'static {
     fn demux_field_name(&self) -> Option<String> {
         None
     }
+
+    /// Returns the name of the field stamped with the time at which each document is indexed,
+    /// expressed in [`Self::timestamp_resolution`] units.
+    fn ingest_timestamp_field_name(&self) -> Option<String> {
+        None
+    }
 }
 
 clone_trait_object!(DocMapper);
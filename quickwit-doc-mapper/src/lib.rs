@@ -29,16 +29,22 @@ mod doc_mapper;
 mod error;
 mod query_builder;
 mod sort_by;
+mod timestamp_resolution;
 
+/// Pruning ranges manipulation.
+pub mod range_pruning;
 /// Pruning tags manipulation.
 pub mod tag_pruning;
 
 pub use default_doc_mapper::{
-    DefaultDocMapper, DefaultDocMapperBuilder, DocParsingError, FieldMappingEntry, SortByConfig,
+    resolve_versioning_fields, validate_field_mapping_name, DateFormat, DefaultDocMapper,
+    DefaultDocMapperBuilder, DocParsingError, FieldMappingEntry, SortByConfig,
 };
 pub use doc_mapper::DocMapper;
 pub use error::QueryParserError;
+pub use query_builder::{default_search_field_warnings, validate_query_syntax};
 pub use sort_by::{SortBy, SortByField, SortOrder};
+pub use timestamp_resolution::TimestampResolution;
 
 /// Field name reserved for storing the source document.
 pub const SOURCE_FIELD_NAME: &str = "_source";
@@ -17,14 +17,74 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::BTreeSet;
+
 use quickwit_proto::SearchRequest;
-use tantivy::query::{Query, QueryParser, QueryParserError as TantivyQueryParserError};
-use tantivy::schema::{Field, Schema};
+use tantivy::query::{EmptyQuery, Query, QueryParser, QueryParserError as TantivyQueryParserError};
+use tantivy::schema::{Field, FieldType, Schema};
 use tantivy::tokenizer::TokenizerManager;
-use tantivy_query_grammar::{UserInputAst, UserInputLeaf};
+use tantivy_query_grammar::{Occur, UserInputAst, UserInputLeaf};
 
 use crate::QueryParserError;
 
+/// Returns an error if `query` is not valid in the query language, whatever fields it targets.
+pub fn validate_query_syntax(query: &str) -> Result<(), QueryParserError> {
+    tantivy_query_grammar::parse_query(query).map_err(|_| TantivyQueryParserError::SyntaxError)?;
+    Ok(())
+}
+
+/// Returns warnings about the default search fields of `schema` on which some queries without
+/// an explicit field fail at query time.
+pub fn default_search_field_warnings(
+    schema: &Schema,
+    default_search_field_names: &[String],
+) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for field_name in default_search_field_names {
+        let field = match schema.get_field(field_name) {
+            Some(field) => field,
+            None => continue,
+        };
+        let field_type_name = match schema.get_field_entry(field).field_type() {
+            FieldType::Str(text_options) => {
+                match text_options.get_indexing_options() {
+                    None => warnings.push(format!(
+                        "Default search field `{}` is not indexed: queries without an explicit \
+                         field fail. Set `indexed: true` on the field or remove it from \
+                         `default_search_fields`.",
+                        field_name
+                    )),
+                    Some(indexing_options)
+                        if indexing_options.tokenizer() != "raw"
+                            && !indexing_options.index_option().has_positions() =>
+                    {
+                        warnings.push(format!(
+                            "Default search field `{}` does not record positions: phrase queries \
+                             such as `\"connection refused\"` fail. Set `record: position` on the \
+                             field to support them.",
+                            field_name
+                        ))
+                    }
+                    Some(_) => {}
+                }
+                continue;
+            }
+            FieldType::U64(_) => "u64",
+            FieldType::I64(_) => "i64",
+            FieldType::F64(_) => "f64",
+            FieldType::Date(_) => "date",
+            _ => continue,
+        };
+        warnings.push(format!(
+            "Default search field `{}` is of type `{}`: queries without an explicit field fail on \
+             terms that are not valid `{}` values. Remove it from `default_search_fields` and \
+             target it explicitly, e.g. `{}:42`.",
+            field_name, field_type_name, field_type_name, field_name
+        ));
+    }
+    warnings
+}
+
 /// Build a `Query` with field resolution & forbidding range clauses.
 pub(crate) fn build_query(
     schema: Schema,
@@ -34,34 +94,199 @@ pub(crate) fn build_query(
     let user_input_ast = tantivy_query_grammar::parse_query(&request.query)
         .map_err(|_| TantivyQueryParserError::SyntaxError)?;
 
-    if has_range_clause(user_input_ast) {
+    if has_range_clause(&user_input_ast) {
+        return Err(anyhow::anyhow!("Range queries are not currently allowed.").into());
+    }
+
+    let search_field_names = if request.search_fields.is_empty() {
+        default_field_names
+    } else {
+        &request.search_fields
+    };
+    parse_query(schema, search_field_names, &request.query)
+}
+
+/// Build the `Query` of a split whose schema, `split_schema`, is an older version of `schema`.
+///
+/// The clauses on the fields of `schema` missing from the split match no document of the split,
+/// as if the split had the fields: a required clause makes its group match no document, while
+/// optional and excluded clauses are dropped. The missing fields referenced by the query are
+/// returned along with the query.
+pub(crate) fn build_split_query(
+    schema: &Schema,
+    split_schema: Schema,
+    request: &SearchRequest,
+    default_field_names: &[String],
+) -> Result<(Box<dyn Query>, Vec<String>), QueryParserError> {
+    let user_input_ast = tantivy_query_grammar::parse_query(&request.query)
+        .map_err(|_| TantivyQueryParserError::SyntaxError)?;
+
+    if has_range_clause(&user_input_ast) {
         return Err(anyhow::anyhow!("Range queries are not currently allowed.").into());
     }
 
-    let search_fields = if request.search_fields.is_empty() {
-        resolve_fields(&schema, default_field_names)?
+    let search_field_names = if request.search_fields.is_empty() {
+        default_field_names
     } else {
-        resolve_fields(&schema, &request.search_fields)?
+        &request.search_fields
+    };
+    let mut rewriter = MissingFieldRewriter {
+        schema,
+        split_schema: &split_schema,
+        split_search_field_names: Vec::new(),
+        missing_search_field_names: Vec::new(),
+        missing_fields: BTreeSet::new(),
+    };
+    for field_name in search_field_names {
+        if rewriter.is_missing_field(field_name) {
+            rewriter.missing_search_field_names.push(field_name.clone());
+        } else {
+            rewriter.split_search_field_names.push(field_name.clone());
+        }
+    }
+    let rewritten_ast_opt = rewriter.rewrite(user_input_ast);
+    let split_search_field_names = rewriter.split_search_field_names;
+    let missing_fields: Vec<String> = rewriter.missing_fields.into_iter().collect();
+
+    if missing_fields.is_empty() {
+        let query = parse_query(split_schema, &split_search_field_names, &request.query)?;
+        return Ok((query, missing_fields));
+    }
+    let rewritten_ast = match rewritten_ast_opt {
+        Some(rewritten_ast) => rewritten_ast,
+        None => return Ok((Box::new(EmptyQuery), missing_fields)),
     };
+    let rewritten_query = format_user_input_ast(&rewritten_ast).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Failed to rewrite the query `{}` for a split missing the fields {:?}.",
+            request.query,
+            missing_fields
+        )
+    })?;
+    let query = parse_query(split_schema, &split_search_field_names, &rewritten_query)?;
+    Ok((query, missing_fields))
+}
 
+fn parse_query(
+    schema: Schema,
+    search_field_names: &[String],
+    query: &str,
+) -> Result<Box<dyn Query>, QueryParserError> {
+    let search_fields = resolve_fields(&schema, search_field_names)?;
     let mut query_parser = QueryParser::new(schema, search_fields, TokenizerManager::default());
     query_parser.set_conjunction_by_default();
-    let query = query_parser.parse_query(&request.query)?;
+    let query = query_parser.parse_query(query)?;
     Ok(query)
 }
 
-fn has_range_clause(user_input_ast: UserInputAst) -> bool {
+fn has_range_clause(user_input_ast: &UserInputAst) -> bool {
     match user_input_ast {
-        UserInputAst::Clause(sub_queries) => {
-            for (_, sub_ast) in sub_queries {
-                if has_range_clause(sub_ast) {
-                    return true;
+        UserInputAst::Clause(sub_queries) => sub_queries
+            .iter()
+            .any(|(_, sub_ast)| has_range_clause(sub_ast)),
+        UserInputAst::Boost(ast, _) => has_range_clause(ast),
+        UserInputAst::Leaf(leaf) => matches!(**leaf, UserInputLeaf::Range { .. }),
+    }
+}
+
+/// Removes the clauses on the fields missing from a split from a query.
+struct MissingFieldRewriter<'a> {
+    schema: &'a Schema,
+    split_schema: &'a Schema,
+    split_search_field_names: Vec<String>,
+    missing_search_field_names: Vec<String>,
+    missing_fields: BTreeSet<String>,
+}
+
+impl<'a> MissingFieldRewriter<'a> {
+    /// Returns whether the field exists in the schema but not in the split. Unknown fields are
+    /// left to the query parser, which reports them.
+    fn is_missing_field(&self, field_name: &str) -> bool {
+        self.split_schema.get_field(field_name).is_none()
+            && self.schema.get_field(field_name).is_some()
+    }
+
+    /// Returns the rewritten `ast`, or `None` if it matches no document of the split.
+    fn rewrite(&mut self, ast: UserInputAst) -> Option<UserInputAst> {
+        match ast {
+            UserInputAst::Clause(sub_asts) => {
+                let mut rewritten_sub_asts = Vec::with_capacity(sub_asts.len());
+                for (occur_opt, sub_ast) in sub_asts {
+                    match (occur_opt, self.rewrite(sub_ast)) {
+                        (_, Some(rewritten_sub_ast)) => {
+                            rewritten_sub_asts.push((occur_opt, rewritten_sub_ast))
+                        }
+                        // Clauses without occur are required, as the query parser uses
+                        // conjunctions by default.
+                        (None, None) | (Some(Occur::Must), None) => return None,
+                        (Some(Occur::Should), None) | (Some(Occur::MustNot), None) => {}
+                    }
+                }
+                if rewritten_sub_asts.is_empty() {
+                    return None;
                 }
+                Some(UserInputAst::Clause(rewritten_sub_asts))
+            }
+            UserInputAst::Boost(sub_ast, boost) => self
+                .rewrite(*sub_ast)
+                .map(|rewritten_sub_ast| UserInputAst::Boost(Box::new(rewritten_sub_ast), boost)),
+            UserInputAst::Leaf(leaf) => {
+                let field_name_opt = match leaf.as_ref() {
+                    UserInputLeaf::Literal(literal) => literal.field_name.clone(),
+                    UserInputLeaf::Range { field, .. } => field.clone(),
+                    UserInputLeaf::All => return Some(UserInputAst::Leaf(leaf)),
+                };
+                match field_name_opt {
+                    Some(field_name) => {
+                        if self.is_missing_field(&field_name) {
+                            self.missing_fields.insert(field_name);
+                            return None;
+                        }
+                    }
+                    None => {
+                        // Unfielded terms are searched in the search fields present in the
+                        // split.
+                        self.missing_fields
+                            .extend(self.missing_search_field_names.iter().cloned());
+                        if !self.missing_search_field_names.is_empty()
+                            && self.split_search_field_names.is_empty()
+                        {
+                            return None;
+                        }
+                    }
+                }
+                Some(UserInputAst::Leaf(leaf))
+            }
+        }
+    }
+}
+
+/// Formats a query AST back into a query string, or returns `None` if the query grammar cannot
+/// express it.
+fn format_user_input_ast(ast: &UserInputAst) -> Option<String> {
+    match ast {
+        UserInputAst::Clause(sub_asts) => {
+            // The grammar only expresses optional clauses with `OR`, between optional clauses.
+            let is_disjunction = sub_asts
+                .iter()
+                .all(|(occur_opt, _)| matches!(occur_opt, Some(Occur::Should)));
+            let mut sub_query_strs = Vec::with_capacity(sub_asts.len());
+            for (occur_opt, sub_ast) in sub_asts {
+                let prefix = match occur_opt {
+                    Some(Occur::Should) if !is_disjunction => return None,
+                    Some(Occur::Should) | None => "",
+                    Some(Occur::Must) => "+",
+                    Some(Occur::MustNot) => "-",
+                };
+                sub_query_strs.push(format!("{}{}", prefix, format_user_input_ast(sub_ast)?));
             }
-            false
+            let separator = if is_disjunction { " OR " } else { " " };
+            Some(format!("({})", sub_query_strs.join(separator)))
+        }
+        UserInputAst::Boost(sub_ast, boost) => {
+            Some(format!("{}^{}", format_user_input_ast(sub_ast)?, boost))
         }
-        UserInputAst::Boost(ast, _) => has_range_clause(*ast),
-        UserInputAst::Leaf(leaf) => matches!(*leaf, UserInputLeaf::Range { .. }),
+        UserInputAst::Leaf(leaf) => Some(format!("{:?}", leaf)),
     }
 }
 
@@ -79,9 +304,11 @@ fn resolve_fields(schema: &Schema, field_names: &[String]) -> anyhow::Result<Vec
 #[cfg(test)]
 mod test {
     use quickwit_proto::SearchRequest;
-    use tantivy::schema::{Schema, TEXT};
+    use tantivy::schema::{
+        IndexRecordOption, Schema, TextFieldIndexing, TextOptions, STORED, STRING, TEXT,
+    };
 
-    use super::build_query;
+    use super::{build_query, build_split_query, default_search_field_warnings};
 
     enum TestExpectation {
         Err(&'static str),
@@ -98,12 +325,8 @@ mod test {
         schema_builder.build()
     }
 
-    fn check_build_query(
-        query_str: &str,
-        search_fields: Vec<String>,
-        expected: TestExpectation,
-    ) -> anyhow::Result<()> {
-        let request = SearchRequest {
+    fn make_request(query_str: &str, search_fields: Vec<String>) -> SearchRequest {
+        SearchRequest {
             index_id: "test_index".to_string(),
             query: query_str.to_string(),
             search_fields,
@@ -113,8 +336,22 @@ mod test {
             start_offset: 0,
             sort_order: None,
             sort_by_field: None,
-        };
+            terms_lookup: None,
+            preferred_searcher_nodes: Vec::new(),
+            profile: false,
+            pin_splits: false,
+            snapshot: None,
+            start_time_expression: None,
+            end_time_expression: None,
+        }
+    }
 
+    fn check_build_query(
+        query_str: &str,
+        search_fields: Vec<String>,
+        expected: TestExpectation,
+    ) -> anyhow::Result<()> {
+        let request = make_request(query_str, search_fields);
         let default_field_names = vec!["title".to_string(), "desc".to_string()];
 
         let query_result = build_query(make_schema(), &request, &default_field_names);
@@ -185,4 +422,118 @@ mod test {
 
         Ok(())
     }
+    /// Schema of a split built before the `desc` and `server.mem` fields were added.
+    fn make_split_schema() -> Schema {
+        let mut schema_builder = Schema::builder();
+        schema_builder.add_text_field("title", TEXT);
+        schema_builder.add_text_field("server.name", TEXT);
+        schema_builder.add_text_field("_source", TEXT);
+        schema_builder.build()
+    }
+
+    fn check_build_split_query(
+        query_str: &str,
+        search_fields: Vec<String>,
+        expected_missing_fields: &[&str],
+        expected_terms: &[&str],
+        unexpected_terms: &[&str],
+    ) -> anyhow::Result<()> {
+        let request = make_request(query_str, search_fields);
+        let default_field_names = vec!["title".to_string(), "desc".to_string()];
+        let (query, missing_fields) = build_split_query(
+            &make_schema(),
+            make_split_schema(),
+            &request,
+            &default_field_names,
+        )?;
+        assert_eq!(missing_fields, expected_missing_fields);
+        let query_debug = format!("{:?}", query);
+        if expected_terms.is_empty() {
+            assert_eq!(query_debug, "EmptyQuery");
+        }
+        for expected_term in expected_terms {
+            assert!(query_debug.contains(expected_term), "{}", query_debug);
+        }
+        for unexpected_term in unexpected_terms {
+            assert!(!query_debug.contains(unexpected_term), "{}", query_debug);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_split_query() -> anyhow::Result<()> {
+        check_build_split_query("title:foo", vec![], &[], &["foo"], &[])?;
+        check_build_split_query("title:foo desc:bar", vec![], &["desc"], &[], &[])?;
+        check_build_split_query(
+            "title:foo OR desc:bar",
+            vec![],
+            &["desc"],
+            &["foo"],
+            &["bar"],
+        )?;
+        check_build_split_query("title:foo -desc:bar", vec![], &["desc"], &["foo"], &["bar"])?;
+        check_build_split_query(
+            "title:foo (desc:bar OR server.mem:baz)",
+            vec![],
+            &["desc", "server.mem"],
+            &[],
+            &[],
+        )?;
+        check_build_split_query(
+            "title:foo OR (desc:bar server.name:baz)",
+            vec![],
+            &["desc"],
+            &["foo"],
+            &["bar", "baz"],
+        )?;
+        check_build_split_query("foo", vec![], &["desc"], &["foo"], &[])?;
+        check_build_split_query("foo", vec!["desc".to_string()], &["desc"], &[], &[])?;
+        check_build_split_query("*", vec!["desc".to_string()], &[], &["AllQuery"], &[])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_split_query_unknown_field() {
+        let request = make_request("url:foo", vec![]);
+        let default_field_names = vec!["title".to_string()];
+        let query_result = build_split_query(
+            &make_schema(),
+            make_split_schema(),
+            &request,
+            &default_field_names,
+        );
+        assert!(format!("{:?}", query_result).contains("Field does not exists: '\"url\"'"));
+    }
+
+    #[test]
+    fn test_default_search_field_warnings() {
+        let mut schema_builder = Schema::builder();
+        schema_builder.add_text_field("severity", STRING);
+        schema_builder.add_text_field(
+            "body",
+            TextOptions::default().set_indexing_options(
+                TextFieldIndexing::default().set_index_option(IndexRecordOption::WithFreqs),
+            ),
+        );
+        schema_builder.add_text_field("title", TEXT);
+        schema_builder.add_text_field("payload", STORED);
+        schema_builder.add_u64_field("status_code", STORED);
+        let schema = schema_builder.build();
+        let default_search_field_names: Vec<String> = [
+            "severity",
+            "body",
+            "title",
+            "payload",
+            "status_code",
+            "unknown",
+        ]
+        .iter()
+        .map(|field_name| field_name.to_string())
+        .collect();
+        let warnings = default_search_field_warnings(&schema, &default_search_field_names);
+        assert_eq!(warnings.len(), 3);
+        assert!(warnings[0].contains("`body` does not record positions"));
+        assert!(warnings[1].contains("`payload` is not indexed"));
+        assert!(warnings[2].contains("`status_code` is of type `u64`"));
+    }
 }
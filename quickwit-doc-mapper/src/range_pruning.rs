@@ -0,0 +1,485 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use tantivy::query::QueryParserError as TantivyQueryParserError;
+use tantivy_query_grammar::{Occur, UserInputAst, UserInputBound, UserInputLeaf, UserInputLiteral};
+
+use crate::QueryParserError;
+
+/// Statistics of a numeric or date fast field over the documents of a split.
+///
+/// They are recorded in the split metadata when the split is packaged, and make it possible to
+/// prune the splits that cannot match a range query on the field, à la Parquet row-group
+/// statistics.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FieldStats {
+    /// Min and max values of the field.
+    #[serde(flatten)]
+    pub bounds: FieldBounds,
+    /// Number of documents without any value for the field.
+    pub num_nulls: u64,
+}
+
+// NaN values are never recorded in the statistics.
+impl Eq for FieldStats {}
+
+/// Min and max values of a field, typed after the field.
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FieldBounds {
+    I64 {
+        min: i64,
+        max: i64,
+    },
+    U64 {
+        min: u64,
+        max: u64,
+    },
+    F64 {
+        min: f64,
+        max: f64,
+    },
+    /// Timestamps in seconds.
+    Date {
+        min: i64,
+        max: i64,
+    },
+}
+
+fn parse_date_bound(value: &str) -> Option<i64> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|date_time| date_time.timestamp())
+}
+
+fn parse_value<T: FromStr>(value: &str) -> Option<T> {
+    value.parse().ok()
+}
+
+/// Bound of a range, as written in the query or parsed after the type of a field.
+#[allow(missing_docs)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Bound<T> {
+    Inclusive(T),
+    Exclusive(T),
+    Unbounded,
+}
+
+impl Bound<String> {
+    fn from_user_input(bound: UserInputBound) -> Self {
+        match bound {
+            UserInputBound::Inclusive(value) => Bound::Inclusive(value),
+            UserInputBound::Exclusive(value) => Bound::Exclusive(value),
+            UserInputBound::Unbounded => Bound::Unbounded,
+        }
+    }
+
+    fn parse<T>(&self, parse: impl Fn(&str) -> Option<T>) -> Option<Bound<T>> {
+        match self {
+            Bound::Inclusive(value) => parse(value).map(Bound::Inclusive),
+            Bound::Exclusive(value) => parse(value).map(Bound::Exclusive),
+            Bound::Unbounded => Some(Bound::Unbounded),
+        }
+    }
+}
+
+impl<T: PartialOrd> Bound<T> {
+    fn is_below(&self, value: &T) -> bool {
+        match self {
+            Bound::Inclusive(bound) => bound <= value,
+            Bound::Exclusive(bound) => bound < value,
+            Bound::Unbounded => true,
+        }
+    }
+
+    fn is_above(&self, value: &T) -> bool {
+        match self {
+            Bound::Inclusive(bound) => value <= bound,
+            Bound::Exclusive(bound) => value < bound,
+            Bound::Unbounded => true,
+        }
+    }
+}
+
+/// Returns false if we are guaranteed that no document of a split whose values are in
+/// `[min, max]` matches the range, or its negation when `is_negated` is set.
+fn may_match<T: PartialOrd>(
+    min: T,
+    max: T,
+    num_nulls: u64,
+    lower: &Bound<String>,
+    upper: &Bound<String>,
+    is_negated: bool,
+    parse: impl Fn(&str) -> Option<T>,
+) -> bool {
+    let (lower, upper) = match (lower.parse(&parse), upper.parse(&parse)) {
+        (Some(lower), Some(upper)) => (lower, upper),
+        _ => return true,
+    };
+    if is_negated {
+        // A document without value, or whose values are all out of the range, matches.
+        num_nulls > 0 || !lower.is_below(&min) || !upper.is_above(&max)
+    } else {
+        lower.is_below(&max) && upper.is_above(&min)
+    }
+}
+
+/// Represents a predicate over the field statistics of a split.
+#[derive(Debug, PartialEq, Clone)]
+pub enum RangeFilterAst {
+    /// All the children must hold.
+    And(Vec<RangeFilterAst>),
+    /// At least one of the children must hold.
+    Or(Vec<RangeFilterAst>),
+    /// Some value of the field is within the bounds, or none is when `is_negated` is set.
+    Range {
+        /// Name of the field.
+        field: String,
+        /// Lower bound of the range.
+        lower: Bound<String>,
+        /// Upper bound of the range.
+        upper: Bound<String>,
+        /// If set, the predicate tests that no value of the field is within the bounds.
+        is_negated: bool,
+    },
+    /// Represents a node which could be true or false regardless of the field statistics.
+    Uninformative,
+}
+
+impl Display for RangeFilterAst {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (separator, children) = match self {
+            RangeFilterAst::And(children) => (" ∧ ", children),
+            RangeFilterAst::Or(children) => (" ∨ ", children),
+            RangeFilterAst::Range {
+                field,
+                lower,
+                upper,
+                is_negated,
+            } => {
+                if *is_negated {
+                    write!(f, "¬")?;
+                }
+                write!(f, "{}:", field)?;
+                match lower {
+                    Bound::Inclusive(value) => write!(f, "[{}", value)?,
+                    Bound::Exclusive(value) => write!(f, "{{{}", value)?,
+                    Bound::Unbounded => write!(f, "{{*")?,
+                }
+                return match upper {
+                    Bound::Inclusive(value) => write!(f, " TO {}]", value),
+                    Bound::Exclusive(value) => write!(f, " TO {}}}", value),
+                    Bound::Unbounded => write!(f, " TO *}}"),
+                };
+            }
+            RangeFilterAst::Uninformative => return write!(f, "⊤"),
+        };
+        write!(f, "(")?;
+        for (child_ord, child) in children.iter().enumerate() {
+            if child_ord > 0 {
+                write!(f, "{}", separator)?;
+            }
+            write!(f, "{}", child)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl RangeFilterAst {
+    /// Evaluates the predicate over the field statistics of a split.
+    ///
+    /// If it evaluates to false, we are guaranteed that no document in the split matches the
+    /// query. Fields without statistics and bounds that cannot be parsed after the type of the
+    /// field evaluate to true.
+    pub fn evaluate(&self, field_stats: &BTreeMap<String, FieldStats>) -> bool {
+        match self {
+            RangeFilterAst::And(children) => {
+                children.iter().all(|child| child.evaluate(field_stats))
+            }
+            RangeFilterAst::Or(children) => {
+                children.iter().any(|child| child.evaluate(field_stats))
+            }
+            RangeFilterAst::Range {
+                field,
+                lower,
+                upper,
+                is_negated,
+            } => {
+                let stats = match field_stats.get(field) {
+                    Some(stats) => stats,
+                    None => return true,
+                };
+                let num_nulls = stats.num_nulls;
+                match stats.bounds {
+                    FieldBounds::I64 { min, max } => {
+                        may_match(min, max, num_nulls, lower, upper, *is_negated, parse_value)
+                    }
+                    FieldBounds::U64 { min, max } => {
+                        may_match(min, max, num_nulls, lower, upper, *is_negated, parse_value)
+                    }
+                    FieldBounds::F64 { min, max } => {
+                        may_match(min, max, num_nulls, lower, upper, *is_negated, parse_value)
+                    }
+                    FieldBounds::Date { min, max } => may_match(
+                        min,
+                        max,
+                        num_nulls,
+                        lower,
+                        upper,
+                        *is_negated,
+                        parse_date_bound,
+                    ),
+                }
+            }
+            RangeFilterAst::Uninformative => true,
+        }
+    }
+
+    fn negate(self) -> RangeFilterAst {
+        match self {
+            RangeFilterAst::And(children) => {
+                RangeFilterAst::Or(children.into_iter().map(Self::negate).collect())
+            }
+            RangeFilterAst::Or(children) => {
+                RangeFilterAst::And(children.into_iter().map(Self::negate).collect())
+            }
+            RangeFilterAst::Range {
+                field,
+                lower,
+                upper,
+                is_negated,
+            } => RangeFilterAst::Range {
+                field,
+                lower,
+                upper,
+                is_negated: !is_negated,
+            },
+            RangeFilterAst::Uninformative => RangeFilterAst::Uninformative,
+        }
+    }
+
+    /// Removes the uninformative nodes. Returns `None` if the whole predicate is uninformative.
+    fn simplify(self) -> Option<RangeFilterAst> {
+        match self {
+            RangeFilterAst::And(children) => {
+                let mut children: Vec<RangeFilterAst> =
+                    children.into_iter().filter_map(Self::simplify).collect();
+                match children.len() {
+                    0 => None,
+                    1 => children.pop(),
+                    _ => Some(RangeFilterAst::And(children)),
+                }
+            }
+            RangeFilterAst::Or(children) => {
+                let mut simplified_children = Vec::with_capacity(children.len());
+                for child in children {
+                    simplified_children.push(child.simplify()?);
+                }
+                match simplified_children.len() {
+                    0 => None,
+                    1 => simplified_children.pop(),
+                    _ => Some(RangeFilterAst::Or(simplified_children)),
+                }
+            }
+            RangeFilterAst::Range { .. } => Some(self),
+            RangeFilterAst::Uninformative => None,
+        }
+    }
+}
+
+/// Converts a user query into a predicate over the field statistics of a split, following the
+/// same rules as [`crate::tag_pruning::extract_tags_from_query`].
+///
+/// Range queries and terms, seen as single value ranges, on fields with statistics are the only
+/// informative leaves. Returns `None` if the query cannot prune any split.
+pub fn extract_ranges_from_query(
+    user_query: &str,
+) -> Result<Option<RangeFilterAst>, QueryParserError> {
+    let user_input_ast = tantivy_query_grammar::parse_query(user_query)
+        .map_err(|_| TantivyQueryParserError::SyntaxError)?;
+    Ok(collect_range_filters(user_input_ast).simplify())
+}
+
+fn collect_range_filters(user_input_ast: UserInputAst) -> RangeFilterAst {
+    match user_input_ast {
+        UserInputAst::Clause(sub_queries) => {
+            let clause: Vec<(Occur, RangeFilterAst)> = sub_queries
+                .into_iter()
+                .map(|(occur_opt, ast)| {
+                    (
+                        occur_opt.unwrap_or(Occur::Should),
+                        collect_range_filters(ast),
+                    )
+                })
+                .collect();
+            if clause.is_empty() {
+                return RangeFilterAst::Uninformative;
+            }
+            if clause.iter().any(|(occur, _)| occur == &Occur::Must) {
+                let children = clause
+                    .into_iter()
+                    .filter_map(|(occur, ast)| match occur {
+                        Occur::Must => Some(ast),
+                        Occur::MustNot => Some(ast.negate()),
+                        Occur::Should => None,
+                    })
+                    .collect();
+                return RangeFilterAst::And(children);
+            }
+            let children = clause
+                .into_iter()
+                .map(|(occur, ast)| match occur {
+                    Occur::MustNot => ast.negate(),
+                    _ => ast,
+                })
+                .collect();
+            RangeFilterAst::Or(children)
+        }
+        UserInputAst::Boost(ast, _) => collect_range_filters(*ast),
+        UserInputAst::Leaf(leaf) => match *leaf {
+            UserInputLeaf::Range {
+                field: Some(field),
+                lower,
+                upper,
+            } => RangeFilterAst::Range {
+                field,
+                lower: Bound::from_user_input(lower),
+                upper: Bound::from_user_input(upper),
+                is_negated: false,
+            },
+            UserInputLeaf::Literal(UserInputLiteral {
+                field_name: Some(field),
+                phrase,
+            }) => RangeFilterAst::Range {
+                field,
+                lower: Bound::Inclusive(phrase.clone()),
+                upper: Bound::Inclusive(phrase),
+                is_negated: false,
+            },
+            _ => RangeFilterAst::Uninformative,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field_stats() -> BTreeMap<String, FieldStats> {
+        let stats = |bounds, num_nulls| FieldStats { bounds, num_nulls };
+        [
+            (
+                "status_code",
+                stats(FieldBounds::U64 { min: 200, max: 404 }, 0),
+            ),
+            (
+                "latency_ms",
+                stats(
+                    FieldBounds::F64 {
+                        min: 0.5,
+                        max: 12.5,
+                    },
+                    3,
+                ),
+            ),
+            (
+                "created_at",
+                stats(
+                    FieldBounds::Date {
+                        min: 1_640_995_200,
+                        max: 1_641_081_600,
+                    },
+                    0,
+                ),
+            ),
+        ]
+        .into_iter()
+        .map(|(field, stats)| (field.to_string(), stats))
+        .collect()
+    }
+
+    fn may_match(user_query: &str) -> bool {
+        extract_ranges_from_query(user_query)
+            .unwrap()
+            .map_or(true, |range_filter| range_filter.evaluate(&field_stats()))
+    }
+
+    #[test]
+    fn test_extract_ranges_from_query() -> anyhow::Result<()> {
+        assert!(extract_ranges_from_query(":>").is_err());
+        assert_eq!(extract_ranges_from_query("*")?, None);
+        assert_eq!(extract_ranges_from_query("foo OR status_code:>500")?, None);
+        assert_eq!(
+            extract_ranges_from_query("body:foo AND status_code:[500 TO 599}")?
+                .unwrap()
+                .to_string(),
+            "(body:[foo TO foo] ∧ status_code:[500 TO 599})"
+        );
+        assert_eq!(
+            extract_ranges_from_query("-status_code:>=500 latency_ms:<10")?
+                .unwrap()
+                .to_string(),
+            "(¬status_code:[500 TO *} ∨ latency_ms:{* TO 10})"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_filter_evaluate() {
+        assert!(may_match("status_code:[400 TO 499]"));
+        assert!(may_match("status_code:404"));
+        assert!(!may_match("status_code:[500 TO 599]"));
+        assert!(!may_match("status_code:>404"));
+        assert!(may_match("status_code:>=404"));
+        assert!(!may_match("status_code:<200"));
+        assert!(!may_match("status_code:500"));
+        assert!(!may_match("body:foo AND status_code:>=500"));
+        assert!(may_match("body:foo OR status_code:>=500"));
+        assert!(may_match("status_code:>=500 OR latency_ms:<1"));
+        assert!(!may_match("status_code:>=500 OR latency_ms:>20"));
+        assert!(!may_match(
+            "created_at:[2022-01-03T00:00:00Z TO 2022-01-04T00:00:00Z]"
+        ));
+        assert!(may_match(
+            "created_at:{2021-12-31T00:00:00Z TO 2022-01-01T00:00:00Z]"
+        ));
+        // Unparsable bounds and fields without statistics cannot prune.
+        assert!(may_match("status_code:>foo"));
+        assert!(may_match("owner:>foo"));
+    }
+
+    #[test]
+    fn test_range_filter_evaluate_negation() {
+        // All the documents hold a status code in the range.
+        assert!(!may_match("-status_code:[200 TO 404]"));
+        assert!(may_match("-status_code:[200 TO 404}"));
+        // Some documents have no latency.
+        assert!(may_match("-latency_ms:[0 TO 20]"));
+        assert!(!may_match(
+            "status_code:[500 TO 599] AND NOT latency_ms:[0 TO 20]"
+        ));
+    }
+}
@@ -50,6 +50,15 @@ impl From<SortOrder> for TantivyOrder {
     }
 }
 
+impl From<TantivyOrder> for SortOrder {
+    fn from(order: TantivyOrder) -> Self {
+        match order {
+            TantivyOrder::Asc => SortOrder::Asc,
+            TantivyOrder::Desc => SortOrder::Desc,
+        }
+    }
+}
+
 /// Specifies how documents are sorted.
 /// In case of a tie, the documents are ordered according to descending `(split_id, segment_ord,
 /// doc_id)`.
@@ -0,0 +1,121 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Unit of the Unix timestamps held by an `i64` field, and therefore of the fast field values
+/// and of the split time ranges when the field is the index timestamp field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampResolution {
+    /// Seconds, the default.
+    Seconds,
+    /// Milliseconds.
+    Milliseconds,
+    /// Microseconds.
+    Microseconds,
+    /// Nanoseconds.
+    Nanoseconds,
+}
+
+impl Default for TimestampResolution {
+    fn default() -> Self {
+        Self::Seconds
+    }
+}
+
+impl TimestampResolution {
+    /// Returns whether the resolution is the default one, seconds.
+    pub fn is_seconds(&self) -> bool {
+        *self == TimestampResolution::Seconds
+    }
+
+    /// Returns the number of units in a second.
+    pub fn num_units_per_sec(&self) -> i64 {
+        match self {
+            TimestampResolution::Seconds => 1,
+            TimestampResolution::Milliseconds => 1_000,
+            TimestampResolution::Microseconds => 1_000_000,
+            TimestampResolution::Nanoseconds => 1_000_000_000,
+        }
+    }
+
+    /// Converts a timestamp in seconds into a timestamp in this resolution, saturating on
+    /// overflow.
+    pub fn secs_to_units(&self, timestamp_secs: i64) -> i64 {
+        timestamp_secs.saturating_mul(self.num_units_per_sec())
+    }
+
+    /// Converts a date into a timestamp in this resolution, saturating on overflow.
+    pub fn date_time_to_units(&self, date_time: &DateTime<Utc>) -> i64 {
+        let num_nanos_per_unit = 1_000_000_000 / self.num_units_per_sec();
+        self.secs_to_units(date_time.timestamp())
+            .saturating_add(date_time.timestamp_subsec_nanos() as i64 / num_nanos_per_unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_timestamp_resolution_conversions() {
+        let date_time = Utc.timestamp(1_639_935_597, 123_456_789);
+        assert_eq!(
+            TimestampResolution::Seconds.date_time_to_units(&date_time),
+            1_639_935_597
+        );
+        assert_eq!(
+            TimestampResolution::Milliseconds.date_time_to_units(&date_time),
+            1_639_935_597_123
+        );
+        assert_eq!(
+            TimestampResolution::Microseconds.date_time_to_units(&date_time),
+            1_639_935_597_123_456
+        );
+        assert_eq!(
+            TimestampResolution::Nanoseconds.date_time_to_units(&date_time),
+            1_639_935_597_123_456_789
+        );
+        assert_eq!(
+            TimestampResolution::Milliseconds.secs_to_units(1_639_935_597),
+            1_639_935_597_000
+        );
+        assert_eq!(
+            TimestampResolution::Nanoseconds.secs_to_units(i64::MAX),
+            i64::MAX
+        );
+        assert_eq!(
+            TimestampResolution::Nanoseconds.secs_to_units(i64::MIN),
+            i64::MIN
+        );
+    }
+
+    #[test]
+    fn test_timestamp_resolution_serde() {
+        assert_eq!(
+            serde_json::from_str::<TimestampResolution>("\"milliseconds\"").unwrap(),
+            TimestampResolution::Milliseconds
+        );
+        assert!(serde_json::from_str::<TimestampResolution>("\"ms\"").is_err());
+    }
+}
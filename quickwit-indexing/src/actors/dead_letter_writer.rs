@@ -0,0 +1,266 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use quickwit_actors::{Actor, ActorContext, ActorExitStatus, AsyncActor, QueueCapacity};
+use quickwit_storage::Storage;
+use serde::Serialize;
+use tracing::info;
+use ulid::Ulid;
+
+/// Size of the buffered dead letters from which they are written without waiting for the flush
+/// interval.
+const MAX_BUFFER_NUM_BYTES: usize = 8_000_000;
+
+/// Document rejected by the doc mapper.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeadLetter {
+    /// The document as emitted by the source, before it was transformed.
+    pub doc: String,
+    /// Reason of the rejection.
+    pub error: String,
+}
+
+#[derive(Debug)]
+pub enum DeadLetterWriterMessage {
+    DeadLetters(Vec<DeadLetter>),
+    Flush,
+}
+
+/// Line of a dead-letter file.
+#[derive(Serialize)]
+struct DeadLetterRecord<'a> {
+    index_id: &'a str,
+    source_id: &'a str,
+    /// Time at which the document was rejected, in seconds since the Unix epoch.
+    rejected_at: i64,
+    error: &'a str,
+    doc: &'a str,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DeadLetterWriterCounters {
+    pub num_written_docs: u64,
+    pub num_written_files: u64,
+}
+
+/// Writes the documents rejected by the indexer to the dead-letter directory of the index, as
+/// newline-delimited JSON files named `<source_id>/<ulid>.ndjson`.
+///
+/// The dead letters are buffered and written at least every flush interval, and when the
+/// writer exits. Dead letters still buffered when the pipeline is killed are lost.
+pub struct DeadLetterWriter {
+    index_id: String,
+    source_id: String,
+    storage: Arc<dyn Storage>,
+    flush_interval: Duration,
+    buffer: Vec<u8>,
+    num_buffered_docs: u64,
+    is_flush_scheduled: bool,
+    counters: DeadLetterWriterCounters,
+}
+
+impl DeadLetterWriter {
+    pub fn new(
+        index_id: String,
+        source_id: String,
+        storage: Arc<dyn Storage>,
+        flush_interval: Duration,
+    ) -> Self {
+        DeadLetterWriter {
+            index_id,
+            source_id,
+            storage,
+            flush_interval,
+            buffer: Vec::new(),
+            num_buffered_docs: 0,
+            is_flush_scheduled: false,
+            counters: DeadLetterWriterCounters::default(),
+        }
+    }
+
+    fn buffer_dead_letters(&mut self, dead_letters: Vec<DeadLetter>) -> anyhow::Result<()> {
+        let rejected_at = chrono::Utc::now().timestamp();
+        for dead_letter in &dead_letters {
+            let record = DeadLetterRecord {
+                index_id: &self.index_id,
+                source_id: &self.source_id,
+                rejected_at,
+                error: &dead_letter.error,
+                doc: &dead_letter.doc,
+            };
+            serde_json::to_writer(&mut self.buffer, &record)?;
+            self.buffer.push(b'\n');
+            self.num_buffered_docs += 1;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> anyhow::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let path = PathBuf::from(&self.source_id).join(format!("{}.ndjson", Ulid::new()));
+        let payload = std::mem::take(&mut self.buffer);
+        self.storage
+            .put(&path, Box::new(payload))
+            .await
+            .with_context(|| format!("Failed to write dead letters to `{}`.", path.display()))?;
+        info!(
+            path = %path.display(),
+            num_docs = self.num_buffered_docs,
+            "Wrote dead letters."
+        );
+        self.counters.num_written_docs += self.num_buffered_docs;
+        self.counters.num_written_files += 1;
+        self.num_buffered_docs = 0;
+        Ok(())
+    }
+}
+
+impl Actor for DeadLetterWriter {
+    type Message = DeadLetterWriterMessage;
+    type ObservableState = DeadLetterWriterCounters;
+
+    fn observable_state(&self) -> Self::ObservableState {
+        self.counters.clone()
+    }
+
+    fn queue_capacity(&self) -> QueueCapacity {
+        QueueCapacity::Bounded(10)
+    }
+
+    fn name(&self) -> String {
+        "DeadLetterWriter".to_string()
+    }
+}
+
+#[async_trait]
+impl AsyncActor for DeadLetterWriter {
+    async fn process_message(
+        &mut self,
+        message: DeadLetterWriterMessage,
+        ctx: &ActorContext<Self>,
+    ) -> Result<(), ActorExitStatus> {
+        match message {
+            DeadLetterWriterMessage::DeadLetters(dead_letters) => {
+                self.buffer_dead_letters(dead_letters)?;
+                if self.buffer.len() >= MAX_BUFFER_NUM_BYTES {
+                    self.flush().await?;
+                } else if !self.is_flush_scheduled {
+                    self.is_flush_scheduled = true;
+                    ctx.schedule_self_msg(self.flush_interval, DeadLetterWriterMessage::Flush)
+                        .await;
+                }
+            }
+            DeadLetterWriterMessage::Flush => {
+                self.is_flush_scheduled = false;
+                self.flush().await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn finalize(
+        &mut self,
+        exit_status: &ActorExitStatus,
+        _ctx: &ActorContext<Self>,
+    ) -> anyhow::Result<()> {
+        match exit_status {
+            ActorExitStatus::Killed | ActorExitStatus::Panicked => Ok(()),
+            _ => self.flush().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use quickwit_actors::Universe;
+    use quickwit_storage::RamStorage;
+    use serde_json::Value as JsonValue;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dead_letter_writer() -> anyhow::Result<()> {
+        let storage = Arc::new(RamStorage::default());
+        let dead_letter_writer = DeadLetterWriter::new(
+            "test-index".to_string(),
+            "test-source".to_string(),
+            storage.clone(),
+            Duration::from_secs(30),
+        );
+        let universe = Universe::new();
+        let (dead_letter_writer_mailbox, dead_letter_writer_handle) =
+            universe.spawn_actor(dead_letter_writer).spawn_async();
+        universe
+            .send_message(
+                &dead_letter_writer_mailbox,
+                DeadLetterWriterMessage::DeadLetters(vec![
+                    DeadLetter {
+                        doc: r#"{"body": 42}"#.to_string(),
+                        error: "The field 'body' could not be parsed.".to_string(),
+                    },
+                    DeadLetter {
+                        doc: "not json".to_string(),
+                        error: "The provided string is not valid JSON.".to_string(),
+                    },
+                ]),
+            )
+            .await?;
+        let counters = dead_letter_writer_handle
+            .process_pending_and_observe()
+            .await;
+        assert_eq!(counters.num_written_docs, 0);
+
+        // The buffered dead letters are written when the writer exits.
+        universe
+            .send_exit_with_success(&dead_letter_writer_mailbox)
+            .await?;
+        let (exit_status, counters) = dead_letter_writer_handle.join().await;
+        assert!(exit_status.is_success());
+        assert_eq!(counters.num_written_docs, 2);
+        assert_eq!(counters.num_written_files, 1);
+
+        let file_paths = storage.list_files().await;
+        assert_eq!(file_paths.len(), 1);
+        assert!(file_paths[0].starts_with(Path::new("test-source")));
+        let file_content = storage.get_all(&file_paths[0]).await?;
+        let records: Vec<JsonValue> = std::str::from_utf8(&file_content)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["index_id"], "test-index");
+        assert_eq!(records[0]["source_id"], "test-source");
+        assert_eq!(records[0]["doc"], r#"{"body": 42}"#);
+        assert_eq!(
+            records[1]["error"],
+            "The provided string is not valid JSON."
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,330 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, HashMap};
+
+use quickwit_actors::{Actor, ActorContext, ActorExitStatus, Mailbox, QueueCapacity, SyncActor};
+use quickwit_metastore::checkpoint::SourceCheckpoint;
+use serde_json::Value as JsonValue;
+
+use crate::models::{IndexerMessage, RawDocBatch};
+
+/// Indexer of one of the indexes the documents of a source are routed to.
+pub struct RoutedIndexer {
+    pub index_id: String,
+    pub indexer_mailbox: Mailbox<IndexerMessage>,
+    /// Checkpoint of the source in the index. The router does not forward the part of the
+    /// batches the index already covers.
+    pub checkpoint: SourceCheckpoint,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DocRouterCounters {
+    /// Number of documents sent to each index.
+    pub num_docs_per_index: BTreeMap<String, u64>,
+}
+
+/// Splits the batches emitted by a source among several indexes following the value of a field
+/// of the documents.
+///
+/// Every index receives the checkpoint delta of every batch, even when no document is routed to
+/// it, so that each index keeps track of the whole source in its own checkpoint.
+pub struct DocRouter {
+    field_path: Vec<String>,
+    /// Ordinal of the routed indexer for each routing value, serialized as JSON.
+    indexer_ords: HashMap<String, usize>,
+    /// The first indexer receives the documents that do not match any route.
+    indexers: Vec<RoutedIndexer>,
+    counters: DocRouterCounters,
+}
+
+impl DocRouter {
+    pub fn new(
+        field: &str,
+        default_indexer: RoutedIndexer,
+        routes: Vec<(Vec<JsonValue>, RoutedIndexer)>,
+    ) -> Self {
+        let mut indexer_ords = HashMap::new();
+        let mut indexers = vec![default_indexer];
+        for (values, indexer) in routes {
+            for value in values {
+                indexer_ords.insert(value.to_string(), indexers.len());
+            }
+            indexers.push(indexer);
+        }
+        DocRouter {
+            field_path: field.split('.').map(str::to_string).collect(),
+            indexer_ords,
+            indexers,
+            counters: DocRouterCounters::default(),
+        }
+    }
+
+    fn indexer_ord(&self, doc_json: &str) -> usize {
+        let doc: JsonValue = match serde_json::from_str(doc_json) {
+            Ok(doc) => doc,
+            Err(_) => return 0,
+        };
+        self.field_path
+            .iter()
+            .try_fold(&doc, |value, field_name| value.get(field_name))
+            .and_then(|value| self.indexer_ords.get(&value.to_string()))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    fn process_batch(
+        &mut self,
+        batch: RawDocBatch,
+        ctx: &ActorContext<Self>,
+    ) -> Result<(), ActorExitStatus> {
+        let mut docs_per_indexer: Vec<Vec<String>> = vec![Vec::new(); self.indexers.len()];
+        for doc_json in batch.docs {
+            let indexer_ord = {
+                let _protect_zone = ctx.protect_zone();
+                self.indexer_ord(&doc_json)
+            };
+            docs_per_indexer[indexer_ord].push(doc_json);
+            ctx.record_progress();
+        }
+        for (indexer, docs) in self.indexers.iter().zip(docs_per_indexer) {
+            let checkpoint_delta = batch.checkpoint_delta.trim(&indexer.checkpoint);
+            if checkpoint_delta.is_empty() && !batch.checkpoint_delta.is_empty() {
+                // The index already contains the documents of this batch.
+                continue;
+            }
+            *self
+                .counters
+                .num_docs_per_index
+                .entry(indexer.index_id.clone())
+                .or_default() += docs.len() as u64;
+            let routed_batch = RawDocBatch {
+                docs,
+                checkpoint_delta,
+            };
+            ctx.send_message_blocking(&indexer.indexer_mailbox, routed_batch.into())?;
+        }
+        Ok(())
+    }
+}
+
+impl Actor for DocRouter {
+    type Message = IndexerMessage;
+
+    type ObservableState = DocRouterCounters;
+
+    fn observable_state(&self) -> Self::ObservableState {
+        self.counters.clone()
+    }
+
+    fn queue_capacity(&self) -> QueueCapacity {
+        QueueCapacity::Bounded(10)
+    }
+
+    fn name(&self) -> String {
+        "DocRouter".to_string()
+    }
+}
+
+impl SyncActor for DocRouter {
+    fn process_message(
+        &mut self,
+        message: IndexerMessage,
+        ctx: &ActorContext<Self>,
+    ) -> Result<(), ActorExitStatus> {
+        match message {
+            IndexerMessage::Batch(batch) => self.process_batch(batch, ctx)?,
+            IndexerMessage::Flush => {
+                for indexer in &self.indexers {
+                    ctx.send_message_blocking(&indexer.indexer_mailbox, IndexerMessage::Flush)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Returns the checkpoint a routing source resumes from: for each partition, the smallest
+/// position reached by the indexes. A partition missing from the checkpoint of one of the
+/// indexes is read from the beginning.
+pub(crate) fn resume_checkpoint(checkpoints: &[SourceCheckpoint]) -> SourceCheckpoint {
+    let (first_checkpoint, other_checkpoints) = match checkpoints.split_first() {
+        Some(split) => split,
+        None => return SourceCheckpoint::default(),
+    };
+    first_checkpoint
+        .iter()
+        .filter_map(|(partition_id, position)| {
+            other_checkpoints
+                .iter()
+                .try_fold(position, |min_position, checkpoint| {
+                    let other_position = checkpoint.position_for_partition(&partition_id)?;
+                    Some(min_position.min(other_position.clone()))
+                })
+                .map(|min_position| (partition_id, min_position))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_actors::{create_test_mailbox, Universe};
+    use quickwit_metastore::checkpoint::{CheckpointDelta, PartitionId, Position};
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_doc_router() -> anyhow::Result<()> {
+        let (default_mailbox, default_inbox) = create_test_mailbox();
+        let (checkout_mailbox, checkout_inbox) = create_test_mailbox();
+        let (search_mailbox, search_inbox) = create_test_mailbox();
+        let routed_indexer =
+            |index_id: &str,
+             indexer_mailbox: Mailbox<IndexerMessage>,
+             checkpoint: SourceCheckpoint| RoutedIndexer {
+                index_id: index_id.to_string(),
+                indexer_mailbox,
+                checkpoint,
+            };
+        // The search index already covers the first batch.
+        let search_checkpoint: SourceCheckpoint =
+            vec![(PartitionId::default(), Position::from(1u64))]
+                .into_iter()
+                .collect();
+        let doc_router = DocRouter::new(
+            "service.name",
+            routed_indexer("logs", default_mailbox, SourceCheckpoint::default()),
+            vec![
+                (
+                    vec![json!("checkout"), json!("payment")],
+                    routed_indexer(
+                        "checkout-logs",
+                        checkout_mailbox,
+                        SourceCheckpoint::default(),
+                    ),
+                ),
+                (
+                    vec![json!("search")],
+                    routed_indexer("search-logs", search_mailbox, search_checkpoint),
+                ),
+            ],
+        );
+        let universe = Universe::new();
+        let (doc_router_mailbox, doc_router_handle) = universe.spawn_actor(doc_router).spawn_sync();
+        let docs = [
+            json!({"service": {"name": "checkout"}, "body": "order placed"}),
+            json!({"service": {"name": "payment"}, "body": "card charged"}),
+            json!({"service": {"name": "search"}, "body": "query served"}),
+        ];
+        universe
+            .send_message(
+                &doc_router_mailbox,
+                RawDocBatch {
+                    docs: docs.iter().map(JsonValue::to_string).collect(),
+                    checkpoint_delta: CheckpointDelta::from(0..2),
+                }
+                .into(),
+            )
+            .await?;
+        let docs = [
+            json!({"service": {"name": "search"}, "body": "query served"}),
+            json!({"service": {"name": "billing"}, "body": "invoice sent"}),
+            json!({"body": "no service"}),
+        ];
+        let mut doc_jsons: Vec<String> = docs.iter().map(JsonValue::to_string).collect();
+        doc_jsons.push("not json".to_string());
+        universe
+            .send_message(
+                &doc_router_mailbox,
+                RawDocBatch {
+                    docs: doc_jsons,
+                    checkpoint_delta: CheckpointDelta::from(2..6),
+                }
+                .into(),
+            )
+            .await?;
+        let doc_router_counters = doc_router_handle.process_pending_and_observe().await.state;
+        assert_eq!(doc_router_counters.num_docs_per_index["logs"], 3);
+        assert_eq!(doc_router_counters.num_docs_per_index["checkout-logs"], 2);
+        assert_eq!(doc_router_counters.num_docs_per_index["search-logs"], 1);
+
+        let batches = |messages: Vec<IndexerMessage>| {
+            messages
+                .into_iter()
+                .map(|message| match message {
+                    IndexerMessage::Batch(batch) => {
+                        (batch.docs.len(), format!("{:?}", batch.checkpoint_delta))
+                    }
+                    _ => panic!("Unexpected indexer message."),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            batches(default_inbox.drain_available_message_for_test()),
+            vec![
+                (0, "∆(:(..00000000000000000001])".to_string()),
+                (
+                    3,
+                    "∆(:(00000000000000000001..00000000000000000005])".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            batches(checkout_inbox.drain_available_message_for_test()),
+            vec![
+                (2, "∆(:(..00000000000000000001])".to_string()),
+                (
+                    0,
+                    "∆(:(00000000000000000001..00000000000000000005])".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            batches(search_inbox.drain_available_message_for_test()),
+            vec![(
+                1,
+                "∆(:(00000000000000000001..00000000000000000005])".to_string()
+            )]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_checkpoint() {
+        let checkpoint = |positions: &[(&str, u64)]| -> SourceCheckpoint {
+            positions
+                .iter()
+                .map(|(partition_id, position)| {
+                    (PartitionId::from(*partition_id), Position::from(*position))
+                })
+                .collect()
+        };
+        assert!(resume_checkpoint(&[]).is_empty());
+        assert_eq!(
+            resume_checkpoint(&[
+                checkpoint(&[("a", 10), ("b", 20), ("c", 30)]),
+                checkpoint(&[("a", 12), ("b", 15)]),
+                checkpoint(&[("a", 11), ("b", 25), ("c", 5)]),
+            ]),
+            checkpoint(&[("a", 10), ("b", 15)])
+        );
+    }
+}
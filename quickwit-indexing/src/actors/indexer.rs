@@ -31,20 +31,31 @@ use tantivy::schema::{Field, Value};
 use tantivy::{Document, IndexBuilder, IndexSettings, IndexSortByField};
 use tracing::{info, warn};
 
+use crate::actors::{DeadLetter, DeadLetterWriterMessage};
+use crate::dedup_filter::DocDeduplicator;
+use crate::doc_filter::DocFilter;
+use crate::doc_transform::DocTransform;
 use crate::models::{
     IndexedSplit, IndexedSplitBatch, IndexerMessage, IndexingDirectory, RawDocBatch,
 };
+use crate::row_coercion::RowCoercer;
 
 #[derive(Clone, Default, Debug, Eq, PartialEq)]
 pub struct IndexerCounters {
     /// Overall number of documents received, partitionned
-    /// into 3 categories:
+    /// into 5 categories:
+    /// - number of docs dropped by the filter or the transform of the source (if the source has
+    /// neither, then this counter is 0)
     /// - number docs that did not parse correctly.
     /// - number docs missing a timestamp (if the index has no timestamp,
     /// then this counter is 0)
+    /// - number of docs dropped as duplicates (if deduplication is disabled,
+    /// then this counter is 0)
     /// - number of valid docs.
+    pub num_filtered_docs: u64,
     pub num_parse_errors: u64,
     pub num_missing_fields: u64,
+    pub num_duplicate_docs: u64,
     pub num_valid_docs: u64,
 
     /// Number of splits that were emitted by the indexer.
@@ -64,7 +75,11 @@ pub struct IndexerCounters {
 impl IndexerCounters {
     /// Returns the overall number of docs that went through the indexer (valid or not).
     pub fn num_processed_docs(&self) -> u64 {
-        self.num_valid_docs + self.num_parse_errors + self.num_missing_fields
+        self.num_valid_docs
+            + self.num_filtered_docs
+            + self.num_parse_errors
+            + self.num_missing_fields
+            + self.num_duplicate_docs
     }
 
     /// Returns the overall number of docs that were sent to the indexer but were invalid.
@@ -75,6 +90,15 @@ impl IndexerCounters {
     }
 }
 
+/// One of the sources feeding the indexer, along with its document transform and filter.
+pub struct IndexerSource {
+    pub source_id: String,
+    /// Whether the source converts CSV or TSV rows into documents, whose values are all strings.
+    pub decodes_rows: bool,
+    pub doc_transform_opt: Option<DocTransform>,
+    pub doc_filter_opt: Option<DocFilter>,
+}
+
 struct IndexerState {
     index_id: String,
     doc_mapper: Arc<dyn DocMapper>,
@@ -82,11 +106,18 @@ struct IndexerState {
     indexing_settings: IndexingSettings,
     timestamp_field_opt: Option<Field>,
     sort_by_field_opt: Option<IndexSortByField>,
+    deduplicator_opt: Option<DocDeduplicator>,
+    row_coercer: RowCoercer,
+    /// Sources feeding the indexer. The batches that are not tagged with a source id belong to
+    /// the first one.
+    sources: Vec<IndexerSource>,
+    /// Whether the documents rejected by the doc mapper are kept for the dead-letter writer.
+    keeps_dead_letters: bool,
 }
 
 enum PrepareDocumentOutcome {
-    ParsingError,
-    MissingField,
+    ParsingError(DocParsingError),
+    MissingField(DocParsingError),
     Document {
         document: Document,
         timestamp_opt: Option<i64>,
@@ -147,8 +178,10 @@ impl IndexerState {
             Err(doc_parsing_error) => {
                 warn!(err=?doc_parsing_error);
                 return match doc_parsing_error {
-                    DocParsingError::RequiredFastField(_) => PrepareDocumentOutcome::MissingField,
-                    _ => PrepareDocumentOutcome::ParsingError,
+                    DocParsingError::RequiredFastField(_) => {
+                        PrepareDocumentOutcome::MissingField(doc_parsing_error)
+                    }
+                    _ => PrepareDocumentOutcome::ParsingError(doc_parsing_error),
                 };
             }
         };
@@ -176,36 +209,107 @@ impl IndexerState {
         }
     }
 
+    /// Indexes the documents of the batch, and returns the documents rejected by the doc mapper
+    /// if dead letters are kept.
     fn process_batch(
-        &self,
+        &mut self,
+        source_id_opt: Option<&str>,
         batch: RawDocBatch,
         current_split_opt: &mut Option<IndexedSplit>,
         counters: &mut IndexerCounters,
         ctx: &ActorContext<Indexer>,
-    ) -> Result<(), ActorExitStatus> {
+    ) -> Result<Vec<DeadLetter>, ActorExitStatus> {
+        let source_ord = match source_id_opt {
+            Some(source_id) => self
+                .sources
+                .iter()
+                .position(|source| source.source_id == source_id)
+                .with_context(|| format!("Unknown source `{}`.", source_id))?,
+            None => 0,
+        };
         let indexed_split = self.get_or_create_current_indexed_split(current_split_opt, ctx)?;
+        let source = &self.sources[source_ord];
         indexed_split
             .checkpoint_delta
-            .extend(batch.checkpoint_delta)
+            .extend(&source.source_id, batch.checkpoint_delta)
             .with_context(|| "Batch delta does not follow indexer checkpoint")?;
+        let mut dead_letters = Vec::new();
         for doc_json in batch.docs {
             counters.overall_num_bytes += doc_json.len() as u64;
+            let raw_doc_opt = if self.keeps_dead_letters {
+                Some(doc_json.clone())
+            } else {
+                None
+            };
+            let doc_json = if source.decodes_rows {
+                let _protect_zone = ctx.protect_zone();
+                self.row_coercer.coerce(doc_json)
+            } else {
+                doc_json
+            };
+            let doc_json = match &source.doc_transform_opt {
+                Some(doc_transform) => {
+                    let transformed_doc_opt = {
+                        let _protect_zone = ctx.protect_zone();
+                        doc_transform.transform(doc_json)
+                    };
+                    match transformed_doc_opt {
+                        Some(transformed_doc) => transformed_doc,
+                        None => {
+                            counters.num_filtered_docs += 1;
+                            ctx.record_progress();
+                            continue;
+                        }
+                    }
+                }
+                None => doc_json,
+            };
+            if let Some(doc_filter) = &source.doc_filter_opt {
+                let is_dropped = {
+                    let _protect_zone = ctx.protect_zone();
+                    doc_filter.is_dropped(&doc_json)
+                };
+                if is_dropped {
+                    counters.num_filtered_docs += 1;
+                    ctx.record_progress();
+                    continue;
+                }
+            }
             indexed_split.docs_size_in_bytes += doc_json.len() as u64;
             let prepared_doc = {
                 let _protect_zone = ctx.protect_zone();
                 self.prepare_document(doc_json)
             };
             match prepared_doc {
-                PrepareDocumentOutcome::ParsingError => {
+                PrepareDocumentOutcome::ParsingError(doc_parsing_error) => {
                     counters.num_parse_errors += 1;
+                    if let Some(raw_doc) = raw_doc_opt {
+                        dead_letters.push(DeadLetter {
+                            doc: raw_doc,
+                            error: doc_parsing_error.to_string(),
+                        });
+                    }
                 }
-                PrepareDocumentOutcome::MissingField => {
+                PrepareDocumentOutcome::MissingField(doc_parsing_error) => {
                     counters.num_missing_fields += 1;
+                    if let Some(raw_doc) = raw_doc_opt {
+                        dead_letters.push(DeadLetter {
+                            doc: raw_doc,
+                            error: doc_parsing_error.to_string(),
+                        });
+                    }
                 }
                 PrepareDocumentOutcome::Document {
                     document,
                     timestamp_opt,
                 } => {
+                    if let Some(deduplicator) = self.deduplicator_opt.as_mut() {
+                        if deduplicator.is_duplicate(&document) {
+                            counters.num_duplicate_docs += 1;
+                            ctx.record_progress();
+                            continue;
+                        }
+                    }
                     counters.num_docs_in_split += 1;
                     counters.num_valid_docs += 1;
                     indexed_split.num_docs += 1;
@@ -221,13 +325,14 @@ impl IndexerState {
             }
             ctx.record_progress();
         }
-        Ok(())
+        Ok(dead_letters)
     }
 }
 
 pub struct Indexer {
     indexer_state: IndexerState,
     packager_mailbox: Mailbox<IndexedSplitBatch>,
+    dead_letter_writer_mailbox_opt: Option<Mailbox<DeadLetterWriterMessage>>,
     current_split_opt: Option<IndexedSplit>,
     counters: IndexerCounters,
 }
@@ -268,11 +373,17 @@ impl SyncActor for Indexer {
     ) -> Result<(), ActorExitStatus> {
         match indexer_message {
             IndexerMessage::Batch(batch) => {
-                self.process_batch(batch, ctx)?;
+                self.process_batch(None, batch, ctx)?;
+            }
+            IndexerMessage::SourceBatch { source_id, batch } => {
+                self.process_batch(Some(&source_id), batch, ctx)?;
             }
             IndexerMessage::CommitTimeout { split_id } => {
                 self.process_commit_timeout(&split_id, ctx)?;
             }
+            IndexerMessage::Flush => {
+                self.send_to_packager(CommitTrigger::Flush, ctx)?;
+            }
         }
         Ok(())
     }
@@ -300,6 +411,7 @@ enum CommitTrigger {
     Timeout,
     NoMoreDocs,
     NumDocsLimit,
+    Flush,
 }
 
 impl Indexer {
@@ -308,6 +420,7 @@ impl Indexer {
         doc_mapper: Arc<dyn DocMapper>,
         indexing_directory: IndexingDirectory,
         indexing_settings: IndexingSettings,
+        source: IndexerSource,
         packager_mailbox: Mailbox<IndexedSplitBatch>,
     ) -> Self {
         let schema = doc_mapper.schema();
@@ -319,6 +432,14 @@ impl Indexer {
                 order: order.into(),
             }),
         };
+        let deduplicator_opt = indexing_settings
+            .deduplication
+            .as_ref()
+            .and_then(|deduplication| {
+                let doc_id_field = schema.get_field(&deduplication.doc_id_field)?;
+                Some(DocDeduplicator::new(doc_id_field, deduplication))
+            });
+        let row_coercer = RowCoercer::new(&schema);
         Self {
             indexer_state: IndexerState {
                 index_id,
@@ -327,25 +448,57 @@ impl Indexer {
                 indexing_settings,
                 timestamp_field_opt,
                 sort_by_field_opt,
+                deduplicator_opt,
+                row_coercer,
+                sources: vec![source],
+                keeps_dead_letters: false,
             },
             packager_mailbox,
+            dead_letter_writer_mailbox_opt: None,
             current_split_opt: None,
             counters: IndexerCounters::default(),
         }
     }
 
+    /// Adds a source feeding the indexer. Its batches must be tagged with its source id.
+    pub fn with_source(mut self, source: IndexerSource) -> Self {
+        self.indexer_state.sources.push(source);
+        self
+    }
+
+    /// Sends the documents rejected by the doc mapper, along with the reason of their rejection,
+    /// to a dead-letter writer.
+    pub fn with_dead_letter_writer(
+        mut self,
+        dead_letter_writer_mailbox: Mailbox<DeadLetterWriterMessage>,
+    ) -> Self {
+        self.indexer_state.keeps_dead_letters = true;
+        self.dead_letter_writer_mailbox_opt = Some(dead_letter_writer_mailbox);
+        self
+    }
+
     fn process_batch(
         &mut self,
+        source_id_opt: Option<&str>,
         batch: RawDocBatch,
         ctx: &ActorContext<Self>,
     ) -> Result<(), ActorExitStatus> {
         fail_point!("indexer:batch:before");
-        self.indexer_state.process_batch(
+        let dead_letters = self.indexer_state.process_batch(
+            source_id_opt,
             batch,
             &mut self.current_split_opt,
             &mut self.counters,
             ctx,
         )?;
+        if let Some(dead_letter_writer_mailbox) = &self.dead_letter_writer_mailbox_opt {
+            if !dead_letters.is_empty() {
+                ctx.send_message_blocking(
+                    dead_letter_writer_mailbox,
+                    DeadLetterWriterMessage::DeadLetters(dead_letters),
+                )?;
+            }
+        }
         if self.counters.num_docs_in_split
             >= self.indexer_state.indexing_settings.split_num_docs_target as u64
         {
@@ -401,13 +554,29 @@ mod tests {
     use std::time::Duration;
 
     use quickwit_actors::{create_test_mailbox, Universe};
-    use quickwit_doc_mapper::SortOrder;
+    use quickwit_config::{
+        DeduplicationSettings, DocFilterCondition, DocFilterOperator, DocFilterParams,
+        DocTransformParams, DocTransformStep,
+    };
+    use quickwit_doc_mapper::{DateFormat, SortOrder};
     use quickwit_metastore::checkpoint::CheckpointDelta;
 
     use super::*;
     use crate::actors::indexer::{record_timestamp, IndexerCounters};
     use crate::models::{IndexingDirectory, RawDocBatch};
 
+    fn test_source(
+        doc_transform_opt: Option<DocTransform>,
+        doc_filter_opt: Option<DocFilter>,
+    ) -> IndexerSource {
+        IndexerSource {
+            source_id: "test-source".to_string(),
+            decodes_rows: false,
+            doc_transform_opt,
+            doc_filter_opt,
+        }
+    }
+
     #[test]
     fn test_record_timestamp() {
         let mut time_range = None;
@@ -435,6 +604,7 @@ mod tests {
             doc_mapper,
             indexing_directory,
             indexing_settings,
+            test_source(None, None),
             mailbox,
         );
         let universe = Universe::new();
@@ -458,8 +628,10 @@ mod tests {
         assert_eq!(
             indexer_counters,
             IndexerCounters {
+                num_filtered_docs: 0,
                 num_parse_errors: 1,
                 num_missing_fields: 1,
+                num_duplicate_docs: 0,
                 num_valid_docs: 2,
                 num_splits_emitted: 0,
                 num_docs_in_split: 2, //< we have not reached the commit limit yet.
@@ -480,8 +652,10 @@ mod tests {
         assert_eq!(
             indexer_counters,
             IndexerCounters {
+                num_filtered_docs: 0,
                 num_parse_errors: 1,
                 num_missing_fields: 1,
+                num_duplicate_docs: 0,
                 num_valid_docs: 3,
                 num_splits_emitted: 1,
                 num_docs_in_split: 0, //< the num docs in split counter has been reset.
@@ -514,6 +688,7 @@ mod tests {
             doc_mapper,
             indexing_directory,
             indexing_settings,
+            test_source(None, None),
             mailbox,
         );
         let universe = Universe::new();
@@ -532,8 +707,10 @@ mod tests {
         assert_eq!(
             indexer_counters,
             IndexerCounters {
+                num_filtered_docs: 0,
                 num_parse_errors: 0,
                 num_missing_fields: 0,
+                num_duplicate_docs: 0,
                 num_valid_docs: 1,
                 num_splits_emitted: 0,
                 num_docs_in_split: 1,
@@ -545,8 +722,10 @@ mod tests {
         assert_eq!(
             indexer_counters,
             IndexerCounters {
+                num_filtered_docs: 0,
                 num_parse_errors: 0,
                 num_missing_fields: 0,
+                num_duplicate_docs: 0,
                 num_valid_docs: 1,
                 num_splits_emitted: 1,
                 num_docs_in_split: 0,
@@ -571,6 +750,7 @@ mod tests {
             doc_mapper,
             indexing_directory,
             indexing_settings,
+            test_source(None, None),
             mailbox,
         );
         let universe = Universe::new();
@@ -591,8 +771,10 @@ mod tests {
         assert_eq!(
             indexer_counters,
             IndexerCounters {
+                num_filtered_docs: 0,
                 num_parse_errors: 0,
                 num_missing_fields: 0,
+                num_duplicate_docs: 0,
                 num_valid_docs: 1,
                 num_splits_emitted: 1,
                 num_docs_in_split: 0,
@@ -604,4 +786,412 @@ mod tests {
         assert_eq!(output_messages[0].splits[0].num_docs, 1);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_indexer_drops_filtered_docs() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let doc_mapper = Arc::new(quickwit_doc_mapper::default_doc_mapper_for_tests());
+        let indexing_directory = IndexingDirectory::for_test().await?;
+        let indexing_settings = IndexingSettings::for_test();
+        let doc_filter = DocFilter::try_new(&DocFilterParams {
+            include: Vec::new(),
+            exclude: vec![DocFilterCondition {
+                field: "body".to_string(),
+                operator: DocFilterOperator::Matches("^healthcheck".to_string()),
+            }],
+        })?;
+        let (mailbox, inbox) = create_test_mailbox();
+        let indexer = Indexer::new(
+            "test-index".to_string(),
+            doc_mapper,
+            indexing_directory,
+            indexing_settings,
+            test_source(None, Some(doc_filter)),
+            mailbox,
+        );
+        let universe = Universe::new();
+        let (indexer_mailbox, indexer_handle) = universe.spawn_actor(indexer).spawn_sync();
+        universe
+            .send_message(
+                &indexer_mailbox,
+                RawDocBatch {
+                    docs: vec![
+                        r#"{"body": "doc-1", "timestamp": 1628837062, "response_date": "2021-12-19T16:39:57+00:00", "response_time": 12, "response_payload": "YWJj"}"#.to_string(),
+                        r#"{"body": "healthcheck ok", "timestamp": 1628837063, "response_date": "2021-12-19T16:39:58+00:00", "response_time": 12, "response_payload": "YWJj"}"#.to_string(),
+                    ],
+                    checkpoint_delta: CheckpointDelta::from(0..2),
+                }
+                .into(),
+            )
+            .await?;
+        universe.send_exit_with_success(&indexer_mailbox).await?;
+        let (exit_status, indexer_counters) = indexer_handle.join().await;
+        assert!(exit_status.is_success());
+        assert_eq!(indexer_counters.num_valid_docs, 1);
+        assert_eq!(indexer_counters.num_filtered_docs, 1);
+        assert_eq!(indexer_counters.num_processed_docs(), 2);
+        let output_messages = inbox.drain_available_message_for_test();
+        assert_eq!(output_messages.len(), 1);
+        assert_eq!(output_messages[0].splits[0].num_docs, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_indexer_multiple_sources() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let doc_mapper = Arc::new(quickwit_doc_mapper::default_doc_mapper_for_tests());
+        let indexing_directory = IndexingDirectory::for_test().await?;
+        let indexing_settings = IndexingSettings::for_test();
+        let doc_filter = DocFilter::try_new(&DocFilterParams {
+            include: Vec::new(),
+            exclude: vec![DocFilterCondition {
+                field: "body".to_string(),
+                operator: DocFilterOperator::Matches("^healthcheck".to_string()),
+            }],
+        })?;
+        let (mailbox, inbox) = create_test_mailbox();
+        let indexer = Indexer::new(
+            "test-index".to_string(),
+            doc_mapper,
+            indexing_directory,
+            indexing_settings,
+            test_source(None, None),
+            mailbox,
+        )
+        .with_source(IndexerSource {
+            source_id: "other-source".to_string(),
+            decodes_rows: false,
+            doc_transform_opt: None,
+            doc_filter_opt: Some(doc_filter),
+        });
+        let universe = Universe::new();
+        let (indexer_mailbox, indexer_handle) = universe.spawn_actor(indexer).spawn_sync();
+        let docs = vec![
+            r#"{"body": "doc-1", "timestamp": 1628837062, "response_date": "2021-12-19T16:39:57+00:00", "response_time": 12, "response_payload": "YWJj"}"#.to_string(),
+            r#"{"body": "healthcheck ok", "timestamp": 1628837063, "response_date": "2021-12-19T16:39:58+00:00", "response_time": 12, "response_payload": "YWJj"}"#.to_string(),
+        ];
+        universe
+            .send_message(
+                &indexer_mailbox,
+                RawDocBatch {
+                    docs: docs.clone(),
+                    checkpoint_delta: CheckpointDelta::from(0..2),
+                }
+                .into(),
+            )
+            .await?;
+        universe
+            .send_message(
+                &indexer_mailbox,
+                IndexerMessage::SourceBatch {
+                    source_id: "other-source".to_string(),
+                    batch: RawDocBatch {
+                        docs,
+                        checkpoint_delta: CheckpointDelta::from(5..7),
+                    },
+                },
+            )
+            .await?;
+        universe.send_exit_with_success(&indexer_mailbox).await?;
+        let (exit_status, indexer_counters) = indexer_handle.join().await;
+        assert!(exit_status.is_success());
+        // Only the other source filters out the health checks.
+        assert_eq!(indexer_counters.num_valid_docs, 3);
+        assert_eq!(indexer_counters.num_filtered_docs, 1);
+        let output_messages = inbox.drain_available_message_for_test();
+        assert_eq!(output_messages.len(), 1);
+        let checkpoint_delta = &output_messages[0].splits[0].checkpoint_delta;
+        assert_eq!(checkpoint_delta.num_sources(), 2);
+        assert_eq!(
+            checkpoint_delta.source_delta("test-source"),
+            Some(&CheckpointDelta::from(0..2))
+        );
+        assert_eq!(
+            checkpoint_delta.source_delta("other-source"),
+            Some(&CheckpointDelta::from(5..7))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_indexer_transforms_docs_before_filtering_them() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let doc_mapper = Arc::new(quickwit_doc_mapper::default_doc_mapper_for_tests());
+        let indexing_directory = IndexingDirectory::for_test().await?;
+        let indexing_settings = IndexingSettings::for_test();
+        let doc_transform = DocTransform::try_new(&DocTransformParams {
+            steps: vec![
+                DocTransformStep::Rename {
+                    from: "message".to_string(),
+                    to: "body".to_string(),
+                },
+                DocTransformStep::ParseTimestamp {
+                    field: "timestamp".to_string(),
+                    input_formats: vec![DateFormat::Rfc3339],
+                    output_format: DateFormat::EpochSecs,
+                },
+                DocTransformStep::DropIf(DocFilterCondition {
+                    field: "debug".to_string(),
+                    operator: DocFilterOperator::Exists(true),
+                }),
+            ],
+        })?;
+        let doc_filter = DocFilter::try_new(&DocFilterParams {
+            include: Vec::new(),
+            exclude: vec![DocFilterCondition {
+                field: "body".to_string(),
+                operator: DocFilterOperator::Matches("^healthcheck".to_string()),
+            }],
+        })?;
+        let (mailbox, inbox) = create_test_mailbox();
+        let indexer = Indexer::new(
+            "test-index".to_string(),
+            doc_mapper,
+            indexing_directory,
+            indexing_settings,
+            test_source(Some(doc_transform), Some(doc_filter)),
+            mailbox,
+        );
+        let universe = Universe::new();
+        let (indexer_mailbox, indexer_handle) = universe.spawn_actor(indexer).spawn_sync();
+        universe
+            .send_message(
+                &indexer_mailbox,
+                RawDocBatch {
+                    docs: vec![
+                        r#"{"message": "doc-1", "timestamp": "2021-08-13T06:44:22Z", "response_date": "2021-12-19T16:39:57+00:00", "response_time": 12, "response_payload": "YWJj"}"#.to_string(),
+                        r#"{"message": "healthcheck ok", "timestamp": "2021-08-13T06:44:23Z", "response_date": "2021-12-19T16:39:58+00:00", "response_time": 12, "response_payload": "YWJj"}"#.to_string(),
+                        r#"{"message": "doc-3", "timestamp": "2021-08-13T06:44:24Z", "response_date": "2021-12-19T16:39:59+00:00", "response_time": 12, "response_payload": "YWJj", "debug": true}"#.to_string(),
+                    ],
+                    checkpoint_delta: CheckpointDelta::from(0..3),
+                }
+                .into(),
+            )
+            .await?;
+        universe.send_exit_with_success(&indexer_mailbox).await?;
+        let (exit_status, indexer_counters) = indexer_handle.join().await;
+        assert!(exit_status.is_success());
+        assert_eq!(indexer_counters.num_valid_docs, 1);
+        assert_eq!(indexer_counters.num_filtered_docs, 2);
+        assert_eq!(indexer_counters.num_parse_errors, 0);
+        let output_messages = inbox.drain_available_message_for_test();
+        assert_eq!(output_messages.len(), 1);
+        assert_eq!(output_messages[0].splits[0].num_docs, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_indexer_sends_rejected_docs_to_dead_letter_writer() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let doc_mapper = Arc::new(quickwit_doc_mapper::default_doc_mapper_for_tests());
+        let indexing_directory = IndexingDirectory::for_test().await?;
+        let mut indexing_settings = IndexingSettings::for_test();
+        indexing_settings.timestamp_field = Some("timestamp".to_string());
+        let (mailbox, _inbox) = create_test_mailbox();
+        let (dead_letter_writer_mailbox, dead_letter_writer_inbox) = create_test_mailbox();
+        let doc_transform = DocTransform::try_new(&DocTransformParams {
+            steps: vec![DocTransformStep::Rename {
+                from: "message".to_string(),
+                to: "body".to_string(),
+            }],
+        })?;
+        let indexer = Indexer::new(
+            "test-index".to_string(),
+            doc_mapper,
+            indexing_directory,
+            indexing_settings,
+            test_source(Some(doc_transform), None),
+            mailbox,
+        )
+        .with_dead_letter_writer(dead_letter_writer_mailbox);
+        let universe = Universe::new();
+        let (indexer_mailbox, indexer_handle) = universe.spawn_actor(indexer).spawn_sync();
+        let missing_timestamp_doc = r#"{"message": "happy", "response_date": "2021-12-19T16:39:57+00:00", "response_time": 12, "response_payload": "YWJj"}"#;
+        universe
+            .send_message(
+                &indexer_mailbox,
+                RawDocBatch {
+                    docs: vec![
+                        missing_timestamp_doc.to_string(),
+                        r#"{"message": "happy", "timestamp": 1628837062, "response_date": "2021-12-19T16:39:59+00:00", "response_time": 2, "response_payload": "YWJj"}"#.to_string(),
+                        "{".to_string(),
+                    ],
+                    checkpoint_delta: CheckpointDelta::from(0..3),
+                }
+                .into(),
+            )
+            .await?;
+        let indexer_counters = indexer_handle.process_pending_and_observe().await.state;
+        assert_eq!(indexer_counters.num_valid_docs, 1);
+        let dead_letter_messages = dead_letter_writer_inbox.drain_available_message_for_test();
+        assert_eq!(dead_letter_messages.len(), 1);
+        let dead_letters = match &dead_letter_messages[0] {
+            DeadLetterWriterMessage::DeadLetters(dead_letters) => dead_letters,
+            DeadLetterWriterMessage::Flush => panic!("Expected dead letters."),
+        };
+        assert_eq!(dead_letters.len(), 2);
+        // The dead letters hold the documents as emitted by the source.
+        assert_eq!(dead_letters[0].doc, missing_timestamp_doc);
+        assert!(dead_letters[0]
+            .error
+            .contains("must contain field \"timestamp\""));
+        assert_eq!(dead_letters[1].doc, "{");
+        assert_eq!(
+            dead_letters[1].error,
+            "The provided string is not valid JSON"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_indexer_coerces_row_values() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let doc_mapper = Arc::new(quickwit_doc_mapper::default_doc_mapper_for_tests());
+        let indexing_directory = IndexingDirectory::for_test().await?;
+        let indexing_settings = IndexingSettings::for_test();
+        let (mailbox, _inbox) = create_test_mailbox();
+        let indexer = Indexer::new(
+            "test-index".to_string(),
+            doc_mapper,
+            indexing_directory,
+            indexing_settings,
+            IndexerSource {
+                decodes_rows: true,
+                ..test_source(None, None)
+            },
+            mailbox,
+        );
+        let universe = Universe::new();
+        let (indexer_mailbox, indexer_handle) = universe.spawn_actor(indexer).spawn_sync();
+        universe
+            .send_message(
+                &indexer_mailbox,
+                RawDocBatch {
+                    docs: vec![
+                        r#"{"body": "happy", "timestamp": "1628837062", "response_date": "2021-12-19T16:39:59+00:00", "response_time": "2.5", "response_payload": "YWJj"}"#.to_string(),
+                        r#"{"body": "sad", "timestamp": "yesterday", "response_date": "2021-12-19T16:39:59+00:00", "response_time": "2.5", "response_payload": "YWJj"}"#.to_string(),
+                    ],
+                    checkpoint_delta: CheckpointDelta::from(0..2),
+                }
+                .into(),
+            )
+            .await?;
+        let indexer_counters = indexer_handle.process_pending_and_observe().await.state;
+        assert_eq!(indexer_counters.num_valid_docs, 1);
+        assert_eq!(indexer_counters.num_parse_errors, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_indexer_drops_duplicate_docs() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let doc_mapper = Arc::new(quickwit_doc_mapper::default_doc_mapper_for_tests());
+        let indexing_directory = IndexingDirectory::for_test().await?;
+        let mut indexing_settings = IndexingSettings::for_test();
+        indexing_settings.deduplication = Some(DeduplicationSettings {
+            doc_id_field: "body".to_string(),
+            window_secs: 600,
+            max_num_docs_per_window: 1_000,
+            false_positive_rate: 0.001,
+        });
+        let (mailbox, inbox) = create_test_mailbox();
+        let indexer = Indexer::new(
+            "test-index".to_string(),
+            doc_mapper,
+            indexing_directory,
+            indexing_settings,
+            test_source(None, None),
+            mailbox,
+        );
+        let universe = Universe::new();
+        let (indexer_mailbox, indexer_handle) = universe.spawn_actor(indexer).spawn_sync();
+        universe
+            .send_message(
+                &indexer_mailbox,
+                RawDocBatch {
+                    docs: vec![
+                        r#"{"body": "doc-1", "timestamp": 1628837062, "response_date": "2021-12-19T16:39:57+00:00", "response_time": 12, "response_payload": "YWJj"}"#.to_string(),
+                        r#"{"body": "doc-2", "timestamp": 1628837063, "response_date": "2021-12-19T16:39:58+00:00", "response_time": 12, "response_payload": "YWJj"}"#.to_string(),
+                        r#"{"body": "doc-1", "timestamp": 1628837062, "response_date": "2021-12-19T16:39:57+00:00", "response_time": 12, "response_payload": "YWJj"}"#.to_string(), // redelivered
+                    ],
+                    checkpoint_delta: CheckpointDelta::from(0..3),
+                }
+                .into(),
+            )
+            .await?;
+        universe.send_exit_with_success(&indexer_mailbox).await?;
+        let (exit_status, indexer_counters) = indexer_handle.join().await;
+        assert!(exit_status.is_success());
+        assert_eq!(indexer_counters.num_valid_docs, 2);
+        assert_eq!(indexer_counters.num_duplicate_docs, 1);
+        assert_eq!(indexer_counters.num_processed_docs(), 3);
+        let output_messages = inbox.drain_available_message_for_test();
+        assert_eq!(output_messages.len(), 1);
+        assert_eq!(output_messages[0].splits[0].num_docs, 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_indexer_forgets_duplicates_on_restart() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let doc = |body: &str| {
+            format!(
+                r#"{{"body": "{}", "timestamp": 1628837062, "response_date": "2021-12-19T16:39:57+00:00", "response_time": 12, "response_payload": "YWJj"}}"#,
+                body
+            )
+        };
+        let universe = Universe::new();
+        let mut indexer_counters_per_run = Vec::new();
+        // The second batch is ingested by a new indexer, as after a restart of the pipeline.
+        for (docs, checkpoint_delta) in [
+            (
+                vec![doc("doc-1"), doc("doc-2")],
+                CheckpointDelta::from(0..2),
+            ),
+            (
+                vec![doc("doc-1"), doc("doc-3"), doc("doc-3")],
+                CheckpointDelta::from(2..5),
+            ),
+        ] {
+            let doc_mapper = Arc::new(quickwit_doc_mapper::default_doc_mapper_for_tests());
+            let indexing_directory = IndexingDirectory::for_test().await?;
+            let mut indexing_settings = IndexingSettings::for_test();
+            indexing_settings.deduplication = Some(DeduplicationSettings {
+                doc_id_field: "body".to_string(),
+                window_secs: 600,
+                max_num_docs_per_window: 1_000,
+                false_positive_rate: 0.001,
+            });
+            let (mailbox, _inbox) = create_test_mailbox();
+            let indexer = Indexer::new(
+                "test-index".to_string(),
+                doc_mapper,
+                indexing_directory,
+                indexing_settings,
+                test_source(None, None),
+                mailbox,
+            );
+            let (indexer_mailbox, indexer_handle) = universe.spawn_actor(indexer).spawn_sync();
+            universe
+                .send_message(
+                    &indexer_mailbox,
+                    RawDocBatch {
+                        docs,
+                        checkpoint_delta,
+                    }
+                    .into(),
+                )
+                .await?;
+            universe.send_exit_with_success(&indexer_mailbox).await?;
+            let (exit_status, indexer_counters) = indexer_handle.join().await;
+            assert!(exit_status.is_success());
+            indexer_counters_per_run.push(indexer_counters);
+        }
+        assert_eq!(indexer_counters_per_run[0].num_valid_docs, 2);
+        assert_eq!(indexer_counters_per_run[0].num_duplicate_docs, 0);
+        // `doc-1` was ingested before the restart: the new filter does not remember it.
+        assert_eq!(indexer_counters_per_run[1].num_valid_docs, 2);
+        assert_eq!(indexer_counters_per_run[1].num_duplicate_docs, 1);
+        Ok(())
+    }
 }
@@ -17,45 +17,79 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::{BTreeMap, HashSet};
+use std::ops::Range;
 use std::path::PathBuf;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use async_trait::async_trait;
 use itertools::Itertools;
 use quickwit_actors::{
     create_mailbox, Actor, ActorContext, ActorExitStatus, ActorHandle, AsyncActor, Health,
-    KillSwitch, QueueCapacity, Supervisable,
+    KillSwitch, Mailbox, QueueCapacity, Supervisable,
 };
+use quickwit_common::uri::Uri;
 use quickwit_config::{build_doc_mapper, IndexingSettings, SourceConfig};
 use quickwit_doc_mapper::DocMapper;
 use quickwit_metastore::{IndexMetadata, Metastore, SplitState};
-use quickwit_storage::Storage;
+use quickwit_storage::{
+    account_storage_costs, quickwit_storage_uri_resolver, Storage, StorageActivity,
+};
+use serde_json::Value as JsonValue;
 use tokio::join;
 use tracing::{debug, error, info, info_span, instrument, Span};
 
+use crate::actors::doc_router::{resume_checkpoint, DocRouter, RoutedIndexer};
 use crate::actors::merge_split_downloader::MergeSplitDownloader;
 use crate::actors::publisher::PublisherType;
+use crate::actors::uploader::MAX_CONCURRENT_SPLIT_UPLOAD;
 use crate::actors::{
-    GarbageCollector, Indexer, MergeExecutor, MergePlanner, NamedField, Packager, Publisher,
-    Uploader,
+    DeadLetterWriter, Indexer, IndexerSource, MergeExecutor, MergePlanner, NamedField, Packager,
+    Publisher, SourceTagger, Uploader,
+};
+use crate::doc_filter::DocFilter;
+use crate::doc_transform::DocTransform;
+use crate::models::{IndexerMessage, IndexingDirectory, IndexingStatistics, ScratchQuota};
+use crate::pipeline_throughput::{
+    clear_pipeline_throughput, set_pipeline_throughput, PipelineStage, StageSample, ThroughputMeter,
 };
-use crate::models::{IndexingDirectory, IndexingStatistics};
-use crate::source::{quickwit_supported_sources, SourceActor};
+use crate::source::{quickwit_supported_sources, SourceActor, SourceActorMessage};
+use crate::source_pause::take_source_checkpoint_reset;
 use crate::split_store::{IndexingSplitStore, IndexingSplitStoreParams};
-use crate::{MergePolicy, StableMultitenantWithTimestampMergePolicy};
+use crate::{
+    is_source_paused, CompactionMergePolicy, MergePolicy, StableMultitenantWithTimestampMergePolicy,
+};
 
 const MAX_RETRY_DELAY: Duration = Duration::from_secs(600); // 10 min.
 
 pub struct IndexingPipelineHandler {
-    /// Indexing pipeline
     pub source: ActorHandle<SourceActor>,
+    /// Other sources of the pipeline, when it runs several sources.
+    pub additional_sources: Vec<ActorHandle<SourceActor>>,
+    /// Taggers forwarding the batches of each source to the indexer, when the pipeline runs
+    /// several sources.
+    pub source_taggers: Vec<ActorHandle<SourceTagger>>,
+    /// Router splitting the documents of the source among the indexes, when the source routes
+    /// documents to other indexes.
+    pub doc_router_opt: Option<ActorHandle<DocRouter>>,
+    /// Actors of the index declaring the source.
+    pub index_chain: IndexChainHandler,
+    /// Actors of the indexes the source routes documents to.
+    pub routed_index_chains: Vec<IndexChainHandler>,
+}
+
+/// Indexing and merging actors of one of the indexes fed by the pipeline.
+pub struct IndexChainHandler {
+    /// Indexing pipeline
     pub indexer: ActorHandle<Indexer>,
+    /// Writer of the documents rejected by the indexer, when the index has a dead-letter output.
+    pub dead_letter_writer_opt: Option<ActorHandle<DeadLetterWriter>>,
     pub packager: ActorHandle<Packager>,
     pub uploader: ActorHandle<Uploader>,
     pub publisher: ActorHandle<Publisher>,
-    pub garbage_collector: ActorHandle<GarbageCollector>,
 
     /// Merging pipeline subpipeline
     pub merge_planner: ActorHandle<MergePlanner>,
@@ -66,6 +100,55 @@ pub struct IndexingPipelineHandler {
     pub merge_publisher: ActorHandle<Publisher>,
 }
 
+impl IndexChainHandler {
+    fn supervisables(&self) -> Vec<&dyn Supervisable> {
+        let mut supervisables: Vec<&dyn Supervisable> = vec![
+            &self.indexer,
+            &self.packager,
+            &self.uploader,
+            &self.publisher,
+            &self.merge_planner,
+            &self.merge_split_downloader,
+            &self.merge_executor,
+            &self.merge_packager,
+            &self.merge_uploader,
+            &self.merge_publisher,
+        ];
+        if let Some(dead_letter_writer) = &self.dead_letter_writer_opt {
+            supervisables.push(dead_letter_writer);
+        }
+        supervisables
+    }
+
+    async fn kill(self) {
+        if let Some(dead_letter_writer) = self.dead_letter_writer_opt {
+            dead_letter_writer.kill().await;
+        }
+        tokio::join!(
+            self.indexer.kill(),
+            self.packager.kill(),
+            self.uploader.kill(),
+            self.publisher.kill(),
+            self.merge_planner.kill(),
+            self.merge_split_downloader.kill(),
+            self.merge_executor.kill(),
+            self.merge_packager.kill(),
+            self.merge_uploader.kill(),
+            self.merge_publisher.kill(),
+        );
+    }
+}
+
+/// Parameters of one of the indexes fed by the pipeline.
+struct IndexChainParams<'a> {
+    index_id: &'a str,
+    doc_mapper: &'a Arc<dyn DocMapper>,
+    indexing_directory: &'a IndexingDirectory,
+    indexing_settings: &'a IndexingSettings,
+    storage: &'a Arc<dyn Storage>,
+    compaction_time_range_opt: Option<&'a Range<i64>>,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum IndexingPipelineMessage {
     Supervise,
@@ -77,7 +160,10 @@ pub struct IndexingPipeline {
     params: IndexingPipelineParams,
     previous_generations_statistics: IndexingStatistics,
     statistics: IndexingStatistics,
+    throughput_meter: ThroughputMeter,
     handlers: Option<IndexingPipelineHandler>,
+    // Sources of the pipeline whose source actor was sent a `Pause` message.
+    paused_source_ids: HashSet<String>,
     // Killswitch used for the actors in the pipeline. This is not the supervisor killswitch.
     kill_switch: KillSwitch,
 }
@@ -106,28 +192,68 @@ impl IndexingPipeline {
             params,
             previous_generations_statistics: Default::default(),
             handlers: None,
+            paused_source_ids: HashSet::new(),
             kill_switch: KillSwitch::default(),
             statistics: IndexingStatistics::default(),
+            throughput_meter: ThroughputMeter::default(),
         }
     }
 
     async fn process_observe(&mut self, ctx: &ActorContext<Self>) -> Result<(), ActorExitStatus> {
         if let Some(handlers) = self.handlers.as_ref() {
-            let (indexer_counters, uploader_counters, publisher_counters) = join!(
-                handlers.indexer.observe(),
-                handlers.uploader.observe(),
-                handlers.publisher.observe(),
-            );
-            self.statistics = self
-                .previous_generations_statistics
-                .clone()
-                .add_actor_counters(
+            let mut statistics = self.previous_generations_statistics.clone();
+            let mut stage_samples: BTreeMap<PipelineStage, StageSample> = BTreeMap::new();
+            for index_chain in
+                std::iter::once(&handlers.index_chain).chain(&handlers.routed_index_chains)
+            {
+                let (indexer_counters, uploader_counters, publisher_counters) = join!(
+                    index_chain.indexer.observe(),
+                    index_chain.uploader.observe(),
+                    index_chain.publisher.observe(),
+                );
+                for (stage, num_docs, num_bytes, saturation) in [
+                    (
+                        PipelineStage::Indexer,
+                        indexer_counters.num_processed_docs(),
+                        indexer_counters.overall_num_bytes,
+                        index_chain.indexer.mailbox().fill_ratio(),
+                    ),
+                    (
+                        PipelineStage::Uploader,
+                        uploader_counters.num_uploaded_docs.load(Ordering::SeqCst),
+                        uploader_counters.num_uploaded_bytes.load(Ordering::SeqCst),
+                        uploader_counters.num_ongoing_uploads as f64
+                            / MAX_CONCURRENT_SPLIT_UPLOAD as f64,
+                    ),
+                    (
+                        PipelineStage::Publisher,
+                        publisher_counters.num_published_docs,
+                        publisher_counters.num_published_bytes,
+                        index_chain.publisher.mailbox().fill_ratio(),
+                    ),
+                ] {
+                    let stage_sample = stage_samples.entry(stage).or_default();
+                    stage_sample.num_docs += num_docs;
+                    stage_sample.num_bytes += num_bytes;
+                    // The saturation of a routed index chain holds back the whole pipeline.
+                    stage_sample.saturation = stage_sample.saturation.max(saturation);
+                }
+                statistics = statistics.add_actor_counters(
                     &*indexer_counters,
                     &*uploader_counters,
                     &*publisher_counters,
-                )
+                );
+            }
+            self.statistics = statistics
                 .set_generation(self.statistics.generation)
                 .set_num_spawn_attempts(self.statistics.num_spawn_attempts);
+            self.throughput_meter.record(Instant::now(), stage_samples);
+            if let Some(throughput) = self
+                .throughput_meter
+                .throughput(&self.params.index_id, &self.params.source.source_id)
+            {
+                set_pipeline_throughput(throughput);
+            }
         }
         ctx.schedule_self_msg(Duration::from_secs(1), IndexingPipelineMessage::Observe)
             .await;
@@ -136,20 +262,20 @@ impl IndexingPipeline {
 
     fn supervisables(&self) -> Vec<&dyn Supervisable> {
         if let Some(handlers) = self.handlers.as_ref() {
-            let supervisables: Vec<&dyn Supervisable> = vec![
-                &handlers.source,
-                &handlers.indexer,
-                &handlers.packager,
-                &handlers.uploader,
-                &handlers.publisher,
-                &handlers.garbage_collector,
-                &handlers.merge_planner,
-                &handlers.merge_split_downloader,
-                &handlers.merge_executor,
-                &handlers.merge_packager,
-                &handlers.merge_uploader,
-                &handlers.merge_publisher,
-            ];
+            let mut supervisables: Vec<&dyn Supervisable> = vec![&handlers.source];
+            for additional_source in &handlers.additional_sources {
+                supervisables.push(additional_source);
+            }
+            for source_tagger in &handlers.source_taggers {
+                supervisables.push(source_tagger);
+            }
+            if let Some(doc_router) = &handlers.doc_router_opt {
+                supervisables.push(doc_router);
+            }
+            supervisables.extend(handlers.index_chain.supervisables());
+            for routed_index_chain in &handlers.routed_index_chains {
+                supervisables.extend(routed_index_chain.supervisables());
+            }
             supervisables
         } else {
             Vec::new()
@@ -202,25 +328,245 @@ impl IndexingPipeline {
     async fn spawn_pipeline(&mut self, ctx: &ActorContext<Self>) -> anyhow::Result<()> {
         self.statistics.num_spawn_attempts += 1;
         self.kill_switch = KillSwitch::default();
+        // The source actors of the new generation start unpaused.
+        self.paused_source_ids.clear();
+        // The counters of the actors of the new generation start from zero.
+        self.throughput_meter = ThroughputMeter::default();
+        let (source_mailbox, source_inbox) = create_mailbox::<<SourceActor as Actor>::Message>(
+            "SourceActor".to_string(),
+            QueueCapacity::Unbounded,
+        );
+        let sources_have_routing = std::iter::once(&self.params.source)
+            .chain(&self.params.additional_sources)
+            .any(|source| source.routing.is_some());
+        if !self.params.additional_sources.is_empty() && sources_have_routing {
+            bail!("The sources of a pipeline running several sources cannot route documents.");
+        }
+        let additional_source_mailboxes_and_inboxes = self
+            .params
+            .additional_sources
+            .iter()
+            .map(|_| {
+                create_mailbox::<<SourceActor as Actor>::Message>(
+                    "SourceActor".to_string(),
+                    QueueCapacity::Unbounded,
+                )
+            })
+            .collect_vec();
+        let additional_source_mailboxes = additional_source_mailboxes_and_inboxes
+            .iter()
+            .map(|(source_mailbox, _)| source_mailbox.clone())
+            .collect_vec();
+        let index_chain_params = IndexChainParams {
+            index_id: &self.params.index_id,
+            doc_mapper: &self.params.doc_mapper,
+            indexing_directory: &self.params.indexing_directory,
+            indexing_settings: &self.params.indexing_settings,
+            storage: &self.params.storage,
+            compaction_time_range_opt: self.params.compaction_time_range_opt.as_ref(),
+        };
+        let (indexer_mailbox, index_chain) = self
+            .spawn_index_chain(
+                ctx,
+                index_chain_params,
+                Some(source_mailbox.clone()),
+                additional_source_mailboxes,
+            )
+            .await?;
+
+        // Fetch index_metadata to be sure to have the last updated checkpoint.
+        let index_metadata = self
+            .params
+            .metastore
+            .index_metadata(&self.params.index_id)
+            .await?;
+        let source_checkpoint = index_metadata
+            .checkpoint
+            .source_checkpoint(&self.params.source.source_id)
+            .cloned()
+            .unwrap_or_default(); // TODO Have a stricter check.
+
+        // When the source routes documents to other indexes, a router sits between the source
+        // and the indexers. The indexes only publish their own checkpoint of the source, so the
+        // source resumes from the position reached by the index lagging the most behind.
+        let mut routed_index_chains = Vec::new();
+        let (batch_sink, doc_router_handler_opt, source_checkpoint) =
+            if let Some(routing_params) = &self.params.source.routing {
+                let mut checkpoints = vec![source_checkpoint.clone()];
+                let mut routes = Vec::with_capacity(self.params.routed_indexes.len());
+                for routed_index in &self.params.routed_indexes {
+                    let routed_index_chain_params = IndexChainParams {
+                        index_id: &routed_index.index_id,
+                        doc_mapper: &routed_index.doc_mapper,
+                        indexing_directory: &routed_index.indexing_directory,
+                        indexing_settings: &routed_index.indexing_settings,
+                        storage: &routed_index.storage,
+                        compaction_time_range_opt: None,
+                    };
+                    let (routed_indexer_mailbox, routed_index_chain) = self
+                        .spawn_index_chain(ctx, routed_index_chain_params, None, Vec::new())
+                        .await?;
+                    routed_index_chains.push(routed_index_chain);
+                    let routed_checkpoint = self
+                        .params
+                        .metastore
+                        .index_metadata(&routed_index.index_id)
+                        .await?
+                        .checkpoint
+                        .source_checkpoint(&self.params.source.source_id)
+                        .cloned()
+                        .unwrap_or_default();
+                    checkpoints.push(routed_checkpoint.clone());
+                    let routed_indexer = RoutedIndexer {
+                        index_id: routed_index.index_id.clone(),
+                        indexer_mailbox: routed_indexer_mailbox,
+                        checkpoint: routed_checkpoint,
+                    };
+                    routes.push((routed_index.values.clone(), routed_indexer));
+                }
+                let default_indexer = RoutedIndexer {
+                    index_id: self.params.index_id.clone(),
+                    indexer_mailbox: indexer_mailbox.clone(),
+                    checkpoint: source_checkpoint,
+                };
+                let routed_indexer_mailboxes: Vec<Mailbox<IndexerMessage>> = routes
+                    .iter()
+                    .map(|(_, routed_indexer)| routed_indexer.indexer_mailbox.clone())
+                    .collect();
+                let doc_router = DocRouter::new(&routing_params.field, default_indexer, routes);
+                let (doc_router_mailbox, doc_router_handler) = ctx
+                    .spawn_actor(doc_router)
+                    .set_kill_switch(self.kill_switch.clone())
+                    .spawn_sync();
+                // The sources slow down as soon as one of the indexers falls behind.
+                doc_router_mailbox.forward_fill_ratio_of(&indexer_mailbox);
+                for routed_indexer_mailbox in &routed_indexer_mailboxes {
+                    doc_router_mailbox.forward_fill_ratio_of(routed_indexer_mailbox);
+                }
+                (
+                    doc_router_mailbox,
+                    Some(doc_router_handler),
+                    resume_checkpoint(&checkpoints),
+                )
+            } else {
+                (indexer_mailbox.clone(), None, source_checkpoint)
+            };
+        // When the pipeline runs several sources, each source feeds the indexer through a
+        // tagger, so that the indexer can tell the batches of the sources apart, and so that
+        // the end of one source does not stop the indexer. Each source resumes from its own
+        // checkpoint.
+        let mut source_taggers = Vec::new();
+        let batch_sink = if self.params.additional_sources.is_empty() {
+            batch_sink
+        } else {
+            let source_tagger =
+                SourceTagger::new(self.params.source.source_id.clone(), batch_sink.clone());
+            let (source_tagger_mailbox, source_tagger_handler) = ctx
+                .spawn_actor(source_tagger)
+                .set_kill_switch(self.kill_switch.clone())
+                .spawn_sync();
+            source_tagger_mailbox.forward_fill_ratio_of(&batch_sink);
+            source_taggers.push(source_tagger_handler);
+            source_tagger_mailbox
+        };
+        let mut additional_sources = Vec::with_capacity(self.params.additional_sources.len());
+        for (source_config, (additional_source_mailbox, additional_source_inbox)) in self
+            .params
+            .additional_sources
+            .iter()
+            .zip(additional_source_mailboxes_and_inboxes)
+        {
+            let additional_source_checkpoint = index_metadata
+                .checkpoint
+                .source_checkpoint(&source_config.source_id)
+                .cloned()
+                .unwrap_or_default();
+            let source_tagger =
+                SourceTagger::new(source_config.source_id.clone(), indexer_mailbox.clone());
+            let (source_tagger_mailbox, source_tagger_handler) = ctx
+                .spawn_actor(source_tagger)
+                .set_kill_switch(self.kill_switch.clone())
+                .spawn_sync();
+            source_tagger_mailbox.forward_fill_ratio_of(&indexer_mailbox);
+            let additional_source = quickwit_supported_sources()
+                .load_source(source_config.clone(), additional_source_checkpoint)
+                .await?;
+            let additional_actor_source =
+                SourceActor::new(additional_source, source_tagger_mailbox);
+            let (_additional_source_mailbox, additional_source_handler) = ctx
+                .spawn_actor(additional_actor_source)
+                .set_kill_switch(self.kill_switch.clone())
+                .set_mailboxes(additional_source_mailbox, additional_source_inbox)
+                .spawn_async();
+            additional_sources.push(additional_source_handler);
+            source_taggers.push(source_tagger_handler);
+        }
+        let source = quickwit_supported_sources()
+            .load_source(self.params.source.clone(), source_checkpoint)
+            .await?;
+        let actor_source = SourceActor::new(source, batch_sink);
+        let (_source_mailbox, source_handler) = ctx
+            .spawn_actor(actor_source)
+            .set_kill_switch(self.kill_switch.clone())
+            .set_mailboxes(source_mailbox, source_inbox)
+            .spawn_async();
+
+        // Increment generation once we are sure there will be no spawning error.
+        self.previous_generations_statistics = self.statistics.clone();
+        self.statistics.generation += 1;
+        self.handlers = Some(IndexingPipelineHandler {
+            source: source_handler,
+            additional_sources,
+            source_taggers,
+            doc_router_opt: doc_router_handler_opt,
+            index_chain,
+            routed_index_chains,
+        });
+        Ok(())
+    }
+
+    /// Spawns the indexing and merging actors of one of the indexes fed by the pipeline and
+    /// returns the mailbox of its indexer.
+    async fn spawn_index_chain(
+        &self,
+        ctx: &ActorContext<Self>,
+        chain_params: IndexChainParams<'_>,
+        source_mailbox_opt: Option<Mailbox<SourceActorMessage>>,
+        additional_source_mailboxes: Vec<Mailbox<SourceActorMessage>>,
+    ) -> anyhow::Result<(Mailbox<IndexerMessage>, IndexChainHandler)> {
         let stable_multitenant_merge_policy = StableMultitenantWithTimestampMergePolicy {
-            demux_enabled: self.params.indexing_settings.demux_enabled,
-            demux_factor: self.params.indexing_settings.merge_policy.demux_factor,
-            demux_field_name: self.params.indexing_settings.demux_field.clone(),
-            merge_enabled: self.params.indexing_settings.merge_enabled,
-            merge_factor: self.params.indexing_settings.merge_policy.merge_factor,
-            max_merge_factor: self.params.indexing_settings.merge_policy.max_merge_factor,
-            split_num_docs_target: self.params.indexing_settings.split_num_docs_target,
+            demux_enabled: chain_params.indexing_settings.demux_enabled,
+            demux_factor: chain_params.indexing_settings.merge_policy.demux_factor,
+            demux_field_name: chain_params.indexing_settings.demux_field.clone(),
+            merge_enabled: chain_params.indexing_settings.merge_enabled,
+            merge_factor: chain_params.indexing_settings.merge_policy.merge_factor,
+            max_merge_factor: chain_params.indexing_settings.merge_policy.max_merge_factor,
+            split_num_docs_target: chain_params.indexing_settings.split_num_docs_target,
             ..Default::default()
         };
-        let merge_policy: Arc<dyn MergePolicy> = Arc::new(stable_multitenant_merge_policy);
+        let merge_policy: Arc<dyn MergePolicy> = if chain_params.compaction_time_range_opt.is_some()
+        {
+            Arc::new(CompactionMergePolicy {
+                max_merge_factor: chain_params.indexing_settings.merge_policy.max_merge_factor,
+                split_num_docs_target: chain_params.indexing_settings.split_num_docs_target,
+            })
+        } else {
+            Arc::new(stable_multitenant_merge_policy)
+        };
         info!(
-            root_dir = %self.params.indexing_directory.path().display(),
+            index = %chain_params.index_id,
+            root_dir = %chain_params.indexing_directory.path().display(),
             merge_policy = ?merge_policy,
             "spawn-indexing-pipeline",
         );
+        let indexing_storage = account_storage_costs(
+            chain_params.storage.clone(),
+            chain_params.index_id,
+            StorageActivity::Indexing,
+        );
         let split_store = IndexingSplitStore::create_with_local_store(
-            self.params.storage.clone(),
-            self.params.indexing_directory.cache_directory.as_path(),
+            indexing_storage,
+            chain_params.indexing_directory.cache_directory.as_path(),
             IndexingSplitStoreParams {
                 max_num_bytes: self.params.split_store_max_num_bytes,
                 max_num_splits: self.params.split_store_max_num_splits,
@@ -230,7 +576,7 @@ impl IndexingPipeline {
         let published_splits = self
             .params
             .metastore
-            .list_splits(&self.params.index_id, SplitState::Published, None, None)
+            .list_splits(chain_params.index_id, SplitState::Published, None, None)
             .await?
             .into_iter()
             .map(|split| split.split_metadata)
@@ -238,6 +584,13 @@ impl IndexingPipeline {
         split_store
             .remove_dangling_splits(&published_splits)
             .await?;
+        // The merge actors share the local split store, but their requests to the remote storage
+        // are attributed to merges.
+        let merge_split_store = split_store.with_remote_storage(account_storage_costs(
+            chain_params.storage.clone(),
+            chain_params.index_id,
+            StorageActivity::Merge,
+        ));
 
         let (merge_planner_mailbox, merge_planner_inbox) =
             create_mailbox::<<MergePlanner as Actor>::Message>(
@@ -245,24 +598,13 @@ impl IndexingPipeline {
                 QueueCapacity::Unbounded,
             );
 
-        // Garbage colletor
-        let garbage_collector = GarbageCollector::new(
-            self.params.index_id.clone(),
-            split_store.clone(),
-            self.params.metastore.clone(),
-        );
-        let (garbage_collector_mailbox, garbage_collector_handler) = ctx
-            .spawn_actor(garbage_collector)
-            .set_kill_switch(self.kill_switch.clone())
-            .spawn_async();
-
         // Merge publisher
         let merge_publisher = Publisher::new(
             PublisherType::MergePublisher,
             self.params.source.source_id.clone(),
             self.params.metastore.clone(),
             merge_planner_mailbox.clone(),
-            garbage_collector_mailbox.clone(),
+            None,
         );
         let (merge_publisher_mailbox, merge_publisher_handler) = ctx
             .spawn_actor(merge_publisher)
@@ -273,7 +615,7 @@ impl IndexingPipeline {
         let merge_uploader = Uploader::new(
             "MergeUploader",
             self.params.metastore.clone(),
-            split_store.clone(),
+            merge_split_store.clone(),
             merge_publisher_mailbox,
         );
         let (merge_uploader_mailbox, merge_uploader_handler) = ctx
@@ -282,7 +624,7 @@ impl IndexingPipeline {
             .spawn_async();
 
         // Merge Packager
-        let index_schema = self.params.doc_mapper.schema();
+        let index_schema = chain_params.doc_mapper.schema();
         let tag_fields = self
             .params
             .doc_mapper
@@ -307,21 +649,29 @@ impl IndexingPipeline {
             .spawn_sync();
 
         let merge_executor = MergeExecutor::new(
-            self.params.index_id.clone(),
+            chain_params.index_id.to_string(),
             merge_packager_mailbox,
-            self.params.indexing_settings.timestamp_field.clone(),
-            self.params.indexing_settings.demux_field.clone(),
-            self.params.indexing_settings.split_num_docs_target as usize,
-            self.params.indexing_settings.split_num_docs_target as usize * 2,
+            chain_params.indexing_settings.timestamp_field.clone(),
+            chain_params.indexing_settings.demux_field.clone(),
+            chain_params.indexing_settings.split_num_docs_target as usize,
+            chain_params.indexing_settings.split_num_docs_target as usize * 2,
+            chain_params.indexing_settings.versioning.clone(),
         );
         let (merge_executor_mailbox, merge_executor_handler) = ctx
             .spawn_actor(merge_executor)
             .set_kill_switch(self.kill_switch.clone())
             .spawn_sync();
 
+        let scratch_quota = ScratchQuota::new(
+            chain_params.index_id,
+            &self.params.source.source_id,
+            chain_params.indexing_directory.scratch_directory.clone(),
+            self.params.max_scratch_num_bytes as u64,
+        );
         let merge_split_downloader = MergeSplitDownloader {
-            scratch_directory: self.params.indexing_directory.scratch_directory.clone(),
-            storage: split_store.clone(),
+            scratch_directory: chain_params.indexing_directory.scratch_directory.clone(),
+            scratch_quota,
+            storage: merge_split_store,
             merge_executor_mailbox,
         };
         let (merge_split_downloader_mailbox, merge_split_downloader_handler) = ctx
@@ -330,10 +680,22 @@ impl IndexingPipeline {
             .spawn_async();
 
         // Merge planner
-        let published_split_metadatas = published_splits.into_iter().collect_vec();
+        let published_split_metadatas = match chain_params.compaction_time_range_opt {
+            Some(compaction_time_range) => published_splits
+                .into_iter()
+                .filter(|split| {
+                    split.time_range.as_ref().map_or(true, |time_range| {
+                        *time_range.start() < compaction_time_range.end
+                            && compaction_time_range.start <= *time_range.end()
+                    })
+                })
+                .collect_vec(),
+            None => published_splits.into_iter().collect_vec(),
+        };
         let merge_planner = MergePlanner::new(
             published_split_metadatas,
             merge_policy.clone(),
+            self.params.max_scratch_num_bytes as u64,
             merge_split_downloader_mailbox,
         );
         let (merge_planner_mailbox, merge_planner_handler) = ctx
@@ -343,13 +705,22 @@ impl IndexingPipeline {
             .spawn_sync();
 
         // Publisher
-        let publisher = Publisher::new(
+        let mut publisher = Publisher::new(
             PublisherType::MainPublisher,
             self.params.source.source_id.clone(),
             self.params.metastore.clone(),
             merge_planner_mailbox,
-            garbage_collector_mailbox,
+            source_mailbox_opt,
         );
+        for (source_config, source_mailbox) in self
+            .params
+            .additional_sources
+            .iter()
+            .zip(additional_source_mailboxes)
+        {
+            publisher =
+                publisher.with_source_mailbox(source_config.source_id.clone(), source_mailbox);
+        }
         let (publisher_mailbox, publisher_handler) = ctx
             .spawn_actor(publisher)
             .set_kill_switch(self.kill_switch.clone())
@@ -375,51 +746,54 @@ impl IndexingPipeline {
             .spawn_sync();
 
         // Indexer
-        let indexer = Indexer::new(
-            self.params.index_id.clone(),
-            self.params.doc_mapper.clone(),
-            self.params.indexing_directory.clone(),
-            self.params.indexing_settings.clone(),
+        let mut indexer = Indexer::new(
+            chain_params.index_id.to_string(),
+            chain_params.doc_mapper.clone(),
+            chain_params.indexing_directory.clone(),
+            chain_params.indexing_settings.clone(),
+            indexer_source(&self.params.source)?,
             packager_mailbox,
         );
+        for source_config in &self.params.additional_sources {
+            indexer = indexer.with_source(indexer_source(source_config)?);
+        }
+
+        // Dead-letter writer
+        let mut dead_letter_writer_opt = None;
+        if let Some(dead_letter) = &chain_params.indexing_settings.dead_letter {
+            let dead_letter_uri = Uri::try_new(&dead_letter.uri)?;
+            let dead_letter_storage = quickwit_storage_uri_resolver()
+                .resolve(dead_letter_uri.as_ref())
+                .with_context(|| {
+                    format!(
+                        "Failed to resolve dead-letter storage `{}`.",
+                        dead_letter_uri
+                    )
+                })?;
+            let dead_letter_writer = DeadLetterWriter::new(
+                chain_params.index_id.to_string(),
+                self.params.source.source_id.clone(),
+                dead_letter_storage,
+                dead_letter.flush_interval(),
+            );
+            let (dead_letter_writer_mailbox, dead_letter_writer_handler) = ctx
+                .spawn_actor(dead_letter_writer)
+                .set_kill_switch(self.kill_switch.clone())
+                .spawn_async();
+            indexer = indexer.with_dead_letter_writer(dead_letter_writer_mailbox);
+            dead_letter_writer_opt = Some(dead_letter_writer_handler);
+        }
         let (indexer_mailbox, indexer_handler) = ctx
             .spawn_actor(indexer)
             .set_kill_switch(self.kill_switch.clone())
             .spawn_sync();
 
-        // Fetch index_metadata to be sure to have the last updated checkpoint.
-        let index_metadata = self
-            .params
-            .metastore
-            .index_metadata(&self.params.index_id)
-            .await?;
-        let source_checkpoint = index_metadata
-            .checkpoint
-            .source_checkpoint(&self.params.source.source_id)
-            .cloned()
-            .unwrap_or_default(); // TODO Have a stricter check.
-        let source = quickwit_supported_sources()
-            .load_source(self.params.source.clone(), source_checkpoint)
-            .await?;
-        let actor_source = SourceActor {
-            source,
-            batch_sink: indexer_mailbox,
-        };
-        let (_source_mailbox, source_handler) = ctx
-            .spawn_actor(actor_source)
-            .set_kill_switch(self.kill_switch.clone())
-            .spawn_async();
-
-        // Increment generation once we are sure there will be no spawning error.
-        self.previous_generations_statistics = self.statistics.clone();
-        self.statistics.generation += 1;
-        self.handlers = Some(IndexingPipelineHandler {
-            source: source_handler,
+        let index_chain = IndexChainHandler {
             indexer: indexer_handler,
+            dead_letter_writer_opt,
             packager: packager_handler,
             uploader: uploader_handler,
             publisher: publisher_handler,
-            garbage_collector: garbage_collector_handler,
 
             merge_planner: merge_planner_handler,
             merge_split_downloader: merge_split_downloader_handler,
@@ -427,8 +801,8 @@ impl IndexingPipeline {
             merge_packager: merge_packager_handler,
             merge_uploader: merge_uploader_handler,
             merge_publisher: merge_publisher_handler,
-        });
-        Ok(())
+        };
+        Ok((indexer_mailbox, index_chain))
     }
 
     // retry_count, wait_time
@@ -471,7 +845,20 @@ impl IndexingPipeline {
     async fn process_supervise(&mut self, ctx: &ActorContext<Self>) -> Result<(), ActorExitStatus> {
         if self.handlers.is_some() {
             match self.healthcheck() {
-                Health::Healthy => {}
+                Health::Healthy => {
+                    if self.apply_source_pauses(ctx).await {
+                        info!(
+                            index_id = %self.params.index_id,
+                            "Restarting indexing pipeline from the reset source checkpoint."
+                        );
+                        self.terminate().await;
+                        ctx.schedule_self_msg(
+                            quickwit_actors::HEARTBEAT,
+                            IndexingPipelineMessage::Spawn { retry_count: 0 },
+                        )
+                        .await;
+                    }
+                }
                 Health::FailureOrUnhealthy => {
                     self.terminate().await;
                     ctx.schedule_self_msg(
@@ -493,22 +880,76 @@ impl IndexingPipeline {
         Ok(())
     }
 
+    /// Pauses the source actors of the sources paused on the node since the last supervision,
+    /// and resumes the ones of the sources resumed since then.
+    ///
+    /// Returns true if the pipeline must be restarted rather than resumed, because the
+    /// checkpoint of a resumed source was reset while it was paused.
+    async fn apply_source_pauses(&mut self, ctx: &ActorContext<Self>) -> bool {
+        let handlers = if let Some(handlers) = self.handlers.as_ref() {
+            handlers
+        } else {
+            return false;
+        };
+        let sources = std::iter::once((&self.params.source, &handlers.source)).chain(
+            self.params
+                .additional_sources
+                .iter()
+                .zip(&handlers.additional_sources),
+        );
+        for (source_config, source_handler) in sources {
+            let source_id = &source_config.source_id;
+            let should_pause = is_source_paused(&self.params.index_id, source_id);
+            if should_pause == self.paused_source_ids.contains(source_id) {
+                continue;
+            }
+            let message = if should_pause {
+                info!(index_id = %self.params.index_id, source_id = %source_id, "Pausing source.");
+                self.paused_source_ids.insert(source_id.clone());
+                SourceActorMessage::Pause
+            } else if take_source_checkpoint_reset(&self.params.index_id, source_id) {
+                return true;
+            } else {
+                info!(index_id = %self.params.index_id, source_id = %source_id, "Resuming source.");
+                self.paused_source_ids.remove(source_id);
+                SourceActorMessage::Resume
+            };
+            // The source actor may have exited already, in which case there is nothing to pause.
+            let _ = ctx.send_message(source_handler.mailbox(), message).await;
+        }
+        false
+    }
+
     async fn terminate(&mut self) {
         self.kill_switch.kill();
         if let Some(handlers) = self.handlers.take() {
+            let IndexingPipelineHandler {
+                source,
+                additional_sources,
+                source_taggers,
+                doc_router_opt,
+                index_chain,
+                routed_index_chains,
+            } = handlers;
+            let kill_doc_router = async move {
+                if let Some(doc_router) = doc_router_opt {
+                    doc_router.kill().await;
+                }
+            };
+            let kill_routed_index_chains = futures::future::join_all(
+                routed_index_chains.into_iter().map(IndexChainHandler::kill),
+            );
+            let kill_additional_sources =
+                futures::future::join_all(additional_sources.into_iter().map(ActorHandle::kill));
+            let kill_source_taggers =
+                futures::future::join_all(source_taggers.into_iter().map(ActorHandle::kill));
             tokio::join!(
-                handlers.source.kill(),
-                handlers.indexer.kill(),
-                handlers.packager.kill(),
-                handlers.uploader.kill(),
-                handlers.publisher.kill(),
-                handlers.garbage_collector.kill(),
-                handlers.merge_planner.kill(),
-                handlers.merge_split_downloader.kill(),
-                handlers.merge_executor.kill(),
-                handlers.merge_packager.kill(),
-                handlers.merge_uploader.kill(),
-                handlers.merge_publisher.kill(),
+                source.kill(),
+                kill_additional_sources,
+                kill_source_taggers,
+                kill_doc_router,
+                index_chain.kill(),
+                kill_routed_index_chains,
             );
         }
     }
@@ -537,6 +978,15 @@ impl AsyncActor for IndexingPipeline {
         }
         Ok(())
     }
+
+    async fn finalize(
+        &mut self,
+        _exit_status: &ActorExitStatus,
+        _ctx: &ActorContext<Self>,
+    ) -> anyhow::Result<()> {
+        clear_pipeline_throughput(&self.params.index_id, &self.params.source.source_id);
+        Ok(())
+    }
 }
 
 pub struct IndexingPipelineParams {
@@ -547,8 +997,17 @@ pub struct IndexingPipelineParams {
     pub source: SourceConfig,
     pub split_store_max_num_bytes: usize,
     pub split_store_max_num_splits: usize,
+    /// Maximum size of the scratch directory of the pipeline, see [`ScratchQuota`].
+    pub max_scratch_num_bytes: usize,
     pub metastore: Arc<dyn Metastore>,
     pub storage: Arc<dyn Storage>,
+    /// When set, the pipeline compacts the published splits overlapping this time range with a
+    /// [`CompactionMergePolicy`] instead of following the index merge policy.
+    pub compaction_time_range_opt: Option<Range<i64>>,
+    /// Indexes the source routes documents to, following its `routing` parameters.
+    pub routed_indexes: Vec<RoutedIndexParams>,
+    /// Other sources of the index run concurrently with `source`, feeding the same indexer.
+    pub additional_sources: Vec<SourceConfig>,
 }
 
 impl IndexingPipelineParams {
@@ -558,6 +1017,7 @@ impl IndexingPipelineParams {
         indexing_dir_path: PathBuf,
         split_store_max_num_bytes: usize,
         split_store_max_num_splits: usize,
+        max_scratch_num_bytes: usize,
         metastore: Arc<dyn Metastore>,
         storage: Arc<dyn Storage>,
     ) -> anyhow::Result<Self> {
@@ -578,8 +1038,68 @@ impl IndexingPipelineParams {
             source,
             split_store_max_num_bytes,
             split_store_max_num_splits,
+            max_scratch_num_bytes,
             metastore,
             storage,
+            compaction_time_range_opt: None,
+            routed_indexes: Vec::new(),
+            additional_sources: Vec::new(),
+        })
+    }
+}
+
+/// Builds the indexer view of a source, with its document transform and filter.
+fn indexer_source(source: &SourceConfig) -> anyhow::Result<IndexerSource> {
+    let doc_transform_opt = source
+        .transform
+        .as_ref()
+        .map(DocTransform::try_new)
+        .transpose()?;
+    let doc_filter_opt = source.filter.as_ref().map(DocFilter::try_new).transpose()?;
+    Ok(IndexerSource {
+        source_id: source.source_id.clone(),
+        decodes_rows: source.decodes_rows(),
+        doc_transform_opt,
+        doc_filter_opt,
+    })
+}
+
+/// Index fed with the documents a source routes to it.
+pub struct RoutedIndexParams {
+    pub index_id: String,
+    /// Values of the routing field of the documents routed to the index.
+    pub values: Vec<JsonValue>,
+    pub doc_mapper: Arc<dyn DocMapper>,
+    pub indexing_directory: IndexingDirectory,
+    pub indexing_settings: IndexingSettings,
+    pub storage: Arc<dyn Storage>,
+}
+
+impl RoutedIndexParams {
+    pub async fn try_new(
+        index_metadata: IndexMetadata,
+        values: Vec<JsonValue>,
+        routing_index_id: &str,
+        source_id: &str,
+        indexing_dir_path: PathBuf,
+        storage: Arc<dyn Storage>,
+    ) -> anyhow::Result<Self> {
+        let doc_mapper = build_doc_mapper(
+            &index_metadata.doc_mapping,
+            &index_metadata.search_settings,
+            &index_metadata.indexing_settings,
+        )?;
+        let indexing_directory_path = indexing_dir_path
+            .join(&index_metadata.index_id)
+            .join(format!("{}.{}", routing_index_id, source_id));
+        let indexing_directory = IndexingDirectory::create_in_dir(indexing_directory_path).await?;
+        Ok(Self {
+            index_id: index_metadata.index_id,
+            values,
+            doc_mapper,
+            indexing_directory,
+            indexing_settings: index_metadata.indexing_settings,
+            storage,
         })
     }
 }
@@ -590,7 +1110,7 @@ mod tests {
     use std::sync::Arc;
 
     use quickwit_actors::Universe;
-    use quickwit_config::{IndexingSettings, SourceParams};
+    use quickwit_config::{DocRoute, DocRoutingParams, IndexingSettings, SourceParams};
     use quickwit_doc_mapper::default_doc_mapper_for_tests;
     use quickwit_metastore::{IndexMetadata, MetastoreError, MockMetastore};
     use quickwit_storage::RamStorage;
@@ -674,6 +1194,9 @@ mod tests {
         let source_config = SourceConfig {
             source_id: "test-source".to_string(),
             source_params: SourceParams::file(PathBuf::from("data/test_corpus.json")),
+            filter: None,
+            routing: None,
+            transform: None,
         };
         let indexing_pipeline_params = IndexingPipelineParams {
             index_id: index_id.to_string(),
@@ -682,9 +1205,13 @@ mod tests {
             indexing_settings: IndexingSettings::for_test(),
             split_store_max_num_bytes: 10_000_000,
             split_store_max_num_splits: 100,
+            max_scratch_num_bytes: 10_000_000_000,
             source: source_config,
             metastore: Arc::new(metastore),
             storage: Arc::new(RamStorage::default()),
+            compaction_time_range_opt: None,
+            routed_indexes: Vec::new(),
+            additional_sources: Vec::new(),
         };
         let pipeline = IndexingPipeline::new(indexing_pipeline_params);
         let (_pipeline_mailbox, pipeline_handler) = universe.spawn_actor(pipeline).spawn_async();
@@ -755,6 +1282,9 @@ mod tests {
         let source = SourceConfig {
             source_id: "test-source".to_string(),
             source_params: SourceParams::file(PathBuf::from("data/test_corpus.json")),
+            filter: None,
+            routing: None,
+            transform: None,
         };
         let pipeline_params = IndexingPipelineParams {
             index_id: "test-index".to_string(),
@@ -763,9 +1293,13 @@ mod tests {
             indexing_settings: IndexingSettings::for_test(),
             split_store_max_num_bytes: 10_000_000,
             split_store_max_num_splits: 100,
+            max_scratch_num_bytes: 10_000_000_000,
             source,
             metastore: Arc::new(metastore),
             storage: Arc::new(RamStorage::default()),
+            compaction_time_range_opt: None,
+            routed_indexes: Vec::new(),
+            additional_sources: Vec::new(),
         };
         let pipeline = IndexingPipeline::new(pipeline_params);
         let (_pipeline_mailbox, pipeline_handler) = universe.spawn_actor(pipeline).spawn_async();
@@ -776,4 +1310,149 @@ mod tests {
         assert_eq!(pipeline_statistics.num_published_splits, 1);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_indexing_pipeline_with_multiple_sources() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let mut metastore = MockMetastore::default();
+        metastore
+            .expect_index_metadata()
+            .withf(|index_id| index_id == "test-index")
+            .returning(move |_| {
+                Ok(IndexMetadata::for_test(
+                    "test-index",
+                    "ram://indexes/my-index",
+                ))
+            });
+        metastore
+            .expect_list_splits()
+            .returning(|_, _, _, _| Ok(Vec::new()));
+        metastore
+            .expect_mark_splits_for_deletion()
+            .returning(|_, _| Ok(()));
+        metastore
+            .expect_stage_split()
+            .withf(move |index_id, _metadata| -> bool { index_id == "test-index" })
+            .times(1)
+            .returning(|_, _| Ok(()));
+        // Both sources feed a single split, which records the position reached by each source.
+        metastore
+            .expect_publish_multi_source_splits()
+            .withf(move |index_id, splits, checkpoint_delta| -> bool {
+                index_id == "test-index"
+                    && splits.len() == 1
+                    && checkpoint_delta.num_sources() == 2
+                    && checkpoint_delta.iter().all(|(_, source_delta)| {
+                        format!("{:?}", source_delta)
+                            .ends_with(":(00000000000000000000..00000000000000000070])")
+                    })
+            })
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        let universe = Universe::new();
+        let source = |source_id: &str| SourceConfig {
+            source_id: source_id.to_string(),
+            source_params: SourceParams::file(PathBuf::from("data/test_corpus.json")),
+            filter: None,
+            routing: None,
+            transform: None,
+        };
+        let pipeline_params = IndexingPipelineParams {
+            index_id: "test-index".to_string(),
+            doc_mapper: Arc::new(default_doc_mapper_for_tests()),
+            indexing_directory: IndexingDirectory::for_test().await?,
+            indexing_settings: IndexingSettings::for_test(),
+            split_store_max_num_bytes: 10_000_000,
+            split_store_max_num_splits: 100,
+            max_scratch_num_bytes: 10_000_000_000,
+            source: source("test-source"),
+            metastore: Arc::new(metastore),
+            storage: Arc::new(RamStorage::default()),
+            compaction_time_range_opt: None,
+            routed_indexes: Vec::new(),
+            additional_sources: vec![source("other-test-source")],
+        };
+        let pipeline = IndexingPipeline::new(pipeline_params);
+        let (_pipeline_mailbox, pipeline_handler) = universe.spawn_actor(pipeline).spawn_async();
+        let (pipeline_exit_status, pipeline_statistics) = pipeline_handler.join().await;
+        assert!(pipeline_exit_status.is_success());
+        assert_eq!(pipeline_statistics.generation, 1);
+        assert_eq!(pipeline_statistics.num_published_splits, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_indexing_pipeline_with_routing() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let mut metastore = MockMetastore::default();
+        metastore
+            .expect_index_metadata()
+            .returning(|index_id| Ok(IndexMetadata::for_test(index_id, "ram://indexes/my-index")));
+        metastore
+            .expect_list_splits()
+            .returning(|_, _, _, _| Ok(Vec::new()));
+        metastore
+            .expect_mark_splits_for_deletion()
+            .returning(|_, _| Ok(()));
+        metastore
+            .expect_stage_split()
+            .times(2)
+            .returning(|_, _| Ok(()));
+        // Both indexes publish the whole checkpoint delta of the source.
+        metastore
+            .expect_publish_splits()
+            .withf(|index_id, source_id, splits, checkpoint_delta| -> bool {
+                (index_id == "test-index" || index_id == "routed-index")
+                    && source_id == "test-source"
+                    && splits.len() == 1
+                    && format!("{:?}", checkpoint_delta)
+                        .ends_with(":(00000000000000000000..00000000000000000070])")
+            })
+            .times(2)
+            .returning(|_, _, _, _| Ok(()));
+        let universe = Universe::new();
+        let source = SourceConfig {
+            source_id: "test-source".to_string(),
+            source_params: SourceParams::file(PathBuf::from("data/test_corpus.json")),
+            filter: None,
+            routing: Some(DocRoutingParams {
+                field: "body".to_string(),
+                routes: vec![DocRoute {
+                    index_id: "routed-index".to_string(),
+                    values: vec![serde_json::json!("tax"), serde_json::json!("payer")],
+                }],
+            }),
+            transform: None,
+        };
+        let routed_index = RoutedIndexParams {
+            index_id: "routed-index".to_string(),
+            values: vec![serde_json::json!("tax"), serde_json::json!("payer")],
+            doc_mapper: Arc::new(default_doc_mapper_for_tests()),
+            indexing_directory: IndexingDirectory::for_test().await?,
+            indexing_settings: IndexingSettings::for_test(),
+            storage: Arc::new(RamStorage::default()),
+        };
+        let pipeline_params = IndexingPipelineParams {
+            index_id: "test-index".to_string(),
+            doc_mapper: Arc::new(default_doc_mapper_for_tests()),
+            indexing_directory: IndexingDirectory::for_test().await?,
+            indexing_settings: IndexingSettings::for_test(),
+            split_store_max_num_bytes: 10_000_000,
+            split_store_max_num_splits: 100,
+            max_scratch_num_bytes: 10_000_000_000,
+            source,
+            metastore: Arc::new(metastore),
+            storage: Arc::new(RamStorage::default()),
+            compaction_time_range_opt: None,
+            routed_indexes: vec![routed_index],
+            additional_sources: Vec::new(),
+        };
+        let pipeline = IndexingPipeline::new(pipeline_params);
+        let (_pipeline_mailbox, pipeline_handler) = universe.spawn_actor(pipeline).spawn_async();
+        let (pipeline_exit_status, pipeline_statistics) = pipeline_handler.join().await;
+        assert!(pipeline_exit_status.is_success());
+        assert_eq!(pipeline_statistics.num_docs, 4);
+        assert_eq!(pipeline_statistics.num_published_splits, 2);
+        Ok(())
+    }
 }
@@ -17,24 +17,42 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::{bail, Context};
 use async_trait::async_trait;
+use byte_unit::Byte;
 use quickwit_actors::{
     Actor, ActorContext, ActorExitStatus, ActorHandle, AsyncActor, Health, Mailbox, Observation,
     Supervisable, Universe,
 };
-use quickwit_config::{IndexerConfig, SourceConfig, SourceParams, VecSourceParams};
-use quickwit_metastore::{IndexMetadata, Metastore};
+use quickwit_config::{
+    DisabledSourceConfig, IndexerConfig, QuickwitConfig, SourceConfig, SourceParams,
+    VecSourceParams,
+};
+use quickwit_metastore::{
+    write_index_metadata, IndexMetadata, LifecyclePhase, Metastore, MetastoreError,
+};
 use quickwit_storage::StorageUriResolver;
 use serde::Serialize;
-use tokio::sync::oneshot;
-use tracing::{error, info};
+use tokio::sync::{oneshot, watch};
+use tracing::{error, info, warn};
+
+use crate::actors::RoutedIndexParams;
+use crate::models::clean_up_scratch_directories;
+use crate::write_block::{DiskUsage, DISK_TOTAL_NUM_BYTES, DISK_USED_NUM_BYTES};
+use crate::{
+    apply_write_block, write_block, write_blocks, IndexingPipeline, IndexingPipelineParams,
+    IndexingStatistics, WriteBlockReason,
+};
 
-use crate::{IndexingPipeline, IndexingPipelineParams, IndexingStatistics};
+/// Interval between two checks of the lifecycle of the followed indexes, see
+/// `apply_lifecycle_updates`.
+const LIFECYCLE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub struct IndexingPipelineId {
@@ -76,18 +94,21 @@ impl IndexingServerClient {
         receiver.await?
     }
 
-    /// Spawns a merge pipeline.
+    /// Spawns a merge pipeline. When `compaction_time_range_opt` is set, the pipeline compacts
+    /// the published splits overlapping that time range regardless of the index merge policy.
     pub async fn spawn_merge_pipeline(
         &self,
         index_id: String,
         merge_enabled: bool,
         demux_enabled: bool,
+        compaction_time_range_opt: Option<Range<i64>>,
     ) -> anyhow::Result<IndexingPipelineId> {
         let (sender, receiver) = oneshot::channel();
         let message = IndexingServerMessage::SpawnMergePipeline {
             index_id,
             merge_enabled,
             demux_enabled,
+            compaction_time_range_opt,
             sender,
         };
         self.universe.send_message(&self.mailbox, message).await?;
@@ -124,6 +145,27 @@ impl IndexingServerClient {
         receiver.await?
     }
 
+    /// Makes the indexing pipelines spawned from now on leave the merges of their splits to the
+    /// janitor of the cluster, which runs merge pipelines for every index. The merge pipelines
+    /// spawned with `spawn_merge_pipeline` still merge.
+    pub async fn delegate_merges_to_janitor(&self) -> anyhow::Result<()> {
+        let message = IndexingServerMessage::DelegateMergesToJanitor;
+        self.universe.send_message(&self.mailbox, message).await?;
+        Ok(())
+    }
+
+    /// Makes the indexing server follow the reloads of the node config: the pipelines of the
+    /// sources that get disabled are stopped, and the pipelines of the sources that get
+    /// enabled again are respawned for the indexes passed to `spawn_pipelines`.
+    pub async fn follow_config(
+        &self,
+        config_rx: watch::Receiver<QuickwitConfig>,
+    ) -> anyhow::Result<()> {
+        let message = IndexingServerMessage::FollowConfig { config_rx };
+        self.universe.send_message(&self.mailbox, message).await?;
+        Ok(())
+    }
+
     pub async fn observe_server(&self) -> Observation<<IndexingServer as Actor>::ObservableState> {
         self.handle.observe().await
     }
@@ -164,13 +206,54 @@ pub struct IndexingServer {
     indexing_dir_path: PathBuf,
     split_store_max_num_bytes: usize,
     split_store_max_num_splits: usize,
+    max_pipeline_heap_size: Byte,
+    disk_high_watermark: f64,
+    max_pipeline_scratch_num_bytes: usize,
     metastore: Arc<dyn Metastore>,
     storage_resolver: StorageUriResolver,
     pipeline_handles: HashMap<IndexingPipelineId, ActorHandle<IndexingPipeline>>,
+    disabled_sources: Vec<DisabledSourceConfig>,
+    // Pipelines running all the enabled sources of an index, see `spawn_pipelines`.
+    shared_pipeline_ids: HashSet<IndexingPipelineId>,
+    // Indexes whose sources are all indexed, see `spawn_pipelines`.
+    followed_index_ids: BTreeSet<String>,
+    // Write aliases whose write index is indexed, along with the ID of this index, see
+    // `apply_lifecycle_updates`.
+    followed_write_aliases: BTreeMap<String, String>,
+    last_lifecycle_check_opt: Option<Instant>,
+    // Indexes whose pipelines were stopped by a write block, see `apply_write_blocks`.
+    write_blocked_index_ids: BTreeSet<String>,
+    // Whether the merges of the indexing pipelines are left to the janitor, see
+    // `delegate_merges_to_janitor`.
+    merges_delegated_to_janitor: bool,
+    config_rx_opt: Option<watch::Receiver<QuickwitConfig>>,
     state: IndexingServerState,
 }
 
+fn indexing_dir_path(data_dir_path: &Path) -> PathBuf {
+    data_dir_path.join("indexing")
+}
+
 impl IndexingServer {
+    /// Empties the scratch directories left by a previous run of the node in the indexing
+    /// directories of `index_ids`. Meant to be called once, when the node starts, before the
+    /// pipelines of these indexes are spawned: the commands spawning an indexing server on the
+    /// same data directory must not empty the scratch directories of the node pipelines.
+    pub async fn clean_up_scratch_directories(data_dir_path: &Path, index_ids: &[String]) {
+        match clean_up_scratch_directories(&indexing_dir_path(data_dir_path), index_ids).await {
+            Ok(0) => {}
+            Ok(num_bytes_freed) => {
+                info!(
+                    num_bytes_freed = num_bytes_freed,
+                    "Cleaned up scratch directories left by a previous run."
+                )
+            }
+            Err(error) => {
+                warn!(error = ?error, "Failed to clean up scratch directories left by a previous run.")
+            }
+        }
+    }
+
     pub fn check_health(&self) -> Health {
         // In the future, check metrics such as available disk space.
         Health::Healthy
@@ -184,13 +267,26 @@ impl IndexingServer {
     ) -> IndexingServerClient {
         let universe = Universe::new();
         let server = Self {
-            indexing_dir_path: data_dir_path.join("indexing"),
+            indexing_dir_path: indexing_dir_path(&data_dir_path),
             split_store_max_num_bytes: indexer_config.split_store_max_num_bytes.get_bytes()
                 as usize,
             split_store_max_num_splits: indexer_config.split_store_max_num_splits,
+            max_pipeline_heap_size: indexer_config.max_pipeline_heap_size,
+            disk_high_watermark: indexer_config.disk_high_watermark,
+            max_pipeline_scratch_num_bytes: indexer_config
+                .max_pipeline_scratch_num_bytes
+                .get_bytes() as usize,
             metastore,
             storage_resolver,
             pipeline_handles: Default::default(),
+            disabled_sources: indexer_config.disabled_sources,
+            shared_pipeline_ids: Default::default(),
+            followed_index_ids: Default::default(),
+            followed_write_aliases: Default::default(),
+            last_lifecycle_check_opt: None,
+            write_blocked_index_ids: Default::default(),
+            merges_delegated_to_janitor: false,
+            config_rx_opt: None,
             state: Default::default(),
         };
         let (mailbox, handle) = universe.spawn_actor(server).spawn_async();
@@ -241,12 +337,29 @@ impl IndexingServer {
             index_id,
             source_id: source.source_id.clone(),
         };
-        let index_metadata = self.index_metadata(ctx, &pipeline_id.index_id).await?;
-        self.spawn_pipeline_inner(ctx, pipeline_id.clone(), index_metadata, source)
-            .await?;
+        let mut index_metadata = self.index_metadata(ctx, &pipeline_id.index_id).await?;
+        self.disable_merges_if_delegated(&mut index_metadata);
+        self.spawn_pipeline_inner(
+            ctx,
+            pipeline_id.clone(),
+            index_metadata,
+            source,
+            Vec::new(),
+            None,
+        )
+        .await?;
         Ok(pipeline_id)
     }
 
+    /// Disables the merges of the indexing pipelines of the index if they are left to the
+    /// janitor.
+    fn disable_merges_if_delegated(&self, index_metadata: &mut IndexMetadata) {
+        if self.merges_delegated_to_janitor {
+            index_metadata.indexing_settings.merge_enabled = false;
+            index_metadata.indexing_settings.demux_enabled = false;
+        }
+    }
+
     async fn spawn_pipelines(
         &mut self,
         ctx: &ActorContext<Self>,
@@ -254,8 +367,36 @@ impl IndexingServer {
     ) -> anyhow::Result<Vec<IndexingPipelineId>> {
         let mut pipeline_ids = Vec::new();
 
-        let index_metadata = self.index_metadata(ctx, &index_id).await?;
+        let index_metadata_res = {
+            let _protect_guard = ctx.protect_zone();
+            self.metastore.index_metadata(&index_id).await
+        };
+        let mut index_metadata = match index_metadata_res {
+            Ok(index_metadata) => index_metadata,
+            // The indexes whose lifecycle is managed are followed through their write alias.
+            Err(MetastoreError::IndexDoesNotExist { .. }) => {
+                let write_index_metadata = self.resolve_write_alias(ctx, &index_id).await?;
+                self.followed_write_aliases
+                    .insert(index_id, write_index_metadata.index_id.clone());
+                write_index_metadata
+            }
+            Err(error) => return Err(error.into()),
+        };
+        let index_id = index_metadata.index_id.clone();
+        self.disable_merges_if_delegated(&mut index_metadata);
+        self.followed_index_ids.insert(index_id.clone());
+        if index_metadata.lifecycle_state.phase != LifecyclePhase::Hot
+            && apply_write_block(&index_id, WriteBlockReason::Lifecycle)
+        {
+            info!(index_id = %index_id, "Index left the hot phase of its lifecycle, write-blocking index.");
+        }
 
+        if index_metadata.indexing_settings.shared_pipeline {
+            if let Some(pipeline_id) = self.spawn_shared_pipeline(ctx, index_metadata).await? {
+                pipeline_ids.push(pipeline_id);
+            }
+            return Ok(pipeline_ids);
+        }
         for source in index_metadata.sources.values() {
             let pipeline_id = IndexingPipelineId {
                 index_id: index_id.clone(),
@@ -264,11 +405,21 @@ impl IndexingServer {
             if self.pipeline_handles.contains_key(&pipeline_id) {
                 continue;
             }
+            if self.is_source_disabled(&pipeline_id) {
+                info!(index_id = %pipeline_id.index_id, source_id = %pipeline_id.source_id, "Source is disabled, skipping indexing pipeline.");
+                continue;
+            }
+            if let Some(reason) = write_block(&pipeline_id.index_id) {
+                info!(index_id = %pipeline_id.index_id, source_id = %pipeline_id.source_id, reason = ?reason, "Index is write-blocked, skipping indexing pipeline.");
+                continue;
+            }
             self.spawn_pipeline_inner(
                 ctx,
                 pipeline_id.clone(),
                 index_metadata.clone(),
                 source.clone(),
+                Vec::new(),
+                None,
             )
             .await?;
             pipeline_ids.push(pipeline_id);
@@ -276,12 +427,112 @@ impl IndexingServer {
         Ok(pipeline_ids)
     }
 
+    /// Spawns a single pipeline running all the enabled sources of an index. The pipeline is
+    /// identified by the first of these sources.
+    async fn spawn_shared_pipeline(
+        &mut self,
+        ctx: &ActorContext<Self>,
+        index_metadata: IndexMetadata,
+    ) -> anyhow::Result<Option<IndexingPipelineId>> {
+        let mut sources: Vec<SourceConfig> = index_metadata
+            .sources
+            .values()
+            .filter(|source| {
+                let pipeline_id = IndexingPipelineId {
+                    index_id: index_metadata.index_id.clone(),
+                    source_id: source.source_id.clone(),
+                };
+                !self.is_source_disabled(&pipeline_id)
+            })
+            .cloned()
+            .collect();
+        if sources.is_empty() {
+            return Ok(None);
+        }
+        sources.sort_by(|left, right| left.source_id.cmp(&right.source_id));
+        let source = sources.remove(0);
+        let pipeline_id = IndexingPipelineId {
+            index_id: index_metadata.index_id.clone(),
+            source_id: source.source_id.clone(),
+        };
+        if self.pipeline_handles.contains_key(&pipeline_id) {
+            return Ok(None);
+        }
+        if let Some(reason) = write_block(&pipeline_id.index_id) {
+            info!(index_id = %pipeline_id.index_id, source_id = %pipeline_id.source_id, reason = ?reason, "Index is write-blocked, skipping indexing pipeline.");
+            return Ok(None);
+        }
+        self.spawn_pipeline_inner(
+            ctx,
+            pipeline_id.clone(),
+            index_metadata,
+            source,
+            sources,
+            None,
+        )
+        .await?;
+        self.shared_pipeline_ids.insert(pipeline_id.clone());
+        Ok(Some(pipeline_id))
+    }
+
+    /// Resolves the indexes a source routes documents to.
+    async fn routed_indexes_params(
+        &self,
+        pipeline_id: &IndexingPipelineId,
+        source: &SourceConfig,
+    ) -> anyhow::Result<Vec<RoutedIndexParams>> {
+        let routes = match &source.routing {
+            Some(routing_params) => &routing_params.routes,
+            None => return Ok(Vec::new()),
+        };
+        let mut routed_indexes = Vec::with_capacity(routes.len());
+        for route in routes {
+            if let Some(reason) = write_block(&route.index_id) {
+                bail!(
+                    "Index `{}` is write-blocked ({:?}).",
+                    route.index_id,
+                    reason
+                );
+            }
+            let mut index_metadata = self.metastore.index_metadata(&route.index_id).await?;
+            self.disable_merges_if_delegated(&mut index_metadata);
+            // The routed documents are checkpointed under the id of the source in the routed
+            // index, which must not declare a source with the same id.
+            if index_metadata.sources.contains_key(&source.source_id) {
+                bail!(
+                    "Source `{}` cannot route documents to index `{}` which declares a source \
+                     with the same id.",
+                    source.source_id,
+                    route.index_id
+                );
+            }
+            let resources = &mut index_metadata.indexing_settings.resources;
+            if resources.heap_size > self.max_pipeline_heap_size {
+                resources.heap_size = self.max_pipeline_heap_size;
+            }
+            let storage = self.storage_resolver.resolve(&index_metadata.index_uri)?;
+            let routed_index = RoutedIndexParams::try_new(
+                index_metadata,
+                route.values.clone(),
+                &pipeline_id.index_id,
+                &source.source_id,
+                self.indexing_dir_path.clone(),
+                storage,
+            )
+            .await?;
+            routed_indexes.push(routed_index);
+        }
+        Ok(routed_indexes)
+    }
+
     async fn spawn_pipeline_inner(
         &mut self,
         ctx: &ActorContext<Self>,
         pipeline_id: IndexingPipelineId,
-        index_metadata: IndexMetadata,
+        mut index_metadata: IndexMetadata,
         source: SourceConfig,
+        additional_sources: Vec<SourceConfig>,
+        compaction_time_range_opt: Option<Range<i64>>,
     ) -> anyhow::Result<()> {
         if self.pipeline_handles.contains_key(&pipeline_id) {
             bail!(
@@ -290,18 +541,36 @@ impl IndexingServer {
                 pipeline_id.source_id
             );
         }
+        if let Some(reason) = write_block(&pipeline_id.index_id) {
+            bail!(
+                "Index `{}` is write-blocked ({:?}).",
+                pipeline_id.index_id,
+                reason
+            );
+        }
+        let resources = &mut index_metadata.indexing_settings.resources;
+        if resources.heap_size > self.max_pipeline_heap_size {
+            info!(index_id = %pipeline_id.index_id, heap_size = %resources.heap_size, max_pipeline_heap_size = %self.max_pipeline_heap_size, "Lowering indexing pipeline heap size to the indexer limit.");
+            resources.heap_size = self.max_pipeline_heap_size;
+        }
         let storage = self.storage_resolver.resolve(&index_metadata.index_uri)?;
 
-        let pipeline_params = IndexingPipelineParams::try_new(
+        let mut pipeline_params = IndexingPipelineParams::try_new(
             index_metadata,
             source,
             self.indexing_dir_path.clone(),
             self.split_store_max_num_bytes,
             self.split_store_max_num_splits,
+            self.max_pipeline_scratch_num_bytes,
             self.metastore.clone(),
             storage,
         )
         .await?;
+        pipeline_params.compaction_time_range_opt = compaction_time_range_opt;
+        pipeline_params.additional_sources = additional_sources;
+        pipeline_params.routed_indexes = self
+            .routed_indexes_params(&pipeline_id, &pipeline_params.source)
+            .await?;
 
         let pipeline = IndexingPipeline::new(pipeline_params);
         let (_pipeline_mailbox, pipeline_handle) = ctx.spawn_actor(pipeline).spawn_async();
@@ -316,6 +585,7 @@ impl IndexingServer {
         index_id: String,
         merge_enabled: bool,
         demux_enabled: bool,
+        compaction_time_range_opt: Option<Range<i64>>,
     ) -> anyhow::Result<IndexingPipelineId> {
         let pipeline_id = IndexingPipelineId {
             index_id,
@@ -328,13 +598,220 @@ impl IndexingServer {
         let source = SourceConfig {
             source_id: pipeline_id.source_id.clone(),
             source_params: SourceParams::Vec(VecSourceParams::default()),
+            filter: None,
+            routing: None,
+            transform: None,
         };
-        self.spawn_pipeline_inner(ctx, pipeline_id.clone(), index_metadata, source)
-            .await?;
+        self.spawn_pipeline_inner(
+            ctx,
+            pipeline_id.clone(),
+            index_metadata,
+            source,
+            Vec::new(),
+            compaction_time_range_opt,
+        )
+        .await?;
         Ok(pipeline_id)
     }
 
+    fn is_source_disabled(&self, pipeline_id: &IndexingPipelineId) -> bool {
+        self.disabled_sources.iter().any(|disabled_source| {
+            disabled_source.index_id == pipeline_id.index_id
+                && disabled_source.source_id == pipeline_id.source_id
+        })
+    }
+
+    /// Stops the pipelines of the newly disabled sources and respawns the pipelines of the
+    /// sources enabled again, if the disabled sources changed since the last check.
+    async fn apply_config_updates(&mut self, ctx: &ActorContext<Self>) {
+        let (disabled_sources, disk_high_watermark) = match &self.config_rx_opt {
+            Some(config_rx) => {
+                let indexer_config = &config_rx.borrow().indexer_config;
+                (
+                    indexer_config.disabled_sources.clone(),
+                    indexer_config.disk_high_watermark,
+                )
+            }
+            None => return,
+        };
+        self.disk_high_watermark = disk_high_watermark;
+        if disabled_sources == self.disabled_sources {
+            return;
+        }
+        // The shared pipelines of the indexes whose disabled sources changed are respawned with
+        // the sources that are now enabled.
+        let updated_index_ids: HashSet<String> = disabled_sources
+            .iter()
+            .filter(|disabled_source| !self.disabled_sources.contains(disabled_source))
+            .chain(
+                self.disabled_sources
+                    .iter()
+                    .filter(|disabled_source| !disabled_sources.contains(disabled_source)),
+            )
+            .map(|disabled_source| disabled_source.index_id.clone())
+            .collect();
+        self.disabled_sources = disabled_sources;
+
+        let disabled_pipeline_ids: Vec<IndexingPipelineId> = self
+            .pipeline_handles
+            .keys()
+            .filter(|pipeline_id| {
+                self.is_source_disabled(pipeline_id)
+                    || (self.shared_pipeline_ids.contains(pipeline_id)
+                        && updated_index_ids.contains(&pipeline_id.index_id))
+            })
+            .cloned()
+            .collect();
+        for pipeline_id in disabled_pipeline_ids {
+            self.shared_pipeline_ids.remove(&pipeline_id);
+            if let Some(pipeline_handle) = self.pipeline_handles.remove(&pipeline_id) {
+                info!(index_id = %pipeline_id.index_id, source_id = %pipeline_id.source_id, "Source was disabled or enabled, stopping indexing pipeline.");
+                pipeline_handle.kill().await;
+                self.state.num_running_pipelines -= 1;
+            }
+        }
+        for index_id in self.followed_index_ids.clone() {
+            if let Err(error) = self.spawn_pipelines(ctx, index_id.clone()).await {
+                error!(index_id = %index_id, error = ?error, "Failed to respawn indexing pipelines.");
+            }
+        }
+    }
+
+    /// Returns the metadata of the index written through the write alias `write_alias`.
+    async fn resolve_write_alias(
+        &self,
+        ctx: &ActorContext<Self>,
+        write_alias: &str,
+    ) -> anyhow::Result<IndexMetadata> {
+        let index_metadatas = {
+            let _protect_guard = ctx.protect_zone();
+            self.metastore.list_indexes_metadatas().await?
+        };
+        write_index_metadata(&index_metadatas, write_alias)
+            .cloned()
+            .with_context(|| format!("Index or write alias `{}` does not exist.", write_alias))
+    }
+
+    /// Write-blocks the followed indexes that left the hot phase of their lifecycle, and
+    /// switches the pipelines of the followed write aliases over to their new write index once
+    /// they are rolled over. The pipelines of the previous write index are stopped by
+    /// `apply_write_blocks`.
+    async fn apply_lifecycle_updates(&mut self, ctx: &ActorContext<Self>) {
+        if self.followed_index_ids.is_empty()
+            || self.last_lifecycle_check_opt.map_or(false, |last_check| {
+                last_check.elapsed() < LIFECYCLE_CHECK_INTERVAL
+            })
+        {
+            return;
+        }
+        self.last_lifecycle_check_opt = Some(Instant::now());
+        let index_metadatas_res = {
+            let _protect_guard = ctx.protect_zone();
+            self.metastore.list_indexes_metadatas().await
+        };
+        let index_metadatas = match index_metadatas_res {
+            Ok(index_metadatas) => index_metadatas,
+            Err(error) => {
+                warn!(error = ?error, "Failed to list indexes to check their lifecycle.");
+                return;
+            }
+        };
+        for index_metadata in &index_metadatas {
+            if self.followed_index_ids.contains(&index_metadata.index_id)
+                && index_metadata.lifecycle_state.phase != LifecyclePhase::Hot
+                && apply_write_block(&index_metadata.index_id, WriteBlockReason::Lifecycle)
+            {
+                info!(index_id = %index_metadata.index_id, "Index left the hot phase of its lifecycle, write-blocking index.");
+            }
+        }
+        for (write_alias, index_id) in self.followed_write_aliases.clone() {
+            let write_index_id = match write_index_metadata(&index_metadatas, &write_alias) {
+                Some(write_index_metadata) => write_index_metadata.index_id.clone(),
+                None => continue,
+            };
+            if write_index_id == index_id {
+                continue;
+            }
+            info!(write_alias = %write_alias, index_id = %index_id, write_index_id = %write_index_id, "Write alias was rolled over, switching indexing pipelines.");
+            apply_write_block(&index_id, WriteBlockReason::Lifecycle);
+            self.followed_index_ids.remove(&index_id);
+            self.followed_write_aliases
+                .insert(write_alias, write_index_id.clone());
+            if let Err(error) = self.spawn_pipelines(ctx, write_index_id.clone()).await {
+                error!(index_id = %write_index_id, error = ?error, "Failed to spawn indexing pipelines.");
+            }
+        }
+    }
+
+    /// Write-blocks the indexes with running pipelines if the scratch disk crossed its high
+    /// watermark.
+    fn check_disk_usage(&self) {
+        let disk_usage = match DiskUsage::of_path(&self.indexing_dir_path) {
+            Ok(disk_usage) => disk_usage,
+            Err(error) => {
+                warn!(error = ?error, "Failed to measure indexing disk usage.");
+                return;
+            }
+        };
+        DISK_USED_NUM_BYTES.set(disk_usage.used_num_bytes() as i64);
+        DISK_TOTAL_NUM_BYTES.set(disk_usage.total_num_bytes as i64);
+        if !disk_usage.is_above_watermark(self.disk_high_watermark) {
+            return;
+        }
+        let index_ids: BTreeSet<&String> = self
+            .pipeline_handles
+            .keys()
+            .map(|pipeline_id| &pipeline_id.index_id)
+            .collect();
+        for index_id in index_ids {
+            if apply_write_block(index_id, WriteBlockReason::DiskHighWatermark) {
+                warn!(index_id = %index_id, used_num_bytes = disk_usage.used_num_bytes(), total_num_bytes = disk_usage.total_num_bytes, disk_high_watermark = self.disk_high_watermark, "Indexing disk crossed its high watermark, write-blocking index.");
+            }
+        }
+    }
+
+    /// Stops the pipelines of the write-blocked indexes and respawns the pipelines of the
+    /// indexes whose write block was cleared.
+    async fn apply_write_blocks(&mut self, ctx: &ActorContext<Self>) {
+        self.check_disk_usage();
+        let write_blocks = write_blocks();
+        let blocked_pipeline_ids: Vec<IndexingPipelineId> = self
+            .pipeline_handles
+            .keys()
+            .filter(|pipeline_id| write_blocks.contains_key(&pipeline_id.index_id))
+            .cloned()
+            .collect();
+        for pipeline_id in blocked_pipeline_ids {
+            if let Some(pipeline_handle) = self.pipeline_handles.remove(&pipeline_id) {
+                warn!(index_id = %pipeline_id.index_id, source_id = %pipeline_id.source_id, "Index is write-blocked, stopping indexing pipeline.");
+                pipeline_handle.kill().await;
+                self.state.num_running_pipelines -= 1;
+                self.write_blocked_index_ids
+                    .insert(pipeline_id.index_id.clone());
+            }
+        }
+        let unblocked_index_ids: Vec<String> = self
+            .write_blocked_index_ids
+            .iter()
+            .filter(|index_id| !write_blocks.contains_key(*index_id))
+            .cloned()
+            .collect();
+        for index_id in unblocked_index_ids {
+            self.write_blocked_index_ids.remove(&index_id);
+            if !self.followed_index_ids.contains(&index_id) {
+                continue;
+            }
+            info!(index_id = %index_id, "Write block was cleared, respawning indexing pipelines.");
+            if let Err(error) = self.spawn_pipelines(ctx, index_id.clone()).await {
+                error!(index_id = %index_id, error = ?error, "Failed to respawn indexing pipelines.");
+            }
+        }
+    }
+
     async fn supervise_pipelines(&mut self, ctx: &ActorContext<Self>) {
+        self.apply_config_updates(ctx).await;
+        self.apply_lifecycle_updates(ctx).await;
+        self.apply_write_blocks(ctx).await;
         self.pipeline_handles
             .retain(|pipeline_id, pipeline_handle| match pipeline_handle
                     .health()
@@ -392,8 +869,13 @@ pub enum IndexingServerMessage {
         index_id: String,
         merge_enabled: bool,
         demux_enabled: bool,
+        compaction_time_range_opt: Option<Range<i64>>,
         sender: oneshot::Sender<anyhow::Result<IndexingPipelineId>>,
     },
+    FollowConfig {
+        config_rx: watch::Receiver<QuickwitConfig>,
+    },
+    DelegateMergesToJanitor,
     Supervise,
 }
 
@@ -448,13 +930,26 @@ impl AsyncActor for IndexingServer {
                 index_id,
                 merge_enabled,
                 demux_enabled,
+                compaction_time_range_opt,
                 sender,
             } => {
                 let spawn_res = self
-                    .spawn_merge_pipeline(ctx, index_id, merge_enabled, demux_enabled)
+                    .spawn_merge_pipeline(
+                        ctx,
+                        index_id,
+                        merge_enabled,
+                        demux_enabled,
+                        compaction_time_range_opt,
+                    )
                     .await;
                 let _ = sender.send(spawn_res);
             }
+            IndexingServerMessage::FollowConfig { config_rx } => {
+                self.config_rx_opt = Some(config_rx);
+            }
+            IndexingServerMessage::DelegateMergesToJanitor => {
+                self.merges_delegated_to_janitor = true;
+            }
             IndexingServerMessage::Supervise => self.supervise_pipelines(ctx).await,
         };
         Ok(())
@@ -467,8 +962,8 @@ mod tests {
 
     use quickwit_actors::ObservationType;
     use quickwit_common::rand::append_random_suffix;
-    use quickwit_config::VecSourceParams;
-    use quickwit_metastore::quickwit_metastore_uri_resolver;
+    use quickwit_config::{LifecycleSettings, VecSourceParams};
+    use quickwit_metastore::{quickwit_metastore_uri_resolver, IndexLifecycleState};
 
     use super::*;
 
@@ -506,6 +1001,9 @@ mod tests {
         let source_1 = SourceConfig {
             source_id: "test-indexing-server--source-1".to_string(),
             source_params: SourceParams::void(),
+            filter: None,
+            routing: None,
+            transform: None,
         };
         let pipeline_id1 = client
             .spawn_pipeline(index_id.clone(), source_1.clone())
@@ -535,6 +1033,9 @@ mod tests {
         let source_2 = SourceConfig {
             source_id: "test-indexing-server--source-2".to_string(),
             source_params: SourceParams::void(),
+            filter: None,
+            routing: None,
+            transform: None,
         };
         metastore.add_source(&index_id, source_2).await.unwrap();
         client.spawn_pipelines(index_id.clone()).await.unwrap();
@@ -542,7 +1043,7 @@ mod tests {
 
         // Test `spawn_merge_pipeline`.
         let merge_pipeline_id = client
-            .spawn_merge_pipeline(index_id.clone(), true, false)
+            .spawn_merge_pipeline(index_id.clone(), true, false, None)
             .await
             .unwrap();
         assert_eq!(client.observe_server().await.num_running_pipelines, 3);
@@ -558,6 +1059,9 @@ mod tests {
                 batch_num_docs: 10,
                 partition: "0".to_string(),
             }),
+            filter: None,
+            routing: None,
+            transform: None,
         };
         client
             .spawn_pipeline(index_id.clone(), source_3)
@@ -571,4 +1075,205 @@ mod tests {
             .await
             .unwrap();
     }
+
+    #[tokio::test]
+    async fn test_indexing_server_follow_config() {
+        let index_id = append_random_suffix("test-indexing-server-follow-config");
+        let index_uri = format!("{}/{}", METASTORE_URI, index_id);
+        let index_metadata = IndexMetadata::for_test(&index_id, &index_uri);
+
+        let metastore = quickwit_metastore_uri_resolver()
+            .resolve(METASTORE_URI)
+            .await
+            .unwrap();
+        metastore.create_index(index_metadata).await.unwrap();
+        for source_id in ["source-1", "source-2"] {
+            let source = SourceConfig {
+                source_id: source_id.to_string(),
+                source_params: SourceParams::void(),
+                filter: None,
+                routing: None,
+                transform: None,
+            };
+            metastore.add_source(&index_id, source).await.unwrap();
+        }
+        let mut config = QuickwitConfig::default();
+        config.indexer_config.disk_high_watermark = 1.0;
+        config.indexer_config.disabled_sources = vec![DisabledSourceConfig {
+            index_id: index_id.clone(),
+            source_id: "source-1".to_string(),
+        }];
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let client = IndexingServer::spawn(
+            temp_dir.path().to_path_buf(),
+            config.indexer_config.clone(),
+            metastore,
+            StorageUriResolver::for_test(),
+        );
+        let (config_tx, config_rx) = watch::channel(config.clone());
+        client.follow_config(config_rx).await.unwrap();
+        let pipeline_ids = client.spawn_pipelines(index_id.clone()).await.unwrap();
+        assert_eq!(pipeline_ids.len(), 1);
+        assert_eq!(pipeline_ids[0].source_id, "source-2");
+
+        // Disable `source-2` and enable `source-1`.
+        config.indexer_config.disabled_sources = vec![DisabledSourceConfig {
+            index_id: index_id.clone(),
+            source_id: "source-2".to_string(),
+        }];
+        config_tx.send(config.clone()).unwrap();
+        let source_1_pipeline_id = IndexingPipelineId {
+            index_id: index_id.clone(),
+            source_id: "source-1".to_string(),
+        };
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while client
+                .observe_pipeline(&source_1_pipeline_id)
+                .await
+                .is_err()
+            {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let source_2_pipeline_id = IndexingPipelineId {
+            index_id,
+            source_id: "source-2".to_string(),
+        };
+        assert!(client
+            .observe_pipeline(&source_2_pipeline_id)
+            .await
+            .is_err());
+        assert_eq!(client.observe_server().await.num_running_pipelines, 1);
+    }
+
+    #[tokio::test]
+    async fn test_indexing_server_write_alias() {
+        let write_alias = append_random_suffix("test-indexing-server-write-alias");
+        let metastore = quickwit_metastore_uri_resolver()
+            .resolve(METASTORE_URI)
+            .await
+            .unwrap();
+        let source = SourceConfig {
+            source_id: "source-1".to_string(),
+            source_params: SourceParams::void(),
+            filter: None,
+            routing: None,
+            transform: None,
+        };
+        let mut index_ids = Vec::new();
+        for (generation, phase) in [(1, LifecyclePhase::ReadOnly), (2, LifecyclePhase::Hot)] {
+            let index_id = format!("{}-{:06}", write_alias, generation);
+            let index_uri = format!("{}/{}", METASTORE_URI, index_id);
+            let mut index_metadata = IndexMetadata::for_test(&index_id, &index_uri);
+            index_metadata.indexing_settings.lifecycle = Some(LifecycleSettings {
+                write_alias: write_alias.clone(),
+                rollover_max_age_secs: Some(3_600),
+                rollover_max_num_bytes: None,
+                freeze_after_secs: None,
+                delete_after_secs: None,
+            });
+            index_metadata.lifecycle_state = IndexLifecycleState {
+                phase,
+                generation,
+                rollover_timestamp: None,
+            };
+            metastore.create_index(index_metadata).await.unwrap();
+            metastore
+                .add_source(&index_id, source.clone())
+                .await
+                .unwrap();
+            index_ids.push(index_id);
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let client = IndexingServer::spawn(
+            temp_dir.path().to_path_buf(),
+            IndexerConfig::for_test().unwrap(),
+            metastore,
+            StorageUriResolver::for_test(),
+        );
+        // The write alias resolves to the hot index with the highest generation.
+        let pipeline_ids = client.spawn_pipelines(write_alias.clone()).await.unwrap();
+        assert_eq!(pipeline_ids.len(), 1);
+        assert_eq!(pipeline_ids[0].index_id, index_ids[1]);
+
+        // The read-only index is write-blocked.
+        let pipeline_ids = client.spawn_pipelines(index_ids[0].clone()).await.unwrap();
+        assert!(pipeline_ids.is_empty());
+        assert_eq!(
+            write_block(&index_ids[0]),
+            Some(WriteBlockReason::Lifecycle)
+        );
+        assert_eq!(client.observe_server().await.num_running_pipelines, 1);
+
+        client
+            .spawn_pipelines(append_random_suffix("test-indexing-server-no-alias"))
+            .await
+            .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn test_indexing_server_write_block() {
+        let index_id = append_random_suffix("test-indexing-server-write-block");
+        let index_uri = format!("{}/{}", METASTORE_URI, index_id);
+        let index_metadata = IndexMetadata::for_test(&index_id, &index_uri);
+
+        let metastore = quickwit_metastore_uri_resolver()
+            .resolve(METASTORE_URI)
+            .await
+            .unwrap();
+        metastore.create_index(index_metadata).await.unwrap();
+        let source = SourceConfig {
+            source_id: "source-1".to_string(),
+            source_params: SourceParams::void(),
+            filter: None,
+            routing: None,
+            transform: None,
+        };
+        metastore
+            .add_source(&index_id, source.clone())
+            .await
+            .unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let client = IndexingServer::spawn(
+            temp_dir.path().to_path_buf(),
+            IndexerConfig::for_test().unwrap(),
+            metastore,
+            StorageUriResolver::for_test(),
+        );
+        let pipeline_ids = client.spawn_pipelines(index_id.clone()).await.unwrap();
+        assert_eq!(pipeline_ids.len(), 1);
+
+        // The pipelines of a write-blocked index are stopped.
+        assert!(apply_write_block(&index_id, WriteBlockReason::Manual));
+        client
+            .wait_for_server(
+                |state| state.num_running_pipelines == 0,
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+        client
+            .spawn_pipeline(index_id.clone(), source)
+            .await
+            .unwrap_err();
+
+        // They are respawned once the block is cleared.
+        assert_eq!(
+            crate::clear_write_block(&index_id),
+            Some(WriteBlockReason::Manual)
+        );
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while client.observe_pipeline(&pipeline_ids[0]).await.is_err() {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(client.observe_server().await.num_running_pipelines, 1);
+    }
 }
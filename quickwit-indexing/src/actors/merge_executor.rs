@@ -26,9 +26,11 @@ use anyhow::Context;
 use fail::fail_point;
 use itertools::{izip, Itertools};
 use quickwit_actors::{Actor, ActorContext, ActorExitStatus, Mailbox, QueueCapacity, SyncActor};
+use quickwit_common::memory_usage::{MemoryComponent, MemoryUsageGuard};
 use quickwit_common::split_file;
+use quickwit_config::VersioningSettings;
 use quickwit_directories::{BundleDirectory, UnionDirectory};
-use quickwit_metastore::checkpoint::CheckpointDelta;
+use quickwit_metastore::checkpoint::IndexCheckpointDelta;
 use quickwit_metastore::SplitMetadata;
 use tantivy::directory::{DirectoryClone, MmapDirectory, RamDirectory};
 use tantivy::fastfield::{DynamicFastFieldReader, FastFieldReader};
@@ -49,6 +51,7 @@ pub struct MergeExecutor {
     demux_field_name: Option<String>,
     min_demuxed_split_num_docs: usize,
     max_demuxed_split_num_docs: usize,
+    versioning_opt: Option<VersioningSettings>,
 }
 
 impl Actor for MergeExecutor {
@@ -249,6 +252,55 @@ fn create_demux_output_directory(
     ))
 }
 
+/// Builds the mapping sending the latest version of each doc ID to the segment ordinal 0 and the
+/// superseded versions to the segment ordinal 1. When two documents share the same doc ID and
+/// version, the last one wins. Documents are compared on the raw `u64` representation of their
+/// fast values, which preserves the order of `i64` values.
+///
+/// Returns `None` when no document is superseded.
+fn build_versioning_mapping(
+    segment_reader: &SegmentReader,
+    versioning: &VersioningSettings,
+) -> anyhow::Result<Option<(DemuxMapping, usize)>> {
+    let schema = segment_reader.schema();
+    let doc_id_field = schema
+        .get_field(&versioning.doc_id_field)
+        .ok_or_else(|| TantivyError::SchemaError("Field does not exist".to_owned()))?;
+    let version_field = schema
+        .get_field(&versioning.version_field)
+        .ok_or_else(|| TantivyError::SchemaError("Field does not exist".to_owned()))?;
+    let doc_id_reader = segment_reader.fast_fields().u64_lenient(doc_id_field)?;
+    let version_reader = segment_reader.fast_fields().u64_lenient(version_field)?;
+    let max_doc = segment_reader.max_doc();
+    // Maps each doc ID value to the version and the doc of its latest version.
+    let mut latest_versions: HashMap<u64, (u64, u32)> = HashMap::new();
+    for doc in 0..max_doc {
+        let version = version_reader.get(doc);
+        latest_versions
+            .entry(doc_id_reader.get(doc))
+            .and_modify(|latest| {
+                if version >= latest.0 {
+                    *latest = (version, doc);
+                }
+            })
+            .or_insert((version, doc));
+    }
+    let num_kept_docs = latest_versions.len();
+    if num_kept_docs == max_doc as usize {
+        return Ok(None);
+    }
+    let mut doc_id_to_segment_ordinal = DocIdToSegmentOrdinal::with_max_doc(max_doc as usize);
+    for doc in 0..max_doc {
+        doc_id_to_segment_ordinal.set(doc, 1);
+    }
+    for (_, doc) in latest_versions.values() {
+        doc_id_to_segment_ordinal.set(*doc, 0);
+    }
+    let mut mapping = DemuxMapping::default();
+    mapping.add(doc_id_to_segment_ordinal);
+    Ok(Some((mapping, num_kept_docs)))
+}
+
 impl MergeExecutor {
     pub fn new(
         index_id: String,
@@ -257,6 +309,7 @@ impl MergeExecutor {
         demux_field_name: Option<String>,
         min_demuxed_split_num_docs: usize,
         max_demuxed_split_num_docs: usize,
+        versioning_opt: Option<VersioningSettings>,
     ) -> Self {
         MergeExecutor {
             index_id,
@@ -265,9 +318,64 @@ impl MergeExecutor {
             demux_field_name,
             min_demuxed_split_num_docs,
             max_demuxed_split_num_docs,
+            versioning_opt,
         }
     }
 
+    /// Rewrites the merged index without the superseded versions of its documents.
+    ///
+    /// Returns the rewritten index, its directories and its number of documents, or `None` when
+    /// the merged index does not contain any superseded version.
+    fn remove_superseded_versions(
+        &self,
+        versioning: &VersioningSettings,
+        merged_index: &Index,
+        merge_scratch_directory: &ScratchDirectory,
+        ctx: &ActorContext<Self>,
+    ) -> anyhow::Result<Option<(Index, ControlledDirectory, ScratchDirectory, u64)>> {
+        let searchable_segments = merged_index.searchable_segments()?;
+        assert_eq!(
+            searchable_segments.len(),
+            1,
+            "A merged split should have only one segment."
+        );
+        let segment_reader = SegmentReader::open(&searchable_segments[0])?;
+        ctx.record_progress();
+        let (versioning_mapping, num_kept_docs) =
+            match build_versioning_mapping(&segment_reader, versioning)? {
+                Some(versioning_mapping) => versioning_mapping,
+                None => return Ok(None),
+            };
+        info!(
+            num_superseded_docs = segment_reader.max_doc() as usize - num_kept_docs,
+            "remove-superseded-versions"
+        );
+        let kept_scratch_directory = merge_scratch_directory.named_temp_child("versioning-kept")?;
+        let superseded_scratch_directory =
+            merge_scratch_directory.named_temp_child("versioning-superseded")?;
+        let kept_directory = create_demux_output_directory(kept_scratch_directory.path(), ctx)?;
+        let superseded_directory =
+            create_demux_output_directory(superseded_scratch_directory.path(), ctx)?;
+        let index_settings = merged_index.load_metas()?.index_settings;
+        let mut indexes = {
+            let _protect_guard = ctx.protect_zone();
+            demux(
+                &searchable_segments,
+                &versioning_mapping,
+                index_settings,
+                vec![kept_directory.box_clone(), superseded_directory.box_clone()],
+            )?
+        };
+        ctx.record_progress();
+        let kept_index = indexes.swap_remove(0);
+        Ok(Some((
+            kept_index,
+            kept_directory,
+            kept_scratch_directory,
+            num_kept_docs as u64,
+        )))
+    }
+
     fn process_merge(
         &mut self,
         split_merge_id: String,
@@ -300,12 +408,35 @@ impl MergeExecutor {
         // This will have the side effect of deleting the directory containing the downloaded
         // splits.
         let time_range = merge_time_range(&splits);
-        let docs_size_in_bytes = sum_doc_sizes_in_bytes(&splits);
-        let num_docs = sum_num_docs(&splits);
+        let mut docs_size_in_bytes = sum_doc_sizes_in_bytes(&splits);
+        let mut num_docs = sum_num_docs(&splits);
 
-        let merged_index = Index::open(controlled_directory.clone())?;
+        let mut merged_index = Index::open(controlled_directory.clone())?;
+        let mut controlled_directory = controlled_directory;
+        let mut split_scratch_directory = merge_scratch_directory;
         ctx.record_progress();
+        if let Some(versioning) = &self.versioning_opt {
+            if let Some((kept_index, kept_directory, kept_scratch_directory, num_kept_docs)) = self
+                .remove_superseded_versions(
+                    versioning,
+                    &merged_index,
+                    &split_scratch_directory,
+                    ctx,
+                )?
+            {
+                // Same estimate as for demuxed splits: the original size of the removed
+                // documents is unknown.
+                docs_size_in_bytes =
+                    (num_kept_docs as f64 * docs_size_in_bytes as f64 / num_docs as f64) as u64;
+                num_docs = num_kept_docs;
+                merged_index = kept_index;
+                controlled_directory = kept_directory;
+                split_scratch_directory = kept_scratch_directory;
+            }
+        }
         let index_writer = merged_index.writer_with_num_threads(1, 3_000_000)?;
+        let index_writer_memory_usage_guard =
+            MemoryUsageGuard::new(MemoryComponent::IndexerHeaps, 3_000_000);
         ctx.record_progress();
 
         let indexed_split = IndexedSplit {
@@ -318,10 +449,11 @@ impl MergeExecutor {
             docs_size_in_bytes,
             // start_time is not very interesting here.
             split_date_of_birth: Instant::now(),
-            checkpoint_delta: CheckpointDelta::default(), //< TODO fixme
+            checkpoint_delta: IndexCheckpointDelta::default(), //< TODO fixme
             index: merged_index,
             index_writer,
-            split_scratch_directory: merge_scratch_directory,
+            index_writer_memory_usage_guard,
+            split_scratch_directory,
             controlled_directory_opt: Some(controlled_directory),
         };
 
@@ -452,6 +584,8 @@ impl MergeExecutor {
                 None
             };
             let index_writer = index.writer_with_num_threads(1, 3_000_000)?;
+            let index_writer_memory_usage_guard =
+                MemoryUsageGuard::new(MemoryComponent::IndexerHeaps, 3_000_000);
             let indexed_split = IndexedSplit {
                 split_id,
                 index_id: self.index_id.clone(),
@@ -461,9 +595,10 @@ impl MergeExecutor {
                 num_docs: num_docs as u64,
                 docs_size_in_bytes,
                 split_date_of_birth: Instant::now(),
-                checkpoint_delta: CheckpointDelta::default(), //< TODO fixme
+                checkpoint_delta: IndexCheckpointDelta::default(), //< TODO fixme
                 index,
                 index_writer,
+                index_writer_memory_usage_guard,
                 split_scratch_directory: scratched_directory,
                 controlled_directory_opt: Some(controlled_directory),
             };
@@ -876,6 +1011,7 @@ mod tests {
             None,
             10_000_000,
             20_000_000,
+            None,
         );
         let universe = Universe::new();
         let (merge_executor_mailbox, merge_executor_handle) =
@@ -896,6 +1032,112 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_merge_executor_removes_superseded_versions() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let index_id = "test-index-versioning";
+        let doc_mapping_yaml = r#"
+            field_mappings:
+              - name: body
+                type: text
+              - name: id
+                type: u64
+                fast: true
+              - name: version
+                type: i64
+                fast: true
+        "#;
+        let indexing_settings_yaml = r#"
+            versioning:
+              doc_id_field: id
+              version_field: version
+        "#;
+        let test_sandbox = TestSandbox::create(
+            index_id,
+            doc_mapping_yaml,
+            indexing_settings_yaml,
+            &["body"],
+        )
+        .await?;
+        for version in 0..3 {
+            let docs = vec![
+                serde_json::json!({"body": format!("doc1-v{}", version), "id": 1, "version": version }),
+                serde_json::json!({"body": format!("doc{}-v0", version + 2), "id": version + 2, "version": 0 }),
+            ];
+            test_sandbox.add_documents(docs).await?;
+        }
+        let metastore = test_sandbox.metastore();
+        let split_metas: Vec<SplitMetadata> = metastore
+            .list_all_splits(index_id)
+            .await?
+            .into_iter()
+            .map(|split| split.split_metadata)
+            .collect();
+        assert_eq!(split_metas.len(), 3);
+        let merge_scratch_directory = ScratchDirectory::for_test()?;
+        let downloaded_splits_directory =
+            merge_scratch_directory.named_temp_child("downloaded-splits-")?;
+        let mut tantivy_dirs: Vec<Box<dyn Directory>> = vec![];
+        for split_meta in &split_metas {
+            let split_filename = split_file(split_meta.split_id());
+            let dest_filepath = downloaded_splits_directory.path().join(&split_filename);
+            test_sandbox
+                .storage()
+                .copy_to_file(Path::new(&split_filename), &dest_filepath)
+                .await?;
+            tantivy_dirs.push(get_tantivy_directory_from_split_bundle(&dest_filepath).unwrap())
+        }
+        let merge_scratch = MergeScratch {
+            merge_operation: MergeOperation::Merge {
+                merge_split_id: crate::new_split_id(),
+                splits: split_metas,
+            },
+            tantivy_dirs,
+            merge_scratch_directory,
+            downloaded_splits_directory,
+        };
+        let (merge_packager_mailbox, merge_packager_inbox) = create_test_mailbox();
+        let merge_executor = MergeExecutor::new(
+            index_id.to_string(),
+            merge_packager_mailbox,
+            None,
+            None,
+            10_000_000,
+            20_000_000,
+            Some(VersioningSettings {
+                doc_id_field: "id".to_string(),
+                version_field: "version".to_string(),
+            }),
+        );
+        let universe = Universe::new();
+        let (merge_executor_mailbox, merge_executor_handle) =
+            universe.spawn_actor(merge_executor).spawn_sync();
+        universe
+            .send_message(&merge_executor_mailbox, merge_scratch)
+            .await?;
+        merge_executor_handle.process_pending_and_observe().await;
+        let mut packager_msgs = merge_packager_inbox.drain_available_message_for_test();
+        assert_eq!(packager_msgs.len(), 1);
+        let packager_msg = packager_msgs.pop().unwrap();
+        assert_eq!(packager_msg.splits[0].num_docs, 4);
+
+        let reader = packager_msg.splits[0].index.reader()?;
+        let searcher = reader.searcher();
+        assert_eq!(searcher.num_docs(), 4);
+        let schema = packager_msg.splits[0].index.schema();
+        let id_field = schema.get_field("id").unwrap();
+        let version_field = schema.get_field("version").unwrap();
+        let segment_reader = searcher.segment_reader(0);
+        let id_reader = segment_reader.fast_fields().u64(id_field)?;
+        let version_reader = segment_reader.fast_fields().i64(version_field)?;
+        let mut ids_and_versions: Vec<(u64, i64)> = (0..segment_reader.max_doc())
+            .map(|doc| (id_reader.get(doc), version_reader.get(doc)))
+            .collect();
+        ids_and_versions.sort_unstable();
+        assert_eq!(ids_and_versions, vec![(1, 2), (2, 0), (3, 0), (4, 0)]);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_demux_execution() -> anyhow::Result<()> {
         quickwit_common::setup_logging_for_tests();
@@ -982,6 +1224,7 @@ mod tests {
             Some("tenant_id".to_string()),
             2,
             5,
+            None,
         );
         let universe = Universe::new();
         let (merge_executor_mailbox, merge_executor_handle) =
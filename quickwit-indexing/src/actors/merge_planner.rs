@@ -21,8 +21,9 @@ use std::sync::Arc;
 
 use quickwit_actors::{Actor, ActorContext, ActorExitStatus, Mailbox, QueueCapacity, SyncActor};
 use quickwit_metastore::SplitMetadata;
-use tracing::info;
+use tracing::{info, warn};
 
+use crate::actors::merge_split_downloader::merge_scratch_num_bytes;
 use crate::merge_policy::MergeOperation;
 use crate::models::MergePlannerMessage;
 use crate::MergePolicy;
@@ -33,6 +34,9 @@ pub struct MergePlanner {
     /// yet and can be candidate to merge and demux operations.
     young_splits: Vec<SplitMetadata>,
     merge_policy: Arc<dyn MergePolicy>,
+    /// Merges needing more scratch space than the quota of the pipeline are not planned, see
+    /// [`crate::models::ScratchQuota`].
+    max_merge_scratch_num_bytes: u64,
     merge_split_downloader_mailbox: Mailbox<MergeOperation>,
 }
 
@@ -82,18 +86,42 @@ impl MergePlanner {
     pub fn new(
         young_splits: Vec<SplitMetadata>,
         merge_policy: Arc<dyn MergePolicy>,
+        max_merge_scratch_num_bytes: u64,
         merge_split_downloader_mailbox: Mailbox<MergeOperation>,
     ) -> MergePlanner {
         MergePlanner {
             young_splits,
             merge_policy,
+            max_merge_scratch_num_bytes,
             merge_split_downloader_mailbox,
         }
     }
 
+    /// Returns the operations to perform. The splits of the operations exceeding the scratch quota
+    /// of the pipeline are kept as young splits, so that they can be part of later operations.
+    fn plan_operations(&mut self) -> Vec<MergeOperation> {
+        let mut merge_operations = Vec::new();
+        let mut oversized_splits = Vec::new();
+        for merge_operation in self.merge_policy.operations(&mut self.young_splits) {
+            let merge_num_bytes = merge_scratch_num_bytes(&merge_operation);
+            if merge_num_bytes > self.max_merge_scratch_num_bytes {
+                warn!(
+                    merge_operation=?merge_operation,
+                    merge_num_bytes=merge_num_bytes,
+                    max_scratch_num_bytes=self.max_merge_scratch_num_bytes,
+                    "Merge exceeds the scratch quota of the pipeline, keeping its splits."
+                );
+                oversized_splits.extend(merge_operation.splits().iter().cloned());
+                continue;
+            }
+            merge_operations.push(merge_operation);
+        }
+        self.young_splits.extend(oversized_splits);
+        merge_operations
+    }
+
     fn send_operations(&mut self, ctx: &ActorContext<Self>) -> Result<(), ActorExitStatus> {
-        let merge_candidates = self.merge_policy.operations(&mut self.young_splits);
-        for merge_operation in merge_candidates {
+        for merge_operation in self.plan_operations() {
             info!(merge_operation=?merge_operation, "planning-merge");
             ctx.send_message_blocking(&self.merge_split_downloader_mailbox, merge_operation)?;
         }
@@ -166,6 +194,9 @@ mod tests {
             tags,
             demux_num_ops: 0,
             footer_offsets: 0..100,
+            sort_by: None,
+            field_stats: Default::default(),
+            replaced_split_ids: Default::default(),
         }
     }
 
@@ -204,6 +235,9 @@ mod tests {
                 tags: tags.clone(),
                 demux_num_ops: 1,
                 footer_offsets: 0..100,
+                sort_by: None,
+                field_stats: Default::default(),
+                replaced_split_ids: Default::default(),
             };
             splits_metadata.push(split_metadata);
         }
@@ -235,7 +269,7 @@ mod tests {
         predicate: Pred,
     ) -> anyhow::Result<()> {
         let (merge_op_mailbox, merge_op_inbox) = create_test_mailbox::<MergeOperation>();
-        let merge_planner = MergePlanner::new(Vec::new(), merge_policy, merge_op_mailbox);
+        let merge_planner = MergePlanner::new(Vec::new(), merge_policy, u64::MAX, merge_op_mailbox);
         let universe = Universe::new();
         let mut split_index: HashMap<String, SplitMetadata> = HashMap::default();
         let (merge_planner_mailbox, merge_planner_handler) =
@@ -287,6 +321,9 @@ mod tests {
             tags: BTreeSet::from_iter(vec!["tenant_id:1".to_string(), "tenant_id:2".to_string()]),
             demux_num_ops: 0,
             footer_offsets: 0..100,
+            sort_by: None,
+            field_stats: Default::default(),
+            replaced_split_ids: Default::default(),
         }
     }
 
@@ -325,6 +362,34 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_merge_planner_keeps_splits_of_merges_exceeding_scratch_quota() {
+        let (merge_op_mailbox, _merge_op_inbox) = create_test_mailbox::<MergeOperation>();
+        let young_splits: Vec<SplitMetadata> = (0..10)
+            .map(|split_ord| {
+                let time_first = split_ord * 1_000;
+                mock_split_meta_from_num_docs(time_first..=time_first + 999, 10_000)
+            })
+            .collect();
+        let merge_policy = Arc::new(StableMultitenantWithTimestampMergePolicy::default());
+        // The 10 splits have a footer ending at 100 bytes: merging them needs 2,000 bytes.
+        let mut merge_planner = MergePlanner::new(
+            young_splits.clone(),
+            merge_policy.clone(),
+            1_999,
+            merge_op_mailbox.clone(),
+        );
+        assert!(merge_planner.plan_operations().is_empty());
+        assert_eq!(merge_planner.young_splits.len(), 10);
+
+        let mut merge_planner =
+            MergePlanner::new(young_splits, merge_policy, 2_000, merge_op_mailbox);
+        let merge_operations = merge_planner.plan_operations();
+        assert_eq!(merge_operations.len(), 1);
+        assert_eq!(merge_operations[0].splits().len(), 10);
+        assert!(merge_planner.young_splits.is_empty());
+    }
+
     #[tokio::test]
     async fn test_simulate_merge_and_demux() -> anyhow::Result<()> {
         let merge_policy = StableMultitenantWithTimestampMergePolicy {
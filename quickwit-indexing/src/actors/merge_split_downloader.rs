@@ -18,6 +18,7 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use std::path::Path;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use quickwit_actors::{Actor, ActorContext, ActorExitStatus, AsyncActor, Mailbox, QueueCapacity};
@@ -26,11 +27,26 @@ use tantivy::Directory;
 use tracing::{info, info_span, warn, Span};
 
 use crate::merge_policy::MergeOperation;
-use crate::models::{MergeScratch, ScratchDirectory};
+use crate::models::{MergeScratch, ScratchDirectory, ScratchQuota};
 use crate::split_store::IndexingSplitStore;
 
+/// Delay before a merge deferred because of the scratch quota of the pipeline is retried.
+const SCRATCH_QUOTA_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Returns the scratch space needed by a merge operation: the downloaded splits take up as much
+/// scratch space as the split files, and the merged split about as much again.
+pub(crate) fn merge_scratch_num_bytes(merge_operation: &MergeOperation) -> u64 {
+    merge_operation
+        .splits()
+        .iter()
+        .map(|split| split.footer_offsets.end)
+        .sum::<u64>()
+        * 2
+}
+
 pub struct MergeSplitDownloader {
     pub scratch_directory: ScratchDirectory,
+    pub scratch_quota: ScratchQuota,
     pub storage: IndexingSplitStore,
     pub merge_executor_mailbox: Mailbox<MergeScratch>,
 }
@@ -94,6 +110,23 @@ impl MergeSplitDownloader {
         merge_operation: MergeOperation,
         ctx: &ActorContext<Self>,
     ) -> Result<(), quickwit_actors::ActorExitStatus> {
+        // The merge planner does not plan merges exceeding the quota on their own, so the merge
+        // eventually fits once the other merges of the pipeline free their scratch space.
+        let merge_num_bytes = merge_scratch_num_bytes(&merge_operation);
+        let has_room = self
+            .scratch_quota
+            .has_room_for(merge_num_bytes)
+            .map_err(|error| anyhow::anyhow!(error))?;
+        if !has_room {
+            info!(
+                merge_num_bytes = merge_num_bytes,
+                max_scratch_num_bytes = self.scratch_quota.max_num_bytes(),
+                "Scratch quota of the pipeline reached, deferring merge."
+            );
+            ctx.schedule_self_msg(SCRATCH_QUOTA_RETRY_DELAY, merge_operation)
+                .await;
+            return Ok(());
+        }
         let merge_scratch_directory = self
             .scratch_directory
             .named_temp_child("merge-")
@@ -149,7 +182,7 @@ mod tests {
     use std::iter;
     use std::sync::Arc;
 
-    use quickwit_actors::{create_test_mailbox, Universe};
+    use quickwit_actors::{create_test_mailbox, ObservationType, Universe};
     use quickwit_common::split_file;
     use quickwit_storage::{PutPayload, RamStorageBuilder, SplitPayloadBuilder};
 
@@ -183,8 +216,10 @@ mod tests {
 
         let universe = Universe::new();
         let (merge_executor_mailbox, merge_executor_inbox) = create_test_mailbox();
+        let scratch_quota = ScratchQuota::for_test(scratch_directory.clone(), 1_000_000);
         let merge_split_downloader = MergeSplitDownloader {
             scratch_directory,
+            scratch_quota,
             storage,
             merge_executor_mailbox,
         };
@@ -215,4 +250,63 @@ mod tests {
         }
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_merge_split_downloader_scratch_quota() -> anyhow::Result<()> {
+        let scratch_directory = ScratchDirectory::for_test()?;
+        let scratch_file_path = scratch_directory.path().join("hello.txt");
+        std::fs::write(&scratch_file_path, b"hello")?;
+        // The merge fits the quota but not the space left in the scratch directory: it is
+        // deferred.
+        let deferred_split = SplitMetadata {
+            split_id: new_split_id(),
+            footer_offsets: 0..8,
+            ..Default::default()
+        };
+        let storage = {
+            let buffer = SplitPayloadBuilder::get_split_payload(&[], &[1, 2, 3])?
+                .read_all()
+                .await?;
+            let ram_storage = RamStorageBuilder::default()
+                .put(&split_file(deferred_split.split_id()), &buffer)
+                .build();
+            IndexingSplitStore::create_with_no_local_store(Arc::new(ram_storage))
+        };
+        let universe = Universe::new();
+        let (merge_executor_mailbox, merge_executor_inbox) = create_test_mailbox();
+        let merge_split_downloader = MergeSplitDownloader {
+            scratch_quota: ScratchQuota::for_test(scratch_directory.clone(), 20),
+            scratch_directory,
+            storage,
+            merge_executor_mailbox,
+        };
+        let (merge_split_downloader_mailbox, merge_split_downloader_handler) =
+            universe.spawn_actor(merge_split_downloader).spawn_async();
+        universe
+            .send_message(
+                &merge_split_downloader_mailbox,
+                MergeOperation::new_merge_operation(vec![deferred_split]),
+            )
+            .await?;
+        let observation = merge_split_downloader_handler
+            .process_pending_and_observe()
+            .await;
+        assert_eq!(observation.obs_type, ObservationType::Alive);
+        assert!(merge_executor_inbox
+            .drain_available_message_for_test()
+            .is_empty());
+
+        // Once the scratch space is freed, the deferred merge goes through.
+        std::fs::remove_file(&scratch_file_path)?;
+        universe
+            .simulate_time_shift(SCRATCH_QUOTA_RETRY_DELAY + Duration::from_secs(1))
+            .await;
+        merge_split_downloader_handler
+            .process_pending_and_observe()
+            .await;
+        let merge_scratchs = merge_executor_inbox.drain_available_message_for_test();
+        assert_eq!(merge_scratchs.len(), 1);
+        assert_eq!(merge_scratchs[0].merge_operation.splits().len(), 1);
+        Ok(())
+    }
 }
@@ -19,15 +19,18 @@
 
 mod indexing_pipeline;
 
-mod garbage_collector;
+mod dead_letter_writer;
+mod doc_router;
 mod indexer;
 mod indexing_server;
 mod packager;
 mod publisher;
+mod source_tagger;
 mod uploader;
 
 pub use indexing_pipeline::{
-    IndexingPipeline, IndexingPipelineHandler, IndexingPipelineMessage, IndexingPipelineParams,
+    IndexChainHandler, IndexingPipeline, IndexingPipelineHandler, IndexingPipelineMessage,
+    IndexingPipelineParams, RoutedIndexParams,
 };
 pub use indexing_server::{IndexingPipelineId, IndexingServer, IndexingServerClient};
 use tantivy::schema::{Field, FieldType};
@@ -35,13 +38,17 @@ mod merge_executor;
 mod merge_planner;
 mod merge_split_downloader;
 
-pub use self::garbage_collector::{GarbageCollector, GarbageCollectorCounters};
-pub use self::indexer::{Indexer, IndexerCounters};
+pub use self::dead_letter_writer::{
+    DeadLetter, DeadLetterWriter, DeadLetterWriterCounters, DeadLetterWriterMessage,
+};
+pub use self::doc_router::{DocRouter, DocRouterCounters, RoutedIndexer};
+pub use self::indexer::{Indexer, IndexerCounters, IndexerSource};
 pub use self::merge_executor::MergeExecutor;
 pub use self::merge_planner::MergePlanner;
 pub use self::merge_split_downloader::MergeSplitDownloader;
 pub use self::packager::Packager;
 pub use self::publisher::{Publisher, PublisherCounters};
+pub use self::source_tagger::SourceTagger;
 pub use self::uploader::{Uploader, UploaderCounters};
 
 /// A struct to wrap a tantivy field with its name.
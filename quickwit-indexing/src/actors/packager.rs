@@ -17,7 +17,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::io;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -27,9 +27,12 @@ use fail::fail_point;
 use itertools::Itertools;
 use quickwit_actors::{Actor, ActorContext, Mailbox, QueueCapacity, SyncActor};
 use quickwit_directories::write_hotcache;
+use quickwit_doc_mapper::range_pruning::{FieldBounds, FieldStats};
 use quickwit_doc_mapper::tag_pruning::append_to_tag_set;
-use tantivy::schema::FieldType;
-use tantivy::{InvertedIndexReader, ReloadPolicy, SegmentId, SegmentMeta};
+use quickwit_doc_mapper::SortByField;
+use tantivy::fastfield::FastFieldReader;
+use tantivy::schema::{Cardinality, Field, FieldType, Schema};
+use tantivy::{InvertedIndexReader, ReloadPolicy, SegmentId, SegmentMeta, SegmentReader};
 use tracing::{debug, info, info_span, warn, Span};
 
 /// Maximum distinct values allowed for a tag field within a split.
@@ -246,6 +249,99 @@ fn try_extract_terms(
     Ok(terms)
 }
 
+/// Computes the statistics of a numeric or date fast field over the segments of a split.
+///
+/// The min / max values are computed on the fast field values, whose order is the order of the
+/// field values whatever their type. Single-valued fast fields are required: their documents
+/// always hold a value. Returns `None` for the other fields and when the statistics would hold
+/// a NaN value.
+fn compute_field_stats(
+    field: Field,
+    field_type: &FieldType,
+    segment_readers: &[SegmentReader],
+) -> anyhow::Result<Option<FieldStats>> {
+    let cardinality = match field_type {
+        FieldType::U64(options)
+        | FieldType::I64(options)
+        | FieldType::F64(options)
+        | FieldType::Date(options) => match options.get_fastfield_cardinality() {
+            Some(cardinality) => cardinality,
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+    let mut min_max_opt: Option<(u64, u64)> = None;
+    let mut num_nulls = 0;
+    let mut record_values = |min: u64, max: u64| {
+        min_max_opt = Some(match min_max_opt {
+            Some((current_min, current_max)) => (current_min.min(min), current_max.max(max)),
+            None => (min, max),
+        });
+    };
+    let mut vals = Vec::new();
+    for segment_reader in segment_readers {
+        if segment_reader.max_doc() == 0 {
+            continue;
+        }
+        match cardinality {
+            Cardinality::SingleValue => {
+                let fast_field_reader = segment_reader.fast_fields().u64_lenient(field)?;
+                record_values(fast_field_reader.min_value(), fast_field_reader.max_value());
+            }
+            Cardinality::MultiValues => {
+                let fast_field_reader = segment_reader.fast_fields().u64s_lenient(field)?;
+                for doc in 0..segment_reader.max_doc() {
+                    fast_field_reader.get_vals(doc, &mut vals);
+                    match (vals.iter().min(), vals.iter().max()) {
+                        (Some(&min), Some(&max)) => record_values(min, max),
+                        _ => num_nulls += 1,
+                    }
+                }
+            }
+        }
+    }
+    let (min, max) = match min_max_opt {
+        Some(min_max) => min_max,
+        None => return Ok(None),
+    };
+    let bounds = match field_type {
+        FieldType::U64(_) => FieldBounds::U64 { min, max },
+        FieldType::I64(_) => FieldBounds::I64 {
+            min: tantivy::u64_to_i64(min),
+            max: tantivy::u64_to_i64(max),
+        },
+        FieldType::F64(_) => {
+            let (min, max) = (tantivy::u64_to_f64(min), tantivy::u64_to_f64(max));
+            if min.is_nan() || max.is_nan() {
+                return Ok(None);
+            }
+            FieldBounds::F64 { min, max }
+        }
+        FieldType::Date(_) => FieldBounds::Date {
+            min: tantivy::u64_to_i64(min),
+            max: tantivy::u64_to_i64(max),
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(FieldStats { bounds, num_nulls }))
+}
+
+/// Extracts the statistics of the numeric and date fast fields of the split, which are used to
+/// prune the splits of range queries.
+fn extract_field_stats(
+    schema: &Schema,
+    segment_readers: &[SegmentReader],
+) -> anyhow::Result<BTreeMap<String, FieldStats>> {
+    let mut field_stats = BTreeMap::new();
+    for (field, field_entry) in schema.fields() {
+        if let Some(stats) = compute_field_stats(field, field_entry.field_type(), segment_readers)?
+        {
+            field_stats.insert(field_entry.name().to_string(), stats);
+        }
+    }
+    Ok(field_stats)
+}
+
 fn create_packaged_split(
     segment_metas: &[SegmentMeta],
     split: IndexedSplit,
@@ -288,6 +384,23 @@ fn create_packaged_split(
 
     ctx.record_progress();
 
+    debug!(split_id = split.split_id.as_str(), "extract-field-stats");
+    let field_stats = extract_field_stats(
+        &split.index.schema(),
+        index_reader.searcher().segment_readers(),
+    )?;
+    ctx.record_progress();
+
+    let sort_by = split
+        .index
+        .settings()
+        .sort_by_field
+        .as_ref()
+        .map(|sort_by_field| SortByField {
+            field_name: sort_by_field.field.clone(),
+            order: sort_by_field.order.clone().into(),
+        });
+
     debug!(split_id = split.split_id.as_str(), "build-hotcache");
     let mut hotcache_bytes = vec![];
     build_hotcache(split.split_scratch_directory.path(), &mut hotcache_bytes)?;
@@ -304,6 +417,8 @@ fn create_packaged_split(
         time_range: split.time_range,
         size_in_bytes: split.docs_size_in_bytes,
         tags,
+        sort_by,
+        field_stats,
         split_date_of_birth: split.split_date_of_birth,
         split_files,
         hotcache_bytes,
@@ -355,7 +470,8 @@ mod tests {
     use std::time::Instant;
 
     use quickwit_actors::{create_test_mailbox, ObservationType, Universe};
-    use quickwit_metastore::checkpoint::CheckpointDelta;
+    use quickwit_common::memory_usage::{MemoryComponent, MemoryUsageGuard};
+    use quickwit_metastore::checkpoint::{CheckpointDelta, IndexCheckpointDelta};
     use tantivy::schema::{IntOptions, Schema, FAST, STRING, TEXT};
     use tantivy::{doc, Index};
 
@@ -375,6 +491,8 @@ mod tests {
         let schema = schema_builder.build();
         let index = Index::create_in_dir(split_scratch_directory.path(), schema)?;
         let mut index_writer = index.writer_with_num_threads(1, 10_000_000)?;
+        let index_writer_memory_usage_guard =
+            MemoryUsageGuard::new(MemoryComponent::IndexerHeaps, 10_000_000);
         let mut timerange_opt: Option<RangeInclusive<i64>> = None;
         let mut num_docs = 0;
         for (segment_num, segment_timestamps) in segments_timestamps.iter().enumerate() {
@@ -420,8 +538,12 @@ mod tests {
             split_date_of_birth: Instant::now(),
             index,
             index_writer,
+            index_writer_memory_usage_guard,
             split_scratch_directory,
-            checkpoint_delta: CheckpointDelta::from(10..20),
+            checkpoint_delta: IndexCheckpointDelta::for_source(
+                "test-source",
+                CheckpointDelta::from(10..20),
+            ),
             replaced_split_ids: Vec::new(),
             controlled_directory_opt: None,
         };
@@ -484,6 +606,60 @@ mod tests {
                 "tag_u64:42"
             ]
         );
+        assert_eq!(
+            split.field_stats.get("timestamp"),
+            Some(&FieldStats {
+                bounds: FieldBounds::U64 {
+                    min: 1628203589,
+                    max: 1628203640,
+                },
+                num_nulls: 0,
+            })
+        );
+        // The other numeric fields are not fast.
+        assert_eq!(split.field_stats.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_field_stats() -> anyhow::Result<()> {
+        let mut schema_builder = Schema::builder();
+        let latency_field = schema_builder.add_f64_field("latency", FAST);
+        let codes_field = schema_builder.add_i64_field(
+            "codes",
+            IntOptions::default().set_fast(Cardinality::MultiValues),
+        );
+        let schema = schema_builder.build();
+        let index = Index::create_in_ram(schema.clone());
+        let mut index_writer = index.writer_with_num_threads(1, 10_000_000)?;
+        index_writer.add_document(doc!(latency_field => 1.5f64, codes_field => -3i64))?;
+        index_writer.add_document(doc!(latency_field => 0.25f64))?;
+        index_writer.commit()?;
+        index_writer.add_document(doc!(
+            latency_field => 12.0f64,
+            codes_field => 7i64,
+            codes_field => 2i64
+        ))?;
+        index_writer.commit()?;
+        let searcher = index.reader()?.searcher();
+        let field_stats = extract_field_stats(&schema, searcher.segment_readers())?;
+        assert_eq!(
+            field_stats.get("latency"),
+            Some(&FieldStats {
+                bounds: FieldBounds::F64 {
+                    min: 0.25,
+                    max: 12.0,
+                },
+                num_nulls: 0,
+            })
+        );
+        assert_eq!(
+            field_stats.get("codes"),
+            Some(&FieldStats {
+                bounds: FieldBounds::I64 { min: -3, max: 7 },
+                num_nulls: 1,
+            })
+        );
         Ok(())
     }
 
@@ -17,22 +17,29 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use anyhow::Context;
 use async_trait::async_trait;
 use fail::fail_point;
 use quickwit_actors::{Actor, ActorContext, AsyncActor, Mailbox, QueueCapacity};
+use quickwit_metastore::checkpoint::{CheckpointDelta, SourceCheckpoint};
 use quickwit_metastore::Metastore;
 use tokio::sync::oneshot::Receiver;
 use tracing::info;
 
 use crate::actors::uploader::MAX_CONCURRENT_SPLIT_UPLOAD;
 use crate::models::{MergePlannerMessage, PublishOperation, PublisherMessage};
+use crate::source::SourceActorMessage;
 
 #[derive(Debug, Clone, Default)]
 pub struct PublisherCounters {
     pub num_published_splits: u64,
+    /// Number of documents of the published splits.
+    pub num_published_docs: u64,
+    /// Size in bytes of the published splits.
+    pub num_published_bytes: u64,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -62,7 +69,8 @@ pub struct Publisher {
     source_id: String,
     metastore: Arc<dyn Metastore>,
     merge_planner_mailbox: Mailbox<MergePlannerMessage>,
-    garbage_collector_mailbox: Mailbox<()>,
+    /// Mailboxes of the sources that are told which positions have been published.
+    source_mailboxes: HashMap<String, Mailbox<SourceActorMessage>>,
     counters: PublisherCounters,
 }
 
@@ -72,18 +80,33 @@ impl Publisher {
         source_id: String,
         metastore: Arc<dyn Metastore>,
         merge_planner_mailbox: Mailbox<MergePlannerMessage>,
-        garbage_collector_mailbox: Mailbox<()>,
+        source_mailbox_opt: Option<Mailbox<SourceActorMessage>>,
     ) -> Publisher {
+        let mut source_mailboxes = HashMap::new();
+        if let Some(source_mailbox) = source_mailbox_opt {
+            source_mailboxes.insert(source_id.clone(), source_mailbox);
+        }
         Publisher {
             publisher_type,
             source_id,
             metastore,
             merge_planner_mailbox,
-            garbage_collector_mailbox,
+            source_mailboxes,
             counters: PublisherCounters::default(),
         }
     }
 
+    /// Registers the mailbox of another source feeding the pipeline, so that it receives the
+    /// positions published for it.
+    pub fn with_source_mailbox(
+        mut self,
+        source_id: String,
+        source_mailbox: Mailbox<SourceActorMessage>,
+    ) -> Self {
+        self.source_mailboxes.insert(source_id, source_mailbox);
+        self
+    }
+
     pub async fn run_publish_operation(
         &self,
         publisher_message: &PublisherMessage,
@@ -95,15 +118,31 @@ impl Publisher {
                 ..
             } => {
                 info!("new-split-start");
-                self.metastore
-                    .publish_splits(
-                        &publisher_message.index_id,
-                        &self.source_id,
-                        &[new_split.split_id()],
-                        checkpoint_delta.clone(),
-                    )
-                    .await
-                    .context("Failed to publish splits.")?;
+                if checkpoint_delta.num_sources() > 1 {
+                    self.metastore
+                        .publish_multi_source_splits(
+                            &publisher_message.index_id,
+                            &[new_split.split_id()],
+                            checkpoint_delta.clone(),
+                        )
+                        .await
+                        .context("Failed to publish splits.")?;
+                } else {
+                    let (source_id, source_delta) = checkpoint_delta
+                        .iter()
+                        .next()
+                        .map(|(source_id, source_delta)| (source_id, source_delta.clone()))
+                        .unwrap_or((self.source_id.as_str(), CheckpointDelta::default()));
+                    self.metastore
+                        .publish_splits(
+                            &publisher_message.index_id,
+                            source_id,
+                            &[new_split.split_id()],
+                            source_delta,
+                        )
+                        .await
+                        .context("Failed to publish splits.")?;
+                }
                 info!("new-split-success");
             }
             PublishOperation::ReplaceSplits {
@@ -184,7 +223,33 @@ impl AsyncActor for Publisher {
             }
         }
 
+        if let PublishOperation::PublishNewSplit {
+            checkpoint_delta, ..
+        } = &publisher_message.operation
+        {
+            for (source_id, source_delta) in checkpoint_delta.iter() {
+                let source_mailbox = match self.source_mailboxes.get(source_id) {
+                    Some(source_mailbox) => source_mailbox,
+                    None => continue,
+                };
+                let mut published_checkpoint = SourceCheckpoint::default();
+                // Applying a delta to an empty checkpoint cannot fail.
+                let _ = published_checkpoint.try_apply_delta(source_delta.clone());
+                // The source is not necessarily alive, for instance when it has reached its end.
+                let _ = ctx
+                    .send_message(
+                        source_mailbox,
+                        SourceActorMessage::SuggestTruncate(published_checkpoint),
+                    )
+                    .await;
+            }
+        }
+
         let new_splits = publisher_message.operation.extract_new_splits();
+        for new_split in &new_splits {
+            self.counters.num_published_docs += new_split.num_docs as u64;
+            self.counters.num_published_bytes += new_split.footer_offsets.end;
+        }
 
         // The merge planner is not necessarily awake and this is not an error.
         // For instance, when a source reaches its end, and the last "new" split
@@ -206,16 +271,8 @@ impl AsyncActor for Publisher {
         _exit_status: &quickwit_actors::ActorExitStatus,
         ctx: &ActorContext<Self>,
     ) -> anyhow::Result<()> {
-        // The `garbage_collector` actor runs for ever.
-        // Periodically scheduling new messages for itself.
-        //
-        // The publisher actor being the last standing actor of the pipeline,
-        // its end of life should also means the end of life of never stopping actors.
-        // After all, when the publisher is stopped, there shouldn't be anything to process.
-        // It's fine if the garbage collector is already dead.
-        let _ = ctx
-            .send_exit_with_success(&self.garbage_collector_mailbox)
-            .await;
+        // The publisher actor being the last standing actor of the pipeline, its end of life
+        // should also mean the end of life of the merge planner, which never stops otherwise.
         let _ = ctx
             .send_exit_with_success(&self.merge_planner_mailbox)
             .await;
@@ -228,7 +285,7 @@ mod tests {
     use std::time::Instant;
 
     use quickwit_actors::{create_test_mailbox, Universe};
-    use quickwit_metastore::checkpoint::CheckpointDelta;
+    use quickwit_metastore::checkpoint::{IndexCheckpointDelta, PartitionId, Position};
     use quickwit_metastore::{MockMetastore, SplitMetadata};
     use tokio::sync::oneshot;
 
@@ -259,13 +316,12 @@ mod tests {
             .times(1)
             .returning(|_, _, _, _| Ok(()));
         let (merge_planner_mailbox, _merge_planner_inbox) = create_test_mailbox();
-        let (garbage_collector_mailbox, _garbage_collector_inbox) = create_test_mailbox();
         let publisher = Publisher::new(
             PublisherType::MainPublisher,
             "source".to_string(),
             Arc::new(mock_metastore),
             merge_planner_mailbox,
-            garbage_collector_mailbox,
+            None,
         );
         let universe = Universe::new();
         let (publisher_mailbox, publisher_handle) = universe.spawn_actor(publisher).spawn_async();
@@ -288,7 +344,10 @@ mod tests {
                         split_id: "split2".to_string(),
                         ..Default::default()
                     },
-                    checkpoint_delta: CheckpointDelta::from(3..7),
+                    checkpoint_delta: IndexCheckpointDelta::for_source(
+                        "source",
+                        CheckpointDelta::from(3..7),
+                    ),
                     split_date_of_birth: Instant::now(),
                 }
             })
@@ -301,7 +360,10 @@ mod tests {
                         split_id: "split1".to_string(),
                         ..Default::default()
                     },
-                    checkpoint_delta: CheckpointDelta::from(1..3),
+                    checkpoint_delta: IndexCheckpointDelta::for_source(
+                        "source",
+                        CheckpointDelta::from(1..3),
+                    ),
                     split_date_of_birth: Instant::now(),
                 },
             })
@@ -324,13 +386,12 @@ mod tests {
             .times(1)
             .returning(|_, _, _| Ok(()));
         let (merge_planner_mailbox, merge_planner_inbox) = create_test_mailbox();
-        let (garbage_collector_mailbox, _garbage_collector_inbox) = create_test_mailbox();
         let publisher = Publisher::new(
             PublisherType::MainPublisher,
             "source".to_string(),
             Arc::new(mock_metastore),
             merge_planner_mailbox,
-            garbage_collector_mailbox,
+            None,
         );
         let universe = Universe::new();
         let (publisher_mailbox, publisher_handle) = universe.spawn_actor(publisher).spawn_async();
@@ -360,4 +421,124 @@ mod tests {
             matches!(merge_planner_msg, MergePlannerMessage { new_splits } if new_splits.len() == 1)
         )
     }
+
+    #[tokio::test]
+    async fn test_publisher_suggests_truncate_to_source() {
+        quickwit_common::setup_logging_for_tests();
+        let mut mock_metastore = MockMetastore::default();
+        mock_metastore
+            .expect_publish_splits()
+            .times(1)
+            .returning(|_, _, _, _| Ok(()));
+        let (merge_planner_mailbox, _merge_planner_inbox) = create_test_mailbox();
+        let (source_mailbox, source_inbox) = create_test_mailbox();
+        let publisher = Publisher::new(
+            PublisherType::MainPublisher,
+            "source".to_string(),
+            Arc::new(mock_metastore),
+            merge_planner_mailbox,
+            Some(source_mailbox),
+        );
+        let universe = Universe::new();
+        let (publisher_mailbox, publisher_handle) = universe.spawn_actor(publisher).spawn_async();
+        let (split_future_tx, split_future_rx) = oneshot::channel::<PublisherMessage>();
+        assert!(universe
+            .send_message(&publisher_mailbox, split_future_rx)
+            .await
+            .is_ok());
+        assert!(split_future_tx
+            .send(PublisherMessage {
+                index_id: "index".to_string(),
+                operation: PublishOperation::PublishNewSplit {
+                    new_split: SplitMetadata {
+                        split_id: "split1".to_string(),
+                        ..Default::default()
+                    },
+                    checkpoint_delta: IndexCheckpointDelta::for_source(
+                        "source",
+                        CheckpointDelta::from(3..7),
+                    ),
+                    split_date_of_birth: Instant::now(),
+                },
+            })
+            .is_ok());
+        let publisher_observation = publisher_handle.process_pending_and_observe().await.state;
+        assert_eq!(publisher_observation.num_published_splits, 1);
+        let mut source_msgs = source_inbox.drain_available_message_for_test();
+        assert_eq!(source_msgs.len(), 1);
+        let expected_checkpoint: SourceCheckpoint =
+            vec![(PartitionId::default(), Position::from(6u64))]
+                .into_iter()
+                .collect();
+        assert!(matches!(
+            source_msgs.pop().unwrap(),
+            SourceActorMessage::SuggestTruncate(checkpoint) if checkpoint == expected_checkpoint
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_publisher_publishes_multi_source_splits() {
+        quickwit_common::setup_logging_for_tests();
+        let mut checkpoint_delta =
+            IndexCheckpointDelta::for_source("source-a", CheckpointDelta::from(3..7));
+        checkpoint_delta
+            .extend("source-b", CheckpointDelta::from(0..2))
+            .unwrap();
+        let expected_checkpoint_delta = checkpoint_delta.clone();
+        let mut mock_metastore = MockMetastore::default();
+        mock_metastore
+            .expect_publish_multi_source_splits()
+            .withf(move |index_id, split_ids, checkpoint_delta| {
+                index_id == "index"
+                    && split_ids[..] == ["split1"]
+                    && checkpoint_delta == &expected_checkpoint_delta
+            })
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        let (merge_planner_mailbox, _merge_planner_inbox) = create_test_mailbox();
+        let (source_a_mailbox, source_a_inbox) = create_test_mailbox();
+        let (source_b_mailbox, source_b_inbox) = create_test_mailbox();
+        let publisher = Publisher::new(
+            PublisherType::MainPublisher,
+            "source-a".to_string(),
+            Arc::new(mock_metastore),
+            merge_planner_mailbox,
+            Some(source_a_mailbox),
+        )
+        .with_source_mailbox("source-b".to_string(), source_b_mailbox);
+        let universe = Universe::new();
+        let (publisher_mailbox, publisher_handle) = universe.spawn_actor(publisher).spawn_async();
+        let (split_future_tx, split_future_rx) = oneshot::channel::<PublisherMessage>();
+        assert!(universe
+            .send_message(&publisher_mailbox, split_future_rx)
+            .await
+            .is_ok());
+        assert!(split_future_tx
+            .send(PublisherMessage {
+                index_id: "index".to_string(),
+                operation: PublishOperation::PublishNewSplit {
+                    new_split: SplitMetadata {
+                        split_id: "split1".to_string(),
+                        ..Default::default()
+                    },
+                    checkpoint_delta,
+                    split_date_of_birth: Instant::now(),
+                },
+            })
+            .is_ok());
+        let publisher_observation = publisher_handle.process_pending_and_observe().await.state;
+        assert_eq!(publisher_observation.num_published_splits, 1);
+        for (source_inbox, position) in [(source_a_inbox, 6u64), (source_b_inbox, 1u64)] {
+            let mut source_msgs = source_inbox.drain_available_message_for_test();
+            assert_eq!(source_msgs.len(), 1);
+            let expected_checkpoint: SourceCheckpoint =
+                vec![(PartitionId::default(), Position::from(position))]
+                    .into_iter()
+                    .collect();
+            assert!(matches!(
+                source_msgs.pop().unwrap(),
+                SourceActorMessage::SuggestTruncate(checkpoint) if checkpoint == expected_checkpoint
+            ));
+        }
+    }
 }
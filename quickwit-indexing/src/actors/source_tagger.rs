@@ -0,0 +1,127 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use quickwit_actors::{Actor, ActorContext, ActorExitStatus, Mailbox, QueueCapacity, SyncActor};
+
+use crate::models::IndexerMessage;
+
+/// Tags the batches emitted by one of the sources of a pipeline running several sources with
+/// the id of the source, before forwarding them to the indexer shared by the sources.
+///
+/// The indexer uses the tag to apply the transform and filter of the source, and to track the
+/// checkpoint of each source separately.
+pub struct SourceTagger {
+    source_id: String,
+    indexer_mailbox: Mailbox<IndexerMessage>,
+    num_batches: u64,
+}
+
+impl SourceTagger {
+    pub fn new(source_id: String, indexer_mailbox: Mailbox<IndexerMessage>) -> Self {
+        SourceTagger {
+            source_id,
+            indexer_mailbox,
+            num_batches: 0,
+        }
+    }
+}
+
+impl Actor for SourceTagger {
+    type Message = IndexerMessage;
+
+    type ObservableState = u64;
+
+    fn observable_state(&self) -> Self::ObservableState {
+        self.num_batches
+    }
+
+    fn queue_capacity(&self) -> QueueCapacity {
+        QueueCapacity::Bounded(1)
+    }
+
+    fn name(&self) -> String {
+        "SourceTagger".to_string()
+    }
+}
+
+impl SyncActor for SourceTagger {
+    fn process_message(
+        &mut self,
+        message: IndexerMessage,
+        ctx: &ActorContext<Self>,
+    ) -> Result<(), ActorExitStatus> {
+        match message {
+            IndexerMessage::Batch(batch) => {
+                let source_batch = IndexerMessage::SourceBatch {
+                    source_id: self.source_id.clone(),
+                    batch,
+                };
+                ctx.send_message_blocking(&self.indexer_mailbox, source_batch)?;
+                self.num_batches += 1;
+            }
+            IndexerMessage::Flush => {
+                ctx.send_message_blocking(&self.indexer_mailbox, IndexerMessage::Flush)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_actors::{create_test_mailbox, Universe};
+    use quickwit_metastore::checkpoint::CheckpointDelta;
+
+    use super::*;
+    use crate::models::RawDocBatch;
+
+    #[tokio::test]
+    async fn test_source_tagger() -> anyhow::Result<()> {
+        let (indexer_mailbox, indexer_inbox) = create_test_mailbox();
+        let source_tagger = SourceTagger::new("my-source".to_string(), indexer_mailbox);
+        let universe = Universe::new();
+        let (source_tagger_mailbox, source_tagger_handle) =
+            universe.spawn_actor(source_tagger).spawn_sync();
+        universe
+            .send_message(
+                &source_tagger_mailbox,
+                RawDocBatch {
+                    docs: vec!["{}".to_string()],
+                    checkpoint_delta: CheckpointDelta::from(0..1),
+                }
+                .into(),
+            )
+            .await?;
+        universe
+            .send_exit_with_success(&source_tagger_mailbox)
+            .await?;
+        let (exit_status, num_batches) = source_tagger_handle.join().await;
+        assert!(exit_status.is_success());
+        assert_eq!(num_batches, 1);
+        let messages = indexer_inbox.drain_available_message_for_test();
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(
+            &messages[0],
+            IndexerMessage::SourceBatch { source_id, batch }
+                if source_id == "my-source" && batch.checkpoint_delta == CheckpointDelta::from(0..1)
+        ));
+        Ok(())
+    }
+}
@@ -82,6 +82,13 @@ impl Uploader {
 pub struct UploaderCounters {
     pub num_staged_splits: Arc<AtomicU64>,
     pub num_uploaded_splits: Arc<AtomicU64>,
+    /// Number of documents of the uploaded splits.
+    pub num_uploaded_docs: Arc<AtomicU64>,
+    /// Size in bytes of the uploaded splits.
+    pub num_uploaded_bytes: Arc<AtomicU64>,
+    /// Number of uploads in progress when the uploader was observed, at most
+    /// [`MAX_CONCURRENT_SPLIT_UPLOAD`].
+    pub num_ongoing_uploads: usize,
 }
 
 impl Actor for Uploader {
@@ -89,9 +96,11 @@ impl Actor for Uploader {
 
     type ObservableState = UploaderCounters;
 
-    #[allow(clippy::unused_unit)]
     fn observable_state(&self) -> Self::ObservableState {
-        self.counters.clone()
+        let mut counters = self.counters.clone();
+        counters.num_ongoing_uploads =
+            MAX_CONCURRENT_SPLIT_UPLOAD - self.concurrent_upload_permits.available_permits();
+        counters
     }
 
     fn queue_capacity(&self) -> QueueCapacity {
@@ -116,6 +125,9 @@ fn create_split_metadata(split: &PackagedSplit, footer_offsets: Range<u64>) -> S
         create_timestamp: Utc::now().timestamp(),
         tags: split.tags.clone(),
         demux_num_ops: split.demux_num_ops,
+        sort_by: split.sort_by.clone(),
+        field_stats: split.field_stats.clone(),
+        replaced_split_ids: split.replaced_split_ids.iter().cloned().collect(),
         footer_offsets,
     }
 }
@@ -180,6 +192,12 @@ async fn stage_and_upload_split(
         )
         .await?;
     counters.num_uploaded_splits.fetch_add(1, Ordering::SeqCst);
+    counters
+        .num_uploaded_docs
+        .fetch_add(split_metadata.num_docs as u64, Ordering::SeqCst);
+    counters
+        .num_uploaded_bytes
+        .fetch_add(split_metadata.footer_offsets.end, Ordering::SeqCst);
     Ok(split_metadata)
 }
 
@@ -269,7 +287,7 @@ mod tests {
     use std::time::Instant;
 
     use quickwit_actors::{create_test_mailbox, ObservationType, Universe};
-    use quickwit_metastore::checkpoint::CheckpointDelta;
+    use quickwit_metastore::checkpoint::{CheckpointDelta, IndexCheckpointDelta};
     use quickwit_metastore::MockMetastore;
     use quickwit_storage::RamStorage;
 
@@ -308,13 +326,18 @@ mod tests {
                 PackagedSplitBatch::new(vec![PackagedSplit {
                     split_id: "test-split".to_string(),
                     index_id: "test-index".to_string(),
-                    checkpoint_deltas: vec![CheckpointDelta::from(3..15)],
+                    checkpoint_deltas: vec![IndexCheckpointDelta::for_source(
+                        "test-source",
+                        CheckpointDelta::from(3..15),
+                    )],
                     time_range: Some(1_628_203_589i64..=1_628_203_640i64),
                     size_in_bytes: 1_000,
                     split_scratch_directory,
                     num_docs: 10,
                     demux_num_ops: 0,
                     tags: Default::default(),
+                    sort_by: None,
+                    field_stats: Default::default(),
                     replaced_split_ids: Vec::new(),
                     split_date_of_birth: Instant::now(),
                     hotcache_bytes: vec![],
@@ -338,7 +361,10 @@ mod tests {
         } = publisher_message.operation
         {
             assert_eq!(new_split.split_id(), "test-split");
-            assert_eq!(checkpoint_delta, CheckpointDelta::from(3..15));
+            assert_eq!(
+                checkpoint_delta,
+                IndexCheckpointDelta::for_source("test-source", CheckpointDelta::from(3..15))
+            );
         } else {
             panic!("Expected publish new split operation");
         }
@@ -379,13 +405,18 @@ mod tests {
         let packaged_split_1 = PackagedSplit {
             split_id: "test-split-1".to_string(),
             index_id: "test-index".to_string(),
-            checkpoint_deltas: vec![CheckpointDelta::from(3..15), CheckpointDelta::from(16..18)],
+            checkpoint_deltas: vec![
+                IndexCheckpointDelta::for_source("test-source", CheckpointDelta::from(3..15)),
+                IndexCheckpointDelta::for_source("test-source", CheckpointDelta::from(16..18)),
+            ],
             time_range: Some(1_628_203_589i64..=1_628_203_640i64),
             size_in_bytes: 1_000,
             split_scratch_directory: split_scratch_directory_1,
             num_docs: 10,
             demux_num_ops: 1,
             tags: Default::default(),
+            sort_by: None,
+            field_stats: Default::default(),
             replaced_split_ids: vec![
                 "replaced-split-1".to_string(),
                 "replaced-split-2".to_string(),
@@ -397,13 +428,18 @@ mod tests {
         let package_split_2 = PackagedSplit {
             split_id: "test-split-2".to_string(),
             index_id: "test-index".to_string(),
-            checkpoint_deltas: vec![CheckpointDelta::from(3..15), CheckpointDelta::from(16..18)],
+            checkpoint_deltas: vec![
+                IndexCheckpointDelta::for_source("test-source", CheckpointDelta::from(3..15)),
+                IndexCheckpointDelta::for_source("test-source", CheckpointDelta::from(16..18)),
+            ],
             time_range: Some(1_628_203_589i64..=1_628_203_640i64),
             size_in_bytes: 1_000,
             split_scratch_directory: split_scratch_directory_2,
             num_docs: 10,
             demux_num_ops: 1,
             tags: Default::default(),
+            sort_by: None,
+            field_stats: Default::default(),
             replaced_split_ids: vec![
                 "replaced-split-1".to_string(),
                 "replaced-split-2".to_string(),
@@ -0,0 +1,238 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use quickwit_config::DeduplicationSettings;
+use tantivy::schema::{Field, Value};
+use tantivy::Document;
+
+/// Fixed-size bloom filter over 64-bit hashes, using double hashing to derive the bit positions.
+struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    fn with_capacity(num_items: usize, false_positive_rate: f64) -> Self {
+        let num_items = num_items.max(1) as f64;
+        let ln_2 = std::f64::consts::LN_2;
+        let num_bits = (-num_items * false_positive_rate.ln() / (ln_2 * ln_2))
+            .ceil()
+            .max(64.0) as u64;
+        let num_hashes = ((num_bits as f64 / num_items) * ln_2)
+            .round()
+            .clamp(1.0, 16.0) as u32;
+        let num_words = ((num_bits + 63) / 64) as usize;
+        Self {
+            bits: vec![0; num_words],
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    fn bit_positions(&self, hash: u64) -> impl Iterator<Item = u64> {
+        let num_bits = self.num_bits;
+        let hash_1 = hash & 0xFFFF_FFFF;
+        let hash_2 = (hash >> 32) | 1;
+        (0..self.num_hashes as u64)
+            .map(move |i| hash_1.wrapping_add(i.wrapping_mul(hash_2)) % num_bits)
+    }
+
+    fn contains(&self, hash: u64) -> bool {
+        self.bit_positions(hash)
+            .all(|position| self.bits[(position / 64) as usize] & (1 << (position % 64)) != 0)
+    }
+
+    fn insert(&mut self, hash: u64) {
+        for position in self.bit_positions(hash) {
+            self.bits[(position / 64) as usize] |= 1 << (position % 64);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.bits.iter_mut().for_each(|word| *word = 0);
+    }
+}
+
+/// Remembers the doc IDs seen within a time window with two generations of bloom filters.
+///
+/// Doc IDs are inserted in the current generation, which becomes the previous one once it is
+/// `window` old. A doc ID is therefore remembered for at least `window` and at most twice
+/// `window`. Like any bloom filter, it can report an ID that was never inserted as seen.
+pub(crate) struct RollingBloomFilter {
+    current: BloomFilter,
+    previous: BloomFilter,
+    current_start: Instant,
+    window: Duration,
+}
+
+impl RollingBloomFilter {
+    pub fn new(
+        window: Duration,
+        max_num_docs_per_window: usize,
+        false_positive_rate: f64,
+        now: Instant,
+    ) -> Self {
+        // Both generations are checked on lookup, so each of them gets half of the false
+        // positive budget.
+        let generation_false_positive_rate = false_positive_rate / 2.0;
+        Self {
+            current: BloomFilter::with_capacity(
+                max_num_docs_per_window,
+                generation_false_positive_rate,
+            ),
+            previous: BloomFilter::with_capacity(
+                max_num_docs_per_window,
+                generation_false_positive_rate,
+            ),
+            current_start: now,
+            window,
+        }
+    }
+
+    fn roll(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.current_start);
+        if elapsed < self.window {
+            return;
+        }
+        if elapsed < self.window * 2 {
+            std::mem::swap(&mut self.current, &mut self.previous);
+            self.current.clear();
+            self.current_start += self.window;
+        } else {
+            self.current.clear();
+            self.previous.clear();
+            self.current_start = now;
+        }
+    }
+
+    /// Records the doc ID hash and returns whether it was (probably) already seen.
+    pub fn check_and_insert(&mut self, doc_id_hash: u64, now: Instant) -> bool {
+        self.roll(now);
+        if self.current.contains(doc_id_hash) || self.previous.contains(doc_id_hash) {
+            return true;
+        }
+        self.current.insert(doc_id_hash);
+        false
+    }
+}
+
+/// Drops documents whose ID was already ingested within the deduplication window.
+///
+/// The filter is not persisted with the checkpoint: it lives as long as the indexer and starts
+/// empty when the indexing pipeline restarts. The documents replayed from the last published
+/// checkpoint were never published, so indexing them again is correct, but a duplicate of a
+/// document published before the restart is not detected until the window is filled again.
+/// Persisting the filter would add megabytes to each checkpoint update, for a gap that only
+/// opens on restarts.
+pub(crate) struct DocDeduplicator {
+    doc_id_field: Field,
+    filter: RollingBloomFilter,
+}
+
+impl DocDeduplicator {
+    pub fn new(doc_id_field: Field, settings: &DeduplicationSettings) -> Self {
+        Self {
+            doc_id_field,
+            filter: RollingBloomFilter::new(
+                settings.window(),
+                settings.max_num_docs_per_window,
+                settings.false_positive_rate,
+                Instant::now(),
+            ),
+        }
+    }
+
+    /// Returns whether the document is a duplicate. Documents without a doc ID are never
+    /// considered duplicates.
+    pub fn is_duplicate(&mut self, document: &Document) -> bool {
+        match document.get_first(self.doc_id_field).and_then(hash_doc_id) {
+            Some(doc_id_hash) => self.filter.check_and_insert(doc_id_hash, Instant::now()),
+            None => false,
+        }
+    }
+}
+
+fn hash_doc_id(value: &Value) -> Option<u64> {
+    let mut hasher = DefaultHasher::new();
+    match value {
+        Value::Str(text) => text.hash(&mut hasher),
+        Value::U64(val) => val.hash(&mut hasher),
+        Value::I64(val) => val.hash(&mut hasher),
+        Value::F64(val) => val.to_bits().hash(&mut hasher),
+        Value::Bytes(bytes) => bytes.hash(&mut hasher),
+        _ => return None,
+    }
+    Some(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc_id_hash(doc_id: u64) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        doc_id.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_rolling_bloom_filter_detects_duplicates() {
+        let now = Instant::now();
+        let mut filter = RollingBloomFilter::new(Duration::from_secs(60), 1_000, 0.001, now);
+        for doc_id in 0..1_000 {
+            assert!(!filter.check_and_insert(doc_id_hash(doc_id), now));
+        }
+        for doc_id in 0..1_000 {
+            assert!(filter.check_and_insert(doc_id_hash(doc_id), now));
+        }
+    }
+
+    #[test]
+    fn test_rolling_bloom_filter_false_positive_rate() {
+        let now = Instant::now();
+        let mut filter = RollingBloomFilter::new(Duration::from_secs(60), 10_000, 0.01, now);
+        for doc_id in 0..10_000 {
+            filter.check_and_insert(doc_id_hash(doc_id), now);
+        }
+        let num_false_positives = (10_000..11_000)
+            .filter(|doc_id| filter.check_and_insert(doc_id_hash(*doc_id), now))
+            .count();
+        assert!(num_false_positives < 30, "{}", num_false_positives);
+    }
+
+    #[test]
+    fn test_rolling_bloom_filter_forgets_after_window() {
+        let window = Duration::from_secs(60);
+        let start = Instant::now();
+        let mut filter = RollingBloomFilter::new(window, 1_000, 0.001, start);
+        assert!(!filter.check_and_insert(42, start));
+        // Still remembered during the next window.
+        assert!(filter.check_and_insert(42, start + Duration::from_secs(90)));
+        // Forgotten once two windows elapsed since it was inserted.
+        assert!(!filter.check_and_insert(42, start + Duration::from_secs(125)));
+        // Forgotten after a long pause.
+        assert!(!filter.check_and_insert(43, start + Duration::from_secs(130)));
+        assert!(!filter.check_and_insert(43, start + Duration::from_secs(1_000)));
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use quickwit_config::{log_level_rank, DocFilterCondition, DocFilterOperator, DocFilterParams};
+use regex::Regex;
+use serde_json::Value as JsonValue;
+
+enum Predicate {
+    Equals(JsonValue),
+    In(Vec<JsonValue>),
+    Matches(Regex),
+    Exists(bool),
+    Gte(f64),
+    Lte(f64),
+    MinLevel(u8),
+}
+
+impl Predicate {
+    fn evaluate(&self, value_opt: Option<&JsonValue>) -> bool {
+        let value = match (self, value_opt) {
+            (Predicate::Exists(exists), _) => return value_opt.is_some() == *exists,
+            (_, Some(value)) => value,
+            (_, None) => return false,
+        };
+        match self {
+            Predicate::Equals(expected) => value == expected,
+            Predicate::In(expected_values) => expected_values.contains(value),
+            Predicate::Matches(regex) => value.as_str().map_or(false, |text| regex.is_match(text)),
+            Predicate::Gte(bound) => value.as_f64().map_or(false, |number| number >= *bound),
+            Predicate::Lte(bound) => value.as_f64().map_or(false, |number| number <= *bound),
+            Predicate::MinLevel(min_rank) => value
+                .as_str()
+                .and_then(log_level_rank)
+                .map_or(false, |rank| rank >= *min_rank),
+            Predicate::Exists(_) => unreachable!(),
+        }
+    }
+}
+
+pub(crate) struct Condition {
+    field_path: Vec<String>,
+    predicate: Predicate,
+}
+
+impl Condition {
+    pub fn try_new(condition: &DocFilterCondition) -> anyhow::Result<Self> {
+        let predicate = match &condition.operator {
+            DocFilterOperator::Equals(value) => Predicate::Equals(value.clone()),
+            DocFilterOperator::In(values) => Predicate::In(values.clone()),
+            DocFilterOperator::Matches(pattern) => Predicate::Matches(Regex::new(pattern)?),
+            DocFilterOperator::Exists(exists) => Predicate::Exists(*exists),
+            DocFilterOperator::Gte(bound) => Predicate::Gte(*bound),
+            DocFilterOperator::Lte(bound) => Predicate::Lte(*bound),
+            DocFilterOperator::MinLevel(level) => {
+                let rank = log_level_rank(level)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown log level `{}`.", level))?;
+                Predicate::MinLevel(rank)
+            }
+        };
+        Ok(Condition {
+            field_path: condition.field.split('.').map(str::to_string).collect(),
+            predicate,
+        })
+    }
+
+    pub fn evaluate(&self, doc: &JsonValue) -> bool {
+        let value_opt = self
+            .field_path
+            .iter()
+            .try_fold(doc, |value, field_name| value.get(field_name));
+        self.predicate.evaluate(value_opt)
+    }
+}
+
+/// Drops the documents of a source following its [`DocFilterParams`], before they are parsed
+/// by the doc mapper.
+pub struct DocFilter {
+    include: Vec<Condition>,
+    exclude: Vec<Condition>,
+}
+
+impl DocFilter {
+    pub fn try_new(filter_params: &DocFilterParams) -> anyhow::Result<Self> {
+        let build_conditions = |conditions: &[DocFilterCondition]| {
+            conditions
+                .iter()
+                .map(Condition::try_new)
+                .collect::<anyhow::Result<Vec<_>>>()
+        };
+        Ok(DocFilter {
+            include: build_conditions(&filter_params.include)?,
+            exclude: build_conditions(&filter_params.exclude)?,
+        })
+    }
+
+    /// Returns `true` if the document must be dropped. Documents that are not valid JSON
+    /// objects are kept, so that the indexer reports them as invalid.
+    pub fn is_dropped(&self, doc_json: &str) -> bool {
+        let doc: JsonValue = match serde_json::from_str(doc_json) {
+            Ok(doc @ JsonValue::Object(_)) => doc,
+            _ => return false,
+        };
+        !self
+            .include
+            .iter()
+            .all(|condition| condition.evaluate(&doc))
+            || self
+                .exclude
+                .iter()
+                .any(|condition| condition.evaluate(&doc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn condition(field: &str, operator: DocFilterOperator) -> DocFilterCondition {
+        DocFilterCondition {
+            field: field.to_string(),
+            operator,
+        }
+    }
+
+    #[test]
+    fn test_doc_filter() -> anyhow::Result<()> {
+        let doc_filter = DocFilter::try_new(&DocFilterParams {
+            include: vec![
+                condition("level", DocFilterOperator::MinLevel("WARN".to_string())),
+                condition("http.status", DocFilterOperator::Gte(400.0)),
+            ],
+            exclude: vec![
+                condition(
+                    "http.path",
+                    DocFilterOperator::Matches("^/health".to_string()),
+                ),
+                condition("service", DocFilterOperator::In(vec![json!("canary")])),
+                condition("debug", DocFilterOperator::Exists(true)),
+            ],
+        })?;
+        let is_dropped = |doc: JsonValue| doc_filter.is_dropped(&doc.to_string());
+
+        assert!(!is_dropped(
+            json!({"level": "error", "http": {"status": 503, "path": "/orders"}})
+        ));
+        assert!(!is_dropped(
+            json!({"level": "WARNING", "http": {"status": 404}, "service": "api"})
+        ));
+        assert!(is_dropped(
+            json!({"level": "INFO", "http": {"status": 500}})
+        ));
+        assert!(is_dropped(
+            json!({"level": "ERROR", "http": {"status": 200}})
+        ));
+        assert!(is_dropped(json!({"level": "ERROR"})));
+        assert!(is_dropped(
+            json!({"level": "ERROR", "http": {"status": 500, "path": "/healthz"}})
+        ));
+        assert!(is_dropped(
+            json!({"level": "ERROR", "http": {"status": 500}, "service": "canary"})
+        ));
+        assert!(is_dropped(
+            json!({"level": "ERROR", "http": {"status": 500}, "debug": false})
+        ));
+        assert!(!doc_filter.is_dropped("not json"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_doc_filter_equals_and_lte() -> anyhow::Result<()> {
+        let doc_filter = DocFilter::try_new(&DocFilterParams {
+            include: vec![
+                condition("env", DocFilterOperator::Equals(json!("prod"))),
+                condition("latency_ms", DocFilterOperator::Lte(1_000.0)),
+            ],
+            exclude: Vec::new(),
+        })?;
+        assert!(!doc_filter.is_dropped(r#"{"env": "prod", "latency_ms": 12.5}"#));
+        assert!(doc_filter.is_dropped(r#"{"env": "prod", "latency_ms": 1500}"#));
+        assert!(doc_filter.is_dropped(r#"{"env": "dev", "latency_ms": 12}"#));
+        assert!(doc_filter.is_dropped(r#"{"env": "prod", "latency_ms": "12"}"#));
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use quickwit_config::{DocTransformParams, DocTransformStep};
+use quickwit_doc_mapper::DateFormat;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+use crate::doc_filter::Condition;
+
+fn field_path(field: &str) -> Vec<String> {
+    field.split('.').map(str::to_string).collect()
+}
+
+enum Step {
+    Rename {
+        from: Vec<String>,
+        to: Vec<String>,
+    },
+    Remove {
+        field_paths: Vec<Vec<String>>,
+    },
+    Set {
+        field_path: Vec<String>,
+        value: JsonValue,
+    },
+    ParseTimestamp {
+        field_path: Vec<String>,
+        input_formats: Vec<DateFormat>,
+        output_format: DateFormat,
+    },
+    DropIf(Condition),
+}
+
+impl Step {
+    fn try_new(step: &DocTransformStep) -> anyhow::Result<Self> {
+        let step = match step {
+            DocTransformStep::Rename { from, to } => Step::Rename {
+                from: field_path(from),
+                to: field_path(to),
+            },
+            DocTransformStep::Remove { fields } => Step::Remove {
+                field_paths: fields.iter().map(|field| field_path(field)).collect(),
+            },
+            DocTransformStep::Set { field, value } => Step::Set {
+                field_path: field_path(field),
+                value: value.clone(),
+            },
+            DocTransformStep::ParseTimestamp {
+                field,
+                input_formats,
+                output_format,
+            } => Step::ParseTimestamp {
+                field_path: field_path(field),
+                input_formats: input_formats.clone(),
+                output_format: output_format.clone(),
+            },
+            DocTransformStep::DropIf(condition) => Step::DropIf(Condition::try_new(condition)?),
+        };
+        Ok(step)
+    }
+}
+
+/// Returns the object holding the fields designated by `parent_path`, creating the missing
+/// objects along the way if `create` is set.
+fn parent_object<'a>(
+    doc: &'a mut JsonValue,
+    parent_path: &[String],
+    create: bool,
+) -> Option<&'a mut JsonMap<String, JsonValue>> {
+    let mut object = doc.as_object_mut()?;
+    for field_name in parent_path {
+        if create && !object.contains_key(field_name) {
+            object.insert(field_name.clone(), JsonValue::Object(JsonMap::new()));
+        }
+        object = object.get_mut(field_name)?.as_object_mut()?;
+    }
+    Some(object)
+}
+
+fn remove_field(doc: &mut JsonValue, field_path: &[String]) -> Option<JsonValue> {
+    let (field_name, parent_path) = field_path.split_last()?;
+    parent_object(doc, parent_path, false)?.remove(field_name)
+}
+
+/// Sets the value of a field. The value is handed back if one of the parents of the field is not
+/// an object.
+fn insert_field(
+    doc: &mut JsonValue,
+    field_path: &[String],
+    value: JsonValue,
+) -> Result<(), JsonValue> {
+    let (field_name, parent_path) = match field_path.split_last() {
+        Some(split) => split,
+        None => return Err(value),
+    };
+    match parent_object(doc, parent_path, true) {
+        Some(object) => {
+            object.insert(field_name.clone(), value);
+            Ok(())
+        }
+        None => Err(value),
+    }
+}
+
+fn get_field_mut<'a>(doc: &'a mut JsonValue, field_path: &[String]) -> Option<&'a mut JsonValue> {
+    let (field_name, parent_path) = field_path.split_last()?;
+    parent_object(doc, parent_path, false)?.get_mut(field_name)
+}
+
+/// Applies the [`DocTransformParams`] of a source to its documents, before they are filtered and
+/// parsed by the doc mapper.
+pub struct DocTransform {
+    steps: Vec<Step>,
+}
+
+impl DocTransform {
+    pub fn try_new(transform_params: &DocTransformParams) -> anyhow::Result<Self> {
+        let steps = transform_params
+            .steps
+            .iter()
+            .map(Step::try_new)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(DocTransform { steps })
+    }
+
+    /// Returns the transformed document, or `None` if the document must be dropped. Documents
+    /// that are not valid JSON objects are returned unchanged, so that the indexer reports them
+    /// as invalid, and so are the fields whose timestamp cannot be parsed.
+    pub fn transform(&self, doc_json: String) -> Option<String> {
+        let mut doc: JsonValue = match serde_json::from_str(&doc_json) {
+            Ok(doc @ JsonValue::Object(_)) => doc,
+            _ => return Some(doc_json),
+        };
+        for step in &self.steps {
+            match step {
+                Step::Rename { from, to } => {
+                    if let Some(value) = remove_field(&mut doc, from) {
+                        // The field keeps its name when it cannot be moved.
+                        if let Err(value) = insert_field(&mut doc, to, value) {
+                            let _ = insert_field(&mut doc, from, value);
+                        }
+                    }
+                }
+                Step::Remove { field_paths } => {
+                    for field_path in field_paths {
+                        remove_field(&mut doc, field_path);
+                    }
+                }
+                Step::Set { field_path, value } => {
+                    let _ = insert_field(&mut doc, field_path, value.clone());
+                }
+                Step::ParseTimestamp {
+                    field_path,
+                    input_formats,
+                    output_format,
+                } => {
+                    if let Some(value) = get_field_mut(&mut doc, field_path) {
+                        // Values that cannot be parsed or formatted are left untouched.
+                        if let Some(formatted_value) = input_formats
+                            .iter()
+                            .find_map(|input_format| input_format.parse_json(value))
+                            .and_then(|date_time| output_format.format_json(&date_time))
+                        {
+                            *value = formatted_value;
+                        }
+                    }
+                }
+                Step::DropIf(condition) => {
+                    if condition.evaluate(&doc) {
+                        return None;
+                    }
+                }
+            }
+        }
+        Some(doc.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_config::{DocFilterCondition, DocFilterOperator};
+    use serde_json::json;
+
+    use super::*;
+
+    fn transform(doc_transform: &DocTransform, doc: JsonValue) -> Option<JsonValue> {
+        doc_transform
+            .transform(doc.to_string())
+            .map(|doc_json| serde_json::from_str(&doc_json).unwrap())
+    }
+
+    #[test]
+    fn test_doc_transform() -> anyhow::Result<()> {
+        let doc_transform = DocTransform::try_new(&DocTransformParams {
+            steps: vec![
+                DocTransformStep::Rename {
+                    from: "ts".to_string(),
+                    to: "timestamp".to_string(),
+                },
+                DocTransformStep::Rename {
+                    from: "svc".to_string(),
+                    to: "service.name".to_string(),
+                },
+                DocTransformStep::Remove {
+                    fields: vec!["debug".to_string(), "http.headers".to_string()],
+                },
+                DocTransformStep::Set {
+                    field: "env".to_string(),
+                    value: json!("prod"),
+                },
+                DocTransformStep::ParseTimestamp {
+                    field: "timestamp".to_string(),
+                    input_formats: vec![
+                        DateFormat::Rfc3339,
+                        DateFormat::Strftime("%Y-%m-%d %H:%M:%S".to_string()),
+                    ],
+                    output_format: DateFormat::EpochSecs,
+                },
+                DocTransformStep::DropIf(DocFilterCondition {
+                    field: "service.name".to_string(),
+                    operator: DocFilterOperator::Equals(json!("canary")),
+                }),
+            ],
+        })?;
+        assert_eq!(
+            transform(
+                &doc_transform,
+                json!({
+                    "ts": "2021-12-16T10:05:30+01:00",
+                    "svc": "checkout",
+                    "debug": true,
+                    "http": {"status": 200, "headers": {"host": "shop"}},
+                })
+            ),
+            Some(json!({
+                "timestamp": 1639645530,
+                "service": {"name": "checkout"},
+                "http": {"status": 200},
+                "env": "prod",
+            }))
+        );
+        assert_eq!(
+            transform(
+                &doc_transform,
+                json!({"ts": "2021-12-16 09:05:30", "env": "dev"})
+            ),
+            Some(json!({"timestamp": 1639645530, "env": "prod"}))
+        );
+        // Timestamps that cannot be parsed are left to the doc mapper.
+        assert_eq!(
+            transform(&doc_transform, json!({"ts": "yesterday"})),
+            Some(json!({"timestamp": "yesterday", "env": "prod"}))
+        );
+        assert_eq!(
+            transform(&doc_transform, json!({"ts": 1, "svc": "canary"})),
+            None
+        );
+        assert_eq!(
+            doc_transform.transform("not json".to_string()).unwrap(),
+            "not json"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_doc_transform_keeps_fields_that_cannot_be_renamed() -> anyhow::Result<()> {
+        let doc_transform = DocTransform::try_new(&DocTransformParams {
+            steps: vec![DocTransformStep::Rename {
+                from: "status".to_string(),
+                to: "http.status".to_string(),
+            }],
+        })?;
+        assert_eq!(
+            transform(&doc_transform, json!({"status": 200, "http": "GET"})),
+            Some(json!({"status": 200, "http": "GET"}))
+        );
+        assert_eq!(
+            transform(&doc_transform, json!({"http": {"method": "GET"}})),
+            Some(json!({"http": {"method": "GET"}}))
+        );
+        Ok(())
+    }
+}
@@ -21,14 +21,12 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use futures::StreamExt;
-use quickwit_actors::ActorContext;
-use quickwit_metastore::{Metastore, MetastoreError, SplitMetadata, SplitState};
+use quickwit_metastore::{LeaseFence, Metastore, MetastoreError, SplitMetadata, SplitState};
 use quickwit_storage::StorageError;
 use tantivy::chrono::Utc;
 use thiserror::Error;
-use tracing::error;
+use tracing::{error, info};
 
-use crate::actors::GarbageCollector;
 use crate::split_store::IndexingSplitStore;
 
 const MAX_CONCURRENT_STORAGE_REQUESTS: usize = if cfg!(test) { 2 } else { 10 };
@@ -72,7 +70,7 @@ impl From<&SplitMetadata> for FileEntry {
 /// * `deletion_grace_period` -  Threshold period after which a marked as deleted split can be
 ///   safely deleted.
 /// * `dry_run` - Should this only return a list of affected files without performing deletion.
-/// * `ctx_opt` - A context for reporting progress (only useful within quickwit actor).
+/// * `fence_opt` - A lease the metastore checks atomically with each mutation of the splits.
 pub async fn run_garbage_collect(
     index_id: &str,
     split_store: IndexingSplitStore,
@@ -80,7 +78,7 @@ pub async fn run_garbage_collect(
     staged_grace_period: Duration,
     deletion_grace_period: Duration,
     dry_run: bool,
-    ctx_opt: Option<&ActorContext<GarbageCollector>>,
+    fence_opt: Option<&LeaseFence>,
 ) -> anyhow::Result<Vec<FileEntry>> {
     // Select staged splits with staging timestamp older than grace period timestamp.
     let grace_period_timestamp = Utc::now().timestamp() - staged_grace_period.as_secs() as i64;
@@ -93,16 +91,14 @@ pub async fn run_garbage_collect(
         .filter(|meta| meta.update_timestamp < grace_period_timestamp)
         .map(|meta| meta.split_metadata)
         .collect();
-    if let Some(ctx) = ctx_opt {
-        ctx.record_progress();
-    }
-
     if dry_run {
+        let leased_split_ids = metastore.list_leased_splits(index_id).await?;
         let mut splits_marked_for_deletion = metastore
             .list_splits(index_id, SplitState::MarkedForDeletion, None, None)
             .await?
             .into_iter()
             .map(|meta| meta.split_metadata)
+            .filter(|split_metadata| !leased_split_ids.contains(split_metadata.split_id()))
             .collect::<Vec<_>>();
         splits_marked_for_deletion.extend(deletable_staged_splits);
 
@@ -118,13 +114,19 @@ pub async fn run_garbage_collect(
         .iter()
         .map(|meta| meta.split_id())
         .collect();
-    metastore
-        .mark_splits_for_deletion(index_id, &split_ids)
-        .await?;
+    if let Some(fence) = fence_opt {
+        metastore
+            .mark_splits_for_deletion_fenced(index_id, &split_ids, fence)
+            .await?;
+    } else {
+        metastore
+            .mark_splits_for_deletion(index_id, &split_ids)
+            .await?;
+    }
 
     // We wait another 2 minutes until the split is actually deleted.
     let grace_period_deletion = Utc::now().timestamp() - deletion_grace_period.as_secs() as i64;
-    let splits_to_delete = metastore
+    let splits_to_delete: Vec<SplitMetadata> = metastore
         .list_splits(index_id, SplitState::MarkedForDeletion, None, None)
         .await?
         .into_iter()
@@ -133,12 +135,29 @@ pub async fn run_garbage_collect(
         .map(|meta| meta.split_metadata)
         .collect();
 
+    // Spare the splits that in-flight searches are still reading. The leases are listed after
+    // the splits to delete, so a search that leased a split before it is listed keeps it. The
+    // deletion grace period covers searches that listed a split before it was marked for deletion
+    // but have not leased it yet.
+    let leased_split_ids = metastore.list_leased_splits(index_id).await?;
+    let (splits_to_delete, leased_splits): (Vec<SplitMetadata>, Vec<SplitMetadata>) =
+        splits_to_delete
+            .into_iter()
+            .partition(|split_metadata| !leased_split_ids.contains(split_metadata.split_id()));
+    if !leased_splits.is_empty() {
+        info!(
+            index_id = index_id,
+            num_leased_splits = leased_splits.len(),
+            "Sparing splits leased by in-flight searches."
+        );
+    }
+
     let deleted_files = delete_splits_with_files(
         index_id,
         split_store.clone(),
         metastore.clone(),
         splits_to_delete,
-        ctx_opt,
+        fence_opt,
     )
     .await?;
 
@@ -152,13 +171,13 @@ pub async fn run_garbage_collect(
 /// * `storage - The storage managing the target index.
 /// * `metastore` - The metastore managing the target index.
 /// * `splits`  - The list of splits to delete.
-/// * `ctx_opt` - A context for reporting progress (only useful within quickwit actor).
+/// * `fence_opt` - A lease the metastore checks atomically with the deletion of the splits.
 pub async fn delete_splits_with_files(
     index_id: &str,
     indexing_split_store: IndexingSplitStore,
     metastore: Arc<dyn Metastore>,
     splits: Vec<SplitMetadata>,
-    ctx_opt: Option<&ActorContext<GarbageCollector>>,
+    fence_opt: Option<&LeaseFence>,
 ) -> anyhow::Result<Vec<FileEntry>, SplitDeletionError> {
     let mut deleted_file_entries = Vec::new();
     let mut deleted_split_ids = Vec::new();
@@ -170,9 +189,6 @@ pub async fn delete_splits_with_files(
             async move {
                 let file_entry = FileEntry::from(&split);
                 let delete_result = moved_indexing_split_store.delete(split.split_id()).await;
-                if let Some(ctx) = ctx_opt {
-                    ctx.record_progress();
-                }
                 (split.split_id().to_string(), file_entry, delete_result)
             }
         })
@@ -199,11 +215,163 @@ pub async fn delete_splits_with_files(
 
     if !deleted_split_ids.is_empty() {
         let split_ids: Vec<&str> = deleted_split_ids.iter().map(String::as_str).collect();
-        metastore
-            .delete_splits(index_id, &split_ids)
-            .await
-            .map_err(SplitDeletionError::MetastoreFailure)?;
+        let delete_result = if let Some(fence) = fence_opt {
+            metastore
+                .delete_splits_fenced(index_id, &split_ids, fence)
+                .await
+        } else {
+            metastore.delete_splits(index_id, &split_ids).await
+        };
+        delete_result.map_err(SplitDeletionError::MetastoreFailure)?;
     }
 
     Ok(deleted_file_entries)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::path::Path;
+
+    use quickwit_metastore::{MockMetastore, Split};
+    use quickwit_storage::MockStorage;
+
+    use super::*;
+
+    const STAGED_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+    const DELETION_GRACE_PERIOD: Duration = Duration::from_secs(120);
+
+    fn make_splits(split_ids: &[&str], split_state: SplitState) -> Vec<Split> {
+        split_ids
+            .iter()
+            .map(|split_id| Split {
+                split_metadata: SplitMetadata {
+                    split_id: split_id.to_string(),
+                    footer_offsets: 5..20,
+                    ..Default::default()
+                },
+                split_state,
+                update_timestamp: 0i64,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_run_garbage_collect_calls_dependencies_appropriately() {
+        quickwit_common::setup_logging_for_tests();
+        let mut mock_storage = MockStorage::default();
+        mock_storage.expect_delete().times(3).returning(|path| {
+            assert!(
+                path == Path::new("a.split")
+                    || path == Path::new("b.split")
+                    || path == Path::new("c.split")
+            );
+            Ok(())
+        });
+        let mut mock_metastore = MockMetastore::default();
+        mock_metastore.expect_list_splits().times(2).returning(
+            |index_id, split_state, _time_range, _tags| {
+                assert_eq!(index_id, "foo-index");
+                let splits = match split_state {
+                    SplitState::Staged => make_splits(&["a"], SplitState::Staged),
+                    SplitState::MarkedForDeletion => {
+                        make_splits(&["a", "b", "c"], SplitState::MarkedForDeletion)
+                    }
+                    _ => panic!("only Staged and MarkedForDeletion expected."),
+                };
+                Ok(splits)
+            },
+        );
+        mock_metastore
+            .expect_list_leased_splits()
+            .returning(|_index_id| Ok(HashSet::new()));
+        mock_metastore
+            .expect_mark_splits_for_deletion()
+            .times(1)
+            .returning(|index_id, split_ids| {
+                assert_eq!(index_id, "foo-index");
+                assert_eq!(split_ids, vec!["a"]);
+                Ok(())
+            });
+        mock_metastore
+            .expect_delete_splits()
+            .times(1)
+            .returning(|index_id, split_ids| {
+                assert_eq!(index_id, "foo-index");
+                assert_eq!(split_ids, vec!["a", "b", "c"]);
+                Ok(())
+            });
+        let deleted_file_entries = run_garbage_collect(
+            "foo-index",
+            IndexingSplitStore::create_with_no_local_store(Arc::new(mock_storage)),
+            Arc::new(mock_metastore),
+            STAGED_GRACE_PERIOD,
+            DELETION_GRACE_PERIOD,
+            false,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(deleted_file_entries.len(), 3);
+        assert_eq!(
+            deleted_file_entries
+                .iter()
+                .map(|entry| entry.file_size_in_bytes)
+                .sum::<u64>(),
+            60
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_garbage_collect_spares_leased_splits() {
+        quickwit_common::setup_logging_for_tests();
+        let mut mock_storage = MockStorage::default();
+        mock_storage.expect_delete().times(1).returning(|path| {
+            assert_eq!(path, Path::new("a.split"));
+            Ok(())
+        });
+        let mut mock_metastore = MockMetastore::default();
+        mock_metastore.expect_list_splits().times(2).returning(
+            |_index_id, split_state, _time_range, _tags| {
+                let splits = match split_state {
+                    SplitState::Staged => Vec::new(),
+                    SplitState::MarkedForDeletion => {
+                        make_splits(&["a", "b"], SplitState::MarkedForDeletion)
+                    }
+                    _ => panic!("only Staged and MarkedForDeletion expected."),
+                };
+                Ok(splits)
+            },
+        );
+        mock_metastore
+            .expect_list_leased_splits()
+            .times(1)
+            .returning(|index_id| {
+                assert_eq!(index_id, "foo-index");
+                Ok(HashSet::from_iter(["b".to_string()]))
+            });
+        mock_metastore
+            .expect_mark_splits_for_deletion()
+            .times(1)
+            .returning(|_index_id, _split_ids| Ok(()));
+        mock_metastore
+            .expect_delete_splits()
+            .times(1)
+            .returning(|_index_id, split_ids| {
+                assert_eq!(split_ids, vec!["a"]);
+                Ok(())
+            });
+        let deleted_file_entries = run_garbage_collect(
+            "foo-index",
+            IndexingSplitStore::create_with_no_local_store(Arc::new(mock_storage)),
+            Arc::new(mock_metastore),
+            STAGED_GRACE_PERIOD,
+            DELETION_GRACE_PERIOD,
+            false,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(deleted_file_entries.len(), 1);
+    }
+}
@@ -0,0 +1,458 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Durable local queues backing the ingest API.
+//!
+//! Each index with an `ingest_api` source has a queue on the node, in the
+//! `<data_dir>/ingest-api/<index_id>` directory. The ingest REST endpoint appends documents to the
+//! queue and the `IngestApiSource` of the index drains it into the indexing pipeline.
+//!
+//! A queue is a log of records split into segment files named after the offset of their first
+//! record. A record is a document prefixed with its length, encoded as a little endian `u32`.
+//! Offsets are byte offsets in the log, and segments are deleted once all their records are
+//! published.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use once_cell::sync::OnceCell;
+use tracing::{info, warn};
+
+/// Name of the directory holding the queues, under the data directory.
+const INGEST_API_DIR_NAME: &str = "ingest-api";
+
+/// Name of the file holding the ID of a queue, under the directory of the queue.
+const QUEUE_ID_FILE_NAME: &str = "queue_id";
+
+const SEGMENT_FILE_EXTENSION: &str = "log";
+
+/// A new segment is started once the active segment exceeds this size.
+const MAX_SEGMENT_NUM_BYTES: u64 = 64 * 1024 * 1024;
+
+const RECORD_HEADER_NUM_BYTES: u64 = 4;
+
+static INGEST_API: OnceCell<IngestApi> = OnceCell::new();
+
+/// Sets up the ingest API of the node, with its queues under `data_dir_path`.
+pub fn init_ingest_api(data_dir_path: &Path) -> anyhow::Result<()> {
+    let ingest_api = IngestApi::new(data_dir_path.join(INGEST_API_DIR_NAME));
+    if INGEST_API.set(ingest_api).is_err() {
+        anyhow::bail!("The ingest API is already initialized.");
+    }
+    Ok(())
+}
+
+/// Returns the ingest API of the node, if the node runs the indexer service.
+pub fn ingest_api() -> Option<&'static IngestApi> {
+    INGEST_API.get()
+}
+
+/// Registry of the queues of the node, per index ID.
+pub struct IngestApi {
+    queues_dir_path: PathBuf,
+    queues: Mutex<HashMap<String, Arc<Mutex<IngestQueue>>>>,
+}
+
+impl IngestApi {
+    fn new(queues_dir_path: PathBuf) -> Self {
+        IngestApi {
+            queues_dir_path,
+            queues: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Opens the queue of the index, creating it if it does not exist.
+    pub fn get_or_create_queue(&self, index_id: &str) -> io::Result<Arc<Mutex<IngestQueue>>> {
+        self.open_queue(index_id, true)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Queue does not exist."))
+    }
+
+    /// Opens the queue of the index, if an `IngestApiSource` of the index created it on the
+    /// node.
+    pub fn queue(&self, index_id: &str) -> io::Result<Option<Arc<Mutex<IngestQueue>>>> {
+        self.open_queue(index_id, false)
+    }
+
+    fn open_queue(
+        &self,
+        index_id: &str,
+        create_if_not_exists: bool,
+    ) -> io::Result<Option<Arc<Mutex<IngestQueue>>>> {
+        if !is_valid_queue_name(index_id) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid queue name `{}`.", index_id),
+            ));
+        }
+        let mut queues = self
+            .queues
+            .lock()
+            .expect("Ingest queues lock should not be poisoned.");
+        if let Some(queue) = queues.get(index_id) {
+            return Ok(Some(queue.clone()));
+        }
+        let queue_dir_path = self.queues_dir_path.join(index_id);
+        if !create_if_not_exists && !queue_dir_path.join(QUEUE_ID_FILE_NAME).exists() {
+            return Ok(None);
+        }
+        let queue = Arc::new(Mutex::new(IngestQueue::open(&queue_dir_path)?));
+        queues.insert(index_id.to_string(), queue.clone());
+        Ok(Some(queue))
+    }
+}
+
+/// Queue names are index IDs: they must be valid directory names.
+fn is_valid_queue_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Durable log of documents, see the module documentation.
+pub struct IngestQueue {
+    dir_path: PathBuf,
+    /// Unique ID of the queue, generated at its creation.
+    queue_id: String,
+    /// Offsets of the first record of the segments, in increasing order. The last segment is
+    /// the active one.
+    segment_start_offsets: Vec<u64>,
+    active_segment_file: File,
+    /// Offset following the last record of the queue.
+    end_offset: u64,
+}
+
+impl IngestQueue {
+    /// Opens the queue held by `dir_path`, creating it if it does not exist. A record partially
+    /// written to the active segment when the node stopped is discarded.
+    pub fn open(dir_path: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir_path)?;
+        let queue_id_path = dir_path.join(QUEUE_ID_FILE_NAME);
+        let queue_id = if queue_id_path.exists() {
+            fs::read_to_string(&queue_id_path)?.trim().to_string()
+        } else {
+            let queue_id = ulid::Ulid::new().to_string();
+            fs::write(&queue_id_path, &queue_id)?;
+            queue_id
+        };
+        let mut segment_start_offsets = Vec::new();
+        for dir_entry_res in fs::read_dir(dir_path)? {
+            let path = dir_entry_res?.path();
+            if path.extension().and_then(|extension| extension.to_str())
+                != Some(SEGMENT_FILE_EXTENSION)
+            {
+                continue;
+            }
+            if let Some(start_offset) = path
+                .file_stem()
+                .and_then(|file_stem| file_stem.to_str())
+                .and_then(|file_stem| file_stem.parse::<u64>().ok())
+            {
+                segment_start_offsets.push(start_offset);
+            }
+        }
+        segment_start_offsets.sort_unstable();
+        if segment_start_offsets.is_empty() {
+            segment_start_offsets.push(0);
+        }
+        let active_segment_start_offset = *segment_start_offsets.last().unwrap();
+        let active_segment_path = segment_path(dir_path, active_segment_start_offset);
+        let mut active_segment_file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&active_segment_path)?;
+        let active_segment_num_bytes = recover_segment(&mut active_segment_file)?;
+        let queue = IngestQueue {
+            dir_path: dir_path.to_path_buf(),
+            queue_id,
+            segment_start_offsets,
+            active_segment_file,
+            end_offset: active_segment_start_offset + active_segment_num_bytes,
+        };
+        info!(
+            queue_dir = %dir_path.display(),
+            queue_id = %queue.queue_id,
+            end_offset = queue.end_offset,
+            "Opened ingest queue."
+        );
+        Ok(queue)
+    }
+
+    pub fn queue_id(&self) -> &str {
+        &self.queue_id
+    }
+
+    /// Returns the offset following the last record of the queue.
+    pub fn end_offset(&self) -> u64 {
+        self.end_offset
+    }
+
+    /// Appends the documents to the queue, and returns once they are persisted to disk. On error,
+    /// the active segment is truncated back to its previous size, so that no partially written
+    /// record is followed by the records of the next appends.
+    pub fn append<T: AsRef<[u8]>>(&mut self, docs: &[T]) -> io::Result<u64> {
+        let num_bytes: usize = docs
+            .iter()
+            .map(|doc| RECORD_HEADER_NUM_BYTES as usize + doc.as_ref().len())
+            .sum();
+        let mut buffer = Vec::with_capacity(num_bytes);
+        for doc in docs {
+            let doc = doc.as_ref();
+            let doc_num_bytes = u32::try_from(doc.len()).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Document is too large.")
+            })?;
+            buffer.extend_from_slice(&doc_num_bytes.to_le_bytes());
+            buffer.extend_from_slice(doc);
+        }
+        let file_num_bytes = self.active_segment_file.metadata()?.len();
+        if let Err(error) = self
+            .active_segment_file
+            .write_all(&buffer)
+            .and_then(|_| self.active_segment_file.sync_data())
+        {
+            self.active_segment_file.set_len(file_num_bytes)?;
+            return Err(error);
+        }
+        self.end_offset += buffer.len() as u64;
+
+        let active_segment_start_offset = *self.segment_start_offsets.last().unwrap();
+        if self.end_offset - active_segment_start_offset >= MAX_SEGMENT_NUM_BYTES {
+            self.roll_segment()?;
+        }
+        Ok(self.end_offset)
+    }
+
+    /// Starts a new active segment at the end of the queue.
+    fn roll_segment(&mut self) -> io::Result<()> {
+        let segment_path = segment_path(&self.dir_path, self.end_offset);
+        self.active_segment_file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&segment_path)?;
+        self.segment_start_offsets.push(self.end_offset);
+        Ok(())
+    }
+
+    /// Reads the documents following `from_offset`, until they exceed `max_num_bytes`. Returns
+    /// the documents read and the offset following the last of them.
+    ///
+    /// Reading from an offset preceding the first segment starts from the first segment.
+    pub fn read(&self, from_offset: u64, max_num_bytes: usize) -> io::Result<(Vec<String>, u64)> {
+        let mut docs = Vec::new();
+        let mut num_bytes = 0;
+        let first_start_offset = self.segment_start_offsets[0];
+        let mut offset = from_offset.max(first_start_offset);
+        let mut segment_ord = self
+            .segment_start_offsets
+            .partition_point(|start_offset| *start_offset <= offset)
+            - 1;
+
+        while offset < self.end_offset && num_bytes < max_num_bytes {
+            let segment_start_offset = self.segment_start_offsets[segment_ord];
+            let segment_end_offset = self
+                .segment_start_offsets
+                .get(segment_ord + 1)
+                .copied()
+                .unwrap_or(self.end_offset);
+            let mut segment_file = File::open(segment_path(&self.dir_path, segment_start_offset))?;
+            segment_file.seek(SeekFrom::Start(offset - segment_start_offset))?;
+            let mut segment_reader = BufReader::new(segment_file);
+
+            while offset < segment_end_offset && num_bytes < max_num_bytes {
+                let mut header = [0u8; RECORD_HEADER_NUM_BYTES as usize];
+                segment_reader.read_exact(&mut header)?;
+                let mut doc = vec![0u8; u32::from_le_bytes(header) as usize];
+                segment_reader.read_exact(&mut doc)?;
+                offset += RECORD_HEADER_NUM_BYTES + doc.len() as u64;
+                num_bytes += doc.len();
+                docs.push(String::from_utf8_lossy(&doc).into_owned());
+            }
+            segment_ord += 1;
+        }
+        Ok((docs, offset))
+    }
+
+    /// Deletes the segments whose records all precede `offset`. Returns the number of deleted
+    /// segments.
+    pub fn truncate(&mut self, offset: u64) -> io::Result<usize> {
+        let mut num_deleted_segments = 0;
+        // The active segment is never deleted.
+        while self.segment_start_offsets.len() > 1 && self.segment_start_offsets[1] <= offset {
+            let segment_start_offset = self.segment_start_offsets.remove(0);
+            fs::remove_file(segment_path(&self.dir_path, segment_start_offset))?;
+            num_deleted_segments += 1;
+        }
+        Ok(num_deleted_segments)
+    }
+}
+
+fn segment_path(dir_path: &Path, start_offset: u64) -> PathBuf {
+    dir_path.join(format!("{:020}.{}", start_offset, SEGMENT_FILE_EXTENSION))
+}
+
+/// Truncates the segment after its last complete record, and returns its size.
+fn recover_segment(segment_file: &mut File) -> io::Result<u64> {
+    let file_num_bytes = segment_file.metadata()?.len();
+    segment_file.seek(SeekFrom::Start(0))?;
+    let mut segment_reader = BufReader::new(&*segment_file);
+    let mut num_bytes = 0;
+    loop {
+        let mut header = [0u8; RECORD_HEADER_NUM_BYTES as usize];
+        if segment_reader.read_exact(&mut header).is_err() {
+            break;
+        }
+        let record_num_bytes = RECORD_HEADER_NUM_BYTES + u32::from_le_bytes(header) as u64;
+        if num_bytes + record_num_bytes > file_num_bytes {
+            break;
+        }
+        segment_reader.seek_relative(record_num_bytes as i64 - RECORD_HEADER_NUM_BYTES as i64)?;
+        num_bytes += record_num_bytes;
+    }
+    if num_bytes < file_num_bytes {
+        warn!(
+            num_discarded_bytes = file_num_bytes - num_bytes,
+            "Discarding partially written record of ingest queue."
+        );
+        segment_file.set_len(num_bytes)?;
+    }
+    Ok(num_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ingest_queue_append_and_read() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut queue = IngestQueue::open(temp_dir.path())?;
+        assert_eq!(queue.end_offset(), 0);
+        assert_eq!(queue.read(0, 1_000)?, (Vec::new(), 0));
+
+        queue.append(&["doc-0", "doc-1"])?;
+        assert_eq!(queue.append(&["doc-2"])?, 27);
+        assert_eq!(
+            queue.read(0, 1_000)?,
+            (
+                vec![
+                    "doc-0".to_string(),
+                    "doc-1".to_string(),
+                    "doc-2".to_string()
+                ],
+                27
+            )
+        );
+        assert_eq!(queue.read(9, 1)?, (vec!["doc-1".to_string()], 18));
+        assert_eq!(queue.read(27, 1_000)?, (Vec::new(), 27));
+
+        let queue_id = queue.queue_id().to_string();
+        drop(queue);
+        let queue = IngestQueue::open(temp_dir.path())?;
+        assert_eq!(queue.queue_id(), queue_id);
+        assert_eq!(queue.end_offset(), 27);
+        assert_eq!(queue.read(18, 1_000)?, (vec!["doc-2".to_string()], 27));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ingest_queue_discards_partially_written_record() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut queue = IngestQueue::open(temp_dir.path())?;
+        queue.append(&["doc-0"])?;
+        drop(queue);
+
+        let mut segment_file = OpenOptions::new()
+            .append(true)
+            .open(segment_path(temp_dir.path(), 0))?;
+        segment_file.write_all(&10u32.to_le_bytes())?;
+        segment_file.write_all(b"doc")?;
+        drop(segment_file);
+
+        let mut queue = IngestQueue::open(temp_dir.path())?;
+        assert_eq!(queue.end_offset(), 9);
+        queue.append(&["doc-1"])?;
+        assert_eq!(
+            queue.read(0, 1_000)?,
+            (vec!["doc-0".to_string(), "doc-1".to_string()], 18)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_ingest_queue_segments() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut queue = IngestQueue::open(temp_dir.path())?;
+        queue.append(&["doc-0"])?;
+        queue.roll_segment()?;
+        queue.append(&["doc-1"])?;
+        queue.roll_segment()?;
+        queue.append(&["doc-2"])?;
+        assert_eq!(queue.segment_start_offsets, vec![0, 9, 18]);
+        assert_eq!(
+            queue.read(0, 1_000)?,
+            (
+                vec![
+                    "doc-0".to_string(),
+                    "doc-1".to_string(),
+                    "doc-2".to_string()
+                ],
+                27
+            )
+        );
+        assert_eq!(queue.read(9, 1_000)?.0, vec!["doc-1", "doc-2"]);
+
+        assert_eq!(queue.truncate(8)?, 0);
+        assert_eq!(queue.truncate(18)?, 2);
+        assert_eq!(queue.truncate(27)?, 0);
+        assert!(!segment_path(temp_dir.path(), 0).exists());
+        assert!(!segment_path(temp_dir.path(), 9).exists());
+        assert_eq!(queue.read(0, 1_000)?, (vec!["doc-2".to_string()], 27));
+
+        drop(queue);
+        let queue = IngestQueue::open(temp_dir.path())?;
+        assert_eq!(queue.segment_start_offsets, vec![18]);
+        assert_eq!(queue.end_offset(), 27);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ingest_api_queues() -> io::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let ingest_api = IngestApi::new(temp_dir.path().to_path_buf());
+        assert!(ingest_api.queue("my-index")?.is_none());
+        let queue = ingest_api.get_or_create_queue("my-index")?;
+        queue.lock().unwrap().append(&["doc-0"])?;
+        let same_queue = ingest_api.queue("my-index")?.unwrap();
+        assert!(Arc::ptr_eq(&queue, &same_queue));
+
+        let other_ingest_api = IngestApi::new(temp_dir.path().to_path_buf());
+        let reopened_queue = other_ingest_api.queue("my-index")?.unwrap();
+        assert_eq!(reopened_queue.lock().unwrap().end_offset(), 9);
+
+        assert!(ingest_api.queue("..").is_err());
+        assert!(ingest_api.get_or_create_queue("my/index").is_err());
+        Ok(())
+    }
+}
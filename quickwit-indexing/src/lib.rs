@@ -17,6 +17,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use std::ops::Range;
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -34,18 +35,43 @@ pub use crate::split_store::{
 
 pub mod actors;
 mod controlled_directory;
+mod dedup_filter;
+mod doc_filter;
+mod doc_transform;
 mod garbage_collection;
+pub mod ingest_api;
 pub mod merge_policy;
 pub mod models;
+mod pipeline_throughput;
+mod row_coercion;
 pub mod source;
+mod source_pause;
 mod split_store;
+#[cfg(any(test, feature = "testsuite"))]
 mod test_utils;
+mod write_block;
 
+#[cfg(any(test, feature = "testsuite"))]
 pub use test_utils::{mock_split, mock_split_meta, TestSandbox};
 
-pub use self::garbage_collection::{delete_splits_with_files, run_garbage_collect, FileEntry};
-pub use self::merge_policy::{MergePolicy, StableMultitenantWithTimestampMergePolicy};
-pub use self::source::check_source_connectivity;
+pub use self::garbage_collection::{
+    delete_splits_with_files, run_garbage_collect, FileEntry, SplitDeletionError,
+};
+pub use self::merge_policy::{
+    CompactionMergePolicy, MergePolicy, StableMultitenantWithTimestampMergePolicy,
+};
+pub use self::pipeline_throughput::{
+    pipeline_throughputs, PipelineStage, PipelineThroughput, StageThroughput,
+};
+pub use self::source::{
+    check_source_connectivity, check_source_decoding, source_checkpoint_at_timestamp,
+};
+pub use self::source_pause::{
+    is_source_paused, pause_source, paused_sources, record_source_checkpoint_reset, resume_source,
+};
+pub use self::write_block::{
+    apply_write_block, clear_write_block, write_block, write_blocks, WriteBlockReason,
+};
 
 pub async fn index_data(
     index_id: String,
@@ -65,6 +91,35 @@ pub async fn index_data(
     Ok(statistics)
 }
 
+/// Runs a merge pipeline on the index and waits for it to complete. The pipeline is killed if
+/// the returned future is dropped before.
+pub async fn merge_index(
+    index_id: String,
+    data_dir_path: PathBuf,
+    metastore: Arc<dyn Metastore>,
+    storage_resolver: StorageUriResolver,
+    merge_enabled: bool,
+    demux_enabled: bool,
+    compaction_time_range_opt: Option<Range<i64>>,
+) -> anyhow::Result<()> {
+    let indexer_config = IndexerConfig::default();
+    let client = IndexingServer::spawn(data_dir_path, indexer_config, metastore, storage_resolver);
+    let pipeline_id = client
+        .spawn_merge_pipeline(
+            index_id,
+            merge_enabled,
+            demux_enabled,
+            compaction_time_range_opt,
+        )
+        .await?;
+    let pipeline_handle = client.detach_pipeline(&pipeline_id).await?;
+    let (exit_status, _statistics) = pipeline_handle.join().await;
+    if !exit_status.is_success() {
+        bail!(exit_status);
+    }
+    Ok(())
+}
+
 pub fn new_split_id() -> String {
     ulid::Ulid::new().to_string()
 }
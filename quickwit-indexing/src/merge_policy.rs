@@ -548,6 +548,62 @@ fn is_sorted(els: &[usize]) -> bool {
     els.windows(2).all(|w| w[0] <= w[1])
 }
 
+/// Merge policy used to compact an index on demand with `quickwit index merge`.
+///
+/// Unlike [`StableMultitenantWithTimestampMergePolicy`], it does not wait for a level to
+/// accumulate `merge_factor` splits before merging them. Young splits are sorted by time and
+/// greedily packed into merge operations, each of them taking splits until it reaches
+/// `split_num_docs_target` documents or `max_merge_factor` splits. The remaining splits are
+/// merged together as long as there are at least two of them, so compacting a burst of small
+/// splits leaves as few splits as possible behind.
+#[derive(Clone, Debug)]
+pub struct CompactionMergePolicy {
+    pub max_merge_factor: usize,
+    pub split_num_docs_target: usize,
+}
+
+impl MergePolicy for CompactionMergePolicy {
+    fn operations(&self, splits: &mut Vec<SplitMetadata>) -> Vec<MergeOperation> {
+        let mature_splits = remove_matching_items(splits, |split| self.is_mature(split));
+        // We stable sort the splits, old first, so that merged splits cover contiguous time
+        // ranges.
+        splits.sort_by_key(|split| {
+            split
+                .time_range
+                .as_ref()
+                .map(|time_range| *time_range.end())
+        });
+        debug!(splits=?splits_short_debug(&splits[..]), "compaction-merge-policy-run");
+
+        let max_merge_factor = self.max_merge_factor.max(2);
+        let mut merge_operations = Vec::new();
+        let mut candidate_splits: Vec<SplitMetadata> = Vec::new();
+        let mut candidate_num_docs = 0;
+        for split in splits.drain(..) {
+            candidate_num_docs += split.num_docs;
+            candidate_splits.push(split);
+            if candidate_num_docs >= self.split_num_docs_target
+                || candidate_splits.len() >= max_merge_factor
+            {
+                let splits_in_merge = std::mem::take(&mut candidate_splits);
+                merge_operations.push(MergeOperation::new_merge_operation(splits_in_merge));
+                candidate_num_docs = 0;
+            }
+        }
+        if candidate_splits.len() >= 2 {
+            merge_operations.push(MergeOperation::new_merge_operation(candidate_splits));
+        } else {
+            splits.extend(candidate_splits);
+        }
+        splits.extend(mature_splits);
+        merge_operations
+    }
+
+    fn is_mature(&self, split: &SplitMetadata) -> bool {
+        split.num_docs >= self.split_num_docs_target || split.demux_num_ops > 0
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeSet;
@@ -1053,4 +1109,64 @@ mod tests {
         let merge_ops = merge_policy.demux_operations(&mut splits);
         assert_eq!(merge_ops.len(), 0);
     }
+
+    #[test]
+    fn test_compaction_merge_policy_merges_all_young_splits() {
+        let merge_policy = CompactionMergePolicy {
+            max_merge_factor: 12,
+            split_num_docs_target: 10_000_000,
+        };
+        let mut splits = create_splits(vec![100; 3]);
+        let merge_ops = merge_policy.operations(&mut splits);
+        assert!(splits.is_empty());
+        assert_eq!(merge_ops.len(), 1);
+        assert_eq!(merge_ops[0].splits().len(), 3);
+    }
+
+    #[test]
+    fn test_compaction_merge_policy_packs_splits_up_to_target() {
+        let merge_policy = CompactionMergePolicy {
+            max_merge_factor: 12,
+            split_num_docs_target: 1_000,
+        };
+        let mut splits = create_splits_with_timestamps(vec![
+            (400, 40..=49),
+            (400, 0..=9),
+            (5_000, 50..=59),
+            (400, 10..=19),
+            (400, 30..=39),
+            (400, 20..=29),
+        ]);
+        let merge_ops = merge_policy.operations(&mut splits);
+        assert_eq!(splits.len(), 1);
+        assert_eq!(splits[0].num_docs, 5_000);
+        assert_eq!(merge_ops.len(), 2);
+        let split_ids = |merge_op: &MergeOperation| {
+            merge_op
+                .splits()
+                .iter()
+                .map(|split| split.split_id().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            split_ids(&merge_ops[0]),
+            &["split_01", "split_03", "split_05"]
+        );
+        assert_eq!(split_ids(&merge_ops[1]), &["split_04", "split_00"]);
+    }
+
+    #[test]
+    fn test_compaction_merge_policy_respects_max_merge_factor() {
+        let merge_policy = CompactionMergePolicy {
+            max_merge_factor: 4,
+            split_num_docs_target: 10_000_000,
+        };
+        let mut splits = create_splits(vec![100; 9]);
+        let merge_ops = merge_policy.operations(&mut splits);
+        assert_eq!(splits.len(), 1);
+        assert_eq!(merge_ops.len(), 2);
+        assert!(merge_ops
+            .iter()
+            .all(|merge_op| merge_op.splits().len() == 4));
+    }
 }
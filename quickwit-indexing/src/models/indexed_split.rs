@@ -23,8 +23,9 @@ use std::path::Path;
 use std::time::Instant;
 
 use quickwit_actors::{KillSwitch, Progress};
+use quickwit_common::memory_usage::{MemoryComponent, MemoryUsageGuard};
 use quickwit_config::IndexingResources;
-use quickwit_metastore::checkpoint::CheckpointDelta;
+use quickwit_metastore::checkpoint::IndexCheckpointDelta;
 use tantivy::directory::MmapDirectory;
 use tantivy::merge_policy::NoMergePolicy;
 use tantivy::IndexBuilder;
@@ -58,10 +59,13 @@ pub struct IndexedSplit {
     /// Number of demux operations this split has undergone.
     pub demux_num_ops: usize,
 
-    pub checkpoint_delta: CheckpointDelta,
+    /// Checkpoint delta of each of the sources that fed the split.
+    pub checkpoint_delta: IndexCheckpointDelta,
 
     pub index: tantivy::Index,
     pub index_writer: tantivy::IndexWriter,
+    /// Accounts for the heap of the index writer in the memory usage of the indexer.
+    pub index_writer_memory_usage_guard: MemoryUsageGuard,
     pub split_scratch_directory: ScratchDirectory,
 
     pub controlled_directory_opt: Option<ControlledDirectory>,
@@ -98,10 +102,11 @@ impl IndexedSplit {
         let controlled_directory =
             ControlledDirectory::new(box_mmap_directory, progress, kill_switch);
         let index = index_builder.open_or_create(controlled_directory.clone())?;
-        let index_writer = index.writer_with_num_threads(
-            indexing_resources.num_threads,
-            indexing_resources.heap_size.get_bytes() as usize,
-        )?;
+        let heap_size = indexing_resources.heap_size.get_bytes() as usize;
+        let index_writer =
+            index.writer_with_num_threads(indexing_resources.num_threads, heap_size)?;
+        let index_writer_memory_usage_guard =
+            MemoryUsageGuard::new(MemoryComponent::IndexerHeaps, heap_size);
         index_writer.set_merge_policy(Box::new(NoMergePolicy));
         Ok(IndexedSplit {
             index_id,
@@ -114,8 +119,9 @@ impl IndexedSplit {
             split_date_of_birth: Instant::now(),
             index,
             index_writer,
+            index_writer_memory_usage_guard,
             split_scratch_directory,
-            checkpoint_delta: CheckpointDelta::default(),
+            checkpoint_delta: IndexCheckpointDelta::default(),
             controlled_directory_opt: Some(controlled_directory),
         })
     }
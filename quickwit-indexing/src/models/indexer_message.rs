@@ -22,7 +22,17 @@ use crate::models::RawDocBatch;
 #[derive(Debug)]
 pub enum IndexerMessage {
     Batch(RawDocBatch),
-    CommitTimeout { split_id: String },
+    /// Batch emitted by one of the sources of a pipeline running several sources.
+    SourceBatch {
+        source_id: String,
+        batch: RawDocBatch,
+    },
+    CommitTimeout {
+        split_id: String,
+    },
+    /// Sent by a source when it is paused: the indexer commits the split in progress right away
+    /// instead of waiting for the commit timeout.
+    Flush,
 }
 
 impl From<RawDocBatch> for IndexerMessage {
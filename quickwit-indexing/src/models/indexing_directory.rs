@@ -105,6 +105,45 @@ impl IndexingDirectory {
     }
 }
 
+/// Empties the scratch directories left by a previous run, for instance after a crash, in the
+/// indexing directories of `index_ids` under `indexing_dir_path`. Indexing directories are laid
+/// out as `<indexing dir>/<index ID>/<source ID>`, including the ones of the sources whose
+/// pipelines are not spawned again, so their scratch space would otherwise never be reclaimed.
+///
+/// The directories of the other indexes are left untouched: they may belong to the pipelines of
+/// another process sharing the data directory, such as an ingest command.
+///
+/// Returns the number of bytes freed. Must not be called while pipelines of `index_ids` are
+/// running.
+pub async fn clean_up_scratch_directories(
+    indexing_dir_path: &Path,
+    index_ids: &[String],
+) -> anyhow::Result<u64> {
+    let mut num_bytes_freed = 0;
+    for index_id in index_ids {
+        let index_dir_path = indexing_dir_path.join(index_id);
+        if !index_dir_path.is_dir() {
+            continue;
+        }
+        let mut source_entries = fs::read_dir(&index_dir_path).await?;
+        while let Some(source_entry) = source_entries.next_entry().await? {
+            let scratch_directory_path = source_entry.path().join("scratch");
+            if !scratch_directory_path.is_dir() {
+                continue;
+            }
+            num_bytes_freed +=
+                ScratchDirectory::new_in_dir(scratch_directory_path.clone()).num_bytes()?;
+            empty_dir(&scratch_directory_path).await.with_context(|| {
+                format!(
+                    "Failed to empty scratch directory `{}`.",
+                    scratch_directory_path.display(),
+                )
+            })?;
+        }
+    }
+    Ok(num_bytes_freed)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -144,4 +183,44 @@ mod tests {
         assert!(!indexing_directory_path.exists());
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_clean_up_scratch_directories() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let index_ids = vec!["test-index".to_string(), "test-missing-index".to_string()];
+        assert_eq!(
+            clean_up_scratch_directories(&tempdir.path().join("indexing"), &index_ids).await?,
+            0
+        );
+        let indexing_directory_path = tempdir.path().join("test-index").join("test-source");
+        let indexing_directory = IndexingDirectory::create_in_dir(&indexing_directory_path).await?;
+        let orphaned_dir_path = indexing_directory
+            .scratch_directory
+            .path()
+            .join("merge-orphaned");
+        fs::create_dir(&orphaned_dir_path).await?;
+        fs::write(orphaned_dir_path.join("split"), b"split").await?;
+        let cache_file_path = indexing_directory.cache_directory.join("file");
+        fs::write(&cache_file_path, b"cache").await?;
+        fs::write(tempdir.path().join("file"), b"file").await?;
+
+        let other_indexing_directory_path = tempdir.path().join("other-index").join("test-source");
+        let other_indexing_directory =
+            IndexingDirectory::create_in_dir(&other_indexing_directory_path).await?;
+        let other_scratch_file_path = other_indexing_directory
+            .scratch_directory
+            .path()
+            .join("split");
+        fs::write(&other_scratch_file_path, b"split").await?;
+
+        assert_eq!(
+            clean_up_scratch_directories(tempdir.path(), &index_ids).await?,
+            5
+        );
+        assert!(!orphaned_dir_path.exists());
+        assert!(indexing_directory.scratch_directory.path().exists());
+        assert!(cache_file_path.exists());
+        assert!(other_scratch_file_path.exists());
+        Ok(())
+    }
 }
@@ -28,6 +28,10 @@ pub struct IndexingStatistics {
     pub num_docs: u64,
     /// Number of document parse error, or missing timestamps
     pub num_invalid_docs: u64,
+    /// Number of documents dropped as duplicates
+    pub num_duplicate_docs: u64,
+    /// Number of documents dropped by the filter of the source
+    pub num_filtered_docs: u64,
     /// Number of created split
     pub num_local_splits: u64,
     /// Number of staged splits
@@ -55,6 +59,8 @@ impl IndexingStatistics {
     ) -> Self {
         self.num_docs += indexer_counters.num_processed_docs();
         self.num_invalid_docs += indexer_counters.num_invalid_docs();
+        self.num_duplicate_docs += indexer_counters.num_duplicate_docs;
+        self.num_filtered_docs += indexer_counters.num_filtered_docs;
         self.num_local_splits += indexer_counters.num_splits_emitted;
         self.total_bytes_processed += indexer_counters.overall_num_bytes;
         self.num_staged_splits += uploader_counters.num_staged_splits.load(Ordering::SeqCst);
@@ -27,10 +27,11 @@ mod packaged_split;
 mod publisher_message;
 mod raw_doc_batch;
 mod scratch_directory;
+mod scratch_quota;
 
 pub use indexed_split::{IndexedSplit, IndexedSplitBatch};
 pub use indexer_message::IndexerMessage;
-pub use indexing_directory::IndexingDirectory;
+pub use indexing_directory::{clean_up_scratch_directories, IndexingDirectory};
 pub use indexing_statistics::IndexingStatistics;
 pub use merge_planner_message::MergePlannerMessage;
 pub use merge_scratch::MergeScratch;
@@ -38,3 +39,4 @@ pub use packaged_split::{PackagedSplit, PackagedSplitBatch};
 pub use publisher_message::{PublishOperation, PublisherMessage};
 pub use raw_doc_batch::RawDocBatch;
 pub use scratch_directory::ScratchDirectory;
+pub use scratch_quota::ScratchQuota;
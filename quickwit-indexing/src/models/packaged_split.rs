@@ -17,12 +17,14 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::{BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt;
 use std::ops::RangeInclusive;
 use std::time::Instant;
 
-use quickwit_metastore::checkpoint::CheckpointDelta;
+use quickwit_doc_mapper::range_pruning::FieldStats;
+use quickwit_doc_mapper::SortByField;
+use quickwit_metastore::checkpoint::IndexCheckpointDelta;
 
 use crate::models::ScratchDirectory;
 
@@ -30,13 +32,15 @@ pub struct PackagedSplit {
     pub split_id: String,
     pub replaced_split_ids: Vec<String>,
     pub index_id: String,
-    pub checkpoint_deltas: Vec<CheckpointDelta>,
+    pub checkpoint_deltas: Vec<IndexCheckpointDelta>,
     pub time_range: Option<RangeInclusive<i64>>,
     pub size_in_bytes: u64,
     pub split_scratch_directory: ScratchDirectory,
     pub num_docs: u64,
     pub demux_num_ops: usize,
     pub tags: BTreeSet<String>,
+    pub sort_by: Option<SortByField>,
+    pub field_stats: BTreeMap<String, FieldStats>,
     pub split_date_of_birth: Instant,
     pub split_files: Vec<std::path::PathBuf>,
     pub hotcache_bytes: Vec<u8>,
@@ -55,6 +59,8 @@ impl fmt::Debug for PackagedSplit {
             .field("num_docs", &self.num_docs)
             .field("demux_num_ops", &self.demux_num_ops)
             .field("tags", &self.tags)
+            .field("sort_by", &self.sort_by)
+            .field("field_stats", &self.field_stats)
             .field("split_date_of_birth", &self.split_date_of_birth)
             .field("split_files", &self.split_files)
             .finish()
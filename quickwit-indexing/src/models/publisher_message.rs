@@ -20,7 +20,7 @@
 use std::fmt;
 use std::time::Instant;
 
-use quickwit_metastore::checkpoint::CheckpointDelta;
+use quickwit_metastore::checkpoint::IndexCheckpointDelta;
 use quickwit_metastore::SplitMetadata;
 
 #[derive(Clone)]
@@ -28,7 +28,7 @@ pub enum PublishOperation {
     /// Publish a new split, coming from the indexer.
     PublishNewSplit {
         new_split: SplitMetadata,
-        checkpoint_delta: CheckpointDelta,
+        checkpoint_delta: IndexCheckpointDelta,
         split_date_of_birth: Instant, // for logging
     },
     /// Publish a merge, replacing several splits (typically 10)
@@ -20,7 +20,7 @@
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::{fmt, io};
+use std::{fmt, fs, io};
 enum ScratchDirectoryType {
     Path(PathBuf),
     TempDir(tempfile::TempDir),
@@ -104,6 +104,40 @@ impl ScratchDirectory {
             inner: Arc::new(inner),
         })
     }
+
+    /// Returns the total size of the files stored in the directory and its children.
+    pub fn num_bytes(&self) -> io::Result<u64> {
+        dir_num_bytes(self.path())
+    }
+}
+
+/// Sums up the size of the files of the directory recursively. Entries removed while walking
+/// the directory, for instance by a child scratch directory getting dropped, are ignored.
+fn dir_num_bytes(dir_path: &Path) -> io::Result<u64> {
+    let entries = match fs::read_dir(dir_path) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(error) => return Err(error),
+    };
+    let mut num_bytes = 0;
+    for entry_res in entries {
+        let entry = match entry_res {
+            Ok(entry) => entry,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+            Err(error) => return Err(error),
+        };
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+            Err(error) => return Err(error),
+        };
+        if metadata.is_dir() {
+            num_bytes += dir_num_bytes(&entry.path())?;
+        } else {
+            num_bytes += metadata.len();
+        }
+    }
+    Ok(num_bytes)
 }
 
 #[cfg(test)]
@@ -171,4 +205,22 @@ mod tests {
         assert!(!tempdir_path.exists());
         Ok(())
     }
+
+    #[test]
+    fn test_scratch_directory_num_bytes() -> io::Result<()> {
+        let parent = ScratchDirectory::for_test()?;
+        assert_eq!(parent.num_bytes()?, 0);
+
+        std::fs::write(parent.path().join("hello.txt"), b"hello")?;
+        assert_eq!(parent.num_bytes()?, 5);
+
+        let child = parent.named_temp_child("child-")?;
+        std::fs::write(child.path().join("world.txt"), b"world!")?;
+        assert_eq!(child.num_bytes()?, 6);
+        assert_eq!(parent.num_bytes()?, 11);
+
+        mem::drop(child);
+        assert_eq!(parent.num_bytes()?, 5);
+        Ok(())
+    }
 }
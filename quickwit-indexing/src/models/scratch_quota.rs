@@ -0,0 +1,114 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::io;
+
+use once_cell::sync::Lazy;
+use prometheus::{IntGauge, IntGaugeVec};
+use quickwit_common::metrics::new_gauge_vec;
+
+use super::ScratchDirectory;
+
+static PIPELINE_SCRATCH_USED_NUM_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    new_gauge_vec(
+        "quickwit_indexing_pipeline_scratch_used_bytes",
+        "Space used by the scratch directory of an indexing pipeline, in bytes.",
+        &["index_id", "source_id"],
+    )
+});
+
+/// Scratch space budget of an indexing pipeline.
+///
+/// The pipelines of a node share the same disk: the quota keeps the merges of an index from
+/// eating up the scratch space needed by the other indexes.
+#[derive(Clone)]
+pub struct ScratchQuota {
+    scratch_directory: ScratchDirectory,
+    max_num_bytes: u64,
+    used_num_bytes_gauge: IntGauge,
+}
+
+impl ScratchQuota {
+    pub fn new(
+        index_id: &str,
+        source_id: &str,
+        scratch_directory: ScratchDirectory,
+        max_num_bytes: u64,
+    ) -> Self {
+        let used_num_bytes_gauge =
+            PIPELINE_SCRATCH_USED_NUM_BYTES.with_label_values(&[index_id, source_id]);
+        used_num_bytes_gauge.set(0);
+        Self {
+            scratch_directory,
+            max_num_bytes,
+            used_num_bytes_gauge,
+        }
+    }
+
+    pub fn max_num_bytes(&self) -> u64 {
+        self.max_num_bytes
+    }
+
+    /// Measures the space currently used by the scratch directory of the pipeline.
+    pub fn used_num_bytes(&self) -> io::Result<u64> {
+        let used_num_bytes = self.scratch_directory.num_bytes()?;
+        self.used_num_bytes_gauge.set(used_num_bytes as i64);
+        Ok(used_num_bytes)
+    }
+
+    /// Returns true if `num_bytes` more bytes can be written to the scratch directory without
+    /// exceeding the quota.
+    pub fn has_room_for(&self, num_bytes: u64) -> io::Result<bool> {
+        let used_num_bytes = self.used_num_bytes()?;
+        Ok(used_num_bytes + num_bytes <= self.max_num_bytes)
+    }
+
+    #[doc(hidden)]
+    pub fn for_test(scratch_directory: ScratchDirectory, max_num_bytes: u64) -> Self {
+        Self {
+            scratch_directory,
+            max_num_bytes,
+            used_num_bytes_gauge: IntGauge::new("scratch_used_bytes", "Test gauge.")
+                .expect("Failed to create gauge"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scratch_quota() -> io::Result<()> {
+        let scratch_directory = ScratchDirectory::for_test()?;
+        let scratch_quota =
+            ScratchQuota::new("test-index", "test-source", scratch_directory.clone(), 10);
+        assert_eq!(scratch_quota.max_num_bytes(), 10);
+        assert_eq!(scratch_quota.used_num_bytes()?, 0);
+        assert!(scratch_quota.has_room_for(10)?);
+        assert!(!scratch_quota.has_room_for(11)?);
+
+        std::fs::write(scratch_directory.path().join("hello.txt"), b"hello")?;
+        assert_eq!(scratch_quota.used_num_bytes()?, 5);
+        assert_eq!(scratch_quota.used_num_bytes_gauge.get(), 5);
+        assert!(scratch_quota.has_room_for(5)?);
+        assert!(!scratch_quota.has_room_for(6)?);
+        Ok(())
+    }
+}
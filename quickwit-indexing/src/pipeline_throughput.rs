@@ -0,0 +1,257 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+/// Duration over which the throughput of the pipelines is averaged.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(30);
+
+/// Average fill ratio of its input from which a stage is considered saturated.
+const SATURATION_THRESHOLD: f64 = 0.8;
+
+/// Stage of an indexing pipeline, from upstream to downstream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PipelineStage {
+    /// Reads the documents. The source is reported as the bottleneck when no other stage is
+    /// saturated: the pipeline keeps up with it.
+    Source,
+    /// Parses and indexes the documents, and packages the splits.
+    Indexer,
+    /// Stages and uploads the splits.
+    Uploader,
+    /// Publishes the splits.
+    Publisher,
+}
+
+/// Throughput of a stage of an indexing pipeline, averaged over the last 30 seconds.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StageThroughput {
+    pub stage: PipelineStage,
+    pub num_docs_per_sec: f64,
+    /// Megabytes of documents for the indexer, megabytes of splits for the other stages.
+    pub num_mb_per_sec: f64,
+    /// Average fill ratio of the input of the stage, between 0 and 1: its mailbox for the
+    /// indexer and the publisher, its upload slots for the uploader.
+    pub saturation: f64,
+}
+
+/// Throughput of an indexing pipeline running on the node.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineThroughput {
+    pub index_id: String,
+    pub source_id: String,
+    pub stages: Vec<StageThroughput>,
+    /// The most downstream saturated stage, which slows down the stages upstream of it.
+    pub bottleneck: PipelineStage,
+}
+
+/// Counters of a stage observed at some point in time. The counters are cumulative since the
+/// pipeline was spawned.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct StageSample {
+    pub num_docs: u64,
+    pub num_bytes: u64,
+    /// Fill ratio of the input of the stage when it was observed.
+    pub saturation: f64,
+}
+
+/// Computes the throughput of the stages of a pipeline from the samples recorded while
+/// observing it.
+#[derive(Default)]
+pub(crate) struct ThroughputMeter {
+    samples: VecDeque<(Instant, BTreeMap<PipelineStage, StageSample>)>,
+}
+
+impl ThroughputMeter {
+    pub fn record(&mut self, now: Instant, stage_samples: BTreeMap<PipelineStage, StageSample>) {
+        while let Some((sampled_at, _)) = self.samples.front() {
+            if now.duration_since(*sampled_at) <= THROUGHPUT_WINDOW {
+                break;
+            }
+            self.samples.pop_front();
+        }
+        self.samples.push_back((now, stage_samples));
+    }
+
+    /// Returns the throughput of the stages over the window, or `None` until two samples have
+    /// been recorded.
+    pub fn throughput(&self, index_id: &str, source_id: &str) -> Option<PipelineThroughput> {
+        let (first_sampled_at, first_samples) = self.samples.front()?;
+        let (last_sampled_at, last_samples) = self.samples.back()?;
+        let elapsed_secs = last_sampled_at
+            .duration_since(*first_sampled_at)
+            .as_secs_f64();
+        if elapsed_secs <= 0.0 {
+            return None;
+        }
+        let mut stages = Vec::new();
+        let mut bottleneck = PipelineStage::Source;
+        for (stage, last_sample) in last_samples {
+            let first_sample = first_samples.get(stage).copied().unwrap_or_default();
+            let saturation = self
+                .samples
+                .iter()
+                .map(|(_, samples)| samples.get(stage).map_or(0.0, |sample| sample.saturation))
+                .sum::<f64>()
+                / self.samples.len() as f64;
+            if saturation >= SATURATION_THRESHOLD {
+                bottleneck = *stage;
+            }
+            stages.push(StageThroughput {
+                stage: *stage,
+                num_docs_per_sec: last_sample.num_docs.saturating_sub(first_sample.num_docs) as f64
+                    / elapsed_secs,
+                num_mb_per_sec: last_sample.num_bytes.saturating_sub(first_sample.num_bytes) as f64
+                    / elapsed_secs
+                    / 1_000_000.0,
+                saturation,
+            });
+        }
+        Some(PipelineThroughput {
+            index_id: index_id.to_string(),
+            source_id: source_id.to_string(),
+            stages,
+            bottleneck,
+        })
+    }
+}
+
+/// Throughput of the pipelines running on the node, per index ID and source ID.
+static PIPELINE_THROUGHPUTS: Lazy<RwLock<BTreeMap<(String, String), PipelineThroughput>>> =
+    Lazy::new(Default::default);
+
+pub(crate) fn set_pipeline_throughput(throughput: PipelineThroughput) {
+    PIPELINE_THROUGHPUTS
+        .write()
+        .expect("Pipeline throughputs lock should not be poisoned.")
+        .insert(
+            (throughput.index_id.clone(), throughput.source_id.clone()),
+            throughput,
+        );
+}
+
+pub(crate) fn clear_pipeline_throughput(index_id: &str, source_id: &str) {
+    PIPELINE_THROUGHPUTS
+        .write()
+        .expect("Pipeline throughputs lock should not be poisoned.")
+        .remove(&(index_id.to_string(), source_id.to_string()));
+}
+
+/// Returns the throughput of the pipelines of the index running on the node.
+pub fn pipeline_throughputs(index_id: &str) -> Vec<PipelineThroughput> {
+    PIPELINE_THROUGHPUTS
+        .read()
+        .expect("Pipeline throughputs lock should not be poisoned.")
+        .values()
+        .filter(|throughput| throughput.index_id == index_id)
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(
+        indexer: (u64, u64, f64),
+        uploader: (u64, u64, f64),
+        publisher: (u64, u64, f64),
+    ) -> BTreeMap<PipelineStage, StageSample> {
+        [
+            (PipelineStage::Indexer, indexer),
+            (PipelineStage::Uploader, uploader),
+            (PipelineStage::Publisher, publisher),
+        ]
+        .into_iter()
+        .map(|(stage, (num_docs, num_bytes, saturation))| {
+            let sample = StageSample {
+                num_docs,
+                num_bytes,
+                saturation,
+            };
+            (stage, sample)
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_throughput_meter() {
+        let mut throughput_meter = ThroughputMeter::default();
+        let start = Instant::now();
+        throughput_meter.record(start, samples((0, 0, 1.0), (0, 0, 1.0), (0, 0, 0.0)));
+        assert!(throughput_meter.throughput("index", "source").is_none());
+
+        throughput_meter.record(
+            start + Duration::from_secs(10),
+            samples((1_000, 20_000_000, 1.0), (500, 5_000_000, 0.5), (0, 0, 0.0)),
+        );
+        let throughput = throughput_meter.throughput("index", "source").unwrap();
+        assert_eq!(throughput.bottleneck, PipelineStage::Indexer);
+        assert_eq!(throughput.stages.len(), 3);
+        assert_eq!(throughput.stages[0].stage, PipelineStage::Indexer);
+        assert_eq!(throughput.stages[0].num_docs_per_sec, 100.0);
+        assert_eq!(throughput.stages[0].num_mb_per_sec, 2.0);
+        assert_eq!(throughput.stages[1].num_docs_per_sec, 50.0);
+        assert_eq!(throughput.stages[1].saturation, 0.75);
+
+        // The first samples fall out of the window.
+        throughput_meter.record(
+            start + Duration::from_secs(35),
+            samples(
+                (1_000, 20_000_000, 0.0),
+                (1_000, 10_000_000, 0.0),
+                (1_000, 0, 0.0),
+            ),
+        );
+        let throughput = throughput_meter.throughput("index", "source").unwrap();
+        assert_eq!(throughput.bottleneck, PipelineStage::Source);
+        assert_eq!(throughput.stages[0].num_docs_per_sec, 0.0);
+        assert_eq!(throughput.stages[2].num_docs_per_sec, 40.0);
+    }
+
+    #[test]
+    fn test_pipeline_throughputs() {
+        let throughput = |index_id: &str, source_id: &str| PipelineThroughput {
+            index_id: index_id.to_string(),
+            source_id: source_id.to_string(),
+            stages: Vec::new(),
+            bottleneck: PipelineStage::Source,
+        };
+        set_pipeline_throughput(throughput("test-pipeline-throughputs", "source-1"));
+        set_pipeline_throughput(throughput("test-pipeline-throughputs", "source-2"));
+        set_pipeline_throughput(throughput("test-pipeline-throughputs-other", "source-1"));
+        assert_eq!(
+            pipeline_throughputs("test-pipeline-throughputs"),
+            vec![
+                throughput("test-pipeline-throughputs", "source-1"),
+                throughput("test-pipeline-throughputs", "source-2"),
+            ]
+        );
+        clear_pipeline_throughput("test-pipeline-throughputs", "source-1");
+        assert_eq!(pipeline_throughputs("test-pipeline-throughputs").len(), 1);
+    }
+}
@@ -0,0 +1,128 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+use std::collections::HashMap;
+
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use tantivy::schema::{FieldType, Schema};
+
+#[derive(Clone, Copy)]
+enum NumericType {
+    I64,
+    U64,
+    F64,
+}
+
+impl NumericType {
+    fn parse(&self, text: &str) -> Option<JsonValue> {
+        match self {
+            NumericType::I64 => text.parse::<i64>().ok().map(JsonValue::from),
+            NumericType::U64 => text.parse::<u64>().ok().map(JsonValue::from),
+            NumericType::F64 => text.parse::<f64>().ok().map(JsonValue::from),
+        }
+    }
+}
+
+/// Converts the values of the documents decoded from CSV or TSV rows, which are all strings,
+/// into the numbers expected by the numeric fields of the doc mapping.
+pub(crate) struct RowCoercer {
+    numeric_fields: HashMap<String, NumericType>,
+}
+
+impl RowCoercer {
+    pub fn new(schema: &Schema) -> Self {
+        let numeric_fields = schema
+            .fields()
+            .filter_map(|(_, field_entry)| {
+                let numeric_type = match field_entry.field_type() {
+                    FieldType::I64(_) => NumericType::I64,
+                    FieldType::U64(_) => NumericType::U64,
+                    FieldType::F64(_) => NumericType::F64,
+                    _ => return None,
+                };
+                Some((field_entry.name().to_string(), numeric_type))
+            })
+            .collect();
+        RowCoercer { numeric_fields }
+    }
+
+    /// Returns the document with the values of its numeric fields converted into numbers. The
+    /// values that are not numbers, and the documents that are not JSON objects, are left as is
+    /// for the doc mapper to reject.
+    pub fn coerce(&self, doc_json: String) -> String {
+        if self.numeric_fields.is_empty() {
+            return doc_json;
+        }
+        let mut doc: JsonMap<String, JsonValue> = match serde_json::from_str(&doc_json) {
+            Ok(doc) => doc,
+            Err(_) => return doc_json,
+        };
+        let mut is_coerced = false;
+        for (field_name, value) in doc.iter_mut() {
+            let number_opt = match (self.numeric_fields.get(field_name), &*value) {
+                (Some(numeric_type), JsonValue::String(text)) => numeric_type.parse(text),
+                _ => None,
+            };
+            if let Some(number) = number_opt {
+                *value = number;
+                is_coerced = true;
+            }
+        }
+        if !is_coerced {
+            return doc_json;
+        }
+        JsonValue::Object(doc).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tantivy::schema::{FAST, STRING};
+
+    use super::*;
+
+    #[test]
+    fn test_row_coercer() {
+        let mut schema_builder = Schema::builder();
+        schema_builder.add_text_field("vendor", STRING);
+        schema_builder.add_i64_field("timestamp", FAST);
+        schema_builder.add_u64_field("passenger_count", FAST);
+        schema_builder.add_f64_field("distance", FAST);
+        let row_coercer = RowCoercer::new(&schema_builder.build());
+        let doc_json = json!({
+            "vendor": "42",
+            "timestamp": "-1650000000",
+            "passenger_count": "2",
+            "distance": "12.5",
+        })
+        .to_string();
+        assert_eq!(
+            serde_json::from_str::<JsonValue>(&row_coercer.coerce(doc_json)).unwrap(),
+            json!({
+                "vendor": "42",
+                "timestamp": -1650000000,
+                "passenger_count": 2,
+                "distance": 12.5,
+            })
+        );
+        let doc_json = json!({"vendor": "acme", "passenger_count": "two"}).to_string();
+        assert_eq!(row_coercer.coerce(doc_json.clone()), doc_json);
+        assert_eq!(row_coercer.coerce("acme,2".to_string()), "acme,2");
+    }
+}
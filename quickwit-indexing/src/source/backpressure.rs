@@ -0,0 +1,132 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Sources adapt to the pace of the indexer: the fuller its mailbox, the smaller the batches they
+//! emit and the longer they wait before polling again. Smaller batches keep the memory held by
+//! the queued batches in check, and the pause gives the indexer time to catch up.
+//!
+//! The sources of a pipeline running several sources, or routing documents to several indexes,
+//! send their batches to a forwarding actor. The mailbox of the forwarding actor reports the
+//! fill ratio of the indexer mailboxes it forwards to, see [`Mailbox::forward_fill_ratio_of`].
+
+use std::time::Duration;
+
+use quickwit_actors::Mailbox;
+
+use crate::models::IndexerMessage;
+
+/// Fill ratio of the indexer mailbox up to which sources emit full-size batches without pausing.
+const BACKPRESSURE_THRESHOLD: f64 = 0.5;
+
+/// Fraction of their target size below which batches do not shrink.
+const MIN_BATCH_SIZE_RATIO: f64 = 0.1;
+
+/// Pause of the source loop when the indexer mailbox is full.
+const MAX_LOOP_DELAY: Duration = Duration::from_millis(500);
+
+/// Returns the backpressure exerted by the indexer, between 0 and 1, from the fill ratio of its
+/// mailbox. Unbounded mailboxes exert no backpressure.
+fn backpressure(batch_sink: &Mailbox<IndexerMessage>) -> f64 {
+    let saturation = batch_sink.fill_ratio();
+    ((saturation - BACKPRESSURE_THRESHOLD) / (1.0 - BACKPRESSURE_THRESHOLD)).clamp(0.0, 1.0)
+}
+
+/// Returns the size in bytes at which a source cuts its next batch: `max_batch_num_bytes` when
+/// the indexer keeps up, down to a tenth of it when the indexer mailbox is full.
+pub(crate) fn target_batch_num_bytes(
+    batch_sink: &Mailbox<IndexerMessage>,
+    max_batch_num_bytes: u64,
+) -> u64 {
+    let batch_size_ratio = 1.0 - backpressure(batch_sink) * (1.0 - MIN_BATCH_SIZE_RATIO);
+    ((max_batch_num_bytes as f64 * batch_size_ratio).round() as u64).max(1)
+}
+
+/// Returns how long the source waits before polling again, or `None` when the indexer keeps up.
+pub(crate) fn loop_delay(batch_sink: &Mailbox<IndexerMessage>) -> Option<Duration> {
+    let backpressure = backpressure(batch_sink);
+    if backpressure > 0.0 {
+        Some(MAX_LOOP_DELAY.mul_f64(backpressure))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_actors::{create_mailbox, create_test_mailbox, QueueCapacity};
+
+    use super::*;
+
+    #[test]
+    fn test_backpressure_unbounded_mailbox() {
+        let (batch_sink, _inbox) = create_test_mailbox();
+        for _ in 0..100 {
+            batch_sink.try_send_message(IndexerMessage::Flush).unwrap();
+        }
+        assert_eq!(target_batch_num_bytes(&batch_sink, 1_000), 1_000);
+        assert_eq!(loop_delay(&batch_sink), None);
+    }
+
+    #[test]
+    fn test_backpressure_bounded_mailbox() {
+        let (batch_sink, _inbox) =
+            create_mailbox("indexer".to_string(), QueueCapacity::Bounded(10));
+        assert_eq!(target_batch_num_bytes(&batch_sink, 1_000), 1_000);
+        assert_eq!(loop_delay(&batch_sink), None);
+
+        for _ in 0..5 {
+            batch_sink.try_send_message(IndexerMessage::Flush).unwrap();
+        }
+        assert_eq!(target_batch_num_bytes(&batch_sink, 1_000), 1_000);
+        assert_eq!(loop_delay(&batch_sink), None);
+
+        for _ in 0..3 {
+            batch_sink.try_send_message(IndexerMessage::Flush).unwrap();
+        }
+        assert_eq!(target_batch_num_bytes(&batch_sink, 1_000), 460);
+        assert_eq!(loop_delay(&batch_sink), Some(Duration::from_millis(300)));
+
+        for _ in 0..2 {
+            batch_sink.try_send_message(IndexerMessage::Flush).unwrap();
+        }
+        assert_eq!(target_batch_num_bytes(&batch_sink, 1_000), 100);
+        assert_eq!(loop_delay(&batch_sink), Some(MAX_LOOP_DELAY));
+    }
+
+    #[test]
+    fn test_backpressure_forwarding_mailbox() {
+        // A source tagger holds at most one batch: the backpressure comes from the indexer.
+        let (batch_sink, _source_tagger_inbox) =
+            create_mailbox("source-tagger".to_string(), QueueCapacity::Bounded(1));
+        let (indexer_mailbox, _indexer_inbox) =
+            create_mailbox("indexer".to_string(), QueueCapacity::Bounded(10));
+        batch_sink.forward_fill_ratio_of(&indexer_mailbox);
+        batch_sink.try_send_message(IndexerMessage::Flush).unwrap();
+        assert_eq!(target_batch_num_bytes(&batch_sink, 1_000), 1_000);
+        assert_eq!(loop_delay(&batch_sink), None);
+
+        for _ in 0..10 {
+            indexer_mailbox
+                .try_send_message(IndexerMessage::Flush)
+                .unwrap();
+        }
+        assert_eq!(target_batch_num_bytes(&batch_sink, 1_000), 100);
+        assert_eq!(loop_delay(&batch_sink), Some(MAX_LOOP_DELAY));
+    }
+}
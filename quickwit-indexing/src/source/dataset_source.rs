@@ -0,0 +1,487 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use parquet::basic::{ConvertedType, Repetition, Type as PhysicalType};
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::record::{Field, Row};
+use parquet::schema::types::Type as SchemaType;
+use quickwit_actors::{ActorExitStatus, Mailbox};
+use quickwit_config::{DatasetFormat, DatasetSourceParams, DocMapping};
+use quickwit_metastore::checkpoint::{CheckpointDelta, PartitionId, Position, SourceCheckpoint};
+use quickwit_storage::{quickwit_storage_uri_resolver, Storage};
+use serde::Serialize;
+use serde_json::{json, Map as JsonMap, Value as JsonValue};
+use tempfile::TempDir;
+use tracing::{info, warn};
+
+use crate::models::{IndexerMessage, RawDocBatch};
+use crate::source::{backpressure, Source, SourceContext, TypedSourceFactory};
+
+/// Cut a new batch as soon as we have read BATCH_NUM_BYTES_THRESHOLD.
+const BATCH_NUM_BYTES_THRESHOLD: u64 = 500_000u64;
+
+#[derive(Default, Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct DatasetSourceCounters {
+    pub num_files_processed: u64,
+    pub num_rows_processed: u64,
+    pub num_bytes_processed: u64,
+}
+
+type RowIterator = Box<dyn Iterator<Item = anyhow::Result<String>> + Send>;
+
+/// The dataset file currently being read.
+struct DatasetFile {
+    partition_id: PartitionId,
+    // The row iterators of the parquet crate are `Send` but not `Sync`.
+    rows: Mutex<RowIterator>,
+    num_rows_read: u64,
+    // Keeps the downloaded file alive while it is being read.
+    _local_copy: TempDir,
+}
+
+/// A source that reads the Parquet or CSV files stored under a storage URI and converts
+/// each of their rows into a JSON document.
+///
+/// Each file is a partition of the source, and the position within a file is the number of
+/// rows read so far. Files fully read are therefore skipped when the source is resumed.
+pub struct DatasetSource {
+    params: DatasetSourceParams,
+    storage: Arc<dyn Storage>,
+    checkpoint: SourceCheckpoint,
+    pending_files: VecDeque<PathBuf>,
+    current_file_opt: Option<DatasetFile>,
+    counters: DatasetSourceCounters,
+}
+
+impl DatasetSource {
+    async fn open_file(&self, path: &Path) -> anyhow::Result<DatasetFile> {
+        let local_copy = tempfile::tempdir()?;
+        let local_path = local_copy.path().join("dataset-file");
+        self.storage
+            .copy_to_file(path, &local_path)
+            .await
+            .with_context(|| format!("Failed to download dataset file `{}`.", path.display()))?;
+        let partition_id = PartitionId::from(path.to_string_lossy().to_string());
+        let num_rows_to_skip = match self.checkpoint.position_for_partition(&partition_id) {
+            Some(Position::Offset(offset_str)) => offset_str.parse::<u64>()?,
+            Some(Position::Beginning) | None => 0,
+        };
+        let mut rows = read_rows(&local_path, self.params.format)?;
+        for _ in 0..num_rows_to_skip {
+            if rows.next().is_none() {
+                break;
+            }
+        }
+        Ok(DatasetFile {
+            partition_id,
+            rows: Mutex::new(rows),
+            num_rows_read: num_rows_to_skip,
+            _local_copy: local_copy,
+        })
+    }
+}
+
+#[async_trait]
+impl Source for DatasetSource {
+    async fn emit_batches(
+        &mut self,
+        batch_sink: &Mailbox<IndexerMessage>,
+        ctx: &SourceContext,
+    ) -> Result<(), ActorExitStatus> {
+        if self.current_file_opt.is_none() {
+            if let Some(path) = self.pending_files.pop_front() {
+                info!(path = %path.display(), "Opening dataset file.");
+                self.current_file_opt = Some(self.open_file(&path).await?);
+            } else {
+                info!("Reached end of dataset.");
+                ctx.send_exit_with_success(batch_sink).await?;
+                return Err(ActorExitStatus::Success);
+            }
+        }
+        let current_file = self
+            .current_file_opt
+            .as_mut()
+            .expect("A dataset file should be open.");
+        let from_num_rows = current_file.num_rows_read;
+        let target_num_bytes =
+            backpressure::target_batch_num_bytes(batch_sink, BATCH_NUM_BYTES_THRESHOLD);
+        let mut num_bytes = 0u64;
+        let mut reached_eof = false;
+        let mut docs = Vec::new();
+        {
+            let mut rows = current_file.rows.lock().unwrap();
+            while num_bytes < target_num_bytes {
+                let doc = match rows.next() {
+                    Some(doc_res) => doc_res?,
+                    None => {
+                        reached_eof = true;
+                        break;
+                    }
+                };
+                num_bytes += doc.len() as u64;
+                docs.push(doc);
+            }
+        }
+        current_file.num_rows_read += docs.len() as u64;
+        self.counters.num_rows_processed += docs.len() as u64;
+        self.counters.num_bytes_processed += num_bytes;
+        if !docs.is_empty() {
+            let checkpoint_delta = CheckpointDelta::from_partition_delta(
+                current_file.partition_id.clone(),
+                Position::from(from_num_rows),
+                Position::from(current_file.num_rows_read),
+            );
+            let raw_doc_batch = RawDocBatch {
+                docs,
+                checkpoint_delta,
+            };
+            ctx.send_message(batch_sink, raw_doc_batch.into()).await?;
+        }
+        if reached_eof {
+            self.counters.num_files_processed += 1;
+            self.current_file_opt = None;
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        "DatasetSource".to_string()
+    }
+
+    fn observable_state(&self) -> serde_json::Value {
+        serde_json::to_value(&self.counters).unwrap()
+    }
+}
+
+pub struct DatasetSourceFactory;
+
+#[async_trait]
+impl TypedSourceFactory for DatasetSourceFactory {
+    type Source = DatasetSource;
+    type Params = DatasetSourceParams;
+
+    async fn typed_create_source(
+        params: DatasetSourceParams,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<DatasetSource> {
+        let storage = quickwit_storage_uri_resolver().resolve(&params.uri)?;
+        let pending_files = list_dataset_files(&*storage, params.format).await?;
+        info!(uri = %params.uri, num_files = pending_files.len(), "Listed dataset files.");
+        Ok(DatasetSource {
+            params,
+            storage,
+            checkpoint,
+            pending_files: pending_files.into(),
+            current_file_opt: None,
+            counters: DatasetSourceCounters::default(),
+        })
+    }
+}
+
+/// Lists the files of a dataset, i.e. the files bearing the extension of the dataset format.
+pub async fn list_dataset_files(
+    storage: &dyn Storage,
+    format: DatasetFormat,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let dataset_files = storage
+        .list_files(Path::new(""))
+        .await?
+        .into_iter()
+        .filter(|path| {
+            path.extension()
+                .map(|extension| extension == format.extension())
+                .unwrap_or(false)
+        })
+        .collect();
+    Ok(dataset_files)
+}
+
+/// Derives a doc mapping from the schema of the first Parquet file of a dataset.
+pub async fn doc_mapping_from_parquet_dataset(uri: &str) -> anyhow::Result<DocMapping> {
+    let storage = quickwit_storage_uri_resolver().resolve(uri)?;
+    let dataset_files = list_dataset_files(&*storage, DatasetFormat::Parquet).await?;
+    let first_file = if let Some(first_file) = dataset_files.first() {
+        first_file
+    } else {
+        bail!("No Parquet file found under `{}`.", uri);
+    };
+    let local_copy = tempfile::tempdir()?;
+    let local_path = local_copy.path().join("dataset-file");
+    storage.copy_to_file(first_file, &local_path).await?;
+    let file_reader = SerializedFileReader::new(File::open(&local_path)?)?;
+    doc_mapping_from_parquet_schema(file_reader.metadata().file_metadata().schema())
+}
+
+/// Builds a doc mapping from a Parquet schema.
+///
+/// Integer, floating point, and string columns are mapped to `i64`, `u64`, `f64` and `text`
+/// fields. Boolean columns are mapped to `u64` fields holding 0 or 1. Nested, binary, and
+/// decimal columns are skipped.
+pub fn doc_mapping_from_parquet_schema(schema: &SchemaType) -> anyhow::Result<DocMapping> {
+    let mut field_mappings = Vec::new();
+    for field in schema.get_fields() {
+        let field_name = field.name();
+        if !field.is_primitive() {
+            warn!(field = field_name, "Skipping nested Parquet column.");
+            continue;
+        }
+        let basic_info = field.get_basic_info();
+        let field_type = match (field.get_physical_type(), basic_info.converted_type()) {
+            (PhysicalType::BOOLEAN, _) => "u64",
+            (PhysicalType::INT32, ConvertedType::UINT_8)
+            | (PhysicalType::INT32, ConvertedType::UINT_16)
+            | (PhysicalType::INT32, ConvertedType::UINT_32)
+            | (PhysicalType::INT64, ConvertedType::UINT_64) => "u64",
+            (PhysicalType::INT32, ConvertedType::DECIMAL)
+            | (PhysicalType::INT64, ConvertedType::DECIMAL) => {
+                warn!(field = field_name, "Skipping decimal Parquet column.");
+                continue;
+            }
+            (PhysicalType::INT32, _) | (PhysicalType::INT64, _) => "i64",
+            (PhysicalType::FLOAT, _) | (PhysicalType::DOUBLE, _) => "f64",
+            (PhysicalType::BYTE_ARRAY, ConvertedType::UTF8)
+            | (PhysicalType::BYTE_ARRAY, ConvertedType::ENUM)
+            | (PhysicalType::BYTE_ARRAY, ConvertedType::JSON) => "text",
+            (physical_type, _) => {
+                warn!(field = field_name, physical_type = ?physical_type, "Skipping unsupported Parquet column.");
+                continue;
+            }
+        };
+        let field_type =
+            if basic_info.has_repetition() && basic_info.repetition() == Repetition::REPEATED {
+                format!("array<{}>", field_type)
+            } else {
+                field_type.to_string()
+            };
+        field_mappings.push(json!({
+            "name": field_name,
+            "type": field_type,
+        }));
+    }
+    if field_mappings.is_empty() {
+        bail!("The Parquet schema does not contain any supported column.");
+    }
+    let doc_mapping = serde_json::from_value(json!({ "field_mappings": field_mappings }))?;
+    Ok(doc_mapping)
+}
+
+fn read_rows(path: &Path, format: DatasetFormat) -> anyhow::Result<RowIterator> {
+    let file = File::open(path)?;
+    match format {
+        DatasetFormat::Csv => {
+            let mut csv_reader = csv::Reader::from_reader(file);
+            let headers = csv_reader.headers()?.clone();
+            let rows = csv_reader.into_records().map(move |record_res| {
+                let record = record_res?;
+                let doc: JsonMap<String, JsonValue> = headers
+                    .iter()
+                    .zip(record.iter())
+                    .filter(|(_, value)| !value.is_empty())
+                    .map(|(header, value)| (header.to_string(), JsonValue::from(value)))
+                    .collect();
+                Ok(serde_json::to_string(&doc)?)
+            });
+            Ok(Box::new(rows))
+        }
+        DatasetFormat::Parquet => {
+            let file_reader = SerializedFileReader::new(file)?;
+            let rows = file_reader
+                .into_iter()
+                .map(|row| Ok(serde_json::to_string(&parquet_row_to_json(&row))?));
+            Ok(Box::new(rows))
+        }
+    }
+}
+
+fn parquet_row_to_json(row: &Row) -> JsonValue {
+    let doc: JsonMap<String, JsonValue> = row
+        .get_column_iter()
+        .filter_map(|(name, field)| {
+            parquet_field_to_json(field).map(|value| (name.to_string(), value))
+        })
+        .collect();
+    JsonValue::Object(doc)
+}
+
+// Converts a Parquet field to the JSON value expected by the doc mapping derived
+// from the Parquet schema.
+fn parquet_field_to_json(field: &Field) -> Option<JsonValue> {
+    let value = match field {
+        Field::Null | Field::Bytes(_) | Field::Decimal(_) => return None,
+        Field::Bool(value) => JsonValue::from(*value as u64),
+        Field::Byte(value) => JsonValue::from(*value),
+        Field::Short(value) => JsonValue::from(*value),
+        Field::Int(value) => JsonValue::from(*value),
+        Field::Long(value) => JsonValue::from(*value),
+        Field::UByte(value) => JsonValue::from(*value),
+        Field::UShort(value) => JsonValue::from(*value),
+        Field::UInt(value) => JsonValue::from(*value),
+        Field::ULong(value) => JsonValue::from(*value),
+        Field::Float(value) => JsonValue::from(*value),
+        Field::Double(value) => JsonValue::from(*value),
+        Field::Str(value) => JsonValue::from(value.as_str()),
+        Field::Date(value) => JsonValue::from(*value),
+        Field::TimestampMillis(value) => JsonValue::from(*value),
+        Field::TimestampMicros(value) => JsonValue::from(*value),
+        Field::Group(row) => parquet_row_to_json(row),
+        Field::ListInternal(list) => list
+            .elements()
+            .iter()
+            .filter_map(parquet_field_to_json)
+            .collect(),
+        other => JsonValue::from(other.to_string()),
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use quickwit_actors::{create_test_mailbox, Command, CommandOrMessage, Universe};
+    use quickwit_storage::RamStorage;
+
+    use super::*;
+    use crate::source::SourceActor;
+
+    #[tokio::test]
+    async fn test_list_dataset_files() -> anyhow::Result<()> {
+        let storage = RamStorage::default();
+        for path in ["a.csv", "b.parquet", "c.csv", "README.md"] {
+            storage.put(Path::new(path), Box::new(b"".to_vec())).await?;
+        }
+        assert_eq!(
+            list_dataset_files(&storage, DatasetFormat::Csv).await?,
+            vec![PathBuf::from("a.csv"), PathBuf::from("c.csv")]
+        );
+        assert_eq!(
+            list_dataset_files(&storage, DatasetFormat::Parquet).await?,
+            vec![PathBuf::from("b.parquet")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_doc_mapping_from_parquet_schema() -> anyhow::Result<()> {
+        let schema = parquet::schema::parser::parse_message_type(
+            "message trip {
+                REQUIRED INT64 pickup_timestamp;
+                REQUIRED INT32 passenger_count (UINT_32);
+                OPTIONAL DOUBLE fare_amount;
+                OPTIONAL BINARY vendor (UTF8);
+                OPTIONAL BOOLEAN store_and_forward;
+                OPTIONAL BINARY payload;
+                OPTIONAL GROUP location {
+                    REQUIRED DOUBLE lat;
+                }
+            }",
+        )?;
+        let doc_mapping = doc_mapping_from_parquet_schema(&schema)?;
+        let field_mappings_json = serde_json::to_value(&doc_mapping.field_mappings)?;
+        let field_types: Vec<(&str, &str)> = field_mappings_json
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field_mapping| {
+                (
+                    field_mapping["name"].as_str().unwrap(),
+                    field_mapping["type"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            field_types,
+            vec![
+                ("pickup_timestamp", "i64"),
+                ("passenger_count", "u64"),
+                ("fare_amount", "f64"),
+                ("vendor", "text"),
+                ("store_and_forward", "u64"),
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dataset_source_csv() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let universe = Universe::new();
+        let (mailbox, inbox) = create_test_mailbox();
+        let dataset_dir = tempfile::tempdir()?;
+        for file_name in ["trips-1.csv", "trips-2.csv"] {
+            let mut csv_file = File::create(dataset_dir.path().join(file_name))?;
+            csv_file.write_all(b"vendor,city\nacme,paris\nfoo,\n")?;
+        }
+        let params = DatasetSourceParams {
+            uri: format!("file://{}", dataset_dir.path().display()),
+            format: DatasetFormat::Csv,
+        };
+        let mut checkpoint = SourceCheckpoint::default();
+        checkpoint.try_apply_delta(CheckpointDelta::from_partition_delta(
+            PartitionId::from("trips-1.csv"),
+            Position::from(0u64),
+            Position::from(1u64),
+        ))?;
+        let dataset_source = DatasetSourceFactory::typed_create_source(params, checkpoint).await?;
+        let dataset_source_actor = SourceActor::new(Box::new(dataset_source), mailbox);
+        let (_dataset_source_mailbox, dataset_source_handle) =
+            universe.spawn_actor(dataset_source_actor).spawn_async();
+        let (actor_termination, counters) = dataset_source_handle.join().await;
+        assert!(actor_termination.is_success());
+        assert_eq!(
+            counters,
+            serde_json::json!({
+                "num_files_processed": 2u64,
+                "num_rows_processed": 3u64,
+                "num_bytes_processed": 64u64,
+            })
+        );
+        let messages = inbox.drain_available_message_or_command_for_test();
+        assert_eq!(messages.len(), 3);
+        let batches: Vec<Vec<String>> = messages[..2]
+            .iter()
+            .map(|message| match message {
+                CommandOrMessage::Message(IndexerMessage::Batch(batch)) => batch.docs.clone(),
+                _ => panic!("Expected a batch."),
+            })
+            .collect();
+        assert_eq!(
+            batches,
+            vec![
+                vec![r#"{"vendor":"foo"}"#.to_string()],
+                vec![
+                    r#"{"city":"paris","vendor":"acme"}"#.to_string(),
+                    r#"{"vendor":"foo"}"#.to_string()
+                ],
+            ]
+        );
+        assert!(matches!(
+            messages[2],
+            CommandOrMessage::Command(Command::ExitWithSuccess)
+        ));
+        Ok(())
+    }
+}
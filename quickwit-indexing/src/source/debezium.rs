@@ -0,0 +1,145 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use anyhow::{bail, Context};
+use quickwit_config::DebeziumParams;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+/// Converts a Debezium change event into the document to index.
+///
+/// Creations, updates and snapshot reads yield the `after` row image. Deletions yield the `before`
+/// row image with the delete marker field set to `"true"`. Other events, such as truncations,
+/// yield no document. Both the plain envelope and the envelope wrapped with its schema by the
+/// Kafka Connect JSON converter are accepted.
+pub(crate) fn unwrap_debezium_envelope(
+    payload: &str,
+    params: &DebeziumParams,
+) -> anyhow::Result<Option<String>> {
+    let mut event: JsonMap<String, JsonValue> =
+        serde_json::from_str(payload).context("Change event is not a JSON object.")?;
+    if !event.contains_key("op") {
+        if let Some(JsonValue::Object(wrapped_event)) = event.remove("payload") {
+            event = wrapped_event;
+        }
+    }
+    let (row_key, is_deleted) = match event.get("op").and_then(JsonValue::as_str) {
+        Some("c") | Some("r") | Some("u") => ("after", false),
+        Some("d") => ("before", true),
+        Some(_) => return Ok(None),
+        None => bail!("Change event has no `op` field."),
+    };
+    let mut row = match event.remove(row_key) {
+        Some(JsonValue::Object(row)) => row,
+        _ => bail!("Change event has no `{}` row image.", row_key),
+    };
+    row.insert(
+        params.delete_marker_field.clone(),
+        JsonValue::String(is_deleted.to_string()),
+    );
+    if let Some(timestamp_field) = &params.timestamp_field {
+        if let Some(timestamp_millis) = event.get("ts_ms").and_then(JsonValue::as_i64) {
+            row.insert(
+                timestamp_field.clone(),
+                JsonValue::from(timestamp_millis.div_euclid(1_000)),
+            );
+        }
+    }
+    let doc = serde_json::to_string(&row).context("Failed to serialize row image.")?;
+    Ok(Some(doc))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn unwrap_to_json(payload: JsonValue, params: &DebeziumParams) -> Option<JsonValue> {
+        unwrap_debezium_envelope(&payload.to_string(), params)
+            .unwrap()
+            .map(|doc| serde_json::from_str(&doc).unwrap())
+    }
+
+    #[test]
+    fn test_unwrap_debezium_envelope_upserts() {
+        let params = DebeziumParams::default();
+        for op in ["c", "r", "u"] {
+            let event = json!({
+                "before": {"id": 1, "name": "old"},
+                "after": {"id": 1, "name": "new"},
+                "op": op,
+                "ts_ms": 1_640_000_000_123i64,
+            });
+            assert_eq!(
+                unwrap_to_json(event, &params),
+                Some(json!({"id": 1, "name": "new", "__deleted": "false"}))
+            );
+        }
+    }
+
+    #[test]
+    fn test_unwrap_debezium_envelope_delete() {
+        let params = DebeziumParams {
+            delete_marker_field: "is_deleted".to_string(),
+            timestamp_field: Some("ts".to_string()),
+        };
+        let event = json!({
+            "before": {"id": 1, "name": "old"},
+            "after": null,
+            "op": "d",
+            "ts_ms": 1_640_000_000_123i64,
+        });
+        assert_eq!(
+            unwrap_to_json(event, &params),
+            Some(json!({"id": 1, "name": "old", "is_deleted": "true", "ts": 1_640_000_000}))
+        );
+    }
+
+    #[test]
+    fn test_unwrap_debezium_envelope_with_schema() {
+        let params = DebeziumParams::default();
+        let event = json!({
+            "schema": {"type": "struct", "fields": []},
+            "payload": {
+                "before": null,
+                "after": {"id": 2},
+                "op": "c",
+            },
+        });
+        assert_eq!(
+            unwrap_to_json(event, &params),
+            Some(json!({"id": 2, "__deleted": "false"}))
+        );
+    }
+
+    #[test]
+    fn test_unwrap_debezium_envelope_skips_and_errors() {
+        let params = DebeziumParams::default();
+        let truncate_event = json!({"before": null, "after": null, "op": "t"});
+        assert_eq!(unwrap_to_json(truncate_event, &params), None);
+
+        let delete_without_before = json!({"before": null, "after": null, "op": "d"});
+        assert!(unwrap_debezium_envelope(&delete_without_before.to_string(), &params).is_err());
+
+        let not_an_event = json!({"id": 1});
+        assert!(unwrap_debezium_envelope(&not_an_event.to_string(), &params).is_err());
+
+        assert!(unwrap_debezium_envelope("[1, 2]", &params).is_err());
+    }
+}
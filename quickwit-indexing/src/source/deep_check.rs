@@ -0,0 +1,216 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//! Deep source checks go further than connectivity checks: they read the first documents of a
+//! source, as an indexing pipeline starting from scratch would, and check that the decoding
+//! settings of the source produce documents that the index accepts.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context};
+use quickwit_actors::{Actor, ActorContext, ActorExitStatus, QueueCapacity, SyncActor, Universe};
+use quickwit_config::{SourceConfig, SourceParams};
+use quickwit_doc_mapper::DocMapper;
+use quickwit_metastore::checkpoint::SourceCheckpoint;
+use tokio::sync::oneshot;
+use tracing::info;
+
+use super::{quickwit_supported_sources, Source, SourceActor};
+use crate::doc_filter::DocFilter;
+use crate::doc_transform::DocTransform;
+use crate::models::IndexerMessage;
+
+/// Maximum time a deep check waits for the first documents of a source.
+const DEEP_CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Reads the first batch of documents of a source and checks that the first document kept by the
+/// transform and the filter of the source is accepted by `doc_mapper`.
+///
+/// The documents read are not acknowledged to the upstream system, so sources relying on
+/// acknowledgements deliver them again later. Sources that receive their documents instead of
+/// reading them are not checked.
+pub async fn check_source_decoding(
+    source_config: &SourceConfig,
+    doc_mapper: &dyn DocMapper,
+) -> anyhow::Result<()> {
+    if matches!(
+        source_config.source_params,
+        SourceParams::IngestApi(_) | SourceParams::Otlp(_) | SourceParams::Syslog(_)
+    ) {
+        info!(
+            source_id = %source_config.source_id,
+            "Skipping the deep check of a source receiving its documents."
+        );
+        return Ok(());
+    }
+    let doc_transform_opt = source_config
+        .transform
+        .as_ref()
+        .map(DocTransform::try_new)
+        .transpose()?;
+    let doc_filter_opt = source_config
+        .filter
+        .as_ref()
+        .map(DocFilter::try_new)
+        .transpose()?;
+    let source = quickwit_supported_sources()
+        .load_source(source_config.clone(), SourceCheckpoint::default())
+        .await?;
+    let docs = tokio::time::timeout(DEEP_CHECK_TIMEOUT, read_first_batch(source))
+        .await
+        .map_err(|_| {
+            anyhow!(
+                "Source emitted no document within {} seconds.",
+                DEEP_CHECK_TIMEOUT.as_secs()
+            )
+        })??;
+    for doc_json in docs {
+        let doc_json = match &doc_transform_opt {
+            Some(doc_transform) => match doc_transform.transform(doc_json) {
+                Some(transformed_doc) => transformed_doc,
+                None => continue,
+            },
+            None => doc_json,
+        };
+        if let Some(doc_filter) = &doc_filter_opt {
+            if doc_filter.is_dropped(&doc_json) {
+                continue;
+            }
+        }
+        doc_mapper
+            .doc_from_json(doc_json)
+            .context("Failed to parse the first document of the source.")?;
+        return Ok(());
+    }
+    bail!("The transform or the filter of the source dropped all the documents read.")
+}
+
+/// Drives the source until it emits a non-empty batch, and returns its documents.
+async fn read_first_batch(source: Box<dyn Source>) -> anyhow::Result<Vec<String>> {
+    // The universe kills the actors once dropped.
+    let universe = Universe::new();
+    let (docs_tx, mut docs_rx) = oneshot::channel();
+    let collector = FirstBatchCollector {
+        docs_tx_opt: Some(docs_tx),
+    };
+    let (collector_mailbox, _collector_handle) = universe.spawn_actor(collector).spawn_sync();
+    let source_actor = SourceActor::new(source, collector_mailbox.clone());
+    let (_source_mailbox, source_handle) = universe.spawn_actor(source_actor).spawn_async();
+    tokio::select! {
+        docs_res = &mut docs_rx => {
+            return docs_res.map_err(|_| anyhow!("Source emitted no document."));
+        }
+        (exit_status, _) = source_handle.join() => {
+            if !exit_status.is_success() {
+                bail!("Source failed: {:?}.", exit_status);
+            }
+        }
+    }
+    // The source is exhausted: the collector processes the batches it already emitted, then
+    // exits.
+    universe.send_exit_with_success(&collector_mailbox).await?;
+    docs_rx
+        .await
+        .map_err(|_| anyhow!("Source emitted no document."))
+}
+
+/// Receives the batches emitted by a source and forwards the documents of the first non-empty
+/// one.
+struct FirstBatchCollector {
+    docs_tx_opt: Option<oneshot::Sender<Vec<String>>>,
+}
+
+impl Actor for FirstBatchCollector {
+    type Message = IndexerMessage;
+
+    type ObservableState = ();
+
+    fn observable_state(&self) -> Self::ObservableState {}
+
+    fn queue_capacity(&self) -> QueueCapacity {
+        QueueCapacity::Bounded(1)
+    }
+
+    fn name(&self) -> String {
+        "FirstBatchCollector".to_string()
+    }
+}
+
+impl SyncActor for FirstBatchCollector {
+    fn process_message(
+        &mut self,
+        message: IndexerMessage,
+        _ctx: &ActorContext<Self>,
+    ) -> Result<(), ActorExitStatus> {
+        if let IndexerMessage::Batch(batch) | IndexerMessage::SourceBatch { batch, .. } = message {
+            if !batch.docs.is_empty() {
+                if let Some(docs_tx) = self.docs_tx_opt.take() {
+                    let _ = docs_tx.send(batch.docs);
+                }
+                return Err(ActorExitStatus::Success);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_config::VecSourceParams;
+    use quickwit_doc_mapper::default_doc_mapper_for_tests;
+
+    use super::*;
+
+    fn vec_source_config(items: &[&str]) -> SourceConfig {
+        SourceConfig {
+            source_id: "vec".to_string(),
+            source_params: SourceParams::Vec(VecSourceParams {
+                items: items.iter().map(|item| item.to_string()).collect(),
+                batch_num_docs: 10,
+                partition: "".to_string(),
+            }),
+            filter: None,
+            routing: None,
+            transform: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_source_decoding() {
+        let doc_mapper = default_doc_mapper_for_tests();
+        let source_config = vec_source_config(&[r#"{"timestamp": 1, "body": "hello"}"#]);
+        check_source_decoding(&source_config, &doc_mapper)
+            .await
+            .unwrap();
+
+        let source_config = vec_source_config(&["not json"]);
+        let error = check_source_decoding(&source_config, &doc_mapper)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to parse the first document of the source."
+        );
+
+        let source_config = vec_source_config(&[]);
+        let error = check_source_decoding(&source_config, &doc_mapper)
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Source emitted no document.");
+    }
+}
@@ -0,0 +1,536 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::SeekFrom;
+use std::time::Duration;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use glob::Pattern;
+use quickwit_actors::{ActorExitStatus, Mailbox};
+use quickwit_config::{DirPublishedAction, DirSourceParams};
+use quickwit_metastore::checkpoint::{CheckpointDelta, PartitionId, Position, SourceCheckpoint};
+use serde::Serialize;
+use serde_json::json;
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, AsyncSeekExt, BufReader};
+use tracing::{info, warn};
+
+use crate::models::{IndexerMessage, RawDocBatch};
+use crate::source::record_splitter::{self, RecordSplitter};
+use crate::source::row_decoder::RowDecoder;
+use crate::source::{backpressure, Source, SourceContext, TypedSourceFactory};
+
+/// Cut a new batch as soon as we have read BATCH_NUM_BYTES_THRESHOLD.
+const BATCH_NUM_BYTES_THRESHOLD: u64 = 500_000u64;
+
+#[derive(Default, Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct DirSourceCounters {
+    pub num_files_processed: u64,
+    pub num_lines_processed: u64,
+    pub num_bytes_processed: u64,
+    pub num_published_files: u64,
+}
+
+/// File of the directory being read.
+struct CurrentFile {
+    file_name: String,
+    partition_id: PartitionId,
+    reader: BufReader<File>,
+    offset: u64,
+    /// Splits the file into documents when they are not delimited by line feeds.
+    record_splitter_opt: Option<RecordSplitter>,
+}
+
+/// Watches a directory for the files whose name matches a glob pattern and indexes them one
+/// after the other, in the lexicographic order of their names.
+///
+/// Each file is a partition of the source named after the file name, whose position is the
+/// number of bytes of the file read so far. A file that grows after being read is read again
+/// from this offset. Once all the documents of a file are published, the file is kept, deleted,
+/// or moved to another directory, depending on `on_published`.
+pub struct DirSource {
+    params: DirSourceParams,
+    pattern: Pattern,
+    /// Number of bytes read per file, seeded from the checkpoint.
+    file_offsets: HashMap<String, u64>,
+    current_file_opt: Option<CurrentFile>,
+    /// Converts the lines into documents when the files hold CSV or TSV rows.
+    row_decoder_opt: Option<RowDecoder>,
+    /// Files read entirely whose documents are not published yet, with their size.
+    unpublished_files: BTreeMap<String, u64>,
+    counters: DirSourceCounters,
+}
+
+impl DirSource {
+    /// Opens the first file of the directory with unread bytes. The published files left over by
+    /// a previous run, or whose action failed, are processed along the way.
+    async fn open_next_file(&mut self) -> anyhow::Result<Option<CurrentFile>> {
+        let mut read_dir = tokio::fs::read_dir(&self.params.dir_path)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to list directory `{}`.",
+                    self.params.dir_path.display()
+                )
+            })?;
+        let mut unread_files = Vec::new();
+        let mut published_files = Vec::new();
+        while let Some(entry) = read_dir.next_entry().await? {
+            let file_name = match entry.file_name().into_string() {
+                Ok(file_name) => file_name,
+                Err(_) => continue,
+            };
+            if !self.pattern.matches(&file_name) || self.unpublished_files.contains_key(&file_name)
+            {
+                continue;
+            }
+            let metadata = entry.metadata().await?;
+            if !metadata.is_file() || metadata.len() == 0 {
+                continue;
+            }
+            let offset = self.file_offsets.get(&file_name).copied().unwrap_or(0);
+            if metadata.len() > offset {
+                unread_files.push((file_name, offset));
+            } else {
+                published_files.push(file_name);
+            }
+        }
+        if self.params.on_published != DirPublishedAction::Keep {
+            for file_name in published_files {
+                self.apply_published_action(&file_name).await;
+            }
+        }
+        let (file_name, offset) = match unread_files.into_iter().min() {
+            Some(unread_file) => unread_file,
+            None => return Ok(None),
+        };
+        let filepath = self.params.dir_path.join(&file_name);
+        let mut file = File::open(&filepath)
+            .await
+            .with_context(|| format!("Failed to open source file `{}`.", filepath.display()))?;
+        if let Some(row_decoder) = &mut self.row_decoder_opt {
+            row_decoder.reset_header();
+            if row_decoder.reads_header() && offset > 0 {
+                // The header line was read before the checkpoint.
+                let mut header_line = String::new();
+                BufReader::new(&mut file)
+                    .read_line(&mut header_line)
+                    .await
+                    .with_context(|| format!("Failed to read file `{}`.", file_name))?;
+                row_decoder.decode(&header_line);
+            }
+        }
+        file.seek(SeekFrom::Start(offset)).await?;
+        info!(filepath = %filepath.display(), offset = offset, "Reading file.");
+        Ok(Some(CurrentFile {
+            partition_id: PartitionId::from(file_name.clone()),
+            file_name,
+            reader: BufReader::new(file),
+            offset,
+            record_splitter_opt: RecordSplitter::new(self.params.framing, offset),
+        }))
+    }
+
+    /// Deletes or moves a file whose documents are all published. Failures are logged: the
+    /// action is attempted again on the next scan of the directory.
+    async fn apply_published_action(&mut self, file_name: &str) {
+        let filepath = self.params.dir_path.join(file_name);
+        let action_result = match (self.params.on_published, &self.params.published_dir_path) {
+            (DirPublishedAction::Delete, _) => tokio::fs::remove_file(&filepath).await,
+            (DirPublishedAction::Move, Some(published_dir_path)) => {
+                tokio::fs::rename(&filepath, published_dir_path.join(file_name)).await
+            }
+            _ => return,
+        };
+        if let Err(error) = action_result {
+            warn!(filepath = %filepath.display(), error = ?error, "Failed to remove published file from directory.");
+            return;
+        }
+        self.counters.num_published_files += 1;
+    }
+}
+
+#[async_trait]
+impl Source for DirSource {
+    async fn emit_batches(
+        &mut self,
+        batch_sink: &Mailbox<IndexerMessage>,
+        ctx: &SourceContext,
+    ) -> Result<(), ActorExitStatus> {
+        if self.current_file_opt.is_none() {
+            let next_file_opt = {
+                let _protect_guard = ctx.protect_zone();
+                self.open_next_file().await?
+            };
+            if next_file_opt.is_none() {
+                ctx.sleep(Duration::from_secs(self.params.poll_interval_secs))
+                    .await;
+                return Ok(());
+            }
+            self.current_file_opt = next_file_opt;
+        }
+        let current_file = self
+            .current_file_opt
+            .as_mut()
+            .expect("The current file should be open.");
+        // We collect batches of documents before sending them to the indexer.
+        let previous_offset = current_file.offset;
+        let limit_num_bytes = previous_offset
+            + backpressure::target_batch_num_bytes(batch_sink, BATCH_NUM_BYTES_THRESHOLD);
+        let mut reached_eof = false;
+        let mut docs = Vec::new();
+        while current_file.offset < limit_num_bytes {
+            let mut doc_line = String::new();
+            let read_result = match &mut current_file.record_splitter_opt {
+                Some(record_splitter) => {
+                    record_splitter::read_record(
+                        &mut current_file.reader,
+                        record_splitter,
+                        &mut doc_line,
+                    )
+                    .await
+                }
+                None => current_file
+                    .reader
+                    .read_line(&mut doc_line)
+                    .await
+                    .map_err(anyhow::Error::from),
+            };
+            let num_bytes = read_result
+                .with_context(|| format!("Failed to read file `{}`.", current_file.file_name))?;
+            if num_bytes == 0 {
+                reached_eof = true;
+                break;
+            }
+            if let Some(row_decoder) = &mut self.row_decoder_opt {
+                docs.extend(row_decoder.decode(&doc_line));
+            } else if current_file.record_splitter_opt.is_none() || !doc_line.is_empty() {
+                // Records made only of separators, such as the end of a JSON array, hold no
+                // document.
+                docs.push(doc_line);
+            }
+            current_file.offset += num_bytes as u64;
+            self.counters.num_lines_processed += 1;
+            self.counters.num_bytes_processed += num_bytes as u64;
+        }
+        let current_offset = current_file.offset;
+        // The header line and blank lines of CSV or TSV files, or the end of a JSON array, may
+        // leave a batch without documents.
+        if current_offset > previous_offset {
+            let checkpoint_delta = CheckpointDelta::from_partition_delta(
+                current_file.partition_id.clone(),
+                Position::from(previous_offset),
+                Position::from(current_offset),
+            );
+            let raw_doc_batch = RawDocBatch {
+                docs,
+                checkpoint_delta,
+            };
+            ctx.send_message(batch_sink, raw_doc_batch.into()).await?;
+        }
+        if reached_eof {
+            let file_name = current_file.file_name.clone();
+            self.file_offsets.insert(file_name.clone(), current_offset);
+            if self.params.on_published != DirPublishedAction::Keep {
+                self.unpublished_files.insert(file_name, current_offset);
+            }
+            self.counters.num_files_processed += 1;
+            self.current_file_opt = None;
+        }
+        Ok(())
+    }
+
+    async fn suggest_truncate(
+        &mut self,
+        checkpoint: SourceCheckpoint,
+        ctx: &SourceContext,
+    ) -> anyhow::Result<()> {
+        let mut published_file_names = Vec::new();
+        for (file_name, file_size) in &self.unpublished_files {
+            let partition_id = PartitionId::from(file_name.clone());
+            if let Some(Position::Offset(offset_str)) =
+                checkpoint.position_for_partition(&partition_id)
+            {
+                if offset_str.parse::<u64>()? >= *file_size {
+                    published_file_names.push(file_name.clone());
+                }
+            }
+        }
+        for file_name in published_file_names {
+            self.unpublished_files.remove(&file_name);
+            let _protect_guard = ctx.protect_zone();
+            self.apply_published_action(&file_name).await;
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        "DirSource".to_string()
+    }
+
+    fn observable_state(&self) -> serde_json::Value {
+        json!({
+            "dir_path": self.params.dir_path,
+            "current_file": self.current_file_opt.as_ref().map(|current_file| &current_file.file_name),
+            "num_unpublished_files": self.unpublished_files.len(),
+            "num_files_processed": self.counters.num_files_processed,
+            "num_lines_processed": self.counters.num_lines_processed,
+            "num_bytes_processed": self.counters.num_bytes_processed,
+            "num_published_files": self.counters.num_published_files,
+        })
+    }
+}
+
+pub struct DirSourceFactory;
+
+#[async_trait]
+impl TypedSourceFactory for DirSourceFactory {
+    type Source = DirSource;
+    type Params = DirSourceParams;
+
+    async fn typed_create_source(
+        params: DirSourceParams,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<DirSource> {
+        let pattern = Pattern::new(&params.pattern)
+            .with_context(|| format!("Invalid file name pattern `{}`.", params.pattern))?;
+        let mut file_offsets = HashMap::new();
+        for (partition_id, position) in checkpoint.iter() {
+            if let Position::Offset(offset_str) = position {
+                file_offsets.insert(partition_id.0.to_string(), offset_str.parse::<u64>()?);
+            }
+        }
+        let row_decoder_opt = RowDecoder::new(params.format, params.columns.as_deref());
+        info!(
+            dir_path = %params.dir_path.display(),
+            pattern = %params.pattern,
+            num_files_in_checkpoint = file_offsets.len(),
+            "Starting directory source."
+        );
+        Ok(DirSource {
+            params,
+            pattern,
+            file_offsets,
+            current_file_opt: None,
+            row_decoder_opt,
+            unpublished_files: BTreeMap::new(),
+            counters: DirSourceCounters::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use quickwit_actors::{create_test_mailbox, Universe};
+    use quickwit_config::{DocFormat, DocFraming};
+
+    use super::*;
+    use crate::source::{SourceActor, SourceActorMessage};
+
+    fn write_lines(filepath: &Path, lines: &[&str]) {
+        let content: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+        std::fs::write(filepath, content).unwrap();
+    }
+
+    fn extract_docs(indexer_messages: Vec<IndexerMessage>) -> Vec<String> {
+        indexer_messages
+            .into_iter()
+            .flat_map(|message| match message {
+                IndexerMessage::Batch(batch) => batch.docs,
+                _ => Vec::new(),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_dir_source_reads_matching_files() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let dir = tempfile::tempdir()?;
+        write_lines(&dir.path().join("b.json"), &["b0"]);
+        write_lines(&dir.path().join("a.json"), &["a0", "a1"]);
+        write_lines(&dir.path().join("c.txt"), &["c0"]);
+        let mut params = DirSourceParams::new(dir.path());
+        params.pattern = "*.json".to_string();
+        params.poll_interval_secs = 1;
+        let dir_source =
+            DirSourceFactory::typed_create_source(params, SourceCheckpoint::default()).await?;
+        let universe = Universe::new();
+        let (mailbox, inbox) = create_test_mailbox();
+        let dir_source_actor = SourceActor::new(Box::new(dir_source), mailbox);
+        let (_dir_source_mailbox, dir_source_handle) =
+            universe.spawn_actor(dir_source_actor).spawn_async();
+        tokio::time::sleep(quickwit_actors::HEARTBEAT).await;
+        let observation = dir_source_handle.process_pending_and_observe().await.state;
+        assert_eq!(observation["num_files_processed"], 2);
+        assert_eq!(observation["num_lines_processed"], 3);
+        assert_eq!(observation["current_file"], serde_json::Value::Null);
+
+        // A new file is picked up on the next scan.
+        write_lines(&dir.path().join("d.json"), &["d0"]);
+        tokio::time::sleep(2 * quickwit_actors::HEARTBEAT).await;
+        let observation = dir_source_handle.process_pending_and_observe().await.state;
+        assert_eq!(observation["num_files_processed"], 3);
+
+        let indexer_messages = inbox.drain_available_message_for_test();
+        assert!(matches!(
+            &indexer_messages[0],
+            IndexerMessage::Batch(batch) if format!("{:?}", batch.checkpoint_delta)
+                == "∆(a.json:(00000000000000000000..00000000000000000006])"
+        ));
+        assert_eq!(
+            extract_docs(indexer_messages),
+            vec!["a0\n", "a1\n", "b0\n", "d0\n"]
+        );
+        dir_source_handle.kill().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dir_source_resumes_from_checkpoint_and_moves_published_files(
+    ) -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let dir = tempfile::tempdir()?;
+        let published_dir = tempfile::tempdir()?;
+        // `a.json` was entirely published by a previous run, `b.json` partially.
+        write_lines(&dir.path().join("a.json"), &["a0"]);
+        write_lines(&dir.path().join("b.json"), &["b0", "b1"]);
+        let mut checkpoint = SourceCheckpoint::default();
+        for (file_name, offset) in [("a.json", 3u64), ("b.json", 3u64)] {
+            checkpoint.try_apply_delta(CheckpointDelta::from_partition_delta(
+                PartitionId::from(file_name),
+                Position::from(0u64),
+                Position::from(offset),
+            ))?;
+        }
+        let mut params = DirSourceParams::new(dir.path());
+        params.poll_interval_secs = 1;
+        params.on_published = DirPublishedAction::Move;
+        params.published_dir_path = Some(published_dir.path().to_path_buf());
+        let dir_source = DirSourceFactory::typed_create_source(params, checkpoint).await?;
+        let universe = Universe::new();
+        let (mailbox, inbox) = create_test_mailbox();
+        let dir_source_actor = SourceActor::new(Box::new(dir_source), mailbox);
+        let (dir_source_mailbox, dir_source_handle) =
+            universe.spawn_actor(dir_source_actor).spawn_async();
+        tokio::time::sleep(quickwit_actors::HEARTBEAT).await;
+        let observation = dir_source_handle.process_pending_and_observe().await.state;
+        assert_eq!(observation["num_files_processed"], 1);
+        assert_eq!(observation["num_unpublished_files"], 1);
+        assert_eq!(observation["num_published_files"], 1);
+        assert!(published_dir.path().join("a.json").exists());
+        assert_eq!(
+            extract_docs(inbox.drain_available_message_for_test()),
+            vec!["b1\n"]
+        );
+
+        // `b.json` is moved once its last documents are published.
+        let mut published_checkpoint = SourceCheckpoint::default();
+        published_checkpoint.try_apply_delta(CheckpointDelta::from_partition_delta(
+            PartitionId::from("b.json"),
+            Position::from(0u64),
+            Position::from(6u64),
+        ))?;
+        universe
+            .send_message(
+                &dir_source_mailbox,
+                SourceActorMessage::SuggestTruncate(published_checkpoint),
+            )
+            .await?;
+        let observation = dir_source_handle.process_pending_and_observe().await.state;
+        assert_eq!(observation["num_unpublished_files"], 0);
+        assert_eq!(observation["num_published_files"], 2);
+        assert!(!dir.path().join("b.json").exists());
+        assert!(published_dir.path().join("b.json").exists());
+        dir_source_handle.kill().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dir_source_length_prefixed() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let dir = tempfile::tempdir()?;
+        let mut bytes = Vec::new();
+        for doc in [r#"{"body": "a"}"#, r#"{"body": "b"}"#] {
+            bytes.extend_from_slice(&(doc.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(doc.as_bytes());
+        }
+        std::fs::write(dir.path().join("a.bin"), &bytes)?;
+        let mut params = DirSourceParams::new(dir.path());
+        params.framing = DocFraming::LengthPrefixed;
+        let dir_source =
+            DirSourceFactory::typed_create_source(params, SourceCheckpoint::default()).await?;
+        let universe = Universe::new();
+        let (mailbox, inbox) = create_test_mailbox();
+        let dir_source_actor = SourceActor::new(Box::new(dir_source), mailbox);
+        let (_dir_source_mailbox, dir_source_handle) =
+            universe.spawn_actor(dir_source_actor).spawn_async();
+        tokio::time::sleep(quickwit_actors::HEARTBEAT).await;
+        let observation = dir_source_handle.process_pending_and_observe().await.state;
+        assert_eq!(observation["num_files_processed"], 1);
+        assert_eq!(observation["num_bytes_processed"], 34);
+        assert_eq!(
+            extract_docs(inbox.drain_available_message_for_test()),
+            vec![r#"{"body": "a"}"#, r#"{"body": "b"}"#]
+        );
+        dir_source_handle.kill().await;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dir_source_csv() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let dir = tempfile::tempdir()?;
+        write_lines(&dir.path().join("a.csv"), &["vendor,city", "acme,paris"]);
+        // `b.csv` was read up to its first row by a previous run.
+        write_lines(
+            &dir.path().join("b.csv"),
+            &["city,vendor", "rome,foo", "oslo,bar"],
+        );
+        let mut checkpoint = SourceCheckpoint::default();
+        checkpoint.try_apply_delta(CheckpointDelta::from_partition_delta(
+            PartitionId::from("b.csv"),
+            Position::from(0u64),
+            Position::from(21u64),
+        ))?;
+        let mut params = DirSourceParams::new(dir.path());
+        params.format = DocFormat::Csv;
+        let dir_source = DirSourceFactory::typed_create_source(params, checkpoint).await?;
+        let universe = Universe::new();
+        let (mailbox, inbox) = create_test_mailbox();
+        let dir_source_actor = SourceActor::new(Box::new(dir_source), mailbox);
+        let (_dir_source_mailbox, dir_source_handle) =
+            universe.spawn_actor(dir_source_actor).spawn_async();
+        tokio::time::sleep(quickwit_actors::HEARTBEAT).await;
+        let observation = dir_source_handle.process_pending_and_observe().await.state;
+        assert_eq!(observation["num_files_processed"], 2);
+        assert_eq!(
+            extract_docs(inbox.drain_available_message_for_test()),
+            vec![
+                serde_json::json!({"vendor": "acme", "city": "paris"}).to_string(),
+                serde_json::json!({"vendor": "bar", "city": "oslo"}).to_string(),
+            ]
+        );
+        dir_source_handle.kill().await;
+        Ok(())
+    }
+}
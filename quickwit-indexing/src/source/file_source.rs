@@ -17,25 +17,40 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::io;
+use std::collections::BTreeMap;
 use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use async_trait::async_trait;
 use quickwit_actors::{ActorExitStatus, Mailbox};
 use quickwit_config::FileSourceParams;
-use quickwit_metastore::checkpoint::{CheckpointDelta, PartitionId, Position};
+use quickwit_metastore::checkpoint::{CheckpointDelta, PartitionId, Position, SourceCheckpoint};
+use quickwit_storage::{quickwit_storage_uri_resolver, Storage};
+use reqwest::StatusCode;
 use serde::Serialize;
+use serde_json::json;
 use tokio::fs::File;
 use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncSeekExt, BufReader};
 use tracing::info;
 
 use crate::models::{IndexerMessage, RawDocBatch};
-use crate::source::{Source, SourceContext, TypedSourceFactory};
+use crate::source::multiline::MultilineAssembler;
+use crate::source::record_splitter::{self, RecordSplitter};
+use crate::source::row_decoder::RowDecoder;
+use crate::source::{backpressure, Source, SourceContext, TypedSourceFactory};
 
 /// Cut a new batch as soon as we have read BATCH_NUM_BYTES_THRESHOLD.
 const BATCH_NUM_BYTES_THRESHOLD: u64 = 500_000u64;
 
+/// Size of the chunks fetched when reading an object of a storage.
+const STORAGE_CHUNK_NUM_BYTES: usize = 5_000_000;
+
+/// Interval between two checks for new lines once the end of a followed file is reached.
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
 #[derive(Default, Clone, Debug, Eq, PartialEq, Serialize)]
 pub struct FileSourceCounters {
     pub previous_offset: u64,
@@ -44,9 +59,386 @@ pub struct FileSourceCounters {
 }
 
 pub struct FileSource {
-    params: FileSourceParams,
     counters: FileSourceCounters,
-    reader: BufReader<Box<dyn AsyncRead + Send + Sync + Unpin>>,
+    reader: DocReader,
+    /// Partition of the checkpoint tracking the file. None when reading from stdin.
+    partition_id_opt: Option<PartitionId>,
+    multiline_assembler_opt: Option<MultilineAssembler<()>>,
+    /// Converts the lines into documents when the file holds CSV or TSV rows.
+    row_decoder_opt: Option<RowDecoder>,
+    /// Splits the file into documents when they are not delimited by line feeds.
+    record_splitter_opt: Option<RecordSplitter>,
+    /// Line read so far. In follow mode, the last line of the file is kept here until its line
+    /// feed is written.
+    line_buffer: String,
+    followed_file_opt: Option<FollowedFile>,
+}
+
+/// Local file read in follow mode.
+struct FollowedFile {
+    filepath: PathBuf,
+    /// ID of the file currently read, which differs from the ID of the file at `filepath` once
+    /// the file is rotated.
+    file_id: u64,
+    is_rotated: bool,
+}
+
+impl FollowedFile {
+    /// Returns true if the file at `filepath` was replaced by a new file, e.g. by a log rotation.
+    async fn check_rotated(&self) -> bool {
+        // While the file is being rotated, the new file may not exist yet.
+        match tokio::fs::metadata(&self.filepath).await {
+            Ok(metadata) => file_id(&metadata) != self.file_id,
+            Err(_) => false,
+        }
+    }
+}
+
+/// Returns the inode of the file, which changes when the file at a given path is replaced.
+#[cfg(unix)]
+fn file_id(metadata: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.ino()
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &std::fs::Metadata) -> u64 {
+    0
+}
+
+/// In follow mode, every file rotated into the path is tracked as a partition of its own, since
+/// its offsets start over.
+fn followed_partition_id(filepath: &Path, file_id: u64) -> PartitionId {
+    PartitionId::from(format!("{}@{}", filepath.to_string_lossy(), file_id))
+}
+
+impl FileSource {
+    fn pending_num_bytes(&self) -> u64 {
+        self.multiline_assembler_opt
+            .as_ref()
+            .map(|multiline_assembler| multiline_assembler.pending_num_bytes() as u64)
+            .unwrap_or(0)
+    }
+
+    /// Reads the next line, or the next record if the documents are not delimited by line
+    /// feeds, and returns its number of bytes, or 0 at the end of the input.
+    async fn read_record(&mut self) -> anyhow::Result<usize> {
+        match &mut self.record_splitter_opt {
+            Some(record_splitter) => {
+                self.reader
+                    .read_record(record_splitter, &mut self.line_buffer)
+                    .await
+            }
+            None => self.reader.read_line(&mut self.line_buffer).await,
+        }
+    }
+
+    fn process_line(&mut self, doc_line: String, num_bytes: usize, docs: &mut Vec<String>) {
+        self.counters.current_offset += num_bytes as u64;
+        self.counters.num_lines_processed += 1;
+        if let Some(multiline_assembler) = &mut self.multiline_assembler_opt {
+            let line = doc_line.trim_end_matches(|c| c == '\n' || c == '\r');
+            if let Some(record) = multiline_assembler.push((), line, num_bytes, Instant::now()) {
+                docs.push(message_doc(&record.text));
+            }
+        } else if let Some(row_decoder) = &mut self.row_decoder_opt {
+            docs.extend(row_decoder.decode(&doc_line));
+        } else if self.record_splitter_opt.is_some() {
+            // Records made only of separators, such as the end of a JSON array, hold no document.
+            if !doc_line.is_empty() {
+                docs.push(doc_line);
+            }
+        } else {
+            docs.push(doc_line);
+        }
+    }
+
+    /// Switches to the file rotated into the followed path, once the previous file is read.
+    async fn follow_rotated_file(&mut self) -> anyhow::Result<()> {
+        let followed_file = self
+            .followed_file_opt
+            .as_mut()
+            .expect("The source should follow a file.");
+        let file = File::open(&followed_file.filepath).await.with_context(|| {
+            format!(
+                "Failed to open source file `{}`.",
+                followed_file.filepath.display()
+            )
+        })?;
+        followed_file.file_id = file_id(&file.metadata().await?);
+        followed_file.is_rotated = false;
+        info!(filepath = %followed_file.filepath.display(), file_id = followed_file.file_id, "follow-rotated-file");
+        self.partition_id_opt = Some(followed_partition_id(
+            &followed_file.filepath,
+            followed_file.file_id,
+        ));
+        self.reader = DocReader::local(file);
+        if let Some(row_decoder) = &mut self.row_decoder_opt {
+            row_decoder.reset_header();
+        }
+        self.counters.previous_offset = 0;
+        self.counters.current_offset = 0;
+        Ok(())
+    }
+}
+
+/// Wraps the text of a multiline record, which is not JSON, into a document.
+fn message_doc(text: &str) -> String {
+    json!({ "message": text }).to_string()
+}
+
+enum DocReader {
+    Local(BufReader<Box<dyn AsyncRead + Send + Sync + Unpin>>),
+    Storage(StorageObjectReader),
+    Http(HttpFileReader),
+}
+
+impl DocReader {
+    fn local<R: AsyncRead + Send + Sync + Unpin + 'static>(reader: R) -> Self {
+        let reader: Box<dyn AsyncRead + Send + Sync + Unpin> = Box::new(reader);
+        DocReader::Local(BufReader::new(reader))
+    }
+
+    /// Appends the next line, including its line feed, to `line` and returns its number of
+    /// bytes, or 0 at the end of the input.
+    async fn read_line(&mut self, line: &mut String) -> anyhow::Result<usize> {
+        match self {
+            DocReader::Local(reader) => Ok(reader.read_line(line).await?),
+            DocReader::Storage(reader) => reader.read_line(line).await,
+            DocReader::Http(reader) => reader.read_line(line).await,
+        }
+    }
+
+    /// Appends the next record, split by `record_splitter`, to `record` and returns the number
+    /// of bytes it consumed, or 0 at the end of the input.
+    async fn read_record(
+        &mut self,
+        record_splitter: &mut RecordSplitter,
+        record: &mut String,
+    ) -> anyhow::Result<usize> {
+        loop {
+            if let Some(num_bytes) = record_splitter.read_record(record)? {
+                return Ok(num_bytes);
+            }
+            let chunk_opt = match self {
+                DocReader::Local(reader) => {
+                    return record_splitter::read_record(reader, record_splitter, record).await;
+                }
+                DocReader::Storage(reader) => reader.read_chunk().await?,
+                DocReader::Http(reader) => reader.read_chunk().await?,
+            };
+            match chunk_opt {
+                Some(chunk) => record_splitter.extend(&chunk),
+                None => return record_splitter.read_remaining(),
+            }
+        }
+    }
+}
+
+/// Bytes of a remote file fetched chunk by chunk, split into lines.
+#[derive(Default)]
+struct LineBuffer {
+    /// Bytes fetched, starting at `read_offset` for the bytes not read yet.
+    buffer: Vec<u8>,
+    read_offset: usize,
+    /// Offset from which the end of the next line is looked for.
+    scan_offset: usize,
+}
+
+impl LineBuffer {
+    /// Appends the next line, including its line feed, to `line` and returns its number of
+    /// bytes, or `None` if the line is not fetched entirely yet.
+    fn read_line(&mut self, line: &mut String) -> anyhow::Result<Option<usize>> {
+        let line_end_opt = self.buffer[self.scan_offset..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map(|pos| self.scan_offset + pos + 1);
+        if let Some(line_end) = line_end_opt {
+            return self.consume(line_end, line).map(Some);
+        }
+        self.scan_offset = self.buffer.len();
+        Ok(None)
+    }
+
+    /// Appends the bytes left once the whole file is fetched, i.e. its last line when it lacks a
+    /// line feed.
+    fn read_remaining(&mut self, line: &mut String) -> anyhow::Result<usize> {
+        self.consume(self.buffer.len(), line)
+    }
+
+    /// Appends a chunk fetched from the file, dropping the bytes already read.
+    fn extend(&mut self, chunk: &[u8]) {
+        self.buffer.drain(..self.read_offset);
+        self.scan_offset -= self.read_offset;
+        self.read_offset = 0;
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    fn consume(&mut self, line_end: usize, line: &mut String) -> anyhow::Result<usize> {
+        let line_bytes = &self.buffer[self.read_offset..line_end];
+        line.push_str(std::str::from_utf8(line_bytes).context("Line is invalid utf-8.")?);
+        self.read_offset = line_end;
+        self.scan_offset = line_end;
+        Ok(line_bytes.len())
+    }
+}
+
+/// Reads the lines of an object of a storage, fetching the object chunk by chunk rather than
+/// downloading it beforehand.
+struct StorageObjectReader {
+    storage: Arc<dyn Storage>,
+    path: PathBuf,
+    num_bytes: usize,
+    chunk_num_bytes: usize,
+    /// Offset in the object of the next chunk to fetch.
+    fetch_offset: usize,
+    line_buffer: LineBuffer,
+}
+
+impl StorageObjectReader {
+    async fn open(uri: &str, offset: usize) -> anyhow::Result<Self> {
+        let (storage, path) = resolve_storage_object(uri)?;
+        let num_bytes = storage
+            .file_num_bytes(&path)
+            .await
+            .with_context(|| format!("Failed to open source file `{}`.", uri))?
+            as usize;
+        Ok(StorageObjectReader {
+            storage,
+            path,
+            num_bytes,
+            chunk_num_bytes: STORAGE_CHUNK_NUM_BYTES,
+            fetch_offset: offset.min(num_bytes),
+            line_buffer: LineBuffer::default(),
+        })
+    }
+
+    async fn read_line(&mut self, line: &mut String) -> anyhow::Result<usize> {
+        loop {
+            if let Some(num_bytes) = self.line_buffer.read_line(line)? {
+                return Ok(num_bytes);
+            }
+            match self.read_chunk().await? {
+                Some(chunk) => self.line_buffer.extend(&chunk),
+                None => return self.line_buffer.read_remaining(line),
+            }
+        }
+    }
+
+    /// Fetches the next chunk of the object, or returns `None` once the whole object is fetched.
+    async fn read_chunk(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
+        if self.fetch_offset == self.num_bytes {
+            return Ok(None);
+        }
+        let fetch_end = (self.fetch_offset + self.chunk_num_bytes).min(self.num_bytes);
+        let chunk = self
+            .storage
+            .get_slice(&self.path, self.fetch_offset..fetch_end)
+            .await?;
+        self.fetch_offset = fetch_end;
+        Ok(Some(chunk.as_slice().to_vec()))
+    }
+}
+
+/// Reads the lines of a file downloaded over HTTP(S), streaming the response body rather than
+/// downloading the file beforehand.
+struct HttpFileReader {
+    /// Response streaming the file, or `None` once the whole file is received.
+    response_opt: Option<reqwest::Response>,
+    /// Number of bytes of the response to drop, when the server ignored the range requested.
+    num_bytes_to_skip: usize,
+    line_buffer: LineBuffer,
+}
+
+impl HttpFileReader {
+    /// Starts downloading the file at `url` from `offset`, with a range request.
+    async fn open(
+        url: &str,
+        headers: &BTreeMap<String, String>,
+        offset: u64,
+    ) -> anyhow::Result<Self> {
+        let mut request = reqwest::Client::new().get(url);
+        for (header_name, header_value) in headers {
+            request = request.header(header_name.as_str(), header_value.as_str());
+        }
+        if offset > 0 {
+            request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to download source file `{}`.", url))?;
+        let status = response.status();
+        if offset > 0 && status == StatusCode::RANGE_NOT_SATISFIABLE {
+            // The offset is the end of the file, which was read entirely.
+            return Ok(HttpFileReader {
+                response_opt: None,
+                num_bytes_to_skip: 0,
+                line_buffer: LineBuffer::default(),
+            });
+        }
+        if !status.is_success() {
+            bail!("Failed to download source file `{}`: {}.", url, status);
+        }
+        let num_bytes_to_skip = if status == StatusCode::PARTIAL_CONTENT {
+            0
+        } else {
+            offset as usize
+        };
+        Ok(HttpFileReader {
+            response_opt: Some(response),
+            num_bytes_to_skip,
+            line_buffer: LineBuffer::default(),
+        })
+    }
+
+    async fn read_line(&mut self, line: &mut String) -> anyhow::Result<usize> {
+        loop {
+            if let Some(num_bytes) = self.line_buffer.read_line(line)? {
+                return Ok(num_bytes);
+            }
+            match self.read_chunk().await? {
+                Some(chunk) => self.line_buffer.extend(&chunk),
+                None => return self.line_buffer.read_remaining(line),
+            }
+        }
+    }
+
+    /// Receives the next chunk of the file, or returns `None` once the whole file is received.
+    async fn read_chunk(&mut self) -> anyhow::Result<Option<Vec<u8>>> {
+        while let Some(response) = &mut self.response_opt {
+            match response.chunk().await? {
+                Some(chunk) => {
+                    let num_skipped_bytes = self.num_bytes_to_skip.min(chunk.len());
+                    self.num_bytes_to_skip -= num_skipped_bytes;
+                    if num_skipped_bytes < chunk.len() {
+                        return Ok(Some(chunk[num_skipped_bytes..].to_vec()));
+                    }
+                }
+                None => self.response_opt = None,
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Checks that the file at `url` can be downloaded, without downloading it entirely.
+pub(crate) async fn check_http_file(
+    url: &str,
+    headers: &BTreeMap<String, String>,
+) -> anyhow::Result<()> {
+    HttpFileReader::open(url, headers, 0).await?;
+    Ok(())
+}
+
+/// Resolves the storage holding the object of a URI such as `s3://bucket/key`, and the path of
+/// the object in that storage.
+pub(crate) fn resolve_storage_object(uri: &str) -> anyhow::Result<(Arc<dyn Storage>, PathBuf)> {
+    let (storage_uri, object_name) = uri
+        .rsplit_once('/')
+        .with_context(|| format!("Failed to parse storage URI `{}`.", uri))?;
+    let storage = quickwit_storage_uri_resolver().resolve(storage_uri)?;
+    Ok((storage, PathBuf::from(object_name)))
 }
 
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
@@ -62,37 +454,59 @@ impl Source for FileSource {
         ctx: &SourceContext,
     ) -> Result<(), ActorExitStatus> {
         // We collect batches of documents before sending them to the indexer.
-        let limit_num_bytes = self.counters.previous_offset + BATCH_NUM_BYTES_THRESHOLD;
+        let limit_num_bytes = self.counters.previous_offset
+            + backpressure::target_batch_num_bytes(batch_sink, BATCH_NUM_BYTES_THRESHOLD);
+        let num_lines_processed = self.counters.num_lines_processed;
+        // The end of the input is final unless the file is followed and was not rotated.
+        let is_eof_final = self
+            .followed_file_opt
+            .as_ref()
+            .map(|followed_file| followed_file.is_rotated)
+            .unwrap_or(true);
         let mut reached_eof = false;
         let mut docs = Vec::new();
         while self.counters.current_offset < limit_num_bytes {
-            let mut doc_line = String::new();
-            let num_bytes = self
-                .reader
-                .read_line(&mut doc_line)
-                .await
-                .map_err(|io_err: io::Error| anyhow::anyhow!(io_err))?;
-            if num_bytes == 0 {
+            let num_bytes = self.read_record().await?;
+            if num_bytes == 0 || (!is_eof_final && !self.line_buffer.ends_with('\n')) {
                 reached_eof = true;
                 break;
             }
-            docs.push(doc_line);
-            self.counters.current_offset += num_bytes as u64;
-            self.counters.num_lines_processed += 1;
+            let doc_line = std::mem::take(&mut self.line_buffer);
+            let num_bytes = if self.record_splitter_opt.is_some() {
+                num_bytes
+            } else {
+                // In follow mode, the beginning of the line may have been read by a previous call.
+                doc_line.len()
+            };
+            self.process_line(doc_line, num_bytes, &mut docs);
+        }
+        if reached_eof && is_eof_final && !self.line_buffer.is_empty() {
+            // The last line of a rotated file may lack its line feed.
+            let doc_line = std::mem::take(&mut self.line_buffer);
+            let num_bytes = doc_line.len();
+            self.process_line(doc_line, num_bytes, &mut docs);
+        }
+        if let Some(multiline_assembler) = &mut self.multiline_assembler_opt {
+            let record_opt = if reached_eof && is_eof_final {
+                multiline_assembler.flush()
+            } else if self.followed_file_opt.is_some() {
+                // Lines may still be appended to the pending record.
+                multiline_assembler.flush_expired(Instant::now())
+            } else {
+                None
+            };
+            docs.extend(record_opt.map(|record| message_doc(&record.text)));
         }
         if !docs.is_empty() {
+            // The pending multiline record is read again after a restart.
+            let checkpoint_offset = self.counters.current_offset - self.pending_num_bytes();
             let mut checkpoint_delta = CheckpointDelta::default();
-            if let Some(filepath) = &self.params.filepath {
-                let filepath_str = filepath
-                    .to_str()
-                    .context("Path is invalid utf-8")?
-                    .to_string();
-                let partition_id = PartitionId::from(filepath_str);
+            if let Some(partition_id) = &self.partition_id_opt {
                 checkpoint_delta
                     .record_partition_delta(
-                        partition_id,
+                        partition_id.clone(),
                         Position::from(self.counters.previous_offset),
-                        Position::from(self.counters.current_offset),
+                        Position::from(checkpoint_offset),
                     )
                     .unwrap();
             }
@@ -100,10 +514,24 @@ impl Source for FileSource {
                 docs,
                 checkpoint_delta,
             };
-            self.counters.previous_offset = self.counters.current_offset;
+            self.counters.previous_offset = checkpoint_offset;
             ctx.send_message(batch_sink, raw_doc_batch.into()).await?;
         }
         if reached_eof {
+            if let Some(followed_file) = &mut self.followed_file_opt {
+                if followed_file.is_rotated {
+                    let _protect_guard = ctx.protect_zone();
+                    self.follow_rotated_file().await?;
+                    return Ok(());
+                }
+                followed_file.is_rotated = followed_file.check_rotated().await;
+                if !followed_file.is_rotated
+                    && self.counters.num_lines_processed == num_lines_processed
+                {
+                    ctx.sleep(FOLLOW_POLL_INTERVAL).await;
+                }
+                return Ok(());
+            }
             info!("EOF");
             ctx.send_exit_with_success(batch_sink).await?;
             return Err(ActorExitStatus::Success);
@@ -127,48 +555,113 @@ impl TypedSourceFactory for FileSourceFactory {
     type Source = FileSource;
     type Params = FileSourceParams;
 
-    // TODO handle checkpoint for files.
     async fn typed_create_source(
         params: FileSourceParams,
-        checkpoint: quickwit_metastore::checkpoint::SourceCheckpoint,
+        checkpoint: SourceCheckpoint,
     ) -> anyhow::Result<FileSource> {
         let mut offset = 0;
-        let reader: Box<dyn AsyncRead + Send + Sync + Unpin> =
-            if let Some(filepath) = &params.filepath {
-                let mut file = File::open(&filepath).await.with_context(|| {
-                    format!("Failed to open source file `{}`.", filepath.display())
-                })?;
-                let partition_id = PartitionId::from(filepath.to_string_lossy().to_string());
-                if let Some(Position::Offset(offset_str)) =
-                    checkpoint.position_for_partition(&partition_id).cloned()
-                {
-                    offset = offset_str.parse::<u64>()?;
-                    file.seek(SeekFrom::Start(offset)).await?;
-                }
-                Box::new(file)
+        let mut partition_id_opt = None;
+        let mut followed_file_opt = None;
+        let reader = if let Some(url) = params.http_url() {
+            let partition_id = PartitionId::from(url.to_string());
+            offset = checkpoint_offset(&checkpoint, &partition_id)?;
+            partition_id_opt = Some(partition_id);
+            DocReader::Http(HttpFileReader::open(url, &params.http_headers, offset).await?)
+        } else if let Some(storage_uri) = params.storage_uri() {
+            let partition_id = PartitionId::from(storage_uri.to_string());
+            offset = checkpoint_offset(&checkpoint, &partition_id)?;
+            partition_id_opt = Some(partition_id);
+            DocReader::Storage(StorageObjectReader::open(storage_uri, offset as usize).await?)
+        } else if let Some(filepath) = &params.filepath {
+            let mut file = File::open(&filepath)
+                .await
+                .with_context(|| format!("Failed to open source file `{}`.", filepath.display()))?;
+            let partition_id = if params.follow {
+                let file_id = file_id(&file.metadata().await?);
+                followed_file_opt = Some(FollowedFile {
+                    filepath: filepath.clone(),
+                    file_id,
+                    is_rotated: false,
+                });
+                followed_partition_id(filepath, file_id)
             } else {
-                // We cannot use the checkpoint.
-                Box::new(tokio::io::stdin())
+                PartitionId::from(filepath.to_string_lossy().to_string())
             };
+            offset = checkpoint_offset(&checkpoint, &partition_id)?;
+            partition_id_opt = Some(partition_id);
+            file.seek(SeekFrom::Start(offset)).await?;
+            DocReader::local(file)
+        } else {
+            // We cannot use the checkpoint.
+            DocReader::local(tokio::io::stdin())
+        };
+        let multiline_assembler_opt = params
+            .multiline
+            .as_ref()
+            .map(MultilineAssembler::try_new)
+            .transpose()?;
+        let record_splitter_opt = RecordSplitter::new(params.framing, offset);
+        let mut row_decoder_opt = RowDecoder::new(params.format, params.columns.as_deref());
+        if let Some(row_decoder) = &mut row_decoder_opt {
+            if row_decoder.reads_header() && offset > 0 {
+                // The header line was read before the checkpoint.
+                let header_line = read_header_line(&params).await?;
+                row_decoder.decode(&header_line);
+            }
+        }
         let file_source = FileSource {
             counters: FileSourceCounters {
                 previous_offset: offset,
                 current_offset: offset,
                 num_lines_processed: 0,
             },
-            reader: BufReader::new(reader),
-            params,
+            reader,
+            partition_id_opt,
+            multiline_assembler_opt,
+            row_decoder_opt,
+            record_splitter_opt,
+            line_buffer: String::new(),
+            followed_file_opt,
         };
         Ok(file_source)
     }
 }
 
+/// Reads the first line of the file, holding the names of the columns of CSV or TSV rows.
+async fn read_header_line(params: &FileSourceParams) -> anyhow::Result<String> {
+    let mut reader = if let Some(url) = params.http_url() {
+        DocReader::Http(HttpFileReader::open(url, &params.http_headers, 0).await?)
+    } else if let Some(storage_uri) = params.storage_uri() {
+        DocReader::Storage(StorageObjectReader::open(storage_uri, 0).await?)
+    } else if let Some(filepath) = &params.filepath {
+        let file = File::open(&filepath)
+            .await
+            .with_context(|| format!("Failed to open source file `{}`.", filepath.display()))?;
+        DocReader::local(file)
+    } else {
+        bail!("The header line of the standard input cannot be read again.");
+    };
+    let mut header_line = String::new();
+    reader.read_line(&mut header_line).await?;
+    Ok(header_line)
+}
+
+/// Returns the offset of the partition in the checkpoint, or 0 if the partition is not in it.
+fn checkpoint_offset(
+    checkpoint: &SourceCheckpoint,
+    partition_id: &PartitionId,
+) -> anyhow::Result<u64> {
+    match checkpoint.position_for_partition(partition_id) {
+        Some(Position::Offset(offset_str)) => Ok(offset_str.parse::<u64>()?),
+        _ => Ok(0),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Write;
 
     use quickwit_actors::{create_test_mailbox, Command, CommandOrMessage, Universe};
-    use quickwit_metastore::checkpoint::SourceCheckpoint;
 
     use super::*;
     use crate::source::SourceActor;
@@ -181,10 +674,7 @@ mod tests {
         let params = FileSourceParams::file("data/test_corpus.json");
         let file_source =
             FileSourceFactory::typed_create_source(params, SourceCheckpoint::default()).await?;
-        let file_source_actor = SourceActor {
-            source: Box::new(file_source),
-            batch_sink: mailbox,
-        };
+        let file_source_actor = SourceActor::new(Box::new(file_source), mailbox);
         let (_file_source_mailbox, file_source_handle) =
             universe.spawn_actor(file_source_actor).spawn_async();
         let (actor_termination, counters) = file_source_handle.join().await;
@@ -228,10 +718,7 @@ mod tests {
             .to_string();
         let source =
             FileSourceFactory::typed_create_source(params, SourceCheckpoint::default()).await?;
-        let file_source_actor = SourceActor {
-            source: Box::new(source),
-            batch_sink: mailbox,
-        };
+        let file_source_actor = SourceActor::new(Box::new(source), mailbox);
         let (_file_source_mailbox, file_source_handle) =
             universe.spawn_actor(file_source_actor).spawn_async();
         let (actor_termination, counters) = file_source_handle.join().await;
@@ -311,10 +798,7 @@ mod tests {
         );
         checkpoint.try_apply_delta(checkpoint_delta)?;
         let source = FileSourceFactory::typed_create_source(params, checkpoint).await?;
-        let file_source_actor = SourceActor {
-            source: Box::new(source),
-            batch_sink: mailbox,
-        };
+        let file_source_actor = SourceActor::new(Box::new(source), mailbox);
         let (_file_source_mailbox, file_source_handle) =
             universe.spawn_actor(file_source_actor).spawn_async();
         let (actor_termination, counters) = file_source_handle.join().await;
@@ -333,4 +817,364 @@ mod tests {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_file_source_multiline() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let universe = Universe::new();
+        let (mailbox, inbox) = create_test_mailbox();
+        let mut temp_file = tempfile::NamedTempFile::new()?;
+        temp_file.write_all(
+            b"2022-02-22 ERROR Failed to connect.\n\
+              java.net.ConnectException: Connection refused\n\
+              \tat java.net.PlainSocketImpl.socketConnect(Native Method)\n\
+              2022-02-22 INFO Retrying.\n",
+        )?;
+        temp_file.flush()?;
+        let params: FileSourceParams = serde_json::from_value(serde_json::json!({
+            "filepath": temp_file.path(),
+            "multiline": {"start_pattern": r"\d{4}-\d{2}-\d{2}"},
+        }))?;
+        let source =
+            FileSourceFactory::typed_create_source(params, SourceCheckpoint::default()).await?;
+        let file_source_actor = SourceActor::new(Box::new(source), mailbox);
+        let (_file_source_mailbox, file_source_handle) =
+            universe.spawn_actor(file_source_actor).spawn_async();
+        let (actor_termination, counters) = file_source_handle.join().await;
+        assert!(actor_termination.is_success());
+        assert_eq!(
+            counters,
+            serde_json::json!({
+                "previous_offset": 166u64,
+                "current_offset": 166u64,
+                "num_lines_processed": 4
+            })
+        );
+        let indexer_msgs = inbox.drain_available_message_for_test();
+        let batch =
+            extract_batch_from_indexer_message(indexer_msgs.into_iter().next().unwrap()).unwrap();
+        assert_eq!(
+            batch.docs,
+            vec![
+                serde_json::json!({
+                    "message": "2022-02-22 ERROR Failed to connect.\njava.net.ConnectException: \
+                                Connection refused\n\tat \
+                                java.net.PlainSocketImpl.socketConnect(Native Method)"
+                })
+                .to_string(),
+                serde_json::json!({"message": "2022-02-22 INFO Retrying."}).to_string(),
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_file_source_json_array() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let mut temp_file = tempfile::NamedTempFile::new()?;
+        temp_file.write_all(b"[\n  {\"body\": \"a\"},\n  {\"body\": \"b\"}\n]\n")?;
+        temp_file.flush()?;
+        let temp_file_path = temp_file.path().canonicalize()?;
+        let params: FileSourceParams = serde_json::from_value(serde_json::json!({
+            "filepath": temp_file_path,
+            "framing": "json_array",
+        }))?;
+        let partition_id = PartitionId::from(temp_file_path.to_string_lossy().to_string());
+        let mut checkpoint = SourceCheckpoint::default();
+        checkpoint.try_apply_delta(CheckpointDelta::from_partition_delta(
+            partition_id,
+            Position::from(0u64),
+            Position::from(17u64),
+        ))?;
+        let expected_docs = [r#"{"body": "a"}"#, r#"{"body": "b"}"#];
+        for (checkpoint, expected_docs) in [
+            (SourceCheckpoint::default(), &expected_docs[..]),
+            (checkpoint, &expected_docs[1..]),
+        ] {
+            let universe = Universe::new();
+            let (mailbox, inbox) = create_test_mailbox();
+            let source = FileSourceFactory::typed_create_source(params.clone(), checkpoint).await?;
+            let file_source_actor = SourceActor::new(Box::new(source), mailbox);
+            let (_file_source_mailbox, file_source_handle) =
+                universe.spawn_actor(file_source_actor).spawn_async();
+            let (actor_termination, counters) = file_source_handle.join().await;
+            assert!(actor_termination.is_success());
+            assert_eq!(counters["current_offset"], 37);
+            let indexer_msgs = inbox.drain_available_message_for_test();
+            let batch =
+                extract_batch_from_indexer_message(indexer_msgs.into_iter().next().unwrap())
+                    .unwrap();
+            assert_eq!(batch.docs, expected_docs);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_file_source_csv() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let mut temp_file = tempfile::NamedTempFile::new()?;
+        temp_file.write_all(b"vendor,city\nacme,paris\nfoo,berlin\n")?;
+        temp_file.flush()?;
+        let temp_file_path = temp_file.path().canonicalize()?;
+        let params: FileSourceParams = serde_json::from_value(serde_json::json!({
+            "filepath": temp_file_path,
+            "format": "csv",
+        }))?;
+        // The second run resumes after the first row, past the header line.
+        let partition_id = PartitionId::from(temp_file_path.to_string_lossy().to_string());
+        let mut checkpoint = SourceCheckpoint::default();
+        checkpoint.try_apply_delta(CheckpointDelta::from_partition_delta(
+            partition_id,
+            Position::from(0u64),
+            Position::from(23u64),
+        ))?;
+        let expected_docs = [
+            serde_json::json!({"vendor": "acme", "city": "paris"}).to_string(),
+            serde_json::json!({"vendor": "foo", "city": "berlin"}).to_string(),
+        ];
+        for (checkpoint, expected_docs) in [
+            (SourceCheckpoint::default(), &expected_docs[..]),
+            (checkpoint, &expected_docs[1..]),
+        ] {
+            let universe = Universe::new();
+            let (mailbox, inbox) = create_test_mailbox();
+            let source = FileSourceFactory::typed_create_source(params.clone(), checkpoint).await?;
+            let file_source_actor = SourceActor::new(Box::new(source), mailbox);
+            let (_file_source_mailbox, file_source_handle) =
+                universe.spawn_actor(file_source_actor).spawn_async();
+            let (actor_termination, _counters) = file_source_handle.join().await;
+            assert!(actor_termination.is_success());
+            let indexer_msgs = inbox.drain_available_message_for_test();
+            let batch =
+                extract_batch_from_indexer_message(indexer_msgs.into_iter().next().unwrap())
+                    .unwrap();
+            assert_eq!(batch.docs, expected_docs);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_file_source_follow() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let universe = Universe::new();
+        let (mailbox, inbox) = create_test_mailbox();
+        let dir = tempfile::tempdir()?;
+        let filepath = dir.path().join("app.log");
+        let rotated_filepath = dir.path().join("app.log.1");
+        let append = |path: &Path, content: &str| {
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .unwrap();
+            file.write_all(content.as_bytes()).unwrap();
+        };
+        // The last line is still being written.
+        append(&filepath, "a0\na1");
+        let mut params = FileSourceParams::file(&filepath);
+        params.follow = true;
+        let source =
+            FileSourceFactory::typed_create_source(params, SourceCheckpoint::default()).await?;
+        let file_source_actor = SourceActor::new(Box::new(source), mailbox);
+        let (_file_source_mailbox, file_source_handle) =
+            universe.spawn_actor(file_source_actor).spawn_async();
+        tokio::time::sleep(quickwit_actors::HEARTBEAT).await;
+        let observation = file_source_handle.process_pending_and_observe().await.state;
+        assert_eq!(observation["num_lines_processed"], 1);
+        assert_eq!(observation["current_offset"], 3);
+
+        append(&filepath, "\na2\n");
+        tokio::time::sleep(quickwit_actors::HEARTBEAT).await;
+        let observation = file_source_handle.process_pending_and_observe().await.state;
+        assert_eq!(observation["num_lines_processed"], 3);
+        assert_eq!(observation["current_offset"], 9);
+
+        // The lines written to the rotated file before the new file is created are read too.
+        let partition_id =
+            followed_partition_id(&filepath, file_id(&std::fs::metadata(&filepath)?));
+        std::fs::rename(&filepath, &rotated_filepath)?;
+        append(&rotated_filepath, "a3\n");
+        append(&filepath, "b0\n");
+        let new_partition_id =
+            followed_partition_id(&filepath, file_id(&std::fs::metadata(&filepath)?));
+        tokio::time::sleep(2 * quickwit_actors::HEARTBEAT).await;
+        let observation = file_source_handle.process_pending_and_observe().await.state;
+        assert_eq!(observation["num_lines_processed"], 5);
+        assert_eq!(observation["current_offset"], 3);
+        file_source_handle.kill().await;
+
+        let batches: Vec<RawDocBatch> = inbox
+            .drain_available_message_for_test()
+            .into_iter()
+            .flat_map(extract_batch_from_indexer_message)
+            .collect();
+        let docs: Vec<&str> = batches
+            .iter()
+            .flat_map(|batch| batch.docs.iter().map(String::as_str))
+            .collect();
+        assert_eq!(docs, vec!["a0\n", "a1\n", "a2\n", "a3\n", "b0\n"]);
+        assert_eq!(
+            format!("{:?}", batches[batches.len() - 2].checkpoint_delta),
+            format!(
+                "∆({}:(00000000000000000009..00000000000000000012])",
+                partition_id.0
+            )
+        );
+        assert_eq!(
+            format!("{:?}", batches[batches.len() - 1].checkpoint_delta),
+            format!(
+                "∆({}:(00000000000000000000..00000000000000000003])",
+                new_partition_id.0
+            )
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_storage_object_reader() -> anyhow::Result<()> {
+        let storage = quickwit_storage_uri_resolver().resolve("ram:///test-object-reader")?;
+        storage
+            .put(
+                Path::new("docs.json"),
+                Box::new(b"first line\nsecond line\n\nlast line".to_vec()),
+            )
+            .await?;
+        let mut reader =
+            StorageObjectReader::open("ram:///test-object-reader/docs.json", 0).await?;
+        // Lines span several chunks.
+        reader.chunk_num_bytes = 4;
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await? == 0 {
+                break;
+            }
+            lines.push(line);
+        }
+        assert_eq!(
+            lines,
+            vec!["first line\n", "second line\n", "\n", "last line"]
+        );
+        let mut reader =
+            StorageObjectReader::open("ram:///test-object-reader/docs.json", 11).await?;
+        let mut line = String::new();
+        assert_eq!(reader.read_line(&mut line).await?, 12);
+        assert_eq!(line, "second line\n");
+        Ok(())
+    }
+
+    const HTTP_FILE_CONTENT: &str = "first line\nsecond line\nlast line";
+
+    /// Serves `HTTP_FILE_CONTENT`, honoring range requests on `/ranged/docs.json` only.
+    fn spawn_http_file_server() -> std::net::SocketAddr {
+        use warp::http::Response;
+        use warp::Filter;
+
+        let routes = warp::path!(String / "docs.json")
+            .and(warp::header::<String>("authorization"))
+            .and(warp::header::optional::<String>("range"))
+            .map(
+                |route: String, authorization: String, range_opt: Option<String>| {
+                    assert_eq!(authorization, "Bearer my-token");
+                    let start_opt = range_opt.filter(|_| route == "ranged").and_then(|range| {
+                        range
+                            .strip_prefix("bytes=")?
+                            .strip_suffix('-')?
+                            .parse::<usize>()
+                            .ok()
+                    });
+                    match start_opt {
+                        Some(start) if start >= HTTP_FILE_CONTENT.len() => {
+                            Response::builder().status(416).body(Vec::new())
+                        }
+                        Some(start) => Response::builder()
+                            .status(206)
+                            .body(HTTP_FILE_CONTENT[start..].as_bytes().to_vec()),
+                        None => Response::builder().body(HTTP_FILE_CONTENT.as_bytes().to_vec()),
+                    }
+                },
+            );
+        let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        addr
+    }
+
+    async fn read_http_file(url: &str, offset: u64) -> anyhow::Result<Vec<String>> {
+        let headers =
+            BTreeMap::from([("Authorization".to_string(), "Bearer my-token".to_string())]);
+        let mut reader = HttpFileReader::open(url, &headers, offset).await?;
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await? == 0 {
+                return Ok(lines);
+            }
+            lines.push(line);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_file_reader() -> anyhow::Result<()> {
+        let addr = spawn_http_file_server();
+        for route in ["ranged", "unranged"] {
+            let url = format!("http://{}/{}/docs.json", addr, route);
+            assert_eq!(
+                read_http_file(&url, 0).await?,
+                vec!["first line\n", "second line\n", "last line"]
+            );
+            assert_eq!(
+                read_http_file(&url, 11).await?,
+                vec!["second line\n", "last line"]
+            );
+            assert!(read_http_file(&url, 32).await?.is_empty());
+        }
+        // The server rejects the requests lacking the authorization header.
+        let url = format!("http://{}/ranged/docs.json", addr);
+        assert!(HttpFileReader::open(&url, &BTreeMap::new(), 0)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_file_source_from_storage() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let universe = Universe::new();
+        let (mailbox, inbox) = create_test_mailbox();
+        let storage = quickwit_storage_uri_resolver().resolve("ram:///test-file-source")?;
+        let docs: String = (0..100).map(|i| format!("{}\n", i)).collect();
+        storage
+            .put(Path::new("docs.json"), Box::new(docs.into_bytes()))
+            .await?;
+        let params: FileSourceParams =
+            serde_json::from_str(r#"{"filepath": "ram:///test-file-source/docs.json"}"#)?;
+        let mut checkpoint = SourceCheckpoint::default();
+        let partition_id = PartitionId::from("ram:///test-file-source/docs.json");
+        let checkpoint_delta = CheckpointDelta::from_partition_delta(
+            partition_id,
+            Position::from(0u64),
+            Position::from(4u64),
+        );
+        checkpoint.try_apply_delta(checkpoint_delta)?;
+        let source = FileSourceFactory::typed_create_source(params, checkpoint).await?;
+        let file_source_actor = SourceActor::new(Box::new(source), mailbox);
+        let (_file_source_mailbox, file_source_handle) =
+            universe.spawn_actor(file_source_actor).spawn_async();
+        let (actor_termination, counters) = file_source_handle.join().await;
+        assert!(actor_termination.is_success());
+        assert_eq!(
+            counters,
+            serde_json::json!({
+                "previous_offset": 290u64,
+                "current_offset": 290u64,
+                "num_lines_processed": 98
+            })
+        );
+        let indexer_msgs = inbox.drain_available_message_for_test();
+        assert!(matches!(
+            &indexer_msgs[0],
+            IndexerMessage::Batch(raw_batch) if raw_batch.docs[0] == "2\n"
+        ));
+        Ok(())
+    }
 }
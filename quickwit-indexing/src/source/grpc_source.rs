@@ -0,0 +1,540 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use quickwit_actors::{ActorExitStatus, Mailbox};
+use quickwit_config::GrpcSourceParams;
+use quickwit_metastore::checkpoint::{CheckpointDelta, PartitionId, Position, SourceCheckpoint};
+use quickwit_proto::source_plugin_service_client::SourcePluginServiceClient;
+use quickwit_proto::{
+    AckRequest, CheckpointRequest, EmitBatchesRequest, EmitBatchesResponse, PartitionPosition,
+};
+use serde_json::json;
+use tonic::transport::{Channel, Endpoint};
+use tracing::{info, warn};
+
+use crate::models::RawDocBatch;
+use crate::source::{IndexerMessage, Source, SourceContext, TypedSourceFactory};
+
+/// Factory for instantiating a `GrpcSource`.
+pub struct GrpcSourceFactory;
+
+#[async_trait]
+impl TypedSourceFactory for GrpcSourceFactory {
+    type Source = GrpcSource;
+    type Params = GrpcSourceParams;
+
+    async fn typed_create_source(
+        params: GrpcSourceParams,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<Self::Source> {
+        GrpcSource::try_new(params, checkpoint).await
+    }
+}
+
+#[derive(Default)]
+pub struct GrpcSourceState {
+    /// Positions of the partitions after the last batch emitted, or checkpointed positions if
+    /// the plugin has not emitted any batch for the partition yet.
+    pub current_positions: HashMap<PartitionId, Position>,
+    /// Number of documents processed by the source.
+    pub num_docs_processed: u64,
+    /// Number of non-empty batches returned by the plugin.
+    pub num_batches_processed: u64,
+}
+
+impl GrpcSourceState {
+    /// Checks that the partition deltas of the batch chain with the current positions, and
+    /// converts them into a checkpoint delta.
+    ///
+    /// The deltas come from a process that Quickwit does not control: they are validated before
+    /// being recorded, so that a faulty plugin fails the pipeline instead of corrupting the
+    /// checkpoint of the source.
+    fn record_batch(
+        &mut self,
+        emit_batches_response: &EmitBatchesResponse,
+    ) -> anyhow::Result<CheckpointDelta> {
+        if !emit_batches_response.docs.is_empty()
+            && emit_batches_response.partition_deltas.is_empty()
+        {
+            bail!("Source plugin returned documents without any partition delta.");
+        }
+        let mut checkpoint_delta = CheckpointDelta::default();
+        let mut new_positions = HashMap::new();
+
+        for partition_delta in &emit_batches_response.partition_deltas {
+            let partition_id = PartitionId::from(partition_delta.partition_id.as_str());
+            let from_position = Position::from(partition_delta.from_position.as_str());
+            let to_position = Position::from(partition_delta.to_position.as_str());
+
+            if to_position <= from_position {
+                bail!(
+                    "Source plugin returned an empty or backward delta `({}, {}]` for partition \
+                     `{}`.",
+                    partition_delta.from_position,
+                    partition_delta.to_position,
+                    partition_delta.partition_id
+                );
+            }
+            let current_position = new_positions
+                .get(&partition_id)
+                .or_else(|| self.current_positions.get(&partition_id))
+                .unwrap_or(&Position::Beginning);
+            if from_position != *current_position {
+                bail!(
+                    "Source plugin returned a delta starting at `{}` for partition `{}`, which is \
+                     at `{}`.",
+                    partition_delta.from_position,
+                    partition_delta.partition_id,
+                    current_position.as_str()
+                );
+            }
+            checkpoint_delta
+                .record_partition_delta(partition_id.clone(), from_position, to_position.clone())
+                .context("Failed to record partition delta.")?;
+            new_positions.insert(partition_id, to_position);
+        }
+        self.current_positions.extend(new_positions);
+
+        if !checkpoint_delta.is_empty() {
+            self.num_docs_processed += emit_batches_response.docs.len() as u64;
+            self.num_batches_processed += 1;
+        }
+        Ok(checkpoint_delta)
+    }
+}
+
+/// A `GrpcSource` proxies to a source plugin, i.e. a sidecar process implementing the
+/// `SourcePluginService` gRPC service, which lets users write sources in any language.
+///
+/// When the source starts, it sends the checkpoint of the source to the plugin, and then polls
+/// it for batches of documents. The plugin tracks its own positions, which it reports with each
+/// batch and which Quickwit records in the checkpoint. Once a split is published, the source
+/// acknowledges its positions to the plugin.
+pub struct GrpcSource {
+    endpoint: String,
+    client: SourcePluginServiceClient<Channel>,
+    batch_num_docs: u64,
+    state: GrpcSourceState,
+}
+
+impl fmt::Debug for GrpcSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "GrpcSource {{ endpoint:{} }}", self.endpoint)
+    }
+}
+
+impl GrpcSource {
+    /// Instantiates a new `GrpcSource`, positioning the plugin at `checkpoint`.
+    pub async fn try_new(
+        params: GrpcSourceParams,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<GrpcSource> {
+        let mut client = connect(&params.endpoint).await?;
+        let params_json = match &params.params {
+            Some(plugin_params) => serde_json::to_string(plugin_params)?,
+            None => String::new(),
+        };
+        let checkpoint_request = CheckpointRequest {
+            params_json,
+            positions: partition_positions(&checkpoint),
+        };
+        info!(
+            endpoint = %params.endpoint,
+            checkpoint = ?checkpoint,
+            "Starting gRPC source."
+        );
+        client
+            .checkpoint(checkpoint_request)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to send checkpoint to source plugin at `{}`.",
+                    params.endpoint
+                )
+            })?;
+        let state = GrpcSourceState {
+            current_positions: checkpoint.iter().collect(),
+            ..Default::default()
+        };
+        Ok(GrpcSource {
+            endpoint: params.endpoint,
+            client,
+            batch_num_docs: params.batch_num_docs,
+            state,
+        })
+    }
+}
+
+#[async_trait]
+impl Source for GrpcSource {
+    async fn emit_batches(
+        &mut self,
+        batch_sink: &Mailbox<IndexerMessage>,
+        ctx: &SourceContext,
+    ) -> Result<(), ActorExitStatus> {
+        let emit_batches_request = EmitBatchesRequest {
+            max_num_docs: self.batch_num_docs,
+        };
+        let emit_batches_response = {
+            let _protect_guard = ctx.protect_zone();
+            self.client
+                .emit_batches(emit_batches_request)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to fetch batch from source plugin at `{}`.",
+                        self.endpoint
+                    )
+                })?
+                .into_inner()
+        };
+        let checkpoint_delta = self.state.record_batch(&emit_batches_response)?;
+
+        if !checkpoint_delta.is_empty() {
+            let batch = RawDocBatch {
+                docs: emit_batches_response.docs,
+                checkpoint_delta,
+            };
+            ctx.send_message(batch_sink, IndexerMessage::from(batch))
+                .await?;
+        } else if !emit_batches_response.end_of_stream {
+            // The plugin has no document available for now.
+            ctx.sleep(quickwit_actors::HEARTBEAT / 2).await;
+        }
+        if emit_batches_response.end_of_stream {
+            info!(endpoint = %self.endpoint, "Reached end of source plugin stream.");
+            ctx.send_exit_with_success(batch_sink).await?;
+            return Err(ActorExitStatus::Success);
+        }
+        Ok(())
+    }
+
+    async fn suggest_truncate(
+        &mut self,
+        checkpoint: SourceCheckpoint,
+        ctx: &SourceContext,
+    ) -> anyhow::Result<()> {
+        let ack_request = AckRequest {
+            positions: partition_positions(&checkpoint),
+        };
+        if ack_request.positions.is_empty() {
+            return Ok(());
+        }
+        let _protect_guard = ctx.protect_zone();
+        // The plugin receives the published positions again with the next published split, so a
+        // lost acknowledgement does not justify failing the pipeline.
+        if let Err(error) = self.client.ack(ack_request).await {
+            warn!(
+                endpoint = %self.endpoint,
+                error = %error,
+                "Failed to acknowledge positions to source plugin."
+            );
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        "GrpcSource".to_string()
+    }
+
+    fn observable_state(&self) -> serde_json::Value {
+        let current_positions: HashMap<&str, &str> = self
+            .state
+            .current_positions
+            .iter()
+            .map(|(partition_id, position)| (partition_id.0.as_str(), position.as_str()))
+            .collect();
+        json!({
+            "endpoint": self.endpoint,
+            "current_positions": current_positions,
+            "num_docs_processed": self.state.num_docs_processed,
+            "num_batches_processed": self.state.num_batches_processed,
+        })
+    }
+}
+
+/// Checks if the plugin is reachable at the endpoint of the parameters.
+pub(super) async fn check_connectivity(params: GrpcSourceParams) -> anyhow::Result<()> {
+    connect(&params.endpoint).await?;
+    Ok(())
+}
+
+async fn connect(endpoint: &str) -> anyhow::Result<SourcePluginServiceClient<Channel>> {
+    let channel = Endpoint::from_shared(endpoint.to_string())
+        .with_context(|| format!("Invalid source plugin endpoint `{}`.", endpoint))?
+        .connect()
+        .await
+        .with_context(|| format!("Failed to connect to source plugin at `{}`.", endpoint))?;
+    Ok(SourcePluginServiceClient::new(channel))
+}
+
+fn partition_positions(checkpoint: &SourceCheckpoint) -> Vec<PartitionPosition> {
+    checkpoint
+        .iter()
+        .map(|(partition_id, position)| PartitionPosition {
+            partition_id: partition_id.0.to_string(),
+            position: position.as_str().to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    use quickwit_actors::{create_test_mailbox, Command, CommandOrMessage, Universe};
+    use quickwit_proto::source_plugin_service_server::{
+        SourcePluginService, SourcePluginServiceServer,
+    };
+    use quickwit_proto::{AckResponse, CheckpointResponse, PartitionDelta};
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+    use tonic::{Request, Response, Status};
+
+    use super::*;
+    use crate::source::{SourceActor, SourceActorMessage};
+
+    #[derive(Clone, Default)]
+    struct MockSourcePlugin {
+        checkpoint_requests: Arc<Mutex<Vec<CheckpointRequest>>>,
+        batches: Arc<Mutex<VecDeque<EmitBatchesResponse>>>,
+        ack_requests: Arc<Mutex<Vec<AckRequest>>>,
+    }
+
+    #[async_trait]
+    impl SourcePluginService for MockSourcePlugin {
+        async fn checkpoint(
+            &self,
+            request: Request<CheckpointRequest>,
+        ) -> Result<Response<CheckpointResponse>, Status> {
+            self.checkpoint_requests
+                .lock()
+                .unwrap()
+                .push(request.into_inner());
+            Ok(Response::new(CheckpointResponse {}))
+        }
+
+        async fn emit_batches(
+            &self,
+            _request: Request<EmitBatchesRequest>,
+        ) -> Result<Response<EmitBatchesResponse>, Status> {
+            let emit_batches_response =
+                self.batches.lock().unwrap().pop_front().unwrap_or_default();
+            Ok(Response::new(emit_batches_response))
+        }
+
+        async fn ack(&self, request: Request<AckRequest>) -> Result<Response<AckResponse>, Status> {
+            self.ack_requests.lock().unwrap().push(request.into_inner());
+            Ok(Response::new(AckResponse {}))
+        }
+    }
+
+    async fn start_mock_source_plugin(plugin: MockSourcePlugin) -> anyhow::Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let endpoint = format!("http://{}", listener.local_addr()?);
+        tokio::spawn(
+            Server::builder()
+                .add_service(SourcePluginServiceServer::new(plugin))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        Ok(endpoint)
+    }
+
+    fn partition_delta(partition_id: &str, from: u64, to: u64) -> PartitionDelta {
+        PartitionDelta {
+            partition_id: partition_id.to_string(),
+            from_position: Position::from(from).as_str().to_string(),
+            to_position: Position::from(to).as_str().to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_grpc_source() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let plugin = MockSourcePlugin::default();
+        plugin.batches.lock().unwrap().extend([
+            EmitBatchesResponse {
+                docs: vec!["{\"body\": \"a\"}".to_string()],
+                partition_deltas: vec![partition_delta("partition-1", 2, 3)],
+                end_of_stream: false,
+            },
+            EmitBatchesResponse::default(),
+            EmitBatchesResponse {
+                docs: vec!["{\"body\": \"b\"}".to_string()],
+                partition_deltas: vec![partition_delta("partition-1", 3, 4)],
+                end_of_stream: true,
+            },
+        ]);
+        let endpoint = start_mock_source_plugin(plugin.clone()).await?;
+        let params = GrpcSourceParams {
+            endpoint,
+            params: Some(json!({"table": "logs"})),
+            batch_num_docs: 10,
+        };
+        let checkpoint: SourceCheckpoint =
+            vec![(PartitionId::from("partition-1"), Position::from(2u64))]
+                .into_iter()
+                .collect();
+        let grpc_source = GrpcSourceFactory::typed_create_source(params, checkpoint).await?;
+        assert_eq!(
+            plugin.checkpoint_requests.lock().unwrap().as_slice(),
+            &[CheckpointRequest {
+                params_json: "{\"table\":\"logs\"}".to_string(),
+                positions: vec![PartitionPosition {
+                    partition_id: "partition-1".to_string(),
+                    position: "00000000000000000002".to_string(),
+                }],
+            }]
+        );
+        let universe = Universe::new();
+        let (mailbox, inbox) = create_test_mailbox();
+        let grpc_source_actor = SourceActor::new(Box::new(grpc_source), mailbox);
+        let (_grpc_source_mailbox, grpc_source_handle) =
+            universe.spawn_actor(grpc_source_actor).spawn_async();
+        let (actor_termination, last_observation) = grpc_source_handle.join().await;
+        assert!(actor_termination.is_success());
+        assert_eq!(last_observation["num_docs_processed"], 2);
+        assert_eq!(last_observation["num_batches_processed"], 2);
+        assert_eq!(
+            last_observation["current_positions"]["partition-1"],
+            "00000000000000000004"
+        );
+        let messages = inbox.drain_available_message_or_command_for_test();
+        assert_eq!(messages.len(), 3);
+        assert!(matches!(
+            &messages[0],
+            CommandOrMessage::Message(IndexerMessage::Batch(raw_batch)) if raw_batch.docs == vec!["{\"body\": \"a\"}"]
+                && format!("{:?}", raw_batch.checkpoint_delta) == "∆(partition-1:(00000000000000000002..00000000000000000003])"
+        ));
+        assert!(matches!(
+            &messages[2],
+            CommandOrMessage::Command(Command::ExitWithSuccess)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grpc_source_acknowledges_published_positions() -> anyhow::Result<()> {
+        let plugin = MockSourcePlugin::default();
+        let endpoint = start_mock_source_plugin(plugin.clone()).await?;
+        let params = GrpcSourceParams {
+            endpoint,
+            params: None,
+            batch_num_docs: 10,
+        };
+        let grpc_source =
+            GrpcSourceFactory::typed_create_source(params, SourceCheckpoint::default()).await?;
+        assert_eq!(
+            plugin.checkpoint_requests.lock().unwrap()[0].params_json,
+            ""
+        );
+
+        let universe = Universe::new();
+        let (mailbox, _inbox) = create_test_mailbox();
+        let grpc_source_actor = SourceActor::new(Box::new(grpc_source), mailbox);
+        let (grpc_source_mailbox, grpc_source_handle) =
+            universe.spawn_actor(grpc_source_actor).spawn_async();
+        let published_checkpoint: SourceCheckpoint =
+            vec![(PartitionId::from("partition-1"), Position::from(7u64))]
+                .into_iter()
+                .collect();
+        universe
+            .send_message(
+                &grpc_source_mailbox,
+                SourceActorMessage::SuggestTruncate(published_checkpoint),
+            )
+            .await?;
+        grpc_source_handle.process_pending_and_observe().await;
+        assert_eq!(
+            plugin.ack_requests.lock().unwrap().as_slice(),
+            &[AckRequest {
+                positions: vec![PartitionPosition {
+                    partition_id: "partition-1".to_string(),
+                    position: "00000000000000000007".to_string(),
+                }],
+            }]
+        );
+        grpc_source_handle.kill().await;
+        Ok(())
+    }
+
+    #[test]
+    fn test_grpc_source_state_record_batch() {
+        let mut state = GrpcSourceState::default();
+        state
+            .current_positions
+            .insert(PartitionId::from("partition-1"), Position::from(2u64));
+        {
+            // `partition-2` starts from the beginning.
+            let emit_batches_response = EmitBatchesResponse {
+                docs: vec!["{}".to_string(), "{}".to_string()],
+                partition_deltas: vec![
+                    partition_delta("partition-1", 2, 3),
+                    PartitionDelta {
+                        partition_id: "partition-2".to_string(),
+                        from_position: String::new(),
+                        to_position: Position::from(1u64).as_str().to_string(),
+                    },
+                ],
+                end_of_stream: false,
+            };
+            let checkpoint_delta = state.record_batch(&emit_batches_response).unwrap();
+            assert_eq!(checkpoint_delta.num_partitions(), 2);
+            assert_eq!(state.num_docs_processed, 2);
+            assert_eq!(
+                state.current_positions[&PartitionId::from("partition-2")],
+                Position::from(1u64)
+            );
+        }
+        // Documents must be covered by a delta.
+        let emit_batches_response = EmitBatchesResponse {
+            docs: vec!["{}".to_string()],
+            partition_deltas: Vec::new(),
+            end_of_stream: false,
+        };
+        assert!(state.record_batch(&emit_batches_response).is_err());
+
+        for invalid_delta in [
+            // Does not chain with the current position.
+            partition_delta("partition-1", 2, 4),
+            // Goes backward.
+            partition_delta("partition-1", 3, 1),
+            // Is empty.
+            partition_delta("partition-1", 3, 3),
+        ] {
+            let emit_batches_response = EmitBatchesResponse {
+                docs: vec!["{}".to_string()],
+                partition_deltas: vec![invalid_delta],
+                end_of_stream: false,
+            };
+            assert!(state.record_batch(&emit_batches_response).is_err());
+        }
+        assert_eq!(
+            state.current_positions[&PartitionId::from("partition-1")],
+            Position::from(3u64)
+        );
+    }
+}
@@ -0,0 +1,281 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use quickwit_actors::{ActorExitStatus, Mailbox};
+use quickwit_config::IngestApiSourceParams;
+use quickwit_metastore::checkpoint::{CheckpointDelta, PartitionId, Position, SourceCheckpoint};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::ingest_api::{ingest_api, IngestQueue};
+use crate::models::{IndexerMessage, RawDocBatch};
+use crate::source::{backpressure, Source, SourceContext, TypedSourceFactory};
+
+/// Maximum number of bytes of documents emitted in one batch.
+const BATCH_NUM_BYTES_LIMIT: usize = 5_000_000;
+
+/// Time waited before polling an empty queue again.
+const EMPTY_QUEUE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Drains the documents pushed to the ingest API of the node for an index into the indexing
+/// pipeline.
+///
+/// The source records the offset of the last document read from the local queue of the index
+/// in the checkpoint, and truncates the queue once the documents are published. Documents are
+/// therefore indexed once, even if the pipeline or the node restarts. The partition of the
+/// source is named after the ID of the queue, which is generated when the queue is created.
+pub struct IngestApiSource {
+    index_id: String,
+    queue: Arc<Mutex<IngestQueue>>,
+    partition_id: PartitionId,
+    current_offset: u64,
+    num_docs_processed: u64,
+    num_bytes_processed: u64,
+}
+
+pub struct IngestApiSourceFactory;
+
+#[async_trait]
+impl TypedSourceFactory for IngestApiSourceFactory {
+    type Source = IngestApiSource;
+    type Params = IngestApiSourceParams;
+
+    async fn typed_create_source(
+        params: IngestApiSourceParams,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<Self::Source> {
+        let ingest_api = ingest_api().context(
+            "The ingest API is not initialized: the source can only run on an indexer node.",
+        )?;
+        let queue = ingest_api
+            .get_or_create_queue(&params.index_id)
+            .with_context(|| {
+                format!(
+                    "Failed to open ingest queue of index `{}`.",
+                    params.index_id
+                )
+            })?;
+        let partition_id = PartitionId::from(format!(
+            "ingest-api:{}",
+            queue
+                .lock()
+                .expect("Ingest queue lock should not be poisoned.")
+                .queue_id()
+        ));
+        let current_offset = match checkpoint.position_for_partition(&partition_id) {
+            Some(Position::Offset(offset_str)) => offset_str.parse::<u64>()?,
+            Some(Position::Beginning) | None => 0,
+        };
+        info!(
+            index_id = %params.index_id,
+            partition_id = ?partition_id,
+            current_offset = current_offset,
+            "Starting ingest API source."
+        );
+        Ok(IngestApiSource {
+            index_id: params.index_id,
+            queue,
+            partition_id,
+            current_offset,
+            num_docs_processed: 0,
+            num_bytes_processed: 0,
+        })
+    }
+}
+
+fn position_from_offset(offset: u64) -> Position {
+    if offset == 0 {
+        return Position::Beginning;
+    }
+    Position::from(offset)
+}
+
+#[async_trait]
+impl Source for IngestApiSource {
+    async fn emit_batches(
+        &mut self,
+        batch_sink: &Mailbox<IndexerMessage>,
+        ctx: &SourceContext,
+    ) -> Result<(), ActorExitStatus> {
+        let batch_num_bytes_limit =
+            backpressure::target_batch_num_bytes(batch_sink, BATCH_NUM_BYTES_LIMIT as u64) as usize;
+        let (docs, next_offset) = {
+            let _protect_guard = ctx.protect_zone();
+            self.queue
+                .lock()
+                .expect("Ingest queue lock should not be poisoned.")
+                .read(self.current_offset, batch_num_bytes_limit)
+                .with_context(|| {
+                    format!("Failed to read ingest queue of index `{}`.", self.index_id)
+                })?
+        };
+        if docs.is_empty() {
+            ctx.sleep(EMPTY_QUEUE_POLL_INTERVAL).await;
+            return Ok(());
+        }
+        self.num_docs_processed += docs.len() as u64;
+        self.num_bytes_processed += docs.iter().map(|doc| doc.len() as u64).sum::<u64>();
+        let checkpoint_delta = CheckpointDelta::from_partition_delta(
+            self.partition_id.clone(),
+            position_from_offset(self.current_offset),
+            position_from_offset(next_offset),
+        );
+        self.current_offset = next_offset;
+        let batch = RawDocBatch {
+            docs,
+            checkpoint_delta,
+        };
+        ctx.send_message(batch_sink, IndexerMessage::from(batch))
+            .await?;
+        Ok(())
+    }
+
+    async fn suggest_truncate(
+        &mut self,
+        checkpoint: SourceCheckpoint,
+        ctx: &SourceContext,
+    ) -> anyhow::Result<()> {
+        let published_offset = match checkpoint.position_for_partition(&self.partition_id) {
+            Some(Position::Offset(offset_str)) => offset_str.parse::<u64>()?,
+            Some(Position::Beginning) | None => return Ok(()),
+        };
+        let _protect_guard = ctx.protect_zone();
+        // The segments are deleted on the next truncation if this one fails, which does not
+        // justify failing the pipeline.
+        if let Err(error) = self
+            .queue
+            .lock()
+            .expect("Ingest queue lock should not be poisoned.")
+            .truncate(published_offset)
+        {
+            warn!(
+                index_id = %self.index_id,
+                error = ?error,
+                "Failed to truncate ingest queue."
+            );
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        "IngestApiSource".to_string()
+    }
+
+    fn observable_state(&self) -> serde_json::Value {
+        let end_offset = self
+            .queue
+            .lock()
+            .expect("Ingest queue lock should not be poisoned.")
+            .end_offset();
+        json!({
+            "index_id": self.index_id,
+            "partition_id": &*self.partition_id.0,
+            "current_offset": self.current_offset,
+            "end_offset": end_offset,
+            "num_docs_processed": self.num_docs_processed,
+            "num_bytes_processed": self.num_bytes_processed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_actors::{create_test_mailbox, Universe};
+
+    use super::*;
+    use crate::ingest_api::init_ingest_api;
+    use crate::source::SourceActor;
+
+    fn init_ingest_api_for_test() {
+        let data_dir_path = tempfile::tempdir().unwrap().into_path();
+        // The ingest API may already be initialized by another test.
+        let _ = init_ingest_api(&data_dir_path);
+    }
+
+    #[tokio::test]
+    async fn test_ingest_api_source() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        init_ingest_api_for_test();
+        let index_id = "test-ingest-api-source";
+        let queue = ingest_api().unwrap().get_or_create_queue(index_id)?;
+        queue.lock().unwrap().append(&[r#"{"body": "doc-0"}"#])?;
+
+        let universe = Universe::new();
+        let (mailbox, inbox) = create_test_mailbox();
+        let params = IngestApiSourceParams {
+            index_id: index_id.to_string(),
+        };
+        let ingest_api_source =
+            IngestApiSourceFactory::typed_create_source(params, SourceCheckpoint::default())
+                .await?;
+        let partition_id = ingest_api_source.partition_id.clone();
+        let ingest_api_source_actor = SourceActor::new(Box::new(ingest_api_source), mailbox);
+        let (_ingest_api_source_mailbox, ingest_api_source_handle) =
+            universe.spawn_actor(ingest_api_source_actor).spawn_async();
+        queue
+            .lock()
+            .unwrap()
+            .append(&[r#"{"body": "doc-1"}"#, r#"{"body": "doc-2"}"#])?;
+        tokio::time::sleep(quickwit_actors::HEARTBEAT).await;
+        let observation = ingest_api_source_handle
+            .process_pending_and_observe()
+            .await
+            .state;
+        assert_eq!(observation["num_docs_processed"], 3);
+        assert_eq!(observation["current_offset"], 63);
+        assert_eq!(observation["end_offset"], 63);
+        let docs: Vec<String> = inbox
+            .drain_available_message_for_test()
+            .into_iter()
+            .flat_map(|message| match message {
+                IndexerMessage::Batch(batch) => batch.docs,
+                _ => Vec::new(),
+            })
+            .collect();
+        assert_eq!(
+            docs,
+            vec![
+                r#"{"body": "doc-0"}"#,
+                r#"{"body": "doc-1"}"#,
+                r#"{"body": "doc-2"}"#
+            ]
+        );
+        ingest_api_source_handle.kill().await;
+
+        // The source resumes from the checkpoint.
+        let mut checkpoint = SourceCheckpoint::default();
+        checkpoint.try_apply_delta(CheckpointDelta::from_partition_delta(
+            partition_id,
+            Position::Beginning,
+            Position::from(21u64),
+        ))?;
+        let params = IngestApiSourceParams {
+            index_id: index_id.to_string(),
+        };
+        let ingest_api_source =
+            IngestApiSourceFactory::typed_create_source(params, checkpoint).await?;
+        assert_eq!(ingest_api_source.current_offset, 21);
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use apache_avro::{from_avro_datum, Schema};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use tracing::{info, warn};
+
+/// First byte of the messages in the Confluent wire format.
+const MAGIC_BYTE: u8 = 0;
+
+const SCHEMA_REGISTRY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SchemaResponse {
+    schema: String,
+    #[serde(default)]
+    schema_type: Option<String>,
+}
+
+/// Splits a message in the Confluent wire format into its schema ID and its Avro datum.
+fn parse_wire_format(payload: &[u8]) -> anyhow::Result<(u32, &[u8])> {
+    if payload.len() < 5 || payload[0] != MAGIC_BYTE {
+        bail!("Message is not in the Confluent wire format.");
+    }
+    let mut schema_id_bytes = [0u8; 4];
+    schema_id_bytes.copy_from_slice(&payload[1..5]);
+    Ok((u32::from_be_bytes(schema_id_bytes), &payload[5..]))
+}
+
+/// Decodes the Avro records of a Kafka source into JSON documents, with the schemas of a
+/// Confluent Schema Registry.
+///
+/// The schemas are fetched asynchronously with [`AvroDecoder::resolve_schema`] before the
+/// messages are decoded, and cached for the lifetime of the source: a schema ID never changes
+/// its schema.
+pub(super) struct AvroDecoder {
+    schema_registry_url: String,
+    credentials_opt: Option<(String, String)>,
+    http_client: reqwest::Client,
+    /// Schemas by ID. `None` stands for an ID unknown to the registry or a schema that is not
+    /// Avro, whose messages are invalid.
+    schemas: HashMap<u32, Option<Schema>>,
+}
+
+impl AvroDecoder {
+    pub fn new(
+        schema_registry_url: &str,
+        username_opt: Option<String>,
+        password_opt: Option<String>,
+    ) -> Self {
+        AvroDecoder {
+            schema_registry_url: schema_registry_url.trim_end_matches('/').to_string(),
+            credentials_opt: username_opt.zip(password_opt),
+            http_client: reqwest::Client::new(),
+            schemas: HashMap::new(),
+        }
+    }
+
+    /// Fetches the schema of the message from the registry, unless it is already cached.
+    /// Returns an error only when the registry cannot be reached, so that the message is
+    /// decoded once it is back.
+    pub async fn resolve_schema(&mut self, payload_opt: Option<&[u8]>) -> anyhow::Result<()> {
+        let schema_id = match payload_opt.map(parse_wire_format) {
+            Some(Ok((schema_id, _))) => schema_id,
+            // Invalid messages are reported when they are decoded.
+            _ => return Ok(()),
+        };
+        if self.schemas.contains_key(&schema_id) {
+            return Ok(());
+        }
+        let schema_opt = self.fetch_schema(schema_id).await?;
+        self.schemas.insert(schema_id, schema_opt);
+        Ok(())
+    }
+
+    async fn fetch_schema(&self, schema_id: u32) -> anyhow::Result<Option<Schema>> {
+        let url = format!("{}/schemas/ids/{}", self.schema_registry_url, schema_id);
+        let mut request = self.http_client.get(&url).timeout(SCHEMA_REGISTRY_TIMEOUT);
+        if let Some((username, password)) = &self.credentials_opt {
+            request = request.basic_auth(username, Some(password));
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to fetch schema from `{}`.", url))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            warn!(schema_id = schema_id, "Schema not found in registry.");
+            return Ok(None);
+        }
+        let schema_response: SchemaResponse = response
+            .error_for_status()
+            .with_context(|| format!("Failed to fetch schema from `{}`.", url))?
+            .json()
+            .await
+            .with_context(|| format!("Failed to parse schema from `{}`.", url))?;
+        if let Some(schema_type) = schema_response
+            .schema_type
+            .filter(|schema_type| schema_type != "AVRO")
+        {
+            warn!(schema_id = schema_id, schema_type = %schema_type, "Schema is not Avro.");
+            return Ok(None);
+        }
+        match Schema::parse_str(&schema_response.schema) {
+            Ok(schema) => {
+                info!(schema_id = schema_id, "Fetched Avro schema from registry.");
+                Ok(Some(schema))
+            }
+            Err(error) => {
+                warn!(schema_id = schema_id, error = ?error, "Failed to parse Avro schema.");
+                Ok(None)
+            }
+        }
+    }
+
+    /// Decodes a message whose schema was resolved into a JSON document.
+    pub fn decode(&self, payload: &[u8]) -> anyhow::Result<String> {
+        let (schema_id, mut datum) = parse_wire_format(payload)?;
+        let schema = match self.schemas.get(&schema_id) {
+            Some(Some(schema)) => schema,
+            Some(None) => bail!("Schema `{}` is missing or invalid.", schema_id),
+            None => bail!("Schema `{}` is not resolved.", schema_id),
+        };
+        let record =
+            from_avro_datum(schema, &mut datum, None).context("Failed to decode Avro record.")?;
+        let doc = JsonValue::try_from(record).context("Failed to convert Avro record to JSON.")?;
+        Ok(doc.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use apache_avro::to_avro_datum;
+    use apache_avro::types::Record;
+    use serde_json::json;
+
+    use super::*;
+
+    const SCHEMA: &str = r#"
+        {
+            "type": "record",
+            "name": "Order",
+            "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "customer", "type": ["null", "string"]},
+                {"name": "status", "type": {"type": "enum", "name": "Status", "symbols": ["PLACED", "SHIPPED"]}}
+            ]
+        }
+    "#;
+
+    fn encode(schema_id: u32, schema: &Schema, record: Record) -> Vec<u8> {
+        let mut payload = vec![MAGIC_BYTE];
+        payload.extend_from_slice(&schema_id.to_be_bytes());
+        payload.extend(to_avro_datum(schema, record).unwrap());
+        payload
+    }
+
+    #[test]
+    fn test_parse_wire_format() {
+        assert_eq!(
+            parse_wire_format(&[0, 0, 0, 1, 2, 42]).unwrap(),
+            (258, &[42u8][..])
+        );
+        assert!(parse_wire_format(&[0, 0, 0]).is_err());
+        assert!(parse_wire_format(br#"{"id": 1}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_avro_decoder() -> anyhow::Result<()> {
+        let schema = Schema::parse_str(SCHEMA)?;
+        let mut avro_decoder = AvroDecoder::new("http://localhost:8081/", None, None);
+        assert_eq!(avro_decoder.schema_registry_url, "http://localhost:8081");
+        avro_decoder.schemas.insert(7, Some(schema.clone()));
+        avro_decoder.schemas.insert(8, None);
+
+        let mut record = Record::new(&schema).unwrap();
+        record.put("id", 42i64);
+        record.put("customer", Some("alice"));
+        record.put(
+            "status",
+            apache_avro::types::Value::Enum(1, "SHIPPED".to_string()),
+        );
+        let payload = encode(7, &schema, record);
+        // The schema is cached: the registry is not queried.
+        avro_decoder.resolve_schema(Some(&payload)).await?;
+        avro_decoder.resolve_schema(Some(b"not avro")).await?;
+
+        let doc: JsonValue = serde_json::from_str(&avro_decoder.decode(&payload)?)?;
+        assert_eq!(
+            doc,
+            json!({"id": 42, "customer": "alice", "status": "SHIPPED"})
+        );
+
+        let mut record = Record::new(&schema).unwrap();
+        record.put("id", 43i64);
+        record.put("customer", None::<String>);
+        record.put(
+            "status",
+            apache_avro::types::Value::Enum(0, "PLACED".to_string()),
+        );
+        let doc: JsonValue =
+            serde_json::from_str(&avro_decoder.decode(&encode(7, &schema, record))?)?;
+        assert_eq!(doc, json!({"id": 43, "customer": null, "status": "PLACED"}));
+
+        let mut payload_with_missing_schema = payload.clone();
+        payload_with_missing_schema[4] = 8;
+        assert!(avro_decoder
+            .decode(&payload_with_missing_schema)
+            .unwrap_err()
+            .to_string()
+            .contains("missing or invalid"));
+        let mut payload_with_unresolved_schema = payload;
+        payload_with_unresolved_schema[4] = 9;
+        assert!(avro_decoder
+            .decode(&payload_with_unresolved_schema)
+            .unwrap_err()
+            .to_string()
+            .contains("not resolved"));
+        assert!(avro_decoder.decode(&[0, 0, 0, 0, 7, 255]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,382 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{BaseConsumer, CommitMode, Consumer, ConsumerContext, Rebalance};
+use rdkafka::error::{KafkaError, KafkaResult};
+use rdkafka::message::OwnedMessage;
+use rdkafka::topic_partition_list::TopicPartitionList;
+use rdkafka::{ClientContext, Message, Offset};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::source::kafka_oauth::{set_oauthbearer_token, OAuthBearerToken};
+
+/// Number of messages buffered between the poller thread and the source.
+const EVENT_CHANNEL_CAPACITY: usize = 1_000;
+
+const POLL_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Maximum time a revocation waits for the messages of the revoked partitions to be published.
+/// It must remain below the `max.poll.interval.ms` of the consumer (5 minutes by default) since
+/// the consumer is not polled in the meantime.
+const REVOCATION_TIMEOUT: Duration = Duration::from_secs(240);
+
+/// Topic and partition number.
+pub(crate) type TopicPartition = (String, i32);
+
+/// Events forwarded by the poller thread of a `ConsumerGroupMember` to the source, in order.
+#[derive(Debug)]
+pub(crate) enum ConsumerGroupEvent {
+    /// The partitions were added to the assignment of the member.
+    Assign(Vec<TopicPartition>),
+    /// The partitions were removed from the assignment of the member, after their messages were
+    /// published.
+    Revoke(Vec<TopicPartition>),
+    Message(OwnedMessage),
+    Error(KafkaError),
+}
+
+#[derive(Default)]
+struct PartitionProgress {
+    /// Offset of the last message received for each partition.
+    received_offsets: HashMap<TopicPartition, i64>,
+    /// Offset of the last message published for each partition.
+    published_offsets: HashMap<TopicPartition, i64>,
+}
+
+/// Holds the revocation of partitions until the messages received from them are published, so
+/// that the next owner of a partition resumes right after the last message indexed by the
+/// previous one and no two pipelines index the same partition concurrently.
+pub(crate) struct RebalanceCoordinator {
+    progress: Mutex<PartitionProgress>,
+    published_condvar: Condvar,
+    is_stopped: AtomicBool,
+    revocation_timeout: Duration,
+}
+
+impl RebalanceCoordinator {
+    pub fn new(revocation_timeout: Duration) -> Self {
+        RebalanceCoordinator {
+            progress: Mutex::new(PartitionProgress::default()),
+            published_condvar: Condvar::new(),
+            is_stopped: AtomicBool::new(false),
+            revocation_timeout,
+        }
+    }
+
+    pub fn record_received(&self, topic_partition: TopicPartition, offset: i64) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.received_offsets.insert(topic_partition, offset);
+    }
+
+    pub fn record_published(&self, published_offsets: Vec<(TopicPartition, i64)>) {
+        let mut progress = self.progress.lock().unwrap();
+        for (topic_partition, offset) in published_offsets {
+            let published_offset = progress
+                .published_offsets
+                .entry(topic_partition)
+                .or_insert(offset);
+            *published_offset = offset.max(*published_offset);
+        }
+        self.published_condvar.notify_all();
+    }
+
+    /// Waits until the last messages received from the partitions are published and forgets
+    /// about the partitions. Returns `false` if the wait timed out or the member is stopping.
+    pub fn wait_for_published(&self, topic_partitions: &[TopicPartition]) -> bool {
+        let deadline = Instant::now() + self.revocation_timeout;
+        let mut progress = self.progress.lock().unwrap();
+        let is_published = loop {
+            if self.is_stopped() {
+                break false;
+            }
+            let is_published = topic_partitions.iter().all(|topic_partition| {
+                match progress.received_offsets.get(topic_partition) {
+                    Some(received_offset) => progress
+                        .published_offsets
+                        .get(topic_partition)
+                        .map(|published_offset| published_offset >= received_offset)
+                        .unwrap_or(false),
+                    None => true,
+                }
+            });
+            if is_published {
+                break true;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                break false;
+            }
+            progress = self
+                .published_condvar
+                .wait_timeout(progress, deadline - now)
+                .unwrap()
+                .0;
+        };
+        for topic_partition in topic_partitions {
+            progress.received_offsets.remove(topic_partition);
+            progress.published_offsets.remove(topic_partition);
+        }
+        is_published
+    }
+
+    pub fn stop(&self) {
+        self.is_stopped.store(true, Ordering::Release);
+        self.published_condvar.notify_all();
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.is_stopped.load(Ordering::Acquire)
+    }
+}
+
+struct ConsumerGroupContext {
+    coordinator: Arc<RebalanceCoordinator>,
+    events_tx: mpsc::Sender<ConsumerGroupEvent>,
+}
+
+impl ConsumerGroupContext {
+    fn send_event(&self, event: ConsumerGroupEvent) {
+        // Once stopped, the consumer may be closed, and the callbacks invoked, from an async
+        // context where blocking is not allowed.
+        if !self.coordinator.is_stopped() {
+            let _ = self.events_tx.blocking_send(event);
+        }
+    }
+}
+
+impl ClientContext for ConsumerGroupContext {}
+
+impl ConsumerContext for ConsumerGroupContext {
+    fn pre_rebalance(&self, rebalance: &Rebalance) {
+        if let Rebalance::Revoke(partitions) = rebalance {
+            let topic_partitions = topic_partitions(partitions);
+            info!(partitions = ?topic_partitions, "Revoking partitions.");
+
+            if !self.coordinator.wait_for_published(&topic_partitions)
+                && !self.coordinator.is_stopped()
+            {
+                warn!(
+                    partitions = ?topic_partitions,
+                    "Timed out waiting for the messages of the revoked partitions to be published."
+                );
+            }
+            self.send_event(ConsumerGroupEvent::Revoke(topic_partitions));
+        }
+    }
+
+    fn post_rebalance(&self, rebalance: &Rebalance) {
+        match rebalance {
+            Rebalance::Assign(partitions) => {
+                let topic_partitions = topic_partitions(partitions);
+                info!(partitions = ?topic_partitions, "Assigned partitions.");
+                self.send_event(ConsumerGroupEvent::Assign(topic_partitions));
+            }
+            Rebalance::Revoke(_) => {}
+            Rebalance::Error(error) => warn!(error = %error, "Failed to rebalance."),
+        }
+    }
+
+    fn commit_callback(&self, result: KafkaResult<()>, _offsets: &TopicPartitionList) {
+        if let Err(error) = result {
+            warn!(error = ?error, "Failed to commit offsets.");
+        }
+    }
+}
+
+fn topic_partitions(partitions: &TopicPartitionList) -> Vec<TopicPartition> {
+    partitions
+        .elements()
+        .iter()
+        .map(|element| (element.topic().to_string(), element.partition()))
+        .collect()
+}
+
+type GroupConsumer = BaseConsumer<ConsumerGroupContext>;
+
+/// Member of a Kafka consumer group. A dedicated thread polls the consumer, since the
+/// revocation of partitions blocks the poll until their messages are published, and forwards
+/// the messages and the assignment changes to the source.
+pub(crate) struct ConsumerGroupMember {
+    consumer: Arc<GroupConsumer>,
+    coordinator: Arc<RebalanceCoordinator>,
+    events_rx: mpsc::Receiver<ConsumerGroupEvent>,
+}
+
+impl ConsumerGroupMember {
+    /// Joins the consumer group of `client_config` and subscribes to `topics`, a topic name or
+    /// a regex starting with `^`, authenticated with the SASL/OAUTHBEARER token, if any.
+    pub fn join(
+        client_config: &ClientConfig,
+        topics: &str,
+        token_opt: Option<&OAuthBearerToken>,
+    ) -> anyhow::Result<Self> {
+        let coordinator = Arc::new(RebalanceCoordinator::new(REVOCATION_TIMEOUT));
+        let (events_tx, events_rx) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+        let context = ConsumerGroupContext {
+            coordinator: coordinator.clone(),
+            events_tx: events_tx.clone(),
+        };
+        let consumer: Arc<GroupConsumer> = Arc::new(
+            client_config
+                .create_with_context(context)
+                .context("Failed to create Kafka consumer.")?,
+        );
+        if let Some(token) = token_opt {
+            set_oauthbearer_token(consumer.client(), token)?;
+        }
+        consumer
+            .subscribe(&[topics])
+            .with_context(|| format!("Failed to subscribe to `{}`.", topics))?;
+
+        let poller_consumer = consumer.clone();
+        let poller_coordinator = coordinator.clone();
+        thread::Builder::new()
+            .name("kafka-consumer-group".to_string())
+            .spawn(move || poll_consumer(poller_consumer, poller_coordinator, events_tx))
+            .context("Failed to spawn Kafka consumer thread.")?;
+        Ok(ConsumerGroupMember {
+            consumer,
+            coordinator,
+            events_rx,
+        })
+    }
+
+    /// Hands a renewed SASL/OAUTHBEARER token to the consumer.
+    pub fn set_oauthbearer_token(&self, token: &OAuthBearerToken) -> anyhow::Result<()> {
+        set_oauthbearer_token(self.consumer.client(), token)
+    }
+
+    /// Receives the next event, if any.
+    pub async fn recv(&mut self) -> Option<ConsumerGroupEvent> {
+        self.events_rx.recv().await
+    }
+
+    /// Records a message skipped by the source, e.g. because it precedes the checkpoint, as if
+    /// it were published.
+    pub fn record_skipped(&self, topic_partition: TopicPartition, offset: i64) {
+        self.coordinator
+            .record_published(vec![(topic_partition, offset)]);
+    }
+
+    /// Commits the offsets of the last messages published, which unblocks the revocation of
+    /// their partitions.
+    pub async fn commit_published(
+        &self,
+        published_offsets: Vec<(TopicPartition, i64)>,
+    ) -> anyhow::Result<()> {
+        if published_offsets.is_empty() {
+            return Ok(());
+        }
+        let mut offsets = TopicPartitionList::with_capacity(published_offsets.len());
+        for ((topic, partition), offset) in &published_offsets {
+            offsets.add_partition_offset(topic, *partition, Offset::Offset(offset + 1))?;
+        }
+        let consumer = self.consumer.clone();
+        tokio::task::spawn_blocking(move || consumer.commit(&offsets, CommitMode::Sync))
+            .await?
+            .context("Failed to commit offsets.")?;
+        self.coordinator.record_published(published_offsets);
+        Ok(())
+    }
+}
+
+impl Drop for ConsumerGroupMember {
+    fn drop(&mut self) {
+        self.coordinator.stop();
+    }
+}
+
+fn poll_consumer(
+    consumer: Arc<GroupConsumer>,
+    coordinator: Arc<RebalanceCoordinator>,
+    events_tx: mpsc::Sender<ConsumerGroupEvent>,
+) {
+    while !coordinator.is_stopped() {
+        let event = match consumer.poll(POLL_TIMEOUT) {
+            Some(Ok(message)) => {
+                coordinator.record_received(
+                    (message.topic().to_string(), message.partition()),
+                    message.offset(),
+                );
+                ConsumerGroupEvent::Message(message.detach())
+            }
+            Some(Err(error)) => ConsumerGroupEvent::Error(error),
+            None => continue,
+        };
+        if events_tx.blocking_send(event).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topic_partition(partition: i32) -> TopicPartition {
+        ("topic".to_string(), partition)
+    }
+
+    #[test]
+    fn test_rebalance_coordinator_waits_for_published() {
+        let coordinator = Arc::new(RebalanceCoordinator::new(Duration::from_secs(10)));
+        coordinator.record_received(topic_partition(0), 42);
+        coordinator.record_received(topic_partition(1), 7);
+
+        let waiting_coordinator = coordinator.clone();
+        let (wait_result_tx, wait_result_rx) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            let is_published =
+                waiting_coordinator.wait_for_published(&[topic_partition(0), topic_partition(2)]);
+            wait_result_tx.send(is_published).unwrap();
+        });
+        coordinator.record_published(vec![(topic_partition(0), 41)]);
+        assert!(wait_result_rx
+            .recv_timeout(Duration::from_millis(50))
+            .is_err());
+
+        coordinator.record_published(vec![(topic_partition(0), 42)]);
+        assert!(wait_result_rx.recv().unwrap());
+        assert!(coordinator.wait_for_published(&[topic_partition(0)]));
+    }
+
+    #[test]
+    fn test_rebalance_coordinator_timeout_and_stop() {
+        let coordinator = Arc::new(RebalanceCoordinator::new(Duration::from_millis(50)));
+        coordinator.record_received(topic_partition(0), 42);
+        assert!(!coordinator.wait_for_published(&[topic_partition(0)]));
+
+        let coordinator = Arc::new(RebalanceCoordinator::new(Duration::from_secs(10)));
+        coordinator.record_received(topic_partition(0), 42);
+        let waiting_coordinator = coordinator.clone();
+        let wait_handle =
+            thread::spawn(move || waiting_coordinator.wait_for_published(&[topic_partition(0)]));
+        coordinator.stop();
+        assert!(!wait_handle.join().unwrap());
+    }
+}
@@ -0,0 +1,319 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context};
+use quickwit_config::KafkaOAuthBearerParams;
+use rdkafka::bindings::rd_kafka_oauthbearer_set_token;
+use rdkafka::client::Client;
+use rdkafka::config::ClientConfig;
+use rdkafka::types::RDKafkaRespErr;
+use rdkafka::ClientContext;
+use rusoto_core::credential::{AwsCredentials, DefaultCredentialsProvider, ProvideAwsCredentials};
+use rusoto_core::signature::SignedRequest;
+use rusoto_core::Region;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+/// Validity of the tokens signed for Amazon MSK.
+const MSK_IAM_TOKEN_VALIDITY: Duration = Duration::from_secs(900);
+
+/// Lifetime assumed for the tokens of an OAuth endpoint that does not return `expires_in`.
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(3_600);
+
+/// Delay before retrying to fetch a token after a failure.
+const REFRESH_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+const TOKEN_ENDPOINT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A SASL/OAUTHBEARER token, along with the metadata the client sends to the brokers.
+#[derive(Clone)]
+pub(super) struct OAuthBearerToken {
+    value: String,
+    principal_name: String,
+    lifetime: Duration,
+    expires_at: SystemTime,
+    extensions: Vec<(String, String)>,
+}
+
+impl OAuthBearerToken {
+    /// Delay after which the token is renewed: 80% of its lifetime, so that the brokers never
+    /// see an expired token.
+    fn refresh_delay(&self) -> Duration {
+        self.lifetime.mul_f64(0.8)
+    }
+}
+
+/// Makes the client authenticate with SASL/OAUTHBEARER. The tokens are not obtained by
+/// librdkafka but set by the source, see [`set_oauthbearer_token`].
+pub(super) fn configure_oauthbearer(client_config: &mut ClientConfig) {
+    client_config.set("sasl.mechanism", "OAUTHBEARER");
+    if client_config.get("security.protocol").is_none() {
+        client_config.set("security.protocol", "SASL_SSL");
+    }
+}
+
+/// Hands a token to the client, which uses it for the connections opened from now on and
+/// re-authenticates the open connections with it.
+pub(super) fn set_oauthbearer_token<C: ClientContext>(
+    client: &Client<C>,
+    token: &OAuthBearerToken,
+) -> anyhow::Result<()> {
+    let value = CString::new(token.value.as_str()).context("Invalid OAUTHBEARER token.")?;
+    let principal_name = CString::new(token.principal_name.as_str())
+        .context("Invalid OAUTHBEARER principal name.")?;
+    let extensions = token
+        .extensions
+        .iter()
+        .flat_map(|(key, value)| [key.as_str(), value.as_str()])
+        .map(CString::new)
+        .collect::<Result<Vec<_>, _>>()
+        .context("Invalid OAUTHBEARER extension.")?;
+    let mut extension_ptrs: Vec<*const c_char> = extensions
+        .iter()
+        .map(|extension| extension.as_ptr())
+        .collect();
+    let expires_at_millis = token
+        .expires_at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64;
+    let mut error_buffer = [0 as c_char; 512];
+    // SAFETY: the client pointer is valid for the lifetime of `client`. The strings and the
+    // array of extensions outlive the call, which copies them, and the error buffer size is
+    // passed along with it.
+    let response_code = unsafe {
+        rd_kafka_oauthbearer_set_token(
+            client.native_ptr(),
+            value.as_ptr(),
+            expires_at_millis,
+            principal_name.as_ptr(),
+            if extension_ptrs.is_empty() {
+                ptr::null_mut()
+            } else {
+                extension_ptrs.as_mut_ptr()
+            },
+            extension_ptrs.len(),
+            error_buffer.as_mut_ptr(),
+            error_buffer.len(),
+        )
+    };
+    if response_code != RDKafkaRespErr::RD_KAFKA_RESP_ERR_NO_ERROR {
+        // SAFETY: librdkafka writes a NUL-terminated message into the buffer on failure.
+        let error_message = unsafe { CStr::from_ptr(error_buffer.as_ptr()) };
+        bail!(
+            "Failed to set OAUTHBEARER token: {}.",
+            error_message.to_string_lossy()
+        );
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+/// Obtains the tokens of a source from its provider.
+pub(super) struct OAuthBearerTokenProvider {
+    params: KafkaOAuthBearerParams,
+    http_client: reqwest::Client,
+}
+
+impl OAuthBearerTokenProvider {
+    pub fn new(params: KafkaOAuthBearerParams) -> Self {
+        OAuthBearerTokenProvider {
+            params,
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    pub async fn fetch_token(&self) -> anyhow::Result<OAuthBearerToken> {
+        match &self.params {
+            KafkaOAuthBearerParams::ClientCredentials {
+                token_endpoint_url,
+                client_id,
+                client_secret,
+                scope,
+                extensions,
+            } => {
+                let mut form = vec![("grant_type", "client_credentials")];
+                if let Some(scope) = scope {
+                    form.push(("scope", scope.as_str()));
+                }
+                let response: TokenResponse = self
+                    .http_client
+                    .post(token_endpoint_url)
+                    .basic_auth(client_id, Some(client_secret))
+                    .form(&form)
+                    .timeout(TOKEN_ENDPOINT_TIMEOUT)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .with_context(|| {
+                        format!("Failed to request token from `{}`.", token_endpoint_url)
+                    })?
+                    .json()
+                    .await
+                    .with_context(|| {
+                        format!("Failed to parse token from `{}`.", token_endpoint_url)
+                    })?;
+                let lifetime = response
+                    .expires_in
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_TOKEN_LIFETIME);
+                Ok(OAuthBearerToken {
+                    value: response.access_token,
+                    principal_name: client_id.clone(),
+                    lifetime,
+                    expires_at: SystemTime::now() + lifetime,
+                    extensions: extensions
+                        .iter()
+                        .map(|(key, value)| (key.clone(), value.clone()))
+                        .collect(),
+                })
+            }
+            KafkaOAuthBearerParams::AwsMskIam { region } => {
+                let region = Region::from_str(region)
+                    .with_context(|| format!("Failed to parse AWS region `{}`.", region))?;
+                let credentials = DefaultCredentialsProvider::new()?
+                    .credentials()
+                    .await
+                    .context("Failed to load AWS credentials.")?;
+                Ok(msk_iam_token(&region, &credentials))
+            }
+        }
+    }
+}
+
+/// Signs a token for the IAM access control of Amazon MSK: a presigned
+/// `kafka-cluster:Connect` request, encoded in URL-safe base64.
+fn msk_iam_token(region: &Region, credentials: &AwsCredentials) -> OAuthBearerToken {
+    let mut request = SignedRequest::new("GET", "kafka-cluster", region, "/");
+    request.set_hostname(Some(format!("kafka.{}.amazonaws.com", region.name())));
+    request.add_param("Action", "kafka-cluster:Connect");
+    let mut url = request.generate_presigned_url(credentials, &MSK_IAM_TOKEN_VALIDITY, false);
+    url.push_str(&format!(
+        "&User-Agent=quickwit/{}",
+        env!("CARGO_PKG_VERSION")
+    ));
+    OAuthBearerToken {
+        value: base64::encode_config(url, base64::URL_SAFE_NO_PAD),
+        principal_name: credentials.aws_access_key_id().to_string(),
+        lifetime: MSK_IAM_TOKEN_VALIDITY,
+        expires_at: SystemTime::now() + MSK_IAM_TOKEN_VALIDITY,
+        extensions: Vec::new(),
+    }
+}
+
+/// Renews the token of a source before it expires. The source polls the refresher from its
+/// emit loop.
+pub(super) struct OAuthBearerTokenRefresher {
+    provider: OAuthBearerTokenProvider,
+    next_refresh_at: Instant,
+}
+
+impl OAuthBearerTokenRefresher {
+    /// Fetches the first token, which the client must be given before connecting.
+    pub async fn try_new(
+        params: KafkaOAuthBearerParams,
+    ) -> anyhow::Result<(Self, OAuthBearerToken)> {
+        let provider = OAuthBearerTokenProvider::new(params);
+        let token = provider.fetch_token().await?;
+        let refresher = OAuthBearerTokenRefresher {
+            provider,
+            next_refresh_at: Instant::now() + token.refresh_delay(),
+        };
+        Ok((refresher, token))
+    }
+
+    /// Returns a new token once the current one is due for renewal. Failures are logged and
+    /// retried shortly after: the current token remains valid for a while.
+    pub async fn poll_refresh(&mut self) -> Option<OAuthBearerToken> {
+        if Instant::now() < self.next_refresh_at {
+            return None;
+        }
+        match self.provider.fetch_token().await {
+            Ok(token) => {
+                info!(expires_in = ?token.lifetime, "Refreshed OAUTHBEARER token.");
+                self.next_refresh_at = Instant::now() + token.refresh_delay();
+                Some(token)
+            }
+            Err(error) => {
+                self.retry_later(&error);
+                None
+            }
+        }
+    }
+
+    pub fn retry_later(&mut self, error: &anyhow::Error) {
+        warn!(error = ?error, "Failed to refresh OAUTHBEARER token.");
+        self.next_refresh_at = Instant::now() + REFRESH_RETRY_DELAY;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_msk_iam_token() {
+        let credentials = AwsCredentials::new("AKIDEXAMPLE", "wJalrXUtnFEMI/K7MDENG", None, None);
+        let token = msk_iam_token(&Region::UsEast1, &credentials);
+        assert_eq!(token.principal_name, "AKIDEXAMPLE");
+        assert_eq!(token.refresh_delay(), Duration::from_secs(720));
+        assert!(!token.value.contains('='));
+
+        let url = String::from_utf8(
+            base64::decode_config(&token.value, base64::URL_SAFE_NO_PAD).unwrap(),
+        )
+        .unwrap();
+        assert!(url.starts_with("https://kafka.us-east-1.amazonaws.com/?"));
+        assert!(url.contains("Action=kafka-cluster%3AConnect"));
+        assert!(url.contains("X-Amz-Credential=AKIDEXAMPLE%2F"));
+        assert!(url.contains("X-Amz-Expires=900"));
+        assert!(url.contains("X-Amz-Signature="));
+        assert!(url.ends_with(&format!(
+            "&User-Agent=quickwit/{}",
+            env!("CARGO_PKG_VERSION")
+        )));
+    }
+
+    #[test]
+    fn test_configure_oauthbearer() {
+        let mut client_config = ClientConfig::new();
+        configure_oauthbearer(&mut client_config);
+        assert_eq!(client_config.get("sasl.mechanism"), Some("OAUTHBEARER"));
+        assert_eq!(client_config.get("security.protocol"), Some("SASL_SSL"));
+
+        let mut client_config = ClientConfig::new();
+        client_config.set("security.protocol", "SASL_PLAINTEXT");
+        configure_oauthbearer(&mut client_config);
+        assert_eq!(
+            client_config.get("security.protocol"),
+            Some("SASL_PLAINTEXT")
+        );
+    }
+}
@@ -20,7 +20,7 @@
 use std::collections::HashMap;
 use std::fmt;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::{bail, Context};
 use async_trait::async_trait;
@@ -28,23 +28,33 @@ use backoff::ExponentialBackoff;
 use futures::{StreamExt, TryFutureExt};
 use itertools::Itertools;
 use quickwit_actors::{ActorExitStatus, Mailbox};
-use quickwit_config::KafkaSourceParams;
+use quickwit_config::{DebeziumParams, KafkaDecodingParams, KafkaSourceParams};
 use quickwit_metastore::checkpoint::{CheckpointDelta, PartitionId, Position, SourceCheckpoint};
 use rdkafka::config::{ClientConfig, RDKafkaLogLevel};
 use rdkafka::consumer::stream_consumer::StreamConsumer;
 use rdkafka::consumer::{Consumer, ConsumerContext, Rebalance};
 use rdkafka::error::{KafkaError, KafkaResult};
-use rdkafka::message::BorrowedMessage;
 use rdkafka::topic_partition_list::TopicPartitionList;
 use rdkafka::types::RDKafkaErrorCode;
 use rdkafka::util::Timeout;
 use rdkafka::{ClientContext, Message, Offset};
+use regex::Regex;
 use serde_json::json;
 use tokio::task::spawn_blocking;
 use tracing::{debug, info, warn};
 
 use crate::models::RawDocBatch;
-use crate::source::{IndexerMessage, Source, SourceContext, TypedSourceFactory};
+use crate::source::debezium::unwrap_debezium_envelope;
+use crate::source::kafka_avro::AvroDecoder;
+use crate::source::kafka_consumer_group::{
+    ConsumerGroupEvent, ConsumerGroupMember, TopicPartition,
+};
+use crate::source::kafka_oauth::{
+    configure_oauthbearer, set_oauthbearer_token, OAuthBearerToken, OAuthBearerTokenRefresher,
+};
+use crate::source::{
+    backpressure, IndexerMessage, ProtobufDecoder, Source, SourceContext, TypedSourceFactory,
+};
 
 /// We try to emit chewable batches for the indexer.
 /// One batch = one message to the indexer actor.
@@ -55,6 +65,7 @@ use crate::source::{IndexerMessage, Source, SourceContext, TypedSourceFactory};
 /// - we will not have a precise control of the timeout before commit.
 ///
 /// 5MB seems like a good one size fits all value.
+/// Batches shrink below it when the indexer falls behind: see `backpressure`.
 const TARGET_BATCH_NUM_BYTES: u64 = 5_000_000;
 
 /// Factory for instantiating a `KafkaSource`.
@@ -95,11 +106,12 @@ type KafkaSourceConsumer = StreamConsumer<KafkaSourceContext>;
 
 #[derive(Default)]
 pub struct KafkaSourceState {
-    /// Partitions IDs assigned to the source.
-    pub assigned_partition_ids: HashMap<i32, PartitionId>,
-    /// Offset for each partition of the last message received.
-    pub current_positions: HashMap<i32, Position>,
-    /// Number of active partitions, i.e., that have not reached EOF.
+    /// Partitions IDs assigned to the source, per topic and Kafka partition.
+    pub assigned_partition_ids: HashMap<String, HashMap<i32, PartitionId>>,
+    /// Position of the last message received for each partition.
+    pub current_positions: HashMap<PartitionId, Position>,
+    /// Number of active partitions, i.e., that have not reached EOF, or that are assigned to the
+    /// source by the consumer group.
     pub num_active_partitions: usize,
     /// Number of bytes processed by the source.
     pub num_bytes_processed: u64,
@@ -109,16 +121,95 @@ pub struct KafkaSourceState {
     pub num_invalid_messages: u64,
 }
 
-/// A `KafkaSource` consumes a topic and forwards its messages to an `Indexer`.
+/// Topics consumed by a `KafkaSource`.
+enum TopicSubscription {
+    /// A single topic, whose partition IDs are the Kafka partition numbers.
+    Topic(String),
+    /// The topics matching a pattern, whose partition IDs are of the form `<topic>/<partition>`
+    /// so that each topic is checkpointed separately.
+    Pattern {
+        topic_pattern: String,
+        topic_regex: Regex,
+        discovery_interval: Duration,
+        next_discovery_at: Instant,
+    },
+}
+
+impl TopicSubscription {
+    fn partition_id(&self, topic: &str, partition: i32) -> PartitionId {
+        match self {
+            TopicSubscription::Topic(_) => PartitionId::from(partition),
+            TopicSubscription::Pattern { .. } => {
+                PartitionId::from(format!("{}/{:0>10}", topic, partition))
+            }
+        }
+    }
+
+    /// Returns the regex of the topics to discover if the discovery interval has elapsed.
+    fn poll_discovery(&mut self) -> Option<Regex> {
+        match self {
+            TopicSubscription::Topic(_) => None,
+            TopicSubscription::Pattern {
+                topic_regex,
+                discovery_interval,
+                next_discovery_at,
+                ..
+            } => {
+                let now = Instant::now();
+                if now < *next_discovery_at {
+                    return None;
+                }
+                *next_discovery_at = now + *discovery_interval;
+                Some(topic_regex.clone())
+            }
+        }
+    }
+}
+
+impl fmt::Display for TopicSubscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TopicSubscription::Topic(topic) => write!(f, "topic:{}", topic),
+            TopicSubscription::Pattern { topic_pattern, .. } => {
+                write!(f, "topic_pattern:{}", topic_pattern)
+            }
+        }
+    }
+}
+
+/// Consumer of a `KafkaSource`.
+enum SourceConsumer {
+    /// Consumes all the partitions, assigned from the checkpoint.
+    Standalone(Arc<KafkaSourceConsumer>),
+    /// Consumes the partitions assigned to the source by the consumer group, shared with the
+    /// other pipelines of the source.
+    ConsumerGroup(ConsumerGroupMember),
+}
+
+/// A `KafkaSource` consumes a topic, or the topics matching a pattern, and forwards their
+/// messages to an `Indexer`.
 pub struct KafkaSource {
-    topic: String,
-    consumer: Arc<KafkaSourceConsumer>,
+    subscription: TopicSubscription,
+    consumer: SourceConsumer,
+    debezium_opt: Option<DebeziumParams>,
+    /// Decodes the Avro records of the messages, if any, into JSON documents.
+    avro_decoder_opt: Option<AvroDecoder>,
+    /// Decodes the protobuf messages, if any, into JSON documents.
+    protobuf_decoder_opt: Option<ProtobufDecoder>,
+    /// Offsets of the checkpoint for the topics that are not assigned yet. A member of a
+    /// consumer group keeps the offsets of all the topics, since the committed offsets of the
+    /// partitions it is assigned may lag behind the checkpoint.
+    pending_checkpoints: HashMap<String, HashMap<i32, i64>>,
+    /// Offsets from which the assigned partitions were first read.
+    initial_offsets: HashMap<PartitionId, Offset>,
+    /// Renews the SASL/OAUTHBEARER token of the consumer, if any.
+    token_refresher_opt: Option<OAuthBearerTokenRefresher>,
     state: KafkaSourceState,
 }
 
 impl fmt::Debug for KafkaSource {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "KafkaSource {{ topic:{} }}", self.topic)
+        write!(f, "KafkaSource {{ {} }}", self.subscription)
     }
 }
 
@@ -128,112 +219,371 @@ impl KafkaSource {
         params: KafkaSourceParams,
         checkpoint: SourceCheckpoint,
     ) -> anyhow::Result<KafkaSource> {
-        let topic = params.topic;
-        let consumer = create_consumer(params.client_log_level, params.client_params)?;
-        let partition_ids = fetch_partition_ids(consumer.clone(), &topic).await?;
-        let assigned_partition_ids = partition_ids
-            .iter()
-            .map(|partition_id| {
-                (
-                    partition_id.clone(),
-                    PartitionId::from(partition_id.clone()),
-                )
-            })
-            .collect();
+        let topic_regex_opt = params.topic_regex()?;
+        let discovery_interval = params.topic_discovery_interval();
+        let (token_refresher_opt, token_opt) = match params.oauthbearer {
+            Some(oauthbearer_params) => {
+                let (token_refresher, token) =
+                    OAuthBearerTokenRefresher::try_new(oauthbearer_params).await?;
+                (Some(token_refresher), Some(token))
+            }
+            None => (None, None),
+        };
+        let (avro_decoder_opt, protobuf_decoder_opt) = match params.decoding {
+            Some(KafkaDecodingParams::Avro {
+                schema_registry_url,
+                schema_registry_username,
+                schema_registry_password,
+            }) => {
+                let avro_decoder = AvroDecoder::new(
+                    &schema_registry_url,
+                    schema_registry_username,
+                    schema_registry_password,
+                );
+                (Some(avro_decoder), None)
+            }
+            Some(KafkaDecodingParams::Protobuf(protobuf_params)) => {
+                let protobuf_decoder = ProtobufDecoder::load(&protobuf_params).await?;
+                (None, Some(protobuf_decoder))
+            }
+            None => (None, None),
+        };
+        if params.enable_rebalancing {
+            let (subscription, pending_checkpoints) = match topic_regex_opt {
+                Some(topic_regex) => {
+                    let subscription = TopicSubscription::Pattern {
+                        topic_pattern: params.topic_pattern.unwrap_or_default(),
+                        topic_regex,
+                        discovery_interval,
+                        next_discovery_at: Instant::now(),
+                    };
+                    let pending_checkpoints =
+                        kafka_checkpoints_per_topic_from_checkpoint(&checkpoint)?;
+                    (subscription, pending_checkpoints)
+                }
+                None => {
+                    let kafka_checkpoint = kafka_checkpoint_from_checkpoint(&checkpoint)?;
+                    let pending_checkpoints =
+                        HashMap::from([(params.topic.clone(), kafka_checkpoint)]);
+                    (TopicSubscription::Topic(params.topic), pending_checkpoints)
+                }
+            };
+            let consumer_group = join_consumer_group(
+                &subscription,
+                params.client_log_level,
+                params.client_params,
+                token_opt.as_ref(),
+            )?;
+            return Ok(KafkaSource {
+                subscription,
+                consumer: SourceConsumer::ConsumerGroup(consumer_group),
+                debezium_opt: params.debezium,
+                avro_decoder_opt,
+                protobuf_decoder_opt,
+                pending_checkpoints,
+                initial_offsets: HashMap::new(),
+                token_refresher_opt,
+                state: KafkaSourceState::default(),
+            });
+        }
+        let consumer = create_consumer(
+            params.client_log_level,
+            params.client_params,
+            token_opt.as_ref(),
+        )?;
+        let (subscription, pending_checkpoints, topics) = match topic_regex_opt {
+            Some(topic_regex) => {
+                let topics = fetch_matching_topics(consumer.clone(), topic_regex.clone()).await?;
+                let subscription = TopicSubscription::Pattern {
+                    topic_pattern: params.topic_pattern.unwrap_or_default(),
+                    topic_regex,
+                    discovery_interval,
+                    next_discovery_at: Instant::now() + discovery_interval,
+                };
+                let pending_checkpoints = kafka_checkpoints_per_topic_from_checkpoint(&checkpoint)?;
+                (subscription, pending_checkpoints, topics)
+            }
+            None => {
+                let topic = params.topic;
+                let partition_ids = fetch_partition_ids(consumer.clone(), &topic).await?;
+                let kafka_checkpoint = kafka_checkpoint_from_checkpoint(&checkpoint)?;
+                let pending_checkpoints = HashMap::from([(topic.clone(), kafka_checkpoint)]);
+                let topics = vec![(topic.clone(), partition_ids)];
+                (TopicSubscription::Topic(topic), pending_checkpoints, topics)
+            }
+        };
+        let mut kafka_source = KafkaSource {
+            subscription,
+            consumer: SourceConsumer::Standalone(consumer.clone()),
+            debezium_opt: params.debezium,
+            avro_decoder_opt,
+            protobuf_decoder_opt,
+            pending_checkpoints,
+            initial_offsets: HashMap::new(),
+            token_refresher_opt,
+            state: KafkaSourceState::default(),
+        };
+        kafka_source.assign_topics(consumer, topics).await?;
+        Ok(kafka_source)
+    }
+
+    /// Assigns the partitions of new topics to the consumer, resuming from the checkpoint. The
+    /// client does not support incremental assignments, so the partitions already assigned are
+    /// assigned again, from their current positions.
+    async fn assign_topics(
+        &mut self,
+        consumer: Arc<KafkaSourceConsumer>,
+        topics: Vec<(String, Vec<i32>)>,
+    ) -> anyhow::Result<()> {
         let timeout = Duration::from_secs(30);
-        let watermarks =
-            fetch_watermarks(consumer.clone(), &topic, &partition_ids, timeout).await?;
-        let kafka_checkpoint = kafka_checkpoint_from_checkpoint(&checkpoint)?;
-        let assignment =
-            compute_assignment(&topic, &partition_ids, &kafka_checkpoint, &watermarks)?;
-
-        debug!(
-            topic = %topic,
-            assignment = ?assignment,
-            "Starting Kafka source."
-        );
+        for (topic, partition_ids) in topics {
+            let watermarks =
+                fetch_watermarks(consumer.clone(), &topic, &partition_ids, timeout).await?;
+            let kafka_checkpoint = self.pending_checkpoints.remove(&topic).unwrap_or_default();
+            let topic_assignment =
+                compute_assignment(&topic, &partition_ids, &kafka_checkpoint, &watermarks)?;
+            debug!(
+                topic = %topic,
+                assignment = ?topic_assignment,
+                "Starting Kafka source."
+            );
+            let topic_partition_ids = self
+                .state
+                .assigned_partition_ids
+                .entry(topic.clone())
+                .or_default();
+            for element in topic_assignment.elements() {
+                let partition_id = self.subscription.partition_id(&topic, element.partition());
+                self.initial_offsets
+                    .insert(partition_id.clone(), element.offset());
+                topic_partition_ids.insert(element.partition(), partition_id);
+            }
+            self.state.num_active_partitions += partition_ids.len();
+        }
+        let mut assignment = TopicPartitionList::with_capacity(self.initial_offsets.len());
+        for (topic, topic_partition_ids) in &self.state.assigned_partition_ids {
+            for (&partition, partition_id) in topic_partition_ids {
+                let offset = match self.state.current_positions.get(partition_id) {
+                    Some(Position::Offset(offset_str)) => Offset::Offset(
+                        offset_str
+                            .parse::<i64>()
+                            .with_context(|| format!("Failed to parse offset `{}`.", offset_str))?
+                            + 1,
+                    ),
+                    _ => self.initial_offsets[partition_id],
+                };
+                let _ = assignment.add_partition_offset(topic, partition, offset)?;
+            }
+        }
         consumer
             .assign(&assignment)
             .context("Failed to resume from checkpoint.")?;
+        Ok(())
+    }
+
+    /// Assigns the topics matching the pattern that are not assigned yet. The new partitions of
+    /// the topics already assigned are not picked up.
+    async fn discover_topics(
+        &mut self,
+        consumer: Arc<KafkaSourceConsumer>,
+        topic_regex: Regex,
+    ) -> anyhow::Result<()> {
+        let new_topics: Vec<(String, Vec<i32>)> =
+            fetch_matching_topics(consumer.clone(), topic_regex)
+                .await?
+                .into_iter()
+                .filter(|(topic, _)| !self.state.assigned_partition_ids.contains_key(topic))
+                .collect();
+        if new_topics.is_empty() {
+            return Ok(());
+        }
+        info!(
+            topics = ?new_topics.iter().map(|(topic, _)| topic).collect::<Vec<_>>(),
+            "Discovered new topics."
+        );
+        self.assign_topics(consumer, new_topics).await
+    }
 
-        let state = KafkaSourceState {
-            assigned_partition_ids,
-            num_active_partitions: partition_ids.len(),
-            ..Default::default()
+    /// Starts tracking the partitions assigned to the source by the consumer group.
+    fn assign_partitions(&mut self, topic_partitions: Vec<TopicPartition>) {
+        for (topic, partition) in topic_partitions {
+            let partition_id = self.subscription.partition_id(&topic, partition);
+            let topic_partition_ids = self.state.assigned_partition_ids.entry(topic).or_default();
+            if topic_partition_ids
+                .insert(partition, partition_id)
+                .is_none()
+            {
+                self.state.num_active_partitions += 1;
+            }
+        }
+    }
+
+    /// Stops tracking the partitions revoked by the consumer group. Their messages are published
+    /// by then, so their next owner resumes from the committed offsets.
+    fn revoke_partitions(&mut self, topic_partitions: Vec<TopicPartition>) {
+        for (topic, partition) in topic_partitions {
+            let topic_partition_ids = if let Some(topic_partition_ids) =
+                self.state.assigned_partition_ids.get_mut(&topic)
+            {
+                topic_partition_ids
+            } else {
+                continue;
+            };
+            if let Some(partition_id) = topic_partition_ids.remove(&partition) {
+                self.state.current_positions.remove(&partition_id);
+                self.state.num_active_partitions -= 1;
+            }
+            if topic_partition_ids.is_empty() {
+                self.state.assigned_partition_ids.remove(&topic);
+            }
+        }
+    }
+
+    /// Appends the payload of the message to `docs` and records its position in
+    /// `checkpoint_delta`. Returns the number of bytes of the message.
+    fn process_message<M: Message>(
+        &mut self,
+        message: &M,
+        docs: &mut Vec<String>,
+        checkpoint_delta: &mut CheckpointDelta,
+    ) -> anyhow::Result<u64> {
+        let partition_id_opt = self
+            .state
+            .assigned_partition_ids
+            .get(message.topic())
+            .and_then(|topic_partition_ids| topic_partition_ids.get(&message.partition()))
+            .cloned();
+        let partition_id = match (partition_id_opt, &self.consumer) {
+            (Some(partition_id), _) => partition_id,
+            (None, SourceConsumer::ConsumerGroup(_)) => {
+                warn!(
+                    topic = %message.topic(),
+                    partition = %message.partition(),
+                    "Ignoring message from a partition not assigned to the source."
+                );
+                return Ok(0);
+            }
+            (None, SourceConsumer::Standalone(_)) => bail!(
+                "Received unexpected message from partition `{}` of topic `{}`.",
+                message.partition(),
+                message.topic(),
+            ),
         };
-        Ok(KafkaSource {
-            topic,
-            consumer,
-            state,
-        })
+        if let SourceConsumer::ConsumerGroup(consumer_group) = &self.consumer {
+            // The committed offsets may lag behind the checkpoint, for instance when the source
+            // previously consumed the topic without a consumer group.
+            let checkpoint_offset_opt = self
+                .pending_checkpoints
+                .get(message.topic())
+                .and_then(|kafka_checkpoint| kafka_checkpoint.get(&message.partition()));
+            if !self.state.current_positions.contains_key(&partition_id)
+                && matches!(checkpoint_offset_opt, Some(&offset) if message.offset() <= offset)
+            {
+                consumer_group.record_skipped(
+                    (message.topic().to_string(), message.partition()),
+                    message.offset(),
+                );
+                return Ok(0);
+            }
+        }
+        let payload_opt = match (&self.avro_decoder_opt, &self.protobuf_decoder_opt) {
+            (Some(avro_decoder), _) => {
+                decode_message_payload(message, "Avro", |payload| avro_decoder.decode(payload))
+            }
+            (None, Some(protobuf_decoder)) => {
+                decode_message_payload(message, "protobuf", |payload| {
+                    protobuf_decoder.decode(payload)
+                })
+            }
+            (None, None) => parse_message_payload(message),
+        };
+        match (payload_opt, &self.debezium_opt) {
+            (Some(doc), None) => docs.push(doc),
+            (Some(payload), Some(debezium)) => match unwrap_debezium_envelope(&payload, debezium) {
+                Ok(Some(doc)) => docs.push(doc),
+                Ok(None) => {}
+                Err(error) => {
+                    warn!(
+                        topic = ?message.topic(),
+                        partition = ?message.partition(),
+                        offset = ?message.offset(),
+                        error = ?error,
+                        "Failed to unwrap Debezium change event."
+                    );
+                    self.state.num_invalid_messages += 1;
+                }
+            },
+            // Debezium follows each delete event with a tombstone for log compaction.
+            (None, Some(_)) if message.payload().is_none() => {}
+            (None, _) => self.state.num_invalid_messages += 1,
+        }
+        let num_bytes = message.payload_len() as u64;
+        self.state.num_bytes_processed += num_bytes;
+        self.state.num_messages_processed += 1;
+
+        let current_position = Position::from(message.offset());
+        let previous_position = self
+            .state
+            .current_positions
+            .insert(partition_id.clone(), current_position.clone())
+            .unwrap_or_else(|| previous_position_for_offset(message.offset()));
+        checkpoint_delta
+            .record_partition_delta(partition_id, previous_position, current_position)
+            .context("Failed to record partition delta.")?;
+        Ok(num_bytes)
     }
-}
 
-#[async_trait]
-impl Source for KafkaSource {
-    async fn emit_batches(
+    async fn emit_standalone_batches(
         &mut self,
+        consumer: Arc<KafkaSourceConsumer>,
         batch_sink: &Mailbox<IndexerMessage>,
         ctx: &SourceContext,
     ) -> Result<(), ActorExitStatus> {
+        if let Some(topic_regex) = self.subscription.poll_discovery() {
+            let _protect_guard = ctx.protect_zone();
+            if let Err(error) = self.discover_topics(consumer.clone(), topic_regex).await {
+                warn!(
+                    subscription = %self.subscription,
+                    error = ?error,
+                    "Failed to discover topics."
+                );
+            }
+        }
         let mut docs = Vec::new();
         let mut checkpoint_delta = CheckpointDelta::default();
 
-        let deadline = tokio::time::sleep(quickwit_actors::HEARTBEAT / 2);
-        let mut message_stream = Box::pin(self.consumer.stream().take_until(deadline));
+        let deadline = ctx.sleep(quickwit_actors::HEARTBEAT / 2);
+        let mut message_stream = Box::pin(consumer.stream().take_until(deadline));
 
         let mut batch_num_bytes = 0;
+        let target_batch_num_bytes =
+            backpressure::target_batch_num_bytes(batch_sink, TARGET_BATCH_NUM_BYTES);
 
         while let Some(message_res) = message_stream.next().await {
             let message = match message_res {
                 Ok(message) => message,
                 Err(KafkaError::PartitionEOF(partition_id)) => {
-                    self.state.num_active_partitions -= 1;
-                    info!(
-                        topic = %self.topic,
-                        partition_id = ?partition_id,
-                        num_active_partitions = ?self.state.num_active_partitions,
-                        "Reached end of partition."
-                    );
+                    // The EOF errors do not tell the topic of the partition, and new topics can
+                    // show up anyway: a source consuming a topic pattern never reaches its end.
+                    if let TopicSubscription::Topic(topic) = &self.subscription {
+                        self.state.num_active_partitions -= 1;
+                        info!(
+                            topic = %topic,
+                            partition_id = ?partition_id,
+                            num_active_partitions = ?self.state.num_active_partitions,
+                            "Reached end of partition."
+                        );
+                    }
                     continue;
                 }
                 // FIXME: This is assuming that Kafka errors are not recoverable, it may not be the
                 // case.
                 Err(err) => return Err(ActorExitStatus::from(anyhow::anyhow!(err))),
             };
-            if let Some(doc) = parse_message_payload(&message) {
-                docs.push(doc);
-            } else {
-                self.state.num_invalid_messages += 1;
-            }
-            batch_num_bytes += message.payload_len() as u64;
-            self.state.num_bytes_processed += message.payload_len() as u64;
-            self.state.num_messages_processed += 1;
+            self.resolve_avro_schema(message.payload(), ctx).await?;
+            batch_num_bytes += self.process_message(&message, &mut docs, &mut checkpoint_delta)?;
 
-            let partition_id = self
-                .state
-                .assigned_partition_ids
-                .get(&message.partition())
-                .ok_or_else(|| {
-                    anyhow::anyhow!(
-                        "Received unexpected message from partition `{}`. Assigned partitions: \
-                         `{{{}}}`.",
-                        message.partition(),
-                        self.state.assigned_partition_ids.keys().join(", "),
-                    )
-                })?
-                .clone();
-            let current_position = Position::from(message.offset());
-            let previous_position = self
-                .state
-                .current_positions
-                .insert(message.partition(), current_position.clone())
-                .unwrap_or_else(|| previous_position_for_offset(message.offset()));
-            checkpoint_delta
-                .record_partition_delta(partition_id, previous_position, current_position)
-                .context("Failed to record partition delta.")?;
-
-            if batch_num_bytes >= TARGET_BATCH_NUM_BYTES {
+            if batch_num_bytes >= target_batch_num_bytes {
                 break;
             }
         }
@@ -245,43 +595,235 @@ impl Source for KafkaSource {
             ctx.send_message(batch_sink, IndexerMessage::from(batch))
                 .await?;
         }
-        if self.state.num_active_partitions == 0 {
-            info!(topic = %self.topic, "Reached end of topic.");
-            ctx.send_exit_with_success(batch_sink).await?;
-            return Err(ActorExitStatus::Success);
+        if let TopicSubscription::Topic(topic) = &self.subscription {
+            if self.state.num_active_partitions == 0 {
+                info!(topic = %topic, "Reached end of topic.");
+                ctx.send_exit_with_success(batch_sink).await?;
+                return Err(ActorExitStatus::Success);
+            }
+        }
+        Ok(())
+    }
+
+    /// Emits the messages of the partitions assigned to the source by the consumer group. Unlike
+    /// a standalone source, a member of a consumer group never reaches the end of its topic.
+    async fn emit_consumer_group_batches(
+        &mut self,
+        batch_sink: &Mailbox<IndexerMessage>,
+        ctx: &SourceContext,
+    ) -> Result<(), ActorExitStatus> {
+        let mut docs = Vec::new();
+        let mut checkpoint_delta = CheckpointDelta::default();
+
+        let deadline = ctx.sleep(quickwit_actors::HEARTBEAT / 2);
+        tokio::pin!(deadline);
+
+        let mut batch_num_bytes = 0;
+        let target_batch_num_bytes =
+            backpressure::target_batch_num_bytes(batch_sink, TARGET_BATCH_NUM_BYTES);
+
+        loop {
+            let consumer_group = match &mut self.consumer {
+                SourceConsumer::ConsumerGroup(consumer_group) => consumer_group,
+                SourceConsumer::Standalone(_) => unreachable!(),
+            };
+            let event = tokio::select! {
+                event_opt = consumer_group.recv() => event_opt.ok_or_else(|| {
+                    anyhow::anyhow!("Kafka consumer thread stopped unexpectedly.")
+                })?,
+                _ = &mut deadline => break,
+            };
+            match event {
+                ConsumerGroupEvent::Assign(topic_partitions) => {
+                    self.assign_partitions(topic_partitions)
+                }
+                ConsumerGroupEvent::Revoke(topic_partitions) => {
+                    self.revoke_partitions(topic_partitions)
+                }
+                ConsumerGroupEvent::Message(message) => {
+                    self.resolve_avro_schema(message.payload(), ctx).await?;
+                    batch_num_bytes +=
+                        self.process_message(&message, &mut docs, &mut checkpoint_delta)?;
+                    if batch_num_bytes >= target_batch_num_bytes {
+                        break;
+                    }
+                }
+                // FIXME: This is assuming that Kafka errors are not recoverable, it may not be the
+                // case.
+                ConsumerGroupEvent::Error(err) => {
+                    return Err(ActorExitStatus::from(anyhow::anyhow!(err)))
+                }
+            }
+        }
+        if !checkpoint_delta.is_empty() {
+            let batch = RawDocBatch {
+                docs,
+                checkpoint_delta,
+            };
+            ctx.send_message(batch_sink, IndexerMessage::from(batch))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Fetches the Avro schema of a message from the registry before it is processed. Failing to
+    /// reach the registry stops the source rather than skipping the message.
+    async fn resolve_avro_schema(
+        &mut self,
+        payload_opt: Option<&[u8]>,
+        ctx: &SourceContext,
+    ) -> anyhow::Result<()> {
+        if let Some(avro_decoder) = &mut self.avro_decoder_opt {
+            let _protect_guard = ctx.protect_zone();
+            avro_decoder.resolve_schema(payload_opt).await?;
         }
         Ok(())
     }
 
+    /// Hands a new SASL/OAUTHBEARER token to the consumer once the current one is due for
+    /// renewal.
+    async fn refresh_oauthbearer_token(&mut self) {
+        let token_refresher = match &mut self.token_refresher_opt {
+            Some(token_refresher) => token_refresher,
+            None => return,
+        };
+        let token = match token_refresher.poll_refresh().await {
+            Some(token) => token,
+            None => return,
+        };
+        let set_token_result = match &self.consumer {
+            SourceConsumer::Standalone(consumer) => {
+                set_oauthbearer_token(consumer.client(), &token)
+            }
+            SourceConsumer::ConsumerGroup(consumer_group) => {
+                consumer_group.set_oauthbearer_token(&token)
+            }
+        };
+        if let Err(error) = set_token_result {
+            token_refresher.retry_later(&error);
+        }
+    }
+
+    fn current_offset(&self, partition_id: &PartitionId) -> Option<i64> {
+        match self.state.current_positions.get(partition_id)? {
+            Position::Offset(offset_str) => offset_str.parse::<i64>().ok(),
+            Position::Beginning => None,
+        }
+    }
+}
+
+#[async_trait]
+impl Source for KafkaSource {
+    async fn emit_batches(
+        &mut self,
+        batch_sink: &Mailbox<IndexerMessage>,
+        ctx: &SourceContext,
+    ) -> Result<(), ActorExitStatus> {
+        self.refresh_oauthbearer_token().await;
+        match &self.consumer {
+            SourceConsumer::Standalone(consumer) => {
+                let consumer = consumer.clone();
+                self.emit_standalone_batches(consumer, batch_sink, ctx)
+                    .await
+            }
+            SourceConsumer::ConsumerGroup(_) => {
+                self.emit_consumer_group_batches(batch_sink, ctx).await
+            }
+        }
+    }
+
+    /// Commits the offsets published to the consumer group, so that the next owner of a
+    /// partition resumes from there.
+    async fn suggest_truncate(
+        &mut self,
+        checkpoint: SourceCheckpoint,
+        _ctx: &SourceContext,
+    ) -> anyhow::Result<()> {
+        let consumer_group = match &self.consumer {
+            SourceConsumer::ConsumerGroup(consumer_group) => consumer_group,
+            SourceConsumer::Standalone(_) => return Ok(()),
+        };
+        let mut published_offsets = Vec::new();
+        for (topic, topic_partition_ids) in &self.state.assigned_partition_ids {
+            for (&partition, partition_id) in topic_partition_ids {
+                if let Some(Position::Offset(offset_str)) =
+                    checkpoint.position_for_partition(partition_id)
+                {
+                    let offset = offset_str
+                        .parse::<i64>()
+                        .with_context(|| format!("Failed to parse offset `{}`.", offset_str))?;
+                    published_offsets.push(((topic.clone(), partition), offset));
+                }
+            }
+        }
+        consumer_group.commit_published(published_offsets).await
+    }
+
     fn name(&self) -> String {
         "KafkaSource".to_string()
     }
 
     fn observable_state(&self) -> serde_json::Value {
-        let assigned_partition_ids: Vec<&i32> =
-            self.state.assigned_partition_ids.keys().sorted().collect();
-        let current_positions: Vec<(&i32, i64)> = self
+        let partitions: Vec<(&str, i32, &PartitionId)> = self
             .state
-            .current_positions
+            .assigned_partition_ids
             .iter()
-            .filter_map(|(partition_id, position)| match position {
-                Position::Offset(offset_str) => offset_str
-                    .parse::<i64>()
-                    .ok()
-                    .map(|offset| (partition_id, offset)),
-                Position::Beginning => None,
+            .flat_map(|(topic, topic_partition_ids)| {
+                topic_partition_ids
+                    .iter()
+                    .map(move |(&partition, partition_id)| {
+                        (topic.as_str(), partition, partition_id)
+                    })
             })
             .sorted()
             .collect();
-        json!({
-            "topic": self.topic,
-            "assigned_partition_ids": assigned_partition_ids,
-            "current_positions": current_positions,
+        let mut state = json!({
             "num_active_partitions": self.state.num_active_partitions,
             "num_bytes_processed": self.state.num_bytes_processed,
             "num_messages_processed": self.state.num_messages_processed,
             "num_invalid_messages": self.state.num_invalid_messages,
-        })
+        });
+        match &self.subscription {
+            TopicSubscription::Topic(topic) => {
+                let assigned_partition_ids: Vec<i32> = partitions
+                    .iter()
+                    .map(|&(_, partition, _)| partition)
+                    .collect();
+                let current_positions: Vec<(i32, i64)> = partitions
+                    .iter()
+                    .filter_map(|&(_, partition, partition_id)| {
+                        self.current_offset(partition_id)
+                            .map(|offset| (partition, offset))
+                    })
+                    .collect();
+                state["topic"] = json!(topic);
+                state["assigned_partition_ids"] = json!(assigned_partition_ids);
+                state["current_positions"] = json!(current_positions);
+            }
+            TopicSubscription::Pattern { topic_pattern, .. } => {
+                let topics: Vec<&str> = partitions
+                    .iter()
+                    .map(|&(topic, _, _)| topic)
+                    .dedup()
+                    .collect();
+                let assigned_partition_ids: Vec<&str> = partitions
+                    .iter()
+                    .map(|&(_, _, partition_id)| partition_id.0.as_str())
+                    .collect();
+                let current_positions: Vec<(&str, i64)> = partitions
+                    .iter()
+                    .filter_map(|&(_, _, partition_id)| {
+                        self.current_offset(partition_id)
+                            .map(|offset| (partition_id.0.as_str(), offset))
+                    })
+                    .collect();
+                state["topic_pattern"] = json!(topic_pattern);
+                state["topics"] = json!(topics);
+                state["assigned_partition_ids"] = json!(assigned_partition_ids);
+                state["current_positions"] = json!(current_positions);
+            }
+        }
+        state
     }
 }
 
@@ -294,24 +836,179 @@ fn previous_position_for_offset(offset: i64) -> Position {
     }
 }
 
+/// Joins the consumer group of the source, with cooperative rebalancing so that the partitions
+/// that stay assigned to a member keep being consumed while the others are moved.
+fn join_consumer_group(
+    subscription: &TopicSubscription,
+    client_log_level: Option<String>,
+    client_params: serde_json::Value,
+    token_opt: Option<&OAuthBearerToken>,
+) -> anyhow::Result<ConsumerGroupMember> {
+    let log_level = parse_client_log_level(client_log_level)?;
+    let mut client_config = parse_client_params(client_params)?;
+    if token_opt.is_some() {
+        configure_oauthbearer(&mut client_config);
+    }
+    for (key, default_value) in [
+        ("partition.assignment.strategy", "cooperative-sticky"),
+        ("auto.offset.reset", "earliest"),
+    ] {
+        if client_config.get(key).is_none() {
+            client_config.set(key, default_value);
+        }
+    }
+    // The assignment of a member changes over time: the end of a partition means nothing.
+    client_config.set("enable.partition.eof", "false");
+    client_config.set_log_level(log_level);
+    // librdkafka treats the subscriptions starting with `^` as POSIX regexes.
+    let topics = match subscription {
+        TopicSubscription::Topic(topic) => topic.clone(),
+        TopicSubscription::Pattern { topic_pattern, .. } => format!("^({})$", topic_pattern),
+    };
+    ConsumerGroupMember::join(&client_config, &topics, token_opt)
+}
+
 /// Checks if connecting with the given parameters works.
 pub(super) async fn check_connectivity(params: KafkaSourceParams) -> anyhow::Result<()> {
-    let consumer = create_consumer(params.client_log_level, params.client_params)?;
-    fetch_partition_ids(consumer, &params.topic).await?;
+    let topic_regex_opt = params.topic_regex()?;
+    let token_opt = match params.oauthbearer {
+        Some(oauthbearer_params) => {
+            let (_token_refresher, token) =
+                OAuthBearerTokenRefresher::try_new(oauthbearer_params).await?;
+            Some(token)
+        }
+        None => None,
+    };
+    let consumer = create_consumer(
+        params.client_log_level,
+        params.client_params,
+        token_opt.as_ref(),
+    )?;
+    match topic_regex_opt {
+        Some(topic_regex) => {
+            fetch_matching_topics(consumer, topic_regex).await?;
+        }
+        None => {
+            fetch_partition_ids(consumer, &params.topic).await?;
+        }
+    }
     Ok(())
 }
 
-/// Creates a new `KafkaSourceConsumer`.
+/// Computes the checkpoint from which the source reads the messages produced at or after
+/// `timestamp`, expressed in seconds, on every partition of its topics. The partitions without
+/// such messages are read from their end.
+pub(super) async fn checkpoint_at_timestamp(
+    params: KafkaSourceParams,
+    timestamp: i64,
+) -> anyhow::Result<SourceCheckpoint> {
+    let topic_regex_opt = params.topic_regex()?;
+    let discovery_interval = params.topic_discovery_interval();
+    let token_opt = match params.oauthbearer {
+        Some(oauthbearer_params) => {
+            let (_token_refresher, token) =
+                OAuthBearerTokenRefresher::try_new(oauthbearer_params).await?;
+            Some(token)
+        }
+        None => None,
+    };
+    let consumer = create_consumer(
+        params.client_log_level,
+        params.client_params,
+        token_opt.as_ref(),
+    )?;
+    let (subscription, topics) = match topic_regex_opt {
+        Some(topic_regex) => {
+            let topics = fetch_matching_topics(consumer.clone(), topic_regex.clone()).await?;
+            let subscription = TopicSubscription::Pattern {
+                topic_pattern: params.topic_pattern.unwrap_or_default(),
+                topic_regex,
+                discovery_interval,
+                next_discovery_at: Instant::now(),
+            };
+            (subscription, topics)
+        }
+        None => {
+            let partition_ids = fetch_partition_ids(consumer.clone(), &params.topic).await?;
+            let topics = vec![(params.topic.clone(), partition_ids)];
+            (TopicSubscription::Topic(params.topic), topics)
+        }
+    };
+    let mut timestamps = TopicPartitionList::new();
+    for (topic, partition_ids) in &topics {
+        for &partition_id in partition_ids {
+            timestamps.add_partition_offset(
+                topic,
+                partition_id,
+                Offset::Offset(timestamp * 1000),
+            )?;
+        }
+    }
+    let consumer_clone = consumer.clone();
+    // The topic partition list is not `Send`: its elements are copied before awaiting.
+    let offsets: Vec<(String, i32, Offset)> = spawn_blocking(move || {
+        let offsets = consumer_clone
+            .offsets_for_times(timestamps, Duration::from_secs(10))
+            .context("Failed to fetch the offsets for the timestamp.")?;
+        let offsets = offsets
+            .elements()
+            .iter()
+            .map(|element| {
+                (
+                    element.topic().to_string(),
+                    element.partition(),
+                    element.offset(),
+                )
+            })
+            .collect();
+        Ok::<_, anyhow::Error>(offsets)
+    })
+    .await??;
+    let mut checkpoint = Vec::with_capacity(offsets.len());
+    for (topic, partition_id, offset) in offsets {
+        let position = match offset {
+            Offset::Offset(offset) => previous_position_for_offset(offset),
+            Offset::End => {
+                let (_low_watermark, high_watermark) = fetch_watermarks_for_partition_id(
+                    consumer.clone(),
+                    topic.clone(),
+                    partition_id,
+                    Duration::from_secs(10),
+                )
+                .await?;
+                previous_position_for_offset(high_watermark)
+            }
+            offset => bail!(
+                "Failed to fetch the offset of topic `{}` and partition `{}` for the timestamp: \
+                 unexpected offset `{:?}`.",
+                topic,
+                partition_id,
+                offset
+            ),
+        };
+        checkpoint.push((subscription.partition_id(&topic, partition_id), position));
+    }
+    Ok(checkpoint.into_iter().collect())
+}
+
+/// Creates a new `KafkaSourceConsumer`, authenticated with the SASL/OAUTHBEARER token, if any.
 fn create_consumer(
     client_log_level: Option<String>,
     client_params: serde_json::Value,
+    token_opt: Option<&OAuthBearerToken>,
 ) -> anyhow::Result<Arc<KafkaSourceConsumer>> {
     let log_level = parse_client_log_level(client_log_level)?;
     let mut client_config = parse_client_params(client_params)?;
+    if token_opt.is_some() {
+        configure_oauthbearer(&mut client_config);
+    }
     let consumer: KafkaSourceConsumer = client_config
         .set_log_level(log_level)
         .create_with_context(KafkaSourceContext)
         .context("Failed to create Kafka consumer.")?;
+    if let Some(token) = token_opt {
+        set_oauthbearer_token(consumer.client(), token)?;
+    }
     Ok(Arc::new(consumer))
 }
 
@@ -379,6 +1076,71 @@ fn kafka_checkpoint_from_checkpoint(
     Ok(kafka_checkpoint)
 }
 
+/// Represents the checkpoint of a source consuming a topic pattern with the Kafka native types,
+/// per topic. The partition IDs of such a source are of the form `<topic>/<partition>`.
+fn kafka_checkpoints_per_topic_from_checkpoint(
+    checkpoint: &SourceCheckpoint,
+) -> anyhow::Result<HashMap<String, HashMap<i32, i64>>> {
+    let mut kafka_checkpoints: HashMap<String, HashMap<i32, i64>> = HashMap::new();
+    for (partition_id, position) in checkpoint.iter() {
+        let (topic, partition_str) = partition_id.0.rsplit_once('/').with_context(|| {
+            format!(
+                "Failed to parse partition ID `{}` to a topic and a partition.",
+                partition_id.0
+            )
+        })?;
+        let partition_i32 = partition_str.parse::<i32>().with_context(|| {
+            format!("Failed to parse partition ID `{}` to i32.", partition_id.0)
+        })?;
+        let offset_i64 = match position {
+            Position::Beginning => continue,
+            Position::Offset(offset_str) => offset_str
+                .parse::<i64>()
+                .with_context(|| format!("Failed to parse offset `{}` to i64.", offset_str))?,
+        };
+        kafka_checkpoints
+            .entry(topic.to_string())
+            .or_default()
+            .insert(partition_i32, offset_i64);
+    }
+    Ok(kafka_checkpoints)
+}
+
+/// Retrieves the topics matching the regex along with their partition IDs, sorted by topic.
+/// Internal topics and topics without partitions are ignored.
+async fn fetch_matching_topics(
+    consumer: Arc<KafkaSourceConsumer>,
+    topic_regex: Regex,
+) -> anyhow::Result<Vec<(String, Vec<i32>)>> {
+    let timeout = Timeout::After(Duration::from_secs(5));
+    let cluster_metadata = spawn_blocking(move || {
+        consumer
+            .fetch_metadata(None, timeout)
+            .context("Failed to fetch cluster metadata.")
+    })
+    .await??;
+
+    let matching_topics = cluster_metadata
+        .topics()
+        .iter()
+        .filter(|topic_metadata| {
+            !topic_metadata.name().starts_with("__")
+                && !topic_metadata.partitions().is_empty()
+                && topic_regex.is_match(topic_metadata.name())
+        })
+        .map(|topic_metadata| {
+            let partition_ids = topic_metadata
+                .partitions()
+                .iter()
+                .map(|partition| partition.id())
+                .collect();
+            (topic_metadata.name().to_string(), partition_ids)
+        })
+        .sorted()
+        .collect();
+    Ok(matching_topics)
+}
+
 /// Retrieves the list of all partition IDs of a given topic.
 async fn fetch_partition_ids(
     consumer: Arc<KafkaSourceConsumer>,
@@ -527,9 +1289,44 @@ fn compute_next_offset(
     );
 }
 
-/// Converts the raw bytes of the message payload to a `String` skipping corrupted or empty
-/// messages.
-fn parse_message_payload(message: &BorrowedMessage) -> Option<String> {
+/// Decodes the raw bytes of an Avro or protobuf message payload into a document, skipping
+/// corrupted or empty messages.
+fn decode_message_payload<M: Message>(
+    message: &M,
+    format: &str,
+    decode_fn: impl Fn(&[u8]) -> anyhow::Result<String>,
+) -> Option<String> {
+    let payload = match message.payload() {
+        Some(payload) if !payload.is_empty() => payload,
+        _ => {
+            debug!(
+                topic = ?message.topic(),
+                partition = ?message.partition(),
+                offset = ?message.offset(),
+                timestamp = ?message.timestamp(),
+                "Message payload is empty."
+            );
+            return None;
+        }
+    };
+    match decode_fn(payload) {
+        Ok(doc) => Some(doc),
+        Err(error) => {
+            warn!(
+                topic = ?message.topic(),
+                partition = ?message.partition(),
+                offset = ?message.offset(),
+                timestamp = ?message.timestamp(),
+                format = %format,
+                error = ?error,
+                "Failed to decode message payload."
+            );
+            None
+        }
+    }
+}
+
+fn parse_message_payload<M: Message>(message: &M) -> Option<String> {
     match message.payload_view::<str>() {
         Some(Ok(payload)) if payload.len() > 0 => {
             let doc = payload.to_string();
@@ -596,6 +1393,35 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_kafka_checkpoints_per_topic_from_checkpoint() -> anyhow::Result<()> {
+        let checkpoint: SourceCheckpoint = vec![
+            ("orders.eu/0000000000", "00000000000000000042"),
+            ("orders.eu/0000000001", ""),
+            ("orders.us/0000000003", "00000000000000001337"),
+        ]
+        .into_iter()
+        .map(|(partition_id, offset)| {
+            let position = if offset.is_empty() {
+                Position::Beginning
+            } else {
+                Position::from(offset.to_string())
+            };
+            (PartitionId::from(partition_id), position)
+        })
+        .collect();
+        let kafka_checkpoints = kafka_checkpoints_per_topic_from_checkpoint(&checkpoint)?;
+        assert_eq!(kafka_checkpoints.len(), 2);
+        assert_eq!(kafka_checkpoints["orders.eu"], HashMap::from([(0, 42)]));
+        assert_eq!(kafka_checkpoints["orders.us"], HashMap::from([(3, 1337)]));
+
+        let checkpoint: SourceCheckpoint = vec![(PartitionId::from(0i32), Position::from(0u64))]
+            .into_iter()
+            .collect();
+        assert!(kafka_checkpoints_per_topic_from_checkpoint(&checkpoint).is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_compute_next_offset() -> anyhow::Result<()> {
         {
@@ -715,7 +1541,7 @@ mod kafka_broker_tests {
             "group.id": group_id,
             "enable.partition.eof": true,
         });
-        create_consumer(Some("info".to_string()), client_params)
+        create_consumer(Some("info".to_string()), client_params, None)
     }
 
     async fn populate_topic<K, M, J, Q>(
@@ -806,13 +1632,22 @@ mod kafka_broker_tests {
             source_id: "test-kafka-source".to_string(),
             source_params: SourceParams::Kafka(KafkaSourceParams {
                 topic: topic.clone(),
+                topic_pattern: None,
+                topic_discovery_interval_secs: None,
                 client_log_level: None,
                 client_params: json!({
                     "bootstrap.servers": bootstrap_servers,
                     "group.id": group_id,
                     "enable.partition.eof": true,
                 }),
+                debezium: None,
+                enable_rebalancing: false,
+                oauthbearer: None,
+                decoding: None,
             }),
+            filter: None,
+            routing: None,
+            transform: None,
         };
 
         let source_loader = quickwit_supported_sources();
@@ -822,10 +1657,7 @@ mod kafka_broker_tests {
             let source = source_loader
                 .load_source(source_config.clone(), checkpoint)
                 .await?;
-            let actor = SourceActor {
-                source,
-                batch_sink: sink.clone(),
-            };
+            let actor = SourceActor::new(source, sink.clone());
             let (_mailbox, handle) = universe.spawn_actor(actor).spawn_async();
             let (exit_status, exit_state) = handle.join().await;
             assert!(exit_status.is_success());
@@ -869,10 +1701,7 @@ mod kafka_broker_tests {
             let source = source_loader
                 .load_source(source_config.clone(), checkpoint)
                 .await?;
-            let actor = SourceActor {
-                source,
-                batch_sink: sink.clone(),
-            };
+            let actor = SourceActor::new(source, sink.clone());
             let (_mailbox, handle) = universe.spawn_actor(actor).spawn_async();
             let (exit_status, state) = handle.join().await;
             assert!(exit_status.is_success());
@@ -923,10 +1752,7 @@ mod kafka_broker_tests {
             let source = source_loader
                 .load_source(source_config.clone(), checkpoint)
                 .await?;
-            let actor = SourceActor {
-                source,
-                batch_sink: sink.clone(),
-            };
+            let actor = SourceActor::new(source, sink.clone());
             let (_mailbox, handle) = universe.spawn_actor(actor).spawn_async();
             let (exit_status, exit_state) = handle.join().await;
             assert!(exit_status.is_success());
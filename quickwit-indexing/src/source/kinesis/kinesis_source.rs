@@ -0,0 +1,501 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use quickwit_actors::{ActorExitStatus, ActorHandle, Mailbox};
+use quickwit_config::KinesisSourceParams;
+use quickwit_metastore::checkpoint::{CheckpointDelta, PartitionId, Position, SourceCheckpoint};
+use rusoto_core::Region;
+use rusoto_kinesis::{KinesisClient, Record};
+use serde_json::json;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+use super::api::{get_shard_iterator, list_shards};
+use super::resharding::{ShardAssignment, ShardLineage};
+use super::shard_consumer::{ShardConsumer, ShardConsumerMessage};
+use crate::models::RawDocBatch;
+use crate::source::{backpressure, IndexerMessage, Source, SourceContext, TypedSourceFactory};
+
+/// Same target as the Kafka source: see `kafka_source::TARGET_BATCH_NUM_BYTES`.
+const TARGET_BATCH_NUM_BYTES: u64 = 5_000_000;
+
+/// Number of messages that the shard consumers can send ahead of the source.
+const SHARD_CONSUMERS_CHANNEL_CAPACITY: usize = 100;
+
+/// Factory for instantiating a `KinesisSource`.
+pub struct KinesisSourceFactory;
+
+#[async_trait]
+impl TypedSourceFactory for KinesisSourceFactory {
+    type Source = KinesisSource;
+    type Params = KinesisSourceParams;
+
+    async fn typed_create_source(
+        params: KinesisSourceParams,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<Self::Source> {
+        KinesisSource::try_new(params, checkpoint).await
+    }
+}
+
+#[derive(Default)]
+pub struct KinesisSourceState {
+    /// Position of the last record processed for each shard being consumed, or its checkpointed
+    /// position if no record has been processed yet.
+    pub current_positions: BTreeMap<String, Position>,
+    /// Number of bytes processed by the source.
+    pub num_bytes_processed: u64,
+    /// Number of records processed by the source (including invalid records).
+    pub num_records_processed: u64,
+    // Number of invalid records, i.e., that were empty or not valid UTF-8.
+    pub num_invalid_records: u64,
+    /// Number of shards closed by a split or a merge and fully consumed.
+    pub num_closed_shards: u64,
+}
+
+impl KinesisSourceState {
+    /// Appends the records of a shard to `docs` and records their sequence numbers in
+    /// `checkpoint_delta`. Returns the number of bytes of the records.
+    fn record_records(
+        &mut self,
+        shard_id: &str,
+        records: Vec<Record>,
+        docs: &mut Vec<String>,
+        checkpoint_delta: &mut CheckpointDelta,
+    ) -> anyhow::Result<u64> {
+        let last_sequence_number = match records.last() {
+            Some(record) => record.sequence_number.clone(),
+            None => return Ok(0),
+        };
+        let to_position = Position::from(last_sequence_number);
+        let from_position = self
+            .current_positions
+            .insert(shard_id.to_string(), to_position.clone())
+            .unwrap_or(Position::Beginning);
+        checkpoint_delta
+            .record_partition_delta(PartitionId::from(shard_id), from_position, to_position)
+            .context("Failed to record partition delta.")?;
+
+        let num_records = records.len() as u64;
+        let mut num_bytes = 0;
+        for record in records {
+            num_bytes += record.data.len() as u64;
+            match String::from_utf8(record.data.to_vec()) {
+                Ok(doc) if !doc.is_empty() => docs.push(doc),
+                _ => {
+                    warn!(
+                        shard_id = %shard_id,
+                        sequence_number = %record.sequence_number,
+                        "Record is empty or not valid UTF-8."
+                    );
+                    self.num_invalid_records += 1;
+                }
+            }
+        }
+        self.num_bytes_processed += num_bytes;
+        self.num_records_processed += num_records;
+        Ok(num_bytes)
+    }
+}
+
+/// A `KinesisSource` consumes the shards of a Kinesis data stream, with one `ShardConsumer`
+/// actor per shard, and forwards their records to an `Indexer`.
+///
+/// The sequence number of the last record indexed is recorded in the checkpoint for each shard.
+/// When a shard is closed by a split or a merge, its consumer reports it once all its records
+/// have been transferred: the source then moves the position of the shard to its ending sequence
+/// number, which flags it as finished in the checkpoint, and starts consuming its children. The
+/// child of a merge waits until both of its parents are finished, which preserves the ordering
+/// of the records sharing a partition key.
+pub struct KinesisSource {
+    stream_name: String,
+    kinesis_client: KinesisClient,
+    lineage: ShardLineage,
+    /// Shards to consume, computed from the checkpoint when the source is created. Their
+    /// consumers are spawned once the source is initialized.
+    initial_assignments: Vec<ShardAssignment>,
+    shard_consumers: BTreeMap<String, ActorHandle<ShardConsumer>>,
+    shard_consumers_tx: mpsc::Sender<ShardConsumerMessage>,
+    shard_consumers_rx: mpsc::Receiver<ShardConsumerMessage>,
+    state: KinesisSourceState,
+}
+
+impl fmt::Debug for KinesisSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KinesisSource {{ stream:{} }}", self.stream_name)
+    }
+}
+
+impl KinesisSource {
+    /// Instantiates a new `KinesisSource`.
+    pub async fn try_new(
+        params: KinesisSourceParams,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<KinesisSource> {
+        let region = region_from_params(&params)?;
+        let kinesis_client = KinesisClient::new(region);
+        let shards = list_shards(&kinesis_client, &params.stream_name, None)
+            .await
+            .with_context(|| {
+                format!("Failed to list shards of stream `{}`.", params.stream_name)
+            })?;
+        let lineage = ShardLineage::new(&shards, &checkpoint);
+        let initial_assignments = lineage.assignments(&checkpoint);
+        info!(
+            stream = %params.stream_name,
+            num_shards = shards.len(),
+            assigned_shards = ?initial_assignments
+                .iter()
+                .map(|assignment| assignment.shard_id.as_str())
+                .collect::<Vec<_>>(),
+            "Starting Kinesis source."
+        );
+        let state = KinesisSourceState {
+            current_positions: checkpoint
+                .iter()
+                .map(|(partition_id, position)| (partition_id.0.to_string(), position))
+                .collect(),
+            ..Default::default()
+        };
+        let (shard_consumers_tx, shard_consumers_rx) =
+            mpsc::channel(SHARD_CONSUMERS_CHANNEL_CAPACITY);
+        Ok(KinesisSource {
+            stream_name: params.stream_name,
+            kinesis_client,
+            lineage,
+            initial_assignments,
+            shard_consumers: BTreeMap::new(),
+            shard_consumers_tx,
+            shard_consumers_rx,
+            state,
+        })
+    }
+
+    fn spawn_shard_consumer(
+        &mut self,
+        shard_id: String,
+        from_sequence_number_exclusive: Option<String>,
+        ctx: &SourceContext,
+    ) {
+        info!(
+            stream = %self.stream_name,
+            shard_id = %shard_id,
+            from_sequence_number_exclusive = ?from_sequence_number_exclusive,
+            "Starting shard consumer."
+        );
+        let shard_consumer = ShardConsumer::new(
+            self.stream_name.clone(),
+            shard_id.clone(),
+            from_sequence_number_exclusive,
+            false,
+            Box::new(self.kinesis_client.clone()),
+            self.shard_consumers_tx.clone(),
+        );
+        // The consumers share the kill switch of the source: a consumer failing, for instance
+        // because its shard iterator expired, restarts the pipeline from the checkpoint.
+        let (_shard_consumer_mailbox, shard_consumer_handle) =
+            ctx.spawn_actor(shard_consumer).spawn_async();
+        self.shard_consumers.insert(shard_id, shard_consumer_handle);
+    }
+
+    /// Records the closure of a shard whose records have all been transferred, and starts
+    /// consuming its children that are ready.
+    async fn close_shard(
+        &mut self,
+        shard_id: &str,
+        checkpoint_delta: &mut CheckpointDelta,
+        ctx: &SourceContext,
+    ) -> anyhow::Result<()> {
+        self.shard_consumers.remove(shard_id);
+        // The ending sequence number of a shard and its children are only listed once the shard
+        // is closed.
+        let shards = {
+            let _protect_guard = ctx.protect_zone();
+            list_shards(&self.kinesis_client, &self.stream_name, None)
+                .await
+                .with_context(|| {
+                    format!("Failed to list shards of stream `{}`.", self.stream_name)
+                })?
+        };
+        self.lineage.update(&shards);
+
+        let current_position = self
+            .state
+            .current_positions
+            .remove(shard_id)
+            .unwrap_or(Position::Beginning);
+        let (shard_delta, ready_child_shard_ids) =
+            self.lineage.close_shard(shard_id, &current_position)?;
+        checkpoint_delta
+            .extend(shard_delta)
+            .context("Failed to record partition delta.")?;
+        self.state.num_closed_shards += 1;
+        info!(
+            stream = %self.stream_name,
+            shard_id = %shard_id,
+            ready_child_shard_ids = ?ready_child_shard_ids,
+            "Shard is closed and fully consumed."
+        );
+        for child_shard_id in ready_child_shard_ids {
+            // A child is only ready once its parents are finished: none of its records have been
+            // consumed yet.
+            self.spawn_shard_consumer(child_shard_id, None, ctx);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Source for KinesisSource {
+    async fn initialize(&mut self, ctx: &SourceContext) -> Result<(), ActorExitStatus> {
+        for assignment in std::mem::take(&mut self.initial_assignments) {
+            self.spawn_shard_consumer(
+                assignment.shard_id,
+                assignment.from_sequence_number_exclusive,
+                ctx,
+            );
+        }
+        Ok(())
+    }
+
+    async fn emit_batches(
+        &mut self,
+        batch_sink: &Mailbox<IndexerMessage>,
+        ctx: &SourceContext,
+    ) -> Result<(), ActorExitStatus> {
+        let mut docs = Vec::new();
+        let mut checkpoint_delta = CheckpointDelta::default();
+        let mut batch_num_bytes = 0;
+        let target_batch_num_bytes =
+            backpressure::target_batch_num_bytes(batch_sink, TARGET_BATCH_NUM_BYTES);
+
+        let deadline = ctx.sleep(quickwit_actors::HEARTBEAT / 2);
+        tokio::pin!(deadline);
+
+        loop {
+            tokio::select! {
+                message_opt = self.shard_consumers_rx.recv() => {
+                    // The channel is only closed once the source is finalized.
+                    let message = match message_opt {
+                        Some(message) => message,
+                        None => break,
+                    };
+                    match message {
+                        ShardConsumerMessage::Records { shard_id, records, .. } => {
+                            batch_num_bytes += self.state.record_records(
+                                &shard_id,
+                                records,
+                                &mut docs,
+                                &mut checkpoint_delta,
+                            )?;
+                        }
+                        ShardConsumerMessage::ChildShards(child_shard_ids) => {
+                            debug!(
+                                stream = %self.stream_name,
+                                child_shard_ids = ?child_shard_ids,
+                                "Shard was split or merged."
+                            );
+                        }
+                        ShardConsumerMessage::ShardClosed(shard_id) => {
+                            self.close_shard(&shard_id, &mut checkpoint_delta, ctx).await?;
+                        }
+                        ShardConsumerMessage::ShardEOF(shard_id) => {
+                            self.shard_consumers.remove(&shard_id);
+                        }
+                    }
+                    if batch_num_bytes >= target_batch_num_bytes {
+                        break;
+                    }
+                }
+                _ = &mut deadline => {
+                    break;
+                }
+            }
+        }
+        if !checkpoint_delta.is_empty() {
+            let batch = RawDocBatch {
+                docs,
+                checkpoint_delta,
+            };
+            ctx.send_message(batch_sink, IndexerMessage::from(batch))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn finalize(
+        &mut self,
+        _exit_status: &ActorExitStatus,
+        _ctx: &SourceContext,
+    ) -> anyhow::Result<()> {
+        // Unblocks the consumers waiting for room in the channel.
+        self.shard_consumers_rx.close();
+        for (_shard_id, shard_consumer_handle) in std::mem::take(&mut self.shard_consumers) {
+            shard_consumer_handle.quit().await;
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        "KinesisSource".to_string()
+    }
+
+    fn observable_state(&self) -> serde_json::Value {
+        let current_positions: BTreeMap<&str, &str> = self
+            .state
+            .current_positions
+            .iter()
+            .map(|(shard_id, position)| (shard_id.as_str(), position.as_str()))
+            .collect();
+        json!({
+            "stream_name": self.stream_name,
+            "assigned_shards": self.shard_consumers.keys().collect::<Vec<_>>(),
+            "current_positions": current_positions,
+            "num_bytes_processed": self.state.num_bytes_processed,
+            "num_records_processed": self.state.num_records_processed,
+            "num_invalid_records": self.state.num_invalid_records,
+            "num_closed_shards": self.state.num_closed_shards,
+        })
+    }
+}
+
+/// Checks if the shards of the stream can be listed, and if the first one can be read.
+pub(crate) async fn check_connectivity(params: KinesisSourceParams) -> anyhow::Result<()> {
+    let region = region_from_params(&params)?;
+    let kinesis_client = KinesisClient::new(region);
+    let shards = list_shards(&kinesis_client, &params.stream_name, None)
+        .await
+        .with_context(|| format!("Failed to list shards of stream `{}`.", params.stream_name))?;
+    // Listing the shards and reading them require distinct permissions.
+    if let Some(shard) = shards.first() {
+        get_shard_iterator(&kinesis_client, &params.stream_name, &shard.shard_id, None)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to get an iterator on shard `{}` of stream `{}`.",
+                    shard.shard_id, params.stream_name
+                )
+            })?;
+    }
+    Ok(())
+}
+
+/// Returns the region of the stream. A custom endpoint, for instance LocalStack's, takes
+/// precedence over the region, which defaults to the region of the environment.
+fn region_from_params(params: &KinesisSourceParams) -> anyhow::Result<Region> {
+    if let Some(endpoint) = &params.endpoint {
+        return Ok(Region::Custom {
+            name: params
+                .region
+                .clone()
+                .unwrap_or_else(|| "us-east-1".to_string()),
+            endpoint: endpoint.clone(),
+        });
+    }
+    match &params.region {
+        Some(region_str) => Region::from_str(region_str)
+            .with_context(|| format!("Invalid region `{}`.", region_str)),
+        None => Ok(Region::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_record(sequence_number: &str, data: &'static str) -> Record {
+        Record {
+            sequence_number: sequence_number.to_string(),
+            data: data.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_kinesis_source_state_record_records() -> anyhow::Result<()> {
+        let mut state = KinesisSourceState::default();
+        state
+            .current_positions
+            .insert("shard-0".to_string(), Position::from("100".to_string()));
+        let mut docs = Vec::new();
+        let mut checkpoint_delta = CheckpointDelta::default();
+
+        let num_bytes = state.record_records(
+            "shard-0",
+            vec![make_record("101", "{}"), make_record("102", "")],
+            &mut docs,
+            &mut checkpoint_delta,
+        )?;
+        assert_eq!(num_bytes, 2);
+        state.record_records(
+            "shard-1",
+            vec![make_record("200", "{\"a\": 1}")],
+            &mut docs,
+            &mut checkpoint_delta,
+        )?;
+        // Records of a shard already in the batch extend its delta.
+        state.record_records(
+            "shard-0",
+            vec![make_record("103", "{}")],
+            &mut docs,
+            &mut checkpoint_delta,
+        )?;
+        assert_eq!(docs, vec!["{}", "{\"a\": 1}", "{}"]);
+        assert_eq!(
+            format!("{:?}", checkpoint_delta),
+            "∆(shard-0:(100..103] shard-1:(..200])"
+        );
+        assert_eq!(state.num_records_processed, 4);
+        assert_eq!(state.num_invalid_records, 1);
+        assert_eq!(
+            state.current_positions["shard-0"],
+            Position::from("103".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_region_from_params() -> anyhow::Result<()> {
+        let mut params = KinesisSourceParams {
+            stream_name: "my-stream".to_string(),
+            region: Some("eu-west-1".to_string()),
+            endpoint: None,
+        };
+        assert_eq!(region_from_params(&params)?, Region::EuWest1);
+
+        params.endpoint = Some("http://localhost:4566".to_string());
+        assert_eq!(
+            region_from_params(&params)?,
+            Region::Custom {
+                name: "eu-west-1".to_string(),
+                endpoint: "http://localhost:4566".to_string(),
+            }
+        );
+        params.region = Some("middle-earth-1".to_string());
+        params.endpoint = None;
+        assert!(region_from_params(&params).is_err());
+        Ok(())
+    }
+}
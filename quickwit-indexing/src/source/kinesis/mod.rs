@@ -19,4 +19,9 @@
 
 mod api;
 mod helpers;
+mod kinesis_source;
+mod resharding;
 mod shard_consumer;
+
+pub(crate) use kinesis_source::check_connectivity;
+pub use kinesis_source::{KinesisSource, KinesisSourceFactory};
@@ -0,0 +1,338 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Context;
+use quickwit_metastore::checkpoint::{CheckpointDelta, PartitionId, Position, SourceCheckpoint};
+use rusoto_kinesis::Shard;
+
+/// Shard of a stream as seen by the lineage.
+#[derive(Debug, Default)]
+struct ShardNode {
+    /// One parent for a split, two for a merge, none for the shards created with the stream.
+    parent_shard_ids: Vec<String>,
+    child_shard_ids: BTreeSet<String>,
+    /// Sequence number bounding the records of the shard once it is closed.
+    ending_sequence_number_opt: Option<String>,
+    /// Whether all the records of the shard have been consumed.
+    finished: bool,
+}
+
+/// Shard to consume, along with the sequence number after which consumption resumes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ShardAssignment {
+    pub shard_id: String,
+    pub from_sequence_number_exclusive: Option<String>,
+}
+
+/// Tracks the parent-child relationships created by the splits and merges of the shards of a
+/// stream, so that:
+/// - the records of a parent shard are all consumed before the records of its children, which
+///   preserves the ordering of the records sharing a partition key;
+/// - the checkpoint records the completion of the parent shards, so that they are neither read
+///   again nor are their children skipped after a restart.
+#[derive(Debug, Default)]
+pub(crate) struct ShardLineage {
+    shards: BTreeMap<String, ShardNode>,
+}
+
+impl ShardLineage {
+    /// Builds the lineage of the shards returned by the `ListShards` API and flags the shards
+    /// that the checkpoint reports as fully consumed.
+    pub fn new(shards: &[Shard], checkpoint: &SourceCheckpoint) -> Self {
+        let mut lineage = ShardLineage::default();
+        lineage.update(shards);
+
+        let checkpointed_shard_ids: Vec<String> = checkpoint
+            .iter()
+            .map(|(partition_id, _)| partition_id.0.to_string())
+            .collect();
+        for (shard_id, node) in lineage.shards.iter_mut() {
+            let position_opt =
+                checkpoint.position_for_partition(&PartitionId::from(shard_id.as_str()));
+            if let (Some(position), Some(ending_sequence_number)) =
+                (position_opt, &node.ending_sequence_number_opt)
+            {
+                node.finished = *position >= Position::from(ending_sequence_number.clone());
+            }
+        }
+        // Children are only consumed once their parents are finished: the ancestors of a
+        // checkpointed shard are finished too.
+        for shard_id in checkpointed_shard_ids {
+            lineage.finish_ancestors(&shard_id);
+        }
+        lineage
+    }
+
+    /// Merges the shards returned by the `ListShards` API into the lineage, typically after a
+    /// shard was closed and its children created.
+    pub fn update(&mut self, shards: &[Shard]) {
+        for shard in shards {
+            let parent_shard_ids: Vec<String> = shard
+                .parent_shard_id
+                .iter()
+                .chain(shard.adjacent_parent_shard_id.iter())
+                .cloned()
+                .collect();
+            for parent_shard_id in &parent_shard_ids {
+                // Parent shards expired past the retention period of the stream are no longer
+                // listed: there is nothing left to consume from them.
+                let parent_node = self
+                    .shards
+                    .entry(parent_shard_id.clone())
+                    .or_insert_with(|| ShardNode {
+                        finished: true,
+                        ..Default::default()
+                    });
+                parent_node.child_shard_ids.insert(shard.shard_id.clone());
+            }
+            let node = self.shards.entry(shard.shard_id.clone()).or_default();
+            node.parent_shard_ids = parent_shard_ids;
+            if node.ending_sequence_number_opt.is_none() {
+                node.ending_sequence_number_opt =
+                    shard.sequence_number_range.ending_sequence_number.clone();
+            }
+        }
+    }
+
+    /// Returns the shards to consume: the unfinished shards whose parents are all finished,
+    /// resuming from the checkpoint.
+    pub fn assignments(&self, checkpoint: &SourceCheckpoint) -> Vec<ShardAssignment> {
+        self.shards
+            .iter()
+            .filter(|(shard_id, node)| !node.finished && self.is_ready(shard_id))
+            .map(|(shard_id, _)| {
+                let from_sequence_number_exclusive = match checkpoint
+                    .position_for_partition(&PartitionId::from(shard_id.as_str()))
+                {
+                    Some(Position::Offset(sequence_number)) => Some(sequence_number.to_string()),
+                    Some(Position::Beginning) | None => None,
+                };
+                ShardAssignment {
+                    shard_id: shard_id.clone(),
+                    from_sequence_number_exclusive,
+                }
+            })
+            .collect()
+    }
+
+    /// Flags a shard as finished once its consumer reports that it is closed. Returns the delta
+    /// moving the position of the shard to its ending sequence number, which marks it as
+    /// finished in the checkpoint, and the children of the shard that are now ready to be
+    /// consumed: the child of a merge waits for both of its parents.
+    ///
+    /// The ending sequence number of the shard must be known, i.e. the lineage must have been
+    /// updated with the shards listed after the closure.
+    pub fn close_shard(
+        &mut self,
+        shard_id: &str,
+        current_position: &Position,
+    ) -> anyhow::Result<(CheckpointDelta, Vec<String>)> {
+        let node = self
+            .shards
+            .get_mut(shard_id)
+            .with_context(|| format!("Shard `{}` is unknown.", shard_id))?;
+        let ending_sequence_number = node
+            .ending_sequence_number_opt
+            .clone()
+            .with_context(|| format!("Shard `{}` is not closed.", shard_id))?;
+        node.finished = true;
+
+        // Sequence numbers have a fixed width, so positions compare like sequence numbers.
+        let ending_position = Position::from(ending_sequence_number);
+        let mut checkpoint_delta = CheckpointDelta::default();
+        if *current_position < ending_position {
+            checkpoint_delta.record_partition_delta(
+                PartitionId::from(shard_id),
+                current_position.clone(),
+                ending_position,
+            )?;
+        }
+        let ready_child_shard_ids = self.shards[shard_id]
+            .child_shard_ids
+            .iter()
+            .filter(|child_shard_id| self.is_ready(child_shard_id))
+            .cloned()
+            .collect();
+        Ok((checkpoint_delta, ready_child_shard_ids))
+    }
+
+    fn is_ready(&self, shard_id: &str) -> bool {
+        self.shards[shard_id]
+            .parent_shard_ids
+            .iter()
+            .all(|parent_shard_id| {
+                self.shards
+                    .get(parent_shard_id)
+                    .map(|parent_node| parent_node.finished)
+                    .unwrap_or(true)
+            })
+    }
+
+    fn finish_ancestors(&mut self, shard_id: &str) {
+        let mut stack: Vec<String> = self
+            .shards
+            .get(shard_id)
+            .map(|node| node.parent_shard_ids.clone())
+            .unwrap_or_default();
+        while let Some(parent_shard_id) = stack.pop() {
+            if let Some(parent_node) = self.shards.get_mut(&parent_shard_id) {
+                if !parent_node.finished {
+                    parent_node.finished = true;
+                    stack.extend(parent_node.parent_shard_ids.iter().cloned());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusoto_kinesis::SequenceNumberRange;
+
+    use super::*;
+
+    fn make_shard(
+        shard_id: &str,
+        parent_shard_ids: &[&str],
+        ending_sequence_number_opt: Option<&str>,
+    ) -> Shard {
+        Shard {
+            shard_id: shard_id.to_string(),
+            parent_shard_id: parent_shard_ids.get(0).map(|shard_id| shard_id.to_string()),
+            adjacent_parent_shard_id: parent_shard_ids.get(1).map(|shard_id| shard_id.to_string()),
+            sequence_number_range: SequenceNumberRange {
+                starting_sequence_number: "0".to_string(),
+                ending_sequence_number: ending_sequence_number_opt
+                    .map(|sequence_number| sequence_number.to_string()),
+            },
+            ..Default::default()
+        }
+    }
+
+    fn make_checkpoint(positions: &[(&str, &str)]) -> SourceCheckpoint {
+        positions
+            .iter()
+            .map(|(shard_id, sequence_number)| {
+                (
+                    PartitionId::from(*shard_id),
+                    Position::from(sequence_number.to_string()),
+                )
+            })
+            .collect()
+    }
+
+    fn shard_ids(assignments: &[ShardAssignment]) -> Vec<&str> {
+        assignments
+            .iter()
+            .map(|assignment| assignment.shard_id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_shard_lineage_split() -> anyhow::Result<()> {
+        // shard-0 was split into shard-1 and shard-2.
+        let shards = [
+            make_shard("shard-0", &[], Some("300")),
+            make_shard("shard-1", &["shard-0"], None),
+            make_shard("shard-2", &["shard-0"], None),
+        ];
+        let checkpoint = make_checkpoint(&[("shard-0", "142")]);
+        let mut lineage = ShardLineage::new(&shards, &checkpoint);
+        let assignments = lineage.assignments(&checkpoint);
+        assert_eq!(
+            assignments,
+            vec![ShardAssignment {
+                shard_id: "shard-0".to_string(),
+                from_sequence_number_exclusive: Some("142".to_string()),
+            }]
+        );
+        let (checkpoint_delta, ready_child_shard_ids) =
+            lineage.close_shard("shard-0", &Position::from("142".to_string()))?;
+        assert_eq!(ready_child_shard_ids, vec!["shard-1", "shard-2"]);
+
+        let mut checkpoint = checkpoint;
+        checkpoint.try_apply_delta(checkpoint_delta)?;
+        assert_eq!(
+            checkpoint.position_for_partition(&PartitionId::from("shard-0")),
+            Some(&Position::from("300".to_string()))
+        );
+        // After a restart, the parent is finished and the children start from their beginning.
+        let lineage = ShardLineage::new(&shards, &checkpoint);
+        let assignments = lineage.assignments(&checkpoint);
+        assert_eq!(shard_ids(&assignments), vec!["shard-1", "shard-2"]);
+        assert!(assignments
+            .iter()
+            .all(|assignment| assignment.from_sequence_number_exclusive.is_none()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_shard_lineage_merge() -> anyhow::Result<()> {
+        // shard-0 and shard-1 were merged into shard-2.
+        let shards = [
+            make_shard("shard-0", &[], Some("100")),
+            make_shard("shard-1", &[], Some("200")),
+            make_shard("shard-2", &["shard-0", "shard-1"], None),
+        ];
+        let checkpoint = SourceCheckpoint::default();
+        let mut lineage = ShardLineage::new(&shards, &checkpoint);
+        assert_eq!(
+            shard_ids(&lineage.assignments(&checkpoint)),
+            vec!["shard-0", "shard-1"]
+        );
+        let (checkpoint_delta, ready_child_shard_ids) =
+            lineage.close_shard("shard-0", &Position::Beginning)?;
+        assert_eq!(checkpoint_delta.num_partitions(), 1);
+        assert!(ready_child_shard_ids.is_empty());
+
+        let (checkpoint_delta, ready_child_shard_ids) =
+            lineage.close_shard("shard-1", &Position::from("200".to_string()))?;
+        assert!(checkpoint_delta.is_empty());
+        assert_eq!(ready_child_shard_ids, vec!["shard-2"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shard_lineage_resumes_from_children() {
+        // The parent shard-0 expired and its finished sibling shard-1 was merged with shard-2.
+        let shards = [
+            make_shard("shard-1", &["shard-0"], Some("100")),
+            make_shard("shard-2", &["shard-0"], Some("200")),
+            make_shard("shard-3", &["shard-1", "shard-2"], None),
+        ];
+        let checkpoint = make_checkpoint(&[("shard-1", "100"), ("shard-2", "150")]);
+        let lineage = ShardLineage::new(&shards, &checkpoint);
+        assert_eq!(
+            lineage.assignments(&checkpoint),
+            vec![ShardAssignment {
+                shard_id: "shard-2".to_string(),
+                from_sequence_number_exclusive: Some("150".to_string()),
+            }]
+        );
+        // A checkpointed child implies that its parents were finished.
+        let checkpoint = make_checkpoint(&[("shard-1", "050"), ("shard-3", "300")]);
+        let lineage = ShardLineage::new(&shards, &checkpoint);
+        assert_eq!(
+            shard_ids(&lineage.assignments(&checkpoint)),
+            vec!["shard-3"]
+        );
+    }
+}
@@ -17,20 +17,18 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-// TODO: Remove when `KinesisSource` is fully implemented.
-#![allow(dead_code)]
-
 use std::time::Duration;
 
 use async_trait::async_trait;
-use quickwit_actors::{Actor, ActorContext, ActorExitStatus, AsyncActor, Mailbox};
+use quickwit_actors::{Actor, ActorContext, ActorExitStatus, AsyncActor};
 use rusoto_kinesis::{Kinesis, Record};
 use serde_json::json;
+use tokio::sync::mpsc;
 
 use crate::source::kinesis::api::{get_records, get_shard_iterator};
 
 #[derive(Debug)]
-enum ShardConsumerMessage {
+pub(super) enum ShardConsumerMessage {
     /// The shard was the subject of a merge or a split and points to one (merge) or two (split)
     /// children.
     ChildShards(Vec<String>),
@@ -60,7 +58,7 @@ struct ShardConsumerState {
     next_shard_iterator: Option<String>,
 }
 
-struct ShardConsumer {
+pub(super) struct ShardConsumer {
     stream_name: String,
     shard_id: String,
     /// Sequence number of the last record processed. Consumption of the shard is resumed right
@@ -71,17 +69,19 @@ struct ShardConsumer {
     eof_enabled: bool,
     state: ShardConsumerState,
     kinesis_client: Box<dyn Kinesis + Send + Sync>,
-    sink: Mailbox<ShardConsumerMessage>,
+    /// The source, which is not an actor itself, receives the messages of the consumers through
+    /// a channel rather than a mailbox.
+    sink: mpsc::Sender<ShardConsumerMessage>,
 }
 
 impl ShardConsumer {
-    fn new(
+    pub fn new(
         stream_name: String,
         shard_id: String,
         from_sequence_number_exclusive: Option<String>,
         eof_enabled: bool,
         kinesis_client: Box<dyn Kinesis + Send + Sync>,
-        sink: Mailbox<ShardConsumerMessage>,
+        sink: mpsc::Sender<ShardConsumerMessage>,
     ) -> Self {
         Self {
             stream_name,
@@ -159,7 +159,7 @@ impl AsyncActor for ShardConsumer {
                     records: response.records,
                     lag_millis: response.millis_behind_latest,
                 };
-                ctx.send_message(&self.sink, message).await?;
+                self.send_message(message, ctx).await?;
             }
             if let Some(children) = response.child_shards {
                 let shard_ids: Vec<String> = children
@@ -170,12 +170,12 @@ impl AsyncActor for ShardConsumer {
                     .collect();
                 if !shard_ids.is_empty() {
                     let message = ShardConsumerMessage::ChildShards(shard_ids);
-                    ctx.send_message(&self.sink, message).await?;
+                    self.send_message(message, ctx).await?;
                 }
             }
             if self.eof_enabled && response.millis_behind_latest == Some(0) {
                 let message = ShardConsumerMessage::ShardEOF(self.shard_id.clone());
-                ctx.send_message(&self.sink, message).await?;
+                self.send_message(message, ctx).await?;
                 return Err(ActorExitStatus::Success);
             };
             // The `GetRecords` API has a limit of 5 transactions per second. 1s / 5 + ε = 205ms.
@@ -184,14 +184,29 @@ impl AsyncActor for ShardConsumer {
             return Ok(());
         }
         let message = ShardConsumerMessage::ShardClosed(self.shard_id.clone());
-        ctx.send_message(&self.sink, message).await?;
+        self.send_message(message, ctx).await?;
         Err(ActorExitStatus::Success)
     }
 }
 
+impl ShardConsumer {
+    async fn send_message(
+        &self,
+        message: ShardConsumerMessage,
+        ctx: &ActorContext<Self>,
+    ) -> Result<(), ActorExitStatus> {
+        // The channel is full while the source waits for the indexer.
+        let _protect_guard = ctx.protect_zone();
+        self.sink
+            .send(message)
+            .await
+            .map_err(|_| ActorExitStatus::DownstreamClosed)
+    }
+}
+
 #[cfg(all(test, feature = "kinesis-localstack-tests"))]
 mod kinesis_localstack_tests {
-    use quickwit_actors::{create_test_mailbox, Universe};
+    use quickwit_actors::Universe;
 
     use super::*;
     use crate::source::kinesis::api::tests::{merge_shards, split_shard};
@@ -199,10 +214,20 @@ mod kinesis_localstack_tests {
         make_shard_id, put_records_into_shards, setup, teardown,
     };
 
+    fn drain_messages(
+        receiver: &mut mpsc::Receiver<ShardConsumerMessage>,
+    ) -> Vec<ShardConsumerMessage> {
+        let mut messages = Vec::new();
+        while let Ok(message) = receiver.try_recv() {
+            messages.push(message);
+        }
+        messages
+    }
+
     #[tokio::test]
     async fn test_shard_eof() -> anyhow::Result<()> {
         let universe = Universe::new();
-        let (sink, inbox) = create_test_mailbox();
+        let (sink, mut receiver) = mpsc::channel(100);
         let (kinesis_client, stream_name) = setup("test-shard-eof", 1).await?;
         let shard_id_0 = make_shard_id(0);
         let shard_consumer = ShardConsumer::new(
@@ -217,7 +242,7 @@ mod kinesis_localstack_tests {
         let (exit_status, exit_state) = handle.join().await;
         assert!(exit_status.is_success());
 
-        let messages = inbox.drain_available_message_for_test();
+        let messages = drain_messages(&mut receiver);
         assert_eq!(messages.len(), 1);
 
         assert!(matches!(
@@ -241,7 +266,7 @@ mod kinesis_localstack_tests {
     #[tokio::test]
     async fn test_start_at_horizon() -> anyhow::Result<()> {
         let universe = Universe::new();
-        let (sink, inbox) = create_test_mailbox();
+        let (sink, mut receiver) = mpsc::channel(100);
         let (kinesis_client, stream_name) = setup("test-start-at-horizon", 1).await?;
         let sequence_numbers = put_records_into_shards(
             &kinesis_client,
@@ -262,7 +287,7 @@ mod kinesis_localstack_tests {
         let (exit_status, exit_state) = handle.join().await;
         assert!(exit_status.is_success());
 
-        let messages = inbox.drain_available_message_for_test();
+        let messages = drain_messages(&mut receiver);
         assert_eq!(messages.len(), 2);
 
         assert!(matches!(
@@ -294,7 +319,7 @@ mod kinesis_localstack_tests {
     #[tokio::test]
     async fn test_start_after_sequence_number() -> anyhow::Result<()> {
         let universe = Universe::new();
-        let (sink, inbox) = create_test_mailbox();
+        let (sink, mut receiver) = mpsc::channel(100);
         let (kinesis_client, stream_name) = setup("test-start-after-sequence-number", 1).await?;
         let sequence_numbers = put_records_into_shards(
             &kinesis_client,
@@ -319,7 +344,7 @@ mod kinesis_localstack_tests {
         let (exit_status, exit_state) = handle.join().await;
         assert!(exit_status.is_success());
 
-        let messages = inbox.drain_available_message_for_test();
+        let messages = drain_messages(&mut receiver);
         assert_eq!(messages.len(), 2);
 
         assert!(matches!(
@@ -354,7 +379,7 @@ mod kinesis_localstack_tests {
     #[tokio::test]
     async fn test_merge_shards() -> anyhow::Result<()> {
         let universe = Universe::new();
-        let (sink, inbox) = create_test_mailbox();
+        let (sink, mut receiver) = mpsc::channel(100);
         let (kinesis_client, stream_name) = setup("test-merge-shards", 2).await?;
         let shard_id_0 = make_shard_id(0);
         let shard_id_1 = make_shard_id(1);
@@ -372,7 +397,7 @@ mod kinesis_localstack_tests {
             let (exit_status, _exit_state) = handle.join().await;
             assert!(exit_status.is_success());
 
-            let messages = inbox.drain_available_message_for_test();
+            let messages = drain_messages(&mut receiver);
             assert_eq!(messages.len(), 2);
 
             assert!(matches!(
@@ -397,7 +422,7 @@ mod kinesis_localstack_tests {
             let (exit_status, _exit_state) = handle.join().await;
             assert!(exit_status.is_success());
 
-            let messages = inbox.drain_available_message_for_test();
+            let messages = drain_messages(&mut receiver);
             assert_eq!(messages.len(), 1);
 
             assert!(matches!(
@@ -415,7 +440,7 @@ mod kinesis_localstack_tests {
     #[tokio::test]
     async fn test_split_shard() -> anyhow::Result<()> {
         let universe = Universe::new();
-        let (sink, inbox) = create_test_mailbox();
+        let (sink, mut receiver) = mpsc::channel(100);
         let (kinesis_client, stream_name) = setup("test-split-shard", 1).await?;
         let shard_id_0 = make_shard_id(0);
         split_shard(&kinesis_client, &stream_name, &shard_id_0, "42").await?;
@@ -432,7 +457,7 @@ mod kinesis_localstack_tests {
         let (exit_status, _exit_state) = handle.join().await;
         assert!(exit_status.is_success());
 
-        let messages = inbox.drain_available_message_for_test();
+        let messages = drain_messages(&mut receiver);
         assert_eq!(messages.len(), 2);
 
         assert!(matches!(
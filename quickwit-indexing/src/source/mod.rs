@@ -17,12 +17,42 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+mod backpressure;
+mod dataset_source;
+#[cfg(feature = "kafka")]
+mod debezium;
+mod deep_check;
+mod dir_source;
 mod file_source;
+mod grpc_source;
+mod ingest_api_source;
+#[cfg(feature = "kafka")]
+mod kafka_avro;
+#[cfg(feature = "kafka")]
+mod kafka_consumer_group;
+#[cfg(feature = "kafka")]
+mod kafka_oauth;
 #[cfg(feature = "kafka")]
 mod kafka_source;
 #[cfg(feature = "kinesis")]
 mod kinesis;
+#[cfg(feature = "mqtt")]
+mod mqtt_source;
+mod multiline;
+#[cfg(feature = "nats")]
+mod nats_source;
+mod otlp_source;
+mod payload_decoder;
+#[cfg(feature = "pubsub")]
+mod pubsub_source;
+#[cfg(feature = "pulsar")]
+mod pulsar_source;
+mod record_splitter;
+mod row_decoder;
 mod source_factory;
+#[cfg(feature = "sqs")]
+mod sqs_source;
+mod syslog_source;
 mod vec_source;
 mod void_source;
 
@@ -31,13 +61,38 @@ use std::path::Path;
 
 use anyhow::bail;
 use async_trait::async_trait;
+pub use dataset_source::{
+    doc_mapping_from_parquet_dataset, doc_mapping_from_parquet_schema, DatasetSource,
+    DatasetSourceFactory,
+};
+pub use deep_check::check_source_decoding;
+pub use dir_source::{DirSource, DirSourceFactory};
 pub use file_source::{FileSource, FileSourceFactory};
+pub use grpc_source::{GrpcSource, GrpcSourceFactory};
+pub use ingest_api_source::{IngestApiSource, IngestApiSourceFactory};
 #[cfg(feature = "kafka")]
 pub use kafka_source::{KafkaSource, KafkaSourceFactory};
+#[cfg(feature = "kinesis")]
+pub use kinesis::{KinesisSource, KinesisSourceFactory};
+#[cfg(feature = "mqtt")]
+pub use mqtt_source::{MqttSource, MqttSourceFactory};
+#[cfg(feature = "nats")]
+pub use nats_source::{NatsSource, NatsSourceFactory};
 use once_cell::sync::OnceCell;
+pub use otlp_source::{OtlpSource, OtlpSourceFactory};
+pub use payload_decoder::{PayloadDecoder, ProtobufDecoder};
+#[cfg(feature = "pubsub")]
+pub use pubsub_source::{PubSubSource, PubSubSourceFactory};
+#[cfg(feature = "pulsar")]
+pub use pulsar_source::{PulsarSource, PulsarSourceFactory};
 use quickwit_actors::{Actor, ActorContext, ActorExitStatus, AsyncActor, Mailbox};
 use quickwit_config::{SourceConfig, SourceParams};
+use quickwit_metastore::checkpoint::SourceCheckpoint;
+use quickwit_storage::quickwit_storage_uri_resolver;
 pub use source_factory::{SourceFactory, SourceLoader, TypedSourceFactory};
+#[cfg(feature = "sqs")]
+pub use sqs_source::{SqsSource, SqsSourceFactory};
+pub use syslog_source::{SyslogSource, SyslogSourceFactory};
 pub use vec_source::{VecSource, VecSourceFactory};
 pub use void_source::{VoidSource, VoidSourceFactory};
 
@@ -82,6 +137,18 @@ pub trait Source: Send + Sync + 'static {
         ctx: &SourceContext,
     ) -> Result<(), ActorExitStatus>;
 
+    /// Called once the splits covering the positions of `checkpoint` have been published.
+    ///
+    /// Sources relying on the upstream system to track their progress can use it to acknowledge
+    /// the messages that are now safely indexed.
+    async fn suggest_truncate(
+        &mut self,
+        _checkpoint: SourceCheckpoint,
+        _ctx: &SourceContext,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
     /// Finalize is called once after the actor terminates.
     async fn finalize(
         &mut self,
@@ -108,12 +175,28 @@ pub trait Source: Send + Sync + 'static {
 pub struct SourceActor {
     pub source: Box<dyn Source>,
     pub batch_sink: Mailbox<IndexerMessage>,
+    /// The source does not emit batches while it is paused.
+    paused: bool,
+    /// Whether a `Loop` message is pending. A paused source lets its loop run out, so that
+    /// resuming it starts a single new loop.
+    is_looping: bool,
+}
+
+impl SourceActor {
+    pub fn new(source: Box<dyn Source>, batch_sink: Mailbox<IndexerMessage>) -> Self {
+        SourceActor {
+            source,
+            batch_sink,
+            paused: false,
+            is_looping: true,
+        }
+    }
 }
 
 /// The goal of this struct is simply to prevent the construction of a Loop object.
 struct PrivateToken;
 
-/// Message used for the SourceActor.
+/// Message used by the SourceActor to run its loop.
 pub struct Loop(PrivateToken);
 
 impl fmt::Debug for Loop {
@@ -122,8 +205,21 @@ impl fmt::Debug for Loop {
     }
 }
 
+/// Message used for the SourceActor.
+#[derive(Debug)]
+pub enum SourceActorMessage {
+    Loop(Loop),
+    /// Sent by the publisher with the positions of the splits it published.
+    SuggestTruncate(SourceCheckpoint),
+    /// Stops emitting batches and asks the indexer to commit the documents it received so far,
+    /// so that the checkpoint of the source stays put until it is resumed.
+    Pause,
+    /// Starts emitting batches again, from where the source was paused.
+    Resume,
+}
+
 impl Actor for SourceActor {
-    type Message = Loop;
+    type Message = SourceActorMessage;
     type ObservableState = serde_json::Value;
 
     fn name(&self) -> String {
@@ -139,17 +235,53 @@ impl Actor for SourceActor {
 impl AsyncActor for SourceActor {
     async fn initialize(&mut self, ctx: &SourceContext) -> Result<(), ActorExitStatus> {
         self.source.initialize(ctx).await?;
-        self.process_message(Loop(PrivateToken), ctx).await?;
+        self.process_message(SourceActorMessage::Loop(Loop(PrivateToken)), ctx)
+            .await?;
         Ok(())
     }
 
     async fn process_message(
         &mut self,
-        _message: Loop,
+        message: SourceActorMessage,
         ctx: &SourceContext,
     ) -> Result<(), ActorExitStatus> {
-        self.source.emit_batches(&self.batch_sink, ctx).await?;
-        ctx.send_self_message(Loop(PrivateToken)).await?;
+        match message {
+            SourceActorMessage::Loop(_) => {
+                if self.paused {
+                    self.is_looping = false;
+                    return Ok(());
+                }
+                self.source.emit_batches(&self.batch_sink, ctx).await?;
+                // The source polls less often while the indexer is falling behind.
+                if let Some(loop_delay) = backpressure::loop_delay(&self.batch_sink) {
+                    ctx.schedule_self_msg(loop_delay, SourceActorMessage::Loop(Loop(PrivateToken)))
+                        .await;
+                } else {
+                    ctx.send_self_message(SourceActorMessage::Loop(Loop(PrivateToken)))
+                        .await?;
+                }
+            }
+            SourceActorMessage::SuggestTruncate(checkpoint) => {
+                self.source.suggest_truncate(checkpoint, ctx).await?;
+            }
+            SourceActorMessage::Pause => {
+                if !self.paused {
+                    self.paused = true;
+                    ctx.send_message(&self.batch_sink, IndexerMessage::Flush)
+                        .await?;
+                }
+            }
+            SourceActorMessage::Resume => {
+                if self.paused {
+                    self.paused = false;
+                    if !self.is_looping {
+                        self.is_looping = true;
+                        ctx.send_self_message(SourceActorMessage::Loop(Loop(PrivateToken)))
+                            .await?;
+                    }
+                }
+            }
+        }
         Ok(())
     }
 
@@ -167,9 +299,27 @@ pub fn quickwit_supported_sources() -> &'static SourceLoader {
     static SOURCE_LOADER: OnceCell<SourceLoader> = OnceCell::new();
     SOURCE_LOADER.get_or_init(|| {
         let mut source_factory = SourceLoader::default();
+        source_factory.add_source("dataset", DatasetSourceFactory);
+        source_factory.add_source("dir", DirSourceFactory);
         source_factory.add_source("file", FileSourceFactory);
+        source_factory.add_source("grpc", GrpcSourceFactory);
+        source_factory.add_source("ingest_api", IngestApiSourceFactory);
         #[cfg(feature = "kafka")]
         source_factory.add_source("kafka", KafkaSourceFactory);
+        #[cfg(feature = "kinesis")]
+        source_factory.add_source("kinesis", KinesisSourceFactory);
+        #[cfg(feature = "mqtt")]
+        source_factory.add_source("mqtt", MqttSourceFactory);
+        #[cfg(feature = "nats")]
+        source_factory.add_source("nats", NatsSourceFactory);
+        source_factory.add_source("otlp", OtlpSourceFactory);
+        #[cfg(feature = "pubsub")]
+        source_factory.add_source("pubsub", PubSubSourceFactory);
+        #[cfg(feature = "pulsar")]
+        source_factory.add_source("pulsar", PulsarSourceFactory);
+        #[cfg(feature = "sqs")]
+        source_factory.add_source("sqs", SqsSourceFactory);
+        source_factory.add_source("syslog", SyslogSourceFactory);
         source_factory.add_source("vec", VecSourceFactory);
         source_factory.add_source("void", VoidSourceFactory);
         source_factory
@@ -178,14 +328,36 @@ pub fn quickwit_supported_sources() -> &'static SourceLoader {
 
 pub async fn check_source_connectivity(source_config: &SourceConfig) -> anyhow::Result<()> {
     match &source_config.source_params {
+        SourceParams::Dataset(params) => {
+            let storage = quickwit_storage_uri_resolver().resolve(&params.uri)?;
+            storage.check().await?;
+            Ok(())
+        }
+        SourceParams::Dir(params) => {
+            if !params.dir_path.is_dir() {
+                bail!("Directory `{}` does not exist.", params.dir_path.display())
+            }
+            Ok(())
+        }
         SourceParams::File(params) => {
-            if let Some(filepath) = &params.filepath {
+            if let Some(url) = params.http_url() {
+                file_source::check_http_file(url, &params.http_headers).await?;
+            } else if let Some(storage_uri) = params.storage_uri() {
+                let (storage, path) = file_source::resolve_storage_object(storage_uri)?;
+                if !storage.exists(&path).await? {
+                    bail!("File `{}` does not exist.", storage_uri)
+                }
+            } else if let Some(filepath) = &params.filepath {
                 if !Path::new(filepath).exists() {
                     bail!("File `{}` does not exist.", filepath.display())
                 }
             }
             Ok(())
         }
+        SourceParams::Grpc(params) => {
+            grpc_source::check_connectivity(params.clone()).await?;
+            Ok(())
+        }
         #[allow(unused_variables)]
         SourceParams::Kafka(params) => {
             #[cfg(not(feature = "kafka"))]
@@ -197,22 +369,163 @@ pub async fn check_source_connectivity(source_config: &SourceConfig) -> anyhow::
                 Ok(())
             }
         }
+        #[allow(unused_variables)]
+        SourceParams::Kinesis(params) => {
+            #[cfg(not(feature = "kinesis"))]
+            bail!("Quickwit binary was not compiled with the `kinesis` feature.");
+
+            #[cfg(feature = "kinesis")]
+            {
+                kinesis::check_connectivity(params.clone()).await?;
+                Ok(())
+            }
+        }
+        #[allow(unused_variables)]
+        SourceParams::Mqtt(params) => {
+            #[cfg(not(feature = "mqtt"))]
+            bail!("Quickwit binary was not compiled with the `mqtt` feature.");
+
+            #[cfg(feature = "mqtt")]
+            {
+                mqtt_source::check_connectivity(params.clone()).await?;
+                Ok(())
+            }
+        }
+        #[allow(unused_variables)]
+        SourceParams::Nats(params) => {
+            #[cfg(not(feature = "nats"))]
+            bail!("Quickwit binary was not compiled with the `nats` feature.");
+
+            #[cfg(feature = "nats")]
+            {
+                nats_source::check_connectivity(params.clone()).await?;
+                Ok(())
+            }
+        }
+        #[allow(unused_variables)]
+        SourceParams::PubSub(params) => {
+            #[cfg(not(feature = "pubsub"))]
+            bail!("Quickwit binary was not compiled with the `pubsub` feature.");
+
+            #[cfg(feature = "pubsub")]
+            {
+                pubsub_source::check_connectivity(params.clone()).await?;
+                Ok(())
+            }
+        }
+        #[allow(unused_variables)]
+        SourceParams::Pulsar(params) => {
+            #[cfg(not(feature = "pulsar"))]
+            bail!("Quickwit binary was not compiled with the `pulsar` feature.");
+
+            #[cfg(feature = "pulsar")]
+            {
+                pulsar_source::check_connectivity(params.clone()).await?;
+                Ok(())
+            }
+        }
+        #[allow(unused_variables)]
+        SourceParams::Sqs(params) => {
+            #[cfg(not(feature = "sqs"))]
+            bail!("Quickwit binary was not compiled with the `sqs` feature.");
+
+            #[cfg(feature = "sqs")]
+            {
+                sqs_source::check_connectivity(params.clone()).await?;
+                Ok(())
+            }
+        }
         _ => Ok(()),
     }
 }
 
+/// Resolves, for each partition of the source, the position of the last record published before
+/// `timestamp` (in milliseconds since the Unix epoch). The returned checkpoint can be used to
+/// reset the source so that indexing resumes from that point in time.
+#[cfg_attr(not(feature = "kafka"), allow(unused_variables))]
+pub async fn source_checkpoint_at_timestamp(
+    source_config: &SourceConfig,
+    timestamp: i64,
+) -> anyhow::Result<SourceCheckpoint> {
+    match &source_config.source_params {
+        SourceParams::Kafka(params) => {
+            #[cfg(not(feature = "kafka"))]
+            bail!("Quickwit binary was not compiled with the `kafka` feature.");
+
+            #[cfg(feature = "kafka")]
+            kafka_source::checkpoint_at_timestamp(params.clone(), timestamp).await
+        }
+        _ => bail!(
+            "Resetting the checkpoint to a timestamp is not supported by `{}` sources.",
+            source_config.source_type()
+        ),
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
+    use quickwit_actors::{create_test_mailbox, Universe};
     use quickwit_config::VecSourceParams;
 
     use super::*;
 
+    #[tokio::test]
+    async fn test_source_actor_pause_and_resume() -> anyhow::Result<()> {
+        let universe = Universe::new();
+        let (batch_sink, batch_inbox) = create_test_mailbox();
+        let params = VecSourceParams {
+            items: std::iter::repeat_with(|| "{}".to_string())
+                .take(100)
+                .collect(),
+            batch_num_docs: 1,
+            partition: "partition".to_string(),
+        };
+        let vec_source =
+            VecSourceFactory::typed_create_source(params, SourceCheckpoint::default()).await?;
+        let source_actor = SourceActor::new(Box::new(vec_source), batch_sink);
+        let (source_mailbox, source_handle) = universe.spawn_actor(source_actor).spawn_async();
+        universe
+            .send_message(&source_mailbox, SourceActorMessage::Pause)
+            .await?;
+        let paused_observation = source_handle.process_pending_and_observe().await.state;
+        let messages = batch_inbox.drain_available_message_for_test();
+        assert!(matches!(messages.last(), Some(IndexerMessage::Flush)));
+        let num_batches = messages.len() - 1;
+        assert!(num_batches < 100);
+        assert_eq!(paused_observation["next_item_idx"], num_batches);
+
+        // A paused source does not emit batches.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            source_handle.process_pending_and_observe().await.state,
+            paused_observation
+        );
+        assert!(batch_inbox.drain_available_message_for_test().is_empty());
+
+        universe
+            .send_message(&source_mailbox, SourceActorMessage::Resume)
+            .await?;
+        let (exit_status, last_observation) = source_handle.join().await;
+        assert!(exit_status.is_success());
+        assert_eq!(last_observation["next_item_idx"], 100);
+        assert_eq!(
+            batch_inbox.drain_available_message_for_test().len(),
+            100 - num_batches
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_check_source_connectivity() -> anyhow::Result<()> {
         {
             let source_config = SourceConfig {
                 source_id: "void".to_string(),
                 source_params: SourceParams::void(),
+                filter: None,
+                routing: None,
+                transform: None,
             };
             check_source_connectivity(&source_config).await?;
         }
@@ -220,6 +533,9 @@ mod tests {
             let source_config = SourceConfig {
                 source_id: "vec".to_string(),
                 source_params: SourceParams::Vec(VecSourceParams::default()),
+                filter: None,
+                routing: None,
+                transform: None,
             };
             check_source_connectivity(&source_config).await?;
         }
@@ -227,6 +543,9 @@ mod tests {
             let source_config = SourceConfig {
                 source_id: "file".to_string(),
                 source_params: SourceParams::file("file-does-not-exist.json"),
+                filter: None,
+                routing: None,
+                transform: None,
             };
             assert!(check_source_connectivity(&source_config).await.is_err());
         }
@@ -234,6 +553,9 @@ mod tests {
             let source_config = SourceConfig {
                 source_id: "file".to_string(),
                 source_params: SourceParams::file("data/test_corpus.json"),
+                filter: None,
+                routing: None,
+                transform: None,
             };
             assert!(check_source_connectivity(&source_config).await.is_ok());
         }
@@ -0,0 +1,578 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use quickwit_actors::{ActorExitStatus, Mailbox};
+use quickwit_config::MqttSourceParams;
+use quickwit_metastore::checkpoint::{CheckpointDelta, PartitionId, Position, SourceCheckpoint};
+use rumqttc::{
+    AsyncClient, ConnectionError, Event, EventLoop, MqttOptions, Packet, Publish, QoS, Transport,
+};
+use serde_json::json;
+use tracing::{debug, info, warn};
+use ulid::Ulid;
+
+use crate::models::RawDocBatch;
+use crate::source::{
+    backpressure, IndexerMessage, PayloadDecoder, Source, SourceContext, TypedSourceFactory,
+};
+
+/// Same target as the Kafka source: see `kafka_source::TARGET_BATCH_NUM_BYTES`.
+const TARGET_BATCH_NUM_BYTES: u64 = 5_000_000;
+
+/// Capacity of the channel buffering the requests (subscriptions, acknowledgements) of the
+/// client until the event loop sends them to the broker.
+const REQUEST_CHANNEL_CAPACITY: usize = 10_000;
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Factory for instantiating a `MqttSource`.
+pub struct MqttSourceFactory;
+
+#[async_trait]
+impl TypedSourceFactory for MqttSourceFactory {
+    type Source = MqttSource;
+    type Params = MqttSourceParams;
+
+    async fn typed_create_source(
+        params: MqttSourceParams,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<Self::Source> {
+        MqttSource::try_new(params, checkpoint).await
+    }
+}
+
+pub struct MqttSourceState {
+    /// Position of the last message received.
+    pub current_position: Position,
+    /// Messages emitted but not published yet, grouped by batch and keyed by the position of the
+    /// last message of the batch.
+    pub pending_acks: VecDeque<(Position, Vec<Publish>)>,
+    /// Number of bytes processed by the source.
+    pub num_bytes_processed: u64,
+    /// Number of messages processed by the source (including invalid messages).
+    pub num_messages_processed: u64,
+    // Number of invalid messages, i.e., that were empty or not valid UTF-8.
+    pub num_invalid_messages: u64,
+    /// Number of messages acknowledged after the publication of their split.
+    pub num_acked_messages: u64,
+}
+
+impl Default for MqttSourceState {
+    fn default() -> Self {
+        MqttSourceState {
+            current_position: Position::Beginning,
+            pending_acks: VecDeque::new(),
+            num_bytes_processed: 0,
+            num_messages_processed: 0,
+            num_invalid_messages: 0,
+            num_acked_messages: 0,
+        }
+    }
+}
+
+impl MqttSourceState {
+    /// Appends the decoded payloads of the messages to `docs`, records their positions in
+    /// `checkpoint_delta`, and holds the messages until the batch is published.
+    fn record_messages(
+        &mut self,
+        partition_id: &PartitionId,
+        messages: Vec<Publish>,
+        payload_decoder: &PayloadDecoder,
+        docs: &mut Vec<String>,
+        checkpoint_delta: &mut CheckpointDelta,
+    ) -> anyhow::Result<()> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+        for message in &messages {
+            match parse_message_payload(&message.topic, &message.payload, payload_decoder) {
+                Some(doc) => docs.push(doc),
+                None => self.num_invalid_messages += 1,
+            }
+            self.num_bytes_processed += message.payload.len() as u64;
+            self.num_messages_processed += 1;
+        }
+        let position = Position::from(self.num_messages_processed);
+        let previous_position = std::mem::replace(&mut self.current_position, position.clone());
+        checkpoint_delta
+            .record_partition_delta(partition_id.clone(), previous_position, position.clone())
+            .context("Failed to record partition delta.")?;
+        self.pending_acks.push_back((position, messages));
+        Ok(())
+    }
+
+    /// Returns the messages up to `published_position`, which can now be acknowledged.
+    fn take_published_messages(&mut self, published_position: &Position) -> Vec<Publish> {
+        let mut messages = Vec::new();
+        while let Some((position, _)) = self.pending_acks.front() {
+            if position > published_position {
+                break;
+            }
+            let (_, batch_messages) = self
+                .pending_acks
+                .pop_front()
+                .expect("The pending acks should not be empty.");
+            messages.extend(batch_messages);
+        }
+        messages
+    }
+}
+
+/// A `MqttSource` subscribes to topics of an MQTT broker with QoS 1 (at least once) and forwards
+/// the messages it receives to an `Indexer`.
+///
+/// The source opens a persistent session (clean session disabled) and acknowledges messages
+/// manually, once the split containing them is published. Messages that were received but not
+/// indexed are therefore redelivered by the broker when the source reconnects. Note that brokers
+/// cap the number of unacknowledged QoS 1 messages in flight per client (e.g.,
+/// `max_inflight_messages` for Mosquitto), which bounds the number of messages per split: this
+/// limit should be raised accordingly.
+///
+/// MQTT messages have no offset, so each instance of the source records its progress in a
+/// partition of its own, named after the client ID and a unique ID, with the number of messages
+/// processed as position.
+pub struct MqttSource {
+    params: MqttSourceParams,
+    client: AsyncClient,
+    event_loop: EventLoop,
+    partition_id: PartitionId,
+    payload_decoder: PayloadDecoder,
+    state: MqttSourceState,
+    /// Published messages whose acknowledgement is not handed over to the client yet.
+    messages_to_ack: VecDeque<Publish>,
+}
+
+impl fmt::Debug for MqttSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "MqttSource {{ broker_url: {}, client_id: {} }}",
+            self.params.broker_url, self.params.client_id
+        )
+    }
+}
+
+impl MqttSource {
+    /// Instantiates a new `MqttSource`.
+    pub async fn try_new(
+        params: MqttSourceParams,
+        _checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<MqttSource> {
+        let mut options = mqtt_options(&params, params.client_id.clone())?;
+        options.set_clean_session(false).set_manual_acks(true);
+        let payload_decoder = PayloadDecoder::try_new(params.decoding.as_ref()).await?;
+        let (client, mut event_loop) = AsyncClient::new(options, REQUEST_CHANNEL_CAPACITY);
+        connect(&params, &mut event_loop).await?;
+        for topic in &params.topics {
+            client
+                .subscribe(topic, QoS::AtLeastOnce)
+                .await
+                .with_context(|| format!("Failed to subscribe to topic `{}`.", topic))?;
+        }
+        let partition_id = PartitionId::from(format!("{}:{}", params.client_id, Ulid::new()));
+        info!(
+            broker_url = %params.broker_url,
+            topics = ?params.topics,
+            partition_id = ?partition_id,
+            "Starting MQTT source."
+        );
+        Ok(MqttSource {
+            params,
+            client,
+            event_loop,
+            partition_id,
+            payload_decoder,
+            state: MqttSourceState::default(),
+            messages_to_ack: VecDeque::new(),
+        })
+    }
+
+    /// Hands over the acknowledgements of the published messages to the client without waiting
+    /// for room in the request channel, since only the event loop polled by this very source
+    /// frees some.
+    fn flush_acks(&mut self) {
+        while let Some(message) = self.messages_to_ack.front() {
+            if self.client.try_ack(message).is_err() {
+                break;
+            }
+            self.messages_to_ack.pop_front();
+            self.state.num_acked_messages += 1;
+        }
+    }
+}
+
+#[async_trait]
+impl Source for MqttSource {
+    async fn emit_batches(
+        &mut self,
+        batch_sink: &Mailbox<IndexerMessage>,
+        ctx: &SourceContext,
+    ) -> Result<(), ActorExitStatus> {
+        self.flush_acks();
+
+        let mut messages = Vec::new();
+        let mut batch_num_bytes = 0;
+        let target_batch_num_bytes =
+            backpressure::target_batch_num_bytes(batch_sink, TARGET_BATCH_NUM_BYTES);
+
+        let deadline = ctx.sleep(quickwit_actors::HEARTBEAT / 2);
+        tokio::pin!(deadline);
+
+        loop {
+            let event_res = tokio::select! {
+                event_res = self.event_loop.poll() => event_res,
+                _ = &mut deadline => break,
+            };
+            match event_res {
+                Ok(Event::Incoming(Packet::Publish(message))) => {
+                    debug!(
+                        topic = %message.topic,
+                        pkid = message.pkid,
+                        num_bytes = message.payload.len(),
+                        "Message received.",
+                    );
+                    batch_num_bytes += message.payload.len() as u64;
+                    messages.push(message);
+                    if batch_num_bytes >= target_batch_num_bytes {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(ConnectionError::ConnectionRefused(return_code)) => {
+                    return Err(ActorExitStatus::from(anyhow::anyhow!(
+                        "Connection to MQTT broker `{}` was refused: {:?}",
+                        self.params.broker_url,
+                        return_code
+                    )));
+                }
+                // The event loop reconnects on the next poll and the broker resumes the
+                // persistent session, which does not justify failing the pipeline.
+                Err(error) => {
+                    warn!(
+                        broker_url = %self.params.broker_url,
+                        error = ?error,
+                        "Connection to MQTT broker lost."
+                    );
+                    break;
+                }
+            }
+        }
+        let mut docs = Vec::with_capacity(messages.len());
+        let mut checkpoint_delta = CheckpointDelta::default();
+        self.state.record_messages(
+            &self.partition_id,
+            messages,
+            &self.payload_decoder,
+            &mut docs,
+            &mut checkpoint_delta,
+        )?;
+        if !checkpoint_delta.is_empty() {
+            let batch = RawDocBatch {
+                docs,
+                checkpoint_delta,
+            };
+            ctx.send_message(batch_sink, IndexerMessage::from(batch))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn suggest_truncate(
+        &mut self,
+        checkpoint: SourceCheckpoint,
+        _ctx: &SourceContext,
+    ) -> anyhow::Result<()> {
+        let published_position =
+            if let Some(position) = checkpoint.position_for_partition(&self.partition_id) {
+                position
+            } else {
+                return Ok(());
+            };
+        let messages = self.state.take_published_messages(published_position);
+        self.messages_to_ack.extend(messages);
+        self.flush_acks();
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        "MqttSource".to_string()
+    }
+
+    fn observable_state(&self) -> serde_json::Value {
+        let num_pending_acks: usize = self
+            .state
+            .pending_acks
+            .iter()
+            .map(|(_, messages)| messages.len())
+            .sum::<usize>()
+            + self.messages_to_ack.len();
+        json!({
+            "broker_url": self.params.broker_url,
+            "topics": self.params.topics,
+            "partition_id": &*self.partition_id.0,
+            "current_position": self.state.current_position.as_str(),
+            "num_bytes_processed": self.state.num_bytes_processed,
+            "num_messages_processed": self.state.num_messages_processed,
+            "num_invalid_messages": self.state.num_invalid_messages,
+            "num_acked_messages": self.state.num_acked_messages,
+            "num_pending_acks": num_pending_acks,
+        })
+    }
+}
+
+/// Checks if connecting with the given parameters works.
+///
+/// The check uses a client ID of its own with a clean session: connecting with the client ID of
+/// the source would take over its session.
+pub(super) async fn check_connectivity(params: MqttSourceParams) -> anyhow::Result<()> {
+    let client_id = format!("{}-check-{}", params.client_id, Ulid::new());
+    let options = mqtt_options(&params, client_id)?;
+    let (_client, mut event_loop) = AsyncClient::new(options, 1);
+    connect(&params, &mut event_loop).await
+}
+
+/// Polls the event loop until the broker accepts the connection.
+async fn connect(params: &MqttSourceParams, event_loop: &mut EventLoop) -> anyhow::Result<()> {
+    let connack_future = async {
+        loop {
+            if let Event::Incoming(Packet::ConnAck(_)) = event_loop.poll().await? {
+                return Ok::<_, ConnectionError>(());
+            }
+        }
+    };
+    match tokio::time::timeout(CONNECTION_TIMEOUT, connack_future).await {
+        Ok(connack_res) => connack_res
+            .with_context(|| format!("Failed to connect to MQTT broker `{}`.", params.broker_url)),
+        Err(_) => bail!(
+            "Timed out connecting to MQTT broker `{}`.",
+            params.broker_url
+        ),
+    }
+}
+
+fn mqtt_options(params: &MqttSourceParams, client_id: String) -> anyhow::Result<MqttOptions> {
+    let (host, port, use_tls) = parse_broker_url(&params.broker_url)?;
+    let mut options = MqttOptions::new(client_id, host, port);
+    options.set_keep_alive(KEEP_ALIVE_INTERVAL);
+    if use_tls {
+        options.set_transport(Transport::tls_with_default_config());
+    }
+    if let Some(username) = &params.username {
+        let password = params.password.clone().unwrap_or_default();
+        options.set_credentials(username, password);
+    }
+    Ok(options)
+}
+
+/// Parses a broker URL of the form `mqtt[s]://host[:port]` into its host, port, and whether TLS
+/// is enabled. The port defaults to 1883, or 8883 with TLS.
+fn parse_broker_url(broker_url: &str) -> anyhow::Result<(String, u16, bool)> {
+    let (host_port, use_tls) = if let Some(host_port) = broker_url.strip_prefix("mqtt://") {
+        (host_port, false)
+    } else if let Some(host_port) = broker_url.strip_prefix("mqtts://") {
+        (host_port, true)
+    } else {
+        bail!(
+            "Broker URL `{}` must start with `mqtt://` or `mqtts://`.",
+            broker_url
+        );
+    };
+    let host_port = host_port.trim_end_matches('/');
+    let (host, port) = match host_port.rsplit_once(':') {
+        Some((host, port_str)) => {
+            let port = port_str.parse::<u16>().with_context(|| {
+                format!("Broker URL `{}` contains an invalid port.", broker_url)
+            })?;
+            (host, port)
+        }
+        None if use_tls => (host_port, 8883),
+        None => (host_port, 1883),
+    };
+    if host.is_empty() {
+        bail!("Broker URL `{}` must contain a host.", broker_url);
+    }
+    Ok((host.to_string(), port, use_tls))
+}
+
+/// Decodes the raw bytes of the message payload into a document, skipping corrupted or empty
+/// messages.
+fn parse_message_payload(
+    topic: &str,
+    payload: &[u8],
+    payload_decoder: &PayloadDecoder,
+) -> Option<String> {
+    if payload.is_empty() {
+        debug!(topic = %topic, "Message payload is empty.");
+        return None;
+    }
+    match payload_decoder.decode(payload) {
+        Ok(doc) => Some(doc),
+        Err(error) => {
+            warn!(
+                topic = %topic,
+                error = ?error,
+                "Failed to decode message payload."
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(payloads: &[&str]) -> Vec<Publish> {
+        payloads
+            .iter()
+            .map(|payload| {
+                let mut message =
+                    Publish::new("devices/0/telemetry", QoS::AtLeastOnce, payload.as_bytes());
+                message.pkid = payload.parse().unwrap_or(u16::MAX);
+                message
+            })
+            .collect()
+    }
+
+    fn pkids(messages: &[Publish]) -> Vec<u16> {
+        messages.iter().map(|message| message.pkid).collect()
+    }
+
+    #[test]
+    fn test_record_messages_and_take_published_messages() -> anyhow::Result<()> {
+        let partition_id = PartitionId::from("quickwit:0");
+        let mut state = MqttSourceState::default();
+        let mut docs = Vec::new();
+
+        let mut checkpoint_delta = CheckpointDelta::default();
+        state.record_messages(
+            &partition_id,
+            messages(&["0", "", "2"]),
+            &PayloadDecoder::Json,
+            &mut docs,
+            &mut checkpoint_delta,
+        )?;
+        assert_eq!(
+            checkpoint_delta,
+            CheckpointDelta::from_partition_delta(
+                partition_id.clone(),
+                Position::Beginning,
+                Position::from(3u64)
+            )
+        );
+        let mut checkpoint_delta = CheckpointDelta::default();
+        state.record_messages(
+            &partition_id,
+            Vec::new(),
+            &PayloadDecoder::Json,
+            &mut docs,
+            &mut checkpoint_delta,
+        )?;
+        assert!(checkpoint_delta.is_empty());
+
+        state.record_messages(
+            &partition_id,
+            messages(&["3"]),
+            &PayloadDecoder::Json,
+            &mut docs,
+            &mut checkpoint_delta,
+        )?;
+        state.record_messages(
+            &partition_id,
+            messages(&["4", "5"]),
+            &PayloadDecoder::Json,
+            &mut docs,
+            &mut checkpoint_delta,
+        )?;
+        assert_eq!(
+            checkpoint_delta,
+            CheckpointDelta::from_partition_delta(
+                partition_id,
+                Position::from(3u64),
+                Position::from(6u64)
+            )
+        );
+        assert_eq!(docs, vec!["0", "2", "3", "4", "5"]);
+        assert_eq!(state.num_messages_processed, 6);
+        assert_eq!(state.num_invalid_messages, 1);
+        assert_eq!(state.num_bytes_processed, 5);
+        assert_eq!(state.pending_acks.len(), 3);
+
+        assert!(state
+            .take_published_messages(&Position::from(2u64))
+            .is_empty());
+        assert_eq!(
+            pkids(&state.take_published_messages(&Position::from(4u64))),
+            vec![0, u16::MAX, 2, 3]
+        );
+        assert_eq!(
+            pkids(&state.take_published_messages(&Position::from(6u64))),
+            vec![4, 5]
+        );
+        assert!(state.pending_acks.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_broker_url() {
+        assert_eq!(
+            parse_broker_url("mqtt://localhost:1884").unwrap(),
+            ("localhost".to_string(), 1884, false)
+        );
+        assert_eq!(
+            parse_broker_url("mqtt://localhost").unwrap(),
+            ("localhost".to_string(), 1883, false)
+        );
+        assert_eq!(
+            parse_broker_url("mqtts://broker.example.com/").unwrap(),
+            ("broker.example.com".to_string(), 8883, true)
+        );
+        assert!(parse_broker_url("tcp://localhost:1883").is_err());
+        assert!(parse_broker_url("mqtt://localhost:port").is_err());
+        assert!(parse_broker_url("mqtt://:1883").is_err());
+    }
+
+    #[test]
+    fn test_parse_message_payload() {
+        assert_eq!(
+            parse_message_payload(
+                "telemetry",
+                br#"{"temperature": 21.5}"#,
+                &PayloadDecoder::Json
+            ),
+            Some(r#"{"temperature": 21.5}"#.to_string())
+        );
+        assert_eq!(
+            parse_message_payload("telemetry", b"", &PayloadDecoder::Json),
+            None
+        );
+        assert_eq!(
+            parse_message_payload("telemetry", &[0xff, 0xfe], &PayloadDecoder::Json),
+            None
+        );
+    }
+}
@@ -0,0 +1,212 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::time::{Duration, Instant};
+
+use quickwit_config::MultilineParams;
+use regex::Regex;
+
+/// Record assembled from one or several lines.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct MultilineRecord<T> {
+    /// Context of the first line of the record, e.g. the other fields of a syslog message.
+    pub context: T,
+    /// Lines of the record, joined by line feeds.
+    pub text: String,
+    /// Number of bytes of input consumed by the record.
+    pub num_bytes: usize,
+}
+
+#[derive(Clone)]
+struct PendingRecord<T> {
+    record: MultilineRecord<T>,
+    num_lines: usize,
+    last_line_at: Instant,
+}
+
+/// Assembles consecutive lines into multiline records, such as Java stack traces, following
+/// [`MultilineParams`].
+#[derive(Clone)]
+pub(crate) struct MultilineAssembler<T> {
+    start_regex_opt: Option<Regex>,
+    continuation_regex_opt: Option<Regex>,
+    max_lines: usize,
+    max_wait: Duration,
+    pending_opt: Option<PendingRecord<T>>,
+}
+
+impl<T> MultilineAssembler<T> {
+    pub fn try_new(params: &MultilineParams) -> anyhow::Result<Self> {
+        let (start_regex_opt, continuation_regex_opt) = params.regexes()?;
+        Ok(MultilineAssembler {
+            start_regex_opt,
+            continuation_regex_opt,
+            max_lines: params.max_lines.max(1),
+            max_wait: Duration::from_millis(params.max_wait_millis),
+            pending_opt: None,
+        })
+    }
+
+    /// Feeds the next line, without its line feed, along with its context and the number of
+    /// bytes of input it consumed. Returns the record that the line completes, if any.
+    pub fn push(
+        &mut self,
+        context: T,
+        line: &str,
+        num_bytes: usize,
+        now: Instant,
+    ) -> Option<MultilineRecord<T>> {
+        let is_continuation = self.is_continuation(line);
+        if let Some(pending) = &mut self.pending_opt {
+            if is_continuation && pending.num_lines < self.max_lines {
+                pending.record.text.push('\n');
+                pending.record.text.push_str(line);
+                pending.record.num_bytes += num_bytes;
+                pending.num_lines += 1;
+                pending.last_line_at = now;
+                return None;
+            }
+        }
+        let pending = PendingRecord {
+            record: MultilineRecord {
+                context,
+                text: line.to_string(),
+                num_bytes,
+            },
+            num_lines: 1,
+            last_line_at: now,
+        };
+        self.pending_opt
+            .replace(pending)
+            .map(|pending| pending.record)
+    }
+
+    /// Returns the pending record if it has waited longer than `max_wait` for its next line.
+    pub fn flush_expired(&mut self, now: Instant) -> Option<MultilineRecord<T>> {
+        let pending = self.pending_opt.as_ref()?;
+        if now.saturating_duration_since(pending.last_line_at) < self.max_wait {
+            return None;
+        }
+        self.flush()
+    }
+
+    /// Returns the pending record, complete or not.
+    pub fn flush(&mut self) -> Option<MultilineRecord<T>> {
+        self.pending_opt.take().map(|pending| pending.record)
+    }
+
+    /// Returns the number of bytes of input consumed by the pending record.
+    pub fn pending_num_bytes(&self) -> usize {
+        self.pending_opt
+            .as_ref()
+            .map(|pending| pending.record.num_bytes)
+            .unwrap_or(0)
+    }
+
+    fn is_continuation(&self, line: &str) -> bool {
+        if let Some(continuation_regex) = &self.continuation_regex_opt {
+            return continuation_regex.is_match(line);
+        }
+        if let Some(start_regex) = &self.start_regex_opt {
+            return !start_regex.is_match(line);
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn multiline_params(
+        start_pattern: Option<&str>,
+        continuation_pattern: Option<&str>,
+    ) -> MultilineParams {
+        MultilineParams {
+            start_pattern: start_pattern.map(|pattern| pattern.to_string()),
+            continuation_pattern: continuation_pattern.map(|pattern| pattern.to_string()),
+            max_lines: 3,
+            max_wait_millis: 1_000,
+        }
+    }
+
+    fn assemble(assembler: &mut MultilineAssembler<()>, lines: &[&str]) -> Vec<String> {
+        let now = Instant::now();
+        let mut records: Vec<String> = lines
+            .iter()
+            .flat_map(|line| assembler.push((), line, line.len() + 1, now))
+            .map(|record| record.text)
+            .collect();
+        records.extend(assembler.flush().map(|record| record.text));
+        records
+    }
+
+    #[test]
+    fn test_multiline_assembler_start_pattern() -> anyhow::Result<()> {
+        let mut assembler = MultilineAssembler::try_new(&multiline_params(Some(r"\d{4}-"), None))?;
+        let lines = [
+            "2022-02-22 ERROR Failed to connect.",
+            "java.net.ConnectException: Connection refused",
+            "    at java.net.PlainSocketImpl.socketConnect(Native Method)",
+            "2022-02-22 INFO Retrying.",
+        ];
+        assert_eq!(
+            assemble(&mut assembler, &lines),
+            vec![
+                "2022-02-22 ERROR Failed to connect.\njava.net.ConnectException: Connection \
+                 refused\n    at java.net.PlainSocketImpl.socketConnect(Native Method)",
+                "2022-02-22 INFO Retrying.",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiline_assembler_continuation_pattern_and_max_lines() -> anyhow::Result<()> {
+        let mut assembler = MultilineAssembler::try_new(&multiline_params(None, Some(r"\s")))?;
+        let lines = ["Exception", "  at a", "  at b", "  at c", "Done"];
+        assert_eq!(
+            assemble(&mut assembler, &lines),
+            vec!["Exception\n  at a\n  at b", "  at c", "Done"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiline_assembler_max_wait() -> anyhow::Result<()> {
+        let mut assembler = MultilineAssembler::try_new(&multiline_params(None, Some(r"\s")))?;
+        let now = Instant::now();
+        assert!(assembler.push("first", "Exception", 10, now).is_none());
+        assert!(assembler.push("second", "  at a", 7, now).is_none());
+        assert_eq!(assembler.pending_num_bytes(), 17);
+        assert!(assembler
+            .flush_expired(now + Duration::from_millis(999))
+            .is_none());
+        assert_eq!(
+            assembler.flush_expired(now + Duration::from_secs(1)),
+            Some(MultilineRecord {
+                context: "first",
+                text: "Exception\n  at a".to_string(),
+                num_bytes: 17,
+            })
+        );
+        assert_eq!(assembler.pending_num_bytes(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,711 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use async_nats::jetstream::consumer::pull::{
+    Config as PullConsumerConfig, Stream as PullMessageStream,
+};
+use async_nats::jetstream::consumer::{AckPolicy, DeliverPolicy, PullConsumer};
+use async_nats::jetstream::{self, Context as JetStreamContext};
+use async_nats::{Client, ConnectOptions};
+use async_trait::async_trait;
+use futures::StreamExt;
+use quickwit_actors::{ActorExitStatus, Mailbox};
+use quickwit_config::NatsSourceParams;
+use quickwit_metastore::checkpoint::{CheckpointDelta, PartitionId, Position, SourceCheckpoint};
+use serde_json::json;
+use tracing::{debug, info, warn};
+
+use crate::models::RawDocBatch;
+use crate::source::{
+    backpressure, IndexerMessage, PayloadDecoder, Source, SourceContext, TypedSourceFactory,
+};
+
+/// Same target as the Kafka source: see `kafka_source::TARGET_BATCH_NUM_BYTES`.
+const TARGET_BATCH_NUM_BYTES: u64 = 5_000_000;
+
+/// Messages are only acknowledged once their split is published, which can take a few commit
+/// timeouts, so the server waits longer than its default before redelivering them.
+const ACK_WAIT: Duration = Duration::from_secs(10 * 60);
+
+/// Factory for instantiating a `NatsSource`.
+pub struct NatsSourceFactory;
+
+#[async_trait]
+impl TypedSourceFactory for NatsSourceFactory {
+    type Source = NatsSource;
+    type Params = NatsSourceParams;
+
+    async fn typed_create_source(
+        params: NatsSourceParams,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<Self::Source> {
+        NatsSource::try_new(params, checkpoint).await
+    }
+}
+
+pub struct NatsSourceState {
+    /// Position of the last message received, or the checkpointed position if no message has
+    /// been received yet.
+    pub current_position: Position,
+    /// Reply subjects of the last message of the batches emitted but not published yet, keyed
+    /// by the position of the message. The consumer acknowledges all the messages up to the
+    /// acknowledged one, so a single message is acknowledged per batch.
+    pub pending_acks: VecDeque<(Position, String)>,
+    /// Number of bytes processed by the source.
+    pub num_bytes_processed: u64,
+    /// Number of messages processed by the source (including invalid messages).
+    pub num_messages_processed: u64,
+    // Number of invalid messages, i.e., that were empty or not valid UTF-8.
+    pub num_invalid_messages: u64,
+    /// Number of messages delivered again, after a restart or once their acknowledgement wait
+    /// expired, and skipped because the checkpoint already covers them.
+    pub num_skipped_messages: u64,
+}
+
+impl Default for NatsSourceState {
+    fn default() -> Self {
+        NatsSourceState {
+            current_position: Position::Beginning,
+            pending_acks: VecDeque::new(),
+            num_bytes_processed: 0,
+            num_messages_processed: 0,
+            num_invalid_messages: 0,
+            num_skipped_messages: 0,
+        }
+    }
+}
+
+impl NatsSourceState {
+    /// Appends the decoded message payload to `docs` and records its stream sequence in
+    /// `checkpoint_delta`. Returns `false` if the message was skipped because the checkpoint
+    /// already covers it.
+    fn record_message(
+        &mut self,
+        partition_id: &PartitionId,
+        stream_sequence: u64,
+        payload: &[u8],
+        payload_decoder: &PayloadDecoder,
+        docs: &mut Vec<String>,
+        checkpoint_delta: &mut CheckpointDelta,
+    ) -> anyhow::Result<bool> {
+        let position = Position::from(stream_sequence);
+        if position <= self.current_position {
+            self.num_skipped_messages += 1;
+            return Ok(false);
+        }
+        let previous_position = std::mem::replace(&mut self.current_position, position.clone());
+        checkpoint_delta
+            .record_partition_delta(partition_id.clone(), previous_position, position)
+            .context("Failed to record partition delta.")?;
+
+        match parse_message_payload(stream_sequence, payload, payload_decoder) {
+            Some(doc) => docs.push(doc),
+            None => self.num_invalid_messages += 1,
+        }
+        self.num_bytes_processed += payload.len() as u64;
+        self.num_messages_processed += 1;
+        Ok(true)
+    }
+
+    /// Returns the reply subject of the last message up to `published_position`, acknowledging
+    /// which acknowledges all the published messages.
+    fn take_published_reply_subject(&mut self, published_position: &Position) -> Option<String> {
+        let mut reply_subject_opt = None;
+        while let Some((position, _)) = self.pending_acks.front() {
+            if position > published_position {
+                break;
+            }
+            let (_, reply_subject) = self
+                .pending_acks
+                .pop_front()
+                .expect("The pending acks should not be empty.");
+            reply_subject_opt = Some(reply_subject);
+        }
+        reply_subject_opt
+    }
+}
+
+/// A `NatsSource` consumes a NATS JetStream stream with a durable pull consumer and forwards its
+/// messages to an `Indexer`.
+///
+/// The stream sequences of the messages are recorded in the checkpoint, in a partition named
+/// after the stream. When the consumer is created, its delivery starts right after the
+/// checkpointed sequence, and messages delivered again are skipped, so that a message is indexed
+/// exactly once. Messages are acknowledged once the split containing them is published, which
+/// lets streams with a work queue or interest retention policy discard them.
+pub struct NatsSource {
+    stream_name: String,
+    consumer_name: String,
+    client: Client,
+    partition_id: PartitionId,
+    message_stream: PullMessageStream,
+    payload_decoder: PayloadDecoder,
+    state: NatsSourceState,
+}
+
+impl fmt::Debug for NatsSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "NatsSource {{ stream:{}, consumer:{} }}",
+            self.stream_name, self.consumer_name
+        )
+    }
+}
+
+impl NatsSource {
+    /// Instantiates a new `NatsSource`.
+    pub async fn try_new(
+        params: NatsSourceParams,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<NatsSource> {
+        let payload_decoder = PayloadDecoder::try_new(params.decoding.as_ref()).await?;
+        let client = connect(&params).await?;
+        let jetstream = jetstream::new(client.clone());
+        let partition_id = PartitionId::from(params.stream.as_str());
+        let current_position = checkpoint
+            .position_for_partition(&partition_id)
+            .cloned()
+            .unwrap_or(Position::Beginning);
+        let checkpointed_sequence = sequence_from_position(&current_position)?;
+        info!(
+            stream = %params.stream,
+            consumer = %params.consumer,
+            position = ?current_position,
+            "Starting NATS JetStream source."
+        );
+        let consumer = get_or_create_consumer(&jetstream, &params, checkpointed_sequence).await?;
+        let message_stream = consumer.messages().await.map_err(|error| {
+            anyhow::anyhow!(
+                "Failed to consume messages of stream `{}`: {}",
+                params.stream,
+                error
+            )
+        })?;
+        let state = NatsSourceState {
+            current_position,
+            ..Default::default()
+        };
+        Ok(NatsSource {
+            stream_name: params.stream,
+            consumer_name: params.consumer,
+            client,
+            partition_id,
+            message_stream,
+            payload_decoder,
+            state,
+        })
+    }
+}
+
+#[async_trait]
+impl Source for NatsSource {
+    async fn emit_batches(
+        &mut self,
+        batch_sink: &Mailbox<IndexerMessage>,
+        ctx: &SourceContext,
+    ) -> Result<(), ActorExitStatus> {
+        let mut docs = Vec::new();
+        let mut checkpoint_delta = CheckpointDelta::default();
+        let mut last_reply_subject_opt = None;
+
+        let deadline = ctx.sleep(quickwit_actors::HEARTBEAT / 2);
+        let mut message_stream = Box::pin((&mut self.message_stream).take_until(deadline));
+
+        let mut batch_num_bytes = 0;
+        let target_batch_num_bytes =
+            backpressure::target_batch_num_bytes(batch_sink, TARGET_BATCH_NUM_BYTES);
+
+        while let Some(message_res) = message_stream.next().await {
+            // FIXME: This is assuming that JetStream errors are not recoverable, it may not be the
+            // case.
+            let message = message_res.map_err(|error| {
+                ActorExitStatus::from(anyhow::anyhow!(
+                    "Failed to receive message from stream `{}`: {}",
+                    self.stream_name,
+                    error
+                ))
+            })?;
+            let stream_sequence = message
+                .info()
+                .map_err(|error| {
+                    ActorExitStatus::from(anyhow::anyhow!(
+                        "Failed to read metadata of message: {}",
+                        error
+                    ))
+                })?
+                .stream_sequence;
+            let payload = &message.payload;
+            if self.state.record_message(
+                &self.partition_id,
+                stream_sequence,
+                payload,
+                &self.payload_decoder,
+                &mut docs,
+                &mut checkpoint_delta,
+            )? {
+                batch_num_bytes += payload.len() as u64;
+                last_reply_subject_opt = message.reply.clone();
+            }
+            if batch_num_bytes >= target_batch_num_bytes {
+                break;
+            }
+        }
+        if let Some(reply_subject) = last_reply_subject_opt {
+            self.state
+                .pending_acks
+                .push_back((self.state.current_position.clone(), reply_subject));
+        }
+        if !checkpoint_delta.is_empty() {
+            let batch = RawDocBatch {
+                docs,
+                checkpoint_delta,
+            };
+            ctx.send_message(batch_sink, IndexerMessage::from(batch))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn suggest_truncate(
+        &mut self,
+        checkpoint: SourceCheckpoint,
+        ctx: &SourceContext,
+    ) -> anyhow::Result<()> {
+        let published_position =
+            if let Some(position) = checkpoint.position_for_partition(&self.partition_id) {
+                position
+            } else {
+                return Ok(());
+            };
+        let reply_subject = match self.state.take_published_reply_subject(published_position) {
+            Some(reply_subject) => reply_subject,
+            None => return Ok(()),
+        };
+        let _protect_guard = ctx.protect_zone();
+        // The published messages are acknowledged again along with the next batch if this
+        // acknowledgement is lost, which does not justify failing the pipeline.
+        let ack_res = match self.client.publish(reply_subject, "+ACK".into()).await {
+            Ok(()) => self.client.flush().await.map_err(|error| error.to_string()),
+            Err(error) => Err(error.to_string()),
+        };
+        if let Err(error) = ack_res {
+            warn!(
+                stream = %self.stream_name,
+                consumer = %self.consumer_name,
+                error = %error,
+                "Failed to acknowledge messages."
+            );
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        "NatsSource".to_string()
+    }
+
+    fn observable_state(&self) -> serde_json::Value {
+        json!({
+            "stream": self.stream_name,
+            "consumer": self.consumer_name,
+            "current_position": self.state.current_position.as_str(),
+            "num_pending_acks": self.state.pending_acks.len(),
+            "num_bytes_processed": self.state.num_bytes_processed,
+            "num_messages_processed": self.state.num_messages_processed,
+            "num_invalid_messages": self.state.num_invalid_messages,
+            "num_skipped_messages": self.state.num_skipped_messages,
+        })
+    }
+}
+
+/// Checks if connecting with the given parameters works.
+pub(super) async fn check_connectivity(params: NatsSourceParams) -> anyhow::Result<()> {
+    let client = connect(&params).await?;
+    let jetstream = jetstream::new(client);
+    jetstream
+        .get_stream(&params.stream)
+        .await
+        .map_err(|error| {
+            anyhow::anyhow!("Failed to look up stream `{}`: {}", params.stream, error)
+        })?;
+    Ok(())
+}
+
+/// Connects to the NATS server, authenticating with the token of the parameters if any.
+async fn connect(params: &NatsSourceParams) -> anyhow::Result<Client> {
+    let options = match &params.auth_token {
+        Some(auth_token) => ConnectOptions::with_token(auth_token.clone()),
+        None => ConnectOptions::new(),
+    };
+    options
+        .name("quickwit")
+        .connect(params.address.as_str())
+        .await
+        .with_context(|| format!("Failed to connect to NATS server at `{}`.", params.address))
+}
+
+/// Looks up the durable consumer of the source, creating it with a delivery starting right
+/// after `checkpointed_sequence` if it does not exist.
+///
+/// An existing consumer resumes from its own state: the messages that it delivers again are
+/// skipped, and the messages not acknowledged are redelivered once their acknowledgement wait
+/// expires.
+async fn get_or_create_consumer(
+    jetstream: &JetStreamContext,
+    params: &NatsSourceParams,
+    checkpointed_sequence: u64,
+) -> anyhow::Result<PullConsumer> {
+    let stream = jetstream
+        .get_stream(&params.stream)
+        .await
+        .map_err(|error| {
+            anyhow::anyhow!("Failed to look up stream `{}`: {}", params.stream, error)
+        })?;
+    let deliver_policy = if checkpointed_sequence == 0 {
+        DeliverPolicy::All
+    } else {
+        DeliverPolicy::ByStartSequence {
+            start_sequence: checkpointed_sequence + 1,
+        }
+    };
+    let config = PullConsumerConfig {
+        durable_name: Some(params.consumer.clone()),
+        deliver_policy,
+        ack_policy: AckPolicy::All,
+        ack_wait: ACK_WAIT,
+        // The messages of several batches are pending until their split is published.
+        max_ack_pending: -1,
+        filter_subject: params.filter_subject.clone().unwrap_or_default(),
+        ..Default::default()
+    };
+    let mut consumer: PullConsumer = stream
+        .get_or_create_consumer(&params.consumer, config)
+        .await
+        .map_err(|error| {
+            anyhow::anyhow!(
+                "Failed to create consumer `{}` on stream `{}`: {}",
+                params.consumer,
+                params.stream,
+                error
+            )
+        })?;
+    let consumer_info = consumer.info().await.map_err(|error| {
+        anyhow::anyhow!(
+            "Failed to fetch info of consumer `{}`: {}",
+            params.consumer,
+            error
+        )
+    })?;
+    if consumer_info.ack_floor.stream_sequence > checkpointed_sequence {
+        warn!(
+            stream = %params.stream,
+            consumer = %params.consumer,
+            ack_floor = consumer_info.ack_floor.stream_sequence,
+            checkpointed_sequence = checkpointed_sequence,
+            "The consumer acknowledged messages not covered by the checkpoint, which cannot be \
+             delivered again. Delete the consumer to consume them."
+        );
+    }
+    Ok(consumer)
+}
+
+/// Converts a position back into the stream sequence of the last message indexed, `0` standing
+/// for the beginning of the stream.
+fn sequence_from_position(position: &Position) -> anyhow::Result<u64> {
+    match position {
+        Position::Beginning => Ok(0),
+        Position::Offset(position_str) => match position_str.parse() {
+            Ok(sequence) => Ok(sequence),
+            Err(_) => bail!("Failed to parse stream sequence `{}`.", position_str),
+        },
+    }
+}
+
+/// Decodes the raw bytes of the message payload into a document, skipping corrupted or empty
+/// messages.
+fn parse_message_payload(
+    stream_sequence: u64,
+    payload: &[u8],
+    payload_decoder: &PayloadDecoder,
+) -> Option<String> {
+    if payload.is_empty() {
+        debug!(
+            stream_sequence = stream_sequence,
+            "Message payload is empty."
+        );
+        return None;
+    }
+    match payload_decoder.decode(payload) {
+        Ok(doc) => {
+            debug!(
+                stream_sequence = stream_sequence,
+                num_bytes = payload.len(),
+                "Message received.",
+            );
+            Some(doc)
+        }
+        Err(error) => {
+            warn!(
+                stream_sequence = stream_sequence,
+                error = ?error,
+                "Failed to decode message payload."
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_from_position() -> anyhow::Result<()> {
+        assert_eq!(sequence_from_position(&Position::Beginning)?, 0);
+        assert_eq!(sequence_from_position(&Position::from(42u64))?, 42);
+        assert!(sequence_from_position(&Position::from("foo")).is_err());
+        assert!(Position::from(9u64) < Position::from(10u64));
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_message() -> anyhow::Result<()> {
+        let partition_id = PartitionId::from("LOGS");
+        let mut state = NatsSourceState {
+            current_position: Position::from(2u64),
+            ..Default::default()
+        };
+        let mut docs = Vec::new();
+        let mut checkpoint_delta = CheckpointDelta::default();
+
+        // The messages covered by the checkpoint are delivered again and skipped.
+        for stream_sequence in [1, 2] {
+            assert!(!state.record_message(
+                &partition_id,
+                stream_sequence,
+                b"Message",
+                &PayloadDecoder::Json,
+                &mut docs,
+                &mut checkpoint_delta,
+            )?);
+        }
+        assert!(state.record_message(
+            &partition_id,
+            3,
+            b"Message #3",
+            &PayloadDecoder::Json,
+            &mut docs,
+            &mut checkpoint_delta,
+        )?);
+        // Messages filtered out of the consumer leave gaps in the stream sequences.
+        assert!(state.record_message(
+            &partition_id,
+            5,
+            b"",
+            &PayloadDecoder::Json,
+            &mut docs,
+            &mut checkpoint_delta,
+        )?);
+        assert!(!state.record_message(
+            &partition_id,
+            3,
+            b"Message #3",
+            &PayloadDecoder::Json,
+            &mut docs,
+            &mut checkpoint_delta,
+        )?);
+
+        assert_eq!(docs, vec!["Message #3".to_string()]);
+        assert_eq!(state.num_skipped_messages, 3);
+        assert_eq!(state.num_messages_processed, 2);
+        assert_eq!(state.num_invalid_messages, 1);
+        assert_eq!(state.num_bytes_processed, 10);
+
+        let expected_checkpoint_delta = CheckpointDelta::from_partition_delta(
+            partition_id,
+            Position::from(2u64),
+            Position::from(5u64),
+        );
+        assert_eq!(checkpoint_delta, expected_checkpoint_delta);
+        Ok(())
+    }
+
+    #[test]
+    fn test_take_published_reply_subject() {
+        let mut state = NatsSourceState::default();
+        for stream_sequence in [3u64, 7, 12] {
+            state.pending_acks.push_back((
+                Position::from(stream_sequence),
+                format!("$JS.ACK.LOGS.quickwit.1.{}", stream_sequence),
+            ));
+        }
+        assert_eq!(
+            state.take_published_reply_subject(&Position::from(2u64)),
+            None
+        );
+        assert_eq!(
+            state.take_published_reply_subject(&Position::from(10u64)),
+            Some("$JS.ACK.LOGS.quickwit.1.7".to_string())
+        );
+        assert_eq!(state.pending_acks.len(), 1);
+        assert_eq!(
+            state.take_published_reply_subject(&Position::from(12u64)),
+            Some("$JS.ACK.LOGS.quickwit.1.12".to_string())
+        );
+        assert!(state.pending_acks.is_empty());
+    }
+
+    #[test]
+    fn test_parse_message_payload() {
+        assert_eq!(
+            parse_message_payload(1, b"Message #1", &PayloadDecoder::Json),
+            Some("Message #1".to_string())
+        );
+        assert_eq!(parse_message_payload(1, b"", &PayloadDecoder::Json), None);
+        assert_eq!(
+            parse_message_payload(1, &[0xff, 0xfe], &PayloadDecoder::Json),
+            None
+        );
+    }
+}
+
+#[cfg(all(test, feature = "nats-server-tests"))]
+mod nats_server_tests {
+    use std::time::Duration;
+
+    use async_nats::jetstream::stream::Config as StreamConfig;
+    use quickwit_actors::{create_test_mailbox, Universe};
+    use quickwit_common::rand::append_random_suffix;
+    use quickwit_config::SourceParams;
+
+    use super::*;
+    use crate::source::{quickwit_supported_sources, SourceActor};
+    use crate::SourceConfig;
+
+    const NATS_ADDRESS: &str = "nats://localhost:4222";
+
+    async fn populate_stream(subject: &str, messages: &[&str]) -> anyhow::Result<()> {
+        let client = async_nats::connect(NATS_ADDRESS).await?;
+        let jetstream = jetstream::new(client);
+        for message in messages {
+            jetstream
+                .publish(subject.to_string(), message.as_bytes().to_vec().into())
+                .await
+                .map_err(|error| anyhow::anyhow!("{}", error))?
+                .await
+                .map_err(|error| anyhow::anyhow!("{}", error))?;
+        }
+        Ok(())
+    }
+
+    fn merge_messages(messages: Vec<IndexerMessage>) -> anyhow::Result<RawDocBatch> {
+        let mut merged_batch = RawDocBatch::default();
+        for message in messages {
+            if let IndexerMessage::Batch(batch) = message {
+                merged_batch.docs.extend(batch.docs);
+                merged_batch
+                    .checkpoint_delta
+                    .extend(batch.checkpoint_delta)?;
+            }
+        }
+        Ok(merged_batch)
+    }
+
+    /// Runs the source until it has processed `num_messages` messages.
+    async fn run_source(
+        universe: &Universe,
+        source_config: &SourceConfig,
+        checkpoint: SourceCheckpoint,
+        num_messages: u64,
+    ) -> anyhow::Result<(RawDocBatch, serde_json::Value)> {
+        let (sink, inbox) = create_test_mailbox();
+        let source = quickwit_supported_sources()
+            .load_source(source_config.clone(), checkpoint)
+            .await?;
+        let actor = SourceActor::new(source, sink.clone());
+        let (_mailbox, handle) = universe.spawn_actor(actor).spawn_async();
+        for _ in 0..100 {
+            let observation = handle.observe().await;
+            if observation.state["num_messages_processed"] == json!(num_messages) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let (_exit_status, exit_state) = handle.quit().await;
+        let batch = merge_messages(inbox.drain_available_message_for_test())?;
+        Ok((batch, exit_state))
+    }
+
+    #[tokio::test]
+    async fn test_nats_source() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+
+        let universe = Universe::new();
+        let stream_name = append_random_suffix("test-nats-source-stream");
+        let subject = format!("{}.logs", stream_name);
+        let client = async_nats::connect(NATS_ADDRESS).await?;
+        jetstream::new(client)
+            .create_stream(StreamConfig {
+                name: stream_name.clone(),
+                subjects: vec![format!("{}.>", stream_name)],
+                ..Default::default()
+            })
+            .await
+            .map_err(|error| anyhow::anyhow!("{}", error))?;
+        populate_stream(&subject, &["Message #1", "", "Message #3"]).await?;
+
+        let source_config = SourceConfig {
+            source_id: "test-nats-source".to_string(),
+            source_params: SourceParams::Nats(NatsSourceParams {
+                address: NATS_ADDRESS.to_string(),
+                stream: stream_name.clone(),
+                consumer: "quickwit".to_string(),
+                filter_subject: None,
+                auth_token: None,
+                decoding: None,
+            }),
+            filter: None,
+            routing: None,
+            transform: None,
+        };
+        let (batch, exit_state) =
+            run_source(&universe, &source_config, SourceCheckpoint::default(), 3).await?;
+        assert_eq!(batch.docs, vec!["Message #1", "Message #3"]);
+        assert_eq!(
+            exit_state["current_position"],
+            json!(Position::from(3u64).as_str())
+        );
+        assert_eq!(exit_state["num_invalid_messages"], json!(1));
+
+        let mut checkpoint = SourceCheckpoint::default();
+        checkpoint.try_apply_delta(batch.checkpoint_delta)?;
+
+        // The durable consumer resumes after the messages it already delivered.
+        populate_stream(&subject, &["Message #4"]).await?;
+        let (batch, exit_state) = run_source(&universe, &source_config, checkpoint, 1).await?;
+        assert_eq!(batch.docs, vec!["Message #4"]);
+        assert_eq!(exit_state["num_messages_processed"], json!(1));
+        Ok(())
+    }
+}
@@ -0,0 +1,966 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::net::SocketAddr;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use futures::StreamExt;
+use prost::Message;
+use quickwit_actors::{ActorExitStatus, Mailbox};
+use quickwit_config::OtlpSourceParams;
+use quickwit_metastore::checkpoint::{CheckpointDelta, PartitionId, Position, SourceCheckpoint};
+use quickwit_proto::otlp_trace;
+use quickwit_proto::otlp_trace::any_value::Value as AnyValueValue;
+use quickwit_proto::otlp_trace::trace_service_server::{TraceService, TraceServiceServer};
+use serde::{Deserialize, Deserializer};
+use serde_json::{json, Map as JsonMap, Value as JsonValue};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tracing::{error, info, warn};
+use warp::http::StatusCode;
+use warp::hyper::body::Bytes;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+use crate::models::{IndexerMessage, RawDocBatch};
+use crate::source::{Source, SourceContext, TypedSourceFactory};
+
+/// Maximum size of an OTLP export request body.
+const MAX_REQUEST_BODY_NUM_BYTES: u64 = 10_000_000;
+
+/// Maximum number of documents emitted in one batch.
+const BATCH_NUM_DOCS_LIMIT: usize = 5_000;
+
+/// Number of export requests buffered between the HTTP receiver and the source.
+const REQUEST_CHANNEL_CAPACITY: usize = 32;
+
+/// Receives the spans pushed by OpenTelemetry exporters and collectors with the OTLP/HTTP
+/// protocol, in its protobuf and JSON encodings, and the OTLP/gRPC protocol, and emits one
+/// document per span.
+///
+/// Export requests are acknowledged as soon as their spans are queued for indexing. Spans
+/// that are not yet published when the pipeline stops are lost.
+pub struct OtlpSource {
+    listen_address: SocketAddr,
+    grpc_listen_address: SocketAddr,
+    partition: PartitionId,
+    next_offset: u64,
+    doc_stream: ReceiverStream<Vec<String>>,
+    doc_sender_opt: Option<mpsc::Sender<Vec<String>>>,
+    shutdown_senders: Vec<oneshot::Sender<()>>,
+    num_spans_received: u64,
+}
+
+pub struct OtlpSourceFactory;
+
+#[async_trait]
+impl TypedSourceFactory for OtlpSourceFactory {
+    type Source = OtlpSource;
+    type Params = OtlpSourceParams;
+
+    async fn typed_create_source(
+        params: OtlpSourceParams,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<Self::Source> {
+        let listen_address: SocketAddr = params.listen_address.parse().with_context(|| {
+            format!(
+                "Failed to parse OTLP listen address `{}`.",
+                params.listen_address
+            )
+        })?;
+        let grpc_listen_address: SocketAddr =
+            params.grpc_listen_address.parse().with_context(|| {
+                format!(
+                    "Failed to parse OTLP gRPC listen address `{}`.",
+                    params.grpc_listen_address
+                )
+            })?;
+        let partition = PartitionId::from(params.listen_address.as_str());
+        let next_offset = match checkpoint.position_for_partition(&partition) {
+            Some(Position::Offset(offset_str)) => offset_str.parse::<u64>()? + 1,
+            Some(Position::Beginning) | None => 0,
+        };
+        let (doc_sender, doc_receiver) = mpsc::channel(REQUEST_CHANNEL_CAPACITY);
+        Ok(OtlpSource {
+            listen_address,
+            grpc_listen_address,
+            partition,
+            next_offset,
+            doc_stream: ReceiverStream::new(doc_receiver),
+            doc_sender_opt: Some(doc_sender),
+            shutdown_senders: Vec::new(),
+            num_spans_received: 0,
+        })
+    }
+}
+
+fn position_from_offset(offset: u64) -> Position {
+    if offset == 0 {
+        return Position::Beginning;
+    }
+    Position::from(offset - 1)
+}
+
+#[async_trait]
+impl Source for OtlpSource {
+    async fn initialize(&mut self, _ctx: &SourceContext) -> Result<(), ActorExitStatus> {
+        let doc_sender = self
+            .doc_sender_opt
+            .take()
+            .expect("The OTLP receiver should be started only once.");
+        let grpc_listener = TcpListener::bind(self.grpc_listen_address)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to bind OTLP gRPC receiver on `{}`.",
+                    self.grpc_listen_address
+                )
+            })?;
+        let (shutdown_sender, shutdown_receiver) = oneshot::channel::<()>();
+        let (listen_address, server) = warp::serve(otlp_traces_filter(doc_sender.clone()))
+            .try_bind_with_graceful_shutdown(self.listen_address, async {
+                shutdown_receiver.await.ok();
+            })
+            .with_context(|| {
+                format!("Failed to bind OTLP receiver on `{}`.", self.listen_address)
+            })?;
+        info!(listen_address = %listen_address, "Starting OTLP trace receiver.");
+        tokio::spawn(server);
+        self.shutdown_senders.push(shutdown_sender);
+
+        let (grpc_shutdown_sender, grpc_shutdown_receiver) = oneshot::channel::<()>();
+        let grpc_listen_address = grpc_listener.local_addr().map_err(anyhow::Error::from)?;
+        let grpc_server = tonic::transport::Server::builder()
+            .add_service(TraceServiceServer::new(OtlpTraceService { doc_sender }))
+            .serve_with_incoming_shutdown(TcpListenerStream::new(grpc_listener), async {
+                grpc_shutdown_receiver.await.ok();
+            });
+        info!(grpc_listen_address = %grpc_listen_address, "Starting OTLP gRPC trace receiver.");
+        tokio::spawn(async move {
+            if let Err(error) = grpc_server.await {
+                error!(error = ?error, "OTLP gRPC trace receiver failed.");
+            }
+        });
+        self.shutdown_senders.push(grpc_shutdown_sender);
+        Ok(())
+    }
+
+    async fn emit_batches(
+        &mut self,
+        batch_sink: &Mailbox<IndexerMessage>,
+        ctx: &SourceContext,
+    ) -> Result<(), ActorExitStatus> {
+        let mut docs = Vec::new();
+        let deadline = ctx.sleep(quickwit_actors::HEARTBEAT / 2);
+        let mut request_stream = Box::pin((&mut self.doc_stream).take_until(deadline));
+
+        while let Some(request_docs) = request_stream.next().await {
+            docs.extend(request_docs);
+            if docs.len() >= BATCH_NUM_DOCS_LIMIT {
+                break;
+            }
+            ctx.record_progress();
+        }
+        if docs.is_empty() {
+            return Ok(());
+        }
+        let from_offset = self.next_offset;
+        self.next_offset += docs.len() as u64;
+        self.num_spans_received += docs.len() as u64;
+        let checkpoint_delta = CheckpointDelta::from_partition_delta(
+            self.partition.clone(),
+            position_from_offset(from_offset),
+            position_from_offset(self.next_offset),
+        );
+        let batch = RawDocBatch {
+            docs,
+            checkpoint_delta,
+        };
+        ctx.send_message(batch_sink, IndexerMessage::from(batch))
+            .await?;
+        Ok(())
+    }
+
+    async fn finalize(
+        &mut self,
+        _exit_status: &ActorExitStatus,
+        _ctx: &SourceContext,
+    ) -> anyhow::Result<()> {
+        for shutdown_sender in self.shutdown_senders.drain(..) {
+            let _ = shutdown_sender.send(());
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        "OtlpSource".to_string()
+    }
+
+    fn observable_state(&self) -> serde_json::Value {
+        json!({
+            "listen_address": self.listen_address.to_string(),
+            "grpc_listen_address": self.grpc_listen_address.to_string(),
+            "next_offset": self.next_offset,
+            "num_spans_received": self.num_spans_received,
+        })
+    }
+}
+
+/// Handles the `POST /v1/traces` OTLP/HTTP export requests, encoded in protobuf or JSON
+/// according to their content type. Requests without content type are decoded as JSON.
+fn otlp_traces_filter(
+    doc_sender: mpsc::Sender<Vec<String>>,
+) -> impl Filter<Extract = (Response,), Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "traces")
+        .and(warp::post())
+        .and(warp::body::content_length_limit(MAX_REQUEST_BODY_NUM_BYTES))
+        .and(warp::header::optional::<String>("content-type"))
+        .and(warp::body::bytes())
+        .and(warp::any().map(move || doc_sender.clone()))
+        .then(export_traces)
+}
+
+async fn export_traces(
+    content_type_opt: Option<String>,
+    body: Bytes,
+    doc_sender: mpsc::Sender<Vec<String>>,
+) -> Response {
+    let media_type = content_type_opt
+        .as_deref()
+        .and_then(|content_type| content_type.split(';').next())
+        .map(str::trim)
+        .unwrap_or("application/json");
+    match media_type {
+        "application/x-protobuf" => {
+            let request = match otlp_trace::ExportTraceServiceRequest::decode(body) {
+                Ok(request) => request,
+                Err(error) => return bad_request_reply(error),
+            };
+            let status_code = queue_docs(spans_to_docs(request.into()), &doc_sender).await;
+            let response_bytes = otlp_trace::ExportTraceServiceResponse::default().encode_to_vec();
+            warp::reply::with_status(
+                warp::reply::with_header(response_bytes, "content-type", "application/x-protobuf"),
+                status_code,
+            )
+            .into_response()
+        }
+        "application/json" => {
+            let request = match serde_json::from_slice::<ExportTraceServiceRequest>(&body) {
+                Ok(request) => request,
+                Err(error) => return bad_request_reply(error),
+            };
+            let status_code = queue_docs(spans_to_docs(request), &doc_sender).await;
+            warp::reply::with_status(warp::reply::json(&json!({})), status_code).into_response()
+        }
+        _ => StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response(),
+    }
+}
+
+fn bad_request_reply(error: impl std::fmt::Display) -> Response {
+    warp::reply::with_status(
+        format!("Failed to decode OTLP export request: {}", error),
+        StatusCode::BAD_REQUEST,
+    )
+    .into_response()
+}
+
+/// Queues the documents of an export request, returning the status code of the response.
+async fn queue_docs(docs: Vec<String>, doc_sender: &mpsc::Sender<Vec<String>>) -> StatusCode {
+    if !docs.is_empty() && doc_sender.send(docs).await.is_err() {
+        warn!("Failed to queue OTLP spans: the source is stopped.");
+        return StatusCode::SERVICE_UNAVAILABLE;
+    }
+    StatusCode::OK
+}
+
+/// Handles the OTLP/gRPC export requests.
+struct OtlpTraceService {
+    doc_sender: mpsc::Sender<Vec<String>>,
+}
+
+#[async_trait]
+impl TraceService for OtlpTraceService {
+    async fn export(
+        &self,
+        request: tonic::Request<otlp_trace::ExportTraceServiceRequest>,
+    ) -> Result<tonic::Response<otlp_trace::ExportTraceServiceResponse>, tonic::Status> {
+        let docs = spans_to_docs(request.into_inner().into());
+        if queue_docs(docs, &self.doc_sender).await != StatusCode::OK {
+            return Err(tonic::Status::unavailable("The OTLP source is stopped."));
+        }
+        Ok(tonic::Response::new(
+            otlp_trace::ExportTraceServiceResponse::default(),
+        ))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportTraceServiceRequest {
+    #[serde(default)]
+    resource_spans: Vec<ResourceSpans>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ResourceSpans {
+    #[serde(default)]
+    resource: Resource,
+    /// Named `instrumentationLibrarySpans` before OTLP 0.15.
+    #[serde(default, alias = "instrumentationLibrarySpans")]
+    scope_spans: Vec<ScopeSpans>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Resource {
+    #[serde(default)]
+    attributes: Vec<KeyValue>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScopeSpans {
+    /// Named `instrumentationLibrary` before OTLP 0.15.
+    #[serde(default, alias = "instrumentationLibrary")]
+    scope: InstrumentationScope,
+    #[serde(default)]
+    spans: Vec<Span>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InstrumentationScope {
+    #[serde(default)]
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Span {
+    trace_id: String,
+    span_id: String,
+    #[serde(default)]
+    parent_span_id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    kind: JsonValue,
+    #[serde(default, deserialize_with = "deserialize_i64_or_str")]
+    start_time_unix_nano: i64,
+    #[serde(default, deserialize_with = "deserialize_i64_or_str")]
+    end_time_unix_nano: i64,
+    #[serde(default)]
+    attributes: Vec<KeyValue>,
+    #[serde(default)]
+    events: Vec<Event>,
+    #[serde(default)]
+    status: Status,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Event {
+    #[serde(default, deserialize_with = "deserialize_i64_or_str")]
+    time_unix_nano: i64,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    attributes: Vec<KeyValue>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Status {
+    #[serde(default)]
+    code: JsonValue,
+    #[serde(default)]
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyValue {
+    key: String,
+    #[serde(default)]
+    value: JsonValue,
+}
+
+/// OTLP encodes 64-bit integers as JSON strings, some exporters as JSON numbers.
+fn deserialize_i64_or_str<'de, D>(deserializer: D) -> Result<i64, D::Error>
+where D: Deserializer<'de> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum I64OrStr {
+        I64(i64),
+        Str(String),
+    }
+    match I64OrStr::deserialize(deserializer)? {
+        I64OrStr::I64(value) => Ok(value),
+        I64OrStr::Str(value_str) => value_str.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+/// Converts an OTLP enum value, sent either as its number or as its name, e.g. `2` or
+/// `SPAN_KIND_SERVER`, into its lowercase short name, e.g. `server`.
+fn enum_name(value: &JsonValue, prefix: &str, names: &[&str]) -> String {
+    match value {
+        JsonValue::Number(number) => number
+            .as_u64()
+            .and_then(|index| names.get(index as usize))
+            .unwrap_or(&names[0])
+            .to_string(),
+        JsonValue::String(name) => name.trim_start_matches(prefix).to_lowercase(),
+        _ => names[0].to_string(),
+    }
+}
+
+/// Converts an export request decoded from protobuf into its JSON encoding, so that both
+/// encodings are flattened into the same documents.
+impl From<otlp_trace::ExportTraceServiceRequest> for ExportTraceServiceRequest {
+    fn from(request: otlp_trace::ExportTraceServiceRequest) -> Self {
+        let resource_spans = request
+            .resource_spans
+            .into_iter()
+            .map(|resource_spans| ResourceSpans {
+                resource: Resource {
+                    attributes: resource_spans
+                        .resource
+                        .map(|resource| key_values_from_proto(resource.attributes))
+                        .unwrap_or_default(),
+                },
+                scope_spans: resource_spans
+                    .scope_spans
+                    .into_iter()
+                    .chain(resource_spans.instrumentation_library_spans)
+                    .map(scope_spans_from_proto)
+                    .collect(),
+            })
+            .collect();
+        ExportTraceServiceRequest { resource_spans }
+    }
+}
+
+fn scope_spans_from_proto(scope_spans: otlp_trace::ScopeSpans) -> ScopeSpans {
+    let spans = scope_spans
+        .spans
+        .into_iter()
+        .map(|span| Span {
+            trace_id: hex_string(&span.trace_id),
+            span_id: hex_string(&span.span_id),
+            parent_span_id: hex_string(&span.parent_span_id),
+            name: span.name,
+            kind: JsonValue::from(span.kind),
+            start_time_unix_nano: span.start_time_unix_nano as i64,
+            end_time_unix_nano: span.end_time_unix_nano as i64,
+            attributes: key_values_from_proto(span.attributes),
+            events: span
+                .events
+                .into_iter()
+                .map(|event| Event {
+                    time_unix_nano: event.time_unix_nano as i64,
+                    name: event.name,
+                    attributes: key_values_from_proto(event.attributes),
+                })
+                .collect(),
+            status: span
+                .status
+                .map(|status| Status {
+                    code: JsonValue::from(status.code),
+                    message: status.message,
+                })
+                .unwrap_or_default(),
+        })
+        .collect();
+    ScopeSpans {
+        scope: InstrumentationScope {
+            name: scope_spans
+                .scope
+                .map(|scope| scope.name)
+                .unwrap_or_default(),
+        },
+        spans,
+    }
+}
+
+/// The JSON encoding of OTLP renders the trace and span IDs as hexadecimal strings.
+fn hex_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn key_values_from_proto(key_values: Vec<otlp_trace::KeyValue>) -> Vec<KeyValue> {
+    key_values
+        .into_iter()
+        .map(|key_value| KeyValue {
+            key: key_value.key,
+            value: any_value_from_proto(key_value.value),
+        })
+        .collect()
+}
+
+/// Renders an `AnyValue` decoded from protobuf in the JSON encoding of OTLP, e.g.
+/// `{"stringValue": "GET"}`.
+fn any_value_from_proto(any_value_opt: Option<otlp_trace::AnyValue>) -> JsonValue {
+    let value = match any_value_opt.and_then(|any_value| any_value.value) {
+        Some(value) => value,
+        None => return json!({}),
+    };
+    match value {
+        AnyValueValue::StringValue(value) => json!({ "stringValue": value }),
+        AnyValueValue::BoolValue(value) => json!({ "boolValue": value }),
+        AnyValueValue::IntValue(value) => json!({ "intValue": value }),
+        AnyValueValue::DoubleValue(value) => json!({ "doubleValue": value }),
+        AnyValueValue::ArrayValue(array_value) => {
+            let values: Vec<JsonValue> = array_value
+                .values
+                .into_iter()
+                .map(|value| any_value_from_proto(Some(value)))
+                .collect();
+            json!({ "arrayValue": { "values": values } })
+        }
+        AnyValueValue::KvlistValue(kvlist_value) => {
+            let values: Vec<JsonValue> = kvlist_value
+                .values
+                .into_iter()
+                .map(|key_value| {
+                    json!({
+                        "key": key_value.key,
+                        "value": any_value_from_proto(key_value.value),
+                    })
+                })
+                .collect();
+            json!({ "kvlistValue": { "values": values } })
+        }
+        AnyValueValue::BytesValue(value) => json!({ "bytesValue": base64::encode(value) }),
+    }
+}
+
+/// Renders an OTLP `AnyValue`, e.g. `{"stringValue": "GET"}`, as a JSON value.
+fn any_value_to_json(any_value: &JsonValue) -> JsonValue {
+    let (value_type, value) = match any_value.as_object().and_then(|map| map.iter().next()) {
+        Some(entry) => entry,
+        None => return JsonValue::Null,
+    };
+    match value_type.as_str() {
+        "intValue" => match value {
+            JsonValue::String(value_str) => value_str
+                .parse::<i64>()
+                .map(JsonValue::from)
+                .unwrap_or_else(|_| value.clone()),
+            _ => value.clone(),
+        },
+        "arrayValue" => JsonValue::Array(
+            value["values"]
+                .as_array()
+                .map(|values| values.iter().map(any_value_to_json).collect())
+                .unwrap_or_default(),
+        ),
+        "kvlistValue" => JsonValue::Object(
+            value["values"]
+                .as_array()
+                .map(|key_values| {
+                    key_values
+                        .iter()
+                        .filter_map(|key_value| {
+                            let key = key_value["key"].as_str()?;
+                            Some((key.to_string(), any_value_to_json(&key_value["value"])))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        ),
+        _ => value.clone(),
+    }
+}
+
+/// Renders attributes as an object, keeping them whole in the document source.
+fn attributes_to_json(attributes: &[KeyValue]) -> JsonMap<String, JsonValue> {
+    attributes
+        .iter()
+        .map(|key_value| (key_value.key.clone(), any_value_to_json(&key_value.value)))
+        .collect()
+}
+
+/// Renders attributes as `key=value` terms, the form in which they are indexed.
+fn attributes_to_terms(attributes: &JsonMap<String, JsonValue>) -> Vec<String> {
+    attributes
+        .iter()
+        .map(|(key, value)| match value {
+            JsonValue::String(value_str) => format!("{}={}", key, value_str),
+            _ => format!("{}={}", key, value),
+        })
+        .collect()
+}
+
+/// Flattens an export request into one JSON document per span.
+fn spans_to_docs(request: ExportTraceServiceRequest) -> Vec<String> {
+    let mut docs = Vec::new();
+    for resource_spans in request.resource_spans {
+        let resource_attributes = attributes_to_json(&resource_spans.resource.attributes);
+        let service_name = resource_attributes
+            .get("service.name")
+            .and_then(JsonValue::as_str)
+            .unwrap_or("unknown_service")
+            .to_string();
+        let resource_attribute_terms = attributes_to_terms(&resource_attributes);
+        for scope_spans in resource_spans.scope_spans {
+            for span in scope_spans.spans {
+                let span_attributes = attributes_to_json(&span.attributes);
+                let events: Vec<JsonValue> = span
+                    .events
+                    .iter()
+                    .map(|event| {
+                        json!({
+                            "event_timestamp_nanos": event.time_unix_nano,
+                            "event_name": event.name,
+                            "event_attributes": attributes_to_json(&event.attributes),
+                        })
+                    })
+                    .collect();
+                let event_names: Vec<&str> = span
+                    .events
+                    .iter()
+                    .map(|event| event.name.as_str())
+                    .collect();
+                let mut doc = json!({
+                    "trace_id": span.trace_id.to_lowercase(),
+                    "span_id": span.span_id.to_lowercase(),
+                    "service_name": service_name,
+                    "scope_name": scope_spans.scope.name,
+                    "span_name": span.name,
+                    "span_kind": enum_name(
+                        &span.kind,
+                        "SPAN_KIND_",
+                        &["unspecified", "internal", "server", "client", "producer", "consumer"],
+                    ),
+                    "span_start_timestamp_nanos": span.start_time_unix_nano,
+                    "span_end_timestamp_nanos": span.end_time_unix_nano,
+                    "span_duration_nanos": span.end_time_unix_nano - span.start_time_unix_nano,
+                    "span_status_code": enum_name(
+                        &span.status.code,
+                        "STATUS_CODE_",
+                        &["unset", "ok", "error"],
+                    ),
+                    "span_status_message": span.status.message,
+                    "span_attributes": attributes_to_terms(&span_attributes),
+                    "resource_attributes": resource_attribute_terms,
+                    "event_names": event_names,
+                    "attributes": span_attributes,
+                    "resource": resource_attributes,
+                    "events": events,
+                });
+                if !span.parent_span_id.is_empty() {
+                    doc["parent_span_id"] = JsonValue::from(span.parent_span_id.to_lowercase());
+                }
+                docs.push(doc.to_string());
+            }
+        }
+    }
+    docs
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_actors::{create_test_mailbox, Universe};
+
+    use super::*;
+    use crate::source::SourceActor;
+
+    fn otlp_export_request() -> JsonValue {
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [
+                        {"key": "service.name", "value": {"stringValue": "checkout"}},
+                        {"key": "host.cpus", "value": {"intValue": "8"}}
+                    ]
+                },
+                "scopeSpans": [{
+                    "scope": {"name": "opentelemetry.instrumentation.http"},
+                    "spans": [{
+                        "traceId": "5B8EFFF798038103D269B633813FC60C",
+                        "spanId": "EEE19B7EC3C1B174",
+                        "parentSpanId": "EEE19B7EC3C1B173",
+                        "name": "GET /cart",
+                        "kind": 2,
+                        "startTimeUnixNano": "1544712660000000000",
+                        "endTimeUnixNano": 1544712661000000000i64,
+                        "attributes": [
+                            {"key": "http.method", "value": {"stringValue": "GET"}},
+                            {"key": "http.status_code", "value": {"intValue": 500}},
+                            {"key": "retry", "value": {"boolValue": true}}
+                        ],
+                        "events": [{
+                            "timeUnixNano": "1544712660500000000",
+                            "name": "exception",
+                            "attributes": [
+                                {"key": "exception.type", "value": {"stringValue": "Timeout"}}
+                            ]
+                        }],
+                        "status": {"code": "STATUS_CODE_ERROR", "message": "Upstream timed out"}
+                    }]
+                }]
+            }, {
+                "instrumentationLibrarySpans": [{
+                    "instrumentationLibrary": {"name": "legacy"},
+                    "spans": [{
+                        "traceId": "5b8efff798038103d269b633813fc60c",
+                        "spanId": "eee19b7ec3c1b175",
+                        "name": "SELECT",
+                        "kind": "SPAN_KIND_CLIENT",
+                        "startTimeUnixNano": "1544712660100000000",
+                        "endTimeUnixNano": "1544712660200000000"
+                    }]
+                }]
+            }]
+        })
+    }
+
+    fn otlp_proto_export_request() -> otlp_trace::ExportTraceServiceRequest {
+        let string_value = |value: &str| otlp_trace::AnyValue {
+            value: Some(AnyValueValue::StringValue(value.to_string())),
+        };
+        otlp_trace::ExportTraceServiceRequest {
+            resource_spans: vec![otlp_trace::ResourceSpans {
+                resource: Some(otlp_trace::Resource {
+                    attributes: vec![otlp_trace::KeyValue {
+                        key: "service.name".to_string(),
+                        value: Some(string_value("checkout")),
+                    }],
+                }),
+                scope_spans: vec![otlp_trace::ScopeSpans {
+                    scope: Some(otlp_trace::InstrumentationScope {
+                        name: "opentelemetry.instrumentation.http".to_string(),
+                        version: "1.0".to_string(),
+                    }),
+                    spans: vec![otlp_trace::Span {
+                        trace_id: vec![0x5b, 0x8e, 0xff, 0xf7],
+                        span_id: vec![0xee, 0xe1, 0x9b, 0x7e],
+                        name: "GET /cart".to_string(),
+                        kind: otlp_trace::SpanKind::Server as i32,
+                        start_time_unix_nano: 1544712660000000000,
+                        end_time_unix_nano: 1544712661000000000,
+                        attributes: vec![
+                            otlp_trace::KeyValue {
+                                key: "http.method".to_string(),
+                                value: Some(string_value("GET")),
+                            },
+                            otlp_trace::KeyValue {
+                                key: "http.request_body".to_string(),
+                                value: Some(otlp_trace::AnyValue {
+                                    value: Some(AnyValueValue::BytesValue(b"cart".to_vec())),
+                                }),
+                            },
+                        ],
+                        status: Some(otlp_trace::Status {
+                            message: String::new(),
+                            code: otlp_trace::StatusCode::Ok as i32,
+                        }),
+                        ..Default::default()
+                    }],
+                }],
+                instrumentation_library_spans: Vec::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_spans_to_docs() {
+        let request: ExportTraceServiceRequest =
+            serde_json::from_value(otlp_export_request()).unwrap();
+        let docs: Vec<JsonValue> = spans_to_docs(request)
+            .iter()
+            .map(|doc| serde_json::from_str(doc).unwrap())
+            .collect();
+        assert_eq!(docs.len(), 2);
+        assert_eq!(
+            docs[0],
+            json!({
+                "trace_id": "5b8efff798038103d269b633813fc60c",
+                "span_id": "eee19b7ec3c1b174",
+                "parent_span_id": "eee19b7ec3c1b173",
+                "service_name": "checkout",
+                "scope_name": "opentelemetry.instrumentation.http",
+                "span_name": "GET /cart",
+                "span_kind": "server",
+                "span_start_timestamp_nanos": 1544712660000000000i64,
+                "span_end_timestamp_nanos": 1544712661000000000i64,
+                "span_duration_nanos": 1000000000,
+                "span_status_code": "error",
+                "span_status_message": "Upstream timed out",
+                "span_attributes": ["http.method=GET", "http.status_code=500", "retry=true"],
+                "resource_attributes": ["host.cpus=8", "service.name=checkout"],
+                "event_names": ["exception"],
+                "attributes": {"http.method": "GET", "http.status_code": 500, "retry": true},
+                "resource": {"service.name": "checkout", "host.cpus": 8},
+                "events": [{
+                    "event_timestamp_nanos": 1544712660500000000i64,
+                    "event_name": "exception",
+                    "event_attributes": {"exception.type": "Timeout"}
+                }]
+            })
+        );
+        assert_eq!(docs[1]["service_name"], "unknown_service");
+        assert_eq!(docs[1]["scope_name"], "legacy");
+        assert_eq!(docs[1]["span_kind"], "client");
+        assert_eq!(docs[1]["span_status_code"], "unset");
+        assert_eq!(docs[1]["span_duration_nanos"], 100000000);
+        assert!(docs[1].get("parent_span_id").is_none());
+    }
+
+    #[test]
+    fn test_any_value_to_json() {
+        assert_eq!(any_value_to_json(&json!({"doubleValue": 0.5})), json!(0.5));
+        assert_eq!(
+            any_value_to_json(&json!({"arrayValue": {"values": [
+                {"stringValue": "a"},
+                {"intValue": "1"}
+            ]}})),
+            json!(["a", 1])
+        );
+        assert_eq!(
+            any_value_to_json(&json!({"kvlistValue": {"values": [
+                {"key": "k", "value": {"boolValue": false}}
+            ]}})),
+            json!({"k": false})
+        );
+        assert_eq!(any_value_to_json(&json!({})), JsonValue::Null);
+    }
+
+    #[test]
+    fn test_proto_spans_to_docs() {
+        let docs: Vec<JsonValue> = spans_to_docs(otlp_proto_export_request().into())
+            .iter()
+            .map(|doc| serde_json::from_str(doc).unwrap())
+            .collect();
+        assert_eq!(docs.len(), 1);
+        assert_eq!(docs[0]["trace_id"], "5b8efff7");
+        assert_eq!(docs[0]["span_id"], "eee19b7e");
+        assert!(docs[0].get("parent_span_id").is_none());
+        assert_eq!(docs[0]["service_name"], "checkout");
+        assert_eq!(docs[0]["scope_name"], "opentelemetry.instrumentation.http");
+        assert_eq!(docs[0]["span_kind"], "server");
+        assert_eq!(docs[0]["span_status_code"], "ok");
+        assert_eq!(docs[0]["span_duration_nanos"], 1000000000);
+        assert_eq!(
+            docs[0]["attributes"],
+            json!({"http.method": "GET", "http.request_body": "Y2FydA=="})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_otlp_traces_filter() {
+        let (doc_sender, mut doc_receiver) = mpsc::channel(1);
+        let filter = otlp_traces_filter(doc_sender);
+        let response = warp::test::request()
+            .method("POST")
+            .path("/v1/traces")
+            .json(&otlp_export_request())
+            .reply(&filter)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(doc_receiver.recv().await.unwrap().len(), 2);
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/v1/traces")
+            .header("content-type", "application/x-protobuf")
+            .body(otlp_proto_export_request().encode_to_vec())
+            .reply(&filter)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/x-protobuf");
+        assert!(otlp_trace::ExportTraceServiceResponse::decode(response.body().clone()).is_ok());
+        assert_eq!(doc_receiver.recv().await.unwrap().len(), 1);
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/v1/traces")
+            .header("content-type", "application/x-protobuf")
+            .body("not protobuf")
+            .reply(&filter)
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/v1/traces")
+            .header("content-type", "text/plain")
+            .body("")
+            .reply(&filter)
+            .await;
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test]
+    async fn test_otlp_trace_service() {
+        let (doc_sender, mut doc_receiver) = mpsc::channel(1);
+        let trace_service = OtlpTraceService { doc_sender };
+        trace_service
+            .export(tonic::Request::new(otlp_proto_export_request()))
+            .await
+            .unwrap();
+        assert_eq!(doc_receiver.recv().await.unwrap().len(), 1);
+
+        drop(doc_receiver);
+        let status = trace_service
+            .export(tonic::Request::new(otlp_proto_export_request()))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+    }
+
+    #[tokio::test]
+    async fn test_otlp_source() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let universe = Universe::new();
+        let (mailbox, inbox) = create_test_mailbox();
+        let params = OtlpSourceParams {
+            listen_address: "127.0.0.1:0".to_string(),
+            grpc_listen_address: "127.0.0.1:0".to_string(),
+        };
+        let mut checkpoint = SourceCheckpoint::default();
+        checkpoint.try_apply_delta(CheckpointDelta::from_partition_delta(
+            PartitionId::from("127.0.0.1:0"),
+            Position::Beginning,
+            Position::from(9u64),
+        ))?;
+        let otlp_source = OtlpSourceFactory::typed_create_source(params, checkpoint).await?;
+        assert_eq!(otlp_source.next_offset, 10);
+        let doc_sender = otlp_source.doc_sender_opt.clone().unwrap();
+        let otlp_source_actor = SourceActor::new(Box::new(otlp_source), mailbox);
+        let (_otlp_source_mailbox, otlp_source_handle) =
+            universe.spawn_actor(otlp_source_actor).spawn_async();
+        doc_sender
+            .send(vec!["{}".to_string(), "{}".to_string()])
+            .await?;
+        tokio::time::sleep(quickwit_actors::HEARTBEAT).await;
+        let observation = otlp_source_handle.process_pending_and_observe().await.state;
+        assert_eq!(observation["next_offset"], 12);
+        assert_eq!(observation["num_spans_received"], 2);
+        let messages = inbox.drain_available_message_for_test();
+        assert!(matches!(
+            &messages[0],
+            IndexerMessage::Batch(ref raw_batch) if format!("{:?}", raw_batch.checkpoint_delta) == "∆(127.0.0.1:0:(00000000000000000009..00000000000000000011])"
+        ));
+        otlp_source_handle.kill().await;
+        Ok(())
+    }
+}
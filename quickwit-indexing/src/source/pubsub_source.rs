@@ -0,0 +1,450 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use google_cloud_pubsub::client::Client;
+use google_cloud_pubsub::subscriber::ReceivedMessage;
+use google_cloud_pubsub::subscription::Subscription;
+use quickwit_actors::{ActorExitStatus, Mailbox};
+use quickwit_config::PubSubSourceParams;
+use quickwit_metastore::checkpoint::{CheckpointDelta, PartitionId, Position, SourceCheckpoint};
+use serde_json::json;
+use tracing::{debug, info, warn};
+use ulid::Ulid;
+
+use crate::models::RawDocBatch;
+use crate::source::{IndexerMessage, PayloadDecoder, Source, SourceContext, TypedSourceFactory};
+
+/// Maximum number of acknowledgement IDs sent in a single acknowledge request.
+const MAX_ACK_IDS_PER_REQUEST: usize = 1_000;
+
+/// Factory for instantiating a `PubSubSource`.
+pub struct PubSubSourceFactory;
+
+#[async_trait]
+impl TypedSourceFactory for PubSubSourceFactory {
+    type Source = PubSubSource;
+    type Params = PubSubSourceParams;
+
+    async fn typed_create_source(
+        params: PubSubSourceParams,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<Self::Source> {
+        PubSubSource::try_new(params, checkpoint).await
+    }
+}
+
+pub struct PubSubSourceState {
+    /// Position of the last message received.
+    pub current_position: Position,
+    /// Acknowledgement IDs of the messages emitted but not published yet, grouped by batch and
+    /// keyed by the position of the last message of the batch.
+    pub pending_acks: VecDeque<(Position, Vec<String>)>,
+    /// Number of bytes processed by the source.
+    pub num_bytes_processed: u64,
+    /// Number of messages processed by the source (including invalid messages).
+    pub num_messages_processed: u64,
+    // Number of invalid messages, i.e., that were empty or could not be decoded.
+    pub num_invalid_messages: u64,
+    /// Number of messages acknowledged after the publication of their split.
+    pub num_acked_messages: u64,
+}
+
+impl Default for PubSubSourceState {
+    fn default() -> Self {
+        PubSubSourceState {
+            current_position: Position::Beginning,
+            pending_acks: VecDeque::new(),
+            num_bytes_processed: 0,
+            num_messages_processed: 0,
+            num_invalid_messages: 0,
+            num_acked_messages: 0,
+        }
+    }
+}
+
+impl PubSubSourceState {
+    /// Appends the payloads of the messages to `docs`, records their positions in
+    /// `checkpoint_delta`, and holds their acknowledgement IDs until the batch is published.
+    fn record_messages(
+        &mut self,
+        partition_id: &PartitionId,
+        messages: Vec<(String, Vec<u8>)>,
+        payload_decoder: &PayloadDecoder,
+        docs: &mut Vec<String>,
+        checkpoint_delta: &mut CheckpointDelta,
+    ) -> anyhow::Result<()> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+        let mut ack_ids = Vec::with_capacity(messages.len());
+        for (ack_id, payload) in messages {
+            match parse_message_payload(&payload, payload_decoder) {
+                Some(doc) => docs.push(doc),
+                None => self.num_invalid_messages += 1,
+            }
+            self.num_bytes_processed += payload.len() as u64;
+            self.num_messages_processed += 1;
+            ack_ids.push(ack_id);
+        }
+        let position = Position::from(self.num_messages_processed);
+        let previous_position = std::mem::replace(&mut self.current_position, position.clone());
+        checkpoint_delta
+            .record_partition_delta(partition_id.clone(), previous_position, position.clone())
+            .context("Failed to record partition delta.")?;
+        self.pending_acks.push_back((position, ack_ids));
+        Ok(())
+    }
+
+    /// Returns the acknowledgement IDs of the messages up to `published_position`, which no
+    /// longer need to be held.
+    fn take_published_ack_ids(&mut self, published_position: &Position) -> Vec<String> {
+        let mut ack_ids = Vec::new();
+        while let Some((position, _)) = self.pending_acks.front() {
+            if position > published_position {
+                break;
+            }
+            let (_, batch_ack_ids) = self
+                .pending_acks
+                .pop_front()
+                .expect("The pending acks should not be empty.");
+            ack_ids.extend(batch_ack_ids);
+        }
+        ack_ids
+    }
+}
+
+/// A `PubSubSource` pulls messages from a Google Pub/Sub subscription and forwards them to an
+/// `Indexer`.
+///
+/// Contrary to the Kafka source, the source relies on the subscription to track its progress:
+/// messages are only acknowledged once the split containing them is published, so messages that
+/// were pulled but not indexed are redelivered after a failure. Messages are therefore indexed
+/// at least once.
+///
+/// Pub/Sub messages have no offset, so each instance of the source records its progress in a
+/// partition of its own, named after the subscription and a unique ID, with the number of
+/// messages processed as position.
+pub struct PubSubSource {
+    subscription_name: String,
+    subscription: Subscription,
+    max_messages_per_pull: i32,
+    partition_id: PartitionId,
+    payload_decoder: PayloadDecoder,
+    state: PubSubSourceState,
+}
+
+impl fmt::Debug for PubSubSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PubSubSource {{ subscription:{} }}",
+            self.subscription_name
+        )
+    }
+}
+
+impl PubSubSource {
+    /// Instantiates a new `PubSubSource`.
+    pub async fn try_new(
+        params: PubSubSourceParams,
+        _checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<PubSubSource> {
+        let subscription = connect(&params).await?;
+        let payload_decoder = PayloadDecoder::try_new(params.decoding.as_ref()).await?;
+        let partition_id = PartitionId::from(format!("{}:{}", params.subscription, Ulid::new()));
+        info!(
+            subscription = %params.subscription,
+            partition_id = ?partition_id,
+            "Starting Pub/Sub source."
+        );
+        Ok(PubSubSource {
+            subscription_name: params.subscription,
+            subscription,
+            max_messages_per_pull: params.max_messages_per_pull,
+            partition_id,
+            payload_decoder,
+            state: PubSubSourceState::default(),
+        })
+    }
+}
+
+#[async_trait]
+impl Source for PubSubSource {
+    async fn emit_batches(
+        &mut self,
+        batch_sink: &Mailbox<IndexerMessage>,
+        ctx: &SourceContext,
+    ) -> Result<(), ActorExitStatus> {
+        let pull_future = {
+            let _protect_guard = ctx.protect_zone();
+            tokio::time::timeout(
+                quickwit_actors::HEARTBEAT / 2,
+                self.subscription
+                    .pull(self.max_messages_per_pull, None, None),
+            )
+            .await
+        };
+        let received_messages: Vec<ReceivedMessage> = match pull_future {
+            Ok(pull_res) => pull_res.map_err(|status| {
+                ActorExitStatus::from(anyhow::anyhow!(
+                    "Failed to pull messages from subscription `{}`: {}",
+                    self.subscription_name,
+                    status
+                ))
+            })?,
+            // No message was received before the deadline.
+            Err(_) => return Ok(()),
+        };
+        let messages: Vec<(String, Vec<u8>)> = received_messages
+            .into_iter()
+            .map(|received_message| {
+                debug!(
+                    subscription = %self.subscription_name,
+                    message_id = %received_message.message.message_id,
+                    num_bytes = received_message.message.data.len(),
+                    "Message received.",
+                );
+                (
+                    received_message.ack_id().to_string(),
+                    received_message.message.data,
+                )
+            })
+            .collect();
+        let mut docs = Vec::with_capacity(messages.len());
+        let mut checkpoint_delta = CheckpointDelta::default();
+        self.state.record_messages(
+            &self.partition_id,
+            messages,
+            &self.payload_decoder,
+            &mut docs,
+            &mut checkpoint_delta,
+        )?;
+        if !checkpoint_delta.is_empty() {
+            let batch = RawDocBatch {
+                docs,
+                checkpoint_delta,
+            };
+            ctx.send_message(batch_sink, IndexerMessage::from(batch))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn suggest_truncate(
+        &mut self,
+        checkpoint: SourceCheckpoint,
+        ctx: &SourceContext,
+    ) -> anyhow::Result<()> {
+        let published_position =
+            if let Some(position) = checkpoint.position_for_partition(&self.partition_id) {
+                position
+            } else {
+                return Ok(());
+            };
+        let ack_ids = self.state.take_published_ack_ids(published_position);
+        for ack_ids_chunk in ack_ids.chunks(MAX_ACK_IDS_PER_REQUEST) {
+            let _protect_guard = ctx.protect_zone();
+            // A message that fails to be acknowledged is redelivered and indexed again, which
+            // does not justify failing the pipeline.
+            if let Err(status) = self.subscription.ack(ack_ids_chunk.to_vec()).await {
+                warn!(
+                    subscription = %self.subscription_name,
+                    num_messages = ack_ids_chunk.len(),
+                    status = %status,
+                    "Failed to acknowledge messages."
+                );
+                continue;
+            }
+            self.state.num_acked_messages += ack_ids_chunk.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        "PubSubSource".to_string()
+    }
+
+    fn observable_state(&self) -> serde_json::Value {
+        let num_pending_acks: usize = self
+            .state
+            .pending_acks
+            .iter()
+            .map(|(_, ack_ids)| ack_ids.len())
+            .sum();
+        json!({
+            "subscription": self.subscription_name,
+            "partition_id": &*self.partition_id.0,
+            "current_position": self.state.current_position.as_str(),
+            "num_bytes_processed": self.state.num_bytes_processed,
+            "num_messages_processed": self.state.num_messages_processed,
+            "num_invalid_messages": self.state.num_invalid_messages,
+            "num_acked_messages": self.state.num_acked_messages,
+            "num_pending_acks": num_pending_acks,
+        })
+    }
+}
+
+/// Checks if connecting with the given parameters works.
+pub(super) async fn check_connectivity(params: PubSubSourceParams) -> anyhow::Result<()> {
+    connect(&params).await?;
+    Ok(())
+}
+
+/// Creates a Pub/Sub client, authenticated with the application default credentials, and checks
+/// that the subscription exists.
+async fn connect(params: &PubSubSourceParams) -> anyhow::Result<Subscription> {
+    let client = Client::default()
+        .await
+        .context("Failed to create Pub/Sub client.")?;
+    let subscription = client.subscription(&params.subscription);
+    let exists = subscription.exists(None, None).await.with_context(|| {
+        format!(
+            "Failed to look up Pub/Sub subscription `{}`.",
+            params.subscription
+        )
+    })?;
+    if !exists {
+        bail!(
+            "Pub/Sub subscription `{}` does not exist.",
+            params.subscription
+        );
+    }
+    Ok(subscription)
+}
+
+/// Decodes the raw bytes of the message payload into a document, skipping corrupted or empty
+/// messages.
+fn parse_message_payload(payload: &[u8], payload_decoder: &PayloadDecoder) -> Option<String> {
+    if payload.is_empty() {
+        debug!("Message payload is empty.");
+        return None;
+    }
+    match payload_decoder.decode(payload) {
+        Ok(doc) => Some(doc),
+        Err(error) => {
+            warn!(error = ?error, "Failed to decode message payload.");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(payloads: &[&str]) -> Vec<(String, Vec<u8>)> {
+        payloads
+            .iter()
+            .map(|payload| (format!("ack-{}", payload), payload.as_bytes().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_record_messages_and_take_published_ack_ids() -> anyhow::Result<()> {
+        let partition_id = PartitionId::from("projects/my-project/subscriptions/logs:0");
+        let mut state = PubSubSourceState::default();
+        let mut docs = Vec::new();
+
+        let mut checkpoint_delta = CheckpointDelta::default();
+        state.record_messages(
+            &partition_id,
+            messages(&["0", "", "2"]),
+            &PayloadDecoder::Json,
+            &mut docs,
+            &mut checkpoint_delta,
+        )?;
+        assert_eq!(
+            checkpoint_delta,
+            CheckpointDelta::from_partition_delta(
+                partition_id.clone(),
+                Position::Beginning,
+                Position::from(3u64)
+            )
+        );
+        let mut checkpoint_delta = CheckpointDelta::default();
+        state.record_messages(
+            &partition_id,
+            Vec::new(),
+            &PayloadDecoder::Json,
+            &mut docs,
+            &mut checkpoint_delta,
+        )?;
+        assert!(checkpoint_delta.is_empty());
+
+        state.record_messages(
+            &partition_id,
+            messages(&["3"]),
+            &PayloadDecoder::Json,
+            &mut docs,
+            &mut checkpoint_delta,
+        )?;
+        state.record_messages(
+            &partition_id,
+            messages(&["4", "5"]),
+            &PayloadDecoder::Json,
+            &mut docs,
+            &mut checkpoint_delta,
+        )?;
+        assert_eq!(
+            checkpoint_delta,
+            CheckpointDelta::from_partition_delta(
+                partition_id,
+                Position::from(3u64),
+                Position::from(6u64)
+            )
+        );
+        assert_eq!(docs, vec!["0", "2", "3", "4", "5"]);
+        assert_eq!(state.num_messages_processed, 6);
+        assert_eq!(state.num_invalid_messages, 1);
+        assert_eq!(state.num_bytes_processed, 5);
+        assert_eq!(state.pending_acks.len(), 3);
+
+        assert!(state
+            .take_published_ack_ids(&Position::from(2u64))
+            .is_empty());
+        assert_eq!(
+            state.take_published_ack_ids(&Position::from(4u64)),
+            vec!["ack-0", "ack-", "ack-2", "ack-3"]
+        );
+        assert_eq!(
+            state.take_published_ack_ids(&Position::from(6u64)),
+            vec!["ack-4", "ack-5"]
+        );
+        assert!(state.pending_acks.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_message_payload() {
+        assert_eq!(
+            parse_message_payload(b"Message #0", &PayloadDecoder::Json),
+            Some("Message #0".to_string())
+        );
+        assert_eq!(parse_message_payload(b"", &PayloadDecoder::Json), None);
+        assert_eq!(
+            parse_message_payload(&[0xff, 0xfe], &PayloadDecoder::Json),
+            None
+        );
+    }
+}
@@ -0,0 +1,643 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use futures::stream::{select_all, BoxStream};
+use futures::StreamExt;
+use itertools::Itertools;
+use pulsar::consumer::{InitialPosition, Message};
+use pulsar::proto::MessageIdData;
+use pulsar::{Authentication, Consumer, ConsumerOptions, Pulsar, SubType, TokioExecutor};
+use quickwit_actors::{ActorExitStatus, Mailbox};
+use quickwit_config::PulsarSourceParams;
+use quickwit_metastore::checkpoint::{CheckpointDelta, PartitionId, Position, SourceCheckpoint};
+use serde_json::json;
+use tracing::{debug, info, warn};
+use ulid::Ulid;
+
+use crate::models::RawDocBatch;
+use crate::source::{
+    backpressure, IndexerMessage, PayloadDecoder, Source, SourceContext, TypedSourceFactory,
+};
+
+/// Same target as the Kafka source: see `kafka_source::TARGET_BATCH_NUM_BYTES`.
+const TARGET_BATCH_NUM_BYTES: u64 = 5_000_000;
+
+/// Factory for instantiating a `PulsarSource`.
+pub struct PulsarSourceFactory;
+
+#[async_trait]
+impl TypedSourceFactory for PulsarSourceFactory {
+    type Source = PulsarSource;
+    type Params = PulsarSourceParams;
+
+    async fn typed_create_source(
+        params: PulsarSourceParams,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<Self::Source> {
+        PulsarSource::try_new(params, checkpoint).await
+    }
+}
+
+type PulsarMessage = Message<Vec<u8>>;
+
+#[derive(Default)]
+pub struct PulsarSourceState {
+    /// Topics of the partitions assigned to the source. The topic of a non-partitioned topic is
+    /// the topic itself.
+    pub assigned_partitions: Vec<String>,
+    /// Position of the last message received for each partition, or the checkpointed position if
+    /// no message has been received yet.
+    pub current_positions: HashMap<String, Position>,
+    /// Number of bytes processed by the source.
+    pub num_bytes_processed: u64,
+    /// Number of messages processed by the source (including invalid messages).
+    pub num_messages_processed: u64,
+    // Number of invalid messages, i.e., that were empty or could not be decoded.
+    pub num_invalid_messages: u64,
+    /// Number of messages received again after a restart and skipped because the checkpoint
+    /// already covers them.
+    pub num_skipped_messages: u64,
+}
+
+impl PulsarSourceState {
+    /// Appends the message payload to `docs` and records its position in `checkpoint_delta`.
+    /// Returns `false` if the message was skipped because the checkpoint already covers it.
+    fn record_message(
+        &mut self,
+        partition_topic: &str,
+        message_id: &MessageIdData,
+        payload: &[u8],
+        payload_decoder: &PayloadDecoder,
+        docs: &mut Vec<String>,
+        checkpoint_delta: &mut CheckpointDelta,
+    ) -> anyhow::Result<bool> {
+        if !self.current_positions.contains_key(partition_topic) {
+            bail!(
+                "Received unexpected message from partition `{}`. Assigned partitions: `{{{}}}`.",
+                partition_topic,
+                self.assigned_partitions.join(", "),
+            );
+        }
+        let position = position_from_message_id(message_id);
+        let current_position = self
+            .current_positions
+            .get_mut(partition_topic)
+            .expect("The partition should be assigned.");
+        if position <= *current_position {
+            self.num_skipped_messages += 1;
+            return Ok(false);
+        }
+        let previous_position = std::mem::replace(current_position, position.clone());
+        checkpoint_delta
+            .record_partition_delta(
+                PartitionId::from(partition_topic),
+                previous_position,
+                position,
+            )
+            .context("Failed to record partition delta.")?;
+
+        match parse_message_payload(partition_topic, message_id, payload, payload_decoder) {
+            Some(doc) => docs.push(doc),
+            None => self.num_invalid_messages += 1,
+        }
+        self.num_bytes_processed += payload.len() as u64;
+        self.num_messages_processed += 1;
+        Ok(true)
+    }
+}
+
+/// A `PulsarSource` consumes a topic and forwards its messages to an `Indexer`.
+///
+/// The source does not rely on Pulsar subscriptions to track its progress: it reads each
+/// partition with a non-durable subscription starting from the position recorded in the
+/// checkpoint, so that a message is indexed exactly once.
+pub struct PulsarSource {
+    topic: String,
+    // Keeps the connections of the consumers open.
+    _pulsar: Pulsar<TokioExecutor>,
+    message_stream: BoxStream<'static, Result<PulsarMessage, pulsar::Error>>,
+    payload_decoder: PayloadDecoder,
+    state: PulsarSourceState,
+}
+
+impl fmt::Debug for PulsarSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PulsarSource {{ topic:{} }}", self.topic)
+    }
+}
+
+impl PulsarSource {
+    /// Instantiates a new `PulsarSource`.
+    pub async fn try_new(
+        params: PulsarSourceParams,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<PulsarSource> {
+        let pulsar = connect(&params).await?;
+        let payload_decoder = PayloadDecoder::try_new(params.decoding.as_ref()).await?;
+        let topic = params.topic;
+        let assigned_partitions = fetch_partition_topics(&pulsar, &topic).await?;
+        // Non-durable subscriptions are removed when their consumer disconnects, so the name only
+        // has to be unique among the live subscriptions of the topic.
+        let subscription_name = format!("quickwit-{}", Ulid::new());
+
+        let mut consumers = Vec::with_capacity(assigned_partitions.len());
+        let mut current_positions = HashMap::with_capacity(assigned_partitions.len());
+        for partition_topic in &assigned_partitions {
+            let position = checkpoint
+                .position_for_partition(&PartitionId::from(partition_topic.as_str()))
+                .cloned()
+                .unwrap_or(Position::Beginning);
+            debug!(
+                topic = %topic,
+                partition_topic = %partition_topic,
+                position = ?position,
+                "Starting Pulsar consumer."
+            );
+            let consumer =
+                create_consumer(&pulsar, partition_topic, &subscription_name, &position).await?;
+            consumers.push(consumer.boxed());
+            current_positions.insert(partition_topic.clone(), position);
+        }
+        let state = PulsarSourceState {
+            assigned_partitions,
+            current_positions,
+            ..Default::default()
+        };
+        Ok(PulsarSource {
+            topic,
+            _pulsar: pulsar,
+            message_stream: select_all(consumers).boxed(),
+            payload_decoder,
+            state,
+        })
+    }
+}
+
+#[async_trait]
+impl Source for PulsarSource {
+    async fn emit_batches(
+        &mut self,
+        batch_sink: &Mailbox<IndexerMessage>,
+        ctx: &SourceContext,
+    ) -> Result<(), ActorExitStatus> {
+        let mut docs = Vec::new();
+        let mut checkpoint_delta = CheckpointDelta::default();
+
+        let deadline = ctx.sleep(quickwit_actors::HEARTBEAT / 2);
+        let mut message_stream = Box::pin((&mut self.message_stream).take_until(deadline));
+
+        let mut batch_num_bytes = 0;
+        let target_batch_num_bytes =
+            backpressure::target_batch_num_bytes(batch_sink, TARGET_BATCH_NUM_BYTES);
+
+        while let Some(message_res) = message_stream.next().await {
+            // FIXME: This is assuming that Pulsar errors are not recoverable, it may not be the
+            // case.
+            let message = message_res.map_err(|err| ActorExitStatus::from(anyhow::anyhow!(err)))?;
+            let payload = &message.payload.data;
+            if self.state.record_message(
+                &message.topic,
+                message.message_id(),
+                payload,
+                &self.payload_decoder,
+                &mut docs,
+                &mut checkpoint_delta,
+            )? {
+                batch_num_bytes += payload.len() as u64;
+            }
+            if batch_num_bytes >= target_batch_num_bytes {
+                break;
+            }
+        }
+        if !checkpoint_delta.is_empty() {
+            let batch = RawDocBatch {
+                docs,
+                checkpoint_delta,
+            };
+            ctx.send_message(batch_sink, IndexerMessage::from(batch))
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        "PulsarSource".to_string()
+    }
+
+    fn observable_state(&self) -> serde_json::Value {
+        let current_positions: Vec<(&String, &str)> = self
+            .state
+            .current_positions
+            .iter()
+            .map(|(partition_topic, position)| (partition_topic, position.as_str()))
+            .sorted()
+            .collect();
+        json!({
+            "topic": self.topic,
+            "assigned_partitions": self.state.assigned_partitions,
+            "current_positions": current_positions,
+            "num_bytes_processed": self.state.num_bytes_processed,
+            "num_messages_processed": self.state.num_messages_processed,
+            "num_invalid_messages": self.state.num_invalid_messages,
+            "num_skipped_messages": self.state.num_skipped_messages,
+        })
+    }
+}
+
+/// Checks if connecting with the given parameters works.
+pub(super) async fn check_connectivity(params: PulsarSourceParams) -> anyhow::Result<()> {
+    let pulsar = connect(&params).await?;
+    fetch_partition_topics(&pulsar, &params.topic).await?;
+    Ok(())
+}
+
+/// Connects to the Pulsar cluster, authenticating with the token of the parameters if any.
+async fn connect(params: &PulsarSourceParams) -> anyhow::Result<Pulsar<TokioExecutor>> {
+    let mut builder = Pulsar::builder(params.address.clone(), TokioExecutor);
+    if let Some(auth_token) = &params.auth_token {
+        builder = builder.with_auth(Authentication {
+            name: "token".to_string(),
+            data: auth_token.clone().into_bytes(),
+        });
+    }
+    builder.build().await.with_context(|| {
+        format!(
+            "Failed to connect to Pulsar cluster at `{}`.",
+            params.address
+        )
+    })
+}
+
+/// Retrieves the topics of the partitions of a given topic, sorted by name. A non-partitioned
+/// topic has a single partition, the topic itself.
+async fn fetch_partition_topics(
+    pulsar: &Pulsar<TokioExecutor>,
+    topic: &str,
+) -> anyhow::Result<Vec<String>> {
+    let partition_topics = pulsar
+        .lookup_partitioned_topic(topic)
+        .await
+        .with_context(|| format!("Failed to look up partitions of topic `{}`.", topic))?
+        .into_iter()
+        .map(|(partition_topic, _broker_address)| partition_topic)
+        .sorted()
+        .collect();
+    Ok(partition_topics)
+}
+
+/// Creates a consumer reading the partition from the message following `position`.
+async fn create_consumer(
+    pulsar: &Pulsar<TokioExecutor>,
+    partition_topic: &str,
+    subscription_name: &str,
+    position: &Position,
+) -> anyhow::Result<Consumer<Vec<u8>, TokioExecutor>> {
+    let mut options = ConsumerOptions::default()
+        .durable(false)
+        .with_initial_position(InitialPosition::Earliest);
+    if let Some(start_message_id) = message_id_from_position(position)? {
+        options = options.starting_on_message(start_message_id);
+    }
+    pulsar
+        .consumer()
+        .with_topic(partition_topic)
+        .with_subscription(subscription_name)
+        .with_subscription_type(SubType::Exclusive)
+        .with_options(options)
+        .build()
+        .await
+        .with_context(|| format!("Failed to create consumer for topic `{}`.", partition_topic))
+}
+
+/// Converts a message ID into a position. Positions are formatted as
+/// `<ledger ID>:<entry ID>:<batch index>`, zero-padded so that they sort like message IDs.
+fn position_from_message_id(message_id: &MessageIdData) -> Position {
+    let batch_index = message_id.batch_index.unwrap_or(0).max(0);
+    let position_str = format!(
+        "{:0>20}:{:0>20}:{:0>10}",
+        message_id.ledger_id, message_id.entry_id, batch_index
+    );
+    Position::from(position_str)
+}
+
+/// Converts a position back into the message ID to start consuming from, `None` standing for the
+/// beginning of the partition.
+///
+/// The batch index is always set: the broker then starts reading from the entry preceding the
+/// message, in case its batch was not consumed entirely, and the source skips the messages
+/// already covered by the checkpoint.
+fn message_id_from_position(position: &Position) -> anyhow::Result<Option<MessageIdData>> {
+    let position_str = match position {
+        Position::Beginning => return Ok(None),
+        Position::Offset(position_str) => position_str,
+    };
+    let parse_error = || format!("Failed to parse Pulsar message ID `{}`.", position_str);
+    let (ledger_id_str, entry_id_str, batch_index_str) = position_str
+        .split(':')
+        .collect_tuple()
+        .with_context(parse_error)?;
+    let message_id = MessageIdData {
+        ledger_id: ledger_id_str.parse().with_context(parse_error)?,
+        entry_id: entry_id_str.parse().with_context(parse_error)?,
+        batch_index: Some(batch_index_str.parse().with_context(parse_error)?),
+        ..Default::default()
+    };
+    Ok(Some(message_id))
+}
+
+/// Decodes the raw bytes of the message payload into a document, skipping corrupted or empty
+/// messages.
+fn parse_message_payload(
+    partition_topic: &str,
+    message_id: &MessageIdData,
+    payload: &[u8],
+    payload_decoder: &PayloadDecoder,
+) -> Option<String> {
+    if payload.is_empty() {
+        debug!(
+            partition_topic = %partition_topic,
+            ledger_id = message_id.ledger_id,
+            entry_id = message_id.entry_id,
+            "Message payload is empty."
+        );
+        return None;
+    }
+    match payload_decoder.decode(payload) {
+        Ok(doc) => {
+            debug!(
+                partition_topic = %partition_topic,
+                ledger_id = message_id.ledger_id,
+                entry_id = message_id.entry_id,
+                num_bytes = payload.len(),
+                "Message received.",
+            );
+            Some(doc)
+        }
+        Err(error) => {
+            warn!(
+                partition_topic = %partition_topic,
+                ledger_id = message_id.ledger_id,
+                entry_id = message_id.entry_id,
+                error = ?error,
+                "Failed to decode message payload."
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message_id(ledger_id: u64, entry_id: u64, batch_index: Option<i32>) -> MessageIdData {
+        MessageIdData {
+            ledger_id,
+            entry_id,
+            batch_index,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_position_from_message_id() -> anyhow::Result<()> {
+        let position = position_from_message_id(&message_id(12, 345, Some(6)));
+        assert_eq!(
+            position.as_str(),
+            "00000000000000000012:00000000000000000345:0000000006"
+        );
+        let start_message_id = message_id_from_position(&position)?.unwrap();
+        assert_eq!(start_message_id, message_id(12, 345, Some(6)));
+
+        assert!(
+            position_from_message_id(&message_id(12, 345, None))
+                < position_from_message_id(&message_id(12, 345, Some(1)))
+        );
+        assert!(
+            position_from_message_id(&message_id(12, 345, Some(9)))
+                < position_from_message_id(&message_id(12, 1000, None))
+        );
+        assert!(
+            position_from_message_id(&message_id(12, 1000, None))
+                < position_from_message_id(&message_id(13, 0, None))
+        );
+        assert!(Position::Beginning < position_from_message_id(&message_id(0, 0, None)));
+
+        assert!(message_id_from_position(&Position::Beginning)?.is_none());
+        assert!(message_id_from_position(&Position::from("12:345")).is_err());
+        assert!(message_id_from_position(&Position::from("12:foo:0")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_message() -> anyhow::Result<()> {
+        let checkpointed_position = position_from_message_id(&message_id(7, 2, Some(1)));
+        let mut state = PulsarSourceState {
+            assigned_partitions: vec!["logs-partition-0".to_string()],
+            current_positions: vec![(
+                "logs-partition-0".to_string(),
+                checkpointed_position.clone(),
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let mut docs = Vec::new();
+        let mut checkpoint_delta = CheckpointDelta::default();
+
+        // The broker rewinds to the previous entry: the messages up to the checkpointed one are
+        // skipped.
+        for (entry_id, batch_index) in [(1, 0), (2, 0), (2, 1)] {
+            assert!(!state.record_message(
+                "logs-partition-0",
+                &message_id(7, entry_id, Some(batch_index)),
+                b"Message",
+                &PayloadDecoder::Json,
+                &mut docs,
+                &mut checkpoint_delta,
+            )?);
+        }
+        assert!(state.record_message(
+            "logs-partition-0",
+            &message_id(7, 2, Some(2)),
+            b"Message #0",
+            &PayloadDecoder::Json,
+            &mut docs,
+            &mut checkpoint_delta,
+        )?);
+        assert!(state.record_message(
+            "logs-partition-0",
+            &message_id(7, 3, None),
+            b"",
+            &PayloadDecoder::Json,
+            &mut docs,
+            &mut checkpoint_delta,
+        )?);
+        assert!(state
+            .record_message(
+                "logs-partition-1",
+                &message_id(8, 0, None),
+                b"Message #1",
+                &PayloadDecoder::Json,
+                &mut docs,
+                &mut checkpoint_delta,
+            )
+            .is_err());
+
+        assert_eq!(docs, vec!["Message #0".to_string()]);
+        assert_eq!(state.num_skipped_messages, 3);
+        assert_eq!(state.num_messages_processed, 2);
+        assert_eq!(state.num_invalid_messages, 1);
+        assert_eq!(state.num_bytes_processed, 10);
+
+        let expected_checkpoint_delta = CheckpointDelta::from_partition_delta(
+            PartitionId::from("logs-partition-0"),
+            checkpointed_position,
+            position_from_message_id(&message_id(7, 3, None)),
+        );
+        assert_eq!(checkpoint_delta, expected_checkpoint_delta);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_message_payload() {
+        let message_id = message_id(0, 0, None);
+        assert_eq!(
+            parse_message_payload("logs", &message_id, b"Message #0", &PayloadDecoder::Json),
+            Some("Message #0".to_string())
+        );
+        assert_eq!(
+            parse_message_payload("logs", &message_id, b"", &PayloadDecoder::Json),
+            None
+        );
+        assert_eq!(
+            parse_message_payload("logs", &message_id, &[0xff, 0xfe], &PayloadDecoder::Json),
+            None
+        );
+    }
+}
+
+#[cfg(all(test, feature = "pulsar-broker-tests"))]
+mod pulsar_broker_tests {
+    use std::time::Duration;
+
+    use quickwit_actors::{create_test_mailbox, Universe};
+    use quickwit_common::rand::append_random_suffix;
+    use quickwit_config::SourceParams;
+
+    use super::*;
+    use crate::source::{quickwit_supported_sources, SourceActor};
+    use crate::SourceConfig;
+
+    const PULSAR_ADDRESS: &str = "pulsar://localhost:6650";
+
+    async fn populate_topic(topic: &str, messages: &[&str]) -> anyhow::Result<()> {
+        let pulsar: Pulsar<TokioExecutor> = Pulsar::builder(PULSAR_ADDRESS, TokioExecutor)
+            .build()
+            .await?;
+        let mut producer = pulsar.producer().with_topic(topic).build().await?;
+        for message in messages {
+            producer.send(message.as_bytes().to_vec()).await?.await?;
+        }
+        Ok(())
+    }
+
+    fn merge_messages(messages: Vec<IndexerMessage>) -> anyhow::Result<RawDocBatch> {
+        let mut merged_batch = RawDocBatch::default();
+        for message in messages {
+            if let IndexerMessage::Batch(batch) = message {
+                merged_batch.docs.extend(batch.docs);
+                merged_batch
+                    .checkpoint_delta
+                    .extend(batch.checkpoint_delta)?;
+            }
+        }
+        merged_batch.docs.sort();
+        Ok(merged_batch)
+    }
+
+    /// Runs the source until it has processed `num_messages` messages.
+    async fn run_source(
+        universe: &Universe,
+        source_config: &SourceConfig,
+        checkpoint: SourceCheckpoint,
+        num_messages: u64,
+    ) -> anyhow::Result<(RawDocBatch, serde_json::Value)> {
+        let (sink, inbox) = create_test_mailbox();
+        let source = quickwit_supported_sources()
+            .load_source(source_config.clone(), checkpoint)
+            .await?;
+        let actor = SourceActor::new(source, sink.clone());
+        let (_mailbox, handle) = universe.spawn_actor(actor).spawn_async();
+        for _ in 0..100 {
+            let observation = handle.observe().await;
+            if observation.state["num_messages_processed"] == json!(num_messages) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let (_exit_status, exit_state) = handle.quit().await;
+        let batch = merge_messages(inbox.drain_available_message_for_test())?;
+        Ok((batch, exit_state))
+    }
+
+    #[tokio::test]
+    async fn test_pulsar_source() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+
+        let universe = Universe::new();
+        let topic = format!(
+            "persistent://public/default/{}",
+            append_random_suffix("test-pulsar-source-topic")
+        );
+        populate_topic(&topic, &["Message #0", "", "Message #2"]).await?;
+
+        let source_config = SourceConfig {
+            source_id: "test-pulsar-source".to_string(),
+            source_params: SourceParams::Pulsar(PulsarSourceParams {
+                topic: topic.clone(),
+                address: PULSAR_ADDRESS.to_string(),
+                auth_token: None,
+                decoding: None,
+            }),
+            filter: None,
+            routing: None,
+            transform: None,
+        };
+        let (batch, exit_state) =
+            run_source(&universe, &source_config, SourceCheckpoint::default(), 3).await?;
+        assert_eq!(batch.docs, vec!["Message #0", "Message #2"]);
+        assert_eq!(exit_state["assigned_partitions"], json!([topic]));
+        assert_eq!(exit_state["num_invalid_messages"], json!(1));
+
+        let mut checkpoint = SourceCheckpoint::default();
+        checkpoint.try_apply_delta(batch.checkpoint_delta)?;
+
+        populate_topic(&topic, &["Message #3"]).await?;
+        let (batch, exit_state) = run_source(&universe, &source_config, checkpoint, 1).await?;
+        assert_eq!(batch.docs, vec!["Message #3"]);
+        assert_eq!(exit_state["num_messages_processed"], json!(1));
+        Ok(())
+    }
+}
@@ -0,0 +1,384 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+use anyhow::{bail, Context};
+use quickwit_config::DocFraming;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+/// Position of the scanner of a JSON array.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ArrayPosition {
+    BeforeArray,
+    BeforeElement,
+    InElement,
+    AfterArray,
+}
+
+/// Pull parser splitting a JSON array into its elements without parsing them, so that arrays
+/// larger than the memory can be read element by element.
+struct JsonArrayScanner {
+    position: ArrayPosition,
+    /// Offset in the buffer of the element being scanned.
+    element_start: usize,
+    /// Number of objects and arrays of the element being scanned that are not closed yet.
+    depth: usize,
+    in_string: bool,
+    is_escaped: bool,
+}
+
+impl JsonArrayScanner {
+    fn new(position: ArrayPosition) -> Self {
+        JsonArrayScanner {
+            position,
+            element_start: 0,
+            depth: 0,
+            in_string: false,
+            is_escaped: false,
+        }
+    }
+
+    /// Scans the bytes of `buffer` from `scan_offset`, and returns the range of the next
+    /// element if it ends within the buffer. `scan_offset` is left where scanning stopped.
+    fn scan(
+        &mut self,
+        buffer: &[u8],
+        scan_offset: &mut usize,
+    ) -> anyhow::Result<Option<(usize, usize)>> {
+        while *scan_offset < buffer.len() {
+            let byte = buffer[*scan_offset];
+            match self.position {
+                ArrayPosition::BeforeArray => {
+                    if byte == b'[' {
+                        self.position = ArrayPosition::BeforeElement;
+                    } else if !byte.is_ascii_whitespace() {
+                        bail!("Expected a JSON array.");
+                    }
+                }
+                ArrayPosition::BeforeElement => {
+                    if byte == b']' {
+                        self.position = ArrayPosition::AfterArray;
+                    } else if byte != b',' && !byte.is_ascii_whitespace() {
+                        self.position = ArrayPosition::InElement;
+                        self.element_start = *scan_offset;
+                        self.depth = 0;
+                        self.in_string = false;
+                        self.is_escaped = false;
+                        // The first byte of the element is scanned as part of it.
+                        continue;
+                    }
+                }
+                ArrayPosition::InElement => {
+                    if let Some(element_end) = self.scan_element_byte(byte, *scan_offset) {
+                        self.position = ArrayPosition::BeforeElement;
+                        *scan_offset = element_end;
+                        return Ok(Some((self.element_start, element_end)));
+                    }
+                }
+                ArrayPosition::AfterArray => {
+                    if !byte.is_ascii_whitespace() {
+                        bail!("Unexpected data after the end of the JSON array.");
+                    }
+                }
+            }
+            *scan_offset += 1;
+        }
+        Ok(None)
+    }
+
+    /// Scans the byte at `offset` of the current element, and returns the end of the element
+    /// if the byte completes it.
+    fn scan_element_byte(&mut self, byte: u8, offset: usize) -> Option<usize> {
+        if self.in_string {
+            if self.is_escaped {
+                self.is_escaped = false;
+            } else if byte == b'\\' {
+                self.is_escaped = true;
+            } else if byte == b'"' {
+                self.in_string = false;
+                if self.depth == 0 {
+                    return Some(offset + 1);
+                }
+            }
+            return None;
+        }
+        match byte {
+            b'"' => self.in_string = true,
+            b'{' | b'[' => self.depth += 1,
+            b'}' | b']' if self.depth > 0 => {
+                self.depth -= 1;
+                if self.depth == 0 {
+                    return Some(offset + 1);
+                }
+            }
+            // Numbers, booleans, and null end with the byte following them.
+            b',' | b']' if self.depth == 0 => return Some(offset),
+            _ if self.depth == 0 && byte.is_ascii_whitespace() => return Some(offset),
+            _ => {}
+        }
+        None
+    }
+}
+
+enum Framing {
+    JsonArray(JsonArrayScanner),
+    LengthPrefixed,
+}
+
+/// Number of bytes of the prefix holding the length of a length-prefixed record.
+const LENGTH_PREFIX_NUM_BYTES: usize = 4;
+
+/// Splits the bytes of a file into the documents it holds, following the framing of a source
+/// whose documents are not delimited by line feeds. Bytes are fed chunk by chunk.
+pub(crate) struct RecordSplitter {
+    framing: Framing,
+    /// Bytes fed, starting at `read_offset` for the bytes not read yet.
+    buffer: Vec<u8>,
+    read_offset: usize,
+    /// Offset from which the end of the next record is looked for.
+    scan_offset: usize,
+}
+
+impl RecordSplitter {
+    /// Creates the splitter of a source reading the file from `offset`, which must be the end
+    /// of a record, or returns `None` if the documents are delimited by line feeds.
+    pub fn new(framing: DocFraming, offset: u64) -> Option<Self> {
+        let framing = match framing {
+            DocFraming::Ndjson => return None,
+            DocFraming::JsonArray if offset > 0 => {
+                Framing::JsonArray(JsonArrayScanner::new(ArrayPosition::BeforeElement))
+            }
+            DocFraming::JsonArray => {
+                Framing::JsonArray(JsonArrayScanner::new(ArrayPosition::BeforeArray))
+            }
+            DocFraming::LengthPrefixed => Framing::LengthPrefixed,
+        };
+        Some(RecordSplitter {
+            framing,
+            buffer: Vec::new(),
+            read_offset: 0,
+            scan_offset: 0,
+        })
+    }
+
+    /// Appends the next record to `record` and returns the number of bytes of the file it
+    /// consumed, separators and prefixes included, or `None` if the record is not fed entirely
+    /// yet.
+    pub fn read_record(&mut self, record: &mut String) -> anyhow::Result<Option<usize>> {
+        let record_range_opt = match &mut self.framing {
+            Framing::JsonArray(scanner) => scanner.scan(&self.buffer, &mut self.scan_offset)?,
+            Framing::LengthPrefixed => {
+                let num_unread_bytes = self.buffer.len() - self.read_offset;
+                if num_unread_bytes < LENGTH_PREFIX_NUM_BYTES {
+                    return Ok(None);
+                }
+                let mut length_prefix = [0u8; LENGTH_PREFIX_NUM_BYTES];
+                length_prefix.copy_from_slice(
+                    &self.buffer[self.read_offset..self.read_offset + LENGTH_PREFIX_NUM_BYTES],
+                );
+                let record_num_bytes = u32::from_be_bytes(length_prefix) as usize;
+                if num_unread_bytes < LENGTH_PREFIX_NUM_BYTES + record_num_bytes {
+                    return Ok(None);
+                }
+                let record_start = self.read_offset + LENGTH_PREFIX_NUM_BYTES;
+                Some((record_start, record_start + record_num_bytes))
+            }
+        };
+        let (record_start, record_end) = match record_range_opt {
+            Some(record_range) => record_range,
+            None => return Ok(None),
+        };
+        record.push_str(
+            std::str::from_utf8(&self.buffer[record_start..record_end])
+                .context("Record is invalid utf-8.")?,
+        );
+        let num_bytes = record_end - self.read_offset;
+        self.read_offset = record_end;
+        self.scan_offset = record_end;
+        Ok(Some(num_bytes))
+    }
+
+    /// Consumes the bytes left once the whole file is fed, which may only be separators, and
+    /// returns their number.
+    pub fn read_remaining(&mut self) -> anyhow::Result<usize> {
+        let remaining_bytes = &self.buffer[self.read_offset..];
+        let is_truncated = match &self.framing {
+            Framing::JsonArray(scanner) => match scanner.position {
+                ArrayPosition::BeforeArray => !remaining_bytes.iter().all(u8::is_ascii_whitespace),
+                ArrayPosition::BeforeElement | ArrayPosition::InElement => true,
+                ArrayPosition::AfterArray => false,
+            },
+            Framing::LengthPrefixed => !remaining_bytes.is_empty(),
+        };
+        if is_truncated {
+            bail!("The file ends with a truncated record.");
+        }
+        let num_bytes = remaining_bytes.len();
+        self.read_offset = self.buffer.len();
+        self.scan_offset = self.buffer.len();
+        Ok(num_bytes)
+    }
+
+    /// Feeds a chunk of the file, dropping the bytes already read.
+    pub fn extend(&mut self, chunk: &[u8]) {
+        self.buffer.drain(..self.read_offset);
+        self.scan_offset -= self.read_offset;
+        if let Framing::JsonArray(scanner) = &mut self.framing {
+            if scanner.position == ArrayPosition::InElement {
+                scanner.element_start -= self.read_offset;
+            }
+        }
+        self.read_offset = 0;
+        self.buffer.extend_from_slice(chunk);
+    }
+}
+
+/// Reads the next record of a local file with `record_splitter`, see
+/// [`RecordSplitter::read_record`]. Returns 0 at the end of the file.
+pub(crate) async fn read_record<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    record_splitter: &mut RecordSplitter,
+    record: &mut String,
+) -> anyhow::Result<usize> {
+    loop {
+        if let Some(num_bytes) = record_splitter.read_record(record)? {
+            return Ok(num_bytes);
+        }
+        let chunk = reader.fill_buf().await?;
+        if chunk.is_empty() {
+            return record_splitter.read_remaining();
+        }
+        let chunk_num_bytes = chunk.len();
+        record_splitter.extend(chunk);
+        reader.consume(chunk_num_bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Splits `bytes` fed in chunks of `chunk_num_bytes`, and returns the records along with the
+    /// offset of their end.
+    fn split(
+        record_splitter: &mut RecordSplitter,
+        bytes: &[u8],
+        chunk_num_bytes: usize,
+    ) -> anyhow::Result<Vec<(String, usize)>> {
+        let mut records = Vec::new();
+        let mut offset = 0;
+        for chunk in bytes.chunks(chunk_num_bytes) {
+            record_splitter.extend(chunk);
+            let mut record = String::new();
+            while let Some(num_bytes) = record_splitter.read_record(&mut record)? {
+                offset += num_bytes;
+                records.push((std::mem::take(&mut record), offset));
+            }
+        }
+        offset += record_splitter.read_remaining()?;
+        assert_eq!(offset, bytes.len());
+        Ok(records)
+    }
+
+    #[test]
+    fn test_record_splitter_ndjson() {
+        assert!(RecordSplitter::new(DocFraming::Ndjson, 0).is_none());
+    }
+
+    #[test]
+    fn test_record_splitter_json_array() -> anyhow::Result<()> {
+        let bytes = br#" [{"body": "a, [b]", "tags": ["}"]},
+            "esc\"aped" , 12,true,{"nested": {"x": null}}]
+        "#;
+        for chunk_num_bytes in [1, 7, bytes.len()] {
+            let mut record_splitter = RecordSplitter::new(DocFraming::JsonArray, 0).unwrap();
+            let records = split(&mut record_splitter, bytes, chunk_num_bytes)?;
+            let record_strs: Vec<&str> =
+                records.iter().map(|(record, _)| record.as_str()).collect();
+            assert_eq!(
+                record_strs,
+                [
+                    r#"{"body": "a, [b]", "tags": ["}"]}"#,
+                    r#""esc\"aped""#,
+                    "12",
+                    "true",
+                    r#"{"nested": {"x": null}}"#,
+                ]
+            );
+        }
+        // Resuming after the first element.
+        let mut record_splitter = RecordSplitter::new(DocFraming::JsonArray, 35).unwrap();
+        let records = split(&mut record_splitter, &bytes[35..], 5)?;
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0].0, r#""esc\"aped""#);
+
+        let mut record_splitter = RecordSplitter::new(DocFraming::JsonArray, 0).unwrap();
+        assert!(split(&mut record_splitter, b"{}", 2).is_err());
+        let mut record_splitter = RecordSplitter::new(DocFraming::JsonArray, 0).unwrap();
+        assert!(split(&mut record_splitter, br#"[{"a": 1}"#, 2).is_err());
+        let mut record_splitter = RecordSplitter::new(DocFraming::JsonArray, 0).unwrap();
+        assert!(split(&mut record_splitter, b"\n", 2)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_splitter_length_prefixed() -> anyhow::Result<()> {
+        let mut bytes = Vec::new();
+        for record in [r#"{"body": "a"}"#, "", r#"{"body": "bc"}"#] {
+            bytes.extend_from_slice(&(record.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(record.as_bytes());
+        }
+        let mut record_splitter = RecordSplitter::new(DocFraming::LengthPrefixed, 0).unwrap();
+        let records = split(&mut record_splitter, &bytes, 3)?;
+        assert_eq!(
+            records,
+            [
+                (r#"{"body": "a"}"#.to_string(), 17),
+                (String::new(), 21),
+                (r#"{"body": "bc"}"#.to_string(), 39),
+            ]
+        );
+        let mut record_splitter = RecordSplitter::new(DocFraming::LengthPrefixed, 0).unwrap();
+        assert!(split(&mut record_splitter, &bytes[..30], 3).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_record() -> anyhow::Result<()> {
+        let bytes: &[u8] = br#"[{"a": 1}, {"b": 2}]"#;
+        let mut reader = tokio::io::BufReader::with_capacity(4, bytes);
+        let mut record_splitter = RecordSplitter::new(DocFraming::JsonArray, 0).unwrap();
+        let mut records = Vec::new();
+        loop {
+            let mut record = String::new();
+            let num_bytes = read_record(&mut reader, &mut record_splitter, &mut record).await?;
+            if num_bytes == 0 {
+                break;
+            }
+            records.push((record, num_bytes));
+        }
+        assert_eq!(
+            records,
+            [
+                (r#"{"a": 1}"#.to_string(), 9),
+                (r#"{"b": 2}"#.to_string(), 10),
+                (String::new(), 1),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+use csv::{ReaderBuilder, StringRecord};
+use quickwit_config::DocFormat;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+
+/// Converts the CSV or TSV rows read line by line by a source into JSON documents keyed by
+/// column name. Empty values are left out of the documents.
+///
+/// Since rows are read line by line, quoted values cannot span several lines.
+pub(crate) struct RowDecoder {
+    reader_builder: ReaderBuilder,
+    /// Column names, configured or read from the header line of the current file.
+    columns_opt: Option<Vec<String>>,
+    /// Whether the first line of every file holds the column names.
+    reads_header: bool,
+}
+
+impl RowDecoder {
+    /// Creates the decoder of a source reading lines of the given format, or returns `None` if
+    /// the lines hold JSON documents.
+    pub fn new(format: DocFormat, columns_opt: Option<&[String]>) -> Option<Self> {
+        let mut reader_builder = ReaderBuilder::new();
+        reader_builder.has_headers(false);
+        match format {
+            DocFormat::Json => return None,
+            DocFormat::Csv => {}
+            DocFormat::Tsv => {
+                reader_builder.delimiter(b'\t').quoting(false);
+            }
+        }
+        Some(RowDecoder {
+            reader_builder,
+            columns_opt: columns_opt.map(|columns| columns.to_vec()),
+            reads_header: columns_opt.is_none(),
+        })
+    }
+
+    /// Returns true if the first line of every file holds the column names.
+    pub fn reads_header(&self) -> bool {
+        self.reads_header
+    }
+
+    /// Forgets the column names read from the header of the previous file, before reading a
+    /// new file.
+    pub fn reset_header(&mut self) {
+        if self.reads_header {
+            self.columns_opt = None;
+        }
+    }
+
+    /// Decodes the next line of the file. Returns `None` for the header line and blank lines.
+    ///
+    /// A row that cannot be parsed, or whose number of values differs from the number of
+    /// columns, is returned as is, so that the indexer rejects it as an invalid document.
+    pub fn decode(&mut self, line: &str) -> Option<String> {
+        let row = line.trim_end_matches(|c| c == '\n' || c == '\r');
+        if row.is_empty() {
+            return None;
+        }
+        let columns = match &self.columns_opt {
+            Some(columns) => columns,
+            None => {
+                // Spreadsheet exports often start with a byte order mark.
+                let header = row.trim_start_matches('\u{feff}');
+                let columns = self
+                    .parse_record(header)
+                    .map(|record| record.iter().map(str::to_string).collect())
+                    .unwrap_or_default();
+                self.columns_opt = Some(columns);
+                return None;
+            }
+        };
+        let record = match self.parse_record(row) {
+            Some(record) if record.len() == columns.len() => record,
+            _ => return Some(row.to_string()),
+        };
+        let doc: JsonMap<String, JsonValue> = columns
+            .iter()
+            .zip(record.iter())
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(column, value)| (column.clone(), JsonValue::from(value)))
+            .collect();
+        Some(JsonValue::Object(doc).to_string())
+    }
+
+    fn parse_record(&self, row: &str) -> Option<StringRecord> {
+        let mut record = StringRecord::new();
+        match self
+            .reader_builder
+            .from_reader(row.as_bytes())
+            .read_record(&mut record)
+        {
+            Ok(true) => Some(record),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn decode_lines(row_decoder: &mut RowDecoder, lines: &[&str]) -> Vec<String> {
+        lines
+            .iter()
+            .flat_map(|line| row_decoder.decode(line))
+            .collect()
+    }
+
+    #[test]
+    fn test_row_decoder_json() {
+        assert!(RowDecoder::new(DocFormat::Json, None).is_none());
+    }
+
+    #[test]
+    fn test_row_decoder_csv_header() {
+        let mut row_decoder = RowDecoder::new(DocFormat::Csv, None).unwrap();
+        assert!(row_decoder.reads_header());
+        let docs = decode_lines(
+            &mut row_decoder,
+            &[
+                "\u{feff}vendor,city,distance\r\n",
+                "acme,\"Paris, France\",12.5\r\n",
+                "\n",
+                "foo,,3\n",
+                "bar,berlin\n",
+            ],
+        );
+        assert_eq!(
+            docs,
+            [
+                json!({"vendor": "acme", "city": "Paris, France", "distance": "12.5"}).to_string(),
+                json!({"vendor": "foo", "distance": "3"}).to_string(),
+                "bar,berlin".to_string(),
+            ]
+        );
+        row_decoder.reset_header();
+        let docs = decode_lines(&mut row_decoder, &["name,age\n", "alice,32\n"]);
+        assert_eq!(docs, [json!({"name": "alice", "age": "32"}).to_string()]);
+    }
+
+    #[test]
+    fn test_row_decoder_tsv_columns() {
+        let columns = ["level".to_string(), "message".to_string()];
+        let mut row_decoder = RowDecoder::new(DocFormat::Tsv, Some(&columns)).unwrap();
+        assert!(!row_decoder.reads_header());
+        row_decoder.reset_header();
+        let docs = decode_lines(
+            &mut row_decoder,
+            &["INFO\tstarted \"worker\"\n", "ERROR\tfailed, retrying\n"],
+        );
+        assert_eq!(
+            docs,
+            [
+                json!({"level": "INFO", "message": "started \"worker\""}).to_string(),
+                json!({"level": "ERROR", "message": "failed, retrying"}).to_string(),
+            ]
+        );
+    }
+}
@@ -126,6 +126,9 @@ mod tests {
         let source_config = SourceConfig {
             source_id: "test-source".to_string(),
             source_params: SourceParams::void(),
+            filter: None,
+            routing: None,
+            transform: None,
         };
         source_loader
             .load_source(source_config, SourceCheckpoint::default())
@@ -0,0 +1,642 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use percent_encoding::percent_decode_str;
+use quickwit_actors::{ActorExitStatus, Mailbox};
+use quickwit_config::SqsSourceParams;
+use quickwit_metastore::checkpoint::{CheckpointDelta, PartitionId, Position, SourceCheckpoint};
+use quickwit_storage::{quickwit_storage_uri_resolver, Storage, StorageErrorKind};
+use rusoto_core::Region;
+use rusoto_sqs::{
+    DeleteMessageRequest, GetQueueAttributesRequest, ReceiveMessageRequest, Sqs, SqsClient,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{debug, info, warn};
+use ulid::Ulid;
+
+use crate::models::RawDocBatch;
+use crate::source::{IndexerMessage, Source, SourceContext, TypedSourceFactory};
+
+/// Maximum number of messages returned by a receive request, as allowed by SQS.
+const MAX_NUMBER_OF_MESSAGES: i64 = 10;
+
+/// Duration of the long polling of receive requests. Kept short so that `emit_batches` returns
+/// quickly.
+const WAIT_TIME_SECONDS: i64 = 1;
+
+/// Factory for instantiating a `SqsSource`.
+pub struct SqsSourceFactory;
+
+#[async_trait]
+impl TypedSourceFactory for SqsSourceFactory {
+    type Source = SqsSource;
+    type Params = SqsSourceParams;
+
+    async fn typed_create_source(
+        params: SqsSourceParams,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<Self::Source> {
+        SqsSource::try_new(params, checkpoint).await
+    }
+}
+
+/// S3 object referenced by an event notification.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct S3Object {
+    bucket: String,
+    key: String,
+}
+
+/// SQS message whose objects are indexed but not published yet.
+#[derive(Debug)]
+struct PendingMessage {
+    receipt_handle: String,
+    objects: Vec<S3Object>,
+}
+
+pub struct SqsSourceState {
+    /// Position of the last message processed.
+    current_position: Position,
+    /// Messages processed but not published yet, keyed by position.
+    pending_messages: VecDeque<(Position, PendingMessage)>,
+    /// Number of bytes of the objects processed by the source.
+    pub num_bytes_processed: u64,
+    /// Number of S3 event notifications processed by the source.
+    pub num_messages_processed: u64,
+    /// Number of S3 objects processed by the source.
+    pub num_objects_processed: u64,
+    /// Number of messages that are not S3 event notifications, or referenced objects that could
+    /// not be read.
+    pub num_invalid_messages: u64,
+    /// Number of messages deleted from the queue after the publication of their documents.
+    pub num_deleted_messages: u64,
+}
+
+impl Default for SqsSourceState {
+    fn default() -> Self {
+        SqsSourceState {
+            current_position: Position::Beginning,
+            pending_messages: VecDeque::new(),
+            num_bytes_processed: 0,
+            num_messages_processed: 0,
+            num_objects_processed: 0,
+            num_invalid_messages: 0,
+            num_deleted_messages: 0,
+        }
+    }
+}
+
+impl SqsSourceState {
+    /// Records the position of a processed message in `checkpoint_delta` and holds the message
+    /// until its documents are published.
+    fn record_message(
+        &mut self,
+        partition_id: &PartitionId,
+        message: PendingMessage,
+        checkpoint_delta: &mut CheckpointDelta,
+    ) -> anyhow::Result<()> {
+        self.num_messages_processed += 1;
+        let position = Position::from(self.num_messages_processed);
+        let previous_position = std::mem::replace(&mut self.current_position, position.clone());
+        checkpoint_delta
+            .record_partition_delta(partition_id.clone(), previous_position, position.clone())
+            .context("Failed to record partition delta.")?;
+        self.pending_messages.push_back((position, message));
+        Ok(())
+    }
+
+    /// Returns the messages up to `published_position`, whose documents are published.
+    fn take_published_messages(&mut self, published_position: &Position) -> Vec<PendingMessage> {
+        let mut published_messages = Vec::new();
+        while let Some((position, _)) = self.pending_messages.front() {
+            if position > published_position {
+                break;
+            }
+            let (_, message) = self
+                .pending_messages
+                .pop_front()
+                .expect("The pending messages should not be empty.");
+            published_messages.push(message);
+        }
+        published_messages
+    }
+}
+
+/// A `SqsSource` consumes the S3 event notifications sent to an SQS queue, downloads the objects
+/// they reference, and forwards their documents to an `Indexer`. Objects must hold one JSON
+/// document per line.
+///
+/// A message is deleted from the queue, and optionally its objects from S3, only once their
+/// documents are published. Messages processed but not published when the indexer fails become
+/// visible again after the visibility timeout of the queue, so objects are indexed at least
+/// once.
+///
+/// Like the Pub/Sub source, each instance of the source records its progress in a partition of
+/// its own, with the number of messages processed as position.
+pub struct SqsSource {
+    params: SqsSourceParams,
+    sqs_client: SqsClient,
+    partition_id: PartitionId,
+    storages: HashMap<String, Arc<dyn Storage>>,
+    state: SqsSourceState,
+}
+
+impl fmt::Debug for SqsSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SqsSource {{ queue_url:{} }}", self.params.queue_url)
+    }
+}
+
+impl SqsSource {
+    /// Instantiates a new `SqsSource`.
+    pub async fn try_new(
+        params: SqsSourceParams,
+        _checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<SqsSource> {
+        let sqs_client = connect(&params).await?;
+        let partition_id = PartitionId::from(format!("{}:{}", params.queue_url, Ulid::new()));
+        info!(
+            queue_url = %params.queue_url,
+            partition_id = ?partition_id,
+            "Starting SQS source."
+        );
+        Ok(SqsSource {
+            params,
+            sqs_client,
+            partition_id,
+            storages: HashMap::new(),
+            state: SqsSourceState::default(),
+        })
+    }
+
+    fn storage(&mut self, bucket: &str) -> anyhow::Result<Arc<dyn Storage>> {
+        if let Some(storage) = self.storages.get(bucket) {
+            return Ok(storage.clone());
+        }
+        let storage = quickwit_storage_uri_resolver().resolve(&format!("s3://{}", bucket))?;
+        self.storages.insert(bucket.to_string(), storage.clone());
+        Ok(storage)
+    }
+
+    /// Downloads an object and appends its documents to `docs`. Returns `false` if the object
+    /// does not exist anymore.
+    async fn read_object(
+        &mut self,
+        object: &S3Object,
+        docs: &mut Vec<String>,
+        ctx: &SourceContext,
+    ) -> anyhow::Result<bool> {
+        let storage = self.storage(&object.bucket)?;
+        let payload_res = {
+            let _protect_guard = ctx.protect_zone();
+            storage.get_all(Path::new(&object.key)).await
+        };
+        let payload = match payload_res {
+            Ok(payload) => payload,
+            Err(error) if error.kind() == StorageErrorKind::DoesNotExist => {
+                warn!(bucket = %object.bucket, key = %object.key, "Object does not exist.");
+                return Ok(false);
+            }
+            Err(error) => {
+                return Err(error).with_context(|| {
+                    format!(
+                        "Failed to download object `s3://{}/{}`.",
+                        object.bucket, object.key
+                    )
+                })
+            }
+        };
+        self.state.num_bytes_processed += payload.len() as u64;
+        self.state.num_objects_processed += 1;
+        match std::str::from_utf8(payload.as_slice()) {
+            Ok(content) => docs.extend(parse_docs(content)),
+            Err(error) => {
+                warn!(
+                    bucket = %object.bucket,
+                    key = %object.key,
+                    error = ?error,
+                    "Object is not valid UTF-8."
+                );
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    async fn delete_message(&self, receipt_handle: String) -> anyhow::Result<()> {
+        let delete_message_request = DeleteMessageRequest {
+            queue_url: self.params.queue_url.clone(),
+            receipt_handle,
+        };
+        self.sqs_client
+            .delete_message(delete_message_request)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to delete message from queue `{}`.",
+                    self.params.queue_url
+                )
+            })
+    }
+}
+
+#[async_trait]
+impl Source for SqsSource {
+    async fn emit_batches(
+        &mut self,
+        batch_sink: &Mailbox<IndexerMessage>,
+        ctx: &SourceContext,
+    ) -> Result<(), ActorExitStatus> {
+        let receive_message_request = ReceiveMessageRequest {
+            queue_url: self.params.queue_url.clone(),
+            max_number_of_messages: Some(MAX_NUMBER_OF_MESSAGES),
+            wait_time_seconds: Some(WAIT_TIME_SECONDS),
+            ..Default::default()
+        };
+        let receive_message_result = {
+            let _protect_guard = ctx.protect_zone();
+            self.sqs_client
+                .receive_message(receive_message_request)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to receive messages from queue `{}`.",
+                        self.params.queue_url
+                    )
+                })?
+        };
+        let mut docs = Vec::new();
+        let mut checkpoint_delta = CheckpointDelta::default();
+
+        for message in receive_message_result.messages.unwrap_or_default() {
+            let receipt_handle = if let Some(receipt_handle) = message.receipt_handle {
+                receipt_handle
+            } else {
+                continue;
+            };
+            let body = message.body.unwrap_or_default();
+            let objects = match parse_s3_event_notification(&body) {
+                Ok(objects) if !objects.is_empty() => objects,
+                // Messages referencing no object, such as the test event sent by S3 when the
+                // notifications are configured, are deleted right away.
+                Ok(_) => {
+                    debug!(message_id = ?message.message_id, "Message references no object.");
+                    self.delete_message(receipt_handle).await?;
+                    continue;
+                }
+                Err(error) => {
+                    warn!(message_id = ?message.message_id, error = ?error, "Invalid message.");
+                    self.state.num_invalid_messages += 1;
+                    self.delete_message(receipt_handle).await?;
+                    continue;
+                }
+            };
+            for object in &objects {
+                if !self.read_object(object, &mut docs, ctx).await? {
+                    self.state.num_invalid_messages += 1;
+                }
+            }
+            let pending_message = PendingMessage {
+                receipt_handle,
+                objects,
+            };
+            self.state.record_message(
+                &self.partition_id,
+                pending_message,
+                &mut checkpoint_delta,
+            )?;
+        }
+        if !checkpoint_delta.is_empty() {
+            let batch = RawDocBatch {
+                docs,
+                checkpoint_delta,
+            };
+            ctx.send_message(batch_sink, IndexerMessage::from(batch))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn suggest_truncate(
+        &mut self,
+        checkpoint: SourceCheckpoint,
+        ctx: &SourceContext,
+    ) -> anyhow::Result<()> {
+        let published_position =
+            if let Some(position) = checkpoint.position_for_partition(&self.partition_id) {
+                position
+            } else {
+                return Ok(());
+            };
+        for message in self.state.take_published_messages(published_position) {
+            let _protect_guard = ctx.protect_zone();
+            if self.params.delete_objects {
+                for object in &message.objects {
+                    let storage = self.storage(&object.bucket)?;
+                    if let Err(error) = storage.delete(Path::new(&object.key)).await {
+                        warn!(
+                            bucket = %object.bucket,
+                            key = %object.key,
+                            error = ?error,
+                            "Failed to delete object."
+                        );
+                    }
+                }
+            }
+            // A message that fails to be deleted becomes visible again and its objects are
+            // indexed twice, which does not justify failing the pipeline.
+            if let Err(error) = self.delete_message(message.receipt_handle).await {
+                warn!(error = ?error, "Failed to delete message.");
+                continue;
+            }
+            self.state.num_deleted_messages += 1;
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        "SqsSource".to_string()
+    }
+
+    fn observable_state(&self) -> serde_json::Value {
+        json!({
+            "queue_url": self.params.queue_url,
+            "partition_id": &*self.partition_id.0,
+            "current_position": self.state.current_position.as_str(),
+            "num_bytes_processed": self.state.num_bytes_processed,
+            "num_messages_processed": self.state.num_messages_processed,
+            "num_objects_processed": self.state.num_objects_processed,
+            "num_invalid_messages": self.state.num_invalid_messages,
+            "num_deleted_messages": self.state.num_deleted_messages,
+            "num_pending_messages": self.state.pending_messages.len(),
+        })
+    }
+}
+
+/// Checks if connecting with the given parameters works.
+pub(super) async fn check_connectivity(params: SqsSourceParams) -> anyhow::Result<()> {
+    connect(&params).await?;
+    Ok(())
+}
+
+/// Creates an SQS client and checks that the queue exists.
+async fn connect(params: &SqsSourceParams) -> anyhow::Result<SqsClient> {
+    let region = region_from_queue_url(&params.queue_url, params.region.as_deref())?;
+    let sqs_client = SqsClient::new(region);
+    let get_queue_attributes_request = GetQueueAttributesRequest {
+        queue_url: params.queue_url.clone(),
+        attribute_names: Some(vec!["QueueArn".to_string()]),
+    };
+    sqs_client
+        .get_queue_attributes(get_queue_attributes_request)
+        .await
+        .with_context(|| format!("Failed to look up queue `{}`.", params.queue_url))?;
+    Ok(sqs_client)
+}
+
+/// Returns the region of the queue. AWS queue URLs have the form
+/// `https://sqs.<region>.amazonaws.com/<account ID>/<queue name>`; other URLs are treated as
+/// custom endpoints, for instance LocalStack's.
+fn region_from_queue_url(queue_url: &str, region_opt: Option<&str>) -> anyhow::Result<Region> {
+    let (scheme, rest) = queue_url
+        .split_once("://")
+        .with_context(|| format!("Invalid queue URL `{}`.", queue_url))?;
+    let host = rest.split('/').next().unwrap_or_default();
+    if let Some(region_str) = host
+        .strip_prefix("sqs.")
+        .and_then(|host| host.strip_suffix(".amazonaws.com"))
+    {
+        let region_str = region_opt.unwrap_or(region_str);
+        return Region::from_str(region_str)
+            .with_context(|| format!("Invalid region `{}`.", region_str));
+    }
+    if host.is_empty() {
+        bail!("Invalid queue URL `{}`.", queue_url);
+    }
+    Ok(Region::Custom {
+        name: region_opt.unwrap_or("us-east-1").to_string(),
+        endpoint: format!("{}://{}", scheme, host),
+    })
+}
+
+#[derive(Deserialize)]
+struct S3EventNotification {
+    #[serde(rename = "Records", default)]
+    records: Vec<S3EventRecord>,
+    /// Notifications relayed by SNS are wrapped in the `Message` field.
+    #[serde(rename = "Message")]
+    sns_message: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct S3EventRecord {
+    #[serde(rename = "eventName")]
+    event_name: String,
+    s3: S3Entity,
+}
+
+#[derive(Deserialize)]
+struct S3Entity {
+    bucket: S3BucketEntity,
+    object: S3ObjectEntity,
+}
+
+#[derive(Deserialize)]
+struct S3BucketEntity {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct S3ObjectEntity {
+    key: String,
+}
+
+/// Returns the objects created according to an S3 event notification.
+fn parse_s3_event_notification(body: &str) -> anyhow::Result<Vec<S3Object>> {
+    let notification: S3EventNotification =
+        serde_json::from_str(body).context("Message is not an S3 event notification.")?;
+    if let Some(sns_message) = notification.sns_message {
+        return parse_s3_event_notification(&sns_message);
+    }
+    notification
+        .records
+        .into_iter()
+        .filter(|record| record.event_name.starts_with("ObjectCreated:"))
+        .map(|record| {
+            Ok(S3Object {
+                bucket: record.s3.bucket.name,
+                key: decode_object_key(&record.s3.object.key)?,
+            })
+        })
+        .collect()
+}
+
+/// Decodes an object key, which S3 event notifications encode like HTML form values.
+fn decode_object_key(encoded_key: &str) -> anyhow::Result<String> {
+    let key = percent_decode_str(&encoded_key.replace('+', " "))
+        .decode_utf8()
+        .with_context(|| format!("Invalid object key `{}`.", encoded_key))?
+        .into_owned();
+    Ok(key)
+}
+
+/// Splits the content of an object into documents, one per non-empty line.
+fn parse_docs(content: &str) -> impl Iterator<Item = String> + '_ {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending_message(receipt_handle: &str) -> PendingMessage {
+        PendingMessage {
+            receipt_handle: receipt_handle.to_string(),
+            objects: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_s3_event_notification() -> anyhow::Result<()> {
+        let body = r#"{
+            "Records": [
+                {
+                    "eventName": "ObjectCreated:Put",
+                    "s3": {
+                        "bucket": {"name": "my-bucket"},
+                        "object": {"key": "logs/2022+01/file%3D1.json", "size": 1024}
+                    }
+                },
+                {
+                    "eventName": "ObjectRemoved:Delete",
+                    "s3": {
+                        "bucket": {"name": "my-bucket"},
+                        "object": {"key": "logs/deleted.json"}
+                    }
+                }
+            ]
+        }"#;
+        let expected_objects = vec![S3Object {
+            bucket: "my-bucket".to_string(),
+            key: "logs/2022 01/file=1.json".to_string(),
+        }];
+        assert_eq!(parse_s3_event_notification(body)?, expected_objects);
+
+        let sns_body = json!({
+            "Type": "Notification",
+            "Message": body,
+        })
+        .to_string();
+        assert_eq!(parse_s3_event_notification(&sns_body)?, expected_objects);
+
+        let test_event_body = r#"{"Service": "Amazon S3", "Event": "s3:TestEvent"}"#;
+        assert!(parse_s3_event_notification(test_event_body)?.is_empty());
+
+        assert!(parse_s3_event_notification("not json").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_region_from_queue_url() -> anyhow::Result<()> {
+        assert_eq!(
+            region_from_queue_url(
+                "https://sqs.eu-west-1.amazonaws.com/123456789012/my-queue",
+                None
+            )?,
+            Region::EuWest1
+        );
+        assert_eq!(
+            region_from_queue_url("http://localhost:4566/000000000000/my-queue", None)?,
+            Region::Custom {
+                name: "us-east-1".to_string(),
+                endpoint: "http://localhost:4566".to_string(),
+            }
+        );
+        assert_eq!(
+            region_from_queue_url(
+                "http://localhost:4566/000000000000/my-queue",
+                Some("eu-west-1")
+            )?,
+            Region::Custom {
+                name: "eu-west-1".to_string(),
+                endpoint: "http://localhost:4566".to_string(),
+            }
+        );
+        assert!(region_from_queue_url("my-queue", None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_docs() {
+        let docs: Vec<String> = parse_docs("{\"a\": 1}\n\n{\"a\": 2}\r\n  \n").collect();
+        assert_eq!(docs, vec!["{\"a\": 1}", "{\"a\": 2}"]);
+    }
+
+    #[test]
+    fn test_record_message_and_take_published_messages() -> anyhow::Result<()> {
+        let partition_id = PartitionId::from("https://sqs.us-east-1.amazonaws.com/1/q:0");
+        let mut state = SqsSourceState::default();
+        let mut checkpoint_delta = CheckpointDelta::default();
+        for receipt_handle in ["handle-1", "handle-2", "handle-3"] {
+            state.record_message(
+                &partition_id,
+                pending_message(receipt_handle),
+                &mut checkpoint_delta,
+            )?;
+        }
+        assert_eq!(
+            checkpoint_delta,
+            CheckpointDelta::from_partition_delta(
+                partition_id,
+                Position::Beginning,
+                Position::from(3u64)
+            )
+        );
+        let receipt_handles = |messages: Vec<PendingMessage>| -> Vec<String> {
+            messages
+                .into_iter()
+                .map(|message| message.receipt_handle)
+                .collect()
+        };
+        assert_eq!(
+            receipt_handles(state.take_published_messages(&Position::from(2u64))),
+            vec!["handle-1", "handle-2"]
+        );
+        assert!(state
+            .take_published_messages(&Position::from(2u64))
+            .is_empty());
+        assert_eq!(
+            receipt_handles(state.take_published_messages(&Position::from(3u64))),
+            vec!["handle-3"]
+        );
+        Ok(())
+    }
+}
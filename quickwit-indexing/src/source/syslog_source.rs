@@ -0,0 +1,810 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use futures::StreamExt;
+use quickwit_actors::{ActorExitStatus, Mailbox};
+use quickwit_config::{SyslogProtocol, SyslogSourceParams};
+use quickwit_metastore::checkpoint::{CheckpointDelta, PartitionId, Position, SourceCheckpoint};
+use serde_json::{json, Map as JsonMap, Value as JsonValue};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{debug, info, warn};
+
+use crate::models::{IndexerMessage, RawDocBatch};
+use crate::source::multiline::{MultilineAssembler, MultilineRecord};
+use crate::source::{Source, SourceContext, TypedSourceFactory};
+
+/// Maximum size of a syslog message. Larger UDP datagrams are truncated and larger TCP frames
+/// are dropped.
+const MAX_MESSAGE_NUM_BYTES: usize = 64 * 1024;
+
+/// Maximum number of documents emitted in one batch.
+const BATCH_NUM_DOCS_LIMIT: usize = 5_000;
+
+/// Number of messages buffered between the listener and the source.
+const MESSAGE_CHANNEL_CAPACITY: usize = 10_000;
+
+/// Facility names indexed by facility code (RFC 5424, section 6.2.1).
+const FACILITY_NAMES: [&str; 24] = [
+    "kern",
+    "user",
+    "mail",
+    "daemon",
+    "auth",
+    "syslog",
+    "lpr",
+    "news",
+    "uucp",
+    "cron",
+    "authpriv",
+    "ftp",
+    "ntp",
+    "security",
+    "console",
+    "solaris-cron",
+    "local0",
+    "local1",
+    "local2",
+    "local3",
+    "local4",
+    "local5",
+    "local6",
+    "local7",
+];
+
+/// Severity names indexed by severity code (RFC 5424, section 6.2.1).
+const SEVERITY_NAMES: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+
+/// Priority assumed for messages without a PRI part: `user.notice` (RFC 3164, section 4.3.3).
+const DEFAULT_PRIORITY: u8 = 13;
+
+/// Receives syslog messages over UDP or TCP, and emits one document per message.
+///
+/// Messages in the RFC 5424 and RFC 3164 (BSD) formats are parsed into documents with the
+/// following fields: `timestamp`, `facility`, `severity`, `hostname`, `app_name`, `proc_id`,
+/// `msg_id`, `structured_data`, `message`, and `remote_ip`. Messages that follow neither format
+/// are indexed whole in `message`.
+///
+/// When multiline assembly is enabled, the consecutive messages of a sender forming a record are
+/// indexed as a single document, whose `message` joins theirs and whose other fields are those of
+/// the first message.
+///
+/// Syslog has no acknowledgement mechanism: messages that are not yet published when the
+/// pipeline stops are lost.
+pub struct SyslogSource {
+    listen_address: SocketAddr,
+    protocol: SyslogProtocol,
+    partition: PartitionId,
+    next_offset: u64,
+    doc_stream: ReceiverStream<SyslogMessage>,
+    doc_sender_opt: Option<mpsc::Sender<SyslogMessage>>,
+    listener_handle_opt: Option<JoinHandle<()>>,
+    /// Assembler cloned for each sender with a pending multiline record.
+    multiline_assembler_opt: Option<MultilineAssembler<JsonMap<String, JsonValue>>>,
+    multiline_assemblers: HashMap<SocketAddr, MultilineAssembler<JsonMap<String, JsonValue>>>,
+    num_messages_received: u64,
+}
+
+/// Parsed syslog message, along with the address of its sender.
+#[derive(Debug)]
+struct SyslogMessage {
+    remote_address: SocketAddr,
+    doc: JsonMap<String, JsonValue>,
+}
+
+pub struct SyslogSourceFactory;
+
+#[async_trait]
+impl TypedSourceFactory for SyslogSourceFactory {
+    type Source = SyslogSource;
+    type Params = SyslogSourceParams;
+
+    async fn typed_create_source(
+        params: SyslogSourceParams,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<Self::Source> {
+        let listen_address: SocketAddr = params.listen_address.parse().with_context(|| {
+            format!(
+                "Failed to parse syslog listen address `{}`.",
+                params.listen_address
+            )
+        })?;
+        let partition = PartitionId::from(format!(
+            "{}://{}",
+            params.protocol.as_str(),
+            params.listen_address
+        ));
+        let next_offset = match checkpoint.position_for_partition(&partition) {
+            Some(Position::Offset(offset_str)) => offset_str.parse::<u64>()? + 1,
+            Some(Position::Beginning) | None => 0,
+        };
+        let multiline_assembler_opt = params
+            .multiline
+            .as_ref()
+            .map(MultilineAssembler::try_new)
+            .transpose()?;
+        let (doc_sender, doc_receiver) = mpsc::channel(MESSAGE_CHANNEL_CAPACITY);
+        Ok(SyslogSource {
+            listen_address,
+            protocol: params.protocol,
+            partition,
+            next_offset,
+            doc_stream: ReceiverStream::new(doc_receiver),
+            doc_sender_opt: Some(doc_sender),
+            listener_handle_opt: None,
+            multiline_assembler_opt,
+            multiline_assemblers: HashMap::new(),
+            num_messages_received: 0,
+        })
+    }
+}
+
+fn position_from_offset(offset: u64) -> Position {
+    if offset == 0 {
+        return Position::Beginning;
+    }
+    Position::from(offset - 1)
+}
+
+#[async_trait]
+impl Source for SyslogSource {
+    async fn initialize(&mut self, _ctx: &SourceContext) -> Result<(), ActorExitStatus> {
+        let doc_sender = self
+            .doc_sender_opt
+            .take()
+            .expect("The syslog listener should be started only once.");
+        let listener_handle = match self.protocol {
+            SyslogProtocol::Udp => {
+                let socket = UdpSocket::bind(self.listen_address)
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "Failed to bind syslog listener on `{}`.",
+                            self.listen_address
+                        )
+                    })?;
+                self.listen_address = socket.local_addr().context("Failed to bind socket.")?;
+                tokio::spawn(receive_datagrams(socket, doc_sender))
+            }
+            SyslogProtocol::Tcp => {
+                let listener = TcpListener::bind(self.listen_address)
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "Failed to bind syslog listener on `{}`.",
+                            self.listen_address
+                        )
+                    })?;
+                self.listen_address = listener.local_addr().context("Failed to bind socket.")?;
+                tokio::spawn(accept_connections(listener, doc_sender))
+            }
+        };
+        info!(
+            listen_address = %self.listen_address,
+            protocol = self.protocol.as_str(),
+            "Starting syslog listener."
+        );
+        self.listener_handle_opt = Some(listener_handle);
+        Ok(())
+    }
+
+    async fn emit_batches(
+        &mut self,
+        batch_sink: &Mailbox<IndexerMessage>,
+        ctx: &SourceContext,
+    ) -> Result<(), ActorExitStatus> {
+        let mut docs = Vec::new();
+        let deadline = ctx.sleep(quickwit_actors::HEARTBEAT / 2);
+        let mut doc_stream = Box::pin((&mut self.doc_stream).take_until(deadline));
+
+        while let Some(message) = doc_stream.next().await {
+            self.num_messages_received += 1;
+            if let Some(multiline_assembler) = &self.multiline_assembler_opt {
+                let line = message
+                    .doc
+                    .get("message")
+                    .and_then(JsonValue::as_str)
+                    .unwrap_or_default()
+                    .to_string();
+                let record_opt = self
+                    .multiline_assemblers
+                    .entry(message.remote_address)
+                    .or_insert_with(|| multiline_assembler.clone())
+                    .push(message.doc, &line, line.len(), Instant::now());
+                docs.extend(record_opt.map(multiline_record_doc));
+            } else {
+                docs.push(JsonValue::Object(message.doc).to_string());
+            }
+            if docs.len() >= BATCH_NUM_DOCS_LIMIT {
+                break;
+            }
+        }
+        // The senders that stopped sending messages complete their pending records.
+        let now = Instant::now();
+        self.multiline_assemblers.retain(|_, multiline_assembler| {
+            match multiline_assembler.flush_expired(now) {
+                Some(record) => {
+                    docs.push(multiline_record_doc(record));
+                    false
+                }
+                None => true,
+            }
+        });
+        if docs.is_empty() {
+            return Ok(());
+        }
+        let from_offset = self.next_offset;
+        self.next_offset += docs.len() as u64;
+        let checkpoint_delta = CheckpointDelta::from_partition_delta(
+            self.partition.clone(),
+            position_from_offset(from_offset),
+            position_from_offset(self.next_offset),
+        );
+        let batch = RawDocBatch {
+            docs,
+            checkpoint_delta,
+        };
+        ctx.send_message(batch_sink, IndexerMessage::from(batch))
+            .await?;
+        Ok(())
+    }
+
+    async fn finalize(
+        &mut self,
+        _exit_status: &ActorExitStatus,
+        _ctx: &SourceContext,
+    ) -> anyhow::Result<()> {
+        // The connection tasks of the TCP listener stop on their next message, once the
+        // receiving end of the channel is dropped with the source.
+        if let Some(listener_handle) = self.listener_handle_opt.take() {
+            listener_handle.abort();
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        "SyslogSource".to_string()
+    }
+
+    fn observable_state(&self) -> serde_json::Value {
+        json!({
+            "listen_address": self.listen_address.to_string(),
+            "protocol": self.protocol.as_str(),
+            "next_offset": self.next_offset,
+            "num_messages_received": self.num_messages_received,
+        })
+    }
+}
+
+/// Builds the document of a multiline record from the fields of its first message.
+fn multiline_record_doc(record: MultilineRecord<JsonMap<String, JsonValue>>) -> String {
+    let mut doc = record.context;
+    doc.insert("message".to_string(), json!(record.text));
+    JsonValue::Object(doc).to_string()
+}
+
+/// Receives one message per datagram.
+async fn receive_datagrams(socket: UdpSocket, doc_sender: mpsc::Sender<SyslogMessage>) {
+    let mut buffer = vec![0u8; MAX_MESSAGE_NUM_BYTES];
+    loop {
+        let (num_bytes, remote_address) = match socket.recv_from(&mut buffer).await {
+            Ok(recv_res) => recv_res,
+            Err(error) => {
+                warn!(error = ?error, "Failed to receive syslog datagram.");
+                continue;
+            }
+        };
+        if let Some(doc) = parse_syslog_message(&buffer[..num_bytes], remote_address, Utc::now()) {
+            let message = SyslogMessage {
+                remote_address,
+                doc,
+            };
+            if doc_sender.send(message).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+async fn accept_connections(listener: TcpListener, doc_sender: mpsc::Sender<SyslogMessage>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, remote_address)) => {
+                debug!(remote_address = %remote_address, "Accepted syslog connection.");
+                tokio::spawn(receive_frames(stream, remote_address, doc_sender.clone()));
+            }
+            Err(error) => warn!(error = ?error, "Failed to accept syslog connection."),
+        }
+    }
+}
+
+async fn receive_frames(
+    stream: TcpStream,
+    remote_address: SocketAddr,
+    doc_sender: mpsc::Sender<SyslogMessage>,
+) {
+    let mut reader = BufReader::new(stream);
+    let mut frame = Vec::new();
+    loop {
+        match read_frame(&mut reader, &mut frame).await {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(error) => {
+                warn!(remote_address = %remote_address, error = ?error, "Failed to read syslog frame.");
+                return;
+            }
+        }
+        if let Some(doc) = parse_syslog_message(&frame, remote_address, Utc::now()) {
+            let message = SyslogMessage {
+                remote_address,
+                doc,
+            };
+            if doc_sender.send(message).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Reads the next frame of a TCP stream into `frame`, supporting both the octet counting
+/// (`<length> <message>`) and the newline delimited framings of RFC 6587. Returns `false` once
+/// the stream is closed.
+async fn read_frame<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    frame: &mut Vec<u8>,
+) -> io::Result<bool> {
+    frame.clear();
+    let first_byte = match reader.fill_buf().await?.first() {
+        Some(first_byte) => *first_byte,
+        None => return Ok(false),
+    };
+    if first_byte.is_ascii_digit() {
+        let mut length_bytes = Vec::new();
+        (&mut *reader)
+            .take(8)
+            .read_until(b' ', &mut length_bytes)
+            .await?;
+        let frame_num_bytes = std::str::from_utf8(&length_bytes)
+            .ok()
+            .and_then(|length_str| length_str.trim_end().parse::<usize>().ok())
+            .filter(|frame_num_bytes| *frame_num_bytes <= MAX_MESSAGE_NUM_BYTES)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid frame length."))?;
+        frame.resize(frame_num_bytes, 0);
+        reader.read_exact(frame).await?;
+    } else {
+        (&mut *reader)
+            .take(MAX_MESSAGE_NUM_BYTES as u64)
+            .read_until(b'\n', frame)
+            .await?;
+    }
+    Ok(true)
+}
+
+/// Parses an RFC 5424 or RFC 3164 syslog message into the fields of a JSON document. Returns
+/// `None` for empty messages.
+fn parse_syslog_message(
+    message: &[u8],
+    remote_address: SocketAddr,
+    received_at: DateTime<Utc>,
+) -> Option<JsonMap<String, JsonValue>> {
+    let message = String::from_utf8_lossy(message);
+    let message = message.trim_end_matches(|c| matches!(c, '\n' | '\r' | '\0'));
+    if message.is_empty() {
+        return None;
+    }
+    let (priority, rest) = parse_priority(message).unwrap_or((DEFAULT_PRIORITY, message));
+    let mut doc = JsonMap::new();
+    doc.insert(
+        "facility".to_string(),
+        json!(FACILITY_NAMES[(priority >> 3) as usize]),
+    );
+    doc.insert(
+        "severity".to_string(),
+        json!(SEVERITY_NAMES[(priority & 7) as usize]),
+    );
+    let timestamp_opt = match rest.strip_prefix("1 ") {
+        Some(rest) => parse_rfc5424(rest, &mut doc),
+        None => parse_rfc3164(rest, received_at, &mut doc),
+    }
+    .unwrap_or_else(|| {
+        debug!("Syslog message does not follow RFC 5424 nor RFC 3164.");
+        doc.insert("message".to_string(), json!(rest));
+        None
+    });
+    let timestamp = timestamp_opt.unwrap_or(received_at);
+    doc.insert(
+        "timestamp".to_string(),
+        json!(timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+    );
+    doc.insert(
+        "remote_ip".to_string(),
+        json!(remote_address.ip().to_string()),
+    );
+    Some(doc)
+}
+
+/// Parses the `<PRI>` part of a message, returning the priority and the rest of the message.
+fn parse_priority(message: &str) -> Option<(u8, &str)> {
+    let rest = message.strip_prefix('<')?;
+    let (priority_str, rest) = rest.split_once('>')?;
+    if priority_str.is_empty() || priority_str.len() > 3 {
+        return None;
+    }
+    let priority = priority_str
+        .parse::<u8>()
+        .ok()
+        .filter(|priority| *priority < 192)?;
+    Some((priority, rest))
+}
+
+/// Parses the part of an RFC 5424 message following the version:
+/// `TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA [MSG]`.
+///
+/// Returns the timestamp of the message, if not nil, or `None` if the message is malformed.
+fn parse_rfc5424(
+    message: &str,
+    doc: &mut JsonMap<String, JsonValue>,
+) -> Option<Option<DateTime<Utc>>> {
+    let mut header_fields = message.splitn(6, ' ');
+    let timestamp_str = header_fields.next()?;
+    let timestamp_opt = if timestamp_str == "-" {
+        None
+    } else {
+        let timestamp = DateTime::parse_from_rfc3339(timestamp_str).ok()?;
+        Some(timestamp.with_timezone(&Utc))
+    };
+    let mut fields = JsonMap::new();
+    for field_name in ["hostname", "app_name", "proc_id", "msg_id"] {
+        let field_value = header_fields.next()?;
+        if field_value != "-" {
+            fields.insert(field_name.to_string(), json!(field_value));
+        }
+    }
+    let rest = header_fields.next()?;
+    let (structured_data_opt, rest) = parse_structured_data(rest)?;
+    if let Some(structured_data) = structured_data_opt {
+        fields.insert(
+            "structured_data".to_string(),
+            JsonValue::Object(structured_data),
+        );
+    }
+    let msg = rest.strip_prefix(' ').unwrap_or(rest);
+    let msg = msg.strip_prefix('\u{feff}').unwrap_or(msg);
+    if !msg.is_empty() {
+        fields.insert("message".to_string(), json!(msg));
+    }
+    doc.extend(fields);
+    Some(timestamp_opt)
+}
+
+/// Parses the structured data of an RFC 5424 message into an object of SD-IDs to objects of
+/// parameters, returning it along with the rest of the message.
+fn parse_structured_data(message: &str) -> Option<(Option<JsonMap<String, JsonValue>>, &str)> {
+    if let Some(rest) = message.strip_prefix('-') {
+        return Some((None, rest));
+    }
+    let mut structured_data = JsonMap::new();
+    let mut rest = message;
+    while let Some(element) = rest.strip_prefix('[') {
+        let id_end = element.find(|c| c == ' ' || c == ']')?;
+        let sd_id = &element[..id_end];
+        let mut params = JsonMap::new();
+        rest = &element[id_end..];
+        loop {
+            if let Some(after_element) = rest.strip_prefix(']') {
+                rest = after_element;
+                break;
+            }
+            let param = rest.strip_prefix(' ')?;
+            let (param_name, param) = param.split_once("=\"")?;
+            let mut param_value = String::new();
+            let mut chars = param.char_indices();
+            let value_end = loop {
+                match chars.next()? {
+                    (idx, '"') => break idx,
+                    (_, '\\') => {
+                        let (_, escaped) = chars.next()?;
+                        if !matches!(escaped, '"' | '\\' | ']') {
+                            param_value.push('\\');
+                        }
+                        param_value.push(escaped);
+                    }
+                    (_, c) => param_value.push(c),
+                }
+            };
+            params.insert(param_name.to_string(), json!(param_value));
+            rest = &param[value_end + 1..];
+        }
+        structured_data.insert(sd_id.to_string(), JsonValue::Object(params));
+    }
+    if structured_data.is_empty() {
+        return None;
+    }
+    Some((Some(structured_data), rest))
+}
+
+/// Parses an RFC 3164 message: `TIMESTAMP HOSTNAME TAG[PID]: MSG`, where the timestamp has the
+/// form `Mmm dd hh:mm:ss` and the hostname may be missing.
+///
+/// The timestamp has neither a year nor a time zone: it is assumed to be UTC and to be in the
+/// year of reception, unless that puts it in the future.
+fn parse_rfc3164(
+    message: &str,
+    received_at: DateTime<Utc>,
+    doc: &mut JsonMap<String, JsonValue>,
+) -> Option<Option<DateTime<Utc>>> {
+    let timestamp_str = message.get(..15)?;
+    let rest = message[15..].strip_prefix(' ')?;
+    let timestamp_str = timestamp_str
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let parse_timestamp = |year: i32| {
+        NaiveDateTime::parse_from_str(&format!("{} {}", year, timestamp_str), "%Y %b %d %H:%M:%S")
+            .ok()
+            .map(|naive_timestamp| Utc.from_utc_datetime(&naive_timestamp))
+    };
+    let mut timestamp = parse_timestamp(received_at.year())?;
+    if timestamp > received_at + Duration::days(1) {
+        timestamp = parse_timestamp(received_at.year() - 1)?;
+    }
+    let (first_token, after_first_token) = rest.split_once(' ').unwrap_or((rest, ""));
+    let (hostname_opt, rest) = if is_rfc3164_tag(first_token) {
+        (None, rest)
+    } else {
+        (Some(first_token), after_first_token)
+    };
+    if let Some(hostname) = hostname_opt {
+        doc.insert("hostname".to_string(), json!(hostname));
+    }
+    let msg = match rest.split_once(' ') {
+        Some((tag, msg)) if is_rfc3164_tag(tag) => {
+            let tag = tag.trim_end_matches(':');
+            let (app_name, proc_id_opt) = match tag.split_once('[') {
+                Some((app_name, proc_id)) => (app_name, proc_id.strip_suffix(']')),
+                None => (tag, None),
+            };
+            doc.insert("app_name".to_string(), json!(app_name));
+            if let Some(proc_id) = proc_id_opt {
+                doc.insert("proc_id".to_string(), json!(proc_id));
+            }
+            msg
+        }
+        _ => rest,
+    };
+    if !msg.is_empty() {
+        doc.insert("message".to_string(), json!(msg));
+    }
+    Some(Some(timestamp))
+}
+
+/// A tag is the name of the program that emitted the message, optionally followed by its PID,
+/// and terminated by a colon, e.g. `sshd[42]:`.
+fn is_rfc3164_tag(token: &str) -> bool {
+    match token.strip_suffix(':') {
+        Some(tag) => {
+            let app_name = tag.split('[').next().unwrap_or_default();
+            !app_name.is_empty()
+                && app_name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'))
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_actors::{create_test_mailbox, Universe};
+
+    use super::*;
+    use crate::source::SourceActor;
+
+    fn parse(message: &str) -> JsonValue {
+        let remote_address: SocketAddr = "192.168.0.1:51234".parse().unwrap();
+        let received_at = Utc.ymd(2022, 1, 1).and_hms(12, 0, 0);
+        let doc = parse_syslog_message(message.as_bytes(), remote_address, received_at).unwrap();
+        JsonValue::Object(doc)
+    }
+
+    #[test]
+    fn test_parse_rfc5424_message() {
+        assert_eq!(
+            parse(
+                "<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 \
+                 [exampleSDID@32473 iut=\"3\" eventSource=\"Application\" \
+                 eventID=\"1011\"][examplePriority@32473 class=\"high \\\"a\\\\b\\]\"] \u{feff}An \
+                 application event log entry...\n"
+            ),
+            json!({
+                "timestamp": "2003-10-11T22:14:15.003Z",
+                "facility": "local4",
+                "severity": "notice",
+                "hostname": "mymachine.example.com",
+                "app_name": "evntslog",
+                "msg_id": "ID47",
+                "structured_data": {
+                    "exampleSDID@32473": {
+                        "iut": "3",
+                        "eventSource": "Application",
+                        "eventID": "1011"
+                    },
+                    "examplePriority@32473": {"class": "high \"a\\b]"}
+                },
+                "message": "An application event log entry...",
+                "remote_ip": "192.168.0.1"
+            })
+        );
+        assert_eq!(
+            parse("<34>1 2003-10-11T22:14:15+02:00 - su 42 - - 'su root' failed"),
+            json!({
+                "timestamp": "2003-10-11T20:14:15Z",
+                "facility": "auth",
+                "severity": "crit",
+                "app_name": "su",
+                "proc_id": "42",
+                "message": "'su root' failed",
+                "remote_ip": "192.168.0.1"
+            })
+        );
+        assert_eq!(
+            parse("<34>1 - - - - - -"),
+            json!({
+                "timestamp": "2022-01-01T12:00:00Z",
+                "facility": "auth",
+                "severity": "crit",
+                "remote_ip": "192.168.0.1"
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_rfc3164_message() {
+        assert_eq!(
+            parse("<34>Oct 11 22:14:15 mymachine su[230]: 'su root' failed for lonvick"),
+            json!({
+                "timestamp": "2021-10-11T22:14:15Z",
+                "facility": "auth",
+                "severity": "crit",
+                "hostname": "mymachine",
+                "app_name": "su",
+                "proc_id": "230",
+                "message": "'su root' failed for lonvick",
+                "remote_ip": "192.168.0.1"
+            })
+        );
+        assert_eq!(
+            parse("<13>Jan  1 11:59:00 sshd: Connection closed"),
+            json!({
+                "timestamp": "2022-01-01T11:59:00Z",
+                "facility": "user",
+                "severity": "notice",
+                "app_name": "sshd",
+                "message": "Connection closed",
+                "remote_ip": "192.168.0.1"
+            })
+        );
+        assert_eq!(
+            parse("<190>Jan  1 11:59:00 switch-01 %LINK-3-UPDOWN Interface down"),
+            json!({
+                "timestamp": "2022-01-01T11:59:00Z",
+                "facility": "local7",
+                "severity": "info",
+                "hostname": "switch-01",
+                "message": "%LINK-3-UPDOWN Interface down",
+                "remote_ip": "192.168.0.1"
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_unstructured_message() {
+        assert_eq!(
+            parse("Interface eth0 down"),
+            json!({
+                "timestamp": "2022-01-01T12:00:00Z",
+                "facility": "user",
+                "severity": "notice",
+                "message": "Interface eth0 down",
+                "remote_ip": "192.168.0.1"
+            })
+        );
+        assert_eq!(
+            parse("<999>1 not a valid header")["message"],
+            "<999>1 not a valid header"
+        );
+        assert_eq!(
+            parse("<14>1 2003-10-11T22:14:15Z host app - - [unterminated")["message"],
+            "1 2003-10-11T22:14:15Z host app - - [unterminated"
+        );
+        let remote_address: SocketAddr = "192.168.0.1:51234".parse().unwrap();
+        assert!(parse_syslog_message(b"\n", remote_address, Utc::now()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_read_frame() -> io::Result<()> {
+        let mut reader: &[u8] = b"11 <14>1 - - -<14>message\n6 <13>12\n<13>last";
+        let mut frame = Vec::new();
+        let mut frames = Vec::new();
+        while read_frame(&mut reader, &mut frame).await? {
+            frames.push(String::from_utf8(frame.clone()).unwrap());
+        }
+        assert_eq!(
+            frames,
+            vec!["<14>1 - - -", "<14>message\n", "<13>12", "\n", "<13>last"]
+        );
+        let mut reader: &[u8] = b"99999999 <14>message";
+        assert!(read_frame(&mut reader, &mut frame).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_syslog_source() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let universe = Universe::new();
+        let (mailbox, inbox) = create_test_mailbox();
+        let params = SyslogSourceParams {
+            listen_address: "127.0.0.1:0".to_string(),
+            protocol: SyslogProtocol::Udp,
+            multiline: None,
+        };
+        let syslog_source =
+            SyslogSourceFactory::typed_create_source(params, SourceCheckpoint::default()).await?;
+        let syslog_source_actor = SourceActor::new(Box::new(syslog_source), mailbox);
+        let (_syslog_source_mailbox, syslog_source_handle) =
+            universe.spawn_actor(syslog_source_actor).spawn_async();
+        let observation = syslog_source_handle
+            .process_pending_and_observe()
+            .await
+            .state;
+        let listen_address: SocketAddr = observation["listen_address"].as_str().unwrap().parse()?;
+        let socket = UdpSocket::bind("127.0.0.1:0").await?;
+        socket
+            .send_to(b"<34>1 - host su - - - message #0", listen_address)
+            .await?;
+        socket.send_to(b"message #1", listen_address).await?;
+        tokio::time::sleep(quickwit_actors::HEARTBEAT).await;
+        let observation = syslog_source_handle
+            .process_pending_and_observe()
+            .await
+            .state;
+        assert_eq!(observation["next_offset"], 2);
+        assert_eq!(observation["num_messages_received"], 2);
+        let messages = inbox.drain_available_message_for_test();
+        assert!(matches!(
+            &messages[0],
+            IndexerMessage::Batch(ref raw_batch) if raw_batch.docs.len() == 2 && format!("{:?}", raw_batch.checkpoint_delta) == "∆(udp://127.0.0.1:0:(..00000000000000000001])"
+        ));
+        syslog_source_handle.kill().await;
+        Ok(())
+    }
+}
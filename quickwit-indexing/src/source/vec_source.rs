@@ -129,10 +129,7 @@ mod tests {
         };
         let vec_source =
             VecSourceFactory::typed_create_source(params, SourceCheckpoint::default()).await?;
-        let vec_source_actor = SourceActor {
-            source: Box::new(vec_source),
-            batch_sink: mailbox,
-        };
+        let vec_source_actor = SourceActor::new(Box::new(vec_source), mailbox);
         assert_eq!(vec_source_actor.name(), "VecSource");
         let (_vec_source_mailbox, vec_source_handle) =
             universe.spawn_actor(vec_source_actor).spawn_async();
@@ -166,10 +163,7 @@ mod tests {
         checkpoint.try_apply_delta(CheckpointDelta::from(0u64..2u64))?;
 
         let vec_source = VecSourceFactory::typed_create_source(params, checkpoint).await?;
-        let vec_source_actor = SourceActor {
-            source: Box::new(vec_source),
-            batch_sink: mailbox,
-        };
+        let vec_source_actor = SourceActor::new(Box::new(vec_source), mailbox);
         let (_vec_source_mailbox, vec_source_handle) =
             universe.spawn_actor(vec_source_actor).spawn_async();
         let (actor_termination, last_observation) = vec_source_handle.join().await;
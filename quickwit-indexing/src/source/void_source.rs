@@ -31,9 +31,9 @@ impl Source for VoidSource {
     async fn emit_batches(
         &mut self,
         _: &Mailbox<IndexerMessage>,
-        _: &SourceContext,
+        ctx: &SourceContext,
     ) -> Result<(), ActorExitStatus> {
-        tokio::time::sleep(HEARTBEAT / 2).await;
+        ctx.sleep(HEARTBEAT / 2).await;
         Ok(())
     }
 
@@ -64,6 +64,8 @@ impl TypedSourceFactory for VoidSourceFactory {
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use quickwit_actors::{create_test_mailbox, Health, Supervisable, Universe};
     use quickwit_config::SourceParams;
     use quickwit_metastore::checkpoint::SourceCheckpoint;
@@ -77,6 +79,9 @@ mod tests {
         let source_config = SourceConfig {
             source_id: "void-test-source".to_string(),
             source_params: SourceParams::void(),
+            filter: None,
+            routing: None,
+            transform: None,
         };
         let source_loader = quickwit_supported_sources();
         let _ = source_loader
@@ -95,10 +100,7 @@ mod tests {
             SourceCheckpoint::default(),
         )
         .await?;
-        let void_source_actor = SourceActor {
-            source: Box::new(void_source),
-            batch_sink: mailbox,
-        };
+        let void_source_actor = SourceActor::new(Box::new(void_source), mailbox);
         let (_, void_source_handle) = universe.spawn_actor(void_source_actor).spawn_async();
         matches!(void_source_handle.health(), Health::Healthy);
         let (actor_termination, observed_state) = void_source_handle.quit().await;
@@ -106,4 +108,30 @@ mod tests {
         matches!(actor_termination, ActorExitStatus::Quit);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_void_source_running_with_simulated_time() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let universe = Universe::with_simulated_time();
+        let (mailbox, inbox) = create_test_mailbox();
+        let void_source = VoidSourceFactory::typed_create_source(
+            VoidSourceParams {},
+            SourceCheckpoint::default(),
+        )
+        .await?;
+        let void_source_actor = SourceActor::new(Box::new(void_source), mailbox);
+        let (_, void_source_handle) = universe.spawn_actor(void_source_actor).spawn_async();
+        let start = std::time::Instant::now();
+        universe.simulate_time_shift(Duration::from_secs(60)).await;
+        assert!(start.elapsed() < Duration::from_secs(30));
+        assert!(matches!(void_source_handle.health(), Health::Healthy));
+        assert!(inbox.drain_available_message_for_test().is_empty());
+        // The source only processes the quit command once it wakes up.
+        let ((actor_termination, _), _) = tokio::join!(
+            void_source_handle.quit(),
+            universe.simulate_time_shift(HEARTBEAT)
+        );
+        assert!(matches!(actor_termination, ActorExitStatus::Quit));
+        Ok(())
+    }
 }
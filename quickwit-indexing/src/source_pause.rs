@@ -0,0 +1,140 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+use prometheus::IntGauge;
+use quickwit_common::metrics::new_gauge;
+
+/// Paused sources of the node, as (index ID, source ID) pairs. The indexing pipelines pause the
+/// source actors of the paused sources and resume them once they are removed from the set.
+static PAUSED_SOURCES: Lazy<RwLock<BTreeSet<(String, String)>>> = Lazy::new(Default::default);
+
+/// Sources whose checkpoint was reset while they were paused, as (index ID, source ID) pairs.
+/// Their source actors hold the positions they reached before the reset, so the indexing
+/// pipelines restart instead of resuming them.
+static RESET_CHECKPOINT_SOURCES: Lazy<RwLock<BTreeSet<(String, String)>>> =
+    Lazy::new(Default::default);
+
+static NUM_PAUSED_SOURCES: Lazy<IntGauge> = Lazy::new(|| {
+    new_gauge(
+        "quickwit_indexing_paused_sources",
+        "Number of sources whose indexing is paused on the node.",
+    )
+});
+
+/// Pauses the source of the index. Returns false if the source was already paused.
+pub fn pause_source(index_id: &str, source_id: &str) -> bool {
+    let mut paused_sources = PAUSED_SOURCES
+        .write()
+        .expect("Paused sources lock should not be poisoned.");
+    let inserted = paused_sources.insert((index_id.to_string(), source_id.to_string()));
+    NUM_PAUSED_SOURCES.set(paused_sources.len() as i64);
+    inserted
+}
+
+/// Resumes the source of the index. Returns false if the source was not paused.
+pub fn resume_source(index_id: &str, source_id: &str) -> bool {
+    let mut paused_sources = PAUSED_SOURCES
+        .write()
+        .expect("Paused sources lock should not be poisoned.");
+    let removed = paused_sources.remove(&(index_id.to_string(), source_id.to_string()));
+    NUM_PAUSED_SOURCES.set(paused_sources.len() as i64);
+    removed
+}
+
+/// Returns true if the source of the index is paused.
+pub fn is_source_paused(index_id: &str, source_id: &str) -> bool {
+    PAUSED_SOURCES
+        .read()
+        .expect("Paused sources lock should not be poisoned.")
+        .contains(&(index_id.to_string(), source_id.to_string()))
+}
+
+/// Records that the checkpoint of the source of the index was reset, so that its indexing
+/// pipeline restarts from the reset checkpoint once the source is resumed.
+pub fn record_source_checkpoint_reset(index_id: &str, source_id: &str) {
+    RESET_CHECKPOINT_SOURCES
+        .write()
+        .expect("Reset checkpoint sources lock should not be poisoned.")
+        .insert((index_id.to_string(), source_id.to_string()));
+}
+
+/// Returns true if the checkpoint of the source of the index was reset since the last call, and
+/// forgets about the reset.
+pub(crate) fn take_source_checkpoint_reset(index_id: &str, source_id: &str) -> bool {
+    RESET_CHECKPOINT_SOURCES
+        .write()
+        .expect("Reset checkpoint sources lock should not be poisoned.")
+        .remove(&(index_id.to_string(), source_id.to_string()))
+}
+
+/// Returns the paused sources of the node, per index ID.
+pub fn paused_sources() -> BTreeMap<String, Vec<String>> {
+    let mut paused_sources: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (index_id, source_id) in PAUSED_SOURCES
+        .read()
+        .expect("Paused sources lock should not be poisoned.")
+        .iter()
+    {
+        paused_sources
+            .entry(index_id.clone())
+            .or_default()
+            .push(source_id.clone());
+    }
+    paused_sources
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paused_sources() {
+        let index_id = "test-paused-sources";
+        assert!(!is_source_paused(index_id, "source-1"));
+        assert!(pause_source(index_id, "source-1"));
+        assert!(!pause_source(index_id, "source-1"));
+        assert!(pause_source(index_id, "source-2"));
+        assert!(is_source_paused(index_id, "source-1"));
+        assert!(!is_source_paused("test-paused-sources-other", "source-1"));
+        assert_eq!(
+            paused_sources().get(index_id),
+            Some(&vec!["source-1".to_string(), "source-2".to_string()])
+        );
+        assert!(resume_source(index_id, "source-1"));
+        assert!(!resume_source(index_id, "source-1"));
+        assert!(!is_source_paused(index_id, "source-1"));
+        assert!(resume_source(index_id, "source-2"));
+        assert!(paused_sources().get(index_id).is_none());
+    }
+
+    #[test]
+    fn test_source_checkpoint_resets() {
+        let index_id = "test-source-checkpoint-resets";
+        assert!(!take_source_checkpoint_reset(index_id, "source-1"));
+        record_source_checkpoint_reset(index_id, "source-1");
+        record_source_checkpoint_reset(index_id, "source-1");
+        assert!(!take_source_checkpoint_reset(index_id, "source-2"));
+        assert!(take_source_checkpoint_reset(index_id, "source-1"));
+        assert!(!take_source_checkpoint_reset(index_id, "source-1"));
+    }
+}
@@ -116,6 +116,16 @@ impl IndexingSplitStore {
         }
     }
 
+    /// Returns a split store sharing the local split store of `self`, but sending its remote
+    /// requests to `remote_storage`, e.g. to account for them separately.
+    pub fn with_remote_storage(&self, remote_storage: Arc<dyn Storage>) -> Self {
+        IndexingSplitStore {
+            remote_storage,
+            local_split_store: self.local_split_store.clone(),
+            merge_policy: self.merge_policy.clone(),
+        }
+    }
+
     /// Stores a split.
     ///
     /// If a split is identified as mature by the merge policy,
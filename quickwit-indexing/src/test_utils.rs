@@ -21,7 +21,7 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use quickwit_config::{
-    build_doc_mapper, IndexerConfig, SourceConfig, SourceParams, VecSourceParams,
+    build_doc_mapper, IndexConfig, IndexerConfig, SourceConfig, SourceParams, VecSourceParams,
 };
 use quickwit_doc_mapper::DocMapper;
 use quickwit_metastore::{
@@ -70,6 +70,25 @@ impl TestSandbox {
             .iter()
             .map(|search_field| search_field.to_string())
             .collect();
+        Self::create_with_index_metadata(index_meta).await
+    }
+
+    /// Creates a new test environment from an index config.
+    ///
+    /// The index URI and the sources of the config are ignored: the splits are stored in RAM and
+    /// documents are only ingested with `add_documents`.
+    pub async fn create_from_index_config(index_config: &IndexConfig) -> anyhow::Result<Self> {
+        let index_uri = index_uri(&index_config.index_id);
+        let mut index_meta = IndexMetadata::for_test(&index_config.index_id, &index_uri);
+        index_meta.doc_mapping = index_config.doc_mapping.clone();
+        index_meta.indexing_settings = index_config.indexing_settings.clone();
+        index_meta.search_settings = index_config.search_settings.clone();
+        Self::create_with_index_metadata(index_meta).await
+    }
+
+    async fn create_with_index_metadata(index_meta: IndexMetadata) -> anyhow::Result<Self> {
+        let index_id = index_meta.index_id.clone();
+        let index_uri = index_meta.index_uri.clone();
         let doc_mapper = build_doc_mapper(
             &index_meta.doc_mapping,
             &index_meta.search_settings,
@@ -89,7 +108,7 @@ impl TestSandbox {
             storage_resolver.clone(),
         );
         Ok(TestSandbox {
-            index_id: index_id.to_string(),
+            index_id,
             client,
             doc_mapper,
             metastore,
@@ -121,6 +140,9 @@ impl TestSandbox {
                 batch_num_docs: 10,
                 partition: format!("add-docs-{}", add_docs_id),
             }),
+            filter: None,
+            routing: None,
+            transform: None,
         };
         let pipeline_id = self
             .client
@@ -131,6 +153,11 @@ impl TestSandbox {
         Ok(pipeline_statistics)
     }
 
+    /// Returns the ID of the index of the TestSandbox.
+    pub fn index_id(&self) -> &str {
+        &self.index_id
+    }
+
     /// Returns the metastore of the TestSandbox.
     ///
     /// The metastore is a file-backed metastore.
@@ -176,6 +203,9 @@ pub fn mock_split_meta(split_id: &str) -> SplitMetadata {
         tags: Default::default(),
         demux_num_ops: 0,
         footer_offsets: 700..800,
+        sort_by: None,
+        field_stats: Default::default(),
+        replaced_split_ids: Default::default(),
     }
 }
 
@@ -0,0 +1,195 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+use prometheus::IntGauge;
+use quickwit_common::metrics::new_gauge;
+use serde::Serialize;
+
+/// Reason why the indexing of an index is blocked on the node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteBlockReason {
+    /// The scratch disk of the indexer crossed its high watermark.
+    DiskHighWatermark,
+    /// The block was applied through the API.
+    Manual,
+    /// The index was rolled over or left the hot phase of its lifecycle.
+    Lifecycle,
+}
+
+/// Write blocks of the node, per index ID. The indexing server stops the pipelines of the
+/// blocked indexes and does not spawn them again until the block is cleared, and the ingest API
+/// rejects the documents of the blocked indexes.
+static WRITE_BLOCKS: Lazy<RwLock<BTreeMap<String, WriteBlockReason>>> = Lazy::new(Default::default);
+
+static NUM_WRITE_BLOCKED_INDEXES: Lazy<IntGauge> = Lazy::new(|| {
+    new_gauge(
+        "quickwit_indexing_write_blocked_indexes",
+        "Number of indexes whose indexing is blocked on the node.",
+    )
+});
+
+pub(crate) static DISK_USED_NUM_BYTES: Lazy<IntGauge> = Lazy::new(|| {
+    new_gauge(
+        "quickwit_indexing_disk_used_bytes",
+        "Space used on the disk holding the indexing scratch directory, in bytes.",
+    )
+});
+
+pub(crate) static DISK_TOTAL_NUM_BYTES: Lazy<IntGauge> = Lazy::new(|| {
+    new_gauge(
+        "quickwit_indexing_disk_total_bytes",
+        "Size of the disk holding the indexing scratch directory, in bytes.",
+    )
+});
+
+/// Blocks the indexing of the index. Returns false if the index was already blocked, in which
+/// case the reason of the existing block is kept.
+pub fn apply_write_block(index_id: &str, reason: WriteBlockReason) -> bool {
+    let mut write_blocks = WRITE_BLOCKS
+        .write()
+        .expect("Write blocks lock should not be poisoned.");
+    if write_blocks.contains_key(index_id) {
+        return false;
+    }
+    write_blocks.insert(index_id.to_string(), reason);
+    NUM_WRITE_BLOCKED_INDEXES.set(write_blocks.len() as i64);
+    true
+}
+
+/// Clears the write block of the index, returning its reason if the index was blocked.
+pub fn clear_write_block(index_id: &str) -> Option<WriteBlockReason> {
+    let mut write_blocks = WRITE_BLOCKS
+        .write()
+        .expect("Write blocks lock should not be poisoned.");
+    let reason_opt = write_blocks.remove(index_id);
+    NUM_WRITE_BLOCKED_INDEXES.set(write_blocks.len() as i64);
+    reason_opt
+}
+
+/// Returns the reason of the write block of the index, if any.
+pub fn write_block(index_id: &str) -> Option<WriteBlockReason> {
+    WRITE_BLOCKS
+        .read()
+        .expect("Write blocks lock should not be poisoned.")
+        .get(index_id)
+        .copied()
+}
+
+/// Returns the write blocks of the node, per index ID.
+pub fn write_blocks() -> BTreeMap<String, WriteBlockReason> {
+    WRITE_BLOCKS
+        .read()
+        .expect("Write blocks lock should not be poisoned.")
+        .clone()
+}
+
+/// Space of the disk holding a directory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct DiskUsage {
+    pub total_num_bytes: u64,
+    pub available_num_bytes: u64,
+}
+
+impl DiskUsage {
+    /// Measures the disk holding `path`, or its closest existing ancestor if `path` does not
+    /// exist yet.
+    pub fn of_path(path: &Path) -> io::Result<Self> {
+        let existing_path = path
+            .ancestors()
+            .find(|ancestor| ancestor.exists())
+            .unwrap_or(path);
+        Ok(DiskUsage {
+            total_num_bytes: fs2::total_space(existing_path)?,
+            available_num_bytes: fs2::available_space(existing_path)?,
+        })
+    }
+
+    pub fn used_num_bytes(&self) -> u64 {
+        self.total_num_bytes
+            .saturating_sub(self.available_num_bytes)
+    }
+
+    /// Returns true if the fraction of the disk in use reached `high_watermark`.
+    pub fn is_above_watermark(&self, high_watermark: f64) -> bool {
+        if self.total_num_bytes == 0 {
+            return false;
+        }
+        self.used_num_bytes() as f64 / self.total_num_bytes as f64 >= high_watermark
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_blocks() {
+        let index_id = "test-write-blocks";
+        assert_eq!(write_block(index_id), None);
+        assert!(apply_write_block(
+            index_id,
+            WriteBlockReason::DiskHighWatermark
+        ));
+        assert!(!apply_write_block(index_id, WriteBlockReason::Manual));
+        assert_eq!(
+            write_block(index_id),
+            Some(WriteBlockReason::DiskHighWatermark)
+        );
+        assert_eq!(
+            write_blocks().get(index_id),
+            Some(&WriteBlockReason::DiskHighWatermark)
+        );
+        assert_eq!(
+            clear_write_block(index_id),
+            Some(WriteBlockReason::DiskHighWatermark)
+        );
+        assert_eq!(clear_write_block(index_id), None);
+        assert_eq!(write_block(index_id), None);
+    }
+
+    #[test]
+    fn test_disk_usage_is_above_watermark() {
+        let disk_usage = DiskUsage {
+            total_num_bytes: 1_000,
+            available_num_bytes: 100,
+        };
+        assert_eq!(disk_usage.used_num_bytes(), 900);
+        assert!(disk_usage.is_above_watermark(0.9));
+        assert!(!disk_usage.is_above_watermark(0.95));
+        assert!(!DiskUsage {
+            total_num_bytes: 0,
+            available_num_bytes: 0,
+        }
+        .is_above_watermark(0.5));
+    }
+
+    #[test]
+    fn test_disk_usage_of_missing_path() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let disk_usage = DiskUsage::of_path(&temp_dir.path().join("indexing/missing")).unwrap();
+        assert!(disk_usage.total_num_bytes > 0);
+    }
+}
@@ -0,0 +1,2 @@
+ALTER TABLE splits
+DROP COLUMN split_metadata_bin;
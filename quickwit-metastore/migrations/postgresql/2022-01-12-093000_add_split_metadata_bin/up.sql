@@ -0,0 +1,3 @@
+-- Compact binary encoding of the split metadata, see `encode_split_metadata`.
+ALTER TABLE splits
+ADD split_metadata_bin BYTEA;
@@ -0,0 +1,7 @@
+-- Leases of the tasks that must run on a single node, see `Metastore::acquire_lease`.
+CREATE TABLE leases (
+    lease_id VARCHAR(255) PRIMARY KEY,
+    holder_id VARCHAR(255) NOT NULL,
+    fencing_token BIGINT NOT NULL,
+    expiration_timestamp BIGINT NOT NULL
+);
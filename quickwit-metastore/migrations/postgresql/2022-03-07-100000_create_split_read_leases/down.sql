@@ -0,0 +1 @@
+DROP TABLE split_read_leases;
@@ -0,0 +1,9 @@
+-- Read leases that the searches take on the splits they read, see
+-- `Metastore::acquire_split_read_leases`.
+CREATE TABLE split_read_leases (
+    index_id VARCHAR(255) NOT NULL,
+    holder_id VARCHAR(255) NOT NULL,
+    split_id VARCHAR(50) NOT NULL,
+    expiration_timestamp BIGINT NOT NULL,
+    PRIMARY KEY (index_id, holder_id, split_id)
+);
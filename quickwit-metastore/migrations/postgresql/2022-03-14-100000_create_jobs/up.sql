@@ -0,0 +1,11 @@
+-- Records of the long-running operations submitted through the jobs API, see
+-- `Metastore::create_job`.
+CREATE TABLE jobs (
+    job_id VARCHAR(50) PRIMARY KEY,
+    index_id VARCHAR(255) NOT NULL,
+    job_state VARCHAR(50) NOT NULL,
+    job_json TEXT NOT NULL,
+    update_timestamp BIGINT NOT NULL
+);
+
+CREATE INDEX jobs_index_id_idx ON jobs(index_id);
@@ -0,0 +1,12 @@
+-- Checkpoint deltas appended by the publications of splits. They hold the positions reached by
+-- the partitions updated by each publication, so that a publication does not rewrite the whole
+-- index metadata. They are folded into the index metadata every once in a while.
+CREATE TABLE checkpoint_deltas (
+    delta_id BIGSERIAL PRIMARY KEY,
+    index_id VARCHAR(255) NOT NULL,
+    delta_json TEXT NOT NULL,
+
+    FOREIGN KEY(index_id) REFERENCES indexes(index_id) ON DELETE CASCADE
+);
+
+CREATE INDEX checkpoint_deltas_index_id_idx ON checkpoint_deltas(index_id);
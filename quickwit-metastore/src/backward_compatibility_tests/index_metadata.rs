@@ -29,7 +29,7 @@ use quickwit_doc_mapper::SortOrder;
 use crate::checkpoint::{
     CheckpointDelta, IndexCheckpoint, PartitionId, Position, SourceCheckpoint,
 };
-use crate::IndexMetadata;
+use crate::{IndexLifecycleState, IndexMetadata};
 
 pub(crate) fn test_index_metadata_eq(
     index_metadata: &IndexMetadata,
@@ -80,6 +80,10 @@ pub(crate) fn test_index_metadata_eq(
         index_metadata.create_timestamp,
         expected_index_metadata.create_timestamp
     );
+    assert_eq!(
+        index_metadata.lifecycle_state,
+        expected_index_metadata.lifecycle_state
+    );
 }
 
 /// Creates a new [`IndexMetadata`] object against which backward compatibility tests will be run.
@@ -153,24 +157,42 @@ pub(crate) fn sample_index_metadata_for_regression() -> IndexMetadata {
         demux_enabled: true,
         demux_field: Some("tenant_id".to_string()),
         timestamp_field: Some("timestamp".to_string()),
+        ingest_timestamp_field: None,
         sort_field: Some("timestamp".to_string()),
         sort_order: Some(SortOrder::Asc),
+        sort_by_timestamp: false,
         commit_timeout_secs: 301,
         split_num_docs_target: 10_000_001,
         merge_enabled: true,
         merge_policy,
         resources: indexing_resources,
+        deduplication: None,
+        versioning: None,
+        retention: None,
+        dead_letter: None,
+        lifecycle: None,
+        shared_pipeline: false,
     };
     let search_settings = SearchSettings {
         default_search_fields: vec!["message".to_string()],
+        preferred_searcher_nodes: Vec::new(),
     };
     let kafka_source = SourceConfig {
         source_id: "kafka-source".to_string(),
         source_params: SourceParams::Kafka(KafkaSourceParams {
             topic: "kafka-topic".to_string(),
+            topic_pattern: None,
+            topic_discovery_interval_secs: None,
             client_log_level: None,
             client_params: serde_json::json!({}),
+            debezium: None,
+            enable_rebalancing: false,
+            oauthbearer: None,
+            decoding: None,
         }),
+        filter: None,
+        routing: None,
+        transform: None,
     };
     let mut sources = HashMap::default();
     sources.insert("kafka-source".to_string(), kafka_source);
@@ -185,6 +207,7 @@ pub(crate) fn sample_index_metadata_for_regression() -> IndexMetadata {
         sources,
         create_timestamp: 1789,
         update_timestamp: 1789,
+        lifecycle_state: IndexLifecycleState::default(),
     }
 }
 
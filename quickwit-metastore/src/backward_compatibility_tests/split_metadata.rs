@@ -17,6 +17,9 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use quickwit_doc_mapper::range_pruning::{FieldBounds, FieldStats};
+use quickwit_doc_mapper::{SortByField, SortOrder};
+
 use crate::SplitMetadata;
 
 /// Creates a split metadata object that will be
@@ -30,6 +33,34 @@ pub(crate) fn sample_split_metadata_for_regression() -> SplitMetadata {
         create_timestamp: 3,
         tags: ["234".to_string(), "aaa".to_string()].into_iter().collect(),
         demux_num_ops: 1,
+        sort_by: Some(SortByField {
+            field_name: "timestamp".to_string(),
+            order: SortOrder::Desc,
+        }),
+        field_stats: [
+            (
+                "latency_ms".to_string(),
+                FieldStats {
+                    bounds: FieldBounds::F64 {
+                        min: 0.5,
+                        max: 12.5,
+                    },
+                    num_nulls: 3,
+                },
+            ),
+            (
+                "status_code".to_string(),
+                FieldStats {
+                    bounds: FieldBounds::U64 { min: 200, max: 404 },
+                    num_nulls: 0,
+                },
+            ),
+        ]
+        .into_iter()
+        .collect(),
+        replaced_split_ids: ["split-a".to_string(), "split-b".to_string()]
+            .into_iter()
+            .collect(),
         footer_offsets: 1000..2000,
     }
 }
@@ -31,7 +31,7 @@ use thiserror::Error;
 use tracing::{info, warn};
 
 /// PartitionId identifies a partition for a given source.
-#[derive(Debug, Default, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Debug, Default, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct PartitionId(pub Arc<String>);
 
 impl From<String> for PartitionId {
@@ -199,6 +199,55 @@ impl IndexCheckpoint {
     pub fn remove_source(&mut self, source_id: &str) {
         self.per_source.remove(source_id);
     }
+
+    /// Replaces the checkpoint of a source, regardless of its current positions. Unlike
+    /// applying a delta, this can move the positions of the source backward.
+    pub fn reset_source(&mut self, source_id: &str, checkpoint: SourceCheckpoint) {
+        self.per_source.insert(source_id.to_string(), checkpoint);
+    }
+
+    /// Updates the checkpoints of all the sources covered by an index checkpoint delta.
+    ///
+    /// The update is all or nothing: if one of the source deltas is not compatible with
+    /// the current checkpoint, an error is returned and the checkpoint remains unchanged.
+    pub fn try_apply_index_delta(
+        &mut self,
+        index_delta: IndexCheckpointDelta,
+    ) -> Result<(), IncompatibleCheckpointDelta> {
+        let mut per_source = self.per_source.clone();
+        for (source_id, delta) in index_delta.per_source {
+            per_source
+                .entry(source_id)
+                .or_default()
+                .try_apply_delta(delta)?;
+        }
+        self.per_source = per_source;
+        Ok(())
+    }
+
+    /// Overwrites the positions of the partitions covered by `positions`, regardless of their
+    /// current positions. The other partitions are left untouched.
+    ///
+    /// Applying the [`IndexCheckpointDelta::end_positions`] of a compatible delta this way is
+    /// equivalent to applying the delta itself.
+    pub fn overlay(&mut self, positions: IndexCheckpoint) {
+        for (source_id, source_positions) in positions.per_source {
+            self.per_source
+                .entry(source_id)
+                .or_default()
+                .per_partition
+                .extend(source_positions.per_partition);
+        }
+    }
+
+    /// Removes some partitions from the checkpoint of a source, typically partitions that were
+    /// retired and will never receive new records.
+    /// Returns successfully regardless of whether the source or the partitions were present.
+    pub fn remove_partitions(&mut self, source_id: &str, partition_ids: &[PartitionId]) {
+        if let Some(source_checkpoint) = self.per_source.get_mut(source_id) {
+            source_checkpoint.remove_partitions(partition_ids);
+        }
+    }
 }
 
 /// A source checkpoint is a map of the last processed position for every partition.
@@ -220,6 +269,14 @@ impl SourceCheckpoint {
     pub fn is_empty(&self) -> bool {
         self.per_partition.is_empty()
     }
+
+    /// Removes some partitions from the checkpoint. If such a partition shows up again later,
+    /// it is processed from the beginning.
+    pub fn remove_partitions(&mut self, partition_ids: &[PartitionId]) {
+        for partition_id in partition_ids {
+            self.per_partition.remove(partition_id);
+        }
+    }
 }
 
 /// Creates a checkpoint from an iterator of `(PartitionId, Position)` tuples.
@@ -493,6 +550,114 @@ impl CheckpointDelta {
     pub fn is_empty(&self) -> bool {
         self.per_partition.is_empty()
     }
+
+    /// Returns the part of the checkpoint delta that comes after a checkpoint. The partitions
+    /// already covered by the checkpoint are dropped, and the partitions it partially covers
+    /// start from the checkpoint position.
+    pub fn trim(&self, checkpoint: &SourceCheckpoint) -> CheckpointDelta {
+        let per_partition = self
+            .per_partition
+            .iter()
+            .filter_map(|(partition_id, partition_delta)| {
+                let position_opt = checkpoint.position_for_partition(partition_id);
+                let partition_delta = match position_opt {
+                    Some(position) if *position >= partition_delta.to => return None,
+                    Some(position) if *position > partition_delta.from => PartitionDelta {
+                        from: position.clone(),
+                        to: partition_delta.to.clone(),
+                    },
+                    _ => partition_delta.clone(),
+                };
+                Some((partition_id.clone(), partition_delta))
+            })
+            .collect();
+        CheckpointDelta { per_partition }
+    }
+}
+
+/// An index checkpoint delta gathers the checkpoint deltas of the different sources
+/// feeding a single split.
+///
+/// It is produced by indexing pipelines running several sources concurrently. Each source
+/// delta is applied to its own source checkpoint, so that the sources progress independently.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct IndexCheckpointDelta {
+    per_source: BTreeMap<String, CheckpointDelta>,
+}
+
+impl IndexCheckpointDelta {
+    /// Creates an index checkpoint delta covering a single source.
+    pub fn for_source(source_id: &str, delta: CheckpointDelta) -> Self {
+        let mut per_source = BTreeMap::new();
+        per_source.insert(source_id.to_string(), delta);
+        IndexCheckpointDelta { per_source }
+    }
+
+    /// Extends the checkpoint delta of a given source in-place.
+    ///
+    /// See [`CheckpointDelta::extend`] for more details.
+    pub fn extend(
+        &mut self,
+        source_id: &str,
+        delta: CheckpointDelta,
+    ) -> Result<(), IncompatibleCheckpointDelta> {
+        self.per_source
+            .entry(source_id.to_string())
+            .or_default()
+            .extend(delta)
+    }
+
+    /// Returns the checkpoint delta associated to a given source.
+    pub fn source_delta(&self, source_id: &str) -> Option<&CheckpointDelta> {
+        self.per_source.get(source_id)
+    }
+
+    /// Returns the number of sources covered by the index checkpoint delta.
+    pub fn num_sources(&self) -> usize {
+        self.per_source.len()
+    }
+
+    /// Returns `true` if the index checkpoint delta does not cover any source.
+    pub fn is_empty(&self) -> bool {
+        self.per_source.is_empty()
+    }
+
+    /// Returns an iterator over the source ids and their checkpoint delta.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &CheckpointDelta)> + '_ {
+        self.per_source
+            .iter()
+            .map(|(source_id, delta)| (source_id.as_str(), delta))
+    }
+
+    /// Returns the positions reached by the delta, covering only the partitions it updates.
+    /// This is a much smaller record than the full checkpoint when the sources have many
+    /// partitions, see [`IndexCheckpoint::overlay`].
+    pub fn end_positions(&self) -> IndexCheckpoint {
+        let per_source = self
+            .per_source
+            .iter()
+            .map(|(source_id, delta)| {
+                let source_checkpoint: SourceCheckpoint = delta
+                    .per_partition
+                    .iter()
+                    .map(|(partition_id, partition_delta)| {
+                        (partition_id.clone(), partition_delta.to.clone())
+                    })
+                    .collect();
+                (source_id.clone(), source_checkpoint)
+            })
+            .collect();
+        IndexCheckpoint { per_source }
+    }
+}
+
+impl IntoIterator for IndexCheckpointDelta {
+    type Item = (String, CheckpointDelta);
+    type IntoIter = std::collections::btree_map::IntoIter<String, CheckpointDelta>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.per_source.into_iter()
+    }
 }
 
 #[cfg(test)]
@@ -678,6 +843,40 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_trim_checkpoint_delta() -> anyhow::Result<()> {
+        let delta = {
+            let mut delta = CheckpointDelta::from_partition_delta(
+                PartitionId::from("a"),
+                Position::from("00123"),
+                Position::from("00128"),
+            );
+            delta.record_partition_delta(
+                PartitionId::from("b"),
+                Position::from("60002"),
+                Position::from("60187"),
+            )?;
+            delta.record_partition_delta(
+                PartitionId::from("c"),
+                Position::from("20001"),
+                Position::from("20008"),
+            )?;
+            delta
+        };
+        let checkpoint: SourceCheckpoint = vec![
+            (PartitionId::from("a"), Position::from("00128")),
+            (PartitionId::from("b"), Position::from("60100")),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            format!("{:?}", delta.trim(&checkpoint)),
+            "∆(b:(60100..60187] c:(20001..20008])"
+        );
+        assert_eq!(delta.trim(&SourceCheckpoint::default()), delta);
+        Ok(())
+    }
+
     #[test]
     fn test_position_u64() {
         let pos = Position::from(4u64);
@@ -706,4 +905,95 @@ mod tests {
             .source_checkpoint("existing_source_with_empty_checkpoint")
             .is_none());
     }
+    #[test]
+    fn test_index_checkpoint_delta() -> anyhow::Result<()> {
+        let mut index_delta = IndexCheckpointDelta::default();
+        assert!(index_delta.is_empty());
+        index_delta.extend("source-a", CheckpointDelta::from(0..2))?;
+        index_delta.extend("source-b", CheckpointDelta::from(0..3))?;
+        index_delta.extend("source-a", CheckpointDelta::from(2..4))?;
+        assert_eq!(index_delta.num_sources(), 2);
+        assert_eq!(
+            index_delta.source_delta("source-a"),
+            Some(&CheckpointDelta::from(0..4))
+        );
+        assert!(index_delta
+            .extend("source-b", CheckpointDelta::from(4..5))
+            .is_err());
+
+        let mut index_checkpoint = IndexCheckpoint::default();
+        index_checkpoint.try_apply_index_delta(index_delta)?;
+        assert_eq!(
+            index_checkpoint
+                .source_checkpoint("source-a")
+                .unwrap()
+                .position_for_partition(&PartitionId::default()),
+            Some(&Position::from(3u64))
+        );
+        assert_eq!(
+            index_checkpoint
+                .source_checkpoint("source-b")
+                .unwrap()
+                .position_for_partition(&PartitionId::default()),
+            Some(&Position::from(2u64))
+        );
+
+        // A delta incompatible with one of the sources leaves all the sources unchanged.
+        let mut index_delta = IndexCheckpointDelta::for_source("source-a", (4..6).into());
+        index_delta.extend("source-b", CheckpointDelta::from(0..1))?;
+        let checkpoint_before = index_checkpoint.clone();
+        assert!(index_checkpoint.try_apply_index_delta(index_delta).is_err());
+        assert_eq!(index_checkpoint, checkpoint_before);
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_checkpoint_overlay_and_remove_partitions() -> anyhow::Result<()> {
+        let mut index_checkpoint = IndexCheckpoint::default();
+        let mut index_delta = IndexCheckpointDelta::default();
+        for partition in ["a", "b", "c"] {
+            index_delta.extend(
+                "source-a",
+                CheckpointDelta::from_partition_delta(
+                    PartitionId::from(partition),
+                    Position::Beginning,
+                    Position::from(10u64),
+                ),
+            )?;
+        }
+        index_checkpoint.try_apply_index_delta(index_delta)?;
+
+        let index_delta = IndexCheckpointDelta::for_source(
+            "source-a",
+            CheckpointDelta::from_partition_delta(
+                PartitionId::from("b"),
+                Position::from(10u64),
+                Position::from(20u64),
+            ),
+        );
+        let end_positions = index_delta.end_positions();
+        assert_eq!(
+            end_positions
+                .source_checkpoint("source-a")
+                .unwrap()
+                .num_partitions(),
+            1
+        );
+        let mut overlaid_checkpoint = index_checkpoint.clone();
+        overlaid_checkpoint.overlay(end_positions);
+        index_checkpoint.try_apply_index_delta(index_delta)?;
+        assert_eq!(overlaid_checkpoint, index_checkpoint);
+
+        index_checkpoint.remove_partitions(
+            "source-a",
+            &[PartitionId::from("a"), PartitionId::from("d")],
+        );
+        index_checkpoint.remove_partitions("source-b", &[PartitionId::from("a")]);
+        let source_checkpoint = index_checkpoint.source_checkpoint("source-a").unwrap();
+        assert_eq!(
+            format!("{:?}", source_checkpoint),
+            "Ckpt(b:00000000000000000020 c:00000000000000000010)"
+        );
+        Ok(())
+    }
 }
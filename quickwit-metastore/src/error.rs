@@ -22,6 +22,7 @@ use std::io;
 use thiserror::Error;
 
 use crate::checkpoint::IncompatibleCheckpointDelta;
+use crate::JobState;
 
 /// Metastore error kinds.
 #[allow(missing_docs)]
@@ -75,6 +76,21 @@ pub enum MetastoreError {
     #[error("Source `{source_id}` does not exist.")]
     SourceDoesNotExist { source_id: String },
 
+    #[error("Lease `{lease_id}` is held by `{holder_id}`.")]
+    LeaseAlreadyHeld { lease_id: String, holder_id: String },
+
+    #[error("Lease `{lease_id}` with fencing token `{fencing_token}` was lost.")]
+    LeaseLost {
+        lease_id: String,
+        fencing_token: u64,
+    },
+
+    #[error("Job `{job_id}` does not exist.")]
+    JobDoesNotExist { job_id: String },
+
+    #[error("Job `{job_id}` is not running: it is {}.", .state.as_str())]
+    JobNotRunning { job_id: String, state: JobState },
+
     #[cfg(feature = "postgres")]
     #[error("Database error: {0:?}.")]
     DbError(diesel::result::Error),
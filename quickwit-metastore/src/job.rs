@@ -0,0 +1,244 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{MetastoreError, MetastoreResult};
+
+/// Period after which the records of the jobs that reached a final state are deleted.
+pub const JOB_RETENTION_PERIOD: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// Long-running operation run by a job.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    /// Exports the documents matching a query to Parquet files.
+    Export,
+    /// Deletes the dangling splits of an index and their files.
+    GarbageCollection,
+    /// Copies the documents of an index into another index.
+    Reindex,
+    /// Merges the splits of an index, optionally restricted to a time range.
+    Merge,
+}
+
+/// State of a job.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// The job is running.
+    Running,
+    /// The job completed.
+    Succeeded,
+    /// The job stopped on an error.
+    Failed,
+    /// The job was cancelled before it completed.
+    Cancelled,
+}
+
+impl JobState {
+    /// Returns the name of the state, as stored in the metastore.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobState::Running => "running",
+            JobState::Succeeded => "succeeded",
+            JobState::Failed => "failed",
+            JobState::Cancelled => "cancelled",
+        }
+    }
+
+    /// Returns true if the job is no longer running. Final states do not change anymore.
+    pub fn is_final(&self) -> bool {
+        *self != JobState::Running
+    }
+}
+
+/// Record of a long-running operation, such as an export or a garbage collection run. Clients
+/// submit jobs and poll their record rather than waiting for the operation to complete on a
+/// single call.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    /// Job ID. Job IDs are ULIDs, so they sort by creation time.
+    pub job_id: String,
+    /// ID of the index the job operates on.
+    pub index_id: String,
+    /// Operation run by the job.
+    pub kind: JobKind,
+    /// State of the job.
+    pub state: JobState,
+    /// Progress of the job, in percent.
+    pub progress: f64,
+    /// ID of the node running the job.
+    pub node_id: String,
+    /// Unix timestamp in seconds at which the job was submitted.
+    pub create_timestamp: i64,
+    /// Unix timestamp in seconds at which the record of the job was last updated.
+    pub update_timestamp: i64,
+    /// Details specific to the kind of job, such as the files written by an export.
+    #[serde(default)]
+    pub details: serde_json::Value,
+    /// Error that stopped the job, if it failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Jobs of a metastore that stores them all in a single map, such as the file-backed metastore.
+pub(crate) type Jobs = BTreeMap<String, Job>;
+
+/// Records a new job, and purges the jobs that reached a final state more than
+/// [`JOB_RETENTION_PERIOD`] ago along the way.
+pub(crate) fn create_job(jobs: &mut Jobs, job: Job, now_timestamp: i64) {
+    let expiration_timestamp = now_timestamp - JOB_RETENTION_PERIOD.as_secs() as i64;
+    jobs.retain(|_, job| !job.state.is_final() || job.update_timestamp > expiration_timestamp);
+    jobs.insert(job.job_id.clone(), job);
+}
+
+/// Replaces the record of a running job.
+pub(crate) fn update_job(jobs: &mut Jobs, job: Job) -> MetastoreResult<()> {
+    match jobs.get_mut(&job.job_id) {
+        Some(current_job) if current_job.state.is_final() => Err(MetastoreError::JobNotRunning {
+            job_id: job.job_id,
+            state: current_job.state,
+        }),
+        Some(current_job) => {
+            *current_job = job;
+            Ok(())
+        }
+        None => Err(MetastoreError::JobDoesNotExist { job_id: job.job_id }),
+    }
+}
+
+/// Returns the jobs, most recent first, optionally restricted to the jobs of an index.
+pub(crate) fn list_jobs(jobs: &Jobs, index_id_opt: Option<&str>) -> Vec<Job> {
+    jobs.values()
+        .rev()
+        .filter(|job| index_id_opt.map_or(true, |index_id| job.index_id == index_id))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(job_id: &str, index_id: &str, state: JobState, update_timestamp: i64) -> Job {
+        Job {
+            job_id: job_id.to_string(),
+            index_id: index_id.to_string(),
+            kind: JobKind::Export,
+            state,
+            progress: 0.0,
+            node_id: "node-1".to_string(),
+            create_timestamp: 0,
+            update_timestamp,
+            details: serde_json::Value::Null,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_job_kind_serialization() {
+        let kinds = [
+            JobKind::Export,
+            JobKind::GarbageCollection,
+            JobKind::Reindex,
+            JobKind::Merge,
+        ];
+        let kind_names: Vec<String> = kinds
+            .iter()
+            .map(|kind| serde_json::to_string(kind).unwrap())
+            .collect();
+        assert_eq!(
+            kind_names,
+            [
+                r#""export""#,
+                r#""garbage_collection""#,
+                r#""reindex""#,
+                r#""merge""#
+            ]
+        );
+    }
+
+    #[test]
+    fn test_create_job_purges_expired_jobs() {
+        let now_timestamp = JOB_RETENTION_PERIOD.as_secs() as i64 + 100;
+        let mut jobs = Jobs::new();
+        create_job(
+            &mut jobs,
+            job("job-1", "index-1", JobState::Succeeded, 50),
+            0,
+        );
+        create_job(&mut jobs, job("job-2", "index-1", JobState::Running, 50), 0);
+        create_job(&mut jobs, job("job-3", "index-1", JobState::Failed, 150), 0);
+        create_job(
+            &mut jobs,
+            job("job-4", "index-2", JobState::Running, now_timestamp),
+            now_timestamp,
+        );
+        let job_ids: Vec<&str> = jobs.keys().map(String::as_str).collect();
+        assert_eq!(job_ids, vec!["job-2", "job-3", "job-4"]);
+    }
+
+    #[test]
+    fn test_update_job() {
+        let mut jobs = Jobs::new();
+        create_job(&mut jobs, job("job-1", "index-1", JobState::Running, 0), 0);
+
+        let mut running_job = job("job-1", "index-1", JobState::Running, 10);
+        running_job.progress = 50.0;
+        update_job(&mut jobs, running_job).unwrap();
+        assert_eq!(jobs["job-1"].progress, 50.0);
+
+        update_job(&mut jobs, job("job-1", "index-1", JobState::Cancelled, 20)).unwrap();
+        assert!(matches!(
+            update_job(&mut jobs, job("job-1", "index-1", JobState::Succeeded, 30)).unwrap_err(),
+            MetastoreError::JobNotRunning {
+                state: JobState::Cancelled,
+                ..
+            }
+        ));
+        assert!(matches!(
+            update_job(&mut jobs, job("job-2", "index-1", JobState::Running, 30)).unwrap_err(),
+            MetastoreError::JobDoesNotExist { .. }
+        ));
+    }
+
+    #[test]
+    fn test_list_jobs() {
+        let mut jobs = Jobs::new();
+        create_job(&mut jobs, job("job-1", "index-1", JobState::Running, 0), 0);
+        create_job(&mut jobs, job("job-2", "index-2", JobState::Running, 0), 0);
+        create_job(&mut jobs, job("job-3", "index-1", JobState::Running, 0), 0);
+        let job_ids =
+            |jobs: Vec<Job>| -> Vec<String> { jobs.into_iter().map(|job| job.job_id).collect() };
+        assert_eq!(
+            job_ids(list_jobs(&jobs, None)),
+            vec!["job-3", "job-2", "job-1"]
+        );
+        assert_eq!(
+            job_ids(list_jobs(&jobs, Some("index-1"))),
+            vec!["job-3", "job-1"]
+        );
+        assert!(list_jobs(&jobs, Some("index-3")).is_empty());
+    }
+}
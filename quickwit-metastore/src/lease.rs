@@ -0,0 +1,330 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{MetastoreError, MetastoreResult};
+
+/// Lease granting its holder the exclusive right to run a task that must run on a single node of
+/// the cluster, such as the janitor.
+///
+/// A lease expires unless its holder renews it in time. Its fencing token is incremented every
+/// time the lease is acquired anew, so a holder that was paused past the expiration of its lease
+/// can detect, with [`crate::Metastore::check_lease`], that another holder took over.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Lease {
+    /// Lease ID, usually named after the task it protects.
+    pub lease_id: String,
+    /// ID of the holder of the lease, usually a node ID.
+    pub holder_id: String,
+    /// Token incremented every time the lease changes hands or lapses.
+    pub fencing_token: u64,
+    /// Unix timestamp in seconds at which the lease expires unless renewed.
+    pub expiration_timestamp: i64,
+}
+
+impl Lease {
+    /// Returns true if the lease has expired at `now_timestamp`.
+    pub fn is_expired(&self, now_timestamp: i64) -> bool {
+        self.expiration_timestamp <= now_timestamp
+    }
+
+    /// Returns the fence conditioning the mutations of the holder on the lease.
+    pub fn fence(&self) -> LeaseFence {
+        LeaseFence {
+            lease_id: self.lease_id.clone(),
+            fencing_token: self.fencing_token,
+        }
+    }
+}
+
+/// Lease and fencing token a mutation of the metastore is conditioned on. The metastore checks
+/// the lease atomically with the mutation, so a holder that lost its lease cannot apply it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LeaseFence {
+    /// ID of the lease.
+    pub lease_id: String,
+    /// Fencing token the lease must still bear.
+    pub fencing_token: u64,
+}
+
+/// Leases of a metastore that stores them all in a single map, such as the file-backed
+/// metastore.
+pub(crate) type Leases = BTreeMap<String, Lease>;
+
+pub(crate) fn acquire_lease(
+    leases: &mut Leases,
+    lease_id: &str,
+    holder_id: &str,
+    ttl: Duration,
+    now_timestamp: i64,
+) -> MetastoreResult<Lease> {
+    let expiration_timestamp = now_timestamp + ttl.as_secs() as i64;
+    let fencing_token = match leases.get(lease_id) {
+        Some(lease) if lease.is_expired(now_timestamp) => lease.fencing_token + 1,
+        Some(lease) if lease.holder_id == holder_id => lease.fencing_token,
+        Some(lease) => {
+            return Err(MetastoreError::LeaseAlreadyHeld {
+                lease_id: lease_id.to_string(),
+                holder_id: lease.holder_id.clone(),
+            })
+        }
+        None => 1,
+    };
+    let lease = Lease {
+        lease_id: lease_id.to_string(),
+        holder_id: holder_id.to_string(),
+        fencing_token,
+        expiration_timestamp,
+    };
+    leases.insert(lease_id.to_string(), lease.clone());
+    Ok(lease)
+}
+
+/// Expires the lease if `holder_id` holds it. The lease is kept so that its fencing token keeps
+/// increasing. Returns true if the lease was released.
+pub(crate) fn release_lease(
+    leases: &mut Leases,
+    lease_id: &str,
+    holder_id: &str,
+    now_timestamp: i64,
+) -> bool {
+    match leases.get_mut(lease_id) {
+        Some(lease) if lease.holder_id == holder_id && !lease.is_expired(now_timestamp) => {
+            lease.expiration_timestamp = now_timestamp;
+            true
+        }
+        _ => false,
+    }
+}
+
+pub(crate) fn check_lease(
+    leases: &Leases,
+    lease_id: &str,
+    fencing_token: u64,
+    now_timestamp: i64,
+) -> MetastoreResult<()> {
+    match leases.get(lease_id) {
+        Some(lease) if lease.fencing_token == fencing_token && !lease.is_expired(now_timestamp) => {
+            Ok(())
+        }
+        _ => Err(MetastoreError::LeaseLost {
+            lease_id: lease_id.to_string(),
+            fencing_token,
+        }),
+    }
+}
+
+/// Read lease taken by a search on the splits it reads, so that the garbage collector does not
+/// delete them before the search completes. Unlike [`Lease`], read leases are shared: any number
+/// of holders can lease the same split.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SplitReadLease {
+    /// IDs of the leased splits.
+    pub split_ids: BTreeSet<String>,
+    /// Unix timestamp in seconds at which the lease expires unless renewed.
+    pub expiration_timestamp: i64,
+}
+
+/// Split read leases of a metastore that stores them all in a single map, keyed by index ID, then
+/// by holder ID.
+pub(crate) type SplitReadLeases = BTreeMap<String, BTreeMap<String, SplitReadLease>>;
+
+/// Acquires or renews the read lease of `holder_id` on `split_ids`, and purges the expired read
+/// leases along the way.
+pub(crate) fn acquire_split_read_leases(
+    split_read_leases: &mut SplitReadLeases,
+    index_id: &str,
+    split_ids: &[&str],
+    holder_id: &str,
+    ttl: Duration,
+    now_timestamp: i64,
+) {
+    for index_leases in split_read_leases.values_mut() {
+        index_leases.retain(|_, lease| lease.expiration_timestamp > now_timestamp);
+    }
+    split_read_leases.retain(|_, index_leases| !index_leases.is_empty());
+
+    let lease = split_read_leases
+        .entry(index_id.to_string())
+        .or_default()
+        .entry(holder_id.to_string())
+        .or_default();
+    lease
+        .split_ids
+        .extend(split_ids.iter().map(|split_id| split_id.to_string()));
+    lease.expiration_timestamp = now_timestamp + ttl.as_secs() as i64;
+}
+
+/// Drops the read lease of `holder_id` on the splits of `index_id`. Returns true if the holder held
+/// a read lease.
+pub(crate) fn release_split_read_leases(
+    split_read_leases: &mut SplitReadLeases,
+    index_id: &str,
+    holder_id: &str,
+) -> bool {
+    let index_leases = match split_read_leases.get_mut(index_id) {
+        Some(index_leases) => index_leases,
+        None => return false,
+    };
+    let has_changed = index_leases.remove(holder_id).is_some();
+    if index_leases.is_empty() {
+        split_read_leases.remove(index_id);
+    }
+    has_changed
+}
+
+/// Returns the IDs of the splits of `index_id` under at least one read lease that has not expired.
+pub(crate) fn leased_split_ids(
+    split_read_leases: &SplitReadLeases,
+    index_id: &str,
+    now_timestamp: i64,
+) -> HashSet<String> {
+    split_read_leases
+        .get(index_id)
+        .into_iter()
+        .flat_map(|index_leases| index_leases.values())
+        .filter(|lease| lease.expiration_timestamp > now_timestamp)
+        .flat_map(|lease| lease.split_ids.iter().cloned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn test_lease_acquire_renew_and_take_over() {
+        let mut leases = Leases::new();
+        let lease = acquire_lease(&mut leases, "janitor", "node-1", TTL, 100).unwrap();
+        assert_eq!(lease.fencing_token, 1);
+        assert_eq!(lease.expiration_timestamp, 110);
+
+        // Renewing keeps the fencing token.
+        let lease = acquire_lease(&mut leases, "janitor", "node-1", TTL, 105).unwrap();
+        assert_eq!(lease.fencing_token, 1);
+        assert_eq!(lease.expiration_timestamp, 115);
+
+        assert!(matches!(
+            acquire_lease(&mut leases, "janitor", "node-2", TTL, 110).unwrap_err(),
+            MetastoreError::LeaseAlreadyHeld { holder_id, .. } if holder_id == "node-1"
+        ));
+        check_lease(&leases, "janitor", 1, 110).unwrap();
+
+        // Once expired, another holder takes over with a greater fencing token.
+        let lease = acquire_lease(&mut leases, "janitor", "node-2", TTL, 115).unwrap();
+        assert_eq!(lease.holder_id, "node-2");
+        assert_eq!(lease.fencing_token, 2);
+        assert!(matches!(
+            check_lease(&leases, "janitor", 1, 116).unwrap_err(),
+            MetastoreError::LeaseLost {
+                fencing_token: 1,
+                ..
+            }
+        ));
+        check_lease(&leases, "janitor", 2, 116).unwrap();
+        assert!(check_lease(&leases, "janitor", 2, 125).is_err());
+        assert!(check_lease(&leases, "lease-does-not-exist", 1, 116).is_err());
+    }
+
+    #[test]
+    fn test_lease_release() {
+        let mut leases = Leases::new();
+        acquire_lease(&mut leases, "janitor", "node-1", TTL, 100).unwrap();
+        assert!(!release_lease(&mut leases, "janitor", "node-2", 101));
+        assert!(release_lease(&mut leases, "janitor", "node-1", 101));
+        assert!(!release_lease(&mut leases, "janitor", "node-1", 101));
+        assert!(check_lease(&leases, "janitor", 1, 101).is_err());
+
+        // The fencing token keeps increasing after a release.
+        let lease = acquire_lease(&mut leases, "janitor", "node-2", TTL, 101).unwrap();
+        assert_eq!(lease.fencing_token, 2);
+    }
+
+    #[test]
+    fn test_split_read_leases() {
+        let mut split_read_leases = SplitReadLeases::new();
+        acquire_split_read_leases(
+            &mut split_read_leases,
+            "index-1",
+            &["split-1", "split-2"],
+            "search-1",
+            TTL,
+            100,
+        );
+        acquire_split_read_leases(
+            &mut split_read_leases,
+            "index-1",
+            &["split-2", "split-3"],
+            "search-2",
+            TTL,
+            105,
+        );
+        let mut split_ids: Vec<String> = leased_split_ids(&split_read_leases, "index-1", 106)
+            .into_iter()
+            .collect();
+        split_ids.sort();
+        assert_eq!(split_ids, ["split-1", "split-2", "split-3"]);
+        assert!(leased_split_ids(&split_read_leases, "index-2", 106).is_empty());
+
+        // The lease of the first search expires, unlike the renewed lease of the second one.
+        acquire_split_read_leases(
+            &mut split_read_leases,
+            "index-1",
+            &["split-2", "split-3"],
+            "search-2",
+            TTL,
+            109,
+        );
+        let mut split_ids: Vec<String> = leased_split_ids(&split_read_leases, "index-1", 112)
+            .into_iter()
+            .collect();
+        split_ids.sort();
+        assert_eq!(split_ids, ["split-2", "split-3"]);
+
+        // Acquiring a lease purges the expired ones.
+        acquire_split_read_leases(
+            &mut split_read_leases,
+            "index-2",
+            &["split-4"],
+            "search-3",
+            TTL,
+            112,
+        );
+        assert!(!split_read_leases["index-1"].contains_key("search-1"));
+
+        assert!(!release_split_read_leases(
+            &mut split_read_leases,
+            "index-1",
+            "search-3"
+        ));
+        assert!(release_split_read_leases(
+            &mut split_read_leases,
+            "index-1",
+            "search-2"
+        ));
+        assert!(leased_split_ids(&split_read_leases, "index-1", 112).is_empty());
+        assert!(!split_read_leases.contains_key("index-1"));
+    }
+}
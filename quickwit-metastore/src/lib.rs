@@ -28,6 +28,7 @@
 #[macro_use]
 mod tests;
 mod split_metadata;
+mod split_metadata_codec;
 mod split_metadata_version;
 
 #[cfg(feature = "postgres")]
@@ -44,6 +45,8 @@ extern crate diesel;
 #[allow(missing_docs)]
 pub mod checkpoint;
 mod error;
+mod job;
+mod lease;
 mod metastore;
 mod metastore_resolver;
 
@@ -52,16 +55,22 @@ mod metastore_resolver;
 pub mod postgresql;
 
 pub use error::{MetastoreError, MetastoreResolverError, MetastoreResult};
+pub use job::{Job, JobKind, JobState, JOB_RETENTION_PERIOD};
+pub use lease::{Lease, LeaseFence};
 pub use metastore::file_backed_metastore::FileBackedMetastore;
 #[cfg(feature = "postgres")]
 pub use metastore::postgresql_metastore::PostgresqlMetastore;
 #[cfg(feature = "testsuite")]
 pub use metastore::MockMetastore;
-pub use metastore::{file_backed_metastore, IndexMetadata, Metastore};
+pub use metastore::{
+    alias_index_metadatas, file_backed_metastore, write_index_metadata, IndexLifecycleState,
+    IndexMetadata, LifecyclePhase, Metastore, SplitPageStream,
+};
 pub use metastore_resolver::{
     quickwit_metastore_uri_resolver, MetastoreFactory, MetastoreUriResolver,
 };
 pub use split_metadata::{Split, SplitMetadata, SplitState};
+pub use split_metadata_codec::{decode_split_metadata, encode_split_metadata};
 pub(crate) use split_metadata_version::VersionedSplitMetadataDeserializeHelper;
 
 #[cfg(test)]
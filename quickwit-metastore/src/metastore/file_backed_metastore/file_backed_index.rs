@@ -26,12 +26,15 @@ use std::ops::{Range, RangeInclusive};
 
 use chrono::Utc;
 use itertools::Itertools;
-use quickwit_config::SourceConfig;
+use quickwit_config::{IndexingSettings, SourceConfig};
 use quickwit_doc_mapper::tag_pruning::TagFilterAst;
 use serde::{Deserialize, Serialize};
 
-use crate::checkpoint::CheckpointDelta;
-use crate::{IndexMetadata, MetastoreError, MetastoreResult, Split, SplitMetadata, SplitState};
+use crate::checkpoint::{CheckpointDelta, IndexCheckpointDelta, PartitionId, SourceCheckpoint};
+use crate::{
+    IndexLifecycleState, IndexMetadata, MetastoreError, MetastoreResult, Split, SplitMetadata,
+    SplitState,
+};
 
 /// A `FileBackedIndex` object carries an index metadata and its split metadata.
 // This struct is meant to be used only within the [`FileBackedMetastore`]. The public visibility is
@@ -295,13 +298,60 @@ impl FileBackedIndex {
         Ok(())
     }
 
+    /// Publishes splits fed by several sources, applying the checkpoint delta of each source.
+    pub(crate) fn publish_multi_source_splits<'a>(
+        &mut self,
+        split_ids: &[&'a str],
+        index_checkpoint_delta: IndexCheckpointDelta,
+    ) -> MetastoreResult<()> {
+        self.metadata
+            .checkpoint
+            .try_apply_index_delta(index_checkpoint_delta)?;
+        self.mark_splits_as_published_helper(split_ids)?;
+        Ok(())
+    }
+
     pub(crate) fn list_splits(
         &self,
         state: SplitState,
         time_range_opt: Option<Range<i64>>,
         tags_filter: Option<TagFilterAst>,
     ) -> MetastoreResult<Vec<Split>> {
-        let time_range_filter = |split: &&Split| match (
+        let splits = self
+            .matching_splits(state, time_range_opt, tags_filter)
+            .cloned()
+            .collect();
+        Ok(splits)
+    }
+
+    pub(crate) fn list_splits_page(
+        &self,
+        state: SplitState,
+        time_range_opt: Option<Range<i64>>,
+        tags_filter: Option<TagFilterAst>,
+        after_split_id_opt: Option<String>,
+        page_size: usize,
+    ) -> MetastoreResult<Vec<Split>> {
+        let mut splits: Vec<&Split> = self
+            .matching_splits(state, time_range_opt, tags_filter)
+            .filter(|split| match after_split_id_opt.as_deref() {
+                Some(after_split_id) => split.split_id() > after_split_id,
+                None => true,
+            })
+            .collect();
+        splits.sort_unstable_by(|left, right| left.split_id().cmp(right.split_id()));
+        let splits = splits.into_iter().take(page_size).cloned().collect();
+        Ok(splits)
+    }
+
+    /// Returns the splits in the given state matching the time range and tags filters.
+    fn matching_splits(
+        &self,
+        state: SplitState,
+        time_range_opt: Option<Range<i64>>,
+        tags_filter: Option<TagFilterAst>,
+    ) -> impl Iterator<Item = &Split> {
+        let time_range_filter = move |split: &&Split| match (
             time_range_opt.as_ref(),
             split.split_metadata.time_range.as_ref(),
         ) {
@@ -311,23 +361,18 @@ impl FileBackedIndex {
             _ => true, // Return `true` if `time_range` is omitted or the split has no time range.
         };
 
-        let tag_filter = |split: &&Split| {
+        let tag_filter = move |split: &&Split| {
             tags_filter
                 .as_ref()
                 .map(|tags_filter_ast| tags_filter_ast.evaluate(&split.split_metadata.tags))
                 .unwrap_or(true)
         };
 
-        let splits = self
-            .splits
+        self.splits
             .values()
-            .filter(|&split| split.split_state == state)
+            .filter(move |&split| split.split_state == state)
             .filter(time_range_filter)
             .filter(tag_filter)
-            .cloned()
-            .collect();
-
-        Ok(splits)
     }
 
     pub(crate) fn list_all_splits(&self) -> MetastoreResult<Vec<Split>> {
@@ -393,4 +438,40 @@ impl FileBackedIndex {
         self.metadata.delete_source(source_id)?;
         Ok(true)
     }
+
+    pub(crate) fn reset_source_checkpoint(
+        &mut self,
+        source_id: &str,
+        checkpoint: SourceCheckpoint,
+    ) -> MetastoreResult<bool> {
+        self.metadata
+            .reset_source_checkpoint(source_id, checkpoint)?;
+        Ok(true)
+    }
+
+    pub(crate) fn prune_source_checkpoint(
+        &mut self,
+        source_id: &str,
+        partition_ids: &[PartitionId],
+    ) -> MetastoreResult<bool> {
+        self.metadata
+            .prune_source_checkpoint(source_id, partition_ids)?;
+        Ok(true)
+    }
+
+    pub(crate) fn update_indexing_settings(
+        &mut self,
+        indexing_settings: IndexingSettings,
+    ) -> MetastoreResult<bool> {
+        self.metadata.update_indexing_settings(indexing_settings);
+        Ok(true)
+    }
+
+    pub(crate) fn update_lifecycle_state(
+        &mut self,
+        lifecycle_state: IndexLifecycleState,
+    ) -> MetastoreResult<bool> {
+        self.metadata.update_lifecycle_state(lifecycle_state);
+        Ok(true)
+    }
 }
@@ -25,13 +25,14 @@ pub mod file_backed_index;
 mod file_backed_metastore_factory;
 mod store_operations;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ops::Range;
 use std::sync::{Arc, Weak};
 use std::time::Duration;
 
 use async_trait::async_trait;
-use quickwit_config::SourceConfig;
+use chrono::Utc;
+use quickwit_config::{IndexingSettings, SourceConfig};
 use quickwit_doc_mapper::tag_pruning::TagFilterAst;
 use quickwit_storage::Storage;
 use tokio::sync::{Mutex, OwnedMutexGuard, RwLock};
@@ -39,18 +40,34 @@ use tracing::error;
 
 use self::file_backed_index::FileBackedIndex;
 pub use self::file_backed_metastore_factory::FileBackedMetastoreFactory;
-use self::store_operations::{delete_index, fetch_index, index_exists, put_index};
-use crate::checkpoint::CheckpointDelta;
+use self::store_operations::{
+    delete_index, fetch_index, fetch_jobs, fetch_leases, fetch_split_read_leases, index_exists,
+    list_index_ids, put_index, put_jobs, put_leases, put_split_read_leases,
+};
+use crate::checkpoint::{CheckpointDelta, IndexCheckpointDelta, PartitionId, SourceCheckpoint};
+use crate::job::{self, Jobs};
+use crate::lease::{self, Leases, SplitReadLeases};
 use crate::{
-    IndexMetadata, Metastore, MetastoreError, MetastoreResult, Split, SplitMetadata, SplitState,
+    IndexLifecycleState, IndexMetadata, Job, Lease, LeaseFence, Metastore, MetastoreError,
+    MetastoreResult, Split, SplitMetadata, SplitState,
 };
 
 /// Metastore that simply stores all of the metadata associated to each index
 /// into as many files.
+///
+/// The leases are stored in a single file, the split read leases in another, and the jobs in a
+/// third one. The storage cannot update a file conditionally, so the leases only exclude one
+/// another among the holders sharing the same metastore instance.
 pub struct FileBackedMetastore {
     storage: Arc<dyn Storage>,
     per_index_metastores: RwLock<HashMap<String, Arc<Mutex<FileBackedIndex>>>>,
     polling_interval_opt: Option<Duration>,
+    /// Serializes the read-modify-write cycles of the leases file.
+    leases_lock: Mutex<()>,
+    /// Serializes the read-modify-write cycles of the split read leases file.
+    split_read_leases_lock: Mutex<()>,
+    /// Serializes the read-modify-write cycles of the jobs file.
+    jobs_lock: Mutex<()>,
 }
 
 async fn poll_metastore_once(
@@ -111,6 +128,9 @@ impl FileBackedMetastore {
             storage,
             per_index_metastores: Default::default(),
             polling_interval_opt: None,
+            leases_lock: Mutex::new(()),
+            split_read_leases_lock: Mutex::new(()),
+            jobs_lock: Mutex::new(()),
         }
     }
 
@@ -223,6 +243,77 @@ impl FileBackedMetastore {
         Ok(index_mutex)
     }
 
+    /// Applies a mutation to the leases, and writes them back if the mutation reports a change.
+    async fn mutate_leases<T>(
+        &self,
+        mutation: impl FnOnce(&mut Leases) -> MetastoreResult<(T, bool)>,
+    ) -> MetastoreResult<T> {
+        let _leases_guard = self.leases_lock.lock().await;
+        let mut leases = fetch_leases(&*self.storage).await?;
+        let (output, has_changed) = mutation(&mut leases)?;
+        if has_changed {
+            put_leases(&*self.storage, &leases).await?;
+        }
+        Ok(output)
+    }
+
+    /// Applies a mutation to an index provided that the lease of `fence` still bears its fencing
+    /// token. The leases lock is held during the mutation, so that the lease cannot change hands
+    /// in between.
+    async fn mutate_fenced(
+        &self,
+        index_id: &str,
+        fence: &LeaseFence,
+        mutation: impl FnOnce(&mut FileBackedIndex) -> crate::MetastoreResult<bool>,
+    ) -> MetastoreResult<()> {
+        let _leases_guard = self.leases_lock.lock().await;
+        let leases = fetch_leases(&*self.storage).await?;
+        lease::check_lease(
+            &leases,
+            &fence.lease_id,
+            fence.fencing_token,
+            Utc::now().timestamp(),
+        )?;
+        self.mutate(index_id, mutation).await
+    }
+
+    /// Applies a mutation to the split read leases, and writes them back if the mutation reports a
+    /// change.
+    async fn mutate_split_read_leases(
+        &self,
+        mutation: impl FnOnce(&mut SplitReadLeases) -> bool,
+    ) -> MetastoreResult<()> {
+        let _split_read_leases_guard = self.split_read_leases_lock.lock().await;
+        let mut split_read_leases = fetch_split_read_leases(&*self.storage).await?;
+        if mutation(&mut split_read_leases) {
+            put_split_read_leases(&*self.storage, &split_read_leases).await?;
+        }
+        Ok(())
+    }
+
+    /// Applies a mutation to the jobs, and writes them back if it succeeds.
+    async fn mutate_jobs(
+        &self,
+        mutation: impl FnOnce(&mut Jobs) -> MetastoreResult<()>,
+    ) -> MetastoreResult<()> {
+        let _jobs_guard = self.jobs_lock.lock().await;
+        let mut jobs = fetch_jobs(&*self.storage).await?;
+        mutation(&mut jobs)?;
+        put_jobs(&*self.storage, &jobs).await
+    }
+
+    async fn rollback_index_creation(&self, index: &FileBackedIndex) {
+        if let Err(error) = delete_index(&*self.storage, index.index_id()).await {
+            error!(index_id = index.index_id(), error = ?error, "Failed to roll back the creation of an index.");
+        }
+    }
+
+    async fn rollback_index_deletion(&self, index: &FileBackedIndex) {
+        if let Err(error) = put_index(&*self.storage, index).await {
+            error!(index_id = index.index_id(), error = ?error, "Failed to roll back the deletion of an index.");
+        }
+    }
+
     // Helper used for testing to obtain the data associated with the given index.
     #[cfg(test)]
     async fn get_index(&self, index_id: &str) -> MetastoreResult<FileBackedIndex> {
@@ -284,6 +375,80 @@ impl Metastore for FileBackedMetastore {
         delete_res
     }
 
+    async fn create_indexes(&self, index_metadatas: Vec<IndexMetadata>) -> MetastoreResult<()> {
+        // We pick the outer lock here, so that we enter a critical section.
+        let mut per_index_metastores_wlock = self.per_index_metastores.write().await;
+
+        // All the indexes are checked before creating any of them.
+        let mut index_ids = HashSet::new();
+        for index_metadata in &index_metadatas {
+            let index_id = &index_metadata.index_id;
+            let exists = !index_ids.insert(index_id.as_str())
+                || per_index_metastores_wlock.contains_key(index_id)
+                || index_exists(&*self.storage, index_id).await?;
+            if exists {
+                return Err(MetastoreError::IndexAlreadyExists {
+                    index_id: index_id.clone(),
+                });
+            }
+        }
+
+        let mut created_indexes = Vec::with_capacity(index_metadatas.len());
+        for index_metadata in index_metadatas {
+            let index = FileBackedIndex::from(index_metadata);
+            if let Err(put_error) = put_index(&*self.storage, &index).await {
+                // We cannot know for sure whether the failed index was written, so it is rolled
+                // back along with the indexes created before it.
+                let _ = delete_index(&*self.storage, index.index_id()).await;
+                for created_index in &created_indexes {
+                    self.rollback_index_creation(created_index).await;
+                }
+                return Err(put_error);
+            }
+            created_indexes.push(index);
+        }
+
+        for index in created_indexes {
+            let index_id = index.index_id().to_string();
+            per_index_metastores_wlock.insert(index_id, Arc::new(Mutex::new(index)));
+        }
+        Ok(())
+    }
+
+    async fn delete_indexes<'a>(&self, index_ids: &[&'a str]) -> MetastoreResult<()> {
+        // We pick the outer lock here, so that we enter a critical section.
+        let mut per_index_metastores_wlock = self.per_index_metastores.write().await;
+
+        // All the indexes are fetched before deleting any of them, so that the deleted indexes
+        // can be restored if a deletion fails.
+        let mut unique_index_ids = HashSet::new();
+        let mut indexes = Vec::with_capacity(index_ids.len());
+        for &index_id in index_ids {
+            if !unique_index_ids.insert(index_id) {
+                return Err(MetastoreError::IndexDoesNotExist {
+                    index_id: index_id.to_string(),
+                });
+            }
+            indexes.push(fetch_index(&*self.storage, index_id).await?);
+        }
+
+        let mut deleted_indexes = Vec::with_capacity(indexes.len());
+        for index in indexes {
+            if let Err(delete_error) = delete_index(&*self.storage, index.index_id()).await {
+                for deleted_index in &deleted_indexes {
+                    self.rollback_index_deletion(deleted_index).await;
+                }
+                return Err(delete_error);
+            }
+            deleted_indexes.push(index);
+        }
+
+        for index in &deleted_indexes {
+            per_index_metastores_wlock.remove(index.index_id());
+        }
+        Ok(())
+    }
+
     /// -------------------------------------------------------------------------------
     /// Mutations over a single index
 
@@ -313,6 +478,19 @@ impl Metastore for FileBackedMetastore {
         .await
     }
 
+    async fn publish_multi_source_splits<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+        index_checkpoint_delta: IndexCheckpointDelta,
+    ) -> MetastoreResult<()> {
+        self.mutate(index_id, |index| {
+            index.publish_multi_source_splits(split_ids, index_checkpoint_delta)?;
+            Ok(true)
+        })
+        .await
+    }
+
     async fn replace_splits<'a>(
         &self,
         index_id: &str,
@@ -347,6 +525,31 @@ impl Metastore for FileBackedMetastore {
         .await
     }
 
+    async fn mark_splits_for_deletion_fenced<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+        fence: &LeaseFence,
+    ) -> MetastoreResult<()> {
+        self.mutate_fenced(index_id, fence, |index| {
+            index.mark_splits_for_deletion(split_ids)
+        })
+        .await
+    }
+
+    async fn delete_splits_fenced<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+        fence: &LeaseFence,
+    ) -> MetastoreResult<()> {
+        self.mutate_fenced(index_id, fence, |index| {
+            index.delete_splits(split_ids)?;
+            Ok(true)
+        })
+        .await
+    }
+
     async fn add_source(&self, index_id: &str, source: SourceConfig) -> MetastoreResult<()> {
         self.mutate(index_id, |index| index.add_source(source))
             .await
@@ -357,6 +560,153 @@ impl Metastore for FileBackedMetastore {
             .await
     }
 
+    async fn reset_source_checkpoint(
+        &self,
+        index_id: &str,
+        source_id: &str,
+        checkpoint: SourceCheckpoint,
+    ) -> MetastoreResult<()> {
+        self.mutate(index_id, |index| {
+            index.reset_source_checkpoint(source_id, checkpoint)
+        })
+        .await
+    }
+
+    async fn prune_source_checkpoint(
+        &self,
+        index_id: &str,
+        source_id: &str,
+        partition_ids: &[PartitionId],
+    ) -> MetastoreResult<()> {
+        self.mutate(index_id, |index| {
+            index.prune_source_checkpoint(source_id, partition_ids)
+        })
+        .await
+    }
+
+    async fn update_indexing_settings(
+        &self,
+        index_id: &str,
+        indexing_settings: IndexingSettings,
+    ) -> MetastoreResult<()> {
+        self.mutate(index_id, |index| {
+            index.update_indexing_settings(indexing_settings)
+        })
+        .await
+    }
+
+    async fn update_lifecycle_state(
+        &self,
+        index_id: &str,
+        lifecycle_state: IndexLifecycleState,
+    ) -> MetastoreResult<()> {
+        self.mutate(index_id, |index| {
+            index.update_lifecycle_state(lifecycle_state)
+        })
+        .await
+    }
+
+    /// -------------------------------------------------------------------------------
+    /// Leases
+
+    async fn acquire_lease(
+        &self,
+        lease_id: &str,
+        holder_id: &str,
+        ttl: Duration,
+    ) -> MetastoreResult<Lease> {
+        self.mutate_leases(|leases| {
+            let lease =
+                lease::acquire_lease(leases, lease_id, holder_id, ttl, Utc::now().timestamp())?;
+            Ok((lease, true))
+        })
+        .await
+    }
+
+    async fn release_lease(&self, lease_id: &str, holder_id: &str) -> MetastoreResult<()> {
+        self.mutate_leases(|leases| {
+            let has_changed =
+                lease::release_lease(leases, lease_id, holder_id, Utc::now().timestamp());
+            Ok(((), has_changed))
+        })
+        .await
+    }
+
+    async fn check_lease(&self, lease_id: &str, fencing_token: u64) -> MetastoreResult<()> {
+        let leases = fetch_leases(&*self.storage).await?;
+        lease::check_lease(&leases, lease_id, fencing_token, Utc::now().timestamp())
+    }
+
+    async fn acquire_split_read_leases<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+        holder_id: &str,
+        ttl: Duration,
+    ) -> MetastoreResult<()> {
+        self.mutate_split_read_leases(|split_read_leases| {
+            lease::acquire_split_read_leases(
+                split_read_leases,
+                index_id,
+                split_ids,
+                holder_id,
+                ttl,
+                Utc::now().timestamp(),
+            );
+            true
+        })
+        .await
+    }
+
+    async fn release_split_read_leases(
+        &self,
+        index_id: &str,
+        holder_id: &str,
+    ) -> MetastoreResult<()> {
+        self.mutate_split_read_leases(|split_read_leases| {
+            lease::release_split_read_leases(split_read_leases, index_id, holder_id)
+        })
+        .await
+    }
+
+    async fn list_leased_splits(&self, index_id: &str) -> MetastoreResult<HashSet<String>> {
+        let split_read_leases = fetch_split_read_leases(&*self.storage).await?;
+        Ok(lease::leased_split_ids(
+            &split_read_leases,
+            index_id,
+            Utc::now().timestamp(),
+        ))
+    }
+
+    /// -------------------------------------------------------------------------------
+    /// Jobs
+
+    async fn create_job(&self, job: Job) -> MetastoreResult<()> {
+        self.mutate_jobs(|jobs| {
+            job::create_job(jobs, job, Utc::now().timestamp());
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_job(&self, job: Job) -> MetastoreResult<()> {
+        self.mutate_jobs(|jobs| job::update_job(jobs, job)).await
+    }
+
+    async fn job(&self, job_id: &str) -> MetastoreResult<Job> {
+        let jobs = fetch_jobs(&*self.storage).await?;
+        jobs.get(job_id)
+            .cloned()
+            .ok_or_else(|| MetastoreError::JobDoesNotExist {
+                job_id: job_id.to_string(),
+            })
+    }
+
+    async fn list_jobs<'a>(&self, index_id_opt: Option<&'a str>) -> MetastoreResult<Vec<Job>> {
+        let jobs = fetch_jobs(&*self.storage).await?;
+        Ok(job::list_jobs(&jobs, index_id_opt))
+    }
+
     /// -------------------------------------------------------------------------------
     /// Read-only accessors
 
@@ -373,6 +723,21 @@ impl Metastore for FileBackedMetastore {
         .await
     }
 
+    async fn list_splits_page(
+        &self,
+        index_id: &str,
+        state: SplitState,
+        time_range_opt: Option<Range<i64>>,
+        tags: Option<TagFilterAst>,
+        after_split_id_opt: Option<String>,
+        page_size: usize,
+    ) -> MetastoreResult<Vec<Split>> {
+        self.read(index_id, |index| {
+            index.list_splits_page(state, time_range_opt, tags, after_split_id_opt, page_size)
+        })
+        .await
+    }
+
     async fn list_all_splits(&self, index_id: &str) -> MetastoreResult<Vec<Split>> {
         self.read(index_id, |index| index.list_all_splits()).await
     }
@@ -382,6 +747,20 @@ impl Metastore for FileBackedMetastore {
             .await
     }
 
+    async fn list_indexes_metadatas(&self) -> MetastoreResult<Vec<IndexMetadata>> {
+        let index_ids = list_index_ids(&*self.storage).await?;
+        let mut index_metadatas = Vec::with_capacity(index_ids.len());
+        for index_id in index_ids {
+            match self.index_metadata(&index_id).await {
+                Ok(index_metadata) => index_metadatas.push(index_metadata),
+                // The index was deleted since it was listed.
+                Err(MetastoreError::IndexDoesNotExist { .. }) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(index_metadatas)
+    }
+
     fn uri(&self) -> String {
         self.storage.uri()
     }
@@ -21,12 +21,24 @@ use std::path::{Path, PathBuf};
 
 use quickwit_storage::{Storage, StorageError, StorageErrorKind};
 
+use crate::job::Jobs;
+use crate::lease::{Leases, SplitReadLeases};
 use crate::metastore::file_backed_metastore::file_backed_index::FileBackedIndex;
 use crate::{MetastoreError, MetastoreResult};
 
 /// Metadata file managed by [`FileBackedMetastore`].
 const META_FILENAME: &str = "metastore.json";
 
+/// File holding the leases of the metastore, at the root of the metastore storage. The leading
+/// underscore keeps it apart from the index directories.
+const LEASES_FILENAME: &str = "_leases.json";
+
+/// File holding the split read leases of the metastore, next to the leases file.
+const SPLIT_READ_LEASES_FILENAME: &str = "_split_read_leases.json";
+
+/// File holding the jobs of the metastore, next to the leases file.
+const JOBS_FILENAME: &str = "_jobs.json";
+
 /// Path to the metadata file from the given index ID.
 pub(crate) fn meta_path(index_id: &str) -> PathBuf {
     Path::new(index_id).join(META_FILENAME)
@@ -82,6 +94,30 @@ pub(crate) async fn index_exists(storage: &dyn Storage, index_id: &str) -> Metas
     Ok(exists)
 }
 
+/// Lists the IDs of the indexes whose metadata file is on the storage, sorted.
+pub(crate) async fn list_index_ids(storage: &dyn Storage) -> MetastoreResult<Vec<String>> {
+    let file_paths = storage
+        .list_files(Path::new(""))
+        .await
+        .map_err(|storage_err| MetastoreError::InternalError {
+            message: "Failed to list index files.".to_string(),
+            cause: anyhow::anyhow!(storage_err),
+        })?;
+    let index_ids = file_paths
+        .iter()
+        .filter(|file_path| file_path.file_name() == Some(META_FILENAME.as_ref()))
+        .filter_map(|file_path| {
+            let index_path = file_path.parent()?;
+            // Skips the metadata files nested deeper, e.g. in split directories.
+            if index_path.parent() != Some(Path::new("")) {
+                return None;
+            }
+            index_path.to_str().map(str::to_string)
+        })
+        .collect();
+    Ok(index_ids)
+}
+
 /// Serializes the `Index` object and stores the data on the storage.
 ///
 /// Do not call this method. Instead, call `put_index`.
@@ -148,3 +184,117 @@ pub(crate) async fn delete_index(storage: &dyn Storage, index_id: &str) -> Metas
 
     Ok(())
 }
+
+/// Fetches the leases of the metastore. A missing leases file holds no lease.
+pub(crate) async fn fetch_leases(storage: &dyn Storage) -> MetastoreResult<Leases> {
+    let content = match storage.get_all(Path::new(LEASES_FILENAME)).await {
+        Ok(content) => content,
+        Err(storage_err) if storage_err.kind() == StorageErrorKind::DoesNotExist => {
+            return Ok(Leases::new())
+        }
+        Err(storage_err) => {
+            return Err(MetastoreError::InternalError {
+                message: "Failed to get leases file.".to_string(),
+                cause: anyhow::anyhow!(storage_err),
+            })
+        }
+    };
+    serde_json::from_slice(&content[..]).map_err(|serde_err| MetastoreError::InternalError {
+        message: "Failed to deserialize leases.".to_string(),
+        cause: anyhow::anyhow!(serde_err),
+    })
+}
+
+/// Serializes the leases of the metastore and stores them on the storage.
+pub(crate) async fn put_leases(storage: &dyn Storage, leases: &Leases) -> MetastoreResult<()> {
+    let content: Vec<u8> =
+        serde_json::to_vec_pretty(leases).map_err(|serde_err| MetastoreError::InternalError {
+            message: "Failed to serialize leases.".to_string(),
+            cause: anyhow::anyhow!(serde_err),
+        })?;
+    storage
+        .put(Path::new(LEASES_FILENAME), Box::new(content))
+        .await
+        .map_err(|storage_err| MetastoreError::InternalError {
+            message: "Failed to write leases file.".to_string(),
+            cause: anyhow::anyhow!(storage_err),
+        })
+}
+
+/// Fetches the split read leases of the metastore. A missing file holds no lease.
+pub(crate) async fn fetch_split_read_leases(
+    storage: &dyn Storage,
+) -> MetastoreResult<SplitReadLeases> {
+    let content = match storage.get_all(Path::new(SPLIT_READ_LEASES_FILENAME)).await {
+        Ok(content) => content,
+        Err(storage_err) if storage_err.kind() == StorageErrorKind::DoesNotExist => {
+            return Ok(SplitReadLeases::new())
+        }
+        Err(storage_err) => {
+            return Err(MetastoreError::InternalError {
+                message: "Failed to get split read leases file.".to_string(),
+                cause: anyhow::anyhow!(storage_err),
+            })
+        }
+    };
+    serde_json::from_slice(&content[..]).map_err(|serde_err| MetastoreError::InternalError {
+        message: "Failed to deserialize split read leases.".to_string(),
+        cause: anyhow::anyhow!(serde_err),
+    })
+}
+
+/// Serializes the split read leases of the metastore and stores them on the storage.
+pub(crate) async fn put_split_read_leases(
+    storage: &dyn Storage,
+    split_read_leases: &SplitReadLeases,
+) -> MetastoreResult<()> {
+    let content: Vec<u8> = serde_json::to_vec_pretty(split_read_leases).map_err(|serde_err| {
+        MetastoreError::InternalError {
+            message: "Failed to serialize split read leases.".to_string(),
+            cause: anyhow::anyhow!(serde_err),
+        }
+    })?;
+    storage
+        .put(Path::new(SPLIT_READ_LEASES_FILENAME), Box::new(content))
+        .await
+        .map_err(|storage_err| MetastoreError::InternalError {
+            message: "Failed to write split read leases file.".to_string(),
+            cause: anyhow::anyhow!(storage_err),
+        })
+}
+
+/// Fetches the jobs of the metastore. A missing file holds no job.
+pub(crate) async fn fetch_jobs(storage: &dyn Storage) -> MetastoreResult<Jobs> {
+    let content = match storage.get_all(Path::new(JOBS_FILENAME)).await {
+        Ok(content) => content,
+        Err(storage_err) if storage_err.kind() == StorageErrorKind::DoesNotExist => {
+            return Ok(Jobs::new())
+        }
+        Err(storage_err) => {
+            return Err(MetastoreError::InternalError {
+                message: "Failed to get jobs file.".to_string(),
+                cause: anyhow::anyhow!(storage_err),
+            })
+        }
+    };
+    serde_json::from_slice(&content[..]).map_err(|serde_err| MetastoreError::InternalError {
+        message: "Failed to deserialize jobs.".to_string(),
+        cause: anyhow::anyhow!(serde_err),
+    })
+}
+
+/// Serializes the jobs of the metastore and stores them on the storage.
+pub(crate) async fn put_jobs(storage: &dyn Storage, jobs: &Jobs) -> MetastoreResult<()> {
+    let content: Vec<u8> =
+        serde_json::to_vec_pretty(jobs).map_err(|serde_err| MetastoreError::InternalError {
+            message: "Failed to serialize jobs.".to_string(),
+            cause: anyhow::anyhow!(serde_err),
+        })?;
+    storage
+        .put(Path::new(JOBS_FILENAME), Box::new(content))
+        .await
+        .map_err(|storage_err| MetastoreError::InternalError {
+            message: "Failed to write jobs file.".to_string(),
+            cause: anyhow::anyhow!(storage_err),
+        })
+}
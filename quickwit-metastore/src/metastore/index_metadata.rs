@@ -30,10 +30,62 @@ use quickwit_doc_mapper::{
 };
 use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::checkpoint::{IndexCheckpoint, SourceCheckpoint};
+use crate::checkpoint::{IndexCheckpoint, PartitionId, SourceCheckpoint};
 use crate::split_metadata::utc_now_timestamp;
 use crate::{MetastoreError, MetastoreResult};
 
+/// Phase of an index whose lifecycle is managed by the janitor, see
+/// [`quickwit_config::LifecycleSettings`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LifecyclePhase {
+    /// The index is indexed, merged, and searched.
+    Hot,
+    /// The index was rolled over: it is merged and searched but not indexed anymore.
+    ReadOnly,
+    /// The index is only searched.
+    Frozen,
+}
+
+impl Default for LifecyclePhase {
+    fn default() -> Self {
+        LifecyclePhase::Hot
+    }
+}
+
+/// Lifecycle state of an index, updated by the janitor.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexLifecycleState {
+    pub phase: LifecyclePhase,
+    /// Generation of the index among the indexes of its write alias, starting at 1 for the
+    /// index created by the user.
+    #[serde(default = "IndexLifecycleState::default_generation")]
+    pub generation: u64,
+    /// Time at which the index was rolled over, if it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollover_timestamp: Option<i64>,
+}
+
+impl IndexLifecycleState {
+    fn default_generation() -> u64 {
+        1
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Default for IndexLifecycleState {
+    fn default() -> Self {
+        Self {
+            phase: LifecyclePhase::default(),
+            generation: Self::default_generation(),
+            rollover_timestamp: None,
+        }
+    }
+}
+
 /// An index metadata carries all meta data about an index.
 #[derive(Clone, Debug, Serialize)]
 #[serde(into = "VersionedIndexMetadata")]
@@ -58,6 +110,8 @@ pub struct IndexMetadata {
     pub create_timestamp: i64,
     /// Time at which the index was last updated.
     pub update_timestamp: i64,
+    /// Lifecycle state of the index, meaningful if the index config has lifecycle settings.
+    pub lifecycle_state: IndexLifecycleState,
 }
 
 impl IndexMetadata {
@@ -136,6 +190,7 @@ impl IndexMetadata {
                 "attributes.server".to_string(),
                 "attributes.server.status".to_string(),
             ],
+            preferred_searcher_nodes: Vec::new(),
         };
         let now_timestamp = utc_now_timestamp();
         Self {
@@ -148,9 +203,18 @@ impl IndexMetadata {
             sources: Default::default(),
             create_timestamp: now_timestamp,
             update_timestamp: now_timestamp,
+            lifecycle_state: IndexLifecycleState::default(),
         }
     }
 
+    /// Returns the write alias of the index, if its lifecycle is managed.
+    pub fn write_alias(&self) -> Option<&str> {
+        self.indexing_settings
+            .lifecycle
+            .as_ref()
+            .map(|lifecycle| lifecycle.write_alias.as_str())
+    }
+
     pub(crate) fn add_source(&mut self, source: SourceConfig) -> MetastoreResult<()> {
         let entry = self.sources.entry(source.source_id.clone());
         let source_id = source.source_id.clone();
@@ -175,6 +239,51 @@ impl IndexMetadata {
         Ok(())
     }
 
+    pub(crate) fn reset_source_checkpoint(
+        &mut self,
+        source_id: &str,
+        checkpoint: SourceCheckpoint,
+    ) -> MetastoreResult<()> {
+        // Sources with a checkpoint but no config, such as the ingest source, can be reset too.
+        if !self.sources.contains_key(source_id)
+            && self.checkpoint.source_checkpoint(source_id).is_none()
+        {
+            return Err(MetastoreError::SourceDoesNotExist {
+                source_id: source_id.to_string(),
+            });
+        }
+        self.checkpoint.reset_source(source_id, checkpoint);
+        self.update_timestamp = utc_now_timestamp();
+        Ok(())
+    }
+
+    pub(crate) fn prune_source_checkpoint(
+        &mut self,
+        source_id: &str,
+        partition_ids: &[PartitionId],
+    ) -> MetastoreResult<()> {
+        if !self.sources.contains_key(source_id)
+            && self.checkpoint.source_checkpoint(source_id).is_none()
+        {
+            return Err(MetastoreError::SourceDoesNotExist {
+                source_id: source_id.to_string(),
+            });
+        }
+        self.checkpoint.remove_partitions(source_id, partition_ids);
+        self.update_timestamp = utc_now_timestamp();
+        Ok(())
+    }
+
+    pub(crate) fn update_indexing_settings(&mut self, indexing_settings: IndexingSettings) {
+        self.indexing_settings = indexing_settings;
+        self.update_timestamp = utc_now_timestamp();
+    }
+
+    pub(crate) fn update_lifecycle_state(&mut self, lifecycle_state: IndexLifecycleState) {
+        self.lifecycle_state = lifecycle_state;
+        self.update_timestamp = utc_now_timestamp();
+    }
+
     /// Builds and returns the doc mapper associated with index.
     pub fn build_doc_mapper(&self) -> anyhow::Result<Arc<dyn DocMapper>> {
         let mut builder = DefaultDocMapperBuilder::new();
@@ -192,6 +301,31 @@ impl IndexMetadata {
     }
 }
 
+/// Returns the indexes of the write alias `write_alias` among `index_metadatas`, ordered by
+/// generation.
+pub fn alias_index_metadatas<'a>(
+    index_metadatas: &'a [IndexMetadata],
+    write_alias: &str,
+) -> Vec<&'a IndexMetadata> {
+    index_metadatas
+        .iter()
+        .filter(|index_metadata| index_metadata.write_alias() == Some(write_alias))
+        .sorted_by_key(|index_metadata| index_metadata.lifecycle_state.generation)
+        .collect()
+}
+
+/// Returns the index written through the write alias `write_alias`: the hot index of the alias
+/// with the highest generation.
+pub fn write_index_metadata<'a>(
+    index_metadatas: &'a [IndexMetadata],
+    write_alias: &str,
+) -> Option<&'a IndexMetadata> {
+    alias_index_metadatas(index_metadatas, write_alias)
+        .into_iter()
+        .filter(|index_metadata| index_metadata.lifecycle_state.phase == LifecyclePhase::Hot)
+        .last()
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub(crate) struct UnversionedIndexMetadata {
     pub index_id: String,
@@ -244,6 +378,9 @@ pub(crate) struct IndexMetadataV1 {
     pub create_timestamp: i64,
     #[serde(default = "utc_now_timestamp")]
     pub update_timestamp: i64,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "IndexLifecycleState::is_default")]
+    pub lifecycle_state: IndexLifecycleState,
 }
 
 impl From<IndexMetadata> for IndexMetadataV1 {
@@ -263,6 +400,7 @@ impl From<IndexMetadata> for IndexMetadataV1 {
             sources,
             create_timestamp: index_metadata.create_timestamp,
             update_timestamp: index_metadata.update_timestamp,
+            lifecycle_state: index_metadata.lifecycle_state,
         }
     }
 }
@@ -284,6 +422,7 @@ impl From<IndexMetadataV1> for IndexMetadata {
             sources,
             create_timestamp: v1.create_timestamp,
             update_timestamp: v1.update_timestamp,
+            lifecycle_state: v1.lifecycle_state,
         }
     }
 }
@@ -338,6 +477,7 @@ impl From<IndexMetadataV0> for IndexMetadata {
             sources,
             create_timestamp: v0.create_timestamp,
             update_timestamp: v0.update_timestamp,
+            lifecycle_state: IndexLifecycleState::default(),
         }
     }
 }
@@ -366,6 +506,7 @@ impl From<UnversionedIndexMetadata> for IndexMetadataV0 {
         };
         let search_settings = SearchSettings {
             default_search_fields: unversioned.doc_mapper.default_search_field_names,
+            preferred_searcher_nodes: Vec::new(),
         };
         let now_timestamp = utc_now_timestamp();
         Self {
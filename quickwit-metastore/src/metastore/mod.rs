@@ -22,15 +22,26 @@ mod index_metadata;
 #[cfg(feature = "postgres")]
 pub mod postgresql_metastore;
 
+use std::collections::HashSet;
 use std::ops::Range;
+use std::time::Duration;
 
 use async_trait::async_trait;
-pub use index_metadata::IndexMetadata;
-use quickwit_config::SourceConfig;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+pub use index_metadata::{
+    alias_index_metadatas, write_index_metadata, IndexLifecycleState, IndexMetadata, LifecyclePhase,
+};
+use quickwit_config::{IndexingSettings, SourceConfig};
 use quickwit_doc_mapper::tag_pruning::TagFilterAst;
 
-use crate::checkpoint::CheckpointDelta;
-use crate::{MetastoreResult, Split, SplitMetadata, SplitState};
+use crate::checkpoint::{CheckpointDelta, IndexCheckpointDelta, PartitionId, SourceCheckpoint};
+use crate::{
+    Job, Lease, LeaseFence, MetastoreError, MetastoreResult, Split, SplitMetadata, SplitState,
+};
+
+/// Stream of pages of splits returned by [`Metastore::list_splits_stream`].
+pub type SplitPageStream = BoxStream<'static, MetastoreResult<Vec<Split>>>;
 
 /// Metastore meant to manage Quickwit's indexes and their splits.
 ///
@@ -81,12 +92,28 @@ pub trait Metastore: Send + Sync + 'static {
     /// TODO consider merging with list_splits to remove one round-trip
     async fn index_metadata(&self, index_id: &str) -> MetastoreResult<IndexMetadata>;
 
+    /// Returns the metadata of all the indexes, sorted by index ID.
+    async fn list_indexes_metadatas(&self) -> MetastoreResult<Vec<IndexMetadata>>;
+
     /// Deletes an index.
     /// This API removes the specified  from the metastore,
     /// but does not remove the index from the storage.
     /// An error will occur if an index that does not exist in the storage is specified.
     async fn delete_index(&self, index_id: &str) -> MetastoreResult<()>;
 
+    /// Creates several indexes, all or nothing: if one of the indexes cannot be created, none
+    /// of them is.
+    /// An error will occur if one of the indexes already exists or if an index is specified
+    /// twice.
+    async fn create_indexes(&self, index_metadatas: Vec<IndexMetadata>) -> MetastoreResult<()>;
+
+    /// Deletes several indexes, all or nothing: if one of the indexes cannot be deleted, none
+    /// of them is.
+    /// Like [`Metastore::delete_index`], this does not remove the indexes from the storage.
+    /// An error will occur if one of the indexes does not exist or if an index is specified
+    /// twice.
+    async fn delete_indexes<'a>(&self, index_ids: &[&'a str]) -> MetastoreResult<()>;
+
     /// Stages a split.
     /// A split needs to be staged before uploading any of its files to the storage.
     /// An error will occur if an index that does not exist in the storage is specified.
@@ -110,6 +137,17 @@ pub trait Metastore: Send + Sync + 'static {
         checkpoint_delta: CheckpointDelta,
     ) -> MetastoreResult<()>;
 
+    /// Publishes a list of splits fed by several sources of the same index.
+    /// Like [`Metastore::publish_splits`], but the checkpoint delta of every source covered by
+    /// `index_checkpoint_delta` is applied to its own source checkpoint. The checkpoint updates
+    /// and the publication are all or nothing.
+    async fn publish_multi_source_splits<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+        index_checkpoint_delta: IndexCheckpointDelta,
+    ) -> MetastoreResult<()>;
+
     /// Replaces a list of splits with another list.
     /// This API is useful during merge and demux operations.
     /// The new splits should be staged, and the replaced splits should exist.
@@ -132,10 +170,77 @@ pub trait Metastore: Send + Sync + 'static {
         tags: Option<TagFilterAst>,
     ) -> MetastoreResult<Vec<Split>>;
 
+    /// Lists one page of the splits returned by [`Metastore::list_splits`]: at most `page_size`
+    /// splits whose ID is greater than `after_split_id_opt`, if any, in the split ID order.
+    /// Passing the ID of the last split of a page as `after_split_id_opt` lists the next page.
+    /// The state, time range, and tags filters are evaluated by the metastore. The default
+    /// implementation pages through the splits returned by [`Metastore::list_splits`].
+    async fn list_splits_page(
+        &self,
+        index_id: &str,
+        split_state: SplitState,
+        time_range: Option<Range<i64>>,
+        tags: Option<TagFilterAst>,
+        after_split_id_opt: Option<String>,
+        page_size: usize,
+    ) -> MetastoreResult<Vec<Split>> {
+        let mut splits: Vec<Split> = self
+            .list_splits(index_id, split_state, time_range, tags)
+            .await?
+            .into_iter()
+            .filter(|split| match after_split_id_opt.as_deref() {
+                Some(after_split_id) => split.split_id() > after_split_id,
+                None => true,
+            })
+            .collect();
+        splits.sort_unstable_by(|left, right| left.split_id().cmp(right.split_id()));
+        splits.truncate(page_size);
+        Ok(splits)
+    }
+
+    /// Lists the splits like [`Metastore::list_splits`], as a stream of pages of at most
+    /// `page_size` splits.
+    /// Metastores that can read their splits incrementally fetch one page at a time, which
+    /// bounds the memory used to list the splits of indexes made of many splits. The default
+    /// implementation lists all the splits at once.
+    async fn list_splits_stream(
+        &self,
+        index_id: &str,
+        split_state: SplitState,
+        time_range: Option<Range<i64>>,
+        tags: Option<TagFilterAst>,
+        page_size: usize,
+    ) -> MetastoreResult<SplitPageStream> {
+        let splits = self
+            .list_splits(index_id, split_state, time_range, tags)
+            .await?;
+        let pages: Vec<Vec<Split>> = splits
+            .chunks(page_size.max(1))
+            .map(<[Split]>::to_vec)
+            .collect();
+        Ok(futures::stream::iter(pages.into_iter().map(Ok)).boxed())
+    }
+
     /// Lists the splits without filtering.
     /// Returns a list of all splits currently known to the metastore regardless of their state.
     async fn list_all_splits(&self, index_id: &str) -> MetastoreResult<Vec<Split>>;
 
+    /// Lists the splits, whatever their state, whose update timestamp is greater than or equal
+    /// to `update_timestamp`, so that a client holding a copy of the splits can catch up with
+    /// the changes. The default implementation filters the splits returned by
+    /// [`Metastore::list_all_splits`].
+    async fn list_splits_updated_since(
+        &self,
+        index_id: &str,
+        update_timestamp: i64,
+    ) -> MetastoreResult<Vec<Split>> {
+        let splits = self.list_all_splits(index_id).await?;
+        Ok(splits
+            .into_iter()
+            .filter(|split| split.update_timestamp >= update_timestamp)
+            .collect())
+    }
+
     /// Marks a list of splits for deletion.
     /// This API will change the state to `MarkedForDeletion` so that it is not referenced by the
     /// client. It actually does not remove the split from storage.
@@ -154,6 +259,26 @@ pub trait Metastore: Send + Sync + 'static {
     async fn delete_splits<'a>(&self, index_id: &str, split_ids: &[&'a str])
         -> MetastoreResult<()>;
 
+    /// Marks a list of splits for deletion like [`Metastore::mark_splits_for_deletion`], provided
+    /// that the lease of `fence` still bears its fencing token. The lease is checked atomically
+    /// with the mutation: fails with [`MetastoreError::LeaseLost`] and changes nothing otherwise.
+    async fn mark_splits_for_deletion_fenced<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+        fence: &LeaseFence,
+    ) -> MetastoreResult<()>;
+
+    /// Deletes a list of splits like [`Metastore::delete_splits`], provided that the lease of
+    /// `fence` still bears its fencing token. The lease is checked atomically with the mutation:
+    /// fails with [`MetastoreError::LeaseLost`] and changes nothing otherwise.
+    async fn delete_splits_fenced<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+        fence: &LeaseFence,
+    ) -> MetastoreResult<()>;
+
     /// Adds a new source. Fails with [`MetastoreError::SourceAlreadyExists`] if a source with the
     /// same ID is already defined for the index.
     ///
@@ -167,6 +292,136 @@ pub trait Metastore: Send + Sync + 'static {
     /// If the checkpoint is missing, this does not trigger an error.
     async fn delete_source(&self, index_id: &str, source_id: &str) -> MetastoreResult<()>;
 
+    /// Returns the checkpoint of a source. Fails with [`MetastoreError::SourceDoesNotExist`] if
+    /// the index has no checkpoint for the source.
+    async fn source_checkpoint(
+        &self,
+        index_id: &str,
+        source_id: &str,
+    ) -> MetastoreResult<SourceCheckpoint> {
+        let index_metadata = self.index_metadata(index_id).await?;
+        index_metadata
+            .checkpoint
+            .source_checkpoint(source_id)
+            .cloned()
+            .ok_or_else(|| MetastoreError::SourceDoesNotExist {
+                source_id: source_id.to_string(),
+            })
+    }
+
+    /// Replaces the checkpoint of a source, so that its indexing resumes from the given
+    /// positions, possibly reprocessing documents. Fails with
+    /// [`MetastoreError::SourceDoesNotExist`] if the source does not exist.
+    ///
+    /// The pipelines indexing the source must be stopped first: a pipeline publishing splits
+    /// from its former positions would overwrite the reset checkpoint.
+    async fn reset_source_checkpoint(
+        &self,
+        index_id: &str,
+        source_id: &str,
+        checkpoint: SourceCheckpoint,
+    ) -> MetastoreResult<()>;
+
+    /// Removes some partitions from the checkpoint of a source. This keeps the checkpoints of
+    /// sources whose partitions come and go, such as the shards of a Kinesis stream or the files
+    /// of a directory, from growing forever. Fails with [`MetastoreError::SourceDoesNotExist`]
+    /// if the source does not exist.
+    ///
+    /// Only partitions that were retired should be pruned: a pruned partition that receives new
+    /// records is processed again from the beginning.
+    async fn prune_source_checkpoint(
+        &self,
+        index_id: &str,
+        source_id: &str,
+        partition_ids: &[PartitionId],
+    ) -> MetastoreResult<()>;
+
+    /// Replaces the indexing settings of an index. The indexing pipelines of the index pick up
+    /// the new settings when they are restarted.
+    async fn update_indexing_settings(
+        &self,
+        index_id: &str,
+        indexing_settings: IndexingSettings,
+    ) -> MetastoreResult<()>;
+
+    /// Replaces the lifecycle state of an index. Called by the janitor when the index is rolled
+    /// over or changes phase.
+    async fn update_lifecycle_state(
+        &self,
+        index_id: &str,
+        lifecycle_state: IndexLifecycleState,
+    ) -> MetastoreResult<()>;
+
+    /// Acquires the lease `lease_id` for `holder_id` for a period of `ttl`, or renews it if
+    /// `holder_id` already holds it. Fails with [`MetastoreError::LeaseAlreadyHeld`] if another
+    /// holder holds the lease and it has not expired.
+    ///
+    /// The fencing token of the lease is incremented every time the lease is acquired anew,
+    /// including by the same holder after the lease expired, but not when it is renewed.
+    async fn acquire_lease(
+        &self,
+        lease_id: &str,
+        holder_id: &str,
+        ttl: Duration,
+    ) -> MetastoreResult<Lease>;
+
+    /// Releases the lease `lease_id` so that other holders can acquire it without waiting for
+    /// it to expire. Does nothing if `holder_id` does not hold the lease.
+    async fn release_lease(&self, lease_id: &str, holder_id: &str) -> MetastoreResult<()>;
+
+    /// Checks that the lease `lease_id` has not expired and still bears the fencing token
+    /// `fencing_token`. Fails with [`MetastoreError::LeaseLost`] otherwise.
+    ///
+    /// The holder of a lease checks it before each of its side effects, so that a holder paused
+    /// past the expiration of its lease does not act concurrently with the next holder. Checking
+    /// the lease before a mutation of the metastore leaves a window for the lease to change hands
+    /// in between: such mutations are fenced instead, see
+    /// [`Metastore::mark_splits_for_deletion_fenced`].
+    async fn check_lease(&self, lease_id: &str, fencing_token: u64) -> MetastoreResult<()>;
+
+    /// Acquires read leases on the splits `split_ids` of the index `index_id` for `holder_id` for
+    /// a period of `ttl`, or renews them if `holder_id` already holds them. Read leases are shared,
+    /// so any number of holders can lease the same split.
+    ///
+    /// Searches lease the splits they read, and the garbage collector does not delete the leased
+    /// splits, see [`Metastore::list_leased_splits`].
+    async fn acquire_split_read_leases<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+        holder_id: &str,
+        ttl: Duration,
+    ) -> MetastoreResult<()>;
+
+    /// Releases the read leases of `holder_id` on the splits of the index `index_id`. Does nothing
+    /// if `holder_id` holds none.
+    async fn release_split_read_leases(
+        &self,
+        index_id: &str,
+        holder_id: &str,
+    ) -> MetastoreResult<()>;
+
+    /// Returns the IDs of the splits of the index `index_id` under at least one read lease that
+    /// has not expired.
+    async fn list_leased_splits(&self, index_id: &str) -> MetastoreResult<HashSet<String>>;
+
+    /// Records a new job. The records of the jobs that reached a final state more than
+    /// [`crate::JOB_RETENTION_PERIOD`] ago are deleted along the way.
+    async fn create_job(&self, job: Job) -> MetastoreResult<()>;
+
+    /// Replaces the record of a running job, for instance to report its progress or its
+    /// completion. Fails with [`MetastoreError::JobDoesNotExist`] if the job does not exist, and
+    /// with [`MetastoreError::JobNotRunning`] if it already reached a final state, e.g. because it
+    /// was cancelled.
+    async fn update_job(&self, job: Job) -> MetastoreResult<()>;
+
+    /// Returns the record of the job `job_id`. Fails with [`MetastoreError::JobDoesNotExist`] if
+    /// the job does not exist.
+    async fn job(&self, job_id: &str) -> MetastoreResult<Job>;
+
+    /// Returns the jobs, most recent first, optionally restricted to the jobs of an index.
+    async fn list_jobs<'a>(&self, index_id_opt: Option<&'a str>) -> MetastoreResult<Vec<Job>>;
+
     /// Returns the Metastore uri.
     fn uri(&self) -> String;
 }
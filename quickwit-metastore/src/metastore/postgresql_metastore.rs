@@ -23,27 +23,37 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
 use diesel::dsl::sql;
 use diesel::pg::Pg;
 use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
 use diesel::result::DatabaseErrorKind;
 use diesel::result::Error::DatabaseError;
-use diesel::sql_types::{Array, Bool, Text};
+use diesel::sql_types::{Array, BigInt, Bool, Text};
 use diesel::{
     debug_query, sql_query, BoolExpressionMethods, BoxableExpression, Connection,
     ExpressionMethods, IntoSql, PgConnection, QueryDsl, RunQueryDsl,
 };
-use quickwit_config::SourceConfig;
+use futures::{StreamExt, TryStreamExt};
+use quickwit_config::{IndexingSettings, SourceConfig};
 use quickwit_doc_mapper::tag_pruning::TagFilterAst;
 use tracing::{debug, error, info, warn};
 
-use crate::metastore::CheckpointDelta;
-use crate::postgresql::model::SELECT_SPLITS_FOR_INDEX;
+use crate::checkpoint::{IndexCheckpointDelta, PartitionId, SourceCheckpoint};
+use crate::metastore::{CheckpointDelta, SplitPageStream};
+use crate::postgresql::model::{
+    ACQUIRE_LEASE, ACQUIRE_SPLIT_READ_LEASES, CHECK_LEASE, DELETE_CHECKPOINT_DELTAS,
+    DELETE_EXPIRED_JOBS, DELETE_EXPIRED_SPLIT_READ_LEASES, INSERT_CHECKPOINT_DELTA, INSERT_JOB,
+    LOCK_LEASE, RELEASE_LEASE, RELEASE_SPLIT_READ_LEASES, SELECT_CHECKPOINT_DELTAS,
+    SELECT_INDEX_JOBS, SELECT_JOB, SELECT_JOBS, SELECT_LEASE, SELECT_LEASED_SPLITS,
+    SELECT_SPLITS_FOR_INDEX, TOUCH_INDEX, UPDATE_RUNNING_JOB,
+};
 use crate::postgresql::schema::splits;
 use crate::postgresql::{model, schema};
 use crate::{
-    IndexMetadata, Metastore, MetastoreError, MetastoreFactory, MetastoreResolverError,
-    MetastoreResult, Split, SplitMetadata, SplitState,
+    encode_split_metadata, IndexLifecycleState, IndexMetadata, Job, Lease, LeaseFence, Metastore,
+    MetastoreError, MetastoreFactory, MetastoreResolverError, MetastoreResult, Split,
+    SplitMetadata, SplitState, JOB_RETENTION_PERIOD,
 };
 
 embed_migrations!("migrations/postgresql");
@@ -53,6 +63,10 @@ const CONNECTION_POOL_TIMEOUT: Duration = Duration::from_secs(10);
 const CONNECTION_POOL_MAX_RETRY_COUNT: u32 = 10;
 const CONNECTION_STATUS_CHECK_MAX_RETRY_COUNT: u32 = 3;
 const CONNECTION_STATUS_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+/// Number of splits fetched per query when listing splits.
+const LIST_SPLITS_PAGE_SIZE: usize = 1_000;
+/// Number of checkpoint deltas an index accumulates before they are folded into its metadata.
+const CHECKPOINT_DELTAS_COMPACTION_THRESHOLD: usize = 100;
 
 /// Establishes a connection to the given database URI.
 fn establish_connection(
@@ -109,6 +123,21 @@ fn initialize_db(pool: &Pool<ConnectionManager<PgConnection>>) -> anyhow::Result
     }
 }
 
+/// Page of splits fetched by a split listing query.
+struct SplitPage {
+    /// The page starts right after this split ID, in the split ID order.
+    after_split_id_opt: Option<String>,
+    page_size: usize,
+}
+
+/// Returns the cursor of the page following `page`, or `None` if `page` is the last one.
+fn next_page_cursor(page: &[Split], page_size: usize) -> Option<String> {
+    if page.len() < page_size {
+        return None;
+    }
+    page.last().map(|split| split.split_id().to_string())
+}
+
 /// PostgreSQL metastore implementation.
 #[derive(Clone)]
 pub struct PostgresqlMetastore {
@@ -204,6 +233,18 @@ impl PostgresqlMetastore {
         conn: &PooledConnection<ConnectionManager<PgConnection>>,
         index_id: &str,
     ) -> MetastoreResult<IndexMetadata> {
+        let (index_metadata, _num_checkpoint_deltas) =
+            self.index_metadata_with_checkpoint_deltas(conn, index_id)?;
+        Ok(index_metadata)
+    }
+
+    /// Returns the metadata of an index with its pending checkpoint deltas applied, along with
+    /// the number of these deltas.
+    fn index_metadata_with_checkpoint_deltas(
+        &self,
+        conn: &PooledConnection<ConnectionManager<PgConnection>>,
+        index_id: &str,
+    ) -> MetastoreResult<(IndexMetadata, usize)> {
         let statement = schema::indexes::dsl::indexes.find(index_id);
         debug!(index_id = %index_id, query = %debug_query::<Pg, _>(&statement).to_string(), "Get index.");
 
@@ -215,8 +256,36 @@ impl PostgresqlMetastore {
                 },
                 _ => MetastoreError::DbError(err),
             })?;
-        let index_metadata = index.index_metadata()?;
-        Ok(index_metadata)
+        let mut index_metadata = index.index_metadata()?;
+        let checkpoint_delta_rows: Vec<model::CheckpointDeltaRow> =
+            sql_query(SELECT_CHECKPOINT_DELTAS)
+                .bind::<Text, _>(index_id)
+                .get_results(conn)?;
+        for checkpoint_delta_row in &checkpoint_delta_rows {
+            index_metadata
+                .checkpoint
+                .overlay(checkpoint_delta_row.end_positions()?);
+        }
+        Ok((index_metadata, checkpoint_delta_rows.len()))
+    }
+
+    /// Bumps the update timestamp of an index, which locks its row until the end of the
+    /// transaction. Must be called within a transaction, before reading the metadata of the index
+    /// to update it, so that concurrent updates do not overwrite each other.
+    fn touch_index(
+        &self,
+        conn: &PooledConnection<ConnectionManager<PgConnection>>,
+        index_id: &str,
+    ) -> MetastoreResult<()> {
+        let num_updated_rows = sql_query(TOUCH_INDEX)
+            .bind::<Text, _>(index_id)
+            .execute(conn)?;
+        if num_updated_rows == 0 {
+            return Err(MetastoreError::IndexDoesNotExist {
+                index_id: index_id.to_string(),
+            });
+        }
+        Ok(())
     }
 
     fn update_index(
@@ -236,6 +305,71 @@ impl PostgresqlMetastore {
         debug!(index_id = %index_id, query = %debug_query::<Pg, _>(&statement).to_string(), "Update index.");
         let num_updated_rows = statement.execute(&*conn).map_err(MetastoreError::DbError)?;
         assert_eq!(num_updated_rows, 1);
+        // The checkpoint of the metadata includes the pending checkpoint deltas of the index.
+        sql_query(DELETE_CHECKPOINT_DELTAS)
+            .bind::<Text, _>(&index_id)
+            .execute(conn)?;
+        Ok(())
+    }
+
+    /// Creates an index. Must be called within a transaction.
+    fn create_index_helper(
+        &self,
+        conn: &PooledConnection<ConnectionManager<PgConnection>>,
+        index_metadata: &IndexMetadata,
+    ) -> MetastoreResult<()> {
+        // Serialize the index metadata to fit the database model.
+        let index_metadata_json =
+            serde_json::to_string(index_metadata).map_err(|err| MetastoreError::InternalError {
+                message: "Failed to serialize index metadata.".to_string(),
+                cause: anyhow::anyhow!(err),
+            })?;
+        let create_index_statement = diesel::insert_into(schema::indexes::dsl::indexes).values((
+            schema::indexes::dsl::index_id.eq(index_metadata.index_id.clone()),
+            schema::indexes::dsl::index_metadata_json.eq(index_metadata_json),
+        ));
+        debug!(sql=%debug_query::<Pg, _>(&create_index_statement).to_string());
+        create_index_statement
+            .execute(conn)
+            .map_err(|err| match err {
+                DatabaseError(db_err_kind, ref err_info) => match db_err_kind {
+                    DatabaseErrorKind::UniqueViolation => {
+                        error!(index_id=?index_metadata.index_id, "Index already exists");
+                        MetastoreError::IndexAlreadyExists {
+                            index_id: index_metadata.index_id.clone(),
+                        }
+                    }
+                    _ => {
+                        error!(index_id=?index_metadata.index_id, "An error has occurred in the database operation. {:?}", err_info.message());
+                        MetastoreError::DbError(err)
+                    }
+                },
+                _ => {
+                    error!(index_id=?index_metadata.index_id, "An error has occurred in the database operation. {:?}", err);
+                    MetastoreError::DbError(err)
+                }
+            })?;
+        debug!(index_id=?index_metadata.index_id, "The index has been created");
+        Ok(())
+    }
+
+    /// Deletes an index. Must be called within a transaction.
+    fn delete_index_helper(
+        &self,
+        conn: &PooledConnection<ConnectionManager<PgConnection>>,
+        index_id: &str,
+    ) -> MetastoreResult<()> {
+        let delete_index_statement = diesel::delete(schema::indexes::dsl::indexes.find(index_id));
+        debug!(sql=%debug_query::<Pg, _>(&delete_index_statement).to_string());
+        let num_affected_rows = delete_index_statement
+            .execute(conn)
+            .map_err(MetastoreError::DbError)?;
+
+        if num_affected_rows == 0 {
+            return Err(MetastoreError::IndexDoesNotExist {
+                index_id: index_id.to_string(),
+            });
+        }
         Ok(())
     }
 
@@ -289,6 +423,95 @@ impl PostgresqlMetastore {
         Ok(marked_split_ids)
     }
 
+    /// Marks splits for deletion, in a transaction checking the lease of `fence_opt` if any.
+    fn mark_splits_for_deletion_inner(
+        &self,
+        index_id: &str,
+        split_ids: &[&str],
+        fence_opt: Option<&LeaseFence>,
+    ) -> MetastoreResult<()> {
+        let conn = self.get_conn()?;
+        conn.transaction::<_, MetastoreError, _>(|| {
+            if let Some(fence) = fence_opt {
+                self.lock_lease(&conn, fence)?;
+            }
+            let marked_split_ids = self.mark_splits_for_deletion(&conn, index_id, split_ids)?;
+
+            // returning `Ok` means `commit` the transaction.
+            if marked_split_ids.len() == split_ids.len() {
+                return Ok(());
+            }
+
+            let _ =
+                self.get_splits_with_invalid_state(&conn, index_id, split_ids, &marked_split_ids)?;
+
+            Err(diesel::result::Error::RollbackTransaction).map_err(MetastoreError::DbError)?
+        })?;
+        Ok(())
+    }
+
+    /// Deletes splits, in a transaction checking the lease of `fence_opt` if any.
+    fn delete_splits_inner(
+        &self,
+        index_id: &str,
+        split_ids: &[&str],
+        fence_opt: Option<&LeaseFence>,
+    ) -> MetastoreResult<()> {
+        let conn = self.get_conn()?;
+        conn.transaction::<_, MetastoreError, _>(|| {
+            if let Some(fence) = fence_opt {
+                self.lock_lease(&conn, fence)?;
+            }
+            let deletable_states = [
+                SplitState::Staged.to_string(),
+                SplitState::MarkedForDeletion.to_string(),
+            ];
+
+            let deleted_split_ids: Vec<String> = diesel::delete(
+                schema::splits::dsl::splits.filter(
+                    schema::splits::dsl::index_id
+                        .eq(index_id)
+                        .and(schema::splits::dsl::split_id.eq_any(split_ids))
+                        .and(schema::splits::dsl::split_state.eq_any(deletable_states)),
+                ),
+            )
+            .returning(schema::splits::dsl::split_id)
+            .get_results(&conn)
+            .map_err(MetastoreError::DbError)?;
+
+            // returning `Ok` means `commit` the transaction.
+            if deleted_split_ids.len() == split_ids.len() {
+                return Ok(());
+            }
+
+            // There is an error, but we want to investigate and return a meaningful error.
+            // From this point, we always have to return `Err` to abort the transaction.
+            let not_deletable_ids =
+                self.get_splits_with_invalid_state(&conn, index_id, split_ids, &deleted_split_ids)?;
+
+            Err(MetastoreError::SplitsNotDeletable {
+                split_ids: not_deletable_ids,
+            })
+        })?;
+        Ok(())
+    }
+
+    /// Checks that the lease of `fence` still bears its fencing token, and locks it until the end
+    /// of the transaction.
+    fn lock_lease(&self, conn: &Conn, fence: &LeaseFence) -> MetastoreResult<()> {
+        let leases: Vec<model::Lease> = sql_query(LOCK_LEASE)
+            .bind::<Text, _>(&fence.lease_id)
+            .bind::<BigInt, _>(fence.fencing_token as i64)
+            .get_results(conn)?;
+        if leases.is_empty() {
+            return Err(MetastoreError::LeaseLost {
+                lease_id: fence.lease_id.clone(),
+                fencing_token: fence.fencing_token,
+            });
+        }
+        Ok(())
+    }
+
     /// Apply checkpoint delta.
     fn apply_checkpoint_delta(
         &self,
@@ -297,11 +520,46 @@ impl PostgresqlMetastore {
         source_id: &str,
         checkpoint_delta: CheckpointDelta,
     ) -> MetastoreResult<()> {
-        let mut index_metadata = self.index_metadata_inner(conn, index_id)?;
+        let index_checkpoint_delta = IndexCheckpointDelta::for_source(source_id, checkpoint_delta);
+        self.apply_index_checkpoint_delta(conn, index_id, index_checkpoint_delta)
+    }
+
+    /// Apply the checkpoint deltas of several sources.
+    ///
+    /// Rather than rewriting the index metadata, whose checkpoint covers every partition of
+    /// every source, the positions reached by the partitions updated by the delta are appended
+    /// to the `checkpoint_deltas` table. The pending deltas are folded into the index metadata
+    /// once there are [`CHECKPOINT_DELTAS_COMPACTION_THRESHOLD`] of them.
+    fn apply_index_checkpoint_delta(
+        &self,
+        conn: &PooledConnection<ConnectionManager<PgConnection>>,
+        index_id: &str,
+        index_checkpoint_delta: IndexCheckpointDelta,
+    ) -> MetastoreResult<()> {
+        self.touch_index(conn, index_id)?;
+        let (mut index_metadata, num_checkpoint_deltas) =
+            self.index_metadata_with_checkpoint_deltas(conn, index_id)?;
+        let end_positions = index_checkpoint_delta.end_positions();
+        let is_empty_delta = index_checkpoint_delta.is_empty();
         index_metadata
             .checkpoint
-            .try_apply_delta(source_id, checkpoint_delta)?;
-        self.update_index(conn, index_metadata)?;
+            .try_apply_index_delta(index_checkpoint_delta)?;
+        if num_checkpoint_deltas + 1 >= CHECKPOINT_DELTAS_COMPACTION_THRESHOLD {
+            debug!(index_id = %index_id, num_checkpoint_deltas = num_checkpoint_deltas, "Compact checkpoint deltas.");
+            return self.update_index(conn, index_metadata);
+        }
+        if is_empty_delta {
+            return Ok(());
+        }
+        let delta_json =
+            serde_json::to_string(&end_positions).map_err(|err| MetastoreError::InternalError {
+                message: "Failed to serialize checkpoint delta.".to_string(),
+                cause: anyhow::anyhow!(err),
+            })?;
+        sql_query(INSERT_CHECKPOINT_DELTA)
+            .bind::<Text, _>(index_id)
+            .bind::<Text, _>(delta_json)
+            .execute(conn)?;
         Ok(())
     }
 
@@ -312,11 +570,22 @@ impl PostgresqlMetastore {
         state_opt: Option<SplitState>,
         time_range_opt: Option<Range<i64>>,
         tags_opt: Option<TagFilterAst>,
+        page_opt: Option<SplitPage>,
     ) -> MetastoreResult<Vec<Split>> {
         let mut select_statement = schema::splits::dsl::splits
             .filter(schema::splits::dsl::index_id.eq(index_id))
             .into_boxed();
 
+        if let Some(page) = page_opt {
+            if let Some(after_split_id) = page.after_split_id_opt {
+                select_statement =
+                    select_statement.filter(schema::splits::dsl::split_id.gt(after_split_id));
+            }
+            select_statement = select_statement
+                .order(schema::splits::dsl::split_id.asc())
+                .limit(page.page_size as i64);
+        }
+
         if let Some(state) = state_opt {
             select_statement =
                 select_statement.filter(schema::splits::dsl::split_state.eq(state.to_string()));
@@ -436,67 +705,40 @@ impl Metastore for PostgresqlMetastore {
     }
 
     async fn create_index(&self, index_metadata: IndexMetadata) -> MetastoreResult<()> {
-        // Serialize the index metadata to fit the database model.
-        let index_metadata_json = serde_json::to_string(&index_metadata).map_err(|err| {
-            MetastoreError::InternalError {
-                message: "Failed to serialize index metadata.".to_string(),
-                cause: anyhow::anyhow!(err),
-            }
+        let conn = self.get_conn()?;
+        conn.transaction::<_, MetastoreError, _>(|| {
+            self.create_index_helper(&conn, &index_metadata)
         })?;
+        Ok(())
+    }
+
+    async fn delete_index(&self, index_id: &str) -> MetastoreResult<()> {
+        let conn = self.get_conn()?;
+        conn.transaction::<_, MetastoreError, _>(|| self.delete_index_helper(&conn, index_id))?;
+        info!(index_id = index_id, "deleted-index");
+        Ok(())
+    }
+
+    async fn create_indexes(&self, index_metadatas: Vec<IndexMetadata>) -> MetastoreResult<()> {
         let conn = self.get_conn()?;
         conn.transaction::<_, MetastoreError, _>(|| {
-            // Create index.
-            let create_index_statement =
-                diesel::insert_into(schema::indexes::dsl::indexes).values((
-                    schema::indexes::dsl::index_id.eq(index_metadata.index_id.clone()),
-                    schema::indexes::dsl::index_metadata_json.eq(index_metadata_json),
-                ));
-            debug!(sql=%debug_query::<Pg, _>(&create_index_statement).to_string());
-            create_index_statement
-                .execute(&*conn)
-                .map_err(|err| match err {
-                    DatabaseError(db_err_kind, ref err_info) => match db_err_kind {
-                        DatabaseErrorKind::UniqueViolation => {
-                            error!(index_id=?index_metadata.index_id, "Index already exists");
-                            MetastoreError::IndexAlreadyExists {
-                                index_id: index_metadata.index_id.clone(),
-                            }
-                        }
-                        _ => {
-                            error!(index_id=?index_metadata.index_id, "An error has occurred in the database operation. {:?}", err_info.message());
-                            MetastoreError::DbError(err)
-                        }
-                    },
-                    _ => {
-                        error!(index_id=?index_metadata.index_id, "An error has occurred in the database operation. {:?}", err);
-                        MetastoreError::DbError(err)
-                    }
-                })?;
-            debug!(index_id=?index_metadata.index_id, "The index has been created");
+            for index_metadata in &index_metadatas {
+                self.create_index_helper(&conn, index_metadata)?;
+            }
             Ok(())
         })?;
         Ok(())
     }
 
-    async fn delete_index(&self, index_id: &str) -> MetastoreResult<()> {
+    async fn delete_indexes<'a>(&self, index_ids: &[&'a str]) -> MetastoreResult<()> {
         let conn = self.get_conn()?;
         conn.transaction::<_, MetastoreError, _>(|| {
-            // Delete index.
-            let delete_index_statement =
-                diesel::delete(schema::indexes::dsl::indexes.find(index_id));
-            debug!(sql=%debug_query::<Pg, _>(&delete_index_statement).to_string());
-            let num_affected_rows = delete_index_statement
-                .execute(&*conn)
-                .map_err(MetastoreError::DbError)?;
-
-            if num_affected_rows == 0 {
-                return Err(MetastoreError::IndexDoesNotExist {
-                    index_id: index_id.to_string(),
-                });
+            for index_id in index_ids {
+                self.delete_index_helper(&conn, index_id)?;
             }
             Ok(())
         })?;
-        info!(index_id = index_id, "deleted-index");
+        info!(index_ids = ?index_ids, "deleted-indexes");
         Ok(())
     }
 
@@ -511,6 +753,9 @@ impl Metastore for PostgresqlMetastore {
                 message: "Failed to serialize split metadata and footer offsets".to_string(),
                 cause: anyhow::anyhow!(err),
             })?;
+        // The JSON metadata is still written so that nodes running a previous version can read
+        // the split.
+        let split_metadata_bin = encode_split_metadata(&metadata);
 
         let conn = self.get_conn()?;
         conn.transaction::<_, MetastoreError, _>(|| {
@@ -528,6 +773,7 @@ impl Metastore for PostgresqlMetastore {
                         .into_iter()
                         .collect::<Vec<String>>()),
                     schema::splits::dsl::split_metadata_json.eq(split_metadata_json),
+                    schema::splits::dsl::split_metadata_bin.eq(Some(split_metadata_bin)),
                     schema::splits::dsl::index_id.eq(index_id.to_string())
                 ));
             debug!(sql=%debug_query::<Pg, _>(&insert_staged_split_statement).to_string());
@@ -603,6 +849,41 @@ impl Metastore for PostgresqlMetastore {
         Ok(())
     }
 
+    async fn publish_multi_source_splits<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+        index_checkpoint_delta: IndexCheckpointDelta,
+    ) -> MetastoreResult<()> {
+        let conn = self.get_conn()?;
+        conn.transaction::<_, MetastoreError, _>(|| {
+            // Update the checkpoints of all the sources.
+            self.apply_index_checkpoint_delta(&conn, index_id, index_checkpoint_delta)?;
+
+            let published_split_ids =
+                self.mark_splits_as_published_helper(&conn, index_id, split_ids)?;
+
+            // returning `Ok` means `commit` the transaction.
+            if published_split_ids.len() == split_ids.len() {
+                return Ok(());
+            }
+
+            // Investigate and report the error.
+            let not_staged_ids = self.get_splits_with_invalid_state(
+                &conn,
+                index_id,
+                split_ids,
+                &published_split_ids,
+            )?;
+
+            Err(MetastoreError::SplitsNotStaged {
+                split_ids: not_staged_ids,
+            })
+        })?;
+
+        Ok(())
+    }
+
     async fn replace_splits<'a>(
         &self,
         index_id: &str,
@@ -656,36 +937,131 @@ impl Metastore for PostgresqlMetastore {
         state: SplitState,
         time_range_opt: Option<Range<i64>>,
         tags: Option<TagFilterAst>,
+    ) -> MetastoreResult<Vec<Split>> {
+        self.list_splits_stream(index_id, state, time_range_opt, tags, LIST_SPLITS_PAGE_SIZE)
+            .await?
+            .try_concat()
+            .await
+    }
+
+    async fn list_splits_page(
+        &self,
+        index_id: &str,
+        state: SplitState,
+        time_range_opt: Option<Range<i64>>,
+        tags: Option<TagFilterAst>,
+        after_split_id_opt: Option<String>,
+        page_size: usize,
     ) -> MetastoreResult<Vec<Split>> {
         let conn = self.get_conn()?;
-        self.list_splits_helper(&conn, index_id, Some(state), time_range_opt, tags)
+        self.list_splits_helper(
+            &conn,
+            index_id,
+            Some(state),
+            time_range_opt,
+            tags,
+            Some(SplitPage {
+                after_split_id_opt,
+                page_size,
+            }),
+        )
+    }
+
+    async fn list_splits_stream(
+        &self,
+        index_id: &str,
+        state: SplitState,
+        time_range_opt: Option<Range<i64>>,
+        tags: Option<TagFilterAst>,
+        page_size: usize,
+    ) -> MetastoreResult<SplitPageStream> {
+        let page_size = page_size.max(1);
+        // The first page is fetched eagerly so that listing the splits of a missing index fails
+        // right away. The next pages are fetched as the stream is consumed, using the last split
+        // ID of the previous page as cursor.
+        let first_page = self
+            .list_splits_page(
+                index_id,
+                state,
+                time_range_opt.clone(),
+                tags.clone(),
+                None,
+                page_size,
+            )
+            .await?;
+        if first_page.is_empty() {
+            return Ok(futures::stream::empty().boxed());
+        }
+        let cursor_opt = next_page_cursor(&first_page, page_size);
+        let metastore = self.clone();
+        let index_id = index_id.to_string();
+        let next_pages = futures::stream::try_unfold(cursor_opt, move |cursor_opt| {
+            let metastore = metastore.clone();
+            let index_id = index_id.clone();
+            let time_range_opt = time_range_opt.clone();
+            let tags = tags.clone();
+            async move {
+                if cursor_opt.is_none() {
+                    return Ok(None);
+                }
+                let page = metastore
+                    .list_splits_page(
+                        &index_id,
+                        state,
+                        time_range_opt,
+                        tags,
+                        cursor_opt,
+                        page_size,
+                    )
+                    .await?;
+                if page.is_empty() {
+                    return Ok(None);
+                }
+                let cursor_opt = next_page_cursor(&page, page_size);
+                Ok(Some((page, cursor_opt)))
+            }
+        });
+        Ok(futures::stream::once(async move { Ok(first_page) })
+            .chain(next_pages)
+            .boxed())
     }
 
     async fn list_all_splits(&self, index_id: &str) -> MetastoreResult<Vec<Split>> {
         let conn = self.get_conn()?;
-        self.list_splits_helper(&conn, index_id, None, None, None)
+        self.list_splits_helper(&conn, index_id, None, None, None, None)
     }
 
-    async fn mark_splits_for_deletion<'a>(
+    async fn list_splits_updated_since(
         &self,
         index_id: &str,
-        split_ids: &[&'a str],
-    ) -> MetastoreResult<()> {
+        update_timestamp: i64,
+    ) -> MetastoreResult<Vec<Split>> {
         let conn = self.get_conn()?;
-        conn.transaction::<_, MetastoreError, _>(|| {
-            let marked_split_ids = self.mark_splits_for_deletion(&conn, index_id, split_ids)?;
-
-            // returning `Ok` means `commit` the transaction.
-            if marked_split_ids.len() == split_ids.len() {
-                return Ok(());
-            }
+        let select_statement = schema::splits::dsl::splits
+            .filter(schema::splits::dsl::index_id.eq(index_id))
+            .filter(
+                schema::splits::dsl::update_timestamp
+                    .ge(NaiveDateTime::from_timestamp(update_timestamp, 0)),
+            );
+        debug!(sql=%debug_query::<Pg, _>(&select_statement).to_string());
+        let splits: Vec<model::Split> = select_statement
+            .load(&conn)
+            .map_err(MetastoreError::DbError)?;
 
-            let _ =
-                self.get_splits_with_invalid_state(&conn, index_id, split_ids, &marked_split_ids)?;
+        if splits.is_empty() && !self.index_exists(&conn, index_id)? {
+            return Err(MetastoreError::IndexDoesNotExist {
+                index_id: index_id.to_string(),
+            });
+        }
+        splits.into_iter().map(|split| split.try_into()).collect()
+    }
 
-            Err(diesel::result::Error::RollbackTransaction).map_err(MetastoreError::DbError)?
-        })?;
-        Ok(())
+    async fn mark_splits_for_deletion<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+    ) -> MetastoreResult<()> {
+        self.mark_splits_for_deletion_inner(index_id, split_ids, None)
     }
 
     async fn delete_splits<'a>(
@@ -693,40 +1069,25 @@ impl Metastore for PostgresqlMetastore {
         index_id: &str,
         split_ids: &[&'a str],
     ) -> MetastoreResult<()> {
-        let conn = self.get_conn()?;
-        conn.transaction::<_, MetastoreError, _>(|| {
-            let deletable_states = [
-                SplitState::Staged.to_string(),
-                SplitState::MarkedForDeletion.to_string(),
-            ];
-
-            let deleted_split_ids: Vec<String> = diesel::delete(
-                schema::splits::dsl::splits.filter(
-                    schema::splits::dsl::index_id
-                        .eq(index_id)
-                        .and(schema::splits::dsl::split_id.eq_any(split_ids))
-                        .and(schema::splits::dsl::split_state.eq_any(deletable_states)),
-                ),
-            )
-            .returning(schema::splits::dsl::split_id)
-            .get_results(&conn)
-            .map_err(MetastoreError::DbError)?;
-
-            // returning `Ok` means `commit` the transaction.
-            if deleted_split_ids.len() == split_ids.len() {
-                return Ok(());
-            }
+        self.delete_splits_inner(index_id, split_ids, None)
+    }
 
-            // There is an error, but we want to investigate and return a meaningful error.
-            // From this point, we always have to return `Err` to abort the transaction.
-            let not_deletable_ids =
-                self.get_splits_with_invalid_state(&conn, index_id, split_ids, &deleted_split_ids)?;
+    async fn mark_splits_for_deletion_fenced<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+        fence: &LeaseFence,
+    ) -> MetastoreResult<()> {
+        self.mark_splits_for_deletion_inner(index_id, split_ids, Some(fence))
+    }
 
-            Err(MetastoreError::SplitsNotDeletable {
-                split_ids: not_deletable_ids,
-            })
-        })?;
-        Ok(())
+    async fn delete_splits_fenced<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+        fence: &LeaseFence,
+    ) -> MetastoreResult<()> {
+        self.delete_splits_inner(index_id, split_ids, Some(fence))
     }
 
     async fn index_metadata(&self, index_id: &str) -> MetastoreResult<IndexMetadata> {
@@ -735,9 +1096,22 @@ impl Metastore for PostgresqlMetastore {
         Ok(index_metadata)
     }
 
+    async fn list_indexes_metadatas(&self) -> MetastoreResult<Vec<IndexMetadata>> {
+        let conn = self.get_conn()?;
+        let index_ids: Vec<String> = schema::indexes::dsl::indexes
+            .select(schema::indexes::dsl::index_id)
+            .order(schema::indexes::dsl::index_id.asc())
+            .load(&conn)?;
+        index_ids
+            .iter()
+            .map(|index_id| self.index_metadata_inner(&conn, index_id))
+            .collect()
+    }
+
     async fn add_source(&self, index_id: &str, source: SourceConfig) -> MetastoreResult<()> {
         let conn = self.get_conn()?;
         conn.transaction::<_, MetastoreError, _>(|| {
+            self.touch_index(&conn, index_id)?;
             let mut index_metadata = self.index_metadata_inner(&conn, index_id)?;
             index_metadata.add_source(source)?;
             self.update_index(&conn, index_metadata)?;
@@ -749,6 +1123,7 @@ impl Metastore for PostgresqlMetastore {
     async fn delete_source(&self, index_id: &str, source_id: &str) -> MetastoreResult<()> {
         let conn = self.get_conn()?;
         conn.transaction::<_, MetastoreError, _>(|| {
+            self.touch_index(&conn, index_id)?;
             let mut index_metadata = self.index_metadata_inner(&conn, index_id)?;
             index_metadata.delete_source(source_id)?;
             self.update_index(&conn, index_metadata)?;
@@ -757,11 +1132,257 @@ impl Metastore for PostgresqlMetastore {
         Ok(())
     }
 
+    async fn reset_source_checkpoint(
+        &self,
+        index_id: &str,
+        source_id: &str,
+        checkpoint: SourceCheckpoint,
+    ) -> MetastoreResult<()> {
+        let conn = self.get_conn()?;
+        conn.transaction::<_, MetastoreError, _>(|| {
+            self.touch_index(&conn, index_id)?;
+            let mut index_metadata = self.index_metadata_inner(&conn, index_id)?;
+            index_metadata.reset_source_checkpoint(source_id, checkpoint)?;
+            self.update_index(&conn, index_metadata)?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    async fn prune_source_checkpoint(
+        &self,
+        index_id: &str,
+        source_id: &str,
+        partition_ids: &[PartitionId],
+    ) -> MetastoreResult<()> {
+        let conn = self.get_conn()?;
+        conn.transaction::<_, MetastoreError, _>(|| {
+            self.touch_index(&conn, index_id)?;
+            let mut index_metadata = self.index_metadata_inner(&conn, index_id)?;
+            index_metadata.prune_source_checkpoint(source_id, partition_ids)?;
+            self.update_index(&conn, index_metadata)?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    async fn update_indexing_settings(
+        &self,
+        index_id: &str,
+        indexing_settings: IndexingSettings,
+    ) -> MetastoreResult<()> {
+        let conn = self.get_conn()?;
+        conn.transaction::<_, MetastoreError, _>(|| {
+            self.touch_index(&conn, index_id)?;
+            let mut index_metadata = self.index_metadata_inner(&conn, index_id)?;
+            index_metadata.update_indexing_settings(indexing_settings);
+            self.update_index(&conn, index_metadata)?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    async fn update_lifecycle_state(
+        &self,
+        index_id: &str,
+        lifecycle_state: IndexLifecycleState,
+    ) -> MetastoreResult<()> {
+        let conn = self.get_conn()?;
+        conn.transaction::<_, MetastoreError, _>(|| {
+            self.touch_index(&conn, index_id)?;
+            let mut index_metadata = self.index_metadata_inner(&conn, index_id)?;
+            index_metadata.update_lifecycle_state(lifecycle_state);
+            self.update_index(&conn, index_metadata)?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    async fn acquire_lease(
+        &self,
+        lease_id: &str,
+        holder_id: &str,
+        ttl: Duration,
+    ) -> MetastoreResult<Lease> {
+        let conn = self.get_conn()?;
+        let acquired_lease_opt: Option<model::Lease> = sql_query(ACQUIRE_LEASE)
+            .bind::<Text, _>(lease_id)
+            .bind::<Text, _>(holder_id)
+            .bind::<BigInt, _>(ttl.as_secs() as i64)
+            .get_results(&conn)?
+            .into_iter()
+            .next();
+        if let Some(acquired_lease) = acquired_lease_opt {
+            return Ok(acquired_lease.into());
+        }
+        let current_lease_opt: Option<model::Lease> = sql_query(SELECT_LEASE)
+            .bind::<Text, _>(lease_id)
+            .get_results(&conn)?
+            .into_iter()
+            .next();
+        Err(MetastoreError::LeaseAlreadyHeld {
+            lease_id: lease_id.to_string(),
+            holder_id: current_lease_opt
+                .map(|current_lease| current_lease.holder_id)
+                .unwrap_or_default(),
+        })
+    }
+
+    async fn release_lease(&self, lease_id: &str, holder_id: &str) -> MetastoreResult<()> {
+        let conn = self.get_conn()?;
+        sql_query(RELEASE_LEASE)
+            .bind::<Text, _>(lease_id)
+            .bind::<Text, _>(holder_id)
+            .execute(&conn)?;
+        Ok(())
+    }
+
+    async fn check_lease(&self, lease_id: &str, fencing_token: u64) -> MetastoreResult<()> {
+        let conn = self.get_conn()?;
+        let leases: Vec<model::Lease> = sql_query(CHECK_LEASE)
+            .bind::<Text, _>(lease_id)
+            .bind::<BigInt, _>(fencing_token as i64)
+            .get_results(&conn)?;
+        if leases.is_empty() {
+            return Err(MetastoreError::LeaseLost {
+                lease_id: lease_id.to_string(),
+                fencing_token,
+            });
+        }
+        Ok(())
+    }
+
+    async fn acquire_split_read_leases<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+        holder_id: &str,
+        ttl: Duration,
+    ) -> MetastoreResult<()> {
+        let conn = self.get_conn()?;
+        conn.transaction::<_, MetastoreError, _>(|| {
+            sql_query(DELETE_EXPIRED_SPLIT_READ_LEASES)
+                .bind::<Text, _>(index_id)
+                .execute(&conn)?;
+            sql_query(ACQUIRE_SPLIT_READ_LEASES)
+                .bind::<Text, _>(index_id)
+                .bind::<Text, _>(holder_id)
+                .bind::<Array<Text>, _>(
+                    split_ids
+                        .iter()
+                        .map(|split_id| split_id.to_string())
+                        .collect::<Vec<_>>(),
+                )
+                .bind::<BigInt, _>(ttl.as_secs() as i64)
+                .execute(&conn)?;
+            Ok(())
+        })
+    }
+
+    async fn release_split_read_leases(
+        &self,
+        index_id: &str,
+        holder_id: &str,
+    ) -> MetastoreResult<()> {
+        let conn = self.get_conn()?;
+        sql_query(RELEASE_SPLIT_READ_LEASES)
+            .bind::<Text, _>(index_id)
+            .bind::<Text, _>(holder_id)
+            .execute(&conn)?;
+        Ok(())
+    }
+
+    async fn list_leased_splits(&self, index_id: &str) -> MetastoreResult<HashSet<String>> {
+        let conn = self.get_conn()?;
+        let leased_split_rows: Vec<model::LeasedSplitRow> = sql_query(SELECT_LEASED_SPLITS)
+            .bind::<Text, _>(index_id)
+            .get_results(&conn)?;
+        Ok(leased_split_rows
+            .into_iter()
+            .map(|leased_split_row| leased_split_row.split_id)
+            .collect())
+    }
+
+    async fn create_job(&self, job: Job) -> MetastoreResult<()> {
+        let job_json = serialize_job(&job)?;
+        let expiration_timestamp = Utc::now().timestamp() - JOB_RETENTION_PERIOD.as_secs() as i64;
+        let conn = self.get_conn()?;
+        conn.transaction::<_, MetastoreError, _>(|| {
+            sql_query(DELETE_EXPIRED_JOBS)
+                .bind::<BigInt, _>(expiration_timestamp)
+                .execute(&conn)?;
+            sql_query(INSERT_JOB)
+                .bind::<Text, _>(&job.job_id)
+                .bind::<Text, _>(&job.index_id)
+                .bind::<Text, _>(job.state.as_str())
+                .bind::<Text, _>(job_json)
+                .bind::<BigInt, _>(job.update_timestamp)
+                .execute(&conn)?;
+            Ok(())
+        })
+    }
+
+    async fn update_job(&self, job: Job) -> MetastoreResult<()> {
+        let job_json = serialize_job(&job)?;
+        let conn = self.get_conn()?;
+        let num_updated_rows = sql_query(UPDATE_RUNNING_JOB)
+            .bind::<Text, _>(&job.job_id)
+            .bind::<Text, _>(job.state.as_str())
+            .bind::<Text, _>(job_json)
+            .bind::<BigInt, _>(job.update_timestamp)
+            .execute(&conn)?;
+        if num_updated_rows > 0 {
+            return Ok(());
+        }
+        let current_job = self.job(&job.job_id).await?;
+        Err(MetastoreError::JobNotRunning {
+            job_id: job.job_id,
+            state: current_job.state,
+        })
+    }
+
+    async fn job(&self, job_id: &str) -> MetastoreResult<Job> {
+        let conn = self.get_conn()?;
+        let job_row_opt: Option<model::JobRow> = sql_query(SELECT_JOB)
+            .bind::<Text, _>(job_id)
+            .get_results(&conn)?
+            .into_iter()
+            .next();
+        match job_row_opt {
+            Some(job_row) => job_row.try_into(),
+            None => Err(MetastoreError::JobDoesNotExist {
+                job_id: job_id.to_string(),
+            }),
+        }
+    }
+
+    async fn list_jobs<'a>(&self, index_id_opt: Option<&'a str>) -> MetastoreResult<Vec<Job>> {
+        let conn = self.get_conn()?;
+        let job_rows: Vec<model::JobRow> = if let Some(index_id) = index_id_opt {
+            sql_query(SELECT_INDEX_JOBS)
+                .bind::<Text, _>(index_id)
+                .get_results(&conn)?
+        } else {
+            sql_query(SELECT_JOBS).get_results(&conn)?
+        };
+        job_rows
+            .into_iter()
+            .map(|job_row| job_row.try_into())
+            .collect()
+    }
+
     fn uri(&self) -> String {
         self.uri.clone()
     }
 }
 
+fn serialize_job(job: &Job) -> MetastoreResult<String> {
+    serde_json::to_string(job).map_err(|serde_err| MetastoreError::InternalError {
+        message: "Failed to serialize job.".to_string(),
+        cause: anyhow::anyhow!(serde_err),
+    })
+}
+
 fn true_expr() -> Box<dyn BoxableExpression<splits::table, Pg, SqlType = Bool>> {
     Box::new(true.into_sql::<Bool>()) // as FilterExpression<QS>;
 }
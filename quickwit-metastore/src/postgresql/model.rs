@@ -21,13 +21,14 @@ use std::convert::TryInto;
 use std::str::FromStr;
 
 use chrono::NaiveDateTime;
-use diesel::sql_types::{Nullable, Text};
+use diesel::sql_types::{BigInt, Nullable, Text};
 use tracing::error;
 
+use crate::checkpoint::IndexCheckpoint;
 use crate::postgresql::schema::{indexes, splits};
 use crate::{
-    IndexMetadata, MetastoreError, MetastoreResult, Split as QuickwitSplit, SplitMetadata,
-    SplitState,
+    decode_split_metadata, IndexMetadata, Job as QuickwitJob, Lease as QuickwitLease,
+    MetastoreError, MetastoreResult, Split as QuickwitSplit, SplitMetadata, SplitState,
 };
 
 // A raw query that helps figure out if index exist, non-existant
@@ -43,6 +44,138 @@ LEFT JOIN (
 ON i.index_id = s.index_id
 WHERE i.index_id = $2"#;
 
+// Acquires or renews a lease. The lease is upserted unless another holder holds it and it has not
+// expired, in which case no row is returned. The fencing token is incremented unless the holder
+// renews a lease that has not expired. Leases rely on the clock of the database rather than on the
+// clocks of the nodes.
+pub const ACQUIRE_LEASE: &str = r#"
+INSERT INTO leases AS l (lease_id, holder_id, fencing_token, expiration_timestamp)
+VALUES ($1, $2, 1, CAST(EXTRACT(EPOCH FROM now()) AS BIGINT) + $3)
+ON CONFLICT (lease_id) DO UPDATE SET
+    holder_id = EXCLUDED.holder_id,
+    fencing_token = CASE
+        WHEN l.holder_id = EXCLUDED.holder_id
+            AND l.expiration_timestamp > CAST(EXTRACT(EPOCH FROM now()) AS BIGINT)
+        THEN l.fencing_token
+        ELSE l.fencing_token + 1
+    END,
+    expiration_timestamp = EXCLUDED.expiration_timestamp
+WHERE l.holder_id = EXCLUDED.holder_id
+    OR l.expiration_timestamp <= CAST(EXTRACT(EPOCH FROM now()) AS BIGINT)
+RETURNING lease_id, holder_id, fencing_token, expiration_timestamp"#;
+
+// Expires a lease held by a holder. The row is kept so that the fencing token keeps increasing.
+pub const RELEASE_LEASE: &str = r#"
+UPDATE leases SET expiration_timestamp = CAST(EXTRACT(EPOCH FROM now()) AS BIGINT)
+WHERE lease_id = $1
+    AND holder_id = $2
+    AND expiration_timestamp > CAST(EXTRACT(EPOCH FROM now()) AS BIGINT)"#;
+
+// Returns a lease if it bears the given fencing token and has not expired.
+pub const CHECK_LEASE: &str = r#"
+SELECT lease_id, holder_id, fencing_token, expiration_timestamp
+FROM leases
+WHERE lease_id = $1
+    AND fencing_token = $2
+    AND expiration_timestamp > CAST(EXTRACT(EPOCH FROM now()) AS BIGINT)"#;
+
+// Returns a lease if it bears the given fencing token and has not expired, and locks it until the
+// end of the transaction, so that it cannot be acquired anew in the meantime.
+pub const LOCK_LEASE: &str = r#"
+SELECT lease_id, holder_id, fencing_token, expiration_timestamp
+FROM leases
+WHERE lease_id = $1
+    AND fencing_token = $2
+    AND expiration_timestamp > CAST(EXTRACT(EPOCH FROM now()) AS BIGINT)
+FOR SHARE"#;
+
+// Returns a lease.
+pub const SELECT_LEASE: &str = r#"
+SELECT lease_id, holder_id, fencing_token, expiration_timestamp
+FROM leases
+WHERE lease_id = $1"#;
+
+// Deletes the expired split read leases of an index.
+pub const DELETE_EXPIRED_SPLIT_READ_LEASES: &str = r#"
+DELETE FROM split_read_leases
+WHERE index_id = $1
+    AND expiration_timestamp <= CAST(EXTRACT(EPOCH FROM now()) AS BIGINT)"#;
+
+// Acquires or renews the read leases of a holder on a list of splits.
+pub const ACQUIRE_SPLIT_READ_LEASES: &str = r#"
+INSERT INTO split_read_leases (index_id, holder_id, split_id, expiration_timestamp)
+SELECT $1, $2, split_id, CAST(EXTRACT(EPOCH FROM now()) AS BIGINT) + $4
+FROM UNNEST($3) AS split_id
+ON CONFLICT (index_id, holder_id, split_id) DO UPDATE SET
+    expiration_timestamp = EXCLUDED.expiration_timestamp"#;
+
+// Drops the read leases of a holder on the splits of an index.
+pub const RELEASE_SPLIT_READ_LEASES: &str = r#"
+DELETE FROM split_read_leases
+WHERE index_id = $1
+    AND holder_id = $2"#;
+
+// Returns the IDs of the splits of an index under at least one read lease that has not expired.
+pub const SELECT_LEASED_SPLITS: &str = r#"
+SELECT DISTINCT split_id
+FROM split_read_leases
+WHERE index_id = $1
+    AND expiration_timestamp > CAST(EXTRACT(EPOCH FROM now()) AS BIGINT)"#;
+
+// Deletes the jobs that reached a final state before a given timestamp.
+pub const DELETE_EXPIRED_JOBS: &str = r#"
+DELETE FROM jobs
+WHERE job_state <> 'running'
+    AND update_timestamp < $1"#;
+
+// Records a new job.
+pub const INSERT_JOB: &str = r#"
+INSERT INTO jobs (job_id, index_id, job_state, job_json, update_timestamp)
+VALUES ($1, $2, $3, $4, $5)"#;
+
+// Replaces the record of a job unless it already reached a final state.
+pub const UPDATE_RUNNING_JOB: &str = r#"
+UPDATE jobs SET job_state = $2, job_json = $3, update_timestamp = $4
+WHERE job_id = $1
+    AND job_state = 'running'"#;
+
+// Returns a job.
+pub const SELECT_JOB: &str = r#"
+SELECT job_json
+FROM jobs
+WHERE job_id = $1"#;
+
+// Returns the jobs, most recent first. Job IDs are ULIDs, which sort by creation time.
+pub const SELECT_JOBS: &str = r#"
+SELECT job_json
+FROM jobs
+ORDER BY job_id DESC"#;
+
+// Returns the jobs of an index, most recent first.
+pub const SELECT_INDEX_JOBS: &str = r#"
+SELECT job_json
+FROM jobs
+WHERE index_id = $1
+ORDER BY job_id DESC"#;
+
+pub const TOUCH_INDEX: &str = r#"
+UPDATE indexes SET update_timestamp = (CURRENT_TIMESTAMP AT TIME ZONE 'UTC')
+WHERE index_id = $1"#;
+
+pub const INSERT_CHECKPOINT_DELTA: &str = r#"
+INSERT INTO checkpoint_deltas (index_id, delta_json)
+VALUES ($1, $2)"#;
+
+pub const SELECT_CHECKPOINT_DELTAS: &str = r#"
+SELECT delta_json
+FROM checkpoint_deltas
+WHERE index_id = $1
+ORDER BY delta_id"#;
+
+pub const DELETE_CHECKPOINT_DELTAS: &str = r#"
+DELETE FROM checkpoint_deltas
+WHERE index_id = $1"#;
+
 #[derive(Queryable, QueryableByName, Debug, Clone)]
 pub struct IndexIdSplitIdRow {
     #[sql_type = "Text"]
@@ -51,6 +184,13 @@ pub struct IndexIdSplitIdRow {
     pub split_id: Option<String>,
 }
 
+/// A row holding the ID of a leased split.
+#[derive(QueryableByName, Debug, Clone)]
+pub struct LeasedSplitRow {
+    #[sql_type = "Text"]
+    pub split_id: String,
+}
+
 /// A model structure for handling index metadata in a database.
 #[derive(Identifiable, Insertable, Queryable, Debug)]
 #[primary_key(index_id)]
@@ -109,12 +249,20 @@ pub struct Split {
     pub split_metadata_json: String,
     /// Index ID. It is used as a foreign key in the database.
     pub index_id: String,
+    /// The split's metadata in the compact binary encoding. Splits staged by previous versions
+    /// only have the JSON metadata.
+    pub split_metadata_bin: Option<Vec<u8>>,
 }
 
 impl Split {
-    /// Deserializes and returns the split's metadata.
+    /// Deserializes and returns the split's metadata, preferably from its binary encoding.
     fn split_metadata(&self) -> MetastoreResult<SplitMetadata> {
-        serde_json::from_str::<SplitMetadata>(&self.split_metadata_json).map_err(|err| {
+        let split_metadata_result = match &self.split_metadata_bin {
+            Some(split_metadata_bin) => decode_split_metadata(split_metadata_bin),
+            None => serde_json::from_str::<SplitMetadata>(&self.split_metadata_json)
+                .map_err(anyhow::Error::from),
+        };
+        split_metadata_result.map_err(|err| {
             error!(
                 index_id = %self.index_id, split_id = %self.split_id,
                 "Failed to deserialize split metadata."
@@ -125,7 +273,7 @@ impl Split {
             );
             MetastoreError::InternalError {
                 message,
-                cause: anyhow::anyhow!(err),
+                cause: err,
             }
         })
     }
@@ -166,3 +314,63 @@ impl TryInto<QuickwitSplit> for Split {
         })
     }
 }
+
+/// A model structure for handling the leases in a database.
+#[derive(QueryableByName, Debug, Clone)]
+pub struct Lease {
+    #[sql_type = "Text"]
+    pub lease_id: String,
+    #[sql_type = "Text"]
+    pub holder_id: String,
+    #[sql_type = "BigInt"]
+    pub fencing_token: i64,
+    #[sql_type = "BigInt"]
+    pub expiration_timestamp: i64,
+}
+
+impl From<Lease> for QuickwitLease {
+    fn from(lease: Lease) -> Self {
+        QuickwitLease {
+            lease_id: lease.lease_id,
+            holder_id: lease.holder_id,
+            fencing_token: lease.fencing_token as u64,
+            expiration_timestamp: lease.expiration_timestamp,
+        }
+    }
+}
+
+/// A row holding a checkpoint delta of an index, serialized as JSON: the positions reached by the
+/// partitions it updated, to be applied on top of the checkpoint of the index in `delta_id` order.
+#[derive(QueryableByName, Debug, Clone)]
+pub struct CheckpointDeltaRow {
+    #[sql_type = "Text"]
+    pub delta_json: String,
+}
+
+impl CheckpointDeltaRow {
+    /// Deserializes the positions reached by the partitions updated by the delta.
+    pub fn end_positions(&self) -> MetastoreResult<IndexCheckpoint> {
+        serde_json::from_str(&self.delta_json).map_err(|serde_err| MetastoreError::InternalError {
+            message: "Failed to deserialize checkpoint delta.".to_string(),
+            cause: anyhow::anyhow!(serde_err),
+        })
+    }
+}
+
+/// A row holding the record of a job, serialized as JSON.
+#[derive(QueryableByName, Debug, Clone)]
+pub struct JobRow {
+    #[sql_type = "Text"]
+    pub job_json: String,
+}
+
+impl TryInto<QuickwitJob> for JobRow {
+    type Error = MetastoreError;
+
+    fn try_into(self) -> Result<QuickwitJob, Self::Error> {
+        serde_json::from_str(&self.job_json).map_err(|serde_err| MetastoreError::InternalError {
+            message: "Failed to deserialize job.".to_string(),
+            cause: anyhow::anyhow!(serde_err),
+        })
+    }
+}
@@ -37,6 +37,43 @@ table! {
         tags -> Array<Text>,
         split_metadata_json -> Text,
         index_id -> Varchar,
+        split_metadata_bin -> Nullable<Bytea>,
+    }
+}
+
+table! {
+    leases (lease_id) {
+        lease_id -> Varchar,
+        holder_id -> Varchar,
+        fencing_token -> Int8,
+        expiration_timestamp -> Int8,
+    }
+}
+
+table! {
+    split_read_leases (index_id, holder_id, split_id) {
+        index_id -> Varchar,
+        holder_id -> Varchar,
+        split_id -> Varchar,
+        expiration_timestamp -> Int8,
+    }
+}
+
+table! {
+    jobs (job_id) {
+        job_id -> Varchar,
+        index_id -> Varchar,
+        job_state -> Varchar,
+        job_json -> Text,
+        update_timestamp -> Int8,
+    }
+}
+
+table! {
+    checkpoint_deltas (delta_id) {
+        delta_id -> Int8,
+        index_id -> Varchar,
+        delta_json -> Text,
     }
 }
 
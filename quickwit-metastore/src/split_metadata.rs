@@ -18,11 +18,13 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use core::fmt;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::ops::{Range, RangeInclusive};
 use std::str::FromStr;
 
 use chrono::Utc;
+use quickwit_doc_mapper::range_pruning::FieldStats;
+use quickwit_doc_mapper::SortByField;
 use serde::{Deserialize, Serialize};
 
 use crate::VersionedSplitMetadataDeserializeHelper;
@@ -93,6 +95,20 @@ pub struct SplitMetadata {
     #[serde(default)]
     pub demux_num_ops: usize,
 
+    /// If the documents of the split were sorted at creation, the sort field and order.
+    #[serde(default)]
+    pub sort_by: Option<SortByField>,
+
+    /// Min / max values and number of documents without value of the numeric and date fast
+    /// fields of the split, per field name. They are used to prune the splits of range queries.
+    #[serde(default)]
+    pub field_stats: BTreeMap<String, FieldStats>,
+
+    /// IDs of the splits this split replaces, if it was produced by a merge or a demux
+    /// operation. Empty for splits produced by indexing.
+    #[serde(default)]
+    pub replaced_split_ids: BTreeSet<String>,
+
     /// Contains the range of bytes of the footer that needs to be downloaded
     /// in order to open a split.
     ///
@@ -112,6 +128,9 @@ impl SplitMetadata {
             create_timestamp: utc_now_timestamp(),
             tags: Default::default(),
             demux_num_ops: 0,
+            sort_by: None,
+            field_stats: BTreeMap::new(),
+            replaced_split_ids: BTreeSet::new(),
             footer_offsets: Default::default(),
         }
     }
@@ -0,0 +1,315 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Compact binary encoding of the split metadata.
+//!
+//! Listing the splits of indexes made of hundreds of thousands of splits is dominated by the
+//! deserialization of their JSON metadata. The binary encoding is a protobuf message prefixed
+//! with a format version byte, which is several times smaller and faster to decode.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use prost::Message;
+use quickwit_doc_mapper::range_pruning::{FieldBounds, FieldStats};
+use quickwit_doc_mapper::{SortByField, SortOrder};
+
+use crate::SplitMetadata;
+
+/// Version of the binary encoding, written as the first byte of the encoded metadata.
+const SPLIT_METADATA_CODEC_VERSION: u8 = 1;
+
+#[derive(Clone, PartialEq, Message)]
+struct SplitMetadataProto {
+    #[prost(string, tag = "1")]
+    split_id: String,
+    #[prost(uint64, tag = "2")]
+    num_docs: u64,
+    #[prost(uint64, tag = "3")]
+    original_size_in_bytes: u64,
+    #[prost(int64, optional, tag = "4")]
+    time_range_start: Option<i64>,
+    #[prost(int64, optional, tag = "5")]
+    time_range_end: Option<i64>,
+    #[prost(int64, tag = "6")]
+    create_timestamp: i64,
+    #[prost(string, repeated, tag = "7")]
+    tags: Vec<String>,
+    #[prost(uint64, tag = "8")]
+    demux_num_ops: u64,
+    #[prost(string, optional, tag = "9")]
+    sort_by_field_name: Option<String>,
+    #[prost(bool, tag = "10")]
+    sort_by_ascending: bool,
+    #[prost(uint64, tag = "11")]
+    footer_offsets_start: u64,
+    #[prost(uint64, tag = "12")]
+    footer_offsets_end: u64,
+    #[prost(message, repeated, tag = "13")]
+    field_stats: Vec<FieldStatsProto>,
+    #[prost(string, repeated, tag = "14")]
+    replaced_split_ids: Vec<String>,
+}
+
+const FIELD_TYPE_I64: u32 = 0;
+const FIELD_TYPE_U64: u32 = 1;
+const FIELD_TYPE_F64: u32 = 2;
+const FIELD_TYPE_DATE: u32 = 3;
+
+/// The min and max values are stored as the bits of the values, whatever their type.
+#[derive(Clone, PartialEq, Message)]
+struct FieldStatsProto {
+    #[prost(string, tag = "1")]
+    field_name: String,
+    #[prost(uint32, tag = "2")]
+    field_type: u32,
+    #[prost(fixed64, tag = "3")]
+    min: u64,
+    #[prost(fixed64, tag = "4")]
+    max: u64,
+    #[prost(uint64, tag = "5")]
+    num_nulls: u64,
+}
+
+impl FieldStatsProto {
+    fn new(field_name: &str, field_stats: &FieldStats) -> Self {
+        let (field_type, min, max) = match field_stats.bounds {
+            FieldBounds::I64 { min, max } => (FIELD_TYPE_I64, min as u64, max as u64),
+            FieldBounds::U64 { min, max } => (FIELD_TYPE_U64, min, max),
+            FieldBounds::F64 { min, max } => (FIELD_TYPE_F64, min.to_bits(), max.to_bits()),
+            FieldBounds::Date { min, max } => (FIELD_TYPE_DATE, min as u64, max as u64),
+        };
+        FieldStatsProto {
+            field_name: field_name.to_string(),
+            field_type,
+            min,
+            max,
+            num_nulls: field_stats.num_nulls,
+        }
+    }
+
+    fn into_field_stats(self) -> anyhow::Result<(String, FieldStats)> {
+        let (min, max) = (self.min, self.max);
+        let bounds = match self.field_type {
+            FIELD_TYPE_I64 => FieldBounds::I64 {
+                min: min as i64,
+                max: max as i64,
+            },
+            FIELD_TYPE_U64 => FieldBounds::U64 { min, max },
+            FIELD_TYPE_F64 => FieldBounds::F64 {
+                min: f64::from_bits(min),
+                max: f64::from_bits(max),
+            },
+            FIELD_TYPE_DATE => FieldBounds::Date {
+                min: min as i64,
+                max: max as i64,
+            },
+            field_type => anyhow::bail!(
+                "Unknown type `{}` for the statistics of field `{}`.",
+                field_type,
+                self.field_name
+            ),
+        };
+        let field_stats = FieldStats {
+            bounds,
+            num_nulls: self.num_nulls,
+        };
+        Ok((self.field_name, field_stats))
+    }
+}
+
+/// Encodes the split metadata with the compact binary encoding.
+pub fn encode_split_metadata(split_metadata: &SplitMetadata) -> Vec<u8> {
+    let (time_range_start, time_range_end) = match &split_metadata.time_range {
+        Some(time_range) => (Some(*time_range.start()), Some(*time_range.end())),
+        None => (None, None),
+    };
+    let split_metadata_proto = SplitMetadataProto {
+        split_id: split_metadata.split_id.clone(),
+        num_docs: split_metadata.num_docs as u64,
+        original_size_in_bytes: split_metadata.original_size_in_bytes,
+        time_range_start,
+        time_range_end,
+        create_timestamp: split_metadata.create_timestamp,
+        tags: split_metadata.tags.iter().cloned().collect(),
+        demux_num_ops: split_metadata.demux_num_ops as u64,
+        sort_by_field_name: split_metadata
+            .sort_by
+            .as_ref()
+            .map(|sort_by| sort_by.field_name.clone()),
+        sort_by_ascending: matches!(
+            split_metadata.sort_by,
+            Some(SortByField {
+                order: SortOrder::Asc,
+                ..
+            })
+        ),
+        footer_offsets_start: split_metadata.footer_offsets.start,
+        footer_offsets_end: split_metadata.footer_offsets.end,
+        field_stats: split_metadata
+            .field_stats
+            .iter()
+            .map(|(field_name, field_stats)| FieldStatsProto::new(field_name, field_stats))
+            .collect(),
+        replaced_split_ids: split_metadata.replaced_split_ids.iter().cloned().collect(),
+    };
+    let mut buffer = Vec::with_capacity(1 + split_metadata_proto.encoded_len());
+    buffer.push(SPLIT_METADATA_CODEC_VERSION);
+    split_metadata_proto
+        .encode(&mut buffer)
+        .expect("The buffer should have enough capacity.");
+    buffer
+}
+
+/// Decodes split metadata encoded with [`encode_split_metadata`].
+pub fn decode_split_metadata(bytes: &[u8]) -> anyhow::Result<SplitMetadata> {
+    let (version, proto_bytes) = bytes
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("Encoded split metadata is empty."))?;
+    if *version != SPLIT_METADATA_CODEC_VERSION {
+        anyhow::bail!(
+            "Unsupported split metadata encoding version `{}`. Supported version is `{}`.",
+            version,
+            SPLIT_METADATA_CODEC_VERSION
+        );
+    }
+    let split_metadata_proto = SplitMetadataProto::decode(proto_bytes)?;
+    let time_range = match (
+        split_metadata_proto.time_range_start,
+        split_metadata_proto.time_range_end,
+    ) {
+        (Some(start), Some(end)) => Some(start..=end),
+        _ => None,
+    };
+    let sort_by = split_metadata_proto
+        .sort_by_field_name
+        .map(|field_name| SortByField {
+            field_name,
+            order: if split_metadata_proto.sort_by_ascending {
+                SortOrder::Asc
+            } else {
+                SortOrder::Desc
+            },
+        });
+    let field_stats = split_metadata_proto
+        .field_stats
+        .into_iter()
+        .map(FieldStatsProto::into_field_stats)
+        .collect::<anyhow::Result<BTreeMap<_, _>>>()?;
+    Ok(SplitMetadata {
+        split_id: split_metadata_proto.split_id,
+        num_docs: split_metadata_proto.num_docs as usize,
+        original_size_in_bytes: split_metadata_proto.original_size_in_bytes,
+        time_range,
+        create_timestamp: split_metadata_proto.create_timestamp,
+        tags: split_metadata_proto
+            .tags
+            .into_iter()
+            .collect::<BTreeSet<_>>(),
+        demux_num_ops: split_metadata_proto.demux_num_ops as usize,
+        sort_by,
+        field_stats,
+        replaced_split_ids: split_metadata_proto
+            .replaced_split_ids
+            .into_iter()
+            .collect::<BTreeSet<_>>(),
+        footer_offsets: split_metadata_proto.footer_offsets_start
+            ..split_metadata_proto.footer_offsets_end,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_split_metadata() -> SplitMetadata {
+        SplitMetadata {
+            split_id: "split-1".to_string(),
+            num_docs: 12_345,
+            original_size_in_bytes: 6_789_000,
+            time_range: Some(1_640_577_000..=1_640_580_600),
+            create_timestamp: 1_640_581_000,
+            tags: ["tenant_id!", "tenant_id:acme"]
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            demux_num_ops: 1,
+            sort_by: Some(SortByField {
+                field_name: "timestamp".to_string(),
+                order: SortOrder::Asc,
+            }),
+            field_stats: [
+                (
+                    "latency_ms".to_string(),
+                    FieldStats {
+                        bounds: FieldBounds::F64 {
+                            min: -0.5,
+                            max: 12.5,
+                        },
+                        num_nulls: 3,
+                    },
+                ),
+                (
+                    "timestamp".to_string(),
+                    FieldStats {
+                        bounds: FieldBounds::I64 {
+                            min: -1_640_577_000,
+                            max: 1_640_580_600,
+                        },
+                        num_nulls: 0,
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            replaced_split_ids: ["split-0", "split-00"]
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            footer_offsets: 1_000..2_000,
+        }
+    }
+
+    #[test]
+    fn test_split_metadata_codec_roundtrip() {
+        let split_metadata = make_split_metadata();
+        let encoded = encode_split_metadata(&split_metadata);
+        assert_eq!(encoded[0], SPLIT_METADATA_CODEC_VERSION);
+        assert_eq!(decode_split_metadata(&encoded).unwrap(), split_metadata);
+
+        let split_metadata = SplitMetadata::new("split-2".to_string());
+        let encoded = encode_split_metadata(&split_metadata);
+        assert_eq!(decode_split_metadata(&encoded).unwrap(), split_metadata);
+    }
+
+    #[test]
+    fn test_split_metadata_codec_is_more_compact_than_json() {
+        let split_metadata = make_split_metadata();
+        let encoded = encode_split_metadata(&split_metadata);
+        let split_metadata_json = serde_json::to_string(&split_metadata).unwrap();
+        assert!(encoded.len() * 2 < split_metadata_json.len());
+    }
+
+    #[test]
+    fn test_split_metadata_codec_rejects_invalid_bytes() {
+        assert!(decode_split_metadata(&[]).is_err());
+        let mut encoded = encode_split_metadata(&make_split_metadata());
+        encoded[0] = 42;
+        assert!(decode_split_metadata(&encoded).is_err());
+    }
+}
@@ -17,9 +17,11 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::ops::{Range, RangeInclusive};
 
+use quickwit_doc_mapper::range_pruning::FieldStats;
+use quickwit_doc_mapper::SortByField;
 use serde::{Deserialize, Serialize};
 
 use crate::split_metadata::utc_now_timestamp;
@@ -85,6 +87,9 @@ impl From<SplitMetadataAndFooterV0> for SplitMetadata {
             create_timestamp: v0.split_metadata.create_timestamp,
             tags: v0.split_metadata.tags,
             demux_num_ops: v0.split_metadata.demux_num_ops,
+            sort_by: None,
+            field_stats: BTreeMap::new(),
+            replaced_split_ids: BTreeSet::new(),
         }
     }
 }
@@ -122,6 +127,21 @@ pub(crate) struct SplitMetadataV1 {
     #[serde(default)]
     pub demux_num_ops: usize,
 
+    /// If the documents of the split were sorted at creation, the sort field and order.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort_by: Option<SortByField>,
+
+    /// Statistics of the numeric and date fast fields of the split, per field name.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub field_stats: BTreeMap<String, FieldStats>,
+
+    /// IDs of the splits replaced by this split, if it was produced by a merge or a demux.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub replaced_split_ids: BTreeSet<String>,
+
     /// Contains the range of bytes of the footer that needs to be downloaded
     /// in order to open a split.
     ///
@@ -141,6 +161,9 @@ impl From<SplitMetadataV1> for SplitMetadata {
             create_timestamp: v1.create_timestamp,
             tags: v1.tags,
             demux_num_ops: v1.demux_num_ops,
+            sort_by: v1.sort_by,
+            field_stats: v1.field_stats,
+            replaced_split_ids: v1.replaced_split_ids,
         }
     }
 }
@@ -156,6 +179,9 @@ impl From<SplitMetadata> for SplitMetadataV1 {
             create_timestamp: v1.create_timestamp,
             tags: v1.tags,
             demux_num_ops: v1.demux_num_ops,
+            sort_by: v1.sort_by,
+            field_stats: v1.field_stats,
+            replaced_split_ids: v1.replaced_split_ids,
         }
     }
 }
@@ -24,12 +24,18 @@ pub mod test_suite {
 
     use async_trait::async_trait;
     use chrono::Utc;
-    use quickwit_config::{SourceConfig, SourceParams};
+    use futures::TryStreamExt;
+    use quickwit_config::{MergePolicy, SourceConfig, SourceParams};
     use quickwit_doc_mapper::tag_pruning::{no_tag, tag, TagFilterAst};
     use tokio::time::{sleep, Duration};
 
-    use crate::checkpoint::{CheckpointDelta, SourceCheckpoint};
-    use crate::{IndexMetadata, Metastore, MetastoreError, SplitMetadata, SplitState};
+    use crate::checkpoint::{
+        CheckpointDelta, IndexCheckpointDelta, PartitionId, Position, SourceCheckpoint,
+    };
+    use crate::{
+        IndexLifecycleState, IndexMetadata, Job, JobKind, JobState, LifecyclePhase, Metastore,
+        MetastoreError, Split, SplitMetadata, SplitState,
+    };
 
     #[async_trait]
     pub trait DefaultForTest {
@@ -87,6 +93,9 @@ pub mod test_suite {
         let source = SourceConfig {
             source_id: source_id.to_string(),
             source_params: SourceParams::void(),
+            filter: None,
+            routing: None,
+            transform: None,
         };
 
         assert_eq!(
@@ -140,6 +149,9 @@ pub mod test_suite {
         let source = SourceConfig {
             source_id: source_id.to_string(),
             source_params: SourceParams::void(),
+            filter: None,
+            routing: None,
+            transform: None,
         };
 
         let mut index_metadata = IndexMetadata::for_test(index_id, index_uri);
@@ -170,141 +182,748 @@ pub mod test_suite {
         ));
     }
 
-    pub async fn test_metastore_create_index<MetastoreToTest: Metastore + DefaultForTest>() {
+    pub async fn test_metastore_reset_source_checkpoint<
+        MetastoreToTest: Metastore + DefaultForTest,
+    >() {
         let metastore = MetastoreToTest::default_for_test().await;
 
-        let index_id = "create-index-index";
-        let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
+        let index_id = "test-metastore-reset-source-checkpoint";
+        let index_uri = "ram://indexes/test-metastore-reset-source-checkpoint";
+        let source_id = "test-metastore-reset-source-checkpoint--void-source-id";
 
-        // Create an index
-        let result = metastore
-            .create_index(index_metadata.clone())
+        let source = SourceConfig {
+            source_id: source_id.to_string(),
+            source_params: SourceParams::void(),
+            filter: None,
+            routing: None,
+            transform: None,
+        };
+        let index_metadata = IndexMetadata::for_test(index_id, index_uri);
+        metastore.create_index(index_metadata).await.unwrap();
+        metastore.add_source(index_id, source).await.unwrap();
+
+        let split_id = "test-metastore-reset-source-checkpoint--split";
+        let split_metadata = SplitMetadata {
+            split_id: split_id.to_string(),
+            ..Default::default()
+        };
+        metastore
+            .stage_split(index_id, split_metadata)
             .await
             .unwrap();
-        assert!(matches!(result, ()));
+        metastore
+            .publish_splits(
+                index_id,
+                source_id,
+                &[split_id],
+                CheckpointDelta::from(0..10),
+            )
+            .await
+            .unwrap();
+        let source_checkpoint = metastore
+            .source_checkpoint(index_id, source_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            source_checkpoint.position_for_partition(&PartitionId::default()),
+            Some(&Position::from(9u64))
+        );
 
-        cleanup_index(&metastore, index_id).await;
+        let reset_checkpoint: SourceCheckpoint =
+            vec![(PartitionId::default(), Position::from(3u64))]
+                .into_iter()
+                .collect();
+        metastore
+            .reset_source_checkpoint(index_id, source_id, reset_checkpoint.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            metastore
+                .source_checkpoint(index_id, source_id)
+                .await
+                .unwrap(),
+            reset_checkpoint
+        );
+
+        assert!(matches!(
+            metastore
+                .source_checkpoint(index_id, "source-id-does-not-exist")
+                .await
+                .unwrap_err(),
+            MetastoreError::SourceDoesNotExist { .. }
+        ));
+        assert!(matches!(
+            metastore
+                .reset_source_checkpoint(
+                    index_id,
+                    "source-id-does-not-exist",
+                    SourceCheckpoint::default()
+                )
+                .await
+                .unwrap_err(),
+            MetastoreError::SourceDoesNotExist { .. }
+        ));
+        assert!(matches!(
+            metastore
+                .reset_source_checkpoint(
+                    "index-id-does-not-exist",
+                    source_id,
+                    SourceCheckpoint::default()
+                )
+                .await
+                .unwrap_err(),
+            MetastoreError::IndexDoesNotExist { .. }
+        ));
     }
 
-    pub async fn test_metastore_delete_index<MetastoreToTest: Metastore + DefaultForTest>() {
+    pub async fn test_metastore_prune_source_checkpoint<
+        MetastoreToTest: Metastore + DefaultForTest,
+    >() {
         let metastore = MetastoreToTest::default_for_test().await;
 
-        let index_id = "delte-index-index";
-        let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
+        let index_id = "test-metastore-prune-source-checkpoint";
+        let index_uri = "ram://indexes/test-metastore-prune-source-checkpoint";
+        let source_id = "test-metastore-prune-source-checkpoint--void-source-id";
 
-        // Delete a non-existent index
-        let result = metastore
-            .delete_index("non-existent-index")
-            .await
-            .unwrap_err();
-        assert!(matches!(result, MetastoreError::IndexDoesNotExist { .. }));
+        let source = SourceConfig {
+            source_id: source_id.to_string(),
+            source_params: SourceParams::void(),
+            filter: None,
+            routing: None,
+            transform: None,
+        };
+        let index_metadata = IndexMetadata::for_test(index_id, index_uri);
+        metastore.create_index(index_metadata).await.unwrap();
+        metastore.add_source(index_id, source).await.unwrap();
+
+        let publish_partition_delta = |split_id: &'static str, partition: &'static str, to: u64| {
+            let metastore = &metastore;
+            async move {
+                let split_metadata = SplitMetadata {
+                    split_id: split_id.to_string(),
+                    ..Default::default()
+                };
+                metastore
+                    .stage_split(index_id, split_metadata)
+                    .await
+                    .unwrap();
+                let from = metastore
+                    .source_checkpoint(index_id, source_id)
+                    .await
+                    .unwrap()
+                    .position_for_partition(&PartitionId::from(partition))
+                    .cloned()
+                    .unwrap_or(Position::Beginning);
+                let checkpoint_delta = CheckpointDelta::from_partition_delta(
+                    PartitionId::from(partition),
+                    from,
+                    Position::from(to),
+                );
+                metastore
+                    .publish_splits(index_id, source_id, &[split_id], checkpoint_delta)
+                    .await
+                    .unwrap();
+            }
+        };
+        publish_partition_delta("split-1", "partition-a", 10).await;
+        publish_partition_delta("split-2", "partition-b", 10).await;
+        publish_partition_delta("split-3", "partition-a", 20).await;
+        let expected_checkpoint: SourceCheckpoint = vec![
+            (PartitionId::from("partition-a"), Position::from(20u64)),
+            (PartitionId::from("partition-b"), Position::from(10u64)),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            metastore
+                .source_checkpoint(index_id, source_id)
+                .await
+                .unwrap(),
+            expected_checkpoint
+        );
 
         metastore
-            .create_index(index_metadata.clone())
+            .prune_source_checkpoint(index_id, source_id, &[PartitionId::from("partition-a")])
             .await
             .unwrap();
+        publish_partition_delta("split-4", "partition-b", 20).await;
+        let expected_checkpoint: SourceCheckpoint =
+            vec![(PartitionId::from("partition-b"), Position::from(20u64))]
+                .into_iter()
+                .collect();
+        assert_eq!(
+            metastore
+                .source_checkpoint(index_id, source_id)
+                .await
+                .unwrap(),
+            expected_checkpoint
+        );
 
-        // Delete an index
-        let result = metastore.delete_index(index_id).await.unwrap();
-        assert!(matches!(result, ()));
+        assert!(matches!(
+            metastore
+                .prune_source_checkpoint(index_id, "source-id-does-not-exist", &[])
+                .await
+                .unwrap_err(),
+            MetastoreError::SourceDoesNotExist { .. }
+        ));
+        assert!(matches!(
+            metastore
+                .prune_source_checkpoint("index-id-does-not-exist", source_id, &[])
+                .await
+                .unwrap_err(),
+            MetastoreError::IndexDoesNotExist { .. }
+        ));
+        cleanup_index(&metastore, index_id).await;
     }
 
-    #[allow(unused_variables)]
-    pub async fn test_metastore_index_metadata<MetastoreToTest: Metastore + DefaultForTest>() {
+    pub async fn test_metastore_update_indexing_settings<
+        MetastoreToTest: Metastore + DefaultForTest,
+    >() {
         let metastore = MetastoreToTest::default_for_test().await;
 
-        let index_id = "index-metadata-index";
-        let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
+        let index_id = "test-metastore-update-indexing-settings";
+        let index_uri = "ram://indexes/test-metastore-update-indexing-settings";
+        let index_metadata = IndexMetadata::for_test(index_id, index_uri);
+        metastore
+            .create_index(index_metadata.clone())
+            .await
+            .unwrap();
 
-        // Get a non-existent index metadata
-        let result = metastore
-            .index_metadata("non-existent-index")
+        let mut indexing_settings = index_metadata.indexing_settings.clone();
+        indexing_settings.split_num_docs_target = 1_000_000;
+        indexing_settings.merge_policy = MergePolicy {
+            merge_factor: 20,
+            max_merge_factor: 25,
+            ..Default::default()
+        };
+        metastore
+            .update_indexing_settings(index_id, indexing_settings.clone())
             .await
-            .unwrap_err();
-        assert!(matches!(result, MetastoreError::IndexDoesNotExist { .. }));
+            .unwrap();
+        let updated_index_metadata = metastore.index_metadata(index_id).await.unwrap();
+        assert_eq!(updated_index_metadata.indexing_settings, indexing_settings);
+        assert_eq!(updated_index_metadata.index_uri, index_uri);
+
+        assert!(matches!(
+            metastore
+                .update_indexing_settings("index-id-does-not-exist", indexing_settings)
+                .await
+                .unwrap_err(),
+            MetastoreError::IndexDoesNotExist { .. }
+        ));
+    }
+
+    pub async fn test_metastore_update_lifecycle_state<
+        MetastoreToTest: Metastore + DefaultForTest,
+    >() {
+        let metastore = MetastoreToTest::default_for_test().await;
 
+        let index_id = "test-metastore-update-lifecycle-state";
+        let index_uri = "ram://indexes/test-metastore-update-lifecycle-state";
+        let index_metadata = IndexMetadata::for_test(index_id, index_uri);
         metastore
             .create_index(index_metadata.clone())
             .await
             .unwrap();
+        assert_eq!(
+            metastore
+                .index_metadata(index_id)
+                .await
+                .unwrap()
+                .lifecycle_state,
+            IndexLifecycleState::default()
+        );
 
-        // Get an index metadata
-        let result = metastore.index_metadata(index_id).await.unwrap();
-        assert!(matches!(result, index_metadata));
+        let lifecycle_state = IndexLifecycleState {
+            phase: LifecyclePhase::ReadOnly,
+            generation: 3,
+            rollover_timestamp: Some(1_650_000_000),
+        };
+        metastore
+            .update_lifecycle_state(index_id, lifecycle_state.clone())
+            .await
+            .unwrap();
+        let updated_index_metadata = metastore.index_metadata(index_id).await.unwrap();
+        assert_eq!(updated_index_metadata.lifecycle_state, lifecycle_state);
+        assert_eq!(
+            updated_index_metadata.indexing_settings,
+            index_metadata.indexing_settings
+        );
 
+        assert!(matches!(
+            metastore
+                .update_lifecycle_state("index-id-does-not-exist", lifecycle_state)
+                .await
+                .unwrap_err(),
+            MetastoreError::IndexDoesNotExist { .. }
+        ));
         cleanup_index(&metastore, index_id).await;
     }
 
-    pub async fn test_metastore_stage_split<MetastoreToTest: Metastore + DefaultForTest>() {
+    pub async fn test_metastore_leases<MetastoreToTest: Metastore + DefaultForTest>() {
         let metastore = MetastoreToTest::default_for_test().await;
 
-        let current_timestamp = Utc::now().timestamp();
-
-        let index_id = "stage-split-index";
-        let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
-
-        let split_id = "stage-split-my-index-one";
-        let split_metadata = SplitMetadata {
-            split_id: split_id.to_string(),
-            num_docs: 1,
-            original_size_in_bytes: 2,
-            time_range: Some(RangeInclusive::new(0, 99)),
-            create_timestamp: current_timestamp,
-            footer_offsets: 1000..2000,
-            ..Default::default()
-        };
+        let lease_id = "test-metastore-leases";
+        let ttl = Duration::from_secs(60);
+        let lease = metastore
+            .acquire_lease(lease_id, "node-1", ttl)
+            .await
+            .unwrap();
+        assert_eq!(lease.lease_id, lease_id);
+        assert_eq!(lease.holder_id, "node-1");
+        assert!(lease.expiration_timestamp > Utc::now().timestamp());
+        let fencing_token = lease.fencing_token;
+
+        // Renewing the lease keeps the fencing token.
+        let renewed_lease = metastore
+            .acquire_lease(lease_id, "node-1", ttl)
+            .await
+            .unwrap();
+        assert_eq!(renewed_lease.fencing_token, fencing_token);
 
-        // Stage a split on a non-existent index
-        let result = metastore
-            .stage_split("non-existent-index", split_metadata.clone())
+        assert!(matches!(
+            metastore
+                .acquire_lease(lease_id, "node-2", ttl)
+                .await
+                .unwrap_err(),
+            MetastoreError::LeaseAlreadyHeld { holder_id, .. } if holder_id == "node-1"
+        ));
+        metastore
+            .check_lease(lease_id, fencing_token)
             .await
-            .unwrap_err();
-        assert!(matches!(result, MetastoreError::IndexDoesNotExist { .. }));
+            .unwrap();
 
+        // Releasing a lease held by another holder does nothing.
+        metastore.release_lease(lease_id, "node-2").await.unwrap();
         metastore
-            .create_index(index_metadata.clone())
+            .check_lease(lease_id, fencing_token)
             .await
             .unwrap();
 
-        // Stage a split on an index
-        let result = metastore
-            .stage_split(index_id, split_metadata.clone())
+        metastore.release_lease(lease_id, "node-1").await.unwrap();
+        assert!(matches!(
+            metastore
+                .check_lease(lease_id, fencing_token)
+                .await
+                .unwrap_err(),
+            MetastoreError::LeaseLost { .. }
+        ));
+
+        let lease = metastore
+            .acquire_lease(lease_id, "node-2", ttl)
+            .await
+            .unwrap();
+        assert_eq!(lease.holder_id, "node-2");
+        assert_eq!(lease.fencing_token, fencing_token + 1);
+        assert!(metastore
+            .check_lease(lease_id, fencing_token)
+            .await
+            .is_err());
+        metastore
+            .check_lease(lease_id, fencing_token + 1)
             .await
             .unwrap();
-        assert!(matches!(result, ()));
 
-        // Stage a existent-split on an index
-        let result = metastore
-            .stage_split(index_id, split_metadata.clone())
+        // A lease acquired with no TTL expires right away.
+        let lease = metastore
+            .acquire_lease("test-metastore-leases-no-ttl", "node-1", Duration::ZERO)
             .await
-            .unwrap_err();
-        assert!(matches!(result, MetastoreError::InternalError { .. }));
+            .unwrap();
+        assert!(metastore
+            .check_lease(&lease.lease_id, lease.fencing_token)
+            .await
+            .is_err());
 
-        cleanup_index(&metastore, index_id).await;
+        metastore.release_lease(lease_id, "node-2").await.unwrap();
     }
 
-    pub async fn test_metastore_publish_splits<MetastoreToTest: Metastore + DefaultForTest>() {
+    pub async fn test_metastore_split_read_leases<MetastoreToTest: Metastore + DefaultForTest>() {
         let metastore = MetastoreToTest::default_for_test().await;
 
-        let current_timestamp = Utc::now().timestamp();
+        let index_id = "split-read-leases-index";
+        let ttl = Duration::from_secs(60);
+        metastore
+            .acquire_split_read_leases(index_id, &["split-1", "split-2"], "search-1", ttl)
+            .await
+            .unwrap();
+        metastore
+            .acquire_split_read_leases(index_id, &["split-2", "split-3"], "search-2", ttl)
+            .await
+            .unwrap();
+        // Renewing a lease is idempotent.
+        metastore
+            .acquire_split_read_leases(index_id, &["split-2", "split-3"], "search-2", ttl)
+            .await
+            .unwrap();
+        let leased_split_ids = metastore.list_leased_splits(index_id).await.unwrap();
+        assert_eq!(
+            leased_split_ids,
+            to_hash_set(&["split-1", "split-2", "split-3"])
+        );
+        assert!(metastore
+            .list_leased_splits("index-id-does-not-exist")
+            .await
+            .unwrap()
+            .is_empty());
 
-        let index_id = "publish-splits-index";
-        let source_id = "publish-splits-source";
-        let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
+        metastore
+            .release_split_read_leases(index_id, "search-1")
+            .await
+            .unwrap();
+        let leased_split_ids = metastore.list_leased_splits(index_id).await.unwrap();
+        assert_eq!(leased_split_ids, to_hash_set(&["split-2", "split-3"]));
 
-        let split_id_1 = "publish-splits-index-one";
-        let split_metadata_1 = SplitMetadata {
-            footer_offsets: 1000..2000,
-            split_id: split_id_1.to_string(),
-            num_docs: 1,
-            original_size_in_bytes: 2,
-            time_range: Some(RangeInclusive::new(0, 99)),
-            create_timestamp: current_timestamp,
-            ..Default::default()
-        };
+        // Releasing leases that do not exist does nothing.
+        metastore
+            .release_split_read_leases(index_id, "search-1")
+            .await
+            .unwrap();
 
-        let split_id_2 = "publish-splits-index-two";
-        let split_metadata_2 = SplitMetadata {
+        // A lease acquired with no TTL expires right away.
+        metastore
+            .acquire_split_read_leases(index_id, &["split-4"], "search-3", Duration::ZERO)
+            .await
+            .unwrap();
+        let leased_split_ids = metastore.list_leased_splits(index_id).await.unwrap();
+        assert_eq!(leased_split_ids, to_hash_set(&["split-2", "split-3"]));
+
+        metastore
+            .release_split_read_leases(index_id, "search-2")
+            .await
+            .unwrap();
+        assert!(metastore
+            .list_leased_splits(index_id)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    pub async fn test_metastore_jobs<MetastoreToTest: Metastore + DefaultForTest>() {
+        let metastore = MetastoreToTest::default_for_test().await;
+
+        let now_timestamp = Utc::now().timestamp();
+        let new_job = |job_id: &str, index_id: &str| Job {
+            job_id: job_id.to_string(),
+            index_id: index_id.to_string(),
+            kind: JobKind::Export,
+            state: JobState::Running,
+            progress: 0.0,
+            node_id: "node-1".to_string(),
+            create_timestamp: now_timestamp,
+            update_timestamp: now_timestamp,
+            details: serde_json::Value::Null,
+            error: None,
+        };
+        metastore
+            .create_job(new_job("jobs-job-1", "jobs-index-1"))
+            .await
+            .unwrap();
+        metastore
+            .create_job(new_job("jobs-job-2", "jobs-index-2"))
+            .await
+            .unwrap();
+
+        let mut job = metastore.job("jobs-job-1").await.unwrap();
+        assert_eq!(job, new_job("jobs-job-1", "jobs-index-1"));
+        assert!(matches!(
+            metastore.job("jobs-job-does-not-exist").await.unwrap_err(),
+            MetastoreError::JobDoesNotExist { .. }
+        ));
+
+        job.progress = 50.0;
+        job.details = serde_json::json!({"numDocs": 10});
+        metastore.update_job(job.clone()).await.unwrap();
+        assert_eq!(metastore.job("jobs-job-1").await.unwrap(), job);
+
+        job.state = JobState::Cancelled;
+        metastore.update_job(job.clone()).await.unwrap();
+        job.state = JobState::Succeeded;
+        assert!(matches!(
+            metastore.update_job(job).await.unwrap_err(),
+            MetastoreError::JobNotRunning {
+                state: JobState::Cancelled,
+                ..
+            }
+        ));
+        assert!(matches!(
+            metastore
+                .update_job(new_job("jobs-job-does-not-exist", "jobs-index-1"))
+                .await
+                .unwrap_err(),
+            MetastoreError::JobDoesNotExist { .. }
+        ));
+
+        let job_ids: Vec<String> = metastore
+            .list_jobs(Some("jobs-index-1"))
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|job| job.job_id)
+            .collect();
+        assert_eq!(job_ids, vec!["jobs-job-1"]);
+        let job_ids: Vec<String> = metastore
+            .list_jobs(None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|job| job.job_id)
+            .filter(|job_id| job_id.starts_with("jobs-"))
+            .collect();
+        assert_eq!(job_ids, vec!["jobs-job-2", "jobs-job-1"]);
+    }
+
+    pub async fn test_metastore_create_index<MetastoreToTest: Metastore + DefaultForTest>() {
+        let metastore = MetastoreToTest::default_for_test().await;
+
+        let index_id = "create-index-index";
+        let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
+
+        // Create an index
+        let result = metastore
+            .create_index(index_metadata.clone())
+            .await
+            .unwrap();
+        assert!(matches!(result, ()));
+
+        cleanup_index(&metastore, index_id).await;
+    }
+
+    pub async fn test_metastore_delete_index<MetastoreToTest: Metastore + DefaultForTest>() {
+        let metastore = MetastoreToTest::default_for_test().await;
+
+        let index_id = "delte-index-index";
+        let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
+
+        // Delete a non-existent index
+        let result = metastore
+            .delete_index("non-existent-index")
+            .await
+            .unwrap_err();
+        assert!(matches!(result, MetastoreError::IndexDoesNotExist { .. }));
+
+        metastore
+            .create_index(index_metadata.clone())
+            .await
+            .unwrap();
+
+        // Delete an index
+        let result = metastore.delete_index(index_id).await.unwrap();
+        assert!(matches!(result, ()));
+    }
+
+    pub async fn test_metastore_create_indexes<MetastoreToTest: Metastore + DefaultForTest>() {
+        let metastore = MetastoreToTest::default_for_test().await;
+
+        let index_ids = ["create-indexes-index-1", "create-indexes-index-2"];
+        let index_metadatas: Vec<IndexMetadata> = index_ids
+            .iter()
+            .map(|index_id| IndexMetadata::for_test(index_id, "ram://indexes/my-index"))
+            .collect();
+
+        // Create indexes
+        metastore
+            .create_indexes(index_metadatas.clone())
+            .await
+            .unwrap();
+        for index_id in index_ids {
+            metastore.index_metadata(index_id).await.unwrap();
+        }
+
+        // None of the indexes is created if one of them already exists
+        let new_index_id = "create-indexes-index-3";
+        let new_index_metadata = IndexMetadata::for_test(new_index_id, "ram://indexes/my-index");
+        let result = metastore
+            .create_indexes(vec![new_index_metadata.clone(), index_metadatas[1].clone()])
+            .await
+            .unwrap_err();
+        assert!(matches!(result, MetastoreError::IndexAlreadyExists { .. }));
+        assert!(matches!(
+            metastore.index_metadata(new_index_id).await.unwrap_err(),
+            MetastoreError::IndexDoesNotExist { .. }
+        ));
+
+        // None of the indexes is created if an index is specified twice
+        let result = metastore
+            .create_indexes(vec![new_index_metadata.clone(), new_index_metadata])
+            .await
+            .unwrap_err();
+        assert!(matches!(result, MetastoreError::IndexAlreadyExists { .. }));
+        assert!(matches!(
+            metastore.index_metadata(new_index_id).await.unwrap_err(),
+            MetastoreError::IndexDoesNotExist { .. }
+        ));
+
+        for index_id in index_ids {
+            cleanup_index(&metastore, index_id).await;
+        }
+    }
+
+    pub async fn test_metastore_list_indexes_metadatas<
+        MetastoreToTest: Metastore + DefaultForTest,
+    >() {
+        let metastore = MetastoreToTest::default_for_test().await;
+
+        let index_ids = ["list-indexes-index-2", "list-indexes-index-1"];
+        for index_id in index_ids {
+            let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
+            metastore.create_index(index_metadata).await.unwrap();
+        }
+        // The metastore may be shared with the other tests.
+        let listed_index_ids = |index_metadatas: Vec<IndexMetadata>| {
+            index_metadatas
+                .into_iter()
+                .map(|index_metadata| index_metadata.index_id)
+                .filter(|index_id| index_id.starts_with("list-indexes-index-"))
+                .collect::<Vec<_>>()
+        };
+        let index_metadatas = metastore.list_indexes_metadatas().await.unwrap();
+        assert_eq!(
+            listed_index_ids(index_metadatas),
+            vec!["list-indexes-index-1", "list-indexes-index-2"]
+        );
+
+        metastore.delete_index(index_ids[0]).await.unwrap();
+        let index_metadatas = metastore.list_indexes_metadatas().await.unwrap();
+        assert_eq!(
+            listed_index_ids(index_metadatas),
+            vec!["list-indexes-index-1"]
+        );
+
+        cleanup_index(&metastore, index_ids[1]).await;
+    }
+
+    pub async fn test_metastore_delete_indexes<MetastoreToTest: Metastore + DefaultForTest>() {
+        let metastore = MetastoreToTest::default_for_test().await;
+
+        let index_ids = ["delete-indexes-index-1", "delete-indexes-index-2"];
+        let index_metadatas: Vec<IndexMetadata> = index_ids
+            .iter()
+            .map(|index_id| IndexMetadata::for_test(index_id, "ram://indexes/my-index"))
+            .collect();
+        metastore.create_indexes(index_metadatas).await.unwrap();
+
+        // None of the indexes is deleted if one of them does not exist
+        let result = metastore
+            .delete_indexes(&[index_ids[0], "non-existent-index"])
+            .await
+            .unwrap_err();
+        assert!(matches!(result, MetastoreError::IndexDoesNotExist { .. }));
+        metastore.index_metadata(index_ids[0]).await.unwrap();
+
+        // None of the indexes is deleted if an index is specified twice
+        let result = metastore
+            .delete_indexes(&[index_ids[0], index_ids[0]])
+            .await
+            .unwrap_err();
+        assert!(matches!(result, MetastoreError::IndexDoesNotExist { .. }));
+        metastore.index_metadata(index_ids[0]).await.unwrap();
+
+        // Delete indexes
+        metastore.delete_indexes(&index_ids).await.unwrap();
+        for index_id in index_ids {
+            assert!(matches!(
+                metastore.index_metadata(index_id).await.unwrap_err(),
+                MetastoreError::IndexDoesNotExist { .. }
+            ));
+        }
+    }
+
+    #[allow(unused_variables)]
+    pub async fn test_metastore_index_metadata<MetastoreToTest: Metastore + DefaultForTest>() {
+        let metastore = MetastoreToTest::default_for_test().await;
+
+        let index_id = "index-metadata-index";
+        let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
+
+        // Get a non-existent index metadata
+        let result = metastore
+            .index_metadata("non-existent-index")
+            .await
+            .unwrap_err();
+        assert!(matches!(result, MetastoreError::IndexDoesNotExist { .. }));
+
+        metastore
+            .create_index(index_metadata.clone())
+            .await
+            .unwrap();
+
+        // Get an index metadata
+        let result = metastore.index_metadata(index_id).await.unwrap();
+        assert!(matches!(result, index_metadata));
+
+        cleanup_index(&metastore, index_id).await;
+    }
+
+    pub async fn test_metastore_stage_split<MetastoreToTest: Metastore + DefaultForTest>() {
+        let metastore = MetastoreToTest::default_for_test().await;
+
+        let current_timestamp = Utc::now().timestamp();
+
+        let index_id = "stage-split-index";
+        let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
+
+        let split_id = "stage-split-my-index-one";
+        let split_metadata = SplitMetadata {
+            split_id: split_id.to_string(),
+            num_docs: 1,
+            original_size_in_bytes: 2,
+            time_range: Some(RangeInclusive::new(0, 99)),
+            create_timestamp: current_timestamp,
+            footer_offsets: 1000..2000,
+            ..Default::default()
+        };
+
+        // Stage a split on a non-existent index
+        let result = metastore
+            .stage_split("non-existent-index", split_metadata.clone())
+            .await
+            .unwrap_err();
+        assert!(matches!(result, MetastoreError::IndexDoesNotExist { .. }));
+
+        metastore
+            .create_index(index_metadata.clone())
+            .await
+            .unwrap();
+
+        // Stage a split on an index
+        let result = metastore
+            .stage_split(index_id, split_metadata.clone())
+            .await
+            .unwrap();
+        assert!(matches!(result, ()));
+
+        // Stage a existent-split on an index
+        let result = metastore
+            .stage_split(index_id, split_metadata.clone())
+            .await
+            .unwrap_err();
+        assert!(matches!(result, MetastoreError::InternalError { .. }));
+
+        cleanup_index(&metastore, index_id).await;
+    }
+
+    pub async fn test_metastore_publish_splits<MetastoreToTest: Metastore + DefaultForTest>() {
+        let metastore = MetastoreToTest::default_for_test().await;
+
+        let current_timestamp = Utc::now().timestamp();
+
+        let index_id = "publish-splits-index";
+        let source_id = "publish-splits-source";
+        let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
+
+        let split_id_1 = "publish-splits-index-one";
+        let split_metadata_1 = SplitMetadata {
+            footer_offsets: 1000..2000,
+            split_id: split_id_1.to_string(),
+            num_docs: 1,
+            original_size_in_bytes: 2,
+            time_range: Some(RangeInclusive::new(0, 99)),
+            create_timestamp: current_timestamp,
+            ..Default::default()
+        };
+
+        let split_id_2 = "publish-splits-index-two";
+        let split_metadata_2 = SplitMetadata {
             footer_offsets: 1000..2000,
             split_id: split_id_2.to_string(),
             num_docs: 5,
@@ -676,51 +1295,163 @@ pub mod test_suite {
         }
     }
 
-    pub async fn test_metastore_replace_splits<MetastoreToTest: Metastore + DefaultForTest>() {
+    pub async fn test_metastore_publish_multi_source_splits<
+        MetastoreToTest: Metastore + DefaultForTest,
+    >() {
         let metastore = MetastoreToTest::default_for_test().await;
 
         let current_timestamp = Utc::now().timestamp();
 
-        let index_id = "replace_splits-index";
-        let source_id = "replace_splits-source";
+        let index_id = "publish-multi-source-splits-index";
         let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
 
-        let split_id_1 = "replace_splits-index-one";
+        let split_id_1 = "publish-multi-source-splits-index-one";
         let split_metadata_1 = SplitMetadata {
             footer_offsets: 1000..2000,
             split_id: split_id_1.to_string(),
             num_docs: 1,
             original_size_in_bytes: 2,
-            time_range: None,
+            time_range: Some(RangeInclusive::new(0, 99)),
             create_timestamp: current_timestamp,
             ..Default::default()
         };
 
-        let split_id_2 = "replace_splits-index-two";
+        let split_id_2 = "publish-multi-source-splits-index-two";
         let split_metadata_2 = SplitMetadata {
             footer_offsets: 1000..2000,
             split_id: split_id_2.to_string(),
             num_docs: 5,
             original_size_in_bytes: 6,
-            time_range: None,
-            create_timestamp: current_timestamp,
-            ..Default::default()
-        };
-
-        let split_id_3 = "replace_splits-index-three";
-        let split_metadata_3 = SplitMetadata {
-            footer_offsets: 1000..2000,
-            split_id: split_id_3.to_string(),
-            num_docs: 5,
-            original_size_in_bytes: 6,
-            time_range: None,
+            time_range: Some(RangeInclusive::new(30, 99)),
             create_timestamp: current_timestamp,
             ..Default::default()
         };
 
-        // Replace splits on a non-existent index
-        {
-            let result = metastore
+        metastore
+            .create_index(index_metadata.clone())
+            .await
+            .unwrap();
+        metastore
+            .stage_split(index_id, split_metadata_1.clone())
+            .await
+            .unwrap();
+        metastore
+            .stage_split(index_id, split_metadata_2.clone())
+            .await
+            .unwrap();
+
+        // Publish a split fed by two sources
+        {
+            let mut index_checkpoint_delta =
+                IndexCheckpointDelta::for_source("source-a", CheckpointDelta::from(0..5));
+            index_checkpoint_delta
+                .extend("source-b", CheckpointDelta::from(0..3))
+                .unwrap();
+            metastore
+                .publish_multi_source_splits(index_id, &[split_id_1], index_checkpoint_delta)
+                .await
+                .unwrap();
+
+            let index_checkpoint = metastore.index_metadata(index_id).await.unwrap().checkpoint;
+            assert_eq!(
+                index_checkpoint.source_checkpoint("source-a"),
+                Some(&SourceCheckpoint::from_iter(vec![(
+                    Default::default(),
+                    4u64.into()
+                )]))
+            );
+            assert_eq!(
+                index_checkpoint.source_checkpoint("source-b"),
+                Some(&SourceCheckpoint::from_iter(vec![(
+                    Default::default(),
+                    2u64.into()
+                )]))
+            );
+        }
+
+        // Publish a split with a delta incompatible with one of the sources
+        {
+            let mut index_checkpoint_delta =
+                IndexCheckpointDelta::for_source("source-a", CheckpointDelta::from(5..7));
+            index_checkpoint_delta
+                .extend("source-b", CheckpointDelta::from(1..2))
+                .unwrap();
+            let result = metastore
+                .publish_multi_source_splits(index_id, &[split_id_2], index_checkpoint_delta)
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                result,
+                MetastoreError::IncompatibleCheckpointDelta(_)
+            ));
+
+            let index_checkpoint = metastore.index_metadata(index_id).await.unwrap().checkpoint;
+            assert_eq!(
+                index_checkpoint.source_checkpoint("source-a"),
+                Some(&SourceCheckpoint::from_iter(vec![(
+                    Default::default(),
+                    4u64.into()
+                )]))
+            );
+            let split_2_state = metastore
+                .list_all_splits(index_id)
+                .await
+                .unwrap()
+                .into_iter()
+                .find(|split| split.split_id() == split_id_2)
+                .unwrap()
+                .split_state;
+            assert_eq!(split_2_state, SplitState::Staged);
+        }
+
+        cleanup_index(&metastore, index_id).await;
+    }
+
+    pub async fn test_metastore_replace_splits<MetastoreToTest: Metastore + DefaultForTest>() {
+        let metastore = MetastoreToTest::default_for_test().await;
+
+        let current_timestamp = Utc::now().timestamp();
+
+        let index_id = "replace_splits-index";
+        let source_id = "replace_splits-source";
+        let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
+
+        let split_id_1 = "replace_splits-index-one";
+        let split_metadata_1 = SplitMetadata {
+            footer_offsets: 1000..2000,
+            split_id: split_id_1.to_string(),
+            num_docs: 1,
+            original_size_in_bytes: 2,
+            time_range: None,
+            create_timestamp: current_timestamp,
+            ..Default::default()
+        };
+
+        let split_id_2 = "replace_splits-index-two";
+        let split_metadata_2 = SplitMetadata {
+            footer_offsets: 1000..2000,
+            split_id: split_id_2.to_string(),
+            num_docs: 5,
+            original_size_in_bytes: 6,
+            time_range: None,
+            create_timestamp: current_timestamp,
+            ..Default::default()
+        };
+
+        let split_id_3 = "replace_splits-index-three";
+        let split_metadata_3 = SplitMetadata {
+            footer_offsets: 1000..2000,
+            split_id: split_id_3.to_string(),
+            num_docs: 5,
+            original_size_in_bytes: 6,
+            time_range: None,
+            create_timestamp: current_timestamp,
+            ..Default::default()
+        };
+
+        // Replace splits on a non-existent index
+        {
+            let result = metastore
                 .replace_splits(
                     "non-existent-index",
                     &["non-existent-split-one"],
@@ -1075,136 +1806,582 @@ pub mod test_suite {
             metastore
                 .publish_splits(
                     index_id,
-                    source_id,
-                    &[split_id_1],
-                    CheckpointDelta::from(0..5),
+                    source_id,
+                    &[split_id_1],
+                    CheckpointDelta::from(0..5),
+                )
+                .await
+                .unwrap();
+
+            let result = metastore
+                .delete_splits(index_id, &[split_id_1])
+                .await
+                .unwrap_err();
+            assert!(matches!(result, MetastoreError::SplitsNotDeletable { .. }));
+
+            cleanup_index(&metastore, index_id).await;
+        }
+    }
+
+    pub async fn test_metastore_fenced_split_mutations<
+        MetastoreToTest: Metastore + DefaultForTest,
+    >() {
+        let metastore = MetastoreToTest::default_for_test().await;
+
+        let current_timestamp = Utc::now().timestamp();
+
+        let index_id = "fenced-split-mutations-index";
+        let lease_id = "test-metastore-fenced-split-mutations";
+        let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
+
+        let split_id_1 = "fenced-split-mutations-index-one";
+        let split_id_2 = "fenced-split-mutations-index-two";
+        metastore
+            .create_index(index_metadata.clone())
+            .await
+            .unwrap();
+        for split_id in [split_id_1, split_id_2] {
+            let split_metadata = SplitMetadata {
+                footer_offsets: 1000..2000,
+                split_id: split_id.to_string(),
+                num_docs: 1,
+                original_size_in_bytes: 2,
+                time_range: Some(RangeInclusive::new(0, 99)),
+                create_timestamp: current_timestamp,
+                ..Default::default()
+            };
+            metastore
+                .stage_split(index_id, split_metadata)
+                .await
+                .unwrap();
+        }
+        let ttl = Duration::from_secs(60);
+        let old_fence = metastore
+            .acquire_lease(lease_id, "node-1", ttl)
+            .await
+            .unwrap()
+            .fence();
+
+        metastore
+            .mark_splits_for_deletion_fenced(index_id, &[split_id_1], &old_fence)
+            .await
+            .unwrap();
+        metastore
+            .delete_splits_fenced(index_id, &[split_id_1], &old_fence)
+            .await
+            .unwrap();
+
+        // Once the lease is held by another holder, the old fence no longer applies.
+        metastore.release_lease(lease_id, "node-1").await.unwrap();
+        let new_fence = metastore
+            .acquire_lease(lease_id, "node-2", ttl)
+            .await
+            .unwrap()
+            .fence();
+
+        let error = metastore
+            .mark_splits_for_deletion_fenced(index_id, &[split_id_2], &old_fence)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, MetastoreError::LeaseLost { .. }));
+        let error = metastore
+            .delete_splits_fenced(index_id, &[split_id_2], &old_fence)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, MetastoreError::LeaseLost { .. }));
+
+        let splits = metastore.list_all_splits(index_id).await.unwrap();
+        assert_eq!(splits.len(), 1);
+        assert_eq!(splits[0].split_id(), split_id_2);
+        assert_eq!(splits[0].split_state, SplitState::Staged);
+
+        metastore
+            .mark_splits_for_deletion_fenced(index_id, &[split_id_2], &new_fence)
+            .await
+            .unwrap();
+        metastore
+            .delete_splits_fenced(index_id, &[split_id_2], &new_fence)
+            .await
+            .unwrap();
+        let splits = metastore.list_all_splits(index_id).await.unwrap();
+        assert!(splits.is_empty());
+
+        metastore.release_lease(lease_id, "node-2").await.unwrap();
+        cleanup_index(&metastore, index_id).await;
+    }
+
+    pub async fn test_metastore_list_all_splits<MetastoreToTest: Metastore + DefaultForTest>() {
+        let metastore = MetastoreToTest::default_for_test().await;
+
+        let current_timestamp = Utc::now().timestamp();
+
+        let index_id = "list-all-splits-index";
+        let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
+
+        let split_id_1 = "list-all-splits-index-one";
+        let split_metadata_1 = SplitMetadata {
+            footer_offsets: 1000..2000,
+            split_id: split_id_1.to_string(),
+            num_docs: 1,
+            original_size_in_bytes: 2,
+            time_range: Some(RangeInclusive::new(0, 99)),
+            create_timestamp: current_timestamp,
+            ..Default::default()
+        };
+
+        let split_metadata_2 = SplitMetadata {
+            footer_offsets: 1000..2000,
+            split_id: "list-all-splits-index-two".to_string(),
+            num_docs: 1,
+            original_size_in_bytes: 2,
+            time_range: Some(RangeInclusive::new(100, 199)),
+            create_timestamp: current_timestamp,
+            ..Default::default()
+        };
+
+        let split_metadata_3 = SplitMetadata {
+            footer_offsets: 1000..2000,
+            split_id: "list-all-splits-index-three".to_string(),
+            num_docs: 1,
+            original_size_in_bytes: 2,
+            time_range: Some(RangeInclusive::new(200, 299)),
+            create_timestamp: current_timestamp,
+            ..Default::default()
+        };
+
+        let split_metadata_4 = SplitMetadata {
+            footer_offsets: 1000..2000,
+            split_id: "list-all-splits-index-four".to_string(),
+            num_docs: 1,
+            original_size_in_bytes: 2,
+            time_range: Some(RangeInclusive::new(300, 399)),
+            create_timestamp: current_timestamp,
+            ..Default::default()
+        };
+
+        let split_metadata_5 = SplitMetadata {
+            footer_offsets: 1000..2000,
+            split_id: "list-all-splits-index-five".to_string(),
+            num_docs: 1,
+            original_size_in_bytes: 2,
+            time_range: None,
+            create_timestamp: current_timestamp,
+            ..Default::default()
+        };
+
+        // List all splits on a non-existent index
+        {
+            let result = metastore
+                .list_all_splits("non-existent-index")
+                .await
+                .unwrap_err();
+            assert!(matches!(result, MetastoreError::IndexDoesNotExist { .. }));
+        }
+
+        // List all splits on an index
+        {
+            metastore
+                .create_index(index_metadata.clone())
+                .await
+                .unwrap();
+
+            metastore
+                .stage_split(index_id, split_metadata_1.clone())
+                .await
+                .unwrap();
+
+            metastore
+                .stage_split(index_id, split_metadata_2.clone())
+                .await
+                .unwrap();
+
+            metastore
+                .stage_split(index_id, split_metadata_3.clone())
+                .await
+                .unwrap();
+
+            metastore
+                .stage_split(index_id, split_metadata_4.clone())
+                .await
+                .unwrap();
+
+            metastore
+                .stage_split(index_id, split_metadata_5.clone())
+                .await
+                .unwrap();
+
+            let splits = metastore.list_all_splits(index_id).await.unwrap();
+            let split_ids: HashSet<String> = splits
+                .into_iter()
+                .map(|metadata| metadata.split_id().to_string())
+                .collect();
+            assert_eq!(split_ids.contains("list-all-splits-index-one"), true);
+            assert_eq!(split_ids.contains("list-all-splits-index-two"), true);
+            assert_eq!(split_ids.contains("list-all-splits-index-three"), true);
+            assert_eq!(split_ids.contains("list-all-splits-index-four"), true);
+            assert_eq!(split_ids.contains("list-all-splits-index-five"), true);
+
+            cleanup_index(&metastore, index_id).await;
+        }
+    }
+
+    pub async fn test_metastore_list_splits_updated_since<
+        MetastoreToTest: Metastore + DefaultForTest,
+    >() {
+        let metastore = MetastoreToTest::default_for_test().await;
+
+        let current_timestamp = Utc::now().timestamp();
+
+        let index_id = "list-splits-updated-since-index";
+        let source_id = "list-splits-updated-since-source";
+        let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
+
+        let split_id_1 = "list-splits-updated-since-one";
+        let split_metadata_1 = SplitMetadata {
+            footer_offsets: 1000..2000,
+            split_id: split_id_1.to_string(),
+            num_docs: 1,
+            original_size_in_bytes: 2,
+            time_range: Some(RangeInclusive::new(0, 99)),
+            create_timestamp: current_timestamp,
+            ..Default::default()
+        };
+
+        let split_id_2 = "list-splits-updated-since-two";
+        let split_metadata_2 = SplitMetadata {
+            footer_offsets: 1000..2000,
+            split_id: split_id_2.to_string(),
+            num_docs: 1,
+            original_size_in_bytes: 2,
+            time_range: Some(RangeInclusive::new(100, 199)),
+            create_timestamp: current_timestamp,
+            ..Default::default()
+        };
+
+        // List splits on a non-existent index
+        {
+            let result = metastore
+                .list_splits_updated_since("non-existent-index", 0)
+                .await
+                .unwrap_err();
+            assert!(matches!(result, MetastoreError::IndexDoesNotExist { .. }));
+        }
+
+        metastore
+            .create_index(index_metadata.clone())
+            .await
+            .unwrap();
+
+        metastore
+            .stage_split(index_id, split_metadata_1.clone())
+            .await
+            .unwrap();
+
+        metastore
+            .stage_split(index_id, split_metadata_2.clone())
+            .await
+            .unwrap();
+
+        let splits = metastore
+            .list_splits_updated_since(index_id, 0)
+            .await
+            .unwrap();
+        assert_eq!(splits.len(), 2);
+
+        let splits = metastore
+            .list_splits_updated_since(index_id, current_timestamp + 3600)
+            .await
+            .unwrap();
+        assert!(splits.is_empty());
+
+        let staged_at = splits_update_timestamp(&metastore, index_id).await;
+
+        // wait for 1s and publish one split: only this split is reported as updated.
+        sleep(Duration::from_secs(1)).await;
+        metastore
+            .publish_splits(
+                index_id,
+                source_id,
+                &[split_id_1],
+                CheckpointDelta::from(0..5),
+            )
+            .await
+            .unwrap();
+
+        let splits = metastore
+            .list_splits_updated_since(index_id, staged_at + 1)
+            .await
+            .unwrap();
+        assert_eq!(splits.len(), 1);
+        assert_eq!(splits[0].split_id(), split_id_1);
+        assert_eq!(splits[0].split_state, SplitState::Published);
+
+        cleanup_index(&metastore, index_id).await;
+    }
+
+    async fn splits_update_timestamp(metastore: &dyn Metastore, index_id: &str) -> i64 {
+        metastore
+            .list_all_splits(index_id)
+            .await
+            .unwrap()
+            .iter()
+            .map(|split| split.update_timestamp)
+            .max()
+            .unwrap_or_default()
+    }
+
+    async fn list_page_split_ids(
+        metastore: &dyn Metastore,
+        index_id: &str,
+        time_range_opt: Option<Range<i64>>,
+        tags: Option<TagFilterAst>,
+        after_split_id_opt: Option<&str>,
+    ) -> Vec<String> {
+        metastore
+            .list_splits_page(
+                index_id,
+                SplitState::Staged,
+                time_range_opt,
+                tags,
+                after_split_id_opt.map(ToString::to_string),
+                2,
+            )
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|split| split.split_id().to_string())
+            .collect()
+    }
+
+    pub async fn test_metastore_list_splits_page<MetastoreToTest: Metastore + DefaultForTest>() {
+        let metastore = MetastoreToTest::default_for_test().await;
+
+        let current_timestamp = Utc::now().timestamp();
+
+        let index_id = "list-splits-page-index";
+        let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
+
+        // List a page of splits on a non-existent index
+        {
+            let result = metastore
+                .list_splits_page(
+                    "non-existent-index",
+                    SplitState::Staged,
+                    None,
+                    None,
+                    None,
+                    2,
+                )
+                .await
+                .unwrap_err();
+            assert!(matches!(result, MetastoreError::IndexDoesNotExist { .. }));
+        }
+
+        metastore
+            .create_index(index_metadata.clone())
+            .await
+            .unwrap();
+
+        for split_ord in 0..5 {
+            let split_metadata = SplitMetadata {
+                footer_offsets: 1000..2000,
+                split_id: format!("list-splits-page-index-{}", split_ord),
+                num_docs: 1,
+                original_size_in_bytes: 2,
+                time_range: Some(RangeInclusive::new(split_ord * 100, split_ord * 100 + 99)),
+                create_timestamp: current_timestamp,
+                tags: to_set(&[format!("tenant:{}", split_ord % 2).as_str()]),
+                ..Default::default()
+            };
+            metastore
+                .stage_split(index_id, split_metadata)
+                .await
+                .unwrap();
+        }
+
+        // List splits page by page
+        {
+            assert_eq!(
+                list_page_split_ids(&metastore, index_id, None, None, None).await,
+                vec!["list-splits-page-index-0", "list-splits-page-index-1"]
+            );
+            assert_eq!(
+                list_page_split_ids(
+                    &metastore,
+                    index_id,
+                    None,
+                    None,
+                    Some("list-splits-page-index-1")
+                )
+                .await,
+                vec!["list-splits-page-index-2", "list-splits-page-index-3"]
+            );
+            assert_eq!(
+                list_page_split_ids(
+                    &metastore,
+                    index_id,
+                    None,
+                    None,
+                    Some("list-splits-page-index-3")
                 )
-                .await
-                .unwrap();
-
-            let result = metastore
-                .delete_splits(index_id, &[split_id_1])
-                .await
-                .unwrap_err();
-            assert!(matches!(result, MetastoreError::SplitsNotDeletable { .. }));
+                .await,
+                vec!["list-splits-page-index-4"]
+            );
+            assert!(list_page_split_ids(
+                &metastore,
+                index_id,
+                None,
+                None,
+                Some("list-splits-page-index-4")
+            )
+            .await
+            .is_empty());
+        }
 
-            cleanup_index(&metastore, index_id).await;
+        // List splits page by page with time range and tags filters
+        {
+            assert_eq!(
+                list_page_split_ids(&metastore, index_id, Some(150..450), None, None).await,
+                vec!["list-splits-page-index-1", "list-splits-page-index-2"]
+            );
+            assert_eq!(
+                list_page_split_ids(
+                    &metastore,
+                    index_id,
+                    Some(150..450),
+                    None,
+                    Some("list-splits-page-index-2")
+                )
+                .await,
+                vec!["list-splits-page-index-3", "list-splits-page-index-4"]
+            );
+            assert_eq!(
+                list_page_split_ids(&metastore, index_id, None, Some(tag("tenant:1")), None).await,
+                vec!["list-splits-page-index-1", "list-splits-page-index-3"]
+            );
+            assert_eq!(
+                list_page_split_ids(
+                    &metastore,
+                    index_id,
+                    Some(0..250),
+                    Some(tag("tenant:0")),
+                    None
+                )
+                .await,
+                vec!["list-splits-page-index-0", "list-splits-page-index-2"]
+            );
         }
+
+        cleanup_index(&metastore, index_id).await;
     }
 
-    pub async fn test_metastore_list_all_splits<MetastoreToTest: Metastore + DefaultForTest>() {
+    pub async fn test_metastore_list_splits_stream<MetastoreToTest: Metastore + DefaultForTest>() {
         let metastore = MetastoreToTest::default_for_test().await;
 
         let current_timestamp = Utc::now().timestamp();
 
-        let index_id = "list-all-splits-index";
+        let index_id = "list-splits-stream-index";
         let index_metadata = IndexMetadata::for_test(index_id, "ram://indexes/my-index");
 
-        let split_id_1 = "list-all-splits-index-one";
-        let split_metadata_1 = SplitMetadata {
-            footer_offsets: 1000..2000,
-            split_id: split_id_1.to_string(),
-            num_docs: 1,
-            original_size_in_bytes: 2,
-            time_range: Some(RangeInclusive::new(0, 99)),
-            create_timestamp: current_timestamp,
-            ..Default::default()
-        };
-
-        let split_metadata_2 = SplitMetadata {
-            footer_offsets: 1000..2000,
-            split_id: "list-all-splits-index-two".to_string(),
-            num_docs: 1,
-            original_size_in_bytes: 2,
-            time_range: Some(RangeInclusive::new(100, 199)),
-            create_timestamp: current_timestamp,
-            ..Default::default()
-        };
-
-        let split_metadata_3 = SplitMetadata {
-            footer_offsets: 1000..2000,
-            split_id: "list-all-splits-index-three".to_string(),
-            num_docs: 1,
-            original_size_in_bytes: 2,
-            time_range: Some(RangeInclusive::new(200, 299)),
-            create_timestamp: current_timestamp,
-            ..Default::default()
-        };
-
-        let split_metadata_4 = SplitMetadata {
-            footer_offsets: 1000..2000,
-            split_id: "list-all-splits-index-four".to_string(),
-            num_docs: 1,
-            original_size_in_bytes: 2,
-            time_range: Some(RangeInclusive::new(300, 399)),
-            create_timestamp: current_timestamp,
-            ..Default::default()
-        };
-
-        let split_metadata_5 = SplitMetadata {
-            footer_offsets: 1000..2000,
-            split_id: "list-all-splits-index-five".to_string(),
-            num_docs: 1,
-            original_size_in_bytes: 2,
-            time_range: None,
-            create_timestamp: current_timestamp,
-            ..Default::default()
-        };
-
-        // List all splits on a non-existent index
+        // List splits on a non-existent index
         {
             let result = metastore
-                .list_all_splits("non-existent-index")
-                .await
-                .unwrap_err();
-            assert!(matches!(result, MetastoreError::IndexDoesNotExist { .. }));
+                .list_splits_stream("non-existent-index", SplitState::Staged, None, None, 2)
+                .await;
+            assert!(matches!(
+                result,
+                Err(MetastoreError::IndexDoesNotExist { .. })
+            ));
         }
 
-        // List all splits on an index
+        metastore
+            .create_index(index_metadata.clone())
+            .await
+            .unwrap();
+
+        // List splits on an empty index
         {
-            metastore
-                .create_index(index_metadata.clone())
+            let pages: Vec<Vec<Split>> = metastore
+                .list_splits_stream(index_id, SplitState::Staged, None, None, 2)
                 .await
-                .unwrap();
-
-            metastore
-                .stage_split(index_id, split_metadata_1.clone())
+                .unwrap()
+                .try_collect()
                 .await
                 .unwrap();
+            assert!(pages.is_empty());
+        }
 
+        for split_ord in 0..5 {
+            let split_metadata = SplitMetadata {
+                footer_offsets: 1000..2000,
+                split_id: format!("list-splits-stream-index-{}", split_ord),
+                num_docs: 1,
+                original_size_in_bytes: 2,
+                time_range: Some(RangeInclusive::new(split_ord * 100, split_ord * 100 + 99)),
+                create_timestamp: current_timestamp,
+                ..Default::default()
+            };
             metastore
-                .stage_split(index_id, split_metadata_2.clone())
+                .stage_split(index_id, split_metadata)
                 .await
                 .unwrap();
+        }
 
-            metastore
-                .stage_split(index_id, split_metadata_3.clone())
+        // List splits by pages
+        {
+            let pages: Vec<Vec<Split>> = metastore
+                .list_splits_stream(index_id, SplitState::Staged, None, None, 2)
                 .await
-                .unwrap();
-
-            metastore
-                .stage_split(index_id, split_metadata_4.clone())
+                .unwrap()
+                .try_collect()
                 .await
                 .unwrap();
+            assert_eq!(
+                pages.iter().map(Vec::len).collect::<Vec<_>>(),
+                vec![2, 2, 1]
+            );
+            let split_ids: HashSet<String> = pages
+                .into_iter()
+                .flatten()
+                .map(|split| split.split_id().to_string())
+                .collect();
+            assert_eq!(
+                split_ids,
+                to_hash_set(&[
+                    "list-splits-stream-index-0",
+                    "list-splits-stream-index-1",
+                    "list-splits-stream-index-2",
+                    "list-splits-stream-index-3",
+                    "list-splits-stream-index-4",
+                ])
+            );
+        }
 
-            metastore
-                .stage_split(index_id, split_metadata_5.clone())
+        // List splits by pages with a time range filter
+        {
+            let pages: Vec<Vec<Split>> = metastore
+                .list_splits_stream(index_id, SplitState::Staged, Some(150..350), None, 2)
+                .await
+                .unwrap()
+                .try_collect()
                 .await
                 .unwrap();
-
-            let splits = metastore.list_all_splits(index_id).await.unwrap();
-            let split_ids: HashSet<String> = splits
+            let split_ids: HashSet<String> = pages
                 .into_iter()
-                .map(|metadata| metadata.split_id().to_string())
+                .flatten()
+                .map(|split| split.split_id().to_string())
                 .collect();
-            assert_eq!(split_ids.contains("list-all-splits-index-one"), true);
-            assert_eq!(split_ids.contains("list-all-splits-index-two"), true);
-            assert_eq!(split_ids.contains("list-all-splits-index-three"), true);
-            assert_eq!(split_ids.contains("list-all-splits-index-four"), true);
-            assert_eq!(split_ids.contains("list-all-splits-index-five"), true);
-
-            cleanup_index(&metastore, index_id).await;
+            assert_eq!(
+                split_ids,
+                to_hash_set(&[
+                    "list-splits-stream-index-1",
+                    "list-splits-stream-index-2",
+                    "list-splits-stream-index-3",
+                ])
+            );
         }
+
+        cleanup_index(&metastore, index_id).await;
     }
 
     pub async fn test_metastore_list_splits<MetastoreToTest: Metastore + DefaultForTest>() {
@@ -1225,6 +2402,9 @@ pub mod test_suite {
             create_timestamp: current_timestamp,
             tags: to_set(&["tag!", "tag:foo", "tag:bar"]),
             demux_num_ops: 0,
+            sort_by: None,
+            field_stats: Default::default(),
+            replaced_split_ids: Default::default(),
         };
 
         let split_metadata_2 = SplitMetadata {
@@ -1236,6 +2416,9 @@ pub mod test_suite {
             create_timestamp: current_timestamp,
             tags: to_set(&["tag!", "tag:bar"]),
             demux_num_ops: 0,
+            sort_by: None,
+            field_stats: Default::default(),
+            replaced_split_ids: Default::default(),
         };
 
         let split_metadata_3 = SplitMetadata {
@@ -1247,6 +2430,9 @@ pub mod test_suite {
             create_timestamp: current_timestamp,
             tags: to_set(&["tag!", "tag:foo", "tag:baz"]),
             demux_num_ops: 0,
+            sort_by: None,
+            field_stats: Default::default(),
+            replaced_split_ids: Default::default(),
         };
 
         let split_metadata_4 = SplitMetadata {
@@ -1258,6 +2444,9 @@ pub mod test_suite {
             create_timestamp: current_timestamp,
             tags: to_set(&["tag!", "tag:foo"]),
             demux_num_ops: 0,
+            sort_by: None,
+            field_stats: Default::default(),
+            replaced_split_ids: Default::default(),
         };
 
         let split_metadata_5 = SplitMetadata {
@@ -1269,6 +2458,9 @@ pub mod test_suite {
             create_timestamp: current_timestamp,
             tags: to_set(&["tag!", "tag:baz", "tag:biz"]),
             demux_num_ops: 0,
+            sort_by: None,
+            field_stats: Default::default(),
+            replaced_split_ids: Default::default(),
         };
 
         // List all splits on a non-existent index
@@ -1684,6 +2876,9 @@ pub mod test_suite {
                 create_timestamp: current_timestamp,
                 tags: to_set(&[]),
                 demux_num_ops: 0,
+                sort_by: None,
+                field_stats: Default::default(),
+                replaced_split_ids: Default::default(),
             };
             metastore
                 .stage_split(index_id, split_metadata_6.clone())
@@ -1863,6 +3058,21 @@ macro_rules! metastore_test_suite {
                 crate::tests::test_suite::test_metastore_delete_index::<$metastore_type>().await;
             }
 
+            #[tokio::test]
+            async fn test_metastore_create_indexes() {
+                crate::tests::test_suite::test_metastore_create_indexes::<$metastore_type>().await;
+            }
+
+            #[tokio::test]
+            async fn test_metastore_list_indexes_metadatas() {
+                crate::tests::test_suite::test_metastore_list_indexes_metadatas::<$metastore_type>().await;
+            }
+
+            #[tokio::test]
+            async fn test_metastore_delete_indexes() {
+                crate::tests::test_suite::test_metastore_delete_indexes::<$metastore_type>().await;
+            }
+
             #[tokio::test]
             async fn test_metastore_index_metadata() {
                 crate::tests::test_suite::test_metastore_index_metadata::<$metastore_type>().await;
@@ -1878,6 +3088,14 @@ macro_rules! metastore_test_suite {
                 crate::tests::test_suite::test_metastore_publish_splits::<$metastore_type>().await;
             }
 
+            #[tokio::test]
+            async fn test_metastore_publish_multi_source_splits() {
+                crate::tests::test_suite::test_metastore_publish_multi_source_splits::<
+                    $metastore_type,
+                >()
+                .await;
+            }
+
             #[tokio::test]
             async fn test_metastore_replace_splits() {
                 crate::tests::test_suite::test_metastore_replace_splits::<$metastore_type>().await;
@@ -1895,6 +3113,13 @@ macro_rules! metastore_test_suite {
                 crate::tests::test_suite::test_metastore_delete_splits::<$metastore_type>().await;
             }
 
+            #[tokio::test]
+            async fn test_metastore_fenced_split_mutations() {
+                crate::tests::test_suite::test_metastore_fenced_split_mutations::<$metastore_type>(
+                )
+                .await;
+            }
+
             #[tokio::test]
             async fn test_metastore_list_all_splits() {
                 crate::tests::test_suite::test_metastore_list_all_splits::<$metastore_type>().await;
@@ -1905,6 +3130,26 @@ macro_rules! metastore_test_suite {
                 crate::tests::test_suite::test_metastore_list_splits::<$metastore_type>().await;
             }
 
+            #[tokio::test]
+            async fn test_metastore_list_splits_updated_since() {
+                crate::tests::test_suite::test_metastore_list_splits_updated_since::<
+                    $metastore_type,
+                >()
+                .await;
+            }
+
+            #[tokio::test]
+            async fn test_metastore_list_splits_page() {
+                crate::tests::test_suite::test_metastore_list_splits_page::<$metastore_type>()
+                    .await;
+            }
+
+            #[tokio::test]
+            async fn test_metastore_list_splits_stream() {
+                crate::tests::test_suite::test_metastore_list_splits_stream::<$metastore_type>()
+                    .await;
+            }
+
             #[tokio::test]
             async fn test_metastore_split_update_timestamp() {
                 crate::tests::test_suite::test_metastore_split_update_timestamp::<$metastore_type>(
@@ -1921,6 +3166,50 @@ macro_rules! metastore_test_suite {
             async fn test_metastore_delete_source() {
                 crate::tests::test_suite::test_metastore_delete_source::<$metastore_type>().await;
             }
+
+            #[tokio::test]
+            async fn test_metastore_reset_source_checkpoint() {
+                crate::tests::test_suite::test_metastore_reset_source_checkpoint::<$metastore_type>(
+                )
+                .await;
+            }
+
+            #[tokio::test]
+            async fn test_metastore_prune_source_checkpoint() {
+                crate::tests::test_suite::test_metastore_prune_source_checkpoint::<$metastore_type>(
+                )
+                .await;
+            }
+
+            #[tokio::test]
+            async fn test_metastore_update_indexing_settings() {
+                crate::tests::test_suite::test_metastore_update_indexing_settings::<$metastore_type>(
+                )
+                .await;
+            }
+
+            #[tokio::test]
+            async fn test_metastore_update_lifecycle_state() {
+                crate::tests::test_suite::test_metastore_update_lifecycle_state::<$metastore_type>(
+                )
+                .await;
+            }
+
+            #[tokio::test]
+            async fn test_metastore_leases() {
+                crate::tests::test_suite::test_metastore_leases::<$metastore_type>().await;
+            }
+
+            #[tokio::test]
+            async fn test_metastore_split_read_leases() {
+                crate::tests::test_suite::test_metastore_split_read_leases::<$metastore_type>()
+                    .await;
+            }
+
+            #[tokio::test]
+            async fn test_metastore_jobs() {
+                crate::tests::test_suite::test_metastore_jobs::<$metastore_type>().await;
+            }
         }
     };
 }
@@ -1940,6 +3229,21 @@ macro_rules! metastore_test_suite_for_postgresql {
                 crate::tests::test_suite::test_metastore_delete_index::<$metastore_type>().await;
             }
 
+            #[tokio::test]
+            async fn test_metastore_create_indexes() {
+                crate::tests::test_suite::test_metastore_create_indexes::<$metastore_type>().await;
+            }
+
+            #[tokio::test]
+            async fn test_metastore_list_indexes_metadatas() {
+                crate::tests::test_suite::test_metastore_list_indexes_metadatas::<$metastore_type>().await;
+            }
+
+            #[tokio::test]
+            async fn test_metastore_delete_indexes() {
+                crate::tests::test_suite::test_metastore_delete_indexes::<$metastore_type>().await;
+            }
+
             #[tokio::test]
             async fn test_metastore_index_metadata() {
                 crate::tests::test_suite::test_metastore_index_metadata::<$metastore_type>().await;
@@ -1955,6 +3259,21 @@ macro_rules! metastore_test_suite_for_postgresql {
                 crate::tests::test_suite::test_metastore_publish_splits::<$metastore_type>().await;
             }
 
+            #[tokio::test]
+            async fn test_metastore_prune_source_checkpoint() {
+                crate::tests::test_suite::test_metastore_prune_source_checkpoint::<$metastore_type>(
+                )
+                .await;
+            }
+
+            #[tokio::test]
+            async fn test_metastore_publish_multi_source_splits() {
+                crate::tests::test_suite::test_metastore_publish_multi_source_splits::<
+                    $metastore_type,
+                >()
+                .await;
+            }
+
             #[tokio::test]
             async fn test_metastore_replace_splits() {
                 crate::tests::test_suite::test_metastore_replace_splits::<$metastore_type>().await;
@@ -1972,6 +3291,13 @@ macro_rules! metastore_test_suite_for_postgresql {
                 crate::tests::test_suite::test_metastore_delete_splits::<$metastore_type>().await;
             }
 
+            #[tokio::test]
+            async fn test_metastore_fenced_split_mutations() {
+                crate::tests::test_suite::test_metastore_fenced_split_mutations::<$metastore_type>(
+                )
+                .await;
+            }
+
             #[tokio::test]
             async fn test_metastore_list_all_splits() {
                 crate::tests::test_suite::test_metastore_list_all_splits::<$metastore_type>().await;
@@ -1982,12 +3308,48 @@ macro_rules! metastore_test_suite_for_postgresql {
                 crate::tests::test_suite::test_metastore_list_splits::<$metastore_type>().await;
             }
 
+            #[tokio::test]
+            async fn test_metastore_list_splits_updated_since() {
+                crate::tests::test_suite::test_metastore_list_splits_updated_since::<
+                    $metastore_type,
+                >()
+                .await;
+            }
+
+            #[tokio::test]
+            async fn test_metastore_list_splits_page() {
+                crate::tests::test_suite::test_metastore_list_splits_page::<$metastore_type>()
+                    .await;
+            }
+
+            #[tokio::test]
+            async fn test_metastore_list_splits_stream() {
+                crate::tests::test_suite::test_metastore_list_splits_stream::<$metastore_type>()
+                    .await;
+            }
+
             #[tokio::test]
             async fn test_metastore_split_update_timestamp() {
                 crate::tests::test_suite::test_metastore_split_update_timestamp::<$metastore_type>(
                 )
                 .await;
             }
+
+            #[tokio::test]
+            async fn test_metastore_leases() {
+                crate::tests::test_suite::test_metastore_leases::<$metastore_type>().await;
+            }
+
+            #[tokio::test]
+            async fn test_metastore_split_read_leases() {
+                crate::tests::test_suite::test_metastore_split_read_leases::<$metastore_type>()
+                    .await;
+            }
+
+            #[tokio::test]
+            async fn test_metastore_jobs() {
+                crate::tests::test_suite::test_metastore_jobs::<$metastore_type>().await;
+            }
         }
     };
 }
@@ -19,7 +19,9 @@
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("cargo:rerun-if-changed=proto/cluster.proto");
+    println!("cargo:rerun-if-changed=proto/otlp_trace.proto");
     println!("cargo:rerun-if-changed=proto/search_api.proto");
+    println!("cargo:rerun-if-changed=proto/source_plugin.proto");
 
     let mut prost_config = prost_build::Config::default();
     prost_config.protoc_arg("--experimental_allow_proto3_optional");
@@ -32,7 +34,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .out_dir("src/")
         .compile_with_config(
             prost_config,
-            &["./proto/cluster.proto", "./proto/search_api.proto"],
+            &[
+                "./proto/cluster.proto",
+                "./proto/otlp_trace.proto",
+                "./proto/search_api.proto",
+                "./proto/source_plugin.proto",
+            ],
             &["./proto"],
         )?;
     Ok(())
@@ -13,6 +13,15 @@ pub struct Member {
     //// If true, it means self.
     #[prost(bool, tag = "3")]
     pub is_self: bool,
+    //// Services run by the member, e.g. `indexer` or `searcher`.
+    #[prost(string, repeated, tag = "4")]
+    pub available_services: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    //// Number of split searches waiting to be admitted on the member, as last reported by it.
+    #[prost(uint64, tag = "5")]
+    pub num_queued_split_searches: u64,
+    //// Number of split searches running on the member, as last reported by it.
+    #[prost(uint64, tag = "6")]
+    pub num_ongoing_split_searches: u64,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -148,8 +157,8 @@ pub mod cluster_service_server {
     #[derive(Debug)]
     pub struct ClusterServiceServer<T: ClusterService> {
         inner: _Inner<T>,
-        accept_compression_encodings: (),
-        send_compression_encodings: (),
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
     }
     struct _Inner<T>(Arc<T>);
     impl<T: ClusterService> ClusterServiceServer<T> {
@@ -168,6 +177,16 @@ pub mod cluster_service_server {
         {
             InterceptedService::new(Self::new(inner), interceptor)
         }
+        #[doc = r" Enable decompressing requests with `gzip`."]
+        pub fn accept_gzip(mut self) -> Self {
+            self.accept_compression_encodings.enable_gzip();
+            self
+        }
+        #[doc = r" Compress responses with `gzip`, if the client supports it."]
+        pub fn send_gzip(mut self) -> Self {
+            self.send_compression_encodings.enable_gzip();
+            self
+        }
     }
     impl<T, B> tonic::codegen::Service<http::Request<B>> for ClusterServiceServer<T>
     where
@@ -19,6 +19,11 @@
 
 mod cluster;
 mod quickwit;
+mod source_plugin;
+
+/// Subset of the OpenTelemetry protocol (OTLP) received by the `otlp` source.
+#[path = "opentelemetry.proto.collector.trace.v1.rs"]
+pub mod otlp_trace;
 
 #[macro_use]
 extern crate serde;
@@ -27,6 +32,7 @@ use std::fmt::{self, Display};
 
 pub use cluster::*;
 pub use quickwit::*;
+pub use source_plugin::*;
 
 impl From<SearchStreamRequest> for SearchRequest {
     fn from(item: SearchStreamRequest) -> Self {
@@ -40,6 +46,11 @@ impl From<SearchStreamRequest> for SearchRequest {
             start_offset: 0,
             sort_by_field: None,
             sort_order: None,
+            terms_lookup: None,
+            preferred_searcher_nodes: Vec::new(),
+            profile: false,
+            pin_splits: false,
+            snapshot: None,
         }
     }
 }
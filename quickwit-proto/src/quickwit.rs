@@ -34,6 +34,55 @@ pub struct SearchRequest {
     /// Sort by fast field. If unset sort by docid
     #[prost(string, optional, tag = "10")]
     pub sort_by_field: ::core::option::Option<::prost::alloc::string::String>,
+    /// Restricts the search to documents whose `target_field` matches
+    /// one of the values returned by a lookup query.
+    #[prost(message, optional, tag = "11")]
+    pub terms_lookup: ::core::option::Option<TermsLookup>,
+    /// IDs of the searcher nodes that the leaf searches are preferably
+    /// assigned to. Overrides the preferred searcher nodes of the index.
+    #[prost(string, repeated, tag = "12")]
+    pub preferred_searcher_nodes: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// If set, the response includes a profile of the execution of the query
+    /// on each split and segment.
+    #[prost(bool, tag = "13")]
+    pub profile: bool,
+    /// If set, the response includes a snapshot token pinning the splits searched
+    /// by the request.
+    #[prost(bool, tag = "14")]
+    pub pin_splits: bool,
+    /// Snapshot token returned by a previous search. The search runs on the
+    /// splits pinned by the token rather than on the currently published splits.
+    #[prost(string, optional, tag = "15")]
+    pub snapshot: ::core::option::Option<::prost::alloc::string::String>,
+    /// Time filter bounds given as time expressions, e.g. `now-15m`. The root
+    /// node resolves them into `start_timestamp` and `end_timestamp`.
+    #[prost(string, optional, tag = "16")]
+    pub start_time_expression: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "17")]
+    pub end_time_expression: ::core::option::Option<::prost::alloc::string::String>,
+}
+/// A terms lookup runs a first query, collects the values of a field
+/// in the matching documents, and uses them as a terms filter.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TermsLookup {
+    /// Index the lookup query is run on.
+    #[prost(string, tag = "1")]
+    pub index_id: ::prost::alloc::string::String,
+    /// Lookup query.
+    #[prost(string, tag = "2")]
+    pub query: ::prost::alloc::string::String,
+    /// Field of the lookup index whose values are collected.
+    #[prost(string, tag = "3")]
+    pub lookup_field: ::prost::alloc::string::String,
+    /// Field of the searched index matched against the collected values.
+    #[prost(string, tag = "4")]
+    pub target_field: ::prost::alloc::string::String,
+    /// Maximum number of distinct values. The search fails if the lookup
+    /// query returns more values.
+    #[prost(uint64, tag = "5")]
+    pub max_terms: u64,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -52,6 +101,94 @@ pub struct SearchResponse {
     /// The searcherrors that occured formatted as string.
     #[prost(string, repeated, tag = "4")]
     pub errors: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Breakdown of the time spent by the leaves, summed over all searched splits.
+    #[prost(message, optional, tag = "5")]
+    pub timings: ::core::option::Option<LeafSearchTimings>,
+    /// The splits whose query was rewritten because they were built before some fields of the query existed.
+    #[prost(message, repeated, tag = "6")]
+    pub query_rewrites: ::prost::alloc::vec::Vec<SplitQueryRewrite>,
+    /// Profiles of the execution of the query on the searched splits. Only set if the request enables `profile`.
+    #[prost(message, repeated, tag = "7")]
+    pub split_profiles: ::prost::alloc::vec::Vec<SplitProfile>,
+    /// Snapshot token pinning the searched splits. Only set if the request enables `pin_splits`.
+    #[prost(string, optional, tag = "8")]
+    pub snapshot: ::core::option::Option<::prost::alloc::string::String>,
+}
+/// Breakdown of the time spent searching splits. A large `warmup_micros` means the
+/// search is IO-bound, while a large `queue_wait_micros` or `cpu_micros` means it is CPU-bound.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LeafSearchTimings {
+    /// Time spent opening the splits and downloading the data required by the query.
+    #[prost(uint64, tag = "1")]
+    pub warmup_micros: u64,
+    /// Time spent waiting for the admission of the splits and for a search thread to become available.
+    #[prost(uint64, tag = "2")]
+    pub queue_wait_micros: u64,
+    /// Time spent collecting the hits on a search thread.
+    #[prost(uint64, tag = "3")]
+    pub cpu_micros: u64,
+    /// Number of splits the timings were measured on.
+    #[prost(uint64, tag = "4")]
+    pub num_splits: u64,
+}
+/// Query of a split rewritten because the split was built before some fields of the query existed.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SplitQueryRewrite {
+    /// Split id whose query was rewritten.
+    #[prost(string, tag = "1")]
+    pub split_id: ::prost::alloc::string::String,
+    /// Fields of the query missing from the split. The clauses on these fields match no document of the split.
+    #[prost(string, repeated, tag = "2")]
+    pub missing_fields: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// Profile of the execution of the query on a split.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SplitProfile {
+    /// Split id the query was executed on.
+    #[prost(string, tag = "1")]
+    pub split_id: ::prost::alloc::string::String,
+    /// Query executed on the split, after its rewriting.
+    #[prost(string, tag = "2")]
+    pub query: ::prost::alloc::string::String,
+    /// Time spent building the weight of the query, which includes looking up the terms of the query.
+    #[prost(uint64, tag = "3")]
+    pub weight_micros: u64,
+    /// Time spent merging the results of the segments.
+    #[prost(uint64, tag = "4")]
+    pub merge_micros: u64,
+    /// Profiles of the segments of the split.
+    #[prost(message, repeated, tag = "5")]
+    pub segments: ::prost::alloc::vec::Vec<SegmentProfile>,
+}
+/// Profile of the execution of the query on a segment of a split.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SegmentProfile {
+    /// Ordinal of the segment in the split.
+    #[prost(uint32, tag = "1")]
+    pub segment_ord: u32,
+    /// Number of documents of the segment, deleted documents included.
+    #[prost(uint32, tag = "2")]
+    pub max_doc: u32,
+    /// Time spent building the scorer of the segment, which includes opening the posting lists.
+    #[prost(uint64, tag = "3")]
+    pub build_scorer_micros: u64,
+    /// Time spent by the scorer advancing to the matching documents.
+    #[prost(uint64, tag = "4")]
+    pub next_doc_micros: u64,
+    /// Time spent by the collector collecting the matching documents and harvesting its results.
+    #[prost(uint64, tag = "5")]
+    pub collect_micros: u64,
+    /// Number of documents matched by the scorer and passed to the collector.
+    #[prost(uint64, tag = "6")]
+    pub num_docs_scored: u64,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -101,6 +238,12 @@ pub struct SplitIdAndFooterOffsets {
     /// The offset of the end of the footer in split bundle. The footer contains the file bundle metada and the hotcache.
     #[prost(uint64, tag = "3")]
     pub split_footer_end: u64,
+    /// Fast field by which the documents of the split were sorted at creation, if any.
+    #[prost(string, optional, tag = "4")]
+    pub sort_by_field: ::core::option::Option<::prost::alloc::string::String>,
+    /// Order in which the documents of the split were sorted at creation, if any.
+    #[prost(enumeration = "SortOrder", optional, tag = "5")]
+    pub sort_order: ::core::option::Option<i32>,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -160,6 +303,26 @@ pub struct LeafSearchResponse {
     /// num_attempted_splits = num_successful_splits + num_failed_splits.
     #[prost(uint64, tag = "4")]
     pub num_attempted_splits: u64,
+    /// Breakdown of the time spent searching the splits.
+    #[prost(message, optional, tag = "5")]
+    pub timings: ::core::option::Option<LeafSearchTimings>,
+    /// The splits whose query was rewritten because they were built before some fields of the query existed.
+    #[prost(message, repeated, tag = "6")]
+    pub query_rewrites: ::prost::alloc::vec::Vec<SplitQueryRewrite>,
+    /// Profiles of the execution of the query on the searched splits. Only set if the request enables `profile`.
+    #[prost(message, repeated, tag = "7")]
+    pub split_profiles: ::prost::alloc::vec::Vec<SplitProfile>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LeafSearchResponseChunk {
+    /// Encoded `LeafSearchResponse` holding a chunk of the response.
+    #[prost(bytes = "vec", tag = "1")]
+    pub payload: ::prost::alloc::vec::Vec<u8>,
+    /// Whether the payload is compressed with zstd.
+    #[prost(bool, tag = "2")]
+    pub zstd_compressed: bool,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -216,6 +379,12 @@ pub struct SearchStreamRequest {
     /// The field by which we want to partition
     #[prost(string, optional, tag = "9")]
     pub partition_by_field: ::core::option::Option<::prost::alloc::string::String>,
+    /// Time filter bounds given as time expressions, e.g. `now-15m`. The root
+    /// node resolves them into `start_timestamp` and `end_timestamp`.
+    #[prost(string, optional, tag = "10")]
+    pub start_time_expression: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "11")]
+    pub end_time_expression: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -413,6 +582,32 @@ pub mod search_service_client {
                 .server_streaming(request.into_request(), path, codec)
                 .await
         }
+        #[doc = " Perform a leaf search on a given set of splits, like `LeafSearch`, and"]
+        #[doc = " return the response in chunks whose size does not exceed the maximum"]
+        #[doc = " message size of the leaf node."]
+        #[doc = ""]
+        #[doc = " Concatenating the partial hits and failed splits of the chunks and summing"]
+        #[doc = " their counts yields the `LeafSearchResponse`."]
+        pub async fn leaf_search_chunks(
+            &mut self,
+            request: impl tonic::IntoRequest<super::LeafSearchRequest>,
+        ) -> Result<
+            tonic::Response<tonic::codec::Streaming<super::LeafSearchResponseChunk>>,
+            tonic::Status,
+        > {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/quickwit.SearchService/LeafSearchChunks");
+            self.inner
+                .server_streaming(request.into_request(), path, codec)
+                .await
+        }
     }
 }
 #[doc = r" Generated server implementations."]
@@ -457,12 +652,26 @@ pub mod search_service_server {
             &self,
             request: tonic::Request<super::LeafSearchStreamRequest>,
         ) -> Result<tonic::Response<Self::LeafSearchStreamStream>, tonic::Status>;
+        #[doc = "Server streaming response type for the LeafSearchChunks method."]
+        type LeafSearchChunksStream: futures_core::Stream<Item = Result<super::LeafSearchResponseChunk, tonic::Status>>
+            + Send
+            + 'static;
+        #[doc = " Perform a leaf search on a given set of splits, like `LeafSearch`, and"]
+        #[doc = " return the response in chunks whose size does not exceed the maximum"]
+        #[doc = " message size of the leaf node."]
+        #[doc = ""]
+        #[doc = " Concatenating the partial hits and failed splits of the chunks and summing"]
+        #[doc = " their counts yields the `LeafSearchResponse`."]
+        async fn leaf_search_chunks(
+            &self,
+            request: tonic::Request<super::LeafSearchRequest>,
+        ) -> Result<tonic::Response<Self::LeafSearchChunksStream>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct SearchServiceServer<T: SearchService> {
         inner: _Inner<T>,
-        accept_compression_encodings: (),
-        send_compression_encodings: (),
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
     }
     struct _Inner<T>(Arc<T>);
     impl<T: SearchService> SearchServiceServer<T> {
@@ -481,6 +690,16 @@ pub mod search_service_server {
         {
             InterceptedService::new(Self::new(inner), interceptor)
         }
+        #[doc = r" Enable decompressing requests with `gzip`."]
+        pub fn accept_gzip(mut self) -> Self {
+            self.accept_compression_encodings.enable_gzip();
+            self
+        }
+        #[doc = r" Compress responses with `gzip`, if the client supports it."]
+        pub fn send_gzip(mut self) -> Self {
+            self.send_compression_encodings.enable_gzip();
+            self
+        }
     }
     impl<T, B> tonic::codegen::Service<http::Request<B>> for SearchServiceServer<T>
     where
@@ -626,6 +845,42 @@ pub mod search_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/quickwit.SearchService/LeafSearchChunks" => {
+                    #[allow(non_camel_case_types)]
+                    struct LeafSearchChunksSvc<T: SearchService>(pub Arc<T>);
+                    impl<T: SearchService>
+                        tonic::server::ServerStreamingService<super::LeafSearchRequest>
+                        for LeafSearchChunksSvc<T>
+                    {
+                        type Response = super::LeafSearchResponseChunk;
+                        type ResponseStream = T::LeafSearchChunksStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::LeafSearchRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).leaf_search_chunks(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = LeafSearchChunksSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)
@@ -0,0 +1,412 @@
+//// Position of a source within one of its partitions.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PartitionPosition {
+    //// Partition ID, e.g. the name of a file or the ID of a Kafka partition.
+    #[prost(string, tag = "1")]
+    pub partition_id: ::prost::alloc::string::String,
+    //// Position within the partition. Positions are compared as strings, so numeric offsets must be
+    //// left-padded with zeros. The empty string designates the beginning of the partition.
+    #[prost(string, tag = "2")]
+    pub position: ::prost::alloc::string::String,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CheckpointRequest {
+    //// Parameters of the plugin configured in the source, as a JSON value.
+    #[prost(string, tag = "1")]
+    pub params_json: ::prost::alloc::string::String,
+    //// Positions of the partitions of the checkpoint. Partitions missing from the list have not
+    //// been read yet.
+    #[prost(message, repeated, tag = "2")]
+    pub positions: ::prost::alloc::vec::Vec<PartitionPosition>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CheckpointResponse {}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EmitBatchesRequest {
+    //// Maximum number of documents to return.
+    #[prost(uint64, tag = "1")]
+    pub max_num_docs: u64,
+}
+//// Range `(from_position, to_position]` of a partition covered by a batch.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PartitionDelta {
+    #[prost(string, tag = "1")]
+    pub partition_id: ::prost::alloc::string::String,
+    //// Position of the partition before the batch. It must be equal to the checkpointed position
+    //// or to the `to_position` of the previous batch for the partition.
+    #[prost(string, tag = "2")]
+    pub from_position: ::prost::alloc::string::String,
+    //// Position of the partition after the batch.
+    #[prost(string, tag = "3")]
+    pub to_position: ::prost::alloc::string::String,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EmitBatchesResponse {
+    //// Documents of the batch, each one holding a JSON object.
+    #[prost(string, repeated, tag = "1")]
+    pub docs: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    //// Ranges of partitions covered by the documents of the batch.
+    #[prost(message, repeated, tag = "2")]
+    pub partition_deltas: ::prost::alloc::vec::Vec<PartitionDelta>,
+    //// Set when the data source is exhausted. Quickwit then stops polling the plugin and the
+    //// indexing pipeline terminates once the batches in flight are indexed.
+    #[prost(bool, tag = "3")]
+    pub end_of_stream: bool,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AckRequest {
+    //// Positions up to which the partitions are durably indexed.
+    #[prost(message, repeated, tag = "1")]
+    pub positions: ::prost::alloc::vec::Vec<PartitionPosition>,
+}
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AckResponse {}
+#[doc = r" Generated client implementations."]
+pub mod source_plugin_service_client {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    #[doc = "/ Contract between Quickwit and a source plugin, i.e. a sidecar process reading documents from a"]
+    #[doc = "/ data source on behalf of a `grpc` source. Quickwit is the client: it positions the plugin with"]
+    #[doc = "/ `Checkpoint`, polls documents with `EmitBatches`, and calls `Ack` once they are durably indexed."]
+    #[doc = "/ A plugin serves a single source."]
+    #[derive(Debug, Clone)]
+    pub struct SourcePluginServiceClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl SourcePluginServiceClient<tonic::transport::Channel> {
+        #[doc = r" Attempt to create a new client by connecting to a given endpoint."]
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: std::convert::TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> SourcePluginServiceClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::ResponseBody: Body + Send + 'static,
+        T::Error: Into<StdError>,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> SourcePluginServiceClient<InterceptedService<T, F>>
+        where
+            F: tonic::service::Interceptor,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<http::Request<tonic::body::BoxBody>>>::Error:
+                Into<StdError> + Send + Sync,
+        {
+            SourcePluginServiceClient::new(InterceptedService::new(inner, interceptor))
+        }
+        #[doc = r" Compress requests with `gzip`."]
+        #[doc = r""]
+        #[doc = r" This requires the server to support it otherwise it might respond with an"]
+        #[doc = r" error."]
+        pub fn send_gzip(mut self) -> Self {
+            self.inner = self.inner.send_gzip();
+            self
+        }
+        #[doc = r" Enable decompressing responses with `gzip`."]
+        pub fn accept_gzip(mut self) -> Self {
+            self.inner = self.inner.accept_gzip();
+            self
+        }
+        #[doc = "/ Positions the plugin at the checkpoint of the source. Quickwit calls it every time the source"]
+        #[doc = "/ starts, before any call to `EmitBatches`: the plugin must then emit the documents following"]
+        #[doc = "/ the checkpointed positions, forgetting the batches that it previously emitted."]
+        pub async fn checkpoint(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CheckpointRequest>,
+        ) -> Result<tonic::Response<super::CheckpointResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/source_plugin.SourcePluginService/Checkpoint",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        #[doc = "/ Returns the next batch of documents. The plugin should return an empty batch rather than"]
+        #[doc = "/ block when no document is available: Quickwit polls it again after a short delay."]
+        pub async fn emit_batches(
+            &mut self,
+            request: impl tonic::IntoRequest<super::EmitBatchesRequest>,
+        ) -> Result<tonic::Response<super::EmitBatchesResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/source_plugin.SourcePluginService/EmitBatches",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        #[doc = "/ Notifies the plugin that the documents up to the given positions are durably indexed, so"]
+        #[doc = "/ that it can acknowledge or delete them upstream."]
+        pub async fn ack(
+            &mut self,
+            request: impl tonic::IntoRequest<super::AckRequest>,
+        ) -> Result<tonic::Response<super::AckResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/source_plugin.SourcePluginService/Ack");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+    }
+}
+#[doc = r" Generated server implementations."]
+pub mod source_plugin_service_server {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    #[doc = "Generated trait containing gRPC methods that should be implemented for use with SourcePluginServiceServer."]
+    #[async_trait]
+    pub trait SourcePluginService: Send + Sync + 'static {
+        #[doc = "/ Positions the plugin at the checkpoint of the source. Quickwit calls it every time the source"]
+        #[doc = "/ starts, before any call to `EmitBatches`: the plugin must then emit the documents following"]
+        #[doc = "/ the checkpointed positions, forgetting the batches that it previously emitted."]
+        async fn checkpoint(
+            &self,
+            request: tonic::Request<super::CheckpointRequest>,
+        ) -> Result<tonic::Response<super::CheckpointResponse>, tonic::Status>;
+        #[doc = "/ Returns the next batch of documents. The plugin should return an empty batch rather than"]
+        #[doc = "/ block when no document is available: Quickwit polls it again after a short delay."]
+        async fn emit_batches(
+            &self,
+            request: tonic::Request<super::EmitBatchesRequest>,
+        ) -> Result<tonic::Response<super::EmitBatchesResponse>, tonic::Status>;
+        #[doc = "/ Notifies the plugin that the documents up to the given positions are durably indexed, so"]
+        #[doc = "/ that it can acknowledge or delete them upstream."]
+        async fn ack(
+            &self,
+            request: tonic::Request<super::AckRequest>,
+        ) -> Result<tonic::Response<super::AckResponse>, tonic::Status>;
+    }
+    #[doc = "/ Contract between Quickwit and a source plugin, i.e. a sidecar process reading documents from a"]
+    #[doc = "/ data source on behalf of a `grpc` source. Quickwit is the client: it positions the plugin with"]
+    #[doc = "/ `Checkpoint`, polls documents with `EmitBatches`, and calls `Ack` once they are durably indexed."]
+    #[doc = "/ A plugin serves a single source."]
+    #[derive(Debug)]
+    pub struct SourcePluginServiceServer<T: SourcePluginService> {
+        inner: _Inner<T>,
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
+    }
+    struct _Inner<T>(Arc<T>);
+    impl<T: SourcePluginService> SourcePluginServiceServer<T> {
+        pub fn new(inner: T) -> Self {
+            let inner = Arc::new(inner);
+            let inner = _Inner(inner);
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+            }
+        }
+        pub fn with_interceptor<F>(inner: T, interceptor: F) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+        #[doc = r" Enable decompressing requests with `gzip`."]
+        pub fn accept_gzip(mut self) -> Self {
+            self.accept_compression_encodings.enable_gzip();
+            self
+        }
+        #[doc = r" Compress responses with `gzip`, if the client supports it."]
+        pub fn send_gzip(mut self) -> Self {
+            self.send_compression_encodings.enable_gzip();
+            self
+        }
+    }
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for SourcePluginServiceServer<T>
+    where
+        T: SourcePluginService,
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Never;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/source_plugin.SourcePluginService/Checkpoint" => {
+                    #[allow(non_camel_case_types)]
+                    struct CheckpointSvc<T: SourcePluginService>(pub Arc<T>);
+                    impl<T: SourcePluginService>
+                        tonic::server::UnaryService<super::CheckpointRequest> for CheckpointSvc<T>
+                    {
+                        type Response = super::CheckpointResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CheckpointRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).checkpoint(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = CheckpointSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/source_plugin.SourcePluginService/EmitBatches" => {
+                    #[allow(non_camel_case_types)]
+                    struct EmitBatchesSvc<T: SourcePluginService>(pub Arc<T>);
+                    impl<T: SourcePluginService>
+                        tonic::server::UnaryService<super::EmitBatchesRequest>
+                        for EmitBatchesSvc<T>
+                    {
+                        type Response = super::EmitBatchesResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::EmitBatchesRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).emit_batches(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = EmitBatchesSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/source_plugin.SourcePluginService/Ack" => {
+                    #[allow(non_camel_case_types)]
+                    struct AckSvc<T: SourcePluginService>(pub Arc<T>);
+                    impl<T: SourcePluginService> tonic::server::UnaryService<super::AckRequest> for AckSvc<T> {
+                        type Response = super::AckResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::AckRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).ack(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = AckSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec).apply_compression_config(
+                            accept_compression_encodings,
+                            send_compression_encodings,
+                        );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => Box::pin(async move {
+                    Ok(http::Response::builder()
+                        .status(200)
+                        .header("grpc-status", "12")
+                        .header("content-type", "application/grpc")
+                        .body(empty_body())
+                        .unwrap())
+                }),
+            }
+        }
+    }
+    impl<T: SourcePluginService> Clone for SourcePluginServiceServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self {
+                inner,
+                accept_compression_encodings: self.accept_compression_encodings,
+                send_compression_encodings: self.send_compression_encodings,
+            }
+        }
+    }
+    impl<T: SourcePluginService> Clone for _Inner<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone())
+        }
+    }
+    impl<T: std::fmt::Debug> std::fmt::Debug for _Inner<T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+    impl<T: SourcePluginService> tonic::transport::NamedService for SourcePluginServiceServer<T> {
+        const NAME: &'static str = "source_plugin.SourcePluginService";
+    }
+}
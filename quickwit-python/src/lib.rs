@@ -0,0 +1,344 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+#![allow(clippy::too_many_arguments)]
+
+//! Python bindings of Quickwit, built with [maturin](https://github.com/PyO3/maturin):
+//! - `quickwit.Client` searches, streams and exports the documents of a Quickwit node through its
+//!   REST API.
+//! - `quickwit.ingest` indexes documents locally, like the `quickwit index ingest` command.
+//!
+//! The calls are blocking and release the GIL while they wait.
+
+use std::fmt::Display;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use quickwit_cli::index::{ingest_docs_cli, IngestDocsArgs};
+use quickwit_client::{
+    ExportQuery, QuickwitClient, RetryPolicy, SearchQuery, SearchStreamQuery, StreamOutputFormat,
+};
+use quickwit_common::uri::Uri;
+use serde::Serialize;
+use tokio::runtime::Runtime;
+
+create_exception!(quickwit, QuickwitError, PyException);
+
+fn to_py_err(error: impl Display) -> PyErr {
+    QuickwitError::new_err(error.to_string())
+}
+
+/// Converts a value serialized by the client into Python objects.
+fn to_py_object(py: Python, value: &impl Serialize) -> PyResult<PyObject> {
+    let json = serde_json::to_string(value).map_err(to_py_err)?;
+    let object = py.import("json")?.call_method1("loads", (json,))?;
+    Ok(object.into())
+}
+
+/// Converts a timestamp passed either as an integer or as a string, e.g. `now-15m`, into a
+/// query parameter.
+fn to_timestamp_param(timestamp_opt: Option<&PyAny>) -> PyResult<Option<String>> {
+    timestamp_opt
+        .map(|timestamp| Ok(timestamp.str()?.to_str()?.to_string()))
+        .transpose()
+}
+
+/// Client of a Quickwit node.
+#[pyclass(module = "quickwit")]
+struct Client {
+    client: QuickwitClient,
+    runtime: Arc<Runtime>,
+}
+
+#[pymethods]
+impl Client {
+    #[new]
+    #[args(timeout_secs = "30.0", max_num_retries = "3")]
+    fn new(rest_endpoint: &str, timeout_secs: f64, max_num_retries: usize) -> PyResult<Self> {
+        let runtime = Runtime::new().map_err(to_py_err)?;
+        let client = QuickwitClient::builder(rest_endpoint)
+            .timeout(Duration::from_secs_f64(timeout_secs))
+            .retry_policy(RetryPolicy {
+                max_num_retries,
+                ..Default::default()
+            })
+            .build()
+            .map_err(to_py_err)?;
+        Ok(Client {
+            client,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// Searches an index and returns the response as a dict with the keys `numHits`, `hits`,
+    /// `elapsedTimeMicros` and `errors`.
+    #[args(
+        search_fields = "None",
+        start_timestamp = "None",
+        end_timestamp = "None",
+        max_hits = "20",
+        start_offset = "0",
+        sort_by_field = "None"
+    )]
+    fn search(
+        &self,
+        py: Python,
+        index_id: &str,
+        query: &str,
+        search_fields: Option<Vec<String>>,
+        start_timestamp: Option<&PyAny>,
+        end_timestamp: Option<&PyAny>,
+        max_hits: u64,
+        start_offset: u64,
+        sort_by_field: Option<String>,
+    ) -> PyResult<PyObject> {
+        let search_query = SearchQuery {
+            query: query.to_string(),
+            search_fields: search_fields.unwrap_or_default(),
+            start_timestamp: to_timestamp_param(start_timestamp)?,
+            end_timestamp: to_timestamp_param(end_timestamp)?,
+            max_hits,
+            start_offset,
+            sort_by_field,
+            pin_splits: false,
+            snapshot: None,
+        };
+        let search_response = py
+            .allow_threads(|| {
+                self.runtime
+                    .block_on(self.client.search(index_id, &search_query))
+            })
+            .map_err(to_py_err)?;
+        to_py_object(py, &search_response)
+    }
+
+    /// Streams the values of a fast field of the documents matching a query. Returns an
+    /// iterator over chunks of bytes.
+    #[args(
+        search_fields = "None",
+        start_timestamp = "None",
+        end_timestamp = "None",
+        output_format = "\"csv\""
+    )]
+    fn search_stream(
+        &self,
+        py: Python,
+        index_id: &str,
+        query: &str,
+        fast_field: &str,
+        search_fields: Option<Vec<String>>,
+        start_timestamp: Option<&PyAny>,
+        end_timestamp: Option<&PyAny>,
+        output_format: &str,
+    ) -> PyResult<SearchStream> {
+        let output_format = match output_format {
+            "csv" => StreamOutputFormat::Csv,
+            "clickHouseRowBinary" => StreamOutputFormat::ClickHouseRowBinary,
+            _ => {
+                return Err(to_py_err(format!(
+                    "Unknown output format `{}`. Expected `csv` or `clickHouseRowBinary`.",
+                    output_format
+                )))
+            }
+        };
+        let search_stream_query = SearchStreamQuery {
+            query: query.to_string(),
+            fast_field: fast_field.to_string(),
+            search_fields: search_fields.unwrap_or_default(),
+            start_timestamp: to_timestamp_param(start_timestamp)?,
+            end_timestamp: to_timestamp_param(end_timestamp)?,
+            output_format,
+        };
+        let stream = py
+            .allow_threads(|| {
+                self.runtime
+                    .block_on(self.client.search_stream(index_id, &search_stream_query))
+            })
+            .map_err(to_py_err)?;
+        Ok(SearchStream {
+            stream_opt: Some(stream),
+            runtime: self.runtime.clone(),
+        })
+    }
+
+    /// Starts a job exporting the documents matching a query to Parquet files written in
+    /// `output_uri`. Returns the record of the job as a dict.
+    #[args(
+        fields = "None",
+        search_fields = "None",
+        start_timestamp = "None",
+        end_timestamp = "None",
+        max_hits = "100_000"
+    )]
+    fn start_export(
+        &self,
+        py: Python,
+        index_id: &str,
+        query: &str,
+        output_uri: &str,
+        fields: Option<Vec<String>>,
+        search_fields: Option<Vec<String>>,
+        start_timestamp: Option<i64>,
+        end_timestamp: Option<i64>,
+        max_hits: u64,
+    ) -> PyResult<PyObject> {
+        let export_query = ExportQuery {
+            query: query.to_string(),
+            search_fields: search_fields.unwrap_or_default(),
+            start_timestamp,
+            end_timestamp,
+            max_hits,
+            fields: fields.unwrap_or_default(),
+            output_uri: output_uri.to_string(),
+        };
+        let job = py
+            .allow_threads(|| {
+                self.runtime
+                    .block_on(self.client.start_export(index_id, &export_query))
+            })
+            .map_err(to_py_err)?;
+        to_py_object(py, &job)
+    }
+
+    /// Returns the record of a job as a dict with the keys `jobId`, `indexId`, `kind`, `state`,
+    /// `progress`, `nodeId`, `createTimestamp`, `updateTimestamp`, `details` and, if the job
+    /// failed, `error`.
+    fn job_status(&self, py: Python, job_id: &str) -> PyResult<PyObject> {
+        let job = py
+            .allow_threads(|| self.runtime.block_on(self.client.job(job_id)))
+            .map_err(to_py_err)?;
+        to_py_object(py, &job)
+    }
+
+    /// Cancels a running job. Returns the record of the job as a dict.
+    fn cancel_job(&self, py: Python, job_id: &str) -> PyResult<PyObject> {
+        let job = py
+            .allow_threads(|| self.runtime.block_on(self.client.cancel_job(job_id)))
+            .map_err(to_py_err)?;
+        to_py_object(py, &job)
+    }
+
+    /// Returns whether the node is alive.
+    fn is_alive(&self, py: Python) -> PyResult<bool> {
+        py.allow_threads(|| self.runtime.block_on(self.client.is_alive()))
+            .map_err(to_py_err)
+    }
+}
+
+/// Iterator over the chunks of a search stream.
+#[pyclass(module = "quickwit")]
+struct SearchStream {
+    stream_opt: Option<quickwit_client::SearchStream>,
+    runtime: Arc<Runtime>,
+}
+
+#[pymethods]
+impl SearchStream {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>, py: Python) -> PyResult<Option<PyObject>> {
+        let SearchStream {
+            stream_opt,
+            runtime,
+        } = &mut *slf;
+        let stream = match stream_opt {
+            Some(stream) => stream,
+            None => return Ok(None),
+        };
+        let chunk_opt = py
+            .allow_threads(|| runtime.block_on(stream.next_chunk()))
+            .map_err(to_py_err)?;
+        match chunk_opt {
+            Some(chunk) => Ok(Some(PyBytes::new(py, &chunk).into())),
+            None => {
+                *stream_opt = None;
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Indexes documents locally, like the `quickwit index ingest` command: the documents are
+/// read from the NDJSON file `input_path`, or taken from the iterable of dicts `docs`.
+#[pyfunction]
+#[args(
+    input_path = "None",
+    docs = "None",
+    data_dir = "None",
+    overwrite = "false"
+)]
+fn ingest(
+    py: Python,
+    index_id: String,
+    config_uri: &str,
+    input_path: Option<PathBuf>,
+    docs: Option<&PyAny>,
+    data_dir: Option<PathBuf>,
+    overwrite: bool,
+) -> PyResult<()> {
+    let config_uri = Uri::try_new(config_uri).map_err(to_py_err)?;
+    // Keeps the temporary file alive until the end of the ingestion.
+    let (input_path, _docs_file_opt) = match (input_path, docs) {
+        (Some(input_path), None) => (input_path, None),
+        (None, Some(docs)) => {
+            let json = py.import("json")?;
+            let mut docs_file = tempfile::NamedTempFile::new().map_err(to_py_err)?;
+            for doc in docs.iter()? {
+                let doc_json: String = json.call_method1("dumps", (doc?,))?.extract()?;
+                writeln!(docs_file, "{}", doc_json).map_err(to_py_err)?;
+            }
+            docs_file.flush().map_err(to_py_err)?;
+            (docs_file.path().to_path_buf(), Some(docs_file))
+        }
+        _ => {
+            return Err(to_py_err(
+                "Exactly one of `input_path` and `docs` must be provided.",
+            ))
+        }
+    };
+    let ingest_docs_args = IngestDocsArgs {
+        index_id,
+        input_path_opt: Some(input_path),
+        config_uri,
+        data_dir,
+        overwrite,
+    };
+    py.allow_threads(|| {
+        let runtime = Runtime::new()?;
+        runtime.block_on(ingest_docs_cli(ingest_docs_args))
+    })
+    .map_err(to_py_err)
+}
+
+#[pymodule]
+fn quickwit(py: Python, module: &PyModule) -> PyResult<()> {
+    module.add_class::<Client>()?;
+    module.add_class::<SearchStream>()?;
+    module.add_function(wrap_pyfunction!(ingest, module)?)?;
+    module.add("QuickwitError", py.get_type::<QuickwitError>())?;
+    Ok(())
+}
@@ -0,0 +1,612 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use futures::StreamExt;
+use quickwit_config::get_searcher_config_instance;
+use quickwit_proto::SplitIdAndFooterOffsets;
+use quickwit_storage::{
+    long_term_cache_fast_field_slices, put_long_term_cache_fast_field_slice, OwnedBytes,
+    StorageUriResolver,
+};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, error, info};
+
+use crate::leaf::{
+    get_split_footer_from_cache_or_fetch, global_split_footer_cache, SplitFooterCacheKey,
+};
+use crate::split_access_tracker;
+
+/// Number of split footers fetched concurrently when warming up the cache.
+const WARM_UP_CONCURRENCY: usize = 10;
+
+/// Manifest of the caches of a searcher.
+///
+/// The split footers are listed from the footer of the hottest split to the footer of the coldest
+/// one, see [`crate::SplitAccessStats::hotness`]. Footers of equally hot splits are listed from the
+/// most recently used to the least recently used. The footers hold the hotcache of the splits, so
+/// reloading them at startup spares the first searches after a restart most of their round trips
+/// to the storage.
+///
+/// The fast field slices are only saved on shutdown, see [`save_cache_snapshot`], because their
+/// bytes are saved along with the manifest. They are listed from the most recently used to the
+/// least recently used, in the order of their bytes in the data file of the manifest.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct CacheManifest {
+    split_footers: Vec<SplitFooterEntry>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fast_field_slices: Vec<FastFieldSliceEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SplitFooterEntry {
+    index_uri: String,
+    split_id: String,
+    footer_start: u64,
+    footer_end: u64,
+}
+
+impl SplitFooterEntry {
+    fn num_bytes(&self) -> u64 {
+        self.footer_end.saturating_sub(self.footer_start)
+    }
+}
+
+impl From<SplitFooterCacheKey> for SplitFooterEntry {
+    fn from(cache_key: SplitFooterCacheKey) -> Self {
+        SplitFooterEntry {
+            index_uri: cache_key.index_uri,
+            split_id: cache_key.split_id,
+            footer_start: cache_key.footer_offsets.start,
+            footer_end: cache_key.footer_offsets.end,
+        }
+    }
+}
+
+/// Slice of a file held by the fast field cache. The `0..usize::MAX` byte range stands for the
+/// entire file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct FastFieldSliceEntry {
+    path: PathBuf,
+    start: u64,
+    end: u64,
+    num_bytes: u64,
+}
+
+impl FastFieldSliceEntry {
+    fn byte_range(&self) -> Range<usize> {
+        self.start as usize..self.end as usize
+    }
+}
+
+/// Returns the path of the cache manifest of the searcher in the data directory.
+pub fn cache_manifest_path(data_dir_path: &Path) -> PathBuf {
+    data_dir_path.join("searcher").join("cache-manifest.json")
+}
+
+/// Returns the path of the file holding the bytes of the fast field slices listed in the manifest
+/// at `manifest_path`.
+fn cache_manifest_data_path(manifest_path: &Path) -> PathBuf {
+    manifest_path.with_extension("data")
+}
+
+/// Saves the manifest of the split footer cache to `manifest_path`, replacing the previous
+/// manifest atomically. Returns the number of footers listed in the manifest.
+pub async fn save_cache_manifest(manifest_path: &Path) -> anyhow::Result<usize> {
+    let split_footers = split_footers_by_hotness();
+    let num_split_footers = split_footers.len();
+    write_cache_manifest(
+        manifest_path,
+        &CacheManifest {
+            split_footers,
+            fast_field_slices: Vec::new(),
+        },
+    )
+    .await?;
+    Ok(num_split_footers)
+}
+
+/// Saves the manifest of the split footer cache to `manifest_path` along with the content of the
+/// fast field cache, so that both caches can be restored when the searcher restarts. Meant to be
+/// called when the searcher shuts down. Returns the number of footers and the number of fast field
+/// slices saved.
+pub async fn save_cache_snapshot(manifest_path: &Path) -> anyhow::Result<(usize, usize)> {
+    let split_footers = split_footers_by_hotness();
+    let num_split_footers = split_footers.len();
+    let fast_field_slices = long_term_cache_fast_field_slices();
+    let num_fast_field_slices = fast_field_slices.len();
+    // The previous manifest may list the slices of the data file about to be replaced, so it is
+    // replaced with a manifest listing none before the data file is written.
+    write_cache_manifest(
+        manifest_path,
+        &CacheManifest {
+            split_footers: split_footers.clone(),
+            fast_field_slices: Vec::new(),
+        },
+    )
+    .await?;
+    let data_path = cache_manifest_data_path(manifest_path);
+    let temp_data_path = data_path.with_extension("data.tmp");
+    let mut data_file = tokio::io::BufWriter::new(
+        tokio::fs::File::create(&temp_data_path)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to create cache data file `{}`.",
+                    temp_data_path.display()
+                )
+            })?,
+    );
+    let mut fast_field_slice_entries = Vec::with_capacity(num_fast_field_slices);
+    for (path, byte_range, bytes) in fast_field_slices {
+        data_file.write_all(bytes.as_slice()).await?;
+        fast_field_slice_entries.push(FastFieldSliceEntry {
+            path,
+            start: byte_range.start as u64,
+            end: byte_range.end as u64,
+            num_bytes: bytes.len() as u64,
+        });
+    }
+    data_file.flush().await?;
+    tokio::fs::rename(&temp_data_path, &data_path).await?;
+    write_cache_manifest(
+        manifest_path,
+        &CacheManifest {
+            split_footers,
+            fast_field_slices: fast_field_slice_entries,
+        },
+    )
+    .await?;
+    Ok((num_split_footers, num_fast_field_slices))
+}
+
+fn split_footers_by_hotness() -> Vec<SplitFooterEntry> {
+    let mut split_footers: Vec<SplitFooterEntry> = global_split_footer_cache()
+        .entries_by_recency()
+        .into_iter()
+        .map(|(cache_key, _)| SplitFooterEntry::from(cache_key))
+        .collect();
+    sort_split_footers_by_hotness(
+        &mut split_footers,
+        &split_access_tracker().hotness_per_split(),
+    );
+    split_footers
+}
+
+/// Writes the manifest to `manifest_path`, replacing the previous manifest atomically.
+async fn write_cache_manifest(
+    manifest_path: &Path,
+    cache_manifest: &CacheManifest,
+) -> anyhow::Result<()> {
+    let manifest_json = serde_json::to_vec(cache_manifest)?;
+    if let Some(parent_dir_path) = manifest_path.parent() {
+        tokio::fs::create_dir_all(parent_dir_path).await?;
+    }
+    let temp_manifest_path = manifest_path.with_extension("json.tmp");
+    tokio::fs::write(&temp_manifest_path, manifest_json)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to write cache manifest `{}`.",
+                temp_manifest_path.display()
+            )
+        })?;
+    tokio::fs::rename(&temp_manifest_path, manifest_path).await?;
+    Ok(())
+}
+
+async fn read_cache_manifest(manifest_path: &Path) -> anyhow::Result<Option<CacheManifest>> {
+    let manifest_json = match tokio::fs::read(manifest_path).await {
+        Ok(manifest_json) => manifest_json,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error.into()),
+    };
+    let manifest: CacheManifest = serde_json::from_slice(&manifest_json).with_context(|| {
+        format!(
+            "Failed to parse cache manifest `{}`.",
+            manifest_path.display()
+        )
+    })?;
+    Ok(Some(manifest))
+}
+
+/// Sorts the split footers from the hottest split to the coldest one. The sort is stable, so the
+/// footers of splits with the same hotness, for instance splits not accessed since the searcher
+/// started, keep their order.
+fn sort_split_footers_by_hotness(
+    split_footers: &mut [SplitFooterEntry],
+    hotness_per_split: &HashMap<String, f64>,
+) {
+    let split_hotness = |split_footer: &SplitFooterEntry| -> f64 {
+        hotness_per_split
+            .get(&split_footer.split_id)
+            .copied()
+            .unwrap_or(0.0)
+    };
+    split_footers.sort_by(|left, right| {
+        split_hotness(right)
+            .partial_cmp(&split_hotness(left))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
+
+/// Keeps the hottest footers that fit in the cache, ordered so that the hottest footers are loaded
+/// last and are therefore the last ones evicted.
+fn select_split_footers_to_warm_up(
+    split_footers: Vec<SplitFooterEntry>,
+    capacity_in_bytes: u64,
+) -> Vec<SplitFooterEntry> {
+    let mut num_bytes = 0;
+    let mut selected_split_footers: Vec<SplitFooterEntry> = split_footers
+        .into_iter()
+        .take_while(|split_footer| {
+            num_bytes += split_footer.num_bytes();
+            num_bytes <= capacity_in_bytes
+        })
+        .collect();
+    selected_split_footers.reverse();
+    selected_split_footers
+}
+
+/// Loads the split footers listed in the manifest at `manifest_path` into the split footer
+/// cache. Footers that cannot be fetched, for instance because their split was deleted since the
+/// manifest was saved, are skipped. Returns the number of footers loaded.
+pub async fn warm_up_split_footer_cache(
+    manifest_path: &Path,
+    storage_uri_resolver: &StorageUriResolver,
+) -> anyhow::Result<usize> {
+    let manifest = match read_cache_manifest(manifest_path).await? {
+        Some(manifest) => manifest,
+        None => return Ok(0),
+    };
+    let capacity_in_bytes = get_searcher_config_instance()
+        .split_footer_cache_capacity
+        .get_bytes() as u64;
+    let split_footers = select_split_footers_to_warm_up(manifest.split_footers, capacity_in_bytes);
+    let num_loaded_split_footers = futures::stream::iter(split_footers)
+        .map(|split_footer| async move {
+            let index_storage = storage_uri_resolver.resolve(&split_footer.index_uri)?;
+            let split_and_footer_offsets = SplitIdAndFooterOffsets {
+                split_id: split_footer.split_id.clone(),
+                split_footer_start: split_footer.footer_start,
+                split_footer_end: split_footer.footer_end,
+                sort_by_field: None,
+                sort_order: None,
+            };
+            get_split_footer_from_cache_or_fetch(index_storage, &split_and_footer_offsets)
+                .await
+                .map_err(|error| {
+                    debug!(split_id = %split_footer.split_id, error = ?error, "Failed to warm up split footer.");
+                    error
+                })
+        })
+        .buffer_unordered(WARM_UP_CONCURRENCY)
+        .filter(|footer_res| futures::future::ready(footer_res.is_ok()))
+        .count()
+        .await;
+    Ok(num_loaded_split_footers)
+}
+
+/// Loads the fast field slices saved along with the manifest at `manifest_path` into the fast
+/// field cache, from the least recently used slice to the most recently used one, so that the
+/// recency order of the cache is preserved. When the cache is smaller than it was, the least
+/// recently used slices are skipped. Returns the number of slices loaded.
+pub async fn warm_up_fast_field_cache(manifest_path: &Path) -> anyhow::Result<usize> {
+    let manifest = match read_cache_manifest(manifest_path).await? {
+        Some(manifest) if !manifest.fast_field_slices.is_empty() => manifest,
+        _ => return Ok(0),
+    };
+    let data_path = cache_manifest_data_path(manifest_path);
+    let data = tokio::fs::read(&data_path)
+        .await
+        .with_context(|| format!("Failed to read cache data file `{}`.", data_path.display()))?;
+    let capacity_in_bytes = get_searcher_config_instance()
+        .fast_field_cache_capacity
+        .get_bytes() as u64;
+    let mut selected_slices = Vec::new();
+    let mut offset = 0;
+    for fast_field_slice in manifest.fast_field_slices {
+        let end_offset = offset + fast_field_slice.num_bytes;
+        if end_offset > capacity_in_bytes || end_offset > data.len() as u64 {
+            break;
+        }
+        selected_slices.push((fast_field_slice, offset as usize..end_offset as usize));
+        offset = end_offset;
+    }
+    let num_loaded_slices = selected_slices.len();
+    for (fast_field_slice, data_range) in selected_slices.into_iter().rev() {
+        // The slices are copied so that the data file is not held in memory by the cache.
+        let bytes = OwnedBytes::new(data[data_range].to_vec());
+        let byte_range = fast_field_slice.byte_range();
+        put_long_term_cache_fast_field_slice(fast_field_slice.path, byte_range, bytes);
+    }
+    Ok(num_loaded_slices)
+}
+
+/// Warms up the split footer and fast field caches from the manifest at `manifest_path`, then
+/// saves the manifest every `save_interval`. Saving only starts once the warm-up is over so that a
+/// manifest is never replaced with the one of a cold cache.
+pub async fn maintain_cache_manifest(
+    manifest_path: PathBuf,
+    storage_uri_resolver: StorageUriResolver,
+    save_interval: Duration,
+) {
+    match warm_up_fast_field_cache(&manifest_path).await {
+        Ok(0) => {}
+        Ok(num_fast_field_slices) => {
+            info!(
+                num_fast_field_slices = num_fast_field_slices,
+                "Warmed up fast field cache."
+            )
+        }
+        Err(error) => error!(error = ?error, "Failed to warm up fast field cache."),
+    }
+    match warm_up_split_footer_cache(&manifest_path, &storage_uri_resolver).await {
+        Ok(0) => {}
+        Ok(num_split_footers) => {
+            info!(
+                num_split_footers = num_split_footers,
+                "Warmed up split footer cache."
+            )
+        }
+        Err(error) => error!(error = ?error, "Failed to warm up split footer cache."),
+    }
+    let mut interval = tokio::time::interval(save_interval);
+    // The first tick completes immediately.
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        if let Err(error) = save_cache_manifest(&manifest_path).await {
+            error!(error = ?error, "Failed to save cache manifest.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use quickwit_storage::{PutPayload, SplitPayloadBuilder};
+
+    use super::*;
+
+    fn split_footer_entry(
+        split_id: &str,
+        footer_offsets: std::ops::Range<u64>,
+    ) -> SplitFooterEntry {
+        SplitFooterEntry {
+            index_uri: "ram:///indexes/test-index".to_string(),
+            split_id: split_id.to_string(),
+            footer_start: footer_offsets.start,
+            footer_end: footer_offsets.end,
+        }
+    }
+
+    #[test]
+    fn test_select_split_footers_to_warm_up() {
+        let split_footers = vec![
+            split_footer_entry("split-1", 0..10),
+            split_footer_entry("split-2", 5..15),
+            split_footer_entry("split-3", 0..10),
+        ];
+        assert!(select_split_footers_to_warm_up(split_footers.clone(), 5).is_empty());
+        assert_eq!(
+            select_split_footers_to_warm_up(split_footers.clone(), 25),
+            vec![
+                split_footer_entry("split-2", 5..15),
+                split_footer_entry("split-1", 0..10),
+            ]
+        );
+        assert_eq!(
+            select_split_footers_to_warm_up(split_footers, 100),
+            vec![
+                split_footer_entry("split-3", 0..10),
+                split_footer_entry("split-2", 5..15),
+                split_footer_entry("split-1", 0..10),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sort_split_footers_by_hotness() {
+        let mut split_footers = vec![
+            split_footer_entry("split-1", 0..10),
+            split_footer_entry("split-2", 0..10),
+            split_footer_entry("split-3", 0..10),
+            split_footer_entry("split-4", 0..10),
+        ];
+        let hotness_per_split: HashMap<String, f64> =
+            [("split-3".to_string(), 2.0), ("split-4".to_string(), 0.5)]
+                .into_iter()
+                .collect();
+        sort_split_footers_by_hotness(&mut split_footers, &hotness_per_split);
+        assert_eq!(
+            split_footers,
+            vec![
+                split_footer_entry("split-3", 0..10),
+                split_footer_entry("split-4", 0..10),
+                split_footer_entry("split-1", 0..10),
+                split_footer_entry("split-2", 0..10),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_split_footer_cache() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let manifest_path = cache_manifest_path(tempdir.path());
+        let storage_uri_resolver = StorageUriResolver::for_test();
+        assert_eq!(
+            warm_up_split_footer_cache(&manifest_path, &storage_uri_resolver).await?,
+            0
+        );
+        let index_uri = "ram:///indexes/test-warm-up-index";
+        let index_storage = storage_uri_resolver.resolve(index_uri)?;
+        let split_payload = SplitPayloadBuilder::get_split_payload(&[], &[1, 2, 3])?
+            .read_all()
+            .await?;
+        let split_id = "test-warm-up-split";
+        index_storage
+            .put(
+                Path::new(&format!("{}.split", split_id)),
+                Box::new(split_payload.to_vec()),
+            )
+            .await?;
+        let footer_offsets = 0..split_payload.len() as u64;
+        let split_footers = vec![
+            SplitFooterEntry {
+                index_uri: index_uri.to_string(),
+                split_id: split_id.to_string(),
+                footer_start: footer_offsets.start,
+                footer_end: footer_offsets.end,
+            },
+            // The split of this footer does not exist: it is skipped.
+            SplitFooterEntry {
+                index_uri: index_uri.to_string(),
+                split_id: "test-warm-up-deleted-split".to_string(),
+                footer_start: 0,
+                footer_end: 1,
+            },
+        ];
+        tokio::fs::create_dir_all(manifest_path.parent().unwrap()).await?;
+        tokio::fs::write(
+            &manifest_path,
+            serde_json::to_vec(&CacheManifest {
+                split_footers,
+                fast_field_slices: Vec::new(),
+            })?,
+        )
+        .await?;
+        assert_eq!(
+            warm_up_split_footer_cache(&manifest_path, &storage_uri_resolver).await?,
+            1
+        );
+        let cache_key = SplitFooterCacheKey {
+            index_uri: index_uri.to_string(),
+            split_id: split_id.to_string(),
+            footer_offsets,
+        };
+        assert!(global_split_footer_cache().get(&cache_key).is_some());
+
+        save_cache_manifest(&manifest_path).await?;
+        let manifest: CacheManifest =
+            serde_json::from_slice(&tokio::fs::read(&manifest_path).await?)?;
+        assert!(manifest
+            .split_footers
+            .contains(&SplitFooterEntry::from(cache_key)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_save_cache_snapshot() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let manifest_path = cache_manifest_path(tempdir.path());
+        let path = PathBuf::from("test-save-cache-snapshot.fast");
+        put_long_term_cache_fast_field_slice(path.clone(), 2..5, OwnedBytes::new(&b"abc"[..]));
+        let (_, num_fast_field_slices) = save_cache_snapshot(&manifest_path).await?;
+        assert!(num_fast_field_slices >= 1);
+
+        let manifest = read_cache_manifest(&manifest_path).await?.unwrap();
+        assert_eq!(manifest.fast_field_slices.len(), num_fast_field_slices);
+        let data = tokio::fs::read(cache_manifest_data_path(&manifest_path)).await?;
+        let mut offset = 0;
+        let mut slice_bytes_opt = None;
+        for fast_field_slice in &manifest.fast_field_slices {
+            let end_offset = offset + fast_field_slice.num_bytes as usize;
+            if fast_field_slice.path == path {
+                assert_eq!(fast_field_slice.byte_range(), 2..5);
+                slice_bytes_opt = Some(data[offset..end_offset].to_vec());
+            }
+            offset = end_offset;
+        }
+        assert_eq!(offset, data.len());
+        assert_eq!(slice_bytes_opt.unwrap(), b"abc");
+
+        // The periodic saves drop the fast field slices.
+        save_cache_manifest(&manifest_path).await?;
+        let manifest = read_cache_manifest(&manifest_path).await?.unwrap();
+        assert!(manifest.fast_field_slices.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_fast_field_cache() -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let manifest_path = cache_manifest_path(tempdir.path());
+        assert_eq!(warm_up_fast_field_cache(&manifest_path).await?, 0);
+
+        let fast_field_slices = vec![
+            FastFieldSliceEntry {
+                path: PathBuf::from("test-warm-up-1.fast"),
+                start: 0,
+                end: usize::MAX as u64,
+                num_bytes: 3,
+            },
+            FastFieldSliceEntry {
+                path: PathBuf::from("test-warm-up-2.fast"),
+                start: 10,
+                end: 12,
+                num_bytes: 2,
+            },
+            // The data file is truncated: this slice is skipped.
+            FastFieldSliceEntry {
+                path: PathBuf::from("test-warm-up-3.fast"),
+                start: 0,
+                end: 4,
+                num_bytes: 4,
+            },
+        ];
+        write_cache_manifest(
+            &manifest_path,
+            &CacheManifest {
+                split_footers: Vec::new(),
+                fast_field_slices,
+            },
+        )
+        .await?;
+        tokio::fs::write(cache_manifest_data_path(&manifest_path), b"abcde").await?;
+        assert_eq!(warm_up_fast_field_cache(&manifest_path).await?, 2);
+
+        let cached_slices: Vec<(PathBuf, Range<usize>, Vec<u8>)> =
+            long_term_cache_fast_field_slices()
+                .into_iter()
+                .filter(|(path, _, _)| path.to_string_lossy().starts_with("test-warm-up-"))
+                .map(|(path, byte_range, bytes)| (path, byte_range, bytes.as_slice().to_vec()))
+                .collect();
+        assert_eq!(
+            cached_slices,
+            vec![
+                (
+                    PathBuf::from("test-warm-up-1.fast"),
+                    0..usize::MAX,
+                    b"abc".to_vec()
+                ),
+                (PathBuf::from("test-warm-up-2.fast"), 10..12, b"de".to_vec()),
+            ]
+        );
+        Ok(())
+    }
+}
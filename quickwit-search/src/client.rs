@@ -32,6 +32,7 @@ use tracing::*;
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::error::parse_grpc_error;
+use crate::leaf_chunks::{decode_leaf_search_response_chunk, merge_leaf_search_response_chunk};
 use crate::SearchService;
 
 struct MetadataMap<'a>(&'a mut tonic::metadata::MetadataMap);
@@ -134,11 +135,23 @@ impl SearchServiceClient {
                         &mut MetadataMap(tonic_request.metadata_mut()),
                     )
                 });
-                let tonic_response = grpc_client
-                    .leaf_search(tonic_request)
+                // Large responses are split into chunks by the leaf node. The chunks are merged
+                // as they are received.
+                let mut chunks_stream = grpc_client
+                    .leaf_search_chunks(tonic_request)
                     .await
-                    .map_err(|tonic_error| parse_grpc_error(&tonic_error))?;
-                Ok(tonic_response.into_inner())
+                    .map_err(|tonic_error| parse_grpc_error(&tonic_error))?
+                    .into_inner();
+                let mut leaf_search_response = quickwit_proto::LeafSearchResponse::default();
+                while let Some(chunk) = chunks_stream
+                    .message()
+                    .await
+                    .map_err(|tonic_error| parse_grpc_error(&tonic_error))?
+                {
+                    let chunk = decode_leaf_search_response_chunk(chunk)?;
+                    merge_leaf_search_response_chunk(&mut leaf_search_response, chunk);
+                }
+                Ok(leaf_search_response)
             }
             SearchServiceClientImpl::Local(service) => service.leaf_search(request).await,
         }
@@ -30,6 +30,7 @@ use tracing::debug;
 use crate::retry::search::LeafSearchRetryPolicy;
 use crate::retry::search_stream::{LeafSearchStreamRetryPolicy, SuccessfullSplitIds};
 use crate::retry::{retry_client, DefaultRetryPolicy, RetryPolicy};
+use crate::search_timings::merge_leaf_search_timings;
 use crate::{SearchClientPool, SearchError, SearchServiceClient};
 
 /// Client that executes placed requests (Request, `SearchServiceClient`) and provides
@@ -161,6 +162,20 @@ fn merge_leaf_search_results(
                     + retry_response.num_attempted_splits,
                 failed_splits: retry_response.failed_splits,
                 partial_hits: initial_response.partial_hits,
+                timings: merge_leaf_search_timings([
+                    initial_response.timings.as_ref(),
+                    retry_response.timings.as_ref(),
+                ]),
+                query_rewrites: initial_response
+                    .query_rewrites
+                    .into_iter()
+                    .chain(retry_response.query_rewrites)
+                    .collect(),
+                split_profiles: initial_response
+                    .split_profiles
+                    .into_iter()
+                    .chain(retry_response.split_profiles)
+                    .collect(),
             };
             Ok(merged_response)
         }
@@ -228,6 +243,8 @@ mod tests {
                 split_id: split_id.to_string(),
                 split_footer_end: 100,
                 split_footer_start: 0,
+                sort_by_field: None,
+                sort_order: None,
             }],
         }
     }
@@ -252,11 +269,15 @@ mod tests {
                     split_id: "split_1".to_string(),
                     split_footer_start: 0,
                     split_footer_end: 100,
+                    sort_by_field: None,
+                    sort_order: None,
                 },
                 SplitIdAndFooterOffsets {
                     split_id: "split_2".to_string(),
                     split_footer_start: 0,
                     split_footer_end: 100,
+                    sort_by_field: None,
+                    sort_order: None,
                 },
             ],
         }
@@ -272,6 +293,8 @@ mod tests {
             fast_field: "fast".to_string(),
             output_format: 0,
             partition_by_field: None,
+            start_time_expression: None,
+            end_time_expression: None,
         };
         LeafSearchStreamRequest {
             request: Some(search_request),
@@ -282,11 +305,15 @@ mod tests {
                     split_id: "split_1".to_string(),
                     split_footer_start: 0,
                     split_footer_end: 100,
+                    sort_by_field: None,
+                    sort_order: None,
                 },
                 SplitIdAndFooterOffsets {
                     split_id: "split_2".to_string(),
                     split_footer_start: 0,
                     split_footer_end: 100,
+                    sort_by_field: None,
+                    sort_order: None,
                 },
             ],
         }
@@ -368,6 +395,9 @@ mod tests {
                     partial_hits: vec![],
                     failed_splits: vec![],
                     num_attempted_splits: 1,
+                    timings: None,
+                    query_rewrites: Vec::new(),
+                    split_profiles: Vec::new(),
                 })
             });
         let client_pool = SearchClientPool::from_mocks(vec![Arc::new(mock_service)]).await?;
@@ -396,6 +426,9 @@ mod tests {
                         retryable_error: true,
                     }],
                     num_attempted_splits: 1,
+                    timings: None,
+                    query_rewrites: Vec::new(),
+                    split_profiles: Vec::new(),
                 })
             });
         mock_service
@@ -411,6 +444,9 @@ mod tests {
                         retryable_error: true,
                     }],
                     num_attempted_splits: 1,
+                    timings: None,
+                    query_rewrites: Vec::new(),
+                    split_profiles: Vec::new(),
                 })
             });
         let client_pool = SearchClientPool::from_mocks(vec![Arc::new(mock_service)]).await?;
@@ -436,12 +472,18 @@ mod tests {
             partial_hits: vec![mock_partial_hit("split_1", 3, 1)],
             failed_splits: vec![split_error],
             num_attempted_splits: 1,
+            timings: None,
+            query_rewrites: Vec::new(),
+            split_profiles: Vec::new(),
         };
         let leaf_response_retry = LeafSearchResponse {
             num_hits: 1,
             partial_hits: vec![mock_partial_hit("split_2", 3, 1)],
             failed_splits: vec![],
             num_attempted_splits: 1,
+            timings: None,
+            query_rewrites: Vec::new(),
+            split_profiles: Vec::new(),
         };
         let merged_leaf_search_response =
             merge_leaf_search_results(Ok(leaf_response), Ok(leaf_response_retry)).unwrap();
@@ -464,6 +506,9 @@ mod tests {
             partial_hits: vec![mock_partial_hit("split_1", 3, 1)],
             failed_splits: vec![split_error],
             num_attempted_splits: 1,
+            timings: None,
+            query_rewrites: Vec::new(),
+            split_profiles: Vec::new(),
         };
         let merged_result = merge_leaf_search_results(
             Err(SearchError::InternalError("error".to_string())),
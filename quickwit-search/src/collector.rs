@@ -20,9 +20,11 @@
 use std::cmp::Ordering;
 use std::collections::{BinaryHeap, HashSet};
 
-use itertools::Itertools;
-use quickwit_doc_mapper::{DocMapper, SortBy, SortOrder};
-use quickwit_proto::{LeafSearchResponse, PartialHit, SearchRequest};
+use quickwit_doc_mapper::{DocMapper, SortBy, SortByField, SortOrder};
+use quickwit_proto::{
+    LeafSearchResponse, LeafSearchTimings, PartialHit, SearchRequest, SplitIdAndFooterOffsets,
+    SplitProfile, SplitQueryRewrite, SplitSearchError,
+};
 use tantivy::collector::{Collector, SegmentCollector};
 use tantivy::fastfield::{DynamicFastFieldReader, FastFieldReader};
 use tantivy::schema::{Field, Schema};
@@ -30,6 +32,7 @@ use tantivy::{DocId, Score, SegmentOrdinal, SegmentReader};
 
 use crate::filters::TimestampFilter;
 use crate::partial_hit_sorting_key;
+use crate::search_timings::merge_leaf_search_timings;
 
 /// The `SortingFieldComputer` can be seen as the specialization of `SortBy` applied to a specific
 /// `SegmentReader`. Its role is to compute the sorting field given a `DocId`.
@@ -136,6 +139,9 @@ pub struct QuickwitSegmentCollector {
     max_hits: usize,
     segment_ord: u32,
     timestamp_filter_opt: Option<TimestampFilter>,
+    /// True if the documents of the segment are visited in decreasing sorting field order.
+    /// Once the heap is full, no later document can enter the top-k.
+    is_presorted: bool,
 }
 
 impl QuickwitSegmentCollector {
@@ -144,6 +150,9 @@ impl QuickwitSegmentCollector {
     }
 
     fn collect_top_k(&mut self, doc_id: DocId) {
+        if self.is_presorted && self.at_capacity() {
+            return;
+        }
         let sorting_field_value: u64 = self.sort_by.compute_sorting_field(doc_id);
         if self.at_capacity() {
             if let Some(limit_sorting_field) = self.hits.peek().map(|head| head.sorting_field_value)
@@ -206,6 +215,9 @@ impl SegmentCollector for QuickwitSegmentCollector {
             partial_hits,
             failed_splits: vec![],
             num_attempted_splits: 1,
+            timings: None,
+            query_rewrites: Vec::new(),
+            split_profiles: Vec::new(),
         }
     }
 }
@@ -229,6 +241,26 @@ pub struct QuickwitCollector {
     pub timestamp_field_opt: Option<Field>,
     pub start_timestamp_opt: Option<i64>,
     pub end_timestamp_opt: Option<i64>,
+    /// Sort applied to the documents of the split at creation, as recorded in its metadata.
+    pub split_sort_by_field_opt: Option<SortByField>,
+}
+
+impl QuickwitCollector {
+    /// Returns true if the documents of each segment of the split are already sorted according to
+    /// the requested sort order. In that case, segment collectors can stop collecting top-k
+    /// candidates as soon as they reach their capacity.
+    fn is_split_presorted(&self) -> bool {
+        match &self.sort_by {
+            SortBy::DocId => true,
+            SortBy::FastField { field_name, order } => {
+                self.split_sort_by_field_opt.as_ref()
+                    == Some(&SortByField {
+                        field_name: field_name.clone(),
+                        order: *order,
+                    })
+            }
+        }
+    }
 }
 
 impl GenericQuickwitCollector for QuickwitCollector {
@@ -270,6 +302,7 @@ impl Collector for QuickwitCollector {
             segment_ord,
             max_hits: leaf_max_hits,
             timestamp_filter_opt,
+            is_presorted: self.is_split_presorted(),
         })
     }
 
@@ -284,72 +317,120 @@ impl Collector for QuickwitCollector {
         &self,
         segment_fruits: Vec<LeafSearchResponse>,
     ) -> tantivy::Result<Self::Fruit> {
-        // We want the hits in [start_offset..start_offset + max_hits).
-        // All leaves will return their top [0..max_hits) documents.
-        // We compute the overall [0..start_offset + max_hits) documents ...
-        let num_hits = self.start_offset + self.max_hits;
-        let mut merged_leaf_response = merge_leaf_responses(segment_fruits, num_hits);
-        // ... and drop the first [..start_offsets) hits.
-        merged_leaf_response
-            .partial_hits
-            .drain(
-                0..self
-                    .start_offset
-                    .min(merged_leaf_response.partial_hits.len()),
-            )
-            .count(); //< we just use count as a way to consume the entire iterator.
-        Ok(merged_leaf_response)
+        let mut merger = LeafResponseMerger::new(self.start_offset, self.max_hits);
+        for segment_fruit in segment_fruits {
+            merger.add(segment_fruit);
+        }
+        Ok(merger.finish())
     }
 }
 
-/// Merges a set of Leaf Results.
-fn merge_leaf_responses(
-    leaf_responses: Vec<LeafSearchResponse>,
-    max_hits: usize,
-) -> LeafSearchResponse {
-    // Optimization: No merging needed if there is only one result.
-    if leaf_responses.len() == 1 {
-        return leaf_responses.into_iter().next().unwrap_or_default(); //< default is actually never called
-    }
-    let num_attempted_splits = leaf_responses
-        .iter()
-        .map(|leaf_response| leaf_response.num_attempted_splits)
-        .sum();
-    let num_hits: u64 = leaf_responses
-        .iter()
-        .map(|leaf_response| leaf_response.num_hits)
-        .sum();
-    let failed_splits = leaf_responses
-        .iter()
-        .flat_map(|leaf_response| leaf_response.failed_splits.iter())
-        .cloned()
-        .collect_vec();
-    let all_partial_hits: Vec<PartialHit> = leaf_responses
-        .into_iter()
-        .flat_map(|leaf_response| leaf_response.partial_hits)
-        .collect();
-    // TODO optimize
-    let top_k_partial_hits = top_k_partial_hits(all_partial_hits, max_hits);
-    LeafSearchResponse {
-        num_hits,
-        partial_hits: top_k_partial_hits,
-        failed_splits,
-        num_attempted_splits,
+/// Orders partial hits from the best to the worst, so that the worst hit is at the top of a
+/// `BinaryHeap`.
+struct PartialHitMergeItem(PartialHit);
+
+impl PartialOrd for PartialHitMergeItem {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
-/// Mutates partial_hits so that it contains the top-num_hitso hits,
-/// and so that these elements are sorted.
+impl Ord for PartialHitMergeItem {
+    fn cmp(&self, other: &Self) -> Ordering {
+        partial_hit_sorting_key(&self.0).cmp(&partial_hit_sorting_key(&other.0))
+    }
+}
+
+impl PartialEq for PartialHitMergeItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PartialHitMergeItem {}
+
+/// Merges leaf responses one at a time, as they arrive, keeping only the top hits seen so far.
 ///
-/// TODO we could possibly optimize the sort away (but I doubt it matters).
-fn top_k_partial_hits(mut partial_hits: Vec<PartialHit>, num_hits: usize) -> Vec<PartialHit> {
-    partial_hits.sort_unstable_by(|left, right| {
-        let left_key = partial_hit_sorting_key(left);
-        let right_key = partial_hit_sorting_key(right);
-        left_key.cmp(&right_key)
-    });
-    partial_hits.truncate(num_hits);
-    partial_hits
+/// Unlike collecting all the responses before merging them, the hits of a response that do not
+/// make it to the top can be dropped as soon as the response is received.
+pub(crate) struct LeafResponseMerger {
+    start_offset: usize,
+    num_top_hits: usize,
+    num_hits: u64,
+    num_attempted_splits: u64,
+    failed_splits: Vec<SplitSearchError>,
+    timings: Option<LeafSearchTimings>,
+    query_rewrites: Vec<SplitQueryRewrite>,
+    split_profiles: Vec<SplitProfile>,
+    top_hits: BinaryHeap<PartialHitMergeItem>,
+}
+
+impl LeafResponseMerger {
+    /// Creates a merger retaining the hits in `[start_offset..start_offset + max_hits)`.
+    pub fn new(start_offset: usize, max_hits: usize) -> LeafResponseMerger {
+        let num_top_hits = start_offset + max_hits;
+        LeafResponseMerger {
+            start_offset,
+            num_top_hits,
+            num_hits: 0,
+            num_attempted_splits: 0,
+            failed_splits: Vec::new(),
+            timings: None,
+            query_rewrites: Vec::new(),
+            split_profiles: Vec::new(),
+            top_hits: BinaryHeap::with_capacity(num_top_hits),
+        }
+    }
+
+    /// Creates a merger retaining the hits requested by `search_request`.
+    pub fn for_request(search_request: &SearchRequest) -> LeafResponseMerger {
+        LeafResponseMerger::new(
+            search_request.start_offset as usize,
+            search_request.max_hits as usize,
+        )
+    }
+
+    /// Merges a leaf response into the responses merged so far.
+    pub fn add(&mut self, leaf_response: LeafSearchResponse) {
+        self.num_hits += leaf_response.num_hits;
+        self.num_attempted_splits += leaf_response.num_attempted_splits;
+        self.failed_splits.extend(leaf_response.failed_splits);
+        self.timings =
+            merge_leaf_search_timings([self.timings.as_ref(), leaf_response.timings.as_ref()]);
+        self.query_rewrites.extend(leaf_response.query_rewrites);
+        self.split_profiles.extend(leaf_response.split_profiles);
+        for partial_hit in leaf_response.partial_hits {
+            let item = PartialHitMergeItem(partial_hit);
+            if self.top_hits.len() < self.num_top_hits {
+                self.top_hits.push(item);
+            } else if let Some(mut worst_item) = self.top_hits.peek_mut() {
+                if item < *worst_item {
+                    *worst_item = item;
+                }
+            }
+        }
+    }
+
+    /// Returns the merged response, with its hits sorted.
+    pub fn finish(self) -> LeafSearchResponse {
+        // The items are sorted from the best to the worst hit.
+        let partial_hits = self
+            .top_hits
+            .into_sorted_vec()
+            .into_iter()
+            .skip(self.start_offset)
+            .map(|item| item.0)
+            .collect();
+        LeafSearchResponse {
+            num_hits: self.num_hits,
+            partial_hits,
+            failed_splits: self.failed_splits,
+            num_attempted_splits: self.num_attempted_splits,
+            timings: self.timings,
+            query_rewrites: self.query_rewrites,
+            split_profiles: self.split_profiles,
+        }
+    }
 }
 
 /// Extracts all fast field names.
@@ -370,6 +451,7 @@ pub fn make_collector_for_split(
     doc_mapper: &dyn DocMapper,
     search_request: &SearchRequest,
     split_schema: &Schema,
+    split: &SplitIdAndFooterOffsets,
 ) -> QuickwitCollector {
     QuickwitCollector {
         split_id,
@@ -380,13 +462,24 @@ pub fn make_collector_for_split(
         timestamp_field_opt: doc_mapper.timestamp_field(split_schema),
         start_timestamp_opt: search_request.start_timestamp,
         end_timestamp_opt: search_request.end_timestamp,
+        split_sort_by_field_opt: split_sort_by_field(split),
     }
 }
 
+/// Returns the sort applied to the documents of the split at creation, which the root reads from
+/// the split metadata.
+fn split_sort_by_field(split: &SplitIdAndFooterOffsets) -> Option<SortByField> {
+    split.sort_by_field.as_ref().map(|field_name| SortByField {
+        field_name: field_name.clone(),
+        order: split.sort_order.map(SortOrder::from).unwrap_or_default(),
+    })
+}
+
 /// Builds a QuickwitCollector that's only useful for merging fruits.
 ///
 /// This collector only needs `start_offset` & `max_hit` so the other attributes
-/// can be set to default.
+/// can be set to default. Merging leaf responses is done by [`LeafResponseMerger`].
+#[cfg(test)]
 pub fn make_merge_collector(search_request: &SearchRequest) -> QuickwitCollector {
     QuickwitCollector {
         split_id: String::default(),
@@ -397,6 +490,7 @@ pub fn make_merge_collector(search_request: &SearchRequest) -> QuickwitCollector
         timestamp_field_opt: None,
         start_timestamp_opt: search_request.start_timestamp,
         end_timestamp_opt: search_request.end_timestamp,
+        split_sort_by_field_opt: None,
     }
 }
 
@@ -404,10 +498,60 @@ pub fn make_merge_collector(search_request: &SearchRequest) -> QuickwitCollector
 mod tests {
     use std::cmp::Ordering;
 
-    use quickwit_proto::PartialHit;
+    use quickwit_doc_mapper::{SortBy, SortByField, SortOrder};
+    use quickwit_proto::{
+        LeafSearchResponse, PartialHit, SearchRequest, SortOrder as ProtoSortOrder,
+        SplitIdAndFooterOffsets, SplitSearchError,
+    };
 
     use super::PartialHitHeapItem;
-    use crate::collector::top_k_partial_hits;
+    use crate::collector::{make_merge_collector, split_sort_by_field, LeafResponseMerger};
+
+    #[test]
+    fn test_split_sort_by_field() {
+        let mut split = SplitIdAndFooterOffsets::default();
+        assert_eq!(split_sort_by_field(&split), None);
+
+        split.sort_by_field = Some("timestamp".to_string());
+        split.sort_order = Some(ProtoSortOrder::Asc as i32);
+        assert_eq!(
+            split_sort_by_field(&split),
+            Some(SortByField {
+                field_name: "timestamp".to_string(),
+                order: SortOrder::Asc,
+            })
+        );
+    }
+
+    #[test]
+    fn test_is_split_presorted() {
+        let mut collector = make_merge_collector(&SearchRequest::default());
+        assert!(collector.is_split_presorted());
+
+        collector.sort_by = SortBy::FastField {
+            field_name: "timestamp".to_string(),
+            order: SortOrder::Desc,
+        };
+        assert!(!collector.is_split_presorted());
+
+        collector.split_sort_by_field_opt = Some(SortByField {
+            field_name: "timestamp".to_string(),
+            order: SortOrder::Desc,
+        });
+        assert!(collector.is_split_presorted());
+
+        collector.split_sort_by_field_opt = Some(SortByField {
+            field_name: "timestamp".to_string(),
+            order: SortOrder::Asc,
+        });
+        assert!(!collector.is_split_presorted());
+
+        collector.split_sort_by_field_opt = Some(SortByField {
+            field_name: "response_time".to_string(),
+            order: SortOrder::Desc,
+        });
+        assert!(!collector.is_split_presorted());
+    }
 
     #[test]
     fn test_partial_hit_ordered_by_sorting_field() {
@@ -422,6 +566,15 @@ mod tests {
         assert_eq!(lesser_score.cmp(&higher_score), Ordering::Greater);
     }
 
+    fn merge_partial_hits(partial_hits: Vec<PartialHit>, max_hits: usize) -> Vec<PartialHit> {
+        let mut merger = LeafResponseMerger::new(0, max_hits);
+        merger.add(LeafSearchResponse {
+            partial_hits,
+            ..Default::default()
+        });
+        merger.finish().partial_hits
+    }
+
     #[test]
     fn test_merge_partial_hits_no_tie() {
         let make_doc = |sorting_field_value: u64| PartialHit {
@@ -431,7 +584,7 @@ mod tests {
             doc_id: 0u32,
         };
         assert_eq!(
-            top_k_partial_hits(vec![make_doc(1u64), make_doc(3u64), make_doc(2u64),], 2),
+            merge_partial_hits(vec![make_doc(1u64), make_doc(3u64), make_doc(2u64),], 2),
             vec![make_doc(3), make_doc(2)]
         );
     }
@@ -445,7 +598,7 @@ mod tests {
             doc_id: 0u32,
         };
         assert_eq!(
-            top_k_partial_hits(
+            merge_partial_hits(
                 vec![
                     make_hit_given_split_id(1u64),
                     make_hit_given_split_id(3u64),
@@ -456,4 +609,49 @@ mod tests {
             vec![make_hit_given_split_id(1), make_hit_given_split_id(2)]
         );
     }
+
+    #[test]
+    fn test_leaf_response_merger() {
+        let make_hit = |split_id: &str, sorting_field_value: u64| PartialHit {
+            sorting_field_value,
+            split_id: split_id.to_string(),
+            segment_ord: 0u32,
+            doc_id: 0u32,
+        };
+        let mut merger = LeafResponseMerger::new(1, 2);
+        merger.add(LeafSearchResponse {
+            num_hits: 10,
+            partial_hits: vec![make_hit("split1", 5), make_hit("split1", 2)],
+            num_attempted_splits: 1,
+            ..Default::default()
+        });
+        merger.add(LeafSearchResponse {
+            num_hits: 0,
+            failed_splits: vec![SplitSearchError {
+                split_id: "split2".to_string(),
+                error: "error".to_string(),
+                retryable_error: true,
+            }],
+            num_attempted_splits: 1,
+            ..Default::default()
+        });
+        merger.add(LeafSearchResponse {
+            num_hits: 20,
+            partial_hits: vec![
+                make_hit("split3", 7),
+                make_hit("split3", 4),
+                make_hit("split3", 1),
+            ],
+            num_attempted_splits: 1,
+            ..Default::default()
+        });
+        let merged_response = merger.finish();
+        assert_eq!(merged_response.num_hits, 30);
+        assert_eq!(merged_response.num_attempted_splits, 3);
+        assert_eq!(merged_response.failed_splits.len(), 1);
+        assert_eq!(
+            merged_response.partial_hits,
+            vec![make_hit("split1", 5), make_hit("split3", 4)]
+        );
+    }
 }
@@ -37,6 +37,13 @@ pub enum SearchError {
     StorageResolverError(#[from] StorageResolverError),
     #[error("Invalid query: {0}")]
     InvalidQuery(String),
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error(
+        "Splits {split_ids:?} pinned by the search no longer exist: they were deleted after being \
+         merged or garbage collected. Retry the search without its snapshot."
+    )]
+    SnapshotExpired { split_ids: Vec<String> },
 }
 
 impl From<SearchError> for tonic::Status {
@@ -46,6 +53,8 @@ impl From<SearchError> for tonic::Status {
             SearchError::InternalError(_) => tonic::Code::Internal,
             SearchError::StorageResolverError(_) => tonic::Code::Internal,
             SearchError::InvalidQuery(_) => tonic::Code::InvalidArgument,
+            SearchError::InvalidArgument(_) => tonic::Code::InvalidArgument,
+            SearchError::SnapshotExpired { .. } => tonic::Code::FailedPrecondition,
         };
         let message = error.to_string();
         tonic::Status::new(code, message)
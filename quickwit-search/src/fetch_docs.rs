@@ -22,12 +22,12 @@ use std::sync::Arc;
 
 use anyhow::Context;
 use itertools::Itertools;
+use quickwit_common::memory_usage::{MemoryComponent, MemoryUsageGuard};
 use quickwit_proto::{FetchDocsResponse, Hit, PartialHit, SplitIdAndFooterOffsets};
 use quickwit_storage::Storage;
-use tantivy::{IndexReader, ReloadPolicy};
 use tracing::error;
 
-use crate::leaf::open_index;
+use crate::split_reader_pool::split_reader_pool;
 use crate::GlobalDocAddress;
 
 /// Given a list of global doc address, fetches all the documents and
@@ -106,6 +106,10 @@ pub async fn fetch_docs(
 
     let mut global_doc_addr_to_doc_json =
         fetch_docs_to_map(global_doc_addrs, index_storage, splits).await?;
+    let _memory_usage_guard = MemoryUsageGuard::new(
+        MemoryComponent::FetchDocs,
+        global_doc_addr_to_doc_json.values().map(String::len).sum(),
+    );
 
     let hits: Vec<Hit> = partial_hits
         .iter()
@@ -124,22 +128,6 @@ pub async fn fetch_docs(
     Ok(FetchDocsResponse { hits })
 }
 
-async fn get_searcher_for_split(
-    num_searchers: usize,
-    index_storage: Arc<dyn Storage>,
-    split: &SplitIdAndFooterOffsets,
-) -> anyhow::Result<IndexReader> {
-    let index = open_index(index_storage, split)
-        .await
-        .with_context(|| "open-index-for-split")?;
-    let reader = index
-        .reader_builder()
-        .num_searchers(num_searchers)
-        .reload_policy(ReloadPolicy::Manual)
-        .try_into()?;
-    Ok(reader)
-}
-
 /// Fetching docs from a specific split.
 #[tracing::instrument(skip(global_doc_addrs, index_storage, split))]
 #[allow(clippy::needless_lifetimes)]
@@ -148,17 +136,21 @@ async fn fetch_docs_in_split<'a>(
     index_storage: Arc<dyn Storage>,
     split: &SplitIdAndFooterOffsets,
 ) -> anyhow::Result<Vec<(GlobalDocAddress<'a>, String)>> {
-    let index_reader = get_searcher_for_split(global_doc_addrs.len(), index_storage, split).await?;
-    let doc_futures = global_doc_addrs.into_iter().map(|global_doc_addr| {
-        let searcher = index_reader.searcher();
-        async move {
+    let searcher = split_reader_pool()
+        .searcher(index_storage, split)
+        .await
+        .with_context(|| "open-index-for-split")?;
+    // The documents are fetched concurrently with the same searcher.
+    let searcher = &searcher;
+    let doc_futures = global_doc_addrs
+        .into_iter()
+        .map(|global_doc_addr| async move {
             let doc = searcher
                 .doc_async(global_doc_addr.doc_addr)
                 .await
                 .context("searcher-doc-async")?;
             let doc_json = searcher.schema().to_json(&doc);
             Ok((global_doc_addr, doc_json))
-        }
-    });
+        });
     futures::future::try_join_all(doc_futures).await
 }
@@ -18,48 +18,73 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use std::collections::{BTreeMap, HashSet};
-use std::path::PathBuf;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 
 use anyhow::Context;
 use futures::future::try_join_all;
-use itertools::{Either, Itertools};
+use futures::stream::{FuturesUnordered, StreamExt};
 use once_cell::sync::OnceCell;
+use quickwit_common::memory_usage::{MemoryComponent, MemoryUsageGuard};
+use quickwit_common::split_file;
+use quickwit_common::uri::Uri;
 use quickwit_config::get_searcher_config_instance;
-use quickwit_directories::{CachingDirectory, HotDirectory, StorageDirectory};
+use quickwit_directories::{BundleDirectory, CachingDirectory, HotDirectory, StorageDirectory};
 use quickwit_doc_mapper::DocMapper;
 use quickwit_proto::{
-    LeafSearchResponse, SearchRequest, SplitIdAndFooterOffsets, SplitSearchError,
+    LeafSearchResponse, LeafSearchTimings, SearchRequest, SplitIdAndFooterOffsets,
+    SplitQueryRewrite, SplitSearchError,
 };
 use quickwit_storage::{
     wrap_storage_with_long_term_cache, BundleStorage, MemorySizedCache, OwnedBytes, Storage,
 };
-use tantivy::collector::Collector;
-use tantivy::directory::FileSlice;
+use tantivy::directory::{FileSlice, MmapDirectory};
 use tantivy::query::Query;
-use tantivy::{Index, ReloadPolicy, Searcher, Term};
-use tokio::task::spawn_blocking;
+use tantivy::{Directory, Index, Searcher, Term};
 use tracing::*;
 
-use crate::collector::{make_collector_for_split, make_merge_collector, GenericQuickwitCollector};
+use crate::collector::{make_collector_for_split, GenericQuickwitCollector, LeafResponseMerger};
+use crate::profile::profile_search;
+use crate::search_timings::record_leaf_search_timings;
+use crate::speculative_search::SpeculativeSplitSearch;
+use crate::split_reader_pool::split_reader_pool;
+use crate::split_search_admission::acquire_split_search_permit;
 use crate::SearchError;
 
-fn global_split_footer_cache() -> &'static MemorySizedCache<String> {
-    static INSTANCE: OnceCell<MemorySizedCache<String>> = OnceCell::new();
+/// Location of the footer of a split, used as key of the split footer cache. Keys hold enough
+/// information to fetch the footers again, see [`crate::cache_manifest`].
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub(crate) struct SplitFooterCacheKey {
+    pub index_uri: String,
+    pub split_id: String,
+    pub footer_offsets: Range<u64>,
+}
+
+pub(crate) fn global_split_footer_cache() -> &'static MemorySizedCache<SplitFooterCacheKey> {
+    static INSTANCE: OnceCell<MemorySizedCache<SplitFooterCacheKey>> = OnceCell::new();
     INSTANCE.get_or_init(|| {
         let config = get_searcher_config_instance();
         MemorySizedCache::with_capacity_in_bytes(
-            config.split_footer_cache_capacity.get_bytes() as usize
+            config.split_footer_cache_capacity.get_bytes() as usize,
+            MemoryComponent::SplitFooterCache,
         )
     })
 }
 
-async fn get_split_footer_from_cache_or_fetch(
+pub(crate) async fn get_split_footer_from_cache_or_fetch(
     index_storage: Arc<dyn Storage>,
     split_and_footer_offsets: &SplitIdAndFooterOffsets,
 ) -> anyhow::Result<OwnedBytes> {
+    let cache_key = SplitFooterCacheKey {
+        index_uri: index_storage.uri(),
+        split_id: split_and_footer_offsets.split_id.clone(),
+        footer_offsets: split_and_footer_offsets.split_footer_start
+            ..split_and_footer_offsets.split_footer_end,
+    };
     {
-        let possible_val = global_split_footer_cache().get(&split_and_footer_offsets.split_id);
+        let possible_val = global_split_footer_cache().get(&cache_key);
         if let Some(footer_data) = possible_val {
             return Ok(footer_data);
         }
@@ -80,21 +105,57 @@ async fn get_split_footer_from_cache_or_fetch(
             )
         })?;
 
-    global_split_footer_cache().put(
-        split_and_footer_offsets.split_id.to_owned(),
-        footer_data_opt.clone(),
-    );
+    global_split_footer_cache().put(cache_key, footer_data_opt.clone());
 
     Ok(footer_data_opt)
 }
 
+/// Returns the path of the split file when the index lives on a local disk. Such splits are
+/// memory-mapped rather than read through the storage, and need no warmup.
+pub(crate) fn local_split_path(index_storage: &dyn Storage, split_id: &str) -> Option<PathBuf> {
+    let index_uri = Uri::try_new(&index_storage.uri()).ok()?;
+    let split_path = index_uri.filepath()?.join(split_file(split_id));
+    if split_path.is_file() {
+        Some(split_path)
+    } else {
+        None
+    }
+}
+
+/// Opens a split stored on a local disk by memory-mapping its file.
+fn open_local_index(split_path: &Path) -> anyhow::Result<Index> {
+    let split_dir_path = split_path
+        .parent()
+        .with_context(|| format!("Split file `{}` has no parent.", split_path.display()))?;
+    let split_file_slice = MmapDirectory::open(split_dir_path)?.open_read(split_path)?;
+    let bundle_directory = BundleDirectory::open_split(split_file_slice)?;
+    let index = Index::open(bundle_directory)?;
+    Ok(index)
+}
+
 /// Opens a `tantivy::Index` for the given split.
 ///
-/// The resulting index uses a dynamic and a static cache.
+/// The resulting index uses a dynamic and a static cache, unless the split is on a local disk,
+/// in which case its file is memory-mapped.
 pub(crate) async fn open_index(
     index_storage: Arc<dyn Storage>,
     split_and_footer_offsets: &SplitIdAndFooterOffsets,
 ) -> anyhow::Result<Index> {
+    let (index, _caching_directory_opt) =
+        open_index_with_cache(index_storage, split_and_footer_offsets).await?;
+    Ok(index)
+}
+
+/// Opens the index of a split like [`open_index`], and also returns the directory caching the
+/// data downloaded while searching the split, unless the split is read from a local file.
+pub(crate) async fn open_index_with_cache(
+    index_storage: Arc<dyn Storage>,
+    split_and_footer_offsets: &SplitIdAndFooterOffsets,
+) -> anyhow::Result<(Index, Option<CachingDirectory>)> {
+    if let Some(split_path) = local_split_path(&*index_storage, &split_and_footer_offsets.split_id)
+    {
+        return Ok((open_local_index(&split_path)?, None));
+    }
     let split_file = PathBuf::from(format!("{}.split", split_and_footer_offsets.split_id));
     let footer_data =
         get_split_footer_from_cache_or_fetch(index_storage.clone(), split_and_footer_offsets)
@@ -108,9 +169,10 @@ pub(crate) async fn open_index(
     let bundle_storage_with_cache = wrap_storage_with_long_term_cache(Arc::new(bundle_storage));
     let directory = StorageDirectory::new(bundle_storage_with_cache);
     let caching_directory = CachingDirectory::new_with_unlimited_capacity(Arc::new(directory));
-    let hot_directory = HotDirectory::open(caching_directory, hotcache_bytes.read_bytes()?)?;
+    let hot_directory =
+        HotDirectory::open(caching_directory.clone(), hotcache_bytes.read_bytes()?)?;
     let index = Index::open(hot_directory)?;
-    Ok(index)
+    Ok((index, Some(caching_directory)))
 }
 
 /// Tantivy search does not make it possible to fetch data asynchronously during
@@ -154,7 +216,11 @@ async fn warm_up_fastfields(
 
         let field_entry = searcher.schema().get_field_entry(fast_field);
         if !field_entry.is_fast() {
-            anyhow::bail!("Field {:?} is not a fast field.", fast_field_name);
+            anyhow::bail!(
+                "Field {:?} is not a fast field, please add the fast property to the field in the \
+                 doc mapping to sort on it or stream its values.",
+                fast_field_name
+            );
         }
         fast_fields.push(fast_field);
     }
@@ -201,31 +267,78 @@ async fn leaf_search_single_split(
     doc_mapper: Arc<dyn DocMapper>,
 ) -> crate::Result<LeafSearchResponse> {
     let split_id = split.split_id.to_string();
-    let index = open_index(storage, &split).await?;
-    let split_schema = index.schema();
+    let admission_start = Instant::now();
+    let _split_search_permit = acquire_split_search_permit(&search_request.index_id).await;
+    let admission_wait_duration = admission_start.elapsed();
+    // The split footer, hotcache included, is held in memory while the split is searched.
+    let _memory_usage_guard = MemoryUsageGuard::new(
+        MemoryComponent::LeafSearch,
+        (split.split_footer_end - split.split_footer_start) as usize,
+    );
+    let warmup_start = Instant::now();
+    let is_local_split = local_split_path(&*storage, &split_id).is_some();
+    let searcher = split_reader_pool().searcher(storage, &split).await?;
+    let split_schema = searcher.schema().clone();
     let quickwit_collector = make_collector_for_split(
         split_id.clone(),
         doc_mapper.as_ref(),
         search_request,
         &split_schema,
+        &split,
     );
-    let query = doc_mapper.query(split_schema, search_request)?;
-    let reader = index
-        .reader_builder()
-        .num_searchers(1)
-        .reload_policy(ReloadPolicy::Manual)
-        .try_into()?;
-    let searcher = reader.searcher();
-    warmup(&*searcher, &query, &quickwit_collector.fast_field_names()).await?;
-    let leaf_search_response = crate::run_cpu_intensive(move || {
+    // Splits built before some fields of the query existed are searched with a rewritten query
+    // instead of failing.
+    let (query, missing_fields) = doc_mapper.split_query(split_schema, search_request)?;
+    if !is_local_split {
+        warmup(&*searcher, &query, &quickwit_collector.fast_field_names()).await?;
+    }
+    let warmup_duration = warmup_start.elapsed();
+    let queued_at = Instant::now();
+    let profile = search_request.profile;
+    let (search_result, queue_wait_duration, cpu_duration) = crate::run_cpu_intensive(move || {
+        let queue_wait_duration = queued_at.elapsed();
         let span = info_span!( "search", split_id = %split.split_id);
         let _span_guard = span.enter();
-        searcher.search(&query, &quickwit_collector)
+        let search_start = Instant::now();
+        let search_result = if profile {
+            profile_search(
+                split.split_id,
+                &searcher,
+                query.as_ref(),
+                &quickwit_collector,
+            )
+            .map(|(leaf_search_response, split_profile)| {
+                (leaf_search_response, Some(split_profile))
+            })
+        } else {
+            searcher
+                .search(&query, &quickwit_collector)
+                .map(|leaf_search_response| (leaf_search_response, None))
+        };
+        (search_result, queue_wait_duration, search_start.elapsed())
     })
     .await
     .map_err(|_| {
         crate::SearchError::InternalError(format!("Leaf search panicked. split={}", split_id))
-    })??;
+    })?;
+    let (mut leaf_search_response, split_profile_opt) = search_result?;
+    let timings = LeafSearchTimings {
+        warmup_micros: warmup_duration.as_micros() as u64,
+        queue_wait_micros: (admission_wait_duration + queue_wait_duration).as_micros() as u64,
+        cpu_micros: cpu_duration.as_micros() as u64,
+        num_splits: 1,
+    };
+    record_leaf_search_timings(&timings);
+    leaf_search_response.timings = Some(timings);
+    leaf_search_response
+        .split_profiles
+        .extend(split_profile_opt);
+    if !missing_fields.is_empty() {
+        leaf_search_response.query_rewrites.push(SplitQueryRewrite {
+            split_id,
+            missing_fields,
+        });
+    }
     Ok(leaf_search_response)
 }
 
@@ -240,50 +353,54 @@ pub async fn leaf_search(
     splits: &[SplitIdAndFooterOffsets],
     doc_mapper: Arc<dyn DocMapper>,
 ) -> Result<LeafSearchResponse, SearchError> {
+    let speculative_search_opt = SpeculativeSplitSearch::new(
+        splits.len(),
+        get_searcher_config_instance().speculative_split_search_factor,
+    );
     let leaf_search_single_split_futures: Vec<_> = splits
         .iter()
         .map(|split| {
-            let doc_mapper_clone = doc_mapper.clone();
-            let index_storage_clone = index_storage.clone();
-            async move {
+            let index_storage = &index_storage;
+            let doc_mapper = &doc_mapper;
+            let search_split = move || {
                 leaf_search_single_split(
                     request,
-                    index_storage_clone,
+                    index_storage.clone(),
                     split.clone(),
-                    doc_mapper_clone,
+                    doc_mapper.clone(),
                 )
-                .await
-                .map_err(|err| (split.split_id.clone(), err))
+            };
+            let speculative_search_opt = speculative_search_opt.as_ref();
+            async move {
+                let search_result = match speculative_search_opt {
+                    Some(speculative_search) => {
+                        speculative_search
+                            .search(&split.split_id, search_split)
+                            .await
+                    }
+                    None => search_split().await,
+                };
+                search_result.map_err(|err| (split.split_id.clone(), err))
             }
         })
         .collect();
-    let split_search_results = futures::future::join_all(leaf_search_single_split_futures).await;
-
-    let (split_search_responses, errors): (Vec<LeafSearchResponse>, Vec<(String, SearchError)>) =
-        split_search_results
-            .into_iter()
-            .partition_map(|split_search_res| match split_search_res {
-                Ok(split_search_resp) => Either::Left(split_search_resp),
-                Err(err) => Either::Right(err),
-            });
-
-    // Creates a collector which merges responses into one
-    let merge_collector = make_merge_collector(request);
-
-    // Merging is a cpu-bound task.
-    // It should be executed by Tokio's blocking threads.
-    let mut merged_search_response =
-        spawn_blocking(move || merge_collector.merge_fruits(split_search_responses))
-            .instrument(info_span!("merge_search_responses"))
-            .await
-            .context("Failed to merge split search responses.")??;
-
-    merged_search_response
-        .failed_splits
-        .extend(errors.iter().map(|(split_id, err)| SplitSearchError {
-            split_id: split_id.to_string(),
-            error: format!("{}", err),
-            retryable_error: true,
-        }));
-    Ok(merged_search_response)
+    // Responses are merged as they arrive rather than once all of them have been collected, so
+    // that only the current top hits are retained in memory.
+    let mut leaf_response_merger = LeafResponseMerger::for_request(request);
+    let mut split_search_results: FuturesUnordered<_> =
+        leaf_search_single_split_futures.into_iter().collect();
+    while let Some(split_search_result) = split_search_results.next().await {
+        match split_search_result {
+            Ok(split_search_response) => leaf_response_merger.add(split_search_response),
+            Err((split_id, err)) => leaf_response_merger.add(LeafSearchResponse {
+                failed_splits: vec![SplitSearchError {
+                    split_id,
+                    error: format!("{}", err),
+                    retryable_error: true,
+                }],
+                ..Default::default()
+            }),
+        }
+    }
+    Ok(leaf_response_merger.finish())
 }
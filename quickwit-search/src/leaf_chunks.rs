@@ -0,0 +1,273 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use prost::Message;
+use quickwit_config::GrpcCompression;
+use quickwit_proto::{LeafSearchResponse, LeafSearchResponseChunk, PartialHit, SplitSearchError};
+
+use crate::SearchError;
+
+/// Upper bound of the number of bytes taken by a field key and a length delimiter in an
+/// encoded message.
+const FIELD_OVERHEAD_NUM_BYTES: usize = 16;
+
+/// Compression level of the chunks compressed with zstd. Low levels compress leaf search
+/// responses almost as well as high levels for a fraction of the CPU.
+const ZSTD_COMPRESSION_LEVEL: i32 = 1;
+
+/// Splits a leaf search response into chunks whose encoded size does not exceed
+/// `max_chunk_num_bytes`, unless a single hit or failed split is larger than that.
+///
+/// The chunks are built lazily, as they are pulled from the returned iterator. The first chunk
+/// carries all the fields of the response but the partial hits and failed splits, so that
+/// merging the chunks restores the original response.
+pub fn split_leaf_search_response(
+    mut leaf_search_response: LeafSearchResponse,
+    max_chunk_num_bytes: usize,
+) -> LeafSearchResponseChunks {
+    if leaf_search_response.encoded_len() <= max_chunk_num_bytes {
+        return LeafSearchResponseChunks {
+            next_chunk_opt: Some(leaf_search_response),
+            partial_hits: Vec::new().into_iter(),
+            failed_splits: Vec::new().into_iter(),
+            max_chunk_num_bytes,
+        };
+    }
+    let partial_hits = std::mem::take(&mut leaf_search_response.partial_hits);
+    let failed_splits = std::mem::take(&mut leaf_search_response.failed_splits);
+    LeafSearchResponseChunks {
+        next_chunk_opt: Some(leaf_search_response),
+        partial_hits: partial_hits.into_iter(),
+        failed_splits: failed_splits.into_iter(),
+        max_chunk_num_bytes,
+    }
+}
+
+/// Iterator over the chunks of a leaf search response, see [`split_leaf_search_response`].
+pub struct LeafSearchResponseChunks {
+    next_chunk_opt: Option<LeafSearchResponse>,
+    partial_hits: std::vec::IntoIter<PartialHit>,
+    failed_splits: std::vec::IntoIter<SplitSearchError>,
+    max_chunk_num_bytes: usize,
+}
+
+impl Iterator for LeafSearchResponseChunks {
+    type Item = LeafSearchResponse;
+
+    fn next(&mut self) -> Option<LeafSearchResponse> {
+        let mut chunk = self.next_chunk_opt.take()?;
+        let mut chunk_num_bytes = chunk.encoded_len();
+
+        while let Some(partial_hit) = self.partial_hits.as_slice().first() {
+            let partial_hit_num_bytes = partial_hit.encoded_len() + FIELD_OVERHEAD_NUM_BYTES;
+            if chunk_num_bytes + partial_hit_num_bytes > self.max_chunk_num_bytes
+                && chunk_num_bytes > 0
+            {
+                self.next_chunk_opt = Some(LeafSearchResponse::default());
+                return Some(chunk);
+            }
+            chunk.partial_hits.extend(self.partial_hits.next());
+            chunk_num_bytes += partial_hit_num_bytes;
+        }
+        while let Some(failed_split) = self.failed_splits.as_slice().first() {
+            let failed_split_num_bytes = failed_split.encoded_len() + FIELD_OVERHEAD_NUM_BYTES;
+            if chunk_num_bytes + failed_split_num_bytes > self.max_chunk_num_bytes
+                && chunk_num_bytes > 0
+            {
+                self.next_chunk_opt = Some(LeafSearchResponse::default());
+                return Some(chunk);
+            }
+            chunk.failed_splits.extend(self.failed_splits.next());
+            chunk_num_bytes += failed_split_num_bytes;
+        }
+        Some(chunk)
+    }
+}
+
+/// Encodes a chunk of a leaf search response into the message sent to the root node,
+/// compressing it with zstd if `grpc_compression` requires it. Gzip compression is applied by
+/// the gRPC server to the whole message.
+pub fn encode_leaf_search_response_chunk(
+    chunk: &LeafSearchResponse,
+    grpc_compression: GrpcCompression,
+) -> crate::Result<LeafSearchResponseChunk> {
+    let payload = chunk.encode_to_vec();
+    if grpc_compression != GrpcCompression::Zstd {
+        return Ok(LeafSearchResponseChunk {
+            payload,
+            zstd_compressed: false,
+        });
+    }
+    let compressed_payload = zstd::stream::encode_all(&payload[..], ZSTD_COMPRESSION_LEVEL)
+        .map_err(|error| {
+            SearchError::InternalError(format!("Failed to compress leaf response: {}", error))
+        })?;
+    Ok(LeafSearchResponseChunk {
+        payload: compressed_payload,
+        zstd_compressed: true,
+    })
+}
+
+/// Decodes a chunk of a leaf search response encoded by
+/// [`encode_leaf_search_response_chunk`].
+pub(crate) fn decode_leaf_search_response_chunk(
+    chunk: LeafSearchResponseChunk,
+) -> crate::Result<LeafSearchResponse> {
+    let payload = if chunk.zstd_compressed {
+        zstd::stream::decode_all(&chunk.payload[..]).map_err(|error| {
+            SearchError::InternalError(format!("Failed to decompress leaf response: {}", error))
+        })?
+    } else {
+        chunk.payload
+    };
+    LeafSearchResponse::decode(&payload[..]).map_err(|error| {
+        SearchError::InternalError(format!("Failed to decode leaf response: {}", error))
+    })
+}
+
+/// Merges a chunk produced by [`split_leaf_search_response`] into the leaf search response
+/// being reassembled.
+pub(crate) fn merge_leaf_search_response_chunk(
+    leaf_search_response: &mut LeafSearchResponse,
+    chunk: LeafSearchResponse,
+) {
+    leaf_search_response.num_hits += chunk.num_hits;
+    leaf_search_response.num_attempted_splits += chunk.num_attempted_splits;
+    leaf_search_response.partial_hits.extend(chunk.partial_hits);
+    leaf_search_response
+        .failed_splits
+        .extend(chunk.failed_splits);
+    leaf_search_response
+        .query_rewrites
+        .extend(chunk.query_rewrites);
+    leaf_search_response
+        .split_profiles
+        .extend(chunk.split_profiles);
+    if chunk.timings.is_some() {
+        leaf_search_response.timings = chunk.timings;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_proto::{LeafSearchTimings, SplitQueryRewrite};
+
+    use super::*;
+
+    fn make_leaf_search_response(num_hits: usize, num_failed_splits: usize) -> LeafSearchResponse {
+        LeafSearchResponse {
+            num_hits: num_hits as u64 * 10,
+            partial_hits: (0..num_hits)
+                .map(|doc_id| PartialHit {
+                    sorting_field_value: doc_id as u64,
+                    split_id: format!("split-{}", doc_id % 7),
+                    segment_ord: 0,
+                    doc_id: doc_id as u32,
+                })
+                .collect(),
+            failed_splits: (0..num_failed_splits)
+                .map(|split_ord| SplitSearchError {
+                    error: "timeout".to_string(),
+                    split_id: format!("failed-split-{}", split_ord),
+                    retryable_error: true,
+                })
+                .collect(),
+            num_attempted_splits: 42,
+            timings: Some(LeafSearchTimings {
+                warmup_micros: 1_000,
+                queue_wait_micros: 10,
+                cpu_micros: 200,
+                num_splits: 42,
+            }),
+            query_rewrites: vec![SplitQueryRewrite {
+                split_id: "split-3".to_string(),
+                missing_fields: vec!["title".to_string()],
+            }],
+            split_profiles: Vec::new(),
+        }
+    }
+
+    fn merge_chunks(chunks: Vec<LeafSearchResponse>) -> LeafSearchResponse {
+        let mut leaf_search_response = LeafSearchResponse::default();
+        for chunk in chunks {
+            merge_leaf_search_response_chunk(&mut leaf_search_response, chunk);
+        }
+        leaf_search_response
+    }
+
+    #[test]
+    fn test_split_small_leaf_search_response() {
+        let leaf_search_response = make_leaf_search_response(10, 1);
+        let chunks: Vec<LeafSearchResponse> =
+            split_leaf_search_response(leaf_search_response.clone(), 4 * 1024 * 1024).collect();
+        assert_eq!(chunks, vec![leaf_search_response]);
+    }
+
+    #[test]
+    fn test_split_and_merge_leaf_search_response() {
+        let leaf_search_response = make_leaf_search_response(10_000, 100);
+        let max_chunk_num_bytes = 16 * 1024;
+        let chunks: Vec<LeafSearchResponse> =
+            split_leaf_search_response(leaf_search_response.clone(), max_chunk_num_bytes).collect();
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.encoded_len() <= max_chunk_num_bytes);
+        }
+        assert_eq!(chunks[0].num_hits, 100_000);
+        assert_eq!(chunks[0].query_rewrites.len(), 1);
+        assert!(chunks[1..].iter().all(|chunk| chunk.num_hits == 0));
+        assert!(chunks[1..].iter().all(|chunk| chunk.timings.is_none()));
+        assert_eq!(merge_chunks(chunks), leaf_search_response);
+    }
+
+    #[test]
+    fn test_split_leaf_search_response_with_oversized_hit() {
+        let leaf_search_response = make_leaf_search_response(3, 0);
+        let chunks: Vec<LeafSearchResponse> =
+            split_leaf_search_response(leaf_search_response.clone(), 1).collect();
+        assert_eq!(chunks.len(), 4);
+        assert!(chunks[0].partial_hits.is_empty());
+        assert_eq!(merge_chunks(chunks), leaf_search_response);
+    }
+
+    #[test]
+    fn test_encode_and_decode_leaf_search_response_chunk() {
+        let leaf_search_response = make_leaf_search_response(1_000, 10);
+        for grpc_compression in [
+            GrpcCompression::None,
+            GrpcCompression::Gzip,
+            GrpcCompression::Zstd,
+        ] {
+            let chunk =
+                encode_leaf_search_response_chunk(&leaf_search_response, grpc_compression).unwrap();
+            assert_eq!(
+                chunk.zstd_compressed,
+                grpc_compression == GrpcCompression::Zstd
+            );
+            assert_eq!(
+                decode_leaf_search_response_chunk(chunk).unwrap(),
+                leaf_search_response
+            );
+        }
+        let compressed_chunk =
+            encode_leaf_search_response_chunk(&leaf_search_response, GrpcCompression::Zstd)
+                .unwrap();
+        assert!(compressed_chunk.payload.len() < leaf_search_response.encoded_len());
+    }
+}
@@ -21,6 +21,7 @@
 #![warn(missing_docs)]
 #![allow(clippy::bool_assert_comparison)]
 
+mod cache_manifest;
 mod client;
 mod cluster_client;
 mod collector;
@@ -28,14 +29,30 @@ mod error;
 mod fetch_docs;
 mod filters;
 mod leaf;
+mod leaf_chunks;
+mod profile;
+mod range_bounds;
+mod relative_time;
 mod rendezvous_hasher;
 mod retry;
 mod root;
 mod search_client_pool;
 mod search_response_rest;
 mod search_stream;
+mod search_timings;
 mod service;
+mod snapshot;
+mod speculative_search;
+mod split_catalog;
+mod split_heatmap;
+mod split_lease;
+mod split_reader_pool;
+mod split_search_admission;
+mod tail;
+mod terms_lookup;
 mod thread_pool;
+mod versioning;
+mod virtual_index;
 
 /// Refer to this as `crate::Result<T>`.
 pub type Result<T> = std::result::Result<T, SearchError>;
@@ -46,24 +63,51 @@ use std::ops::Range;
 
 use anyhow::Context;
 use itertools::Itertools;
-use quickwit_config::build_doc_mapper;
+use quickwit_config::{build_doc_mapper, timestamp_resolution};
+use quickwit_doc_mapper::range_pruning::extract_ranges_from_query;
 use quickwit_doc_mapper::tag_pruning::extract_tags_from_query;
-use quickwit_metastore::{Metastore, SplitMetadata, SplitState};
-use quickwit_proto::{PartialHit, SearchRequest, SearchResponse, SplitIdAndFooterOffsets};
-use quickwit_storage::StorageUriResolver;
+use quickwit_doc_mapper::SortOrder;
+use quickwit_metastore::{IndexMetadata, Metastore, SplitMetadata, SplitState};
+use quickwit_proto::{
+    PartialHit, SearchRequest, SearchResponse, SortOrder as ProtoSortOrder, SplitIdAndFooterOffsets,
+};
+use quickwit_storage::{account_storage_costs, StorageActivity, StorageUriResolver};
 use tantivy::DocAddress;
 
+pub use crate::cache_manifest::{
+    cache_manifest_path, maintain_cache_manifest, save_cache_manifest, save_cache_snapshot,
+    warm_up_fast_field_cache, warm_up_split_footer_cache,
+};
 pub use crate::client::SearchServiceClient;
 pub use crate::cluster_client::ClusterClient;
 pub use crate::error::{parse_grpc_error, SearchError};
 use crate::fetch_docs::fetch_docs;
 use crate::leaf::leaf_search;
+pub use crate::leaf_chunks::{
+    encode_leaf_search_response_chunk, split_leaf_search_response, LeafSearchResponseChunks,
+};
+use crate::range_bounds::parse_range_bounds_in_query;
+use crate::relative_time::resolve_search_request_times;
+pub use crate::relative_time::{
+    now_timestamp, resolve_relative_times_in_query, TimeExpression, TimeOperation, TimeUnit,
+};
 pub use crate::root::root_search;
 pub use crate::search_client_pool::SearchClientPool;
 pub use crate::search_response_rest::SearchResponseRest;
 pub use crate::search_stream::root_search_stream;
 pub use crate::service::{MockSearchService, SearchService, SearchServiceImpl};
+use crate::snapshot::{filter_relevant_splits, pin_all_splits, plan_splits, take_snapshot};
+pub use crate::split_catalog::SplitCatalogMetastore;
+pub use crate::split_heatmap::{
+    split_access_tracker, split_heatmap, HeatStats, HeatmapBucket, IndexHeatmap, SplitAccessStats,
+    SplitAccessTracker, SplitHeat, SplitHeatmap, SplitHeatmapOptions,
+};
+pub use crate::split_search_admission::broadcast_split_search_load;
+pub use crate::tail::{root_search_tail, single_node_search_tail, TAIL_POLL_INTERVAL};
+use crate::terms_lookup::resolve_terms_lookup;
+pub use crate::terms_lookup::TERMS_LOOKUP_MAX_DOCS;
 use crate::thread_pool::run_cpu_intensive;
+use crate::versioning::search_latest_versions;
 
 /// Compute the gRPC port from the SWIM port.
 /// Add 1 to the SWIM port to get the gRPC port.
@@ -118,11 +162,37 @@ fn extract_time_range(
     }
 }
 
+/// Converts the timestamp bounds of a request, expressed in seconds, into the resolution of the
+/// index timestamp field, which is the unit of its fast field values and of its split time ranges.
+/// Must be applied once, before listing the splits of the request.
+fn convert_timestamp_bounds(
+    index_metadata: &IndexMetadata,
+    start_timestamp_opt: &mut Option<i64>,
+    end_timestamp_opt: &mut Option<i64>,
+) {
+    let resolution = timestamp_resolution(
+        &index_metadata.doc_mapping,
+        &index_metadata.indexing_settings,
+    );
+    for timestamp in start_timestamp_opt
+        .iter_mut()
+        .chain(end_timestamp_opt.iter_mut())
+    {
+        *timestamp = resolution.secs_to_units(*timestamp);
+    }
+}
+
 fn extract_split_and_footer_offsets(split_metadata: &SplitMetadata) -> SplitIdAndFooterOffsets {
+    let sort_by_field_opt = split_metadata.sort_by.as_ref();
     SplitIdAndFooterOffsets {
         split_id: split_metadata.split_id.clone(),
         split_footer_start: split_metadata.footer_offsets.start as u64,
         split_footer_end: split_metadata.footer_offsets.end as u64,
+        sort_by_field: sort_by_field_opt.map(|sort_by_field| sort_by_field.field_name.clone()),
+        sort_order: sort_by_field_opt.map(|sort_by_field| match sort_by_field.order {
+            SortOrder::Asc => ProtoSortOrder::Asc as i32,
+            SortOrder::Desc => ProtoSortOrder::Desc as i32,
+        }),
     }
 }
 
@@ -133,7 +203,16 @@ async fn list_relevant_splits(
 ) -> crate::Result<Vec<SplitMetadata>> {
     let time_range_opt =
         extract_time_range(search_request.start_timestamp, search_request.end_timestamp);
+    // No split intersects an empty time range.
+    if time_range_opt
+        .as_ref()
+        .map(|time_range| time_range.is_empty())
+        .unwrap_or(false)
+    {
+        return Ok(Vec::new());
+    }
     let tags_filter = extract_tags_from_query(&search_request.query)?;
+    let range_filter_opt = extract_ranges_from_query(&search_request.query)?;
     let split_metas = metastore
         .list_splits(
             &search_request.index_id,
@@ -145,20 +224,112 @@ async fn list_relevant_splits(
     Ok(split_metas
         .into_iter()
         .map(|metadata| metadata.split_metadata)
+        .filter(|split_metadata| {
+            range_filter_opt.as_ref().map_or(true, |range_filter| {
+                range_filter.evaluate(&split_metadata.field_stats)
+            })
+        })
         .collect::<Vec<_>>())
 }
 
 /// Performs a search on the current node.
 /// See also `[distributed_search]`.
+///
+/// If the request holds a terms lookup, the lookup query is run first on the current node and
+/// its results are turned into a terms filter of the search query.
 pub async fn single_node_search(
     search_request: &SearchRequest,
     metastore: &dyn Metastore,
     storage_resolver: StorageUriResolver,
 ) -> crate::Result<SearchResponse> {
+    let mut search_request = search_request.clone();
+    resolve_search_request_times(&mut search_request, now_timestamp())?;
+    let search_request = &search_request;
+    let terms_lookup = if let Some(terms_lookup) = &search_request.terms_lookup {
+        terms_lookup
+    } else {
+        return single_node_search_index(search_request, metastore, storage_resolver).await;
+    };
     let start_instant = tokio::time::Instant::now();
+    let lookup_storage_resolver = storage_resolver.clone();
+    let resolved_request_opt =
+        resolve_terms_lookup(search_request, terms_lookup, |lookup_request| async move {
+            single_node_search_index(&lookup_request, metastore, lookup_storage_resolver).await
+        })
+        .await?;
+    let mut search_response = if let Some(resolved_request) = resolved_request_opt {
+        single_node_search_index(&resolved_request, metastore, storage_resolver).await?
+    } else {
+        SearchResponse::default()
+    };
+    search_response.elapsed_time_micros = start_instant.elapsed().as_micros() as u64;
+    Ok(search_response)
+}
+
+async fn single_node_search_index(
+    search_request: &SearchRequest,
+    metastore: &dyn Metastore,
+    storage_resolver: StorageUriResolver,
+) -> crate::Result<SearchResponse> {
     let index_metadata = metastore.index_metadata(&search_request.index_id).await?;
-    let index_storage = storage_resolver.resolve(&index_metadata.index_uri)?;
-    let metas = list_relevant_splits(search_request, metastore).await?;
+    let mut search_request = search_request.clone();
+    convert_timestamp_bounds(
+        &index_metadata,
+        &mut search_request.start_timestamp,
+        &mut search_request.end_timestamp,
+    );
+    search_request.query =
+        parse_range_bounds_in_query(&search_request.query, &index_metadata.doc_mapping)?;
+    let search_request = &search_request;
+    if let Some(versioning) = &index_metadata.indexing_settings.versioning {
+        // The splits are pinned once, so that the search and the versions lookup run on the same
+        // splits.
+        let pinned_metas = pin_all_splits(search_request, metastore).await?;
+        let snapshot_opt = if search_request.pin_splits {
+            Some(take_snapshot(search_request, &pinned_metas, metastore).await?)
+        } else {
+            None
+        };
+        let pinned_metas = &pinned_metas;
+        let index_metadata = &index_metadata;
+        let mut search_response = search_latest_versions(search_request, versioning, |request| {
+            let storage_resolver = storage_resolver.clone();
+            async move {
+                let metas = filter_relevant_splits(&request, pinned_metas)?;
+                single_node_search_splits(&request, index_metadata, &metas, storage_resolver).await
+            }
+        })
+        .await?;
+        search_response.snapshot = snapshot_opt;
+        return Ok(search_response);
+    }
+    let metas = plan_splits(search_request, metastore).await?;
+    let snapshot_opt = if search_request.pin_splits {
+        Some(take_snapshot(search_request, &metas, metastore).await?)
+    } else {
+        None
+    };
+    let mut search_response =
+        single_node_search_splits(search_request, &index_metadata, &metas, storage_resolver)
+            .await?;
+    search_response.snapshot = snapshot_opt;
+    Ok(search_response)
+}
+
+/// Performs a search on the current node, restricted to the given splits of the index described
+/// by `index_metadata`.
+async fn single_node_search_splits(
+    search_request: &SearchRequest,
+    index_metadata: &IndexMetadata,
+    metas: &[SplitMetadata],
+    storage_resolver: StorageUriResolver,
+) -> crate::Result<SearchResponse> {
+    let start_instant = tokio::time::Instant::now();
+    let index_storage = account_storage_costs(
+        storage_resolver.resolve(&index_metadata.index_uri)?,
+        &search_request.index_id,
+        StorageActivity::Search,
+    );
     let split_metadata: Vec<SplitIdAndFooterOffsets> =
         metas.iter().map(extract_split_and_footer_offsets).collect();
     let doc_mapper = build_doc_mapper(
@@ -194,6 +365,10 @@ pub async fn single_node_search(
             .iter()
             .map(|error| format!("{:?}", error))
             .collect_vec(),
+        timings: leaf_search_response.timings,
+        query_rewrites: leaf_search_response.query_rewrites,
+        split_profiles: leaf_search_response.split_profiles,
+        snapshot: None,
     })
 }
 
@@ -204,6 +379,7 @@ mod tests {
 
     use assert_json_diff::assert_json_include;
     use quickwit_indexing::TestSandbox;
+    use quickwit_storage::FaultProfile;
     use serde_json::json;
 
     use super::*;
@@ -249,6 +425,92 @@ mod tests {
         assert_json_include!(actual: hit_json, expected: expected_json);
         assert!(single_node_result.elapsed_time_micros > 10);
         assert!(single_node_result.elapsed_time_micros < 1_000_000);
+        let timings = single_node_result.timings.unwrap();
+        assert_eq!(timings.num_splits, 1);
+        assert!(
+            timings.warmup_micros + timings.cpu_micros <= single_node_result.elapsed_time_micros
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_single_node_search_terms_lookup() -> anyhow::Result<()> {
+        let index_id = "single-node-terms-lookup";
+        let doc_mapping_yaml = r#"
+            field_mappings:
+              - name: title
+                type: text
+              - name: body
+                type: text
+              - name: url
+                type: text
+        "#;
+        let test_sandbox = TestSandbox::create(index_id, doc_mapping_yaml, "{}", &["body"]).await?;
+        let docs = vec![
+            json!({"title": "snoopy", "body": "Snoopy is an anthropomorphic beagle[5] in the comic strip...", "url": "http://snoopy"}),
+            json!({"title": "beagle", "body": "The beagle is a breed of small scent hound, similar in appearance to the much larger foxhound.", "url": "http://beagle"}),
+        ];
+        test_sandbox.add_documents(docs).await?;
+        let search_request = SearchRequest {
+            index_id: index_id.to_string(),
+            query: "body:beagle".to_string(),
+            max_hits: 10,
+            terms_lookup: Some(quickwit_proto::TermsLookup {
+                index_id: index_id.to_string(),
+                query: "title:beagle".to_string(),
+                lookup_field: "url".to_string(),
+                target_field: "url".to_string(),
+                max_terms: 10,
+            }),
+            ..Default::default()
+        };
+        let single_node_result = single_node_search(
+            &search_request,
+            &*test_sandbox.metastore(),
+            test_sandbox.storage_uri_resolver(),
+        )
+        .await?;
+        assert_eq!(single_node_result.num_hits, 1);
+        assert!(single_node_result.hits[0].json.contains("http://beagle"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_single_node_search_with_faulty_storage() -> anyhow::Result<()> {
+        let index_id = "single-node-faulty-storage";
+        let doc_mapping_yaml = r#"
+            field_mappings:
+              - name: title
+                type: text
+        "#;
+        let test_sandbox =
+            TestSandbox::create(index_id, doc_mapping_yaml, "{}", &["title"]).await?;
+        test_sandbox
+            .add_documents(vec![json!({"title": "snoopy"})])
+            .await?;
+        test_sandbox
+            .add_documents(vec![json!({"title": "snoopy and woodstock"})])
+            .await?;
+        let search_request = SearchRequest {
+            index_id: index_id.to_string(),
+            query: "snoopy".to_string(),
+            max_hits: 10,
+            ..Default::default()
+        };
+        let fault_profile = FaultProfile {
+            throttling_probability: 1.0,
+            ..Default::default()
+        };
+        let single_node_result = single_node_search(
+            &search_request,
+            &*test_sandbox.metastore(),
+            test_sandbox
+                .storage_uri_resolver()
+                .with_fault_profile(fault_profile),
+        )
+        .await?;
+        assert_eq!(single_node_result.num_hits, 0);
+        assert_eq!(single_node_result.errors.len(), 2);
         Ok(())
     }
 
@@ -424,6 +686,59 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_single_node_filtering_with_timestamp_resolution() -> anyhow::Result<()> {
+        let index_id = "single-node-filtering-with-timestamp-resolution";
+        let doc_mapping_yaml = r#"
+            field_mappings:
+              - name: body
+                type: text
+              - name: ts
+                type: i64
+                fast: true
+                resolution: milliseconds
+        "#;
+        let indexing_settings_json = r#"{
+            "timestamp_field": "ts",
+            "sort_field": "ts",
+            "sort_order": "desc"
+        }"#;
+        let test_sandbox = TestSandbox::create(
+            index_id,
+            doc_mapping_yaml,
+            indexing_settings_json,
+            &["body"],
+        )
+        .await?;
+
+        // One document every 500ms, starting at t=0s.
+        let mut docs = vec![];
+        for i in 0..30 {
+            let body = format!("info @ t:{}", i * 500);
+            docs.push(json!({"body": body, "ts": i * 500}));
+        }
+        test_sandbox.add_documents(docs).await?;
+
+        let search_request = SearchRequest {
+            index_id: index_id.to_string(),
+            query: "info".to_string(),
+            start_timestamp: Some(5),
+            end_timestamp: Some(10),
+            max_hits: 15,
+            ..Default::default()
+        };
+        let single_node_response = single_node_search(
+            &search_request,
+            &*test_sandbox.metastore(),
+            test_sandbox.storage_uri_resolver(),
+        )
+        .await?;
+        assert_eq!(single_node_response.num_hits, 10);
+        assert!(&single_node_response.hits[0].json.contains("t:9500"));
+        assert!(&single_node_response.hits[9].json.contains("t:5000"));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_single_node_split_pruning_by_tags() -> anyhow::Result<()> {
         let doc_mapping_yaml = r#"
@@ -492,4 +807,50 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_single_node_split_pruning_by_ranges() -> anyhow::Result<()> {
+        let doc_mapping_yaml = r#"
+            field_mappings:
+              - name: body
+                type: text
+              - name: status_code
+                type: u64
+                fast: true
+        "#;
+        let index_id = "single-node-pruning-by-ranges";
+        let test_sandbox = TestSandbox::create(index_id, doc_mapping_yaml, "{}", &["body"]).await?;
+        for status_codes in [200..210, 500..510] {
+            let docs = status_codes
+                .map(|status_code| json!({"body": "content", "status_code": status_code}))
+                .collect();
+            test_sandbox.add_documents(docs).await?;
+        }
+        let num_relevant_splits = |query: &str| {
+            let search_request = SearchRequest {
+                index_id: index_id.to_string(),
+                query: query.to_string(),
+                ..Default::default()
+            };
+            let metastore = test_sandbox.metastore();
+            async move {
+                list_relevant_splits(&search_request, &*metastore)
+                    .await
+                    .unwrap()
+                    .len()
+            }
+        };
+        assert_eq!(num_relevant_splits("body:content").await, 2);
+        assert_eq!(num_relevant_splits("status_code:[500 TO 599]").await, 1);
+        assert_eq!(num_relevant_splits("status_code:404").await, 0);
+        assert_eq!(
+            num_relevant_splits("status_code:>=505 OR status_code:<201").await,
+            2
+        );
+        assert_eq!(
+            num_relevant_splits("body:content AND NOT status_code:[200 TO 299]").await,
+            1
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,119 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+use std::time::{Duration, Instant};
+
+use quickwit_proto::{SegmentProfile, SplitProfile};
+use tantivy::collector::{Collector, SegmentCollector};
+use tantivy::query::{Query, Scorer, Weight};
+use tantivy::{DocSet, Searcher, SegmentOrdinal, SegmentReader, TERMINATED};
+
+/// Runs `query` on the segments of a split like [`Searcher::search`] does, and profiles the
+/// construction of the query weight and, for each segment, of its scorer, the matching of the
+/// documents, and their collection.
+///
+/// Timing the calls to the collector one by one adds some overhead to the search. It should
+/// therefore only be used for requests enabling `profile`.
+pub(crate) fn profile_search<C: Collector>(
+    split_id: String,
+    searcher: &Searcher,
+    query: &dyn Query,
+    collector: &C,
+) -> tantivy::Result<(C::Fruit, SplitProfile)> {
+    let weight_start = Instant::now();
+    let weight = query.weight(searcher, collector.requires_scoring())?;
+    let weight_duration = weight_start.elapsed();
+    let mut segment_fruits = Vec::with_capacity(searcher.segment_readers().len());
+    let mut segment_profiles = Vec::with_capacity(searcher.segment_readers().len());
+    for (segment_ord, segment_reader) in searcher.segment_readers().iter().enumerate() {
+        let (segment_fruit, segment_profile) = profile_segment_search(
+            weight.as_ref(),
+            segment_ord as SegmentOrdinal,
+            segment_reader,
+            collector,
+        )?;
+        segment_fruits.push(segment_fruit);
+        segment_profiles.push(segment_profile);
+    }
+    let merge_start = Instant::now();
+    let fruit = collector.merge_fruits(segment_fruits)?;
+    let split_profile = SplitProfile {
+        split_id,
+        query: format!("{:?}", query),
+        weight_micros: weight_duration.as_micros() as u64,
+        merge_micros: merge_start.elapsed().as_micros() as u64,
+        segments: segment_profiles,
+    };
+    Ok((fruit, split_profile))
+}
+
+fn profile_segment_search<C: Collector>(
+    weight: &dyn Weight,
+    segment_ord: SegmentOrdinal,
+    segment_reader: &SegmentReader,
+    collector: &C,
+) -> tantivy::Result<(<C::Child as SegmentCollector>::Fruit, SegmentProfile)> {
+    let for_segment_start = Instant::now();
+    let mut segment_collector = collector.for_segment(segment_ord, segment_reader)?;
+    let for_segment_duration = for_segment_start.elapsed();
+
+    let build_scorer_start = Instant::now();
+    let mut scorer = weight.scorer(segment_reader, 1.0)?;
+    let build_scorer_duration = build_scorer_start.elapsed();
+
+    let alive_bitset_opt = segment_reader.alive_bitset();
+    let requires_scoring = collector.requires_scoring();
+    let mut num_docs_scored = 0u64;
+    let mut collect_duration = Duration::default();
+    let search_start = Instant::now();
+    let mut doc = scorer.doc();
+    while doc != TERMINATED {
+        let is_alive = alive_bitset_opt
+            .map(|alive_bitset| alive_bitset.is_alive(doc))
+            .unwrap_or(true);
+        if is_alive {
+            let score = if requires_scoring {
+                scorer.score()
+            } else {
+                0.0
+            };
+            let collect_start = Instant::now();
+            segment_collector.collect(doc, score);
+            collect_duration += collect_start.elapsed();
+            num_docs_scored += 1;
+        }
+        doc = scorer.advance();
+    }
+    // The time spent matching documents is the time spent iterating over the scorer minus the
+    // time spent in the collector.
+    let next_doc_duration = search_start.elapsed().saturating_sub(collect_duration);
+
+    let harvest_start = Instant::now();
+    let segment_fruit = segment_collector.harvest();
+    collect_duration += for_segment_duration + harvest_start.elapsed();
+
+    let segment_profile = SegmentProfile {
+        segment_ord,
+        max_doc: segment_reader.max_doc(),
+        build_scorer_micros: build_scorer_duration.as_micros() as u64,
+        next_doc_micros: next_doc_duration.as_micros() as u64,
+        collect_micros: collect_duration.as_micros() as u64,
+        num_docs_scored,
+    };
+    Ok((segment_fruit, segment_profile))
+}
@@ -0,0 +1,161 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use once_cell::sync::Lazy;
+use quickwit_config::DocMapping;
+use regex::{Captures, Regex};
+
+use crate::SearchError;
+
+/// Matches the range queries and their field, e.g. `timestamp:[2021-12-16T10:00:00Z TO *]`.
+static RANGE_QUERY_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"([A-Za-z_][A-Za-z0-9_.\-]*)(:[\[{]\s*)([^\s\]}]+)(\s+TO\s+)([^\s\]}]+)(\s*[\]}])")
+        .expect("Range query pattern should compile.")
+});
+
+/// Parses the bounds of the range queries of `query` with the input formats of their field, and
+/// replaces them with the values the field stores, e.g. timestamps in the field resolution, so
+/// that the splits are pruned on the values they were indexed with. Unbounded bounds, `*`, and the
+/// bounds of the fields missing from the doc mapping are left unchanged.
+pub(crate) fn parse_range_bounds_in_query(
+    query: &str,
+    doc_mapping: &DocMapping,
+) -> crate::Result<String> {
+    let mut parse_error_opt = None;
+    let parsed_query = RANGE_QUERY_PATTERN.replace_all(query, |captures: &Captures| {
+        let field_name = &captures[1];
+        let field_mapping_opt = doc_mapping
+            .field_mappings
+            .iter()
+            .find_map(|field_mapping| field_mapping.find_field_mapping(field_name));
+        let mut parse_bound = |bound: &str| -> String {
+            let field_mapping = match field_mapping_opt {
+                Some(field_mapping) if bound != "*" => field_mapping,
+                _ => return bound.to_string(),
+            };
+            match field_mapping.parse_range_bound(bound) {
+                Some(parsed_bound) => parsed_bound,
+                None => {
+                    parse_error_opt.get_or_insert_with(|| {
+                        format!("Invalid bound `{}` for field `{}`.", bound, field_name)
+                    });
+                    bound.to_string()
+                }
+            }
+        };
+        let lower_bound = parse_bound(&captures[3]);
+        let upper_bound = parse_bound(&captures[5]);
+        format!(
+            "{}{}{}{}{}{}",
+            &captures[1], &captures[2], lower_bound, &captures[4], upper_bound, &captures[6]
+        )
+    });
+    if let Some(parse_error) = parse_error_opt {
+        return Err(SearchError::InvalidQuery(parse_error));
+    }
+    Ok(parsed_query.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolve_relative_times_in_query;
+
+    fn doc_mapping() -> DocMapping {
+        serde_json::from_str(
+            r#"{
+                "field_mappings": [
+                    {
+                        "name": "timestamp",
+                        "type": "i64",
+                        "fast": true,
+                        "input_formats": ["epoch_secs", "%Y-%m-%d"],
+                        "resolution": "milliseconds"
+                    },
+                    {
+                        "name": "attributes",
+                        "type": "object",
+                        "field_mappings": [
+                            {
+                                "name": "created_at",
+                                "type": "date",
+                                "input_formats": ["%d/%m/%Y"]
+                            }
+                        ]
+                    },
+                    {
+                        "name": "severity",
+                        "type": "text"
+                    }
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_range_bounds_in_query() {
+        let doc_mapping = doc_mapping();
+        assert_eq!(
+            parse_range_bounds_in_query(
+                "severity:ERROR AND timestamp:[1639612800 TO 2021-12-17}",
+                &doc_mapping
+            )
+            .unwrap(),
+            "severity:ERROR AND timestamp:[1639612800000 TO 1639699200000}"
+        );
+        assert_eq!(
+            parse_range_bounds_in_query("timestamp:{2021-12-16T00:00:00Z TO *]", &doc_mapping)
+                .unwrap(),
+            "timestamp:{1639612800000 TO *]"
+        );
+        assert_eq!(
+            parse_range_bounds_in_query("attributes.created_at:[16/12/2021 TO *]", &doc_mapping)
+                .unwrap(),
+            "attributes.created_at:[2021-12-16T00:00:00+00:00 TO *]"
+        );
+        assert_eq!(
+            parse_range_bounds_in_query("severity:[a TO b] AND unknown:[c TO d]", &doc_mapping)
+                .unwrap(),
+            "severity:[a TO b] AND unknown:[c TO d]"
+        );
+        assert!(matches!(
+            parse_range_bounds_in_query("timestamp:[yesterday TO *]", &doc_mapping),
+            Err(SearchError::InvalidQuery(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_resolved_relative_times_in_query() {
+        // 2021-12-16T10:25:30Z
+        let now = 1_639_650_330;
+        let resolved_query =
+            resolve_relative_times_in_query("timestamp:[now-1h TO now]", now).unwrap();
+        assert_eq!(
+            parse_range_bounds_in_query(&resolved_query, &doc_mapping()).unwrap(),
+            "timestamp:[1639646730000 TO 1639650330000]"
+        );
+        let resolved_query =
+            resolve_relative_times_in_query("attributes.created_at:[now/d TO *]", now).unwrap();
+        assert_eq!(
+            parse_range_bounds_in_query(&resolved_query, &doc_mapping()).unwrap(),
+            "attributes.created_at:[2021-12-16T00:00:00+00:00 TO *]"
+        );
+    }
+}
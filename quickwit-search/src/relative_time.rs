@@ -0,0 +1,535 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use once_cell::sync::Lazy;
+use quickwit_proto::{SearchRequest, SearchStreamRequest};
+use regex::{Captures, Regex};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use crate::SearchError;
+
+const SECONDS_PER_MINUTE: i64 = 60;
+const SECONDS_PER_HOUR: i64 = 60 * SECONDS_PER_MINUTE;
+const SECONDS_PER_DAY: i64 = 24 * SECONDS_PER_HOUR;
+const SECONDS_PER_WEEK: i64 = 7 * SECONDS_PER_DAY;
+
+/// The Unix epoch was a Thursday: weeks, which start on Monday, are shifted by three days.
+const WEEK_START_SHIFT_SECS: i64 = 3 * SECONDS_PER_DAY;
+
+/// Matches the bounds of the range queries, e.g. `timestamp:[now-1h TO now]`.
+static RANGE_BOUNDS_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"([\[{]\s*)([^\s\]}]+)(\s+TO\s+)([^\s\]}]+)(\s*[\]}])")
+        .expect("Range bounds pattern should compile.")
+});
+
+/// Unit of a relative time expression.
+#[allow(missing_docs)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeUnit {
+    Second,
+    Minute,
+    Hour,
+    Day,
+    Week,
+}
+
+impl TimeUnit {
+    fn parse(unit_str: &str) -> Option<TimeUnit> {
+        match unit_str {
+            "s" => Some(TimeUnit::Second),
+            "m" => Some(TimeUnit::Minute),
+            "h" => Some(TimeUnit::Hour),
+            "d" => Some(TimeUnit::Day),
+            "w" => Some(TimeUnit::Week),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            TimeUnit::Second => "s",
+            TimeUnit::Minute => "m",
+            TimeUnit::Hour => "h",
+            TimeUnit::Day => "d",
+            TimeUnit::Week => "w",
+        }
+    }
+
+    fn num_seconds(&self) -> i64 {
+        match self {
+            TimeUnit::Second => 1,
+            TimeUnit::Minute => SECONDS_PER_MINUTE,
+            TimeUnit::Hour => SECONDS_PER_HOUR,
+            TimeUnit::Day => SECONDS_PER_DAY,
+            TimeUnit::Week => SECONDS_PER_WEEK,
+        }
+    }
+
+    /// Rounds `timestamp` down to the start of the unit, in UTC. Returns `None` on overflow.
+    fn round_down(&self, timestamp: i64) -> Option<i64> {
+        let shift = if *self == TimeUnit::Week {
+            WEEK_START_SHIFT_SECS
+        } else {
+            0
+        };
+        let num_units = timestamp.checked_add(shift)?.div_euclid(self.num_seconds());
+        num_units
+            .checked_mul(self.num_seconds())?
+            .checked_sub(shift)
+    }
+}
+
+/// Operation of a relative time expression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeOperation {
+    /// Adds a number of units, `+<n><unit>` or `-<n><unit>`.
+    Add(i64, TimeUnit),
+    /// Rounds down to the start of the unit, `/<unit>`.
+    RoundDown(TimeUnit),
+}
+
+/// A point in time given to the search API: either a Unix timestamp in seconds, an RFC 3339
+/// date normalized to UTC, or an expression relative to the time of the query, such as `now-15m`
+/// (15 minutes ago) or `now/d` (the start of the current day, in UTC). Relative expressions start
+/// with `now`, followed by any number of `+<n><unit>`, `-<n><unit>`, and `/<unit>` operations
+/// applied from left to right, with the units `s`, `m`, `h`, `d`, and `w`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TimeExpression {
+    /// Unix timestamp in seconds.
+    Absolute(i64),
+    /// Expression relative to the time of the query.
+    Relative(Vec<TimeOperation>),
+}
+
+impl TimeExpression {
+    /// Returns the Unix timestamp the expression stands for, `now` being the time of the query,
+    /// or `None` if it does not fit in an `i64`.
+    pub fn resolve(&self, now: i64) -> Option<i64> {
+        match self {
+            TimeExpression::Absolute(timestamp) => Some(*timestamp),
+            TimeExpression::Relative(operations) => {
+                operations
+                    .iter()
+                    .try_fold(now, |timestamp, operation| match operation {
+                        TimeOperation::Add(num_units, unit) => {
+                            timestamp.checked_add(num_units.checked_mul(unit.num_seconds())?)
+                        }
+                        TimeOperation::RoundDown(unit) => unit.round_down(timestamp),
+                    })
+            }
+        }
+    }
+}
+
+impl fmt::Display for TimeExpression {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeExpression::Absolute(timestamp) => write!(formatter, "{}", timestamp),
+            TimeExpression::Relative(operations) => {
+                formatter.write_str("now")?;
+                for operation in operations {
+                    match operation {
+                        TimeOperation::Add(num_units, unit) => {
+                            write!(formatter, "{:+}{}", num_units, unit.as_str())?
+                        }
+                        TimeOperation::RoundDown(unit) => write!(formatter, "/{}", unit.as_str())?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl FromStr for TimeExpression {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        if let Ok(timestamp) = expression.parse::<i64>() {
+            return Ok(TimeExpression::Absolute(timestamp));
+        }
+        if let Ok(date_time) = DateTime::parse_from_rfc3339(expression) {
+            return Ok(TimeExpression::Absolute(date_time.timestamp()));
+        }
+        let invalid_expression_error = || format!("Invalid time expression `{}`.", expression);
+        let mut remaining = expression
+            .strip_prefix("now")
+            .ok_or_else(invalid_expression_error)?;
+        let mut operations = Vec::new();
+        while !remaining.is_empty() {
+            let mut chars = remaining.chars();
+            let operator = chars.next().ok_or_else(invalid_expression_error)?;
+            let rest = chars.as_str();
+            let unit_pos = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .ok_or_else(invalid_expression_error)?;
+            let (num_units_str, rest) = rest.split_at(unit_pos);
+            let unit_len = rest
+                .find(|c: char| matches!(c, '+' | '-' | '/'))
+                .unwrap_or(rest.len());
+            let (unit_str, rest) = rest.split_at(unit_len);
+            let unit = TimeUnit::parse(unit_str).ok_or_else(invalid_expression_error)?;
+            let operation = match (operator, num_units_str) {
+                ('/', "") => TimeOperation::RoundDown(unit),
+                ('+' | '-', num_units_str) if !num_units_str.is_empty() => {
+                    let num_units: i64 = num_units_str
+                        .parse()
+                        .map_err(|_| invalid_expression_error())?;
+                    let sign = if operator == '-' { -1 } else { 1 };
+                    TimeOperation::Add(sign * num_units, unit)
+                }
+                _ => return Err(invalid_expression_error()),
+            };
+            operations.push(operation);
+            remaining = rest;
+        }
+        Ok(TimeExpression::Relative(operations))
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeExpression {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        struct TimeExpressionVisitor;
+
+        impl<'de> Visitor<'de> for TimeExpressionVisitor {
+            type Value = TimeExpression;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter
+                    .write_str("a Unix timestamp, an RFC 3339 date, or a relative time expression")
+            }
+
+            fn visit_i64<E: de::Error>(self, timestamp: i64) -> Result<Self::Value, E> {
+                Ok(TimeExpression::Absolute(timestamp))
+            }
+
+            fn visit_u64<E: de::Error>(self, timestamp: u64) -> Result<Self::Value, E> {
+                i64::try_from(timestamp)
+                    .map(TimeExpression::Absolute)
+                    .map_err(|_| E::custom(format!("Timestamp `{}` is out of range.", timestamp)))
+            }
+
+            fn visit_str<E: de::Error>(self, expression: &str) -> Result<Self::Value, E> {
+                expression.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(TimeExpressionVisitor)
+    }
+}
+
+/// Returns the current Unix timestamp in seconds, used as `now` to resolve the time expressions
+/// of a query.
+pub fn now_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Replaces the relative time expressions found in the bounds of the range queries of `query`,
+/// e.g. `timestamp:[now-1h TO now]`, with the RFC 3339 dates in UTC they stand for, so that all
+/// the leaves of a distributed search see the same time window. The dates are then parsed like
+/// any other bound, into the resolution of the ranged field.
+pub fn resolve_relative_times_in_query(query: &str, now: i64) -> crate::Result<String> {
+    let mut resolve_error_opt = None;
+    let resolved_query = RANGE_BOUNDS_PATTERN.replace_all(query, |captures: &Captures| {
+        let mut resolve_bound = |bound: &str| -> String {
+            if !bound.starts_with("now") {
+                return bound.to_string();
+            }
+            let resolve_result = bound.parse::<TimeExpression>().and_then(|time_expression| {
+                time_expression
+                    .resolve(now)
+                    .and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single())
+                    .ok_or_else(|| format!("Time expression `{}` is out of range.", bound))
+            });
+            match resolve_result {
+                Ok(date_time) => date_time.to_rfc3339_opts(SecondsFormat::Secs, true),
+                Err(error) => {
+                    resolve_error_opt.get_or_insert(error);
+                    bound.to_string()
+                }
+            }
+        };
+        let lower_bound = resolve_bound(&captures[2]);
+        let upper_bound = resolve_bound(&captures[4]);
+        format!(
+            "{}{}{}{}{}",
+            &captures[1], lower_bound, &captures[3], upper_bound, &captures[5]
+        )
+    });
+    if let Some(resolve_error) = resolve_error_opt {
+        return Err(SearchError::InvalidQuery(resolve_error));
+    }
+    Ok(resolved_query.into_owned())
+}
+
+/// Resolves the relative time expressions of the query and of the time filter of a search
+/// request against the same `now`. The root node calls it before dispatching the request, so
+/// that the leaves all search the same absolute time window.
+pub(crate) fn resolve_search_request_times(
+    search_request: &mut SearchRequest,
+    now: i64,
+) -> crate::Result<()> {
+    search_request.query = resolve_relative_times_in_query(&search_request.query, now)?;
+    resolve_time_bound(
+        &mut search_request.start_timestamp,
+        search_request.start_time_expression.take(),
+        now,
+    )?;
+    resolve_time_bound(
+        &mut search_request.end_timestamp,
+        search_request.end_time_expression.take(),
+        now,
+    )
+}
+
+/// Resolves the relative time expressions of a search stream request, see
+/// [`resolve_search_request_times`].
+pub(crate) fn resolve_search_stream_request_times(
+    search_stream_request: &mut SearchStreamRequest,
+    now: i64,
+) -> crate::Result<()> {
+    search_stream_request.query =
+        resolve_relative_times_in_query(&search_stream_request.query, now)?;
+    resolve_time_bound(
+        &mut search_stream_request.start_timestamp,
+        search_stream_request.start_time_expression.take(),
+        now,
+    )?;
+    resolve_time_bound(
+        &mut search_stream_request.end_timestamp,
+        search_stream_request.end_time_expression.take(),
+        now,
+    )
+}
+
+fn resolve_time_bound(
+    timestamp_opt: &mut Option<i64>,
+    time_expression_opt: Option<String>,
+    now: i64,
+) -> crate::Result<()> {
+    let time_expression_str = if let Some(time_expression_str) = time_expression_opt {
+        time_expression_str
+    } else {
+        return Ok(());
+    };
+    if timestamp_opt.is_some() {
+        return Err(SearchError::InvalidArgument(format!(
+            "The time bound `{}` conflicts with the timestamp bound of the request.",
+            time_expression_str
+        )));
+    }
+    let timestamp = time_expression_str
+        .parse::<TimeExpression>()
+        .map_err(SearchError::InvalidArgument)?
+        .resolve(now)
+        .ok_or_else(|| {
+            SearchError::InvalidArgument(format!(
+                "Time expression `{}` is out of range.",
+                time_expression_str
+            ))
+        })?;
+    *timestamp_opt = Some(timestamp);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2021-12-16T10:25:30Z, a Thursday.
+    const NOW: i64 = 1_639_650_330;
+
+    fn resolve(expression: &str) -> i64 {
+        expression
+            .parse::<TimeExpression>()
+            .unwrap()
+            .resolve(NOW)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_time_expression_resolve() {
+        assert_eq!(resolve("1450720000"), 1_450_720_000);
+        assert_eq!(resolve("-10"), -10);
+        assert_eq!(resolve("2021-12-16T11:25:30+01:00"), NOW);
+        assert_eq!(resolve("now"), NOW);
+        assert_eq!(resolve("now-15m"), NOW - 15 * 60);
+        assert_eq!(resolve("now+2h"), NOW + 2 * 3_600);
+        assert_eq!(resolve("now/s"), NOW);
+        assert_eq!(resolve("now/m"), 1_639_650_300);
+        assert_eq!(resolve("now/h"), 1_639_648_800);
+        // 2021-12-16T00:00:00Z
+        assert_eq!(resolve("now/d"), 1_639_612_800);
+        // 2021-12-13T00:00:00Z, a Monday.
+        assert_eq!(resolve("now/w"), 1_639_353_600);
+        // 2021-12-15T00:00:00Z
+        assert_eq!(resolve("now-1d/d"), 1_639_526_400);
+        assert_eq!(resolve("now/d+6h"), 1_639_612_800 + 6 * 3_600);
+    }
+
+    #[test]
+    fn test_time_expression_parse_error() {
+        for expression in [
+            "",
+            "yesterday",
+            "now-",
+            "now-15",
+            "now-m",
+            "now/1d",
+            "now-1M",
+            "now*2d",
+            "nowé",
+            "now-1hé",
+        ] {
+            assert!(
+                expression.parse::<TimeExpression>().is_err(),
+                "`{}` should be invalid.",
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn test_time_expression_resolve_overflow() {
+        for expression in [
+            "now-99999999999999999w",
+            "now+9223372036854775807s",
+            "now+1s",
+        ] {
+            let time_expression = expression.parse::<TimeExpression>().unwrap();
+            assert_eq!(time_expression.resolve(i64::MAX), None, "{}", expression);
+        }
+        let time_expression = "now/w".parse::<TimeExpression>().unwrap();
+        assert_eq!(time_expression.resolve(i64::MAX), None);
+    }
+
+    #[test]
+    fn test_time_expression_deserialize() {
+        let time_expression: TimeExpression = serde_json::from_str("1450720000").unwrap();
+        assert_eq!(time_expression, TimeExpression::Absolute(1_450_720_000));
+        let time_expression: TimeExpression = serde_json::from_str("\"now/d\"").unwrap();
+        assert_eq!(
+            time_expression,
+            TimeExpression::Relative(vec![TimeOperation::RoundDown(TimeUnit::Day)])
+        );
+        assert!(serde_json::from_str::<TimeExpression>("\"now-1y\"").is_err());
+    }
+
+    #[test]
+    fn test_time_expression_display() {
+        for expression in [
+            "1450720000",
+            "now",
+            "now-15m",
+            "now+2h",
+            "now-1d/d+6h",
+            "now/w",
+        ] {
+            assert_eq!(
+                expression.parse::<TimeExpression>().unwrap().to_string(),
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_search_request_times() {
+        let mut search_request = SearchRequest {
+            index_id: "my-index".to_string(),
+            query: "timestamp:[now-1h TO now]".to_string(),
+            start_time_expression: Some("now-15m".to_string()),
+            end_timestamp: Some(1_639_650_000),
+            ..Default::default()
+        };
+        resolve_search_request_times(&mut search_request, NOW).unwrap();
+        assert_eq!(
+            search_request.query,
+            "timestamp:[2021-12-16T09:25:30Z TO 2021-12-16T10:25:30Z]"
+        );
+        assert_eq!(search_request.start_timestamp, Some(NOW - 15 * 60));
+        assert_eq!(search_request.start_time_expression, None);
+        assert_eq!(search_request.end_timestamp, Some(1_639_650_000));
+
+        let mut search_stream_request = SearchStreamRequest {
+            index_id: "my-index".to_string(),
+            query: "*".to_string(),
+            end_time_expression: Some("now/d".to_string()),
+            ..Default::default()
+        };
+        resolve_search_stream_request_times(&mut search_stream_request, NOW).unwrap();
+        assert_eq!(search_stream_request.end_timestamp, Some(1_639_612_800));
+        assert_eq!(search_stream_request.end_time_expression, None);
+
+        for (start_timestamp, start_time_expression) in [
+            (None, "yesterday"),
+            (None, "now-99999999999999999w"),
+            (Some(1_639_650_000), "now-15m"),
+        ] {
+            let mut search_request = SearchRequest {
+                index_id: "my-index".to_string(),
+                query: "*".to_string(),
+                start_timestamp,
+                start_time_expression: Some(start_time_expression.to_string()),
+                ..Default::default()
+            };
+            assert!(matches!(
+                resolve_search_request_times(&mut search_request, NOW),
+                Err(SearchError::InvalidArgument(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_resolve_relative_times_in_query() {
+        assert_eq!(
+            resolve_relative_times_in_query("severity:ERROR AND timestamp:[now-1h TO now]", NOW)
+                .unwrap(),
+            "severity:ERROR AND timestamp:[2021-12-16T09:25:30Z TO 2021-12-16T10:25:30Z]"
+        );
+        assert_eq!(
+            resolve_relative_times_in_query("timestamp:{now/d TO *}", NOW).unwrap(),
+            "timestamp:{2021-12-16T00:00:00Z TO *}"
+        );
+        assert_eq!(
+            resolve_relative_times_in_query("timestamp:[10 TO 20] AND body:now", NOW).unwrap(),
+            "timestamp:[10 TO 20] AND body:now"
+        );
+        for query in [
+            "timestamp:[now-1y TO now]",
+            "timestamp:[nowé TO now]",
+            "timestamp:[now-99999999999999999w TO now]",
+            "timestamp:[now TO now+9999999999999w]",
+        ] {
+            assert!(matches!(
+                resolve_relative_times_in_query(query, NOW),
+                Err(SearchError::InvalidQuery(_))
+            ));
+        }
+    }
+}
@@ -114,6 +114,8 @@ mod tests {
             split_id: "split_1".to_string(),
             split_footer_end: 100,
             split_footer_start: 0,
+            sort_by_field: None,
+            sort_order: None,
         };
         let client_for_retry = retry_client(
             &client_pool,
@@ -82,11 +82,15 @@ mod tests {
                     split_id: "split_1".to_string(),
                     split_footer_end: 100,
                     split_footer_start: 0,
+                    sort_by_field: None,
+                    sort_order: None,
                 },
                 SplitIdAndFooterOffsets {
                     split_id: "split_2".to_string(),
                     split_footer_end: 100,
                     split_footer_start: 0,
+                    sort_by_field: None,
+                    sort_order: None,
                 },
             ],
         }
@@ -113,6 +117,9 @@ mod tests {
             partial_hits: vec![],
             failed_splits: vec![],
             num_attempted_splits: 1,
+            timings: None,
+            query_rewrites: Vec::new(),
+            split_profiles: Vec::new(),
         };
         let result = Result::<LeafSearchResponse, SearchError>::Ok(leaf_response);
         let retry_request_opt = retry_policy.retry_request(request, result.as_ref());
@@ -136,6 +143,9 @@ mod tests {
             partial_hits: vec![],
             failed_splits: vec![split_error],
             num_attempted_splits: 1,
+            timings: None,
+            query_rewrites: Vec::new(),
+            split_profiles: Vec::new(),
         };
         let result = Result::<LeafSearchResponse, SearchError>::Ok(leaf_response);
         let retry_request_opt = retry_policy.retry_request(request, result.as_ref());
@@ -112,11 +112,15 @@ mod tests {
                 split_id: "split_1".to_string(),
                 split_footer_end: 100,
                 split_footer_start: 0,
+                sort_by_field: None,
+                sort_order: None,
             },
             SplitIdAndFooterOffsets {
                 split_id: "split_2".to_string(),
                 split_footer_end: 100,
                 split_footer_start: 0,
+                sort_by_field: None,
+                sort_order: None,
             },
         ];
 
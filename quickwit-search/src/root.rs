@@ -19,24 +19,32 @@
 
 use std::cmp::Reverse;
 use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
 
 use futures::future::try_join_all;
-use quickwit_config::build_doc_mapper;
-use quickwit_metastore::{Metastore, SplitMetadata};
+use futures::stream::{FuturesUnordered, TryStreamExt};
+use quickwit_config::{build_doc_mapper, get_searcher_config_instance, VirtualIndexConfig};
+use quickwit_metastore::{alias_index_metadatas, IndexMetadata, Metastore, SplitMetadata};
 use quickwit_proto::{
-    FetchDocsRequest, FetchDocsResponse, Hit, LeafSearchRequest, LeafSearchResponse, PartialHit,
-    SearchRequest, SearchResponse, SplitIdAndFooterOffsets,
+    FetchDocsRequest, FetchDocsResponse, Hit, LeafSearchRequest, PartialHit, SearchRequest,
+    SearchResponse, SplitIdAndFooterOffsets,
 };
-use tantivy::collector::Collector;
-use tantivy::TantivyError;
-use tokio::task::spawn_blocking;
 use tracing::{debug, error, instrument};
 
 use crate::cluster_client::ClusterClient;
-use crate::collector::make_merge_collector;
+use crate::collector::LeafResponseMerger;
+use crate::range_bounds::parse_range_bounds_in_query;
+use crate::relative_time::{now_timestamp, resolve_search_request_times};
 use crate::search_client_pool::Job;
+use crate::snapshot::{
+    check_failed_splits_not_deleted, filter_relevant_splits, pin_all_splits, plan_splits,
+    take_snapshot,
+};
+use crate::terms_lookup::resolve_terms_lookup;
+use crate::versioning::search_latest_versions;
+use crate::virtual_index::{search_virtual_index, validate_doc_mappings_compatibility};
 use crate::{
-    extract_split_and_footer_offsets, list_relevant_splits, SearchClientPool, SearchError,
+    convert_timestamp_bounds, extract_split_and_footer_offsets, SearchClientPool, SearchError,
     SearchServiceClient,
 };
 
@@ -110,6 +118,12 @@ impl From<FetchDocsJob> for SplitIdAndFooterOffsets {
 /// 2. Merges the search results.
 /// 3. Sends fetch docs requests to multiple leaf nodes.
 /// 4. Builds the response with docs and returns.
+///
+/// The relative time expressions of the request are resolved first, so that all the leaves
+/// search the same time window.
+///
+/// If the request holds a terms lookup, the lookup query is run first and
+/// its results are turned into a terms filter of the search query.
 #[instrument(skip(search_request, cluster_client, client_pool, metastore))]
 pub async fn root_search(
     search_request: &SearchRequest,
@@ -117,9 +131,181 @@ pub async fn root_search(
     cluster_client: &ClusterClient,
     client_pool: &SearchClientPool,
 ) -> crate::Result<SearchResponse> {
+    let mut search_request = search_request.clone();
+    resolve_search_request_times(&mut search_request, now_timestamp())?;
+    let search_request = &search_request;
+    let terms_lookup = if let Some(terms_lookup) = &search_request.terms_lookup {
+        terms_lookup
+    } else {
+        return search_index(search_request, metastore, cluster_client, client_pool).await;
+    };
     let start_instant = tokio::time::Instant::now();
+    let resolved_request_opt =
+        resolve_terms_lookup(search_request, terms_lookup, |lookup_request| async move {
+            search_index(&lookup_request, metastore, cluster_client, client_pool).await
+        })
+        .await?;
+    let mut search_response = if let Some(resolved_request) = resolved_request_opt {
+        search_index(&resolved_request, metastore, cluster_client, client_pool).await?
+    } else {
+        SearchResponse::default()
+    };
+    search_response.elapsed_time_micros = start_instant.elapsed().as_micros() as u64;
+    Ok(search_response)
+}
 
+/// Searches an index, which may be a virtual index defined in the searcher config or the write
+/// alias of indexes whose lifecycle is managed. A write alias is searched as the virtual index
+/// made of all its generations.
+async fn search_index(
+    search_request: &SearchRequest,
+    metastore: &dyn Metastore,
+    cluster_client: &ClusterClient,
+    client_pool: &SearchClientPool,
+) -> crate::Result<SearchResponse> {
+    let searcher_config = get_searcher_config_instance();
+    let virtual_index_opt = searcher_config.virtual_index(&search_request.index_id);
+    if let Some(virtual_index) = virtual_index_opt {
+        let index_metadatas = metastore.list_indexes_metadatas().await?;
+        // The missing indexes are reported by the search of the virtual index.
+        let member_index_metadatas: Vec<&IndexMetadata> = index_metadatas
+            .iter()
+            .filter(|index_metadata| virtual_index.indexes.contains(&index_metadata.index_id))
+            .collect();
+        return search_indexes_union(
+            search_request,
+            virtual_index,
+            &member_index_metadatas,
+            metastore,
+            cluster_client,
+            client_pool,
+        )
+        .await;
+    }
+    match search_concrete_index(search_request, metastore, cluster_client, client_pool).await {
+        Err(SearchError::IndexDoesNotExist { index_id }) => {
+            let index_metadatas = metastore.list_indexes_metadatas().await?;
+            let alias_index_metadatas = alias_index_metadatas(&index_metadatas, &index_id);
+            if alias_index_metadatas.is_empty() {
+                return Err(SearchError::IndexDoesNotExist { index_id });
+            }
+            let write_alias = VirtualIndexConfig {
+                index_id,
+                indexes: alias_index_metadatas
+                    .iter()
+                    .map(|index_metadata| index_metadata.index_id.clone())
+                    .collect(),
+            };
+            search_indexes_union(
+                search_request,
+                &write_alias,
+                &alias_index_metadatas,
+                metastore,
+                cluster_client,
+                client_pool,
+            )
+            .await
+        }
+        search_result => search_result,
+    }
+}
+
+/// Searches the union of the indexes of a virtual index or of a write alias, once their doc
+/// mappings are checked to be compatible.
+async fn search_indexes_union(
+    search_request: &SearchRequest,
+    virtual_index: &VirtualIndexConfig,
+    index_metadatas: &[&IndexMetadata],
+    metastore: &dyn Metastore,
+    cluster_client: &ClusterClient,
+    client_pool: &SearchClientPool,
+) -> crate::Result<SearchResponse> {
+    validate_doc_mappings_compatibility(&virtual_index.index_id, index_metadatas)?;
+    let start_instant = tokio::time::Instant::now();
+    let mut search_response =
+        search_virtual_index(search_request, virtual_index, |index_request| async move {
+            search_concrete_index(&index_request, metastore, cluster_client, client_pool).await
+        })
+        .await?;
+    search_response.elapsed_time_micros = start_instant.elapsed().as_micros() as u64;
+    Ok(search_response)
+}
+
+async fn search_concrete_index(
+    search_request: &SearchRequest,
+    metastore: &dyn Metastore,
+    cluster_client: &ClusterClient,
+    client_pool: &SearchClientPool,
+) -> crate::Result<SearchResponse> {
     let index_metadata = metastore.index_metadata(&search_request.index_id).await?;
+    let mut search_request = search_request.clone();
+    convert_timestamp_bounds(
+        &index_metadata,
+        &mut search_request.start_timestamp,
+        &mut search_request.end_timestamp,
+    );
+    search_request.query =
+        parse_range_bounds_in_query(&search_request.query, &index_metadata.doc_mapping)?;
+    let search_request = &search_request;
+    if let Some(versioning) = &index_metadata.indexing_settings.versioning {
+        // The splits are pinned once, so that the search and the versions lookup run on the same
+        // splits.
+        let pinned_split_metadatas = pin_all_splits(search_request, metastore).await?;
+        let snapshot_opt = if search_request.pin_splits {
+            Some(take_snapshot(search_request, &pinned_split_metadatas, metastore).await?)
+        } else {
+            None
+        };
+        let pinned_split_metadatas = &pinned_split_metadatas;
+        let index_metadata = &index_metadata;
+        let mut search_response =
+            search_latest_versions(search_request, versioning, |request| async move {
+                let split_metadatas: Vec<SplitMetadata> =
+                    filter_relevant_splits(&request, pinned_split_metadatas)?;
+                root_search_splits(
+                    &request,
+                    index_metadata,
+                    split_metadatas,
+                    metastore,
+                    cluster_client,
+                    client_pool,
+                )
+                .await
+            })
+            .await?;
+        search_response.snapshot = snapshot_opt;
+        return Ok(search_response);
+    }
+    let split_metadatas: Vec<SplitMetadata> = plan_splits(search_request, metastore).await?;
+    let snapshot_opt = if search_request.pin_splits {
+        Some(take_snapshot(search_request, &split_metadatas, metastore).await?)
+    } else {
+        None
+    };
+    let mut search_response = root_search_splits(
+        search_request,
+        &index_metadata,
+        split_metadatas,
+        metastore,
+        cluster_client,
+        client_pool,
+    )
+    .await?;
+    search_response.snapshot = snapshot_opt;
+    Ok(search_response)
+}
+
+/// Performs a distributed search restricted to the given splits of a concrete index, described
+/// by `index_metadata`.
+pub(crate) async fn root_search_splits(
+    search_request: &SearchRequest,
+    index_metadata: &IndexMetadata,
+    split_metadatas: Vec<SplitMetadata>,
+    metastore: &dyn Metastore,
+    cluster_client: &ClusterClient,
+    client_pool: &SearchClientPool,
+) -> crate::Result<SearchResponse> {
+    let start_instant = tokio::time::Instant::now();
 
     let doc_mapper = build_doc_mapper(
         &index_metadata.doc_mapping,
@@ -133,13 +319,26 @@ pub async fn root_search(
     // try to build query against current schema
     let _query = doc_mapper.query(doc_mapper.schema(), search_request)?;
 
+    // The index has no published splits or none of them intersects the time range of the
+    // request: no leaf request is dispatched.
+    if split_metadatas.is_empty() {
+        debug!("No split to search, returning an empty response.");
+        return Ok(SearchResponse {
+            num_hits: 0,
+            hits: Vec::new(),
+            elapsed_time_micros: start_instant.elapsed().as_micros() as u64,
+            errors: Vec::new(),
+            timings: None,
+            query_rewrites: Vec::new(),
+            split_profiles: Vec::new(),
+            snapshot: None,
+        });
+    }
+
     let doc_mapper_str = serde_json::to_string(&doc_mapper).map_err(|err| {
         SearchError::InternalError(format!("Failed to serialize doc mapper: Cause {}", err))
     })?;
 
-    let split_metadatas: Vec<SplitMetadata> =
-        list_relevant_splits(search_request, metastore).await?;
-
     let split_offsets_map: HashMap<String, SplitIdAndFooterOffsets> = split_metadatas
         .iter()
         .map(|metadata| {
@@ -150,39 +349,41 @@ pub async fn root_search(
         })
         .collect();
 
+    let excluded_addresses = client_pool
+        .addresses_excluded_by_preference(preferred_searcher_nodes(search_request, index_metadata));
     let jobs: Vec<SearchJob> = split_metadatas.iter().map(SearchJob::from).collect();
-    let assigned_leaf_search_jobs = client_pool.assign_jobs(jobs, &HashSet::default())?;
+    let assigned_leaf_search_jobs = client_pool.assign_jobs(jobs, &excluded_addresses)?;
     debug!(assigned_leaf_search_jobs=?assigned_leaf_search_jobs, "Assigned leaf search jobs.");
-    let leaf_search_responses: Vec<LeafSearchResponse> = try_join_all(
-        assigned_leaf_search_jobs
-            .into_iter()
-            .map(|(client, client_jobs)| {
-                let leaf_request = jobs_to_leaf_request(
-                    search_request,
-                    &doc_mapper_str,
-                    &index_metadata.index_uri,
-                    client_jobs,
-                );
-                cluster_client.leaf_search(leaf_request, client)
-            }),
-    )
-    .await?;
+    let mut leaf_search_responses: FuturesUnordered<_> = assigned_leaf_search_jobs
+        .into_iter()
+        .map(|(client, client_jobs)| {
+            let leaf_request = jobs_to_leaf_request(
+                search_request,
+                &doc_mapper_str,
+                &index_metadata.index_uri,
+                client_jobs,
+            );
+            cluster_client.leaf_search(leaf_request, client)
+        })
+        .collect();
 
-    // Creates a collector which merges responses into one
-    let merge_collector = make_merge_collector(search_request);
-
-    // Merging is a cpu-bound task.
-    // It should be executed by Tokio's blocking threads.
-    let leaf_search_response =
-        spawn_blocking(move || merge_collector.merge_fruits(leaf_search_responses))
-            .await?
-            .map_err(|merge_error: TantivyError| {
-                crate::SearchError::InternalError(format!("{}", merge_error))
-            })?;
+    // Leaf responses are merged as they arrive, retaining only the current top hits.
+    let mut leaf_response_merger = LeafResponseMerger::for_request(search_request);
+    while let Some(leaf_search_response) = leaf_search_responses.try_next().await? {
+        leaf_response_merger.add(leaf_search_response);
+    }
+    let leaf_search_response = leaf_response_merger.finish();
     debug!(leaf_search_response = ?leaf_search_response, "Merged leaf search response.");
 
     if !leaf_search_response.failed_splits.is_empty() {
         error!(failed_splits = ?leaf_search_response.failed_splits, "Leaf search response contains at least one failed split.");
+        let failed_split_ids: Vec<String> = leaf_search_response
+            .failed_splits
+            .iter()
+            .map(|failed_split| failed_split.split_id.clone())
+            .collect();
+        check_failed_splits_not_deleted(&search_request.index_id, &failed_split_ids, metastore)
+            .await?;
         let errors: String = leaf_search_response
             .failed_splits
             .iter()
@@ -197,6 +398,7 @@ pub async fn root_search(
             &leaf_search_response.partial_hits,
             &split_offsets_map,
             client_pool,
+            &excluded_addresses,
         )?;
 
     let fetch_docs_resp_futures =
@@ -243,6 +445,10 @@ pub async fn root_search(
         hits,
         elapsed_time_micros: elapsed.as_micros() as u64,
         errors: vec![],
+        timings: leaf_search_response.timings,
+        query_rewrites: leaf_search_response.query_rewrites,
+        split_profiles: leaf_search_response.split_profiles,
+        snapshot: None,
     })
 }
 
@@ -250,6 +456,7 @@ fn assign_client_fetch_doc_tasks(
     partial_hits: &[PartialHit],
     split_offsets_map: &HashMap<String, SplitIdAndFooterOffsets>,
     client_pool: &SearchClientPool,
+    excluded_addresses: &HashSet<SocketAddr>,
 ) -> crate::Result<Vec<(SearchServiceClient, Vec<FetchDocsJob>)>> {
     // Group the partial hits per split
     let mut partial_hits_map: HashMap<String, Vec<PartialHit>> = HashMap::new();
@@ -278,10 +485,23 @@ fn assign_client_fetch_doc_tasks(
     }
 
     let assigned_jobs: Vec<(SearchServiceClient, Vec<FetchDocsJob>)> =
-        client_pool.assign_jobs(fetch_docs_req_jobs, &HashSet::new())?;
+        client_pool.assign_jobs(fetch_docs_req_jobs, excluded_addresses)?;
     Ok(assigned_jobs)
 }
 
+/// Returns the IDs of the searcher nodes that the jobs of the request are preferably assigned to:
+/// the nodes requested by the search request if any, the nodes configured for the index
+/// otherwise.
+pub(crate) fn preferred_searcher_nodes<'a>(
+    search_request: &'a SearchRequest,
+    index_metadata: &'a IndexMetadata,
+) -> &'a [String] {
+    if !search_request.preferred_searcher_nodes.is_empty() {
+        return &search_request.preferred_searcher_nodes;
+    }
+    &index_metadata.search_settings.preferred_searcher_nodes
+}
+
 // Measure the cost associated to searching in a given split metadata.
 fn compute_split_cost(_split_metadata: &SplitMetadata) -> u32 {
     // TODO: Have a smarter cost, by smoothing the number of docs.
@@ -310,8 +530,9 @@ mod tests {
     use std::ops::Range;
     use std::sync::Arc;
 
+    use quickwit_config::LifecycleSettings;
     use quickwit_indexing::mock_split;
-    use quickwit_metastore::{IndexMetadata, MockMetastore, SplitState};
+    use quickwit_metastore::{IndexMetadata, MetastoreError, MockMetastore, SplitState};
     use quickwit_proto::SplitSearchError;
 
     use super::*;
@@ -383,6 +604,9 @@ mod tests {
                     ],
                     failed_splits: Vec::new(),
                     num_attempted_splits: 1,
+                    timings: None,
+                    query_rewrites: Vec::new(),
+                    split_profiles: Vec::new(),
                 })
             },
         );
@@ -404,6 +628,9 @@ mod tests {
                     ],
                     failed_splits: Vec::new(),
                     num_attempted_splits: 1,
+                    timings: None,
+                    query_rewrites: Vec::new(),
+                    split_profiles: Vec::new(),
                 })
             },
         );
@@ -427,6 +654,87 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_root_search_no_split() -> anyhow::Result<()> {
+        let search_request = quickwit_proto::SearchRequest {
+            index_id: "test-idx".to_string(),
+            query: "test".to_string(),
+            search_fields: vec!["body".to_string()],
+            max_hits: 10,
+            ..Default::default()
+        };
+        let mut metastore = MockMetastore::new();
+        metastore
+            .expect_index_metadata()
+            .returning(|_index_id: &str| {
+                Ok(IndexMetadata::for_test(
+                    "test-idx",
+                    "file:///path/to/index/test-idx",
+                ))
+            });
+        metastore.expect_list_splits().returning(
+            |_index_id: &str, _split_state: SplitState, _time_range: Option<Range<i64>>, _tags| {
+                Ok(Vec::new())
+            },
+        );
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service.expect_leaf_search().never();
+        let client_pool = SearchClientPool::from_mocks(vec![Arc::new(mock_search_service)]).await?;
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        let search_response =
+            root_search(&search_request, &metastore, &cluster_client, &client_pool).await?;
+        assert_eq!(search_response.num_hits, 0);
+        assert!(search_response.hits.is_empty());
+        assert!(search_response.errors.is_empty());
+
+        // Invalid queries still fail.
+        let invalid_search_request = quickwit_proto::SearchRequest {
+            query: r#"invalid_field:"test""#.to_string(),
+            ..search_request
+        };
+        assert!(root_search(
+            &invalid_search_request,
+            &metastore,
+            &cluster_client,
+            &client_pool
+        )
+        .await
+        .is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_root_search_empty_time_range() -> anyhow::Result<()> {
+        let search_request = quickwit_proto::SearchRequest {
+            index_id: "test-idx".to_string(),
+            query: "test".to_string(),
+            search_fields: vec!["body".to_string()],
+            start_timestamp: Some(20),
+            end_timestamp: Some(10),
+            max_hits: 10,
+            ..Default::default()
+        };
+        let mut metastore = MockMetastore::new();
+        metastore
+            .expect_index_metadata()
+            .returning(|_index_id: &str| {
+                Ok(IndexMetadata::for_test(
+                    "test-idx",
+                    "file:///path/to/index/test-idx",
+                ))
+            });
+        metastore.expect_list_splits().never();
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service.expect_leaf_search().never();
+        let client_pool = SearchClientPool::from_mocks(vec![Arc::new(mock_search_service)]).await?;
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        let search_response =
+            root_search(&search_request, &metastore, &cluster_client, &client_pool).await?;
+        assert_eq!(search_response.num_hits, 0);
+        assert!(search_response.hits.is_empty());
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_root_search_single_split() -> anyhow::Result<()> {
         let search_request = quickwit_proto::SearchRequest {
@@ -465,6 +773,9 @@ mod tests {
                     ],
                     failed_splits: Vec::new(),
                     num_attempted_splits: 1,
+                    timings: None,
+                    query_rewrites: Vec::new(),
+                    split_profiles: Vec::new(),
                 })
             },
         );
@@ -484,6 +795,101 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_root_search_write_alias() -> anyhow::Result<()> {
+        let search_request = quickwit_proto::SearchRequest {
+            index_id: "logs".to_string(),
+            query: "test".to_string(),
+            search_fields: vec!["body".to_string()],
+            max_hits: 10,
+            ..Default::default()
+        };
+        let alias_index_metadata = |index_id: &str| {
+            let index_uri = format!("file:///path/to/index/{}", index_id);
+            let mut index_metadata = IndexMetadata::for_test(index_id, &index_uri);
+            index_metadata.indexing_settings.lifecycle = Some(LifecycleSettings {
+                write_alias: "logs".to_string(),
+                rollover_max_age_secs: Some(3_600),
+                rollover_max_num_bytes: None,
+                freeze_after_secs: None,
+                delete_after_secs: None,
+            });
+            index_metadata
+        };
+        let mut metastore = MockMetastore::new();
+        metastore
+            .expect_index_metadata()
+            .returning(move |index_id: &str| {
+                if index_id == "logs" {
+                    return Err(MetastoreError::IndexDoesNotExist {
+                        index_id: index_id.to_string(),
+                    });
+                }
+                Ok(alias_index_metadata(index_id))
+            });
+        metastore
+            .expect_list_indexes_metadatas()
+            .returning(move || {
+                Ok(vec![
+                    alias_index_metadata("logs-000001"),
+                    alias_index_metadata("logs-000002"),
+                    IndexMetadata::for_test("other", "file:///path/to/index/other"),
+                ])
+            });
+        metastore.expect_list_splits().returning(
+            |index_id: &str, _split_state: SplitState, _time_range: Option<Range<i64>>, _tags| {
+                Ok(vec![mock_split(&format!("split-{}", index_id))])
+            },
+        );
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service.expect_leaf_search().returning(
+            |leaf_search_req: quickwit_proto::LeafSearchRequest| {
+                let split_id = &leaf_search_req.split_offsets[0].split_id;
+                Ok(quickwit_proto::LeafSearchResponse {
+                    num_hits: 1,
+                    partial_hits: vec![mock_partial_hit(split_id, 1, 1)],
+                    num_attempted_splits: 1,
+                    ..Default::default()
+                })
+            },
+        );
+        mock_search_service.expect_fetch_docs().returning(
+            |fetch_docs_req: quickwit_proto::FetchDocsRequest| {
+                Ok(quickwit_proto::FetchDocsResponse {
+                    hits: get_doc_for_fetch_req(fetch_docs_req),
+                })
+            },
+        );
+        let client_pool = SearchClientPool::from_mocks(vec![Arc::new(mock_search_service)]).await?;
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        // The write alias is searched as all its generations.
+        let search_response =
+            root_search(&search_request, &metastore, &cluster_client, &client_pool).await?;
+        assert_eq!(search_response.num_hits, 2);
+        assert_eq!(search_response.hits.len(), 2);
+
+        // An ID that is neither an index nor a write alias is reported as a missing index.
+        metastore.checkpoint();
+        metastore
+            .expect_index_metadata()
+            .returning(|index_id: &str| {
+                Err(MetastoreError::IndexDoesNotExist {
+                    index_id: index_id.to_string(),
+                })
+            });
+        metastore
+            .expect_list_indexes_metadatas()
+            .returning(|| Ok(Vec::new()));
+        let search_error = root_search(&search_request, &metastore, &cluster_client, &client_pool)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            search_error,
+            SearchError::IndexDoesNotExist { .. }
+        ));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_root_search_multiple_splits() -> anyhow::Result<()> {
         let search_request = quickwit_proto::SearchRequest {
@@ -521,6 +927,9 @@ mod tests {
                     ],
                     failed_splits: Vec::new(),
                     num_attempted_splits: 1,
+                    timings: None,
+                    query_rewrites: Vec::new(),
+                    split_profiles: Vec::new(),
                 })
             },
         );
@@ -539,6 +948,9 @@ mod tests {
                     partial_hits: vec![mock_partial_hit("split2", 2, 2)],
                     failed_splits: Vec::new(),
                     num_attempted_splits: 1,
+                    timings: None,
+                    query_rewrites: Vec::new(),
+                    split_profiles: Vec::new(),
                 })
             },
         );
@@ -604,6 +1016,9 @@ mod tests {
                         retryable_error: true,
                     }],
                     num_attempted_splits: 1,
+                    timings: None,
+                    query_rewrites: Vec::new(),
+                    split_profiles: Vec::new(),
                 })
             });
 
@@ -634,6 +1049,9 @@ mod tests {
                         ],
                         failed_splits: Vec::new(),
                         num_attempted_splits: 1,
+                        timings: None,
+                        query_rewrites: Vec::new(),
+                        split_profiles: Vec::new(),
                     })
                 } else if split_ids == ["split2"] {
                     // RETRY REQUEST!
@@ -642,6 +1060,9 @@ mod tests {
                         partial_hits: vec![mock_partial_hit("split2", 2, 2)],
                         failed_splits: Vec::new(),
                         num_attempted_splits: 1,
+                        timings: None,
+                        query_rewrites: Vec::new(),
+                        split_profiles: Vec::new(),
                     })
                 } else {
                     panic!("unexpected request in test {:?}", split_ids);
@@ -709,6 +1130,9 @@ mod tests {
                         retryable_error: true,
                     }],
                     num_attempted_splits: 1,
+                    timings: None,
+                    query_rewrites: Vec::new(),
+                    split_profiles: Vec::new(),
                 })
             });
         mock_search_service1
@@ -725,6 +1149,9 @@ mod tests {
                     ],
                     failed_splits: Vec::new(),
                     num_attempted_splits: 1,
+                    timings: None,
+                    query_rewrites: Vec::new(),
+                    split_profiles: Vec::new(),
                 })
             });
         mock_search_service1.expect_fetch_docs().returning(
@@ -746,6 +1173,9 @@ mod tests {
                     partial_hits: vec![mock_partial_hit("split2", 2, 2)],
                     failed_splits: Vec::new(),
                     num_attempted_splits: 1,
+                    timings: None,
+                    query_rewrites: Vec::new(),
+                    split_profiles: Vec::new(),
                 })
             });
         mock_search_service2
@@ -764,6 +1194,9 @@ mod tests {
                         retryable_error: true,
                     }],
                     num_attempted_splits: 1,
+                    timings: None,
+                    query_rewrites: Vec::new(),
+                    split_profiles: Vec::new(),
                 })
             });
         mock_search_service2.expect_fetch_docs().returning(
@@ -830,6 +1263,9 @@ mod tests {
                             retryable_error: true,
                         }],
                         num_attempted_splits: 1,
+                        timings: None,
+                        query_rewrites: Vec::new(),
+                        split_profiles: Vec::new(),
                     })
                 } else {
                     Ok(quickwit_proto::LeafSearchResponse {
@@ -837,6 +1273,9 @@ mod tests {
                         partial_hits: vec![mock_partial_hit("split1", 2, 2)],
                         failed_splits: Vec::new(),
                         num_attempted_splits: 1,
+                        timings: None,
+                        query_rewrites: Vec::new(),
+                        split_profiles: Vec::new(),
                     })
                 }
             });
@@ -883,6 +1322,9 @@ mod tests {
                 Ok(vec![mock_split("split1")])
             },
         );
+        metastore
+            .expect_list_all_splits()
+            .returning(|_index_id: &str| Ok(vec![mock_split("split1")]));
 
         let mut mock_search_service1 = MockSearchService::new();
         mock_search_service1
@@ -898,6 +1340,9 @@ mod tests {
                         retryable_error: true,
                     }],
                     num_attempted_splits: 1,
+                    timings: None,
+                    query_rewrites: Vec::new(),
+                    split_profiles: Vec::new(),
                 })
             });
         mock_search_service1.expect_fetch_docs().returning(
@@ -914,6 +1359,129 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_root_search_split_deleted_during_search() -> anyhow::Result<()> {
+        let search_request = quickwit_proto::SearchRequest {
+            index_id: "test-idx".to_string(),
+            query: "test".to_string(),
+            search_fields: vec!["body".to_string()],
+            max_hits: 10,
+            ..Default::default()
+        };
+        let mut metastore = MockMetastore::new();
+        metastore
+            .expect_index_metadata()
+            .returning(|_index_id: &str| {
+                Ok(IndexMetadata::for_test(
+                    "test-idx",
+                    "file:///path/to/index/test-idx",
+                ))
+            });
+        metastore.expect_list_splits().returning(
+            |_index_id: &str, _split_state: SplitState, _time_range: Option<Range<i64>>, _tags| {
+                Ok(vec![mock_split("split1")])
+            },
+        );
+        // The split was merged and garbage collected while being searched.
+        metastore
+            .expect_list_all_splits()
+            .returning(|_index_id: &str| Ok(Vec::new()));
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service.expect_leaf_search().returning(
+            |_leaf_search_req: quickwit_proto::LeafSearchRequest| {
+                Ok(quickwit_proto::LeafSearchResponse {
+                    failed_splits: vec![SplitSearchError {
+                        error: "split1.split does not exist".to_string(),
+                        split_id: "split1".to_string(),
+                        retryable_error: true,
+                    }],
+                    num_attempted_splits: 1,
+                    ..Default::default()
+                })
+            },
+        );
+        let client_pool = SearchClientPool::from_mocks(vec![Arc::new(mock_search_service)]).await?;
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        let search_error = root_search(&search_request, &metastore, &cluster_client, &client_pool)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            search_error,
+            SearchError::SnapshotExpired { split_ids } if split_ids == ["split1"]
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_root_search_pin_splits() -> anyhow::Result<()> {
+        let mut metastore = MockMetastore::new();
+        metastore
+            .expect_index_metadata()
+            .returning(|_index_id: &str| {
+                Ok(IndexMetadata::for_test(
+                    "test-idx",
+                    "file:///path/to/index/test-idx",
+                ))
+            });
+        metastore.expect_list_splits().times(1).returning(
+            |_index_id: &str, _split_state: SplitState, _time_range: Option<Range<i64>>, _tags| {
+                Ok(vec![mock_split("split1")])
+            },
+        );
+        // Since the first search, `split1` was merged into `split2`.
+        metastore
+            .expect_list_all_splits()
+            .returning(|_index_id: &str| {
+                let mut split1 = mock_split("split1");
+                split1.split_state = SplitState::MarkedForDeletion;
+                Ok(vec![split1, mock_split("split2")])
+            });
+        // The snapshot leases `split1` when it is taken, then renews its lease when it is used.
+        metastore
+            .expect_acquire_split_read_leases()
+            .times(2)
+            .returning(|_index_id, split_ids, holder_id, _ttl| {
+                assert_eq!(split_ids, vec!["split1"]);
+                assert!(holder_id.starts_with("snapshot-"));
+                Ok(())
+            });
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service.expect_leaf_search().times(2).returning(
+            |leaf_search_req: quickwit_proto::LeafSearchRequest| {
+                assert_eq!(leaf_search_req.split_offsets.len(), 1);
+                assert_eq!(leaf_search_req.split_offsets[0].split_id, "split1");
+                Ok(quickwit_proto::LeafSearchResponse {
+                    num_attempted_splits: 1,
+                    ..Default::default()
+                })
+            },
+        );
+        let client_pool = SearchClientPool::from_mocks(vec![Arc::new(mock_search_service)]).await?;
+        let cluster_client = ClusterClient::new(client_pool.clone());
+        let search_request = quickwit_proto::SearchRequest {
+            index_id: "test-idx".to_string(),
+            query: "test".to_string(),
+            search_fields: vec!["body".to_string()],
+            max_hits: 10,
+            pin_splits: true,
+            ..Default::default()
+        };
+        let search_response =
+            root_search(&search_request, &metastore, &cluster_client, &client_pool).await?;
+        let snapshot = search_response.snapshot.unwrap();
+
+        let search_request = quickwit_proto::SearchRequest {
+            start_offset: 10,
+            pin_splits: false,
+            snapshot: Some(snapshot),
+            ..search_request
+        };
+        let search_response =
+            root_search(&search_request, &metastore, &cluster_client, &client_pool).await?;
+        assert!(search_response.snapshot.is_none());
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_root_search_one_splits_two_nodes_but_one_is_failing_for_split(
     ) -> anyhow::Result<()> {
@@ -951,6 +1519,9 @@ mod tests {
                     partial_hits: vec![mock_partial_hit("split1", 2, 2)],
                     failed_splits: Vec::new(),
                     num_attempted_splits: 1,
+                    timings: None,
+                    query_rewrites: Vec::new(),
+                    split_profiles: Vec::new(),
                 })
             },
         );
@@ -974,6 +1545,9 @@ mod tests {
                         retryable_error: true,
                     }],
                     num_attempted_splits: 1,
+                    timings: None,
+                    query_rewrites: Vec::new(),
+                    split_profiles: Vec::new(),
                 })
             },
         );
@@ -1032,6 +1606,9 @@ mod tests {
                     partial_hits: vec![mock_partial_hit("split1", 2, 2)],
                     failed_splits: Vec::new(),
                     num_attempted_splits: 1,
+                    timings: None,
+                    query_rewrites: Vec::new(),
+                    split_profiles: Vec::new(),
                 })
             },
         );
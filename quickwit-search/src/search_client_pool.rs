@@ -25,7 +25,7 @@ use std::net::SocketAddr;
 use std::sync::{Arc, RwLock};
 
 use http::Uri;
-use quickwit_cluster::cluster::{Cluster, Member};
+use quickwit_cluster::cluster::{Cluster, Member, QuickwitService};
 use tokio_stream::StreamExt;
 use tonic::transport::Endpoint;
 use tracing::*;
@@ -45,8 +45,9 @@ async fn create_search_service_client(
         .build()?;
     // Create a channel with connect_lazy to automatically reconnect to the node.
     let channel = Endpoint::from(uri).connect_lazy();
+    // Leaf nodes compress their responses only if configured to do so.
     let client = SearchServiceClient::from_grpc_client(
-        quickwit_proto::search_service_client::SearchServiceClient::new(channel),
+        quickwit_proto::search_service_client::SearchServiceClient::new(channel).accept_gzip(),
         grpc_addr,
     );
     Ok(client)
@@ -74,13 +75,24 @@ pub struct SearchClientPool {
     /// A hash map with gRPC's SocketAddr as the key and SearchServiceClient as the value.
     /// It is not the cluster listen address.
     clients: Arc<RwLock<HashMap<SocketAddr, SearchServiceClient>>>,
+    /// Node IDs of the searchers, keyed by gRPC's SocketAddr.
+    /// They are unknown for pools created for a static list of addresses.
+    node_ids: Arc<RwLock<HashMap<SocketAddr, String>>>,
+    /// Cluster through which the searchers report their load, if the pool is kept updated
+    /// with its members.
+    cluster_opt: Option<Arc<Cluster>>,
 }
 
 /// Update the client pool given a new list of members.
+/// Only the members running the leaf searcher service are assigned leaf search requests.
 async fn update_client_map(
     members: &[Member],
     new_clients: &mut HashMap<SocketAddr, crate::SearchServiceClient>,
 ) {
+    let members: Vec<&Member> = members
+        .iter()
+        .filter(|member| member.has_service(QuickwitService::LeafSearcher))
+        .collect();
     // Create a list of addresses to be removed.
     let members_addresses: HashSet<SocketAddr> = members
         .iter()
@@ -127,13 +139,26 @@ impl SearchClientPool {
         }
         Ok(SearchClientPool {
             clients: Arc::new(RwLock::from(clients_map)),
+            node_ids: Default::default(),
+            cluster_opt: None,
         })
     }
 
     async fn update_members(&self, members: &[Member]) {
         let mut new_clients = self.clients();
         update_client_map(members, &mut new_clients).await;
+        let new_node_ids: HashMap<SocketAddr, String> = members
+            .iter()
+            .filter(|member| member.has_service(QuickwitService::LeafSearcher))
+            .map(|member| {
+                (
+                    swim_addr_to_grpc_addr(member.listen_addr),
+                    member.node_id.clone(),
+                )
+            })
+            .collect();
         *self.clients.write().unwrap() = new_clients;
+        *self.node_ids.write().unwrap() = new_node_ids;
     }
 
     /// Returns a copy of the entire member map.
@@ -158,6 +183,8 @@ impl SearchClientPool {
 
         Ok(SearchClientPool {
             clients: Arc::new(RwLock::new(mock_clients)),
+            node_ids: Default::default(),
+            cluster_opt: None,
         })
     }
 
@@ -165,7 +192,10 @@ impl SearchClientPool {
     /// When a client pool is created, the thread that monitors cluster members
     /// will be started at the same time.
     pub async fn create_and_keep_updated(cluster: Arc<Cluster>) -> Self {
-        let search_client_pool = SearchClientPool::default();
+        let search_client_pool = SearchClientPool {
+            cluster_opt: Some(cluster.clone()),
+            ..Default::default()
+        };
         search_client_pool.update_members(&cluster.members()).await;
 
         // Prepare to start a thread that will monitor cluster members.
@@ -187,6 +217,15 @@ fn job_order_key<J: Job>(job: &J) -> (Reverse<u32>, &str) {
     (Reverse(job.cost()), job.split_id())
 }
 
+/// Returns the average cost of the jobs, rounded up, and at least 1.
+fn average_job_cost<J: Job>(jobs: &[J]) -> u64 {
+    if jobs.is_empty() {
+        return 1;
+    }
+    let total_cost: u64 = jobs.iter().map(|job| job.cost() as u64).sum();
+    ((total_cost + jobs.len() as u64 - 1) / jobs.len() as u64).max(1)
+}
+
 /// Node is a utility struct used to represent a rendez-vous hashing node.
 /// It's used to track the load and the computed hash for a given key
 #[derive(Debug, Clone)]
@@ -202,10 +241,65 @@ impl Hash for Node {
 }
 
 impl SearchClientPool {
+    /// Returns the addresses of the searchers that are not among the given preferred nodes, to
+    /// be excluded from the assignment of jobs.
+    ///
+    /// No address is excluded if there is no preference, or if none of the preferred nodes is
+    /// part of the pool, so that jobs are assigned to all the searchers rather than failing.
+    pub fn addresses_excluded_by_preference(
+        &self,
+        preferred_node_ids: &[String],
+    ) -> HashSet<SocketAddr> {
+        if preferred_node_ids.is_empty() {
+            return HashSet::new();
+        }
+        let node_ids = self.node_ids.read().expect("Node IDs lock is poisoned.");
+        let (preferred_addrs, excluded_addrs): (HashSet<SocketAddr>, HashSet<SocketAddr>) =
+            self.clients().into_keys().partition(|grpc_addr| {
+                node_ids
+                    .get(grpc_addr)
+                    .map(|node_id| preferred_node_ids.contains(node_id))
+                    .unwrap_or(false)
+            });
+        if preferred_addrs.is_empty() {
+            warn!(
+                preferred_node_ids = ?preferred_node_ids,
+                "None of the preferred searcher nodes is available."
+            );
+            return HashSet::new();
+        }
+        excluded_addrs
+    }
+
+    /// Returns the number of split searches queued or running on each searcher, as last reported
+    /// by the searchers through the cluster.
+    fn reported_loads(&self) -> HashMap<SocketAddr, u64> {
+        let cluster = match &self.cluster_opt {
+            Some(cluster) => cluster,
+            None => return HashMap::new(),
+        };
+        let node_loads = cluster.node_loads();
+        self.node_ids
+            .read()
+            .expect("Node IDs lock is poisoned.")
+            .iter()
+            .filter_map(|(grpc_addr, node_id)| {
+                let node_load = node_loads.get(node_id)?;
+                Some((*grpc_addr, node_load.num_split_searches()))
+            })
+            .collect()
+    }
+
     /// Assign the given job to the clients.
     /// Returns a list of pair (SocketAddr, Vec<Job>)
     ///
     /// When exclude_addresses filters all clients it is ignored.
+    ///
+    /// Each job is assigned to the least loaded of its two preferred nodes according to
+    /// rendez-vous hashing. The load of a node is the cost of the jobs already assigned to it,
+    /// plus the split searches it last reported as queued or running, so that busy nodes are
+    /// avoided when another node can serve the split. The reported split searches are counted in
+    /// job cost units, each of them weighing the average cost of the jobs to assign.
     pub fn assign_jobs<J: Job>(
         &self,
         mut jobs: Vec<J>,
@@ -220,6 +314,8 @@ impl SearchClientPool {
         {
             // TODO optimize the case where there are few jobs and many clients.
             let clients = self.clients();
+            let reported_loads = self.reported_loads();
+            let average_job_cost = average_job_cost(&jobs);
 
             // when exclude_addresses excludes all adresses we discard it
             let empty_set = HashSet::default();
@@ -235,7 +331,11 @@ impl SearchClientPool {
             {
                 nodes.push(Node {
                     peer_grpc_addr: grpc_addr,
-                    load: 0,
+                    load: reported_loads
+                        .get(&grpc_addr)
+                        .copied()
+                        .unwrap_or(0)
+                        .saturating_mul(average_job_cost),
                 });
                 socket_to_client.insert(grpc_addr, client);
             }
@@ -305,15 +405,18 @@ impl SearchClientPool {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashSet;
-    use std::net::SocketAddr;
+    use std::collections::{HashMap, HashSet};
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
     use std::sync::Arc;
     use std::time::Duration;
 
     use itertools::Itertools;
-    use quickwit_cluster::cluster::create_cluster_for_test;
+    use quickwit_cluster::cluster::{
+        create_cluster_for_test, create_cluster_for_test_with_id, Cluster, NodeLoad,
+        QuickwitService,
+    };
 
-    use super::create_search_service_client;
+    use super::{average_job_cost, create_search_service_client};
     use crate::root::SearchJob;
     use crate::{swim_addr_to_grpc_addr, SearchClientPool};
 
@@ -351,6 +454,28 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_search_client_pool_ignores_non_searcher_nodes() -> anyhow::Result<()> {
+        let cluster1 = Arc::new(create_cluster_for_test()?);
+        let port = quickwit_common::net::find_available_port()?;
+        let cluster2_listen_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port);
+        let cluster2 = Cluster::new(
+            "indexer".to_string(),
+            [QuickwitService::Indexer].into_iter().collect(),
+            cluster2_listen_addr,
+        )?;
+
+        cluster2.add_peer_node(cluster1.listen_addr).await;
+        cluster1
+            .wait_for_members(|members| members.len() == 2, Duration::from_secs(5))
+            .await?;
+
+        let client_pool = SearchClientPool::create_and_keep_updated(cluster1.clone()).await;
+        let addrs: Vec<SocketAddr> = client_pool.clients().into_keys().collect();
+        assert_eq!(addrs, vec![swim_addr_to_grpc_addr(cluster1.listen_addr)]);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_search_client_pool_single_node_assign_jobs() -> anyhow::Result<()> {
         let cluster = Arc::new(create_cluster_for_test()?);
@@ -378,4 +503,123 @@ mod tests {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_search_client_pool_assign_jobs_to_preferred_nodes() -> anyhow::Result<()> {
+        let cluster1 = Arc::new(create_cluster_for_test_with_id("searcher-1".to_string())?);
+        let cluster2 = Arc::new(create_cluster_for_test_with_id("searcher-2".to_string())?);
+
+        cluster2.add_peer_node(cluster1.listen_addr).await;
+        cluster1
+            .wait_for_members(|members| members.len() == 2, Duration::from_secs(5))
+            .await?;
+
+        let client_pool = SearchClientPool::create_and_keep_updated(cluster1.clone()).await;
+        assert!(client_pool.addresses_excluded_by_preference(&[]).is_empty());
+        assert!(client_pool
+            .addresses_excluded_by_preference(&["unknown-searcher".to_string()])
+            .is_empty());
+
+        let excluded_addrs =
+            client_pool.addresses_excluded_by_preference(&["searcher-2".to_string()]);
+        let expected_excluded_addrs: HashSet<SocketAddr> =
+            [swim_addr_to_grpc_addr(cluster1.listen_addr)]
+                .into_iter()
+                .collect();
+        assert_eq!(excluded_addrs, expected_excluded_addrs);
+
+        let jobs = vec![
+            SearchJob::for_test("split1", 1),
+            SearchJob::for_test("split2", 2),
+            SearchJob::for_test("split3", 3),
+        ];
+        let assigned_jobs = client_pool.assign_jobs(jobs, &excluded_addrs)?;
+        assert_eq!(assigned_jobs.len(), 1);
+        assert_eq!(
+            assigned_jobs[0].0.grpc_addr(),
+            swim_addr_to_grpc_addr(cluster2.listen_addr)
+        );
+        assert_eq!(assigned_jobs[0].1.len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_client_pool_assign_jobs_to_less_loaded_nodes() -> anyhow::Result<()> {
+        let cluster1 = Arc::new(create_cluster_for_test()?);
+        let cluster2 = Arc::new(create_cluster_for_test()?);
+
+        cluster2.add_peer_node(cluster1.listen_addr).await;
+        cluster1
+            .wait_for_members(|members| members.len() == 2, Duration::from_secs(5))
+            .await?;
+
+        let client_pool = SearchClientPool::create_and_keep_updated(cluster1.clone()).await;
+        cluster1.broadcast_load(NodeLoad {
+            num_queued_split_searches: 50,
+            num_ongoing_split_searches: 100,
+        });
+        let jobs = (0..10)
+            .map(|split_ord| SearchJob::for_test(&format!("split{}", split_ord), 1))
+            .collect();
+        let assigned_jobs = client_pool.assign_jobs(jobs, &HashSet::new())?;
+        assert_eq!(assigned_jobs.len(), 1);
+        assert_eq!(
+            assigned_jobs[0].0.grpc_addr(),
+            swim_addr_to_grpc_addr(cluster2.listen_addr)
+        );
+        assert_eq!(assigned_jobs[0].1.len(), 10);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_client_pool_assign_jobs_counts_load_in_job_cost_units(
+    ) -> anyhow::Result<()> {
+        let cluster1 = Arc::new(create_cluster_for_test()?);
+        let cluster2 = Arc::new(create_cluster_for_test()?);
+
+        cluster2.add_peer_node(cluster1.listen_addr).await;
+        cluster1
+            .wait_for_members(|members| members.len() == 2, Duration::from_secs(5))
+            .await?;
+
+        let client_pool = SearchClientPool::create_and_keep_updated(cluster1.clone()).await;
+        cluster1.broadcast_load(NodeLoad {
+            num_queued_split_searches: 1,
+            num_ongoing_split_searches: 2,
+        });
+        // The 3 split searches of the first node weigh as much as 3 jobs of cost 100, so the
+        // second node gets 3 more jobs than the first one, give or take one.
+        let jobs = (0..10)
+            .map(|split_ord| SearchJob::for_test(&format!("split{}", split_ord), 100))
+            .collect();
+        let assigned_jobs = client_pool.assign_jobs(jobs, &HashSet::new())?;
+        let num_jobs_per_addr: HashMap<SocketAddr, usize> = assigned_jobs
+            .iter()
+            .map(|(client, jobs)| (client.grpc_addr(), jobs.len()))
+            .collect();
+        let num_jobs_1 = num_jobs_per_addr
+            .get(&swim_addr_to_grpc_addr(cluster1.listen_addr))
+            .copied()
+            .unwrap_or(0);
+        let num_jobs_2 = num_jobs_per_addr
+            .get(&swim_addr_to_grpc_addr(cluster2.listen_addr))
+            .copied()
+            .unwrap_or(0);
+        assert_eq!(num_jobs_1 + num_jobs_2, 10);
+        assert!((3..=4).contains(&num_jobs_1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_average_job_cost() {
+        assert_eq!(average_job_cost::<SearchJob>(&[]), 1);
+        assert_eq!(average_job_cost(&[SearchJob::for_test("split1", 0)]), 1);
+        assert_eq!(
+            average_job_cost(&[
+                SearchJob::for_test("split1", 1),
+                SearchJob::for_test("split2", 4),
+            ]),
+            3
+        );
+    }
 }
@@ -19,6 +19,7 @@
 
 use std::convert::TryFrom;
 
+use quickwit_proto::{LeafSearchTimings, SplitProfile, SplitQueryRewrite};
 use serde::Serialize;
 
 use crate::error::SearchError;
@@ -36,6 +37,19 @@ pub struct SearchResponseRest {
     pub elapsed_time_micros: u64,
     /// Search errors.
     pub errors: Vec<String>,
+    /// Breakdown of the time spent by the leaves, summed over all searched splits.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<LeafSearchTimings>,
+    /// Splits whose query was rewritten because they were built before some fields of the query
+    /// existed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub query_rewrites: Vec<SplitQueryRewrite>,
+    /// Profiles of the execution of the query on the searched splits, if requested.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub split_profiles: Vec<SplitProfile>,
+    /// Snapshot token pinning the searched splits, if requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
 }
 
 impl TryFrom<quickwit_proto::SearchResponse> for SearchResponseRest {
@@ -59,6 +73,10 @@ impl TryFrom<quickwit_proto::SearchResponse> for SearchResponseRest {
             hits,
             elapsed_time_micros: search_response.elapsed_time_micros,
             errors: search_response.errors,
+            timings: search_response.timings,
+            query_rewrites: search_response.query_rewrites,
+            split_profiles: search_response.split_profiles,
+            snapshot: search_response.snapshot,
         })
     }
 }
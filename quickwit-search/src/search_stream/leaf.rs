@@ -19,7 +19,7 @@
 
 use std::collections::HashSet;
 use std::hash::Hash;
-use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use futures::{FutureExt, StreamExt};
@@ -34,31 +34,121 @@ use quickwit_storage::Storage;
 use tantivy::fastfield::FastValue;
 use tantivy::query::Query;
 use tantivy::schema::{Field, Schema, Type};
-use tantivy::{LeasedItem, ReloadPolicy, Searcher};
 use tokio::sync::{Semaphore, SemaphorePermit};
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tracing::*;
 
 use super::collector::{PartionnedFastFieldCollector, PartitionValues};
 use super::FastFieldCollector;
-use crate::leaf::{open_index, warmup};
+use crate::leaf::{local_split_path, warmup};
+use crate::split_reader_pool::{split_reader_pool, SplitSearcher};
 use crate::{Result, SearchError};
 
 fn get_max_num_concurrent_split_streams() -> usize {
-    static INSTANCE: OnceCell<usize> = OnceCell::new();
-    *INSTANCE.get_or_init(|| get_searcher_config_instance().max_num_concurrent_split_streams)
+    get_searcher_config_instance().max_num_concurrent_split_streams
 }
 
-async fn get_split_stream_semaphore() -> SemaphorePermit<'static> {
-    static INSTANCE: OnceCell<Arc<Semaphore>> = OnceCell::new();
-    INSTANCE
-        .get_or_init(|| {
-            let max_num_concurrent_split_streams = get_max_num_concurrent_split_streams();
-            Arc::new(Semaphore::new(max_num_concurrent_split_streams))
-        })
-        .acquire()
-        .await
-        .expect("Failed to acquire permit. This should never happen. Please report.")
+/// The semaphore limiting the number of concurrent split streams, along with its current
+/// number of permits.
+struct SplitStreamSemaphore {
+    semaphore: Semaphore,
+    num_permits: AtomicUsize,
+    /// Number of permits in use to discard when they are released, after the semaphore shrank.
+    num_permits_to_forget: AtomicUsize,
+}
+
+impl SplitStreamSemaphore {
+    fn new(num_permits: usize) -> Self {
+        Self {
+            semaphore: Semaphore::new(num_permits),
+            num_permits: AtomicUsize::new(num_permits),
+            num_permits_to_forget: AtomicUsize::new(0),
+        }
+    }
+
+    /// Resizes the semaphore when the limit of the searcher config was changed by a config
+    /// reload. Shrinking discards the available permits right away, and the permits in use as
+    /// they are released.
+    fn resize(&self, max_num_concurrent_split_streams: usize) {
+        let num_permits = self
+            .num_permits
+            .swap(max_num_concurrent_split_streams, Ordering::SeqCst);
+        if max_num_concurrent_split_streams > num_permits {
+            let num_new_permits = max_num_concurrent_split_streams - num_permits;
+            // The permits still to be discarded are kept instead of adding new ones.
+            let num_kept_permits = self
+                .num_permits_to_forget
+                .fetch_update(
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                    |num_permits_to_forget| {
+                        Some(num_permits_to_forget.saturating_sub(num_new_permits))
+                    },
+                )
+                .expect("The update function always returns a value.")
+                .min(num_new_permits);
+            self.semaphore
+                .add_permits(num_new_permits - num_kept_permits);
+        } else if max_num_concurrent_split_streams < num_permits {
+            let num_extra_permits = num_permits - max_num_concurrent_split_streams;
+            let mut num_forgotten_permits = 0;
+            while num_forgotten_permits < num_extra_permits {
+                match self.semaphore.try_acquire() {
+                    Ok(permit) => permit.forget(),
+                    Err(_) => break,
+                }
+                num_forgotten_permits += 1;
+            }
+            self.num_permits_to_forget
+                .fetch_add(num_extra_permits - num_forgotten_permits, Ordering::SeqCst);
+        }
+    }
+
+    async fn acquire(&self) -> SplitStreamPermit<'_> {
+        let permit = self
+            .semaphore
+            .acquire()
+            .await
+            .expect("Failed to acquire permit. This should never happen. Please report.");
+        SplitStreamPermit {
+            permit_opt: Some(permit),
+            num_permits_to_forget: &self.num_permits_to_forget,
+        }
+    }
+}
+
+/// Permit to stream a split. On release, the permit is discarded instead of being returned to
+/// the semaphore if the semaphore shrank while it was in use.
+struct SplitStreamPermit<'a> {
+    permit_opt: Option<SemaphorePermit<'a>>,
+    num_permits_to_forget: &'a AtomicUsize,
+}
+
+impl Drop for SplitStreamPermit<'_> {
+    fn drop(&mut self) {
+        let should_forget = self
+            .num_permits_to_forget
+            .fetch_update(
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+                |num_permits_to_forget| num_permits_to_forget.checked_sub(1),
+            )
+            .is_ok();
+        if let Some(permit) = self.permit_opt.take() {
+            if should_forget {
+                permit.forget();
+            }
+        }
+    }
+}
+
+async fn get_split_stream_semaphore() -> SplitStreamPermit<'static> {
+    static INSTANCE: OnceCell<SplitStreamSemaphore> = OnceCell::new();
+    let max_num_concurrent_split_streams = get_max_num_concurrent_split_streams();
+    let split_stream_semaphore =
+        INSTANCE.get_or_init(|| SplitStreamSemaphore::new(max_num_concurrent_split_streams));
+    split_stream_semaphore.resize(max_num_concurrent_split_streams);
+    split_stream_semaphore.acquire().await
 }
 
 /// `leaf` step of search stream.
@@ -124,8 +214,9 @@ async fn leaf_search_stream_single_split(
 ) -> crate::Result<LeafSearchStreamResponse> {
     let _leaf_permit = get_split_stream_semaphore().await;
 
-    let index = open_index(storage, &split).await?;
-    let split_schema = index.schema();
+    let is_local_split = local_split_path(&*storage, &split.split_id).is_some();
+    let searcher = split_reader_pool().searcher(storage, &split).await?;
+    let split_schema = searcher.schema().clone();
 
     let request_fields = Arc::new(SearchStreamRequestFields::from_request(
         &stream_request,
@@ -149,18 +240,14 @@ async fn leaf_search_stream_single_split(
 
     let search_request = Arc::new(SearchRequest::from(stream_request.clone()));
     let query = doc_mapper.query(split_schema.clone(), &search_request)?;
-    let reader = index
-        .reader_builder()
-        .num_searchers(1)
-        .reload_policy(ReloadPolicy::Manual)
-        .try_into()?;
-    let searcher = reader.searcher();
-    warmup(
-        &*searcher,
-        query.as_ref(),
-        &request_fields.fast_fields_for_request(),
-    )
-    .await?;
+    if !is_local_split {
+        warmup(
+            &*searcher,
+            query.as_ref(),
+            &request_fields.fast_fields_for_request(),
+        )
+        .await?;
+    }
 
     let span = info_span!(
         "collect_fast_field",
@@ -258,7 +345,7 @@ fn collect_values<TFastValue: FastValue>(
     request_fields: &SearchStreamRequestFields,
     start_timestamp: Option<i64>,
     end_timestamp: Option<i64>,
-    searcher: LeasedItem<Searcher>,
+    searcher: SplitSearcher,
     query: &dyn Query,
 ) -> crate::Result<Vec<TFastValue>> {
     let collector = FastFieldCollector::<TFastValue> {
@@ -276,7 +363,7 @@ fn collect_partitioned_values<TFastValue: FastValue, TPartitionValue: FastValue
     request_fields: &SearchStreamRequestFields,
     start_timestamp_opt: Option<i64>,
     end_timestamp_opt: Option<i64>,
-    searcher: LeasedItem<Searcher>,
+    searcher: SplitSearcher,
     query: &dyn Query,
 ) -> crate::Result<Vec<PartitionValues<TFastValue, TPartitionValue>>> {
     let collector = PartionnedFastFieldCollector::<TFastValue, TPartitionValue> {
@@ -418,6 +505,37 @@ mod tests {
 
     use super::*;
 
+    #[tokio::test]
+    async fn test_split_stream_semaphore_resize() {
+        let split_stream_semaphore = SplitStreamSemaphore::new(2);
+        split_stream_semaphore.resize(4);
+        assert_eq!(split_stream_semaphore.semaphore.available_permits(), 4);
+
+        split_stream_semaphore.resize(1);
+        assert_eq!(split_stream_semaphore.semaphore.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_split_stream_semaphore_shrink_with_permits_in_use() {
+        let split_stream_semaphore = SplitStreamSemaphore::new(3);
+        let first_permit = split_stream_semaphore.acquire().await;
+        let second_permit = split_stream_semaphore.acquire().await;
+        split_stream_semaphore.resize(1);
+        // The available permit is discarded right away, the permits in use as they are released.
+        assert_eq!(split_stream_semaphore.semaphore.available_permits(), 0);
+        drop(first_permit);
+        assert_eq!(split_stream_semaphore.semaphore.available_permits(), 0);
+        drop(second_permit);
+        assert_eq!(split_stream_semaphore.semaphore.available_permits(), 1);
+
+        // Growing back cancels the permits that remain to be discarded first.
+        let permit = split_stream_semaphore.acquire().await;
+        split_stream_semaphore.resize(0);
+        split_stream_semaphore.resize(2);
+        drop(permit);
+        assert_eq!(split_stream_semaphore.semaphore.available_permits(), 2);
+    }
+
     #[tokio::test]
     async fn test_leaf_search_stream_to_csv_output_with_filtering() -> anyhow::Result<()> {
         let index_id = "single-node-simple";
@@ -461,6 +579,8 @@ mod tests {
             fast_field: "ts".to_string(),
             output_format: 0,
             partition_by_field: None,
+            start_time_expression: None,
+            end_time_expression: None,
         };
         let splits = test_sandbox.metastore().list_all_splits(index_id).await?;
         let splits_offsets = splits
@@ -469,6 +589,8 @@ mod tests {
                 split_id: split_meta.split_id().to_string(),
                 split_footer_start: split_meta.split_metadata.footer_offsets.start,
                 split_footer_end: split_meta.split_metadata.footer_offsets.end,
+                sort_by_field: None,
+                sort_order: None,
             })
             .collect();
         let mut single_node_stream = leaf_search_stream(
@@ -555,6 +677,8 @@ mod tests {
             fast_field: "fast_field".to_string(),
             output_format: 1,
             partition_by_field: Some(String::from("partition_by_fast_field")),
+            start_time_expression: None,
+            end_time_expression: None,
         };
         let splits = test_sandbox.metastore().list_all_splits(index_id).await?;
         let splits_offsets = splits
@@ -563,6 +687,8 @@ mod tests {
                 split_id: split_meta.split_id().to_string(),
                 split_footer_start: split_meta.split_metadata.footer_offsets.start,
                 split_footer_end: split_meta.split_metadata.footer_offsets.end,
+                sort_by_field: None,
+                sort_order: None,
             })
             .collect();
         let mut single_node_stream = leaf_search_stream(
@@ -17,7 +17,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::collections::HashSet;
+use std::sync::Arc;
 
 use bytes::Bytes;
 use futures::{StreamExt, TryStreamExt};
@@ -28,23 +28,40 @@ use tokio_stream::StreamMap;
 use tracing::*;
 
 use crate::cluster_client::ClusterClient;
+use crate::range_bounds::parse_range_bounds_in_query;
+use crate::relative_time::{now_timestamp, resolve_search_stream_request_times};
 use crate::root::SearchJob;
-use crate::{list_relevant_splits, SearchClientPool, SearchError, SearchServiceClient};
+use crate::split_lease::SplitReadLeaseGuard;
+use crate::{
+    convert_timestamp_bounds, list_relevant_splits, SearchClientPool, SearchError,
+    SearchServiceClient,
+};
 
 /// Perform a distributed search stream.
 #[instrument(skip(metastore, cluster_client, client_pool))]
 pub async fn root_search_stream(
     search_stream_request: SearchStreamRequest,
-    metastore: &dyn Metastore,
+    metastore: Arc<dyn Metastore>,
     cluster_client: ClusterClient,
     client_pool: &SearchClientPool,
 ) -> crate::Result<impl futures::Stream<Item = crate::Result<Bytes>>> {
     // TODO: building a search request should not be necessary for listing splits.
     // This needs some refactoring: relevant splits, metadata_map, jobs...
 
+    let mut search_stream_request = search_stream_request;
+    resolve_search_stream_request_times(&mut search_stream_request, now_timestamp())?;
+    let index_metadata = metastore
+        .index_metadata(&search_stream_request.index_id)
+        .await?;
+    convert_timestamp_bounds(
+        &index_metadata,
+        &mut search_stream_request.start_timestamp,
+        &mut search_stream_request.end_timestamp,
+    );
+    search_stream_request.query =
+        parse_range_bounds_in_query(&search_stream_request.query, &index_metadata.doc_mapping)?;
     let search_request = SearchRequest::from(search_stream_request.clone());
-    let index_metadata = metastore.index_metadata(&search_request.index_id).await?;
-    let split_metadatas = list_relevant_splits(&search_request, metastore).await?;
+    let split_metadatas = list_relevant_splits(&search_request, &*metastore).await?;
     let doc_mapper = build_doc_mapper(
         &index_metadata.doc_mapping,
         &index_metadata.search_settings,
@@ -61,10 +78,24 @@ pub async fn root_search_stream(
         SearchError::InternalError(format!("Failed to serialize doc mapper: Cause {}", err))
     })?;
 
+    // The splits are leased until the stream is dropped, so that the garbage collector does not
+    // delete them while they are streamed.
+    let split_lease_guard = SplitReadLeaseGuard::acquire(
+        metastore,
+        &search_stream_request.index_id,
+        split_metadatas
+            .iter()
+            .map(|split_metadata| split_metadata.split_id.clone())
+            .collect(),
+    )
+    .await?;
+
+    let excluded_addresses = client_pool
+        .addresses_excluded_by_preference(&index_metadata.search_settings.preferred_searcher_nodes);
     let leaf_search_jobs: Vec<SearchJob> = split_metadatas.iter().map(SearchJob::from).collect();
 
     let assigned_leaf_search_jobs: Vec<(SearchServiceClient, Vec<SearchJob>)> =
-        client_pool.assign_jobs(leaf_search_jobs, &HashSet::default())?;
+        client_pool.assign_jobs(leaf_search_jobs, &excluded_addresses)?;
     debug!(assigned_leaf_search_jobs=?assigned_leaf_search_jobs, "Assigned leaf search jobs.");
 
     let mut stream_map: StreamMap<usize, _> = StreamMap::new();
@@ -82,7 +113,10 @@ pub async fn root_search_stream(
     }
     Ok(stream_map
         .map(|(_leaf_ord, result)| result)
-        .map_ok(|leaf_response| Bytes::from(leaf_response.data)))
+        .map_ok(move |leaf_response| {
+            let _split_lease_guard = &split_lease_guard;
+            Bytes::from(leaf_response.data)
+        }))
 }
 
 fn jobs_to_leaf_request(
@@ -123,6 +157,8 @@ mod tests {
             fast_field: "timestamp".to_string(),
             output_format: OutputFormat::Csv as i32,
             partition_by_field: None,
+            start_time_expression: None,
+            end_time_expression: None,
         };
         let mut metastore = MockMetastore::new();
         metastore
@@ -138,6 +174,12 @@ mod tests {
                 Ok(vec![mock_split("split1")])
             },
         );
+        metastore
+            .expect_acquire_split_read_leases()
+            .returning(|_index_id, _split_ids, _holder_id, _ttl| Ok(()));
+        metastore
+            .expect_release_split_read_leases()
+            .returning(|_index_id, _holder_id| Ok(()));
         let mut mock_search_service = MockSearchService::new();
         let (result_sender, result_receiver) = tokio::sync::mpsc::unbounded_channel();
         result_sender.send(Ok(quickwit_proto::LeafSearchStreamResponse {
@@ -159,7 +201,7 @@ mod tests {
 
         let cluster_client = ClusterClient::new(client_pool.clone());
         let result: Vec<Bytes> =
-            root_search_stream(request, &metastore, cluster_client, &client_pool)
+            root_search_stream(request, Arc::new(metastore), cluster_client, &client_pool)
                 .await?
                 .try_collect()
                 .await?;
@@ -180,6 +222,8 @@ mod tests {
             fast_field: "timestamp".to_string(),
             output_format: OutputFormat::Csv as i32,
             partition_by_field: Some("timestamp".to_string()),
+            start_time_expression: None,
+            end_time_expression: None,
         };
         let mut metastore = MockMetastore::new();
         metastore
@@ -195,6 +239,12 @@ mod tests {
                 Ok(vec![mock_split("split1")])
             },
         );
+        metastore
+            .expect_acquire_split_read_leases()
+            .returning(|_index_id, _split_ids, _holder_id, _ttl| Ok(()));
+        metastore
+            .expect_release_split_read_leases()
+            .returning(|_index_id, _holder_id| Ok(()));
         let mut mock_search_service = MockSearchService::new();
         let (result_sender, result_receiver) = tokio::sync::mpsc::unbounded_channel();
         result_sender.send(Ok(quickwit_proto::LeafSearchStreamResponse {
@@ -214,7 +264,8 @@ mod tests {
         drop(result_sender);
         let client_pool = SearchClientPool::from_mocks(vec![Arc::new(mock_search_service)]).await?;
         let cluster_client = ClusterClient::new(client_pool.clone());
-        let stream = root_search_stream(request, &metastore, cluster_client, &client_pool).await?;
+        let stream =
+            root_search_stream(request, Arc::new(metastore), cluster_client, &client_pool).await?;
         let result: Vec<_> = stream.try_collect().await?;
         assert_eq!(result.len(), 2);
         assert_eq!(&result[0], &b"123"[..]);
@@ -233,6 +284,8 @@ mod tests {
             fast_field: "timestamp".to_string(),
             output_format: OutputFormat::Csv as i32,
             partition_by_field: None,
+            start_time_expression: None,
+            end_time_expression: None,
         };
         let mut metastore = MockMetastore::new();
         metastore
@@ -248,6 +301,12 @@ mod tests {
                 Ok(vec![mock_split("split1"), mock_split("split2")])
             },
         );
+        metastore
+            .expect_acquire_split_read_leases()
+            .returning(|_index_id, _split_ids, _holder_id, _ttl| Ok(()));
+        metastore
+            .expect_release_split_read_leases()
+            .returning(|_index_id, _holder_id| Ok(()));
         let mut mock_search_service = MockSearchService::new();
         let (result_sender, result_receiver) = tokio::sync::mpsc::unbounded_channel();
         result_sender.send(Ok(quickwit_proto::LeafSearchStreamResponse {
@@ -275,7 +334,8 @@ mod tests {
         drop(result_sender);
         let client_pool = SearchClientPool::from_mocks(vec![Arc::new(mock_search_service)]).await?;
         let cluster_client = ClusterClient::new(client_pool.clone());
-        let stream = root_search_stream(request, &metastore, cluster_client, &client_pool).await?;
+        let stream =
+            root_search_stream(request, Arc::new(metastore), cluster_client, &client_pool).await?;
         let result: Result<Vec<_>, SearchError> = stream.try_collect().await;
         assert_eq!(result.is_err(), true);
         assert_eq!(result.unwrap_err().to_string(), "Internal error: `error`.");
@@ -299,6 +359,7 @@ mod tests {
             },
         );
 
+        let metastore = Arc::new(metastore);
         let client_pool =
             SearchClientPool::from_mocks(vec![Arc::new(MockSearchService::new())]).await?;
 
@@ -312,8 +373,10 @@ mod tests {
                 fast_field: "timestamp".to_string(),
                 output_format: OutputFormat::Csv as i32,
                 partition_by_field: Some("timestamp".to_string()),
+                start_time_expression: None,
+                end_time_expression: None,
             },
-            &metastore,
+            metastore.clone(),
             ClusterClient::new(client_pool.clone()),
             &client_pool,
         )
@@ -330,8 +393,10 @@ mod tests {
                 fast_field: "timestamp".to_string(),
                 output_format: OutputFormat::Csv as i32,
                 partition_by_field: Some("timestamp".to_string()),
+                start_time_expression: None,
+                end_time_expression: None,
             },
-            &metastore,
+            metastore.clone(),
             ClusterClient::new(client_pool.clone()),
             &client_pool
         )
@@ -0,0 +1,102 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use once_cell::sync::Lazy;
+use prometheus::IntCounter;
+use quickwit_common::metrics::new_counter;
+use quickwit_proto::LeafSearchTimings;
+
+static LEAF_SEARCH_WARMUP_MICROS: Lazy<IntCounter> = Lazy::new(|| {
+    new_counter(
+        "quickwit_search_leaf_warmup_micros_total",
+        "Time spent opening splits and downloading the data required by leaf searches, in \
+         microseconds.",
+    )
+});
+
+static LEAF_SEARCH_QUEUE_WAIT_MICROS: Lazy<IntCounter> = Lazy::new(|| {
+    new_counter(
+        "quickwit_search_leaf_queue_wait_micros_total",
+        "Time spent by leaf searches waiting for a search thread, in microseconds.",
+    )
+});
+
+static LEAF_SEARCH_CPU_MICROS: Lazy<IntCounter> = Lazy::new(|| {
+    new_counter(
+        "quickwit_search_leaf_cpu_micros_total",
+        "Time spent by leaf searches collecting hits on a search thread, in microseconds.",
+    )
+});
+
+/// Adds the timings of a split search to the leaf search metrics.
+pub(crate) fn record_leaf_search_timings(timings: &LeafSearchTimings) {
+    LEAF_SEARCH_WARMUP_MICROS.inc_by(timings.warmup_micros);
+    LEAF_SEARCH_QUEUE_WAIT_MICROS.inc_by(timings.queue_wait_micros);
+    LEAF_SEARCH_CPU_MICROS.inc_by(timings.cpu_micros);
+}
+
+/// Sums the timings of several leaf search responses. Returns `None` if none of the responses
+/// carries timings, which is the case of responses sent by older searchers.
+pub(crate) fn merge_leaf_search_timings<'a>(
+    timings: impl IntoIterator<Item = Option<&'a LeafSearchTimings>>,
+) -> Option<LeafSearchTimings> {
+    timings
+        .into_iter()
+        .flatten()
+        .fold(None, |merged_opt, timings| {
+            let mut merged = merged_opt.unwrap_or_default();
+            merged.warmup_micros += timings.warmup_micros;
+            merged.queue_wait_micros += timings.queue_wait_micros;
+            merged.cpu_micros += timings.cpu_micros;
+            merged.num_splits += timings.num_splits;
+            Some(merged)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_leaf_search_timings() {
+        assert_eq!(merge_leaf_search_timings(Vec::new()), None);
+        assert_eq!(merge_leaf_search_timings(vec![None, None]), None);
+        let timings = LeafSearchTimings {
+            warmup_micros: 10,
+            queue_wait_micros: 2,
+            cpu_micros: 5,
+            num_splits: 1,
+        };
+        let other_timings = LeafSearchTimings {
+            warmup_micros: 20,
+            queue_wait_micros: 0,
+            cpu_micros: 7,
+            num_splits: 2,
+        };
+        assert_eq!(
+            merge_leaf_search_timings(vec![Some(&timings), None, Some(&other_timings)]),
+            Some(LeafSearchTimings {
+                warmup_micros: 30,
+                queue_wait_micros: 2,
+                cpu_micros: 12,
+                num_splits: 3,
+            })
+        );
+    }
+}
@@ -25,16 +25,19 @@ use bytes::Bytes;
 use quickwit_doc_mapper::DocMapper;
 use quickwit_metastore::Metastore;
 use quickwit_proto::{
-    FetchDocsRequest, FetchDocsResponse, LeafSearchRequest, LeafSearchResponse,
+    FetchDocsRequest, FetchDocsResponse, Hit, LeafSearchRequest, LeafSearchResponse,
     LeafSearchStreamRequest, LeafSearchStreamResponse, SearchRequest, SearchResponse,
     SearchStreamRequest,
 };
-use quickwit_storage::StorageUriResolver;
+use quickwit_storage::{account_storage_costs, StorageActivity, StorageUriResolver};
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tracing::info;
 
 use crate::search_stream::{leaf_search_stream, root_search_stream};
-use crate::{fetch_docs, leaf_search, root_search, ClusterClient, SearchClientPool, SearchError};
+use crate::{
+    fetch_docs, leaf_search, root_search, root_search_tail, split_access_tracker, ClusterClient,
+    SearchClientPool, SearchError, TAIL_POLL_INTERVAL,
+};
 
 #[derive(Clone)]
 /// The search service implementation.
@@ -85,6 +88,13 @@ pub trait SearchService: 'static + Send + Sync {
         &self,
         request: LeafSearchStreamRequest,
     ) -> crate::Result<UnboundedReceiverStream<crate::Result<LeafSearchStreamResponse>>>;
+
+    /// Performs a root search in tail mode, returning a stream of the hits
+    /// matching the query as new splits are published.
+    async fn root_search_tail(
+        &self,
+        request: SearchRequest,
+    ) -> crate::Result<Pin<Box<dyn futures::Stream<Item = crate::Result<Hit>> + Send>>>;
 }
 
 impl SearchServiceImpl {
@@ -102,6 +112,11 @@ impl SearchServiceImpl {
             client_pool,
         }
     }
+
+    /// Returns the metastore used by the search service.
+    pub fn metastore(&self) -> Arc<dyn Metastore> {
+        self.metastore.clone()
+    }
 }
 
 fn deserialize_doc_mapper(doc_mapper_str: &str) -> crate::Result<Arc<dyn DocMapper>> {
@@ -133,10 +148,17 @@ impl SearchService for SearchServiceImpl {
             .search_request
             .ok_or_else(|| SearchError::InternalError("No search request.".to_string()))?;
         info!(index=?search_request.index_id, splits=?leaf_search_request.split_offsets, "leaf_search");
-        let storage = self
-            .storage_uri_resolver
-            .resolve(&leaf_search_request.index_uri)?;
+        let storage = account_storage_costs(
+            self.storage_uri_resolver
+                .resolve(&leaf_search_request.index_uri)?,
+            &search_request.index_id,
+            StorageActivity::Search,
+        );
         let split_ids = leaf_search_request.split_offsets;
+        split_access_tracker().record_accesses(
+            &search_request.index_id,
+            split_ids.iter().map(|split| split.split_id.as_str()),
+        );
         let doc_mapper = deserialize_doc_mapper(&leaf_search_request.doc_mapper)?;
 
         let leaf_search_response =
@@ -149,9 +171,19 @@ impl SearchService for SearchServiceImpl {
         &self,
         fetch_docs_request: FetchDocsRequest,
     ) -> crate::Result<FetchDocsResponse> {
-        let storage = self
-            .storage_uri_resolver
-            .resolve(&fetch_docs_request.index_uri)?;
+        let storage = account_storage_costs(
+            self.storage_uri_resolver
+                .resolve(&fetch_docs_request.index_uri)?,
+            &fetch_docs_request.index_id,
+            StorageActivity::Search,
+        );
+        split_access_tracker().record_accesses(
+            &fetch_docs_request.index_id,
+            fetch_docs_request
+                .split_offsets
+                .iter()
+                .map(|split| split.split_id.as_str()),
+        );
 
         let fetch_docs_response = fetch_docs(
             fetch_docs_request.partial_hits,
@@ -169,7 +201,7 @@ impl SearchService for SearchServiceImpl {
     ) -> crate::Result<Pin<Box<dyn futures::Stream<Item = crate::Result<Bytes>> + Send>>> {
         let data = root_search_stream(
             stream_request,
-            self.metastore.as_ref(),
+            self.metastore.clone(),
             self.cluster_client.clone(),
             &self.client_pool,
         )
@@ -185,9 +217,19 @@ impl SearchService for SearchServiceImpl {
             .request
             .ok_or_else(|| SearchError::InternalError("No search request.".to_string()))?;
         info!(index=?stream_request.index_id, splits=?leaf_stream_request.split_offsets, "leaf_search");
-        let storage = self
-            .storage_uri_resolver
-            .resolve(&leaf_stream_request.index_uri)?;
+        let storage = account_storage_costs(
+            self.storage_uri_resolver
+                .resolve(&leaf_stream_request.index_uri)?,
+            &stream_request.index_id,
+            StorageActivity::Search,
+        );
+        split_access_tracker().record_accesses(
+            &stream_request.index_id,
+            leaf_stream_request
+                .split_offsets
+                .iter()
+                .map(|split| split.split_id.as_str()),
+        );
         let doc_mapper = deserialize_doc_mapper(&leaf_stream_request.doc_mapper)?;
         let leaf_receiver = leaf_search_stream(
             stream_request,
@@ -198,4 +240,18 @@ impl SearchService for SearchServiceImpl {
         .await;
         Ok(leaf_receiver)
     }
+
+    async fn root_search_tail(
+        &self,
+        search_request: SearchRequest,
+    ) -> crate::Result<Pin<Box<dyn futures::Stream<Item = crate::Result<Hit>> + Send>>> {
+        let hit_stream = root_search_tail(
+            search_request,
+            self.metastore.clone(),
+            self.cluster_client.clone(),
+            self.client_pool.clone(),
+            TAIL_POLL_INTERVAL,
+        )?;
+        Ok(Box::pin(hit_stream))
+    }
 }
@@ -0,0 +1,398 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Search snapshots pin the splits searched by a query when the query is planned, so that the
+//! query sees the same splits from start to end, even if splits are published, merged or
+//! garbage collected in the meantime.
+//!
+//! A snapshot is returned to the client as an opaque token holding the IDs of the pinned splits.
+//! Passing the token to the following searches, e.g. to fetch the next pages of hits, runs them
+//! on the same splits. The pinned splits are leased, so the garbage collector does not delete the
+//! splits replaced by a merge as long as the snapshot is used at least once every
+//! [`SPLIT_READ_LEASE_TTL`]. Once the lease expires and the splits are deleted, the token expires.
+
+use std::collections::{HashMap, HashSet};
+
+use quickwit_common::new_coolid;
+use quickwit_doc_mapper::range_pruning::extract_ranges_from_query;
+use quickwit_doc_mapper::tag_pruning::extract_tags_from_query;
+use quickwit_metastore::{Metastore, Split, SplitMetadata, SplitState};
+use quickwit_proto::SearchRequest;
+use serde::{Deserialize, Serialize};
+
+use crate::split_lease::SPLIT_READ_LEASE_TTL;
+use crate::{extract_time_range, SearchError};
+
+/// Content of a snapshot token.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct SnapshotToken {
+    /// ID of the snapshot, holding the read leases on the pinned splits.
+    snapshot_id: String,
+    index_id: String,
+    split_ids: Vec<String>,
+}
+
+impl SnapshotToken {
+    fn encode(&self) -> String {
+        let token_json =
+            serde_json::to_vec(self).expect("Snapshot tokens should always be serializable.");
+        base64::encode_config(token_json, base64::URL_SAFE_NO_PAD)
+    }
+
+    fn decode(snapshot: &str) -> crate::Result<SnapshotToken> {
+        base64::decode_config(snapshot, base64::URL_SAFE_NO_PAD)
+            .ok()
+            .and_then(|token_json| serde_json::from_slice(&token_json).ok())
+            .ok_or_else(|| SearchError::InvalidQuery("Invalid snapshot token.".to_string()))
+    }
+}
+
+/// Returns the snapshot token pinning the splits searched by a request that asked for one. The
+/// snapshot of the request is returned as is if it has one, since its leases were renewed when it
+/// was resolved. Otherwise, a new snapshot leasing the given splits is taken.
+pub(crate) async fn take_snapshot(
+    search_request: &SearchRequest,
+    split_metadatas: &[SplitMetadata],
+    metastore: &dyn Metastore,
+) -> crate::Result<String> {
+    if let Some(snapshot) = &search_request.snapshot {
+        return Ok(snapshot.clone());
+    }
+    let snapshot_token = SnapshotToken {
+        snapshot_id: new_coolid("snapshot"),
+        index_id: search_request.index_id.clone(),
+        split_ids: split_metadatas
+            .iter()
+            .map(|split_metadata| split_metadata.split_id.clone())
+            .collect(),
+    };
+    lease_pinned_splits(&snapshot_token, metastore).await?;
+    Ok(snapshot_token.encode())
+}
+
+/// Acquires or renews the read leases of a snapshot on its pinned splits.
+async fn lease_pinned_splits(
+    snapshot_token: &SnapshotToken,
+    metastore: &dyn Metastore,
+) -> crate::Result<()> {
+    let split_ids: Vec<&str> = snapshot_token
+        .split_ids
+        .iter()
+        .map(String::as_str)
+        .collect();
+    metastore
+        .acquire_split_read_leases(
+            &snapshot_token.index_id,
+            &split_ids,
+            &snapshot_token.snapshot_id,
+            SPLIT_READ_LEASE_TTL,
+        )
+        .await?;
+    Ok(())
+}
+
+/// Returns the splits pinned by a snapshot token, and renews the leases of the snapshot on them.
+/// The pinned splits replaced by a merge since the snapshot was taken are still returned as long
+/// as they have not been garbage collected.
+///
+/// Fails with [`SearchError::SnapshotExpired`] if some of the pinned splits were deleted.
+pub(crate) async fn resolve_snapshot(
+    index_id: &str,
+    snapshot: &str,
+    metastore: &dyn Metastore,
+) -> crate::Result<Vec<SplitMetadata>> {
+    let snapshot_token = SnapshotToken::decode(snapshot)?;
+    if snapshot_token.index_id != index_id {
+        return Err(SearchError::InvalidQuery(format!(
+            "The snapshot pins the splits of index `{}`, not of index `{}`.",
+            snapshot_token.index_id, index_id
+        )));
+    }
+    // The leases are renewed before the pinned splits are checked, so that they cannot be deleted
+    // in between.
+    lease_pinned_splits(&snapshot_token, metastore).await?;
+    let mut splits: HashMap<String, Split> = metastore
+        .list_all_splits(index_id)
+        .await?
+        .into_iter()
+        .map(|split| (split.split_id().to_string(), split))
+        .collect();
+    let mut pinned_split_metadatas = Vec::with_capacity(snapshot_token.split_ids.len());
+    let mut deleted_split_ids = Vec::new();
+    for split_id in snapshot_token.split_ids {
+        match splits.remove(&split_id) {
+            Some(split) if split.split_state != SplitState::Staged => {
+                pinned_split_metadatas.push(split.split_metadata)
+            }
+            _ => deleted_split_ids.push(split_id),
+        }
+    }
+    if !deleted_split_ids.is_empty() {
+        return Err(SearchError::SnapshotExpired {
+            split_ids: deleted_split_ids,
+        });
+    }
+    Ok(pinned_split_metadatas)
+}
+
+/// Keeps the splits relevant to a request, evaluating the time range, tags and range filters of
+/// the request like the metastore does when listing the published splits.
+pub(crate) fn filter_relevant_splits(
+    search_request: &SearchRequest,
+    split_metadatas: &[SplitMetadata],
+) -> crate::Result<Vec<SplitMetadata>> {
+    let time_range_opt =
+        extract_time_range(search_request.start_timestamp, search_request.end_timestamp);
+    if let Some(time_range) = &time_range_opt {
+        // No split intersects an empty time range.
+        if time_range.is_empty() {
+            return Ok(Vec::new());
+        }
+    }
+    let tags_filter_opt = extract_tags_from_query(&search_request.query)?;
+    let range_filter_opt = extract_ranges_from_query(&search_request.query)?;
+    let relevant_split_metadatas = split_metadatas
+        .iter()
+        .filter(
+            |split_metadata| match (&time_range_opt, &split_metadata.time_range) {
+                (Some(time_range), Some(split_time_range)) => {
+                    time_range.start <= *split_time_range.end()
+                        && *split_time_range.start() < time_range.end
+                }
+                _ => true,
+            },
+        )
+        .filter(|split_metadata| {
+            tags_filter_opt.as_ref().map_or(true, |tags_filter| {
+                tags_filter.evaluate(&split_metadata.tags)
+            })
+        })
+        .filter(|split_metadata| {
+            range_filter_opt.as_ref().map_or(true, |range_filter| {
+                range_filter.evaluate(&split_metadata.field_stats)
+            })
+        })
+        .cloned()
+        .collect();
+    Ok(relevant_split_metadatas)
+}
+
+/// Lists the splits searched by a request when it is planned: the relevant splits pinned by the
+/// snapshot of the request if it has one, the relevant published splits otherwise.
+pub(crate) async fn plan_splits(
+    search_request: &SearchRequest,
+    metastore: &dyn Metastore,
+) -> crate::Result<Vec<SplitMetadata>> {
+    match &search_request.snapshot {
+        Some(snapshot) => {
+            let pinned_split_metadatas =
+                resolve_snapshot(&search_request.index_id, snapshot, metastore).await?;
+            filter_relevant_splits(search_request, &pinned_split_metadatas)
+        }
+        None => crate::list_relevant_splits(search_request, metastore).await,
+    }
+}
+
+/// Pins all the splits that the queries run on behalf of a request may search, regardless of
+/// their relevance to the request: the splits pinned by the snapshot of the request if it has
+/// one, the published splits otherwise.
+pub(crate) async fn pin_all_splits(
+    search_request: &SearchRequest,
+    metastore: &dyn Metastore,
+) -> crate::Result<Vec<SplitMetadata>> {
+    if let Some(snapshot) = &search_request.snapshot {
+        return resolve_snapshot(&search_request.index_id, snapshot, metastore).await;
+    }
+    let split_metadatas = metastore
+        .list_splits(&search_request.index_id, SplitState::Published, None, None)
+        .await?
+        .into_iter()
+        .map(|split| split.split_metadata)
+        .collect();
+    Ok(split_metadatas)
+}
+
+/// Checks whether the splits that failed to be searched were deleted while the search was
+/// running, and returns [`SearchError::SnapshotExpired`] if so, since the deletion is the likely
+/// cause of the failure.
+pub(crate) async fn check_failed_splits_not_deleted(
+    index_id: &str,
+    failed_split_ids: &[String],
+    metastore: &dyn Metastore,
+) -> crate::Result<()> {
+    let published_split_ids: HashSet<String> = metastore
+        .list_all_splits(index_id)
+        .await?
+        .into_iter()
+        .filter(|split| split.split_state == SplitState::Published)
+        .map(|split| split.split_id().to_string())
+        .collect();
+    let deleted_split_ids: Vec<String> = failed_split_ids
+        .iter()
+        .filter(|split_id| !published_split_ids.contains(*split_id))
+        .cloned()
+        .collect();
+    if !deleted_split_ids.is_empty() {
+        return Err(SearchError::SnapshotExpired {
+            split_ids: deleted_split_ids,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_metastore::MockMetastore;
+
+    use super::*;
+
+    fn mock_split(split_id: &str, split_state: SplitState, time_range: (i64, i64)) -> Split {
+        Split {
+            split_state,
+            update_timestamp: 0,
+            split_metadata: SplitMetadata {
+                split_id: split_id.to_string(),
+                time_range: Some(time_range.0..=time_range.1),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_snapshot() {
+        let mut metastore = MockMetastore::new();
+        metastore.expect_list_all_splits().returning(|_index_id| {
+            Ok(vec![
+                mock_split("split-1", SplitState::MarkedForDeletion, (0, 10)),
+                mock_split("split-2", SplitState::Published, (10, 20)),
+                mock_split("split-3", SplitState::Published, (0, 20)),
+            ])
+        });
+        metastore.expect_acquire_split_read_leases().returning(
+            |index_id, _split_ids, holder_id, ttl| {
+                assert_eq!(index_id, "test-index");
+                assert!(holder_id.starts_with("snapshot-"));
+                assert_eq!(ttl, SPLIT_READ_LEASE_TTL);
+                Ok(())
+            },
+        );
+        let pinned_splits = [
+            mock_split("split-1", SplitState::Published, (0, 10)).split_metadata,
+            mock_split("split-2", SplitState::Published, (10, 20)).split_metadata,
+        ];
+        let pin_request = SearchRequest {
+            index_id: "test-index".to_string(),
+            query: "*".to_string(),
+            pin_splits: true,
+            ..Default::default()
+        };
+        let snapshot = take_snapshot(&pin_request, &pinned_splits, &metastore)
+            .await
+            .unwrap();
+
+        let split_metadatas = resolve_snapshot("test-index", &snapshot, &metastore)
+            .await
+            .unwrap();
+        let split_ids: Vec<&str> = split_metadatas
+            .iter()
+            .map(|split_metadata| split_metadata.split_id())
+            .collect();
+        assert_eq!(split_ids, ["split-1", "split-2"]);
+
+        let search_request = SearchRequest {
+            index_id: "test-index".to_string(),
+            query: "*".to_string(),
+            start_timestamp: Some(15),
+            snapshot: Some(snapshot.clone()),
+            ..Default::default()
+        };
+        let split_metadatas = plan_splits(&search_request, &metastore).await.unwrap();
+        assert_eq!(split_metadatas.len(), 1);
+        assert_eq!(split_metadatas[0].split_id(), "split-2");
+        // Searches run on a snapshot keep it.
+        let kept_snapshot = take_snapshot(&search_request, &split_metadatas, &metastore)
+            .await
+            .unwrap();
+        assert_eq!(kept_snapshot, snapshot);
+
+        let error = resolve_snapshot("other-index", &snapshot, &metastore)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, SearchError::InvalidQuery(_)));
+
+        let error = resolve_snapshot("test-index", "not-a-snapshot", &metastore)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, SearchError::InvalidQuery(_)));
+
+        // Tokens must hold the ID of their snapshot.
+        let token_without_id = base64::encode_config(
+            r#"{"indexId":"test-index","splitIds":["split-1"]}"#,
+            base64::URL_SAFE_NO_PAD,
+        );
+        let error = resolve_snapshot("test-index", &token_without_id, &metastore)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, SearchError::InvalidQuery(_)));
+
+        let expired_snapshot = take_snapshot(
+            &pin_request,
+            &[mock_split("split-0", SplitState::Published, (0, 10)).split_metadata],
+            &metastore,
+        )
+        .await
+        .unwrap();
+        let error = resolve_snapshot("test-index", &expired_snapshot, &metastore)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error, SearchError::SnapshotExpired { split_ids } if split_ids == ["split-0"])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_failed_splits_not_deleted() {
+        let mut metastore = MockMetastore::new();
+        metastore.expect_list_all_splits().returning(|_index_id| {
+            Ok(vec![
+                mock_split("split-1", SplitState::MarkedForDeletion, (0, 10)),
+                mock_split("split-2", SplitState::Published, (10, 20)),
+            ])
+        });
+        check_failed_splits_not_deleted("test-index", &["split-2".to_string()], &metastore)
+            .await
+            .unwrap();
+        let error = check_failed_splits_not_deleted(
+            "test-index",
+            &[
+                "split-0".to_string(),
+                "split-1".to_string(),
+                "split-2".to_string(),
+            ],
+            &metastore,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            SearchError::SnapshotExpired { split_ids } if split_ids == ["split-0", "split-1"]
+        ));
+    }
+}
@@ -0,0 +1,241 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use prometheus::IntCounter;
+use quickwit_common::metrics::new_counter;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+use tracing::debug;
+
+/// Requests on fewer splits are never searched speculatively: their median duration is not
+/// meaningful.
+const MIN_NUM_SPLITS: usize = 4;
+
+/// At most one split out of `MAX_SPECULATIVE_SEARCH_RATIO` is searched again per request, so that
+/// a slow storage does not double the load of the searcher.
+const MAX_SPECULATIVE_SEARCH_RATIO: usize = 10;
+
+static SPECULATIVE_SPLIT_SEARCHES: Lazy<IntCounter> = Lazy::new(|| {
+    new_counter(
+        "quickwit_search_speculative_split_searches_total",
+        "Number of splits searched again because their search lagged behind the other splits of \
+         the request.",
+    )
+});
+
+static SPECULATIVE_SPLIT_SEARCH_WINS: Lazy<IntCounter> = Lazy::new(|| {
+    new_counter(
+        "quickwit_search_speculative_split_search_wins_total",
+        "Number of speculative split searches that finished before the original search.",
+    )
+});
+
+/// Tracks the split searches of a leaf search request and searches again the splits lagging far
+/// behind the others.
+pub(crate) struct SpeculativeSplitSearch {
+    num_splits: usize,
+    factor: f64,
+    max_num_speculative_searches: usize,
+    num_speculative_searches: AtomicUsize,
+    completed_durations: Mutex<Vec<Duration>>,
+    completion_notify: Notify,
+}
+
+impl SpeculativeSplitSearch {
+    /// Returns `None` if speculative searches are disabled or not worth it for the request.
+    pub fn new(num_splits: usize, factor: f64) -> Option<SpeculativeSplitSearch> {
+        if factor < 1.0 || num_splits < MIN_NUM_SPLITS {
+            return None;
+        }
+        Some(SpeculativeSplitSearch {
+            num_splits,
+            factor,
+            max_num_speculative_searches: (num_splits / MAX_SPECULATIVE_SEARCH_RATIO).max(1),
+            num_speculative_searches: AtomicUsize::new(0),
+            completed_durations: Mutex::new(Vec::with_capacity(num_splits)),
+            completion_notify: Notify::new(),
+        })
+    }
+
+    fn record_completion(&self, duration: Duration) {
+        self.completed_durations
+            .lock()
+            .expect("Lock should not be poisoned.")
+            .push(duration);
+        self.completion_notify.notify_waiters();
+    }
+
+    /// Returns the duration after which a split search is considered lagging, once more than half
+    /// of the splits are searched.
+    ///
+    /// The splits still being searched take longer than the completed ones, so the median of the
+    /// request is known as soon as more than half of the splits are searched.
+    fn straggler_threshold(&self) -> Option<Duration> {
+        let mut completed_durations = self
+            .completed_durations
+            .lock()
+            .expect("Lock should not be poisoned.");
+        let median_rank = self.num_splits / 2;
+        if completed_durations.len() <= median_rank {
+            return None;
+        }
+        let (_, median_duration, _) = completed_durations.select_nth_unstable(median_rank);
+        Some(median_duration.mul_f64(self.factor))
+    }
+
+    fn try_acquire_speculative_search(&self) -> bool {
+        self.num_speculative_searches
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |num_searches| {
+                if num_searches < self.max_num_speculative_searches {
+                    Some(num_searches + 1)
+                } else {
+                    None
+                }
+            })
+            .is_ok()
+    }
+
+    /// Runs `search_split`, and runs it again concurrently if it lags far behind the other splits
+    /// of the request. Returns the result of the attempt that finishes first.
+    pub async fn search<F, Fut, T>(&self, split_id: &str, search_split: F) -> T
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let start = Instant::now();
+        let first_attempt = search_split();
+        tokio::pin!(first_attempt);
+        loop {
+            // Registered before computing the threshold so that no completion is missed.
+            let completion_notified = self.completion_notify.notified();
+            let deadline_opt = self
+                .straggler_threshold()
+                .map(|threshold| start + threshold);
+            tokio::select! {
+                result = &mut first_attempt => {
+                    self.record_completion(start.elapsed());
+                    return result;
+                }
+                _ = completion_notified => {}
+                _ = sleep_until_opt(deadline_opt) => {
+                    if self.try_acquire_speculative_search() {
+                        break;
+                    }
+                    let result = first_attempt.await;
+                    self.record_completion(start.elapsed());
+                    return result;
+                }
+            }
+        }
+        debug!(split_id = %split_id, elapsed = ?start.elapsed(), "Searching lagging split again.");
+        SPECULATIVE_SPLIT_SEARCHES.inc();
+        let speculative_attempt = search_split();
+        tokio::pin!(speculative_attempt);
+        tokio::select! {
+            result = &mut first_attempt => result,
+            result = &mut speculative_attempt => {
+                SPECULATIVE_SPLIT_SEARCH_WINS.inc();
+                result
+            }
+        }
+    }
+}
+
+async fn sleep_until_opt(deadline_opt: Option<Instant>) {
+    match deadline_opt {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => futures::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn test_speculative_split_search_disabled() {
+        assert!(SpeculativeSplitSearch::new(10, 0.0).is_none());
+        assert!(SpeculativeSplitSearch::new(MIN_NUM_SPLITS - 1, 3.0).is_none());
+        assert!(SpeculativeSplitSearch::new(MIN_NUM_SPLITS, 3.0).is_some());
+    }
+
+    #[test]
+    fn test_speculative_split_search_straggler_threshold() {
+        let speculative_search = SpeculativeSplitSearch::new(5, 3.0).unwrap();
+        speculative_search.record_completion(Duration::from_millis(30));
+        speculative_search.record_completion(Duration::from_millis(10));
+        assert!(speculative_search.straggler_threshold().is_none());
+        speculative_search.record_completion(Duration::from_millis(20));
+        assert_eq!(
+            speculative_search.straggler_threshold(),
+            Some(Duration::from_millis(90))
+        );
+    }
+
+    #[test]
+    fn test_speculative_split_search_max_num_speculative_searches() {
+        let speculative_search = SpeculativeSplitSearch::new(25, 3.0).unwrap();
+        assert!(speculative_search.try_acquire_speculative_search());
+        assert!(speculative_search.try_acquire_speculative_search());
+        assert!(!speculative_search.try_acquire_speculative_search());
+    }
+
+    #[tokio::test]
+    async fn test_speculative_split_search_searches_lagging_split_again() {
+        let speculative_search = SpeculativeSplitSearch::new(4, 2.0).unwrap();
+        let num_attempts = Arc::new(AtomicUsize::new(0));
+        let search_futures = (0..4).map(|split_ord| {
+            let speculative_search = &speculative_search;
+            let num_attempts = num_attempts.clone();
+            async move {
+                speculative_search
+                    .search(&format!("split-{}", split_ord), || {
+                        let attempt_ord = num_attempts.fetch_add(1, Ordering::SeqCst);
+                        async move {
+                            // The first attempt on the last split hangs.
+                            let delay = if split_ord == 3 && attempt_ord < 4 {
+                                Duration::from_secs(3_600)
+                            } else {
+                                Duration::from_millis(10)
+                            };
+                            tokio::time::sleep(delay).await;
+                            split_ord
+                        }
+                    })
+                    .await
+            }
+        });
+        let results = tokio::time::timeout(
+            Duration::from_secs(5),
+            futures::future::join_all(search_futures),
+        )
+        .await
+        .unwrap();
+        assert_eq!(results, vec![0, 1, 2, 3]);
+        assert_eq!(num_attempts.load(Ordering::SeqCst), 5);
+    }
+}
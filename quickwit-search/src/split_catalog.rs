@@ -0,0 +1,1152 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use quickwit_config::{IndexingSettings, SourceConfig};
+use quickwit_doc_mapper::tag_pruning::TagFilterAst;
+use quickwit_metastore::checkpoint::{
+    CheckpointDelta, IndexCheckpointDelta, PartitionId, SourceCheckpoint,
+};
+use quickwit_metastore::{
+    IndexLifecycleState, IndexMetadata, Job, Lease, LeaseFence, Metastore, MetastoreError,
+    MetastoreResult, Split, SplitMetadata, SplitPageStream, SplitState,
+};
+use tokio::time::Instant;
+use tracing::{debug, error};
+
+/// Number of splits fetched per metastore query when loading the catalog of an index.
+const CATALOG_PAGE_SIZE: usize = 1_000;
+
+/// Indexes that have not been searched for this long are dropped from the catalog.
+const CATALOG_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Catalogs are reloaded from scratch at this period, to drop the splits deleted from the
+/// metastore without having been seen in another state by the incremental refreshes.
+const CATALOG_FULL_RELOAD_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// The incremental refreshes list the splits updated since the latest update timestamp seen
+/// minus this margin: update timestamps have a one second resolution, and a split may become
+/// visible after splits updated later.
+const CATALOG_REFRESH_OVERLAP_SECS: i64 = 5;
+
+/// Metastore keeping an in-memory catalog of the published splits of the indexes it is asked
+/// about, so that listing the splits relevant to a query is a lookup in the catalog rather
+/// than a metastore query and a scan of the split metadata.
+///
+/// The catalog of an index is loaded the first time its published splits are listed, then
+/// refreshed in the background every `refresh_interval` from the splits updated since the
+/// previous refresh, and reloaded from scratch every [`CATALOG_FULL_RELOAD_INTERVAL`]. Newly
+/// published splits may therefore take up to `refresh_interval` to be searchable. The other
+/// calls are forwarded to the underlying metastore, and the calls changing the published splits
+/// of an index through this metastore invalidate its catalog.
+pub struct SplitCatalogMetastore {
+    metastore: Arc<dyn Metastore>,
+    catalogs: RwLock<Catalogs>,
+}
+
+#[derive(Default)]
+struct Catalogs {
+    catalogs: HashMap<String, Arc<IndexSplitCatalog>>,
+    /// Incremented every time the catalog of an index is invalidated, so that a catalog loaded
+    /// concurrently with an invalidation is discarded instead of being inserted.
+    generations: HashMap<String, u64>,
+}
+
+impl SplitCatalogMetastore {
+    /// Creates a split catalog metastore and spawns the task refreshing its catalogs every
+    /// `refresh_interval`. The task stops when the metastore is dropped.
+    pub fn new(metastore: Arc<dyn Metastore>, refresh_interval: Duration) -> Arc<Self> {
+        let split_catalog_metastore = Arc::new(SplitCatalogMetastore {
+            metastore,
+            catalogs: RwLock::new(Catalogs::default()),
+        });
+        tokio::spawn(refresh_catalogs_loop(
+            Arc::downgrade(&split_catalog_metastore),
+            refresh_interval,
+        ));
+        split_catalog_metastore
+    }
+
+    fn catalog(&self, index_id: &str) -> Option<Arc<IndexSplitCatalog>> {
+        self.catalogs
+            .read()
+            .expect("Split catalog lock should not be poisoned.")
+            .catalogs
+            .get(index_id)
+            .cloned()
+    }
+
+    fn index_ids(&self) -> Vec<String> {
+        self.catalogs
+            .read()
+            .expect("Split catalog lock should not be poisoned.")
+            .catalogs
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the generation of the catalog of an index, to be passed to
+    /// [`Self::insert_catalog`] once the catalog is loaded.
+    fn generation(&self, index_id: &str) -> u64 {
+        self.catalogs
+            .read()
+            .expect("Split catalog lock should not be poisoned.")
+            .generations
+            .get(index_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Inserts the catalog of an index, unless the catalog was invalidated since `generation`
+    /// was read. Returns true if the catalog was inserted.
+    fn insert_catalog(
+        &self,
+        index_id: &str,
+        catalog: Arc<IndexSplitCatalog>,
+        generation: u64,
+    ) -> bool {
+        let mut catalogs = self
+            .catalogs
+            .write()
+            .expect("Split catalog lock should not be poisoned.");
+        let current_generation = catalogs
+            .generations
+            .get(index_id)
+            .copied()
+            .unwrap_or_default();
+        if current_generation != generation {
+            return false;
+        }
+        catalogs.catalogs.insert(index_id.to_string(), catalog);
+        true
+    }
+
+    fn invalidate(&self, index_id: &str) {
+        let mut catalogs = self
+            .catalogs
+            .write()
+            .expect("Split catalog lock should not be poisoned.");
+        *catalogs
+            .generations
+            .entry(index_id.to_string())
+            .or_default() += 1;
+        catalogs.catalogs.remove(index_id);
+    }
+
+    async fn load_catalog(&self, index_id: &str) -> MetastoreResult<Vec<Split>> {
+        self.metastore
+            .list_splits_stream(
+                index_id,
+                SplitState::Published,
+                None,
+                None,
+                CATALOG_PAGE_SIZE,
+            )
+            .await?
+            .try_concat()
+            .await
+    }
+
+    /// Applies the splits updated since the previous refresh to the catalog of an index, or
+    /// reloads it from scratch if it was loaded more than [`CATALOG_FULL_RELOAD_INTERVAL`] ago.
+    /// The catalog is reused in place if the published splits did not change.
+    async fn refresh_catalog(&self, index_id: &str, catalog: &IndexSplitCatalog) {
+        let generation = self.generation(index_id);
+        let refreshed_catalog_res = if catalog.loaded_at.elapsed() > CATALOG_FULL_RELOAD_INTERVAL {
+            self.load_catalog(index_id)
+                .await
+                .map(|splits| Some(IndexSplitCatalog::new(splits)))
+        } else {
+            let update_timestamp =
+                catalog.update_timestamp_watermark() - CATALOG_REFRESH_OVERLAP_SECS;
+            self.metastore
+                .list_splits_updated_since(index_id, update_timestamp)
+                .await
+                .map(|updated_splits| catalog.apply_updated_splits(updated_splits))
+        };
+        match refreshed_catalog_res {
+            Ok(Some(refreshed_catalog)) => {
+                debug!(index_id = %index_id, num_splits = refreshed_catalog.splits.len(), "Split catalog refreshed.");
+                refreshed_catalog.touch_at(catalog.last_accessed_at());
+                self.insert_catalog(index_id, Arc::new(refreshed_catalog), generation);
+            }
+            Ok(None) => {}
+            Err(MetastoreError::IndexDoesNotExist { .. }) => self.invalidate(index_id),
+            Err(error) => {
+                error!(index_id = %index_id, error = ?error, "Failed to refresh split catalog.")
+            }
+        }
+    }
+}
+
+async fn refresh_catalogs_loop(
+    split_catalog_metastore_weak: Weak<SplitCatalogMetastore>,
+    refresh_interval: Duration,
+) {
+    let mut interval = tokio::time::interval(refresh_interval);
+    // The first tick completes immediately.
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let split_catalog_metastore = match split_catalog_metastore_weak.upgrade() {
+            Some(split_catalog_metastore) => split_catalog_metastore,
+            None => return,
+        };
+        for index_id in split_catalog_metastore.index_ids() {
+            let catalog = match split_catalog_metastore.catalog(&index_id) {
+                Some(catalog) => catalog,
+                None => continue,
+            };
+            if catalog.last_accessed_at().elapsed() > CATALOG_IDLE_TIMEOUT {
+                debug!(index_id = %index_id, "Split catalog dropped.");
+                split_catalog_metastore.invalidate(&index_id);
+                continue;
+            }
+            split_catalog_metastore
+                .refresh_catalog(&index_id, &catalog)
+                .await;
+        }
+    }
+}
+
+#[async_trait]
+impl Metastore for SplitCatalogMetastore {
+    async fn check_connectivity(&self) -> anyhow::Result<()> {
+        self.metastore.check_connectivity().await
+    }
+
+    async fn create_index(&self, index_metadata: IndexMetadata) -> MetastoreResult<()> {
+        self.metastore.create_index(index_metadata).await
+    }
+
+    async fn index_metadata(&self, index_id: &str) -> MetastoreResult<IndexMetadata> {
+        self.metastore.index_metadata(index_id).await
+    }
+
+    async fn list_indexes_metadatas(&self) -> MetastoreResult<Vec<IndexMetadata>> {
+        self.metastore.list_indexes_metadatas().await
+    }
+
+    async fn delete_index(&self, index_id: &str) -> MetastoreResult<()> {
+        let delete_res = self.metastore.delete_index(index_id).await;
+        self.invalidate(index_id);
+        delete_res
+    }
+
+    async fn create_indexes(&self, index_metadatas: Vec<IndexMetadata>) -> MetastoreResult<()> {
+        self.metastore.create_indexes(index_metadatas).await
+    }
+
+    async fn delete_indexes<'a>(&self, index_ids: &[&'a str]) -> MetastoreResult<()> {
+        let delete_res = self.metastore.delete_indexes(index_ids).await;
+        for index_id in index_ids {
+            self.invalidate(index_id);
+        }
+        delete_res
+    }
+
+    async fn stage_split(
+        &self,
+        index_id: &str,
+        split_metadata: SplitMetadata,
+    ) -> MetastoreResult<()> {
+        self.metastore.stage_split(index_id, split_metadata).await
+    }
+
+    async fn publish_splits<'a>(
+        &self,
+        index_id: &str,
+        source_id: &str,
+        split_ids: &[&'a str],
+        checkpoint_delta: CheckpointDelta,
+    ) -> MetastoreResult<()> {
+        let publish_res = self
+            .metastore
+            .publish_splits(index_id, source_id, split_ids, checkpoint_delta)
+            .await;
+        self.invalidate(index_id);
+        publish_res
+    }
+
+    async fn publish_multi_source_splits<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+        index_checkpoint_delta: IndexCheckpointDelta,
+    ) -> MetastoreResult<()> {
+        let publish_res = self
+            .metastore
+            .publish_multi_source_splits(index_id, split_ids, index_checkpoint_delta)
+            .await;
+        self.invalidate(index_id);
+        publish_res
+    }
+
+    async fn replace_splits<'a>(
+        &self,
+        index_id: &str,
+        new_split_ids: &[&'a str],
+        replaced_split_ids: &[&'a str],
+    ) -> MetastoreResult<()> {
+        let replace_res = self
+            .metastore
+            .replace_splits(index_id, new_split_ids, replaced_split_ids)
+            .await;
+        self.invalidate(index_id);
+        replace_res
+    }
+
+    async fn list_splits(
+        &self,
+        index_id: &str,
+        split_state: SplitState,
+        time_range: Option<Range<i64>>,
+        tags: Option<TagFilterAst>,
+    ) -> MetastoreResult<Vec<Split>> {
+        if split_state != SplitState::Published {
+            return self
+                .metastore
+                .list_splits(index_id, split_state, time_range, tags)
+                .await;
+        }
+        let catalog = match self.catalog(index_id) {
+            Some(catalog) => catalog,
+            None => {
+                let generation = self.generation(index_id);
+                let splits = self.load_catalog(index_id).await?;
+                let catalog = Arc::new(IndexSplitCatalog::new(splits));
+                self.insert_catalog(index_id, catalog.clone(), generation);
+                catalog
+            }
+        };
+        catalog.touch_at(Instant::now());
+        Ok(catalog.find_splits(time_range.as_ref(), tags.as_ref()))
+    }
+
+    async fn list_splits_page(
+        &self,
+        index_id: &str,
+        split_state: SplitState,
+        time_range: Option<Range<i64>>,
+        tags: Option<TagFilterAst>,
+        after_split_id_opt: Option<String>,
+        page_size: usize,
+    ) -> MetastoreResult<Vec<Split>> {
+        self.metastore
+            .list_splits_page(
+                index_id,
+                split_state,
+                time_range,
+                tags,
+                after_split_id_opt,
+                page_size,
+            )
+            .await
+    }
+
+    async fn list_splits_stream(
+        &self,
+        index_id: &str,
+        split_state: SplitState,
+        time_range: Option<Range<i64>>,
+        tags: Option<TagFilterAst>,
+        page_size: usize,
+    ) -> MetastoreResult<SplitPageStream> {
+        self.metastore
+            .list_splits_stream(index_id, split_state, time_range, tags, page_size)
+            .await
+    }
+
+    async fn list_all_splits(&self, index_id: &str) -> MetastoreResult<Vec<Split>> {
+        self.metastore.list_all_splits(index_id).await
+    }
+
+    async fn list_splits_updated_since(
+        &self,
+        index_id: &str,
+        update_timestamp: i64,
+    ) -> MetastoreResult<Vec<Split>> {
+        self.metastore
+            .list_splits_updated_since(index_id, update_timestamp)
+            .await
+    }
+
+    async fn mark_splits_for_deletion<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+    ) -> MetastoreResult<()> {
+        let mark_res = self
+            .metastore
+            .mark_splits_for_deletion(index_id, split_ids)
+            .await;
+        self.invalidate(index_id);
+        mark_res
+    }
+
+    async fn delete_splits<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+    ) -> MetastoreResult<()> {
+        self.metastore.delete_splits(index_id, split_ids).await
+    }
+
+    async fn mark_splits_for_deletion_fenced<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+        fence: &LeaseFence,
+    ) -> MetastoreResult<()> {
+        let mark_res = self
+            .metastore
+            .mark_splits_for_deletion_fenced(index_id, split_ids, fence)
+            .await;
+        self.invalidate(index_id);
+        mark_res
+    }
+
+    async fn delete_splits_fenced<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+        fence: &LeaseFence,
+    ) -> MetastoreResult<()> {
+        self.metastore
+            .delete_splits_fenced(index_id, split_ids, fence)
+            .await
+    }
+
+    async fn add_source(&self, index_id: &str, source: SourceConfig) -> MetastoreResult<()> {
+        self.metastore.add_source(index_id, source).await
+    }
+
+    async fn delete_source(&self, index_id: &str, source_id: &str) -> MetastoreResult<()> {
+        self.metastore.delete_source(index_id, source_id).await
+    }
+
+    async fn reset_source_checkpoint(
+        &self,
+        index_id: &str,
+        source_id: &str,
+        checkpoint: SourceCheckpoint,
+    ) -> MetastoreResult<()> {
+        self.metastore
+            .reset_source_checkpoint(index_id, source_id, checkpoint)
+            .await
+    }
+
+    async fn prune_source_checkpoint(
+        &self,
+        index_id: &str,
+        source_id: &str,
+        partition_ids: &[PartitionId],
+    ) -> MetastoreResult<()> {
+        self.metastore
+            .prune_source_checkpoint(index_id, source_id, partition_ids)
+            .await
+    }
+
+    async fn update_indexing_settings(
+        &self,
+        index_id: &str,
+        indexing_settings: IndexingSettings,
+    ) -> MetastoreResult<()> {
+        self.metastore
+            .update_indexing_settings(index_id, indexing_settings)
+            .await
+    }
+
+    async fn update_lifecycle_state(
+        &self,
+        index_id: &str,
+        lifecycle_state: IndexLifecycleState,
+    ) -> MetastoreResult<()> {
+        self.metastore
+            .update_lifecycle_state(index_id, lifecycle_state)
+            .await
+    }
+
+    async fn acquire_lease(
+        &self,
+        lease_id: &str,
+        holder_id: &str,
+        ttl: Duration,
+    ) -> MetastoreResult<Lease> {
+        self.metastore.acquire_lease(lease_id, holder_id, ttl).await
+    }
+
+    async fn release_lease(&self, lease_id: &str, holder_id: &str) -> MetastoreResult<()> {
+        self.metastore.release_lease(lease_id, holder_id).await
+    }
+
+    async fn check_lease(&self, lease_id: &str, fencing_token: u64) -> MetastoreResult<()> {
+        self.metastore.check_lease(lease_id, fencing_token).await
+    }
+
+    async fn acquire_split_read_leases<'a>(
+        &self,
+        index_id: &str,
+        split_ids: &[&'a str],
+        holder_id: &str,
+        ttl: Duration,
+    ) -> MetastoreResult<()> {
+        self.metastore
+            .acquire_split_read_leases(index_id, split_ids, holder_id, ttl)
+            .await
+    }
+
+    async fn release_split_read_leases(
+        &self,
+        index_id: &str,
+        holder_id: &str,
+    ) -> MetastoreResult<()> {
+        self.metastore
+            .release_split_read_leases(index_id, holder_id)
+            .await
+    }
+
+    async fn list_leased_splits(&self, index_id: &str) -> MetastoreResult<HashSet<String>> {
+        self.metastore.list_leased_splits(index_id).await
+    }
+
+    async fn create_job(&self, job: Job) -> MetastoreResult<()> {
+        self.metastore.create_job(job).await
+    }
+
+    async fn update_job(&self, job: Job) -> MetastoreResult<()> {
+        self.metastore.update_job(job).await
+    }
+
+    async fn job(&self, job_id: &str) -> MetastoreResult<Job> {
+        self.metastore.job(job_id).await
+    }
+
+    async fn list_jobs<'a>(&self, index_id_opt: Option<&'a str>) -> MetastoreResult<Vec<Job>> {
+        self.metastore.list_jobs(index_id_opt).await
+    }
+
+    fn uri(&self) -> String {
+        self.metastore.uri()
+    }
+}
+
+/// Sorted list of split ordinals.
+type Postings = Vec<u32>;
+
+/// Published splits of an index, indexed by time range and by tag.
+struct IndexSplitCatalog {
+    splits: Vec<Split>,
+    time_range_index: TimeRangeIndex,
+    /// Splits without time range, which match any time range.
+    untimed_split_ords: Postings,
+    tag_index: HashMap<String, Postings>,
+    /// Greatest update timestamp of the splits seen by the catalog, published or not.
+    update_timestamp_watermark: AtomicI64,
+    loaded_at: Instant,
+    last_accessed_at: RwLock<Instant>,
+}
+
+impl IndexSplitCatalog {
+    fn new(mut splits: Vec<Split>) -> Self {
+        splits.sort_unstable_by(|left, right| left.split_id().cmp(right.split_id()));
+        let mut time_ranges = Vec::new();
+        let mut untimed_split_ords = Vec::new();
+        let mut tag_index: HashMap<String, Postings> = HashMap::new();
+        for (split_ord, split) in splits.iter().enumerate() {
+            let split_ord = split_ord as u32;
+            match &split.split_metadata.time_range {
+                Some(time_range) => {
+                    time_ranges.push((*time_range.start(), *time_range.end(), split_ord))
+                }
+                None => untimed_split_ords.push(split_ord),
+            }
+            for tag in &split.split_metadata.tags {
+                tag_index.entry(tag.clone()).or_default().push(split_ord);
+            }
+        }
+        let update_timestamp_watermark = splits
+            .iter()
+            .map(|split| split.update_timestamp)
+            .max()
+            .unwrap_or_default();
+        IndexSplitCatalog {
+            splits,
+            time_range_index: TimeRangeIndex::new(time_ranges),
+            untimed_split_ords,
+            tag_index,
+            update_timestamp_watermark: AtomicI64::new(update_timestamp_watermark),
+            loaded_at: Instant::now(),
+            last_accessed_at: RwLock::new(Instant::now()),
+        }
+    }
+
+    fn update_timestamp_watermark(&self) -> i64 {
+        self.update_timestamp_watermark.load(Ordering::Relaxed)
+    }
+
+    fn last_accessed_at(&self) -> Instant {
+        *self
+            .last_accessed_at
+            .read()
+            .expect("Split catalog lock should not be poisoned.")
+    }
+
+    fn touch_at(&self, instant: Instant) {
+        *self
+            .last_accessed_at
+            .write()
+            .expect("Split catalog lock should not be poisoned.") = instant;
+    }
+
+    fn get_split(&self, split_id: &str) -> Option<&Split> {
+        self.splits
+            .binary_search_by(|split| split.split_id().cmp(split_id))
+            .ok()
+            .map(|split_ord| &self.splits[split_ord])
+    }
+
+    /// Applies splits updated since the catalog was built: the published splits are inserted or
+    /// replaced, and the splits in any other state are removed. Returns `None` if the published
+    /// splits did not change, in which case only the update timestamp watermark is advanced.
+    fn apply_updated_splits(&self, updated_splits: Vec<Split>) -> Option<IndexSplitCatalog> {
+        let update_timestamp_watermark = updated_splits
+            .iter()
+            .map(|split| split.update_timestamp)
+            .max()
+            .unwrap_or_default();
+        self.update_timestamp_watermark
+            .fetch_max(update_timestamp_watermark, Ordering::Relaxed);
+
+        let has_changes = updated_splits.iter().any(|updated_split| {
+            let split_opt = self.get_split(updated_split.split_id());
+            if updated_split.split_state == SplitState::Published {
+                split_opt.map(|split| split.update_timestamp)
+                    != Some(updated_split.update_timestamp)
+            } else {
+                split_opt.is_some()
+            }
+        });
+        if !has_changes {
+            return None;
+        }
+        let mut splits: HashMap<String, Split> = self
+            .splits
+            .iter()
+            .map(|split| (split.split_id().to_string(), split.clone()))
+            .collect();
+        for updated_split in updated_splits {
+            if updated_split.split_state == SplitState::Published {
+                splits.insert(updated_split.split_id().to_string(), updated_split);
+            } else {
+                splits.remove(updated_split.split_id());
+            }
+        }
+        let mut catalog =
+            IndexSplitCatalog::new(splits.into_iter().map(|(_, split)| split).collect());
+        catalog
+            .update_timestamp_watermark
+            .fetch_max(self.update_timestamp_watermark(), Ordering::Relaxed);
+        catalog.loaded_at = self.loaded_at;
+        Some(catalog)
+    }
+
+    /// Returns the splits intersecting `time_range_opt` and matching `tags_opt`, with the
+    /// semantics of [`Metastore::list_splits`].
+    fn find_splits(
+        &self,
+        time_range_opt: Option<&Range<i64>>,
+        tags_opt: Option<&TagFilterAst>,
+    ) -> Vec<Split> {
+        let mut split_ords = match time_range_opt {
+            Some(time_range) => {
+                let mut split_ords = self.time_range_index.find(time_range);
+                split_ords.sort_unstable();
+                union(&split_ords, &self.untimed_split_ords)
+            }
+            None => (0..self.splits.len() as u32).collect(),
+        };
+        if let Some(tags) = tags_opt {
+            split_ords = intersection(&split_ords, &self.eval_tags(tags));
+        }
+        split_ords
+            .into_iter()
+            .map(|split_ord| self.splits[split_ord as usize].clone())
+            .collect()
+    }
+
+    fn eval_tags(&self, tags: &TagFilterAst) -> Postings {
+        match tags {
+            TagFilterAst::And(children) => {
+                let mut postings: Postings = (0..self.splits.len() as u32).collect();
+                for child in children {
+                    postings = intersection(&postings, &self.eval_tags(child));
+                }
+                postings
+            }
+            TagFilterAst::Or(children) => {
+                let mut postings = Postings::new();
+                for child in children {
+                    postings = union(&postings, &self.eval_tags(child));
+                }
+                postings
+            }
+            TagFilterAst::Tag { is_present, tag } => {
+                let tag_postings = self.tag_index.get(tag).cloned().unwrap_or_default();
+                if *is_present {
+                    tag_postings
+                } else {
+                    let all_postings: Postings = (0..self.splits.len() as u32).collect();
+                    difference(&all_postings, &tag_postings)
+                }
+            }
+        }
+    }
+}
+
+fn intersection(left: &[u32], right: &[u32]) -> Postings {
+    let mut postings = Vec::with_capacity(left.len().min(right.len()));
+    let (mut left_idx, mut right_idx) = (0, 0);
+    while left_idx < left.len() && right_idx < right.len() {
+        match left[left_idx].cmp(&right[right_idx]) {
+            std::cmp::Ordering::Less => left_idx += 1,
+            std::cmp::Ordering::Greater => right_idx += 1,
+            std::cmp::Ordering::Equal => {
+                postings.push(left[left_idx]);
+                left_idx += 1;
+                right_idx += 1;
+            }
+        }
+    }
+    postings
+}
+
+fn union(left: &[u32], right: &[u32]) -> Postings {
+    let mut postings = Vec::with_capacity(left.len() + right.len());
+    let (mut left_idx, mut right_idx) = (0, 0);
+    while left_idx < left.len() && right_idx < right.len() {
+        match left[left_idx].cmp(&right[right_idx]) {
+            std::cmp::Ordering::Less => {
+                postings.push(left[left_idx]);
+                left_idx += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                postings.push(right[right_idx]);
+                right_idx += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                postings.push(left[left_idx]);
+                left_idx += 1;
+                right_idx += 1;
+            }
+        }
+    }
+    postings.extend_from_slice(&left[left_idx..]);
+    postings.extend_from_slice(&right[right_idx..]);
+    postings
+}
+
+fn difference(left: &[u32], right: &[u32]) -> Postings {
+    let mut postings = Vec::with_capacity(left.len());
+    let mut right_idx = 0;
+    for &split_ord in left {
+        while right_idx < right.len() && right[right_idx] < split_ord {
+            right_idx += 1;
+        }
+        if right_idx == right.len() || right[right_idx] != split_ord {
+            postings.push(split_ord);
+        }
+    }
+    postings
+}
+
+/// Static interval tree over the time ranges of the splits: the time ranges are sorted by start
+/// and laid out as an implicit balanced binary search tree, in which each node also stores the
+/// greatest end of its subtree. Finding the `k` time ranges intersecting a range takes
+/// `O(k log n)` and skips the subtrees that end before the range.
+struct TimeRangeIndex {
+    /// `(start, end, split_ord)` tuples sorted by start. The ends are inclusive.
+    time_ranges: Vec<(i64, i64, u32)>,
+    /// Greatest end of the subtree rooted at each node.
+    max_ends: Vec<i64>,
+}
+
+impl TimeRangeIndex {
+    fn new(mut time_ranges: Vec<(i64, i64, u32)>) -> Self {
+        time_ranges.sort_unstable();
+        let mut max_ends = vec![i64::MIN; time_ranges.len()];
+        Self::compute_max_ends(&time_ranges, &mut max_ends, 0, time_ranges.len());
+        TimeRangeIndex {
+            time_ranges,
+            max_ends,
+        }
+    }
+
+    fn compute_max_ends(
+        time_ranges: &[(i64, i64, u32)],
+        max_ends: &mut [i64],
+        lo: usize,
+        hi: usize,
+    ) -> i64 {
+        if lo >= hi {
+            return i64::MIN;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let left_max_end = Self::compute_max_ends(time_ranges, max_ends, lo, mid);
+        let right_max_end = Self::compute_max_ends(time_ranges, max_ends, mid + 1, hi);
+        max_ends[mid] = time_ranges[mid].1.max(left_max_end).max(right_max_end);
+        max_ends[mid]
+    }
+
+    /// Returns the ordinals of the splits whose time range intersects `time_range`.
+    fn find(&self, time_range: &Range<i64>) -> Postings {
+        let mut split_ords = Vec::new();
+        self.find_in_subtree(time_range, 0, self.time_ranges.len(), &mut split_ords);
+        split_ords
+    }
+
+    fn find_in_subtree(
+        &self,
+        time_range: &Range<i64>,
+        lo: usize,
+        hi: usize,
+        split_ords: &mut Postings,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        if self.max_ends[mid] < time_range.start {
+            return;
+        }
+        self.find_in_subtree(time_range, lo, mid, split_ords);
+        let (start, end, split_ord) = self.time_ranges[mid];
+        // The node and its right subtree start after the range.
+        if start >= time_range.end {
+            return;
+        }
+        if end >= time_range.start {
+            split_ords.push(split_ord);
+        }
+        self.find_in_subtree(time_range, mid + 1, hi, split_ords);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use futures::StreamExt;
+    use quickwit_doc_mapper::tag_pruning::{no_tag, tag};
+    use quickwit_metastore::MockMetastore;
+
+    use super::*;
+
+    fn make_split(split_ord: u64, time_range_opt: Option<(i64, i64)>, tags: &[&str]) -> Split {
+        Split {
+            split_state: SplitState::Published,
+            update_timestamp: 0,
+            split_metadata: SplitMetadata {
+                split_id: format!("split-{:05}", split_ord),
+                time_range: time_range_opt.map(|(start, end)| start..=end),
+                tags: tags.iter().map(ToString::to_string).collect(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Returns pseudo-random splits, with a deterministic linear congruential generator.
+    fn make_splits(num_splits: u64) -> Vec<Split> {
+        let mut state = 42u64;
+        let mut next = move || {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            state >> 33
+        };
+        (0..num_splits)
+            .map(|split_ord| {
+                let time_range_opt = if next() % 10 == 0 {
+                    None
+                } else {
+                    let start = (next() % 10_000) as i64;
+                    Some((start, start + (next() % 500) as i64))
+                };
+                let tenant_tag = format!("tenant:{}", next() % 4);
+                let tags: Vec<&str> = if next() % 3 == 0 {
+                    Vec::new()
+                } else {
+                    vec!["tenant!", tenant_tag.as_str()]
+                };
+                make_split(split_ord, time_range_opt, &tags)
+            })
+            .collect()
+    }
+
+    /// Filters the splits like the metastores do.
+    fn scan_splits(
+        splits: &[Split],
+        time_range_opt: Option<&Range<i64>>,
+        tags_opt: Option<&TagFilterAst>,
+    ) -> BTreeSet<String> {
+        splits
+            .iter()
+            .filter(
+                |split| match (time_range_opt, &split.split_metadata.time_range) {
+                    (Some(time_range), Some(split_time_range)) => {
+                        *split_time_range.end() >= time_range.start
+                            && *split_time_range.start() < time_range.end
+                    }
+                    _ => true,
+                },
+            )
+            .filter(|split| {
+                tags_opt
+                    .map(|tags| tags.evaluate(&split.split_metadata.tags))
+                    .unwrap_or(true)
+            })
+            .map(|split| split.split_id().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_index_split_catalog_find_splits() {
+        let splits = make_splits(2_000);
+        let catalog = IndexSplitCatalog::new(splits.clone());
+        let time_ranges = [0..10_000, 100..200, 5_000..5_001, 9_999..20_000, -10..0];
+        let tag_filters = [
+            tag("tenant:1"),
+            no_tag("tenant:2"),
+            TagFilterAst::Or(vec![tag("tenant:0"), no_tag("tenant!")]),
+            TagFilterAst::And(vec![tag("tenant!"), no_tag("tenant:3")]),
+        ];
+        for time_range in &time_ranges {
+            let split_ids: BTreeSet<String> = catalog
+                .find_splits(Some(time_range), None)
+                .iter()
+                .map(|split| split.split_id().to_string())
+                .collect();
+            assert_eq!(split_ids, scan_splits(&splits, Some(time_range), None));
+
+            for tags in &tag_filters {
+                let split_ids: BTreeSet<String> = catalog
+                    .find_splits(Some(time_range), Some(tags))
+                    .iter()
+                    .map(|split| split.split_id().to_string())
+                    .collect();
+                assert_eq!(
+                    split_ids,
+                    scan_splits(&splits, Some(time_range), Some(tags))
+                );
+            }
+        }
+        assert_eq!(catalog.find_splits(None, None).len(), 2_000);
+    }
+
+    #[test]
+    fn test_postings_operations() {
+        assert_eq!(intersection(&[1, 3, 5, 7], &[2, 3, 7, 9]), vec![3, 7]);
+        assert_eq!(union(&[1, 3, 5], &[2, 3, 9]), vec![1, 2, 3, 5, 9]);
+        assert_eq!(difference(&[1, 2, 3, 4], &[2, 4, 6]), vec![1, 3]);
+        assert!(intersection(&[], &[1]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_split_catalog_metastore() {
+        let mut mock_metastore = MockMetastore::new();
+        mock_metastore
+            .expect_list_splits_stream()
+            .times(1)
+            .returning(|index_id, split_state, _, _, _| {
+                assert_eq!(index_id, "test-index");
+                assert_eq!(split_state, SplitState::Published);
+                let splits = vec![
+                    make_split(1, Some((0, 99)), &[]),
+                    make_split(2, Some((100, 199)), &[]),
+                    make_split(3, None, &[]),
+                ];
+                Ok(futures::stream::iter(vec![Ok(splits)]).boxed())
+            });
+        mock_metastore
+            .expect_list_splits()
+            .times(1)
+            .returning(|_, split_state, _, _| {
+                assert_eq!(split_state, SplitState::Staged);
+                Ok(Vec::new())
+            });
+        let split_catalog_metastore =
+            SplitCatalogMetastore::new(Arc::new(mock_metastore), Duration::from_secs(3_600));
+
+        let splits = split_catalog_metastore
+            .list_splits("test-index", SplitState::Published, Some(50..150), None)
+            .await
+            .unwrap();
+        assert_eq!(splits.len(), 3);
+        // The second listing is served by the catalog.
+        let splits = split_catalog_metastore
+            .list_splits("test-index", SplitState::Published, Some(150..250), None)
+            .await
+            .unwrap();
+        let split_ids: Vec<&str> = splits.iter().map(|split| split.split_id()).collect();
+        assert_eq!(split_ids, vec!["split-00002", "split-00003"]);
+
+        let splits = split_catalog_metastore
+            .list_splits("test-index", SplitState::Staged, None, None)
+            .await
+            .unwrap();
+        assert!(splits.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_split_catalog_metastore_invalidates_catalog_on_publish() {
+        let mut mock_metastore = MockMetastore::new();
+        mock_metastore
+            .expect_list_splits_stream()
+            .times(2)
+            .returning(|_, _, _, _, _| {
+                let splits = vec![make_split(1, None, &[])];
+                Ok(futures::stream::iter(vec![Ok(splits)]).boxed())
+            });
+        mock_metastore
+            .expect_publish_splits()
+            .times(1)
+            .returning(|_, _, _, _| Ok(()));
+        let split_catalog_metastore =
+            SplitCatalogMetastore::new(Arc::new(mock_metastore), Duration::from_secs(3_600));
+        split_catalog_metastore
+            .list_splits("test-index", SplitState::Published, None, None)
+            .await
+            .unwrap();
+        split_catalog_metastore
+            .publish_splits(
+                "test-index",
+                "test-source",
+                &["split-00002"],
+                CheckpointDelta::default(),
+            )
+            .await
+            .unwrap();
+        split_catalog_metastore
+            .list_splits("test-index", SplitState::Published, None, None)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_split_catalog_metastore_forwards_errors() {
+        let mut mock_metastore = MockMetastore::new();
+        mock_metastore
+            .expect_list_splits_stream()
+            .returning(|index_id, _, _, _, _| {
+                Err(MetastoreError::IndexDoesNotExist {
+                    index_id: index_id.to_string(),
+                })
+            });
+        let split_catalog_metastore =
+            SplitCatalogMetastore::new(Arc::new(mock_metastore), Duration::from_secs(3_600));
+        let error = split_catalog_metastore
+            .list_splits("test-index", SplitState::Published, None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, MetastoreError::IndexDoesNotExist { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_split_catalog_metastore_discards_catalog_loaded_before_invalidation() {
+        let mock_metastore = MockMetastore::new();
+        let split_catalog_metastore =
+            SplitCatalogMetastore::new(Arc::new(mock_metastore), Duration::from_secs(3_600));
+        let stale_catalog = Arc::new(IndexSplitCatalog::new(vec![make_split(1, None, &[])]));
+        let generation = split_catalog_metastore.generation("test-index");
+        split_catalog_metastore.invalidate("test-index");
+        assert!(!split_catalog_metastore.insert_catalog(
+            "test-index",
+            stale_catalog.clone(),
+            generation
+        ));
+        assert!(split_catalog_metastore.catalog("test-index").is_none());
+
+        let generation = split_catalog_metastore.generation("test-index");
+        assert!(split_catalog_metastore.insert_catalog("test-index", stale_catalog, generation));
+        assert!(split_catalog_metastore.catalog("test-index").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_split_catalog_metastore_refreshes_catalog_incrementally() {
+        let mut mock_metastore = MockMetastore::new();
+        mock_metastore
+            .expect_list_splits_stream()
+            .times(1)
+            .returning(|_, _, _, _, _| {
+                let mut splits = vec![make_split(1, None, &[]), make_split(2, None, &[])];
+                for split in &mut splits {
+                    split.update_timestamp = 100;
+                }
+                Ok(futures::stream::iter(vec![Ok(splits)]).boxed())
+            });
+        mock_metastore
+            .expect_list_splits_updated_since()
+            .times(2)
+            .returning(|_, update_timestamp| {
+                if update_timestamp == 100 - CATALOG_REFRESH_OVERLAP_SECS {
+                    let mut marked_split = make_split(2, None, &[]);
+                    marked_split.split_state = SplitState::MarkedForDeletion;
+                    marked_split.update_timestamp = 110;
+                    let mut published_split = make_split(3, None, &[]);
+                    published_split.update_timestamp = 110;
+                    Ok(vec![marked_split, published_split])
+                } else {
+                    assert_eq!(update_timestamp, 110 - CATALOG_REFRESH_OVERLAP_SECS);
+                    Ok(Vec::new())
+                }
+            });
+        let split_catalog_metastore =
+            SplitCatalogMetastore::new(Arc::new(mock_metastore), Duration::from_secs(3_600));
+        split_catalog_metastore
+            .list_splits("test-index", SplitState::Published, None, None)
+            .await
+            .unwrap();
+
+        let catalog = split_catalog_metastore.catalog("test-index").unwrap();
+        split_catalog_metastore
+            .refresh_catalog("test-index", &catalog)
+            .await;
+        let refreshed_catalog = split_catalog_metastore.catalog("test-index").unwrap();
+        let splits = refreshed_catalog.find_splits(None, None);
+        let split_ids: Vec<&str> = splits.iter().map(|split| split.split_id()).collect();
+        assert_eq!(split_ids, vec!["split-00001", "split-00003"]);
+
+        // Nothing changed since the previous refresh: the catalog is reused in place.
+        split_catalog_metastore
+            .refresh_catalog("test-index", &refreshed_catalog)
+            .await;
+        assert!(Arc::ptr_eq(
+            &refreshed_catalog,
+            &split_catalog_metastore.catalog("test-index").unwrap()
+        ));
+    }
+}
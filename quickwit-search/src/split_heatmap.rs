@@ -0,0 +1,413 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
+use std::sync::Mutex;
+
+use chrono::Utc;
+use once_cell::sync::OnceCell;
+use quickwit_metastore::{Metastore, SplitMetadata, SplitState};
+use serde::Serialize;
+
+/// Duration after which an access to a split weighs half as much in the hotness of the split.
+const HOTNESS_HALF_LIFE_SECS: f64 = 3_600.0;
+
+/// Maximum number of splits tracked by a searcher. Beyond that, the coldest splits are forgotten.
+const MAX_NUM_TRACKED_SPLITS: usize = 100_000;
+
+/// Access statistics of a split on this searcher.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitAccessStats {
+    pub index_id: String,
+    pub num_accesses: u64,
+    pub last_access_timestamp: i64,
+    #[serde(skip)]
+    decayed_num_accesses: f64,
+}
+
+impl SplitAccessStats {
+    fn new(index_id: String) -> Self {
+        SplitAccessStats {
+            index_id,
+            num_accesses: 0,
+            last_access_timestamp: i64::MIN,
+            decayed_num_accesses: 0.0,
+        }
+    }
+
+    fn record_access(&mut self, now: i64) {
+        self.decayed_num_accesses = self.hotness(now) + 1.0;
+        self.num_accesses += 1;
+        self.last_access_timestamp = self.last_access_timestamp.max(now);
+    }
+
+    /// Returns the number of accesses to the split, each access weighing half as much every
+    /// [`HOTNESS_HALF_LIFE_SECS`] elapsed since it happened. Frequently and recently accessed
+    /// splits are therefore the hottest.
+    pub fn hotness(&self, now: i64) -> f64 {
+        if self.num_accesses == 0 {
+            return 0.0;
+        }
+        let elapsed_secs = now.saturating_sub(self.last_access_timestamp).max(0) as f64;
+        self.decayed_num_accesses * 0.5f64.powf(elapsed_secs / HOTNESS_HALF_LIFE_SECS)
+    }
+}
+
+/// Records the accesses of a searcher to the splits, see [`split_access_tracker`].
+#[derive(Debug, Default)]
+pub struct SplitAccessTracker {
+    split_access_stats: Mutex<HashMap<String, SplitAccessStats>>,
+}
+
+impl SplitAccessTracker {
+    /// Records an access to each of the `split_ids` of the index `index_id`.
+    pub fn record_accesses<'a>(
+        &self,
+        index_id: &str,
+        split_ids: impl IntoIterator<Item = &'a str>,
+    ) {
+        self.record_accesses_at(index_id, split_ids, Utc::now().timestamp())
+    }
+
+    fn record_accesses_at<'a>(
+        &self,
+        index_id: &str,
+        split_ids: impl IntoIterator<Item = &'a str>,
+        now: i64,
+    ) {
+        let mut split_access_stats = self.split_access_stats.lock().unwrap();
+        for split_id in split_ids {
+            split_access_stats
+                .entry(split_id.to_string())
+                .or_insert_with(|| SplitAccessStats::new(index_id.to_string()))
+                .record_access(now);
+        }
+        if split_access_stats.len() > MAX_NUM_TRACKED_SPLITS {
+            forget_coldest_splits(
+                &mut split_access_stats,
+                MAX_NUM_TRACKED_SPLITS * 9 / 10,
+                now,
+            );
+        }
+    }
+
+    /// Returns the access statistics of the tracked splits, per split ID.
+    pub fn split_access_stats(&self) -> HashMap<String, SplitAccessStats> {
+        self.split_access_stats.lock().unwrap().clone()
+    }
+
+    /// Returns the current hotness of each tracked split, per split ID.
+    pub fn hotness_per_split(&self) -> HashMap<String, f64> {
+        let now = Utc::now().timestamp();
+        self.split_access_stats
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(split_id, stats)| (split_id.clone(), stats.hotness(now)))
+            .collect()
+    }
+}
+
+fn forget_coldest_splits(
+    split_access_stats: &mut HashMap<String, SplitAccessStats>,
+    num_splits_to_keep: usize,
+    now: i64,
+) {
+    let mut hotness_per_split: Vec<(f64, String)> = split_access_stats
+        .iter()
+        .map(|(split_id, stats)| (stats.hotness(now), split_id.clone()))
+        .collect();
+    hotness_per_split
+        .sort_by(|left, right| right.0.partial_cmp(&left.0).unwrap_or(Ordering::Equal));
+    for (_, split_id) in hotness_per_split.into_iter().skip(num_splits_to_keep) {
+        split_access_stats.remove(&split_id);
+    }
+}
+
+/// Returns the split access tracker of the searcher.
+pub fn split_access_tracker() -> &'static SplitAccessTracker {
+    static INSTANCE: OnceCell<SplitAccessTracker> = OnceCell::new();
+    INSTANCE.get_or_init(SplitAccessTracker::default)
+}
+
+/// Accesses to a group of splits.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeatStats {
+    pub num_splits: usize,
+    pub num_accessed_splits: usize,
+    pub num_accesses: u64,
+    pub hotness: f64,
+    /// Size of the footers of the splits, i.e. the size they take in the split footer cache.
+    pub footer_num_bytes: u64,
+}
+
+impl HeatStats {
+    fn add_split(
+        &mut self,
+        split_metadata: &SplitMetadata,
+        stats_opt: Option<&SplitAccessStats>,
+        now: i64,
+    ) {
+        self.num_splits += 1;
+        self.footer_num_bytes +=
+            split_metadata.footer_offsets.end - split_metadata.footer_offsets.start;
+        if let Some(stats) = stats_opt {
+            self.num_accessed_splits += 1;
+            self.num_accesses += stats.num_accesses;
+            self.hotness += stats.hotness(now);
+        }
+    }
+}
+
+/// Accesses to the splits whose time range ends in `[start_timestamp, end_timestamp)`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeatmapBucket {
+    pub start_timestamp: i64,
+    pub end_timestamp: i64,
+    #[serde(flatten)]
+    pub stats: HeatStats,
+}
+
+/// Accesses to a split.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitHeat {
+    pub split_id: String,
+    pub time_range: Option<RangeInclusive<i64>>,
+    pub num_accesses: u64,
+    pub last_access_timestamp: i64,
+    pub hotness: f64,
+    pub footer_num_bytes: u64,
+}
+
+/// Heatmap of the accesses to the published splits of an index.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexHeatmap {
+    #[serde(flatten)]
+    pub total: HeatStats,
+    /// Splits bucketed by the end of their time range, from the oldest bucket to the newest one.
+    pub buckets: Vec<HeatmapBucket>,
+    /// Splits without time range.
+    pub untimed: HeatStats,
+    /// Hottest splits of the index, from the hottest to the coldest.
+    pub hottest_splits: Vec<SplitHeat>,
+}
+
+/// Heatmap of the accesses of the searcher to the published splits, per index ID.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitHeatmap {
+    pub indexes: BTreeMap<String, IndexHeatmap>,
+}
+
+/// Options of the split heatmap report.
+#[derive(Debug, Clone)]
+pub struct SplitHeatmapOptions {
+    /// Only reports the index with this ID, if any. Otherwise, reports the indexes whose splits
+    /// were accessed by the searcher.
+    pub index_id_opt: Option<String>,
+    /// Width of the buckets of the heatmap.
+    pub bucket_width_secs: i64,
+    /// Number of hottest splits listed per index.
+    pub num_hottest_splits: usize,
+}
+
+/// Builds the heatmap of the accesses of the searcher to the published splits of the indexes.
+/// Splits never accessed are reported too, so that the heatmap also shows the cold time ranges.
+pub async fn split_heatmap(
+    metastore: &dyn Metastore,
+    options: &SplitHeatmapOptions,
+) -> crate::Result<SplitHeatmap> {
+    let split_access_stats = split_access_tracker().split_access_stats();
+    let index_ids: Vec<String> = if let Some(index_id) = &options.index_id_opt {
+        vec![index_id.clone()]
+    } else {
+        let mut index_ids: Vec<String> = split_access_stats
+            .values()
+            .map(|stats| stats.index_id.clone())
+            .collect();
+        index_ids.sort();
+        index_ids.dedup();
+        index_ids
+    };
+    let now = Utc::now().timestamp();
+    let mut split_heatmap = SplitHeatmap::default();
+    for index_id in index_ids {
+        let splits_metadata: Vec<SplitMetadata> = metastore
+            .list_splits(&index_id, SplitState::Published, None, None)
+            .await?
+            .into_iter()
+            .map(|split| split.split_metadata)
+            .collect();
+        let index_heatmap =
+            build_index_heatmap(&splits_metadata, &split_access_stats, options, now);
+        split_heatmap.indexes.insert(index_id, index_heatmap);
+    }
+    Ok(split_heatmap)
+}
+
+fn build_index_heatmap(
+    splits_metadata: &[SplitMetadata],
+    split_access_stats: &HashMap<String, SplitAccessStats>,
+    options: &SplitHeatmapOptions,
+    now: i64,
+) -> IndexHeatmap {
+    let bucket_width_secs = options.bucket_width_secs.max(1);
+    let mut index_heatmap = IndexHeatmap::default();
+    let mut buckets: BTreeMap<i64, HeatStats> = BTreeMap::new();
+    let mut split_heats: Vec<SplitHeat> = Vec::new();
+
+    for split_metadata in splits_metadata {
+        let stats_opt = split_access_stats.get(&split_metadata.split_id);
+        index_heatmap
+            .total
+            .add_split(split_metadata, stats_opt, now);
+        if let Some(time_range) = &split_metadata.time_range {
+            let bucket_start_timestamp =
+                time_range.end().div_euclid(bucket_width_secs) * bucket_width_secs;
+            buckets
+                .entry(bucket_start_timestamp)
+                .or_default()
+                .add_split(split_metadata, stats_opt, now);
+        } else {
+            index_heatmap
+                .untimed
+                .add_split(split_metadata, stats_opt, now);
+        }
+        if let Some(stats) = stats_opt {
+            split_heats.push(SplitHeat {
+                split_id: split_metadata.split_id.clone(),
+                time_range: split_metadata.time_range.clone(),
+                num_accesses: stats.num_accesses,
+                last_access_timestamp: stats.last_access_timestamp,
+                hotness: stats.hotness(now),
+                footer_num_bytes: split_metadata.footer_offsets.end
+                    - split_metadata.footer_offsets.start,
+            });
+        }
+    }
+    index_heatmap.buckets = buckets
+        .into_iter()
+        .map(|(start_timestamp, stats)| HeatmapBucket {
+            start_timestamp,
+            end_timestamp: start_timestamp + bucket_width_secs,
+            stats,
+        })
+        .collect();
+    split_heats.sort_by(|left, right| {
+        right
+            .hotness
+            .partial_cmp(&left.hotness)
+            .unwrap_or(Ordering::Equal)
+    });
+    split_heats.truncate(options.num_hottest_splits);
+    index_heatmap.hottest_splits = split_heats;
+    index_heatmap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_metadata(split_id: &str, time_range: Option<RangeInclusive<i64>>) -> SplitMetadata {
+        SplitMetadata {
+            split_id: split_id.to_string(),
+            time_range,
+            footer_offsets: 1_000..1_100,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_split_access_stats_hotness() {
+        let mut stats = SplitAccessStats::new("my-index".to_string());
+        assert_eq!(stats.hotness(0), 0.0);
+        stats.record_access(0);
+        stats.record_access(0);
+        assert_eq!(stats.num_accesses, 2);
+        assert!((stats.hotness(0) - 2.0).abs() < 1e-9);
+        assert!((stats.hotness(3_600) - 1.0).abs() < 1e-9);
+        stats.record_access(3_600);
+        assert_eq!(stats.last_access_timestamp, 3_600);
+        assert!((stats.hotness(3_600) - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_split_access_tracker_forgets_coldest_splits() {
+        let tracker = SplitAccessTracker::default();
+        tracker.record_accesses_at("my-index", ["split-1", "split-2"], 0);
+        tracker.record_accesses_at("my-index", ["split-2", "split-3"], 3_600);
+        let mut split_access_stats = tracker.split_access_stats();
+        assert_eq!(split_access_stats.len(), 3);
+        assert_eq!(split_access_stats["split-2"].num_accesses, 2);
+
+        forget_coldest_splits(&mut split_access_stats, 2, 3_600);
+        let mut split_ids: Vec<&str> = split_access_stats.keys().map(String::as_str).collect();
+        split_ids.sort_unstable();
+        assert_eq!(split_ids, ["split-2", "split-3"]);
+    }
+
+    #[test]
+    fn test_build_index_heatmap() {
+        let splits_metadata = vec![
+            split_metadata("split-1", Some(0..=3_599)),
+            split_metadata("split-2", Some(3_000..=4_000)),
+            split_metadata("split-3", Some(4_500..=5_000)),
+            split_metadata("split-4", None),
+        ];
+        let tracker = SplitAccessTracker::default();
+        tracker.record_accesses_at("my-index", ["split-2", "split-3"], 0);
+        tracker.record_accesses_at("my-index", ["split-3", "split-4"], 0);
+        let options = SplitHeatmapOptions {
+            index_id_opt: None,
+            bucket_width_secs: 3_600,
+            num_hottest_splits: 2,
+        };
+        let index_heatmap =
+            build_index_heatmap(&splits_metadata, &tracker.split_access_stats(), &options, 0);
+        assert_eq!(index_heatmap.total.num_splits, 4);
+        assert_eq!(index_heatmap.total.num_accessed_splits, 3);
+        assert_eq!(index_heatmap.total.num_accesses, 4);
+        assert_eq!(index_heatmap.total.footer_num_bytes, 400);
+        assert_eq!(index_heatmap.buckets.len(), 2);
+        assert_eq!(index_heatmap.buckets[0].start_timestamp, 0);
+        assert_eq!(index_heatmap.buckets[0].end_timestamp, 3_600);
+        assert_eq!(index_heatmap.buckets[0].stats.num_splits, 1);
+        assert_eq!(index_heatmap.buckets[0].stats.num_accesses, 0);
+        assert_eq!(index_heatmap.buckets[1].start_timestamp, 3_600);
+        assert_eq!(index_heatmap.buckets[1].stats.num_splits, 2);
+        assert_eq!(index_heatmap.buckets[1].stats.num_accessed_splits, 2);
+        assert_eq!(index_heatmap.buckets[1].stats.num_accesses, 3);
+        assert_eq!(index_heatmap.untimed.num_splits, 1);
+        assert_eq!(index_heatmap.untimed.num_accesses, 1);
+        let hottest_split_ids: Vec<&str> = index_heatmap
+            .hottest_splits
+            .iter()
+            .map(|split_heat| split_heat.split_id.as_str())
+            .collect();
+        assert_eq!(hottest_split_ids[0], "split-3");
+        assert_eq!(hottest_split_ids.len(), 2);
+    }
+}
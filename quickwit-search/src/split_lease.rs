@@ -0,0 +1,181 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Long-running searches lease the splits they read, so that the garbage collector does not
+//! delete a split that was merged away while the search is still streaming from it.
+//!
+//! Leases are short-lived: their holder renews them while the search runs, and a lease held by a
+//! search that crashed expires on its own after [`SPLIT_READ_LEASE_TTL`].
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use quickwit_common::new_coolid;
+use quickwit_metastore::Metastore;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Period after which a split read lease expires unless it is renewed.
+pub const SPLIT_READ_LEASE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Interval at which the holders of split read leases renew them.
+const SPLIT_READ_LEASE_RENEWAL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Read leases on the splits of a search, renewed in the background for as long as the guard is
+/// alive, and released when it is dropped.
+pub(crate) struct SplitReadLeaseGuard {
+    metastore: Arc<dyn Metastore>,
+    index_id: String,
+    holder_id: String,
+    renewal_task_handle: JoinHandle<()>,
+}
+
+impl SplitReadLeaseGuard {
+    /// Leases the splits `split_ids` of the index `index_id`.
+    pub async fn acquire(
+        metastore: Arc<dyn Metastore>,
+        index_id: &str,
+        split_ids: Vec<String>,
+    ) -> crate::Result<Self> {
+        let holder_id = new_coolid("search");
+        let split_id_refs: Vec<&str> = split_ids.iter().map(String::as_str).collect();
+        metastore
+            .acquire_split_read_leases(index_id, &split_id_refs, &holder_id, SPLIT_READ_LEASE_TTL)
+            .await?;
+        let renewal_task_handle = tokio::spawn(renew_split_read_leases_loop(
+            metastore.clone(),
+            index_id.to_string(),
+            split_ids,
+            holder_id.clone(),
+        ));
+        Ok(SplitReadLeaseGuard {
+            metastore,
+            index_id: index_id.to_string(),
+            holder_id,
+            renewal_task_handle,
+        })
+    }
+}
+
+impl Drop for SplitReadLeaseGuard {
+    fn drop(&mut self) {
+        self.renewal_task_handle.abort();
+        let metastore = self.metastore.clone();
+        let index_id = std::mem::take(&mut self.index_id);
+        let holder_id = std::mem::take(&mut self.holder_id);
+        // The guard may be dropped outside of a runtime, e.g. while the runtime shuts down, in
+        // which case the leases expire on their own.
+        let runtime_handle = match tokio::runtime::Handle::try_current() {
+            Ok(runtime_handle) => runtime_handle,
+            Err(_) => {
+                debug!(index_id = %index_id, holder_id = %holder_id, "Split read leases dropped outside of a runtime, letting them expire.");
+                return;
+            }
+        };
+        runtime_handle.spawn(async move {
+            if let Err(error) = metastore
+                .release_split_read_leases(&index_id, &holder_id)
+                .await
+            {
+                // The leases expire on their own.
+                warn!(index_id = %index_id, holder_id = %holder_id, error = ?error, "Failed to release split read leases.");
+            }
+        });
+    }
+}
+
+async fn renew_split_read_leases_loop(
+    metastore: Arc<dyn Metastore>,
+    index_id: String,
+    split_ids: Vec<String>,
+    holder_id: String,
+) {
+    let split_id_refs: Vec<&str> = split_ids.iter().map(String::as_str).collect();
+    let mut interval = tokio::time::interval(SPLIT_READ_LEASE_RENEWAL_INTERVAL);
+    // The first tick completes immediately.
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        if let Err(error) = metastore
+            .acquire_split_read_leases(&index_id, &split_id_refs, &holder_id, SPLIT_READ_LEASE_TTL)
+            .await
+        {
+            warn!(index_id = %index_id, holder_id = %holder_id, error = ?error, "Failed to renew split read leases.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_metastore::MockMetastore;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_split_read_lease_guard() -> anyhow::Result<()> {
+        let (release_sender, mut release_receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut mock_metastore = MockMetastore::new();
+        mock_metastore
+            .expect_acquire_split_read_leases()
+            .times(1)
+            .returning(|index_id, split_ids, _holder_id, ttl| {
+                assert_eq!(index_id, "test-idx");
+                assert_eq!(split_ids, vec!["split1", "split2"]);
+                assert_eq!(ttl, SPLIT_READ_LEASE_TTL);
+                Ok(())
+            });
+        mock_metastore
+            .expect_release_split_read_leases()
+            .times(1)
+            .returning(move |index_id, holder_id| {
+                assert_eq!(index_id, "test-idx");
+                release_sender.send(holder_id.to_string()).unwrap();
+                Ok(())
+            });
+        let lease_guard = SplitReadLeaseGuard::acquire(
+            Arc::new(mock_metastore),
+            "test-idx",
+            vec!["split1".to_string(), "split2".to_string()],
+        )
+        .await?;
+        let holder_id = lease_guard.holder_id.clone();
+        drop(lease_guard);
+        assert_eq!(release_receiver.recv().await, Some(holder_id));
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_read_lease_guard_dropped_outside_runtime() -> anyhow::Result<()> {
+        let mut mock_metastore = MockMetastore::new();
+        mock_metastore
+            .expect_acquire_split_read_leases()
+            .times(1)
+            .returning(|_index_id, _split_ids, _holder_id, _ttl| Ok(()));
+        mock_metastore.expect_release_split_read_leases().times(0);
+        let runtime = tokio::runtime::Runtime::new()?;
+        let lease_guard = runtime.block_on(SplitReadLeaseGuard::acquire(
+            Arc::new(mock_metastore),
+            "test-idx",
+            vec!["split1".to_string()],
+        ))?;
+        drop(runtime);
+        drop(lease_guard);
+        Ok(())
+    }
+}
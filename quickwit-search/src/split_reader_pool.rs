@@ -0,0 +1,504 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+use once_cell::sync::{Lazy, OnceCell};
+use prometheus::{IntCounter, IntGauge};
+use quickwit_common::metrics::{new_counter, new_gauge};
+use quickwit_config::get_searcher_config_instance;
+use quickwit_directories::CachingDirectory;
+use quickwit_proto::SplitIdAndFooterOffsets;
+use quickwit_storage::Storage;
+use tantivy::{IndexReader, LeasedItem, ReloadPolicy, Searcher};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::leaf::{open_index, open_index_with_cache, SplitFooterCacheKey};
+
+static SPLIT_READER_POOL_HITS: Lazy<IntCounter> = Lazy::new(|| {
+    new_counter(
+        "quickwit_search_split_reader_pool_hits_total",
+        "Number of searches served by a split opened in the split reader pool.",
+    )
+});
+
+static SPLIT_READER_POOL_MISSES: Lazy<IntCounter> = Lazy::new(|| {
+    new_counter(
+        "quickwit_search_split_reader_pool_misses_total",
+        "Number of searches that opened a split missing from the split reader pool.",
+    )
+});
+
+static SPLIT_READER_POOL_BUSY: Lazy<IntCounter> = Lazy::new(|| {
+    new_counter(
+        "quickwit_search_split_reader_pool_busy_total",
+        "Number of searches that opened a split of the split reader pool again because all its \
+         searchers were in use.",
+    )
+});
+
+static SPLIT_READER_POOL_EVICTIONS: Lazy<IntCounter> = Lazy::new(|| {
+    new_counter(
+        "quickwit_search_split_reader_pool_evictions_total",
+        "Number of splits evicted from the split reader pool, either because their TTL expired or \
+         to make room for other splits or to release the data cached with them.",
+    )
+});
+
+static SPLIT_READER_POOL_NUM_SPLITS: Lazy<IntGauge> = Lazy::new(|| {
+    new_gauge(
+        "quickwit_search_split_reader_pool_splits",
+        "Number of opened splits held by the split reader pool.",
+    )
+});
+
+static SPLIT_READER_POOL_NUM_BYTES: Lazy<IntGauge> = Lazy::new(|| {
+    new_gauge(
+        "quickwit_search_split_reader_pool_bytes",
+        "Number of bytes cached by the opened splits held by the split reader pool.",
+    )
+});
+
+pub(crate) fn split_reader_pool() -> &'static SplitReaderPool {
+    static INSTANCE: OnceCell<SplitReaderPool> = OnceCell::new();
+    INSTANCE.get_or_init(|| {
+        let config = get_searcher_config_instance();
+        SplitReaderPool::new(
+            config.split_reader_pool_capacity,
+            config.split_reader_pool_max_size.get_bytes() as usize,
+            Duration::from_secs(config.split_reader_pool_ttl_secs),
+            config.split_reader_pool_num_searchers,
+        )
+    })
+}
+
+/// A split opened by the pool. Splits are immutable, so its reader is never reloaded.
+struct PooledSplitReader {
+    reader: IndexReader,
+    // One permit per searcher of the reader: leasing a searcher with a permit never blocks.
+    searcher_permits: Arc<Semaphore>,
+    // Directory caching the data downloaded while searching the split.
+    caching_directory_opt: Option<CachingDirectory>,
+    opened_at: Instant,
+}
+
+impl PooledSplitReader {
+    fn num_bytes(&self) -> usize {
+        self.caching_directory_opt
+            .as_ref()
+            .map(CachingDirectory::num_bytes)
+            .unwrap_or(0)
+    }
+
+    fn try_lease_searcher(&self) -> Option<SplitSearcher> {
+        let permit = self.searcher_permits.clone().try_acquire_owned().ok()?;
+        Some(SplitSearcher {
+            searcher: self.reader.searcher(),
+            _permit_opt: Some(permit),
+        })
+    }
+}
+
+/// Searcher of a split, leased from the split reader pool or from a reader opened for the
+/// search only.
+pub(crate) struct SplitSearcher {
+    // Declared before the permit so that the searcher is returned to its reader first.
+    searcher: LeasedItem<Searcher>,
+    _permit_opt: Option<OwnedSemaphorePermit>,
+}
+
+impl SplitSearcher {
+    #[cfg(test)]
+    fn is_pooled(&self) -> bool {
+        self._permit_opt.is_some()
+    }
+}
+
+impl Deref for SplitSearcher {
+    type Target = Searcher;
+
+    fn deref(&self) -> &Searcher {
+        &self.searcher
+    }
+}
+
+/// LRU pool of opened splits, so that repeated searches on the same splits skip downloading
+/// their footer and opening them.
+///
+/// A split is dropped from the pool once its TTL, counted from when it was opened, expires. The
+/// data downloaded while searching a split is cached along with it: the least recently used
+/// splits are also dropped as long as the data cached by the splits of the pool exceeds
+/// `max_num_bytes`.
+pub(crate) struct SplitReaderPool {
+    readers_opt: Option<Mutex<LruCache<SplitFooterCacheKey, Arc<PooledSplitReader>>>>,
+    max_num_bytes: usize,
+    ttl: Duration,
+    num_searchers: usize,
+}
+
+impl SplitReaderPool {
+    /// Creates a pool holding at most `capacity` splits, caching at most `max_num_bytes` of
+    /// their data. A capacity of 0 disables the pool.
+    pub fn new(capacity: usize, max_num_bytes: usize, ttl: Duration, num_searchers: usize) -> Self {
+        let readers_opt = if capacity > 0 {
+            Some(Mutex::new(LruCache::new(capacity)))
+        } else {
+            None
+        };
+        SplitReaderPool {
+            readers_opt,
+            max_num_bytes,
+            ttl,
+            num_searchers: num_searchers.max(1),
+        }
+    }
+
+    /// Leases a searcher of the split, opening the split if it is not in the pool.
+    ///
+    /// If all the searchers of the pooled split are in use, the split is opened again for this
+    /// search only rather than waiting for a searcher.
+    pub async fn searcher(
+        &self,
+        index_storage: Arc<dyn Storage>,
+        split: &SplitIdAndFooterOffsets,
+    ) -> anyhow::Result<SplitSearcher> {
+        let readers = match &self.readers_opt {
+            Some(readers) => readers,
+            None => return open_searcher(index_storage, split).await,
+        };
+        let cache_key = SplitFooterCacheKey {
+            index_uri: index_storage.uri(),
+            split_id: split.split_id.clone(),
+            footer_offsets: split.split_footer_start..split.split_footer_end,
+        };
+        if let Some(pooled_reader) = self.get(readers, &cache_key) {
+            if let Some(searcher) = pooled_reader.try_lease_searcher() {
+                SPLIT_READER_POOL_HITS.inc();
+                return Ok(searcher);
+            }
+            SPLIT_READER_POOL_BUSY.inc();
+            return open_searcher(index_storage, split).await;
+        }
+        SPLIT_READER_POOL_MISSES.inc();
+        let (index, caching_directory_opt) = open_index_with_cache(index_storage, split).await?;
+        let reader = index
+            .reader_builder()
+            .num_searchers(self.num_searchers)
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        let pooled_reader = Arc::new(PooledSplitReader {
+            reader,
+            searcher_permits: Arc::new(Semaphore::new(self.num_searchers)),
+            caching_directory_opt,
+            opened_at: Instant::now(),
+        });
+        let searcher = pooled_reader
+            .try_lease_searcher()
+            .expect("A split that was just opened should have a searcher available.");
+        self.insert(readers, cache_key, pooled_reader);
+        Ok(searcher)
+    }
+
+    fn is_expired(&self, pooled_reader: &PooledSplitReader) -> bool {
+        pooled_reader.opened_at.elapsed() >= self.ttl
+    }
+
+    fn get(
+        &self,
+        readers: &Mutex<LruCache<SplitFooterCacheKey, Arc<PooledSplitReader>>>,
+        cache_key: &SplitFooterCacheKey,
+    ) -> Option<Arc<PooledSplitReader>> {
+        let mut readers_guard = readers.lock().unwrap();
+        let pooled_reader = readers_guard.get(cache_key)?.clone();
+        if self.is_expired(&pooled_reader) {
+            readers_guard.pop(cache_key);
+            SPLIT_READER_POOL_EVICTIONS.inc();
+            SPLIT_READER_POOL_NUM_SPLITS.set(readers_guard.len() as i64);
+            return None;
+        }
+        // The data cached by the splits grows as they are searched. Evicting the split
+        // returned here does not interrupt the search, which keeps the split open.
+        self.evict_over_max_num_bytes(&mut readers_guard);
+        Some(pooled_reader)
+    }
+
+    /// Drops the least recently used splits until the data they cache fits in
+    /// `max_num_bytes`.
+    fn evict_over_max_num_bytes(
+        &self,
+        readers_guard: &mut LruCache<SplitFooterCacheKey, Arc<PooledSplitReader>>,
+    ) {
+        let mut num_bytes: usize = readers_guard
+            .iter()
+            .map(|(_, pooled_reader)| pooled_reader.num_bytes())
+            .sum();
+        while num_bytes > self.max_num_bytes {
+            let (_, lru_reader) = match readers_guard.pop_lru() {
+                Some(lru_entry) => lru_entry,
+                None => break,
+            };
+            num_bytes -= lru_reader.num_bytes().min(num_bytes);
+            SPLIT_READER_POOL_EVICTIONS.inc();
+        }
+        SPLIT_READER_POOL_NUM_SPLITS.set(readers_guard.len() as i64);
+        SPLIT_READER_POOL_NUM_BYTES.set(num_bytes as i64);
+    }
+
+    fn insert(
+        &self,
+        readers: &Mutex<LruCache<SplitFooterCacheKey, Arc<PooledSplitReader>>>,
+        cache_key: SplitFooterCacheKey,
+        pooled_reader: Arc<PooledSplitReader>,
+    ) {
+        let mut readers_guard = readers.lock().unwrap();
+        // The least recently used splits are the most likely to have expired without being
+        // accessed since.
+        while let Some((_, lru_reader)) = readers_guard.peek_lru() {
+            if !self.is_expired(lru_reader) {
+                break;
+            }
+            readers_guard.pop_lru();
+            SPLIT_READER_POOL_EVICTIONS.inc();
+        }
+        let inserted_key = cache_key.clone();
+        if let Some((evicted_key, _)) = readers_guard.push(cache_key, pooled_reader) {
+            // `push` also returns the entry it replaces, when the split was opened by concurrent
+            // searches.
+            if evicted_key != inserted_key {
+                SPLIT_READER_POOL_EVICTIONS.inc();
+            }
+        }
+        self.evict_over_max_num_bytes(&mut readers_guard);
+    }
+
+    #[cfg(test)]
+    fn num_splits(&self) -> usize {
+        self.readers_opt
+            .as_ref()
+            .map(|readers| readers.lock().unwrap().len())
+            .unwrap_or(0)
+    }
+}
+
+/// Opens the split for a single search.
+async fn open_searcher(
+    index_storage: Arc<dyn Storage>,
+    split: &SplitIdAndFooterOffsets,
+) -> anyhow::Result<SplitSearcher> {
+    let index = open_index(index_storage, split).await?;
+    let reader: IndexReader = index
+        .reader_builder()
+        .num_searchers(1)
+        .reload_policy(ReloadPolicy::Manual)
+        .try_into()?;
+    Ok(SplitSearcher {
+        searcher: reader.searcher(),
+        _permit_opt: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use quickwit_indexing::TestSandbox;
+    use quickwit_storage::LocalFileStorage;
+    use serde_json::json;
+    use tantivy::DocAddress;
+
+    use super::*;
+    use crate::extract_split_and_footer_offsets;
+    use crate::leaf::local_split_path;
+
+    #[tokio::test]
+    async fn test_split_reader_pool() -> anyhow::Result<()> {
+        let index_id = "test-split-reader-pool";
+        let doc_mapping_yaml = r#"
+            field_mappings:
+              - name: body
+                type: text
+        "#;
+        let test_sandbox = TestSandbox::create(index_id, doc_mapping_yaml, "{}", &["body"]).await?;
+        test_sandbox
+            .add_documents(vec![json!({"body": "first split"})])
+            .await?;
+        test_sandbox
+            .add_documents(vec![
+                json!({"body": "second split"}),
+                json!({"body": "second split"}),
+            ])
+            .await?;
+        let mut splits: Vec<SplitIdAndFooterOffsets> = test_sandbox
+            .metastore()
+            .list_all_splits(index_id)
+            .await?
+            .iter()
+            .map(|split| extract_split_and_footer_offsets(&split.split_metadata))
+            .collect();
+        splits.sort_by_key(|split| split.split_id.clone());
+        let storage = test_sandbox.storage();
+
+        let pool = SplitReaderPool::new(1, usize::MAX, Duration::from_secs(3600), 1);
+        {
+            let searcher = pool.searcher(storage.clone(), &splits[0]).await?;
+            assert!(searcher.is_pooled());
+            assert_eq!(pool.num_splits(), 1);
+            // The only searcher of the pooled split is in use.
+            let other_searcher = pool.searcher(storage.clone(), &splits[0]).await?;
+            assert!(!other_searcher.is_pooled());
+            assert_eq!(searcher.num_docs(), other_searcher.num_docs());
+        }
+        let searcher = pool.searcher(storage.clone(), &splits[0]).await?;
+        assert!(searcher.is_pooled());
+        drop(searcher);
+
+        // Opening the other split evicts the first one.
+        let searcher = pool.searcher(storage.clone(), &splits[1]).await?;
+        assert!(searcher.is_pooled());
+        assert_eq!(pool.num_splits(), 1);
+
+        let disabled_pool = SplitReaderPool::new(0, usize::MAX, Duration::from_secs(3600), 1);
+        let searcher = disabled_pool.searcher(storage.clone(), &splits[0]).await?;
+        assert!(!searcher.is_pooled());
+        assert_eq!(disabled_pool.num_splits(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_split_reader_pool_ttl() -> anyhow::Result<()> {
+        let index_id = "test-split-reader-pool-ttl";
+        let doc_mapping_yaml = r#"
+            field_mappings:
+              - name: body
+                type: text
+        "#;
+        let test_sandbox = TestSandbox::create(index_id, doc_mapping_yaml, "{}", &["body"]).await?;
+        test_sandbox
+            .add_documents(vec![json!({"body": "hello"})])
+            .await?;
+        let splits = test_sandbox.metastore().list_all_splits(index_id).await?;
+        let split = extract_split_and_footer_offsets(&splits[0].split_metadata);
+
+        let pool = SplitReaderPool::new(10, usize::MAX, Duration::ZERO, 1);
+        let searcher = pool.searcher(test_sandbox.storage(), &split).await?;
+        assert_eq!(searcher.num_docs(), 1);
+        assert_eq!(pool.num_splits(), 1);
+        let cache_key = SplitFooterCacheKey {
+            index_uri: test_sandbox.storage().uri(),
+            split_id: split.split_id.clone(),
+            footer_offsets: split.split_footer_start..split.split_footer_end,
+        };
+        let readers = pool.readers_opt.as_ref().unwrap();
+        assert!(pool.get(readers, &cache_key).is_none());
+        assert_eq!(pool.num_splits(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_split_reader_pool_max_num_bytes() -> anyhow::Result<()> {
+        let index_id = "test-split-reader-pool-max-num-bytes";
+        let doc_mapping_yaml = r#"
+            field_mappings:
+              - name: body
+                type: text
+        "#;
+        let test_sandbox = TestSandbox::create(index_id, doc_mapping_yaml, "{}", &["body"]).await?;
+        test_sandbox
+            .add_documents(vec![json!({"body": "first split"})])
+            .await?;
+        test_sandbox
+            .add_documents(vec![json!({"body": "second split"})])
+            .await?;
+        let splits: Vec<SplitIdAndFooterOffsets> = test_sandbox
+            .metastore()
+            .list_all_splits(index_id)
+            .await?
+            .iter()
+            .map(|split| extract_split_and_footer_offsets(&split.split_metadata))
+            .collect();
+        let storage = test_sandbox.storage();
+
+        let pool = SplitReaderPool::new(10, 0, Duration::from_secs(3600), 1);
+        let searcher = pool.searcher(storage.clone(), &splits[0]).await?;
+        assert!(searcher.is_pooled());
+        // Reading a document caches the data of the doc store.
+        searcher
+            .doc_async(DocAddress {
+                segment_ord: 0,
+                doc_id: 0,
+            })
+            .await?;
+        drop(searcher);
+        assert_eq!(pool.num_splits(), 1);
+
+        // The first split now caches more data than allowed and is evicted on the next access.
+        let _searcher = pool.searcher(storage.clone(), &splits[1]).await?;
+        let cache_key = SplitFooterCacheKey {
+            index_uri: storage.uri(),
+            split_id: splits[0].split_id.clone(),
+            footer_offsets: splits[0].split_footer_start..splits[0].split_footer_end,
+        };
+        let readers = pool.readers_opt.as_ref().unwrap();
+        assert!(readers.lock().unwrap().peek(&cache_key).is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_open_local_split() -> anyhow::Result<()> {
+        let index_id = "test-open-local-split";
+        let doc_mapping_yaml = r#"
+            field_mappings:
+              - name: body
+                type: text
+        "#;
+        let test_sandbox = TestSandbox::create(index_id, doc_mapping_yaml, "{}", &["body"]).await?;
+        test_sandbox
+            .add_documents(vec![json!({"body": "hello"}), json!({"body": "world"})])
+            .await?;
+        let splits = test_sandbox.metastore().list_all_splits(index_id).await?;
+        let split = extract_split_and_footer_offsets(&splits[0].split_metadata);
+        let split_file_name = quickwit_common::split_file(&split.split_id);
+        assert!(local_split_path(&*test_sandbox.storage(), &split.split_id).is_none());
+
+        // Copy the split to a local directory, as if the index lived on a local disk.
+        let split_bytes = test_sandbox
+            .storage()
+            .get_all(Path::new(&split_file_name))
+            .await?;
+        let temp_dir = tempfile::tempdir()?;
+        std::fs::write(
+            temp_dir.path().join(&split_file_name),
+            split_bytes.as_slice(),
+        )?;
+        let local_storage: Arc<dyn Storage> = Arc::new(LocalFileStorage::from_uri(&format!(
+            "file://{}",
+            temp_dir.path().display()
+        ))?);
+        assert_eq!(
+            local_split_path(&*local_storage, &split.split_id),
+            Some(temp_dir.path().join(&split_file_name))
+        );
+        let searcher = open_searcher(local_storage, &split).await?;
+        assert_eq!(searcher.num_docs(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,355 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use quickwit_cluster::cluster::{Cluster, NodeLoad, QuickwitService};
+use quickwit_config::get_searcher_config_instance;
+use tokio::sync::oneshot;
+
+/// Interval at which the searcher reports its split search load to the other members of the
+/// cluster.
+const LOAD_BROADCAST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Split search waiting for a permit.
+struct Waiter {
+    /// Virtual time at which the search would be admitted if the indexes were served in
+    /// perfect proportion to their weights.
+    finish_tag: f64,
+    /// Arrival order of the search, which breaks the ties between finish tags.
+    sequence: u64,
+    permit_tx: oneshot::Sender<SplitSearchPermit>,
+}
+
+/// Split searches of an index waiting for a permit, in arrival order.
+struct IndexQueue {
+    last_finish_tag: f64,
+    waiters: VecDeque<Waiter>,
+}
+
+struct FairSemaphoreState {
+    num_permits: usize,
+    num_acquired_permits: usize,
+    /// Finish tag of the last admitted search. The searches of an index that had no search
+    /// waiting are tagged from this time, so that an idle index does not accumulate credit.
+    virtual_time: f64,
+    next_sequence: u64,
+    index_queues: HashMap<String, IndexQueue>,
+}
+
+impl FairSemaphoreState {
+    /// Returns the index whose next waiting search has the smallest finish tag.
+    fn next_index_id(&self) -> Option<String> {
+        self.index_queues
+            .iter()
+            .filter_map(|(index_id, index_queue)| {
+                let waiter = index_queue.waiters.front()?;
+                Some((index_id, (waiter.finish_tag, waiter.sequence)))
+            })
+            .min_by(|(_, left_key), (_, right_key)| {
+                left_key
+                    .partial_cmp(right_key)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|(index_id, _)| index_id.clone())
+    }
+
+    /// Hands out the available permits to the waiting searches, smallest finish tag first.
+    fn admit_waiters(&mut self, state: &Arc<Mutex<FairSemaphoreState>>) {
+        while self.num_acquired_permits < self.num_permits {
+            let index_id = match self.next_index_id() {
+                Some(index_id) => index_id,
+                None => return,
+            };
+            let index_queue = self
+                .index_queues
+                .get_mut(&index_id)
+                .expect("The index queue should exist.");
+            let waiter = index_queue
+                .waiters
+                .pop_front()
+                .expect("The index queue should not be empty.");
+            if index_queue.waiters.is_empty() {
+                self.index_queues.remove(&index_id);
+            }
+            self.virtual_time = self.virtual_time.max(waiter.finish_tag);
+            self.num_acquired_permits += 1;
+            let permit = SplitSearchPermit {
+                state_opt: Some(state.clone()),
+            };
+            // The search was cancelled while waiting: the permit goes to the next one.
+            if let Err(mut permit) = waiter.permit_tx.send(permit) {
+                permit.state_opt = None;
+                self.num_acquired_permits -= 1;
+            }
+        }
+    }
+}
+
+/// Limits the number of splits searched concurrently on the node.
+///
+/// When all the permits are taken, the waiting searches are admitted in weighted fair order
+/// across indexes rather than in arrival order: each index is granted a share of the permits
+/// proportional to its weight, so that a burst of expensive searches on one index does not
+/// starve the searches on the other indexes. The searches of an index are admitted in arrival
+/// order.
+pub(crate) struct FairSemaphore {
+    state: Arc<Mutex<FairSemaphoreState>>,
+}
+
+impl FairSemaphore {
+    pub fn new(num_permits: usize) -> Self {
+        let state = FairSemaphoreState {
+            num_permits,
+            num_acquired_permits: 0,
+            virtual_time: 0.0,
+            next_sequence: 0,
+            index_queues: HashMap::new(),
+        };
+        FairSemaphore {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Resizes the semaphore when the limit of the searcher config was changed by a config
+    /// reload. Permits acquired beyond the new limit are not revoked.
+    pub fn resize(&self, num_permits: usize) {
+        let mut state = self.lock_state();
+        state.num_permits = num_permits;
+        state.admit_waiters(&self.state);
+    }
+
+    /// Waits for a permit to search a split of the index. The permit is released when dropped.
+    pub async fn acquire(&self, index_id: &str, weight: u32) -> SplitSearchPermit {
+        let permit_rx = {
+            let mut state = self.lock_state();
+            if state.index_queues.is_empty() && state.num_acquired_permits < state.num_permits {
+                state.num_acquired_permits += 1;
+                return SplitSearchPermit {
+                    state_opt: Some(self.state.clone()),
+                };
+            }
+            let virtual_time = state.virtual_time;
+            let sequence = state.next_sequence;
+            state.next_sequence += 1;
+            let index_queue = state
+                .index_queues
+                .entry(index_id.to_string())
+                .or_insert_with(|| IndexQueue {
+                    last_finish_tag: virtual_time,
+                    waiters: VecDeque::new(),
+                });
+            let finish_tag =
+                index_queue.last_finish_tag.max(virtual_time) + 1.0 / weight.max(1) as f64;
+            index_queue.last_finish_tag = finish_tag;
+            let (permit_tx, permit_rx) = oneshot::channel();
+            index_queue.waiters.push_back(Waiter {
+                finish_tag,
+                sequence,
+                permit_tx,
+            });
+            permit_rx
+        };
+        permit_rx
+            .await
+            .expect("The semaphore should not drop waiting searches.")
+    }
+
+    /// Returns the number of searches waiting for a permit and holding one.
+    pub fn load(&self) -> NodeLoad {
+        let state = self.lock_state();
+        NodeLoad {
+            num_queued_split_searches: state
+                .index_queues
+                .values()
+                .map(|index_queue| index_queue.waiters.len() as u64)
+                .sum(),
+            num_ongoing_split_searches: state.num_acquired_permits as u64,
+        }
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, FairSemaphoreState> {
+        self.state
+            .lock()
+            .expect("Split search semaphore lock should not be poisoned.")
+    }
+}
+
+/// Permit to search a split, released when dropped.
+pub(crate) struct SplitSearchPermit {
+    state_opt: Option<Arc<Mutex<FairSemaphoreState>>>,
+}
+
+impl Drop for SplitSearchPermit {
+    fn drop(&mut self) {
+        if let Some(state) = self.state_opt.take() {
+            let mut state_guard = state
+                .lock()
+                .expect("Split search semaphore lock should not be poisoned.");
+            state_guard.num_acquired_permits -= 1;
+            state_guard.admit_waiters(&state);
+        }
+    }
+}
+
+static SPLIT_SEARCH_SEMAPHORE: Lazy<FairSemaphore> = Lazy::new(|| {
+    FairSemaphore::new(get_searcher_config_instance().max_num_concurrent_split_searches)
+});
+
+/// Waits for a permit to search a split of the index, following the limit and the weights of
+/// the searcher config.
+pub(crate) async fn acquire_split_search_permit(index_id: &str) -> SplitSearchPermit {
+    let searcher_config = get_searcher_config_instance();
+    SPLIT_SEARCH_SEMAPHORE.resize(searcher_config.max_num_concurrent_split_searches);
+    let weight = searcher_config
+        .index_search_weights
+        .get(index_id)
+        .copied()
+        .unwrap_or(1);
+    SPLIT_SEARCH_SEMAPHORE.acquire(index_id, weight).await
+}
+
+/// Periodically reports the split search load of the node to the other members of the cluster,
+/// so that the root searchers prefer the less loaded replicas. Returns right away if the node
+/// does not run the leaf searcher service, since it is never assigned split searches.
+pub async fn broadcast_split_search_load(cluster: Arc<Cluster>) {
+    if !cluster
+        .available_services
+        .contains(&QuickwitService::LeafSearcher)
+    {
+        return;
+    }
+    let mut interval = tokio::time::interval(LOAD_BROADCAST_INTERVAL);
+    loop {
+        interval.tick().await;
+        cluster.broadcast_load(SPLIT_SEARCH_SEMAPHORE.load());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::time::Duration;
+
+    use futures::FutureExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fair_semaphore_admits_indexes_by_weight() {
+        let semaphore = FairSemaphore::new(1);
+        let permit = semaphore.acquire("heavy", 1).await;
+
+        // A burst of searches on `heavy`, then a few searches on `light` and `weighted`.
+        let mut pending_searches = Vec::new();
+        for (index_id, weight) in [
+            ("heavy", 1),
+            ("heavy", 1),
+            ("heavy", 1),
+            ("heavy", 1),
+            ("light", 1),
+            ("light", 1),
+            ("weighted", 2),
+            ("weighted", 2),
+        ] {
+            pending_searches.push((index_id, semaphore.acquire(index_id, weight).boxed()));
+        }
+        for (_, search) in pending_searches.iter_mut() {
+            assert!(search.as_mut().now_or_never().is_none());
+        }
+        drop(permit);
+
+        let mut admission_order = Vec::new();
+        while !pending_searches.is_empty() {
+            let position = pending_searches
+                .iter_mut()
+                .position(|(_, search)| search.as_mut().now_or_never().is_some())
+                .expect("One search should be admitted.");
+            let (index_id, _) = pending_searches.remove(position);
+            admission_order.push(index_id);
+        }
+        assert_eq!(
+            admission_order,
+            vec!["weighted", "heavy", "light", "weighted", "heavy", "light", "heavy", "heavy"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fair_semaphore_skips_cancelled_searches() {
+        let semaphore = FairSemaphore::new(1);
+        let permit = semaphore.acquire("index-1", 1).await;
+        let mut cancelled_search = semaphore.acquire("index-1", 1).boxed();
+        let mut search = semaphore.acquire("index-2", 1).boxed();
+        assert!(cancelled_search.as_mut().now_or_never().is_none());
+        assert!(search.as_mut().now_or_never().is_none());
+        drop(cancelled_search);
+        drop(permit);
+        let _permit = tokio::time::timeout(Duration::from_secs(1), search)
+            .await
+            .unwrap();
+        assert_eq!(
+            semaphore.load(),
+            NodeLoad {
+                num_queued_split_searches: 0,
+                num_ongoing_split_searches: 1,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fair_semaphore_resize() {
+        let semaphore = FairSemaphore::new(1);
+        let _permit = semaphore.acquire("index", 1).await;
+        let mut search = semaphore.acquire("index", 1).boxed();
+        assert!(search.as_mut().now_or_never().is_none());
+        assert_eq!(
+            semaphore.load(),
+            NodeLoad {
+                num_queued_split_searches: 1,
+                num_ongoing_split_searches: 1,
+            }
+        );
+        semaphore.resize(2);
+        assert!(search.now_or_never().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_split_search_load_requires_searcher_service() -> anyhow::Result<()> {
+        let listen_addr = SocketAddr::new(
+            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            quickwit_common::net::find_available_port()?,
+        );
+        let cluster = Arc::new(Cluster::new(
+            "indexer".to_string(),
+            [QuickwitService::Indexer].into_iter().collect(),
+            listen_addr,
+        )?);
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            broadcast_split_search_load(cluster.clone()),
+        )
+        .await?;
+        assert!(cluster.node_loads().is_empty());
+        cluster.leave().await;
+        Ok(())
+    }
+}
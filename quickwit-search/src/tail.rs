@@ -0,0 +1,438 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use quickwit_metastore::{IndexMetadata, Metastore, SplitMetadata};
+use quickwit_proto::{Hit, SearchRequest, SearchResponse};
+use quickwit_storage::StorageUriResolver;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::debug;
+
+use crate::range_bounds::parse_range_bounds_in_query;
+use crate::relative_time::{now_timestamp, resolve_search_request_times};
+use crate::root::root_search_splits;
+use crate::{
+    convert_timestamp_bounds, list_relevant_splits, single_node_search_splits, ClusterClient,
+    SearchClientPool,
+};
+
+/// Interval at which the metastore is polled for newly published splits in tail mode.
+pub const TAIL_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of hits buffered ahead of a slow tail consumer.
+const TAIL_CHANNEL_CAPACITY: usize = 1_000;
+
+/// Keeps track of the splits whose documents have already been streamed by a tail search.
+#[derive(Debug, Default)]
+struct TailCursor {
+    followed_split_ids: HashSet<String>,
+}
+
+impl TailCursor {
+    /// Returns the splits of `published_splits` holding documents that have not been streamed
+    /// yet, and starts following them.
+    ///
+    /// Merges and demuxes replace followed splits with new splits holding the same documents.
+    /// A new split is considered the output of such an operation, and is not returned, only if
+    /// all the splits it replaces were followed. A merge of followed splits and splits never
+    /// seen by the cursor is returned: its followed documents are streamed twice rather than
+    /// the others being lost.
+    fn advance(&mut self, published_splits: Vec<SplitMetadata>) -> Vec<SplitMetadata> {
+        let previously_followed_split_ids = std::mem::take(&mut self.followed_split_ids);
+        let mut new_splits = Vec::new();
+        for split_metadata in published_splits {
+            let split_id = split_metadata.split_id().to_string();
+            if !previously_followed_split_ids.contains(&split_id) {
+                let is_replacement = !split_metadata.replaced_split_ids.is_empty()
+                    && split_metadata
+                        .replaced_split_ids
+                        .iter()
+                        .all(|replaced_split_id| {
+                            previously_followed_split_ids.contains(replaced_split_id)
+                        });
+                if is_replacement {
+                    debug!(split_id = %split_id, "Skipping replacement split.");
+                } else {
+                    new_splits.push(split_metadata);
+                }
+            }
+            self.followed_split_ids.insert(split_id);
+        }
+        new_splits
+    }
+}
+
+/// Streams the hits of `search_request`, following the index as new splits are published.
+///
+/// The last `max_hits` matching documents are streamed first, then all the matching documents
+/// of each newly published split. Hits are streamed oldest first. The stream ends on the first
+/// error, or when the receiver is dropped.
+///
+/// The metadata of the index is fetched once, then handed to `search_splits_fn` along with the
+/// new splits of each poll.
+fn tail_search<F, Fut>(
+    search_request: SearchRequest,
+    metastore: Arc<dyn Metastore>,
+    poll_interval: Duration,
+    search_splits_fn: F,
+) -> ReceiverStream<crate::Result<Hit>>
+where
+    F: Fn(SearchRequest, Arc<IndexMetadata>, Vec<SplitMetadata>) -> Fut + Send + 'static,
+    Fut: Future<Output = crate::Result<SearchResponse>> + Send + 'static,
+{
+    let (hit_sender, hit_receiver) = mpsc::channel(TAIL_CHANNEL_CAPACITY);
+    tokio::spawn(async move {
+        let mut search_request = search_request;
+        let index_metadata =
+            match convert_tail_request_bounds(&mut search_request, &*metastore).await {
+                Ok(index_metadata) => Arc::new(index_metadata),
+                Err(error) => {
+                    let _ = hit_sender.send(Err(error)).await;
+                    return;
+                }
+            };
+        let mut tail_cursor = TailCursor::default();
+        let mut is_first_poll = true;
+        let mut interval = tokio::time::interval(poll_interval);
+        loop {
+            interval.tick().await;
+            if hit_sender.is_closed() {
+                return;
+            }
+            let new_splits = match list_relevant_splits(&search_request, &*metastore).await {
+                Ok(published_splits) => tail_cursor.advance(published_splits),
+                Err(error) => {
+                    let _ = hit_sender.send(Err(error)).await;
+                    return;
+                }
+            };
+            let max_hits = if is_first_poll {
+                search_request.max_hits
+            } else {
+                new_splits
+                    .iter()
+                    .map(|split_metadata| split_metadata.num_docs as u64)
+                    .sum()
+            };
+            is_first_poll = false;
+            if new_splits.is_empty() || max_hits == 0 {
+                continue;
+            }
+            let tail_request = SearchRequest {
+                max_hits,
+                start_offset: 0,
+                ..search_request.clone()
+            };
+            let search_response =
+                match search_splits_fn(tail_request, index_metadata.clone(), new_splits).await {
+                    Ok(search_response) => search_response,
+                    Err(error) => {
+                        let _ = hit_sender.send(Err(error)).await;
+                        return;
+                    }
+                };
+            for hit in search_response.hits.into_iter().rev() {
+                if hit_sender.send(Ok(hit)).await.is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    ReceiverStream::new(hit_receiver)
+}
+
+fn validate_tail_request(search_request: &SearchRequest) -> crate::Result<()> {
+    if search_request.terms_lookup.is_some() {
+        return Err(crate::SearchError::InvalidQuery(
+            "Terms lookups are not supported in tail mode.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Converts the bounds of the request like the other searches do, and returns the metadata of
+/// the index.
+async fn convert_tail_request_bounds(
+    search_request: &mut SearchRequest,
+    metastore: &dyn Metastore,
+) -> crate::Result<IndexMetadata> {
+    let index_metadata = metastore.index_metadata(&search_request.index_id).await?;
+    convert_timestamp_bounds(
+        &index_metadata,
+        &mut search_request.start_timestamp,
+        &mut search_request.end_timestamp,
+    );
+    search_request.query =
+        parse_range_bounds_in_query(&search_request.query, &index_metadata.doc_mapping)?;
+    Ok(index_metadata)
+}
+
+/// Performs a distributed tail search.
+/// See also `[tail_search]`.
+pub fn root_search_tail(
+    mut search_request: SearchRequest,
+    metastore: Arc<dyn Metastore>,
+    cluster_client: ClusterClient,
+    client_pool: SearchClientPool,
+    poll_interval: Duration,
+) -> crate::Result<ReceiverStream<crate::Result<Hit>>> {
+    resolve_search_request_times(&mut search_request, now_timestamp())?;
+    validate_tail_request(&search_request)?;
+    let search_metastore = metastore.clone();
+    Ok(tail_search(
+        search_request,
+        metastore,
+        poll_interval,
+        move |tail_request, index_metadata, split_metadatas| {
+            let metastore = search_metastore.clone();
+            let cluster_client = cluster_client.clone();
+            let client_pool = client_pool.clone();
+            async move {
+                root_search_splits(
+                    &tail_request,
+                    &index_metadata,
+                    split_metadatas,
+                    &*metastore,
+                    &cluster_client,
+                    &client_pool,
+                )
+                .await
+            }
+        },
+    ))
+}
+
+/// Performs a tail search on the current node.
+/// See also `[tail_search]`.
+pub fn single_node_search_tail(
+    mut search_request: SearchRequest,
+    metastore: Arc<dyn Metastore>,
+    storage_resolver: StorageUriResolver,
+    poll_interval: Duration,
+) -> crate::Result<ReceiverStream<crate::Result<Hit>>> {
+    resolve_search_request_times(&mut search_request, now_timestamp())?;
+    validate_tail_request(&search_request)?;
+    Ok(tail_search(
+        search_request,
+        metastore,
+        poll_interval,
+        move |tail_request, index_metadata, split_metadatas| {
+            let storage_resolver = storage_resolver.clone();
+            async move {
+                single_node_search_splits(
+                    &tail_request,
+                    &index_metadata,
+                    &split_metadatas,
+                    storage_resolver,
+                )
+                .await
+            }
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::RangeInclusive;
+
+    use quickwit_indexing::TestSandbox;
+    use serde_json::json;
+    use tokio_stream::StreamExt;
+
+    use super::*;
+
+    fn mock_split_metadata(split_id: &str, time_range: RangeInclusive<i64>) -> SplitMetadata {
+        SplitMetadata {
+            time_range: Some(time_range),
+            ..SplitMetadata::new(split_id.to_string())
+        }
+    }
+
+    fn mock_merged_split_metadata(
+        split_id: &str,
+        time_range: RangeInclusive<i64>,
+        replaced_split_ids: &[&str],
+    ) -> SplitMetadata {
+        SplitMetadata {
+            replaced_split_ids: replaced_split_ids
+                .iter()
+                .map(|split_id| split_id.to_string())
+                .collect(),
+            ..mock_split_metadata(split_id, time_range)
+        }
+    }
+
+    fn split_ids(split_metadatas: &[SplitMetadata]) -> Vec<&str> {
+        split_metadatas
+            .iter()
+            .map(|split_metadata| split_metadata.split_id())
+            .collect()
+    }
+
+    #[test]
+    fn test_tail_cursor() {
+        let mut tail_cursor = TailCursor::default();
+        let new_splits = tail_cursor.advance(vec![
+            mock_split_metadata("split-1", 0..=9),
+            mock_split_metadata("split-2", 10..=19),
+        ]);
+        assert_eq!(split_ids(&new_splits), vec!["split-1", "split-2"]);
+
+        let new_splits = tail_cursor.advance(vec![
+            mock_split_metadata("split-1", 0..=9),
+            mock_split_metadata("split-2", 10..=19),
+        ]);
+        assert!(new_splits.is_empty());
+
+        let new_splits = tail_cursor.advance(vec![
+            mock_merged_split_metadata("split-3", 0..=19, &["split-1", "split-2"]),
+            mock_split_metadata("split-4", 20..=29),
+        ]);
+        assert_eq!(split_ids(&new_splits), vec!["split-4"]);
+
+        let new_splits = tail_cursor.advance(vec![
+            mock_split_metadata("split-3", 0..=19),
+            mock_split_metadata("split-4", 20..=29),
+        ]);
+        assert!(new_splits.is_empty());
+    }
+
+    #[test]
+    fn test_tail_cursor_merge_and_new_split_in_same_poll() {
+        let mut tail_cursor = TailCursor::default();
+        let new_splits = tail_cursor.advance(vec![
+            mock_split_metadata("split-1", 0..=9),
+            mock_split_metadata("split-2", 10..=19),
+        ]);
+        assert_eq!(split_ids(&new_splits), vec!["split-1", "split-2"]);
+
+        // `split-1` and `split-2` are merged into `split-3` while `split-4` and `split-5` are
+        // published. `split-4` overlaps the time range of the merged splits, and `split-5` has
+        // no time range: both must be streamed.
+        let new_splits = tail_cursor.advance(vec![
+            mock_merged_split_metadata("split-3", 0..=19, &["split-1", "split-2"]),
+            mock_split_metadata("split-4", 5..=15),
+            SplitMetadata::new("split-5".to_string()),
+        ]);
+        assert_eq!(split_ids(&new_splits), vec!["split-4", "split-5"]);
+    }
+
+    #[test]
+    fn test_tail_cursor_merge_of_unseen_split() {
+        let mut tail_cursor = TailCursor::default();
+        let new_splits = tail_cursor.advance(vec![mock_split_metadata("split-1", 0..=9)]);
+        assert_eq!(split_ids(&new_splits), vec!["split-1"]);
+
+        // `split-2` was published and merged with `split-1` between two polls.
+        let new_splits = tail_cursor.advance(vec![mock_merged_split_metadata(
+            "split-3",
+            0..=19,
+            &["split-1", "split-2"],
+        )]);
+        assert_eq!(split_ids(&new_splits), vec!["split-3"]);
+    }
+
+    #[test]
+    fn test_tail_cursor_without_time_range() {
+        let mut tail_cursor = TailCursor::default();
+        let new_splits = tail_cursor.advance(vec![SplitMetadata::new("split-1".to_string())]);
+        assert_eq!(split_ids(&new_splits), vec!["split-1"]);
+
+        let new_splits = tail_cursor.advance(vec![
+            SplitMetadata::new("split-1".to_string()),
+            SplitMetadata::new("split-2".to_string()),
+        ]);
+        assert_eq!(split_ids(&new_splits), vec!["split-2"]);
+
+        let merged_split_metadata = SplitMetadata {
+            replaced_split_ids: ["split-1".to_string(), "split-2".to_string()]
+                .into_iter()
+                .collect(),
+            ..SplitMetadata::new("split-3".to_string())
+        };
+        let new_splits = tail_cursor.advance(vec![
+            merged_split_metadata,
+            SplitMetadata::new("split-4".to_string()),
+        ]);
+        assert_eq!(split_ids(&new_splits), vec!["split-4"]);
+    }
+
+    #[tokio::test]
+    async fn test_single_node_search_tail() -> anyhow::Result<()> {
+        let index_id = "single-node-search-tail";
+        let doc_mapping_yaml = r#"
+            field_mappings:
+              - name: body
+                type: text
+              - name: ts
+                type: i64
+                fast: true
+        "#;
+        let indexing_settings_json = r#"{
+            "timestamp_field": "ts",
+            "sort_field": "ts",
+            "sort_order": "desc"
+        }"#;
+        let test_sandbox = TestSandbox::create(
+            index_id,
+            doc_mapping_yaml,
+            indexing_settings_json,
+            &["body"],
+        )
+        .await?;
+        test_sandbox
+            .add_documents(vec![
+                json!({"body": "info @ t:1", "ts": 1}),
+                json!({"body": "error @ t:2", "ts": 2}),
+                json!({"body": "info @ t:3", "ts": 3}),
+                json!({"body": "info @ t:4", "ts": 4}),
+            ])
+            .await?;
+        let search_request = SearchRequest {
+            index_id: index_id.to_string(),
+            query: "info".to_string(),
+            max_hits: 2,
+            ..Default::default()
+        };
+        let mut hit_stream = single_node_search_tail(
+            search_request,
+            test_sandbox.metastore(),
+            test_sandbox.storage_uri_resolver(),
+            Duration::from_millis(50),
+        )?;
+        assert!(hit_stream.next().await.unwrap()?.json.contains("t:3"));
+        assert!(hit_stream.next().await.unwrap()?.json.contains("t:4"));
+
+        test_sandbox
+            .add_documents(vec![
+                json!({"body": "info @ t:5", "ts": 5}),
+                json!({"body": "error @ t:6", "ts": 6}),
+                json!({"body": "info @ t:7", "ts": 7}),
+            ])
+            .await?;
+        assert!(hit_stream.next().await.unwrap()?.json.contains("t:5"));
+        assert!(hit_stream.next().await.unwrap()?.json.contains("t:7"));
+        Ok(())
+    }
+}
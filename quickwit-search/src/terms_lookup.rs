@@ -0,0 +1,285 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeSet;
+use std::future::Future;
+
+use quickwit_doc_mapper::{validate_field_mapping_name, validate_query_syntax};
+use quickwit_proto::{SearchRequest, SearchResponse, TermsLookup};
+use serde_json::Value as JsonValue;
+
+use crate::SearchError;
+
+/// Maximum number of documents a lookup query may match.
+pub const TERMS_LOOKUP_MAX_DOCS: u64 = 10_000;
+
+/// Runs the lookup query of `search_request` with `search_fn` and rewrites the request
+/// so that it only matches documents whose target field holds one of the values found.
+///
+/// Returns `None` if the lookup query did not return any value, in which case the
+/// search request cannot match any document.
+///
+/// The terms filter is spliced into the query text, so the query and the target field are
+/// validated first: a query that does not parse on its own could otherwise escape the
+/// parentheses it is wrapped in, and match documents the filter excludes.
+pub(crate) async fn resolve_terms_lookup<F, Fut>(
+    search_request: &SearchRequest,
+    terms_lookup: &TermsLookup,
+    search_fn: F,
+) -> crate::Result<Option<SearchRequest>>
+where
+    F: FnOnce(SearchRequest) -> Fut,
+    Fut: Future<Output = crate::Result<SearchResponse>>,
+{
+    validate_query_syntax(&search_request.query).map_err(|error| {
+        SearchError::InvalidQuery(format!(
+            "Failed to parse query `{}`: {}",
+            search_request.query, error
+        ))
+    })?;
+    validate_field_mapping_name(&terms_lookup.target_field).map_err(|error| {
+        SearchError::InvalidQuery(format!("Invalid terms lookup target field: {}", error))
+    })?;
+    let lookup_request = SearchRequest {
+        index_id: terms_lookup.index_id.clone(),
+        query: terms_lookup.query.clone(),
+        max_hits: TERMS_LOOKUP_MAX_DOCS,
+        ..Default::default()
+    };
+    let lookup_response = search_fn(lookup_request).await?;
+    if lookup_response.num_hits > TERMS_LOOKUP_MAX_DOCS {
+        return Err(SearchError::InvalidQuery(format!(
+            "The terms lookup query matches {} documents, the limit is {}.",
+            lookup_response.num_hits, TERMS_LOOKUP_MAX_DOCS
+        )));
+    }
+    let mut terms = BTreeSet::new();
+    for hit in &lookup_response.hits {
+        let doc: JsonValue = serde_json::from_str(&hit.json).map_err(|err| {
+            SearchError::InternalError(format!(
+                "Failed to deserialize document `{}` to JSON: `{}`.",
+                hit.json, err
+            ))
+        })?;
+        let field_values = match doc.get(&terms_lookup.lookup_field) {
+            Some(JsonValue::Array(field_values)) => field_values,
+            _ => continue,
+        };
+        for field_value in field_values {
+            terms.insert(term_query_value(field_value)?);
+        }
+        if terms.len() as u64 > terms_lookup.max_terms {
+            return Err(SearchError::InvalidQuery(format!(
+                "The terms lookup query returns more than {} distinct values.",
+                terms_lookup.max_terms
+            )));
+        }
+    }
+    if terms.is_empty() {
+        return Ok(None);
+    }
+    let terms_query = terms
+        .iter()
+        .map(|term| format!("{}:{}", terms_lookup.target_field, term))
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    Ok(Some(SearchRequest {
+        query: format!("({}) AND ({})", search_request.query, terms_query),
+        terms_lookup: None,
+        ..search_request.clone()
+    }))
+}
+
+/// Formats a field value as a term of the query language. The value is always quoted, so that
+/// the operators and special characters it holds, such as a leading `-` or a `)`, are matched
+/// literally. The query language has no escape sequence for a double quote within a quoted
+/// term, so such values are rejected.
+fn term_query_value(field_value: &JsonValue) -> crate::Result<String> {
+    let text = match field_value {
+        JsonValue::String(text) => text.clone(),
+        JsonValue::Number(number) => number.to_string(),
+        other => {
+            return Err(SearchError::InvalidQuery(format!(
+                "The terms lookup value `{}` is not a string or a number.",
+                other
+            )))
+        }
+    };
+    if text.contains('"') {
+        return Err(SearchError::InvalidQuery(format!(
+            "The terms lookup value `{}` contains a double quote.",
+            text
+        )));
+    }
+    Ok(format!("\"{}\"", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_proto::Hit;
+
+    use super::*;
+
+    fn search_response_with_docs(docs: &[&str]) -> SearchResponse {
+        SearchResponse {
+            num_hits: docs.len() as u64,
+            hits: docs
+                .iter()
+                .map(|doc| Hit {
+                    json: doc.to_string(),
+                    partial_hit: None,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn terms_lookup(max_terms: u64) -> TermsLookup {
+        TermsLookup {
+            index_id: "alerts".to_string(),
+            query: "severity:high".to_string(),
+            lookup_field: "ip".to_string(),
+            target_field: "client_ip".to_string(),
+            max_terms,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_terms_lookup() -> crate::Result<()> {
+        let search_request = SearchRequest {
+            index_id: "access-logs".to_string(),
+            query: "status:500".to_string(),
+            max_hits: 10,
+            terms_lookup: Some(terms_lookup(10)),
+            ..Default::default()
+        };
+        let terms_lookup = search_request.terms_lookup.clone().unwrap();
+        let resolved_request = resolve_terms_lookup(
+            &search_request,
+            &terms_lookup,
+            |lookup_request| async move {
+                assert_eq!(lookup_request.index_id, "alerts");
+                assert_eq!(lookup_request.query, "severity:high");
+                Ok(search_response_with_docs(&[
+                    r#"{"ip": ["10.0.0.2"]}"#,
+                    r#"{"ip": ["10.0.0.1", "10.0.0.2"]}"#,
+                    r#"{"host": ["foo"]}"#,
+                ]))
+            },
+        )
+        .await?
+        .unwrap();
+        assert_eq!(
+            resolved_request,
+            SearchRequest {
+                index_id: "access-logs".to_string(),
+                query: r#"(status:500) AND (client_ip:"10.0.0.1" OR client_ip:"10.0.0.2")"#
+                    .to_string(),
+                max_hits: 10,
+                ..Default::default()
+            }
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_terms_lookup_no_values() -> crate::Result<()> {
+        let search_request = SearchRequest {
+            query: "status:500".to_string(),
+            terms_lookup: Some(terms_lookup(10)),
+            ..Default::default()
+        };
+        let terms_lookup = search_request.terms_lookup.clone().unwrap();
+        let resolved_request_opt =
+            resolve_terms_lookup(&search_request, &terms_lookup, |_| async {
+                Ok(search_response_with_docs(&[]))
+            })
+            .await?;
+        assert!(resolved_request_opt.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_terms_lookup_too_many_values() {
+        let search_request = SearchRequest {
+            query: "status:500".to_string(),
+            terms_lookup: Some(terms_lookup(1)),
+            ..Default::default()
+        };
+        let terms_lookup = search_request.terms_lookup.clone().unwrap();
+        let resolve_result = resolve_terms_lookup(&search_request, &terms_lookup, |_| async {
+            Ok(search_response_with_docs(&[r#"{"ip": [1, 2]}"#]))
+        })
+        .await;
+        assert!(matches!(resolve_result, Err(SearchError::InvalidQuery(_))));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_terms_lookup_quotes_values() -> crate::Result<()> {
+        let search_request = SearchRequest {
+            query: "status:500".to_string(),
+            terms_lookup: Some(terms_lookup(10)),
+            ..Default::default()
+        };
+        let terms_lookup = search_request.terms_lookup.clone().unwrap();
+        let resolved_request = resolve_terms_lookup(&search_request, &terms_lookup, |_| async {
+            Ok(search_response_with_docs(&[r#"{"ip": [-1, "a) OR (b"]}"#]))
+        })
+        .await?
+        .unwrap();
+        assert_eq!(
+            resolved_request.query,
+            r#"(status:500) AND (client_ip:"-1" OR client_ip:"a) OR (b")"#
+        );
+
+        let resolve_result = resolve_terms_lookup(&search_request, &terms_lookup, |_| async {
+            Ok(search_response_with_docs(&[r#"{"ip": ["a\" OR \"b"]}"#]))
+        })
+        .await;
+        assert!(matches!(resolve_result, Err(SearchError::InvalidQuery(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_terms_lookup_rejects_unbalanced_query_and_invalid_target_field() {
+        let search_request = SearchRequest {
+            query: "status:500) OR (status:200".to_string(),
+            terms_lookup: Some(terms_lookup(10)),
+            ..Default::default()
+        };
+        let terms_lookup = search_request.terms_lookup.clone().unwrap();
+        let resolve_result = resolve_terms_lookup(&search_request, &terms_lookup, |_| async {
+            Ok(search_response_with_docs(&[r#"{"ip": ["10.0.0.1"]}"#]))
+        })
+        .await;
+        assert!(matches!(resolve_result, Err(SearchError::InvalidQuery(_))));
+
+        let search_request = SearchRequest {
+            query: "status:500".to_string(),
+            ..search_request
+        };
+        let mut terms_lookup = terms_lookup;
+        terms_lookup.target_field = "client_ip:* OR client_ip".to_string();
+        let resolve_result = resolve_terms_lookup(&search_request, &terms_lookup, |_| async {
+            Ok(search_response_with_docs(&[r#"{"ip": ["10.0.0.1"]}"#]))
+        })
+        .await;
+        assert!(matches!(resolve_result, Err(SearchError::InvalidQuery(_))));
+    }
+}
@@ -0,0 +1,496 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::future::Future;
+
+use quickwit_config::VersioningSettings;
+use quickwit_proto::{Hit, SearchRequest, SearchResponse, SortOrder};
+use serde_json::Value as JsonValue;
+
+use crate::SearchError;
+
+/// Number of hits fetched per page for each requested hit, to make up for the superseded versions
+/// removed from the results.
+const VERSIONING_OVERFETCH_FACTOR: u64 = 2;
+
+/// Maximum number of documents a versions lookup query may return.
+const VERSIONS_LOOKUP_MAX_DOCS: u64 = 10_000;
+
+/// Doc ID and version of a hit, as read from its JSON document.
+struct HitVersion {
+    doc_id: String,
+    version: i128,
+}
+
+/// Runs `search_request` with `search_fn` and removes from its hits the documents superseded by
+/// a more recent version of the same doc ID, which may not have been merged away yet.
+///
+/// The hits are fetched page by page until the requested page is full or the hits run out. The
+/// latest versions of the doc IDs of each page are found with lookup queries. Hits without a doc
+/// ID or a version are kept as is. `num_hits` still counts all the versions matching the query.
+pub(crate) async fn search_latest_versions<F, Fut>(
+    search_request: &SearchRequest,
+    versioning: &VersioningSettings,
+    search_fn: F,
+) -> crate::Result<SearchResponse>
+where
+    F: Fn(SearchRequest) -> Fut,
+    Fut: Future<Output = crate::Result<SearchResponse>>,
+{
+    let num_hits_to_return = search_request
+        .start_offset
+        .checked_add(search_request.max_hits)
+        .ok_or_else(|| {
+            SearchError::InvalidArgument(format!(
+                "`start_offset` ({}) plus `max_hits` ({}) overflows.",
+                search_request.start_offset, search_request.max_hits
+            ))
+        })?;
+    let page_num_hits = num_hits_to_return.saturating_mul(VERSIONING_OVERFETCH_FACTOR);
+    let mut search_response_opt: Option<SearchResponse> = None;
+    let mut latest_hits = Vec::new();
+    let mut returned_doc_ids = HashSet::new();
+    let mut page_start_offset = 0;
+    loop {
+        let page_request = SearchRequest {
+            start_offset: page_start_offset,
+            max_hits: page_num_hits,
+            ..search_request.clone()
+        };
+        let mut page_response = search_fn(page_request).await?;
+        let page_hits = std::mem::take(&mut page_response.hits);
+        let is_last_page = (page_hits.len() as u64) < page_num_hits;
+        let hit_versions: Vec<Option<HitVersion>> = page_hits
+            .iter()
+            .map(|hit| hit_version(hit, versioning))
+            .collect::<crate::Result<_>>()?;
+        let latest_versions =
+            lookup_latest_versions(search_request, versioning, &hit_versions, &search_fn).await?;
+        for (hit, hit_version_opt) in page_hits.into_iter().zip(hit_versions) {
+            let is_latest = match &hit_version_opt {
+                Some(hit_version) => {
+                    hit_version.version >= latest_versions[&hit_version.doc_id]
+                        // Several documents may hold the latest version, only the first one is
+                        // returned.
+                        && returned_doc_ids.insert(hit_version.doc_id.clone())
+                }
+                None => true,
+            };
+            if is_latest {
+                latest_hits.push(hit);
+            }
+        }
+        search_response_opt.get_or_insert(page_response);
+        if is_last_page || latest_hits.len() as u64 >= num_hits_to_return {
+            break;
+        }
+        page_start_offset = match page_start_offset.checked_add(page_num_hits) {
+            Some(next_page_start_offset) => next_page_start_offset,
+            None => break,
+        };
+    }
+    let mut search_response = search_response_opt.unwrap_or_default();
+    search_response.hits = latest_hits
+        .into_iter()
+        .skip(search_request.start_offset as usize)
+        .take(search_request.max_hits as usize)
+        .collect();
+    Ok(search_response)
+}
+
+/// Returns the latest version of each doc ID of `hit_versions`.
+///
+/// The versions of all the doc IDs are looked up at once, sorted by decreasing version, so the
+/// first version returned for a doc ID is its latest one. When the lookup is truncated to
+/// [`VERSIONS_LOOKUP_MAX_DOCS`] documents, the doc IDs missing from it are looked up again.
+async fn lookup_latest_versions<F, Fut>(
+    search_request: &SearchRequest,
+    versioning: &VersioningSettings,
+    hit_versions: &[Option<HitVersion>],
+    search_fn: &F,
+) -> crate::Result<HashMap<String, i128>>
+where
+    F: Fn(SearchRequest) -> Fut,
+    Fut: Future<Output = crate::Result<SearchResponse>>,
+{
+    let mut latest_versions: HashMap<String, i128> = HashMap::new();
+    let mut doc_ids_to_lookup: BTreeSet<&str> = hit_versions
+        .iter()
+        .flatten()
+        .map(|hit_version| hit_version.doc_id.as_str())
+        .collect();
+    while !doc_ids_to_lookup.is_empty() {
+        let lookup_query = doc_ids_to_lookup
+            .iter()
+            .map(|doc_id| format!("{}:{}", versioning.doc_id_field, doc_id))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        let lookup_request = SearchRequest {
+            index_id: search_request.index_id.clone(),
+            query: lookup_query,
+            max_hits: VERSIONS_LOOKUP_MAX_DOCS,
+            sort_by_field: Some(versioning.version_field.clone()),
+            sort_order: Some(SortOrder::Desc as i32),
+            ..Default::default()
+        };
+        let lookup_response = search_fn(lookup_request).await?;
+        let num_doc_ids_to_lookup = doc_ids_to_lookup.len();
+        for hit in &lookup_response.hits {
+            if let Some(hit_version) = hit_version(hit, versioning)? {
+                doc_ids_to_lookup.remove(hit_version.doc_id.as_str());
+                let latest_version = latest_versions
+                    .entry(hit_version.doc_id)
+                    .or_insert(hit_version.version);
+                *latest_version = (*latest_version).max(hit_version.version);
+            }
+        }
+        let is_truncated = lookup_response.num_hits > lookup_response.hits.len() as u64;
+        if !is_truncated {
+            break;
+        }
+        if doc_ids_to_lookup.len() == num_doc_ids_to_lookup {
+            return Err(SearchError::InternalError(format!(
+                "Failed to look up the latest versions of doc IDs {:?}: the lookup returned {} \
+                 documents without any of them.",
+                doc_ids_to_lookup,
+                lookup_response.hits.len()
+            )));
+        }
+    }
+    // The doc IDs of the hits are always found, unless they are missing from the index.
+    for hit_version in hit_versions.iter().flatten() {
+        latest_versions
+            .entry(hit_version.doc_id.clone())
+            .or_insert(hit_version.version);
+    }
+    Ok(latest_versions)
+}
+
+/// Reads the doc ID and the version of a hit. Returns `None` if one of them is missing.
+fn hit_version(hit: &Hit, versioning: &VersioningSettings) -> crate::Result<Option<HitVersion>> {
+    let doc: JsonValue = serde_json::from_str(&hit.json).map_err(|err| {
+        SearchError::InternalError(format!(
+            "Failed to deserialize document `{}` to JSON: `{}`.",
+            hit.json, err
+        ))
+    })?;
+    let doc_id = match first_integer(&doc, &versioning.doc_id_field)? {
+        Some(doc_id) => doc_id,
+        None => return Ok(None),
+    };
+    let version = match first_integer(&doc, &versioning.version_field)? {
+        Some(version) => version,
+        None => return Ok(None),
+    };
+    Ok(Some(HitVersion {
+        doc_id: doc_id.to_string(),
+        version,
+    }))
+}
+
+/// Returns the first value of an integer field of a document, or `None` if the document does not
+/// have the field. The index config checks that the versioning fields are `u64` or `i64` fields,
+/// so any other value is an error.
+fn first_integer(doc: &JsonValue, field_name: &str) -> crate::Result<Option<i128>> {
+    let field_value = match doc.get(field_name) {
+        Some(JsonValue::Array(field_values)) => match field_values.first() {
+            Some(field_value) => field_value,
+            None => return Ok(None),
+        },
+        Some(JsonValue::Null) | None => return Ok(None),
+        Some(field_value) => field_value,
+    };
+    field_value
+        .as_i64()
+        .map(i128::from)
+        .or_else(|| field_value.as_u64().map(i128::from))
+        .map(Some)
+        .ok_or_else(|| {
+            SearchError::InternalError(format!(
+                "Versioning field `{}` holds `{}` instead of an integer.",
+                field_name, field_value
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search_response_with_docs(num_hits: u64, docs: &[&str]) -> SearchResponse {
+        SearchResponse {
+            num_hits,
+            hits: docs
+                .iter()
+                .map(|doc| Hit {
+                    json: doc.to_string(),
+                    partial_hit: None,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn versioning() -> VersioningSettings {
+        VersioningSettings {
+            doc_id_field: "id".to_string(),
+            version_field: "version".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_latest_versions() -> crate::Result<()> {
+        let search_request = SearchRequest {
+            index_id: "users".to_string(),
+            query: "country:fr".to_string(),
+            max_hits: 2,
+            ..Default::default()
+        };
+        let search_response =
+            search_latest_versions(&search_request, &versioning(), |request| async move {
+                if request.query == "country:fr" {
+                    assert_eq!(request.max_hits, 4);
+                    return Ok(search_response_with_docs(
+                        5,
+                        &[
+                            r#"{"id": [1], "version": [1]}"#,
+                            r#"{"id": [2], "version": [3]}"#,
+                            r#"{"name": ["no id"]}"#,
+                            r#"{"id": [3], "version": [1]}"#,
+                        ],
+                    ));
+                }
+                assert_eq!(request.query, "id:1 OR id:2 OR id:3");
+                assert_eq!(request.sort_by_field.as_deref(), Some("version"));
+                Ok(search_response_with_docs(
+                    4,
+                    &[
+                        r#"{"id": [1], "version": [2]}"#,
+                        r#"{"id": [2], "version": [3]}"#,
+                        r#"{"id": [3], "version": [1]}"#,
+                        r#"{"id": [1], "version": [1]}"#,
+                    ],
+                ))
+            })
+            .await?;
+        assert_eq!(search_response.num_hits, 5);
+        let hits: Vec<&str> = search_response
+            .hits
+            .iter()
+            .map(|hit| hit.json.as_str())
+            .collect();
+        assert_eq!(
+            hits,
+            vec![r#"{"id": [2], "version": [3]}"#, r#"{"name": ["no id"]}"#]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_latest_versions_skips_duplicates_and_offset() -> crate::Result<()> {
+        let search_request = SearchRequest {
+            index_id: "users".to_string(),
+            query: "*".to_string(),
+            start_offset: 1,
+            max_hits: 1,
+            ..Default::default()
+        };
+        let search_response =
+            search_latest_versions(&search_request, &versioning(), |request| async move {
+                let docs = [
+                    r#"{"id": [1], "version": [2]}"#,
+                    r#"{"id": [1], "version": [2]}"#,
+                    r#"{"id": [2], "version": [-1]}"#,
+                ];
+                if request.query == "*" {
+                    assert_eq!(request.start_offset, 0);
+                    assert_eq!(request.max_hits, 4);
+                }
+                Ok(search_response_with_docs(3, &docs))
+            })
+            .await?;
+        assert_eq!(search_response.hits.len(), 1);
+        assert_eq!(
+            search_response.hits[0].json,
+            r#"{"id": [2], "version": [-1]}"#
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_latest_versions_without_versioned_hits() -> crate::Result<()> {
+        let search_request = SearchRequest {
+            index_id: "users".to_string(),
+            query: "*".to_string(),
+            max_hits: 10,
+            ..Default::default()
+        };
+        let search_response =
+            search_latest_versions(&search_request, &versioning(), |request| async move {
+                assert_eq!(request.query, "*");
+                Ok(search_response_with_docs(1, &[r#"{"name": ["foo"]}"#]))
+            })
+            .await?;
+        assert_eq!(search_response.hits.len(), 1);
+        Ok(())
+    }
+    #[tokio::test]
+    async fn test_search_latest_versions_fetches_pages_until_full() -> crate::Result<()> {
+        let search_request = SearchRequest {
+            index_id: "users".to_string(),
+            query: "*".to_string(),
+            max_hits: 2,
+            ..Default::default()
+        };
+        let search_response =
+            search_latest_versions(&search_request, &versioning(), |request| async move {
+                match (request.query.as_str(), request.start_offset) {
+                    ("*", 0) => {
+                        assert_eq!(request.max_hits, 4);
+                        Ok(search_response_with_docs(
+                            6,
+                            &[
+                                r#"{"id": [1], "version": [1]}"#,
+                                r#"{"id": [1], "version": [2]}"#,
+                                r#"{"id": [1], "version": [2]}"#,
+                                r#"{"id": [2], "version": [1]}"#,
+                            ],
+                        ))
+                    }
+                    ("*", 4) => Ok(search_response_with_docs(
+                        6,
+                        &[
+                            r#"{"id": [3], "version": [1]}"#,
+                            r#"{"id": [4], "version": [1]}"#,
+                        ],
+                    )),
+                    ("id:1 OR id:2", _) => Ok(search_response_with_docs(
+                        2,
+                        &[
+                            r#"{"id": [1], "version": [2]}"#,
+                            r#"{"id": [2], "version": [2]}"#,
+                        ],
+                    )),
+                    ("id:3 OR id:4", _) => Ok(search_response_with_docs(
+                        2,
+                        &[
+                            r#"{"id": [3], "version": [1]}"#,
+                            r#"{"id": [4], "version": [1]}"#,
+                        ],
+                    )),
+                    (query, start_offset) => {
+                        panic!("Unexpected request `{}` at {}.", query, start_offset)
+                    }
+                }
+            })
+            .await?;
+        assert_eq!(search_response.num_hits, 6);
+        let hits: Vec<&str> = search_response
+            .hits
+            .iter()
+            .map(|hit| hit.json.as_str())
+            .collect();
+        assert_eq!(
+            hits,
+            vec![
+                r#"{"id": [1], "version": [2]}"#,
+                r#"{"id": [3], "version": [1]}"#
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_latest_versions_looks_up_truncated_doc_ids() -> crate::Result<()> {
+        let search_request = SearchRequest {
+            index_id: "users".to_string(),
+            query: "*".to_string(),
+            max_hits: 2,
+            ..Default::default()
+        };
+        let search_response =
+            search_latest_versions(&search_request, &versioning(), |request| async move {
+                match request.query.as_str() {
+                    "*" => Ok(search_response_with_docs(
+                        2,
+                        &[
+                            r#"{"id": [1], "version": [3]}"#,
+                            r#"{"id": [2], "version": [1]}"#,
+                        ],
+                    )),
+                    // The versions of doc ID 1 fill the lookup.
+                    "id:1 OR id:2" => Ok(search_response_with_docs(
+                        VERSIONS_LOOKUP_MAX_DOCS + 2,
+                        &[r#"{"id": [1], "version": [3]}"#],
+                    )),
+                    "id:2" => Ok(search_response_with_docs(
+                        2,
+                        &[
+                            r#"{"id": [2], "version": [2]}"#,
+                            r#"{"id": [2], "version": [1]}"#,
+                        ],
+                    )),
+                    query => panic!("Unexpected query `{}`.", query),
+                }
+            })
+            .await?;
+        let hits: Vec<&str> = search_response
+            .hits
+            .iter()
+            .map(|hit| hit.json.as_str())
+            .collect();
+        assert_eq!(hits, vec![r#"{"id": [1], "version": [3]}"#]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_latest_versions_errors() {
+        let search_request = SearchRequest {
+            index_id: "users".to_string(),
+            query: "*".to_string(),
+            start_offset: u64::MAX,
+            max_hits: 1,
+            ..Default::default()
+        };
+        let search_result = search_latest_versions(&search_request, &versioning(), |_| async {
+            Ok(SearchResponse::default())
+        })
+        .await;
+        assert!(matches!(
+            search_result,
+            Err(SearchError::InvalidArgument(_))
+        ));
+
+        let search_request = SearchRequest {
+            index_id: "users".to_string(),
+            query: "*".to_string(),
+            max_hits: 1,
+            ..Default::default()
+        };
+        let search_result = search_latest_versions(&search_request, &versioning(), |_| async {
+            Ok(search_response_with_docs(
+                1,
+                &[r#"{"id": ["one"], "version": [1]}"#],
+            ))
+        })
+        .await;
+        assert!(matches!(search_result, Err(SearchError::InternalError(_))));
+    }
+}
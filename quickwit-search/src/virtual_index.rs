@@ -0,0 +1,316 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::future::Future;
+
+use futures::future::join_all;
+use quickwit_config::{build_doc_mapper, VirtualIndexConfig};
+use quickwit_doc_mapper::SortBy;
+use quickwit_metastore::IndexMetadata;
+use quickwit_proto::{Hit, SearchRequest, SearchResponse};
+use tantivy::schema::Type;
+
+use crate::search_timings::merge_leaf_search_timings;
+use crate::{partial_hit_sorting_key, SearchError};
+
+/// Checks that the hits of the indexes of a virtual index can be merged: the indexes must sort
+/// their hits the same way, and the fields they share must have the same type. Otherwise, the
+/// sorting values of their hits would not be comparable.
+pub(crate) fn validate_doc_mappings_compatibility(
+    virtual_index_id: &str,
+    index_metadatas: &[&IndexMetadata],
+) -> crate::Result<()> {
+    let mut first_sort_by_opt: Option<(&str, SortBy)> = None;
+    let mut field_types: HashMap<String, (&str, Type)> = HashMap::new();
+    for index_metadata in index_metadatas {
+        let index_id = index_metadata.index_id.as_str();
+        let doc_mapper = build_doc_mapper(
+            &index_metadata.doc_mapping,
+            &index_metadata.search_settings,
+            &index_metadata.indexing_settings,
+        )
+        .map_err(|error| {
+            SearchError::InternalError(format!(
+                "Failed to build doc mapper of index `{}`. Cause: {}",
+                index_id, error
+            ))
+        })?;
+        let sort_by = doc_mapper.sort_by();
+        if let Some((first_index_id, first_sort_by)) = &first_sort_by_opt {
+            if *first_sort_by != sort_by {
+                return Err(SearchError::InvalidArgument(format!(
+                    "Indexes `{}` and `{}` of virtual index `{}` sort their documents \
+                     differently: `{:?}` and `{:?}`.",
+                    first_index_id, index_id, virtual_index_id, first_sort_by, sort_by
+                )));
+            }
+        } else {
+            first_sort_by_opt = Some((index_id, sort_by));
+        }
+        for (_, field_entry) in doc_mapper.schema().fields() {
+            let value_type = field_entry.field_type().value_type();
+            match field_types.entry(field_entry.name().to_string()) {
+                Entry::Occupied(entry) => {
+                    let (other_index_id, other_value_type) = entry.get();
+                    if *other_value_type != value_type {
+                        return Err(SearchError::InvalidArgument(format!(
+                            "Indexes `{}` and `{}` of virtual index `{}` map field `{}` to \
+                             different types: `{:?}` and `{:?}`.",
+                            other_index_id,
+                            index_id,
+                            virtual_index_id,
+                            field_entry.name(),
+                            other_value_type,
+                            value_type
+                        )));
+                    }
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert((index_id, value_type));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Searches each index of a virtual index with `search_fn` and merges the responses. The hits
+/// are merged in the same order as the leaf responses of a concrete index.
+///
+/// The failure of an index does not fail the search: it is reported in the `errors` of the
+/// response, and the hits of the other indexes are returned. The search only fails if all the
+/// indexes fail.
+pub(crate) async fn search_virtual_index<F, Fut>(
+    search_request: &SearchRequest,
+    virtual_index: &VirtualIndexConfig,
+    search_fn: F,
+) -> crate::Result<SearchResponse>
+where
+    F: Fn(SearchRequest) -> Fut,
+    Fut: Future<Output = crate::Result<SearchResponse>>,
+{
+    // A snapshot token pins the splits of a single index, so none is taken on a virtual index.
+    if search_request.snapshot.is_some() {
+        return Err(SearchError::InvalidQuery(
+            "Snapshots are not supported on virtual indexes.".to_string(),
+        ));
+    }
+    // Each index returns its top `start_offset + max_hits` hits, and pagination is applied
+    // on the merged hits.
+    let num_hits_per_index = search_request.start_offset + search_request.max_hits;
+    let search_futures = virtual_index.indexes.iter().map(|index_id| {
+        search_fn(SearchRequest {
+            index_id: index_id.clone(),
+            max_hits: num_hits_per_index,
+            start_offset: 0,
+            pin_splits: false,
+            ..search_request.clone()
+        })
+    });
+    let search_results = join_all(search_futures).await;
+
+    let mut num_hits = 0;
+    let mut hits: Vec<Hit> = Vec::new();
+    let mut errors: Vec<String> = Vec::new();
+    let mut timings = Vec::new();
+    let mut query_rewrites = Vec::new();
+    let mut split_profiles = Vec::new();
+    let mut first_error_opt = None;
+    let mut num_failed_indexes = 0;
+    for (index_id, search_result) in virtual_index.indexes.iter().zip(search_results) {
+        match search_result {
+            Ok(search_response) => {
+                num_hits += search_response.num_hits;
+                hits.extend(search_response.hits);
+                errors.extend(
+                    search_response
+                        .errors
+                        .into_iter()
+                        .map(|error| format!("Index `{}`: {}", index_id, error)),
+                );
+                timings.extend(search_response.timings);
+                query_rewrites.extend(search_response.query_rewrites);
+                split_profiles.extend(search_response.split_profiles);
+            }
+            Err(search_error) => {
+                num_failed_indexes += 1;
+                errors.push(format!("Index `{}`: {}", index_id, search_error));
+                first_error_opt.get_or_insert(search_error);
+            }
+        }
+    }
+    if num_failed_indexes == virtual_index.indexes.len() {
+        if let Some(first_error) = first_error_opt {
+            return Err(first_error);
+        }
+    }
+    hits.sort_by(|left_hit, right_hit| {
+        let left_key = left_hit.partial_hit.as_ref().map(partial_hit_sorting_key);
+        let right_key = right_hit.partial_hit.as_ref().map(partial_hit_sorting_key);
+        left_key.cmp(&right_key)
+    });
+    let hits = hits
+        .into_iter()
+        .skip(search_request.start_offset as usize)
+        .take(search_request.max_hits as usize)
+        .collect();
+    Ok(SearchResponse {
+        num_hits,
+        hits,
+        elapsed_time_micros: 0,
+        errors,
+        timings: merge_leaf_search_timings(timings.iter().map(Some)),
+        query_rewrites,
+        split_profiles,
+        snapshot: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_proto::PartialHit;
+
+    use super::*;
+    use crate::SearchError;
+
+    fn mock_hit(split_id: &str, sorting_field_value: u64) -> Hit {
+        Hit {
+            json: format!(r#"{{"split": ["{}"]}}"#, split_id),
+            partial_hit: Some(PartialHit {
+                sorting_field_value,
+                split_id: split_id.to_string(),
+                segment_ord: 0,
+                doc_id: 0,
+            }),
+        }
+    }
+
+    fn virtual_index() -> VirtualIndexConfig {
+        VirtualIndexConfig {
+            index_id: "logs".to_string(),
+            indexes: vec!["logs-a".to_string(), "logs-b".to_string()],
+        }
+    }
+
+    async fn mock_search(search_request: SearchRequest) -> crate::Result<SearchResponse> {
+        assert_eq!(search_request.start_offset, 0);
+        assert_eq!(search_request.max_hits, 3);
+        match search_request.index_id.as_str() {
+            "logs-a" => Ok(SearchResponse {
+                num_hits: 2,
+                hits: vec![mock_hit("split-a", 5), mock_hit("split-a", 1)],
+                ..Default::default()
+            }),
+            "logs-b" => Ok(SearchResponse {
+                num_hits: 1,
+                hits: vec![mock_hit("split-b", 3)],
+                ..Default::default()
+            }),
+            _ => Err(SearchError::IndexDoesNotExist {
+                index_id: search_request.index_id,
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_virtual_index() -> crate::Result<()> {
+        let search_request = SearchRequest {
+            index_id: "logs".to_string(),
+            query: "*".to_string(),
+            max_hits: 2,
+            start_offset: 1,
+            ..Default::default()
+        };
+        let search_response =
+            search_virtual_index(&search_request, &virtual_index(), mock_search).await?;
+        assert_eq!(search_response.num_hits, 3);
+        let sorting_values: Vec<u64> = search_response
+            .hits
+            .iter()
+            .map(|hit| hit.partial_hit.as_ref().unwrap().sorting_field_value)
+            .collect();
+        assert_eq!(sorting_values, vec![3, 1]);
+        assert!(search_response.errors.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_doc_mappings_compatibility() {
+        let index_metadata = |index_id: &str| {
+            IndexMetadata::for_test(index_id, &format!("file:///path/to/index/{}", index_id))
+        };
+        let logs_a = index_metadata("logs-a");
+        let logs_b = index_metadata("logs-b");
+        validate_doc_mappings_compatibility("logs", &[&logs_a, &logs_b]).unwrap();
+
+        let mut unsorted_logs_b = index_metadata("logs-b");
+        unsorted_logs_b.indexing_settings.sort_field = None;
+        let error =
+            validate_doc_mappings_compatibility("logs", &[&logs_a, &unsorted_logs_b]).unwrap_err();
+        assert!(matches!(error, SearchError::InvalidArgument(_)));
+
+        let mut retyped_logs_b = index_metadata("logs-b");
+        let field_mappings = &mut retyped_logs_b.doc_mapping.field_mappings;
+        let timestamp_mapping_type = field_mappings
+            .iter()
+            .find(|field_mapping| field_mapping.name == "timestamp")
+            .unwrap()
+            .mapping_type
+            .clone();
+        field_mappings
+            .iter_mut()
+            .find(|field_mapping| field_mapping.name == "response_time")
+            .unwrap()
+            .mapping_type = timestamp_mapping_type;
+        let error =
+            validate_doc_mappings_compatibility("logs", &[&logs_a, &retyped_logs_b]).unwrap_err();
+        assert!(error.to_string().contains("field `response_time`"));
+    }
+
+    #[tokio::test]
+    async fn test_search_virtual_index_failure_isolation() -> crate::Result<()> {
+        let search_request = SearchRequest {
+            index_id: "logs".to_string(),
+            query: "*".to_string(),
+            max_hits: 3,
+            ..Default::default()
+        };
+        let mut virtual_index = virtual_index();
+        virtual_index.indexes.push("logs-missing".to_string());
+        let search_response =
+            search_virtual_index(&search_request, &virtual_index, mock_search).await?;
+        assert_eq!(search_response.num_hits, 3);
+        assert_eq!(search_response.errors.len(), 1);
+        assert!(search_response.errors[0].starts_with("Index `logs-missing`"));
+
+        let virtual_index = VirtualIndexConfig {
+            index_id: "logs".to_string(),
+            indexes: vec!["logs-missing".to_string()],
+        };
+        let search_result =
+            search_virtual_index(&search_request, &virtual_index, mock_search).await;
+        assert!(matches!(
+            search_result,
+            Err(SearchError::IndexDoesNotExist { .. })
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,301 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use quickwit_config::{AuthConfig, QuickwitConfig, VirtualIndexConfig};
+use quickwit_proto::Hit;
+use serde_json::Value as JsonValue;
+use tokio::sync::watch;
+use warp::{Filter, Rejection};
+
+/// Rejection of the REST requests without a valid API key.
+#[derive(Debug)]
+pub(crate) struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+/// Authenticates the REST requests with the auth config of the node, following the config
+/// reloads. Without auth config, every request is let through and no field is masked.
+#[derive(Clone, Default)]
+pub struct Authenticator {
+    config_rx_opt: Option<watch::Receiver<QuickwitConfig>>,
+}
+
+impl Authenticator {
+    pub fn new(config_rx: watch::Receiver<QuickwitConfig>) -> Self {
+        Self {
+            config_rx_opt: Some(config_rx),
+        }
+    }
+
+    /// Identifies the caller by the bearer token of the `Authorization` header of the request.
+    fn authenticate(&self, authorization_opt: Option<&str>) -> Result<Caller, Rejection> {
+        let config_rx = match &self.config_rx_opt {
+            Some(config_rx) => config_rx,
+            None => return Ok(Caller::default()),
+        };
+        let config = config_rx.borrow();
+        let auth_config = match &config.auth {
+            Some(auth_config) => auth_config,
+            None => return Ok(Caller::default()),
+        };
+        let role = authorization_opt
+            .and_then(|authorization| authorization.strip_prefix("Bearer "))
+            .and_then(|api_key| auth_config.role(api_key.trim()))
+            .ok_or_else(|| warp::reject::custom(Unauthorized))?;
+        Ok(Caller {
+            role_opt: Some(role.to_string()),
+            auth_config: auth_config.clone(),
+            virtual_indexes: config.searcher_config.virtual_indexes.clone(),
+        })
+    }
+
+    /// Extracts the caller of the request. Rejects the request if the node has an auth config
+    /// and the API key of the request is missing or unknown.
+    pub fn caller(&self) -> impl Filter<Extract = (Caller,), Error = Rejection> + Clone {
+        let authenticator = self.clone();
+        warp::header::optional::<String>("authorization").and_then(
+            move |authorization_opt: Option<String>| {
+                futures::future::ready(authenticator.authenticate(authorization_opt.as_deref()))
+            },
+        )
+    }
+
+    /// Rejects the requests without a valid API key, if the node has an auth config.
+    pub fn require_auth(&self) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+        self.caller().map(|_caller: Caller| ()).untuple_one()
+    }
+}
+
+/// Caller of a REST request, along with the field masks applying to it.
+#[derive(Clone, Debug, Default)]
+pub struct Caller {
+    /// Role granted by the API key of the request, `None` if the node has no auth config.
+    role_opt: Option<String>,
+    auth_config: AuthConfig,
+    virtual_indexes: Vec<VirtualIndexConfig>,
+}
+
+impl Caller {
+    /// Returns the fields hidden from the caller in the documents of the index `index_id`. The
+    /// searches on a virtual index hide the fields masked on any of its indexes.
+    pub fn masked_fields(&self, index_id: &str) -> Vec<String> {
+        let role = match &self.role_opt {
+            Some(role) => role,
+            None => return Vec::new(),
+        };
+        let mut masked_fields = self.auth_config.masked_fields(index_id, role);
+        if let Some(virtual_index) = self
+            .virtual_indexes
+            .iter()
+            .find(|virtual_index| virtual_index.index_id == index_id)
+        {
+            for member_index_id in &virtual_index.indexes {
+                masked_fields.extend(self.auth_config.masked_fields(member_index_id, role));
+            }
+            masked_fields.sort();
+            masked_fields.dedup();
+        }
+        masked_fields
+    }
+}
+
+/// Returns true if the values of `field_name` are hidden by the masks, either because the field
+/// is masked or because it is nested in a masked object.
+pub(crate) fn is_masked(field_name: &str, masked_fields: &[String]) -> bool {
+    masked_fields.iter().any(|masked_field| {
+        field_name == masked_field
+            || field_name
+                .strip_prefix(masked_field.as_str())
+                .map(|suffix| suffix.starts_with('.'))
+                .unwrap_or(false)
+    })
+}
+
+/// Removes the masked fields from the document.
+pub(crate) fn redact_doc(doc: &mut JsonValue, masked_fields: &[String]) {
+    for masked_field in masked_fields {
+        remove_field(doc, masked_field);
+    }
+}
+
+/// Removes the masked fields from the document of the hit.
+pub(crate) fn redact_hit(hit: &mut Hit, masked_fields: &[String]) -> serde_json::Result<()> {
+    if masked_fields.is_empty() {
+        return Ok(());
+    }
+    let mut doc: JsonValue = serde_json::from_str(&hit.json)?;
+    redact_doc(&mut doc, masked_fields);
+    hit.json = serde_json::to_string(&doc)?;
+    Ok(())
+}
+
+/// Removes the field at the dotted path `field_path`, whether the document holds it as a
+/// nested object or as a flat key. The values of the hits are wrapped in arrays, which are
+/// searched through.
+fn remove_field(value: &mut JsonValue, field_path: &str) {
+    match value {
+        JsonValue::Array(values) => {
+            for value in values {
+                remove_field(value, field_path);
+            }
+        }
+        JsonValue::Object(object) => {
+            object.remove(field_path);
+            if let Some((parent_field, child_path)) = field_path.split_once('.') {
+                if let Some(child) = object.get_mut(parent_field) {
+                    remove_field(child, child_path);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_config::{ApiKeyConfig, FieldMaskConfig};
+    use serde_json::json;
+
+    use super::*;
+
+    fn auth_config() -> AuthConfig {
+        AuthConfig {
+            api_keys: vec![
+                ApiKeyConfig {
+                    key: "admin-key".to_string(),
+                    role: "admin".to_string(),
+                },
+                ApiKeyConfig {
+                    key: "analyst-key".to_string(),
+                    role: "analyst".to_string(),
+                },
+            ],
+            field_masks: vec![FieldMaskConfig {
+                index_id: "logs-team-a".to_string(),
+                roles: vec!["analyst".to_string()],
+                fields: vec!["user.email".to_string()],
+            }],
+        }
+    }
+
+    fn authenticator(auth_opt: Option<AuthConfig>) -> Authenticator {
+        let mut config = QuickwitConfig {
+            auth: auth_opt,
+            ..Default::default()
+        };
+        config.searcher_config.virtual_indexes = vec![VirtualIndexConfig {
+            index_id: "logs".to_string(),
+            indexes: vec!["logs-team-a".to_string(), "logs-team-b".to_string()],
+        }];
+        let (_config_tx, config_rx) = watch::channel(config);
+        Authenticator::new(config_rx)
+    }
+
+    #[tokio::test]
+    async fn test_authenticator_without_auth_config() {
+        let caller = warp::test::request()
+            .filter(&authenticator(None).caller())
+            .await
+            .unwrap();
+        assert!(caller.masked_fields("logs-team-a").is_empty());
+
+        let caller = warp::test::request()
+            .filter(&Authenticator::default().caller())
+            .await
+            .unwrap();
+        assert!(caller.masked_fields("logs-team-a").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_authenticator() {
+        let authenticator = authenticator(Some(auth_config()));
+        let rejection = warp::test::request()
+            .filter(&authenticator.caller())
+            .await
+            .unwrap_err();
+        assert!(rejection.find::<Unauthorized>().is_some());
+
+        let rejection = warp::test::request()
+            .header("Authorization", "Bearer unknown-key")
+            .filter(&authenticator.caller())
+            .await
+            .unwrap_err();
+        assert!(rejection.find::<Unauthorized>().is_some());
+
+        let admin = warp::test::request()
+            .header("Authorization", "Bearer admin-key")
+            .filter(&authenticator.caller())
+            .await
+            .unwrap();
+        assert!(admin.masked_fields("logs-team-a").is_empty());
+
+        let analyst = warp::test::request()
+            .header("Authorization", "Bearer analyst-key")
+            .filter(&authenticator.caller())
+            .await
+            .unwrap();
+        assert_eq!(analyst.masked_fields("logs-team-a"), vec!["user.email"]);
+        assert!(analyst.masked_fields("logs-team-b").is_empty());
+        assert_eq!(analyst.masked_fields("logs"), vec!["user.email"]);
+    }
+
+    #[test]
+    fn test_is_masked() {
+        let masked_fields = vec!["user".to_string(), "ip".to_string()];
+        assert!(is_masked("user", &masked_fields));
+        assert!(is_masked("user.email", &masked_fields));
+        assert!(!is_masked("username", &masked_fields));
+        assert!(!is_masked("severity", &masked_fields));
+    }
+
+    #[test]
+    fn test_redact_doc() {
+        let masked_fields = vec![
+            "user.email".to_string(),
+            "client.ip".to_string(),
+            "token".to_string(),
+        ];
+        let mut doc = json!({
+            "body": ["login"],
+            "token": ["abc"],
+            "user": [{"name": ["alice"], "email": ["alice@example.com"]}],
+            "client.ip": ["10.0.0.1"],
+        });
+        redact_doc(&mut doc, &masked_fields);
+        assert_eq!(
+            doc,
+            json!({
+                "body": ["login"],
+                "user": [{"name": ["alice"]}],
+            })
+        );
+    }
+
+    #[test]
+    fn test_redact_hit() {
+        let mut hit = Hit {
+            json: r#"{"body":["login"],"user":{"email":"alice@example.com"}}"#.to_string(),
+            ..Default::default()
+        };
+        redact_hit(&mut hit, &[]).unwrap();
+        assert!(hit.json.contains("alice@example.com"));
+        redact_hit(&mut hit, &["user.email".to_string()]).unwrap();
+        assert_eq!(hit.json, r#"{"body":["login"],"user":{}}"#);
+    }
+}
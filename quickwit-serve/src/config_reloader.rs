@@ -0,0 +1,189 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::future::Future;
+use std::sync::Arc;
+
+use anyhow::Context;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use quickwit_config::{
+    set_searcher_config_instance, ConfigReloadReport, QuickwitConfig, SearcherConfig,
+};
+use tokio::sync::{watch, Mutex};
+use tracing::{error, info};
+
+type LoadConfigFn =
+    Box<dyn Fn() -> BoxFuture<'static, anyhow::Result<QuickwitConfig>> + Send + Sync>;
+
+type ApplySearcherConfigFn = Box<dyn Fn(SearcherConfig) + Send + Sync>;
+
+/// Reloads the node config from its source, applies the settings that can change while the
+/// node is running, and publishes the resulting config to the subsystems following it.
+pub struct ConfigReloader {
+    load_config_fn_opt: Option<LoadConfigFn>,
+    // Applies the reloaded searcher config, by default to the searcher config of the node.
+    apply_searcher_config_fn: ApplySearcherConfigFn,
+    config_tx: watch::Sender<QuickwitConfig>,
+    // Keeping a receiver around guarantees that publishing a config never fails.
+    config_rx: watch::Receiver<QuickwitConfig>,
+    reload_lock: Mutex<()>,
+}
+
+impl ConfigReloader {
+    /// Creates a reloader for a config that was not loaded from a file and hence cannot be
+    /// reloaded.
+    pub fn new(config: QuickwitConfig) -> Self {
+        let (config_tx, config_rx) = watch::channel(config);
+        Self {
+            load_config_fn_opt: None,
+            apply_searcher_config_fn: Box::new(set_searcher_config_instance),
+            config_tx,
+            config_rx,
+            reload_lock: Mutex::new(()),
+        }
+    }
+
+    /// Creates a reloader that loads the new config with `load_config_fn`.
+    pub fn with_loader<F, Fut>(config: QuickwitConfig, load_config_fn: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<QuickwitConfig>> + Send + 'static,
+    {
+        let mut config_reloader = Self::new(config);
+        config_reloader.load_config_fn_opt = Some(Box::new(move || load_config_fn().boxed()));
+        config_reloader
+    }
+
+    /// Applies the reloaded searcher configs with `apply_searcher_config_fn` instead of setting
+    /// the searcher config of the node, which is shared by the tests running in parallel.
+    #[cfg(test)]
+    pub(crate) fn with_searcher_config_applier<F>(mut self, apply_searcher_config_fn: F) -> Self
+    where F: Fn(SearcherConfig) + Send + Sync + 'static {
+        self.apply_searcher_config_fn = Box::new(apply_searcher_config_fn);
+        self
+    }
+
+    /// Returns the current config of the node.
+    pub fn config(&self) -> QuickwitConfig {
+        self.config_rx.borrow().clone()
+    }
+
+    /// Returns a receiver notified each time the config is reloaded.
+    pub fn subscribe(&self) -> watch::Receiver<QuickwitConfig> {
+        self.config_rx.clone()
+    }
+
+    /// Loads the config again and applies its reloadable settings. The returned report lists
+    /// the changed settings, distinguishing those that only take effect after a restart.
+    pub async fn reload(&self) -> anyhow::Result<ConfigReloadReport> {
+        let load_config_fn = self
+            .load_config_fn_opt
+            .as_ref()
+            .context("The node was started without a config file, there is no config to reload.")?;
+        let _reload_guard = self.reload_lock.lock().await;
+        let new_config = load_config_fn().await?;
+        let (reloaded_config, report) = self.config().reload(&new_config);
+        (self.apply_searcher_config_fn)(reloaded_config.searcher_config.clone());
+        self.config_tx
+            .send(reloaded_config)
+            .expect("The reloader holds a config receiver.");
+        info!(
+            applied_settings = ?report.applied_settings,
+            restart_required_settings = ?report.restart_required_settings,
+            "Config reloaded."
+        );
+        Ok(report)
+    }
+}
+
+/// Reloads the config each time the process receives a `SIGHUP` signal.
+#[cfg(unix)]
+pub async fn reload_config_on_sighup(config_reloader: Arc<ConfigReloader>) -> anyhow::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sighup_stream = signal(SignalKind::hangup())?;
+    while sighup_stream.recv().await.is_some() {
+        info!("Received SIGHUP, reloading config.");
+        if let Err(error) = config_reloader.reload().await {
+            error!(error = ?error, "Failed to reload config.");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex as StdMutex;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_config_reloader() -> anyhow::Result<()> {
+        let config = QuickwitConfig::default();
+        let mut new_config = config.clone();
+        new_config.rest_listen_port = 1111;
+        new_config.searcher_config.max_num_concurrent_split_streams = 7;
+        let num_loads = Arc::new(AtomicUsize::new(0));
+        let num_loads_clone = num_loads.clone();
+        let applied_searcher_config = Arc::new(StdMutex::new(None));
+        let applied_searcher_config_clone = applied_searcher_config.clone();
+        let config_reloader = ConfigReloader::with_loader(config.clone(), move || {
+            num_loads_clone.fetch_add(1, Ordering::SeqCst);
+            futures::future::ready(Ok(new_config.clone()))
+        })
+        .with_searcher_config_applier(move |searcher_config| {
+            *applied_searcher_config_clone.lock().unwrap() = Some(searcher_config);
+        });
+        let config_rx = config_reloader.subscribe();
+        let report = config_reloader.reload().await?;
+        assert_eq!(num_loads.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            report.applied_settings,
+            vec!["searcher.max_num_concurrent_split_streams"]
+        );
+        assert_eq!(report.restart_required_settings, vec!["rest_listen_port"]);
+
+        let reloaded_config = config_rx.borrow().clone();
+        assert_eq!(reloaded_config, config_reloader.config());
+        assert_eq!(reloaded_config.rest_listen_port, config.rest_listen_port);
+        assert_eq!(
+            reloaded_config
+                .searcher_config
+                .max_num_concurrent_split_streams,
+            7
+        );
+        assert_eq!(
+            applied_searcher_config
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map(|searcher_config| searcher_config.max_num_concurrent_split_streams),
+            Some(7)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_config_reloader_without_loader() {
+        let config_reloader = ConfigReloader::new(QuickwitConfig::default());
+        assert!(config_reloader.reload().await.is_err());
+    }
+}
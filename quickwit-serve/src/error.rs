@@ -18,6 +18,7 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use quickwit_cluster::error::ClusterError;
+use quickwit_metastore::MetastoreError;
 use quickwit_search::SearchError;
 use serde::ser::SerializeMap;
 use thiserror::Error;
@@ -36,6 +37,43 @@ pub enum ApiError {
     SearchError(#[from] SearchError),
     #[error("Cluster error. {0}.")]
     ClusterError(#[from] ClusterError),
+    #[error("Failed to reload config: {0}.")]
+    ConfigReloadError(String),
+    #[error("Failed to change log filter: {0}.")]
+    LogFilterError(String),
+    #[error("Index `{index_id}` is not write-blocked.")]
+    WriteBlockNotFound { index_id: String },
+    #[error("Source `{source_id}` of index `{index_id}` is not paused.")]
+    SourceNotPaused { index_id: String, source_id: String },
+    #[error(
+        "Source `{source_id}` of index `{index_id}` must be paused on this node before resetting \
+         its checkpoint."
+    )]
+    SourceNotPausedForReset { index_id: String, source_id: String },
+    #[error("Failed to resolve the checkpoint: {0}.")]
+    CheckpointResolutionError(String),
+    #[error("Metastore error. {0}")]
+    MetastoreError(#[from] MetastoreError),
+    #[error("The ingest API is only available on the nodes running the indexer service.")]
+    IngestApiNotAvailable,
+    #[error("Index `{index_id}` has no `ingest_api` source running on this node.")]
+    IngestQueueNotFound { index_id: String },
+    #[error("Index `{index_id}` is write-blocked on this node.")]
+    IndexWriteBlocked { index_id: String },
+    #[error(
+        "A request to index `{index_id}` with idempotency key `{idempotency_key}` is already in \
+         progress."
+    )]
+    IdempotencyKeyInFlight {
+        index_id: String,
+        idempotency_key: String,
+    },
+    #[error("Failed to ingest documents: {0}.")]
+    IngestError(String),
+    #[error("Missing or invalid API key.")]
+    Unauthorized,
+    #[error("Field `{field_name}` is masked for the role of the API key.")]
+    FieldMasked { field_name: String },
     #[error("Route not found")]
     NotFound,
 }
@@ -48,9 +86,31 @@ impl ApiError {
                 SearchError::InternalError(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
                 SearchError::StorageResolverError(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
                 SearchError::InvalidQuery(_) => http::StatusCode::BAD_REQUEST,
+                SearchError::InvalidArgument(_) => http::StatusCode::BAD_REQUEST,
+                SearchError::SnapshotExpired { .. } => http::StatusCode::GONE,
             },
             ApiError::ClusterError(_cluster_error) => http::StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::InvalidArgument(_err) => StatusCode::BAD_REQUEST,
+            ApiError::ConfigReloadError(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::LogFilterError(_) => http::StatusCode::BAD_REQUEST,
+            ApiError::WriteBlockNotFound { .. } => http::StatusCode::NOT_FOUND,
+            ApiError::SourceNotPaused { .. } => http::StatusCode::NOT_FOUND,
+            ApiError::SourceNotPausedForReset { .. } => http::StatusCode::CONFLICT,
+            ApiError::CheckpointResolutionError(_) => http::StatusCode::BAD_REQUEST,
+            ApiError::MetastoreError(metastore_error) => match metastore_error {
+                MetastoreError::IndexDoesNotExist { .. }
+                | MetastoreError::SourceDoesNotExist { .. }
+                | MetastoreError::JobDoesNotExist { .. } => http::StatusCode::NOT_FOUND,
+                MetastoreError::JobNotRunning { .. } => http::StatusCode::CONFLICT,
+                _ => http::StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            ApiError::IngestApiNotAvailable => http::StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::IngestQueueNotFound { .. } => http::StatusCode::NOT_FOUND,
+            ApiError::IndexWriteBlocked { .. } => http::StatusCode::TOO_MANY_REQUESTS,
+            ApiError::IdempotencyKeyInFlight { .. } => http::StatusCode::CONFLICT,
+            ApiError::IngestError(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Unauthorized => http::StatusCode::UNAUTHORIZED,
+            ApiError::FieldMasked { .. } => http::StatusCode::FORBIDDEN,
             ApiError::NotFound => http::StatusCode::NOT_FOUND,
         }
     }
@@ -0,0 +1,360 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use parquet::basic::{ConvertedType, Repetition, Type as PhysicalType};
+use parquet::column::writer::ColumnWriter;
+use parquet::data_type::ByteArray;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::{FileWriter, RowGroupWriter, SerializedFileWriter};
+use parquet::schema::types::Type as SchemaType;
+use parquet::util::cursor::InMemoryWriteableCursor;
+use quickwit_metastore::{Job, JobKind, MetastoreResult};
+use quickwit_search::{now_timestamp, resolve_relative_times_in_query, SearchService};
+use quickwit_storage::quickwit_storage_uri_resolver;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::auth::redact_doc;
+use crate::jobs::{JobContext, JobService};
+
+/// Number of documents fetched per search request, and written per Parquet file.
+const EXPORT_PAGE_NUM_DOCS: u64 = 10_000;
+
+fn default_export_max_hits() -> u64 {
+    100_000
+}
+
+/// Request of an export job, passed as the JSON body of the export REST API.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportRequest {
+    /// Query text. The query language is that of tantivy.
+    pub query: String,
+    /// Fields to search on.
+    #[serde(default)]
+    pub search_fields: Vec<String>,
+    /// If set, restricts search to documents with a `timestamp >= start_timestamp`.
+    pub start_timestamp: Option<i64>,
+    /// If set, restricts search to documents with a `timestamp < end_timestamp``.
+    pub end_timestamp: Option<i64>,
+    /// Maximum number of documents to export (by default 100_000).
+    #[serde(default = "default_export_max_hits")]
+    pub max_hits: u64,
+    /// Columns to export. All the stored fields are exported if empty.
+    #[serde(default)]
+    pub fields: Vec<String>,
+    /// Storage URI of the directory where the Parquet files are written.
+    pub output_uri: String,
+}
+
+/// Details of an export job, reported in the `details` field of its record.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportJobDetails {
+    pub num_docs_exported: u64,
+    /// URIs of the Parquet files written so far.
+    pub files: Vec<String>,
+}
+
+/// Submits a job exporting the documents of the index `index_id` matching `export_request`,
+/// without the `masked_fields`.
+pub async fn submit_export_job<TSearchService: SearchService>(
+    job_service: &JobService,
+    search_service: Arc<TSearchService>,
+    index_id: String,
+    export_request: ExportRequest,
+    masked_fields: Vec<String>,
+) -> MetastoreResult<Job> {
+    let job_index_id = index_id.clone();
+    job_service
+        .submit(&job_index_id, JobKind::Export, move |ctx| async move {
+            run_export_job(
+                ctx,
+                &*search_service,
+                index_id,
+                export_request,
+                &masked_fields,
+            )
+            .await
+        })
+        .await
+}
+
+async fn run_export_job<TSearchService: SearchService>(
+    ctx: JobContext,
+    search_service: &TSearchService,
+    index_id: String,
+    export_request: ExportRequest,
+    masked_fields: &[String],
+) -> anyhow::Result<()> {
+    let storage = quickwit_storage_uri_resolver().resolve(&export_request.output_uri)?;
+    let mut details = ExportJobDetails::default();
+    let mut start_offset = 0;
+    // The pages are searched on the splits pinned by the first page, whose snapshot keeps them
+    // from being garbage collected until the export completes.
+    let mut snapshot_opt: Option<String> = None;
+    // The relative times of the query are resolved once, so that all the pages search the same
+    // time window.
+    let query = resolve_relative_times_in_query(&export_request.query, now_timestamp())?;
+    while start_offset < export_request.max_hits {
+        let max_hits = EXPORT_PAGE_NUM_DOCS.min(export_request.max_hits - start_offset);
+        let search_request = quickwit_proto::SearchRequest {
+            index_id: index_id.clone(),
+            query: query.clone(),
+            search_fields: export_request.search_fields.clone(),
+            start_timestamp: export_request.start_timestamp,
+            end_timestamp: export_request.end_timestamp,
+            max_hits,
+            start_offset,
+            sort_order: None,
+            sort_by_field: None,
+            terms_lookup: None,
+            preferred_searcher_nodes: Vec::new(),
+            profile: false,
+            pin_splits: snapshot_opt.is_none(),
+            snapshot: snapshot_opt.clone(),
+            start_time_expression: None,
+            end_time_expression: None,
+        };
+        let search_response = search_service.root_search(search_request).await?;
+        if snapshot_opt.is_none() {
+            snapshot_opt = search_response.snapshot.clone();
+        }
+        let num_docs = search_response.hits.len() as u64;
+        if num_docs == 0 {
+            break;
+        }
+        let mut docs = search_response
+            .hits
+            .into_iter()
+            .map(|hit| serde_json::from_str(&hit.json))
+            .collect::<Result<Vec<JsonValue>, _>>()?;
+        for doc in &mut docs {
+            redact_doc(doc, masked_fields);
+        }
+        let parquet_bytes = docs_to_parquet(&docs, &export_request.fields)?;
+        let file_path = PathBuf::from(format!(
+            "{}/part-{:05}.parquet",
+            ctx.job_id(),
+            start_offset / EXPORT_PAGE_NUM_DOCS
+        ));
+        storage.put(&file_path, Box::new(parquet_bytes)).await?;
+        let file_uri = format!(
+            "{}/{}",
+            export_request.output_uri.trim_end_matches('/'),
+            file_path.display()
+        );
+        start_offset += num_docs;
+        details.num_docs_exported += num_docs;
+        details.files.push(file_uri);
+        let num_docs_to_export = export_request.max_hits.min(search_response.num_hits);
+        let progress = 100.0 * start_offset as f64 / num_docs_to_export.max(1) as f64;
+        ctx.report_progress(progress, serde_json::to_value(&details)?)
+            .await?;
+        if num_docs < max_hits || start_offset >= search_response.num_hits {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// The hits returned by the search API hold the values of each field in an array.
+/// Single values are unwrapped, and multiple values are exported as a JSON string.
+fn field_value(doc: &JsonValue, field_name: &str) -> JsonValue {
+    match doc.get(field_name) {
+        Some(JsonValue::Array(values)) if values.len() == 1 => values[0].clone(),
+        Some(JsonValue::Array(values)) if values.is_empty() => JsonValue::Null,
+        Some(values @ JsonValue::Array(_)) => JsonValue::from(values.to_string()),
+        Some(value) => value.clone(),
+        None => JsonValue::Null,
+    }
+}
+
+/// Writes the documents into an in-memory Parquet file. The type of each column is
+/// inferred from its values: integers, floats, and booleans are written as such, and any other
+/// value is written as a string.
+fn docs_to_parquet(docs: &[JsonValue], fields: &[String]) -> anyhow::Result<Vec<u8>> {
+    let column_names: Vec<String> = if fields.is_empty() {
+        let mut seen_field_names = HashSet::new();
+        docs.iter()
+            .filter_map(|doc| doc.as_object())
+            .flat_map(|doc| doc.keys())
+            .filter(|field_name| seen_field_names.insert(field_name.to_string()))
+            .cloned()
+            .collect()
+    } else {
+        fields.to_vec()
+    };
+    let columns: Vec<Vec<JsonValue>> = column_names
+        .iter()
+        .map(|column_name| {
+            docs.iter()
+                .map(|doc| field_value(doc, column_name))
+                .collect()
+        })
+        .collect();
+    let mut schema_fields = Vec::with_capacity(columns.len());
+    for (column_name, column) in column_names.iter().zip(columns.iter()) {
+        let (physical_type, converted_type) = infer_column_type(column);
+        let schema_field = SchemaType::primitive_type_builder(column_name, physical_type)
+            .with_repetition(Repetition::OPTIONAL)
+            .with_converted_type(converted_type)
+            .build()?;
+        schema_fields.push(Arc::new(schema_field));
+    }
+    let schema = SchemaType::group_type_builder("export")
+        .with_fields(&mut schema_fields)
+        .build()?;
+    let cursor = InMemoryWriteableCursor::default();
+    let writer_props = Arc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(cursor.clone(), Arc::new(schema), writer_props)?;
+    let mut row_group_writer = writer.next_row_group()?;
+    let mut columns_it = columns.iter();
+    while let Some(mut column_writer) = row_group_writer.next_column()? {
+        let column = columns_it
+            .next()
+            .expect("The schema should have one column per exported field.");
+        write_column(&mut column_writer, column)?;
+        row_group_writer.close_column(column_writer)?;
+    }
+    writer.close_row_group(row_group_writer)?;
+    writer.close()?;
+    Ok(cursor.data())
+}
+
+fn infer_column_type(column: &[JsonValue]) -> (PhysicalType, ConvertedType) {
+    let mut values = column.iter().filter(|value| !value.is_null()).peekable();
+    if values.peek().is_none() {
+        return (PhysicalType::BYTE_ARRAY, ConvertedType::UTF8);
+    }
+    let values: Vec<&JsonValue> = values.collect();
+    if values.iter().all(|value| value.is_i64()) {
+        (PhysicalType::INT64, ConvertedType::NONE)
+    } else if values.iter().all(|value| value.is_u64()) {
+        (PhysicalType::INT64, ConvertedType::UINT_64)
+    } else if values.iter().all(|value| value.is_number()) {
+        (PhysicalType::DOUBLE, ConvertedType::NONE)
+    } else if values.iter().all(|value| value.is_boolean()) {
+        (PhysicalType::BOOLEAN, ConvertedType::NONE)
+    } else {
+        (PhysicalType::BYTE_ARRAY, ConvertedType::UTF8)
+    }
+}
+
+fn write_column(column_writer: &mut ColumnWriter, column: &[JsonValue]) -> anyhow::Result<()> {
+    let def_levels: Vec<i16> = column
+        .iter()
+        .map(|value| if value.is_null() { 0 } else { 1 })
+        .collect();
+    let values = column.iter().filter(|value| !value.is_null());
+    match column_writer {
+        ColumnWriter::BoolColumnWriter(typed_writer) => {
+            let values: Vec<bool> = values.filter_map(JsonValue::as_bool).collect();
+            typed_writer.write_batch(&values, Some(&def_levels), None)?;
+        }
+        ColumnWriter::Int64ColumnWriter(typed_writer) => {
+            // Unsigned values are stored with the same bits as their signed counterpart.
+            let values: Vec<i64> = values
+                .filter_map(|value| {
+                    value
+                        .as_i64()
+                        .or_else(|| value.as_u64().map(|val| val as i64))
+                })
+                .collect();
+            typed_writer.write_batch(&values, Some(&def_levels), None)?;
+        }
+        ColumnWriter::DoubleColumnWriter(typed_writer) => {
+            let values: Vec<f64> = values.filter_map(JsonValue::as_f64).collect();
+            typed_writer.write_batch(&values, Some(&def_levels), None)?;
+        }
+        ColumnWriter::ByteArrayColumnWriter(typed_writer) => {
+            let values: Vec<ByteArray> = values
+                .map(|value| match value {
+                    JsonValue::String(text) => ByteArray::from(text.as_str()),
+                    other => ByteArray::from(other.to_string().as_str()),
+                })
+                .collect();
+            typed_writer.write_batch(&values, Some(&def_levels), None)?;
+        }
+        _ => anyhow::bail!("Unexpected Parquet column type."),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::util::cursor::SliceableCursor;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_docs_to_parquet() -> anyhow::Result<()> {
+        let docs = vec![
+            json!({"title": ["foo"], "count": [3], "score": [1.5], "tags": ["a", "b"]}),
+            json!({"title": ["bar"], "count": [], "score": [2]}),
+        ];
+        let parquet_bytes = docs_to_parquet(&docs, &[])?;
+        let file_reader = SerializedFileReader::new(SliceableCursor::new(parquet_bytes))?;
+        let schema = file_reader.metadata().file_metadata().schema();
+        let column_types: Vec<(&str, PhysicalType)> = schema
+            .get_fields()
+            .iter()
+            .map(|field| (field.name(), field.get_physical_type()))
+            .collect();
+        assert_eq!(
+            column_types,
+            vec![
+                ("count", PhysicalType::INT64),
+                ("score", PhysicalType::DOUBLE),
+                ("tags", PhysicalType::BYTE_ARRAY),
+                ("title", PhysicalType::BYTE_ARRAY),
+            ]
+        );
+        let rows: Vec<String> = file_reader
+            .get_row_iter(None)?
+            .map(|row| row.to_string())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                r#"{count: 3, score: 1.5, tags: "[\"a\",\"b\"]", title: "foo"}"#,
+                r#"{count: null, score: 2.0, tags: null, title: "bar"}"#,
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_docs_to_parquet_selected_fields() -> anyhow::Result<()> {
+        let docs = vec![json!({"title": ["foo"], "body": ["bar"]})];
+        let parquet_bytes = docs_to_parquet(&docs, &["body".to_string()])?;
+        let file_reader = SerializedFileReader::new(SliceableCursor::new(parquet_bytes))?;
+        let schema = file_reader.metadata().file_metadata().schema();
+        assert_eq!(schema.get_fields().len(), 1);
+        assert_eq!(schema.get_fields()[0].name(), "body");
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use quickwit_indexing::{run_garbage_collect, IndexingSplitStore};
+use quickwit_metastore::{Job, JobKind, Metastore, MetastoreResult};
+use quickwit_storage::quickwit_storage_uri_resolver;
+use serde::{Deserialize, Serialize};
+
+use crate::jobs::{JobContext, JobService};
+
+fn default_grace_period_secs() -> u64 {
+    3_600
+}
+
+/// Request of a garbage collection job, passed as the JSON body of the garbage collection REST
+/// API.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct GarbageCollectionRequest {
+    /// Period after which a staged split is considered dangling (by default 1 hour).
+    #[serde(default = "default_grace_period_secs")]
+    pub grace_period_secs: u64,
+    /// If set, the job only reports the files that it would delete.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Details of a garbage collection job, reported in the `details` field of its record.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GarbageCollectionJobDetails {
+    /// Number of files deleted, or that would be deleted on a dry run.
+    pub num_deleted_files: usize,
+    /// Number of bytes deleted, or that would be deleted on a dry run.
+    pub num_deleted_bytes: u64,
+}
+
+/// Submits a job deleting the dangling splits of the index `index_id` and their files.
+pub async fn submit_garbage_collection_job(
+    job_service: &JobService,
+    index_id: String,
+    gc_request: GarbageCollectionRequest,
+) -> MetastoreResult<Job> {
+    let metastore = job_service.metastore();
+    let job_index_id = index_id.clone();
+    job_service
+        .submit(
+            &job_index_id,
+            JobKind::GarbageCollection,
+            move |ctx| async move {
+                run_garbage_collection_job(ctx, metastore, index_id, gc_request).await
+            },
+        )
+        .await
+}
+
+async fn run_garbage_collection_job(
+    ctx: JobContext,
+    metastore: Arc<dyn Metastore>,
+    index_id: String,
+    gc_request: GarbageCollectionRequest,
+) -> anyhow::Result<()> {
+    let index_uri = metastore.index_metadata(&index_id).await?.index_uri;
+    let storage = quickwit_storage_uri_resolver().resolve(&index_uri)?;
+    let split_store = IndexingSplitStore::create_with_no_local_store(storage);
+    let file_entries = run_garbage_collect(
+        &index_id,
+        split_store,
+        metastore,
+        Duration::from_secs(gc_request.grace_period_secs),
+        // Like the `gc` command, the job deletes the splits as soon as it marks them for
+        // deletion.
+        Duration::ZERO,
+        gc_request.dry_run,
+        None,
+    )
+    .await?;
+    let details = GarbageCollectionJobDetails {
+        num_deleted_files: file_entries.len(),
+        num_deleted_bytes: file_entries
+            .iter()
+            .map(|file_entry| file_entry.file_size_in_bytes)
+            .sum(),
+    };
+    ctx.report_progress(100.0, serde_json::to_value(&details)?)
+        .await
+}
@@ -19,6 +19,7 @@
 
 use std::net::SocketAddr;
 
+use quickwit_config::GrpcCompression;
 use quickwit_proto::cluster_service_server::ClusterServiceServer;
 use quickwit_proto::search_service_server::SearchServiceServer;
 use tonic::transport::Server;
@@ -28,15 +29,23 @@ use crate::grpc_adapter::cluster_adapter::GrpcClusterAdapter;
 use crate::grpc_adapter::search_adapter::GrpcSearchAdapter;
 
 /// Start gRPC service given a gRPC address and a search service and cluster service.
+/// The search service accepts gzip compressed requests, and compresses its responses with gzip
+/// if `grpc_compression` requires it. Zstd compression is applied by the search service to the
+/// leaf search response chunks, as the gRPC server only supports gzip.
 pub async fn start_grpc_service(
     grpc_addr: SocketAddr,
     search_service: GrpcSearchAdapter,
     cluster_service: GrpcClusterAdapter,
+    grpc_compression: GrpcCompression,
 ) -> anyhow::Result<()> {
-    info!(grpc_addr=?grpc_addr, "Start gRPC service.");
+    info!(grpc_addr=?grpc_addr, grpc_compression=?grpc_compression, "Start gRPC service.");
+    let mut search_service_server = SearchServiceServer::new(search_service).accept_gzip();
+    if grpc_compression == GrpcCompression::Gzip {
+        search_service_server = search_service_server.send_gzip();
+    }
     Server::builder()
         .add_service(ClusterServiceServer::new(cluster_service))
-        .add_service(SearchServiceServer::new(search_service))
+        .add_service(search_service_server)
         .serve(grpc_addr)
         .await?;
 
@@ -23,10 +23,14 @@ use async_trait::async_trait;
 use futures::TryStreamExt;
 use opentelemetry::global;
 use opentelemetry::propagation::Extractor;
+use quickwit_config::get_searcher_config_instance;
 use quickwit_proto::{
-    search_service_server as grpc, LeafSearchStreamRequest, LeafSearchStreamResponse,
+    search_service_server as grpc, LeafSearchRequest, LeafSearchResponseChunk,
+    LeafSearchStreamRequest, LeafSearchStreamResponse,
+};
+use quickwit_search::{
+    encode_leaf_search_response_chunk, split_leaf_search_response, SearchService, SearchServiceImpl,
 };
-use quickwit_search::{SearchService, SearchServiceImpl};
 use tracing::{instrument, Span};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
@@ -144,4 +148,41 @@ impl grpc::SearchService for GrpcSearchAdapter {
             .map_err(Into::<tonic::Status>::into);
         Ok(tonic::Response::new(Box::pin(leaf_search_result)))
     }
+
+    type LeafSearchChunksStream = std::pin::Pin<
+        Box<
+            dyn futures::Stream<Item = Result<LeafSearchResponseChunk, tonic::Status>>
+                + Send
+                + Sync,
+        >,
+    >;
+    #[instrument(name = "search_adapter:leaf_search_chunks", skip(self, request))]
+    async fn leaf_search_chunks(
+        &self,
+        request: tonic::Request<LeafSearchRequest>,
+    ) -> Result<tonic::Response<Self::LeafSearchChunksStream>, tonic::Status> {
+        let parent_cx =
+            global::get_text_map_propagator(|prop| prop.extract(&MetadataMap(request.metadata())));
+        Span::current().set_parent(parent_cx);
+        let leaf_search_request = request.into_inner();
+        let leaf_search_response = self
+            .0
+            .leaf_search(leaf_search_request)
+            .await
+            .map_err(Into::<tonic::Status>::into)?;
+        let searcher_config = get_searcher_config_instance();
+        let max_chunk_num_bytes = searcher_config.max_grpc_message_size.get_bytes() as usize;
+        let grpc_compression = searcher_config.grpc_compression;
+        // The chunks are built and encoded one at a time, as the gRPC server pulls them from the
+        // stream to send them.
+        let chunks = split_leaf_search_response(leaf_search_response, max_chunk_num_bytes).map(
+            move |chunk| {
+                encode_leaf_search_response_chunk(&chunk, grpc_compression)
+                    .map_err(Into::<tonic::Status>::into)
+            },
+        );
+        Ok(tonic::Response::new(Box::pin(futures::stream::iter(
+            chunks,
+        ))))
+    }
 }
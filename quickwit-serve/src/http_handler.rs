@@ -18,4 +18,21 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 pub mod cluster;
+pub mod config_reload;
+pub mod export;
+pub mod garbage_collection;
 pub mod health_check;
+pub mod ingest;
+pub mod jobs;
+pub mod live_search;
+pub mod log_filter;
+pub mod memory_usage;
+pub mod merge;
+pub mod pipeline_throughput;
+pub mod reindex;
+pub mod service_map;
+pub mod source_checkpoint;
+pub mod source_pause;
+pub mod split_heatmap;
+pub mod storage_costs;
+pub mod write_block;
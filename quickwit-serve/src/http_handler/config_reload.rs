@@ -0,0 +1,112 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use quickwit_config::ConfigReloadReport;
+use warp::{Filter, Rejection};
+
+use crate::config_reloader::ConfigReloader;
+use crate::rest::Format;
+use crate::ApiError;
+
+/// Config reload handler: reloads the node config and replies with the changed settings.
+pub fn config_reload_handler(
+    config_reloader: Arc<ConfigReloader>,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    config_reload_filter()
+        .and(warp::any().map(move || config_reloader.clone()))
+        .and_then(reload_config)
+}
+
+fn config_reload_filter() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::path!("api" / "v1" / "config" / "reload").and(warp::post())
+}
+
+async fn reload_config(
+    config_reloader: Arc<ConfigReloader>,
+) -> Result<impl warp::Reply, Infallible> {
+    Ok(Format::PrettyJson.make_reply(reload_config_endpoint(&*config_reloader).await))
+}
+
+async fn reload_config_endpoint(
+    config_reloader: &ConfigReloader,
+) -> Result<ConfigReloadReport, ApiError> {
+    let report = config_reloader
+        .reload()
+        .await
+        .map_err(|error| ApiError::ConfigReloadError(format!("{:#}", error)))?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_config::QuickwitConfig;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_config_reload_api() {
+        let config = QuickwitConfig::default();
+        let mut new_config = config.clone();
+        new_config.log_level = Some("debug".to_string());
+        new_config.peer_seeds = vec!["quickwit-searcher-0.local".to_string()];
+        let config_reloader = Arc::new(
+            ConfigReloader::with_loader(config, move || {
+                futures::future::ready(Ok(new_config.clone()))
+            })
+            .with_searcher_config_applier(|_searcher_config| {}),
+        );
+        let response = warp::test::request()
+            .method("POST")
+            .path("/api/v1/config/reload")
+            .reply(&config_reload_handler(config_reloader.clone()))
+            .await;
+        assert_eq!(response.status(), 200);
+        let report: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(
+            report,
+            serde_json::json!({
+                "appliedSettings": ["log_level"],
+                "restartRequiredSettings": ["peer_seeds"],
+            })
+        );
+        assert_eq!(
+            config_reloader.config().log_level,
+            Some("debug".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_config_reload_api_without_config_file() {
+        let config_reloader = Arc::new(ConfigReloader::new(QuickwitConfig::default()));
+        let response = warp::test::request()
+            .method("POST")
+            .path("/api/v1/config/reload")
+            .reply(&config_reload_handler(config_reloader))
+            .await;
+        assert_eq!(response.status(), 500);
+        let response = warp::test::request()
+            .path("/api/v1/config/reload")
+            .filter(&config_reload_filter())
+            .await;
+        assert!(response.is_err());
+    }
+}
@@ -0,0 +1,105 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use quickwit_search::SearchService;
+use tracing::info;
+use warp::{Filter, Rejection};
+
+use crate::auth::{Authenticator, Caller};
+use crate::export::{submit_export_job, ExportRequest};
+use crate::jobs::JobService;
+use crate::rest::Format;
+use crate::ApiError;
+
+/// Export handler: starts export jobs, whose status is then polled through the jobs API.
+pub fn export_handler<TSearchService: SearchService>(
+    search_service: Arc<TSearchService>,
+    job_service: JobService,
+    authenticator: Authenticator,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    start_export_filter()
+        .and(authenticator.caller())
+        .and(warp::any().map(move || search_service.clone()))
+        .and(warp::any().map(move || job_service.clone()))
+        .and_then(start_export)
+}
+
+fn start_export_filter() -> impl Filter<Extract = (String, ExportRequest), Error = Rejection> + Clone
+{
+    warp::path!("api" / "v1" / String / "export")
+        .and(warp::post())
+        .and(warp::body::json())
+}
+
+async fn start_export<TSearchService: SearchService>(
+    index_id: String,
+    export_request: ExportRequest,
+    caller: Caller,
+    search_service: Arc<TSearchService>,
+    job_service: JobService,
+) -> Result<impl warp::Reply, Infallible> {
+    info!(index_id = %index_id, request =? export_request, "export");
+    let masked_fields = caller.masked_fields(&index_id);
+    let job_res = submit_export_job(
+        &job_service,
+        search_service,
+        index_id,
+        export_request,
+        masked_fields,
+    )
+    .await
+    .map_err(ApiError::MetastoreError);
+    Ok(Format::PrettyJson.make_reply(job_res))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_start_export_filter() {
+        let (index_id, export_request) = warp::test::request()
+            .method("POST")
+            .path("/api/v1/my-index/export")
+            .json(&serde_json::json!({
+                "query": "severity:ERROR",
+                "fields": ["timestamp", "body"],
+                "outputUri": "s3://my-bucket/exports"
+            }))
+            .filter(&start_export_filter())
+            .await
+            .unwrap();
+        assert_eq!(index_id, "my-index");
+        assert_eq!(
+            export_request,
+            ExportRequest {
+                query: "severity:ERROR".to_string(),
+                search_fields: Vec::new(),
+                start_timestamp: None,
+                end_timestamp: None,
+                max_hits: 100_000,
+                fields: vec!["timestamp".to_string(), "body".to_string()],
+                output_uri: "s3://my-bucket/exports".to_string(),
+            }
+        );
+    }
+}
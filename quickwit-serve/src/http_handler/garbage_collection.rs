@@ -0,0 +1,80 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+use std::convert::Infallible;
+
+use tracing::info;
+use warp::{Filter, Rejection};
+
+use crate::garbage_collection::{submit_garbage_collection_job, GarbageCollectionRequest};
+use crate::jobs::JobService;
+use crate::rest::Format;
+use crate::ApiError;
+
+/// Garbage collection handler: starts garbage collection jobs, whose status is then polled
+/// through the jobs API.
+pub fn garbage_collection_handler(
+    job_service: JobService,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    start_garbage_collection_filter()
+        .and(warp::any().map(move || job_service.clone()))
+        .and_then(start_garbage_collection)
+}
+
+fn start_garbage_collection_filter(
+) -> impl Filter<Extract = (String, GarbageCollectionRequest), Error = Rejection> + Clone {
+    warp::path!("api" / "v1" / String / "garbage-collect")
+        .and(warp::post())
+        .and(warp::body::json())
+}
+
+async fn start_garbage_collection(
+    index_id: String,
+    gc_request: GarbageCollectionRequest,
+    job_service: JobService,
+) -> Result<impl warp::Reply, Infallible> {
+    info!(index_id = %index_id, request =? gc_request, "garbage-collect");
+    let job_res = submit_garbage_collection_job(&job_service, index_id, gc_request)
+        .await
+        .map_err(ApiError::MetastoreError);
+    Ok(Format::PrettyJson.make_reply(job_res))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_start_garbage_collection_filter() {
+        let (index_id, gc_request) = warp::test::request()
+            .method("POST")
+            .path("/api/v1/my-index/garbage-collect")
+            .json(&serde_json::json!({ "dryRun": true }))
+            .filter(&start_garbage_collection_filter())
+            .await
+            .unwrap();
+        assert_eq!(index_id, "my-index");
+        assert_eq!(
+            gc_request,
+            GarbageCollectionRequest {
+                grace_period_secs: 3_600,
+                dry_run: true,
+            }
+        );
+    }
+}
@@ -0,0 +1,449 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use once_cell::sync::Lazy;
+use quickwit_indexing::ingest_api::ingest_api;
+use quickwit_indexing::write_block;
+use serde::Serialize;
+use warp::{Filter, Rejection};
+
+use crate::rest::Format;
+use crate::ApiError;
+
+/// Maximum size of an ingest request body.
+const MAX_REQUEST_BODY_NUM_BYTES: u64 = 10_000_000;
+
+/// Maximum length of an idempotency key.
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// Period during which the node remembers the idempotency keys of the ingested requests, so that
+/// the retries of a request within this period are acknowledged without being ingested again.
+const IDEMPOTENCY_KEY_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// Maximum number of idempotency keys remembered by the node. Beyond it, the oldest keys are
+/// forgotten before the end of the window.
+const MAX_NUM_IDEMPOTENCY_KEYS: usize = 100_000;
+
+/// Idempotency keys seen by the node within the window, per index ID.
+static IDEMPOTENCY_KEYS: Lazy<Mutex<IdempotencyKeys>> =
+    Lazy::new(|| Mutex::new(IdempotencyKeys::with_capacity(MAX_NUM_IDEMPOTENCY_KEYS)));
+
+/// State of a request bearing an idempotency key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IdempotencyKeyState {
+    /// The documents of the request are being appended to the queue.
+    InFlight,
+    /// The documents of the request were persisted to the queue.
+    Ingested { num_docs: u64 },
+}
+
+type IdempotencyKey = (String, String);
+
+/// Window of the idempotency keys seen by the node, bounded by a number of keys.
+///
+/// The keys are remembered in the memory of the process only: a retry sent after a restart of
+/// the node is ingested again, and so is a retry sent to another node of the cluster.
+struct IdempotencyKeys {
+    capacity: usize,
+    states: HashMap<IdempotencyKey, (Instant, IdempotencyKeyState)>,
+    /// Keys in the order they were first seen, to expire them.
+    seen_keys: VecDeque<(Instant, IdempotencyKey)>,
+}
+
+impl IdempotencyKeys {
+    fn with_capacity(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "Idempotency keys capacity should be positive."
+        );
+        Self {
+            capacity,
+            states: HashMap::new(),
+            seen_keys: VecDeque::new(),
+        }
+    }
+
+    /// Returns the state of the key if it was seen within the window. Otherwise, records the key
+    /// as in flight and returns `None`.
+    fn start(&mut self, key: &IdempotencyKey, now: Instant) -> Option<IdempotencyKeyState> {
+        self.expire(now);
+        if let Some((_, state)) = self.states.get(key) {
+            return Some(*state);
+        }
+        while self.seen_keys.len() >= self.capacity {
+            if !self.evict_oldest() {
+                break;
+            }
+        }
+        self.states
+            .insert(key.clone(), (now, IdempotencyKeyState::InFlight));
+        self.seen_keys.push_back((now, key.clone()));
+        None
+    }
+
+    /// Records the outcome of the in-flight request bearing the key: the key is kept until the
+    /// end of the window if the documents were ingested, forgotten otherwise so that the request
+    /// can be retried.
+    fn finish(&mut self, key: &IdempotencyKey, num_docs_opt: Option<u64>) {
+        match num_docs_opt {
+            Some(num_docs) => {
+                if let Some((_, state)) = self.states.get_mut(key) {
+                    *state = IdempotencyKeyState::Ingested { num_docs };
+                }
+            }
+            None => {
+                self.states.remove(key);
+            }
+        }
+    }
+
+    /// Forgets the oldest key seen, unless its request is still in flight. Returns whether a
+    /// key was evicted.
+    fn evict_oldest(&mut self) -> bool {
+        let (seen_at, key) = match self.seen_keys.front() {
+            Some(seen_key) => seen_key,
+            None => return false,
+        };
+        match self.states.get(key) {
+            Some((started_at, IdempotencyKeyState::InFlight)) if started_at == seen_at => {
+                return false;
+            }
+            Some((started_at, _)) if started_at == seen_at => {
+                self.states.remove(key);
+            }
+            _ => {}
+        }
+        self.seen_keys.pop_front();
+        true
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some((seen_at, _)) = self.seen_keys.front() {
+            if now.saturating_duration_since(*seen_at) < IDEMPOTENCY_KEY_WINDOW {
+                break;
+            }
+            let (seen_at, key) = self.seen_keys.pop_front().expect("The key should exist.");
+            // The key may have been forgotten then seen again since.
+            if matches!(self.states.get(&key), Some((started_at, _)) if *started_at == seen_at) {
+                self.states.remove(&key);
+            }
+        }
+    }
+}
+
+/// Forgets the idempotency key of a request on drop, unless the documents of the request were
+/// ingested.
+struct IdempotencyKeyGuard {
+    key: IdempotencyKey,
+    num_docs_opt: Option<u64>,
+}
+
+impl Drop for IdempotencyKeyGuard {
+    fn drop(&mut self) {
+        IDEMPOTENCY_KEYS
+            .lock()
+            .expect("Idempotency keys lock should not be poisoned.")
+            .finish(&self.key, self.num_docs_opt);
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct IngestResponse {
+    /// Number of documents persisted to the queue, to be indexed by the `ingest_api` source.
+    num_docs_for_processing: u64,
+}
+
+/// Ingest handler: appends the documents of the NDJSON request body to the ingest queue of the
+/// index on the node. The request succeeds once the documents are persisted to disk, and is
+/// rejected while the index is write-blocked, e.g. because the disk crossed its high watermark.
+///
+/// A request bearing an `Idempotency-Key` header is ingested once: its retries with the same key
+/// within [`IDEMPOTENCY_KEY_WINDOW`] get the response of the first request.
+pub fn ingest_handler() -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "v1" / String / "ingest")
+        .and(warp::post())
+        .and(warp::header::optional::<String>("idempotency-key"))
+        .and(warp::body::content_length_limit(MAX_REQUEST_BODY_NUM_BYTES))
+        .and(warp::body::bytes())
+        .and_then(ingest)
+}
+
+async fn ingest(
+    index_id: String,
+    idempotency_key_opt: Option<String>,
+    body: Bytes,
+) -> Result<impl warp::Reply, Infallible> {
+    Ok(Format::PrettyJson.make_reply(ingest_docs(index_id, idempotency_key_opt, body).await))
+}
+
+async fn ingest_docs(
+    index_id: String,
+    idempotency_key_opt: Option<String>,
+    body: Bytes,
+) -> Result<IngestResponse, ApiError> {
+    let docs: Vec<Bytes> = parse_ndjson(&body)?
+        .into_iter()
+        .map(|doc| body.slice_ref(doc))
+        .collect();
+    let mut idempotency_key_guard_opt = None;
+    if let Some(idempotency_key) = idempotency_key_opt {
+        if idempotency_key.is_empty() || idempotency_key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+            return Err(ApiError::InvalidArgument(format!(
+                "Idempotency key must hold between 1 and {} bytes",
+                MAX_IDEMPOTENCY_KEY_LEN
+            )));
+        }
+        let key = (index_id.clone(), idempotency_key);
+        let state_opt = IDEMPOTENCY_KEYS
+            .lock()
+            .expect("Idempotency keys lock should not be poisoned.")
+            .start(&key, Instant::now());
+        match state_opt {
+            Some(IdempotencyKeyState::InFlight) => {
+                return Err(ApiError::IdempotencyKeyInFlight {
+                    index_id,
+                    idempotency_key: key.1,
+                });
+            }
+            Some(IdempotencyKeyState::Ingested { num_docs }) => {
+                return Ok(IngestResponse {
+                    num_docs_for_processing: num_docs,
+                });
+            }
+            None => {}
+        }
+        idempotency_key_guard_opt = Some(IdempotencyKeyGuard {
+            key,
+            num_docs_opt: None,
+        });
+    }
+    let ingest_api = ingest_api().ok_or(ApiError::IngestApiNotAvailable)?;
+    let queue = ingest_api
+        .queue(&index_id)
+        .map_err(|error| ApiError::IngestError(error.to_string()))?
+        .ok_or(ApiError::IngestQueueNotFound { index_id })?;
+    if write_block(&index_id).is_some() {
+        return Err(ApiError::IndexWriteBlocked { index_id });
+    }
+    let num_docs = docs.len() as u64;
+    // Appending fsyncs the queue, which must not block the runtime. The idempotency key is
+    // recorded by the blocking task, which completes even if the request is dropped meanwhile.
+    tokio::task::spawn_blocking(move || {
+        if num_docs > 0 {
+            queue
+                .lock()
+                .expect("Ingest queue lock should not be poisoned.")
+                .append(&docs)?;
+        }
+        if let Some(idempotency_key_guard) = idempotency_key_guard_opt.as_mut() {
+            idempotency_key_guard.num_docs_opt = Some(num_docs);
+        }
+        Ok::<_, std::io::Error>(())
+    })
+    .await
+    .map_err(|error| ApiError::IngestError(error.to_string()))?
+    .map_err(|error| ApiError::IngestError(error.to_string()))?;
+    Ok(IngestResponse {
+        num_docs_for_processing: num_docs,
+    })
+}
+
+/// Splits the body into its lines, skipping empty lines, and checks that each line holds a
+/// JSON object, so that invalid documents are rejected before being queued.
+fn parse_ndjson(body: &[u8]) -> Result<Vec<&[u8]>, ApiError> {
+    let mut docs = Vec::new();
+    for (line_idx, line) in body.split(|byte| *byte == b'\n').enumerate() {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        if let Err(error) =
+            serde_json::from_slice::<serde_json::Map<String, serde_json::Value>>(line)
+        {
+            return Err(ApiError::InvalidArgument(format!(
+                "Line {} is not a JSON object: {}",
+                line_idx + 1,
+                error
+            )));
+        }
+        docs.push(line);
+    }
+    Ok(docs)
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_indexing::ingest_api::init_ingest_api;
+    use quickwit_indexing::{apply_write_block, clear_write_block, WriteBlockReason};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_ndjson() {
+        let docs = parse_ndjson(b"{\"body\": \"doc-0\"}\r\n\n  \n{\"body\": \"doc-1\"}").unwrap();
+        assert_eq!(
+            docs,
+            vec![&b"{\"body\": \"doc-0\"}"[..], &b"{\"body\": \"doc-1\"}"[..]]
+        );
+        let error = parse_ndjson(b"{}\n[]").unwrap_err();
+        assert!(error.message().contains("Line 2"));
+        assert!(parse_ndjson(b"{\"body\":").is_err());
+    }
+
+    #[test]
+    fn test_idempotency_keys() {
+        let mut idempotency_keys = IdempotencyKeys::with_capacity(10);
+        let key = ("test-index".to_string(), "key-1".to_string());
+        let other_key = ("test-index".to_string(), "key-2".to_string());
+        let now = Instant::now();
+        assert_eq!(idempotency_keys.start(&key, now), None);
+        assert_eq!(
+            idempotency_keys.start(&key, now),
+            Some(IdempotencyKeyState::InFlight)
+        );
+        // A failed request is forgotten, so that it can be retried.
+        idempotency_keys.finish(&key, None);
+        assert_eq!(idempotency_keys.start(&key, now), None);
+        idempotency_keys.finish(&key, Some(2));
+
+        let later = now + IDEMPOTENCY_KEY_WINDOW / 2;
+        assert_eq!(
+            idempotency_keys.start(&key, later),
+            Some(IdempotencyKeyState::Ingested { num_docs: 2 })
+        );
+        assert_eq!(idempotency_keys.start(&other_key, later), None);
+        idempotency_keys.finish(&other_key, Some(3));
+
+        let expired = now + IDEMPOTENCY_KEY_WINDOW;
+        assert_eq!(idempotency_keys.start(&key, expired), None);
+        assert_eq!(
+            idempotency_keys.start(&other_key, expired),
+            Some(IdempotencyKeyState::Ingested { num_docs: 3 })
+        );
+        // The key seen again is not expired by its first sighting.
+        assert_eq!(
+            idempotency_keys.start(&key, later + IDEMPOTENCY_KEY_WINDOW),
+            Some(IdempotencyKeyState::InFlight)
+        );
+    }
+
+    #[test]
+    fn test_idempotency_keys_capacity() {
+        let mut idempotency_keys = IdempotencyKeys::with_capacity(2);
+        let keys: Vec<IdempotencyKey> = (0..4)
+            .map(|idx| ("test-index".to_string(), format!("key-{}", idx)))
+            .collect();
+        let now = Instant::now();
+        assert_eq!(idempotency_keys.start(&keys[0], now), None);
+        idempotency_keys.finish(&keys[0], Some(1));
+        assert_eq!(idempotency_keys.start(&keys[1], now), None);
+        // The oldest ingested key is evicted to make room for the new one.
+        assert_eq!(idempotency_keys.start(&keys[2], now), None);
+        assert_eq!(idempotency_keys.states.len(), 2);
+        idempotency_keys.finish(&keys[2], Some(1));
+        // The key of an in-flight request is not evicted: the capacity is exceeded instead.
+        assert_eq!(idempotency_keys.start(&keys[3], now), None);
+        assert_eq!(idempotency_keys.states.len(), 3);
+        assert_eq!(
+            idempotency_keys.start(&keys[1], now),
+            Some(IdempotencyKeyState::InFlight)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ingest_api() {
+        let data_dir_path = tempfile::tempdir().unwrap().into_path();
+        // The ingest API may already be initialized by another test.
+        let _ = init_ingest_api(&data_dir_path);
+        let index_id = "test-ingest-api";
+
+        let response = warp::test::request()
+            .method("POST")
+            .path(&format!("/api/v1/{}/ingest", index_id))
+            .body("{}")
+            .reply(&ingest_handler())
+            .await;
+        assert_eq!(response.status(), 404);
+
+        let queue = ingest_api().unwrap().get_or_create_queue(index_id).unwrap();
+        let response = warp::test::request()
+            .method("POST")
+            .path(&format!("/api/v1/{}/ingest", index_id))
+            .body("{\"body\": \"doc-0\"}\n{\"body\": \"doc-1\"}\n")
+            .reply(&ingest_handler())
+            .await;
+        assert_eq!(response.status(), 200);
+        let ingest_response: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(ingest_response["numDocsForProcessing"], 2);
+        let (docs, _) = queue.lock().unwrap().read(0, 1_000).unwrap();
+        assert_eq!(docs, vec!["{\"body\": \"doc-0\"}", "{\"body\": \"doc-1\"}"]);
+
+        let response = warp::test::request()
+            .method("POST")
+            .path(&format!("/api/v1/{}/ingest", index_id))
+            .body("{\"body\": \"doc-2\"}\nnot json")
+            .reply(&ingest_handler())
+            .await;
+        assert_eq!(response.status(), 400);
+        assert_eq!(queue.lock().unwrap().read(0, 1_000).unwrap().0.len(), 2);
+
+        apply_write_block(index_id, WriteBlockReason::DiskHighWatermark);
+        let response = warp::test::request()
+            .method("POST")
+            .path(&format!("/api/v1/{}/ingest", index_id))
+            .body("{\"body\": \"doc-2\"}")
+            .reply(&ingest_handler())
+            .await;
+        clear_write_block(index_id);
+        assert_eq!(response.status(), 429);
+        assert_eq!(queue.lock().unwrap().read(0, 1_000).unwrap().0.len(), 2);
+
+        // The retries of a request bearing an idempotency key are not ingested again.
+        for _ in 0..2 {
+            let response = warp::test::request()
+                .method("POST")
+                .path(&format!("/api/v1/{}/ingest", index_id))
+                .header("Idempotency-Key", "batch-1")
+                .body("{\"body\": \"doc-2\"}")
+                .reply(&ingest_handler())
+                .await;
+            assert_eq!(response.status(), 200);
+            let ingest_response: serde_json::Value =
+                serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(ingest_response["numDocsForProcessing"], 1);
+        }
+        assert_eq!(queue.lock().unwrap().read(0, 1_000).unwrap().0.len(), 3);
+
+        let response = warp::test::request()
+            .method("POST")
+            .path(&format!("/api/v1/{}/ingest", index_id))
+            .header("Idempotency-Key", "")
+            .body("{\"body\": \"doc-3\"}")
+            .reply(&ingest_handler())
+            .await;
+        assert_eq!(response.status(), 400);
+    }
+}
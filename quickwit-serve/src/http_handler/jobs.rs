@@ -0,0 +1,156 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+use std::convert::Infallible;
+
+use serde::Deserialize;
+use warp::{Filter, Rejection};
+
+use crate::jobs::JobService;
+use crate::rest::Format;
+use crate::ApiError;
+
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+struct ListJobsQuery {
+    /// Only lists the jobs of the index with this ID.
+    #[serde(default)]
+    index_id: Option<String>,
+}
+
+/// Jobs handler: lists, polls and cancels the jobs running long operations such as exports and
+/// garbage collection runs.
+pub fn jobs_handler(
+    job_service: JobService,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    let job_service_clone = job_service.clone();
+    let job_service_clone_2 = job_service.clone();
+    warp::path!("api" / "v1" / "jobs")
+        .and(warp::get())
+        .and(serde_qs::warp::query(serde_qs::Config::default()))
+        .and(warp::any().map(move || job_service_clone.clone()))
+        .and_then(list_jobs)
+        .or(warp::path!("api" / "v1" / "jobs" / String)
+            .and(warp::get())
+            .and(warp::any().map(move || job_service_clone_2.clone()))
+            .and_then(get_job))
+        .or(warp::path!("api" / "v1" / "jobs" / String / "cancel")
+            .and(warp::post())
+            .and(warp::any().map(move || job_service.clone()))
+            .and_then(cancel_job))
+}
+
+async fn list_jobs(
+    query: ListJobsQuery,
+    job_service: JobService,
+) -> Result<impl warp::Reply, Infallible> {
+    let jobs_res = job_service
+        .list_jobs(query.index_id.as_deref())
+        .await
+        .map_err(ApiError::MetastoreError);
+    Ok(Format::PrettyJson.make_reply(jobs_res))
+}
+
+async fn get_job(job_id: String, job_service: JobService) -> Result<impl warp::Reply, Infallible> {
+    let job_res = job_service
+        .job(&job_id)
+        .await
+        .map_err(ApiError::MetastoreError);
+    Ok(Format::PrettyJson.make_reply(job_res))
+}
+
+async fn cancel_job(
+    job_id: String,
+    job_service: JobService,
+) -> Result<impl warp::Reply, Infallible> {
+    let job_res = job_service
+        .cancel(&job_id)
+        .await
+        .map_err(ApiError::MetastoreError);
+    Ok(Format::PrettyJson.make_reply(job_res))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use quickwit_metastore::{FileBackedMetastore, JobKind};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_jobs_api() {
+        let job_service = JobService::new(
+            Arc::new(FileBackedMetastore::for_test()),
+            "node-1".to_string(),
+        );
+        let job = job_service
+            .submit("my-index", JobKind::Export, |_ctx| {
+                futures::future::pending::<anyhow::Result<()>>()
+            })
+            .await
+            .unwrap();
+        let handler = jobs_handler(job_service);
+
+        let response = warp::test::request()
+            .path("/api/v1/jobs?indexId=my-index")
+            .reply(&handler)
+            .await;
+        assert_eq!(response.status(), 200);
+        let jobs: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(jobs.as_array().unwrap().len(), 1);
+        assert_eq!(jobs[0]["jobId"], job.job_id);
+        assert_eq!(jobs[0]["kind"], "export");
+        assert_eq!(jobs[0]["state"], "running");
+
+        let response = warp::test::request()
+            .path("/api/v1/jobs?indexId=other-index")
+            .reply(&handler)
+            .await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.body(), "[]");
+
+        let job_path = format!("/api/v1/jobs/{}", job.job_id);
+        let response = warp::test::request().path(&job_path).reply(&handler).await;
+        assert_eq!(response.status(), 200);
+
+        let cancel_path = format!("/api/v1/jobs/{}/cancel", job.job_id);
+        let response = warp::test::request()
+            .method("POST")
+            .path(&cancel_path)
+            .reply(&handler)
+            .await;
+        assert_eq!(response.status(), 200);
+        let job: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(job["state"], "cancelled");
+
+        let response = warp::test::request()
+            .method("POST")
+            .path(&cancel_path)
+            .reply(&handler)
+            .await;
+        assert_eq!(response.status(), 409);
+
+        let response = warp::test::request()
+            .path("/api/v1/jobs/does-not-exist")
+            .reply(&handler)
+            .await;
+        assert_eq!(response.status(), 404);
+    }
+}
@@ -0,0 +1,212 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use futures::{Stream, StreamExt};
+use quickwit_proto::{Hit, SearchRequest};
+use quickwit_search::{SearchError, SearchService, TimeExpression};
+use serde::Deserialize;
+use tracing::info;
+use warp::sse::Event;
+use warp::{Filter, Rejection, Reply};
+
+use crate::auth::{redact_hit, Authenticator, Caller};
+use crate::rest::{default_max_hits, from_simple_list, time_bound_fields, Format};
+use crate::ApiError;
+
+/// This struct represents the live search query passed to
+/// the REST API.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveSearchRequestQueryString {
+    /// Query text. The query language is that of tantivy.
+    pub query: String,
+    // Fields to search on.
+    #[serde(default)]
+    #[serde(rename(deserialize = "searchField"))]
+    #[serde(deserialize_with = "from_simple_list")]
+    pub search_fields: Option<Vec<String>>,
+    /// If set, restricts search to documents with a `timestamp >= start_timestamp`.
+    /// Accepts relative time expressions such as `now-15m`.
+    pub start_timestamp: Option<TimeExpression>,
+    /// If set, restricts search to documents with a `timestamp < end_timestamp``.
+    /// Accepts relative time expressions such as `now/d`.
+    pub end_timestamp: Option<TimeExpression>,
+    /// Number of past matching documents sent before following the index (by default 20).
+    #[serde(default = "default_max_hits")]
+    pub max_hits: u64,
+}
+
+/// Live search handler: streams the documents matching a query as Server-Sent Events,
+/// following the index as new splits are published.
+pub fn live_search_handler<TSearchService: SearchService>(
+    search_service: Arc<TSearchService>,
+    authenticator: Authenticator,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    live_search_filter()
+        .and(authenticator.caller())
+        .and(warp::any().map(move || search_service.clone()))
+        .and_then(live_search)
+}
+
+fn live_search_filter(
+) -> impl Filter<Extract = (String, LiveSearchRequestQueryString), Error = Rejection> + Clone {
+    warp::path!("api" / "v1" / String / "search" / "live")
+        .and(warp::get())
+        .and(serde_qs::warp::query(serde_qs::Config::default()))
+}
+
+async fn live_search<TSearchService: SearchService>(
+    index_id: String,
+    request: LiveSearchRequestQueryString,
+    caller: Caller,
+    search_service: Arc<TSearchService>,
+) -> Result<warp::reply::Response, Infallible> {
+    info!(index_id = %index_id, request =? request, "live_search");
+    let masked_fields = caller.masked_fields(&index_id);
+    let (start_timestamp, start_time_expression) = time_bound_fields(request.start_timestamp);
+    let (end_timestamp, end_time_expression) = time_bound_fields(request.end_timestamp);
+    let search_request = SearchRequest {
+        index_id,
+        query: request.query,
+        search_fields: request.search_fields.unwrap_or_default(),
+        start_timestamp,
+        end_timestamp,
+        start_time_expression,
+        end_time_expression,
+        max_hits: request.max_hits,
+        ..Default::default()
+    };
+    match search_service.root_search_tail(search_request).await {
+        Ok(hit_stream) => {
+            let event_stream =
+                warp::sse::keep_alive().stream(hit_events(hit_stream, masked_fields));
+            Ok(warp::sse::reply(event_stream).into_response())
+        }
+        Err(search_error) => Ok(Format::PrettyJson
+            .make_reply(Err::<(), ApiError>(ApiError::SearchError(search_error)))
+            .into_response()),
+    }
+}
+
+/// Turns each hit into a `hit` event holding the document JSON, without the masked fields.
+/// Errors are sent as `error` events, after which the tail search ends the stream.
+fn hit_events<S>(
+    hit_stream: S,
+    masked_fields: Vec<String>,
+) -> impl Stream<Item = Result<Event, Infallible>> + Send + 'static
+where
+    S: Stream<Item = quickwit_search::Result<Hit>> + Send + 'static,
+{
+    hit_stream.map(move |hit_result| {
+        let redacted_hit_result = hit_result.and_then(|mut hit| {
+            redact_hit(&mut hit, &masked_fields)
+                .map_err(|error| SearchError::InternalError(error.to_string()))?;
+            Ok(hit)
+        });
+        let event = match redacted_hit_result {
+            Ok(hit) => Event::default().event("hit").data(hit.json),
+            Err(search_error) => Event::default()
+                .event("error")
+                .data(search_error.to_string()),
+        };
+        Ok(event)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::predicate;
+    use quickwit_search::{MockSearchService, SearchError};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_live_search_filter() {
+        let (index_id, request) = warp::test::request()
+            .path("/api/v1/my-index/search/live?query=severity:ERROR&searchField=body,title")
+            .filter(&live_search_filter())
+            .await
+            .unwrap();
+        assert_eq!(index_id, "my-index");
+        assert_eq!(
+            request,
+            LiveSearchRequestQueryString {
+                query: "severity:ERROR".to_string(),
+                search_fields: Some(vec!["body".to_string(), "title".to_string()]),
+                start_timestamp: None,
+                end_timestamp: None,
+                max_hits: 20,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_live_search_events() {
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service
+            .expect_root_search_tail()
+            .with(predicate::function(|search_request: &SearchRequest| {
+                search_request.index_id == "my-index" && search_request.max_hits == 5
+            }))
+            .return_once(|_| {
+                Ok(Box::pin(futures::stream::iter(vec![
+                    Ok(Hit {
+                        json: r#"{"title":["first"]}"#.to_string(),
+                        partial_hit: None,
+                    }),
+                    Err(SearchError::InternalError("split unavailable".to_string())),
+                ])))
+            });
+        let response = warp::test::request()
+            .path("/api/v1/my-index/search/live?query=*&maxHits=5")
+            .reply(&live_search_handler(
+                Arc::new(mock_search_service),
+                Authenticator::default(),
+            ))
+            .await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "text/event-stream"
+        );
+        let body = String::from_utf8_lossy(response.body());
+        assert!(body.contains("event:hit\ndata:{\"title\":[\"first\"]}\n"));
+        assert!(body.contains("event:error\ndata:Internal error: `split unavailable`.\n"));
+    }
+
+    #[tokio::test]
+    async fn test_live_search_invalid_query() {
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service
+            .expect_root_search_tail()
+            .return_once(|_| Err(SearchError::InvalidQuery("invalid query".to_string())));
+        let response = warp::test::request()
+            .path("/api/v1/my-index/search/live?query=myfield:")
+            .reply(&live_search_handler(
+                Arc::new(mock_search_service),
+                Authenticator::default(),
+            ))
+            .await;
+        assert_eq!(response.status(), 400);
+    }
+}
@@ -0,0 +1,207 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::convert::Infallible;
+use std::time::Duration;
+
+use quickwit_common::log_filter::{log_filter_directives, set_log_filter_directives};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use warp::{Filter, Rejection};
+
+use crate::rest::Format;
+use crate::ApiError;
+
+/// Request changing the log filter, passed as the JSON body of the log filter REST API.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct LogFilterUpdate {
+    /// Log filter directives, with the syntax of `RUST_LOG`, e.g.
+    /// `quickwit=info,quickwit_search=debug`.
+    pub directives: String,
+    /// If set, the previous directives are restored after this delay, unless the log filter
+    /// changed again in the meantime.
+    pub reset_after_secs: Option<u64>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct LogFilterResponse {
+    directives: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous_directives: Option<String>,
+}
+
+/// Log filter handler: reports and changes the log levels of the running node.
+pub fn log_filter_handler() -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    get_log_filter_filter()
+        .and_then(get_log_filter)
+        .or(update_log_filter_filter().and_then(update_log_filter))
+}
+
+fn get_log_filter_filter() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::path!("api" / "v1" / "log-filter").and(warp::get())
+}
+
+fn update_log_filter_filter() -> impl Filter<Extract = (LogFilterUpdate,), Error = Rejection> + Clone
+{
+    warp::path!("api" / "v1" / "log-filter")
+        .and(warp::put())
+        .and(warp::body::json())
+}
+
+async fn get_log_filter() -> Result<impl warp::Reply, Infallible> {
+    Ok(Format::PrettyJson.make_reply(get_log_filter_endpoint()))
+}
+
+fn get_log_filter_endpoint() -> Result<LogFilterResponse, ApiError> {
+    let directives = log_filter_directives().ok_or_else(|| {
+        ApiError::LogFilterError("the log filter of this node cannot change at runtime".to_string())
+    })?;
+    Ok(LogFilterResponse {
+        directives,
+        previous_directives: None,
+    })
+}
+
+async fn update_log_filter(
+    log_filter_update: LogFilterUpdate,
+) -> Result<impl warp::Reply, Infallible> {
+    Ok(Format::PrettyJson.make_reply(update_log_filter_endpoint(log_filter_update)))
+}
+
+fn update_log_filter_endpoint(
+    log_filter_update: LogFilterUpdate,
+) -> Result<LogFilterResponse, ApiError> {
+    let directives = log_filter_update.directives;
+    let previous_directives = set_log_filter_directives(&directives)
+        .map_err(|error| ApiError::LogFilterError(format!("{:#}", error)))?;
+    info!(directives = %directives, previous_directives = %previous_directives, "Log filter changed.");
+    if let Some(reset_after_secs) = log_filter_update.reset_after_secs {
+        let directives_clone = directives.clone();
+        let previous_directives_clone = previous_directives.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(reset_after_secs)).await;
+            reset_log_filter(&directives_clone, &previous_directives_clone);
+        });
+    }
+    Ok(LogFilterResponse {
+        directives,
+        previous_directives: Some(previous_directives),
+    })
+}
+
+/// Restores the previous directives, unless the log filter changed since `directives` were set.
+fn reset_log_filter(directives: &str, previous_directives: &str) {
+    if log_filter_directives().as_deref() != Some(directives) {
+        return;
+    }
+    match set_log_filter_directives(previous_directives) {
+        Ok(_) => info!(directives = %previous_directives, "Log filter restored."),
+        Err(error) => error!(error = ?error, "Failed to restore log filter."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_common::log_filter::set_log_filter_reloader;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_update_log_filter_filter() {
+        let log_filter_update = warp::test::request()
+            .method("PUT")
+            .path("/api/v1/log-filter")
+            .json(&serde_json::json!({
+                "directives": "quickwit=info,quickwit_search=debug",
+                "resetAfterSecs": 600
+            }))
+            .filter(&update_log_filter_filter())
+            .await
+            .unwrap();
+        assert_eq!(
+            log_filter_update,
+            LogFilterUpdate {
+                directives: "quickwit=info,quickwit_search=debug".to_string(),
+                reset_after_secs: Some(600),
+            }
+        );
+        let rejection = warp::test::request()
+            .method("PUT")
+            .path("/api/v1/log-filter")
+            .json(&serde_json::json!({ "directives": "quickwit=info", "level": "debug" }))
+            .filter(&update_log_filter_filter())
+            .await;
+        assert!(rejection.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_log_filter_api() {
+        set_log_filter_reloader(
+            |directives: &str| {
+                if directives.contains('!') {
+                    anyhow::bail!("Invalid directives.");
+                }
+                Ok(())
+            },
+            "quickwit=info".to_string(),
+        );
+        let response = warp::test::request()
+            .path("/api/v1/log-filter")
+            .reply(&log_filter_handler())
+            .await;
+        assert_eq!(response.status(), 200);
+        let log_filter: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(
+            log_filter,
+            serde_json::json!({ "directives": "quickwit=info" })
+        );
+
+        let response = warp::test::request()
+            .method("PUT")
+            .path("/api/v1/log-filter")
+            .json(&serde_json::json!({ "directives": "quickwit=!" }))
+            .reply(&log_filter_handler())
+            .await;
+        assert_eq!(response.status(), 400);
+
+        let response = warp::test::request()
+            .method("PUT")
+            .path("/api/v1/log-filter")
+            .json(&serde_json::json!({
+                "directives": "quickwit=info,quickwit_search=debug",
+                "resetAfterSecs": 0
+            }))
+            .reply(&log_filter_handler())
+            .await;
+        assert_eq!(response.status(), 200);
+        let log_filter: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(
+            log_filter,
+            serde_json::json!({
+                "directives": "quickwit=info,quickwit_search=debug",
+                "previousDirectives": "quickwit=info"
+            })
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(log_filter_directives().unwrap(), "quickwit=info");
+    }
+}
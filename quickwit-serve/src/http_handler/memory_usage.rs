@@ -0,0 +1,87 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+
+use quickwit_common::memory_usage::{memory_usage, MemoryComponent};
+use serde::Serialize;
+use warp::{Filter, Rejection};
+
+use crate::rest::Format;
+use crate::ApiError;
+
+/// Memory used by the node, broken down per component.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct MemoryUsageResponse {
+    total_num_bytes: i64,
+    components: BTreeMap<&'static str, i64>,
+}
+
+/// Memory usage handler: reports the memory used by the node per component.
+pub fn memory_usage_handler() -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone
+{
+    warp::path!("api" / "v1" / "memory-usage")
+        .and(warp::get())
+        .and_then(get_memory_usage)
+}
+
+async fn get_memory_usage() -> Result<impl warp::Reply, Infallible> {
+    Ok(Format::PrettyJson.make_reply(Ok::<_, ApiError>(memory_usage_response())))
+}
+
+fn memory_usage_response() -> MemoryUsageResponse {
+    let components: BTreeMap<&'static str, i64> = MemoryComponent::all()
+        .iter()
+        .map(|component| (component.as_str(), memory_usage(*component)))
+        .collect();
+    MemoryUsageResponse {
+        total_num_bytes: components.values().sum(),
+        components,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_common::memory_usage::MemoryUsageGuard;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_usage_api() {
+        let _memory_usage_guard = MemoryUsageGuard::new(MemoryComponent::IndexerHeaps, 1_000);
+        let response = warp::test::request()
+            .path("/api/v1/memory-usage")
+            .reply(&memory_usage_handler())
+            .await;
+        assert_eq!(response.status(), 200);
+        let memory_usage: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        let components = memory_usage["components"].as_object().unwrap();
+        assert_eq!(components.len(), MemoryComponent::all().len());
+        assert!(components["indexer_heaps"].as_i64().unwrap() >= 1_000);
+        assert_eq!(
+            memory_usage["totalNumBytes"].as_i64().unwrap(),
+            components
+                .values()
+                .map(|num_bytes| num_bytes.as_i64().unwrap())
+                .sum::<i64>()
+        );
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::convert::Infallible;
+use std::path::PathBuf;
+
+use quickwit_metastore::Job;
+use tracing::info;
+use warp::{Filter, Rejection};
+
+use crate::jobs::JobService;
+use crate::merge::{submit_merge_job, MergeRequest};
+use crate::rest::Format;
+use crate::ApiError;
+
+/// Merge handler: starts merge jobs, whose status is then polled through the jobs API.
+pub fn merge_handler(
+    job_service: JobService,
+    data_dir_path: PathBuf,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    start_merge_filter()
+        .and(warp::any().map(move || job_service.clone()))
+        .and(warp::any().map(move || data_dir_path.clone()))
+        .and_then(start_merge)
+}
+
+fn start_merge_filter() -> impl Filter<Extract = (String, MergeRequest), Error = Rejection> + Clone
+{
+    warp::path!("api" / "v1" / String / "merge")
+        .and(warp::post())
+        .and(warp::body::json())
+}
+
+async fn start_merge(
+    index_id: String,
+    merge_request: MergeRequest,
+    job_service: JobService,
+    data_dir_path: PathBuf,
+) -> Result<impl warp::Reply, Infallible> {
+    info!(index_id = %index_id, request =? merge_request, "merge");
+    let job_res = start_merge_job(&job_service, data_dir_path, index_id, merge_request).await;
+    Ok(Format::PrettyJson.make_reply(job_res))
+}
+
+async fn start_merge_job(
+    job_service: &JobService,
+    data_dir_path: PathBuf,
+    index_id: String,
+    merge_request: MergeRequest,
+) -> Result<Job, ApiError> {
+    if let (Some(start_timestamp), Some(end_timestamp)) =
+        (merge_request.start_timestamp, merge_request.end_timestamp)
+    {
+        if start_timestamp >= end_timestamp {
+            return Err(ApiError::InvalidArgument(format!(
+                "'startTimestamp' `{}` must be lower than 'endTimestamp' `{}`",
+                start_timestamp, end_timestamp
+            )));
+        }
+    }
+    submit_merge_job(job_service, data_dir_path, index_id, merge_request)
+        .await
+        .map_err(ApiError::MetastoreError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_start_merge_filter() {
+        let (index_id, merge_request) = warp::test::request()
+            .method("POST")
+            .path("/api/v1/my-index/merge")
+            .json(&serde_json::json!({ "startTimestamp": 1_000 }))
+            .filter(&start_merge_filter())
+            .await
+            .unwrap();
+        assert_eq!(index_id, "my-index");
+        assert_eq!(
+            merge_request,
+            MergeRequest {
+                start_timestamp: Some(1_000),
+                end_timestamp: None,
+            }
+        );
+    }
+}
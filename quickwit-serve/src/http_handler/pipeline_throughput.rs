@@ -0,0 +1,66 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::convert::Infallible;
+
+use quickwit_indexing::{pipeline_throughputs, PipelineThroughput};
+use serde::Serialize;
+use warp::{Filter, Rejection};
+
+use crate::rest::Format;
+use crate::ApiError;
+
+/// Throughput of the indexing pipelines of an index running on the node.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct PipelineThroughputsResponse {
+    pipelines: Vec<PipelineThroughput>,
+}
+
+/// Pipeline throughput handler: reports the throughput of each stage of the indexing pipelines
+/// of an index running on the node, and their bottleneck.
+pub fn pipeline_throughput_handler(
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "v1" / "indexes" / String / "pipelines")
+        .and(warp::get())
+        .and_then(get_pipeline_throughputs)
+}
+
+async fn get_pipeline_throughputs(index_id: String) -> Result<impl warp::Reply, Infallible> {
+    let response = PipelineThroughputsResponse {
+        pipelines: pipeline_throughputs(&index_id),
+    };
+    Ok(Format::PrettyJson.make_reply(Ok::<_, ApiError>(response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pipeline_throughput_api() {
+        let response = warp::test::request()
+            .path("/api/v1/indexes/test-pipeline-throughput-api/pipelines")
+            .reply(&pipeline_throughput_handler())
+            .await;
+        assert_eq!(response.status(), 200);
+        let pipelines: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(pipelines, serde_json::json!({ "pipelines": [] }));
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use quickwit_search::SearchService;
+use tracing::info;
+use warp::{Filter, Rejection};
+
+use crate::auth::{Authenticator, Caller};
+use crate::jobs::JobService;
+use crate::reindex::{submit_reindex_job, ReindexRequest};
+use crate::rest::Format;
+use crate::ApiError;
+
+/// Reindex handler: starts reindex jobs, whose status is then polled through the jobs API.
+pub fn reindex_handler<TSearchService: SearchService>(
+    search_service: Arc<TSearchService>,
+    job_service: JobService,
+    authenticator: Authenticator,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    start_reindex_filter()
+        .and(authenticator.caller())
+        .and(warp::any().map(move || search_service.clone()))
+        .and(warp::any().map(move || job_service.clone()))
+        .and_then(start_reindex)
+}
+
+fn start_reindex_filter(
+) -> impl Filter<Extract = (String, ReindexRequest), Error = Rejection> + Clone {
+    warp::path!("api" / "v1" / String / "reindex")
+        .and(warp::post())
+        .and(warp::body::json())
+}
+
+async fn start_reindex<TSearchService: SearchService>(
+    index_id: String,
+    reindex_request: ReindexRequest,
+    caller: Caller,
+    search_service: Arc<TSearchService>,
+    job_service: JobService,
+) -> Result<impl warp::Reply, Infallible> {
+    info!(index_id = %index_id, request =? reindex_request, "reindex");
+    // Reindexing copies whole documents, which would leak the fields masked to the caller.
+    let job_res = match caller.masked_fields(&index_id).into_iter().next() {
+        Some(field_name) => Err(ApiError::FieldMasked { field_name }),
+        None => submit_reindex_job(&job_service, search_service, index_id, reindex_request)
+            .await
+            .map_err(ApiError::MetastoreError),
+    };
+    Ok(Format::PrettyJson.make_reply(job_res))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_start_reindex_filter() {
+        let (index_id, reindex_request) = warp::test::request()
+            .method("POST")
+            .path("/api/v1/my-index/reindex")
+            .json(&serde_json::json!({
+                "targetIndexId": "my-new-index",
+                "query": "severity:ERROR"
+            }))
+            .filter(&start_reindex_filter())
+            .await
+            .unwrap();
+        assert_eq!(index_id, "my-index");
+        assert_eq!(
+            reindex_request,
+            ReindexRequest {
+                target_index_id: "my-new-index".to_string(),
+                query: "severity:ERROR".to_string(),
+                search_fields: Vec::new(),
+                start_timestamp: None,
+                end_timestamp: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,470 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use quickwit_proto::SearchRequest;
+use quickwit_search::{SearchService, TimeExpression};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use tracing::info;
+use warp::{Filter, Rejection};
+
+use crate::rest::{time_bound_fields, Format};
+use crate::ApiError;
+
+/// Maximum number of spans a service map can be computed from.
+const MAX_SPANS_LIMIT: u64 = 100_000;
+
+fn default_query() -> String {
+    "*".to_string()
+}
+
+fn default_max_spans() -> u64 {
+    10_000
+}
+
+/// This struct represents the service map query passed to the REST API.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceMapQueryString {
+    /// Query selecting the spans the service map is computed from (by default all of them).
+    #[serde(default = "default_query")]
+    pub query: String,
+    /// If set, restricts the service map to spans with a `timestamp >= start_timestamp`.
+    /// Accepts relative time expressions such as `now-15m`.
+    pub start_timestamp: Option<TimeExpression>,
+    /// If set, restricts the service map to spans with a `timestamp < end_timestamp`.
+    /// Accepts relative time expressions such as `now/d`.
+    pub end_timestamp: Option<TimeExpression>,
+    /// Maximum number of spans the service map is computed from (by default 10,000).
+    #[serde(default = "default_max_spans")]
+    pub max_spans: u64,
+}
+
+/// Dependencies between the services of a trace index and latencies of their operations.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceMap {
+    /// Number of spans matching the query.
+    pub num_spans_matched: u64,
+    /// Number of spans the service map is computed from, at most `max_spans`.
+    pub num_spans_analyzed: u64,
+    pub services: Vec<ServiceStats>,
+    pub operations: Vec<OperationStats>,
+    pub edges: Vec<ServiceEdge>,
+}
+
+/// Latency percentiles of a set of spans, in nanoseconds.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyPercentiles {
+    pub p50_nanos: i64,
+    pub p90_nanos: i64,
+    pub p99_nanos: i64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceStats {
+    pub service_name: String,
+    pub num_spans: u64,
+    pub num_errors: u64,
+    pub latency: LatencyPercentiles,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationStats {
+    pub service_name: String,
+    pub span_name: String,
+    pub num_spans: u64,
+    pub num_errors: u64,
+    pub latency: LatencyPercentiles,
+}
+
+/// Calls from a service to another one, i.e. spans of `child_service` whose parent span
+/// belongs to `parent_service`. The latency is that of the child spans.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceEdge {
+    pub parent_service: String,
+    pub child_service: String,
+    pub num_calls: u64,
+    pub num_errors: u64,
+    pub latency: LatencyPercentiles,
+}
+
+/// Service map handler: computes the service dependencies and the operation latencies of an
+/// index holding the spans received by an `otlp` source.
+pub fn service_map_handler<TSearchService: SearchService>(
+    search_service: Arc<TSearchService>,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    service_map_filter()
+        .and(warp::any().map(move || search_service.clone()))
+        .and_then(service_map)
+}
+
+fn service_map_filter(
+) -> impl Filter<Extract = (String, ServiceMapQueryString), Error = Rejection> + Clone {
+    warp::path!("api" / "v1" / String / "service-map")
+        .and(warp::get())
+        .and(serde_qs::warp::query(serde_qs::Config::default()))
+}
+
+async fn service_map<TSearchService: SearchService>(
+    index_id: String,
+    request: ServiceMapQueryString,
+    search_service: Arc<TSearchService>,
+) -> Result<impl warp::Reply, Infallible> {
+    info!(index_id = %index_id, request =? request, "service_map");
+    Ok(Format::PrettyJson
+        .make_reply(service_map_endpoint(index_id, request, search_service.as_ref()).await))
+}
+
+async fn service_map_endpoint<TSearchService: SearchService>(
+    index_id: String,
+    request: ServiceMapQueryString,
+    search_service: &TSearchService,
+) -> Result<ServiceMap, ApiError> {
+    if request.max_spans == 0 || request.max_spans > MAX_SPANS_LIMIT {
+        return Err(ApiError::InvalidArgument(format!(
+            "`maxSpans` must be in the range [1, {}]",
+            MAX_SPANS_LIMIT
+        )));
+    }
+    let (start_timestamp, start_time_expression) = time_bound_fields(request.start_timestamp);
+    let (end_timestamp, end_time_expression) = time_bound_fields(request.end_timestamp);
+    let search_request = SearchRequest {
+        index_id,
+        query: request.query,
+        start_timestamp,
+        end_timestamp,
+        start_time_expression,
+        end_time_expression,
+        max_hits: request.max_spans,
+        ..Default::default()
+    };
+    let search_response = search_service.root_search(search_request).await?;
+    let spans = search_response
+        .hits
+        .iter()
+        .filter_map(|hit| serde_json::from_str::<JsonValue>(&hit.json).ok())
+        .filter_map(|doc| SpanRecord::from_doc(&doc))
+        .collect::<Vec<_>>();
+    let mut service_map = build_service_map(&spans);
+    service_map.num_spans_matched = search_response.num_hits;
+    Ok(service_map)
+}
+
+/// Fields of a span document the service map is computed from.
+#[derive(Debug)]
+struct SpanRecord {
+    trace_id: String,
+    span_id: String,
+    parent_span_id: Option<String>,
+    service_name: String,
+    span_name: String,
+    duration_nanos: i64,
+    is_error: bool,
+}
+
+impl SpanRecord {
+    fn from_doc(doc: &JsonValue) -> Option<Self> {
+        Some(SpanRecord {
+            trace_id: doc["trace_id"].as_str()?.to_string(),
+            span_id: doc["span_id"].as_str()?.to_string(),
+            parent_span_id: doc["parent_span_id"].as_str().map(str::to_string),
+            service_name: doc["service_name"].as_str()?.to_string(),
+            span_name: doc["span_name"].as_str().unwrap_or_default().to_string(),
+            duration_nanos: doc["span_duration_nanos"].as_i64().unwrap_or_default(),
+            is_error: doc["span_status_code"] == "error",
+        })
+    }
+}
+
+/// Span durations and error count of a group of spans.
+#[derive(Default)]
+struct SpanGroup {
+    durations_nanos: Vec<i64>,
+    num_errors: u64,
+}
+
+impl SpanGroup {
+    fn add(&mut self, span: &SpanRecord) {
+        self.durations_nanos.push(span.duration_nanos);
+        if span.is_error {
+            self.num_errors += 1;
+        }
+    }
+
+    fn num_spans(&self) -> u64 {
+        self.durations_nanos.len() as u64
+    }
+
+    fn latency(mut self) -> LatencyPercentiles {
+        self.durations_nanos.sort_unstable();
+        LatencyPercentiles {
+            p50_nanos: percentile(&self.durations_nanos, 50),
+            p90_nanos: percentile(&self.durations_nanos, 90),
+            p99_nanos: percentile(&self.durations_nanos, 99),
+        }
+    }
+}
+
+/// Returns the nearest-rank percentile of sorted values.
+fn percentile(sorted_values: &[i64], percent: usize) -> i64 {
+    if sorted_values.is_empty() {
+        return 0;
+    }
+    let rank = (percent * sorted_values.len() + 99) / 100;
+    sorted_values[rank.max(1) - 1]
+}
+
+/// Groups spans by service, by operation, and by pair of calling and called services. A span
+/// whose parent is not among `spans` does not contribute to any edge.
+fn build_service_map(spans: &[SpanRecord]) -> ServiceMap {
+    let service_by_span_id: HashMap<(&str, &str), &str> = spans
+        .iter()
+        .map(|span| {
+            (
+                (span.trace_id.as_str(), span.span_id.as_str()),
+                span.service_name.as_str(),
+            )
+        })
+        .collect();
+    let mut services: BTreeMap<&str, SpanGroup> = BTreeMap::new();
+    let mut operations: BTreeMap<(&str, &str), SpanGroup> = BTreeMap::new();
+    let mut edges: BTreeMap<(&str, &str), SpanGroup> = BTreeMap::new();
+
+    for span in spans {
+        services
+            .entry(span.service_name.as_str())
+            .or_default()
+            .add(span);
+        operations
+            .entry((span.service_name.as_str(), span.span_name.as_str()))
+            .or_default()
+            .add(span);
+        let parent_service_opt = span.parent_span_id.as_ref().and_then(|parent_span_id| {
+            service_by_span_id.get(&(span.trace_id.as_str(), parent_span_id.as_str()))
+        });
+        if let Some(parent_service) = parent_service_opt {
+            if *parent_service != span.service_name {
+                edges
+                    .entry((*parent_service, span.service_name.as_str()))
+                    .or_default()
+                    .add(span);
+            }
+        }
+    }
+    ServiceMap {
+        num_spans_matched: spans.len() as u64,
+        num_spans_analyzed: spans.len() as u64,
+        services: services
+            .into_iter()
+            .map(|(service_name, group)| ServiceStats {
+                service_name: service_name.to_string(),
+                num_spans: group.num_spans(),
+                num_errors: group.num_errors,
+                latency: group.latency(),
+            })
+            .collect(),
+        operations: operations
+            .into_iter()
+            .map(|((service_name, span_name), group)| OperationStats {
+                service_name: service_name.to_string(),
+                span_name: span_name.to_string(),
+                num_spans: group.num_spans(),
+                num_errors: group.num_errors,
+                latency: group.latency(),
+            })
+            .collect(),
+        edges: edges
+            .into_iter()
+            .map(|((parent_service, child_service), group)| ServiceEdge {
+                parent_service: parent_service.to_string(),
+                child_service: child_service.to_string(),
+                num_calls: group.num_spans(),
+                num_errors: group.num_errors,
+                latency: group.latency(),
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::predicate;
+    use quickwit_proto::{Hit, SearchResponse};
+    use quickwit_search::MockSearchService;
+    use serde_json::json;
+
+    use super::*;
+
+    fn span_doc(
+        span_id: &str,
+        parent_span_id: Option<&str>,
+        service_name: &str,
+        span_name: &str,
+        duration_nanos: i64,
+        status_code: &str,
+    ) -> String {
+        let mut doc = json!({
+            "trace_id": "trace-1",
+            "span_id": span_id,
+            "service_name": service_name,
+            "span_name": span_name,
+            "span_duration_nanos": duration_nanos,
+            "span_status_code": status_code,
+        });
+        if let Some(parent_span_id) = parent_span_id {
+            doc["parent_span_id"] = json!(parent_span_id);
+        }
+        doc.to_string()
+    }
+
+    #[test]
+    fn test_percentile() {
+        let values: Vec<i64> = (1..=100).collect();
+        assert_eq!(percentile(&values, 50), 50);
+        assert_eq!(percentile(&values, 99), 99);
+        assert_eq!(percentile(&[7], 90), 7);
+        assert_eq!(percentile(&[], 50), 0);
+    }
+
+    #[tokio::test]
+    async fn test_service_map_filter() {
+        let (index_id, request) = warp::test::request()
+            .path("/api/v1/otel-traces/service-map?startTimestamp=10&maxSpans=500")
+            .filter(&service_map_filter())
+            .await
+            .unwrap();
+        assert_eq!(index_id, "otel-traces");
+        assert_eq!(
+            request,
+            ServiceMapQueryString {
+                query: "*".to_string(),
+                start_timestamp: Some(TimeExpression::Absolute(10)),
+                end_timestamp: None,
+                max_spans: 500,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_service_map_endpoint() -> anyhow::Result<()> {
+        let hits = vec![
+            span_doc("a", None, "frontend", "GET /cart", 300, "unset"),
+            span_doc("b", Some("a"), "cart", "GetCart", 200, "ok"),
+            span_doc("c", Some("b"), "cart", "SELECT", 50, "unset"),
+            span_doc("d", Some("a"), "cart", "GetCart", 100, "error"),
+            span_doc("e", Some("unknown"), "checkout", "Checkout", 10, "unset"),
+        ]
+        .into_iter()
+        .map(|json| Hit {
+            json,
+            ..Default::default()
+        })
+        .collect();
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service
+            .expect_root_search()
+            .with(predicate::function(|request: &SearchRequest| {
+                request.index_id == "otel-traces"
+                    && request.query == "*"
+                    && request.max_hits == 10_000
+            }))
+            .return_once(|_| {
+                Ok(SearchResponse {
+                    num_hits: 20,
+                    hits,
+                    ..Default::default()
+                })
+            });
+        let request = ServiceMapQueryString {
+            query: "*".to_string(),
+            start_timestamp: None,
+            end_timestamp: None,
+            max_spans: default_max_spans(),
+        };
+        let service_map =
+            service_map_endpoint("otel-traces".to_string(), request, &mock_search_service).await?;
+        assert_eq!(service_map.num_spans_matched, 20);
+        assert_eq!(service_map.num_spans_analyzed, 5);
+        assert_eq!(
+            service_map
+                .services
+                .iter()
+                .map(|service| (service.service_name.as_str(), service.num_spans))
+                .collect::<Vec<_>>(),
+            vec![("cart", 3), ("checkout", 1), ("frontend", 1)]
+        );
+        assert_eq!(
+            service_map.operations[0],
+            OperationStats {
+                service_name: "cart".to_string(),
+                span_name: "GetCart".to_string(),
+                num_spans: 2,
+                num_errors: 1,
+                latency: LatencyPercentiles {
+                    p50_nanos: 100,
+                    p90_nanos: 200,
+                    p99_nanos: 200,
+                },
+            }
+        );
+        assert_eq!(
+            service_map.edges,
+            vec![ServiceEdge {
+                parent_service: "frontend".to_string(),
+                child_service: "cart".to_string(),
+                num_calls: 2,
+                num_errors: 1,
+                latency: LatencyPercentiles {
+                    p50_nanos: 100,
+                    p90_nanos: 200,
+                    p99_nanos: 200,
+                },
+            }]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_service_map_endpoint_invalid_max_spans() {
+        let request = ServiceMapQueryString {
+            query: "*".to_string(),
+            start_timestamp: None,
+            end_timestamp: None,
+            max_spans: MAX_SPANS_LIMIT + 1,
+        };
+        let error = service_map_endpoint(
+            "otel-traces".to_string(),
+            request,
+            &MockSearchService::new(),
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(error, ApiError::InvalidArgument(_)));
+    }
+}
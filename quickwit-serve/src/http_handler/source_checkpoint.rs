@@ -0,0 +1,206 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use quickwit_indexing::{
+    is_source_paused, record_source_checkpoint_reset, source_checkpoint_at_timestamp,
+};
+use quickwit_metastore::checkpoint::SourceCheckpoint;
+use quickwit_metastore::{Metastore, MetastoreError};
+use serde::{Deserialize, Serialize};
+use warp::{Filter, Rejection};
+
+use crate::rest::Format;
+use crate::ApiError;
+
+/// Checkpoint of a source: the last position indexed for each partition.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SourceCheckpointResponse {
+    checkpoint: SourceCheckpoint,
+}
+
+/// Body of a checkpoint reset request. When neither the positions nor the timestamp are set, the
+/// checkpoint is reset to the beginning of every partition.
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+struct ResetSourceCheckpointRequest {
+    /// Last positions to consider indexed, per partition ID.
+    #[serde(default)]
+    positions: Option<SourceCheckpoint>,
+    /// Resumes from the records published after this timestamp, in milliseconds.
+    #[serde(default)]
+    timestamp: Option<i64>,
+}
+
+/// Source checkpoint handler: returns the checkpoint of a source, and resets it to some given
+/// positions or to a point in time.
+pub fn source_checkpoint_handler(
+    metastore: Arc<dyn Metastore>,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    let get_metastore = metastore.clone();
+    warp::path!("api" / "v1" / "indexes" / String / "sources" / String / "checkpoint")
+        .and(warp::get())
+        .and(warp::any().map(move || get_metastore.clone()))
+        .and_then(get_source_checkpoint)
+        .or(
+            warp::path!("api" / "v1" / "indexes" / String / "sources" / String / "checkpoint")
+                .and(warp::put())
+                .and(warp::body::json())
+                .and(warp::any().map(move || metastore.clone()))
+                .and_then(put_source_checkpoint),
+        )
+}
+
+async fn get_source_checkpoint(
+    index_id: String,
+    source_id: String,
+    metastore: Arc<dyn Metastore>,
+) -> Result<impl warp::Reply, Infallible> {
+    let reply_res = metastore
+        .source_checkpoint(&index_id, &source_id)
+        .await
+        .map(|checkpoint| SourceCheckpointResponse { checkpoint })
+        .map_err(ApiError::MetastoreError);
+    Ok(Format::PrettyJson.make_reply(reply_res))
+}
+
+async fn put_source_checkpoint(
+    index_id: String,
+    source_id: String,
+    request: ResetSourceCheckpointRequest,
+    metastore: Arc<dyn Metastore>,
+) -> Result<impl warp::Reply, Infallible> {
+    let reply_res = reset_source_checkpoint(index_id, source_id, request, metastore).await;
+    Ok(Format::PrettyJson.make_reply(reply_res))
+}
+
+async fn reset_source_checkpoint(
+    index_id: String,
+    source_id: String,
+    request: ResetSourceCheckpointRequest,
+    metastore: Arc<dyn Metastore>,
+) -> Result<SourceCheckpointResponse, ApiError> {
+    // The pipeline indexing the source would otherwise publish its in-flight positions on top of
+    // the reset checkpoint.
+    if !is_source_paused(&index_id, &source_id) {
+        return Err(ApiError::SourceNotPausedForReset {
+            index_id,
+            source_id,
+        });
+    }
+    let checkpoint = match (request.positions, request.timestamp) {
+        (Some(_), Some(_)) => {
+            return Err(ApiError::InvalidArgument(
+                "`positions` and `timestamp` are mutually exclusive".to_string(),
+            ))
+        }
+        (Some(positions), None) => positions,
+        (None, Some(timestamp)) => {
+            let index_metadata = metastore.index_metadata(&index_id).await?;
+            let source_config = index_metadata.sources.get(&source_id).ok_or_else(|| {
+                MetastoreError::SourceDoesNotExist {
+                    source_id: source_id.clone(),
+                }
+            })?;
+            source_checkpoint_at_timestamp(source_config, timestamp)
+                .await
+                .map_err(|error| ApiError::CheckpointResolutionError(error.to_string()))?
+        }
+        (None, None) => SourceCheckpoint::default(),
+    };
+    metastore
+        .reset_source_checkpoint(&index_id, &source_id, checkpoint.clone())
+        .await?;
+    record_source_checkpoint_reset(&index_id, &source_id);
+    Ok(SourceCheckpointResponse { checkpoint })
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_indexing::{pause_source, resume_source};
+    use quickwit_metastore::checkpoint::{PartitionId, Position};
+    use quickwit_metastore::MockMetastore;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_source_checkpoint_api() {
+        let index_id = "test-source-checkpoint-api";
+        let checkpoint_path = format!("/api/v1/indexes/{}/sources/my-source/checkpoint", index_id);
+        let mut metastore = MockMetastore::new();
+        metastore
+            .expect_source_checkpoint()
+            .returning(|index_id, source_id| {
+                assert_eq!(index_id, "test-source-checkpoint-api");
+                assert_eq!(source_id, "my-source");
+                let checkpoint: SourceCheckpoint =
+                    vec![(PartitionId::from("partition-0"), Position::from(42u64))]
+                        .into_iter()
+                        .collect();
+                Ok(checkpoint)
+            });
+        metastore
+            .expect_reset_source_checkpoint()
+            .times(1)
+            .returning(|index_id, source_id, checkpoint| {
+                assert_eq!(index_id, "test-source-checkpoint-api");
+                assert_eq!(source_id, "my-source");
+                assert_eq!(
+                    checkpoint.position_for_partition(&PartitionId::from("partition-0")),
+                    Some(&Position::from(7u64))
+                );
+                Ok(())
+            });
+        let handler = source_checkpoint_handler(Arc::new(metastore));
+
+        let response = warp::test::request()
+            .path(&checkpoint_path)
+            .reply(&handler)
+            .await;
+        assert_eq!(response.status(), 200);
+        let source_checkpoint: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(
+            source_checkpoint["checkpoint"],
+            serde_json::json!({"partition-0": "00000000000000000042"})
+        );
+
+        let reset_body = serde_json::json!({"positions": {"partition-0": "00000000000000000007"}});
+        let response = warp::test::request()
+            .method("PUT")
+            .path(&checkpoint_path)
+            .json(&reset_body)
+            .reply(&handler)
+            .await;
+        assert_eq!(response.status(), 409);
+
+        pause_source(index_id, "my-source");
+        let response = warp::test::request()
+            .method("PUT")
+            .path(&checkpoint_path)
+            .json(&reset_body)
+            .reply(&handler)
+            .await;
+        assert_eq!(response.status(), 200);
+        resume_source(index_id, "my-source");
+    }
+}
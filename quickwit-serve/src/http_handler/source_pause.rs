@@ -0,0 +1,131 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+
+use quickwit_indexing::{pause_source, paused_sources, resume_source};
+use serde::Serialize;
+use warp::{Filter, Rejection};
+
+use crate::rest::Format;
+use crate::ApiError;
+
+/// Paused sources of the node, per index ID.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct PausedSourcesResponse {
+    paused_sources: BTreeMap<String, Vec<String>>,
+}
+
+/// Source pause handler: lists the paused sources of the node, and pauses or resumes the
+/// indexing of a source.
+pub fn source_pause_handler() -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone
+{
+    warp::path!("api" / "v1" / "paused-sources")
+        .and(warp::get())
+        .and_then(get_paused_sources)
+        .or(
+            warp::path!("api" / "v1" / "indexes" / String / "sources" / String / "pause")
+                .and(warp::put())
+                .and_then(put_source_pause),
+        )
+        .or(
+            warp::path!("api" / "v1" / "indexes" / String / "sources" / String / "pause")
+                .and(warp::delete())
+                .and_then(delete_source_pause),
+        )
+}
+
+async fn get_paused_sources() -> Result<impl warp::Reply, Infallible> {
+    Ok(Format::PrettyJson.make_reply(Ok::<_, ApiError>(paused_sources_response())))
+}
+
+async fn put_source_pause(
+    index_id: String,
+    source_id: String,
+) -> Result<impl warp::Reply, Infallible> {
+    pause_source(&index_id, &source_id);
+    Ok(Format::PrettyJson.make_reply(Ok::<_, ApiError>(paused_sources_response())))
+}
+
+async fn delete_source_pause(
+    index_id: String,
+    source_id: String,
+) -> Result<impl warp::Reply, Infallible> {
+    let reply_res = if resume_source(&index_id, &source_id) {
+        Ok(paused_sources_response())
+    } else {
+        Err(ApiError::SourceNotPaused {
+            index_id,
+            source_id,
+        })
+    };
+    Ok(Format::PrettyJson.make_reply(reply_res))
+}
+
+fn paused_sources_response() -> PausedSourcesResponse {
+    PausedSourcesResponse {
+        paused_sources: paused_sources(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_source_pause_api() {
+        let index_id = "test-source-pause-api";
+        let pause_path = format!("/api/v1/indexes/{}/sources/my-source/pause", index_id);
+        let response = warp::test::request()
+            .method("PUT")
+            .path(&pause_path)
+            .reply(&source_pause_handler())
+            .await;
+        assert_eq!(response.status(), 200);
+
+        let response = warp::test::request()
+            .path("/api/v1/paused-sources")
+            .reply(&source_pause_handler())
+            .await;
+        assert_eq!(response.status(), 200);
+        let paused_sources: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(
+            paused_sources["pausedSources"][index_id],
+            serde_json::json!(["my-source"])
+        );
+
+        let response = warp::test::request()
+            .method("DELETE")
+            .path(&pause_path)
+            .reply(&source_pause_handler())
+            .await;
+        assert_eq!(response.status(), 200);
+        let paused_sources: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert!(paused_sources["pausedSources"].get(index_id).is_none());
+
+        let response = warp::test::request()
+            .method("DELETE")
+            .path(&pause_path)
+            .reply(&source_pause_handler())
+            .await;
+        assert_eq!(response.status(), 404);
+    }
+}
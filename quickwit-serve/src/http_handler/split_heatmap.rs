@@ -0,0 +1,140 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use quickwit_metastore::Metastore;
+use quickwit_search::{split_heatmap, SplitHeatmapOptions};
+use serde::Deserialize;
+use warp::{Filter, Rejection};
+
+use crate::rest::Format;
+use crate::ApiError;
+
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+struct SplitHeatmapQuery {
+    /// Only reports the index with this ID.
+    #[serde(default)]
+    index_id: Option<String>,
+    /// Width of the time range buckets of the heatmap.
+    #[serde(default = "default_bucket_width_secs")]
+    bucket_width_secs: i64,
+    /// Number of hottest splits listed per index.
+    #[serde(default = "default_num_hottest_splits")]
+    num_hottest_splits: usize,
+}
+
+fn default_bucket_width_secs() -> i64 {
+    86_400
+}
+
+fn default_num_hottest_splits() -> usize {
+    20
+}
+
+/// Split heatmap handler: reports how often and how recently the searcher accessed the splits of
+/// the indexes, bucketed by the time range of the splits.
+pub fn split_heatmap_handler(
+    metastore: Arc<dyn Metastore>,
+) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "v1" / "split-heatmap")
+        .and(warp::get())
+        .and(serde_qs::warp::query(serde_qs::Config::default()))
+        .and(warp::any().map(move || metastore.clone()))
+        .and_then(get_split_heatmap)
+}
+
+async fn get_split_heatmap(
+    query: SplitHeatmapQuery,
+    metastore: Arc<dyn Metastore>,
+) -> Result<impl warp::Reply, Infallible> {
+    let options = SplitHeatmapOptions {
+        index_id_opt: query.index_id,
+        bucket_width_secs: query.bucket_width_secs,
+        num_hottest_splits: query.num_hottest_splits,
+    };
+    let split_heatmap_res = split_heatmap(metastore.as_ref(), &options)
+        .await
+        .map_err(ApiError::SearchError);
+    Ok(Format::PrettyJson.make_reply(split_heatmap_res))
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_metastore::{MockMetastore, Split, SplitMetadata, SplitState};
+    use quickwit_search::split_access_tracker;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_split_heatmap_api() {
+        let mut metastore = MockMetastore::new();
+        metastore
+            .expect_list_splits()
+            .returning(|index_id, split_state, _, _| {
+                assert_eq!(index_id, "test-heatmap-index");
+                assert_eq!(split_state, SplitState::Published);
+                Ok(vec![
+                    Split {
+                        split_state: SplitState::Published,
+                        update_timestamp: 0,
+                        split_metadata: SplitMetadata {
+                            split_id: "test-heatmap-split-1".to_string(),
+                            time_range: Some(86_400..=86_500),
+                            footer_offsets: 0..100,
+                            ..Default::default()
+                        },
+                    },
+                    Split {
+                        split_state: SplitState::Published,
+                        update_timestamp: 0,
+                        split_metadata: SplitMetadata {
+                            split_id: "test-heatmap-split-2".to_string(),
+                            time_range: Some(0..=100),
+                            footer_offsets: 0..100,
+                            ..Default::default()
+                        },
+                    },
+                ])
+            });
+        split_access_tracker().record_accesses("test-heatmap-index", ["test-heatmap-split-1"]);
+        let response = warp::test::request()
+            .path("/api/v1/split-heatmap?indexId=test-heatmap-index&numHottestSplits=5")
+            .reply(&split_heatmap_handler(Arc::new(metastore)))
+            .await;
+        assert_eq!(response.status(), 200);
+        let split_heatmap: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        let index_heatmap = &split_heatmap["indexes"]["test-heatmap-index"];
+        assert_eq!(index_heatmap["numSplits"], 2);
+        assert_eq!(index_heatmap["numAccessedSplits"], 1);
+        assert_eq!(index_heatmap["footerNumBytes"], 200);
+        let buckets = index_heatmap["buckets"].as_array().unwrap();
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[0]["startTimestamp"], 0);
+        assert_eq!(buckets[0]["numAccesses"], 0);
+        assert_eq!(buckets[1]["startTimestamp"], 86_400);
+        assert_eq!(buckets[1]["numAccesses"], 1);
+        let hottest_splits = index_heatmap["hottestSplits"].as_array().unwrap();
+        assert_eq!(hottest_splits.len(), 1);
+        assert_eq!(hottest_splits[0]["splitId"], "test-heatmap-split-1");
+    }
+}
@@ -0,0 +1,231 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+
+use quickwit_storage::{storage_costs, StorageActivity, StorageCosts};
+use serde::{Deserialize, Serialize};
+use warp::{Filter, Rejection};
+
+use crate::rest::Format;
+use crate::ApiError;
+
+/// Prices used to estimate the storage costs, in any currency, per 1,000 requests.
+#[derive(Deserialize, Debug, Default, PartialEq, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+struct StorageCostsQuery {
+    /// Price of 1,000 GET or HEAD requests.
+    #[serde(default)]
+    get_request_price: Option<f64>,
+    /// Price of 1,000 PUT or LIST requests.
+    #[serde(default)]
+    put_request_price: Option<f64>,
+}
+
+impl StorageCostsQuery {
+    fn estimate_cost(&self, storage_costs: &StorageCosts) -> Option<f64> {
+        if self.get_request_price.is_none() && self.put_request_price.is_none() {
+            return None;
+        }
+        let get_requests_cost =
+            self.get_request_price.unwrap_or(0.0) * storage_costs.num_get_requests as f64 / 1_000.0;
+        let put_requests_cost =
+            self.put_request_price.unwrap_or(0.0) * storage_costs.num_put_requests as f64 / 1_000.0;
+        Some(get_requests_cost + put_requests_cost)
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct StorageCostsEntry {
+    #[serde(flatten)]
+    costs: StorageCosts,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    estimated_cost: Option<f64>,
+}
+
+/// Storage costs of the node, per index ID and per activity.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct StorageCostsResponse {
+    indexes: BTreeMap<String, BTreeMap<StorageActivity, StorageCostsEntry>>,
+    total: StorageCostsEntry,
+}
+
+/// Storage costs handler: reports the requests and bytes sent to the storage by the node since
+/// it started, per index and per activity (indexing, merge, search).
+pub fn storage_costs_handler() -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone
+{
+    warp::path!("api" / "v1" / "storage-costs")
+        .and(warp::get())
+        .and(serde_qs::warp::query(serde_qs::Config::default()))
+        .and_then(get_storage_costs)
+}
+
+async fn get_storage_costs(query: StorageCostsQuery) -> Result<impl warp::Reply, Infallible> {
+    Ok(
+        Format::PrettyJson.make_reply(Ok::<_, ApiError>(storage_costs_response(
+            storage_costs(),
+            query,
+        ))),
+    )
+}
+
+fn storage_costs_response(
+    storage_costs: BTreeMap<String, BTreeMap<StorageActivity, StorageCosts>>,
+    query: StorageCostsQuery,
+) -> StorageCostsResponse {
+    let mut total_costs = StorageCosts::default();
+    let indexes = storage_costs
+        .into_iter()
+        .map(|(index_id, activity_costs)| {
+            let entries = activity_costs
+                .into_iter()
+                .map(|(activity, costs)| {
+                    total_costs.num_get_requests += costs.num_get_requests;
+                    total_costs.num_put_requests += costs.num_put_requests;
+                    total_costs.num_delete_requests += costs.num_delete_requests;
+                    total_costs.num_bytes_read += costs.num_bytes_read;
+                    total_costs.num_bytes_written += costs.num_bytes_written;
+                    let entry = StorageCostsEntry {
+                        costs,
+                        estimated_cost: query.estimate_cost(&costs),
+                    };
+                    (activity, entry)
+                })
+                .collect();
+            (index_id, entries)
+        })
+        .collect();
+    StorageCostsResponse {
+        indexes,
+        total: StorageCostsEntry {
+            costs: total_costs,
+            estimated_cost: query.estimate_cost(&total_costs),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use quickwit_storage::{account_storage_costs, RamStorage, Storage};
+
+    use super::*;
+
+    #[test]
+    fn test_storage_costs_response() {
+        let mut storage_costs = BTreeMap::new();
+        storage_costs.insert(
+            "my-index".to_string(),
+            [
+                (
+                    StorageActivity::Indexing,
+                    StorageCosts {
+                        num_put_requests: 2_000,
+                        num_bytes_written: 100,
+                        ..Default::default()
+                    },
+                ),
+                (
+                    StorageActivity::Search,
+                    StorageCosts {
+                        num_get_requests: 10_000,
+                        num_bytes_read: 1_000,
+                        ..Default::default()
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let response = storage_costs_response(storage_costs.clone(), StorageCostsQuery::default());
+        assert_eq!(
+            response.total.costs,
+            StorageCosts {
+                num_get_requests: 10_000,
+                num_put_requests: 2_000,
+                num_bytes_read: 1_000,
+                num_bytes_written: 100,
+                ..Default::default()
+            }
+        );
+        assert!(response.total.estimated_cost.is_none());
+
+        let query = StorageCostsQuery {
+            get_request_price: Some(0.0004),
+            put_request_price: Some(0.005),
+        };
+        let response = storage_costs_response(storage_costs, query);
+        let index_costs = &response.indexes["my-index"];
+        assert!(
+            (index_costs[&StorageActivity::Indexing]
+                .estimated_cost
+                .unwrap()
+                - 0.01)
+                .abs()
+                < 1e-9
+        );
+        assert!(
+            (index_costs[&StorageActivity::Search]
+                .estimated_cost
+                .unwrap()
+                - 0.004)
+                .abs()
+                < 1e-9
+        );
+        assert!((response.total.estimated_cost.unwrap() - 0.014).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_storage_costs_api() {
+        let index_id = "test-storage-costs-api";
+        let storage = account_storage_costs(
+            Arc::new(RamStorage::default()),
+            index_id,
+            StorageActivity::Indexing,
+        );
+        storage
+            .put(Path::new("split"), Box::new(b"split".to_vec()))
+            .await
+            .unwrap();
+
+        let response = warp::test::request()
+            .path("/api/v1/storage-costs?putRequestPrice=5")
+            .reply(&storage_costs_handler())
+            .await;
+        assert_eq!(response.status(), 200);
+        let storage_costs: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        let indexing_costs = &storage_costs["indexes"][index_id]["indexing"];
+        assert_eq!(indexing_costs["numPutRequests"], 1);
+        assert_eq!(indexing_costs["numBytesWritten"], 5);
+        assert_eq!(indexing_costs["estimatedCost"], 0.005);
+
+        let rejection = warp::test::request()
+            .path("/api/v1/storage-costs?putPrice=5")
+            .filter(&storage_costs_handler())
+            .await
+            .unwrap_err();
+        assert!(rejection.find::<serde_qs::Error>().is_some());
+    }
+}
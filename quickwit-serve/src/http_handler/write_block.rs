@@ -0,0 +1,116 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+
+use quickwit_indexing::{apply_write_block, clear_write_block, write_blocks, WriteBlockReason};
+use serde::Serialize;
+use warp::{Filter, Rejection};
+
+use crate::rest::Format;
+use crate::ApiError;
+
+/// Write blocks of the node, per index ID.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct WriteBlocksResponse {
+    write_blocks: BTreeMap<String, WriteBlockReason>,
+}
+
+/// Write block handler: lists the write-blocked indexes of the node, and blocks or unblocks the
+/// indexing of an index.
+pub fn write_block_handler() -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
+    warp::path!("api" / "v1" / "write-blocks")
+        .and(warp::get())
+        .and_then(get_write_blocks)
+        .or(
+            warp::path!("api" / "v1" / "indexes" / String / "write-block")
+                .and(warp::put())
+                .and_then(put_write_block),
+        )
+        .or(
+            warp::path!("api" / "v1" / "indexes" / String / "write-block")
+                .and(warp::delete())
+                .and_then(delete_write_block),
+        )
+}
+
+async fn get_write_blocks() -> Result<impl warp::Reply, Infallible> {
+    Ok(Format::PrettyJson.make_reply(Ok::<_, ApiError>(write_blocks_response())))
+}
+
+async fn put_write_block(index_id: String) -> Result<impl warp::Reply, Infallible> {
+    apply_write_block(&index_id, WriteBlockReason::Manual);
+    Ok(Format::PrettyJson.make_reply(Ok::<_, ApiError>(write_blocks_response())))
+}
+
+async fn delete_write_block(index_id: String) -> Result<impl warp::Reply, Infallible> {
+    let reply_res = match clear_write_block(&index_id) {
+        Some(_) => Ok(write_blocks_response()),
+        None => Err(ApiError::WriteBlockNotFound { index_id }),
+    };
+    Ok(Format::PrettyJson.make_reply(reply_res))
+}
+
+fn write_blocks_response() -> WriteBlocksResponse {
+    WriteBlocksResponse {
+        write_blocks: write_blocks(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_write_block_api() {
+        let index_id = "test-write-block-api";
+        let response = warp::test::request()
+            .method("PUT")
+            .path(&format!("/api/v1/indexes/{}/write-block", index_id))
+            .reply(&write_block_handler())
+            .await;
+        assert_eq!(response.status(), 200);
+
+        let response = warp::test::request()
+            .path("/api/v1/write-blocks")
+            .reply(&write_block_handler())
+            .await;
+        assert_eq!(response.status(), 200);
+        let write_blocks: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(write_blocks["writeBlocks"][index_id], "manual");
+
+        let response = warp::test::request()
+            .method("DELETE")
+            .path(&format!("/api/v1/indexes/{}/write-block", index_id))
+            .reply(&write_block_handler())
+            .await;
+        assert_eq!(response.status(), 200);
+        let write_blocks: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert!(write_blocks["writeBlocks"].get(index_id).is_none());
+
+        let response = warp::test::request()
+            .method("DELETE")
+            .path(&format!("/api/v1/indexes/{}/write-block", index_id))
+            .reply(&write_block_handler())
+            .await;
+        assert_eq!(response.status(), 404);
+    }
+}
@@ -0,0 +1,300 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Long-running operations, such as exports and garbage collection runs, run as jobs: the REST
+//! API records the job in the metastore, runs it in the background, and returns right away.
+//! Clients then poll the record of the job, which holds its state and progress.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use quickwit_metastore::{Job, JobKind, JobState, Metastore, MetastoreError, MetastoreResult};
+use quickwit_search::now_timestamp;
+use serde_json::Value as JsonValue;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use ulid::Ulid;
+
+/// Submits, runs and cancels the jobs of the node.
+#[derive(Clone)]
+pub struct JobService {
+    metastore: Arc<dyn Metastore>,
+    node_id: String,
+    /// Tasks of the jobs running on this node, aborted when their job is cancelled.
+    running_jobs: Arc<Mutex<HashMap<String, JoinHandle<()>>>>,
+}
+
+impl JobService {
+    /// Creates a job service running its jobs on the node `node_id`.
+    pub fn new(metastore: Arc<dyn Metastore>, node_id: String) -> Self {
+        JobService {
+            metastore,
+            node_id,
+            running_jobs: Default::default(),
+        }
+    }
+
+    /// Returns the metastore holding the job records.
+    pub fn metastore(&self) -> Arc<dyn Metastore> {
+        self.metastore.clone()
+    }
+
+    /// Records a new job on the index `index_id`, runs it in the background, and returns its
+    /// record.
+    ///
+    /// `run_job` reports the progress of the job through the [`JobContext`] it is passed. The job
+    /// succeeds if the future it returns resolves to `Ok`, and fails otherwise.
+    pub async fn submit<F, Fut>(
+        &self,
+        index_id: &str,
+        kind: JobKind,
+        run_job: F,
+    ) -> MetastoreResult<Job>
+    where
+        F: FnOnce(JobContext) -> Fut,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let now_timestamp = now_timestamp();
+        let job = Job {
+            job_id: Ulid::new().to_string(),
+            index_id: index_id.to_string(),
+            kind,
+            state: JobState::Running,
+            progress: 0.0,
+            node_id: self.node_id.clone(),
+            create_timestamp: now_timestamp,
+            update_timestamp: now_timestamp,
+            details: JsonValue::Null,
+            error: None,
+        };
+        self.metastore.create_job(job.clone()).await?;
+        let ctx = JobContext {
+            metastore: self.metastore.clone(),
+            job_id: job.job_id.clone(),
+            job: Arc::new(tokio::sync::Mutex::new(job.clone())),
+        };
+        let job_future = run_job(ctx.clone());
+        let running_jobs = self.running_jobs.clone();
+        // The lock is held until the task is registered, so that the task cannot unregister
+        // itself before.
+        let mut running_jobs_guard = self.running_jobs.lock().unwrap();
+        let job_id = job.job_id.clone();
+        let job_handle = tokio::spawn(async move {
+            info!(job_id = %ctx.job_id, "job-start");
+            let job_res = job_future.await;
+            ctx.finish(job_res).await;
+            running_jobs.lock().unwrap().remove(&ctx.job_id);
+        });
+        running_jobs_guard.insert(job_id, job_handle);
+        Ok(job)
+    }
+
+    /// Returns the record of the job `job_id`.
+    pub async fn job(&self, job_id: &str) -> MetastoreResult<Job> {
+        self.metastore.job(job_id).await
+    }
+
+    /// Returns the jobs, most recent first, optionally restricted to the jobs of an index.
+    pub async fn list_jobs(&self, index_id_opt: Option<&str>) -> MetastoreResult<Vec<Job>> {
+        self.metastore.list_jobs(index_id_opt).await
+    }
+
+    /// Cancels the job `job_id`, and returns its record. Fails with
+    /// [`MetastoreError::JobNotRunning`] if the job already reached a final state.
+    ///
+    /// A job running on this node stops right away. A job running on another node stops the next
+    /// time it reports its progress.
+    pub async fn cancel(&self, job_id: &str) -> MetastoreResult<Job> {
+        let mut job = self.metastore.job(job_id).await?;
+        if job.state.is_final() {
+            return Err(MetastoreError::JobNotRunning {
+                job_id: job.job_id,
+                state: job.state,
+            });
+        }
+        job.state = JobState::Cancelled;
+        job.update_timestamp = now_timestamp();
+        self.metastore.update_job(job.clone()).await?;
+        if let Some(job_handle) = self.running_jobs.lock().unwrap().remove(job_id) {
+            job_handle.abort();
+        }
+        info!(job_id = %job_id, "job-cancelled");
+        Ok(job)
+    }
+}
+
+/// Handle through which a running job reports its progress.
+#[derive(Clone)]
+pub struct JobContext {
+    metastore: Arc<dyn Metastore>,
+    job_id: String,
+    job: Arc<tokio::sync::Mutex<Job>>,
+}
+
+impl JobContext {
+    /// Returns the ID of the job.
+    pub fn job_id(&self) -> &str {
+        &self.job_id
+    }
+
+    /// Records the progress of the job, in percent, along with details specific to its kind.
+    ///
+    /// Fails with [`MetastoreError::JobNotRunning`] if the job was cancelled, in which case the
+    /// job should stop: returning the error does just that.
+    pub async fn report_progress(&self, progress: f64, details: JsonValue) -> anyhow::Result<()> {
+        let mut job = self.job.lock().await;
+        job.progress = progress.max(0.0).min(100.0);
+        job.details = details;
+        job.update_timestamp = now_timestamp();
+        self.metastore.update_job(job.clone()).await?;
+        Ok(())
+    }
+
+    /// Records the final state of the job.
+    async fn finish(&self, job_res: anyhow::Result<()>) {
+        let mut job = self.job.lock().await;
+        match job_res {
+            Ok(()) => {
+                info!(job_id = %self.job_id, "job-success");
+                job.state = JobState::Succeeded;
+                job.progress = 100.0;
+            }
+            Err(job_error) => {
+                if let Some(MetastoreError::JobNotRunning { .. }) =
+                    job_error.downcast_ref::<MetastoreError>()
+                {
+                    info!(job_id = %self.job_id, "Job stopped after it was cancelled.");
+                    return;
+                }
+                error!(job_id = %self.job_id, error = ?job_error, "Job failed.");
+                job.state = JobState::Failed;
+                job.error = Some(format!("{:#}", job_error));
+            }
+        }
+        job.update_timestamp = now_timestamp();
+        if let Err(metastore_error) = self.metastore.update_job(job.clone()).await {
+            warn!(job_id = %self.job_id, error = ?metastore_error, "Failed to record the final state of the job.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use quickwit_metastore::FileBackedMetastore;
+
+    use super::*;
+
+    async fn wait_for_final_state(job_service: &JobService, job_id: &str) -> Job {
+        loop {
+            let job = job_service.job(job_id).await.unwrap();
+            if job.state.is_final() {
+                return job;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_job_service_success_and_failure() {
+        let job_service = JobService::new(
+            Arc::new(FileBackedMetastore::for_test()),
+            "node-1".to_string(),
+        );
+        let job = job_service
+            .submit("my-index", JobKind::Export, |ctx| async move {
+                ctx.report_progress(50.0, serde_json::json!({"numDocs": 10}))
+                    .await
+            })
+            .await
+            .unwrap();
+        assert_eq!(job.state, JobState::Running);
+        assert_eq!(job.node_id, "node-1");
+        let job = wait_for_final_state(&job_service, &job.job_id).await;
+        assert_eq!(job.state, JobState::Succeeded);
+        assert_eq!(job.progress, 100.0);
+        assert_eq!(job.details, serde_json::json!({"numDocs": 10}));
+
+        let job = job_service
+            .submit("my-index", JobKind::GarbageCollection, |_ctx| async move {
+                anyhow::bail!("Storage unavailable.")
+            })
+            .await
+            .unwrap();
+        let job = wait_for_final_state(&job_service, &job.job_id).await;
+        assert_eq!(job.state, JobState::Failed);
+        assert_eq!(job.error.as_deref(), Some("Storage unavailable."));
+
+        let jobs = job_service.list_jobs(Some("my-index")).await.unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert!(job_service
+            .list_jobs(Some("other-index"))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_job_service_cancel() {
+        let job_service = JobService::new(
+            Arc::new(FileBackedMetastore::for_test()),
+            "node-1".to_string(),
+        );
+        let job = job_service
+            .submit("my-index", JobKind::Export, |_ctx| {
+                futures::future::pending::<anyhow::Result<()>>()
+            })
+            .await
+            .unwrap();
+        let cancelled_job = job_service.cancel(&job.job_id).await.unwrap();
+        assert_eq!(cancelled_job.state, JobState::Cancelled);
+        assert!(job_service.running_jobs.lock().unwrap().is_empty());
+        assert!(matches!(
+            job_service.cancel(&job.job_id).await.unwrap_err(),
+            MetastoreError::JobNotRunning { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_job_stops_when_cancelled_elsewhere() {
+        let metastore: Arc<dyn Metastore> = Arc::new(FileBackedMetastore::for_test());
+        let job_service = JobService::new(metastore.clone(), "node-1".to_string());
+        let (progress_tx, mut progress_rx) = tokio::sync::mpsc::channel(1);
+        let job = job_service
+            .submit("my-index", JobKind::Export, |ctx| async move {
+                loop {
+                    ctx.report_progress(0.0, JsonValue::Null).await?;
+                    progress_tx.send(()).await?;
+                }
+            })
+            .await
+            .unwrap();
+        progress_rx.recv().await.unwrap();
+        // Another node cancels the job through the metastore.
+        let mut cancelled_job = metastore.job(&job.job_id).await.unwrap();
+        cancelled_job.state = JobState::Cancelled;
+        metastore.update_job(cancelled_job).await.unwrap();
+        // The job stops at its next progress report, and its state is left as is.
+        while progress_rx.recv().await.is_some() {}
+        let job = metastore.job(&job.job_id).await.unwrap();
+        assert_eq!(job.state, JobState::Cancelled);
+    }
+}
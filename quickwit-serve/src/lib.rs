@@ -18,29 +18,49 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 mod args;
+mod auth;
+mod config_reloader;
 mod counters;
 mod error;
+mod export;
+mod garbage_collection;
 mod grpc;
 mod grpc_adapter;
 mod http_handler;
+mod jobs;
+mod merge;
+mod reindex;
 mod rest;
 
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
 
-use quickwit_cluster::cluster::Cluster;
+pub use quickwit_cluster::cluster::{Cluster, QuickwitService};
 use quickwit_cluster::service::ClusterServiceImpl;
-use quickwit_config::{QuickwitConfig, SEARCHER_CONFIG_INSTANCE};
+use quickwit_config::{set_searcher_config_instance, QuickwitConfig};
 use quickwit_metastore::Metastore;
-use quickwit_search::{ClusterClient, SearchClientPool, SearchServiceImpl};
+use quickwit_search::{
+    broadcast_split_search_load, cache_manifest_path, maintain_cache_manifest, save_cache_snapshot,
+    ClusterClient, SearchClientPool, SearchServiceImpl, SplitCatalogMetastore,
+};
 use quickwit_storage::quickwit_storage_uri_resolver;
-use tracing::{debug, info};
+use tracing::{debug, error, info};
 
 pub use crate::args::ServeArgs;
+#[cfg(unix)]
+pub use crate::config_reloader::reload_config_on_sighup;
+pub use crate::config_reloader::ConfigReloader;
 pub use crate::counters::COUNTERS;
 pub use crate::error::ApiError;
+pub use crate::export::{ExportJobDetails, ExportRequest};
+pub use crate::garbage_collection::{GarbageCollectionJobDetails, GarbageCollectionRequest};
 use crate::grpc::start_grpc_service;
 use crate::grpc_adapter::cluster_adapter::GrpcClusterAdapter;
 use crate::grpc_adapter::search_adapter::GrpcSearchAdapter;
+pub use crate::jobs::{JobContext, JobService};
+pub use crate::merge::{MergeJobDetails, MergeRequest};
+pub use crate::reindex::{ReindexJobDetails, ReindexRequest};
 use crate::rest::start_rest_service;
 
 /// Starts a search node, aka a `searcher`.
@@ -48,11 +68,20 @@ pub async fn run_searcher(
     quickwit_config: QuickwitConfig,
     metastore: Arc<dyn Metastore>,
 ) -> anyhow::Result<()> {
-    SEARCHER_CONFIG_INSTANCE
-        .set(quickwit_config.searcher_config.clone())
-        .expect("could not set searcher config in global once cell");
+    let cluster = join_cluster(&quickwit_config, QuickwitService::searcher()).await?;
+    let config_reloader = Arc::new(ConfigReloader::new(quickwit_config));
+    serve_quickwit(config_reloader, metastore, cluster).await
+}
+
+/// Joins the cluster, advertising the services run by the node so that requests are routed
+/// to the right nodes and leaders are elected among the nodes running a service.
+pub async fn join_cluster(
+    quickwit_config: &QuickwitConfig,
+    services: HashSet<QuickwitService>,
+) -> anyhow::Result<Arc<Cluster>> {
     let cluster = Arc::new(Cluster::new(
         quickwit_config.node_id.clone(),
+        services,
         quickwit_config.gossip_socket_addr()?,
     )?);
     for seed_socket_addr in quickwit_config.seed_socket_addrs()? {
@@ -61,11 +90,66 @@ pub async fn run_searcher(
         debug!(peer_seed_addr = %seed_socket_addr, "Add peer seed node.");
         cluster.add_peer_node(seed_socket_addr).await;
     }
+    Ok(cluster)
+}
+
+/// Serves the REST and gRPC APIs if the node runs a root or leaf searcher or the control plane.
+/// Otherwise, it only keeps the cluster membership of the node alive. The REST routes of the
+/// root searcher and of the control plane are only served by the nodes running them.
+pub async fn serve_quickwit(
+    config_reloader: Arc<ConfigReloader>,
+    metastore: Arc<dyn Metastore>,
+    cluster: Arc<Cluster>,
+) -> anyhow::Result<()> {
+    let quickwit_config = config_reloader.config();
+    let is_leaf_searcher = cluster
+        .available_services
+        .contains(&QuickwitService::LeafSearcher);
+    if !is_leaf_searcher
+        && !cluster
+            .available_services
+            .contains(&QuickwitService::RootSearcher)
+        && !cluster
+            .available_services
+            .contains(&QuickwitService::ControlPlane)
+    {
+        info!("Node joined the cluster without the searcher and control plane services.");
+        // Dropping the cluster would make the node leave it.
+        futures::future::pending::<()>().await;
+        return Ok(());
+    }
+    set_searcher_config_instance(quickwit_config.searcher_config.clone());
     let storage_uri_resolver = quickwit_storage_uri_resolver().clone();
+    let cache_manifest_save_interval_secs = quickwit_config
+        .searcher_config
+        .cache_manifest_save_interval_secs;
+    // The caches and the load only matter to the nodes searching splits.
+    if is_leaf_searcher && cache_manifest_save_interval_secs > 0 {
+        let cache_manifest_path = cache_manifest_path(&quickwit_config.data_dir_path);
+        tokio::spawn(maintain_cache_manifest(
+            cache_manifest_path,
+            storage_uri_resolver.clone(),
+            Duration::from_secs(cache_manifest_save_interval_secs),
+        ));
+    }
+    tokio::spawn(broadcast_split_search_load(cluster.clone()));
     let client_pool = SearchClientPool::create_and_keep_updated(cluster.clone()).await;
     let cluster_client = ClusterClient::new(client_pool.clone());
+    // Jobs are recorded in the metastore itself rather than in the split catalog cache.
+    let job_service = JobService::new(metastore.clone(), quickwit_config.node_id.clone());
+    let split_catalog_refresh_interval_secs = quickwit_config
+        .searcher_config
+        .split_catalog_refresh_interval_secs;
+    let search_metastore: Arc<dyn Metastore> = if split_catalog_refresh_interval_secs > 0 {
+        SplitCatalogMetastore::new(
+            metastore,
+            Duration::from_secs(split_catalog_refresh_interval_secs),
+        )
+    } else {
+        metastore
+    };
     let search_service = Arc::new(SearchServiceImpl::new(
-        metastore,
+        search_metastore,
         storage_uri_resolver,
         cluster_client,
         client_pool,
@@ -76,10 +160,22 @@ pub async fn run_searcher(
     let grpc_addr = quickwit_config.grpc_socket_addr()?;
     let grpc_search_service = GrpcSearchAdapter::from(search_service.clone());
     let grpc_cluster_service = GrpcClusterAdapter::from(cluster_service.clone());
-    let grpc_server = start_grpc_service(grpc_addr, grpc_search_service, grpc_cluster_service);
+    let grpc_server = start_grpc_service(
+        grpc_addr,
+        grpc_search_service,
+        grpc_cluster_service,
+        quickwit_config.searcher_config.grpc_compression,
+    );
 
     let rest_socket_addr = quickwit_config.rest_socket_addr()?;
-    let rest_server = start_rest_service(rest_socket_addr, search_service, cluster_service);
+    let rest_server = start_rest_service(
+        rest_socket_addr,
+        search_service,
+        cluster_service,
+        config_reloader,
+        job_service,
+        cluster.available_services.clone(),
+    );
     info!(
         "Searcher ready to accept requests at http://{}/",
         rest_socket_addr
@@ -88,6 +184,28 @@ pub async fn run_searcher(
     Ok(())
 }
 
+/// Saves the caches of the searcher along with their manifest, so that they are warmed up when
+/// the searcher restarts. Meant to be called from the shutdown path of the caller of
+/// [`serve_quickwit`], once the node stops serving requests.
+pub async fn save_searcher_caches(quickwit_config: &QuickwitConfig) {
+    if quickwit_config
+        .searcher_config
+        .cache_manifest_save_interval_secs
+        == 0
+    {
+        return;
+    }
+    let cache_manifest_path = cache_manifest_path(&quickwit_config.data_dir_path);
+    match save_cache_snapshot(&cache_manifest_path).await {
+        Ok((num_split_footers, num_fast_field_slices)) => info!(
+            num_split_footers = num_split_footers,
+            num_fast_field_slices = num_fast_field_slices,
+            "Saved searcher caches."
+        ),
+        Err(error) => error!(error = ?error, "Failed to save searcher caches."),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::net::SocketAddr;
@@ -132,6 +250,8 @@ mod tests {
             fast_field: "timestamp".to_string(),
             output_format: OutputFormat::Csv as i32,
             partition_by_field: None,
+            start_time_expression: None,
+            end_time_expression: None,
         };
         let mut metastore = MockMetastore::new();
         metastore
@@ -147,6 +267,12 @@ mod tests {
                 Ok(vec![mock_split("split_1"), mock_split("split_2")])
             },
         );
+        metastore
+            .expect_acquire_split_read_leases()
+            .returning(|_index_id, _split_ids, _holder_id, _ttl| Ok(()));
+        metastore
+            .expect_release_split_read_leases()
+            .returning(|_index_id, _holder_id| Ok(()));
         let mut mock_search_service = MockSearchService::new();
         let (result_sender, result_receiver) = tokio::sync::mpsc::unbounded_channel();
         result_sender.send(Ok(quickwit_proto::LeafSearchStreamResponse {
@@ -181,7 +307,8 @@ mod tests {
         start_test_server(grpc_addr, Arc::new(mock_search_service)).await?;
         let client_pool = SearchClientPool::for_addrs(&[grpc_addr]).await?;
         let cluster_client = ClusterClient::new(client_pool.clone());
-        let stream = root_search_stream(request, &metastore, cluster_client, &client_pool).await?;
+        let stream =
+            root_search_stream(request, Arc::new(metastore), cluster_client, &client_pool).await?;
         let result: Result<Vec<_>, SearchError> = stream.try_collect().await;
         assert!(result.is_err());
         assert_eq!(
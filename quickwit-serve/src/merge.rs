@@ -0,0 +1,133 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use quickwit_config::timestamp_resolution;
+use quickwit_indexing::merge_index;
+use quickwit_metastore::{Job, JobKind, Metastore, MetastoreResult, SplitState};
+use quickwit_storage::quickwit_storage_uri_resolver;
+use serde::{Deserialize, Serialize};
+
+use crate::jobs::{JobContext, JobService};
+
+/// Request of a merge job, passed as the JSON body of the merge REST API.
+#[derive(Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeRequest {
+    /// If set, only merges the splits overlapping the time range starting at `start_timestamp`
+    /// (in seconds).
+    pub start_timestamp: Option<i64>,
+    /// If set, only merges the splits overlapping the time range ending at `end_timestamp`
+    /// (in seconds, excluded).
+    pub end_timestamp: Option<i64>,
+}
+
+/// Details of a merge job, reported in the `details` field of its record.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeJobDetails {
+    /// Number of published splits in the time range before the merge.
+    pub num_splits_before: usize,
+    /// Number of published splits in the time range after the merge.
+    pub num_splits_after: usize,
+}
+
+/// Submits a job merging the splits of the index `index_id`, like the `merge` command. The merge
+/// pipeline stores its scratch files under `data_dir_path`.
+pub async fn submit_merge_job(
+    job_service: &JobService,
+    data_dir_path: PathBuf,
+    index_id: String,
+    merge_request: MergeRequest,
+) -> MetastoreResult<Job> {
+    let metastore = job_service.metastore();
+    let job_index_id = index_id.clone();
+    job_service
+        .submit(&job_index_id, JobKind::Merge, move |ctx| async move {
+            run_merge_job(ctx, metastore, data_dir_path, index_id, merge_request).await
+        })
+        .await
+}
+
+async fn run_merge_job(
+    ctx: JobContext,
+    metastore: Arc<dyn Metastore>,
+    data_dir_path: PathBuf,
+    index_id: String,
+    merge_request: MergeRequest,
+) -> anyhow::Result<()> {
+    // Split time ranges are expressed in the resolution of the index timestamp field.
+    let index_metadata = metastore.index_metadata(&index_id).await?;
+    let resolution = timestamp_resolution(
+        &index_metadata.doc_mapping,
+        &index_metadata.indexing_settings,
+    );
+    let compaction_time_range = Range {
+        start: merge_request
+            .start_timestamp
+            .map_or(i64::MIN, |start_timestamp| {
+                resolution.secs_to_units(start_timestamp)
+            }),
+        end: merge_request
+            .end_timestamp
+            .map_or(i64::MAX, |end_timestamp| {
+                resolution.secs_to_units(end_timestamp)
+            }),
+    };
+    let mut details = MergeJobDetails {
+        num_splits_before: metastore
+            .list_splits(
+                &index_id,
+                SplitState::Published,
+                Some(compaction_time_range.clone()),
+                None,
+            )
+            .await?
+            .len(),
+        num_splits_after: 0,
+    };
+    ctx.report_progress(0.0, serde_json::to_value(&details)?)
+        .await?;
+    // The merge pipeline is killed if the job is aborted, as the job future is then dropped.
+    merge_index(
+        index_id.clone(),
+        data_dir_path,
+        metastore.clone(),
+        quickwit_storage_uri_resolver().clone(),
+        true,
+        false,
+        Some(compaction_time_range.clone()),
+    )
+    .await?;
+    details.num_splits_after = metastore
+        .list_splits(
+            &index_id,
+            SplitState::Published,
+            Some(compaction_time_range),
+            None,
+        )
+        .await?
+        .len();
+    ctx.report_progress(100.0, serde_json::to_value(&details)?)
+        .await
+}
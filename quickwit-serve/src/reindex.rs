@@ -0,0 +1,185 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use anyhow::{bail, Context};
+use quickwit_doc_mapper::SOURCE_FIELD_NAME;
+use quickwit_indexing::ingest_api::ingest_api;
+use quickwit_indexing::write_block;
+use quickwit_metastore::{Job, JobKind, MetastoreResult};
+use quickwit_search::{now_timestamp, resolve_relative_times_in_query, SearchService};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use crate::jobs::{JobContext, JobService};
+
+/// Number of documents fetched per search request, and appended per batch to the ingest queue.
+const REINDEX_PAGE_NUM_DOCS: u64 = 10_000;
+
+/// Request of a reindex job, passed as the JSON body of the reindex REST API.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct ReindexRequest {
+    /// Index receiving the documents, through its ingest API source.
+    pub target_index_id: String,
+    /// Query text selecting the documents to reindex. The query language is that of tantivy.
+    pub query: String,
+    /// Fields to search on.
+    #[serde(default)]
+    pub search_fields: Vec<String>,
+    /// If set, restricts search to documents with a `timestamp >= start_timestamp`.
+    pub start_timestamp: Option<i64>,
+    /// If set, restricts search to documents with a `timestamp < end_timestamp``.
+    pub end_timestamp: Option<i64>,
+}
+
+/// Details of a reindex job, reported in the `details` field of its record.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReindexJobDetails {
+    /// Number of documents appended to the ingest queue of the target index so far.
+    pub num_docs_reindexed: u64,
+}
+
+/// Submits a job copying the documents of the index `index_id` matching `reindex_request` into
+/// its target index.
+pub async fn submit_reindex_job<TSearchService: SearchService>(
+    job_service: &JobService,
+    search_service: Arc<TSearchService>,
+    index_id: String,
+    reindex_request: ReindexRequest,
+) -> MetastoreResult<Job> {
+    let job_index_id = index_id.clone();
+    job_service
+        .submit(&job_index_id, JobKind::Reindex, move |ctx| async move {
+            run_reindex_job(ctx, &*search_service, index_id, reindex_request).await
+        })
+        .await
+}
+
+async fn run_reindex_job<TSearchService: SearchService>(
+    ctx: JobContext,
+    search_service: &TSearchService,
+    index_id: String,
+    reindex_request: ReindexRequest,
+) -> anyhow::Result<()> {
+    let target_index_id = reindex_request.target_index_id.clone();
+    let ingest_api = ingest_api().context("The ingest API is not available on this node.")?;
+    let queue = ingest_api.queue(&target_index_id)?.with_context(|| {
+        format!(
+            "Index `{}` has no ingest API source to receive the documents.",
+            target_index_id
+        )
+    })?;
+    let mut details = ReindexJobDetails::default();
+    let mut start_offset = 0;
+    // Like exports, the pages are searched on the splits pinned by the first page, and with the
+    // relative times of the query resolved once.
+    let mut snapshot_opt: Option<String> = None;
+    let query = resolve_relative_times_in_query(&reindex_request.query, now_timestamp())?;
+    loop {
+        let search_request = quickwit_proto::SearchRequest {
+            index_id: index_id.clone(),
+            query: query.clone(),
+            search_fields: reindex_request.search_fields.clone(),
+            start_timestamp: reindex_request.start_timestamp,
+            end_timestamp: reindex_request.end_timestamp,
+            max_hits: REINDEX_PAGE_NUM_DOCS,
+            start_offset,
+            sort_order: None,
+            sort_by_field: None,
+            terms_lookup: None,
+            preferred_searcher_nodes: Vec::new(),
+            profile: false,
+            pin_splits: snapshot_opt.is_none(),
+            snapshot: snapshot_opt.clone(),
+            start_time_expression: None,
+            end_time_expression: None,
+        };
+        let search_response = search_service.root_search(search_request).await?;
+        if snapshot_opt.is_none() {
+            snapshot_opt = search_response.snapshot.clone();
+        }
+        let num_docs = search_response.hits.len() as u64;
+        if num_docs == 0 {
+            break;
+        }
+        let docs = search_response
+            .hits
+            .iter()
+            .map(|hit| hit_source(&hit.json))
+            .collect::<anyhow::Result<Vec<String>>>()?;
+        // The write block is checked on every batch, as the ingest API does for each request.
+        if write_block(&target_index_id).is_some() {
+            bail!("Writes to index `{}` are blocked.", target_index_id);
+        }
+        let queue = queue.clone();
+        tokio::task::spawn_blocking(move || {
+            queue
+                .lock()
+                .expect("Ingest queue lock should not be poisoned.")
+                .append(&docs)
+        })
+        .await??;
+        start_offset += num_docs;
+        details.num_docs_reindexed += num_docs;
+        let progress = 100.0 * start_offset as f64 / search_response.num_hits.max(1) as f64;
+        ctx.report_progress(progress, serde_json::to_value(&details)?)
+            .await?;
+        if num_docs < REINDEX_PAGE_NUM_DOCS || start_offset >= search_response.num_hits {
+            break;
+        }
+    }
+    ctx.report_progress(100.0, serde_json::to_value(&details)?)
+        .await
+}
+
+/// Extracts the original document from a hit. Only the documents of indexes storing their source
+/// can be reindexed, as the stored fields alone may not hold the whole document.
+fn hit_source(hit_json: &str) -> anyhow::Result<String> {
+    let hit: JsonValue = serde_json::from_str(hit_json)?;
+    match hit.get(SOURCE_FIELD_NAME) {
+        Some(JsonValue::Array(values)) => match values.first() {
+            Some(JsonValue::String(source)) => Ok(source.clone()),
+            _ => bail!("The hit holds an invalid `{}` field.", SOURCE_FIELD_NAME),
+        },
+        _ => bail!(
+            "The source index must store the source of its documents (`store_source: true`) to be \
+             reindexed."
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hit_source() {
+        let hit_json = r#"{"title": ["foo"], "_source": ["{\"title\":\"foo\",\"extra\":1}"]}"#;
+        assert_eq!(
+            hit_source(hit_json).unwrap(),
+            r#"{"title":"foo","extra":1}"#
+        );
+        assert!(hit_source(r#"{"title": ["foo"]}"#).is_err());
+        assert!(hit_source(r#"{"_source": []}"#).is_err());
+    }
+}
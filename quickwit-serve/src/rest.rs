@@ -17,26 +17,51 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::HashSet;
 use std::convert::{Infallible, TryFrom};
 use std::net::SocketAddr;
 use std::sync::Arc;
 
+use bytes::Bytes;
 use futures::stream::StreamExt;
 use hyper::header::HeaderValue;
 use hyper::HeaderMap;
+use quickwit_cluster::cluster::QuickwitService;
 use quickwit_cluster::service::ClusterServiceImpl;
 use quickwit_common::metrics;
 use quickwit_doc_mapper::{SortByField, SortOrder};
-use quickwit_proto::{OutputFormat, SortOrder as ProtoSortOrder};
-use quickwit_search::{SearchResponseRest, SearchService, SearchServiceImpl};
+use quickwit_proto::{OutputFormat, SortOrder as ProtoSortOrder, TermsLookup};
+use quickwit_search::{
+    SearchError, SearchResponseRest, SearchService, SearchServiceImpl, TimeExpression,
+};
 use serde::{de, Deserialize, Deserializer};
 use tracing::info;
 use warp::hyper::header::CONTENT_TYPE;
 use warp::hyper::StatusCode;
 use warp::{reply, Filter, Rejection, Reply};
 
+use crate::auth::{is_masked, redact_doc, redact_hit, Authenticator, Caller, Unauthorized};
+use crate::config_reloader::ConfigReloader;
 use crate::http_handler::cluster::cluster_handler;
+use crate::http_handler::config_reload::config_reload_handler;
+use crate::http_handler::export::export_handler;
+use crate::http_handler::garbage_collection::garbage_collection_handler;
 use crate::http_handler::health_check::liveness_check_handler;
+use crate::http_handler::ingest::ingest_handler;
+use crate::http_handler::jobs::jobs_handler;
+use crate::http_handler::live_search::live_search_handler;
+use crate::http_handler::log_filter::log_filter_handler;
+use crate::http_handler::memory_usage::memory_usage_handler;
+use crate::http_handler::merge::merge_handler;
+use crate::http_handler::pipeline_throughput::pipeline_throughput_handler;
+use crate::http_handler::reindex::reindex_handler;
+use crate::http_handler::service_map::service_map_handler;
+use crate::http_handler::source_checkpoint::source_checkpoint_handler;
+use crate::http_handler::source_pause::source_pause_handler;
+use crate::http_handler::split_heatmap::split_heatmap_handler;
+use crate::http_handler::storage_costs::storage_costs_handler;
+use crate::http_handler::write_block::write_block_handler;
+use crate::jobs::JobService;
 use crate::ApiError;
 
 /// Start REST service given a HTTP address and a search service.
@@ -44,29 +69,105 @@ pub async fn start_rest_service(
     rest_addr: SocketAddr,
     search_service: Arc<SearchServiceImpl>,
     cluster_service: Arc<ClusterServiceImpl>,
+    config_reloader: Arc<ConfigReloader>,
+    job_service: JobService,
+    available_services: HashSet<QuickwitService>,
 ) -> anyhow::Result<()> {
     info!(rest_addr=?rest_addr, "Starting REST service.");
+    let root_searcher = require_service(&available_services, QuickwitService::RootSearcher);
+    let control_plane = require_service(&available_services, QuickwitService::ControlPlane);
+    let authenticator = Authenticator::new(config_reloader.subscribe());
+    let data_dir_path = config_reloader.config().data_dir_path;
     let request_counter = warp::log::custom(|_| {
         crate::COUNTERS.num_requests.inc();
     });
     let metrics_service = warp::path("metrics")
         .and(warp::get())
         .map(metrics::metrics_handler);
+    let api_routes = cluster_handler(cluster_service)
+        .or(config_reload_handler(config_reloader))
+        .or(log_filter_handler())
+        .or(memory_usage_handler())
+        .or(write_block_handler())
+        .or(source_pause_handler())
+        .or(pipeline_throughput_handler())
+        .or(ingest_handler())
+        .or(storage_costs_handler())
+        .or(root_searcher
+            .clone()
+            .and(split_heatmap_handler(search_service.metastore())))
+        .or(control_plane
+            .clone()
+            .and(source_checkpoint_handler(search_service.metastore())))
+        .or(root_searcher.clone().and(search_handler(
+            search_service.clone(),
+            authenticator.clone(),
+        )))
+        .or(root_searcher.clone().and(search_stream_handler(
+            search_service.clone(),
+            authenticator.clone(),
+        )))
+        .or(root_searcher.clone().and(live_search_handler(
+            search_service.clone(),
+            authenticator.clone(),
+        )))
+        .or(root_searcher
+            .clone()
+            .and(service_map_handler(search_service.clone())))
+        .or(root_searcher.clone().and(export_handler(
+            search_service.clone(),
+            job_service.clone(),
+            authenticator.clone(),
+        )))
+        .or(root_searcher.and(reindex_handler(
+            search_service,
+            job_service.clone(),
+            authenticator.clone(),
+        )))
+        .or(control_plane
+            .clone()
+            .and(garbage_collection_handler(job_service.clone())))
+        .or(control_plane
+            .clone()
+            .and(merge_handler(job_service.clone(), data_dir_path)))
+        .or(control_plane.and(jobs_handler(job_service)));
+    // The liveness checks and the metrics stay reachable without API key.
     let rest_routes = liveness_check_handler()
-        .or(cluster_handler(cluster_service))
-        .or(search_handler(search_service.clone()))
-        .or(search_stream_handler(search_service))
         .or(metrics_service)
+        .or(authenticator.require_auth().and(api_routes))
         .with(request_counter)
         .recover(recover_fn);
     warp::serve(rest_routes).run(rest_addr).await;
     Ok(())
 }
 
-fn default_max_hits() -> u64 {
+/// Rejects the requests as not found if the node does not run the given service, so that the
+/// routes of a service are only served by the nodes running it. The routes managing the node
+/// itself are served whatever its services.
+fn require_service(
+    available_services: &HashSet<QuickwitService>,
+    service: QuickwitService,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    let has_service = available_services.contains(&service);
+    warp::any()
+        .and_then(move || async move {
+            if has_service {
+                Ok(())
+            } else {
+                Err(warp::reject::not_found())
+            }
+        })
+        .untuple_one()
+}
+
+pub(crate) fn default_max_hits() -> u64 {
     20
 }
 
+fn default_max_terms() -> u64 {
+    1_000
+}
+
 /// Output format for the search results.
 #[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
@@ -133,9 +234,11 @@ pub struct SearchRequestQueryString {
     #[serde(deserialize_with = "from_simple_list")]
     pub search_fields: Option<Vec<String>>,
     /// If set, restrict search to documents with a `timestamp >= start_timestamp`.
-    pub start_timestamp: Option<i64>,
+    /// Accepts relative time expressions such as `now-15m`.
+    pub start_timestamp: Option<TimeExpression>,
     /// If set, restrict search to documents with a `timestamp < end_timestamp``.
-    pub end_timestamp: Option<i64>,
+    /// Accepts relative time expressions such as `now/d`.
+    pub end_timestamp: Option<TimeExpression>,
     /// Maximum number of hits to return (by default 20).
     #[serde(default = "default_max_hits")]
     pub max_hits: u64,
@@ -153,6 +256,62 @@ pub struct SearchRequestQueryString {
     #[serde(deserialize_with = "sort_by_field_mini_dsl")]
     #[serde(default)]
     sort_by_field: Option<SortByField>,
+    /// If set, restricts search to documents whose target field matches one of
+    /// the values returned by a lookup query.
+    #[serde(default)]
+    pub terms_lookup: Option<TermsLookupQueryString>,
+    /// IDs of the searcher nodes that the leaf searches are preferably assigned to.
+    /// Overrides the preferred searcher nodes of the index.
+    #[serde(default)]
+    #[serde(rename(deserialize = "preferredSearcherNode"))]
+    #[serde(deserialize_with = "from_simple_list")]
+    pub preferred_searcher_nodes: Option<Vec<String>>,
+    /// If set, keeps the query open and streams the matching documents as
+    /// new splits are published.
+    #[serde(default)]
+    pub tail: bool,
+    /// If set, the response includes a profile of the execution of the query on each split
+    /// and segment.
+    #[serde(default)]
+    pub profile: bool,
+    /// If set, the response includes a snapshot token pinning the searched splits.
+    #[serde(default)]
+    pub pin_splits: bool,
+    /// Snapshot token returned by a previous search. If set, the search runs on the splits
+    /// pinned by the token.
+    #[serde(default)]
+    pub snapshot: Option<String>,
+}
+
+/// This struct represents the terms lookup passed to the REST search API,
+/// e.g. `termsLookup[indexId]=alerts&termsLookup[query]=...`.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+pub struct TermsLookupQueryString {
+    /// Index the lookup query is run on.
+    pub index_id: String,
+    /// Lookup query.
+    pub query: String,
+    /// Field of the lookup index whose values are collected.
+    pub lookup_field: String,
+    /// Field of the searched index matched against the collected values.
+    pub target_field: String,
+    /// Maximum number of distinct values (by default 1000).
+    #[serde(default = "default_max_terms")]
+    pub max_terms: u64,
+}
+
+impl From<TermsLookupQueryString> for TermsLookup {
+    fn from(terms_lookup: TermsLookupQueryString) -> Self {
+        TermsLookup {
+            index_id: terms_lookup.index_id,
+            query: terms_lookup.query,
+            lookup_field: terms_lookup.lookup_field,
+            target_field: terms_lookup.target_field,
+            max_terms: terms_lookup.max_terms,
+        }
+    }
 }
 
 fn get_proto_search_by(search_request: &SearchRequestQueryString) -> (Option<i32>, Option<String>) {
@@ -167,26 +326,59 @@ fn get_proto_search_by(search_request: &SearchRequestQueryString) -> (Option<i32
     }
 }
 
-async fn search_endpoint<TSearchService: SearchService>(
+/// Splits a time bound of a request into the timestamp and the time expression fields of the
+/// search requests. Relative time expressions are resolved by the root search.
+pub(crate) fn time_bound_fields(
+    time_expression_opt: Option<TimeExpression>,
+) -> (Option<i64>, Option<String>) {
+    match time_expression_opt {
+        Some(TimeExpression::Absolute(timestamp)) => (Some(timestamp), None),
+        Some(time_expression) => (None, Some(time_expression.to_string())),
+        None => (None, None),
+    }
+}
+
+fn build_search_request(
     index_id: String,
     search_request: SearchRequestQueryString,
-    search_service: &TSearchService,
-) -> Result<SearchResponseRest, ApiError> {
+) -> Result<quickwit_proto::SearchRequest, ApiError> {
     let (sort_order, sort_by_field) = get_proto_search_by(&search_request);
-    let search_request = quickwit_proto::SearchRequest {
+    let (start_timestamp, start_time_expression) =
+        time_bound_fields(search_request.start_timestamp);
+    let (end_timestamp, end_time_expression) = time_bound_fields(search_request.end_timestamp);
+    Ok(quickwit_proto::SearchRequest {
         index_id,
         query: search_request.query,
         search_fields: search_request.search_fields.unwrap_or_default(),
-        start_timestamp: search_request.start_timestamp,
-        end_timestamp: search_request.end_timestamp,
+        start_timestamp,
+        end_timestamp,
         max_hits: search_request.max_hits,
         start_offset: search_request.start_offset,
         sort_order,
         sort_by_field,
-    };
+        terms_lookup: search_request.terms_lookup.map(TermsLookup::from),
+        preferred_searcher_nodes: search_request.preferred_searcher_nodes.unwrap_or_default(),
+        profile: search_request.profile,
+        pin_splits: search_request.pin_splits,
+        snapshot: search_request.snapshot,
+        start_time_expression,
+        end_time_expression,
+    })
+}
+
+async fn search_endpoint<TSearchService: SearchService>(
+    index_id: String,
+    search_request: SearchRequestQueryString,
+    masked_fields: &[String],
+    search_service: &TSearchService,
+) -> Result<SearchResponseRest, ApiError> {
+    let search_request = build_search_request(index_id, search_request)?;
     let search_response = search_service.root_search(search_request).await?;
-    let search_response_rest =
+    let mut search_response_rest =
         SearchResponseRest::try_from(search_response).map_err(ApiError::SearchError)?;
+    for hit in &mut search_response_rest.hits {
+        redact_doc(hit, masked_fields);
+    }
     Ok(search_response_rest)
 }
 
@@ -197,24 +389,60 @@ fn search_filter(
         .and(serde_qs::warp::query(serde_qs::Config::default()))
 }
 
+/// Streams the hits of a tail search as newline-delimited JSON documents.
+async fn search_tail_endpoint<TSearchService: SearchService>(
+    index_id: String,
+    search_request: SearchRequestQueryString,
+    masked_fields: Vec<String>,
+    search_service: &TSearchService,
+) -> Result<hyper::Body, ApiError> {
+    let search_request = build_search_request(index_id, search_request)?;
+    let hit_stream = search_service.root_search_tail(search_request).await?;
+    let data = hit_stream.map(move |hit_result| {
+        hit_result.and_then(|mut hit| {
+            redact_hit(&mut hit, &masked_fields)
+                .map_err(|error| SearchError::InternalError(error.to_string()))?;
+            let mut json = hit.json;
+            json.push('\n');
+            Ok(Bytes::from(json))
+        })
+    });
+    Ok(spawn_streaming_body(data))
+}
+
 async fn search<TSearchService: SearchService>(
     index_id: String,
     search_request: SearchRequestQueryString,
+    caller: Caller,
     search_service: Arc<TSearchService>,
-) -> Result<impl warp::Reply, Infallible> {
+) -> Result<warp::reply::Response, Infallible> {
     info!(index_id = %index_id, request =? search_request, "search");
-    Ok(search_request
-        .format
-        .make_reply(search_endpoint(index_id, search_request, &*search_service).await))
+    let masked_fields = caller.masked_fields(&index_id);
+    if search_request.tail {
+        let reply = make_streaming_reply(
+            search_tail_endpoint(index_id, search_request, masked_fields, &*search_service).await,
+        );
+        let reply_with_header = reply::with_header(reply, CONTENT_TYPE, "application/x-ndjson");
+        return Ok(reply_with_header.into_response());
+    }
+    let format = search_request.format;
+    Ok(format
+        .make_reply(
+            search_endpoint(index_id, search_request, &masked_fields, &*search_service).await,
+        )
+        .into_response())
 }
 
 /// REST search handler.
 ///
-/// Parses the search request from the
+/// Parses the search request from the query string, and removes the fields masked for the
+/// caller from the returned documents.
 pub fn search_handler<TSearchService: SearchService>(
     search_service: Arc<TSearchService>,
+    authenticator: Authenticator,
 ) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
     search_filter()
+        .and(authenticator.caller())
         .and(warp::any().map(move || search_service.clone()))
         .and_then(search)
 }
@@ -233,9 +461,11 @@ pub struct SearchStreamRequestQueryString {
     #[serde(deserialize_with = "from_simple_list")]
     pub search_fields: Option<Vec<String>>,
     /// If set, restricts search to documents with a `timestamp >= start_timestamp`.
-    pub start_timestamp: Option<i64>,
+    /// Accepts relative time expressions such as `now-15m`.
+    pub start_timestamp: Option<TimeExpression>,
     /// If set, restricts search to documents with a `timestamp < end_timestamp``.
-    pub end_timestamp: Option<i64>,
+    /// Accepts relative time expressions such as `now/d`.
+    pub end_timestamp: Option<TimeExpression>,
     /// The fast field to extract.
     #[serde(deserialize_with = "deserialize_not_empty_string")]
     pub fast_field: String,
@@ -244,24 +474,55 @@ pub struct SearchStreamRequestQueryString {
     pub output_format: OutputFormat,
     #[serde(default)]
     pub partition_by_field: Option<String>,
+    /// Not supported by the search stream API: accepted only to reject it with an explicit
+    /// error rather than as an unknown parameter.
+    #[serde(default)]
+    pub terms_lookup: Option<TermsLookupQueryString>,
 }
 
 async fn search_stream_endpoint<TSearchService: SearchService>(
     index_id: String,
     search_request: SearchStreamRequestQueryString,
+    masked_fields: &[String],
     search_service: &TSearchService,
 ) -> Result<hyper::Body, ApiError> {
+    if search_request.terms_lookup.is_some() {
+        return Err(ApiError::InvalidArgument(
+            "Terms lookups are not supported by the search stream API".to_string(),
+        ));
+    }
+    // The streamed values cannot be redacted, the request is rejected instead.
+    for field_name in std::iter::once(&search_request.fast_field)
+        .chain(search_request.partition_by_field.as_ref())
+    {
+        if is_masked(field_name, masked_fields) {
+            return Err(ApiError::FieldMasked {
+                field_name: field_name.clone(),
+            });
+        }
+    }
+    let (start_timestamp, start_time_expression) =
+        time_bound_fields(search_request.start_timestamp);
+    let (end_timestamp, end_time_expression) = time_bound_fields(search_request.end_timestamp);
     let request = quickwit_proto::SearchStreamRequest {
         index_id,
         query: search_request.query,
         search_fields: search_request.search_fields.unwrap_or_default(),
-        start_timestamp: search_request.start_timestamp,
-        end_timestamp: search_request.end_timestamp,
+        start_timestamp,
+        end_timestamp,
         fast_field: search_request.fast_field,
         output_format: search_request.output_format as i32,
         partition_by_field: search_request.partition_by_field,
+        start_time_expression,
+        end_time_expression,
     };
-    let mut data = search_service.root_search_stream(request).await?;
+    let data = search_service.root_search_stream(request).await?;
+    Ok(spawn_streaming_body(data))
+}
+
+/// Returns a body fed by a spawned task forwarding the `data` stream.
+fn spawn_streaming_body<S>(mut data: S) -> hyper::Body
+where S: futures::Stream<Item = quickwit_search::Result<Bytes>> + Send + Unpin + 'static {
     let (mut sender, body) = hyper::Body::channel();
     tokio::spawn(async move {
         while let Some(result) = data.next().await {
@@ -293,7 +554,7 @@ async fn search_stream_endpoint<TSearchService: SearchService>(
             };
         }
     });
-    Ok(body)
+    body
 }
 
 fn make_streaming_reply(result: Result<hyper::Body, ApiError>) -> impl Reply {
@@ -314,6 +575,7 @@ fn make_streaming_reply(result: Result<hyper::Body, ApiError>) -> impl Reply {
 async fn search_stream<TSearchService: SearchService>(
     index_id: String,
     request: SearchStreamRequestQueryString,
+    caller: Caller,
     search_service: Arc<TSearchService>,
 ) -> Result<impl warp::Reply, Infallible> {
     info!(index_id=%index_id,request=?request, "search_stream");
@@ -321,8 +583,10 @@ async fn search_stream<TSearchService: SearchService>(
         OutputFormat::ClickHouseRowBinary => "application/octet-stream",
         OutputFormat::Csv => "text/csv",
     };
-    let reply =
-        make_streaming_reply(search_stream_endpoint(index_id, request, &*search_service).await);
+    let masked_fields = caller.masked_fields(&index_id);
+    let reply = make_streaming_reply(
+        search_stream_endpoint(index_id, request, &masked_fields, &*search_service).await,
+    );
     let reply_with_header = reply::with_header(reply, CONTENT_TYPE, content_type);
     Ok(reply_with_header)
 }
@@ -336,8 +600,10 @@ fn search_stream_filter(
 
 pub fn search_stream_handler<TSearchService: SearchService>(
     search_service: Arc<TSearchService>,
+    authenticator: Authenticator,
 ) -> impl Filter<Extract = impl warp::Reply, Error = Rejection> + Clone {
     search_stream_filter()
+        .and(authenticator.caller())
         .and(warp::any().map(move || search_service.clone()))
         .and_then(search_stream)
 }
@@ -345,6 +611,9 @@ pub fn search_stream_handler<TSearchService: SearchService>(
 /// This function returns a formated error based on the given rejection reason.
 async fn recover_fn(rejection: Rejection) -> Result<impl Reply, Rejection> {
     // TODO handle more errors.
+    if rejection.find::<Unauthorized>().is_some() {
+        return Ok(Format::PrettyJson.make_reply(Err::<(), ApiError>(ApiError::Unauthorized)));
+    }
     match rejection.find::<serde_qs::Error>() {
         Some(err) => {
             // The querystring was incorrect.
@@ -364,7 +633,7 @@ where D: Deserializer<'de> {
     Ok(Some(string.into()))
 }
 
-fn from_simple_list<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+pub(crate) fn from_simple_list<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
 where D: Deserializer<'de> {
     let str_sequence = String::deserialize(deserializer)?;
     Ok(Some(
@@ -398,7 +667,6 @@ where D: Deserializer<'de> {
 #[cfg(test)]
 mod tests {
     use assert_json_diff::assert_json_include;
-    use bytes::Bytes;
     use mockall::predicate;
     use quickwit_search::{MockSearchService, SearchError};
     use serde_json::json;
@@ -412,12 +680,27 @@ mod tests {
             hits: Vec::new(),
             elapsed_time_micros: 0u64,
             errors: Vec::new(),
+            timings: Some(quickwit_proto::LeafSearchTimings {
+                warmup_micros: 300,
+                queue_wait_micros: 20,
+                cpu_micros: 100,
+                num_splits: 2,
+            }),
+            query_rewrites: Vec::new(),
+            split_profiles: Vec::new(),
+            snapshot: None,
         };
         let search_response_json: serde_json::Value = serde_json::to_value(&search_response)?;
         let expected_search_response_json: serde_json::Value = json!({
             "numHits": 55,
             "hits": [],
             "elapsedTimeMicros": 0,
+            "timings": {
+                "warmupMicros": 300,
+                "queueWaitMicros": 20,
+                "cpuMicros": 100,
+                "numSplits": 2,
+            },
         });
         assert_json_include!(
             actual: search_response_json,
@@ -444,11 +727,17 @@ mod tests {
                 query: "*".to_string(),
                 search_fields: None,
                 start_timestamp: None,
-                end_timestamp: Some(1450720000),
+                end_timestamp: Some(TimeExpression::Absolute(1450720000)),
                 max_hits: 10,
                 start_offset: 22,
                 format: Format::default(),
-                sort_by_field: None
+                sort_by_field: None,
+                terms_lookup: None,
+                preferred_searcher_nodes: None,
+                tail: false,
+                profile: false,
+                pin_splits: false,
+                snapshot: None
             }
         );
     }
@@ -471,11 +760,17 @@ mod tests {
                 query: "*".to_string(),
                 search_fields: Some(vec!["title".to_string(), "body".to_string()]),
                 start_timestamp: None,
-                end_timestamp: Some(1450720000),
+                end_timestamp: Some(TimeExpression::Absolute(1450720000)),
                 max_hits: 20,
                 start_offset: 0,
                 format: Format::default(),
-                sort_by_field: None
+                sort_by_field: None,
+                terms_lookup: None,
+                preferred_searcher_nodes: None,
+                tail: false,
+                profile: false,
+                pin_splits: false,
+                snapshot: None
             }
         );
     }
@@ -499,7 +794,13 @@ mod tests {
                 start_offset: 0,
                 format: Format::Json,
                 search_fields: None,
-                sort_by_field: None
+                sort_by_field: None,
+                terms_lookup: None,
+                preferred_searcher_nodes: None,
+                tail: false,
+                profile: false,
+                pin_splits: false,
+                snapshot: None
             }
         );
     }
@@ -525,7 +826,13 @@ mod tests {
                 sort_by_field: Some(SortByField {
                     field_name: "field".to_string(),
                     order: SortOrder::Asc
-                })
+                }),
+                terms_lookup: None,
+                preferred_searcher_nodes: None,
+                tail: false,
+                profile: false,
+                pin_splits: false,
+                snapshot: None
             }
         );
 
@@ -548,7 +855,13 @@ mod tests {
                 sort_by_field: Some(SortByField {
                     field_name: "field".to_string(),
                     order: SortOrder::Asc
-                })
+                }),
+                terms_lookup: None,
+                preferred_searcher_nodes: None,
+                tail: false,
+                profile: false,
+                pin_splits: false,
+                snapshot: None
             }
         );
 
@@ -571,16 +884,104 @@ mod tests {
                 sort_by_field: Some(SortByField {
                     field_name: "field".to_string(),
                     order: SortOrder::Desc
-                })
+                }),
+                terms_lookup: None,
+                preferred_searcher_nodes: None,
+                tail: false,
+                profile: false,
+                pin_splits: false,
+                snapshot: None
             }
         );
     }
 
+    #[tokio::test]
+    async fn test_rest_search_api_route_terms_lookup() {
+        let rest_search_api_filter = search_filter();
+        let (_, req) = warp::test::request()
+            .path(
+                "/api/v1/access-logs/search?query=status:500&termsLookup[indexId]=alerts&\
+                 termsLookup[query]=severity:high&termsLookup[lookupField]=ip&\
+                 termsLookup[targetField]=client_ip",
+            )
+            .filter(&rest_search_api_filter)
+            .await
+            .unwrap();
+        assert_eq!(
+            req.terms_lookup,
+            Some(TermsLookupQueryString {
+                index_id: "alerts".to_string(),
+                query: "severity:high".to_string(),
+                lookup_field: "ip".to_string(),
+                target_field: "client_ip".to_string(),
+                max_terms: 1_000,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rest_search_api_route_preferred_searcher_nodes() {
+        let rest_search_api_filter = search_filter();
+        let (index, req) = warp::test::request()
+            .path(
+                "/api/v1/quickwit-demo-index/search?query=*&preferredSearcherNode=searcher-1,\
+                 searcher-2",
+            )
+            .filter(&rest_search_api_filter)
+            .await
+            .unwrap();
+        assert_eq!(
+            req.preferred_searcher_nodes,
+            Some(vec!["searcher-1".to_string(), "searcher-2".to_string()])
+        );
+        let search_request = super::build_search_request(index, req).unwrap();
+        assert_eq!(
+            search_request.preferred_searcher_nodes,
+            vec!["searcher-1".to_string(), "searcher-2".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rest_search_api_route_profile() {
+        let rest_search_api_filter = search_filter();
+        let (index, req) = warp::test::request()
+            .path("/api/v1/quickwit-demo-index/search?query=*&profile=true")
+            .filter(&rest_search_api_filter)
+            .await
+            .unwrap();
+        assert!(req.profile);
+        let search_request = super::build_search_request(index, req).unwrap();
+        assert!(search_request.profile);
+    }
+
+    #[tokio::test]
+    async fn test_rest_search_api_route_snapshot() {
+        let rest_search_api_filter = search_filter();
+        let (index, req) = warp::test::request()
+            .path("/api/v1/quickwit-demo-index/search?query=*&pinSplits=true")
+            .filter(&rest_search_api_filter)
+            .await
+            .unwrap();
+        let search_request = super::build_search_request(index, req).unwrap();
+        assert!(search_request.pin_splits);
+        assert!(search_request.snapshot.is_none());
+
+        let (index, req) = warp::test::request()
+            .path("/api/v1/quickwit-demo-index/search?query=*&startOffset=20&snapshot=abc")
+            .filter(&rest_search_api_filter)
+            .await
+            .unwrap();
+        let search_request = super::build_search_request(index, req).unwrap();
+        assert!(!search_request.pin_splits);
+        assert_eq!(search_request.snapshot.as_deref(), Some("abc"));
+    }
+
     #[tokio::test]
     async fn test_rest_search_api_route_invalid_key() -> anyhow::Result<()> {
         let mock_search_service = MockSearchService::new();
         let rest_search_api_handler =
-            super::search_handler(Arc::new(mock_search_service)).recover(recover_fn);
+            super::search_handler(Arc::new(mock_search_service), Authenticator::default())
+                .recover(recover_fn);
         let resp = warp::test::request()
             .path("/api/v1/quickwit-demo-index/search?query=*&endUnixTimestamp=1450720000")
             .reply(&rest_search_api_handler)
@@ -588,7 +989,7 @@ mod tests {
         assert_eq!(resp.status(), 400);
         let resp_json: serde_json::Value = serde_json::from_slice(resp.body())?;
         let exp_resp_json = serde_json::json!({
-            "error": "InvalidArgument: failed with reason: unknown field `endUnixTimestamp`, expected one of `query`, `searchField`, `startTimestamp`, `endTimestamp`, `maxHits`, `startOffset`, `format`, `sortByField`."
+            "error": "InvalidArgument: failed with reason: unknown field `endUnixTimestamp`, expected one of `query`, `searchField`, `startTimestamp`, `endTimestamp`, `maxHits`, `startOffset`, `format`, `sortByField`, `termsLookup`, `preferredSearcherNode`, `tail`, `profile`, `pinSplits`, `snapshot`."
         });
         assert_eq!(resp_json, exp_resp_json);
         Ok(())
@@ -603,10 +1004,15 @@ mod tests {
                 num_hits: 10,
                 elapsed_time_micros: 16,
                 errors: vec![],
+                timings: None,
+                query_rewrites: Vec::new(),
+                split_profiles: Vec::new(),
+                snapshot: None,
             })
         });
         let rest_search_api_handler =
-            super::search_handler(Arc::new(mock_search_service)).recover(recover_fn);
+            super::search_handler(Arc::new(mock_search_service), Authenticator::default())
+                .recover(recover_fn);
         let resp = warp::test::request()
             .path("/api/v1/quickwit-demo-index/search?query=*")
             .reply(&rest_search_api_handler)
@@ -634,7 +1040,8 @@ mod tests {
             ))
             .returning(|_| Ok(Default::default()));
         let rest_search_api_handler =
-            super::search_handler(Arc::new(mock_search_service)).recover(recover_fn);
+            super::search_handler(Arc::new(mock_search_service), Authenticator::default())
+                .recover(recover_fn);
         assert_eq!(
             warp::test::request()
                 .path("/api/v1/quickwit-demo-index/search?query=*&startOffset=5&maxHits=30")
@@ -655,7 +1062,8 @@ mod tests {
             })
         });
         let rest_search_api_handler =
-            super::search_handler(Arc::new(mock_search_service)).recover(recover_fn);
+            super::search_handler(Arc::new(mock_search_service), Authenticator::default())
+                .recover(recover_fn);
         assert_eq!(
             warp::test::request()
                 .path("/api/v1/index-does-not-exist/search?query=myfield:test")
@@ -674,7 +1082,8 @@ mod tests {
             .expect_root_search()
             .returning(|_| Err(SearchError::InternalError("ty".to_string())));
         let rest_search_api_handler =
-            super::search_handler(Arc::new(mock_search_service)).recover(recover_fn);
+            super::search_handler(Arc::new(mock_search_service), Authenticator::default())
+                .recover(recover_fn);
         assert_eq!(
             warp::test::request()
                 .path("/api/v1/index-does-not-exist/search?query=myfield:test")
@@ -693,7 +1102,8 @@ mod tests {
             .expect_root_search()
             .returning(|_| Err(SearchError::InvalidQuery("invalid query".to_string())));
         let rest_search_api_handler =
-            super::search_handler(Arc::new(mock_search_service)).recover(recover_fn);
+            super::search_handler(Arc::new(mock_search_service), Authenticator::default())
+                .recover(recover_fn);
         assert_eq!(
             warp::test::request()
                 .path("/api/v1/my-index/search?query=myfield:test")
@@ -705,6 +1115,204 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_rest_search_api_relative_times() -> anyhow::Result<()> {
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service
+            .expect_root_search()
+            .with(predicate::function(
+                |search_request: &quickwit_proto::SearchRequest| {
+                    // The relative times are resolved by the root search.
+                    search_request.query == "timestamp:[now-1h TO now]"
+                        && search_request.start_timestamp.is_none()
+                        && search_request.start_time_expression.as_deref() == Some("now-15m")
+                        && search_request.end_timestamp == Some(1450720000)
+                        && search_request.end_time_expression.is_none()
+                },
+            ))
+            .returning(|_| Ok(Default::default()));
+        let rest_search_api_handler =
+            super::search_handler(Arc::new(mock_search_service), Authenticator::default())
+                .recover(recover_fn);
+        assert_eq!(
+            warp::test::request()
+                .path(
+                    "/api/v1/my-index/search?query=timestamp:%5Bnow-1h%20TO%20now%5D&\
+                     startTimestamp=now-15m&endTimestamp=1450720000",
+                )
+                .reply(&rest_search_api_handler)
+                .await
+                .status(),
+            200
+        );
+        for path in [
+            "/api/v1/my-index/search?query=*&startTimestamp=yesterday",
+            "/api/v1/my-index/search?query=*&startTimestamp=now%C3%A9",
+        ] {
+            assert_eq!(
+                warp::test::request()
+                    .path(path)
+                    .reply(&rest_search_api_handler)
+                    .await
+                    .status(),
+                400,
+                "{}",
+                path
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rest_search_api_tail() -> anyhow::Result<()> {
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service
+            .expect_root_search_tail()
+            .with(predicate::function(
+                |search_request: &quickwit_proto::SearchRequest| search_request.max_hits == 5,
+            ))
+            .return_once(|_| {
+                Ok(Box::pin(futures::stream::iter(vec![
+                    Ok(quickwit_proto::Hit {
+                        json: r#"{"title":["first"]}"#.to_string(),
+                        partial_hit: None,
+                    }),
+                    Ok(quickwit_proto::Hit {
+                        json: r#"{"title":["second"]}"#.to_string(),
+                        partial_hit: None,
+                    }),
+                ])))
+            });
+        let rest_search_api_handler =
+            super::search_handler(Arc::new(mock_search_service), Authenticator::default())
+                .recover(recover_fn);
+        let response = warp::test::request()
+            .path("/api/v1/my-index/search?query=*&maxHits=5&tail=true")
+            .reply(&rest_search_api_handler)
+            .await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "application/x-ndjson"
+        );
+        let body = String::from_utf8_lossy(response.body());
+        assert_eq!(body, "{\"title\":[\"first\"]}\n{\"title\":[\"second\"]}\n");
+        Ok(())
+    }
+
+    fn authenticator_with_field_masks() -> Authenticator {
+        let auth_config = quickwit_config::AuthConfig {
+            api_keys: vec![
+                quickwit_config::ApiKeyConfig {
+                    key: "admin-key".to_string(),
+                    role: "admin".to_string(),
+                },
+                quickwit_config::ApiKeyConfig {
+                    key: "analyst-key".to_string(),
+                    role: "analyst".to_string(),
+                },
+            ],
+            field_masks: vec![quickwit_config::FieldMaskConfig {
+                index_id: "my-index".to_string(),
+                roles: vec!["analyst".to_string()],
+                fields: vec!["user.email".to_string()],
+            }],
+        };
+        let config = quickwit_config::QuickwitConfig {
+            auth: Some(auth_config),
+            ..Default::default()
+        };
+        let (_config_tx, config_rx) = tokio::sync::watch::channel(config);
+        Authenticator::new(config_rx)
+    }
+
+    #[tokio::test]
+    async fn test_rest_search_api_field_masks() -> anyhow::Result<()> {
+        let mut mock_search_service = MockSearchService::new();
+        mock_search_service.expect_root_search().returning(|_| {
+            Ok(quickwit_proto::SearchResponse {
+                hits: vec![quickwit_proto::Hit {
+                    json: r#"{"title":["login"],"user":{"email":["alice@example.com"]}}"#
+                        .to_string(),
+                    partial_hit: None,
+                }],
+                num_hits: 1,
+                ..Default::default()
+            })
+        });
+        let rest_search_api_handler = super::search_handler(
+            Arc::new(mock_search_service),
+            authenticator_with_field_masks(),
+        )
+        .recover(recover_fn);
+
+        let response = warp::test::request()
+            .path("/api/v1/my-index/search?query=*")
+            .reply(&rest_search_api_handler)
+            .await;
+        assert_eq!(response.status(), 401);
+
+        let response = warp::test::request()
+            .path("/api/v1/my-index/search?query=*")
+            .header("Authorization", "Bearer admin-key")
+            .reply(&rest_search_api_handler)
+            .await;
+        assert_eq!(response.status(), 200);
+        let response_json: serde_json::Value = serde_json::from_slice(response.body())?;
+        assert_eq!(
+            response_json["hits"],
+            json!([{"title": ["login"], "user": {"email": ["alice@example.com"]}}])
+        );
+
+        let response = warp::test::request()
+            .path("/api/v1/my-index/search?query=*")
+            .header("Authorization", "Bearer analyst-key")
+            .reply(&rest_search_api_handler)
+            .await;
+        assert_eq!(response.status(), 200);
+        let response_json: serde_json::Value = serde_json::from_slice(response.body())?;
+        assert_eq!(
+            response_json["hits"],
+            json!([{"title": ["login"], "user": {}}])
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rest_search_stream_api_field_masks() {
+        let mock_search_service = MockSearchService::new();
+        let rest_search_stream_api_handler = super::search_stream_handler(
+            Arc::new(mock_search_service),
+            authenticator_with_field_masks(),
+        )
+        .recover(recover_fn);
+        let response = warp::test::request()
+            .path("/api/v1/my-index/search/stream?query=*&fastField=user.email")
+            .header("Authorization", "Bearer analyst-key")
+            .reply(&rest_search_stream_api_handler)
+            .await;
+        assert_eq!(response.status(), 403);
+    }
+
+    #[tokio::test]
+    async fn test_rest_search_stream_api_rejects_terms_lookup() {
+        let rest_search_stream_api_handler = super::search_stream_handler(
+            Arc::new(MockSearchService::new()),
+            Authenticator::default(),
+        )
+        .recover(recover_fn);
+        let response = warp::test::request()
+            .path(
+                "/api/v1/my-index/search/stream?query=*&fastField=external_id&\
+                 termsLookup[indexId]=alerts&termsLookup[query]=severity:high&\
+                 termsLookup[lookupField]=ip&termsLookup[targetField]=client_ip",
+            )
+            .reply(&rest_search_stream_api_handler)
+            .await;
+        assert_eq!(response.status(), 400);
+        assert!(String::from_utf8_lossy(response.body()).contains("Terms lookups"));
+    }
+
     #[tokio::test]
     async fn test_rest_search_stream_api() -> anyhow::Result<()> {
         let mut mock_search_service = MockSearchService::new();
@@ -717,7 +1325,8 @@ mod tests {
                 ])))
             });
         let rest_search_stream_api_handler =
-            super::search_stream_handler(Arc::new(mock_search_service)).recover(recover_fn);
+            super::search_stream_handler(Arc::new(mock_search_service), Authenticator::default())
+                .recover(recover_fn);
         let response = warp::test::request()
             .path(
                 "/api/v1/my-index/search/stream?query=obama&fastField=external_id&outputFormat=csv",
@@ -750,6 +1359,7 @@ mod tests {
                 fast_field: "external_id".to_string(),
                 output_format: OutputFormat::Csv,
                 partition_by_field: None,
+                terms_lookup: None,
             }
         );
     }
@@ -775,6 +1385,7 @@ mod tests {
                 fast_field: "external_id".to_string(),
                 output_format: OutputFormat::ClickHouseRowBinary,
                 partition_by_field: None,
+                terms_lookup: None,
             }
         );
     }
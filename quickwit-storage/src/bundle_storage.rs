@@ -245,6 +245,19 @@ impl Storage for BundleStorage {
         Ok(file_range.end - file_range.start as u64)
     }
 
+    async fn list_files(&self, prefix: &Path) -> StorageResult<Vec<PathBuf>> {
+        let prefix_str = prefix.to_string_lossy();
+        let mut files: Vec<PathBuf> = self
+            .metadata
+            .files
+            .keys()
+            .filter(|path| path.to_string_lossy().starts_with(prefix_str.as_ref()))
+            .cloned()
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
     fn uri(&self) -> String {
         self.storage.uri()
     }
@@ -22,6 +22,8 @@ use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
+use quickwit_common::memory_usage::MemoryComponent;
+
 use super::memory_sized_cache::MemorySizedCache;
 use crate::OwnedBytes;
 
@@ -37,19 +39,24 @@ pub struct SliceCache {
 }
 
 impl SliceCache {
-    /// Creates an slice cache with the given capacity.
-    pub fn with_capacity_in_bytes(capacity_in_bytes: usize) -> Self {
+    /// Creates an slice cache with the given capacity. Its memory usage is accounted for
+    /// `memory_component`.
+    pub fn with_capacity_in_bytes(
+        capacity_in_bytes: usize,
+        memory_component: MemoryComponent,
+    ) -> Self {
         SliceCache {
             inner: Mutex::new(MemorySizedCache::<SliceAddress>::with_capacity_in_bytes(
                 capacity_in_bytes,
+                memory_component,
             )),
         }
     }
 
     /// Creates a slice cache that nevers removes any entry.
-    pub fn with_infinite_capacity() -> Self {
+    pub fn with_infinite_capacity(memory_component: MemoryComponent) -> Self {
         SliceCache {
-            inner: Mutex::new(MemorySizedCache::with_infinite_capacity()),
+            inner: Mutex::new(MemorySizedCache::with_infinite_capacity(memory_component)),
         }
     }
 
@@ -69,6 +76,23 @@ impl SliceCache {
         let slice_addr = SliceAddress { path, byte_range };
         self.inner.lock().unwrap().put(slice_addr, bytes);
     }
+
+    /// Returns the number of bytes held by the cache.
+    pub fn num_bytes(&self) -> usize {
+        self.inner.lock().unwrap().num_bytes()
+    }
+
+    /// Returns the cached slices, from the most recently used slice to the least recently used
+    /// one.
+    pub fn slices_by_recency(&self) -> Vec<(PathBuf, Range<usize>, OwnedBytes)> {
+        self.inner
+            .lock()
+            .unwrap()
+            .values_by_recency()
+            .into_iter()
+            .map(|(slice_addr, bytes)| (slice_addr.path, slice_addr.byte_range, bytes))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -78,7 +102,7 @@ mod tests {
 
     #[test]
     fn test_cache_edge_condition() {
-        let cache = SliceCache::with_capacity_in_bytes(5);
+        let cache = SliceCache::with_capacity_in_bytes(5, MemoryComponent::CachingDirectories);
         {
             let data = OwnedBytes::new(&b"abc"[..]);
             cache.put(PathBuf::from("3"), 0..3, data);
@@ -111,7 +135,7 @@ mod tests {
 
     #[test]
     fn test_cache_edge_unlimited_capacity() {
-        let cache = SliceCache::with_infinite_capacity();
+        let cache = SliceCache::with_infinite_capacity(MemoryComponent::CachingDirectories);
         {
             let data = OwnedBytes::new(&b"abc"[..]);
             cache.put(PathBuf::from("3"), 0..3, data);
@@ -127,7 +151,7 @@ mod tests {
 
     #[test]
     fn test_cache() {
-        let cache = SliceCache::with_capacity_in_bytes(10_000);
+        let cache = SliceCache::with_capacity_in_bytes(10_000, MemoryComponent::CachingDirectories);
         assert!(cache.get(Path::new("hello.seg"), 1..3).is_none());
         let data = OwnedBytes::new(&b"werwer"[..]);
         cache.put(PathBuf::from("hello.seg"), 1..3, data);
@@ -137,9 +161,29 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_cache_slices_by_recency() {
+        let cache = SliceCache::with_capacity_in_bytes(10_000, MemoryComponent::CachingDirectories);
+        cache.put(PathBuf::from("a.fast"), 0..3, OwnedBytes::new(&b"abc"[..]));
+        cache.put(PathBuf::from("b.fast"), 2..4, OwnedBytes::new(&b"de"[..]));
+        cache.get(Path::new("a.fast"), 0..3);
+        let slices: Vec<(PathBuf, Range<usize>, Vec<u8>)> = cache
+            .slices_by_recency()
+            .into_iter()
+            .map(|(path, byte_range, bytes)| (path, byte_range, bytes.as_slice().to_vec()))
+            .collect();
+        assert_eq!(
+            slices,
+            vec![
+                (PathBuf::from("a.fast"), 0..3, b"abc".to_vec()),
+                (PathBuf::from("b.fast"), 2..4, b"de".to_vec()),
+            ]
+        );
+    }
+
     #[test]
     fn test_cache_different_slice() {
-        let cache = SliceCache::with_capacity_in_bytes(10_000);
+        let cache = SliceCache::with_capacity_in_bytes(10_000, MemoryComponent::CachingDirectories);
         assert!(cache.get(Path::new("hello.seg"), 1..3).is_none());
         let data = OwnedBytes::new(&b"werwer"[..]);
         // We could actually have a cache hit here, but this is not useful for Quickwit.
@@ -22,6 +22,7 @@ use std::hash::Hash;
 use std::sync::Mutex;
 
 use lru::{KeyRef, LruCache};
+use quickwit_common::memory_usage::{MemoryComponent, MemoryUsageGuard};
 use tracing::{error, warn};
 
 use crate::OwnedBytes;
@@ -44,11 +45,12 @@ struct NeedMutMemorySizedCache<K: Hash + Eq> {
     lru_cache: LruCache<K, OwnedBytes>,
     num_bytes: usize,
     capacity: Capacity,
+    memory_usage_guard: MemoryUsageGuard,
 }
 
 impl<K: Hash + Eq> NeedMutMemorySizedCache<K> {
     /// Creates a new NeedMutSliceCache with the given capacity.
-    fn with_capacity(capacity: Capacity) -> Self {
+    fn with_capacity(capacity: Capacity, memory_component: MemoryComponent) -> Self {
         NeedMutMemorySizedCache {
             // The limit will be decided by the amount of memory in the cache,
             // not the number of items in the cache.
@@ -56,6 +58,7 @@ impl<K: Hash + Eq> NeedMutMemorySizedCache<K> {
             lru_cache: LruCache::unbounded(),
             num_bytes: 0,
             capacity,
+            memory_usage_guard: MemoryUsageGuard::new(memory_component, 0),
         }
     }
 
@@ -67,6 +70,26 @@ impl<K: Hash + Eq> NeedMutMemorySizedCache<K> {
         self.lru_cache.get(cache_key).cloned()
     }
 
+    fn num_bytes(&self) -> usize {
+        self.num_bytes
+    }
+
+    fn entries_by_recency(&self) -> Vec<(K, usize)>
+    where K: Clone {
+        self.lru_cache
+            .iter()
+            .map(|(key, bytes)| (key.clone(), bytes.len()))
+            .collect()
+    }
+
+    fn values_by_recency(&self) -> Vec<(K, OwnedBytes)>
+    where K: Clone {
+        self.lru_cache
+            .iter()
+            .map(|(key, bytes)| (key.clone(), bytes.clone()))
+            .collect()
+    }
+
     /// Attempt to put the given amount of data in the cache.
     /// This may fail silently if the owned_bytes slice is larger than the cache
     /// capacity.
@@ -82,10 +105,12 @@ impl<K: Hash + Eq> NeedMutMemorySizedCache<K> {
         }
         if let Some(previous_data) = self.lru_cache.pop(&key) {
             self.num_bytes -= previous_data.len();
+            self.memory_usage_guard.sub(previous_data.len());
         }
         while self.capacity.exceeds_capacity(self.num_bytes + bytes.len()) {
             if let Some((_, bytes)) = self.lru_cache.pop_lru() {
                 self.num_bytes -= bytes.len();
+                self.memory_usage_guard.sub(bytes.len());
             } else {
                 error!(
                     "Logical error. Even after removing all of the items in the cache the \
@@ -96,6 +121,7 @@ impl<K: Hash + Eq> NeedMutMemorySizedCache<K> {
             }
         }
         self.num_bytes += bytes.len();
+        self.memory_usage_guard.add(bytes.len());
         self.lru_cache.put(key, bytes);
     }
 }
@@ -106,19 +132,27 @@ pub struct MemorySizedCache<K: Hash + Eq> {
 }
 
 impl<K: Hash + Eq> MemorySizedCache<K> {
-    /// Creates an slice cache with the given capacity.
-    pub fn with_capacity_in_bytes(capacity_in_bytes: usize) -> Self {
+    /// Creates an slice cache with the given capacity. Its memory usage is accounted for
+    /// `memory_component`.
+    pub fn with_capacity_in_bytes(
+        capacity_in_bytes: usize,
+        memory_component: MemoryComponent,
+    ) -> Self {
         MemorySizedCache {
-            inner: Mutex::new(NeedMutMemorySizedCache::with_capacity(Capacity::InBytes(
-                capacity_in_bytes,
-            ))),
+            inner: Mutex::new(NeedMutMemorySizedCache::with_capacity(
+                Capacity::InBytes(capacity_in_bytes),
+                memory_component,
+            )),
         }
     }
 
     /// Creates a slice cache that nevers removes any entry.
-    pub fn with_infinite_capacity() -> Self {
+    pub fn with_infinite_capacity(memory_component: MemoryComponent) -> Self {
         MemorySizedCache {
-            inner: Mutex::new(NeedMutMemorySizedCache::with_capacity(Capacity::Unlimited)),
+            inner: Mutex::new(NeedMutMemorySizedCache::with_capacity(
+                Capacity::Unlimited,
+                memory_component,
+            )),
         }
     }
 
@@ -137,6 +171,25 @@ impl<K: Hash + Eq> MemorySizedCache<K> {
     pub fn put(&self, val: K, bytes: OwnedBytes) {
         self.inner.lock().unwrap().put(val, bytes);
     }
+
+    /// Returns the number of bytes held by the cache.
+    pub fn num_bytes(&self) -> usize {
+        self.inner.lock().unwrap().num_bytes()
+    }
+
+    /// Returns the keys of the cached entries along with the size of their value, from the most
+    /// recently used entry to the least recently used one.
+    pub fn entries_by_recency(&self) -> Vec<(K, usize)>
+    where K: Clone {
+        self.inner.lock().unwrap().entries_by_recency()
+    }
+
+    /// Returns the cached entries along with their value, from the most recently used entry to
+    /// the least recently used one.
+    pub fn values_by_recency(&self) -> Vec<(K, OwnedBytes)>
+    where K: Clone {
+        self.inner.lock().unwrap().values_by_recency()
+    }
 }
 
 #[cfg(test)]
@@ -146,7 +199,10 @@ mod tests {
 
     #[test]
     fn test_cache_edge_condition() {
-        let cache = MemorySizedCache::<String>::with_capacity_in_bytes(5);
+        let cache = MemorySizedCache::<String>::with_capacity_in_bytes(
+            5,
+            MemoryComponent::SplitFooterCache,
+        );
         {
             let data = OwnedBytes::new(&b"abc"[..]);
             cache.put("3".to_string(), data);
@@ -179,7 +235,7 @@ mod tests {
 
     #[test]
     fn test_cache_edge_unlimited_capacity() {
-        let cache = MemorySizedCache::with_infinite_capacity();
+        let cache = MemorySizedCache::with_infinite_capacity(MemoryComponent::SplitFooterCache);
         {
             let data = OwnedBytes::new(&b"abc"[..]);
             cache.put("3".to_string(), data);
@@ -195,10 +251,39 @@ mod tests {
 
     #[test]
     fn test_cache() {
-        let cache = MemorySizedCache::with_capacity_in_bytes(10_000);
+        let cache =
+            MemorySizedCache::with_capacity_in_bytes(10_000, MemoryComponent::SplitFooterCache);
         assert!(cache.get(&"hello.seg").is_none());
         let data = OwnedBytes::new(&b"werwer"[..]);
         cache.put("hello.seg", data);
         assert_eq!(cache.get(&"hello.seg").unwrap(), &b"werwer"[..]);
     }
+
+    #[test]
+    fn test_cache_memory_usage() {
+        let cache = MemorySizedCache::with_capacity_in_bytes(5, MemoryComponent::SplitFooterCache);
+        let memory_usage = |cache: &MemorySizedCache<&str>| {
+            cache.inner.lock().unwrap().memory_usage_guard.num_bytes()
+        };
+        cache.put("3", OwnedBytes::new(&b"abc"[..]));
+        cache.put("2", OwnedBytes::new(&b"de"[..]));
+        assert_eq!(memory_usage(&cache), 5);
+        cache.put("3", OwnedBytes::new(&b"a"[..]));
+        assert_eq!(memory_usage(&cache), 3);
+        cache.put("4", OwnedBytes::new(&b"fghi"[..]));
+        assert_eq!(memory_usage(&cache), 5);
+        assert_eq!(cache.num_bytes(), 5);
+    }
+
+    #[test]
+    fn test_cache_entries_by_recency() {
+        let cache =
+            MemorySizedCache::with_capacity_in_bytes(10_000, MemoryComponent::SplitFooterCache);
+        assert!(cache.entries_by_recency().is_empty());
+        cache.put("3", OwnedBytes::new(&b"abc"[..]));
+        cache.put("2", OwnedBytes::new(&b"de"[..]));
+        assert_eq!(cache.entries_by_recency(), vec![("2", 2), ("3", 3)]);
+        cache.get(&"3");
+        assert_eq!(cache.entries_by_recency(), vec![("3", 3), ("2", 2)]);
+    }
 }
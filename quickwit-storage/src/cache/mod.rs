@@ -44,13 +44,37 @@ use crate::{OwnedBytes, Storage};
 /// have universally unique names. It happens to be true today, but this might be very error prone
 /// in the future.
 pub fn wrap_storage_with_long_term_cache(storage: Arc<dyn Storage>) -> Arc<dyn Storage> {
-    static SINGLETON: OnceCell<Arc<dyn Cache>> = OnceCell::new();
-    let cache = SINGLETON
-        .get_or_init(|| Arc::new(QuickwitCache::default()))
-        .clone();
+    let cache: Arc<dyn Cache> = long_term_cache().clone();
     Arc::new(StorageWithCache { storage, cache })
 }
 
+fn long_term_cache() -> &'static Arc<QuickwitCache> {
+    static SINGLETON: OnceCell<Arc<QuickwitCache>> = OnceCell::new();
+    SINGLETON.get_or_init(|| Arc::new(QuickwitCache::default()))
+}
+
+/// Returns the slices held by the fast field cache of the long-term cache, from the most recently
+/// used slice to the least recently used one.
+pub fn long_term_cache_fast_field_slices() -> Vec<(PathBuf, Range<usize>, OwnedBytes)> {
+    long_term_cache()
+        .fast_field_cache()
+        .map(SliceCache::slices_by_recency)
+        .unwrap_or_default()
+}
+
+/// Puts a slice into the fast field cache of the long-term cache. Like the other entries of this
+/// cache, the slice is identified by the file name only, which is assumed to be universally
+/// unique.
+pub fn put_long_term_cache_fast_field_slice(
+    path: PathBuf,
+    byte_range: Range<usize>,
+    bytes: OwnedBytes,
+) {
+    if let Some(fast_field_cache) = long_term_cache().fast_field_cache() {
+        fast_field_cache.put(path, byte_range, bytes);
+    }
+}
+
 /// The `Cache` trait is the abstraction used to describe the caching logic
 /// used in front of a storage. See `StorageWithCache`.
 #[cfg_attr(any(test, feature = "testsuite"), mockall::automock)]
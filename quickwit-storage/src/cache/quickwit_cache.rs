@@ -22,6 +22,7 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use quickwit_common::memory_usage::MemoryComponent;
 use quickwit_config::get_searcher_config_instance;
 
 use crate::{Cache, OwnedBytes, SliceCache};
@@ -30,11 +31,15 @@ const FULL_SLICE: Range<usize> = 0..usize::MAX;
 
 pub(crate) struct QuickwitCache {
     router: Vec<(&'static str, Arc<dyn Cache>)>,
+    fast_field_cache_opt: Option<Arc<SliceCache>>,
 }
 
 impl From<Vec<(&'static str, Arc<dyn Cache>)>> for QuickwitCache {
     fn from(router: Vec<(&'static str, Arc<dyn Cache>)>) -> Self {
-        QuickwitCache { router }
+        QuickwitCache {
+            router,
+            fast_field_cache_opt: None,
+        }
     }
 }
 
@@ -43,10 +48,17 @@ impl Default for QuickwitCache {
         let mut quickwit_cache = QuickwitCache::empty();
         let config = get_searcher_config_instance();
         let fast_cache_cap = config.fast_field_cache_capacity.get_bytes();
+        let fast_field_cache = Arc::new(SliceCache::with_capacity_in_bytes(
+            fast_cache_cap as usize,
+            MemoryComponent::FastFieldCache,
+        ));
         quickwit_cache.add_route(
             ".fast",
-            Arc::new(SimpleCache::with_capacity_in_bytes(fast_cache_cap as usize)),
+            Arc::new(SimpleCache {
+                slice_cache: fast_field_cache.clone(),
+            }),
         );
+        quickwit_cache.fast_field_cache_opt = Some(fast_field_cache);
         quickwit_cache
     }
 }
@@ -56,6 +68,11 @@ impl QuickwitCache {
         QuickwitCache::from(Vec::new())
     }
 
+    /// Returns the slice cache of the fast fields, if the cache was built with the default routes.
+    pub fn fast_field_cache(&self) -> Option<&SliceCache> {
+        self.fast_field_cache_opt.as_deref()
+    }
+
     pub fn add_route(&mut self, path_suffix: &'static str, route_cache: Arc<dyn Cache>) {
         self.router.push((path_suffix, route_cache));
     }
@@ -106,15 +123,7 @@ impl Cache for QuickwitCache {
 /// HACK! We use `0..usize::MAX` to signify the "entire file".
 /// TODO fixme
 struct SimpleCache {
-    slice_cache: SliceCache,
-}
-
-impl SimpleCache {
-    fn with_capacity_in_bytes(capacity_in_bytes: usize) -> Self {
-        SimpleCache {
-            slice_cache: SliceCache::with_capacity_in_bytes(capacity_in_bytes),
-        }
-    }
+    slice_cache: Arc<SliceCache>,
 }
 
 #[async_trait]
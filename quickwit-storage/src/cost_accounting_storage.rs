@@ -0,0 +1,303 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::{OwnedBytes, PutPayload, Storage, StorageResult};
+
+/// Activity to which the requests sent to a storage are attributed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageActivity {
+    /// Upload of the splits produced by the indexing pipelines.
+    Indexing,
+    /// Download, upload and deletion of the splits merged by the merge pipelines.
+    Merge,
+    /// Reads of the splits being searched.
+    Search,
+}
+
+impl StorageActivity {
+    /// Returns the name of the activity.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StorageActivity::Indexing => "indexing",
+            StorageActivity::Merge => "merge",
+            StorageActivity::Search => "search",
+        }
+    }
+}
+
+impl fmt::Display for StorageActivity {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.as_str())
+    }
+}
+
+/// Requests and bytes sent to a storage, grouped by billing class: object storages charge GET
+/// and HEAD requests at one rate, and PUT and LIST requests at a higher rate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageCosts {
+    /// Number of GET and HEAD requests.
+    pub num_get_requests: u64,
+    /// Number of PUT and LIST requests.
+    pub num_put_requests: u64,
+    /// Number of DELETE requests.
+    pub num_delete_requests: u64,
+    /// Number of bytes downloaded.
+    pub num_bytes_read: u64,
+    /// Number of bytes uploaded.
+    pub num_bytes_written: u64,
+}
+
+#[derive(Default)]
+struct StorageCostCounters {
+    num_get_requests: AtomicU64,
+    num_put_requests: AtomicU64,
+    num_delete_requests: AtomicU64,
+    num_bytes_read: AtomicU64,
+    num_bytes_written: AtomicU64,
+}
+
+impl StorageCostCounters {
+    fn snapshot(&self) -> StorageCosts {
+        StorageCosts {
+            num_get_requests: self.num_get_requests.load(Ordering::Relaxed),
+            num_put_requests: self.num_put_requests.load(Ordering::Relaxed),
+            num_delete_requests: self.num_delete_requests.load(Ordering::Relaxed),
+            num_bytes_read: self.num_bytes_read.load(Ordering::Relaxed),
+            num_bytes_written: self.num_bytes_written.load(Ordering::Relaxed),
+        }
+    }
+}
+
+type StorageCostRegistry = RwLock<BTreeMap<(String, StorageActivity), Arc<StorageCostCounters>>>;
+
+static STORAGE_COST_REGISTRY: Lazy<StorageCostRegistry> = Lazy::new(Default::default);
+
+fn storage_cost_counters(index_id: &str, activity: StorageActivity) -> Arc<StorageCostCounters> {
+    let key = (index_id.to_string(), activity);
+    if let Some(counters) = STORAGE_COST_REGISTRY
+        .read()
+        .expect("Storage cost registry lock should not be poisoned.")
+        .get(&key)
+    {
+        return counters.clone();
+    }
+    STORAGE_COST_REGISTRY
+        .write()
+        .expect("Storage cost registry lock should not be poisoned.")
+        .entry(key)
+        .or_default()
+        .clone()
+}
+
+/// Returns the storage costs accumulated by this node since it started, per index ID and per
+/// activity.
+pub fn storage_costs() -> BTreeMap<String, BTreeMap<StorageActivity, StorageCosts>> {
+    let mut storage_costs: BTreeMap<String, BTreeMap<StorageActivity, StorageCosts>> =
+        BTreeMap::new();
+    for ((index_id, activity), counters) in STORAGE_COST_REGISTRY
+        .read()
+        .expect("Storage cost registry lock should not be poisoned.")
+        .iter()
+    {
+        storage_costs
+            .entry(index_id.clone())
+            .or_default()
+            .insert(*activity, counters.snapshot());
+    }
+    storage_costs
+}
+
+/// Wraps a storage so that its requests are attributed to the index `index_id` and to
+/// `activity` in the [`storage_costs`] report.
+///
+/// The wrapper should sit below the caches, so that only the requests actually reaching the
+/// storage are counted.
+pub fn account_storage_costs(
+    storage: Arc<dyn Storage>,
+    index_id: &str,
+    activity: StorageActivity,
+) -> Arc<dyn Storage> {
+    Arc::new(CostAccountingStorage {
+        storage,
+        counters: storage_cost_counters(index_id, activity),
+    })
+}
+
+/// Storage counting the requests and bytes sent to the underlying storage. Requests are counted
+/// whether they succeed or not, as object storages charge failed requests as well.
+struct CostAccountingStorage {
+    storage: Arc<dyn Storage>,
+    counters: Arc<StorageCostCounters>,
+}
+
+impl CostAccountingStorage {
+    fn record_get(&self, num_bytes_read: u64) {
+        self.counters
+            .num_get_requests
+            .fetch_add(1, Ordering::Relaxed);
+        self.counters
+            .num_bytes_read
+            .fetch_add(num_bytes_read, Ordering::Relaxed);
+    }
+}
+
+#[async_trait]
+impl Storage for CostAccountingStorage {
+    async fn check(&self) -> anyhow::Result<()> {
+        self.storage.check().await
+    }
+
+    async fn put(&self, path: &Path, payload: Box<dyn PutPayload>) -> StorageResult<()> {
+        let payload_len = payload.len();
+        self.counters
+            .num_put_requests
+            .fetch_add(1, Ordering::Relaxed);
+        self.storage.put(path, payload).await?;
+        self.counters
+            .num_bytes_written
+            .fetch_add(payload_len, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn copy_to_file(&self, path: &Path, output_path: &Path) -> StorageResult<()> {
+        let copy_result = self.storage.copy_to_file(path, output_path).await;
+        let num_bytes_read = if copy_result.is_ok() {
+            tokio::fs::metadata(output_path)
+                .await
+                .map(|metadata| metadata.len())
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        self.record_get(num_bytes_read);
+        copy_result
+    }
+
+    async fn get_slice(&self, path: &Path, range: Range<usize>) -> StorageResult<OwnedBytes> {
+        let get_result = self.storage.get_slice(path, range).await;
+        self.record_get(get_result.as_ref().map_or(0, |bytes| bytes.len() as u64));
+        get_result
+    }
+
+    async fn get_all(&self, path: &Path) -> StorageResult<OwnedBytes> {
+        let get_result = self.storage.get_all(path).await;
+        self.record_get(get_result.as_ref().map_or(0, |bytes| bytes.len() as u64));
+        get_result
+    }
+
+    async fn delete(&self, path: &Path) -> StorageResult<()> {
+        self.counters
+            .num_delete_requests
+            .fetch_add(1, Ordering::Relaxed);
+        self.storage.delete(path).await
+    }
+
+    async fn exists(&self, path: &Path) -> StorageResult<bool> {
+        self.record_get(0);
+        self.storage.exists(path).await
+    }
+
+    async fn file_num_bytes(&self, path: &Path) -> StorageResult<u64> {
+        self.record_get(0);
+        self.storage.file_num_bytes(path).await
+    }
+
+    async fn list_files(&self, prefix: &Path) -> StorageResult<Vec<PathBuf>> {
+        self.counters
+            .num_put_requests
+            .fetch_add(1, Ordering::Relaxed);
+        self.storage.list_files(prefix).await
+    }
+
+    fn uri(&self) -> String {
+        self.storage.uri()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RamStorage;
+
+    #[tokio::test]
+    async fn test_cost_accounting_storage() -> anyhow::Result<()> {
+        let index_id = "test-cost-accounting-storage";
+        let ram_storage: Arc<dyn Storage> = Arc::new(RamStorage::default());
+        let merge_storage =
+            account_storage_costs(ram_storage.clone(), index_id, StorageActivity::Merge);
+        let search_storage = account_storage_costs(ram_storage, index_id, StorageActivity::Search);
+
+        merge_storage
+            .put(Path::new("split"), Box::new(b"abcdef".to_vec()))
+            .await?;
+        merge_storage.delete(Path::new("other-split")).await?;
+        search_storage.get_slice(Path::new("split"), 1..4).await?;
+        search_storage.get_all(Path::new("split")).await?;
+        search_storage
+            .get_all(Path::new("missing-split"))
+            .await
+            .unwrap_err();
+
+        let index_storage_costs = &storage_costs()[index_id];
+        assert_eq!(index_storage_costs.len(), 2);
+        assert_eq!(
+            index_storage_costs[&StorageActivity::Merge],
+            StorageCosts {
+                num_put_requests: 1,
+                num_delete_requests: 1,
+                num_bytes_written: 6,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            index_storage_costs[&StorageActivity::Search],
+            StorageCosts {
+                num_get_requests: 3,
+                num_bytes_read: 9,
+                ..Default::default()
+            }
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cost_accounting_storage_test_suite() -> anyhow::Result<()> {
+        let ram_storage: Arc<dyn Storage> = Arc::new(RamStorage::default());
+        let mut storage = CostAccountingStorage {
+            storage: ram_storage,
+            counters: Arc::default(),
+        };
+        crate::test_suite::storage_test_suite(&mut storage).await?;
+        assert!(storage.counters.snapshot().num_put_requests > 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,366 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+use crate::{OwnedBytes, PutPayload, Storage, StorageErrorKind, StorageResult};
+
+/// Environment variable holding the JSON fault profile applied to every storage resolved by
+/// the default storage resolver. This is meant for chaos testing only.
+pub const QW_STORAGE_FAULT_PROFILE_ENV_KEY: &str = "QW_STORAGE_FAULT_PROFILE";
+
+/// Describes the faults injected by a [`FaultInjectionStorage`].
+///
+/// The faults are drawn from a random generator seeded with `seed`, so that a given sequence
+/// of calls always experiences the same faults.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct FaultProfile {
+    /// Seed of the random generator drawing the faults.
+    pub seed: u64,
+    /// Minimum latency added to each call, in milliseconds.
+    pub min_latency_millis: u64,
+    /// Maximum latency added to each call, in milliseconds.
+    pub max_latency_millis: u64,
+    /// Probability for a call to fail with a throttling error.
+    pub throttling_probability: f64,
+    /// Probability for a read to return only a prefix of the requested bytes.
+    pub partial_read_probability: f64,
+}
+
+impl FaultProfile {
+    /// Checks that the latencies and the probabilities of the profile are consistent.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.min_latency_millis > self.max_latency_millis {
+            anyhow::bail!(
+                "Min latency ({}ms) must be lower than or equal to max latency ({}ms).",
+                self.min_latency_millis,
+                self.max_latency_millis
+            );
+        }
+        for (name, probability) in [
+            ("throttling_probability", self.throttling_probability),
+            ("partial_read_probability", self.partial_read_probability),
+        ] {
+            if !(0.0..=1.0).contains(&probability) {
+                anyhow::bail!("`{}` must be between 0 and 1, got `{}`.", name, probability);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads the fault profile from the `QW_STORAGE_FAULT_PROFILE` environment variable.
+pub(crate) fn fault_profile_from_env() -> Option<FaultProfile> {
+    let fault_profile_json = std::env::var(QW_STORAGE_FAULT_PROFILE_ENV_KEY).ok()?;
+    let fault_profile = serde_json::from_str::<FaultProfile>(&fault_profile_json)
+        .map_err(anyhow::Error::from)
+        .and_then(|fault_profile| fault_profile.validate().map(|_| fault_profile));
+    match fault_profile {
+        Ok(fault_profile) => {
+            warn!(
+                fault_profile = ?fault_profile,
+                "Storage chaos mode enabled: injecting storage faults."
+            );
+            Some(fault_profile)
+        }
+        Err(error) => {
+            error!(
+                error = ?error,
+                "Failed to parse `{}` from environment.",
+                QW_STORAGE_FAULT_PROFILE_ENV_KEY
+            );
+            None
+        }
+    }
+}
+
+/// The faults drawn for a single call.
+struct Faults {
+    latency: Duration,
+    is_throttled: bool,
+    /// Fraction of the requested bytes actually returned, for reads.
+    partial_read_ratio_opt: Option<f64>,
+}
+
+/// This storage acts as a proxy to another storage that injects latency, throttling errors and
+/// partial reads according to a [`FaultProfile`].
+///
+/// It is meant to validate the retry and failed split handling logic in tests.
+pub struct FaultInjectionStorage {
+    storage: Arc<dyn Storage>,
+    fault_profile: FaultProfile,
+    rng: Mutex<StdRng>,
+    num_throttled_calls: AtomicU64,
+    num_partial_reads: AtomicU64,
+}
+
+impl FaultInjectionStorage {
+    /// Wraps `storage` into a storage injecting the faults described by `fault_profile`.
+    ///
+    /// The random generator is seeded with both the seed of the profile and the URI of the
+    /// storage, so that two storages sharing a profile do not experience the same faults.
+    pub fn new(storage: Arc<dyn Storage>, fault_profile: FaultProfile) -> Self {
+        let mut hasher = DefaultHasher::new();
+        fault_profile.seed.hash(&mut hasher);
+        storage.uri().hash(&mut hasher);
+        let rng = StdRng::seed_from_u64(hasher.finish());
+        FaultInjectionStorage {
+            storage,
+            fault_profile,
+            rng: Mutex::new(rng),
+            num_throttled_calls: AtomicU64::new(0),
+            num_partial_reads: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the number of calls that failed with an injected throttling error.
+    pub fn num_throttled_calls(&self) -> u64 {
+        self.num_throttled_calls.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of reads that returned only a prefix of the requested bytes.
+    pub fn num_partial_reads(&self) -> u64 {
+        self.num_partial_reads.load(Ordering::Relaxed)
+    }
+
+    fn draw_faults(&self, is_read: bool) -> Faults {
+        let mut rng = self.rng.lock().unwrap();
+        let latency_millis = rng.gen_range(
+            self.fault_profile.min_latency_millis..=self.fault_profile.max_latency_millis,
+        );
+        let is_throttled = rng.gen_bool(self.fault_profile.throttling_probability);
+        let partial_read_ratio_opt =
+            if is_read && rng.gen_bool(self.fault_profile.partial_read_probability) {
+                Some(rng.gen_range(0.0..1.0))
+            } else {
+                None
+            };
+        Faults {
+            latency: Duration::from_millis(latency_millis),
+            is_throttled,
+            partial_read_ratio_opt,
+        }
+    }
+
+    /// Waits for the injected latency, and fails if the call is throttled.
+    async fn inject_faults(&self, is_read: bool) -> StorageResult<Option<f64>> {
+        let faults = self.draw_faults(is_read);
+        if faults.latency > Duration::ZERO {
+            tokio::time::sleep(faults.latency).await;
+        }
+        if faults.is_throttled {
+            self.num_throttled_calls.fetch_add(1, Ordering::Relaxed);
+            return Err(StorageErrorKind::Service.with_error(anyhow::anyhow!(
+                "Injected fault: request throttled (SlowDown)."
+            )));
+        }
+        Ok(faults.partial_read_ratio_opt)
+    }
+
+    fn truncate(&self, bytes: OwnedBytes, partial_read_ratio_opt: Option<f64>) -> OwnedBytes {
+        if let Some(partial_read_ratio) = partial_read_ratio_opt {
+            self.num_partial_reads.fetch_add(1, Ordering::Relaxed);
+            let num_bytes = (bytes.len() as f64 * partial_read_ratio) as usize;
+            return bytes.slice(0..num_bytes);
+        }
+        bytes
+    }
+}
+
+#[async_trait]
+impl Storage for FaultInjectionStorage {
+    async fn check(&self) -> anyhow::Result<()> {
+        self.storage.check().await
+    }
+
+    async fn put(&self, path: &Path, payload: Box<dyn PutPayload>) -> StorageResult<()> {
+        self.inject_faults(false).await?;
+        self.storage.put(path, payload).await
+    }
+
+    async fn copy_to_file(&self, path: &Path, output_path: &Path) -> StorageResult<()> {
+        self.inject_faults(false).await?;
+        self.storage.copy_to_file(path, output_path).await
+    }
+
+    async fn get_slice(&self, path: &Path, range: Range<usize>) -> StorageResult<OwnedBytes> {
+        let partial_read_ratio_opt = self.inject_faults(true).await?;
+        let bytes = self.storage.get_slice(path, range).await?;
+        Ok(self.truncate(bytes, partial_read_ratio_opt))
+    }
+
+    async fn get_all(&self, path: &Path) -> StorageResult<OwnedBytes> {
+        let partial_read_ratio_opt = self.inject_faults(true).await?;
+        let bytes = self.storage.get_all(path).await?;
+        Ok(self.truncate(bytes, partial_read_ratio_opt))
+    }
+
+    async fn delete(&self, path: &Path) -> StorageResult<()> {
+        self.inject_faults(false).await?;
+        self.storage.delete(path).await
+    }
+
+    async fn exists(&self, path: &Path) -> StorageResult<bool> {
+        self.inject_faults(false).await?;
+        self.storage.exists(path).await
+    }
+
+    async fn file_num_bytes(&self, path: &Path) -> StorageResult<u64> {
+        self.inject_faults(false).await?;
+        self.storage.file_num_bytes(path).await
+    }
+
+    async fn list_files(&self, prefix: &Path) -> StorageResult<Vec<PathBuf>> {
+        self.inject_faults(false).await?;
+        self.storage.list_files(prefix).await
+    }
+
+    fn uri(&self) -> String {
+        self.storage.uri()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RamStorage;
+
+    fn ram_storage_with_file() -> Arc<dyn Storage> {
+        Arc::new(
+            RamStorage::builder()
+                .put("file", b"abcdefghijklmnopqrstuvwxyz")
+                .build(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_fault_injection_storage_without_faults() -> anyhow::Result<()> {
+        let mut storage =
+            FaultInjectionStorage::new(Arc::new(RamStorage::default()), FaultProfile::default());
+        crate::test_suite::storage_test_suite(&mut storage).await?;
+        assert_eq!(storage.num_throttled_calls(), 0);
+        assert_eq!(storage.num_partial_reads(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fault_injection_storage_throttling() {
+        let fault_profile = FaultProfile {
+            throttling_probability: 1.0,
+            ..Default::default()
+        };
+        let storage = FaultInjectionStorage::new(ram_storage_with_file(), fault_profile);
+        let error = storage.get_all(Path::new("file")).await.unwrap_err();
+        assert_eq!(error.kind(), StorageErrorKind::Service);
+        let error = storage
+            .put(Path::new("other-file"), Box::new(b"abc".to_vec()))
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), StorageErrorKind::Service);
+        assert_eq!(storage.num_throttled_calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fault_injection_storage_partial_reads() {
+        let fault_profile = FaultProfile {
+            partial_read_probability: 1.0,
+            ..Default::default()
+        };
+        let storage = FaultInjectionStorage::new(ram_storage_with_file(), fault_profile);
+        let bytes = storage.get_all(Path::new("file")).await.unwrap();
+        assert!(bytes.len() < 26);
+        assert!(b"abcdefghijklmnopqrstuvwxyz".starts_with(bytes.as_slice()));
+        let bytes = storage.get_slice(Path::new("file"), 3..6).await.unwrap();
+        assert!(b"def".starts_with(bytes.as_slice()));
+        assert_eq!(storage.file_num_bytes(Path::new("file")).await.unwrap(), 26);
+        assert_eq!(storage.num_partial_reads(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fault_injection_storage_is_deterministic() {
+        let fault_profile = FaultProfile {
+            seed: 42,
+            throttling_probability: 0.5,
+            partial_read_probability: 0.5,
+            ..Default::default()
+        };
+        let mut outcomes = Vec::new();
+        for _ in 0..2 {
+            let storage =
+                FaultInjectionStorage::new(ram_storage_with_file(), fault_profile.clone());
+            let mut storage_outcomes = Vec::new();
+            for _ in 0..32 {
+                let outcome = storage
+                    .get_all(Path::new("file"))
+                    .await
+                    .map(|bytes| bytes.len())
+                    .map_err(|error| error.kind());
+                storage_outcomes.push(outcome);
+            }
+            outcomes.push(storage_outcomes);
+        }
+        assert_eq!(outcomes[0], outcomes[1]);
+        assert!(outcomes[0].iter().any(|outcome| outcome.is_err()));
+        assert!(outcomes[0].iter().any(|outcome| outcome.is_ok()));
+    }
+
+    #[test]
+    fn test_fault_profile_deserialize_and_validate() {
+        let fault_profile: FaultProfile = serde_json::from_str(
+            r#"{"seed": 7, "max_latency_millis": 20, "throttling_probability": 0.1}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            fault_profile,
+            FaultProfile {
+                seed: 7,
+                min_latency_millis: 0,
+                max_latency_millis: 20,
+                throttling_probability: 0.1,
+                partial_read_probability: 0.0,
+            }
+        );
+        assert!(fault_profile.validate().is_ok());
+        assert!(serde_json::from_str::<FaultProfile>(r#"{"unknown": 1}"#).is_err());
+        let invalid_latency_profile = FaultProfile {
+            min_latency_millis: 10,
+            max_latency_millis: 5,
+            ..Default::default()
+        };
+        assert!(invalid_latency_profile.validate().is_err());
+        let invalid_probability_profile = FaultProfile {
+            throttling_probability: 1.5,
+            ..Default::default()
+        };
+        assert!(invalid_probability_profile.validate().is_err());
+    }
+}
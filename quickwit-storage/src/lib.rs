@@ -35,7 +35,9 @@ pub use self::payload::PutPayload;
 pub use self::storage::Storage;
 
 mod bundle_storage;
+mod cost_accounting_storage;
 mod error;
+mod fault_injection_storage;
 mod local_file_storage;
 mod object_storage;
 mod payload;
@@ -54,6 +56,12 @@ pub use tantivy::directory::OwnedBytes;
 pub use self::bundle_storage::{BundleStorage, BundleStorageFileOffsets};
 #[cfg(any(test, feature = "testsuite"))]
 pub use self::cache::MockCache;
+pub use self::cost_accounting_storage::{
+    account_storage_costs, storage_costs, StorageActivity, StorageCosts,
+};
+pub use self::fault_injection_storage::{
+    FaultInjectionStorage, FaultProfile, QW_STORAGE_FAULT_PROFILE_ENV_KEY,
+};
 pub use self::local_file_storage::{LocalFileStorage, LocalFileStorageFactory};
 pub use self::object_storage::{
     MultiPartPolicy, S3CompatibleObjectStorage, S3CompatibleObjectStorageFactory,
@@ -70,7 +78,10 @@ pub use self::storage_resolver::{
 };
 #[cfg(feature = "testsuite")]
 pub use self::test_suite::storage_test_suite;
-pub use crate::cache::{wrap_storage_with_long_term_cache, Cache, MemorySizedCache, SliceCache};
+pub use crate::cache::{
+    long_term_cache_fast_field_slices, put_long_term_cache_fast_field_slice,
+    wrap_storage_with_long_term_cache, Cache, MemorySizedCache, SliceCache,
+};
 pub use crate::error::{StorageError, StorageErrorKind, StorageResolverError, StorageResult};
 
 /// Loads an entire local or remote file into memory.
@@ -110,7 +121,7 @@ mod tests {
 #[cfg(any(test, feature = "testsuite"))]
 pub(crate) mod test_suite {
 
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
 
     use anyhow::Context;
 
@@ -227,6 +238,33 @@ pub(crate) mod test_suite {
         Ok(())
     }
 
+    async fn test_list_files(storage: &mut dyn Storage) -> anyhow::Result<()> {
+        for test_path in ["list/dir/file-1", "list/dir/file-2", "list/other-file"] {
+            storage
+                .put(Path::new(test_path), Box::new(b"abc"[..].to_vec()))
+                .await?;
+        }
+        assert_eq!(
+            storage.list_files(Path::new("list/dir/")).await?,
+            vec![
+                PathBuf::from("list/dir/file-1"),
+                PathBuf::from("list/dir/file-2")
+            ]
+        );
+        assert_eq!(
+            storage.list_files(Path::new("list/other")).await?,
+            vec![PathBuf::from("list/other-file")]
+        );
+        assert!(storage
+            .list_files(Path::new("list/missing/"))
+            .await?
+            .is_empty());
+        for test_path in ["list/dir/file-1", "list/dir/file-2", "list/other-file"] {
+            storage.delete(Path::new(test_path)).await?;
+        }
+        Ok(())
+    }
+
     /// Generic test suite for a storage.
     pub async fn storage_test_suite(storage: &mut dyn Storage) -> anyhow::Result<()> {
         test_get_inexistent_file(storage)
@@ -254,6 +292,9 @@ pub(crate) mod test_suite {
         test_delete_missing_file(storage)
             .await
             .with_context(|| "delete_missing_file")?;
+        test_list_files(storage)
+            .await
+            .with_context(|| "list_files")?;
         Ok(())
     }
 }
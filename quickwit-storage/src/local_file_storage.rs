@@ -147,6 +147,30 @@ fn delete_all_dirs(root: PathBuf, path: &Path) -> BoxFuture<'_, std::io::Result<
     .boxed()
 }
 
+/// Recursively collects the files located under `{root}/{path}`. The returned paths are relative
+/// to `root`.
+fn list_files_rec(root: PathBuf, path: PathBuf) -> BoxFuture<'static, io::Result<Vec<PathBuf>>> {
+    async move {
+        let full_path = root.join(&path);
+        let mut read_dir = match fs::read_dir(&full_path).await {
+            Ok(read_dir) => read_dir,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        let mut files = Vec::new();
+        while let Some(entry) = read_dir.next_entry().await? {
+            let entry_path = path.join(entry.file_name());
+            if entry.file_type().await?.is_dir() {
+                files.extend(list_files_rec(root.clone(), entry_path).await?);
+            } else {
+                files.push(entry_path);
+            }
+        }
+        Ok(files)
+    }
+    .boxed()
+}
+
 fn missing_file_is_ok(io_result: io::Result<()>) -> io::Result<()> {
     match io_result {
         Ok(()) => Ok(()),
@@ -254,6 +278,24 @@ impl Storage for LocalFileStorage {
             }
         }
     }
+
+    async fn list_files(&self, prefix: &Path) -> StorageResult<Vec<PathBuf>> {
+        // The prefix may end in the middle of a file name, so we list its parent directory
+        // and filter out the files that do not match.
+        let prefix_str = prefix.to_string_lossy().to_string();
+        let dir_path = if prefix_str.is_empty() || prefix_str.ends_with('/') {
+            prefix.to_path_buf()
+        } else {
+            prefix.parent().map(Path::to_path_buf).unwrap_or_default()
+        };
+        let mut files: Vec<PathBuf> = list_files_rec(self.root.clone(), dir_path)
+            .await?
+            .into_iter()
+            .filter(|path| path.to_string_lossy().starts_with(&prefix_str))
+            .collect();
+        files.sort();
+        Ok(files)
+    }
 }
 
 /// A File storage resolver
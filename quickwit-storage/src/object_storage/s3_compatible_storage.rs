@@ -22,17 +22,21 @@ use std::io;
 use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Mutex;
 use std::time::Duration;
 
 use anyhow::Context;
 use async_trait::async_trait;
 use ec2_instance_metadata::{InstanceMetadata, InstanceMetadataClient};
 use futures::{stream, StreamExt};
-use once_cell::sync::OnceCell;
+use hyper::client::HttpConnector;
+use hyper_rustls::HttpsConnector;
+use once_cell::sync::{Lazy, OnceCell};
 use quickwit_common::{chunk_range, into_u64_range};
+use quickwit_config::{get_storage_config_instance, StorageHttpClientConfig};
 use regex::Regex;
 use rusoto_core::credential::{AutoRefreshingProvider, ChainProvider};
-use rusoto_core::{ByteStream, HttpClient, HttpConfig, Region, RusotoError};
+use rusoto_core::{ByteStream, Client, HttpClient, Region, RusotoError};
 use rusoto_s3::{
     AbortMultipartUploadRequest, CompleteMultipartUploadRequest, CompletedMultipartUpload,
     CompletedPart, CreateMultipartUploadError, CreateMultipartUploadRequest, DeleteObjectRequest,
@@ -51,9 +55,6 @@ use crate::{OwnedBytes, Storage, StorageError, StorageErrorKind, StorageResult};
 /// A credential timeout.
 const CREDENTIAL_TIMEOUT: u64 = 5;
 
-/// An timeout for idle sockets being kept-alive.
-const POOL_IDLE_TIMEOUT: u64 = 10;
-
 /// Returns the region to use for the S3 object storage.
 ///
 /// This function tests different methods in turn to get the region.
@@ -161,22 +162,80 @@ impl fmt::Debug for S3CompatibleObjectStorage {
     }
 }
 
-fn create_s3_client(region: Region) -> anyhow::Result<S3Client> {
+fn create_http_client(
+    http_client_config: &StorageHttpClientConfig,
+) -> anyhow::Result<HttpClient<HttpsConnector<HttpConnector>>> {
+    let keep_alive_interval_opt = if http_client_config.keep_alive_interval_secs > 0 {
+        Some(Duration::from_secs(
+            http_client_config.keep_alive_interval_secs,
+        ))
+    } else {
+        None
+    };
+    let mut http_connector = HttpConnector::new();
+    http_connector.enforce_http(false);
+    http_connector.set_nodelay(true);
+    http_connector.set_keepalive(keep_alive_interval_opt);
+
+    let mut tls_config = rustls::ClientConfig::new();
+    tls_config.root_store = rustls_native_certs::load_native_certs()
+        .map_err(|(_, error)| error)
+        .context("Failed to load the native root certificates.")?;
+    tls_config.alpn_protocols = if http_client_config.http2 {
+        vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    } else {
+        vec![b"http/1.1".to_vec()]
+    };
+    let https_connector = HttpsConnector::from((http_connector, tls_config));
+
+    let mut client_builder = hyper::Client::builder();
+    // We experience an issue similar to https://github.com/hyperium/hyper/issues/2312.
+    // It seems like the idle timeout below solved it.
+    client_builder
+        .pool_idle_timeout(Duration::from_secs(
+            http_client_config.pool_idle_timeout_secs,
+        ))
+        .pool_max_idle_per_host(http_client_config.pool_max_idle_per_host);
+    if http_client_config.http2 {
+        client_builder
+            .http2_keep_alive_interval(keep_alive_interval_opt)
+            .http2_keep_alive_while_idle(true);
+    }
+    Ok(HttpClient::from_builder(client_builder, https_connector))
+}
+
+/// Returns the client dispatching the requests of the S3 storages.
+///
+/// The client and its connection pool are shared by all the storages, so that the many range
+/// requests of a search reuse the connections opened by the previous ones. A new client is
+/// created only when the HTTP client config of the node changes.
+fn shared_client() -> anyhow::Result<Client> {
+    static SHARED_CLIENT: Lazy<Mutex<Option<(StorageHttpClientConfig, Client)>>> =
+        Lazy::new(|| Mutex::new(None));
+
+    let http_client_config = get_storage_config_instance().s3_config.http_client;
+    let mut shared_client_guard = SHARED_CLIENT
+        .lock()
+        .expect("Shared client lock should not be poisoned.");
+    if let Some((config, client)) = shared_client_guard.as_ref() {
+        if *config == http_client_config {
+            return Ok(client.clone());
+        }
+    }
     let mut chain_provider = ChainProvider::new();
     chain_provider.set_timeout(Duration::from_secs(CREDENTIAL_TIMEOUT));
     let credentials_provider = AutoRefreshingProvider::new(chain_provider)
         .with_context(|| "Failed to fetch credentials for the object storage.")?;
-    let mut http_config: HttpConfig = HttpConfig::default();
-    // We experience an issue similar to https://github.com/hyperium/hyper/issues/2312.
-    // It seems like the setting below solved it.
-    http_config.pool_idle_timeout(std::time::Duration::from_secs(POOL_IDLE_TIMEOUT));
-    let http_client = HttpClient::new_with_config(http_config)
+    let http_client = create_http_client(&http_client_config)
         .with_context(|| "failed to create request dispatcher")?;
-    Ok(S3Client::new_with(
-        http_client,
-        credentials_provider,
-        region,
-    ))
+    let client = Client::new_with(credentials_provider, http_client);
+    *shared_client_guard = Some((http_client_config, client.clone()));
+    Ok(client)
+}
+
+fn create_s3_client(region: Region) -> anyhow::Result<S3Client> {
+    let client = shared_client()?;
+    Ok(S3Client::new_with_client(client, region))
 }
 
 impl S3CompatibleObjectStorage {
@@ -686,6 +745,41 @@ impl Storage for S3CompatibleObjectStorage {
             Err(err) => Err(err.into()),
         }
     }
+
+    async fn list_files(&self, prefix: &Path) -> StorageResult<Vec<PathBuf>> {
+        let key_prefix = self.key(prefix);
+        let mut files = Vec::new();
+        let mut continuation_token: Option<String> = None;
+        loop {
+            let list_objects_req = ListObjectsV2Request {
+                bucket: self.bucket.clone(),
+                prefix: Some(key_prefix.clone()),
+                continuation_token: continuation_token.clone(),
+                ..Default::default()
+            };
+            let list_objects_output = retry(|| async {
+                self.s3_client
+                    .list_objects_v2(list_objects_req.clone())
+                    .await
+                    .map_err(RusotoErrorWrapper::from)
+            })
+            .await?;
+            for object in list_objects_output.contents.unwrap_or_default() {
+                if let Some(key) = object.key {
+                    if let Ok(relative_path) = Path::new(&key).strip_prefix(&self.prefix) {
+                        files.push(relative_path.to_path_buf());
+                    }
+                }
+            }
+            continuation_token = list_objects_output.next_continuation_token;
+            if continuation_token.is_none() {
+                break;
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
     fn uri(&self) -> String {
         format!("s3://{}/{}", self.bucket, self.prefix.to_string_lossy())
     }
@@ -126,6 +126,20 @@ impl Storage for RamStorage {
             Err(StorageErrorKind::DoesNotExist.with_error(err))
         }
     }
+
+    async fn list_files(&self, prefix: &Path) -> StorageResult<Vec<PathBuf>> {
+        let prefix_str = prefix.to_string_lossy();
+        let mut files: Vec<PathBuf> = self
+            .files
+            .read()
+            .await
+            .keys()
+            .filter(|path| path.to_string_lossy().starts_with(prefix_str.as_ref()))
+            .cloned()
+            .collect();
+        files.sort();
+        Ok(files)
+    }
 }
 
 /// Builder to create a prepopulated [`RamStorage`]. This is mostly useful for tests.
@@ -18,7 +18,7 @@
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
 use std::ops::Range;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use async_trait::async_trait;
 
@@ -73,6 +73,10 @@ pub trait Storage: Send + Sync + 'static {
     /// Returns a file size.
     async fn file_num_bytes(&self, path: &Path) -> StorageResult<u64>;
 
+    /// Lists the files whose path starts with `prefix`, in lexicographic order.
+    /// The returned paths are relative to the storage root.
+    async fn list_files(&self, prefix: &Path) -> StorageResult<Vec<PathBuf>>;
+
     /// Returns an URI identifying the storage
     fn uri(&self) -> String;
 }
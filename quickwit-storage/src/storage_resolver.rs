@@ -23,19 +23,26 @@ use std::sync::Arc;
 
 use once_cell::sync::OnceCell;
 
+use crate::fault_injection_storage::fault_profile_from_env;
 use crate::local_file_storage::LocalFileStorageFactory;
 use crate::ram_storage::RamStorageFactory;
-use crate::{S3CompatibleObjectStorageFactory, Storage, StorageResolverError};
+use crate::{
+    FaultInjectionStorage, FaultProfile, S3CompatibleObjectStorageFactory, Storage,
+    StorageResolverError,
+};
 
 /// Quickwit supported storage resolvers.
 pub fn quickwit_storage_uri_resolver() -> &'static StorageUriResolver {
     static STORAGE_URI_RESOLVER: OnceCell<StorageUriResolver> = OnceCell::new();
     STORAGE_URI_RESOLVER.get_or_init(|| {
-        StorageUriResolver::builder()
+        let mut builder = StorageUriResolver::builder()
             .register(RamStorageFactory::default())
             .register(LocalFileStorageFactory::default())
-            .register(S3CompatibleObjectStorageFactory::default())
-            .build()
+            .register(S3CompatibleObjectStorageFactory::default());
+        if let Some(fault_profile) = fault_profile_from_env() {
+            builder = builder.fault_profile(fault_profile);
+        }
+        builder.build()
     })
 }
 
@@ -53,11 +60,13 @@ pub trait StorageFactory: Send + Sync + 'static {
 #[derive(Clone)]
 pub struct StorageUriResolver {
     per_protocol_resolver: Arc<HashMap<String, Arc<dyn StorageFactory>>>,
+    fault_profile_opt: Option<FaultProfile>,
 }
 
 #[derive(Default)]
 pub struct StorageUriResolverBuilder {
     per_protocol_resolver: HashMap<String, Arc<dyn StorageFactory>>,
+    fault_profile_opt: Option<FaultProfile>,
 }
 
 impl StorageUriResolverBuilder {
@@ -71,10 +80,18 @@ impl StorageUriResolverBuilder {
         self
     }
 
+    /// Wraps the resolved storages into a [`FaultInjectionStorage`] injecting the faults
+    /// described by `fault_profile`.
+    pub fn fault_profile(mut self, fault_profile: FaultProfile) -> Self {
+        self.fault_profile_opt = Some(fault_profile);
+        self
+    }
+
     /// Builds the `StorageUriResolver`.
     pub fn build(self) -> StorageUriResolver {
         StorageUriResolver {
             per_protocol_resolver: Arc::new(self.per_protocol_resolver),
+            fault_profile_opt: self.fault_profile_opt,
         }
     }
 }
@@ -117,8 +134,22 @@ impl StorageUriResolver {
                     .unwrap_or_else(String::new),
             }
         })?;
+        if let Some(fault_profile) = &self.fault_profile_opt {
+            let fault_injection_storage =
+                FaultInjectionStorage::new(storage, fault_profile.clone());
+            return Ok(Arc::new(fault_injection_storage));
+        }
         Ok(storage)
     }
+
+    /// Returns a copy of this resolver whose resolved storages inject the faults described by
+    /// `fault_profile`.
+    pub fn with_fault_profile(&self, fault_profile: FaultProfile) -> Self {
+        StorageUriResolver {
+            per_protocol_resolver: self.per_protocol_resolver.clone(),
+            fault_profile_opt: Some(fault_profile),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -177,6 +208,28 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_storage_resolver_with_fault_profile() -> anyhow::Result<()> {
+        let storage_resolver = StorageUriResolver::for_test();
+        let storage = storage_resolver.resolve("ram://fault-injection")?;
+        storage
+            .put(Path::new("hello"), Box::new(b"hello_content".to_vec()))
+            .await?;
+        let fault_profile = FaultProfile {
+            throttling_probability: 1.0,
+            ..Default::default()
+        };
+        let faulty_storage = storage_resolver
+            .with_fault_profile(fault_profile)
+            .resolve("ram://fault-injection")?;
+        let error = faulty_storage
+            .get_all(Path::new("hello"))
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), crate::StorageErrorKind::Service);
+        Ok(())
+    }
+
     #[test]
     fn test_storage_resolver_unsupported_protocol() {
         let storage_resolver = StorageUriResolver::for_test();
@@ -0,0 +1,69 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+#![warn(missing_docs)]
+
+//! `quickwit-testing` provides the building blocks to write integration tests for custom
+//! sources and index configs without depending on Quickwit internals:
+//! - `ram_storage` and `ram_metastore` for an isolated in-memory storage and metastore
+//! - `SourceTestHarness` for driving a source and capturing the batches it emits
+//! - `TestSandbox` and the `search` helpers for indexing documents into splits and searching them
+
+mod search;
+mod source_harness;
+
+use std::sync::Arc;
+
+pub use quickwit_indexing::{mock_split, mock_split_meta, TestSandbox};
+use quickwit_metastore::{FileBackedMetastore, Metastore};
+use quickwit_storage::{RamStorage, Storage};
+
+pub use self::search::{search_docs, search_sandbox};
+pub use self::source_harness::SourceTestHarness;
+
+/// Returns a new empty storage held in RAM.
+pub fn ram_storage() -> Arc<dyn Storage> {
+    Arc::new(RamStorage::default())
+}
+
+/// Returns a new empty file-backed metastore whose files are held in RAM.
+pub fn ram_metastore() -> Arc<dyn Metastore> {
+    Arc::new(FileBackedMetastore::new(ram_storage()))
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_metastore::IndexMetadata;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_ram_metastore_is_isolated() -> anyhow::Result<()> {
+        let metastore = ram_metastore();
+        metastore
+            .create_index(IndexMetadata::for_test(
+                "test-index",
+                "ram://indexes/test-index",
+            ))
+            .await?;
+        assert!(metastore.index_metadata("test-index").await.is_ok());
+        assert!(ram_metastore().index_metadata("test-index").await.is_err());
+        Ok(())
+    }
+}
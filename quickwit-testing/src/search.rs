@@ -0,0 +1,178 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use quickwit_indexing::TestSandbox;
+use quickwit_proto::{SearchRequest, SearchResponse};
+use quickwit_search::single_node_search;
+
+/// Searches the splits of a [`TestSandbox`] index, as a single Quickwit node would.
+///
+/// The `index_id` of the request is overridden with the index of the sandbox.
+pub async fn search_sandbox(
+    test_sandbox: &TestSandbox,
+    search_request: &SearchRequest,
+) -> anyhow::Result<SearchResponse> {
+    let search_request = SearchRequest {
+        index_id: test_sandbox.index_id().to_string(),
+        ..search_request.clone()
+    };
+    let search_response = single_node_search(
+        &search_request,
+        &*test_sandbox.metastore(),
+        test_sandbox.storage_uri_resolver(),
+    )
+    .await?;
+    Ok(search_response)
+}
+
+/// Searches the splits of a [`TestSandbox`] index on its default search fields and returns up
+/// to `max_hits` matching documents, in the format of the search API hits.
+pub async fn search_docs(
+    test_sandbox: &TestSandbox,
+    query: &str,
+    max_hits: u64,
+) -> anyhow::Result<Vec<serde_json::Value>> {
+    let search_request = SearchRequest {
+        query: query.to_string(),
+        max_hits,
+        ..Default::default()
+    };
+    let search_response = search_sandbox(test_sandbox, &search_request).await?;
+    search_response
+        .hits
+        .iter()
+        .map(|hit| Ok(serde_json::from_str(&hit.json)?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use quickwit_common::uri::Uri;
+    use quickwit_config::IndexConfig;
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_search_docs_over_built_splits() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let index_config_yaml = r#"
+            version: 0
+            index_id: test-search-docs
+            doc_mapping:
+              field_mappings:
+                - name: title
+                  type: text
+                - name: body
+                  type: text
+                - name: views
+                  type: u64
+            search_settings:
+              default_search_fields: [title, body]
+        "#;
+        let index_config_uri = Uri::try_new("file:///index-config.yaml")?;
+        let index_config =
+            IndexConfig::load(&index_config_uri, index_config_yaml.as_bytes()).await?;
+        let test_sandbox = TestSandbox::create_from_index_config(&index_config).await?;
+        test_sandbox
+            .add_documents(vec![
+                json!({"title": "Hurricane Fay", "body": "tropical storm", "views": 3}),
+                json!({"title": "Ganimede", "body": "moon of jupiter", "views": 8}),
+            ])
+            .await?;
+        test_sandbox
+            .add_documents(vec![
+                json!({"title": "Io", "body": "moon of jupiter", "views": 5}),
+            ])
+            .await?;
+
+        let docs = search_docs(&test_sandbox, "jupiter", 10).await?;
+        assert_eq!(docs.len(), 2);
+        let hurricane_docs = search_docs(&test_sandbox, "title:hurricane", 10).await?;
+        assert_eq!(hurricane_docs.len(), 1);
+        assert_eq!(hurricane_docs[0]["title"], json!(["Hurricane Fay"]));
+        let search_request = SearchRequest {
+            query: "views:[4 TO 10]".to_string(),
+            max_hits: 10,
+            ..Default::default()
+        };
+        let search_response = search_sandbox(&test_sandbox, &search_request).await?;
+        assert_eq!(search_response.num_hits, 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_sandbox_profile() -> anyhow::Result<()> {
+        quickwit_common::setup_logging_for_tests();
+        let index_config_yaml = r#"
+            version: 0
+            index_id: test-search-profile
+            doc_mapping:
+              field_mappings:
+                - name: body
+                  type: text
+            search_settings:
+              default_search_fields: [body]
+        "#;
+        let index_config_uri = Uri::try_new("file:///index-config.yaml")?;
+        let index_config =
+            IndexConfig::load(&index_config_uri, index_config_yaml.as_bytes()).await?;
+        let test_sandbox = TestSandbox::create_from_index_config(&index_config).await?;
+        test_sandbox
+            .add_documents(vec![
+                json!({"body": "tropical storm"}),
+                json!({"body": "moon of jupiter"}),
+            ])
+            .await?;
+        test_sandbox
+            .add_documents(vec![json!({"body": "moon of jupiter"})])
+            .await?;
+
+        let search_request = SearchRequest {
+            query: "jupiter".to_string(),
+            max_hits: 10,
+            ..Default::default()
+        };
+        let search_response = search_sandbox(&test_sandbox, &search_request).await?;
+        assert!(search_response.split_profiles.is_empty());
+
+        let search_request = SearchRequest {
+            profile: true,
+            ..search_request
+        };
+        let search_response = search_sandbox(&test_sandbox, &search_request).await?;
+        assert_eq!(search_response.num_hits, 2);
+        assert_eq!(search_response.split_profiles.len(), 2);
+        let num_docs_scored: u64 = search_response
+            .split_profiles
+            .iter()
+            .flat_map(|split_profile| &split_profile.segments)
+            .map(|segment_profile| segment_profile.num_docs_scored)
+            .sum();
+        assert_eq!(num_docs_scored, 2);
+        let max_doc: u32 = search_response
+            .split_profiles
+            .iter()
+            .flat_map(|split_profile| &split_profile.segments)
+            .map(|segment_profile| segment_profile.max_doc)
+            .sum();
+        assert_eq!(max_doc, 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright (C) 2021 Quickwit, Inc.
+//
+// Quickwit is offered under the AGPL v3.0 and as commercial software.
+// For commercial licensing, contact us at hello@quickwit.io.
+//
+// AGPL:
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use quickwit_actors::{create_test_mailbox, ActorExitStatus, ActorHandle, Inbox, Universe};
+use quickwit_config::SourceConfig;
+use quickwit_indexing::models::{IndexerMessage, RawDocBatch};
+use quickwit_indexing::source::{
+    quickwit_supported_sources, Source, SourceActor, TypedSourceFactory,
+};
+use quickwit_metastore::checkpoint::SourceCheckpoint;
+
+/// Drives a [`Source`] in an actor universe and captures the batches it emits, as an indexing
+/// pipeline would.
+///
+/// The source is initialized, then `emit_batches` is called in a loop until the source exits or
+/// the harness is quit.
+pub struct SourceTestHarness {
+    universe: Universe,
+    source_handle: ActorHandle<SourceActor>,
+    inbox: Inbox<IndexerMessage>,
+}
+
+impl SourceTestHarness {
+    /// Starts driving the given source.
+    pub fn new(source: Box<dyn Source>) -> Self {
+        let universe = Universe::new();
+        let (batch_sink, inbox) = create_test_mailbox();
+        let source_actor = SourceActor::new(source, batch_sink);
+        let (_source_mailbox, source_handle) = universe.spawn_actor(source_actor).spawn_async();
+        SourceTestHarness {
+            universe,
+            source_handle,
+            inbox,
+        }
+    }
+
+    /// Creates a source with a factory, typically a custom one, and starts driving it.
+    pub async fn from_factory<F: TypedSourceFactory>(
+        params: F::Params,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<Self> {
+        let source = F::typed_create_source(params, checkpoint).await?;
+        Ok(Self::new(Box::new(source)))
+    }
+
+    /// Creates one of the sources supported by Quickwit from its config and starts driving it.
+    pub async fn from_config(
+        source_config: SourceConfig,
+        checkpoint: SourceCheckpoint,
+    ) -> anyhow::Result<Self> {
+        let source = quickwit_supported_sources()
+            .load_source(source_config, checkpoint)
+            .await?;
+        Ok(Self::new(source))
+    }
+
+    /// Returns the observable state of the source once its pending calls to `emit_batches` are
+    /// processed.
+    pub async fn observe(&self) -> serde_json::Value {
+        self.source_handle.process_pending_and_observe().await.state
+    }
+
+    /// Returns the batches emitted by the source since the last call.
+    pub fn drain_batches(&self) -> Vec<RawDocBatch> {
+        batches(self.inbox.drain_available_message_for_test())
+    }
+
+    /// Returns the documents emitted by the source since the last call.
+    pub fn drain_docs(&self) -> Vec<String> {
+        self.drain_batches()
+            .into_iter()
+            .flat_map(|batch| batch.docs)
+            .collect()
+    }
+
+    /// Waits for the source to exit on its own, as bounded sources do once exhausted, and
+    /// returns its exit status, its last observable state, and the batches not drained yet.
+    pub async fn join(self) -> (ActorExitStatus, serde_json::Value, Vec<RawDocBatch>) {
+        let (exit_status, observable_state) = self.source_handle.join().await;
+        let batches = batches(self.inbox.drain_available_message_for_test());
+        (exit_status, observable_state, batches)
+    }
+
+    /// Stops the source, as an indexing pipeline does on shutdown, and returns its exit
+    /// status, its last observable state, and the batches not drained yet.
+    pub async fn quit(self) -> (ActorExitStatus, serde_json::Value, Vec<RawDocBatch>) {
+        let (exit_status, observable_state) = self.source_handle.quit().await;
+        let batches = batches(self.inbox.drain_available_message_for_test());
+        self.universe.kill();
+        (exit_status, observable_state, batches)
+    }
+}
+
+fn batches(messages: Vec<IndexerMessage>) -> Vec<RawDocBatch> {
+    messages
+        .into_iter()
+        .filter_map(|message| match message {
+            IndexerMessage::Batch(batch) | IndexerMessage::SourceBatch { batch, .. } => Some(batch),
+            IndexerMessage::CommitTimeout { .. } | IndexerMessage::Flush => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use quickwit_actors::Mailbox;
+    use quickwit_config::{SourceParams, VecSourceParams};
+    use quickwit_indexing::source::SourceContext;
+    use quickwit_metastore::checkpoint::CheckpointDelta;
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_source_test_harness_from_config() -> anyhow::Result<()> {
+        let source_config = SourceConfig {
+            source_id: "vec".to_string(),
+            source_params: SourceParams::Vec(VecSourceParams {
+                items: (0..5).map(|item| item.to_string()).collect(),
+                batch_num_docs: 2,
+                partition: "".to_string(),
+            }),
+            filter: None,
+            routing: None,
+            transform: None,
+        };
+        let mut checkpoint = SourceCheckpoint::default();
+        checkpoint.try_apply_delta(CheckpointDelta::from(0u64..2u64))?;
+        let harness = SourceTestHarness::from_config(source_config, checkpoint).await?;
+        let (exit_status, observable_state, batches) = harness.join().await;
+        assert!(exit_status.is_success());
+        assert_eq!(observable_state, json!({"next_item_idx": 5}));
+        let docs: Vec<String> = batches.into_iter().flat_map(|batch| batch.docs).collect();
+        assert_eq!(docs, vec!["2", "3", "4"]);
+        Ok(())
+    }
+
+    struct CountingSource {
+        count: u64,
+    }
+
+    #[async_trait]
+    impl Source for CountingSource {
+        async fn emit_batches(
+            &mut self,
+            batch_sink: &Mailbox<IndexerMessage>,
+            ctx: &SourceContext,
+        ) -> Result<(), ActorExitStatus> {
+            let batch = RawDocBatch {
+                docs: vec![json!({ "count": self.count }).to_string()],
+                checkpoint_delta: CheckpointDelta::from(self.count..self.count + 1),
+            };
+            self.count += 1;
+            ctx.send_message(batch_sink, IndexerMessage::from(batch))
+                .await?;
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            Ok(())
+        }
+
+        fn name(&self) -> String {
+            "CountingSource".to_string()
+        }
+
+        fn observable_state(&self) -> serde_json::Value {
+            json!({ "count": self.count })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_source_test_harness_with_custom_source() -> anyhow::Result<()> {
+        let harness = SourceTestHarness::new(Box::new(CountingSource { count: 0 }));
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let observable_state = harness.observe().await;
+        assert!(observable_state["count"].as_u64().unwrap() > 0);
+        let docs = harness.drain_docs();
+        assert!(!docs.is_empty());
+        assert_eq!(docs[0], r#"{"count":0}"#);
+        let (exit_status, _, _) = harness.quit().await;
+        assert!(matches!(exit_status, ActorExitStatus::Quit));
+        Ok(())
+    }
+}